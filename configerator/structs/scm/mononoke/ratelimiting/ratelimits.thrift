@@ -23,6 +23,8 @@ union Target {
     4: string identity,
     // A static slice of hosts that are chosen by hashing the client's hostname
     5: StaticSlice static_slice,
+    // Hosts in a class, as decided by the config's host_classifier
+    6: string host_class,
 }
 
 struct StaticSlice {
@@ -52,6 +54,8 @@ enum RegionalMetric {
     GetpackFiles = 2,
     // The number of commits served
     Commits = 3,
+    // The amount of bytes ingressed by Mononoke servers
+    IngressBytes = 4,
 }
 
 struct RateLimitBody {
@@ -97,4 +101,22 @@ struct MononokeRateLimits {
     4: RateLimitBody commits_per_author,
     // A rate limit for the number of files that can be changed
     5: optional RateLimitBody total_file_changes,
+    // How client hosts are classified.  If this is null then hosts are
+    // classified by the alphabetic prefix of their hostname.
+    6: optional HostClassifier host_classifier,
+} (rust.exhaustive)
+
+struct HostClassRule {
+    // Regex matched against the client's hostname
+    1: string regex,
+    // The class of hosts that match.  This may refer to capture groups in the
+    // regex, e.g. "$1".
+    2: string class,
+} (rust.exhaustive)
+
+struct HostClassifier {
+    // Rules to classify hosts by, in order.  The first matching rule is used.
+    1: list<HostClassRule> rules,
+    // Classes for specific hostnames, which take precedence over the rules
+    2: map<string, string> overrides,
 } (rust.exhaustive)
@@ -187,6 +187,21 @@ struct RawRepoConfig {
   51: optional string bookmark_scribe_category;
   // Configuration for logging of repo updates.
   52: optional RawUpdateLoggingConfig update_logging_config;
+  // Rules that the paths of new files must follow
+  53: optional RawPathPolicyConfig path_policy;
+  // Limits on the size of pushed commits
+  54: optional RawCommitLimitsConfig commit_limits;
+  // Paths whose contents may only be read by specific identities
+  55: optional list<RawPathReadAcl> path_read_acls;
+  // Scheduled windows during which pushes to publishing bookmarks are
+  // rejected, e.g. around releases
+  56: optional list<RawFreezeWindow> freeze_windows;
+  // Policy comparing the authors of pushed commits with the identity of
+  // the pusher
+  57: optional RawAuthorPolicyConfig author_policy;
+  // Thresholds on the growth of the repo that fire growth alarms when
+  // exceeded
+  58: optional RawGrowthAlarmsConfig growth_alarms;
 } (rust.exhaustive)
 
 struct RawWalkerConfig {
@@ -250,6 +265,61 @@ struct RawBackupRepoConfig {
 
 struct RawRepoClientKnobs {
   1: bool allow_short_getpack_history;
+  // Shadow a sample of read-only wireproto commands to a test tier
+  2: optional RawWireprotoShadowing shadowing;
+  // Include scratch bookmarks in the response to the legacy `heads`
+  // command. By default only publishing bookmarks are returned; clients
+  // that need other heads should use `headspaginated`.
+  3: optional bool legacy_heads_include_scratch;
+  // Maximum number of concurrent operations of each kind for this repo.
+  // Operations over the limit wait until others complete.  Unset means
+  // unlimited.
+  4: optional i64 max_concurrent_getbundles;
+  5: optional i64 max_concurrent_unbundles;
+  6: optional i64 max_concurrent_hook_runs;
+  // Repos with LFS enabled serve changegroup version 03 in getbundle
+  // responses. Clients that only support version 02 are sent a downgraded
+  // response with file contents inline, unless this is set, in which case
+  // their requests are refused.
+  7: optional bool reject_legacy_changegroup_clients;
+  // Re-verify one in this many manifests served by gettreepack, by
+  // recomputing the node hash from the content and parents. Manifests that
+  // fail verification are quarantined. Unset means no sampling.
+  8: optional i64 manifest_verification_sample_rate;
+  // How wireproto commands are sampled for tracing
+  9: optional RawTraceSampling trace_sampling;
+  // Custom bundle2 part types that pushes may include, even if the client
+  // marks them as mandatory. Their payload is logged and discarded.
+  10: optional list<string> accepted_bundle2_parts;
+  // Additional listkeys namespaces served with a fixed set of keys, e.g.
+  // metadata that clients read at the start of a pull. These can't replace
+  // the built-in bookmarks, phases and infinitepush namespaces.
+  11: optional map<string, map<string, string>> listkeys_namespaces;
+} (rust.exhaustive)
+
+struct RawTraceSampling {
+  // Trace one in this many commands. If omitted, the
+  // wireproto_trace_sampling_rate tunable is used.
+  1: optional i64 sample_rate;
+  // Sample rates for specific commands, overriding sample_rate. A rate of
+  // 0 disables tracing of the command.
+  2: optional map<string, i64> command_sample_rates;
+  // Commands that are traced in full when sampled. Other commands are only
+  // traced at the top level. If omitted, all commands are traced in full.
+  3: optional list<string> full_trace_commands;
+  // Identities whose commands are always traced in full
+  4: optional list<RawAllowlistIdentity> always_trace_identities;
+} (rust.exhaustive)
+
+struct RawWireprotoShadowing {
+  // Scribe category shadowed requests are published to. The test tier
+  // replays the requests from this category.
+  1: string scribe_category;
+  // Shadow one in this many requests (default 100)
+  2: optional i64 sample_rate;
+  // Commands to shadow. If omitted, no commands are shadowed. Pushes
+  // (unbundle) are never shadowed.
+  3: optional list<string> commands;
 } (rust.exhaustive)
 
 struct RawDerivedDataConfig {
@@ -261,6 +331,17 @@ struct RawDerivedDataConfig {
   6: optional RawDerivedDataTypesConfig backfilling; // deprecated
   7: optional map<string, RawDerivedDataTypesConfig> available_configs;
   8: optional string enabled_config_name;
+  // Derived data to derive asynchronously for commits landed by pushes
+  9: optional RawDerivationQueueConfig queue;
+} (rust.exhaustive)
+
+struct RawDerivationQueueConfig {
+  // Derived data types to enqueue for derivation when a push lands. These
+  // must be enabled for the repo.
+  1: list<string> types;
+  // Number of attempts at deriving a type before the task is moved to the
+  // dead letter queue (default 5)
+  2: optional i32 max_attempts;
 } (rust.exhaustive)
 
 struct RawDerivedDataTypesConfig {
@@ -339,6 +420,13 @@ struct RawBlobstorePack {
   1: RawBlobstoreConfig blobstore (rust.box);
   2: optional RawBlobstorePackConfig pack_config;
 } (rust.exhaustive)
+// Reads blobs missing from the primary blobstore from the secondary one,
+// and copies them into the primary.  Writes only go to the primary.  Used
+// to migrate between storages.
+struct RawBlobstoreFallback {
+  1: RawBlobstoreConfig primary (rust.box);
+  2: RawBlobstoreConfig secondary (rust.box);
+} (rust.exhaustive)
 struct RawBlobstoreS3 {
   1: string bucket;
   2: string keychain_group;
@@ -370,6 +458,7 @@ union RawBlobstoreConfig {
   10: RawBlobstorePack pack;
   11: RawBlobstoreS3 s3;
   12: RawBlobstoreMultiplexedWal multiplexed_wal;
+  13: RawBlobstoreFallback fallback;
 }
 
 // A write-mostly blobstore is one that is not read from in normal operation.
@@ -500,6 +589,28 @@ struct RawPushrebaseParams {
   // Pushes to which bookmark should be logged to ODS for monitoring
   // This will usually be the "main bookmark" of the repo
   13: optional string monitoring_bookmark;
+  // Queue pushrebases onto the same bookmark within a server instead of
+  // letting them race each other for the bookmark move
+  14: optional bool serialize_per_bookmark;
+  // Rules for rewriting the messages of pushrebased commits, applied in
+  // order
+  15: optional list<RawCommitMessageRewriteRule> commit_message_rewrite_rules;
+} (rust.exhaustive)
+
+// A rule for rewriting the messages of commits as they are pushrebased.
+// When a message is rewritten, the original message is kept in the
+// original_message extra.
+struct RawCommitMessageRewriteRule {
+  // Only rewrite commits pushrebased onto bookmarks matching this regex.
+  // If omitted, the rule applies to all bookmarks.
+  1: optional string bookmark_regex;
+  // Text matching these regexes is removed from the message
+  2: optional list<string> strip_patterns;
+  // Template for a line appended to the message. {bookmark}, {push_id}
+  // and {original_commit} are replaced with the bookmark pushrebased onto,
+  // the id of the pushing session and the id of the commit before it was
+  // pushrebased.
+  3: optional string append_template;
 } (rust.exhaustive)
 
 struct RawBookmarkConfig {
@@ -561,6 +672,43 @@ struct RawBookmarkConfig {
   // because commit is already public, meaning that hooks already
   // should have been run when the commit was first made public.
   11: optional bool allow_move_to_public_commits_without_hooks;
+
+  // Derived data types that must be derived for a commit before this
+  // bookmark can be moved to it.  If they are not, derivation is started
+  // and the move is rejected until it has finished, so that services that
+  // read derived data for the bookmark never find it missing.
+  12: optional list<string> required_derived_data;
+
+  // Restrictions on merge commits landing on this bookmark
+  13: optional RawMergePolicy merge_policy;
+
+  // Publish moves of this bookmark to clients in stages: canaries first,
+  // and everyone else after a delay or once the approval bookmark reaches
+  // the move
+  14: optional RawBookmarkPublishingDelay publishing_delay;
+} (rust.exhaustive)
+
+struct RawBookmarkPublishingDelay {
+  // How long after a move the bookmark's new position is published to all
+  // clients, unless it is approved sooner
+  1: i64 delay_secs;
+  // Identities that see moves of the bookmark as soon as they land
+  2: optional list<RawAllowlistIdentity> canary_identities;
+  // Bookmark that canary checks move to the positions of this bookmark that
+  // they approve.  A position is published as soon as it is approved.
+  3: optional string approval_bookmark;
+} (rust.exhaustive)
+
+struct RawMergePolicy {
+  // Reject merge commits pushed or pushrebased onto the bookmark
+  1: optional bool deny_merges;
+  // Identities (e.g. merge bots) that may still push merge commits when
+  // deny_merges is set
+  2: optional list<RawAllowlistIdentity> allowed_merge_identities;
+  // Reject any bookmark move that would bring a merge commit into the
+  // bookmark's history, including commits that are already in the repo.
+  // This can't be bypassed by allowed_merge_identities.
+  3: optional bool require_linear_history;
 } (rust.exhaustive)
 
 struct RawAllowlistIdentity {
@@ -619,6 +767,9 @@ struct RawCacheWarmupConfig {
   1: string bookmark;
   2: optional i64 commit_limit;
   3: optional bool microwave_preload;
+  // Also prefetch the tips, ancestry and root trees of all publishing
+  // bookmarks before the repo is served.
+  4: optional bool published_heads;
 } (rust.exhaustive)
 
 struct RawBookmarkHook {
@@ -646,6 +797,41 @@ struct RawHookConfig {
   11: optional map<string, list<i64>> (
     rust.type = "HashMap",
   ) config_int_64_lists;
+  // Maximum wall time a single execution of this hook may take
+  12: optional i64 timeout_ms;
+  // Maximum number of file contents a single execution of this hook may
+  // fetch
+  13: optional i64 max_file_fetches;
+  // If set, a hook that exceeds its limits or is disabled at runtime is
+  // treated as accepting the change. Otherwise, it is treated as rejecting
+  // it.
+  14: optional bool fail_open;
+  15: optional RawHookRules rules;
+  // Hooks that must have run on a changeset before this one runs on it. If
+  // any of them rejects the changeset, this hook is not run on it.
+  16: optional list<string> run_after;
+  // If set, the hook only looks at which files changed and their sizes,
+  // never at their contents, and any attempt to fetch contents fails.
+  17: optional bool metadata_only;
+} (rust.exhaustive)
+
+// Common hook behaviors that are applied by the hook manager, so that hooks
+// don't have to implement them individually.
+struct RawHookRules {
+  // Globs of paths the hook applies to. `*` and `?` match within a path
+  // component, `**` matches any number of components. If unset, the hook
+  // applies to all paths.
+  1: optional list<string> include_paths;
+  // Globs of paths the hook never applies to, even if they are included.
+  2: optional list<string> exclude_paths;
+  // Regexes of bookmarks the hook applies to. If unset, the hook applies to
+  // all bookmarks it is enabled for.
+  3: optional list<string> bookmarks;
+  // Either "block" (the default) to reject the push when the hook fails,
+  // or "warn" to only log the failure.
+  4: optional string severity;
+  // Name of a pushvar that bypasses the hook when set to "true".
+  5: optional string bypass_pushvar_name;
 } (rust.exhaustive)
 
 struct RawLfsParams {
@@ -668,13 +854,35 @@ struct RawInfinitepushParams {
   // 4: deleted
   // (NOTE: will be replaced by update_logging_config.new_commit_logging_destination)
   5: optional string commit_scribe_category;
-// 6: deleted
-// 7: deleted
+  // 6: deleted
+  // 7: deleted
+  // Scratch bookmarks whose commits are private to the user that owns them
+  8: optional RawPrivateScratchNamespace private_namespace;
+} (rust.exhaustive)
+
+// Scratch bookmarks that are private to the user they are named after.
+// While they are draft, commits pushed to these bookmarks may only be read
+// by their owner and the identities they have been shared with.
+struct RawPrivateScratchNamespace {
+  // Regex matching private scratch bookmarks. It must have a capture group
+  // named `user` that matches the user name of the bookmark's owner.
+  1: string pattern;
+  // Identity type that owners' user names are matched against, e.g. USER
+  2: string owner_identity_type;
+  // Identities that may read all private scratch commits, e.g. services
+  3: optional list<RawAllowlistIdentity> readers;
+  // Identities that each owner has shared their private scratch commits
+  // with, keyed by the owner's user name
+  4: optional map<string, list<RawAllowlistIdentity>> shared_with;
 } (rust.exhaustive)
 
 struct RawFilestoreParams {
   1: i64 chunk_size;
   2: i32 concurrency;
+  // Split files at content-defined boundaries, so that identical chunks are
+  // stored once. Chunks are at most chunk_size, and a quarter of it on
+  // average.
+  3: optional bool content_defined_chunking;
 } (rust.exhaustive)
 
 struct RawCommitSyncSmallRepoConfig {
@@ -711,6 +919,10 @@ struct RawSourceControlServiceParams {
 
   // Whether users can create commits without parents.
   5: bool permit_commits_without_parents;
+
+  // Identities that may fetch raw blobs from the repo's blobstore by key,
+  // for debugging.
+  6: optional list<RawAllowlistIdentity> raw_blob_readers;
 } (rust.exhaustive)
 
 struct RawServiceWriteRestrictions {
@@ -898,9 +1110,138 @@ union RawLoggingDestination {
   2: RawLoggingDestinationScribe scribe;
 }
 
+struct RawEventSinkWebhook {
+  // URL events are POSTed to
+  1: string url;
+  // How many times delivery is attempted before giving up (default 3)
+  2: optional i32 max_attempts;
+} (rust.exhaustive)
+
+struct RawEventSinkUnixSocket {
+  // Path of the unix socket events are written to
+  1: string path;
+} (rust.exhaustive)
+
+union RawEventSink {
+  1: RawEventSinkWebhook webhook;
+  2: RawLoggingDestinationScribe scribe;
+  3: RawEventSinkUnixSocket unix_socket;
+}
+
+struct RawEventSubscription {
+  1: RawEventSink sink;
+  // Kinds of events to deliver: "push_accepted", "bookmark_moved",
+  // "hook_rejected", "redaction_added", "growth_alarm". All of them if
+  // unset.
+  2: optional list<string> events;
+  // Only deliver events about bookmarks matching this regex
+  3: optional string bookmark_regex;
+} (rust.exhaustive)
+
 struct RawUpdateLoggingConfig {
   // Destination to log bookmark updates to
   4: optional RawLoggingDestination bookmark_logging_destination;
   // Destination to log new commits to
   7: optional RawLoggingDestination new_commit_logging_destination;
+  // External subscribers to notify of repo events
+  8: optional list<RawEventSubscription> event_subscriptions;
+} (rust.exhaustive)
+
+// Rules that the paths of files added to the repo must follow, checked when
+// commits are created or pushed. Limits that are unset are not enforced.
+// The targets of new symlinks are checked as well: targets containing NUL
+// bytes or longer than 4096 bytes are rejected even if no rule is set.
+struct RawPathPolicyConfig {
+  // Maximum length in bytes of each path component
+  1: optional i64 max_component_length;
+  // Maximum length in bytes of the whole path
+  2: optional i64 max_path_length;
+  // Maximum number of components in a path
+  3: optional i64 max_depth;
+  // Characters that may not appear anywhere in a path
+  4: optional string forbidden_characters;
+  // Path components that may not be used, e.g. "CON" or ".hg"
+  5: optional list<string> reserved_names;
+  // Whether reserved names are matched regardless of case
+  6: optional bool reserved_names_case_insensitive;
+  // Whether symlinks may not point to absolute paths
+  7: optional bool forbid_absolute_symlinks;
+  // Whether symlinks may not point outside of the repo
+  8: optional bool forbid_escaping_symlinks;
+} (rust.exhaustive)
+
+// Limits on the size of the commits pushed to the repo, protecting the server
+// from pathological commits such as ones that create directories with
+// millions of entries. Limits that are unset are not enforced. A push can
+// exceed the limits by setting the ALLOW_LARGE_COMMITS=true pushvar.
+struct RawCommitLimitsConfig {
+  // Maximum number of files a commit may change
+  1: optional i64 max_files_changed;
+  // Maximum number of entries a commit may add to a single directory
+  2: optional i64 max_new_directory_entries;
+  // Maximum size in bytes of the manifest of any directory a commit changes
+  3: optional i64 max_directory_manifest_size;
+  // Identities that may push commits over the limits with the
+  // ALLOW_LARGE_COMMITS pushvar
+  4: optional list<RawAllowlistIdentity> large_commit_identities;
+} (rust.exhaustive)
+
+// A path prefix whose contents may only be read by the listed identities,
+// even if they have read access to the rest of the repo.
+struct RawPathReadAcl {
+  // The protected path. All files and directories under it are protected.
+  1: string path_prefix;
+  // Identities that are permitted to read the protected path
+  2: list<RawAllowlistIdentity> allowed_identities;
+} (rust.exhaustive)
+
+// A recurring window during which pushes to publishing bookmarks are
+// rejected. Pushes can bypass an active window by setting the
+// BYPASS_FREEZE_WINDOW=true pushvar, if they are permitted to bypass
+// read-only mode.
+struct RawFreezeWindow {
+  // Name of the window, reported to clients whose pushes are rejected
+  1: string name;
+  // When the window starts, as a cron expression evaluated in UTC with
+  // the fields "sec min hour day-of-month month day-of-week [year]"
+  2: string schedule;
+  // How long the window lasts once it starts
+  3: i64 duration_secs;
+  // Bookmarks the window applies to. If omitted, the window applies to
+  // all publishing bookmarks.
+  4: optional string bookmark_regex;
+  // Landing queue that clients should send their changes to instead
+  // while the window is active
+  5: optional string landing_queue;
+} (rust.exhaustive)
+
+// Protects against pushes that spoof the author of commits: the author of
+// each commit pushed to a publishing bookmark is compared with the
+// authenticated user that pushes it. An author matches if its email's user
+// name, or the whole author if it has no email, is the pusher's user name.
+// Mismatches are logged to scuba for auditing.
+struct RawAuthorPolicyConfig {
+  // What happens to commits whose author doesn't match the pusher: "off"
+  // (the default), "warn" to log them, "block" to reject them, or
+  // "require_service_identity" to reject them unless they are pushed by
+  // one of the service_identities
+  1: optional string enforcement;
+  // Identities of services that push commits on behalf of others, such as
+  // landing services. Only used by "require_service_identity".
+  2: optional list<RawAllowlistIdentity> service_identities;
+  // Whether the committer must match the pusher as well as the author
+  3: optional bool check_committer;
+} (rust.exhaustive)
+
+// Catches runaway growth of a repo early, e.g. automation writing millions
+// of scratch commits. When a threshold is exceeded, a growth alarm is
+// logged to scuba and published as a "growth_alarm" repo event. Growth per
+// day is measured over the last 24 hours by each server.
+struct RawGrowthAlarmsConfig {
+  // Total size of the files changed by new commits per day
+  1: optional i64 blob_bytes_per_day;
+  // Number of new commits per day, including draft commits
+  2: optional i64 commits_per_day;
+  // Number of bookmarks, including scratch bookmarks
+  3: optional i64 bookmark_count;
 } (rust.exhaustive)
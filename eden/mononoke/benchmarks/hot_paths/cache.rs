@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use cacheblob::dummy::DummyCache;
+use cacheblob::dummy::DummyLease;
+use cacheblob::CacheBlobstore;
+use cacheblob::CacheOps;
+use cacheblob::MemWritesBlobstore;
+use context::CoreContext;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use memblob::Memblob;
+use mononoke_types::BlobstoreBytes;
+use tokio::runtime::Handle;
+
+use crate::fixtures::content;
+use crate::fixtures::rng;
+use crate::fixtures::KB;
+use crate::fixtures::MB;
+
+/// A cache that keeps everything in memory, so that the benchmark measures
+/// the overhead of the caching layer rather than of a real cache.
+#[derive(Clone, Debug, Default)]
+struct MemCache {
+    entries: Arc<Mutex<HashMap<String, BlobstoreGetData>>>,
+}
+
+impl fmt::Display for MemCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemCache")
+    }
+}
+
+#[async_trait]
+impl CacheOps for MemCache {
+    async fn get(&self, key: &str) -> Option<BlobstoreGetData> {
+        self.entries
+            .lock()
+            .expect("lock poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    async fn put(&self, key: &str, value: BlobstoreGetData) {
+        self.entries
+            .lock()
+            .expect("lock poisoned")
+            .insert(key.to_string(), value);
+    }
+
+    async fn check_present(&self, key: &str) -> bool {
+        self.entries
+            .lock()
+            .expect("lock poisoned")
+            .contains_key(key)
+    }
+}
+
+pub fn benchmark_gets(c: &mut Criterion, ctx: &CoreContext, runtime: &Handle) {
+    let mut group = c.benchmark_group("blobstore_cache_gets");
+
+    let memblob = Memblob::default();
+    let layers: Vec<(&str, Arc<dyn Blobstore>)> = vec![
+        ("memblob", Arc::new(memblob.clone())),
+        (
+            "cache_hit",
+            Arc::new(CacheBlobstore::new(
+                MemCache::default(),
+                DummyLease {},
+                memblob.clone(),
+                false,
+            )),
+        ),
+        (
+            "cache_miss",
+            Arc::new(CacheBlobstore::new(
+                DummyCache {},
+                DummyLease {},
+                memblob.clone(),
+                false,
+            )),
+        ),
+        ("mem_writes", Arc::new(MemWritesBlobstore::new(memblob))),
+    ];
+
+    let mut rng = rng();
+    for size in [KB, MB] {
+        let key = format!("benchmark.{}", size);
+        let value = BlobstoreBytes::from_bytes(content(&mut rng, size));
+        runtime
+            .block_on(layers[0].1.put(ctx, key.clone(), value))
+            .expect("Put failed");
+
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, blobstore) in layers.iter() {
+            // Fill the caches before measuring.
+            runtime
+                .block_on(blobstore.get(ctx, &key))
+                .expect("Get failed");
+            group.bench_with_input(BenchmarkId::new(*name, size), &key, |b, key| {
+                b.iter(|| {
+                    runtime
+                        .block_on(blobstore.get(ctx, key))
+                        .expect("Get failed")
+                        .expect("Blob is present")
+                });
+            });
+        }
+    }
+    group.finish();
+}
@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use futures_old::stream;
+use futures_old::Future;
+use futures_old::Stream;
+use mercurial_bundles::changegroup::packer::CgPacker;
+use mercurial_bundles::changegroup::CgDeltaChunk;
+use mercurial_bundles::changegroup::Part;
+use mercurial_bundles::changegroup::Section;
+use mercurial_types::delta::Delta;
+use mercurial_types::MPath;
+use mercurial_types::NULL_HASH;
+
+use crate::fixtures::content;
+use crate::fixtures::node_hash;
+use crate::fixtures::rng;
+use crate::fixtures::KB;
+
+const CHUNK_SIZE: usize = KB;
+const FILES: u64 = 100;
+
+/// A changegroup with `commits` changesets and manifests, and a filelog
+/// section for each of `FILES` files with a revision for each commit.
+fn changegroup_parts(commits: u64) -> Vec<Part> {
+    let mut rng = rng();
+    let mut chunk = |section: u64, index: u64| CgDeltaChunk {
+        node: node_hash(section, index),
+        p1: if index == 0 {
+            NULL_HASH
+        } else {
+            node_hash(section, index - 1)
+        },
+        p2: NULL_HASH,
+        base: NULL_HASH,
+        linknode: node_hash(0, index),
+        delta: Delta::new_fulltext(content(&mut rng, CHUNK_SIZE)),
+        flags: None,
+    };
+
+    let mut parts = Vec::new();
+    for (seed, section) in [(0, Section::Changeset), (1, Section::Manifest)] {
+        for index in 0..commits {
+            parts.push(Part::CgChunk(section.clone(), chunk(seed, index)));
+        }
+        parts.push(Part::SectionEnd(section));
+    }
+    for file in 0..FILES {
+        let section =
+            Section::Filelog(MPath::new(format!("dir/file_{:04}", file)).expect("Valid path"));
+        for index in 0..commits {
+            parts.push(Part::CgChunk(section.clone(), chunk(file + 2, index)));
+        }
+        parts.push(Part::SectionEnd(section));
+    }
+    parts.push(Part::End);
+    parts
+}
+
+pub fn benchmark_pack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("changegroup_pack");
+
+    for commits in [10, 100] {
+        let parts = changegroup_parts(commits);
+        group.throughput(Throughput::Bytes((FILES + 2) * commits * CHUNK_SIZE as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(commits), &parts, |b, parts| {
+            b.iter(|| {
+                CgPacker::new(stream::iter_ok::<_, Error>(parts.clone()))
+                    .collect()
+                    .wait()
+                    .expect("Pack failed")
+            });
+        });
+    }
+    group.finish();
+}
@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Deterministic generators for benchmark inputs.
+
+use mercurial_types::HgNodeHash;
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+pub const KB: usize = 1024;
+pub const MB: usize = KB * 1024;
+
+/// A random number generator that produces the same fixtures on every run.
+pub fn rng() -> SmallRng {
+    SmallRng::seed_from_u64(0x5eed)
+}
+
+/// A node hash unique to `seed` and `index`.
+pub fn node_hash(seed: u64, index: u64) -> HgNodeHash {
+    let mut bytes = [0u8; 20];
+    bytes[..8].copy_from_slice(&seed.to_be_bytes());
+    bytes[8..16].copy_from_slice(&index.to_be_bytes());
+    HgNodeHash::from_bytes(&bytes).expect("20 bytes is a valid hash")
+}
+
+/// Random content of the given size.
+pub fn content(rng: &mut SmallRng, size: usize) -> Vec<u8> {
+    let mut content = vec![0; size];
+    rng.fill(&mut content[..]);
+    content
+}
+
+/// Serialize manifest entries in Mercurial's manifest format.  Entries must
+/// be sorted by name.
+pub fn manifest_text(
+    entries: impl IntoIterator<Item = (String, HgNodeHash, &'static str)>,
+) -> Vec<u8> {
+    let mut text = Vec::new();
+    for (name, hash, flags) in entries {
+        text.extend_from_slice(name.as_bytes());
+        text.push(0);
+        text.extend_from_slice(hash.to_string().as_bytes());
+        text.extend_from_slice(flags.as_bytes());
+        text.push(b'\n');
+    }
+    text
+}
@@ -0,0 +1,31 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use mercurial_types::hash::Sha1;
+use mercurial_types::SHA1_BACKEND;
+
+use crate::fixtures::content;
+use crate::fixtures::rng;
+use crate::fixtures::KB;
+use crate::fixtures::MB;
+
+pub fn benchmark_sha1(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("sha1 ({})", SHA1_BACKEND));
+
+    let mut rng = rng();
+    for size in [64, 4 * KB, MB] {
+        let data = content(&mut rng, size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| Sha1::from(&data[..]));
+        });
+    }
+    group.finish();
+}
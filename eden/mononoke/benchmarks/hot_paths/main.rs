@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Benchmarks for hot paths in the server that don't need a real repo:
+//! SHA-1 hashing, manifest parsing and diffing, wirepack and changegroup
+//! encoding, and the blobstore caching layers.
+//!
+//! All fixtures are generated in memory, so results are comparable between
+//! machines and runs.  Use `--save-baseline` before a change and
+//! `--use-baseline` after it to compare.
+
+use std::time::Duration;
+
+use clap::App;
+use clap::Arg;
+use context::CoreContext;
+use criterion::Criterion;
+
+mod cache;
+mod changegroup;
+mod fixtures;
+mod hashing;
+mod manifest;
+mod wirepack;
+
+const ARG_SAVE_BASELINE: &str = "save-baseline";
+const ARG_USE_BASELINE: &str = "use-baseline";
+const ARG_FILTER_BENCHMARKS: &str = "filter";
+
+#[fbinit::main]
+fn main(fb: fbinit::FacebookInit) {
+    let matches = App::new("benchmark_hot_paths")
+        .arg(
+            Arg::with_name(ARG_SAVE_BASELINE)
+                .long(ARG_SAVE_BASELINE)
+                .takes_value(true)
+                .required(false)
+                .help("save results as a baseline under given name, for comparison"),
+        )
+        .arg(
+            Arg::with_name(ARG_USE_BASELINE)
+                .long(ARG_USE_BASELINE)
+                .takes_value(true)
+                .required(false)
+                .conflicts_with(ARG_SAVE_BASELINE)
+                .help("compare to named baseline instead of last run"),
+        )
+        .arg(
+            Arg::with_name(ARG_FILTER_BENCHMARKS)
+                .long(ARG_FILTER_BENCHMARKS)
+                .takes_value(true)
+                .required(false)
+                .multiple(true)
+                .help("limit to benchmarks whose name contains this string. Repetition tightens the filter"),
+        )
+        .get_matches();
+
+    let mut criterion = Criterion::default()
+        .measurement_time(Duration::from_secs(10))
+        .warm_up_time(Duration::from_secs(3));
+
+    if let Some(baseline) = matches.value_of(ARG_SAVE_BASELINE) {
+        criterion = criterion.save_baseline(baseline.to_string());
+    }
+    if let Some(baseline) = matches.value_of(ARG_USE_BASELINE) {
+        criterion = criterion.retain_baseline(baseline.to_string());
+    }
+
+    if let Some(filters) = matches.values_of(ARG_FILTER_BENCHMARKS) {
+        for filter in filters {
+            criterion = criterion.with_filter(filter.to_string())
+        }
+    }
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start runtime");
+    let ctx = CoreContext::test_mock(fb);
+
+    hashing::benchmark_sha1(&mut criterion);
+    manifest::benchmark_parse(&mut criterion);
+    manifest::benchmark_diff(&mut criterion, &ctx, runtime.handle());
+    wirepack::benchmark_verify(&mut criterion);
+    wirepack::benchmark_pack(&mut criterion);
+    changegroup::benchmark_pack(&mut criterion);
+    cache::benchmark_gets(&mut criterion, &ctx, runtime.handle());
+
+    criterion.final_summary();
+}
@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blobstore::Blobstore;
+use bytes::Bytes;
+use context::CoreContext;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use futures::stream::TryStreamExt;
+use manifest::ManifestOps;
+use memblob::Memblob;
+use mercurial_types::blobs::ManifestContent;
+use mercurial_types::HgManifestEnvelopeMut;
+use mercurial_types::HgManifestId;
+use mercurial_types::HgNodeHash;
+use tokio::runtime::Handle;
+
+use crate::fixtures::manifest_text;
+use crate::fixtures::node_hash;
+
+/// Number of directories in the tree used for diffing.
+const DIFF_DIRECTORIES: u64 = 100;
+/// Number of files in each directory of the tree used for diffing.
+const DIFF_FILES_PER_DIRECTORY: u64 = 1000;
+
+fn file_entries(seed: u64, count: u64) -> Vec<(String, HgNodeHash, &'static str)> {
+    (0..count)
+        .map(|index| {
+            let flags = match index % 10 {
+                0 => "x",
+                1 => "l",
+                _ => "",
+            };
+            (
+                format!("file_{:06}.txt", index),
+                node_hash(seed, index),
+                flags,
+            )
+        })
+        .collect()
+}
+
+pub fn benchmark_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("manifest_parse");
+
+    for entries in [100, 10_000, 100_000] {
+        let text = manifest_text(file_entries(0, entries));
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(entries), &text, |b, text| {
+            b.iter(|| ManifestContent::parse(text).expect("Parse failed"));
+        });
+    }
+    group.finish();
+}
+
+async fn store_manifest(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    node_id: HgNodeHash,
+    text: Vec<u8>,
+) -> Result<HgManifestId> {
+    let envelope = HgManifestEnvelopeMut {
+        node_id,
+        p1: None,
+        p2: None,
+        computed_node_id: node_id,
+        contents: Bytes::from(text),
+    }
+    .freeze();
+    let manifest_id = HgManifestId::new(node_id);
+    blobstore
+        .put(
+            ctx,
+            manifest_id.blobstore_key(),
+            envelope.into_blob().into(),
+        )
+        .await?;
+    Ok(manifest_id)
+}
+
+/// Store a two-level tree, where the first `changed` directories differ
+/// from the tree with `changed` of zero by a single file.
+async fn store_tree(
+    ctx: &CoreContext,
+    blobstore: &Arc<dyn Blobstore>,
+    changed: u64,
+) -> Result<HgManifestId> {
+    let mut directories = Vec::new();
+    for dir in 0..DIFF_DIRECTORIES {
+        let mut files = file_entries(dir, DIFF_FILES_PER_DIRECTORY);
+        let mut dir_id = node_hash(u64::MAX, dir);
+        if dir < changed {
+            files[0].1 = node_hash(u64::MAX - 1, dir);
+            dir_id = node_hash(u64::MAX - 2, dir);
+        }
+        store_manifest(ctx, blobstore, dir_id, manifest_text(files)).await?;
+        directories.push((format!("dir_{:04}", dir), dir_id, "t"));
+    }
+    let root_id = node_hash(u64::MAX - 3, changed);
+    store_manifest(ctx, blobstore, root_id, manifest_text(directories)).await
+}
+
+pub fn benchmark_diff(c: &mut Criterion, ctx: &CoreContext, runtime: &Handle) {
+    let mut group = c.benchmark_group("manifest_diff");
+
+    let blobstore: Arc<dyn Blobstore> = Arc::new(Memblob::default());
+    let base = runtime
+        .block_on(store_tree(ctx, &blobstore, 0))
+        .expect("Failed to store base tree");
+
+    for changed in [1, 10, DIFF_DIRECTORIES] {
+        let other = runtime
+            .block_on(store_tree(ctx, &blobstore, changed))
+            .expect("Failed to store changed tree");
+        group.throughput(Throughput::Elements(changed));
+        group.bench_with_input(BenchmarkId::from_parameter(changed), &other, |b, other| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let diff: Vec<_> = base
+                        .diff(ctx.clone(), blobstore.clone(), *other)
+                        .try_collect()
+                        .await
+                        .expect("Diff failed");
+                    assert_eq!(diff.len() as u64, changed * 2);
+                })
+            });
+        });
+    }
+    group.finish();
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use futures_old::stream;
+use futures_old::Future;
+use futures_old::Stream;
+use mercurial_bundles::wirepack::packer::WirePackPacker;
+use mercurial_bundles::wirepack::DataEntry;
+use mercurial_bundles::wirepack::Kind;
+use mercurial_bundles::wirepack::Part;
+use mercurial_types::delta::Delta;
+use mercurial_types::MPath;
+use mercurial_types::RepoPath;
+use mercurial_types::NULL_HASH;
+
+use crate::fixtures::content;
+use crate::fixtures::node_hash;
+use crate::fixtures::rng;
+use crate::fixtures::KB;
+
+const ENTRY_SIZE: usize = 4 * KB;
+
+/// Data entries, alternating between fulltexts and deltas against the
+/// previous entry.
+fn data_entries(count: u64) -> Vec<DataEntry> {
+    let mut rng = rng();
+    (0..count)
+        .map(|index| {
+            let delta_base = if index % 2 == 0 {
+                NULL_HASH
+            } else {
+                node_hash(0, index - 1)
+            };
+            DataEntry {
+                node: node_hash(0, index),
+                delta_base,
+                delta: Delta::new_fulltext(content(&mut rng, ENTRY_SIZE)),
+                metadata: None,
+            }
+        })
+        .collect()
+}
+
+pub fn benchmark_verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wirepack_data_entry_verify");
+
+    for count in [1_000, 100_000] {
+        let entries = data_entries(count);
+        group.throughput(Throughput::Elements(count));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &entries,
+            |b, entries| {
+                b.iter(|| {
+                    for entry in entries {
+                        entry.verify().expect("Verify failed");
+                    }
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+pub fn benchmark_pack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wirepack_pack");
+
+    for count in [100, 10_000] {
+        let path = RepoPath::FilePath(MPath::new("dir/file").expect("Valid path"));
+        let mut parts = vec![
+            Part::HistoryMeta {
+                path: path.clone(),
+                entry_count: 0,
+            },
+            Part::DataMeta {
+                path,
+                entry_count: count as u32,
+            },
+        ];
+        parts.extend(data_entries(count).into_iter().map(Part::Data));
+        parts.push(Part::End);
+
+        group.throughput(Throughput::Bytes(count * ENTRY_SIZE as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &parts, |b, parts| {
+            b.iter(|| {
+                WirePackPacker::new(stream::iter_ok(parts.clone()), Kind::File)
+                    .collect()
+                    .wait()
+                    .expect("Pack failed")
+            });
+        });
+    }
+    group.finish();
+}
@@ -31,6 +31,7 @@ use futures::future::TryFutureExt;
 use futures::stream::BoxStream;
 use futures_ext::FbTryFutureExt;
 use futures_stats::TimedTryFutureExt;
+use hg_sha256_mapping::record_hg_changeset;
 use mercurial_types::blobs::ChangesetMetadata;
 use mercurial_types::blobs::HgBlobChangeset;
 use mercurial_types::HgFileNodeId;
@@ -42,6 +43,7 @@ use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataArc;
 use scuba_ext::MononokeScubaSampleBuilder;
 use stats::prelude::*;
 use uuid::Uuid;
@@ -260,6 +262,10 @@ impl CreateChangeset {
                     }
                 }
 
+                bonsai_cs
+                    .validate()
+                    .map_err(|e| ErrorKind::InvalidBonsaiChangeset(cs_id, e))?;
+
                 scuba_logger
                     .add("changeset_id", format!("{}", cs_id))
                     .log_with_msg("Changeset uuid to hash mapping", None);
@@ -319,6 +325,8 @@ impl CreateChangeset {
 
         let complete_changesets = repo.get_changesets_object();
         let bonsai_hg_mapping = repo.bonsai_hg_mapping_arc().clone();
+        let repo_derived_data = repo.repo_derived_data_arc();
+        let blobstore = repo.get_blobstore();
         let changeset_complete_fut = async move {
             let ((hg_cs, bonsai_cs), _) = future::try_join(changeset, parents_complete).await?;
 
@@ -332,6 +340,18 @@ impl CreateChangeset {
                 .await
                 .context("While inserting into changeset table")?;
 
+            // record the SHA-256 id before the changeset can be found
+            if let Some(hg_sha256_mapping) = repo_derived_data.manager().hg_sha256_mapping() {
+                record_hg_changeset(
+                    &ctx,
+                    hg_sha256_mapping,
+                    &blobstore,
+                    hg_cs.get_changeset_id(),
+                )
+                .await
+                .context("While recording SHA-256 id")?;
+            }
+
             // update bonsai mapping
             let bcs_id = bonsai_cs.get_changeset_id();
             let bonsai_hg_entry = BonsaiHgMappingEntry {
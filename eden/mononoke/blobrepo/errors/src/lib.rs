@@ -19,6 +19,7 @@ use mercurial_types::MPath;
 use mercurial_types::RepoPath;
 use mercurial_types::Type;
 use mononoke_types::hash::Sha256;
+use mononoke_types::BonsaiValidationError;
 use mononoke_types::ChangesetId;
 use mononoke_types::FileType;
 use thiserror::Error;
@@ -97,6 +98,8 @@ pub enum ErrorKind {
          computed: {1} for blob: {2:#?}"
     )]
     InconsistentChangesetHash(HgNodeHash, HgNodeHash, HgBlobChangeset),
+    #[error("Changeset {0} is not a valid bonsai changeset: {1}")]
+    InvalidBonsaiChangeset(HgNodeHash, BonsaiValidationError),
     #[error("Bookmark {0} does not exist")]
     BookmarkNotFound(AsciiString),
     #[error("Unresolved conflict at {0} with parents: {1:?}")]
@@ -13,13 +13,23 @@ use blobstore::Blobstore;
 use blobstore::BlobstoreGetData;
 use blobstore::BlobstoreIsPresent;
 use context::CoreContext;
+use context::PerfCounterType;
 use mononoke_types::BlobstoreBytes;
 use mononoke_types::RepositoryId;
 use prefixblob::PrefixBlobstore;
+use redactedblobstore::config::GET_OPERATION;
 use redactedblobstore::RedactedBlobs;
 use redactedblobstore::RedactedBlobstore;
 use redactedblobstore::RedactedBlobstoreConfig;
 use scuba_ext::MononokeScubaSampleBuilder;
+use stats::prelude::*;
+use tunables::tunables;
+
+define_stats! {
+    prefix = "mononoke.repo_blobstore";
+    memo_hits: timeseries(Rate, Sum),
+    memo_hit_bytes: timeseries(Sum),
+}
 
 /// RedactedBlobstore should be part of every blobstore since it is a layer
 /// which adds security by preventing users to access sensitive content.
@@ -103,7 +113,30 @@ impl Blobstore for RepoBlobstore {
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<Option<BlobstoreGetData>> {
-        self.0.0.get(ctx, key).await
+        let max_size = tunables().get_session_blob_memo_max_bytes();
+        if max_size <= 0 {
+            return self.0.0.get(ctx, key).await;
+        }
+
+        // Blobs fetched more than once during a session, for instance by
+        // different parts of a getbundle, are memoized in the session.  The
+        // redaction check still applies to every fetch.
+        let blobstore = self.0.0.access_blobstore(ctx, key, GET_OPERATION)?;
+        let memo_key = blobstore.prepend(key);
+        let memo = ctx.session().blob_memo();
+        if let Some(data) = memo.get::<BlobstoreGetData>(&memo_key) {
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::BlobGetsMemoized);
+            STATS::memo_hits.add_value(1);
+            STATS::memo_hit_bytes.add_value(data.len() as i64);
+            return Ok(Some(data));
+        }
+
+        let data = blobstore.get(ctx, key).await?;
+        if let Some(ref data) = data {
+            memo.insert(memo_key, data.clone(), data.len(), max_size as usize);
+        }
+        Ok(data)
     }
     async fn put<'a>(
         &'a self,
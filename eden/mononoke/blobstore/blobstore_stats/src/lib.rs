@@ -71,6 +71,19 @@ impl From<OperationType> for ScubaValue {
     }
 }
 
+/// The type of blob that a key is for, e.g. `content` for
+/// `repo0000.content.blake2.<hash>`.  Repo and bubble prefixes are skipped.
+pub fn key_type(key: &str) -> &str {
+    let is_prefix = |part: &str, prefix: &str| {
+        part.strip_prefix(prefix).map_or(false, |id| {
+            !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())
+        })
+    };
+    key.split('.')
+        .find(|part| !is_prefix(part, "repo") && !is_prefix(part, "eph"))
+        .unwrap_or(key)
+}
+
 pub fn add_completion_time(
     scuba: &mut MononokeScubaSampleBuilder,
     session: &str,
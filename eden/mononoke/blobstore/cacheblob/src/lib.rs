@@ -28,3 +28,5 @@ pub use crate::memcache_cache_lease::MemcacheOps;
 
 mod mem_writes;
 pub use crate::mem_writes::MemWritesBlobstore;
+
+mod negative_cache;
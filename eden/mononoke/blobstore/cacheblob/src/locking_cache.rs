@@ -6,6 +6,7 @@
  */
 
 use std::fmt;
+use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -13,6 +14,7 @@ use blobstore::Blobstore;
 use blobstore::BlobstoreGetData;
 use blobstore::BlobstoreIsPresent;
 use blobstore::CountedBlobstore;
+use blobstore_stats::key_type;
 use cloned::cloned;
 use context::CoreContext;
 use context::PerfCounterType;
@@ -24,12 +26,16 @@ use redactedblobstore::config::GET_OPERATION;
 use redactedblobstore::RedactedBlobstore;
 use stats::prelude::*;
 
+use crate::negative_cache::NegativeCache;
+
 define_stats! {
     prefix = "mononoke.blobstore.cacheblob";
     get_miss: dynamic_timeseries("{}.get_miss", (cache_name: &'static str); Rate, Sum),
     get_hit: dynamic_timeseries("{}.get_hit", (cache_name: &'static str); Rate, Sum),
     presence_hit: dynamic_timeseries("{}.presence_hit", (cache_name: &'static str); Rate, Sum),
     presence_miss: dynamic_timeseries("{}.presence_miss", (cache_name: &'static str); Rate, Sum),
+    // Lookups answered from the negative cache instead of the backing store.
+    negative_cache_hit: dynamic_timeseries("{}.negative_cache_hit", (cache_name: &'static str); Rate, Sum),
 }
 
 /// Extra operations that can be performed on a cache. Other wrappers can implement this trait for
@@ -120,7 +126,10 @@ pub trait LeaseOps: fmt::Display + fmt::Debug + Send + Sync {
 
 /// A caching layer over a blobstore, using a cache defined by its CacheOps. The idea is that
 /// generic code that any caching layer needs is defined here, while code that's cache-specific
-/// goes into CacheOps
+/// goes into CacheOps.
+///
+/// Keys that the backing store doesn't have are remembered for a while in a `NegativeCache`, so
+/// that repeated lookups of them don't all reach the backing store.
 #[derive(Clone)]
 pub struct CacheBlobstore<C, L, T>
 where
@@ -132,6 +141,7 @@ where
     cache: C,
     lease: L,
     lazy_cache_put: bool,
+    negative_cache: Arc<NegativeCache>,
 }
 
 impl<C, L, T> fmt::Display for CacheBlobstore<C, L, T>
@@ -161,6 +171,7 @@ where
             cache,
             lease,
             lazy_cache_put,
+            negative_cache: Arc::new(NegativeCache::default()),
         }
     }
 
@@ -191,23 +202,46 @@ where
         ctx: &'a CoreContext,
         key: &'a str,
     ) -> Result<Option<BlobstoreGetData>> {
+        let mut span = ctx.trace().start_span("cache_get");
+        span.add("key_type", key_type(key))
+            .add("cache", C::CACHE_NAME);
         let blob = self.cache.get(key).await;
-        if blob.is_some() {
+        if let Some(ref data) = blob {
             if let Some(counter) = C::HIT_COUNTER {
                 ctx.perf_counters().increment_counter(counter);
             }
             STATS::get_hit.add_value(1, (C::CACHE_NAME,));
+            span.add("hit", true).add("size", data.len());
+            span.finish();
             Ok(blob)
         } else {
             if let Some(counter) = C::MISS_COUNTER {
                 ctx.perf_counters().increment_counter(counter);
             }
             STATS::get_miss.add_value(1, (C::CACHE_NAME,));
-            let blob = self.blobstore.get(ctx, key).await?;
+            span.add("hit", false);
+            if self.negative_cache.is_missing(key, ctx.now()) {
+                STATS::negative_cache_hit.add_value(1, (C::CACHE_NAME,));
+                span.add("negative_cache_hit", true);
+                span.finish();
+                return Ok(None);
+            }
+            let started = ctx.now();
+            // Reads from the next tier are children of the miss.
+            let blob = if span.trace().is_enabled() {
+                let ctx = ctx.clone_with_trace(span.trace().clone());
+                self.blobstore.get(&ctx, key).await
+            } else {
+                self.blobstore.get(ctx, key).await
+            };
+            span.finish();
+            let blob = blob?;
             if let Some(ref blob) = blob {
                 let key = key.to_owned();
                 cloned!(self.cache, blob);
                 tokio::spawn(async move { cache.put(&key, blob).await });
+            } else {
+                self.negative_cache.insert_missing(key, started, ctx.now());
             }
             Ok(blob)
         }
@@ -221,7 +255,11 @@ where
     ) -> Result<()> {
         let can_put = self.take_put_lease(&key).await;
         if can_put {
-            self.blobstore.put(ctx, key.clone(), value.clone()).await?;
+            let result = self.blobstore.put(ctx, key.clone(), value.clone()).await;
+            // Invalidate once the blob is in the backing store, so that
+            // lookups racing with the put can't mark it as missing again.
+            self.negative_cache.invalidate(&key, ctx.now());
+            result?;
 
             cloned!(self.cache, self.lease);
             let cache_put = async move {
@@ -233,6 +271,8 @@ where
             } else {
                 let _ = cache_put.await;
             }
+        } else {
+            self.negative_cache.invalidate(&key, ctx.now());
         }
         Ok(())
     }
@@ -248,7 +288,16 @@ where
             Ok(BlobstoreIsPresent::Present)
         } else {
             STATS::presence_miss.add_value(1, (C::CACHE_NAME,));
-            self.blobstore.is_present(ctx, key).await
+            if self.negative_cache.is_missing(key, ctx.now()) {
+                STATS::negative_cache_hit.add_value(1, (C::CACHE_NAME,));
+                return Ok(BlobstoreIsPresent::Absent);
+            }
+            let started = ctx.now();
+            let present = self.blobstore.is_present(ctx, key).await?;
+            if let BlobstoreIsPresent::Absent = present {
+                self.negative_cache.insert_missing(key, started, ctx.now());
+            }
+            Ok(present)
         }
     }
 }
@@ -365,3 +414,64 @@ impl<T: CacheBlobstoreExt> CacheBlobstoreExt for RedactedBlobstore<T> {
         blobstore.get_cache_only(ctx, key).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use futures::FutureExt;
+    use maplit::hashmap;
+    use memblob::Memblob;
+    use tunables::with_tunables_async;
+    use tunables::MononokeTunables;
+
+    use super::*;
+    use crate::dummy::DummyCache;
+    use crate::dummy::DummyLease;
+
+    #[fbinit::test]
+    async fn test_negative_cache(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let tunables = MononokeTunables::default();
+        tunables.update_ints(&hashmap! {
+            "blobstore_negative_cache_ttl_ms".to_string() => 3_600_000,
+        });
+        let inner = Memblob::default();
+        let blobstore = CacheBlobstore::new(DummyCache {}, DummyLease {}, inner.clone(), false);
+        let value = BlobstoreBytes::from_bytes("value");
+
+        with_tunables_async(
+            tunables,
+            async move {
+                borrowed!(ctx);
+                assert!(blobstore.get(ctx, "key").await?.is_none());
+                assert!(matches!(
+                    blobstore.is_present(ctx, "other").await?,
+                    BlobstoreIsPresent::Absent
+                ));
+
+                // Writes by others aren't seen while the keys are remembered
+                // as missing.
+                inner.put(ctx, "key".to_string(), value.clone()).await?;
+                inner.put(ctx, "other".to_string(), value.clone()).await?;
+                assert!(blobstore.get(ctx, "key").await?.is_none());
+                assert!(matches!(
+                    blobstore.is_present(ctx, "other").await?,
+                    BlobstoreIsPresent::Absent
+                ));
+
+                // Writes through the blobstore are.
+                blobstore.put(ctx, "key".to_string(), value.clone()).await?;
+                blobstore.put(ctx, "other".to_string(), value).await?;
+                assert!(blobstore.get(ctx, "key").await?.is_some());
+                assert!(matches!(
+                    blobstore.is_present(ctx, "other").await?,
+                    BlobstoreIsPresent::Present
+                ));
+                Ok(())
+            }
+            .boxed(),
+        )
+        .await
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use tunables::tunables;
+
+/// Maximum number of keys remembered as missing or written.  When it is
+/// reached, the expired keys are dropped, and if that doesn't free any
+/// space, all of them.
+const MAX_NEGATIVE_CACHE_ENTRIES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    /// The key was found to be missing at this time.
+    Missing(SystemTime),
+    /// The key was written at this time.  Kept as a tombstone so that
+    /// lookups that started before the write don't record the key as missing
+    /// after it.
+    Written(SystemTime),
+}
+
+impl Entry {
+    fn at(&self) -> SystemTime {
+        match self {
+            Entry::Missing(at) | Entry::Written(at) => *at,
+        }
+    }
+
+    fn is_expired(&self, now: SystemTime, ttl: Duration) -> bool {
+        now.duration_since(self.at()).unwrap_or_default() >= ttl
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    keys: HashMap<String, Entry>,
+    /// When all of the keys, and so their tombstones, were last dropped.
+    /// Lookups that started before then aren't recorded.
+    cleared: Option<SystemTime>,
+}
+
+impl Entries {
+    fn insert(&mut self, key: &str, entry: Entry, now: SystemTime, ttl: Duration) {
+        if self.keys.len() >= MAX_NEGATIVE_CACHE_ENTRIES && !self.keys.contains_key(key) {
+            self.keys.retain(|_, entry| !entry.is_expired(now, ttl));
+            if self.keys.len() >= MAX_NEGATIVE_CACHE_ENTRIES {
+                self.keys.clear();
+                self.cleared = Some(now);
+            }
+        }
+        self.keys.insert(key.to_string(), entry);
+    }
+}
+
+/// Keys that were recently found to be missing from the backing store, so
+/// that repeated lookups of them, e.g. by clients probing for objects during
+/// discovery, don't all reach the backing store.
+///
+/// Keys are remembered for `blobstore_negative_cache_ttl_ms`, and only while
+/// it is non-zero.  Puts through this process invalidate the key, but puts
+/// by other processes don't, so the TTL bounds how long a blob written
+/// elsewhere can appear to be missing.
+///
+/// Callers pass in the current time from their context's clock, so that
+/// tests can control expiry.
+#[derive(Debug, Default)]
+pub struct NegativeCache {
+    entries: Mutex<Entries>,
+}
+
+impl NegativeCache {
+    fn ttl() -> Option<Duration> {
+        let ttl_ms = tunables().get_blobstore_negative_cache_ttl_ms();
+        if ttl_ms > 0 {
+            Some(Duration::from_millis(ttl_ms as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the key was found to be missing within the TTL.
+    pub fn is_missing(&self, key: &str, now: SystemTime) -> bool {
+        let ttl = match Self::ttl() {
+            Some(ttl) => ttl,
+            None => return false,
+        };
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        match entries.keys.get(key) {
+            Some(entry) if entry.is_expired(now, ttl) => {
+                entries.keys.remove(key);
+                false
+            }
+            Some(Entry::Missing(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Record that a lookup of the backing store which started at `started`
+    /// found the key to be missing, unless the key has been written since.
+    pub fn insert_missing(&self, key: &str, started: SystemTime, now: SystemTime) {
+        let ttl = match Self::ttl() {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        if entries.cleared.map_or(false, |cleared| cleared >= started) {
+            return;
+        }
+        if let Some(Entry::Written(written)) = entries.keys.get(key) {
+            if *written >= started {
+                return;
+            }
+        }
+        entries.insert(key, Entry::Missing(now), now, ttl);
+    }
+
+    /// Forget that the key was missing, because it has been written.
+    pub fn invalidate(&self, key: &str, now: SystemTime) {
+        let ttl = match Self::ttl() {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        self.entries
+            .lock()
+            .expect("lock poisoned")
+            .insert(key, Entry::Written(now), now, ttl);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+    use tunables::with_tunables;
+    use tunables::MononokeTunables;
+
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        let tunables = MononokeTunables::default();
+        tunables.update_ints(&hashmap! {
+            "blobstore_negative_cache_ttl_ms".to_string() => 3_600_000,
+        });
+        with_tunables(tunables, || {
+            let cache = NegativeCache::default();
+            assert!(!cache.is_missing("key", at(0)));
+
+            cache.insert_missing("key", at(0), at(1));
+            assert!(cache.is_missing("key", at(1)));
+            assert!(!cache.is_missing("other", at(1)));
+
+            cache.invalidate("key", at(2));
+            assert!(!cache.is_missing("key", at(2)));
+
+            // A lookup that started before a put doesn't mark the key as
+            // missing, but one of another key does.
+            cache.invalidate("key", at(4));
+            cache.insert_missing("key", at(3), at(5));
+            cache.insert_missing("other", at(3), at(5));
+            assert!(!cache.is_missing("key", at(5)));
+            assert!(cache.is_missing("other", at(5)));
+
+            // A lookup that started after the put does.
+            cache.insert_missing("key", at(6), at(7));
+            assert!(cache.is_missing("key", at(7)));
+
+            // Keys are forgotten once the TTL has passed.
+            assert!(cache.is_missing("key", at(3_600)));
+            assert!(!cache.is_missing("key", at(3_607)));
+        });
+    }
+
+    #[test]
+    fn test_negative_cache_disabled() {
+        with_tunables(MononokeTunables::default(), || {
+            let cache = NegativeCache::default();
+            cache.insert_missing("key", at(0), at(1));
+            assert!(!cache.is_missing("key", at(1)));
+            cache.invalidate("key", at(2));
+            assert!(cache.entries.lock().unwrap().keys.is_empty());
+        });
+    }
+}
@@ -0,0 +1,212 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use stats::prelude::*;
+use thiserror::Error;
+
+define_stats! {
+    prefix = "mononoke.blobstore.circuit_breaker";
+    tripped: dynamic_timeseries("{}.tripped", (backend: String); Rate, Sum),
+    rejected: dynamic_timeseries("{}.rejected", (backend: String); Rate, Sum),
+    closed: dynamic_timeseries("{}.closed", (backend: String); Rate, Sum),
+}
+
+#[derive(Debug, Error)]
+#[error("Circuit breaker for {0} is open")]
+pub struct CircuitOpen(pub String);
+
+/// When a circuit breaker trips, and for how long it stays open.
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerOptions {
+    /// Length of the windows over which the error rate is measured.
+    pub window: Duration,
+    /// Minimum number of requests in a window for the breaker to trip.
+    pub min_requests: u64,
+    /// Proportion of requests in a window that must fail for the breaker to
+    /// trip, greater than 0 and at most 1.
+    pub error_rate: f64,
+    /// How long the breaker stays open before letting a probe through.
+    pub open_duration: Duration,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Requests go through, and their outcomes are counted.
+    Closed {
+        window_start: Instant,
+        requests: u64,
+        errors: u64,
+    },
+    /// Requests fail fast until the given time.
+    Open { until: Instant },
+    /// A single probe request has been let through.  Its outcome decides
+    /// whether the breaker closes or opens again.  If the probe is dropped
+    /// before it completes, another one is let through after a while.
+    HalfOpen { probe_start: Instant },
+}
+
+impl State {
+    fn closed(now: Instant) -> Self {
+        State::Closed {
+            window_start: now,
+            requests: 0,
+            errors: 0,
+        }
+    }
+}
+
+/// Circuit breaker for a backend.  Once too many requests to the backend
+/// fail, the breaker opens and further requests fail immediately rather than
+/// waiting on a backend that is unlikely to answer.  After a while a probe
+/// request is let through, and if it succeeds the breaker closes again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    backend: String,
+    options: CircuitBreakerOptions,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(backend: impl Into<String>, options: CircuitBreakerOptions) -> Self {
+        Self {
+            backend: backend.into(),
+            options,
+            state: Mutex::new(State::closed(Instant::now())),
+        }
+    }
+
+    /// Check whether a request may go to the backend.  If it may, its outcome
+    /// must be passed to `record`.
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        self.check_at(Instant::now())
+    }
+
+    /// Record the outcome of a request that was allowed by `check`.
+    pub fn record(&self, success: bool) {
+        self.record_at(Instant::now(), success)
+    }
+
+    fn check_at(&self, now: Instant) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock().expect("lock poisoned");
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen { probe_start: now };
+                Ok(())
+            }
+            State::HalfOpen { probe_start }
+                if now.saturating_duration_since(probe_start) >= self.options.open_duration =>
+            {
+                *state = State::HalfOpen { probe_start: now };
+                Ok(())
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                STATS::rejected.add_value(1, (self.backend.clone(),));
+                Err(CircuitOpen(self.backend.clone()))
+            }
+        }
+    }
+
+    fn record_at(&self, now: Instant, success: bool) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        match &mut *state {
+            State::Closed {
+                window_start,
+                requests,
+                errors,
+            } => {
+                if now.saturating_duration_since(*window_start) >= self.options.window {
+                    *window_start = now;
+                    *requests = 0;
+                    *errors = 0;
+                }
+                *requests += 1;
+                if !success {
+                    *errors += 1;
+                }
+                if *requests >= self.options.min_requests
+                    && (*errors as f64) >= (*requests as f64) * self.options.error_rate
+                {
+                    STATS::tripped.add_value(1, (self.backend.clone(),));
+                    *state = State::Open {
+                        until: now + self.options.open_duration,
+                    };
+                }
+            }
+            State::HalfOpen { .. } if success => {
+                STATS::closed.add_value(1, (self.backend.clone(),));
+                *state = State::closed(now);
+            }
+            State::HalfOpen { .. } => {
+                *state = State::Open {
+                    until: now + self.options.open_duration,
+                };
+            }
+            // Requests that were let through before the breaker opened.
+            State::Open { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OPTIONS: CircuitBreakerOptions = CircuitBreakerOptions {
+        window: Duration::from_secs(10),
+        min_requests: 4,
+        error_rate: 0.5,
+        open_duration: Duration::from_secs(5),
+    };
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new("test", OPTIONS);
+        let start = Instant::now();
+
+        // Too few requests to trip, whatever their outcome.
+        for _ in 0..3 {
+            assert!(breaker.check_at(start).is_ok());
+            breaker.record_at(start, false);
+        }
+        assert!(breaker.check_at(start).is_ok());
+        breaker.record_at(start, true);
+        assert!(breaker.check_at(start).is_err());
+
+        // Once open, a probe is let through after a while, and only one.
+        let later = start + Duration::from_secs(6);
+        assert!(breaker.check_at(later).is_ok());
+        assert!(breaker.check_at(later).is_err());
+
+        // A failed probe opens the breaker again.
+        breaker.record_at(later, false);
+        assert!(breaker.check_at(later + Duration::from_secs(1)).is_err());
+
+        // A successful probe closes it.
+        let later = later + Duration::from_secs(6);
+        assert!(breaker.check_at(later).is_ok());
+        breaker.record_at(later, true);
+        assert!(breaker.check_at(later).is_ok());
+    }
+
+    #[test]
+    fn test_window() {
+        let breaker = CircuitBreaker::new("test", OPTIONS);
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_at(start, false);
+        }
+        // The errors of previous windows don't count.
+        let later = start + Duration::from_secs(11);
+        breaker.record_at(later, false);
+        assert!(breaker.check_at(later).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use metaconfig_types::BlobstoreId;
+use mononoke_types::BlobstoreBytes;
+
+mod breaker;
+
+pub use crate::breaker::CircuitBreaker;
+pub use crate::breaker::CircuitBreakerOptions;
+pub use crate::breaker::CircuitOpen;
+
+/// The circuit breakers of the multiplexed blobstore components used by a
+/// process, keyed by blobstore id.  Repos whose multiplexed blobstores share
+/// a component share its breaker.
+#[derive(Clone, Debug)]
+pub struct CircuitBreakers {
+    options: CircuitBreakerOptions,
+    breakers: Arc<Mutex<HashMap<BlobstoreId, Arc<CircuitBreaker>>>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(options: CircuitBreakerOptions) -> Result<Self> {
+        if !(options.error_rate > 0.0 && options.error_rate <= 1.0) {
+            bail!(
+                "Circuit breaker error rate must be greater than 0 and at most 1, got {}",
+                options.error_rate
+            );
+        }
+        Ok(Self {
+            options,
+            breakers: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// The circuit breaker for a multiplexed blobstore component.
+    pub fn get(&self, blobstore_id: BlobstoreId) -> Arc<CircuitBreaker> {
+        self.breakers
+            .lock()
+            .expect("lock poisoned")
+            .entry(blobstore_id)
+            .or_insert_with(|| {
+                Arc::new(CircuitBreaker::new(
+                    format!("blobstore{}", blobstore_id),
+                    self.options,
+                ))
+            })
+            .clone()
+    }
+}
+
+/// A layer over an existing blobstore that stops sending it requests once
+/// too many of them fail.  While the circuit breaker is open, gets and puts
+/// fail immediately, and presence checks report that the blob is probably
+/// not present, so that callers such as the multiplexed blobstore can carry
+/// on with the other stores rather than wait for this one to time out.
+#[derive(Debug)]
+pub struct CircuitBreakerBlobstore<T> {
+    blobstore: T,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for CircuitBreakerBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CircuitBreakerBlobstore<{}>", &self.blobstore)
+    }
+}
+
+impl<T> CircuitBreakerBlobstore<T> {
+    pub fn new(blobstore: T, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { blobstore, breaker }
+    }
+
+    async fn call<V>(&self, fut: impl Future<Output = Result<V>>) -> Result<V> {
+        self.breaker.check()?;
+        let result = fut.await;
+        self.breaker.record(result.is_ok());
+        result
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> Blobstore for CircuitBreakerBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.call(self.blobstore.get(ctx, key)).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.call(self.blobstore.put(ctx, key, value)).await
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        if let Err(open) = self.breaker.check() {
+            return Ok(BlobstoreIsPresent::ProbablyNotPresent(open.into()));
+        }
+        let result = self.blobstore.is_present(ctx, key).await;
+        self.breaker.record(matches!(
+            result,
+            Ok(BlobstoreIsPresent::Present | BlobstoreIsPresent::Absent)
+        ));
+        result
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for CircuitBreakerBlobstore<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.call(self.blobstore.put_explicit(ctx, key, value, put_behaviour))
+            .await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.call(self.blobstore.put_with_status(ctx, key, value))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn options(error_rate: f64) -> CircuitBreakerOptions {
+        CircuitBreakerOptions {
+            window: Duration::from_secs(10),
+            min_requests: 4,
+            error_rate,
+            open_duration: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_error_rate_validation() {
+        assert!(CircuitBreakers::new(options(0.0)).is_err());
+        assert!(CircuitBreakers::new(options(-0.5)).is_err());
+        assert!(CircuitBreakers::new(options(1.5)).is_err());
+        assert!(CircuitBreakers::new(options(f64::NAN)).is_err());
+        assert!(CircuitBreakers::new(options(0.5)).is_ok());
+        assert!(CircuitBreakers::new(options(1.0)).is_ok());
+    }
+
+    #[test]
+    fn test_breakers_keyed_by_blobstore_id() -> Result<()> {
+        let breakers = CircuitBreakers::new(options(0.5))?;
+        let first = breakers.get(BlobstoreId::new(1));
+        assert!(Arc::ptr_eq(&first, &breakers.get(BlobstoreId::new(1))));
+        assert!(!Arc::ptr_eq(&first, &breakers.get(BlobstoreId::new(2))));
+        Ok(())
+    }
+}
@@ -36,6 +36,7 @@ use metaconfig_types::RepoConfigArc;
 use mononoke_types::repo::EPH_ID_PREFIX;
 use mononoke_types::repo::EPH_ID_SUFFIX;
 use mononoke_types::DateTime;
+use mutable_counters::MutableCountersArc;
 use prefixblob::PrefixBlobstore;
 use repo_blobstore::RepoBlobstore;
 use repo_blobstore::RepoBlobstoreRef;
@@ -413,17 +414,24 @@ impl Bubble {
     pub fn repo_view(
         &self,
         container: &(
-             impl RepoBlobstoreRef + RepoIdentityRef + RepoIdentityArc + ChangesetsArc + RepoConfigArc
+             impl RepoBlobstoreRef
+             + RepoIdentityRef
+             + RepoIdentityArc
+             + ChangesetsArc
+             + RepoConfigArc
+             + MutableCountersArc
          ),
     ) -> EphemeralRepoView {
         let repo_blobstore = self.wrap_repo_blobstore(container.repo_blobstore().clone());
         let repo_identity = container.repo_identity_arc();
         let repo_config = container.repo_config_arc();
+        let mutable_counters = container.mutable_counters_arc();
         EphemeralRepoView {
             repo_blobstore: Arc::new(repo_blobstore.clone()),
             changesets: Arc::new(self.changesets_with_blobstore(repo_blobstore, container)),
             repo_identity,
             repo_config,
+            mutable_counters,
         }
     }
 
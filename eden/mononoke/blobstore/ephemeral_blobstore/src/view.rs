@@ -7,6 +7,7 @@
 
 use changesets::Changesets;
 use metaconfig_types::RepoConfig;
+use mutable_counters::MutableCounters;
 use repo_blobstore::RepoBlobstore;
 use repo_identity::RepoIdentity;
 
@@ -23,4 +24,7 @@ pub struct EphemeralRepoView {
 
     #[facet]
     pub(crate) repo_config: RepoConfig,
+
+    #[facet]
+    pub(crate) mutable_counters: dyn MutableCounters,
 }
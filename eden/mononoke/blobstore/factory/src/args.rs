@@ -7,14 +7,19 @@
 
 use std::num::NonZeroU32;
 use std::num::NonZeroUsize;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
 use arg_extensions::ArgDefaults;
+use circuitbreakerblob::CircuitBreakerOptions;
+use circuitbreakerblob::CircuitBreakers;
 use clap::Args;
 use metaconfig_types::PackFormat;
 use rand_distr::Normal;
+use retry::RetryLogic;
+use retry::RetryPolicy;
 
 use crate::PutBehaviour;
 
@@ -104,6 +109,38 @@ pub struct BlobstoreArgs {
     /// Desired blobstore behaviour when a put is made to an existing key.
     #[clap(long)]
     pub blobstore_put_behaviour: Option<PutBehaviour>,
+
+    /// Maximum number of attempts for operations on the underlying
+    /// blobstores.  Failed operations are not retried if this isn't set.
+    #[clap(long)]
+    pub blobstore_retry_attempts: Option<usize>,
+
+    /// Delay before the first retry of a blobstore operation.  Later retries
+    /// back off exponentially, with jitter.
+    #[clap(long, default_value_t = 100)]
+    pub blobstore_retry_base_delay_ms: u64,
+
+    /// Proportion of failed operations, greater than 0 and at most 1, at
+    /// which requests to a component of a multiplexed blobstore are stopped
+    /// for a while.  Components are not protected by circuit breakers if
+    /// this isn't set.
+    #[clap(long)]
+    pub blobstore_circuit_breaker_error_rate: Option<f64>,
+
+    /// Minimum number of operations in a window for a circuit breaker to
+    /// trip.
+    #[clap(long, default_value_t = 20)]
+    pub blobstore_circuit_breaker_min_requests: u64,
+
+    /// Length of the windows over which circuit breakers measure the error
+    /// rate.
+    #[clap(long, default_value_t = 10)]
+    pub blobstore_circuit_breaker_window_secs: u64,
+
+    /// How long a circuit breaker stays open before probing the blobstore
+    /// again.
+    #[clap(long, default_value_t = 5)]
+    pub blobstore_circuit_breaker_open_secs: u64,
 }
 
 impl BlobstoreArgs {
@@ -122,6 +159,37 @@ impl BlobstoreArgs {
         }
     }
 
+    pub fn retry_policy(&self) -> Option<RetryPolicy> {
+        let base = Duration::from_millis(self.blobstore_retry_base_delay_ms);
+        self.blobstore_retry_attempts
+            .filter(|attempts| *attempts > 1)
+            .map(|attempts| {
+                RetryPolicy::new(
+                    "blobstore",
+                    attempts,
+                    RetryLogic::ExponentialWithJitter {
+                        base,
+                        factor: 2.0,
+                        jitter: base,
+                    },
+                )
+            })
+    }
+
+    pub fn circuit_breakers(&self) -> Result<Option<CircuitBreakers>> {
+        self.blobstore_circuit_breaker_error_rate
+            .map(|error_rate| {
+                CircuitBreakers::new(CircuitBreakerOptions {
+                    window: Duration::from_secs(self.blobstore_circuit_breaker_window_secs),
+                    min_requests: self.blobstore_circuit_breaker_min_requests,
+                    error_rate,
+                    open_duration: Duration::from_secs(self.blobstore_circuit_breaker_open_secs),
+                })
+                .context("Invalid arguments: --blobstore-circuit-breaker-error-rate")
+            })
+            .transpose()
+    }
+
     pub fn get_delay_distribution(&self) -> Result<Option<Normal<f64>>> {
         delay_distribution(
             self.blobstore_get_mean_delay_secs,
@@ -27,8 +27,11 @@ use cacheblob::CachelibBlobstoreOptions;
 use cached_config::ConfigStore;
 use chaosblob::ChaosBlobstore;
 use chaosblob::ChaosOptions;
+use circuitbreakerblob::CircuitBreakerBlobstore;
+use circuitbreakerblob::CircuitBreakers;
 use delayblob::DelayOptions;
 use delayblob::DelayedBlobstore;
+use fallbackblob::FallbackBlobstore;
 use fbinit::FacebookInit;
 use fileblob::Fileblob;
 use futures::future;
@@ -56,6 +59,8 @@ use multiplexedblob_wal::WalMultiplexedBlobstore;
 use packblob::PackBlob;
 use packblob::PackOptions;
 use readonlyblob::ReadOnlyBlobstore;
+use retry::RetryPolicy;
+use retryblob::RetryBlobstore;
 use samplingblob::ComponentSamplingHandler;
 use samplingblob::SamplingBlobstorePutOps;
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -82,6 +87,8 @@ pub struct BlobstoreOptions {
     pub put_behaviour: PutBehaviour,
     pub scrub_options: Option<ScrubOptions>,
     pub sqlblob_mysql_options: MysqlOptions,
+    pub retry_policy: Option<RetryPolicy>,
+    pub circuit_breakers: Option<CircuitBreakers>,
 }
 
 impl BlobstoreOptions {
@@ -108,6 +115,26 @@ impl BlobstoreOptions {
             // These are added via the builder methods
             scrub_options: None,
             sqlblob_mysql_options,
+            retry_policy: None,
+            circuit_breakers: None,
+        }
+    }
+
+    /// Retry failed operations on the underlying blobstores with this
+    /// policy.
+    pub fn with_retry_policy(self, retry_policy: Option<RetryPolicy>) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Stop sending requests to multiplexed blobstore components that fail
+    /// too often, using these circuit breakers.
+    pub fn with_circuit_breakers(self, circuit_breakers: Option<CircuitBreakers>) -> Self {
+        Self {
+            circuit_breakers,
+            ..self
         }
     }
 
@@ -634,6 +661,44 @@ fn make_blobstore_put_ops<'a>(
                     })?;
                 Arc::new(LogBlob::new(store, scuba, scuba_sample_rate)) as Arc<dyn BlobstorePutOps>
             }
+            Fallback { primary, secondary } => {
+                needs_wrappers = false;
+                let primary = make_blobstore_put_ops(
+                    fb,
+                    *primary,
+                    mysql_options,
+                    readonly_storage,
+                    blobstore_options,
+                    logger,
+                    config_store,
+                    scrub_handler,
+                    component_sampler,
+                    None,
+                )
+                .watched(logger)
+                .await?;
+                let secondary = make_blobstore_put_ops(
+                    fb,
+                    *secondary,
+                    mysql_options,
+                    // The secondary is never written to.
+                    ReadOnlyStorage(true),
+                    blobstore_options,
+                    logger,
+                    config_store,
+                    scrub_handler,
+                    component_sampler,
+                    None,
+                )
+                .watched(logger)
+                .await?;
+                Arc::new(FallbackBlobstore::new(
+                    primary,
+                    secondary,
+                    // Read-only storage can't be backfilled.
+                    !readonly_storage.0,
+                )) as Arc<dyn BlobstorePutOps>
+            }
             Pack { .. } => {
                 // NB packblob does not apply the wrappers internally
                 make_packblob(
@@ -651,6 +716,27 @@ fn make_blobstore_put_ops<'a>(
         };
 
         let store = if needs_wrappers {
+            // Retries go nearest to the backend, so that the other wrappers
+            // see each operation once.
+            let store = if let Some(retry_policy) = blobstore_options.retry_policy {
+                Arc::new(RetryBlobstore::new(store, retry_policy)) as Arc<dyn BlobstorePutOps>
+            } else {
+                store
+            };
+
+            // The circuit breaker goes outside of the retries, so that it
+            // sees the outcome of each operation once it has been retried.
+            // Only components of multiplexed blobstores get one, as there
+            // is nothing to carry on with when a lone blobstore is cut off.
+            let store = match (&blobstore_options.circuit_breakers, blobstore_id) {
+                (Some(circuit_breakers), Some(blobstore_id)) => {
+                    let breaker = circuit_breakers.get(blobstore_id);
+                    Arc::new(CircuitBreakerBlobstore::new(store, breaker))
+                        as Arc<dyn BlobstorePutOps>
+                }
+                _ => store,
+            };
+
             let store = if let Some(component_sampler) = component_sampler {
                 Arc::new(SamplingBlobstorePutOps::new(
                     store,
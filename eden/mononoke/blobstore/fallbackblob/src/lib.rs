@@ -0,0 +1,218 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! A blobstore that falls back to a secondary blobstore for reads.
+//!
+//! This is used to migrate between storages without a flag day: the new
+//! storage is made primary, and the old one secondary.  Writes only go to the
+//! primary.  Reads of blobs that are missing from the primary are served from
+//! the secondary, and the blob is copied into the primary so that the next
+//! read doesn't need to fall back.  The fallback rate shows how much of the
+//! data in use is still to be migrated.
+
+use std::fmt;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.blobstore.fallbackblob";
+    get: timeseries(Rate, Sum),
+    get_fallback: timeseries(Rate, Sum),
+    get_fallback_hit: timeseries(Rate, Sum),
+    is_present: timeseries(Rate, Sum),
+    is_present_fallback: timeseries(Rate, Sum),
+    is_present_fallback_hit: timeseries(Rate, Sum),
+    backfill: timeseries(Rate, Sum),
+    backfill_error: timeseries(Rate, Sum),
+}
+
+/// A layer over two blobstores that reads from `secondary` whatever is
+/// missing from `primary`, and copies it into `primary` if `backfill` is
+/// set.
+#[derive(Debug)]
+pub struct FallbackBlobstore<P, S> {
+    primary: P,
+    secondary: S,
+    backfill: bool,
+}
+
+impl<P: fmt::Display, S: fmt::Display> fmt::Display for FallbackBlobstore<P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FallbackBlobstore<{}, {}>",
+            &self.primary, &self.secondary
+        )
+    }
+}
+
+impl<P, S> FallbackBlobstore<P, S> {
+    pub fn new(primary: P, secondary: S, backfill: bool) -> Self {
+        Self {
+            primary,
+            secondary,
+            backfill,
+        }
+    }
+}
+
+impl<P: BlobstorePutOps, S: Blobstore> FallbackBlobstore<P, S> {
+    /// Copy a blob that was found in the secondary into the primary.  This
+    /// is best effort: the blob was found, so failing to copy it shouldn't
+    /// fail the read.
+    async fn backfill(&self, ctx: &CoreContext, key: &str, value: BlobstoreBytes) {
+        STATS::backfill.add_value(1);
+        // Don't overwrite a value written to the primary since we read it.
+        if let Err(e) = self
+            .primary
+            .put_explicit(ctx, key.to_owned(), value, PutBehaviour::IfAbsent)
+            .await
+        {
+            STATS::backfill_error.add_value(1);
+            warn!(
+                ctx.logger(),
+                "Failed to backfill {} into {}: {:?}", key, self.primary, e
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl<P: BlobstorePutOps, S: Blobstore> Blobstore for FallbackBlobstore<P, S> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        STATS::get.add_value(1);
+        if let Some(data) = self.primary.get(ctx, key).await? {
+            return Ok(Some(data));
+        }
+
+        STATS::get_fallback.add_value(1);
+        let data = self.secondary.get(ctx, key).await?;
+        if let Some(data) = &data {
+            STATS::get_fallback_hit.add_value(1);
+            if self.backfill {
+                self.backfill(ctx, key, data.as_bytes().clone()).await;
+            }
+        }
+        Ok(data)
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.primary.put(ctx, key, value).await
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        STATS::is_present.add_value(1);
+        let primary = self.primary.is_present(ctx, key).await?;
+        if let BlobstoreIsPresent::Present = primary {
+            return Ok(primary);
+        }
+
+        STATS::is_present_fallback.add_value(1);
+        match self.secondary.is_present(ctx, key).await? {
+            BlobstoreIsPresent::Present => {
+                STATS::is_present_fallback_hit.add_value(1);
+                Ok(BlobstoreIsPresent::Present)
+            }
+            // Absent from both, unless either of them was unsure.
+            BlobstoreIsPresent::Absent => Ok(primary),
+            secondary => match primary {
+                BlobstoreIsPresent::Absent => Ok(secondary),
+                primary => Ok(primary),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl<P: BlobstorePutOps, S: Blobstore> BlobstorePutOps for FallbackBlobstore<P, S> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.primary
+            .put_explicit(ctx, key, value, put_behaviour)
+            .await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.primary.put_with_status(ctx, key, value).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_fallback_and_backfill(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let primary = Memblob::default();
+        let secondary = Memblob::default();
+        let store = FallbackBlobstore::new(primary.clone(), secondary.clone(), true);
+
+        let value = BlobstoreBytes::from_bytes("legacy");
+        secondary
+            .put(ctx, "legacy".to_owned(), value.clone())
+            .await?;
+
+        // Blobs missing from the primary are read from the secondary, and
+        // copied into the primary.
+        assert!(store.is_present(ctx, "legacy").await?.fail_if_unsure()?);
+        let data = store.get(ctx, "legacy").await?;
+        assert_eq!(data.map(|d| d.into_bytes()), Some(value.clone()));
+        let data = primary.get(ctx, "legacy").await?;
+        assert_eq!(data.map(|d| d.into_bytes()), Some(value));
+
+        // Writes only go to the primary.
+        let value = BlobstoreBytes::from_bytes("new");
+        store.put(ctx, "new".to_owned(), value.clone()).await?;
+        assert!(primary.is_present(ctx, "new").await?.fail_if_unsure()?);
+        assert!(!secondary.is_present(ctx, "new").await?.fail_if_unsure()?);
+
+        assert!(store.get(ctx, "missing").await?.is_none());
+        assert!(!store.is_present(ctx, "missing").await?.fail_if_unsure()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use fault_injection::FaultInjected;
+use fault_injection::FaultInjector;
+use fault_injection::Operation;
+use mononoke_types::BlobstoreBytes;
+
+/// A layer over an existing blobstore that injects faults chosen by a
+/// `FaultInjector`.
+///
+/// Partial writes store a truncated value in the underlying blobstore and
+/// then fail, as if the writer crashed part-way through.  Corrupted reads
+/// return the stored value with one byte altered.
+#[derive(Clone)]
+pub struct FaultBlobstore<T> {
+    blobstore: T,
+    injector: Arc<FaultInjector>,
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for FaultBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FaultBlobstore<{}>", &self.blobstore)
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for FaultBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaultBlobstore")
+            .field("blobstore", &self.blobstore)
+            .finish()
+    }
+}
+
+impl<T> FaultBlobstore<T> {
+    pub fn new(blobstore: T, injector: Arc<FaultInjector>) -> Self {
+        Self {
+            blobstore,
+            injector,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore> Blobstore for FaultBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let fault = self
+            .injector
+            .inject(Operation::Read, &format!("get of {}", key))
+            .await?;
+        let data = self.blobstore.get(ctx, key).await?;
+        match (fault, data) {
+            (Some(fault), Some(data)) => {
+                let meta = data.as_meta().clone();
+                let mut bytes = data.into_raw_bytes().to_vec();
+                fault.corrupt(&mut bytes);
+                Ok(Some(BlobstoreGetData::new(
+                    meta,
+                    BlobstoreBytes::from_bytes(bytes),
+                )))
+            }
+            (_, data) => Ok(data),
+        }
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.put_impl(key, value, |key, value| self.blobstore.put(ctx, key, value))
+            .await
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        // Presence checks don't return data, so corruption doesn't apply.
+        self.injector
+            .inject(Operation::Read, &format!("is_present of {}", key))
+            .await?;
+        self.blobstore.is_present(ctx, key).await
+    }
+}
+
+impl<T> FaultBlobstore<T> {
+    async fn put_impl<R, Fut>(
+        &self,
+        key: String,
+        value: BlobstoreBytes,
+        put: impl FnOnce(String, BlobstoreBytes) -> Fut,
+    ) -> Result<R>
+    where
+        Fut: Future<Output = Result<R>>,
+    {
+        let description = format!("put of {}", key);
+        let fault = self.injector.inject(Operation::Write, &description).await?;
+        match fault {
+            Some(fault) => {
+                let len = fault.partial_len(value.len());
+                let truncated = value.into_bytes().slice(..len);
+                put(key, BlobstoreBytes::from_bytes(truncated)).await?;
+                Err(FaultInjected::PartialWrite(description).into())
+            }
+            None => put(key, value).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for FaultBlobstore<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(key, value, |key, value| {
+            self.blobstore.put_explicit(ctx, key, value, put_behaviour)
+        })
+        .await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(key, value, |key, value| {
+            self.blobstore.put_with_status(ctx, key, value)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use borrowed::borrowed;
+    use fault_injection::FaultConfig;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    use super::*;
+
+    fn wrap(base: &Memblob, config: FaultConfig) -> FaultBlobstore<Memblob> {
+        FaultBlobstore::new(base.clone(), Arc::new(FaultInjector::new(0, config)))
+    }
+
+    #[fbinit::test]
+    async fn test_partial_write(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = wrap(
+            &base,
+            FaultConfig {
+                partial_write_probability: 1.0,
+                ..Default::default()
+            },
+        );
+
+        let value = BlobstoreBytes::from_bytes("test foobar");
+        let r = wrapper.put(ctx, "foobar".to_owned(), value.clone()).await;
+        assert!(r.is_err());
+        let stored = base
+            .get(ctx, "foobar")
+            .await
+            .unwrap()
+            .expect("partial value was stored")
+            .into_bytes();
+        assert!(stored.len() < value.len());
+        assert_eq!(stored.as_bytes()[..], value.as_bytes()[..stored.len()]);
+    }
+
+    #[fbinit::test]
+    async fn test_corrupt_read(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let value = BlobstoreBytes::from_bytes("test foobar");
+        base.put(ctx, "foobar".to_owned(), value.clone())
+            .await
+            .unwrap();
+        let wrapper = wrap(
+            &base,
+            FaultConfig {
+                corrupt_read_probability: 1.0,
+                ..Default::default()
+            },
+        );
+
+        let read = wrapper
+            .get(ctx, "foobar")
+            .await
+            .unwrap()
+            .expect("value is present")
+            .into_bytes();
+        assert_eq!(read.len(), value.len());
+        assert_ne!(read, value);
+        assert_eq!(wrapper.injector.injected_faults(), 1);
+    }
+
+    #[fbinit::test]
+    async fn test_timeout(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = Memblob::default();
+        let wrapper = wrap(
+            &base,
+            FaultConfig {
+                timeout_probability: 1.0,
+                timeout: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+
+        let r = wrapper
+            .put(
+                ctx,
+                "foobar".to_owned(),
+                BlobstoreBytes::from_bytes("test foobar"),
+            )
+            .await;
+        assert!(r.is_err());
+        let base_present = base
+            .is_present(ctx, "foobar")
+            .await
+            .unwrap()
+            .assume_not_found_if_unsure();
+        assert!(!base_present);
+    }
+}
@@ -15,6 +15,7 @@ use blobstore::BlobstoreIsPresent;
 use blobstore::BlobstorePutOps;
 use blobstore::OverwriteStatus;
 use blobstore::PutBehaviour;
+use blobstore_stats::key_type;
 use blobstore_stats::record_get_stats;
 use blobstore_stats::record_is_present_stats;
 use blobstore_stats::record_put_stats;
@@ -69,8 +70,18 @@ impl<B: Blobstore + BlobstorePutOps> Blobstore for LogBlob<B> {
 
         let pc = ctx.fork_perf_counters();
 
+        let mut span = ctx.trace().start_span("blobstore_get");
+        span.add("key_type", key_type(key))
+            .add("blobstore", &self.inner);
+
         let get = self.inner.get(&ctx, key);
         let (stats, result) = get.timed().await;
+        match &result {
+            Ok(Some(data)) => span.add("size", data.len()),
+            Ok(None) => span.add("present", false),
+            Err(_) => span.add("error", true),
+        };
+        span.finish();
         record_get_stats(
             &mut scuba,
             &pc,
@@ -110,8 +121,16 @@ impl<B: Blobstore + BlobstorePutOps> Blobstore for LogBlob<B> {
 
         let pc = ctx.fork_perf_counters();
 
+        let mut span = ctx.trace().start_span("blobstore_is_present");
+        span.add("key_type", key_type(key))
+            .add("blobstore", &self.inner);
+
         let is_present = self.inner.is_present(&ctx, key);
         let (stats, result) = is_present.timed().await;
+        if result.is_err() {
+            span.add("error", true);
+        }
+        span.finish();
         record_is_present_stats(
             &mut scuba,
             &pc,
@@ -154,6 +173,11 @@ impl<B: BlobstorePutOps> LogBlob<B> {
 
         let pc = ctx.fork_perf_counters();
 
+        let mut span = ctx.trace().start_span("blobstore_put");
+        span.add("key_type", key_type(&key))
+            .add("blobstore", &self.inner)
+            .add("size", size);
+
         let put = if let Some(put_behaviour) = put_behaviour {
             self.inner
                 .put_explicit(&ctx, key.clone(), value, put_behaviour)
@@ -161,6 +185,10 @@ impl<B: BlobstorePutOps> LogBlob<B> {
             self.inner.put_with_status(&ctx, key.clone(), value)
         };
         let (stats, result) = put.timed().await;
+        if result.is_err() {
+            span.add("error", true);
+        }
+        span.finish();
         record_put_stats(
             &mut scuba,
             &pc,
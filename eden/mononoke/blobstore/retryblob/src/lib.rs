@@ -0,0 +1,254 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use anyhow::Result;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use blobstore::BlobstoreIsPresent;
+use blobstore::BlobstorePutOps;
+use blobstore::OverwriteStatus;
+use blobstore::PutBehaviour;
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+use retry::RetryPolicy;
+
+/// Whether a failed blobstore operation is worth retrying.  Errors that the
+/// blobstore layer raises itself describe the request rather than the
+/// backend, so retrying them would fail in the same way.
+fn is_retryable(error: &Error) -> bool {
+    error.downcast_ref::<blobstore::ErrorKind>().is_none()
+}
+
+/// A layer over an existing blobstore that retries failed operations
+/// according to a `RetryPolicy`, so that transient failures of the backend
+/// are not seen by its users.
+///
+/// Puts are safe to retry, as blobs are immutable: a retried put stores the
+/// same value again.
+#[derive(Debug)]
+pub struct RetryBlobstore<T> {
+    blobstore: T,
+    policy: RetryPolicy,
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for RetryBlobstore<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RetryBlobstore<{}>", &self.blobstore)
+    }
+}
+
+impl<T> RetryBlobstore<T> {
+    pub fn new(blobstore: T, policy: RetryPolicy) -> Self {
+        Self { blobstore, policy }
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> Blobstore for RetryBlobstore<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        self.policy
+            .run(|_| self.blobstore.get(ctx, key), is_retryable)
+            .await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.put_impl(ctx, key, value, None).await?;
+        Ok(())
+    }
+
+    async fn is_present<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<BlobstoreIsPresent> {
+        self.policy
+            .run(|_| self.blobstore.is_present(ctx, key), is_retryable)
+            .await
+    }
+}
+
+impl<T: BlobstorePutOps> RetryBlobstore<T> {
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        self.policy
+            .run(
+                |_| {
+                    let key = key.clone();
+                    let value = value.clone();
+                    async move {
+                        if let Some(put_behaviour) = put_behaviour {
+                            self.blobstore
+                                .put_explicit(ctx, key, value, put_behaviour)
+                                .await
+                        } else {
+                            self.blobstore.put_with_status(ctx, key, value).await
+                        }
+                    }
+                },
+                is_retryable,
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for RetryBlobstore<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+    use retry::RetryLogic;
+
+    use super::*;
+
+    /// Blobstore whose operations fail until a number of them have been
+    /// attempted.
+    #[derive(Debug)]
+    struct FlakyBlobstore {
+        inner: Memblob,
+        failures: AtomicUsize,
+    }
+
+    impl std::fmt::Display for FlakyBlobstore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "FlakyBlobstore")
+        }
+    }
+
+    impl FlakyBlobstore {
+        fn fail(&self) -> Result<()> {
+            let remaining = self.failures.load(Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::SeqCst);
+                return Err(anyhow!("transient failure"));
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Blobstore for FlakyBlobstore {
+        async fn get<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: &'a str,
+        ) -> Result<Option<BlobstoreGetData>> {
+            self.fail()?;
+            self.inner.get(ctx, key).await
+        }
+
+        async fn put<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<()> {
+            self.fail()?;
+            self.inner.put(ctx, key, value).await
+        }
+    }
+
+    #[async_trait]
+    impl BlobstorePutOps for FlakyBlobstore {
+        async fn put_explicit<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+            put_behaviour: PutBehaviour,
+        ) -> Result<OverwriteStatus> {
+            self.fail()?;
+            self.inner
+                .put_explicit(ctx, key, value, put_behaviour)
+                .await
+        }
+
+        async fn put_with_status<'a>(
+            &'a self,
+            ctx: &'a CoreContext,
+            key: String,
+            value: BlobstoreBytes,
+        ) -> Result<OverwriteStatus> {
+            self.fail()?;
+            self.inner.put_with_status(ctx, key, value).await
+        }
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy::new(
+        "test",
+        3,
+        RetryLogic::Exponential {
+            base: Duration::from_millis(1),
+            factor: 1.0,
+        },
+    );
+
+    #[fbinit::test]
+    async fn test_retries(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let base = FlakyBlobstore {
+            inner: Memblob::default(),
+            failures: AtomicUsize::new(2),
+        };
+        let blobstore = RetryBlobstore::new(base, POLICY);
+
+        let value = BlobstoreBytes::from_bytes("value");
+        blobstore.put(ctx, "key".to_owned(), value.clone()).await?;
+
+        blobstore.blobstore.failures.store(2, Ordering::SeqCst);
+        let data = blobstore.get(ctx, "key").await?;
+        assert_eq!(data.map(|data| data.into_bytes()), Some(value));
+
+        // With more failures than attempts, the error is returned.
+        blobstore.blobstore.failures.store(3, Ordering::SeqCst);
+        assert!(blobstore.get(ctx, "key").await.is_err());
+        Ok(())
+    }
+}
@@ -48,6 +48,10 @@ impl PushrebaseHook for GitMappingPushrebaseHook {
         }) as Box<dyn PushrebaseCommitHook>;
         Ok(hook)
     }
+
+    fn batchable(&self) -> bool {
+        true
+    }
 }
 
 struct GitMappingCommitHook {
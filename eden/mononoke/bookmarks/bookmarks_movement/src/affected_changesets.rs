@@ -30,17 +30,29 @@ use futures_ext::FbStreamExt;
 use hooks::CrossRepoPushSource;
 use hooks::HookManager;
 use hooks::PushAuthoredBy;
+use metaconfig_types::AuthorPolicyEnforcement;
+use metaconfig_types::MergePolicy;
+use metaconfig_types::PathPolicyConfig;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
+use path_policy::PathPolicy;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_authorization::AuthorizationContext;
 use repo_update_logger::log_new_commits;
+use repo_update_logger::publish_repo_event;
 use repo_update_logger::CommitInfo;
+use repo_update_logger::RepoEvent;
 use revset::DifferenceOfUnionsOfAncestorsNodeStream;
 use skeleton_manifest::RootSkeletonManifestId;
 use tunables::tunables;
 
+use crate::author_policy;
+use crate::commit_limits::allows_large_commits;
+use crate::commit_limits::check_commit_limits;
+use crate::commit_limits::has_commit_limits;
+use crate::commit_limits::large_commits_requested;
 use crate::hook_running::run_hooks;
+use crate::merge_policy;
 use crate::restrictions::should_run_hooks;
 use crate::BookmarkMovementError;
 use crate::Repo;
@@ -259,6 +271,26 @@ impl AffectedChangesets {
         self.check_case_conflicts(ctx, repo, lca_hint, bookmark, kind, additional_changesets)
             .await?;
 
+        self.check_path_policy(ctx, repo, lca_hint, bookmark, kind, additional_changesets)
+            .await?;
+
+        self.check_commit_limits(
+            ctx,
+            repo,
+            lca_hint,
+            bookmark,
+            kind,
+            additional_changesets,
+            pushvars,
+        )
+        .await?;
+
+        self.check_author_policy(ctx, repo, lca_hint, bookmark, kind, additional_changesets)
+            .await?;
+
+        self.check_merge_policy(ctx, repo, lca_hint, bookmark, kind, additional_changesets)
+            .await?;
+
         self.check_hooks(
             ctx,
             authz,
@@ -386,6 +418,200 @@ impl AffectedChangesets {
         Ok(())
     }
 
+    /// If the push is to a public bookmark, check that no affected changeset
+    /// adds paths that the repo's path policy forbids.
+    async fn check_path_policy(
+        &mut self,
+        ctx: &CoreContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        bookmark: &BookmarkName,
+        kind: BookmarkKind,
+        additional_changesets: AdditionalChangesets,
+    ) -> Result<(), BookmarkMovementError> {
+        if kind != BookmarkKind::Publishing && kind != BookmarkKind::PullDefaultPublishing {
+            return Ok(());
+        }
+
+        // Symlink targets that can never be checked out are rejected even
+        // if the repo doesn't configure a path policy.
+        self.load_additional_changesets(ctx, repo, lca_hint, bookmark, additional_changesets)
+            .await
+            .context("Failed to load additional affected changesets")?;
+
+        let config = &repo.repo_config().path_policy;
+        let policy = PathPolicy::new(config);
+        if *config != PathPolicyConfig::default() {
+            for bcs in self.iter() {
+                policy.check_changeset(bcs).map_err(|violation| {
+                    BookmarkMovementError::PathPolicyViolation {
+                        changeset_id: bcs.get_changeset_id(),
+                        violation,
+                    }
+                })?;
+            }
+        }
+
+        // Symlink targets are file contents, so they must be fetched.  Their
+        // length is checked first, so that large contents aren't fetched.
+        let symlinks = self.iter().flat_map(|bcs| {
+            PathPolicy::changed_symlinks(bcs).map(move |(path, content_id, size)| {
+                (bcs.get_changeset_id(), path, content_id, size)
+            })
+        });
+        stream::iter(symlinks.map(Ok))
+            .try_for_each_concurrent(100, |(changeset_id, path, content_id, size)| {
+                let policy = &policy;
+                async move {
+                    let violation = |violation| BookmarkMovementError::PathPolicyViolation {
+                        changeset_id,
+                        violation,
+                    };
+                    policy
+                        .check_symlink_target_length(path, size)
+                        .map_err(violation)?;
+                    let target =
+                        filestore::fetch_concat(repo.repo_blobstore(), ctx, content_id).await?;
+                    policy
+                        .check_symlink_target(path, &target)
+                        .map_err(violation)
+                }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Check that the affected changesets are within the repo's commit
+    /// limits, unless the pusher is allowed to lift them and asks to with a
+    /// pushvar.  Changesets that are already in the repository are checked
+    /// if they are becoming public.
+    async fn check_commit_limits(
+        &mut self,
+        ctx: &CoreContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        bookmark: &BookmarkName,
+        kind: BookmarkKind,
+        additional_changesets: AdditionalChangesets,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<(), BookmarkMovementError> {
+        let config = &repo.repo_config().commit_limits;
+        if !has_commit_limits(config) {
+            return Ok(());
+        }
+        if large_commits_requested(pushvars) {
+            let allowed = allows_large_commits(config, ctx.metadata().identities());
+            let mut scuba = ctx.scuba().clone();
+            scuba.add("commit_limits_lifted", allowed);
+            scuba.log_with_msg("Commit limits lift requested by pushvar", None);
+            if allowed {
+                return Ok(());
+            }
+        }
+
+        if kind == BookmarkKind::Publishing || kind == BookmarkKind::PullDefaultPublishing {
+            self.load_additional_changesets(ctx, repo, lca_hint, bookmark, additional_changesets)
+                .await
+                .context("Failed to load additional affected changesets")?;
+        }
+
+        stream::iter(self.iter().map(Ok))
+            .try_for_each_concurrent(10, |bcs| check_commit_limits(ctx, repo, config, bcs))
+            .await
+    }
+
+    /// If the push is to a public bookmark, check that the authors of the
+    /// affected changesets match the pusher.  Every mismatch is logged to
+    /// scuba for auditing, and the push is rejected unless the policy
+    /// accepts mismatches from the pusher.
+    async fn check_author_policy(
+        &mut self,
+        ctx: &CoreContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        bookmark: &BookmarkName,
+        kind: BookmarkKind,
+        additional_changesets: AdditionalChangesets,
+    ) -> Result<(), BookmarkMovementError> {
+        let policy = &repo.repo_config().author_policy;
+        if (kind != BookmarkKind::Publishing && kind != BookmarkKind::PullDefaultPublishing)
+            || policy.enforcement == AuthorPolicyEnforcement::Off
+        {
+            return Ok(());
+        }
+
+        self.load_additional_changesets(ctx, repo, lca_hint, bookmark, additional_changesets)
+            .await
+            .context("Failed to load additional affected changesets")?;
+
+        let identities = ctx.metadata().identities();
+        let pusher = ctx.metadata().unix_name();
+        let accepted = author_policy::accepts_violations(policy, identities);
+        for bcs in self.iter() {
+            if let Err(violation) = author_policy::check_changeset(policy, pusher, bcs) {
+                let mut scuba = ctx.scuba().clone();
+                scuba.add("changeset_id", bcs.get_changeset_id().to_string());
+                scuba.add("author_policy_violation", violation.to_string());
+                scuba.add(
+                    "author_policy_enforcement",
+                    format!("{:?}", policy.enforcement),
+                );
+                scuba.add("author_policy_accepted", accepted);
+                scuba.log_with_msg("Author policy violation", None);
+                if !accepted {
+                    return Err(BookmarkMovementError::AuthorPolicyViolation {
+                        changeset_id: bcs.get_changeset_id(),
+                        violation,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// If the push is to a public bookmark, check the affected changesets
+    /// against the merge policies of the bookmark.
+    async fn check_merge_policy(
+        &mut self,
+        ctx: &CoreContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        bookmark: &BookmarkName,
+        kind: BookmarkKind,
+        additional_changesets: AdditionalChangesets,
+    ) -> Result<(), BookmarkMovementError> {
+        if kind != BookmarkKind::Publishing && kind != BookmarkKind::PullDefaultPublishing {
+            return Ok(());
+        }
+        let policies = repo
+            .repo_bookmark_attrs()
+            .select(bookmark)
+            .map(|attr| &attr.params().merge_policy)
+            .filter(|policy| **policy != MergePolicy::default())
+            .collect::<Vec<_>>();
+        if policies.is_empty() {
+            return Ok(());
+        }
+
+        self.load_additional_changesets(ctx, repo, lca_hint, bookmark, additional_changesets)
+            .await
+            .context("Failed to load additional affected changesets")?;
+
+        let pusher = ctx.metadata().identities();
+        for bcs in self.iter() {
+            for policy in policies.iter() {
+                merge_policy::check_new_changeset(policy, pusher, bcs)
+                    .and_then(|()| merge_policy::check_history_changeset(policy, bcs))
+                    .map_err(|violation| BookmarkMovementError::MergePolicyViolation {
+                        changeset_id: bcs.get_changeset_id(),
+                        violation,
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// If this is a user-initiated update to a public bookmark, run the
     /// hooks against the affected changesets. Also run hooks if it is a
     /// service-initiated pushrebase but hooks will run with taking this
@@ -456,7 +682,7 @@ impl AffectedChangesets {
                     } else {
                         PushAuthoredBy::User
                     };
-                    run_hooks(
+                    let res = run_hooks(
                         ctx,
                         hook_manager,
                         bookmark,
@@ -465,7 +691,22 @@ impl AffectedChangesets {
                         cross_repo_push_source,
                         push_authored_by,
                     )
-                    .await?;
+                    .await;
+                    if let Err(BookmarkMovementError::HookFailure(rejections)) = &res {
+                        for rejection in rejections {
+                            let _ = publish_repo_event(
+                                ctx,
+                                repo,
+                                RepoEvent::HookRejected {
+                                    bookmark: bookmark.to_string(),
+                                    changeset_id: rejection.cs_id.to_string(),
+                                    hook_name: rejection.hook_name.clone(),
+                                    description: rejection.reason.description.to_string(),
+                                },
+                            );
+                        }
+                    }
+                    res?;
                 }
             }
         }
@@ -510,6 +751,27 @@ pub async fn find_draft_ancestors(
         .clone()
         .log_with_msg("Started finding draft ancestors", None);
 
+    let drafts = find_draft_ancestor_ids(ctx, repo, to_cs_id).await?;
+    let drafts = stream::iter(drafts)
+        .map(Ok)
+        .map_ok(|cs_id| async move { cs_id.load(ctx, repo.repo_blobstore()).await })
+        .try_buffer_unordered(100)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    ctx.scuba()
+        .clone()
+        .log_with_msg("Found draft ancestors", Some(format!("{}", drafts.len())));
+    Ok(drafts)
+}
+
+/// Find the ids of the draft ancestors of a changeset, including the
+/// changeset itself if it is draft.
+pub(crate) async fn find_draft_ancestor_ids(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    to_cs_id: ChangesetId,
+) -> Result<Vec<ChangesetId>, Error> {
     let phases = repo.phases();
     let mut queue = VecDeque::new();
     let mut visited = HashSet::new();
@@ -537,17 +799,6 @@ pub async fn find_draft_ancestors(
             }
         }
     }
-
-    let drafts = stream::iter(drafts)
-        .map(Ok)
-        .map_ok(|cs_id| async move { cs_id.load(ctx, repo.repo_blobstore()).await })
-        .try_buffer_unordered(100)
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    ctx.scuba()
-        .clone()
-        .log_with_msg("Found draft ancestors", Some(format!("{}", drafts.len())));
     Ok(drafts)
 }
 
@@ -575,11 +826,21 @@ mod test {
     use std::collections::HashSet;
 
     use blobrepo::AsBlobRepo;
+    use context::SessionContainer;
     use fbinit::FacebookInit;
+    use maplit::btreeset;
+    use maplit::hashmap;
     use maplit::hashset;
+    use metaconfig_types::CommitLimitsConfig;
+    use metaconfig_types::Identity;
+    use metadata::Metadata;
     use mononoke_api_types::InnerRepo;
+    use permission_checker::MononokeIdentity;
+    use repo_blobstore::RepoBlobstoreRef;
+    use test_repo_factory::TestRepoFactory;
     use tests_utils::bookmark;
     use tests_utils::drawdag::create_from_dag;
+    use tests_utils::CreateCommitContext;
 
     use super::*;
 
@@ -676,4 +937,65 @@ mod test {
 
         Ok(())
     }
+
+    #[fbinit::test]
+    async fn test_commit_limits_pushvar(fb: FacebookInit) -> Result<(), Error> {
+        let repo: InnerRepo = TestRepoFactory::new(fb)?
+            .with_config_override(|config| {
+                config.commit_limits = CommitLimitsConfig {
+                    max_files_changed: Some(1),
+                    large_commit_identities: vec![Identity {
+                        id_type: "USER".to_string(),
+                        id_data: "alice".to_string(),
+                    }],
+                    ..Default::default()
+                };
+            })
+            .build()?;
+        let ctx = CoreContext::test_mock(fb);
+        let cs_id = CreateCommitContext::new_root(&ctx, repo.as_blob_repo())
+            .add_file("a", "a")
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        let bcs = cs_id.load(&ctx, repo.repo_blobstore()).await?;
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = repo.skiplist_index.clone();
+        let bookmark = BookmarkName::new("main")?;
+        let allow = hashmap! { "ALLOW_LARGE_COMMITS".to_string() => Bytes::from("true") };
+
+        for (user, pushvars, allowed) in [
+            ("alice", Some(&allow), true),
+            ("alice", None, false),
+            ("bob", Some(&allow), false),
+        ] {
+            let metadata = Metadata::default()
+                .set_identities(btreeset! { MononokeIdentity::new("USER", user) });
+            let session = SessionContainer::builder(fb)
+                .metadata(Arc::new(metadata))
+                .build();
+            let ctx = CoreContext::test_mock_session(session);
+            let res = AffectedChangesets::with_source_changesets(hashset! { bcs.clone() })
+                .check_commit_limits(
+                    &ctx,
+                    &repo,
+                    &lca_hint,
+                    &bookmark,
+                    BookmarkKind::Scratch,
+                    AdditionalChangesets::None,
+                    pushvars,
+                )
+                .await;
+            if allowed {
+                assert!(res.is_ok(), "{} should be allowed: {:?}", user, res);
+            } else {
+                assert!(
+                    matches!(res, Err(BookmarkMovementError::CommitLimitExceeded { .. })),
+                    "{} should be rejected: {:?}",
+                    user,
+                    res
+                );
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::AuthorPolicyConfig;
+use metaconfig_types::AuthorPolicyEnforcement;
+use metaconfig_types::Identity;
+use mononoke_types::BonsaiChangeset;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use thiserror::Error;
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum AuthorPolicyViolation {
+    #[error("Author '{author}' doesn't match the pusher {}", display_pusher(.pusher))]
+    AuthorMismatch {
+        author: String,
+        pusher: Option<String>,
+    },
+
+    #[error("Committer '{committer}' doesn't match the pusher {}", display_pusher(.pusher))]
+    CommitterMismatch {
+        committer: String,
+        pusher: Option<String>,
+    },
+}
+
+fn display_pusher(pusher: &Option<String>) -> String {
+    match pusher {
+        Some(pusher) => format!("'{}'", pusher),
+        None => "(no user name)".to_string(),
+    }
+}
+
+/// The user name of an author or committer, which is the user name of their
+/// email if they have one, e.g. `alice` for `Alice <alice@example.com>`.
+fn user_name(author: &str) -> &str {
+    let email = match (author.rfind('<'), author.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &author[start + 1..end],
+        _ => author,
+    };
+    match email.split_once('@') {
+        Some((user_name, _)) => user_name.trim(),
+        None => email.trim(),
+    }
+}
+
+fn matches_pusher(author: &str, pusher: Option<&str>) -> bool {
+    pusher.map_or(false, |pusher| user_name(author) == pusher)
+}
+
+/// Check that the author, and the committer if the policy requires it, of a
+/// changeset that is being pushed match the user name of the pusher.
+pub(crate) fn check_changeset(
+    policy: &AuthorPolicyConfig,
+    pusher: Option<&str>,
+    bcs: &BonsaiChangeset,
+) -> Result<(), AuthorPolicyViolation> {
+    if !matches_pusher(bcs.author(), pusher) {
+        return Err(AuthorPolicyViolation::AuthorMismatch {
+            author: bcs.author().to_string(),
+            pusher: pusher.map(str::to_string),
+        });
+    }
+    if policy.check_committer {
+        if let Some(committer) = bcs.committer() {
+            if !matches_pusher(committer, pusher) {
+                return Err(AuthorPolicyViolation::CommitterMismatch {
+                    committer: committer.to_string(),
+                    pusher: pusher.map(str::to_string),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether changesets that violate the policy are accepted when pushed by
+/// these identities.
+pub(crate) fn accepts_violations(
+    policy: &AuthorPolicyConfig,
+    identities: &MononokeIdentitySet,
+) -> bool {
+    match &policy.enforcement {
+        AuthorPolicyEnforcement::Off | AuthorPolicyEnforcement::Warn => true,
+        AuthorPolicyEnforcement::Block => false,
+        AuthorPolicyEnforcement::AllowFrom(allowed) => {
+            allowed.iter().any(|Identity { id_type, id_data }| {
+                identities.contains(&MononokeIdentity::new(id_type, id_data))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mononoke_types::BonsaiChangesetMut;
+    use mononoke_types::DateTime;
+    use sorted_vector_map::SortedVectorMap;
+
+    use super::*;
+
+    fn changeset(author: &str, committer: Option<&str>) -> BonsaiChangeset {
+        BonsaiChangesetMut {
+            parents: vec![],
+            author: author.to_string(),
+            author_date: DateTime::from_timestamp(0, 0).unwrap(),
+            committer: committer.map(str::to_string),
+            committer_date: None,
+            message: "message".to_string(),
+            extra: SortedVectorMap::new(),
+            file_changes: SortedVectorMap::new(),
+            is_snapshot: false,
+        }
+        .freeze()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_author_policy() {
+        let policy = AuthorPolicyConfig {
+            enforcement: AuthorPolicyEnforcement::Block,
+            check_committer: false,
+        };
+        let own = changeset("Alice <alice@example.com>", Some("bob"));
+        let plain = changeset("alice", None);
+        let spoofed = changeset("Bob <bob@example.com>", None);
+
+        assert_eq!(check_changeset(&policy, Some("alice"), &own), Ok(()));
+        assert_eq!(check_changeset(&policy, Some("alice"), &plain), Ok(()));
+        assert_eq!(
+            check_changeset(&policy, Some("alice"), &spoofed),
+            Err(AuthorPolicyViolation::AuthorMismatch {
+                author: "Bob <bob@example.com>".to_string(),
+                pusher: Some("alice".to_string()),
+            })
+        );
+        assert!(check_changeset(&policy, None, &own).is_err());
+
+        let policy = AuthorPolicyConfig {
+            enforcement: AuthorPolicyEnforcement::Block,
+            check_committer: true,
+        };
+        assert_eq!(
+            check_changeset(&policy, Some("alice"), &own),
+            Err(AuthorPolicyViolation::CommitterMismatch {
+                committer: "bob".to_string(),
+                pusher: Some("alice".to_string()),
+            })
+        );
+        assert_eq!(check_changeset(&policy, Some("alice"), &plain), Ok(()));
+    }
+
+    #[test]
+    fn test_author_policy_enforcement() {
+        let service = [MononokeIdentity::new("SERVICE_IDENTITY", "lander")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+        let user = [MononokeIdentity::new("USER", "alice")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+        let policy = |enforcement| AuthorPolicyConfig {
+            enforcement,
+            check_committer: false,
+        };
+
+        assert!(accepts_violations(
+            &policy(AuthorPolicyEnforcement::Warn),
+            &user
+        ));
+        assert!(!accepts_violations(
+            &policy(AuthorPolicyEnforcement::Block),
+            &service
+        ));
+        let allow_service = policy(AuthorPolicyEnforcement::AllowFrom(vec![Identity {
+            id_type: "SERVICE_IDENTITY".to_string(),
+            id_data: "lander".to_string(),
+        }]));
+        assert!(accepts_violations(&allow_service, &service));
+        assert!(!accepts_violations(&allow_service, &user));
+    }
+}
@@ -0,0 +1,425 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Loadable;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use futures::try_join;
+use manifest::Diff;
+use manifest::Entry;
+use manifest::ManifestOps;
+use metaconfig_types::CommitLimitsConfig;
+use metaconfig_types::Identity;
+use mononoke_types::BlobstoreValue;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::MPath;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use skeleton_manifest::RootSkeletonManifestId;
+use thiserror::Error;
+
+use crate::BookmarkMovementError;
+use crate::Repo;
+
+/// Pushvar that lets a push exceed the repo's commit limits, for approved
+/// large changes.  Only honoured for the repo's large commit identities.
+const ALLOW_LARGE_COMMITS_PUSHVAR: &str = "ALLOW_LARGE_COMMITS";
+
+#[derive(Debug, Error)]
+pub enum CommitLimitViolation {
+    #[error("{count} files changed, the limit is {limit}")]
+    TooManyFilesChanged { count: u64, limit: u64 },
+
+    #[error("{count} entries added to directory '{}', the limit is {limit}", display_dir(.path))]
+    TooManyNewDirectoryEntries {
+        path: Option<MPath>,
+        count: u64,
+        limit: u64,
+    },
+
+    #[error("Manifest of directory '{}' is {size} bytes, the limit is {limit}", display_dir(.path))]
+    DirectoryManifestTooLarge {
+        path: Option<MPath>,
+        size: u64,
+        limit: u64,
+    },
+}
+
+fn display_dir(path: &Option<MPath>) -> String {
+    match path {
+        Some(path) => path.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Whether the repo configures any commit limits.
+pub(crate) fn has_commit_limits(config: &CommitLimitsConfig) -> bool {
+    config.max_files_changed.is_some()
+        || config.max_new_directory_entries.is_some()
+        || config.max_directory_manifest_size.is_some()
+}
+
+/// Whether the pushvars ask for the commit limits to be lifted.
+pub(crate) fn large_commits_requested(pushvars: Option<&HashMap<String, Bytes>>) -> bool {
+    pushvars
+        .and_then(|pushvars| pushvars.get(ALLOW_LARGE_COMMITS_PUSHVAR))
+        .map_or(false, |value| value.to_ascii_lowercase() == b"true")
+}
+
+/// Whether the pusher may lift the commit limits.
+pub(crate) fn allows_large_commits(
+    config: &CommitLimitsConfig,
+    identities: &MononokeIdentitySet,
+) -> bool {
+    config
+        .large_commit_identities
+        .iter()
+        .any(|Identity { id_type, id_data }| {
+            identities.contains(&MononokeIdentity::new(id_type, id_data))
+        })
+}
+
+/// Number of files a changeset touches.  As well as the files in its file
+/// changes, this counts the files it adds or removes compared to its first
+/// parent without listing them, such as the files a merge brings in from its
+/// other parents, or the files implicitly deleted when a file replaces a
+/// directory.  Counting stops once the count is over the limit.
+async fn touched_files_count(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bcs: &BonsaiChangeset,
+    limit: u64,
+) -> Result<u64> {
+    let mut touched: HashSet<MPath> = bcs.file_changes_map().keys().cloned().collect();
+    let parent = match bcs.parents().next() {
+        Some(parent) => parent,
+        // Root changesets list all of their files.
+        None => return Ok(touched.len() as u64),
+    };
+    if touched.len() as u64 > limit {
+        return Ok(touched.len() as u64);
+    }
+
+    let (parent_root, root) = try_join!(
+        repo.repo_derived_data()
+            .derive::<RootSkeletonManifestId>(ctx, parent),
+        repo.repo_derived_data()
+            .derive::<RootSkeletonManifestId>(ctx, bcs.get_changeset_id()),
+    )?;
+    let mut diff = parent_root.into_skeleton_manifest_id().diff(
+        ctx.clone(),
+        repo.repo_blobstore().clone(),
+        root.into_skeleton_manifest_id(),
+    );
+    while let Some(diff) = diff.try_next().await? {
+        if let Diff::Added(Some(path), Entry::Leaf(_)) | Diff::Removed(Some(path), Entry::Leaf(_)) =
+            diff
+        {
+            touched.insert(path);
+            if touched.len() as u64 > limit {
+                break;
+            }
+        }
+    }
+    Ok(touched.len() as u64)
+}
+
+/// Number of entries and serialized size of each of the given directories in
+/// a changeset.  Directories that don't exist in the changeset are omitted.
+async fn directory_sizes(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    cs_id: ChangesetId,
+    dirs: &HashSet<Option<MPath>>,
+    with_manifest_size: bool,
+) -> Result<HashMap<Option<MPath>, (u64, u64)>> {
+    let root = repo
+        .repo_derived_data()
+        .derive::<RootSkeletonManifestId>(ctx, cs_id)
+        .await?
+        .into_skeleton_manifest_id();
+    root.find_entries(
+        ctx.clone(),
+        repo.repo_blobstore().clone(),
+        dirs.iter().cloned(),
+    )
+    .try_filter_map(|(path, entry)| async move {
+        match entry {
+            Entry::Tree(id) => Ok(Some((path, id))),
+            Entry::Leaf(_) => Ok(None),
+        }
+    })
+    .map_ok(|(path, id)| async move {
+        let mf = id.load(ctx, repo.repo_blobstore()).await?;
+        let summary = mf.summary();
+        let entries = summary.child_files_count + summary.child_dirs_count;
+        let size = if with_manifest_size {
+            mf.into_blob().len() as u64
+        } else {
+            0
+        };
+        Ok::<_, Error>((path, (entries, size)))
+    })
+    .try_buffer_unordered(100)
+    .try_collect()
+    .await
+}
+
+/// Check that a changeset being pushed is within the repo's commit limits.
+pub(crate) async fn check_commit_limits(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    config: &CommitLimitsConfig,
+    bcs: &BonsaiChangeset,
+) -> Result<(), BookmarkMovementError> {
+    let violation = |violation| BookmarkMovementError::CommitLimitExceeded {
+        changeset_id: bcs.get_changeset_id(),
+        violation,
+    };
+
+    if let Some(limit) = config.max_files_changed {
+        let count = touched_files_count(ctx, repo, bcs, limit).await?;
+        if count > limit {
+            return Err(violation(CommitLimitViolation::TooManyFilesChanged {
+                count,
+                limit,
+            }));
+        }
+    }
+
+    if config.max_new_directory_entries.is_none() && config.max_directory_manifest_size.is_none() {
+        return Ok(());
+    }
+
+    // The directories the changeset changes are those that contain the
+    // changed paths, at any depth.
+    let mut dirs = HashSet::new();
+    dirs.insert(None);
+    for path in bcs.file_changes_map().keys() {
+        if let (Some(dir), _) = path.split_dirname() {
+            dirs.extend(dir.into_parent_dir_iter().map(Some));
+        }
+    }
+
+    let with_manifest_size = config.max_directory_manifest_size.is_some();
+    let sizes =
+        directory_sizes(ctx, repo, bcs.get_changeset_id(), &dirs, with_manifest_size).await?;
+
+    if let Some(limit) = config.max_directory_manifest_size {
+        if let Some((path, (_, size))) = sizes.iter().find(|(_, (_, size))| *size > limit) {
+            return Err(violation(CommitLimitViolation::DirectoryManifestTooLarge {
+                path: path.clone(),
+                size: *size,
+                limit,
+            }));
+        }
+    }
+
+    if let Some(limit) = config.max_new_directory_entries {
+        // Entries are new if they are in none of the parents.  As an
+        // approximation, compare with the parent the directory is largest in.
+        let parent_sizes = stream::iter(bcs.parents())
+            .map(|parent| directory_sizes(ctx, repo, parent, &dirs, false))
+            .buffered(10)
+            .try_collect::<Vec<_>>()
+            .await?;
+        for (path, (entries, _)) in sizes {
+            let parent_entries = parent_sizes
+                .iter()
+                .filter_map(|sizes| sizes.get(&path))
+                .map(|(entries, _)| *entries)
+                .max()
+                .unwrap_or(0);
+            let count = entries.saturating_sub(parent_entries);
+            if count > limit {
+                return Err(violation(
+                    CommitLimitViolation::TooManyNewDirectoryEntries { path, count, limit },
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use blobrepo::AsBlobRepo;
+    use fbinit::FacebookInit;
+    use maplit::hashmap;
+    use maplit::hashset;
+    use mononoke_api_types::InnerRepo;
+    use repo_blobstore::RepoBlobstoreRef;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+
+    async fn violation(
+        ctx: &CoreContext,
+        repo: &InnerRepo,
+        config: &CommitLimitsConfig,
+        cs_id: ChangesetId,
+    ) -> Result<Option<CommitLimitViolation>> {
+        let bcs = cs_id.load(ctx, repo.repo_blobstore()).await?;
+        match check_commit_limits(ctx, repo, config, &bcs).await {
+            Ok(()) => Ok(None),
+            Err(BookmarkMovementError::CommitLimitExceeded { violation, .. }) => {
+                Ok(Some(violation))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_max_files_changed(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: InnerRepo = test_repo_factory::build_empty(fb)?;
+        let root = CreateCommitContext::new_root(&ctx, repo.as_blob_repo())
+            .add_file("a", "a")
+            .add_file("b", "b")
+            .add_file("c", "c")
+            .commit()
+            .await?;
+        let child = CreateCommitContext::new(&ctx, repo.as_blob_repo(), vec![root])
+            .add_file("a", "a2")
+            .add_file("b", "b2")
+            .commit()
+            .await?;
+        let side = CreateCommitContext::new(&ctx, repo.as_blob_repo(), vec![root])
+            .add_file("x1", "x1")
+            .add_file("x2", "x2")
+            .add_file("x3", "x3")
+            .commit()
+            .await?;
+        // The merge has no file changes of its own, but brings in the three
+        // files added on the side branch.
+        let merge = CreateCommitContext::new(&ctx, repo.as_blob_repo(), vec![child, side])
+            .commit()
+            .await?;
+
+        let config = |limit| CommitLimitsConfig {
+            max_files_changed: Some(limit),
+            ..Default::default()
+        };
+        assert!(violation(&ctx, &repo, &config(2), child).await?.is_none());
+        assert!(matches!(
+            violation(&ctx, &repo, &config(1), child).await?,
+            Some(CommitLimitViolation::TooManyFilesChanged { count: 2, limit: 1 })
+        ));
+        assert!(violation(&ctx, &repo, &config(3), merge).await?.is_none());
+        assert!(matches!(
+            violation(&ctx, &repo, &config(2), merge).await?,
+            Some(CommitLimitViolation::TooManyFilesChanged { count: 3, limit: 2 })
+        ));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_max_new_directory_entries(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: InnerRepo = test_repo_factory::build_empty(fb)?;
+        let root = CreateCommitContext::new_root(&ctx, repo.as_blob_repo())
+            .add_file("dir/x", "x")
+            .commit()
+            .await?;
+        // Changing an existing file doesn't add an entry.
+        let child = CreateCommitContext::new(&ctx, repo.as_blob_repo(), vec![root])
+            .add_file("dir/x", "x2")
+            .add_file("dir/a", "a")
+            .add_file("dir/b", "b")
+            .add_file("dir/c", "c")
+            .commit()
+            .await?;
+
+        let config = |limit| CommitLimitsConfig {
+            max_new_directory_entries: Some(limit),
+            ..Default::default()
+        };
+        assert!(violation(&ctx, &repo, &config(3), child).await?.is_none());
+        match violation(&ctx, &repo, &config(2), child).await? {
+            Some(CommitLimitViolation::TooManyNewDirectoryEntries { path, count, limit }) => {
+                assert_eq!(path, Some(MPath::new("dir")?));
+                assert_eq!((count, limit), (3, 2));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_max_directory_manifest_size(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: InnerRepo = test_repo_factory::build_empty(fb)?;
+        let root = CreateCommitContext::new_root(&ctx, repo.as_blob_repo())
+            .add_file("dir/a", "a")
+            .add_file("dir/b", "b")
+            .add_file("other/c", "c")
+            .commit()
+            .await?;
+
+        let config = |limit| CommitLimitsConfig {
+            max_directory_manifest_size: Some(limit),
+            ..Default::default()
+        };
+        assert!(
+            violation(&ctx, &repo, &config(1_000_000), root)
+                .await?
+                .is_none()
+        );
+        match violation(&ctx, &repo, &config(1), root).await? {
+            Some(CommitLimitViolation::DirectoryManifestTooLarge { path, size, limit }) => {
+                assert!(
+                    hashset! {None, Some(MPath::new("dir")?), Some(MPath::new("other")?)}
+                        .contains(&path)
+                );
+                assert!(size > 1);
+                assert_eq!(limit, 1);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_commits_pushvar() {
+        let pushvars = |value: &str| {
+            hashmap! {
+                ALLOW_LARGE_COMMITS_PUSHVAR.to_string() => Bytes::from(value.to_string()),
+            }
+        };
+        assert!(large_commits_requested(Some(&pushvars("true"))));
+        assert!(large_commits_requested(Some(&pushvars("TRUE"))));
+        assert!(!large_commits_requested(Some(&pushvars("false"))));
+        assert!(!large_commits_requested(Some(&HashMap::new())));
+        assert!(!large_commits_requested(None));
+
+        let config = CommitLimitsConfig {
+            max_files_changed: Some(1),
+            large_commit_identities: vec![Identity {
+                id_type: "USER".to_string(),
+                id_data: "alice".to_string(),
+            }],
+            ..Default::default()
+        };
+        let identities = |user: &str| {
+            [MononokeIdentity::new("USER", user)]
+                .into_iter()
+                .collect::<MononokeIdentitySet>()
+        };
+        assert!(allows_large_commits(&config, &identities("alice")));
+        assert!(!allows_large_commits(&config, &identities("bob")));
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Rewriting of commit messages as commits are pushrebased.
+
+use std::borrow::Cow;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use bookmarks::BookmarkTransactionError;
+use bookmarks_types::BookmarkName;
+use context::CoreContext;
+use metaconfig_types::CommitMessageRewriteRule;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use pushrebase_hook::PushrebaseCommitHook;
+use pushrebase_hook::PushrebaseHook;
+use pushrebase_hook::PushrebaseTransactionHook;
+use pushrebase_hook::RebasedChangesets;
+use sql::Transaction;
+
+/// Extra in which the message a commit was pushed with is kept if the
+/// message is rewritten.
+pub const ORIGINAL_MESSAGE_EXTRA: &str = "original_message";
+
+/// Pushrebase hook that rewrites the messages of the pushrebased commits
+/// according to the repo's rewrite rules.
+pub struct CommitMessageRewritePushrebaseHook {
+    rules: Vec<CommitMessageRewriteRule>,
+    bookmark: BookmarkName,
+    push_id: String,
+}
+
+impl CommitMessageRewritePushrebaseHook {
+    /// Create a hook for a pushrebase onto `bookmark`, or `None` if none of
+    /// the rules apply to that bookmark.
+    pub fn new(
+        ctx: &CoreContext,
+        rules: &[CommitMessageRewriteRule],
+        bookmark: &BookmarkName,
+    ) -> Option<Box<dyn PushrebaseHook>> {
+        let rules = rules
+            .iter()
+            .filter(|rule| match &rule.bookmark_regex {
+                Some(regex) => regex.is_match(bookmark.as_str()),
+                None => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            return None;
+        }
+        Some(Box::new(Self {
+            rules,
+            bookmark: bookmark.clone(),
+            push_id: ctx.metadata().session_id().to_string(),
+        }))
+    }
+}
+
+#[async_trait]
+impl PushrebaseHook for CommitMessageRewritePushrebaseHook {
+    async fn prepushrebase(&self) -> Result<Box<dyn PushrebaseCommitHook>, Error> {
+        Ok(Box::new(CommitMessageRewriteCommitHook {
+            rules: self.rules.clone(),
+            bookmark: self.bookmark.clone(),
+            push_id: self.push_id.clone(),
+        }) as Box<dyn PushrebaseCommitHook>)
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+}
+
+struct CommitMessageRewriteCommitHook {
+    rules: Vec<CommitMessageRewriteRule>,
+    bookmark: BookmarkName,
+    push_id: String,
+}
+
+impl CommitMessageRewriteCommitHook {
+    fn rewrite(&self, original_commit: ChangesetId, message: &str) -> String {
+        let mut message = message.to_string();
+        for rule in self.rules.iter() {
+            for pattern in rule.strip_patterns.iter() {
+                if let Cow::Owned(stripped) = pattern.replace_all(&message, "") {
+                    message = stripped.trim_end().to_string();
+                }
+            }
+            if let Some(template) = &rule.append_template {
+                let line = template
+                    .replace("{bookmark}", self.bookmark.as_str())
+                    .replace("{push_id}", &self.push_id)
+                    .replace("{original_commit}", &original_commit.to_string());
+                message = format!("{}\n\n{}", message.trim_end(), line);
+            }
+        }
+        message
+    }
+}
+
+#[async_trait]
+impl PushrebaseCommitHook for CommitMessageRewriteCommitHook {
+    fn post_rebase_changeset(
+        &mut self,
+        bcs_old: ChangesetId,
+        bcs_new: &mut BonsaiChangesetMut,
+    ) -> Result<(), Error> {
+        let message = self.rewrite(bcs_old, &bcs_new.message);
+        if message != bcs_new.message {
+            // If the commit was rewritten before, the first message is the
+            // one that the author wrote.
+            if !bcs_new.extra.contains_key(ORIGINAL_MESSAGE_EXTRA) {
+                bcs_new.extra.insert(
+                    ORIGINAL_MESSAGE_EXTRA.to_string(),
+                    bcs_new.message.clone().into_bytes(),
+                );
+            }
+            bcs_new.message = message;
+        }
+        Ok(())
+    }
+
+    async fn into_transaction_hook(
+        self: Box<Self>,
+        _ctx: &CoreContext,
+        _rebased: &RebasedChangesets,
+    ) -> Result<Box<dyn PushrebaseTransactionHook>, Error> {
+        Ok(Box::new(CommitMessageRewriteTransactionHook) as Box<dyn PushrebaseTransactionHook>)
+    }
+}
+
+/// The messages are part of the rebased commits, so there is nothing else
+/// to write when the bookmark moves.
+struct CommitMessageRewriteTransactionHook;
+
+#[async_trait]
+impl PushrebaseTransactionHook for CommitMessageRewriteTransactionHook {
+    async fn populate_transaction(
+        &self,
+        _ctx: &CoreContext,
+        txn: Transaction,
+    ) -> Result<Transaction, BookmarkTransactionError> {
+        Ok(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let hook = CommitMessageRewriteCommitHook {
+            rules: vec![CommitMessageRewriteRule {
+                bookmark_regex: None,
+                strip_patterns: vec![Regex::new("(?m)^Local-Marker: .*$").unwrap().into()],
+                append_template: Some("Landed-On: {bookmark} ({push_id})".to_string()),
+            }],
+            bookmark: BookmarkName::new("main").unwrap(),
+            push_id: "push1".to_string(),
+        };
+
+        assert_eq!(
+            hook.rewrite(ONES_CSID, "Fix a bug\n\nLocal-Marker: abc\n"),
+            "Fix a bug\n\nLanded-On: main (push1)"
+        );
+        assert_eq!(
+            hook.rewrite(ONES_CSID, "Fix a bug"),
+            "Fix a bug\n\nLanded-On: main (push1)"
+        );
+    }
+}
@@ -20,6 +20,7 @@ use mononoke_types::ChangesetId;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_authorization::AuthorizationContext;
 use repo_authorization::RepoWriteOperation;
+use repo_update_logger::check_bookmark_count;
 use repo_update_logger::log_bookmark_operation;
 use repo_update_logger::BookmarkInfo;
 use repo_update_logger::BookmarkOperation;
@@ -28,6 +29,9 @@ use crate::affected_changesets::find_draft_ancestors;
 use crate::affected_changesets::log_new_bonsai_changesets;
 use crate::affected_changesets::AdditionalChangesets;
 use crate::affected_changesets::AffectedChangesets;
+use crate::derivation::enqueue_derivation;
+use crate::freeze_windows::check_freeze_windows;
+use crate::private_scratch::record_private_scratch_commits;
 use crate::repo_lock::check_repo_lock;
 use crate::restrictions::check_bookmark_sync_config;
 use crate::restrictions::BookmarkKindRestrictions;
@@ -154,6 +158,7 @@ impl<'op> CreateBookmarkOp<'op> {
             .await?;
 
         check_repo_lock(repo, kind, self.pushvars, ctx.metadata().identities()).await?;
+        check_freeze_windows(ctx, repo, self.bookmark, kind, self.pushvars).await?;
 
         let mut txn = repo.bookmarks().create_transaction(ctx.clone());
         let txn_hook;
@@ -162,6 +167,8 @@ impl<'op> CreateBookmarkOp<'op> {
             BookmarkKind::Scratch => {
                 txn_hook = None;
 
+                record_private_scratch_commits(ctx, repo, self.bookmark, self.target).await?;
+
                 ctx.scuba()
                     .clone()
                     .add("bookmark", self.bookmark.to_string())
@@ -180,6 +187,14 @@ impl<'op> CreateBookmarkOp<'op> {
                 )
                 .await?;
 
+                crate::restrictions::check_restriction_required_derived_data(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.target,
+                )
+                .await?;
+
                 let txn_hook_fut = crate::git_mapping::populate_git_mapping_txn_hook(
                     ctx,
                     repo,
@@ -237,6 +252,8 @@ impl<'op> CreateBookmarkOp<'op> {
             reason: self.reason,
         };
         log_bookmark_operation(ctx, repo, &info).await;
+        enqueue_derivation(ctx, repo, kind, self.target).await;
+        check_bookmark_count(ctx, repo);
         Ok(())
     }
 }
@@ -19,6 +19,7 @@ use repo_update_logger::log_bookmark_operation;
 use repo_update_logger::BookmarkInfo;
 use repo_update_logger::BookmarkOperation;
 
+use crate::freeze_windows::check_freeze_windows;
 use crate::repo_lock::check_repo_lock;
 use crate::restrictions::check_bookmark_sync_config;
 use crate::restrictions::BookmarkKindRestrictions;
@@ -111,6 +112,7 @@ impl<'op> DeleteBookmarkOp<'op> {
         }
 
         check_repo_lock(repo, kind, self.pushvars, ctx.metadata().identities()).await?;
+        check_freeze_windows(ctx, repo, self.bookmark, kind, self.pushvars).await?;
 
         ctx.scuba()
             .clone()
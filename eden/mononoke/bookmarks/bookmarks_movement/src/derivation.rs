@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use bookmarks_types::BookmarkKind;
+use context::CoreContext;
+use derivation_queue::DerivationQueueRef;
+use metaconfig_types::RepoConfigRef;
+use mononoke_types::ChangesetId;
+use repo_identity::RepoIdentityRef;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.bookmarks_movement";
+    derivation_enqueue_failure: dynamic_timeseries("{}.derivation_enqueue_failure", (reponame: String); Rate, Sum),
+}
+
+/// Enqueue asynchronous derivation of the repo's queued derived data types
+/// for a changeset that a public bookmark has been moved to.  The bookmark
+/// has already moved, so failures are logged rather than returned; the
+/// derived data tailer will derive the data eventually anyway.
+pub(crate) async fn enqueue_derivation(
+    ctx: &CoreContext,
+    repo: &(impl DerivationQueueRef + RepoConfigRef + RepoIdentityRef),
+    kind: BookmarkKind,
+    cs_id: ChangesetId,
+) {
+    if !kind.is_public() {
+        return;
+    }
+    let queue_config = match &repo.repo_config().derived_data_config.queue {
+        Some(queue_config) => queue_config,
+        None => return,
+    };
+    if let Err(err) = repo
+        .derivation_queue()
+        .enqueue(ctx, cs_id, &queue_config.types)
+        .await
+    {
+        STATS::derivation_enqueue_failure.add_value(1, (repo.repo_identity().name().to_string(),));
+        warn!(
+            ctx.logger(),
+            "Failed to enqueue derivation for {}: {:#}", cs_id, err
+        );
+    }
+}
@@ -0,0 +1,316 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use async_trait::async_trait;
+use bookmarks::BookmarkTransactionError;
+use bookmarks_types::BookmarkKind;
+use bookmarks_types::BookmarkName;
+use bytes::Bytes;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use context::CoreContext;
+use metaconfig_types::FreezeWindow;
+use metaconfig_types::RepoConfigRef;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use pushrebase_hook::PushrebaseCommitHook;
+use pushrebase_hook::PushrebaseHook;
+use pushrebase_hook::PushrebaseTransactionHook;
+use pushrebase_hook::RebasedChangesets;
+use repo_permission_checker::RepoPermissionCheckerRef;
+use sql::Transaction;
+
+use crate::BookmarkMovementError;
+
+/// Pushvar that bypasses any active freeze windows.
+const BYPASS_FREEZE_WINDOW: &str = "BYPASS_FREEZE_WINDOW";
+
+/// Find the freeze window that is active for a bookmark at a given time, and
+/// when it ends.  If several windows are active, the one that ends last is
+/// returned.
+fn active_freeze_window<'a>(
+    windows: &'a [FreezeWindow],
+    bookmark: &BookmarkName,
+    now: DateTime<Utc>,
+) -> Result<Option<(&'a FreezeWindow, DateTime<Utc>)>> {
+    let mut active: Option<(&FreezeWindow, DateTime<Utc>)> = None;
+    for window in windows {
+        if !window.applies_to(bookmark) {
+            continue;
+        }
+        let duration = Duration::from_std(window.duration)?;
+        // The window is active if it started less than its duration ago.
+        // Only its first start since then is needed, so this takes the same
+        // time however often the window recurs.
+        let first_start = match window.schedule.after(&(now - duration)).next() {
+            Some(start) if start <= now => start,
+            _ => continue,
+        };
+        // If the window recurs more often than it lasts, its starts overlap,
+        // and it ends a duration after the most recent one.
+        let last_start = window
+            .schedule
+            .after(&now)
+            .next_back()
+            .map_or(first_start, |start| start.max(first_start));
+        let end = last_start + duration;
+        if active.map_or(true, |(_, active_end)| end > active_end) {
+            active = Some((window, end));
+        }
+    }
+    Ok(active)
+}
+
+async fn should_check_freeze_windows(
+    ctx: &CoreContext,
+    repo: &impl RepoPermissionCheckerRef,
+    kind: BookmarkKind,
+    pushvars: Option<&HashMap<String, Bytes>>,
+) -> bool {
+    match kind {
+        BookmarkKind::Scratch => false,
+        BookmarkKind::Publishing | BookmarkKind::PullDefaultPublishing => {
+            let bypass_requested = pushvars
+                .and_then(|pushvars| pushvars.get(BYPASS_FREEZE_WINDOW))
+                .map_or(false, |value| value.to_ascii_lowercase() == b"true");
+            if !bypass_requested {
+                return true;
+            }
+            let bypass_allowed = repo
+                .repo_permission_checker()
+                .check_if_read_only_bypass_allowed(ctx.metadata().identities())
+                .await;
+            !bypass_allowed
+        }
+    }
+}
+
+/// Reject the push if one of the windows is active for the bookmark.
+fn reject_if_frozen(
+    ctx: &CoreContext,
+    windows: &[FreezeWindow],
+    bookmark: &BookmarkName,
+) -> Result<(), BookmarkMovementError> {
+    let now = DateTime::<Utc>::from(ctx.now());
+    if let Some((window, until)) = active_freeze_window(windows, bookmark, now)? {
+        ctx.scuba()
+            .clone()
+            .add("bookmark", bookmark.to_string())
+            .log_with_msg(
+                "Rejected push during freeze window",
+                Some(window.name.clone()),
+            );
+        return Err(BookmarkMovementError::BookmarkFrozen {
+            bookmark: bookmark.clone(),
+            window: window.name.clone(),
+            until,
+            landing_queue: window.landing_queue.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that the bookmark is not frozen by one of the repo's freeze
+/// windows.
+pub(crate) async fn check_freeze_windows(
+    ctx: &CoreContext,
+    repo: &(impl RepoConfigRef + RepoPermissionCheckerRef),
+    bookmark: &BookmarkName,
+    kind: BookmarkKind,
+    pushvars: Option<&HashMap<String, Bytes>>,
+) -> Result<(), BookmarkMovementError> {
+    let windows = &repo.repo_config().freeze_windows;
+    if windows.is_empty() || !should_check_freeze_windows(ctx, repo, kind, pushvars).await {
+        return Ok(());
+    }
+
+    reject_if_frozen(ctx, windows, bookmark)
+}
+
+/// Pushrebase hook that checks the freeze windows again as part of the
+/// bookmark update transaction, in case a window started while the
+/// pushrebase was in progress.
+pub(crate) struct FreezeWindowPushrebaseHook {
+    windows: Vec<FreezeWindow>,
+    bookmark: BookmarkName,
+}
+
+impl FreezeWindowPushrebaseHook {
+    pub(crate) async fn new(
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoPermissionCheckerRef),
+        bookmark: &BookmarkName,
+        kind: BookmarkKind,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Option<Box<dyn PushrebaseHook>> {
+        let windows = repo
+            .repo_config()
+            .freeze_windows
+            .iter()
+            .filter(|window| window.applies_to(bookmark))
+            .cloned()
+            .collect::<Vec<_>>();
+        if windows.is_empty() || !should_check_freeze_windows(ctx, repo, kind, pushvars).await {
+            return None;
+        }
+        let hook = Box::new(FreezeWindowPushrebaseHook {
+            windows,
+            bookmark: bookmark.clone(),
+        });
+        Some(hook as Box<dyn PushrebaseHook>)
+    }
+}
+
+#[async_trait]
+impl PushrebaseHook for FreezeWindowPushrebaseHook {
+    async fn prepushrebase(&self) -> Result<Box<dyn PushrebaseCommitHook>> {
+        let hook = Box::new(FreezeWindowCommitTransactionHook {
+            windows: self.windows.clone(),
+            bookmark: self.bookmark.clone(),
+        });
+        Ok(hook as Box<dyn PushrebaseCommitHook>)
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+}
+
+struct FreezeWindowCommitTransactionHook {
+    windows: Vec<FreezeWindow>,
+    bookmark: BookmarkName,
+}
+
+#[async_trait]
+impl PushrebaseCommitHook for FreezeWindowCommitTransactionHook {
+    fn post_rebase_changeset(
+        &mut self,
+        _bcs_old: ChangesetId,
+        _bcs_new: &mut BonsaiChangesetMut,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn into_transaction_hook(
+        self: Box<Self>,
+        _ctx: &CoreContext,
+        _rebased: &RebasedChangesets,
+    ) -> Result<Box<dyn PushrebaseTransactionHook>> {
+        Ok(self as Box<dyn PushrebaseTransactionHook>)
+    }
+}
+
+#[async_trait]
+impl PushrebaseTransactionHook for FreezeWindowCommitTransactionHook {
+    async fn populate_transaction(
+        &self,
+        ctx: &CoreContext,
+        txn: Transaction,
+    ) -> Result<Transaction, BookmarkTransactionError> {
+        reject_if_frozen(ctx, &self.windows, &self.bookmark)
+            .map_err(|e| BookmarkTransactionError::Other(anyhow!("{}", e)))?;
+        Ok(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use regex::Regex;
+
+    use super::*;
+
+    fn window(
+        name: &str,
+        schedule: &str,
+        hours: u64,
+        bookmark_regex: Option<&str>,
+    ) -> FreezeWindow {
+        FreezeWindow {
+            name: name.to_string(),
+            schedule: schedule.parse().unwrap(),
+            duration: std::time::Duration::from_secs(hours * 3600),
+            bookmark_regex: bookmark_regex.map(|regex| Regex::new(regex).unwrap().into()),
+            landing_queue: None,
+        }
+    }
+
+    #[test]
+    fn test_active_freeze_window() -> Result<()> {
+        // Every Friday at 18:00 for the weekend, and every day at midnight
+        // for an hour on release branches.
+        let windows = vec![
+            window("weekend", "0 0 18 * * Fri", 60, None),
+            window("nightly", "0 0 0 * * *", 1, Some("^release/")),
+        ];
+        let main = BookmarkName::new("main")?;
+        let release = BookmarkName::new("release/1")?;
+        let active = |bookmark, now| {
+            active_freeze_window(&windows, bookmark, now)
+                .map(|active| active.map(|(window, until)| (window.name.clone(), until)))
+        };
+
+        // Thursday 2022-11-03 12:00: nothing is frozen.
+        let thursday = Utc.ymd(2022, 11, 3).and_hms(12, 0, 0);
+        assert_eq!(active(&main, thursday)?, None);
+        assert_eq!(active(&release, thursday)?, None);
+
+        // Friday 2022-11-04 00:30: only release branches are frozen.
+        let friday_night = Utc.ymd(2022, 11, 4).and_hms(0, 30, 0);
+        assert_eq!(active(&main, friday_night)?, None);
+        assert_eq!(
+            active(&release, friday_night)?,
+            Some(("nightly".to_string(), Utc.ymd(2022, 11, 4).and_hms(1, 0, 0)))
+        );
+
+        // Friday 2022-11-04 18:00: the weekend window starts.
+        let friday_evening = Utc.ymd(2022, 11, 4).and_hms(18, 0, 0);
+        let monday_morning = Utc.ymd(2022, 11, 7).and_hms(6, 0, 0);
+        assert_eq!(
+            active(&main, friday_evening)?,
+            Some(("weekend".to_string(), monday_morning))
+        );
+
+        // Sunday 2022-11-06 00:30: both windows are active, and the
+        // weekend window ends last.
+        let sunday = Utc.ymd(2022, 11, 6).and_hms(0, 30, 0);
+        assert_eq!(
+            active(&release, sunday)?,
+            Some(("weekend".to_string(), monday_morning))
+        );
+
+        // Monday 2022-11-07 06:00: the weekend window has ended.
+        assert_eq!(active(&main, monday_morning)?, None);
+
+        Ok(())
+    }
+    #[test]
+    fn test_overlapping_freeze_window() -> Result<()> {
+        // A window that starts every minute and lasts for a week is always
+        // active, and ends a week after its most recent start.
+        let windows = vec![window("always", "0 * * * * *", 7 * 24, None)];
+        let main = BookmarkName::new("main")?;
+        let now = Utc.ymd(2022, 11, 3).and_hms(12, 0, 30);
+        let active = active_freeze_window(&windows, &main, now)?
+            .map(|(window, until)| (window.name.clone(), until));
+        assert_eq!(
+            active,
+            Some((
+                "always".to_string(),
+                Utc.ymd(2022, 11, 10).and_hms(12, 0, 0)
+            ))
+        );
+
+        Ok(())
+    }
+}
@@ -58,12 +58,12 @@ struct ChangesetMappingNeeded<'a> {
     bcs: Cow<'a, BonsaiChangeset>,
 }
 
-/// Find ancestors of `start` which have git mapping extras but do not
+/// Find ancestors of `heads` which have git mapping extras but do not
 /// have a git mapping entry set in db, and generate their mapping entries.
 async fn new_mapping_entries(
     ctx: &CoreContext,
     repo: &impl Repo,
-    start: ChangesetId,
+    heads: &[ChangesetId],
     new_changesets: &HashMap<ChangesetId, BonsaiChangeset>,
 ) -> Result<NewMappingEntries> {
     let mut new_mapping_entries = Vec::new();
@@ -113,7 +113,9 @@ async fn new_mapping_entries(
         }
     };
 
-    queue.extend(get_new_queue_entry(start));
+    for head in heads {
+        queue.extend(get_new_queue_entry(*head));
+    }
 
     while let Some(entry) = queue.next().await {
         let needed = match entry? {
@@ -208,8 +210,20 @@ pub(crate) async fn populate_git_mapping_txn_hook(
     new_head: ChangesetId,
     new_changesets: &HashMap<ChangesetId, BonsaiChangeset>,
 ) -> Result<Option<BookmarkTransactionHook>, BookmarkMovementError> {
-    if repo.repo_config().pushrebase.populate_git_mapping {
-        let entries = new_mapping_entries(ctx, repo, new_head, new_changesets).await?;
+    populate_git_mapping_txn_hook_for_heads(ctx, repo, &[new_head], new_changesets).await
+}
+
+/// Generate a bookmark transaction hook that will populate the git mapping
+/// with new entries for the new mapped commits reachable from any of
+/// `new_heads`, for transactions that move several bookmarks at once.
+pub(crate) async fn populate_git_mapping_txn_hook_for_heads(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    new_heads: &[ChangesetId],
+    new_changesets: &HashMap<ChangesetId, BonsaiChangeset>,
+) -> Result<Option<BookmarkTransactionHook>, BookmarkMovementError> {
+    if repo.repo_config().pushrebase.populate_git_mapping && !new_heads.is_empty() {
+        let entries = new_mapping_entries(ctx, repo, new_heads, new_changesets).await?;
         Ok(Some(upload_mapping_entries_bookmark_txn_hook(
             repo.bonsai_git_mapping_arc(),
             entries,
@@ -343,7 +357,7 @@ mod tests {
         let entries = new_mapping_entries(
             ctx,
             repo,
-            b,
+            &[b],
             &hashmap! {
                 a => a_bcs,
                 b => b_bcs,
@@ -361,7 +375,7 @@ mod tests {
         apply_entries(ctx, repo, &bookmark, z, b, entries).await?;
 
         // Addition using existing changesets.
-        let entries = new_mapping_entries(ctx, repo, d, &hashmap! {}).await?;
+        let entries = new_mapping_entries(ctx, repo, &[d], &hashmap! {}).await?;
         assert_eq!(
             mapping_entries(&entries.new_mapping_entries),
             hashset! { (c, THREES_GIT_SHA1), (d, FOURS_GIT_SHA1) },
@@ -372,7 +386,7 @@ mod tests {
         apply_entries(ctx, repo, &bookmark, b, d, entries).await?;
 
         // Move to commits with no mapping.
-        let entries = new_mapping_entries(ctx, repo, y, &hashmap! {y => y_bcs}).await?;
+        let entries = new_mapping_entries(ctx, repo, &[y], &hashmap! {y => y_bcs}).await?;
         assert_eq!(mapping_entries(&entries.new_mapping_entries), hashset! {});
         assert_eq!(entries.from_new_changesets, 0);
         assert_eq!(entries.from_ancestors_no_mapping, 0);
@@ -380,7 +394,7 @@ mod tests {
         apply_entries(ctx, repo, &bookmark, d, y, entries).await?;
 
         // Move to descendants of commit with no mapping.
-        let entries = new_mapping_entries(ctx, repo, f, &hashmap! {f => f_bcs}).await?;
+        let entries = new_mapping_entries(ctx, repo, &[f], &hashmap! {f => f_bcs}).await?;
         assert_eq!(
             mapping_entries(&entries.new_mapping_entries),
             hashset! {
@@ -12,14 +12,21 @@ use blobrepo::AsBlobRepo;
 use bonsai_git_mapping::BonsaiGitMappingArc;
 use bonsai_globalrev_mapping::BonsaiGlobalrevMappingArc;
 use bonsai_hg_mapping::BonsaiHgMappingRef;
+use bookmarks::BookmarksArc;
 use bookmarks::BookmarksRef;
 use bookmarks_types::BookmarkName;
 use changeset_fetcher::ChangesetFetcherArc;
 use changesets::ChangesetsRef;
+use chrono::DateTime;
+use chrono::Utc;
+use derivation_queue::DerivationQueueRef;
 use itertools::Itertools;
+use metaconfig_types::RepoConfigArc;
 use metaconfig_types::RepoConfigRef;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
+use mutable_counters::MutableCountersArc;
+use path_policy::PathPolicyViolation;
 use phases::PhasesRef;
 use pushrebase::PushrebaseError;
 use pushrebase_mutation_mapping::PushrebaseMutationMappingRef;
@@ -28,15 +35,24 @@ use repo_blobstore::RepoBlobstoreRef;
 use repo_bookmark_attrs::RepoBookmarkAttrsRef;
 use repo_cross_repo::RepoCrossRepoRef;
 use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityArc;
 use repo_identity::RepoIdentityRef;
 use repo_permission_checker::RepoPermissionCheckerRef;
 use thiserror::Error;
 
 mod affected_changesets;
+mod author_policy;
+mod commit_limits;
+mod commit_message_rewrite;
 mod create;
 mod delete;
+mod derivation;
+mod freeze_windows;
 mod git_mapping;
 mod hook_running;
+mod merge_policy;
+mod multiple;
+mod private_scratch;
 mod pushrebase_onto;
 mod repo_lock;
 mod restrictions;
@@ -47,9 +63,15 @@ pub use hooks::CrossRepoPushSource;
 pub use hooks::HookRejection;
 pub use pushrebase::PushrebaseOutcome;
 
+pub use crate::author_policy::AuthorPolicyViolation;
+pub use crate::commit_limits::CommitLimitViolation;
+pub use crate::commit_message_rewrite::ORIGINAL_MESSAGE_EXTRA;
 pub use crate::create::CreateBookmarkOp;
 pub use crate::delete::DeleteBookmarkOp;
 pub use crate::hook_running::run_hooks;
+pub use crate::merge_policy::MergePolicyViolation;
+pub use crate::multiple::BookmarkMovement;
+pub use crate::multiple::MultipleBookmarksOp;
 pub use crate::pushrebase_onto::get_pushrebase_hooks;
 pub use crate::pushrebase_onto::PushrebaseOntoBookmarkOp;
 pub use crate::restrictions::check_bookmark_sync_config;
@@ -66,16 +88,21 @@ pub trait Repo = AsBlobRepo
     + BonsaiHgMappingRef
     + BonsaiGitMappingArc
     + BonsaiGlobalrevMappingArc
+    + BookmarksArc
     + BookmarksRef
     + ChangesetFetcherArc
     + ChangesetsRef
+    + DerivationQueueRef
+    + MutableCountersArc
     + PhasesRef
     + PushrebaseMutationMappingRef
     + RepoBookmarkAttrsRef
+    + RepoConfigArc
     + RepoConfigRef
     + RepoDerivedDataRef
     + RepoBlobstoreRef
     + RepoCrossRepoRef
+    + RepoIdentityArc
     + RepoIdentityRef
     + RepoPermissionCheckerRef
     + RepoLockRef
@@ -118,6 +145,9 @@ pub enum BookmarkMovementError {
     #[error("Bookmark transaction failed")]
     TransactionFailed,
 
+    #[error("Bookmark '{bookmark}' cannot be moved more than once in the same transaction")]
+    DuplicateBookmarkMovement { bookmark: BookmarkName },
+
     #[error("Hooks failed:\n{}", describe_hook_rejections(.0.as_slice()))]
     HookFailure(Vec<HookRejection>),
 
@@ -127,6 +157,17 @@ pub enum BookmarkMovementError {
     #[error("Repo is locked: {0}")]
     RepoLocked(String),
 
+    #[error(
+        "Bookmark '{bookmark}' is frozen by freeze window '{window}' until {until}{}",
+        describe_landing_queue(.landing_queue)
+    )]
+    BookmarkFrozen {
+        bookmark: BookmarkName,
+        window: String,
+        until: DateTime<Utc>,
+        landing_queue: Option<String>,
+    },
+
     #[error("Case conflict found in {changeset_id}: {path1} conflicts with {path2}")]
     CaseConflict {
         changeset_id: ChangesetId,
@@ -134,6 +175,30 @@ pub enum BookmarkMovementError {
         path2: MPath,
     },
 
+    #[error("Path policy violation in {changeset_id}: {violation}")]
+    PathPolicyViolation {
+        changeset_id: ChangesetId,
+        violation: PathPolicyViolation,
+    },
+
+    #[error("Commit limit exceeded in {changeset_id}: {violation}")]
+    CommitLimitExceeded {
+        changeset_id: ChangesetId,
+        violation: CommitLimitViolation,
+    },
+
+    #[error("Author policy violation in {changeset_id}: {violation}")]
+    AuthorPolicyViolation {
+        changeset_id: ChangesetId,
+        violation: AuthorPolicyViolation,
+    },
+
+    #[error("Merge policy violation in {changeset_id}: {violation}")]
+    MergePolicyViolation {
+        changeset_id: ChangesetId,
+        violation: MergePolicyViolation,
+    },
+
     #[error(
         "This repository uses Globalrevs. Pushrebase is only allowed onto the bookmark '{}', this push was for '{}'",
         .globalrevs_publishing_bookmark,
@@ -160,6 +225,15 @@ pub enum BookmarkMovementError {
         descendant_bookmark: BookmarkName,
     },
 
+    #[error(
+        "Bookmark '{bookmark}' cannot be moved to {target} until {derived_data_types} have been derived for it (derivation did not finish in time, retry later)"
+    )]
+    RequiredDerivedDataPending {
+        bookmark: BookmarkName,
+        target: ChangesetId,
+        derived_data_types: String,
+    },
+
     #[error(
         "Bookmark '{bookmark}' cannot be moved because publishing bookmarks are being redirected"
     )]
@@ -174,6 +248,13 @@ pub enum BookmarkMovementError {
     Error(#[from] anyhow::Error),
 }
 
+fn describe_landing_queue(landing_queue: &Option<String>) -> String {
+    match landing_queue {
+        Some(landing_queue) => format!(", please use landing queue '{}' instead", landing_queue),
+        None => String::new(),
+    }
+}
+
 pub fn describe_hook_rejections(rejections: &[HookRejection]) -> String {
     rejections
         .iter()
@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use metaconfig_types::Identity;
+use metaconfig_types::MergeCommitPolicy;
+use metaconfig_types::MergePolicy;
+use mononoke_types::BonsaiChangeset;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use thiserror::Error;
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum MergePolicyViolation {
+    #[error("Merge commits are not allowed on this bookmark")]
+    MergesDenied,
+
+    #[error("Merge commits on this bookmark may only be pushed by {}", display_identities(.allowed))]
+    MergeNotFromAllowedIdentity { allowed: Vec<Identity> },
+
+    #[error("The history of this bookmark must be linear, but this commit has {parents} parents")]
+    NonLinearHistory { parents: usize },
+}
+
+fn display_identities(identities: &[Identity]) -> String {
+    identities
+        .iter()
+        .map(|identity| format!("{}:{}", identity.id_type, identity.id_data))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Check a changeset that is being pushed or pushrebased onto a bookmark,
+/// or that will become an ancestor of it, against the bookmark's merge
+/// policy.
+pub(crate) fn check_new_changeset(
+    policy: &MergePolicy,
+    pusher: &MononokeIdentitySet,
+    bcs: &BonsaiChangeset,
+) -> Result<(), MergePolicyViolation> {
+    if !bcs.is_merge() {
+        return Ok(());
+    }
+    match &policy.merges {
+        MergeCommitPolicy::AllowAll => Ok(()),
+        MergeCommitPolicy::DenyAll => Err(MergePolicyViolation::MergesDenied),
+        MergeCommitPolicy::AllowFrom(allowed) => {
+            let permitted = allowed.iter().any(|Identity { id_type, id_data }| {
+                pusher.contains(&MononokeIdentity::new(id_type, id_data))
+            });
+            if permitted {
+                Ok(())
+            } else {
+                Err(MergePolicyViolation::MergeNotFromAllowedIdentity {
+                    allowed: allowed.clone(),
+                })
+            }
+        }
+    }
+}
+
+/// Check a changeset that will become an ancestor of a bookmark against the
+/// bookmark's linear history requirement.
+pub(crate) fn check_history_changeset(
+    policy: &MergePolicy,
+    bcs: &BonsaiChangeset,
+) -> Result<(), MergePolicyViolation> {
+    if policy.require_linear_history && bcs.is_merge() {
+        return Err(MergePolicyViolation::NonLinearHistory {
+            parents: bcs.parents().count(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mononoke_types::BonsaiChangesetMut;
+    use mononoke_types::DateTime;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use sorted_vector_map::SortedVectorMap;
+
+    use super::*;
+
+    fn changeset(parents: Vec<mononoke_types::ChangesetId>) -> BonsaiChangeset {
+        BonsaiChangesetMut {
+            parents,
+            author: "author".to_string(),
+            author_date: DateTime::from_timestamp(0, 0).unwrap(),
+            committer: None,
+            committer_date: None,
+            message: "message".to_string(),
+            extra: SortedVectorMap::new(),
+            file_changes: SortedVectorMap::new(),
+            is_snapshot: false,
+        }
+        .freeze()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_policy() {
+        let linear = changeset(vec![ONES_CSID]);
+        let merge = changeset(vec![ONES_CSID, TWOS_CSID]);
+        let octopus = changeset(vec![ONES_CSID, TWOS_CSID, THREES_CSID]);
+        let bot = Identity {
+            id_type: "SERVICE_IDENTITY".to_string(),
+            id_data: "merge_bot".to_string(),
+        };
+        let bot_pusher = [MononokeIdentity::new("SERVICE_IDENTITY", "merge_bot")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+        let user_pusher = [MononokeIdentity::new("USER", "alice")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+
+        let allow_all = MergePolicy::default();
+        assert_eq!(
+            check_new_changeset(&allow_all, &user_pusher, &merge),
+            Ok(())
+        );
+        assert_eq!(check_history_changeset(&allow_all, &octopus), Ok(()));
+
+        let deny_all = MergePolicy {
+            merges: MergeCommitPolicy::DenyAll,
+            require_linear_history: false,
+        };
+        assert_eq!(
+            check_new_changeset(&deny_all, &user_pusher, &linear),
+            Ok(())
+        );
+        assert_eq!(
+            check_new_changeset(&deny_all, &bot_pusher, &merge),
+            Err(MergePolicyViolation::MergesDenied)
+        );
+        assert_eq!(check_history_changeset(&deny_all, &merge), Ok(()));
+
+        let allow_bot = MergePolicy {
+            merges: MergeCommitPolicy::AllowFrom(vec![bot.clone()]),
+            require_linear_history: false,
+        };
+        assert_eq!(check_new_changeset(&allow_bot, &bot_pusher, &merge), Ok(()));
+        assert_eq!(
+            check_new_changeset(&allow_bot, &user_pusher, &merge),
+            Err(MergePolicyViolation::MergeNotFromAllowedIdentity { allowed: vec![bot] })
+        );
+
+        let linear_history = MergePolicy {
+            merges: MergeCommitPolicy::AllowAll,
+            require_linear_history: true,
+        };
+        assert_eq!(check_history_changeset(&linear_history, &linear), Ok(()));
+        assert_eq!(
+            check_history_changeset(&linear_history, &octopus),
+            Err(MergePolicyViolation::NonLinearHistory { parents: 3 })
+        );
+    }
+}
@@ -0,0 +1,423 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use bookmarks::BookmarkUpdateReason;
+use bookmarks_types::BookmarkKind;
+use bookmarks_types::BookmarkName;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::future;
+use hooks::CrossRepoPushSource;
+use hooks::HookManager;
+use mononoke_types::ChangesetId;
+use reachabilityindex::LeastCommonAncestorsHint;
+use repo_authorization::AuthorizationContext;
+use repo_authorization::RepoWriteOperation;
+use repo_update_logger::log_bookmark_operation;
+use repo_update_logger::BookmarkInfo;
+use repo_update_logger::BookmarkOperation;
+
+use crate::affected_changesets::find_draft_ancestors;
+use crate::affected_changesets::log_new_bonsai_changesets;
+use crate::affected_changesets::AdditionalChangesets;
+use crate::affected_changesets::AffectedChangesets;
+use crate::derivation::enqueue_derivation;
+use crate::freeze_windows::check_freeze_windows;
+use crate::private_scratch::record_private_scratch_commits;
+use crate::repo_lock::check_repo_lock;
+use crate::restrictions::check_bookmark_sync_config;
+use crate::restrictions::BookmarkKindRestrictions;
+use crate::update::BookmarkUpdatePolicy;
+use crate::update::BookmarkUpdateTargets;
+use crate::BookmarkMovementError;
+use crate::Repo;
+
+/// A single bookmark movement that is part of a multiple bookmark
+/// transaction.
+pub enum BookmarkMovement<'op> {
+    Create {
+        bookmark: &'op BookmarkName,
+        target: ChangesetId,
+    },
+    Update {
+        bookmark: &'op BookmarkName,
+        targets: BookmarkUpdateTargets,
+        update_policy: BookmarkUpdatePolicy,
+    },
+    Delete {
+        bookmark: &'op BookmarkName,
+        old_target: ChangesetId,
+    },
+}
+
+impl<'op> BookmarkMovement<'op> {
+    fn bookmark(&self) -> &'op BookmarkName {
+        match self {
+            Self::Create { bookmark, .. }
+            | Self::Update { bookmark, .. }
+            | Self::Delete { bookmark, .. } => bookmark,
+        }
+    }
+
+    /// The new target of the bookmark, if it is not being deleted.
+    fn new_target(&self) -> Option<ChangesetId> {
+        match self {
+            Self::Create { target, .. } => Some(*target),
+            Self::Update { targets, .. } => Some(targets.new),
+            Self::Delete { .. } => None,
+        }
+    }
+
+    fn write_operation(&self, kind: BookmarkKind) -> RepoWriteOperation {
+        match self {
+            Self::Create { .. } => RepoWriteOperation::CreateBookmark(kind),
+            Self::Update { .. } => RepoWriteOperation::UpdateBookmark(kind),
+            Self::Delete { .. } => RepoWriteOperation::DeleteBookmark(kind),
+        }
+    }
+
+    fn additional_changesets(&self) -> Option<AdditionalChangesets> {
+        match self {
+            Self::Create { target, .. } => Some(AdditionalChangesets::Ancestors(*target)),
+            Self::Update { targets, .. } => Some(AdditionalChangesets::Range {
+                head: targets.new,
+                base: targets.old,
+            }),
+            Self::Delete { .. } => None,
+        }
+    }
+
+    fn operation(&self) -> BookmarkOperation {
+        match self {
+            Self::Create { target, .. } => BookmarkOperation::Create(*target),
+            Self::Update { targets, .. } => BookmarkOperation::Update(targets.old, targets.new),
+            Self::Delete { old_target, .. } => BookmarkOperation::Delete(*old_target),
+        }
+    }
+}
+
+/// Move several bookmarks in a single transaction.  Either all of the
+/// bookmarks are moved, or none of them are.
+///
+/// Each movement is subject to the same checks as the equivalent single
+/// bookmark operation.  Hooks are run for all of the movements before any
+/// of them are applied, and all hook rejections are reported together.
+#[must_use = "MultipleBookmarksOp must be run to have an effect"]
+pub struct MultipleBookmarksOp<'op> {
+    movements: Vec<BookmarkMovement<'op>>,
+    reason: BookmarkUpdateReason,
+    kind_restrictions: BookmarkKindRestrictions,
+    cross_repo_push_source: CrossRepoPushSource,
+    pushvars: Option<&'op HashMap<String, Bytes>>,
+    log_new_public_commits_to_scribe: bool,
+}
+
+impl<'op> MultipleBookmarksOp<'op> {
+    pub fn new(
+        movements: Vec<BookmarkMovement<'op>>,
+        reason: BookmarkUpdateReason,
+    ) -> MultipleBookmarksOp<'op> {
+        MultipleBookmarksOp {
+            movements,
+            reason,
+            kind_restrictions: BookmarkKindRestrictions::AnyKind,
+            cross_repo_push_source: CrossRepoPushSource::NativeToThisRepo,
+            pushvars: None,
+            log_new_public_commits_to_scribe: false,
+        }
+    }
+
+    pub fn only_if_scratch(mut self) -> Self {
+        self.kind_restrictions = BookmarkKindRestrictions::OnlyScratch;
+        self
+    }
+
+    pub fn only_if_public(mut self) -> Self {
+        self.kind_restrictions = BookmarkKindRestrictions::OnlyPublishing;
+        self
+    }
+
+    pub fn with_pushvars(mut self, pushvars: Option<&'op HashMap<String, Bytes>>) -> Self {
+        self.pushvars = pushvars;
+        self
+    }
+
+    pub fn with_push_source(mut self, cross_repo_push_source: CrossRepoPushSource) -> Self {
+        self.cross_repo_push_source = cross_repo_push_source;
+        self
+    }
+
+    pub fn log_new_public_commits_to_scribe(mut self) -> Self {
+        self.log_new_public_commits_to_scribe = true;
+        self
+    }
+
+    /// Check that a single movement is permitted, other than its effect on
+    /// the affected changesets.
+    async fn check_movement(
+        &self,
+        ctx: &CoreContext,
+        authz: &AuthorizationContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        movement: &BookmarkMovement<'op>,
+    ) -> Result<BookmarkKind, BookmarkMovementError> {
+        let bookmark = movement.bookmark();
+        let kind = self.kind_restrictions.check_kind(repo, bookmark)?;
+
+        authz
+            .require_repo_write(ctx, repo, movement.write_operation(kind))
+            .await?;
+        authz.require_bookmark_modify(ctx, repo, bookmark).await?;
+
+        check_bookmark_sync_config(repo, bookmark, kind)?;
+
+        match movement {
+            BookmarkMovement::Update {
+                targets,
+                update_policy,
+                ..
+            } => {
+                update_policy
+                    .check_update_permitted(ctx, repo, lca_hint.as_ref(), bookmark, targets)
+                    .await?;
+            }
+            BookmarkMovement::Delete { .. } => {
+                if repo.repo_bookmark_attrs().is_fast_forward_only(bookmark) {
+                    // Cannot delete fast-forward-only bookmarks.
+                    return Err(BookmarkMovementError::DeletionProhibited {
+                        bookmark: bookmark.clone(),
+                    });
+                }
+            }
+            BookmarkMovement::Create { .. } => {}
+        }
+
+        if kind != BookmarkKind::Scratch {
+            if let Some(target) = movement.new_target() {
+                crate::restrictions::check_restriction_ensure_ancestor_of(
+                    ctx, repo, bookmark, lca_hint, target,
+                )
+                .await?;
+
+                crate::restrictions::check_restriction_required_derived_data(
+                    ctx, repo, bookmark, target,
+                )
+                .await?;
+            }
+        }
+
+        Ok(kind)
+    }
+
+    /// Check the changesets affected by a single movement, including running
+    /// hooks on them.
+    async fn check_affected_changesets(
+        &self,
+        ctx: &CoreContext,
+        authz: &AuthorizationContext,
+        repo: &impl Repo,
+        lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+        hook_manager: &HookManager,
+        movement: &BookmarkMovement<'op>,
+        kind: BookmarkKind,
+    ) -> Result<(), BookmarkMovementError> {
+        if let Some(additional_changesets) = movement.additional_changesets() {
+            AffectedChangesets::new()
+                .check_restrictions(
+                    ctx,
+                    authz,
+                    repo,
+                    lca_hint,
+                    hook_manager,
+                    movement.bookmark(),
+                    self.pushvars,
+                    self.reason,
+                    kind,
+                    additional_changesets,
+                    self.cross_repo_push_source,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn run(
+        self,
+        ctx: &'op CoreContext,
+        authz: &'op AuthorizationContext,
+        repo: &'op impl Repo,
+        lca_hint: &'op Arc<dyn LeastCommonAncestorsHint>,
+        hook_manager: &'op HookManager,
+    ) -> Result<(), BookmarkMovementError> {
+        let mut seen = HashSet::new();
+        for movement in self.movements.iter() {
+            if !seen.insert(movement.bookmark()) {
+                return Err(BookmarkMovementError::DuplicateBookmarkMovement {
+                    bookmark: movement.bookmark().clone(),
+                });
+            }
+        }
+
+        let kinds = future::try_join_all(
+            self.movements
+                .iter()
+                .map(|movement| self.check_movement(ctx, authz, repo, lca_hint, movement)),
+        )
+        .await?;
+
+        // Check all of the movements before reporting any hook rejections,
+        // so that the caller sees every rejection at once.
+        let results = future::join_all(self.movements.iter().zip(kinds.iter()).map(
+            |(movement, kind)| {
+                self.check_affected_changesets(
+                    ctx,
+                    authz,
+                    repo,
+                    lca_hint,
+                    hook_manager,
+                    movement,
+                    *kind,
+                )
+            },
+        ))
+        .await;
+        let mut rejections = Vec::new();
+        for result in results {
+            match result {
+                Ok(()) => {}
+                Err(BookmarkMovementError::HookFailure(hook_rejections)) => {
+                    rejections.extend(hook_rejections)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if !rejections.is_empty() {
+            return Err(BookmarkMovementError::HookFailure(rejections));
+        }
+
+        for kind in kinds.iter().collect::<HashSet<_>>() {
+            check_repo_lock(repo, *kind, self.pushvars, ctx.metadata().identities()).await?;
+        }
+        for (movement, kind) in self.movements.iter().zip(kinds.iter()) {
+            check_freeze_windows(ctx, repo, movement.bookmark(), *kind, self.pushvars).await?;
+        }
+
+        let public_targets = self
+            .movements
+            .iter()
+            .zip(kinds.iter())
+            .filter(|(_, kind)| **kind != BookmarkKind::Scratch)
+            .filter_map(|(movement, _)| movement.new_target())
+            .collect::<Vec<_>>();
+        let new_changesets = HashMap::new();
+        let txn_hook = crate::git_mapping::populate_git_mapping_txn_hook_for_heads(
+            ctx,
+            repo,
+            &public_targets,
+            &new_changesets,
+        );
+        let log_to_scribe = self.log_new_public_commits_to_scribe;
+        let to_log = future::join_all(self.movements.iter().zip(kinds.iter()).map(
+            |(movement, kind)| async move {
+                match (movement.new_target(), kind) {
+                    (
+                        Some(target),
+                        BookmarkKind::Publishing | BookmarkKind::PullDefaultPublishing,
+                    ) if log_to_scribe => match find_draft_ancestors(ctx, repo, target).await {
+                        Ok(bcss) => bcss,
+                        Err(err) => {
+                            ctx.scuba().clone().log_with_msg(
+                                "Failed to find draft ancestors",
+                                Some(format!("{}", err)),
+                            );
+                            vec![]
+                        }
+                    },
+                    _ => vec![],
+                }
+            },
+        ));
+        let (txn_hook, to_log) = futures::join!(txn_hook, to_log);
+        let txn_hook = txn_hook?;
+
+        for (movement, kind) in self.movements.iter().zip(kinds.iter()) {
+            if let (Some(target), BookmarkKind::Scratch) = (movement.new_target(), kind) {
+                record_private_scratch_commits(ctx, repo, movement.bookmark(), target).await?;
+            }
+        }
+
+        let mut txn = repo.bookmarks().create_transaction(ctx.clone());
+        for (movement, kind) in self.movements.iter().zip(kinds.iter()) {
+            let bookmark = movement.bookmark();
+            match (movement, kind) {
+                (BookmarkMovement::Create { target, .. }, BookmarkKind::Scratch) => {
+                    txn.create_scratch(bookmark, *target)?
+                }
+                (BookmarkMovement::Create { target, .. }, _) => {
+                    txn.create(bookmark, *target, self.reason)?
+                }
+                (BookmarkMovement::Update { targets, .. }, BookmarkKind::Scratch) => {
+                    txn.update_scratch(bookmark, targets.new, targets.old)?
+                }
+                (BookmarkMovement::Update { targets, .. }, _) => {
+                    txn.update(bookmark, targets.new, targets.old, self.reason)?
+                }
+                (BookmarkMovement::Delete { old_target, .. }, BookmarkKind::Scratch) => {
+                    txn.delete_scratch(bookmark, *old_target)?
+                }
+                (BookmarkMovement::Delete { old_target, .. }, _) => {
+                    txn.delete(bookmark, *old_target, self.reason)?
+                }
+            }
+        }
+
+        ctx.scuba()
+            .clone()
+            .add(
+                "bookmarks",
+                self.movements
+                    .iter()
+                    .map(|movement| movement.bookmark().to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .log_with_msg("Moving multiple bookmarks", None);
+
+        let ok = match txn_hook {
+            Some(txn_hook) => txn.commit_with_hook(txn_hook).await?,
+            None => txn.commit().await?,
+        };
+        if !ok {
+            return Err(BookmarkMovementError::TransactionFailed);
+        }
+
+        for ((movement, kind), commits_to_log) in
+            self.movements.iter().zip(kinds.into_iter()).zip(to_log)
+        {
+            if log_to_scribe {
+                log_new_bonsai_changesets(ctx, repo, movement.bookmark(), kind, commits_to_log)
+                    .await;
+            }
+            let info = BookmarkInfo {
+                bookmark_name: movement.bookmark().clone(),
+                bookmark_kind: kind,
+                operation: movement.operation(),
+                reason: self.reason,
+            };
+            log_bookmark_operation(ctx, repo, &info).await;
+            if let Some(target) = movement.new_target() {
+                enqueue_derivation(ctx, repo, kind, target).await;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Loadable;
+use bookmarks_types::BookmarkName;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use manifest::find_intersection_of_diffs;
+use manifest::Entry;
+use mercurial_derived_data::DeriveHgChangeset;
+use mercurial_types::HgChangesetId;
+use mercurial_types::HgNodeHash;
+use mononoke_types::ChangesetId;
+use repo_authorization::load_draft_commit_owner;
+use repo_authorization::record_draft_commit_owner;
+use repo_authorization::record_draft_hg_nodes;
+
+use crate::affected_changesets::find_draft_ancestor_ids;
+use crate::Repo;
+
+/// Maximum number of commits that are checked or recorded concurrently.
+const RECORD_CONCURRENCY: usize = 10;
+
+/// The hg trees and file nodes that a changeset introduces, compared to its
+/// parents.
+async fn new_hg_nodes(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    cs_id: ChangesetId,
+) -> Result<Vec<HgNodeHash>> {
+    let hg_cs = repo
+        .derive_hg_changeset(ctx, cs_id)
+        .await?
+        .load(ctx, repo.repo_blobstore())
+        .await?;
+    let parent_mf_ids = stream::iter(hg_cs.p1().into_iter().chain(hg_cs.p2()))
+        .then(|parent| async move {
+            let parent = HgChangesetId::new(parent)
+                .load(ctx, repo.repo_blobstore())
+                .await?;
+            Ok::<_, Error>(parent.manifestid())
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+    find_intersection_of_diffs(
+        ctx.clone(),
+        repo.repo_blobstore().clone(),
+        hg_cs.manifestid(),
+        parent_mf_ids,
+    )
+    .map_ok(|(_path, entry)| match entry {
+        Entry::Tree(mf_id) => mf_id.into_nodehash(),
+        Entry::Leaf((_file_type, filenode_id)) => filenode_id.into_nodehash(),
+    })
+    .try_collect()
+    .await
+}
+
+/// If the bookmark is a private scratch bookmark, record its owner as the
+/// owner of the draft commits it is being moved to, along with the hg nodes
+/// those commits introduce.
+///
+/// This is done for every movement of a private scratch bookmark rather
+/// than only when commits are uploaded, so that commits uploaded through
+/// any path, or before the namespace was configured, get an owner once they
+/// are on a private scratch bookmark.
+pub(crate) async fn record_private_scratch_commits(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bookmark: &BookmarkName,
+    target: ChangesetId,
+) -> Result<()> {
+    let owner = match &repo.repo_config().infinitepush.private_namespace {
+        Some(namespace) => match namespace.owner(bookmark) {
+            Some(owner) => owner,
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+    // Commits that already have an owner have had their nodes recorded.
+    let drafts = stream::iter(find_draft_ancestor_ids(ctx, repo, target).await?)
+        .map(|cs_id| async move {
+            let owner = load_draft_commit_owner(ctx, repo, cs_id).await?;
+            Ok::<_, Error>(owner.is_none().then_some(cs_id))
+        })
+        .buffer_unordered(RECORD_CONCURRENCY)
+        .try_filter_map(|cs_id| async move { Ok(cs_id) })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // The nodes are recorded before the owners, so that a commit that has
+    // an owner has all of its nodes recorded.
+    stream::iter(drafts.iter().copied())
+        .map(|cs_id| async move {
+            let nodes = new_hg_nodes(ctx, repo, cs_id).await?;
+            record_draft_hg_nodes(ctx, repo, cs_id, nodes).await
+        })
+        .buffer_unordered(RECORD_CONCURRENCY)
+        .try_collect::<()>()
+        .await
+        .context("Failed to record hg nodes of private scratch commits")?;
+    record_draft_commit_owner(ctx, repo, owner, drafts)
+        .await
+        .context("Failed to record owner of private scratch commits")
+}
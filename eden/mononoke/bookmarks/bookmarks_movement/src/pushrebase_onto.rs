@@ -36,10 +36,15 @@ use repo_update_logger::BookmarkOperation;
 
 use crate::affected_changesets::AdditionalChangesets;
 use crate::affected_changesets::AffectedChangesets;
+use crate::commit_message_rewrite::CommitMessageRewritePushrebaseHook;
+use crate::derivation::enqueue_derivation;
+use crate::freeze_windows::check_freeze_windows;
+use crate::freeze_windows::FreezeWindowPushrebaseHook;
 use crate::repo_lock::check_repo_lock;
 use crate::repo_lock::RepoLockPushrebaseHook;
 use crate::restrictions::check_bookmark_sync_config;
 use crate::restrictions::BookmarkKindRestrictions;
+use crate::restrictions::RequiredDerivedDataPushrebaseHook;
 use crate::BookmarkMovementError;
 use crate::Repo;
 
@@ -174,6 +179,7 @@ impl<'op> PushrebaseOntoBookmarkOp<'op> {
         // bookmark update transaction, to check if the repo got locked while
         // we were peforming the pushrebase.
         check_repo_lock(repo, kind, self.pushvars, ctx.metadata().identities()).await?;
+        check_freeze_windows(ctx, repo, self.bookmark, kind, self.pushvars).await?;
 
         if let Some(hook) = RepoLockPushrebaseHook::new(
             repo.repo_identity().id(),
@@ -187,6 +193,18 @@ impl<'op> PushrebaseOntoBookmarkOp<'op> {
             pushrebase_hooks.push(hook);
         }
 
+        // Likewise, freeze windows are checked again as part of the bookmark
+        // update transaction, in case one started during the pushrebase.
+        if let Some(hook) =
+            FreezeWindowPushrebaseHook::new(ctx, repo, self.bookmark, kind, self.pushvars).await
+        {
+            pushrebase_hooks.push(hook);
+        }
+
+        if let Some(hook) = RequiredDerivedDataPushrebaseHook::new(repo, self.bookmark) {
+            pushrebase_hooks.push(hook);
+        }
+
         let mut flags = repo.repo_config().pushrebase.flags.clone();
         if let Some(rewritedates) = repo
             .repo_bookmark_attrs()
@@ -232,6 +250,7 @@ impl<'op> PushrebaseOntoBookmarkOp<'op> {
                     reason,
                 };
                 log_bookmark_operation(ctx, repo, &info).await;
+                enqueue_derivation(ctx, repo, kind, outcome.head).await;
             }
             Err(err) => scuba_logger.log_with_msg("Pushrebase failed", Some(format!("{:#?}", err))),
         }
@@ -296,5 +315,14 @@ pub fn get_pushrebase_hooks(
         Some(hook) => pushrebase_hooks.push(hook),
         None => {}
     }
+
+    // Rewrite messages last, so that other hooks see the commits as pushed.
+    if let Some(hook) = CommitMessageRewritePushrebaseHook::new(
+        ctx,
+        &pushrebase_params.commit_message_rewrite_rules,
+        bookmark,
+    ) {
+        pushrebase_hooks.push(hook);
+    }
     Ok(pushrebase_hooks)
 }
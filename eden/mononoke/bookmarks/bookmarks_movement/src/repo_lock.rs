@@ -116,6 +116,10 @@ impl PushrebaseHook for RepoLockPushrebaseHook {
         });
         Ok(hook as Box<dyn PushrebaseCommitHook>)
     }
+
+    fn batchable(&self) -> bool {
+        true
+    }
 }
 
 struct RepoLockCommitTransactionHook {
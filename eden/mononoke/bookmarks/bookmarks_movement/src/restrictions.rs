@@ -5,19 +5,36 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blobrepo::AsBlobRepo;
+use blobrepo::BlobRepo;
 use bookmarks::BookmarkKind;
 use bookmarks::BookmarkName;
+use bookmarks::BookmarkTransactionError;
 use bookmarks::BookmarkUpdateReason;
 use context::CoreContext;
+use derived_data_utils::derived_data_utils;
 use futures::stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use metaconfig_types::RepoConfigRef;
+use mononoke_types::BonsaiChangesetMut;
 use mononoke_types::ChangesetId;
+use pushrebase_hook::PushrebaseCommitHook;
+use pushrebase_hook::PushrebaseHook;
+use pushrebase_hook::PushrebaseTransactionHook;
+use pushrebase_hook::RebasedChangesets;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_authorization::AuthorizationContext;
 use repo_cross_repo::RepoCrossRepoRef;
+use repo_derived_data::RepoDerivedDataArc;
 use repo_identity::RepoIdentityRef;
+use sql::Transaction;
 use tunables::tunables;
 
 use crate::BookmarkMovementError;
@@ -116,6 +133,179 @@ pub(crate) async fn check_restriction_ensure_ancestor_of(
     Ok(())
 }
 
+/// How long a bookmark move waits for the derived data that is required
+/// for the bookmark to be derived.
+const REQUIRED_DERIVED_DATA_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn required_derived_data(repo: &impl Repo, bookmark: &BookmarkName) -> BTreeSet<String> {
+    let mut required = BTreeSet::new();
+    for attr in repo.repo_bookmark_attrs().select(bookmark) {
+        required.extend(attr.params().required_derived_data.iter().cloned());
+    }
+    required
+}
+
+/// Derive the required derived data types for a changeset, waiting for up
+/// to `REQUIRED_DERIVED_DATA_TIMEOUT`.  Returns the types that haven't
+/// been derived in that time.
+async fn derive_required_derived_data(
+    ctx: &CoreContext,
+    blob_repo: &BlobRepo,
+    required: &BTreeSet<String>,
+    target: ChangesetId,
+) -> Result<Vec<&'static str>> {
+    stream::iter(required)
+        .map(|derived_data_type| async move {
+            let utils = derived_data_utils(ctx.fb, blob_repo, derived_data_type)?;
+            if utils.is_derived(ctx, target).await? {
+                return Ok::<_, anyhow::Error>(None);
+            }
+            let derivation = utils.derive(ctx.clone(), blob_repo.repo_derived_data_arc(), target);
+            match tokio::time::timeout(REQUIRED_DERIVED_DATA_TIMEOUT, derivation).await {
+                Ok(res) => {
+                    res?;
+                    Ok(None)
+                }
+                Err(_) => Ok(Some(utils.name())),
+            }
+        })
+        .buffered(10)
+        .try_filter_map(|derived_data_type| async move { Ok(derived_data_type) })
+        .try_collect()
+        .await
+}
+
+/// Check that the derived data that is required for the bookmark has been
+/// derived for the target, deriving it if it has not.
+///
+/// If derivation doesn't finish in time the move is rejected as pending.
+/// Derivation makes progress each time, so it can be retried.
+pub(crate) async fn check_restriction_required_derived_data(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    bookmark_to_move: &BookmarkName,
+    target: ChangesetId,
+) -> Result<(), BookmarkMovementError> {
+    let required = required_derived_data(repo, bookmark_to_move);
+    if required.is_empty() {
+        return Ok(());
+    }
+
+    let pending = derive_required_derived_data(ctx, repo.as_blob_repo(), &required, target).await?;
+    if !pending.is_empty() {
+        return Err(BookmarkMovementError::RequiredDerivedDataPending {
+            bookmark: bookmark_to_move.clone(),
+            target,
+            derived_data_types: pending.join(", "),
+        });
+    }
+
+    Ok(())
+}
+
+/// Pushrebase hook that derives the derived data that is required for the
+/// bookmark for the rebased commits before the bookmark is moved to them.
+pub(crate) struct RequiredDerivedDataPushrebaseHook {
+    blob_repo: BlobRepo,
+    bookmark: BookmarkName,
+    required: BTreeSet<String>,
+}
+
+impl RequiredDerivedDataPushrebaseHook {
+    pub(crate) fn new(
+        repo: &impl Repo,
+        bookmark: &BookmarkName,
+    ) -> Option<Box<dyn PushrebaseHook>> {
+        let required = required_derived_data(repo, bookmark);
+        if required.is_empty() {
+            return None;
+        }
+        Some(Box::new(RequiredDerivedDataPushrebaseHook {
+            blob_repo: repo.as_blob_repo().clone(),
+            bookmark: bookmark.clone(),
+            required,
+        }))
+    }
+}
+
+#[async_trait]
+impl PushrebaseHook for RequiredDerivedDataPushrebaseHook {
+    async fn prepushrebase(&self) -> Result<Box<dyn PushrebaseCommitHook>> {
+        let hook = Box::new(RequiredDerivedDataCommitHook {
+            blob_repo: self.blob_repo.clone(),
+            bookmark: self.bookmark.clone(),
+            required: self.required.clone(),
+            rebased_parents: HashSet::new(),
+        });
+        Ok(hook as Box<dyn PushrebaseCommitHook>)
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+}
+
+struct RequiredDerivedDataCommitHook {
+    blob_repo: BlobRepo,
+    bookmark: BookmarkName,
+    required: BTreeSet<String>,
+    /// Parents of the rebased commits, used to find the new head.
+    rebased_parents: HashSet<ChangesetId>,
+}
+
+#[async_trait]
+impl PushrebaseCommitHook for RequiredDerivedDataCommitHook {
+    fn post_rebase_changeset(
+        &mut self,
+        _bcs_old: ChangesetId,
+        bcs_new: &mut BonsaiChangesetMut,
+    ) -> Result<()> {
+        self.rebased_parents.extend(bcs_new.parents.iter().copied());
+        Ok(())
+    }
+
+    async fn into_transaction_hook(
+        self: Box<Self>,
+        ctx: &CoreContext,
+        rebased: &RebasedChangesets,
+    ) -> Result<Box<dyn PushrebaseTransactionHook>> {
+        // This runs before the bookmark is moved, so the move only happens
+        // once the data has been derived for all of the rebased commits.
+        for (new_cs_id, _) in rebased.values() {
+            if self.rebased_parents.contains(new_cs_id) {
+                continue;
+            }
+            let pending =
+                derive_required_derived_data(ctx, &self.blob_repo, &self.required, *new_cs_id)
+                    .await?;
+            if !pending.is_empty() {
+                return Err(BookmarkMovementError::RequiredDerivedDataPending {
+                    bookmark: self.bookmark.clone(),
+                    target: *new_cs_id,
+                    derived_data_types: pending.join(", "),
+                }
+                .into());
+            }
+        }
+        Ok(Box::new(RequiredDerivedDataTransactionHook) as Box<dyn PushrebaseTransactionHook>)
+    }
+}
+
+/// The derived data is written before the bookmark moves, so there is
+/// nothing to add to the transaction.
+struct RequiredDerivedDataTransactionHook;
+
+#[async_trait]
+impl PushrebaseTransactionHook for RequiredDerivedDataTransactionHook {
+    async fn populate_transaction(
+        &self,
+        _ctx: &CoreContext,
+        txn: Transaction,
+    ) -> Result<Transaction, BookmarkTransactionError> {
+        Ok(txn)
+    }
+}
+
 pub(crate) async fn ensure_ancestor_of(
     ctx: &CoreContext,
     repo: &impl Repo,
@@ -173,3 +363,114 @@ pub fn check_bookmark_sync_config(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use blobstore::Loadable;
+    use bookmarks::BookmarksRef;
+    use fbinit::FacebookInit;
+    use metaconfig_types::BookmarkParams;
+    use metaconfig_types::MergePolicy;
+    use metaconfig_types::PushrebaseFlags;
+    use mononoke_api_types::InnerRepo;
+    use repo_blobstore::RepoBlobstoreRef;
+    use test_repo_factory::TestRepoFactory;
+    use tests_utils::bookmark;
+    use tests_utils::drawdag::create_from_dag;
+
+    use super::*;
+
+    fn repo_requiring_fsnodes(fb: FacebookInit, bookmark: &str) -> Result<InnerRepo> {
+        let bookmark = BookmarkName::new(bookmark)?;
+        TestRepoFactory::new(fb)?
+            .with_config_override(|config| {
+                config.bookmarks = vec![BookmarkParams {
+                    bookmark: bookmark.into(),
+                    hooks: vec![],
+                    only_fast_forward: false,
+                    rewrite_dates: None,
+                    allowed_users: None,
+                    allowed_hipster_group: None,
+                    hooks_skip_ancestors_of: vec![],
+                    ensure_ancestor_of: None,
+                    allow_move_to_public_commits_without_hooks: false,
+                    required_derived_data: vec!["fsnodes".to_string()],
+                    merge_policy: MergePolicy::default(),
+                    publishing_delay: None,
+                }];
+            })
+            .build()
+    }
+
+    async fn is_derived(ctx: &CoreContext, repo: &InnerRepo, cs_id: ChangesetId) -> Result<bool> {
+        derived_data_utils(ctx.fb, repo.as_blob_repo(), "fsnodes")?
+            .is_derived(ctx, cs_id)
+            .await
+    }
+
+    #[fbinit::test]
+    async fn test_required_derived_data(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = repo_requiring_fsnodes(fb, "main")?;
+        let mapping = create_from_dag(&ctx, repo.as_blob_repo(), "A-B").await?;
+        let b = mapping["B"];
+
+        // Other bookmarks don't require anything.
+        let other = BookmarkName::new("other")?;
+        check_restriction_required_derived_data(&ctx, &repo, &other, b).await?;
+        assert!(!is_derived(&ctx, &repo, b).await?);
+
+        // Moving the bookmark derives the required data first.
+        let main = BookmarkName::new("main")?;
+        check_restriction_required_derived_data(&ctx, &repo, &main, b).await?;
+        assert!(is_derived(&ctx, &repo, b).await?);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_required_derived_data_pushrebase(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = repo_requiring_fsnodes(fb, "main")?;
+        let mapping = create_from_dag(
+            &ctx,
+            repo.as_blob_repo(),
+            r##"
+            A-B
+             \
+              C-D
+            "##,
+        )
+        .await?;
+        bookmark(&ctx, repo.as_blob_repo(), "main")
+            .set_to(mapping["B"])
+            .await?;
+
+        let main = BookmarkName::new("main")?;
+        let hook = RequiredDerivedDataPushrebaseHook::new(&repo, &main)
+            .expect("hook should be required for main");
+        let mut pushed = HashSet::new();
+        for name in ["C", "D"] {
+            pushed.insert(mapping[name].load(&ctx, repo.repo_blobstore()).await?);
+        }
+        let outcome = pushrebase::do_pushrebase_bonsai(
+            &ctx,
+            repo.as_blob_repo(),
+            &PushrebaseFlags::default(),
+            &main,
+            &pushed,
+            &[hook],
+        )
+        .await?;
+
+        // The bookmark only moved once the data was derived for the new
+        // head.
+        assert!(is_derived(&ctx, &repo, outcome.head).await?);
+        assert_eq!(
+            repo.bookmarks().get(ctx.clone(), &main).await?,
+            Some(outcome.head)
+        );
+
+        Ok(())
+    }
+}
@@ -29,6 +29,9 @@ use crate::affected_changesets::find_draft_ancestors;
 use crate::affected_changesets::log_new_bonsai_changesets;
 use crate::affected_changesets::AdditionalChangesets;
 use crate::affected_changesets::AffectedChangesets;
+use crate::derivation::enqueue_derivation;
+use crate::freeze_windows::check_freeze_windows;
+use crate::private_scratch::record_private_scratch_commits;
 use crate::repo_lock::check_repo_lock;
 use crate::restrictions::check_bookmark_sync_config;
 use crate::restrictions::BookmarkKindRestrictions;
@@ -55,7 +58,7 @@ pub enum BookmarkUpdatePolicy {
 }
 
 impl BookmarkUpdatePolicy {
-    async fn check_update_permitted(
+    pub(crate) async fn check_update_permitted(
         &self,
         ctx: &CoreContext,
         repo: &impl Repo,
@@ -218,6 +221,7 @@ impl<'op> UpdateBookmarkOp<'op> {
             .await?;
 
         check_repo_lock(repo, kind, self.pushvars, ctx.metadata().identities()).await?;
+        check_freeze_windows(ctx, repo, self.bookmark, kind, self.pushvars).await?;
 
         let mut txn = repo.bookmarks().create_transaction(ctx.clone());
         let txn_hook;
@@ -226,6 +230,8 @@ impl<'op> UpdateBookmarkOp<'op> {
             BookmarkKind::Scratch => {
                 txn_hook = None;
 
+                record_private_scratch_commits(ctx, repo, self.bookmark, self.targets.new).await?;
+
                 ctx.scuba()
                     .clone()
                     .add("bookmark", self.bookmark.to_string())
@@ -244,6 +250,14 @@ impl<'op> UpdateBookmarkOp<'op> {
                 )
                 .await?;
 
+                crate::restrictions::check_restriction_required_derived_data(
+                    ctx,
+                    repo,
+                    self.bookmark,
+                    self.targets.new,
+                )
+                .await?;
+
                 let txn_hook_fut = crate::git_mapping::populate_git_mapping_txn_hook(
                     ctx,
                     repo,
@@ -305,6 +319,7 @@ impl<'op> UpdateBookmarkOp<'op> {
             reason: self.reason,
         };
         log_bookmark_operation(ctx, repo, &info).await;
+        enqueue_derivation(ctx, repo, kind, self.targets.new).await;
 
         Ok(())
     }
@@ -9,7 +9,10 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::ops::Deref;
 use std::ops::RangeBounds;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::Duration;
@@ -84,10 +87,73 @@ define_stats! {
 }
 
 pub struct WarmBookmarksCache {
-    bookmarks: Arc<RwLock<HashMap<BookmarkName, (ChangesetId, BookmarkKind)>>>,
+    bookmarks: Arc<RwLock<CachedBookmarks>>,
+    running_updaters: Arc<AtomicUsize>,
     terminate: Option<oneshot::Sender<()>>,
 }
 
+/// The bookmarks held by a warm bookmarks cache, along with the approximate
+/// memory they use, which is kept up to date as they change so that it can
+/// be reported cheaply.
+#[derive(Clone, Default)]
+struct CachedBookmarks {
+    bookmarks: HashMap<BookmarkName, (ChangesetId, BookmarkKind)>,
+    bytes: usize,
+}
+
+impl CachedBookmarks {
+    fn entry_bytes(name: &BookmarkName) -> usize {
+        name.as_str().len() + std::mem::size_of::<(BookmarkName, (ChangesetId, BookmarkKind))>()
+    }
+
+    fn insert(
+        &mut self,
+        name: BookmarkName,
+        value: (ChangesetId, BookmarkKind),
+    ) -> Option<(ChangesetId, BookmarkKind)> {
+        let bytes = Self::entry_bytes(&name);
+        let old = self.bookmarks.insert(name, value);
+        if old.is_none() {
+            self.bytes += bytes;
+        }
+        old
+    }
+
+    fn remove(&mut self, name: &BookmarkName) -> Option<(ChangesetId, BookmarkKind)> {
+        let old = self.bookmarks.remove(name);
+        if old.is_some() {
+            self.bytes -= Self::entry_bytes(name);
+        }
+        old
+    }
+}
+
+impl From<HashMap<BookmarkName, (ChangesetId, BookmarkKind)>> for CachedBookmarks {
+    fn from(bookmarks: HashMap<BookmarkName, (ChangesetId, BookmarkKind)>) -> Self {
+        let bytes = bookmarks.keys().map(Self::entry_bytes).sum();
+        Self { bookmarks, bytes }
+    }
+}
+
+impl Deref for CachedBookmarks {
+    type Target = HashMap<BookmarkName, (ChangesetId, BookmarkKind)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bookmarks
+    }
+}
+
+/// Resources held by a bookmarks cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BookmarksCacheUsage {
+    /// Number of bookmarks held in the cache.
+    pub entries: usize,
+    /// Approximate memory used by the cached bookmarks.
+    pub bytes: usize,
+    /// Number of background tasks keeping the cache up to date.
+    pub background_tasks: usize,
+}
+
 pub type WarmerFn =
     dyn for<'a> Fn(&'a CoreContext, ChangesetId) -> BoxFuture<'a, Result<(), Error>> + Send + Sync;
 
@@ -301,6 +367,12 @@ pub trait BookmarksCache: Send + Sync {
         pagination: &BookmarkPagination,
         limit: Option<u64>,
     ) -> Result<Vec<(BookmarkName, (ChangesetId, BookmarkKind))>, Error>;
+
+    /// Resources held by the cache.  Caches that don't hold anything report
+    /// no usage.
+    fn usage(&self) -> BookmarksCacheUsage {
+        BookmarksCacheUsage::default()
+    }
 }
 
 /// A drop-in replacement for warm bookmark cache that doesn't
@@ -375,20 +447,22 @@ impl WarmBookmarksCache {
         )
         .await?;
 
-        let bookmarks_to_watch = Arc::new(RwLock::new(bookmarks_to_watch));
+        let bookmarks_to_watch = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks_to_watch)));
 
-        BookmarksCoordinator::new(
+        let coordinator = BookmarksCoordinator::new(
             bookmarks_to_watch.clone(),
             sub,
             bookmarks.clone(),
             bookmark_update_log.clone(),
             repo_identity.clone(),
             warmers.clone(),
-        )
-        .spawn(ctx.clone(), receiver);
+        );
+        let running_updaters = coordinator.running_updaters.clone();
+        coordinator.spawn(ctx.clone(), receiver);
 
         Ok(Self {
             bookmarks: bookmarks_to_watch,
+            running_updaters,
             terminate: Some(sender),
         })
     }
@@ -444,6 +518,19 @@ impl BookmarksCache for WarmBookmarksCache {
             Ok(matches)
         }
     }
+
+    fn usage(&self) -> BookmarksCacheUsage {
+        let (entries, bytes) = self
+            .bookmarks
+            .with_read(|bookmarks| (bookmarks.len(), bookmarks.bytes));
+        BookmarksCacheUsage {
+            entries,
+            bytes,
+            // The coordinator, plus an updater for each bookmark that is
+            // being warmed.
+            background_tasks: 1 + self.running_updaters.load(Ordering::Relaxed),
+        }
+    }
 }
 
 impl Drop for WarmBookmarksCache {
@@ -695,16 +782,18 @@ struct BookmarksCoordinatorRepo {
 }
 
 struct BookmarksCoordinator {
-    bookmarks: Arc<RwLock<HashMap<BookmarkName, (ChangesetId, BookmarkKind)>>>,
+    bookmarks: Arc<RwLock<CachedBookmarks>>,
     sub: Box<dyn BookmarksSubscription>,
     repo: BookmarksCoordinatorRepo,
     warmers: Arc<Vec<Warmer>>,
     live_updaters: Arc<RwLock<HashMap<BookmarkName, BookmarkUpdaterState>>>,
+    /// Number of bookmark updaters that haven't finished yet.
+    running_updaters: Arc<AtomicUsize>,
 }
 
 impl BookmarksCoordinator {
     fn new(
-        bookmarks: Arc<RwLock<HashMap<BookmarkName, (ChangesetId, BookmarkKind)>>>,
+        bookmarks: Arc<RwLock<CachedBookmarks>>,
         sub: Box<dyn BookmarksSubscription>,
         bookmarks_fetcher: ArcBookmarks,
         bookmark_update_log: ArcBookmarkUpdateLog,
@@ -723,6 +812,7 @@ impl BookmarksCoordinator {
             repo,
             warmers,
             live_updaters: Arc::new(RwLock::new(HashMap::new())),
+            running_updaters: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -734,7 +824,9 @@ impl BookmarksCoordinator {
             self.repo.repo_identity().name(),
         );
 
-        let cur_bookmarks = self.bookmarks.with_read(|bookmarks| bookmarks.clone());
+        let cur_bookmarks = self
+            .bookmarks
+            .with_read(|bookmarks| HashMap::clone(bookmarks));
 
         let new_bookmarks = if tunables().get_warm_bookmark_cache_disable_subscription() {
             let books = self
@@ -811,8 +903,10 @@ impl BookmarksCoordinator {
                     self.repo,
                     self.bookmarks,
                     self.live_updaters,
+                    self.running_updaters,
                     self.warmers,
                 );
+                running_updaters.fetch_add(1, Ordering::Relaxed);
                 let _ = tokio::spawn(async move {
                     let res = single_bookmark_updater(
                         &ctx,
@@ -843,6 +937,7 @@ impl BookmarksCoordinator {
                             live_updaters.insert(book.name().clone(), state.into_finished(&res));
                         }
                     });
+                    running_updaters.fetch_sub(1, Ordering::Relaxed);
                 });
             }
         }
@@ -985,7 +1080,7 @@ async fn single_bookmark_updater(
     ctx: &CoreContext,
     repo: &(impl BookmarksRef + BookmarkUpdateLogRef),
     bookmark: &Bookmark,
-    bookmarks: &Arc<RwLock<HashMap<BookmarkName, (ChangesetId, BookmarkKind)>>>,
+    bookmarks: &Arc<RwLock<CachedBookmarks>>,
     warmers: &Arc<Vec<Warmer>>,
     mut staleness_reporter: impl FnMut(Timestamp),
 ) -> Result<(), Error> {
@@ -1087,6 +1182,8 @@ mod tests {
     use memblob::Memblob;
     use mononoke_api_types::InnerRepo;
     use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
     use repo_derived_data::RepoDerivedDataArc;
     use repo_identity::RepoIdentityArc;
     use sql_ext::mononoke_queries;
@@ -1098,6 +1195,29 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_cached_bookmarks_usage() -> Result<(), Error> {
+        let main = BookmarkName::new("main")?;
+        let release = BookmarkName::new("release")?;
+        let value = (ONES_CSID, BookmarkKind::Publishing);
+
+        let mut bookmarks = CachedBookmarks::from(hashmap! {main.clone() => value});
+        assert_eq!(bookmarks.bytes, CachedBookmarks::entry_bytes(&main));
+
+        bookmarks.insert(release.clone(), value);
+        bookmarks.insert(release.clone(), (TWOS_CSID, BookmarkKind::Publishing));
+        assert_eq!(
+            bookmarks.bytes,
+            CachedBookmarks::entry_bytes(&main) + CachedBookmarks::entry_bytes(&release)
+        );
+
+        bookmarks.remove(&main);
+        bookmarks.remove(&main);
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks.bytes, CachedBookmarks::entry_bytes(&release));
+        Ok(())
+    }
+
     #[fbinit::test]
     async fn test_simple(fb: FacebookInit) -> Result<(), Error> {
         let repo = Linear::get_inner_repo(fb).await;
@@ -1353,7 +1473,7 @@ mod tests {
             })
             .try_collect::<HashMap<_, _>>()
             .await?;
-        let bookmarks = Arc::new(RwLock::new(bookmarks));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks)));
 
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(create_derived_data_warmer::<RootUnodeManifestId>(
@@ -1412,7 +1532,7 @@ mod tests {
             })
             .try_collect::<HashMap<_, _>>()
             .await?;
-        let bookmarks = Arc::new(RwLock::new(bookmarks));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks)));
 
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(create_derived_data_warmer::<RootUnodeManifestId>(
@@ -1506,7 +1626,7 @@ mod tests {
             })
             .try_collect::<HashMap<_, _>>()
             .await?;
-        let bookmarks = Arc::new(RwLock::new(bookmarks));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks)));
 
         let failing_cs_id = CreateCommitContext::new(&ctx, &repo.blob_repo, vec!["master"])
             .add_file("failed", "failed")
@@ -1681,7 +1801,7 @@ mod tests {
             })
             .try_collect::<HashMap<_, _>>()
             .await?;
-        let bookmarks = Arc::new(RwLock::new(bookmarks));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks)));
 
         let master = CreateCommitContext::new(&ctx, &repo.blob_repo, vec!["master"])
             .add_file("somefile", "content")
@@ -1741,7 +1861,7 @@ mod tests {
             .try_collect::<HashMap<_, _>>()
             .await?;
 
-        let bookmarks = Arc::new(RwLock::new(bookmarks));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::from(bookmarks)));
 
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(create_derived_data_warmer::<RootUnodeManifestId>(
@@ -1812,7 +1932,7 @@ mod tests {
         Linear::initrepo(fb, &repo.blob_repo).await;
         let ctx = CoreContext::test_mock(fb);
 
-        let bookmarks = Arc::new(RwLock::new(HashMap::new()));
+        let bookmarks = Arc::new(RwLock::new(CachedBookmarks::default()));
 
         let mut warmers: Vec<Warmer> = Vec::new();
         warmers.push(create_derived_data_warmer::<RootUnodeManifestId>(
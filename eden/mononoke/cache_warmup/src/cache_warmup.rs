@@ -5,12 +5,18 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
+
 use anyhow::Context;
 use anyhow::Error;
 use blobrepo::BlobRepo;
 use blobrepo_hg::BlobRepoHg;
 use blobstore::Loadable;
+use bookmarks::BookmarkKind;
 use bookmarks::BookmarkName;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::Freshness;
 use cloned::cloned;
 use context::CoreContext;
 use context::PerfCounterType;
@@ -20,6 +26,7 @@ use filenodes::FilenodeResult;
 use futures::compat::Stream01CompatExt;
 use futures::future;
 use futures::future::TryFutureExt;
+use futures::stream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures_stats::TimedFutureExt;
@@ -60,6 +67,7 @@ pub struct CacheWarmupRequest {
     pub target: CacheWarmupTarget,
     pub commit_limit: usize,
     pub microwave_preload: bool,
+    pub published_heads: bool,
 }
 
 impl From<CacheWarmupParams> for CacheWarmupRequest {
@@ -68,12 +76,14 @@ impl From<CacheWarmupParams> for CacheWarmupRequest {
             bookmark,
             commit_limit,
             microwave_preload,
+            published_heads,
         } = other;
 
         Self {
             target: CacheWarmupTarget::Bookmark(bookmark),
             commit_limit,
             microwave_preload,
+            published_heads,
         }
     }
 }
@@ -186,11 +196,71 @@ async fn changesets_warmup(
     Ok(())
 }
 
+// Fetch the tip of each publishing bookmark, its entry in the changesets
+// table and its root manifest, so the first requests for any published head
+// don't have to go to the backing stores.  Heads whose hg changeset can't be
+// derived are skipped, rather than failing the whole warmup.  Returns the
+// number of heads that were warmed up.
+async fn published_heads_warmup(ctx: &CoreContext, repo: &BlobRepo) -> Result<usize, Error> {
+    info!(ctx.logger(), "about to start warming up published heads");
+
+    let heads: HashSet<ChangesetId> = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MaybeStale,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            u64::MAX,
+        )
+        .map_ok(|(_, cs_id)| cs_id)
+        .try_collect()
+        .await?;
+
+    let changeset_fetcher = repo.get_changeset_fetcher();
+    let count = heads.len();
+    let warmed = stream::iter(heads)
+        .map(|cs_id| {
+            cloned!(ctx, repo, changeset_fetcher);
+            async move {
+                changeset_fetcher
+                    .get_generation_number(ctx.clone(), cs_id)
+                    .await?;
+                changeset_fetcher.get_parents(ctx.clone(), cs_id).await?;
+                let hg_cs_id = match repo.derive_hg_changeset(&ctx, cs_id).await {
+                    Ok(hg_cs_id) => hg_cs_id,
+                    Err(err) => {
+                        warn!(
+                            ctx.logger(),
+                            "skipping warmup of published head {}: {:#}", cs_id, err
+                        );
+                        return Ok(false);
+                    }
+                };
+                let hg_cs = hg_cs_id.load(&ctx, repo.blobstore()).await?;
+                hg_cs.manifestid().load(&ctx, repo.blobstore()).await?;
+                Ok::<_, Error>(true)
+            }
+        })
+        .buffer_unordered(100)
+        .try_fold(0, |warmed, done| future::ok(warmed + done as usize))
+        .await?;
+
+    debug!(
+        ctx.logger(),
+        "finished warming up {} of {} published heads", warmed, count
+    );
+
+    Ok(warmed)
+}
+
 async fn do_cache_warmup(
     ctx: &CoreContext,
     repo: &BlobRepo,
     target: CacheWarmupTarget,
     commit_limit: usize,
+    published_heads: bool,
 ) -> Result<(), Error> {
     let ctx = ctx.clone_and_reset();
 
@@ -223,10 +293,25 @@ async fn do_cache_warmup(
         }
     });
 
-    let (stats, res) = future::try_join(blobstore_warmup, cs_warmup).timed().await;
-    let (blobstore_warmup, cs_warmup) = res?;
+    let heads_warmup = task::spawn({
+        cloned!(ctx, repo);
+        async move {
+            if published_heads {
+                published_heads_warmup(&ctx, &repo)
+                    .await
+                    .context("While warming up published heads")?;
+            }
+            Ok::<_, Error>(())
+        }
+    });
+
+    let (stats, res) = future::try_join3(blobstore_warmup, cs_warmup, heads_warmup)
+        .timed()
+        .await;
+    let (blobstore_warmup, cs_warmup, heads_warmup) = res?;
     blobstore_warmup?;
     cs_warmup?;
+    heads_warmup?;
 
     info!(ctx.logger(), "finished initial warmup");
 
@@ -251,7 +336,8 @@ async fn microwave_preload(ctx: &CoreContext, repo: &BlobRepo, req: &CacheWarmup
 }
 
 /// Fetch all manifest entries for a bookmark, and fetches up to `commit_warmup_limit`
-/// ancestors of the bookmark.
+/// ancestors of the bookmark.  If requested, also warms up the tips of all
+/// publishing bookmarks.
 pub async fn cache_warmup<T: Into<CacheWarmupRequest>>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -262,10 +348,54 @@ pub async fn cache_warmup<T: Into<CacheWarmupRequest>>(
 
         microwave_preload(ctx, repo, &req).await;
 
-        do_cache_warmup(ctx, repo, req.target, req.commit_limit)
+        do_cache_warmup(ctx, repo, req.target, req.commit_limit, req.published_heads)
             .await
             .with_context(|| format!("while warming up repo {}", repo.get_repoid()))?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use derived_data_manager::BonsaiDerivable;
+    use fbinit::FacebookInit;
+    use mercurial_derived_data::MappedHgChangesetId;
+    use test_repo_factory::TestRepoFactory;
+    use tests_utils::bookmark;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_published_heads_warmup(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut factory = TestRepoFactory::new(fb)?;
+        let repo: BlobRepo = factory.build()?;
+
+        let root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let head = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo, "main").set_to(head).await?;
+        bookmark(&ctx, &repo, "release").set_to(root).await?;
+
+        assert_eq!(published_heads_warmup(&ctx, &repo).await?, 2);
+
+        // A repo that can't derive hg changesets skips the heads instead of
+        // failing.
+        let repo: BlobRepo = factory
+            .with_config_override(|config| {
+                for types_config in config.derived_data_config.available_configs.values_mut() {
+                    types_config.types.remove(MappedHgChangesetId::NAME);
+                }
+            })
+            .build()?;
+        assert_eq!(published_heads_warmup(&ctx, &repo).await?, 0);
+        Ok(())
+    }
+}
@@ -8,13 +8,18 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Error;
 use anyhow::Result;
 use clap::Args;
 use fbinit::FacebookInit;
 use observability::ObservabilityContext;
+use scuba_ext::JsonlFileSink;
 use scuba_ext::MononokeScubaSampleBuilder;
+use scuba_ext::ScubaSampleSink;
+use scuba_ext::StdoutSink;
 use tunables::tunables;
 
 /// Command line arguments that control scuba logging
@@ -31,6 +36,16 @@ pub struct ScubaLoggingArgs {
     /// use warm bookmark cache then this parameter is ignored
     #[clap(long)]
     pub warm_bookmark_cache_scuba_dataset: Option<String>,
+    /// Also write scuba samples as JSON Lines to this file, or to stdout
+    /// if this is `-`.  This works whether or not scuba is available
+    #[clap(long)]
+    pub scuba_sink_path: Option<PathBuf>,
+    /// Rotate the scuba sample file once it reaches this size in bytes
+    #[clap(long, default_value_t = 100 * 1024 * 1024)]
+    pub scuba_sink_max_size: u64,
+    /// Number of rotated scuba sample files to keep
+    #[clap(long, default_value_t = 5)]
+    pub scuba_sink_max_files: usize,
 }
 
 impl ScubaLoggingArgs {
@@ -51,6 +66,17 @@ impl ScubaLoggingArgs {
         } else {
             MononokeScubaSampleBuilder::with_discard()
         };
+        let scuba_logger = match self.create_scuba_sink()? {
+            Some(sink) => {
+                let dataset = self
+                    .scuba_dataset
+                    .as_deref()
+                    .or(default_scuba_set.as_deref())
+                    .unwrap_or("mononoke");
+                scuba_logger.with_sink(dataset, sink)
+            }
+            None => scuba_logger,
+        };
         let mut scuba_logger = scuba_logger
             .with_observability_context(observability_context.clone())
             .with_seq("seq");
@@ -60,6 +86,18 @@ impl ScubaLoggingArgs {
         Ok(scuba_logger)
     }
 
+    fn create_scuba_sink(&self) -> Result<Option<Arc<dyn ScubaSampleSink>>> {
+        Ok(match &self.scuba_sink_path {
+            Some(path) if path.as_os_str() == "-" => Some(Arc::new(StdoutSink)),
+            Some(path) => Some(Arc::new(JsonlFileSink::new(
+                path,
+                self.scuba_sink_max_size,
+                self.scuba_sink_max_files,
+            )?)),
+            None => None,
+        })
+    }
+
     pub fn create_warm_bookmark_cache_scuba_sample_builder(
         &self,
         fb: FacebookInit,
@@ -35,6 +35,13 @@ impl RepoArgs {
         }
     }
 
+    pub fn from_repo_name(repo_name: String) -> Self {
+        RepoArgs {
+            repo_id: None,
+            repo_name: Some(repo_name),
+        }
+    }
+
     pub fn id_or_name(&self) -> Result<RepoArg> {
         match self {
             RepoArgs {
@@ -518,7 +518,9 @@ fn create_blobstore_options(
         cachelib_blobstore_options,
         blobstore_put_behaviour,
         mysql_sqlblob_options,
-    );
+    )
+    .with_retry_policy(blobstore_args.retry_policy())
+    .with_circuit_breakers(blobstore_args.circuit_breakers()?);
 
     Ok(blobstore_options)
 }
@@ -30,9 +30,9 @@ use context::CoreContext;
 use fbinit::FacebookInit;
 use futures::future::try_join;
 use git_types::Tree as GitTree;
+use mercurial_types::blobs::manifest_shards::decode_manifest_envelope;
 use mercurial_types::HgChangesetEnvelope;
 use mercurial_types::HgFileEnvelope;
-use mercurial_types::HgManifestEnvelope;
 use metaconfig_types::BlobConfig;
 use metaconfig_types::BlobstoreId;
 use metaconfig_types::Redaction;
@@ -243,13 +243,15 @@ pub async fn subcommand_blobstore_fetch<'a>(
         try_join(blobstore_fut, maybe_redacted_blobs_fut).await?;
 
     info!(logger, "using blobstore: {:?}", blobstore);
+    // The shards of sharded manifests are stored under the repo's prefix.
+    let repo_blobstore = PrefixBlobstore::new(blobstore.clone(), repo_id.prefix());
     let value = get_from_sources(
         fb,
         use_memcache,
         blobstore,
         no_prefix,
         key.clone(),
-        ctx,
+        ctx.clone(),
         maybe_redacted_blobs,
         scuba_redaction_builder,
         repo_id,
@@ -269,7 +271,11 @@ pub async fn subcommand_blobstore_fetch<'a>(
 
         match decode_as {
             Some("changeset") => display(&HgChangesetEnvelope::from_blob(value.into())),
-            Some("manifest") => display(&HgManifestEnvelope::from_blob(value.into())),
+            // Manifests of wide directories are stored in shards, which
+            // must be fetched to get the manifest's contents.
+            Some("manifest") => {
+                display(&decode_manifest_envelope(&ctx, &repo_blobstore, value.into()).await)
+            }
             Some("file") => display(&HgFileEnvelope::from_blob(value.into())),
             // TODO: (rain1) T30974137 add a better way to print out file contents
             Some("contents") => println!(
@@ -0,0 +1,144 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Error;
+use blobstore_factory::make_metadata_sql_factory;
+use blobstore_factory::ReadOnlyStorage;
+use clap_old::App;
+use clap_old::Arg;
+use clap_old::ArgMatches;
+use clap_old::SubCommand;
+use cmdlib::args;
+use cmdlib::args::MononokeMatches;
+use context::CoreContext;
+use context::SessionClass;
+use fbinit::FacebookInit;
+use repo_blobstore::RepoBlobstore;
+use repo_identity::RepoIdentity;
+use segmented_changelog::CompactionOptions;
+use segmented_changelog::SegmentedChangelogCompactor;
+use segmented_changelog::SegmentedChangelogSqlConnections;
+use slog::info;
+use slog::Logger;
+
+use crate::error::SubcommandError;
+
+pub const COMPACT_SEGMENTED_CHANGELOG: &str = "compact-segmented-changelog";
+const ARG_BATCH_SIZE: &str = "batch-size";
+const ARG_BATCH_DELAY_MS: &str = "batch-delay-ms";
+const ARG_GRACE_PERIOD_SECS: &str = "grace-period-secs";
+const ARG_DRY_RUN: &str = "dry-run";
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(COMPACT_SEGMENTED_CHANGELOG)
+        .about(
+            "removes superseded segmented changelog versions and merges small segments. \
+            Safe to run while the tailer is running.",
+        )
+        .arg(
+            Arg::with_name(ARG_BATCH_SIZE)
+                .long(ARG_BATCH_SIZE)
+                .help("number of idmap rows to delete in a single query")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name(ARG_BATCH_DELAY_MS)
+                .long(ARG_BATCH_DELAY_MS)
+                .help("milliseconds to wait between deletions")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name(ARG_GRACE_PERIOD_SECS)
+                .long(ARG_GRACE_PERIOD_SECS)
+                .help("seconds to keep an idmap version after it is first found superseded")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name(ARG_DRY_RUN)
+                .long(ARG_DRY_RUN)
+                .help("only report what would be compacted")
+                .takes_value(false)
+                .required(false),
+        )
+}
+
+pub async fn subcommand_compact_segmented_changelog<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a MononokeMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    let mut options = CompactionOptions::default();
+    if let Some(batch_size) = sub_m.value_of(ARG_BATCH_SIZE) {
+        options.batch_size = batch_size.parse::<u64>().map_err(Error::from)?;
+    }
+    if let Some(batch_delay_ms) = sub_m.value_of(ARG_BATCH_DELAY_MS) {
+        options.batch_delay =
+            Duration::from_millis(batch_delay_ms.parse::<u64>().map_err(Error::from)?);
+    }
+    if let Some(grace_period_secs) = sub_m.value_of(ARG_GRACE_PERIOD_SECS) {
+        options.grace_period =
+            Duration::from_secs(grace_period_secs.parse::<u64>().map_err(Error::from)?);
+    }
+    options.dry_run = sub_m.is_present(ARG_DRY_RUN);
+
+    let mut ctx = CoreContext::new_with_logger(fb, logger.clone());
+    ctx.session_mut()
+        .override_session_class(SessionClass::Background);
+
+    #[facet::container]
+    struct CompactSegmentedChangelogContainer {
+        #[facet]
+        id: RepoIdentity,
+        #[facet]
+        blobstore: RepoBlobstore,
+    }
+    let container: CompactSegmentedChangelogContainer =
+        args::not_shardmanager_compatible::open_repo(fb, &logger, matches).await?;
+
+    let config_store = matches.config_store();
+    let mysql_options = matches.mysql_options();
+    let (_, config) = args::not_shardmanager_compatible::get_config(config_store, matches)?;
+    let sql_factory = make_metadata_sql_factory(
+        ctx.fb,
+        config.storage_config.metadata,
+        mysql_options.clone(),
+        ReadOnlyStorage(false),
+    )
+    .await
+    .context("constructing metadata sql factory")?;
+    let segmented_changelog_sql_connections = sql_factory
+        .open::<SegmentedChangelogSqlConnections>()
+        .context("error opening segmented changelog sql connections")?;
+
+    let compactor = SegmentedChangelogCompactor::new(
+        container.id.id(),
+        segmented_changelog_sql_connections.0,
+        container.blobstore,
+    );
+    let stats = compactor
+        .compact(&ctx, &options)
+        .await
+        .context("While compacting")?;
+    info!(
+        logger,
+        "removed {} superseded idmap versions ({} rows), kept {} in their grace period, \
+        iddag has {} flat segments (was {})",
+        stats.superseded_idmap_versions.len(),
+        stats.idmap_rows_deleted,
+        stats.retained_idmap_versions.len(),
+        stats.flat_segments_after,
+        stats.flat_segments_before,
+    );
+    Ok(())
+}
@@ -38,6 +38,7 @@ mod blobstore_upload;
 mod bonsai_fetch;
 mod bookmarks_manager;
 mod common;
+mod compact_segmented_changelog;
 mod content_fetch;
 mod crossrepo;
 mod derived_data;
@@ -87,6 +88,7 @@ fn setup_app<'a, 'b>() -> MononokeClapApp<'a, 'b> {
         .subcommand(rsync::build_subcommand())
         .subcommand(subcommand_skeleton_manifests::build_subcommand())
         .subcommand(truncate_segmented_changelog::build_subcommand())
+        .subcommand(compact_segmented_changelog::build_subcommand())
 }
 
 #[fbinit::main]
@@ -181,6 +183,12 @@ fn main(fb: FacebookInit) -> ExitCode {
                 )
                 .await
             }
+            (compact_segmented_changelog::COMPACT_SEGMENTED_CHANGELOG, Some(sub_m)) => {
+                compact_segmented_changelog::subcommand_compact_segmented_changelog(
+                    fb, logger, &matches, sub_m,
+                )
+                .await
+            }
             _ => Err(SubcommandError::InvalidArgs),
         }
     });
@@ -93,6 +93,7 @@ use tunables::tunables;
 use wait_for_replication::WaitForReplication;
 
 mod commit_discovery;
+mod queue;
 mod regenerate;
 mod slice;
 mod validation;
@@ -125,10 +126,12 @@ const ARG_GAP_SIZE: &str = "gap-size";
 const ARG_JSON: &str = "json";
 const ARG_VALIDATE_CHUNK_SIZE: &str = "validate-chunk-size";
 const ARG_BACKFILL_CONFIG_NAME: &str = "backfill-config-name";
+const ARG_REQUEUE_DEAD_LETTERS: &str = "requeue-dead-letters";
 
 const SUBCOMMAND_BACKFILL: &str = "backfill";
 const SUBCOMMAND_BACKFILL_ALL: &str = "backfill-all";
 const SUBCOMMAND_BENCHMARK: &str = "benchmark";
+const SUBCOMMAND_PROCESS_QUEUE: &str = "process-queue";
 const SUBCOMMAND_TAIL: &str = "tail";
 const SUBCOMMAND_SINGLE: &str = "single";
 const SUBCOMMAND_VALIDATE: &str = "validate";
@@ -336,6 +339,26 @@ impl DerivedDataProcess {
                             .takes_value(true),
                     ),
             )
+            .subcommand(
+                SubCommand::with_name(SUBCOMMAND_PROCESS_QUEUE)
+                    .about("derive data for landed pushes from the repo's derivation queue")
+                    .arg(
+                        Arg::with_name(ARG_STOP_ON_IDLE)
+                            .long(ARG_STOP_ON_IDLE)
+                            .help("Stop processing the queue when it is empty"),
+                    )
+                    .arg(
+                        Arg::with_name(ARG_BATCH_SIZE)
+                            .long(ARG_BATCH_SIZE)
+                            .default_value(DEFAULT_BATCH_SIZE_STR)
+                            .help("number of tasks to claim and derive at once"),
+                    )
+                    .arg(
+                        Arg::with_name(ARG_REQUEUE_DEAD_LETTERS)
+                            .long(ARG_REQUEUE_DEAD_LETTERS)
+                            .help("move tasks in the dead letter queue back to the queue first"),
+                    ),
+            )
             .subcommand(
                 SubCommand::with_name(SUBCOMMAND_SINGLE)
                     .about("backfill single changeset (mainly for performance testing purposes)")
@@ -869,6 +892,29 @@ async fn run_subcmd<'a>(
             )
             .await
         }
+        (SUBCOMMAND_PROCESS_QUEUE, Some(sub_m)) => {
+            let stop_on_idle = sub_m.is_present(ARG_STOP_ON_IDLE);
+            let requeue_dead_letters = sub_m.is_present(ARG_REQUEUE_DEAD_LETTERS);
+            let batch_size = sub_m
+                .value_of(ARG_BATCH_SIZE)
+                .expect("batch-size must be set")
+                .parse::<u64>()?;
+            let types = args::get_config_by_name(config_store, matches, repo_name.clone())?
+                .derived_data_config
+                .queue
+                .map_or_else(Vec::new, |queue| queue.types);
+            let repo: InnerRepo =
+                open_repo_maybe_unredacted(fb, logger, matches, &types, repo_name).await?;
+            queue::subcommand_process_queue(
+                ctx,
+                &repo,
+                batch_size,
+                stop_on_idle,
+                requeue_dead_letters,
+                cancellation_requested,
+            )
+            .await
+        }
         (SUBCOMMAND_SINGLE, Some(sub_m)) => {
             let hash_or_bookmark = sub_m
                 .value_of_lossy(ARG_CHANGESET)
@@ -0,0 +1,209 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Worker for the derivation queue.  Landed pushes enqueue derivation tasks
+//! rather than waiting for derivation, and this worker derives them.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use context::CoreContext;
+use derivation_queue::DerivationTask;
+use derivation_queue::FailedTask;
+use derived_data_utils::derived_data_utils;
+use futures::stream;
+use futures::stream::StreamExt;
+use mononoke_api_types::InnerRepo;
+use repo_derived_data::RepoDerivedDataArc;
+use slog::error;
+use slog::info;
+use slog::warn;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.derived_data.queue";
+    derived: dynamic_timeseries("{}.derived", (reponame: String); Rate, Sum),
+    retried: dynamic_timeseries("{}.retried", (reponame: String); Rate, Sum),
+    dead_lettered: dynamic_timeseries("{}.dead_lettered", (reponame: String); Rate, Sum),
+    queued_time_ms: dynamic_timeseries("{}.queued_time_ms", (reponame: String); Average, Sum),
+}
+
+/// How long a worker holds the tasks it claims for.  Tasks that aren't
+/// completed in this time, e.g. because the worker was restarted, become
+/// available to other workers again.
+const QUEUE_LEASE: Duration = Duration::from_secs(600);
+
+/// How long to wait before polling the queue again when it is empty.
+const IDLE_SLEEP: Duration = Duration::from_secs(1);
+
+/// Maximum number of dead-lettered tasks to report on startup.
+const DEAD_LETTER_REPORT_LIMIT: u64 = 100;
+
+/// Claim and derive tasks from the repo's derivation queue until cancelled,
+/// or until the queue is empty if `stop_on_idle` is set.
+pub(crate) async fn subcommand_process_queue(
+    ctx: &CoreContext,
+    repo: &InnerRepo,
+    batch_size: u64,
+    stop_on_idle: bool,
+    requeue_dead_letters: bool,
+    cancellation_requested: Arc<AtomicBool>,
+) -> Result<()> {
+    let repo_name = repo.blob_repo.name();
+    let max_attempts = repo
+        .repo_config
+        .derived_data_config
+        .queue
+        .as_ref()
+        .ok_or_else(|| anyhow!("The derivation queue is not configured for {}", repo_name))?
+        .max_attempts;
+
+    if requeue_dead_letters {
+        let requeued = repo.derivation_queue.requeue_dead_letters(ctx).await?;
+        info!(
+            ctx.logger(),
+            "[{}] requeued {} dead-lettered tasks", repo_name, requeued
+        );
+    }
+    for task in repo
+        .derivation_queue
+        .dead_letters(ctx, DEAD_LETTER_REPORT_LIMIT)
+        .await?
+    {
+        warn!(
+            ctx.logger(),
+            "[{}] dead-lettered: {} for {} after {} attempts: {}",
+            repo_name,
+            task.derived_data_type,
+            task.cs_id,
+            task.attempts,
+            task.last_error.as_deref().unwrap_or("unknown error"),
+        );
+    }
+
+    loop {
+        if cancellation_requested.load(Ordering::Relaxed) {
+            info!(
+                ctx.logger(),
+                "process-queue stopping due to cancellation request"
+            );
+            return Ok(());
+        }
+        let tasks = repo
+            .derivation_queue
+            .claim(ctx, batch_size, QUEUE_LEASE)
+            .await?;
+        if tasks.is_empty() {
+            if stop_on_idle {
+                info!(ctx.logger(), "process-queue stopping due to --stop-on-idle");
+                return Ok(());
+            }
+            tokio::time::sleep(IDLE_SLEEP).await;
+            continue;
+        }
+        stream::iter(tasks)
+            .map(|task| process_task(ctx, repo, task, max_attempts))
+            .buffer_unordered(batch_size as usize)
+            .collect::<()>()
+            .await;
+    }
+}
+
+async fn process_task(
+    ctx: &CoreContext,
+    repo: &InnerRepo,
+    task: DerivationTask,
+    max_attempts: u32,
+) {
+    let repo_name = repo.blob_repo.name().to_string();
+    let result = async {
+        let utils = derived_data_utils(ctx.fb, &repo.blob_repo, &task.derived_data_type)?;
+        utils
+            .derive(
+                ctx.clone(),
+                repo.blob_repo.repo_derived_data_arc(),
+                task.cs_id,
+            )
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(_) => {
+            // If the task can't be marked as completed, its lease expires
+            // and it is derived again, which is a no-op.
+            if let Err(err) = repo.derivation_queue.complete(ctx, &task).await {
+                error!(
+                    ctx.logger(),
+                    "[{}] failed to complete task for {} of {}: {:#}",
+                    repo_name,
+                    task.derived_data_type,
+                    task.cs_id,
+                    err,
+                );
+                return;
+            }
+            STATS::derived.add_value(1, (repo_name.clone(),));
+            STATS::queued_time_ms.add_value(task.enqueued_at.since_millis(), (repo_name,));
+        }
+        Err(err) => {
+            let err = format!("{:#}", err);
+            // If the failure can't be recorded, the task's lease expires
+            // and it is retried.
+            let failed = match repo
+                .derivation_queue
+                .fail(ctx, &task, &err, max_attempts)
+                .await
+            {
+                Ok(failed) => failed,
+                Err(fail_err) => {
+                    error!(
+                        ctx.logger(),
+                        "[{}] failed to record failure to derive {} for {}: {:#} (derivation error: {})",
+                        repo_name,
+                        task.derived_data_type,
+                        task.cs_id,
+                        fail_err,
+                        err,
+                    );
+                    return;
+                }
+            };
+            match failed {
+                FailedTask::Retry { delay } => {
+                    STATS::retried.add_value(1, (repo_name.clone(),));
+                    warn!(
+                        ctx.logger(),
+                        "[{}] failed to derive {} for {} (attempt {}), retrying in {:?}: {}",
+                        repo_name,
+                        task.derived_data_type,
+                        task.cs_id,
+                        task.attempts,
+                        delay,
+                        err,
+                    );
+                }
+                FailedTask::DeadLettered => {
+                    STATS::dead_lettered.add_value(1, (repo_name.clone(),));
+                    error!(
+                        ctx.logger(),
+                        "[{}] failed to derive {} for {} after {} attempts, giving up: {}",
+                        repo_name,
+                        task.derived_data_type,
+                        task.cs_id,
+                        task.attempts,
+                        err,
+                    );
+                }
+            }
+        }
+    }
+}
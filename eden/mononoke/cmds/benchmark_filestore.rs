@@ -132,6 +132,7 @@ async fn run_benchmark_filestore<'a>(
     let config = FilestoreConfig {
         chunk_size: Some(chunk_size),
         concurrency,
+        content_defined_chunking: false,
     };
 
     eprintln!("Test with {:?}, writing into {:?}", config, blob);
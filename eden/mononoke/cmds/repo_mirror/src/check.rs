@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::Freshness;
+use context::CoreContext;
+use futures::try_join;
+use futures::TryStreamExt;
+use mercurial_types::HgChangesetId;
+use mononoke_types::ChangesetId;
+
+/// A difference between the primary and the follower found by
+/// `check_consistency`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    MissingInFollower {
+        bookmark: BookmarkName,
+        primary: ChangesetId,
+    },
+    MissingInPrimary {
+        bookmark: BookmarkName,
+        follower: ChangesetId,
+    },
+    Diverged {
+        bookmark: BookmarkName,
+        primary: ChangesetId,
+        follower: ChangesetId,
+    },
+    HgMismatch {
+        cs_id: ChangesetId,
+        primary: HgChangesetId,
+        follower: Option<HgChangesetId>,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingInFollower { bookmark, primary } => write!(
+                f,
+                "{} points to {} in primary but is missing in follower",
+                bookmark, primary
+            ),
+            Self::MissingInPrimary { bookmark, follower } => write!(
+                f,
+                "{} points to {} in follower but is missing in primary",
+                bookmark, follower
+            ),
+            Self::Diverged {
+                bookmark,
+                primary,
+                follower,
+            } => write!(
+                f,
+                "{} points to {} in primary but to {} in follower",
+                bookmark, primary, follower
+            ),
+            Self::HgMismatch {
+                cs_id,
+                primary,
+                follower,
+            } => write!(
+                f,
+                "{} maps to hg changeset {} in primary but to {:?} in follower",
+                cs_id, primary, follower
+            ),
+        }
+    }
+}
+
+async fn list_publishing_bookmarks(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+) -> Result<BTreeMap<BookmarkName, ChangesetId>, Error> {
+    repo.bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            std::u64::MAX,
+        )
+        .map_ok(|(bookmark, cs_id)| (bookmark.name, cs_id))
+        .try_collect()
+        .await
+}
+
+/// Compare the publishing bookmarks of the primary and the follower, and the
+/// hg changesets that the bookmarked commits map to.
+///
+/// Any bookmark moves that the follower hasn't replicated yet show up as
+/// inconsistencies, so this is only meaningful when the follower has caught
+/// up with the primary.
+pub async fn check_consistency(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+) -> Result<Vec<Inconsistency>, Error> {
+    let (primary_bookmarks, follower_bookmarks) = try_join!(
+        list_publishing_bookmarks(ctx, primary),
+        list_publishing_bookmarks(ctx, follower),
+    )?;
+
+    let mut inconsistencies = vec![];
+    let mut to_compare = BTreeSet::new();
+    for (bookmark, primary_cs_id) in &primary_bookmarks {
+        match follower_bookmarks.get(bookmark) {
+            None => inconsistencies.push(Inconsistency::MissingInFollower {
+                bookmark: bookmark.clone(),
+                primary: *primary_cs_id,
+            }),
+            Some(follower_cs_id) if follower_cs_id != primary_cs_id => {
+                inconsistencies.push(Inconsistency::Diverged {
+                    bookmark: bookmark.clone(),
+                    primary: *primary_cs_id,
+                    follower: *follower_cs_id,
+                })
+            }
+            Some(_) => {
+                to_compare.insert(*primary_cs_id);
+            }
+        }
+    }
+    for (bookmark, follower_cs_id) in &follower_bookmarks {
+        if !primary_bookmarks.contains_key(bookmark) {
+            inconsistencies.push(Inconsistency::MissingInPrimary {
+                bookmark: bookmark.clone(),
+                follower: *follower_cs_id,
+            });
+        }
+    }
+
+    for cs_id in to_compare {
+        let (primary_hg_cs_id, follower_hg_cs_id) = try_join!(
+            primary.bonsai_hg_mapping().get_hg_from_bonsai(ctx, cs_id),
+            follower.bonsai_hg_mapping().get_hg_from_bonsai(ctx, cs_id),
+        )?;
+        // The follower derives hg changesets as it replicates, so it may
+        // have a mapping that the primary hasn't derived yet.
+        if let Some(primary_hg_cs_id) = primary_hg_cs_id {
+            if follower_hg_cs_id != Some(primary_hg_cs_id) {
+                inconsistencies.push(Inconsistency::HgMismatch {
+                    cs_id,
+                    primary: primary_hg_cs_id,
+                    follower: follower_hg_cs_id,
+                });
+            }
+        }
+    }
+
+    Ok(inconsistencies)
+}
@@ -0,0 +1,226 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! This tool replicates a primary repo into a follower repo, so that the
+//! follower can serve reads from its own storage, e.g. as a geo replica.
+//!
+//! The source repo is the primary and the target repo is the follower.
+//! "tail" follows the bookmark update log of the primary and applies every
+//! bookmark move to the follower, copying the commits and file contents that
+//! the follower is missing. The position in the log is stored in a mutable
+//! counter of the follower, and the replication lag is exported as stats.
+//! "check" compares the bookmarks of both repos and reports any difference.
+//!
+//! The follower must be configured as read-only, so that it isn't written
+//! to by anything else, and it is checked before it is opened.
+//!
+//! LIMITATIONS:
+//! 1) Only bonsai changesets, file contents and hg changesets are replicated;
+//!    other derived data is derived in the follower on demand
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use clap_old::Arg;
+use clap_old::ArgMatches;
+use clap_old::SubCommand;
+use cmdlib::args;
+use cmdlib::args::MononokeMatches;
+use cmdlib::helpers;
+use cmdlib::monitoring::AliveService;
+use context::CoreContext;
+use context::SessionContainer;
+use fbinit::FacebookInit;
+use futures::future::try_join;
+use metaconfig_types::RepoReadOnly;
+use slog::debug;
+use slog::info;
+use slog::warn;
+
+use crate::check::check_consistency;
+use crate::replicate::calculate_lag;
+use crate::replicate::log_lag;
+use crate::replicate::replicate_once;
+
+mod check;
+mod replicate;
+
+const APP_NAME: &str = "repo mirror";
+const ARG_BATCH_SIZE: &str = "batch-size";
+const ARG_ONCE: &str = "once";
+const ARG_SLEEP_SECS: &str = "sleep-secs";
+const DEFAULT_BATCH_SIZE: u64 = 100;
+const DEFAULT_SLEEP_SECS: u64 = 1;
+const SUBCOMMAND_CHECK: &str = "check";
+const SUBCOMMAND_TAIL: &str = "tail";
+
+async fn open_repos<'a>(
+    ctx: &CoreContext,
+    matches: &'a MononokeMatches<'_>,
+) -> Result<(BlobRepo, BlobRepo), Error> {
+    let config_store = matches.config_store();
+    let primary_repo_id =
+        args::not_shardmanager_compatible::get_source_repo_id(config_store, matches)?;
+    let follower_repo_id =
+        args::not_shardmanager_compatible::get_target_repo_id(config_store, matches)?;
+    if primary_repo_id == follower_repo_id {
+        return Err(anyhow!("primary and follower must be different repos"));
+    }
+    let (follower_repo_name, follower_config) =
+        args::get_config_by_repoid(config_store, matches, follower_repo_id)?;
+    if follower_config.readonly == RepoReadOnly::ReadWrite {
+        return Err(anyhow!(
+            "follower {} must be configured as read-only",
+            follower_repo_name
+        ));
+    }
+
+    try_join(
+        args::open_repo_with_repo_id(ctx.fb, ctx.logger(), primary_repo_id, matches),
+        args::open_repo_with_repo_id(ctx.fb, ctx.logger(), follower_repo_id, matches),
+    )
+    .await
+}
+
+async fn subcommand_tail<'a>(
+    ctx: &CoreContext,
+    matches: &'a MononokeMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), Error> {
+    let (primary, follower) = open_repos(ctx, matches).await?;
+    let batch_size = args::get_u64(sub_m, ARG_BATCH_SIZE, DEFAULT_BATCH_SIZE);
+    let sleep = Duration::from_secs(args::get_u64(sub_m, ARG_SLEEP_SECS, DEFAULT_SLEEP_SECS));
+
+    info!(
+        ctx.logger(),
+        "mirroring {} into {}",
+        primary.name(),
+        follower.name()
+    );
+
+    loop {
+        let lag = calculate_lag(ctx, &primary, &follower).await?;
+        log_lag(ctx, &lag, &primary, &follower);
+
+        if lag.remaining_entries == 0 {
+            debug!(ctx.logger(), "no entries remained");
+            if sub_m.is_present(ARG_ONCE) {
+                break Ok(());
+            }
+            tokio::time::sleep(sleep).await;
+            continue;
+        }
+
+        let applied = replicate_once(ctx, &primary, &follower, batch_size).await?;
+        debug!(ctx.logger(), "replicated {} entries", applied);
+    }
+}
+
+async fn subcommand_check<'a>(
+    ctx: &CoreContext,
+    matches: &'a MononokeMatches<'_>,
+) -> Result<(), Error> {
+    let (primary, follower) = open_repos(ctx, matches).await?;
+
+    let lag = calculate_lag(ctx, &primary, &follower).await?;
+    if lag.remaining_entries > 0 {
+        warn!(
+            ctx.logger(),
+            "follower is {} entries ({}s) behind, recent bookmark moves will show up as inconsistencies",
+            lag.remaining_entries,
+            lag.delay_secs
+        );
+    }
+
+    let inconsistencies = check_consistency(ctx, &primary, &follower).await?;
+    for inconsistency in &inconsistencies {
+        warn!(ctx.logger(), "{}", inconsistency);
+    }
+    if inconsistencies.is_empty() {
+        info!(
+            ctx.logger(),
+            "{} is consistent with {}",
+            follower.name(),
+            primary.name()
+        );
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "found {} inconsistencies between {} and {}",
+            inconsistencies.len(),
+            primary.name(),
+            follower.name()
+        ))
+    }
+}
+
+async fn run<'a>(ctx: &CoreContext, matches: &'a MononokeMatches<'_>) -> Result<(), Error> {
+    match matches.subcommand() {
+        (SUBCOMMAND_TAIL, Some(sub_m)) => subcommand_tail(ctx, matches, sub_m).await,
+        (SUBCOMMAND_CHECK, Some(_)) => subcommand_check(ctx, matches).await,
+        (subcommand, _) => Err(anyhow!("unknown subcommand {}!", subcommand)),
+    }
+}
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let matches = args::MononokeAppBuilder::new(APP_NAME)
+        .with_advanced_args_hidden()
+        .with_fb303_args()
+        .with_scuba_logging_args()
+        .with_source_and_target_repos()
+        .build()
+        .about(
+            "Replicates a primary repo (the source repo) into a read-only follower \
+        repo (the target repo).",
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_TAIL)
+                .about("Tail the bookmark update log of the primary and apply it to the follower")
+                .arg(
+                    Arg::with_name(ARG_BATCH_SIZE)
+                        .long(ARG_BATCH_SIZE)
+                        .required(false)
+                        .takes_value(true)
+                        .help("How many bookmark update log entries to apply at once"),
+                )
+                .arg(
+                    Arg::with_name(ARG_SLEEP_SECS)
+                        .long(ARG_SLEEP_SECS)
+                        .required(false)
+                        .takes_value(true)
+                        .help("How long to wait before polling the primary again once caught up"),
+                )
+                .arg(
+                    Arg::with_name(ARG_ONCE)
+                        .long(ARG_ONCE)
+                        .required(false)
+                        .takes_value(false)
+                        .help("Exit once the follower has caught up"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_CHECK)
+                .about("Check that the bookmarks of the follower match the primary"),
+        )
+        .get_matches(fb)?;
+
+    let logger = matches.logger();
+    let scuba = matches.scuba_sample_builder();
+    let ctx = SessionContainer::new_with_defaults(fb).new_context(logger.clone(), scuba);
+
+    helpers::block_execute(
+        run(&ctx, &matches),
+        fb,
+        APP_NAME,
+        logger,
+        &matches,
+        AliveService,
+    )
+}
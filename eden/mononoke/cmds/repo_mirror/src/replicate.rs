@@ -0,0 +1,768 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::format_err;
+use anyhow::Error;
+use blobrepo::save_bonsai_changesets;
+use blobrepo::BlobRepo;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingEntry;
+use bookmarks::BookmarkTransactionError;
+use bookmarks::BookmarkUpdateLogEntry;
+use bookmarks::Freshness;
+use bounded_traversal::bounded_traversal;
+use changesets::ChangesetEntry;
+use changesets::ChangesetsRef;
+use commit_transformation::copy_file_contents;
+use context::CoreContext;
+use futures::stream;
+use futures::try_join;
+use futures::FutureExt;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use manifest::Entry;
+use manifest::Manifest;
+use mercurial_derived_data::DeriveHgChangeset;
+use mercurial_types::blobs::HgBlobChangeset;
+use mercurial_types::HgChangesetId;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::FileChange;
+use mononoke_types::RepositoryId;
+use mutable_counters::MutableCountersRef;
+use mutable_counters::SqlMutableCounters;
+use repo_blobstore::RepoBlobstore;
+use slog::debug;
+use slog::info;
+use sql::Transaction;
+use sql_ext::TransactionResult;
+use stats::prelude::*;
+
+const CHUNK_SIZE: usize = 100;
+
+/// Maximum number of missing commits that are looked for at a time.
+const MAX_WALK_SIZE: usize = 10_000;
+
+define_stats! {
+    prefix = "mononoke.repo_mirror";
+    replicated_entries: dynamic_timeseries("{}.replicated_entries", (follower_repo_name: String); Rate, Sum),
+    replicated_commits: dynamic_timeseries("{}.replicated_commits", (follower_repo_name: String); Rate, Sum),
+    remaining_entries: dynamic_singleton_counter(
+        "{}.{}.remaining_entries",
+        (primary_repo_name: String, follower_repo_name: String)
+    ),
+    delay_secs: dynamic_singleton_counter(
+        "{}.{}.delay_secs",
+        (primary_repo_name: String, follower_repo_name: String)
+    ),
+}
+
+/// Name of the mutable counter, stored in the follower repo, that tracks the
+/// id of the latest bookmark update log entry of the primary repo that was
+/// applied to the follower.
+pub fn format_counter(primary_repo_id: &RepositoryId) -> String {
+    format!("mirror_from_{}", primary_repo_id.id())
+}
+
+/// How far behind the primary the follower is.
+pub struct Lag {
+    pub delay_secs: i64,
+    pub remaining_entries: u64,
+}
+
+pub async fn get_counter(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+) -> Result<Option<i64>, Error> {
+    follower
+        .mutable_counters()
+        .get_counter(ctx, &format_counter(&primary.get_repoid()))
+        .await
+}
+
+pub async fn calculate_lag(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+) -> Result<Lag, Error> {
+    let counter = get_counter(ctx, primary, follower).await?.unwrap_or(0);
+    let next_entry = primary
+        .bookmark_update_log()
+        .read_next_bookmark_log_entries(ctx.clone(), counter as u64, 1, Freshness::MostRecent)
+        .try_collect::<Vec<_>>();
+    let remaining_entries = primary
+        .bookmark_update_log()
+        .count_further_bookmark_log_entries(ctx.clone(), counter as u64, None);
+
+    let (next_entry, remaining_entries) = try_join!(next_entry, remaining_entries)?;
+    let delay_secs = next_entry
+        .get(0)
+        .map_or(0, |entry| entry.timestamp.since_seconds());
+
+    Ok(Lag {
+        delay_secs,
+        remaining_entries,
+    })
+}
+
+pub fn log_lag(ctx: &CoreContext, lag: &Lag, primary: &BlobRepo, follower: &BlobRepo) {
+    STATS::remaining_entries.set_value(
+        ctx.fb,
+        lag.remaining_entries as i64,
+        (primary.name().clone(), follower.name().clone()),
+    );
+    STATS::delay_secs.set_value(
+        ctx.fb,
+        lag.delay_secs,
+        (primary.name().clone(), follower.name().clone()),
+    );
+}
+
+/// Apply at most `limit` new bookmark update log entries of the primary repo
+/// to the follower repo. Returns the number of entries that were applied.
+///
+/// For every entry, the commits that the bookmark moves to and that the
+/// follower doesn't have yet are verified and copied over together with their
+/// file contents, hg changesets and hg mapping entries. The bookmark move and
+/// the counter update are then committed in a single transaction, so a crash
+/// at any point leaves the follower at a consistent position from which
+/// replication can resume.
+pub async fn replicate_once(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+    limit: u64,
+) -> Result<usize, Error> {
+    let mut prev_counter = get_counter(ctx, primary, follower).await?;
+    let entries: Vec<_> = primary
+        .bookmark_update_log()
+        .read_next_bookmark_log_entries(
+            ctx.clone(),
+            prev_counter.unwrap_or(0) as u64,
+            limit,
+            Freshness::MostRecent,
+        )
+        .try_collect()
+        .await?;
+
+    let mut applied = 0;
+    for entry in entries {
+        let counter = prev_counter.unwrap_or(0);
+        if counter >= entry.id {
+            continue;
+        }
+        debug!(ctx.logger(), "replicating entry {} ...", entry.id);
+
+        let mut scuba_sample = ctx.scuba().clone();
+        scuba_sample.add("mirror_bookmark_log_entry_id", entry.id);
+        let start_instant = Instant::now();
+
+        let mut copied = 0;
+        if let Some(to_cs_id) = entry.to_changeset_id {
+            copied = copy_changesets(ctx, primary, follower, to_cs_id, MAX_WALK_SIZE).await?;
+        }
+
+        let new_counter = entry.id;
+        let success =
+            apply_entry(ctx, primary.get_repoid(), follower, prev_counter, &entry).await?;
+
+        scuba_sample.add(
+            "mirror_duration_ms",
+            u64::try_from(start_instant.elapsed().as_millis()).unwrap_or(u64::max_value()),
+        );
+        scuba_sample.add("mirror_copied_commits", copied);
+        scuba_sample.add("mirror_previously_done", !success);
+        scuba_sample.log_with_msg("Mirroring", None);
+
+        if success {
+            prev_counter = Some(new_counter);
+        } else {
+            // Another replicator may have applied this entry concurrently.
+            // That's fine as long as it moved the counter forward.
+            let latest_counter = get_counter(ctx, primary, follower).await?.unwrap_or(0);
+            if latest_counter <= counter {
+                return Err(format_err!(
+                    "mirror transaction failed, but the counter didn't move forward. Was {}, became {}",
+                    counter,
+                    latest_counter,
+                ));
+            }
+            debug!(
+                ctx.logger(),
+                "entry {} was already replicated by another process", entry.id
+            );
+            prev_counter = Some(latest_counter);
+        }
+
+        applied += 1;
+        STATS::replicated_entries.add_value(1, (follower.name().clone(),));
+        STATS::replicated_commits.add_value(copied as i64, (follower.name().clone(),));
+    }
+
+    Ok(applied)
+}
+
+/// Copy `head` and all of its ancestors that are missing in the follower.
+/// Returns the number of copied commits.
+///
+/// The missing commits are found by walking the primary's commit graph,
+/// about `max_walk_size` commits at a time.  If there are more, e.g. when the
+/// follower is bootstrapped, the oldest commit found so far is copied first,
+/// together with its ancestors, and the walk is started again.
+async fn copy_changesets(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+    head: ChangesetId,
+    max_walk_size: usize,
+) -> Result<usize, Error> {
+    let mut copied = 0;
+    let mut heads = vec![head];
+    while let Some(head) = heads.last().copied() {
+        match find_missing_changesets(ctx, primary, follower, head, max_walk_size).await? {
+            MissingChangesets::Complete(missing) => {
+                if !missing.is_empty() {
+                    info!(
+                        ctx.logger(),
+                        "copying {} commits to {} for {}",
+                        missing.len(),
+                        follower.name(),
+                        head
+                    );
+                }
+                for chunk in missing.chunks(CHUNK_SIZE) {
+                    copy_chunk(ctx, primary, follower, chunk).await?;
+                }
+                copied += missing.len();
+                heads.pop();
+            }
+            MissingChangesets::Truncated(oldest) => heads.push(oldest),
+        }
+    }
+    Ok(copied)
+}
+
+enum MissingChangesets {
+    /// All of the missing ancestors, sorted so that parents come before
+    /// their children.
+    Complete(Vec<ChangesetEntry>),
+    /// There are too many missing ancestors, and this is the oldest one that
+    /// was found.
+    Truncated(ChangesetId),
+}
+
+async fn find_missing_changesets(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+    head: ChangesetId,
+    max_walk_size: usize,
+) -> Result<MissingChangesets, Error> {
+    let mut missing = HashMap::new();
+    let mut queue = vec![head];
+    while !queue.is_empty() {
+        // Anything found besides `head` is older than it, so walking again
+        // from the oldest commit makes progress.
+        if missing.len() > 1 && missing.len() >= max_walk_size {
+            let oldest = missing
+                .values()
+                .min_by_key(|entry| entry.gen)
+                .map(|entry| entry.cs_id)
+                .expect("walk is not empty");
+            return Ok(MissingChangesets::Truncated(oldest));
+        }
+        let batch = queue
+            .split_off(queue.len().saturating_sub(CHUNK_SIZE))
+            .into_iter()
+            .filter(|cs_id| !missing.contains_key(cs_id))
+            .collect::<Vec<_>>();
+        let present = follower
+            .changesets()
+            .get_many(ctx.clone(), batch.clone())
+            .await?
+            .into_iter()
+            .map(|entry| entry.cs_id)
+            .collect::<HashSet<_>>();
+        let batch = batch
+            .into_iter()
+            .filter(|cs_id| !present.contains(cs_id))
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            continue;
+        }
+        let entries = primary.changesets().get_many(ctx.clone(), batch).await?;
+        for entry in entries {
+            queue.extend(entry.parents.iter().copied());
+            missing.insert(entry.cs_id, entry);
+        }
+    }
+
+    // Parents always have a lower generation number than their children.
+    let mut missing = missing.into_values().collect::<Vec<_>>();
+    missing.sort_by_key(|entry| entry.gen);
+    Ok(MissingChangesets::Complete(missing))
+}
+
+/// Copy a chunk of commits whose parents are either in the follower or
+/// earlier in the chunk.  Every commit is verified against its id and the
+/// primary's hg mapping before anything is written.  The hg changesets are
+/// copied from the primary together with their mapping entries, and the
+/// commits are only added to the follower's changesets once everything they
+/// refer to is there, so that a commit that exists in the follower is
+/// complete.
+async fn copy_chunk(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+    chunk: &[ChangesetEntry],
+) -> Result<(), Error> {
+    let primary_blobstore = primary.get_blobstore();
+
+    let bcss = stream::iter(chunk.iter().map(|entry| {
+        let primary_blobstore = &primary_blobstore;
+        async move { Ok::<_, Error>(entry.cs_id.load(ctx, primary_blobstore).await?) }
+    }))
+    .buffered(CHUNK_SIZE)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let hg_cs_ids = get_hg_changesets(ctx, primary, bcss.iter()).await?;
+    for (entry, bcs) in chunk.iter().zip(bcss.iter()) {
+        verify_changeset(ctx, primary, entry, bcs, &hg_cs_ids).await?;
+    }
+
+    let contents = bcss
+        .iter()
+        .flat_map(|bcs| bcs.file_changes())
+        .filter_map(|(_, change)| match change {
+            FileChange::Change(tc) => Some(tc.content_id()),
+            FileChange::UntrackedChange(uc) => Some(uc.content_id()),
+            FileChange::Deletion | FileChange::UntrackedDeletion => None,
+        })
+        .collect::<Vec<_>>();
+    copy_file_contents(ctx, primary, follower, contents, |_| {}).await?;
+
+    for bcs in bcss.iter() {
+        let hg_cs_id = hg_cs_ids[&bcs.get_changeset_id()];
+        copy_hg_changeset(ctx, primary, follower, hg_cs_id).await?;
+        follower
+            .bonsai_hg_mapping()
+            .add(
+                ctx,
+                BonsaiHgMappingEntry {
+                    hg_cs_id,
+                    bcs_id: bcs.get_changeset_id(),
+                },
+            )
+            .await?;
+    }
+
+    save_bonsai_changesets(bcss, ctx.clone(), follower).await?;
+    Ok(())
+}
+
+/// The primary's hg changesets of the given commits and their parents.
+/// Commits that don't have one yet are derived in the primary, as they would
+/// be the first time they're read from it.
+async fn get_hg_changesets<'a>(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    bcss: impl Iterator<Item = &'a BonsaiChangeset>,
+) -> Result<HashMap<ChangesetId, HgChangesetId>, Error> {
+    let cs_ids = bcss
+        .flat_map(|bcs| iter::once(bcs.get_changeset_id()).chain(bcs.parents()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut hg_cs_ids = primary
+        .bonsai_hg_mapping()
+        .get(ctx, cs_ids.clone().into())
+        .await?
+        .into_iter()
+        .map(|entry| (entry.bcs_id, entry.hg_cs_id))
+        .collect::<HashMap<_, _>>();
+    for cs_id in cs_ids {
+        if !hg_cs_ids.contains_key(&cs_id) {
+            let hg_cs_id = primary.derive_hg_changeset(ctx, cs_id).await?;
+            hg_cs_ids.insert(cs_id, hg_cs_id);
+        }
+    }
+    Ok(hg_cs_ids)
+}
+
+/// Check that a commit loaded from the primary hashes to its id, and that
+/// its hg changeset hashes to the id in the primary's mapping and has the hg
+/// changesets of the commit's parents as parents.
+async fn verify_changeset(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    entry: &ChangesetEntry,
+    bcs: &BonsaiChangeset,
+    hg_cs_ids: &HashMap<ChangesetId, HgChangesetId>,
+) -> Result<(), Error> {
+    let cs_id = entry.cs_id;
+    let computed_cs_id = bcs.clone().into_mut().freeze()?.get_changeset_id();
+    if computed_cs_id != cs_id {
+        return Err(format_err!(
+            "commit {} in {} hashes to {}",
+            cs_id,
+            primary.name(),
+            computed_cs_id
+        ));
+    }
+    if bcs.parents().collect::<Vec<_>>() != entry.parents {
+        return Err(format_err!(
+            "parents of commit {} in {} don't match its changesets entry",
+            cs_id,
+            primary.name()
+        ));
+    }
+
+    let hg_cs_id = hg_cs_ids[&cs_id];
+    let hg_cs = HgBlobChangeset::load(ctx, &primary.get_blobstore(), hg_cs_id)
+        .await?
+        .ok_or_else(|| format_err!("hg changeset {} is missing in {}", hg_cs_id, primary.name()))?;
+    let computed_hg_cs_id = hg_cs.compute_hash()?;
+    if computed_hg_cs_id != hg_cs_id {
+        return Err(format_err!(
+            "hg changeset {} of {} in {} hashes to {}",
+            hg_cs_id,
+            cs_id,
+            primary.name(),
+            computed_hg_cs_id
+        ));
+    }
+    let hg_parents = bcs
+        .parents()
+        .map(|parent| hg_cs_ids[&parent].into_nodehash())
+        .collect::<Vec<_>>();
+    if (hg_cs.p1(), hg_cs.p2()) != (hg_parents.get(0).copied(), hg_parents.get(1).copied()) {
+        return Err(format_err!(
+            "parents of hg changeset {} of {} in {} don't match the commit's parents",
+            hg_cs_id,
+            cs_id,
+            primary.name()
+        ));
+    }
+    Ok(())
+}
+
+/// Copy the blobs of an hg changeset that the follower doesn't have yet.
+/// Manifests are only written once everything below them is written, so a
+/// manifest that is already in the follower doesn't need to be visited.
+async fn copy_hg_changeset(
+    ctx: &CoreContext,
+    primary: &BlobRepo,
+    follower: &BlobRepo,
+    hg_cs_id: HgChangesetId,
+) -> Result<(), Error> {
+    let primary_blobstore = &primary.get_blobstore();
+    let follower_blobstore = &follower.get_blobstore();
+    let hg_cs = HgBlobChangeset::load(ctx, primary_blobstore, hg_cs_id)
+        .await?
+        .ok_or_else(|| format_err!("hg changeset {} is missing in {}", hg_cs_id, primary.name()))?;
+
+    bounded_traversal(
+        256,
+        Entry::Tree(hg_cs.manifestid()),
+        |entry| {
+            async move {
+                let key = match entry {
+                    Entry::Tree(manifest_id) => manifest_id.blobstore_key(),
+                    Entry::Leaf((_, filenode_id)) => filenode_id.blobstore_key(),
+                };
+                if follower_blobstore
+                    .is_present(ctx, &key)
+                    .await?
+                    .assume_not_found_if_unsure()
+                {
+                    return Ok((None, Vec::new()));
+                }
+                let children = match entry {
+                    Entry::Tree(manifest_id) => manifest_id
+                        .load(ctx, primary_blobstore)
+                        .await?
+                        .list()
+                        .map(|(_, entry)| entry)
+                        .collect(),
+                    Entry::Leaf(_) => Vec::new(),
+                };
+                Ok::<_, Error>((Some(key), children))
+            }
+            .boxed()
+        },
+        |key, _children| {
+            async move {
+                if let Some(key) = key {
+                    copy_blob(ctx, primary_blobstore, follower_blobstore, key).await?;
+                }
+                Ok::<_, Error>(())
+            }
+            .boxed()
+        },
+    )
+    .await?;
+
+    copy_blob(
+        ctx,
+        primary_blobstore,
+        follower_blobstore,
+        hg_cs_id.blobstore_key(),
+    )
+    .await
+}
+
+async fn copy_blob(
+    ctx: &CoreContext,
+    primary_blobstore: &RepoBlobstore,
+    follower_blobstore: &RepoBlobstore,
+    key: String,
+) -> Result<(), Error> {
+    let blob = primary_blobstore
+        .get(ctx, &key)
+        .await?
+        .ok_or_else(|| format_err!("blob {} is missing in primary", key))?;
+    follower_blobstore.put(ctx, key, blob.into_bytes()).await
+}
+
+async fn apply_entry(
+    ctx: &CoreContext,
+    primary_repo_id: RepositoryId,
+    follower: &BlobRepo,
+    prev_counter: Option<i64>,
+    entry: &BookmarkUpdateLogEntry,
+) -> Result<bool, Error> {
+    let follower_repo_id = follower.get_repoid();
+    let new_counter = entry.id;
+
+    let txn_hook = Arc::new({
+        move |ctx: CoreContext, txn: Transaction| {
+            async move {
+                // This relies on the mutable counters being stored in the
+                // same db as the bookmarks, like the backsyncer does.
+                let txn_result = SqlMutableCounters::set_counter_on_txn(
+                    &ctx,
+                    follower_repo_id,
+                    &format_counter(&primary_repo_id),
+                    new_counter,
+                    prev_counter,
+                    txn,
+                )
+                .await?;
+
+                match txn_result {
+                    TransactionResult::Succeeded(txn) => Ok(txn),
+                    TransactionResult::Failed => Err(BookmarkTransactionError::LogicError),
+                }
+            }
+            .boxed()
+        }
+    });
+
+    // The follower is read-only, so it mirrors whatever the primary did
+    // regardless of where its bookmark currently points.
+    let mut txn = follower.bookmarks().create_transaction(ctx.clone());
+    match entry.to_changeset_id {
+        Some(to_cs_id) => {
+            debug!(
+                ctx.logger(),
+                "setting {} to {} in {}",
+                entry.bookmark_name,
+                to_cs_id,
+                follower.name()
+            );
+            txn.force_set(&entry.bookmark_name, to_cs_id, entry.reason)?;
+        }
+        None => {
+            debug!(
+                ctx.logger(),
+                "deleting {} in {}",
+                entry.bookmark_name,
+                follower.name()
+            );
+            txn.force_delete(&entry.bookmark_name, entry.reason)?;
+        }
+    }
+
+    txn.commit_with_hook(txn_hook).await
+}
+
+#[cfg(test)]
+mod tests {
+    use bookmarks::BookmarkName;
+    use fbinit::FacebookInit;
+    use mononoke_types::RepositoryId;
+    use test_repo_factory::TestRepoFactory;
+    use tests_utils::bookmark;
+    use tests_utils::list_working_copy_utf8;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+    use crate::check::check_consistency;
+
+    fn build_repos(fb: FacebookInit) -> Result<(BlobRepo, BlobRepo), Error> {
+        let mut factory = TestRepoFactory::new(fb)?;
+        let primary = factory
+            .with_id(RepositoryId::new(0))
+            .with_name("primary")
+            .build()?;
+        let follower = factory
+            .with_id(RepositoryId::new(1))
+            .with_name("follower")
+            .build()?;
+        Ok((primary, follower))
+    }
+
+    #[fbinit::test]
+    async fn test_replicate_commits_and_bookmarks(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let (primary, follower) = build_repos(fb)?;
+        let main = BookmarkName::new("main")?;
+
+        let root = CreateCommitContext::new_root(&ctx, &primary)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let child = CreateCommitContext::new(&ctx, &primary, vec![root])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        bookmark(&ctx, &primary, &main).set_to(root).await?;
+        bookmark(&ctx, &primary, &main).set_to(child).await?;
+
+        let lag = calculate_lag(&ctx, &primary, &follower).await?;
+        assert_eq!(lag.remaining_entries, 2);
+
+        assert_eq!(replicate_once(&ctx, &primary, &follower, 100).await?, 2);
+        assert_eq!(
+            follower.bookmarks().get(ctx.clone(), &main).await?,
+            Some(child)
+        );
+        assert_eq!(
+            list_working_copy_utf8(&ctx, &follower, child).await?,
+            list_working_copy_utf8(&ctx, &primary, child).await?,
+        );
+        assert_eq!(
+            calculate_lag(&ctx, &primary, &follower)
+                .await?
+                .remaining_entries,
+            0
+        );
+        assert!(
+            check_consistency(&ctx, &primary, &follower)
+                .await?
+                .is_empty()
+        );
+
+        // Nothing new to replicate.
+        assert_eq!(replicate_once(&ctx, &primary, &follower, 100).await?, 0);
+
+        bookmark(&ctx, &primary, &main).delete().await?;
+        assert_eq!(replicate_once(&ctx, &primary, &follower, 100).await?, 1);
+        assert_eq!(follower.bookmarks().get(ctx.clone(), &main).await?, None);
+        assert!(
+            check_consistency(&ctx, &primary, &follower)
+                .await?
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_copy_changesets_in_walks(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let (primary, follower) = build_repos(fb)?;
+
+        let mut head = CreateCommitContext::new_root(&ctx, &primary)
+            .add_file("0", "0")
+            .commit()
+            .await?;
+        let mut commits = vec![head];
+        for i in 1..5 {
+            head = CreateCommitContext::new(&ctx, &primary, vec![head])
+                .add_file(i.to_string().as_str(), i.to_string())
+                .commit()
+                .await?;
+            commits.push(head);
+        }
+
+        assert_eq!(
+            copy_changesets(&ctx, &primary, &follower, head, 2).await?,
+            5
+        );
+        for cs_id in commits {
+            assert!(follower.changesets().exists(&ctx, cs_id).await?);
+            assert_eq!(
+                follower
+                    .bonsai_hg_mapping()
+                    .get_hg_from_bonsai(&ctx, cs_id)
+                    .await?,
+                primary
+                    .bonsai_hg_mapping()
+                    .get_hg_from_bonsai(&ctx, cs_id)
+                    .await?,
+            );
+        }
+        assert_eq!(
+            list_working_copy_utf8(&ctx, &follower, head).await?,
+            list_working_copy_utf8(&ctx, &primary, head).await?,
+        );
+        assert_eq!(
+            copy_changesets(&ctx, &primary, &follower, head, 2).await?,
+            0
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_replicate_respects_limit(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let (primary, follower) = build_repos(fb)?;
+        let main = BookmarkName::new("main")?;
+
+        let root = CreateCommitContext::new_root(&ctx, &primary)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let child = CreateCommitContext::new(&ctx, &primary, vec![root])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        bookmark(&ctx, &primary, &main).set_to(root).await?;
+        bookmark(&ctx, &primary, &main).set_to(child).await?;
+
+        assert_eq!(replicate_once(&ctx, &primary, &follower, 1).await?, 1);
+        assert_eq!(
+            follower.bookmarks().get(ctx.clone(), &main).await?,
+            Some(root)
+        );
+        assert!(!follower.changesets().exists(&ctx, child).await?);
+        assert!(
+            !check_consistency(&ctx, &primary, &follower)
+                .await?
+                .is_empty()
+        );
+
+        assert_eq!(replicate_once(&ctx, &primary, &follower, 1).await?, 1);
+        assert_eq!(
+            follower.bookmarks().get(ctx.clone(), &main).await?,
+            Some(child)
+        );
+
+        Ok(())
+    }
+}
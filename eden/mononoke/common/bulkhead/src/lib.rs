@@ -0,0 +1,235 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Per-repo limits on concurrent expensive operations.
+//!
+//! Repos served by the same process share its CPU, memory and storage
+//! connections.  A bulkhead limits how many operations of one kind can run
+//! at once for each repo, so that a single busy repo can't starve the
+//! others.  Operations over the limit wait for a permit, and the time spent
+//! waiting is recorded against the repo.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use context::CoreContext;
+use context::PerfCounterType;
+use lazy_static::lazy_static;
+use stats::prelude::*;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+define_stats! {
+    prefix = "mononoke.bulkhead";
+    acquired: dynamic_timeseries("{}.{}.acquired", (repo: String, op: &'static str); Rate, Sum),
+    saturated: dynamic_timeseries("{}.{}.saturated", (repo: String, op: &'static str); Rate, Sum),
+    queue_time_ms: dynamic_histogram("{}.{}.queue_time_ms", (repo: String, op: &'static str); 100, 0, 10_000, Average, Sum, Count; P 50; P 95; P 99),
+}
+
+lazy_static! {
+    static ref REPO_BULKHEADS: Mutex<HashMap<String, Arc<RepoBulkheads>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The kinds of operation that are limited.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BulkheadOp {
+    Getbundle,
+    Unbundle,
+    Hooks,
+}
+
+impl BulkheadOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkheadOp::Getbundle => "getbundle",
+            BulkheadOp::Unbundle => "unbundle",
+            BulkheadOp::Hooks => "hooks",
+        }
+    }
+}
+
+/// Maximum number of concurrent operations of each kind.  `None` means
+/// unlimited.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BulkheadLimits {
+    pub getbundle: Option<NonZeroUsize>,
+    pub unbundle: Option<NonZeroUsize>,
+    pub hooks: Option<NonZeroUsize>,
+}
+
+/// Limits concurrency of one kind of operation in one repo.
+pub struct Bulkhead {
+    repo_name: String,
+    op: BulkheadOp,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl Bulkhead {
+    fn new(repo_name: &str, op: BulkheadOp, limit: Option<NonZeroUsize>) -> Self {
+        Self {
+            repo_name: repo_name.to_string(),
+            op,
+            semaphore: limit.map(|limit| Arc::new(Semaphore::new(limit.get()))),
+        }
+    }
+
+    /// Wait until the operation may run.  The operation may run for as long
+    /// as the returned permit is held.
+    pub async fn acquire(&self, ctx: &CoreContext) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self.semaphore.as_ref()?;
+        let op = self.op.name();
+        if semaphore.available_permits() == 0 {
+            STATS::saturated.add_value(1, (self.repo_name.clone(), op));
+        }
+
+        let start = Instant::now();
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("bulkhead semaphore is never closed");
+        let waited_ms = start.elapsed().as_millis() as i64;
+
+        STATS::acquired.add_value(1, (self.repo_name.clone(), op));
+        STATS::queue_time_ms.add_value(waited_ms, (self.repo_name.clone(), op));
+        ctx.perf_counters()
+            .add_to_counter(PerfCounterType::BulkheadQueueTimeMs, waited_ms);
+        Some(permit)
+    }
+
+    /// Run a future once the operation may run.
+    pub async fn run<F: Future>(&self, ctx: &CoreContext, fut: F) -> F::Output {
+        let _permit = self.acquire(ctx).await;
+        fut.await
+    }
+}
+
+/// The bulkheads for a single repo.
+pub struct RepoBulkheads {
+    limits: BulkheadLimits,
+    getbundle: Bulkhead,
+    unbundle: Bulkhead,
+    hooks: Bulkhead,
+}
+
+impl RepoBulkheads {
+    fn new(repo_name: &str, limits: BulkheadLimits) -> Self {
+        Self {
+            limits,
+            getbundle: Bulkhead::new(repo_name, BulkheadOp::Getbundle, limits.getbundle),
+            unbundle: Bulkhead::new(repo_name, BulkheadOp::Unbundle, limits.unbundle),
+            hooks: Bulkhead::new(repo_name, BulkheadOp::Hooks, limits.hooks),
+        }
+    }
+
+    pub fn get(&self, op: BulkheadOp) -> &Bulkhead {
+        match op {
+            BulkheadOp::Getbundle => &self.getbundle,
+            BulkheadOp::Unbundle => &self.unbundle,
+            BulkheadOp::Hooks => &self.hooks,
+        }
+    }
+}
+
+/// Set the limits for a repo, and return its bulkheads.  If the limits have
+/// changed, operations that are already running keep their permits, but
+/// new operations are limited by the new limits.
+pub fn configure_repo_bulkheads(repo_name: &str, limits: BulkheadLimits) -> Arc<RepoBulkheads> {
+    let mut all = REPO_BULKHEADS.lock().expect("lock poisoned");
+    match all.get(repo_name) {
+        Some(bulkheads) if bulkheads.limits == limits => bulkheads.clone(),
+        _ => {
+            let bulkheads = Arc::new(RepoBulkheads::new(repo_name, limits));
+            all.insert(repo_name.to_string(), bulkheads.clone());
+            bulkheads
+        }
+    }
+}
+
+/// The bulkheads for a repo.  Repos that haven't been configured are
+/// unlimited.
+pub fn repo_bulkheads(repo_name: &str) -> Arc<RepoBulkheads> {
+    let mut all = REPO_BULKHEADS.lock().expect("lock poisoned");
+    all.entry(repo_name.to_string())
+        .or_insert_with(|| Arc::new(RepoBulkheads::new(repo_name, BulkheadLimits::default())))
+        .clone()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use fbinit::FacebookInit;
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_limits_concurrency(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let bulkheads = configure_repo_bulkheads(
+            "test_limits_concurrency",
+            BulkheadLimits {
+                unbundle: Some(nonzero!(1usize)),
+                ..Default::default()
+            },
+        );
+        let unbundle = bulkheads.get(BulkheadOp::Unbundle);
+
+        let first = unbundle.acquire(&ctx).await;
+        assert!(first.is_some());
+        let second = tokio::time::timeout(Duration::from_millis(10), unbundle.acquire(&ctx)).await;
+        assert!(second.is_err(), "second unbundle should wait");
+
+        // Other operations and other repos are unaffected.
+        assert!(
+            bulkheads
+                .get(BulkheadOp::Getbundle)
+                .acquire(&ctx)
+                .await
+                .is_none()
+        );
+        assert!(
+            repo_bulkheads("other_repo")
+                .get(BulkheadOp::Unbundle)
+                .acquire(&ctx)
+                .await
+                .is_none()
+        );
+
+        drop(first);
+        assert!(unbundle.acquire(&ctx).await.is_some());
+    }
+
+    #[fbinit::test]
+    async fn test_reconfigure(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let limits = BulkheadLimits {
+            hooks: Some(nonzero!(2usize)),
+            ..Default::default()
+        };
+        let first = configure_repo_bulkheads("test_reconfigure", limits);
+        let same = configure_repo_bulkheads("test_reconfigure", limits);
+        assert!(Arc::ptr_eq(&first, &same));
+        assert!(Arc::ptr_eq(&first, &repo_bulkheads("test_reconfigure")));
+
+        let unlimited = configure_repo_bulkheads("test_reconfigure", BulkheadLimits::default());
+        assert!(!Arc::ptr_eq(&first, &unlimited));
+        assert!(
+            unlimited
+                .get(BulkheadOp::Hooks)
+                .acquire(&ctx)
+                .await
+                .is_none()
+        );
+    }
+}
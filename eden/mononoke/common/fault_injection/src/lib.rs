@@ -0,0 +1,264 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Seeded fault injection for storage and network operations.
+//!
+//! A `FaultInjector` decides, for each operation, whether it should be
+//! delayed, fail, time out, or have its data damaged.  Decisions are drawn
+//! from a random number generator seeded by the test, so a failing test can
+//! be reproduced by re-running it with the same seed, as long as the
+//! operations are issued in the same order.
+//!
+//! The injector itself doesn't know about any particular storage; wrappers
+//! (such as `faultblob::FaultBlobstore`, or the SQL fault injection in
+//! `sql_ext`) consult it before each operation and apply the fault.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use thiserror::Error;
+
+/// Probabilities of each kind of fault.  All probabilities are between 0.0
+/// (never) and 1.0 (always), and default to never.
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+    /// Probability that an operation is delayed before it starts.
+    pub latency_probability: f64,
+    /// Maximum delay.  Delays are chosen uniformly up to this value.
+    pub max_latency: Duration,
+    /// Probability that an operation fails without being attempted.
+    pub error_probability: f64,
+    /// Probability that an operation hangs for `timeout` and then fails
+    /// without being attempted.
+    pub timeout_probability: f64,
+    /// How long operations that time out hang for.
+    pub timeout: Duration,
+    /// Probability that only part of the data of a write is stored, after
+    /// which the write fails.
+    pub partial_write_probability: f64,
+    /// Probability that the data returned by a read is corrupted.
+    pub corrupt_read_probability: f64,
+}
+
+/// Whether an operation reads or writes data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    Read,
+    Write,
+}
+
+/// A fault that the caller must apply to the data of the operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DataFault {
+    /// Store a prefix of this length, and then fail the write.
+    PartialWrite { len: usize },
+    /// Flip the bits of the byte at this offset (modulo the data length).
+    CorruptRead { offset: usize },
+}
+
+#[derive(Debug, Error)]
+pub enum FaultInjected {
+    #[error("Injected failure in {0}")]
+    Error(String),
+    #[error("Injected timeout in {0}")]
+    Timeout(String),
+    #[error("Injected partial write in {0}")]
+    PartialWrite(String),
+}
+
+pub struct FaultInjector {
+    config: FaultConfig,
+    rng: Mutex<StdRng>,
+    injected: AtomicU64,
+}
+
+struct Decision {
+    latency: Option<Duration>,
+    error: bool,
+    timeout: bool,
+    data_fault: Option<DataFault>,
+}
+
+impl FaultInjector {
+    pub fn new(seed: u64, config: FaultConfig) -> Self {
+        Self {
+            config,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    /// Decide the fate of an operation, and apply any delay.
+    ///
+    /// Returns an error if the operation should fail without being
+    /// attempted, or the fault to apply to its data, if any.  `description`
+    /// is included in injected errors so they can be recognised in logs.
+    pub async fn inject(
+        &self,
+        operation: Operation,
+        description: &str,
+    ) -> Result<Option<DataFault>, FaultInjected> {
+        self.inject_impl(Some(operation), description).await
+    }
+
+    /// Like `inject`, for operations whose data can't be damaged, such as
+    /// database queries.  Only delays, failures and timeouts are applied.
+    pub async fn inject_failure(&self, description: &str) -> Result<(), FaultInjected> {
+        self.inject_impl(None, description).await?;
+        Ok(())
+    }
+
+    async fn inject_impl(
+        &self,
+        operation: Option<Operation>,
+        description: &str,
+    ) -> Result<Option<DataFault>, FaultInjected> {
+        let decision = self.decide(operation);
+        if let Some(latency) = decision.latency {
+            tokio::time::sleep(latency).await;
+        }
+        if decision.error {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+            return Err(FaultInjected::Error(description.to_string()));
+        }
+        if decision.timeout {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(self.config.timeout).await;
+            return Err(FaultInjected::Timeout(description.to_string()));
+        }
+        if decision.data_fault.is_some() {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(decision.data_fault)
+    }
+
+    /// Number of operations that have had a fault injected, not counting
+    /// delays.
+    pub fn injected_faults(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    fn decide(&self, operation: Option<Operation>) -> Decision {
+        let mut rng = self.rng.lock().expect("lock poisoned");
+        // Always draw the same number of values for each operation, so that
+        // changing one probability doesn't change the decisions made for
+        // the other kinds of fault.
+        let latency = rng.gen_bool(self.config.latency_probability);
+        let latency_fraction: f64 = rng.gen();
+        let error = rng.gen_bool(self.config.error_probability);
+        let timeout = rng.gen_bool(self.config.timeout_probability);
+        let partial_write = rng.gen_bool(self.config.partial_write_probability);
+        let corrupt_read = rng.gen_bool(self.config.corrupt_read_probability);
+        let position: usize = rng.gen();
+
+        let data_fault = match operation {
+            Some(Operation::Write) if partial_write => {
+                Some(DataFault::PartialWrite { len: position })
+            }
+            Some(Operation::Read) if corrupt_read => {
+                Some(DataFault::CorruptRead { offset: position })
+            }
+            _ => None,
+        };
+        Decision {
+            latency: latency.then(|| self.config.max_latency.mul_f64(latency_fraction)),
+            error,
+            timeout,
+            data_fault,
+        }
+    }
+}
+
+impl DataFault {
+    /// Apply a corruption to data that was read.
+    pub fn corrupt(&self, data: &mut [u8]) {
+        if let DataFault::CorruptRead { offset } = self {
+            if !data.is_empty() {
+                let offset = offset % data.len();
+                data[offset] = !data[offset];
+            }
+        }
+    }
+
+    /// The length of the prefix of data of length `len` that should be
+    /// written.  This is always shorter than the full data.
+    pub fn partial_len(&self, len: usize) -> usize {
+        match self {
+            DataFault::PartialWrite { len: partial } if len > 0 => partial % len,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> FaultConfig {
+        FaultConfig {
+            error_probability: 0.3,
+            partial_write_probability: 0.3,
+            corrupt_read_probability: 0.3,
+            ..Default::default()
+        }
+    }
+
+    async fn run(injector: &FaultInjector) -> Vec<String> {
+        let mut outcomes = Vec::new();
+        for i in 0..100 {
+            let operation = if i % 2 == 0 {
+                Operation::Read
+            } else {
+                Operation::Write
+            };
+            outcomes.push(format!("{:?}", injector.inject(operation, "test").await));
+        }
+        outcomes
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_faults() {
+        let first = FaultInjector::new(42, config());
+        let second = FaultInjector::new(42, config());
+        assert_eq!(run(&first).await, run(&second).await);
+        assert!(first.injected_faults() > 0);
+        assert_eq!(first.injected_faults(), second.injected_faults());
+    }
+
+    #[tokio::test]
+    async fn test_data_faults_match_operation() {
+        let injector = FaultInjector::new(
+            1,
+            FaultConfig {
+                corrupt_read_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            injector.inject(Operation::Read, "read").await,
+            Ok(Some(DataFault::CorruptRead { .. }))
+        ));
+        assert!(matches!(
+            injector.inject(Operation::Write, "write").await,
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn test_data_fault_application() {
+        let mut data = vec![0u8; 4];
+        DataFault::CorruptRead { offset: 6 }.corrupt(&mut data);
+        assert_eq!(data, vec![0, 0, 0xff, 0]);
+        assert_eq!(DataFault::PartialWrite { len: 7 }.partial_len(4), 3);
+        assert_eq!(DataFault::PartialWrite { len: 7 }.partial_len(0), 0);
+    }
+}
@@ -0,0 +1,363 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Typed feature flags, scoped per repo and per identity.
+//!
+//! Flags are declared as constants next to the code that uses them, e.g.
+//!
+//! ```ignore
+//! const SHORT_HISTORY: BoolFlag = BoolFlag::new("short_history", false);
+//! ```
+//!
+//! and read through the `CoreContext`, which knows the repo and the
+//! identities of the session:
+//!
+//! ```ignore
+//! if ctx.feature_flags().get(&SHORT_HISTORY) { ... }
+//! ```
+//!
+//! Flag values come from a config that is reloaded while the server runs.
+//! A flag can be set globally, per repo, and per identity, with the most
+//! specific setting winning.  Flags that are not set take their default.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use cached_config::ConfigHandle;
+use cached_config::ConfigStore;
+use permission_checker::MononokeIdentitySet;
+use serde::Deserialize;
+
+/// The value a flag is set to in the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum FlagValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+}
+
+/// How a single flag is set.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct FlagSettings {
+    /// Value for all repos and identities, unless overridden.
+    #[serde(default)]
+    pub value: Option<FlagValue>,
+    /// Values for particular repos, by repo name.
+    #[serde(default)]
+    pub repos: HashMap<String, FlagValue>,
+    /// Values for particular identities, as `TYPE:data`.  These take
+    /// precedence over values for repos.
+    #[serde(default)]
+    pub identities: HashMap<String, FlagValue>,
+}
+
+impl FlagSettings {
+    fn lookup(&self, repo: Option<&str>, identities: &[String]) -> Option<&FlagValue> {
+        identities
+            .iter()
+            .find_map(|identity| self.identities.get(identity))
+            .or_else(|| repo.and_then(|repo| self.repos.get(repo)))
+            .or(self.value.as_ref())
+    }
+}
+
+/// The config the flag values are loaded from.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct FeatureFlagsConfig {
+    #[serde(default)]
+    pub flags: HashMap<String, FlagSettings>,
+}
+
+/// A typed feature flag.
+pub trait Flag {
+    type Value: fmt::Debug;
+
+    fn name(&self) -> &'static str;
+
+    /// The value of the flag if it isn't set, for a session whose rollout
+    /// position is `bucket`, in the range 0..10000.
+    fn default_value(&self, bucket: u64) -> Self::Value;
+
+    /// Interpret a value from the config, for a session whose rollout
+    /// position is `bucket`.  Returns `None` if the value has the wrong type
+    /// for this flag.
+    fn interpret(&self, value: &FlagValue, bucket: u64) -> Option<Self::Value>;
+}
+
+/// A flag that is either on or off.
+pub struct BoolFlag {
+    name: &'static str,
+    default: bool,
+}
+
+impl BoolFlag {
+    pub const fn new(name: &'static str, default: bool) -> Self {
+        Self { name, default }
+    }
+}
+
+impl Flag for BoolFlag {
+    type Value = bool;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn default_value(&self, _bucket: u64) -> bool {
+        self.default
+    }
+
+    fn interpret(&self, value: &FlagValue, _bucket: u64) -> Option<bool> {
+        match value {
+            FlagValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// A flag holding a number, e.g. a limit.
+pub struct IntFlag {
+    name: &'static str,
+    default: i64,
+}
+
+impl IntFlag {
+    pub const fn new(name: &'static str, default: i64) -> Self {
+        Self { name, default }
+    }
+}
+
+impl Flag for IntFlag {
+    type Value = i64;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn default_value(&self, _bucket: u64) -> i64 {
+        self.default
+    }
+
+    fn interpret(&self, value: &FlagValue, _bucket: u64) -> Option<i64> {
+        match value {
+            FlagValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// A flag that is on for a percentage of sessions.  A session is either in
+/// or out of the rollout for the whole of its lifetime, and as the
+/// percentage grows, sessions that were in stay in.
+pub struct PercentageFlag {
+    name: &'static str,
+    default: f64,
+}
+
+impl PercentageFlag {
+    pub const fn new(name: &'static str, default: f64) -> Self {
+        Self { name, default }
+    }
+
+    fn enabled(percentage: f64, bucket: u64) -> bool {
+        (bucket as f64) < percentage * 100.0
+    }
+}
+
+impl Flag for PercentageFlag {
+    type Value = bool;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn default_value(&self, bucket: u64) -> bool {
+        Self::enabled(self.default, bucket)
+    }
+
+    fn interpret(&self, value: &FlagValue, bucket: u64) -> Option<bool> {
+        match value {
+            FlagValue::Int(value) => Some(Self::enabled(*value as f64, bucket)),
+            FlagValue::Float(value) => Some(Self::enabled(*value, bucket)),
+            FlagValue::Bool(_) => None,
+        }
+    }
+}
+
+/// The feature flags of a server.  This is cheap to clone.
+#[derive(Clone, Default)]
+pub struct FeatureFlags {
+    config: ConfigHandle<FeatureFlagsConfig>,
+}
+
+impl FeatureFlags {
+    pub fn new(config: ConfigHandle<FeatureFlagsConfig>) -> Self {
+        Self { config }
+    }
+
+    /// Load the flags from `path` in the config store, reloading them when
+    /// the config changes.
+    pub fn from_config_store(config_store: &ConfigStore, path: impl ToString) -> Result<Self> {
+        Ok(Self::new(config_store.get_config_handle(path.to_string())?))
+    }
+
+    /// The flags as they apply to a session.
+    pub fn for_session(
+        &self,
+        repo: Option<String>,
+        identities: &MononokeIdentitySet,
+        session_id: &str,
+    ) -> SessionFeatureFlags {
+        SessionFeatureFlags {
+            config: self.config.clone(),
+            repo,
+            identities: identities.iter().map(ToString::to_string).collect(),
+            session_id: session_id.to_string(),
+            decisions: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+/// The feature flags as they apply to a session, i.e. to its repo and
+/// identities.  The flags that the session reads are recorded, so that the
+/// decisions can be logged with the session.
+pub struct SessionFeatureFlags {
+    config: ConfigHandle<FeatureFlagsConfig>,
+    repo: Option<String>,
+    identities: Vec<String>,
+    session_id: String,
+    decisions: Mutex<BTreeMap<&'static str, String>>,
+}
+
+impl SessionFeatureFlags {
+    /// Flags with their defaults, for sessions outside of a server.
+    pub fn defaults() -> Self {
+        FeatureFlags::default().for_session(None, &MononokeIdentitySet::new(), "")
+    }
+
+    /// The value of a flag for this session.
+    pub fn get<F: Flag>(&self, flag: &F) -> F::Value {
+        let default = flag.default_value(self.bucket(flag.name()));
+        self.get_or(flag, default)
+    }
+
+    /// The value of a flag for this session, or `default` if the flag isn't
+    /// set for it.  This allows flags to override existing configuration.
+    pub fn get_or<F: Flag>(&self, flag: &F, default: F::Value) -> F::Value {
+        let bucket = self.bucket(flag.name());
+        let value = self
+            .config
+            .get()
+            .flags
+            .get(flag.name())
+            .and_then(|settings| settings.lookup(self.repo.as_deref(), &self.identities))
+            .and_then(|value| flag.interpret(value, bucket))
+            .unwrap_or(default);
+        self.decisions
+            .lock()
+            .expect("lock poisoned")
+            .insert(flag.name(), format!("{:?}", value));
+        value
+    }
+
+    /// The flags read by this session so far, as `name=value`.
+    pub fn decisions(&self) -> Vec<String> {
+        self.decisions
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect()
+    }
+
+    /// The position of this session in rollouts of the flag, from 0 to
+    /// 9999.  Hashing the flag name too means that different flags are
+    /// rolled out to different sessions.
+    fn bucket(&self, name: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        self.session_id.hash(&mut hasher);
+        hasher.finish() % 10000
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use permission_checker::MononokeIdentity;
+
+    use super::*;
+
+    const FLAG: BoolFlag = BoolFlag::new("flag", false);
+    const LIMIT: IntFlag = IntFlag::new("limit", 10);
+    const ROLLOUT: PercentageFlag = PercentageFlag::new("rollout", 0.0);
+
+    fn flags(json: &str) -> FeatureFlags {
+        FeatureFlags::new(ConfigHandle::from_json(json).unwrap())
+    }
+
+    #[test]
+    fn test_scopes() -> Result<()> {
+        let flags = flags(
+            r#"{"flags": {
+                "flag": {
+                    "value": false,
+                    "repos": {"repo1": true},
+                    "identities": {"USER:alice": false}
+                },
+                "limit": {"value": 20}
+            }}"#,
+        );
+        let mut alice = MononokeIdentitySet::new();
+        alice.insert(MononokeIdentity::new("USER", "alice"));
+
+        let session =
+            flags.for_session(Some("repo1".to_string()), &MononokeIdentitySet::new(), "a");
+        assert!(session.get(&FLAG));
+        assert_eq!(session.get(&LIMIT), 20);
+        assert_eq!(session.decisions(), vec!["flag=true", "limit=20"]);
+
+        let session = flags.for_session(Some("repo1".to_string()), &alice, "b");
+        assert!(!session.get(&FLAG));
+
+        let session =
+            flags.for_session(Some("repo2".to_string()), &MononokeIdentitySet::new(), "c");
+        assert!(!session.get(&FLAG));
+        assert!(session.get_or(&ROLLOUT, true));
+
+        let session = SessionFeatureFlags::defaults();
+        assert_eq!(session.get(&LIMIT), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_percentage() {
+        let count = |percentage: u32| {
+            let flags = flags(&format!(
+                r#"{{"flags": {{"rollout": {{"value": {}}}}}}}"#,
+                percentage
+            ));
+            (0..1000)
+                .filter(|i| {
+                    flags
+                        .for_session(None, &MononokeIdentitySet::new(), &i.to_string())
+                        .get(&ROLLOUT)
+                })
+                .count()
+        };
+        assert_eq!(count(0), 0);
+        assert_eq!(count(100), 1000);
+        let half = count(50);
+        assert!(half > 400 && half < 600, "{} sessions enabled", half);
+    }
+}
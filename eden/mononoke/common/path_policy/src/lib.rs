@@ -0,0 +1,419 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Rules for the paths of files added to a repo.
+//!
+//! `MPath` itself only rejects paths that could never be stored.  Repos can
+//! additionally restrict paths to those that their clients can check out,
+//! e.g. limiting the length of path components or reserving names that mean
+//! something special on some platforms.  These rules are configured per repo
+//! in `PathPolicyConfig`, and every place where new paths enter the repo
+//! checks them through a `PathPolicy`, so the same paths are accepted
+//! everywhere and rejected with the same messages.
+//!
+//! The policy also covers the targets of symlinks, which clients check out as
+//! paths too.  Targets with NUL bytes or longer than
+//! `MAX_SYMLINK_TARGET_LENGTH` can never be checked out, so they are
+//! rejected in every repo.  Repos can additionally forbid targets that are
+//! absolute or that point outside of the repo.
+
+use std::collections::HashSet;
+
+use metaconfig_types::PathPolicyConfig;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ContentId;
+use mononoke_types::FileType;
+use mononoke_types::MPath;
+use thiserror::Error;
+
+/// Maximum length of a symlink target, in bytes.  This is the longest path
+/// that common platforms allow.
+pub const MAX_SYMLINK_TARGET_LENGTH: u64 = 4096;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PathPolicyViolation {
+    #[error("Path '{path}' has a component of {length} bytes, the limit is {limit}")]
+    ComponentTooLong {
+        path: MPath,
+        length: usize,
+        limit: usize,
+    },
+    #[error("Path '{path}' is {length} bytes long, the limit is {limit}")]
+    PathTooLong {
+        path: MPath,
+        length: usize,
+        limit: usize,
+    },
+    #[error("Path '{path}' is {depth} directories deep, the limit is {limit}")]
+    TooDeep {
+        path: MPath,
+        depth: usize,
+        limit: usize,
+    },
+    #[error("Path '{path}' contains the forbidden character {character:?}")]
+    ForbiddenCharacter { path: MPath, character: char },
+    #[error("Path '{path}' uses the reserved name '{name}'")]
+    ReservedName { path: MPath, name: String },
+    #[error("Symlink '{path}' has a target containing a NUL byte")]
+    SymlinkTargetHasNul { path: MPath },
+    #[error("Symlink '{path}' has a target of {length} bytes, the limit is {limit}")]
+    SymlinkTargetTooLong {
+        path: MPath,
+        length: u64,
+        limit: u64,
+    },
+    #[error("Symlink '{path}' points to the absolute path '{target}'")]
+    AbsoluteSymlink { path: MPath, target: String },
+    #[error("Symlink '{path}' points to '{target}', which is outside of the repo")]
+    EscapingSymlink { path: MPath, target: String },
+}
+
+impl PathPolicyViolation {
+    /// The path that violates the policy.
+    pub fn path(&self) -> &MPath {
+        match self {
+            PathPolicyViolation::ComponentTooLong { path, .. }
+            | PathPolicyViolation::PathTooLong { path, .. }
+            | PathPolicyViolation::TooDeep { path, .. }
+            | PathPolicyViolation::ForbiddenCharacter { path, .. }
+            | PathPolicyViolation::ReservedName { path, .. }
+            | PathPolicyViolation::SymlinkTargetHasNul { path }
+            | PathPolicyViolation::SymlinkTargetTooLong { path, .. }
+            | PathPolicyViolation::AbsoluteSymlink { path, .. }
+            | PathPolicyViolation::EscapingSymlink { path, .. } => path,
+        }
+    }
+}
+
+/// The path rules of a repo.
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    config: PathPolicyConfig,
+    reserved_names: HashSet<Vec<u8>>,
+}
+
+impl PathPolicy {
+    pub fn new(config: &PathPolicyConfig) -> Self {
+        let reserved_names = config
+            .reserved_names
+            .iter()
+            .map(|name| {
+                if config.reserved_names_case_insensitive {
+                    name.to_ascii_lowercase().into_bytes()
+                } else {
+                    name.clone().into_bytes()
+                }
+            })
+            .collect();
+        PathPolicy {
+            config: config.clone(),
+            reserved_names,
+        }
+    }
+
+    /// Check a single path against the policy.
+    pub fn check_path(&self, path: &MPath) -> Result<(), PathPolicyViolation> {
+        if let Some(limit) = self.config.max_depth {
+            let depth = path.num_components();
+            if depth > limit {
+                return Err(PathPolicyViolation::TooDeep {
+                    path: path.clone(),
+                    depth,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.config.max_path_length {
+            let length = path.len();
+            if length > limit {
+                return Err(PathPolicyViolation::PathTooLong {
+                    path: path.clone(),
+                    length,
+                    limit,
+                });
+            }
+        }
+
+        for element in path {
+            let bytes = element.as_ref();
+
+            if let Some(limit) = self.config.max_component_length {
+                if bytes.len() > limit {
+                    return Err(PathPolicyViolation::ComponentTooLong {
+                        path: path.clone(),
+                        length: bytes.len(),
+                        limit,
+                    });
+                }
+            }
+
+            if !self.config.forbidden_characters.is_empty() {
+                if let Some(character) = String::from_utf8_lossy(bytes)
+                    .chars()
+                    .find(|c| self.config.forbidden_characters.contains(c))
+                {
+                    return Err(PathPolicyViolation::ForbiddenCharacter {
+                        path: path.clone(),
+                        character,
+                    });
+                }
+            }
+
+            if !self.reserved_names.is_empty() {
+                let is_reserved = if self.config.reserved_names_case_insensitive {
+                    self.reserved_names.contains(&bytes.to_ascii_lowercase())
+                } else {
+                    self.reserved_names.contains(bytes)
+                };
+                if is_reserved {
+                    return Err(PathPolicyViolation::ReservedName {
+                        path: path.clone(),
+                        name: String::from_utf8_lossy(bytes).into_owned(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the paths added or modified by a changeset.  Deletions are
+    /// always allowed, so that paths which predate the policy can be
+    /// removed.
+    pub fn check_changeset(&self, bcs: &BonsaiChangeset) -> Result<(), PathPolicyViolation> {
+        for (path, change) in bcs.file_changes() {
+            if change.is_changed() {
+                self.check_path(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the length of the target of a symlink at `path`, so that
+    /// targets that are too long can be rejected without fetching them.
+    pub fn check_symlink_target_length(
+        &self,
+        path: &MPath,
+        length: u64,
+    ) -> Result<(), PathPolicyViolation> {
+        if length > MAX_SYMLINK_TARGET_LENGTH {
+            return Err(PathPolicyViolation::SymlinkTargetTooLong {
+                path: path.clone(),
+                length,
+                limit: MAX_SYMLINK_TARGET_LENGTH,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the target of a symlink at `path` against the policy.
+    pub fn check_symlink_target(
+        &self,
+        path: &MPath,
+        target: &[u8],
+    ) -> Result<(), PathPolicyViolation> {
+        self.check_symlink_target_length(path, target.len() as u64)?;
+
+        if target.contains(&0) {
+            return Err(PathPolicyViolation::SymlinkTargetHasNul { path: path.clone() });
+        }
+
+        if self.config.forbid_absolute_symlinks && is_absolute(target) {
+            return Err(PathPolicyViolation::AbsoluteSymlink {
+                path: path.clone(),
+                target: String::from_utf8_lossy(target).into_owned(),
+            });
+        }
+
+        if self.config.forbid_escaping_symlinks && !is_absolute(target) {
+            // The target is relative to the directory containing the
+            // symlink.  Follow it, and check it never goes above the root.
+            let mut depth = path.num_components() - 1;
+            for component in target.split(|b| *b == b'/' || *b == b'\\') {
+                match component {
+                    b"" | b"." => {}
+                    b".." => match depth.checked_sub(1) {
+                        Some(d) => depth = d,
+                        None => {
+                            return Err(PathPolicyViolation::EscapingSymlink {
+                                path: path.clone(),
+                                target: String::from_utf8_lossy(target).into_owned(),
+                            });
+                        }
+                    },
+                    _ => depth += 1,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The symlinks added or modified by a changeset, with the content id
+    /// and size of their targets, which must be checked with
+    /// `check_symlink_target`.
+    pub fn changed_symlinks(
+        bcs: &BonsaiChangeset,
+    ) -> impl Iterator<Item = (&MPath, ContentId, u64)> {
+        bcs.file_changes().filter_map(|(path, change)| {
+            let change = change.simplify()?;
+            (change.file_type() == FileType::Symlink)
+                .then(|| (path, change.content_id(), change.size()))
+        })
+    }
+}
+
+/// Whether a symlink target is an absolute path, on any platform.
+fn is_absolute(target: &[u8]) -> bool {
+    match target {
+        [b'/' | b'\\', ..] => true,
+        [drive, b':', ..] => drive.is_ascii_alphabetic(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(p: &str) -> MPath {
+        MPath::new(p).unwrap()
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let policy = PathPolicy::new(&PathPolicyConfig::default());
+        assert_eq!(policy.check_path(&path("a/b/c/CON/.hg/x:y")), Ok(()));
+    }
+
+    #[test]
+    fn test_limits() {
+        let policy = PathPolicy::new(&PathPolicyConfig {
+            max_component_length: Some(4),
+            max_path_length: Some(10),
+            max_depth: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(policy.check_path(&path("abcd/efgh")), Ok(()));
+        assert_eq!(
+            policy.check_path(&path("abcde")),
+            Err(PathPolicyViolation::ComponentTooLong {
+                path: path("abcde"),
+                length: 5,
+                limit: 4,
+            })
+        );
+        assert_eq!(
+            policy.check_path(&path("ab/cd/ef/gh")),
+            Err(PathPolicyViolation::TooDeep {
+                path: path("ab/cd/ef/gh"),
+                depth: 4,
+                limit: 3,
+            })
+        );
+        assert_eq!(
+            policy.check_path(&path("abcd/efgh/ij")),
+            Err(PathPolicyViolation::PathTooLong {
+                path: path("abcd/efgh/ij"),
+                length: 12,
+                limit: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn test_forbidden_characters() {
+        let policy = PathPolicy::new(&PathPolicyConfig {
+            forbidden_characters: vec![':', '\\'],
+            ..Default::default()
+        });
+        assert_eq!(policy.check_path(&path("dir/file.txt")), Ok(()));
+        assert_eq!(
+            policy.check_path(&path("dir/a:b")),
+            Err(PathPolicyViolation::ForbiddenCharacter {
+                path: path("dir/a:b"),
+                character: ':',
+            })
+        );
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        let config = PathPolicyConfig {
+            reserved_names: vec!["CON".to_string(), ".hg".to_string()],
+            ..Default::default()
+        };
+        let policy = PathPolicy::new(&config);
+        assert!(policy.check_path(&path("dir/CON/file")).is_err());
+        assert!(policy.check_path(&path("dir/.hg")).is_err());
+        assert_eq!(policy.check_path(&path("dir/con/file")), Ok(()));
+        assert_eq!(policy.check_path(&path("dir/CONSOLE")), Ok(()));
+
+        let policy = PathPolicy::new(&PathPolicyConfig {
+            reserved_names_case_insensitive: true,
+            ..config
+        });
+        let err = policy.check_path(&path("dir/con/file")).unwrap_err();
+        assert_eq!(err.path(), &path("dir/con/file"));
+        assert_eq!(
+            err.to_string(),
+            "Path 'dir/con/file' uses the reserved name 'con'"
+        );
+    }
+
+    #[test]
+    fn test_symlink_targets() {
+        let policy = PathPolicy::new(&PathPolicyConfig::default());
+        assert_eq!(
+            policy.check_symlink_target(&path("a/link"), b"/etc"),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check_symlink_target(&path("a/link"), b"../../x"),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check_symlink_target(&path("a/link"), b"x\0y"),
+            Err(PathPolicyViolation::SymlinkTargetHasNul {
+                path: path("a/link")
+            })
+        );
+        assert_eq!(
+            policy.check_symlink_target(&path("a/link"), &[b'x'; 4097]),
+            Err(PathPolicyViolation::SymlinkTargetTooLong {
+                path: path("a/link"),
+                length: 4097,
+                limit: MAX_SYMLINK_TARGET_LENGTH,
+            })
+        );
+
+        let policy = PathPolicy::new(&PathPolicyConfig {
+            forbid_absolute_symlinks: true,
+            forbid_escaping_symlinks: true,
+            ..Default::default()
+        });
+        assert_eq!(policy.check_symlink_target(&path("a/link"), b"b/c"), Ok(()));
+        assert_eq!(
+            policy.check_symlink_target(&path("a/b/link"), b"../../c"),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check_symlink_target(&path("a/link"), b"../b/../../c"),
+            Err(PathPolicyViolation::EscapingSymlink {
+                path: path("a/link"),
+                target: "../b/../../c".to_string(),
+            })
+        );
+        assert!(policy.check_symlink_target(&path("link"), b"/etc").is_err());
+        assert!(
+            policy
+                .check_symlink_target(&path("link"), b"C:\\Windows")
+                .is_err()
+        );
+    }
+}
@@ -10,10 +10,19 @@ use std::time::Duration;
 use futures::Future;
 use slog::info;
 use slog::Logger;
+use stats::prelude::*;
+
+define_stats! {
+    prefix = "mononoke.retry";
+    attempts: dynamic_timeseries("{}.attempts", (backend: &'static str); Rate, Sum),
+    retries: dynamic_timeseries("{}.retries", (backend: &'static str); Rate, Sum),
+    exhausted: dynamic_timeseries("{}.exhausted", (backend: &'static str); Rate, Sum),
+}
 
 #[derive(Copy, Clone)]
 pub struct RetryAttemptsCount(pub usize);
 
+#[derive(Clone, Copy, Debug)]
 pub enum RetryLogic {
     /// Multiply by a factor every time
     Exponential { base: Duration, factor: f64 },
@@ -39,6 +48,67 @@ impl RetryLogic {
     }
 }
 
+/// How the calls to a backend are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Name of the backend, used for the retry counters.
+    pub backend: &'static str,
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: usize,
+    pub logic: RetryLogic,
+}
+
+impl RetryPolicy {
+    pub const fn new(backend: &'static str, max_attempts: usize, logic: RetryLogic) -> Self {
+        Self {
+            backend,
+            max_attempts,
+            logic,
+        }
+    }
+
+    /// Call `func`, retrying the errors for which `should_retry` returns
+    /// true.  The number of attempts and retries are counted for the
+    /// backend, as are the calls that fail with a retryable error after
+    /// exhausting their attempts.
+    pub async fn run<V, Fut, Func, RetryFunc, Error>(
+        &self,
+        mut func: Func,
+        mut should_retry: RetryFunc,
+    ) -> Result<V, Error>
+    where
+        V: Send + 'static,
+        Fut: Future<Output = Result<V, Error>>,
+        Func: FnMut(usize) -> Fut + Send,
+        RetryFunc: FnMut(&Error) -> bool + Send,
+    {
+        let backend = self.backend;
+        let result = retry(
+            None,
+            |attempt| {
+                STATS::attempts.add_value(1, (backend,));
+                if attempt > 1 {
+                    STATS::retries.add_value(1, (backend,));
+                }
+                func(attempt)
+            },
+            |err| should_retry(err),
+            self.logic,
+            self.max_attempts,
+        )
+        .await;
+        match result {
+            Ok((value, _attempts)) => Ok(value),
+            Err(err) => {
+                if self.max_attempts > 1 && should_retry(&err) {
+                    STATS::exhausted.add_value(1, (backend,));
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
 /// Retry a function whenever it fails.
 /// See `retry` for more information.
 pub async fn retry_always<V, Fut, Func, Error>(
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::Result;
+use fault_injection::FaultInjector;
+
+tokio::task_local! {
+    static SQL_FAULT_INJECTOR: Arc<FaultInjector>;
+}
+
+/// Run a future with faults injected into the SQL queries that it makes.
+///
+/// This applies to every attempt of every query defined with
+/// `mononoke_queries!` that is run by the future itself, but not to queries
+/// made by tasks it spawns, nor to queries run within a transaction.
+pub async fn with_sql_fault_injection<F: Future>(
+    injector: Arc<FaultInjector>,
+    fut: F,
+) -> F::Output {
+    SQL_FAULT_INJECTOR.scope(injector, fut).await
+}
+
+pub(crate) async fn inject_sql_fault() -> Result<()> {
+    let injector = SQL_FAULT_INJECTOR
+        .try_with(|injector| injector.clone())
+        .ok();
+    if let Some(injector) = injector {
+        injector.inject_failure("SQL query").await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use fault_injection::FaultConfig;
+    use sql::Connection;
+
+    use super::*;
+    use crate::open_sqlite_in_memory;
+
+    crate::mononoke_queries! {
+        read SelectOne() -> (i64) {
+            "SELECT 1"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sql_fault_injection() -> Result<()> {
+        let connection = Connection::with_sqlite(open_sqlite_in_memory()?);
+        let injector = Arc::new(FaultInjector::new(
+            0,
+            FaultConfig {
+                error_probability: 1.0,
+                ..Default::default()
+            },
+        ));
+
+        let res = with_sql_fault_injection(injector.clone(), SelectOne::query(&connection)).await;
+        assert!(res.is_err());
+        assert_eq!(injector.injected_faults(), 1);
+
+        // Queries outside of the scope are unaffected.
+        assert_eq!(SelectOne::query(&connection).await?, vec![(1,)]);
+        assert_eq!(injector.injected_faults(), 1);
+        Ok(())
+    }
+}
@@ -5,12 +5,14 @@
  * GNU General Public License version 2.
  */
 
+mod faults;
 mod mononoke_queries;
 #[cfg(not(fbcode_build))]
 mod oss;
 pub mod replication;
 mod sqlite;
 
+pub use faults::with_sql_fault_injection;
 pub use sql::SqlConnections;
 pub use sql::SqlShardedConnections;
 use sql::Transaction;
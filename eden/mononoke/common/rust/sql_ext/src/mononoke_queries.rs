@@ -20,13 +20,38 @@ use caching_ext::*;
 use maplit::hashmap;
 use maplit::hashset;
 use memcache::KeyGen;
-use retry::retry;
 use retry::RetryLogic;
+use retry::RetryPolicy;
 use sql_query_config::CachingConfig;
 use tunables::tunables;
 
+use crate::faults::inject_sql_fault;
+
 const RETRY_ATTEMPTS: usize = 2;
 
+/// Retry policy for queries, unless the number of attempts is overridden
+/// by the `sql_retry_max_attempts` tunable.
+// See https://fburl.com/7dmedu1u for backoff reasoning
+const SQL_RETRY_POLICY: RetryPolicy = RetryPolicy::new(
+    "sql",
+    RETRY_ATTEMPTS,
+    RetryLogic::ExponentialWithJitter {
+        base: Duration::from_secs(10),
+        factor: 1.2,
+        jitter: Duration::from_secs(5),
+    },
+);
+
+fn sql_retry_policy() -> RetryPolicy {
+    match tunables().get_sql_retry_max_attempts() {
+        attempts if attempts > 0 => RetryPolicy {
+            max_attempts: attempts as usize,
+            ..SQL_RETRY_POLICY
+        },
+        _ => SQL_RETRY_POLICY,
+    }
+}
+
 // This wraps around rust/shed/sql::queries, check that macro: https://fburl.com/code/semq9xm3
 /// Define SQL queries that automatically retry on certain errors.
 ///
@@ -418,23 +443,17 @@ where
     T: Send + 'static,
     Fut: Future<Output = Result<T>>,
 {
+    let do_query = &do_query;
+    let attempt = move || async move {
+        inject_sql_fault().await?;
+        do_query().await
+    };
     if tunables().get_disable_sql_auto_retries() {
-        return do_query().await;
+        return attempt().await;
     }
-    Ok(retry(
-        None,
-        |_| do_query(),
-        should_retry_mysql_query,
-        // See https://fburl.com/7dmedu1u for backoff reasoning
-        RetryLogic::ExponentialWithJitter {
-            base: Duration::from_secs(10),
-            factor: 1.2,
-            jitter: Duration::from_secs(5),
-        },
-        RETRY_ATTEMPTS,
-    )
-    .await?
-    .0)
+    sql_retry_policy()
+        .run(|_| attempt(), should_retry_mysql_query)
+        .await
 }
 
 pub async fn query_with_retry<T, Fut>(
@@ -9,7 +9,10 @@ use std::collections::hash_map::Entry;
 use std::io::Error as IoError;
 use std::num::NonZeroU64;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use anyhow::Result;
 use fbinit::FacebookInit;
@@ -30,6 +33,13 @@ pub use scuba::ScubaValue;
 use time_ext::DurationExt;
 use tunables::tunables;
 
+mod sink;
+
+pub use crate::sink::JsonlFileSink;
+pub use crate::sink::ScubaSampleSink;
+pub use crate::sink::StdoutSink;
+pub use crate::sink::SCHEMA_VERSION;
+
 const FILE_PREFIX: &str = "file://";
 
 /// An extensible wrapper struct around `ScubaSampleBuilder`
@@ -40,6 +50,9 @@ pub struct MononokeScubaSampleBuilder {
     // This field decides if sampled out requests should
     // still be logged when verbose logging is enabled
     fallback_sampled_out_to_verbose: bool,
+    // Sink that logged samples are also written to, with the name of the
+    // dataset they are written as
+    maybe_sink: Option<(Arc<str>, Arc<dyn ScubaSampleSink>)>,
 }
 
 impl std::fmt::Debug for MononokeScubaSampleBuilder {
@@ -67,6 +80,7 @@ impl MononokeScubaSampleBuilder {
             inner: Self::get_scuba_sample_builder(fb, get_scuba_logging_type(scuba_table))?,
             maybe_observability_context: None,
             fallback_sampled_out_to_verbose: false,
+            maybe_sink: None,
         })
     }
 
@@ -75,6 +89,7 @@ impl MononokeScubaSampleBuilder {
             inner: ScubaSampleBuilder::with_discard(),
             maybe_observability_context: None,
             fallback_sampled_out_to_verbose: false,
+            maybe_sink: None,
         }
     }
 
@@ -92,6 +107,24 @@ impl MononokeScubaSampleBuilder {
         }
     }
 
+    /// Also write the samples that are logged to `sink`, as `dataset`.
+    /// This is how samples are kept by deployments without scuba.
+    pub fn with_sink(self, dataset: &str, sink: Arc<dyn ScubaSampleSink>) -> Self {
+        Self {
+            maybe_sink: Some((dataset.into(), sink)),
+            ..self
+        }
+    }
+
+    fn write_to_sink(&self, time: u64) {
+        if let Some((dataset, sink)) = &self.maybe_sink {
+            let mut sample = self.inner.get_sample().clone();
+            sample.add("time", time);
+            // As with scuba, samples are logged on a best effort basis.
+            let _ = sink.write(dataset, &sample);
+        }
+    }
+
     fn get_scuba_sample_builder(
         fb: FacebookInit,
         scuba_logging_type: ScubaLoggingType,
@@ -164,6 +197,26 @@ impl MononokeScubaSampleBuilder {
             .add_opt("client_tw_job", metadata.clientinfo_tw_job());
         self.inner
             .add_opt("client_tw_task", metadata.clientinfo_tw_task());
+        self.inner
+            .add_opt("client_version", metadata.client_version());
+        if !metadata.client_features().is_empty() {
+            self.inner
+                .add("client_features", metadata.client_features().to_vec());
+        }
+        self.inner.add_opt(
+            "client_clock_offset_secs",
+            metadata.client_clock_offset_secs(),
+        );
+        if let Some(preamble) = metadata.client_preamble() {
+            self.inner.add("client_preamble_version", preamble.version);
+            // Only log the names of fields we don't understand, as the
+            // values could be anything.
+            let unknown_fields: Vec<_> = preamble.unknown_fields().map(str::to_string).collect();
+            if !unknown_fields.is_empty() {
+                self.inner
+                    .add("client_preamble_unknown_fields", unknown_fields);
+            }
+        }
 
         self
     }
@@ -199,7 +252,7 @@ impl MononokeScubaSampleBuilder {
 
             self.inner.add("msg", msg);
         }
-        self.inner.log();
+        self.log();
     }
 
     /// Same as `log_with_msg`, but sample is assumed to be verbose and is only logged
@@ -238,7 +291,7 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn is_discard(&self) -> bool {
-        self.inner.is_discard()
+        self.inner.is_discard() && self.maybe_sink.is_none()
     }
 
     pub fn sampled(&mut self, sample_rate: NonZeroU64) -> &mut Self {
@@ -259,7 +312,10 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn log(&mut self) -> bool {
-        self.inner.log()
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        self.log_with_time(time)
     }
 
     /// Same as `log`, but sample is assumed to be verbose and is only logged
@@ -303,7 +359,11 @@ impl MononokeScubaSampleBuilder {
     }
 
     pub fn log_with_time(&mut self, time: u64) -> bool {
-        self.inner.log_with_time(time)
+        let logged = self.inner.log_with_time(time);
+        if logged {
+            self.write_to_sink(time);
+        }
+        logged
     }
 
     pub fn entry<K: Into<String>>(&mut self, key: K) -> Entry<String, ScubaValue> {
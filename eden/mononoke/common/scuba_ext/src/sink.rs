@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sinks that samples can be written to in addition to scuba, so that
+//! deployments without scuba can keep them.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use scuba::ScubaSample;
+use serde_json::Value;
+
+/// Version of the layout of the samples written to sinks.  Bump this when
+/// columns are renamed or change meaning, so that consumers of the samples
+/// can tell the layouts apart.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// A destination for scuba samples.
+pub trait ScubaSampleSink: Send + Sync {
+    /// Write a sample that was logged to `dataset`.
+    fn write(&self, dataset: &str, sample: &ScubaSample) -> Result<()>;
+}
+
+/// Render a sample as a single JSON line, tagged with the dataset and the
+/// schema version.
+fn to_json_line(dataset: &str, sample: &ScubaSample) -> Result<Vec<u8>> {
+    let mut json = sample.to_json()?;
+    if let Value::Object(object) = &mut json {
+        object.insert("dataset".to_string(), Value::from(dataset));
+        object.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    }
+    let mut line = serde_json::to_vec(&json)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Sink that writes samples to stdout as JSON Lines.
+pub struct StdoutSink;
+
+impl ScubaSampleSink for StdoutSink {
+    fn write(&self, dataset: &str, sample: &ScubaSample) -> Result<()> {
+        let line = to_json_line(dataset, sample)?;
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        stdout.write_all(&line)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+struct JsonlFile {
+    file: File,
+    size: u64,
+}
+
+/// Sink that appends samples to a file as JSON Lines.  Once the file
+/// reaches `max_size` bytes it is rotated: `path` is renamed to `path.1`,
+/// `path.1` to `path.2` and so on, keeping at most `max_files` old files.
+pub struct JsonlFileSink {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    current: Mutex<JsonlFile>,
+}
+
+impl JsonlFileSink {
+    pub fn new(path: impl AsRef<Path>, max_size: u64, max_files: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let current = Self::open(&path)?;
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            current: Mutex::new(current),
+        })
+    }
+
+    fn open(path: &Path) -> Result<JsonlFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open scuba sample file {}", path.display()))?;
+        let size = file.metadata()?.len();
+        Ok(JsonlFile { file, size })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&self, current: &mut JsonlFile) -> Result<()> {
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        *current = Self::open(&self.path)?;
+        Ok(())
+    }
+}
+
+impl ScubaSampleSink for JsonlFileSink {
+    fn write(&self, dataset: &str, sample: &ScubaSample) -> Result<()> {
+        let line = to_json_line(dataset, sample)?;
+        let mut current = self.current.lock().expect("lock poisoned");
+        if current.size > 0 && current.size + line.len() as u64 > self.max_size {
+            self.rotate(&mut current)?;
+        }
+        current.file.write_all(&line)?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(value: &str) -> ScubaSample {
+        let mut sample = ScubaSample::new();
+        sample.add("value", value);
+        sample
+    }
+
+    fn read_lines(path: &Path) -> Result<Vec<Value>> {
+        std::fs::read_to_string(path)?
+            .lines()
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    #[test]
+    fn test_json_line() -> Result<()> {
+        let line = to_json_line("dataset", &sample("a"))?;
+        assert_eq!(line.last(), Some(&b'\n'));
+        let json: Value = serde_json::from_slice(&line)?;
+        assert_eq!(json["dataset"], Value::from("dataset"));
+        assert_eq!(json["schema_version"], Value::from(SCHEMA_VERSION));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_file_sink() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("samples.jsonl");
+        let sink = JsonlFileSink::new(&path, u64::MAX, 2)?;
+        sink.write("first", &sample("a"))?;
+        sink.write("second", &sample("b"))?;
+
+        let lines = read_lines(&path)?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["dataset"], Value::from("first"));
+        assert_eq!(lines[1]["dataset"], Value::from("second"));
+
+        // Reopening the file appends to it.
+        let sink = JsonlFileSink::new(&path, u64::MAX, 2)?;
+        sink.write("third", &sample("c"))?;
+        assert_eq!(read_lines(&path)?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_file_sink_rotation() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("samples.jsonl");
+        // Every sample is larger than the limit, so each is written to a
+        // new file.
+        let sink = JsonlFileSink::new(&path, 1, 2)?;
+        for dataset in ["first", "second", "third", "fourth"] {
+            sink.write(dataset, &sample("a"))?;
+        }
+
+        assert_eq!(read_lines(&path)?[0]["dataset"], Value::from("fourth"));
+        assert_eq!(
+            read_lines(&sink.rotated_path(1))?[0]["dataset"],
+            Value::from("third")
+        );
+        assert_eq!(
+            read_lines(&sink.rotated_path(2))?[0]["dataset"],
+            Value::from("second")
+        );
+        assert!(!sink.rotated_path(3).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_file_sink_without_old_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("samples.jsonl");
+        let sink = JsonlFileSink::new(&path, 1, 0)?;
+        sink.write("first", &sample("a"))?;
+        sink.write("second", &sample("b"))?;
+
+        let lines = read_lines(&path)?;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["dataset"], Value::from("second"));
+        assert!(!sink.rotated_path(1).exists());
+        Ok(())
+    }
+}
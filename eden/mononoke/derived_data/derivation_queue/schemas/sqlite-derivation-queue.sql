@@ -0,0 +1,23 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `derivation_queue` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `cs_id` BINARY(32) NOT NULL,
+  `derived_data_type` VARCHAR(255) NOT NULL,
+  `enqueued_at` BIGINT NOT NULL,
+  /* time the task can next be claimed by a worker */
+  `available_at` BIGINT NOT NULL,
+  `attempts` INTEGER NOT NULL DEFAULT 0,
+  `dead_letter` TINYINT NOT NULL DEFAULT 0,
+  `last_error` TEXT NULL,
+  UNIQUE (`repo_id`, `cs_id`, `derived_data_type`)
+);
+
+CREATE INDEX IF NOT EXISTS `derivation_queue_available`
+  ON `derivation_queue` (`repo_id`, `dead_letter`, `available_at`);
@@ -0,0 +1,477 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Persistent queue of derived data to derive asynchronously.
+//!
+//! Accepted pushes enqueue a task for each derived data type the repo is
+//! configured to derive asynchronously, so that the push doesn't have to
+//! wait for derivation.  Workers claim tasks from the queue, derive the data
+//! and then complete the task.
+//!
+//! A claimed task is leased to the worker that claimed it.  If the worker
+//! doesn't complete the task before the lease expires (e.g. because it was
+//! restarted), the task becomes available to other workers again.  Each
+//! claim counts as an attempt.  Tasks that fail are retried with
+//! exponential backoff until they run out of attempts, at which point they
+//! are moved to the dead letter queue, where they stay until they are
+//! requeued.
+
+use std::time::Duration;
+
+use anyhow::Error;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// Delay before the first retry of a failed task.  The delay doubles with
+/// each further attempt.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Maximum delay between retries of a failed task.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+#[facet::facet]
+pub struct DerivationQueue {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+pub struct DerivationQueueBuilder {
+    connections: SqlConnections,
+}
+
+/// A request to derive a derived data type for a changeset.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DerivationTask {
+    pub id: u64,
+    pub cs_id: ChangesetId,
+    pub derived_data_type: String,
+    /// Number of times the task has been claimed.
+    pub attempts: u32,
+    pub enqueued_at: Timestamp,
+    /// The error the most recent failed attempt failed with.
+    pub last_error: Option<String>,
+}
+
+/// What happened to a task that failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FailedTask {
+    /// The task will be retried after a delay.
+    Retry { delay: Duration },
+    /// The task has run out of attempts and has been moved to the dead
+    /// letter queue.
+    DeadLettered,
+}
+
+type DerivationTaskRow = (
+    u64,
+    ChangesetId,
+    Vec<u8>,
+    u64,
+    Timestamp,
+    Timestamp,
+    Option<Vec<u8>>,
+);
+
+mononoke_queries! {
+    write InsertTasks(values: (
+        repo_id: RepositoryId,
+        cs_id: ChangesetId,
+        derived_data_type: &str,
+        enqueued_at: Timestamp,
+        available_at: Timestamp,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO derivation_queue
+         (repo_id, cs_id, derived_data_type, enqueued_at, available_at)
+         VALUES {values}"
+    }
+
+    read SelectAvailableTasks(repo_id: RepositoryId, now: Timestamp, limit: u64) -> (
+        u64, ChangesetId, Vec<u8>, u64, Timestamp, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, cs_id, derived_data_type, attempts, enqueued_at, available_at, last_error
+         FROM derivation_queue
+         WHERE repo_id = {repo_id} AND dead_letter = 0 AND available_at <= {now}
+         ORDER BY id
+         LIMIT {limit}"
+    }
+
+    read SelectDeadLetters(repo_id: RepositoryId, limit: u64) -> (
+        u64, ChangesetId, Vec<u8>, u64, Timestamp, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, cs_id, derived_data_type, attempts, enqueued_at, available_at, last_error
+         FROM derivation_queue
+         WHERE repo_id = {repo_id} AND dead_letter = 1
+         ORDER BY id
+         LIMIT {limit}"
+    }
+
+    write ClaimTask(
+        repo_id: RepositoryId,
+        id: u64,
+        available_at: Timestamp,
+        lease_expiry: Timestamp,
+    ) {
+        none,
+        "UPDATE derivation_queue
+         SET available_at = {lease_expiry}, attempts = attempts + 1
+         WHERE repo_id = {repo_id} AND id = {id} AND dead_letter = 0
+           AND available_at = {available_at}"
+    }
+
+    write DeleteTask(repo_id: RepositoryId, id: u64) {
+        none,
+        "DELETE FROM derivation_queue WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    write RetryTask(repo_id: RepositoryId, id: u64, available_at: Timestamp, last_error: &str) {
+        none,
+        "UPDATE derivation_queue
+         SET available_at = {available_at}, last_error = {last_error}
+         WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    write DeadLetterTask(repo_id: RepositoryId, id: u64, last_error: &str) {
+        none,
+        "UPDATE derivation_queue
+         SET dead_letter = 1, last_error = {last_error}
+         WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    write RequeueDeadLetters(repo_id: RepositoryId, now: Timestamp) {
+        none,
+        "UPDATE derivation_queue
+         SET dead_letter = 0, attempts = 0, available_at = {now}
+         WHERE repo_id = {repo_id} AND dead_letter = 1"
+    }
+}
+
+impl SqlConstruct for DerivationQueueBuilder {
+    const LABEL: &'static str = "derivation-queue";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-derivation-queue.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for DerivationQueueBuilder {}
+
+impl DerivationQueueBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> DerivationQueue {
+        DerivationQueue {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn task_from_row(
+    (id, cs_id, derived_data_type, attempts, enqueued_at, _available_at, last_error): DerivationTaskRow,
+) -> DerivationTask {
+    DerivationTask {
+        id,
+        cs_id,
+        derived_data_type: utf8(derived_data_type),
+        attempts: attempts as u32,
+        enqueued_at,
+        last_error: last_error.map(utf8),
+    }
+}
+
+/// Delay before retrying a task that has failed after the given number of
+/// attempts.
+fn retry_delay(attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    (BASE_RETRY_DELAY * 2u32.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+impl DerivationQueue {
+    /// Enqueue tasks to derive each of the given derived data types for a
+    /// changeset.  Types that are already queued for the changeset are
+    /// skipped.
+    pub async fn enqueue(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        derived_data_types: &[String],
+    ) -> Result<(), Error> {
+        if derived_data_types.is_empty() {
+            return Ok(());
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let now = Timestamp::from(ctx.now());
+        let derived_data_types = derived_data_types
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let rows = derived_data_types
+            .iter()
+            .map(|derived_data_type| (&self.repo_id, &cs_id, derived_data_type, &now, &now))
+            .collect::<Vec<_>>();
+        InsertTasks::query(&self.connections.write_connection, &rows).await?;
+        Ok(())
+    }
+
+    /// Claim up to `limit` available tasks, leasing them for the given
+    /// duration.  Tasks that are claimed concurrently by another worker are
+    /// skipped.
+    pub async fn claim(
+        &self,
+        ctx: &CoreContext,
+        limit: u64,
+        lease: Duration,
+    ) -> Result<Vec<DerivationTask>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+
+        let now = ctx.now();
+        let lease_expiry = Timestamp::from(now + lease);
+        let rows = SelectAvailableTasks::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &Timestamp::from(now),
+            &limit,
+        )
+        .await?;
+
+        let mut claimed = Vec::with_capacity(rows.len());
+        for row in rows {
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlWrites);
+            let res = ClaimTask::query(
+                &self.connections.write_connection,
+                &self.repo_id,
+                &row.0,
+                &row.5,
+                &lease_expiry,
+            )
+            .await?;
+            if res.affected_rows() == 1 {
+                let mut task = task_from_row(row);
+                task.attempts += 1;
+                claimed.push(task);
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Remove a task that has been completed from the queue.
+    pub async fn complete(&self, ctx: &CoreContext, task: &DerivationTask) -> Result<(), Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        DeleteTask::query(&self.connections.write_connection, &self.repo_id, &task.id).await?;
+        Ok(())
+    }
+
+    /// Record that an attempt at a task has failed.  The task is retried
+    /// after a delay, unless it has made `max_attempts` attempts, in which
+    /// case it is moved to the dead letter queue.
+    pub async fn fail(
+        &self,
+        ctx: &CoreContext,
+        task: &DerivationTask,
+        error: &str,
+        max_attempts: u32,
+    ) -> Result<FailedTask, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let conn = &self.connections.write_connection;
+        if task.attempts >= max_attempts {
+            DeadLetterTask::query(conn, &self.repo_id, &task.id, &error).await?;
+            return Ok(FailedTask::DeadLettered);
+        }
+        let delay = retry_delay(task.attempts);
+        let available_at = Timestamp::from(ctx.now() + delay);
+        RetryTask::query(conn, &self.repo_id, &task.id, &available_at, &error).await?;
+        Ok(FailedTask::Retry { delay })
+    }
+
+    /// List the tasks in the dead letter queue, oldest first.
+    pub async fn dead_letters(
+        &self,
+        ctx: &CoreContext,
+        limit: u64,
+    ) -> Result<Vec<DerivationTask>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows =
+            SelectDeadLetters::query(&self.connections.read_connection, &self.repo_id, &limit)
+                .await?;
+        Ok(rows.into_iter().map(task_from_row).collect())
+    }
+
+    /// Move all of the tasks in the dead letter queue back to the queue,
+    /// with their attempts reset.  Returns the number of tasks requeued.
+    pub async fn requeue_dead_letters(&self, ctx: &CoreContext) -> Result<u64, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let res = RequeueDeadLetters::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &Timestamp::from(ctx.now()),
+        )
+        .await?;
+        Ok(res.affected_rows())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use context::SessionContainer;
+    use context::TestClock;
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    const LEASE: Duration = Duration::from_secs(600);
+
+    fn types(types: &[&str]) -> Vec<String> {
+        types.iter().map(|ty| ty.to_string()).collect()
+    }
+
+    #[fbinit::test]
+    async fn test_claim_and_complete(fb: FacebookInit) -> Result<(), Error> {
+        let clock = Arc::new(TestClock::at_unix_secs(1000));
+        let ctx = CoreContext::test_mock_session(
+            SessionContainer::builder(fb).clock(clock.clone()).build(),
+        );
+        let queue = DerivationQueueBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        queue
+            .enqueue(&ctx, ONES_CSID, &types(&["blame", "fsnodes"]))
+            .await?;
+        // Enqueueing the same work again is a no-op.
+        queue.enqueue(&ctx, ONES_CSID, &types(&["blame"])).await?;
+        queue.enqueue(&ctx, TWOS_CSID, &types(&["blame"])).await?;
+
+        let claimed = queue.claim(&ctx, 2, LEASE).await?;
+        assert_eq!(
+            claimed
+                .iter()
+                .map(|task| (task.cs_id, task.derived_data_type.as_str(), task.attempts))
+                .collect::<Vec<_>>(),
+            vec![(ONES_CSID, "blame", 1), (ONES_CSID, "fsnodes", 1)]
+        );
+
+        // Claimed tasks are leased, so can't be claimed by another worker.
+        let rest = queue.claim(&ctx, 10, LEASE).await?;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].cs_id, TWOS_CSID);
+        assert!(queue.claim(&ctx, 10, LEASE).await?.is_empty());
+
+        // Tasks whose lease expires without being completed are available
+        // to other workers again.
+        queue.complete(&ctx, &claimed[0]).await?;
+        queue.complete(&ctx, &rest[0]).await?;
+        clock.advance(LEASE);
+        let reclaimed = queue.claim(&ctx, 10, LEASE).await?;
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].derived_data_type, "fsnodes");
+        assert_eq!(reclaimed[0].attempts, 2);
+
+        queue.complete(&ctx, &reclaimed[0]).await?;
+        clock.advance(LEASE);
+        assert!(queue.claim(&ctx, 10, LEASE).await?.is_empty());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_retry_and_dead_letter(fb: FacebookInit) -> Result<(), Error> {
+        let clock = Arc::new(TestClock::at_unix_secs(1000));
+        let ctx = CoreContext::test_mock_session(
+            SessionContainer::builder(fb).clock(clock.clone()).build(),
+        );
+        let queue = DerivationQueueBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        queue.enqueue(&ctx, ONES_CSID, &types(&["blame"])).await?;
+
+        let task = queue.claim(&ctx, 1, LEASE).await?.remove(0);
+        assert_eq!(
+            queue.fail(&ctx, &task, "first failure", 3).await?,
+            FailedTask::Retry {
+                delay: BASE_RETRY_DELAY
+            }
+        );
+        // The task is not available until the retry delay has passed.
+        assert!(queue.claim(&ctx, 1, LEASE).await?.is_empty());
+        clock.advance(BASE_RETRY_DELAY);
+
+        let task = queue.claim(&ctx, 1, LEASE).await?.remove(0);
+        assert_eq!(task.last_error.as_deref(), Some("first failure"));
+        assert_eq!(
+            queue.fail(&ctx, &task, "second failure", 3).await?,
+            FailedTask::Retry {
+                delay: BASE_RETRY_DELAY * 2
+            }
+        );
+        clock.advance(BASE_RETRY_DELAY * 2);
+
+        let task = queue.claim(&ctx, 1, LEASE).await?.remove(0);
+        assert_eq!(
+            queue.fail(&ctx, &task, "third failure", 3).await?,
+            FailedTask::DeadLettered
+        );
+        clock.advance(MAX_RETRY_DELAY);
+        assert!(queue.claim(&ctx, 1, LEASE).await?.is_empty());
+
+        let dead_letters = queue.dead_letters(&ctx, 10).await?;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].attempts, 3);
+        assert_eq!(dead_letters[0].last_error.as_deref(), Some("third failure"));
+
+        assert_eq!(queue.requeue_dead_letters(&ctx).await?, 1);
+        assert!(queue.dead_letters(&ctx, 10).await?.is_empty());
+        let task = queue.claim(&ctx, 1, LEASE).await?.remove(0);
+        assert_eq!(task.attempts, 1);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let zero = DerivationQueueBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        zero.enqueue(&ctx, ONES_CSID, &types(&["blame"])).await?;
+        let one = DerivationQueue {
+            connections: zero.connections.clone(),
+            repo_id: REPO_ONE,
+        };
+        assert!(one.claim(&ctx, 10, LEASE).await?.is_empty());
+        assert_eq!(zero.claim(&ctx, 10, LEASE).await?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(5), Duration::from_secs(480));
+        assert_eq!(retry_delay(8), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+}
@@ -17,6 +17,7 @@ use cacheblob::MemWritesBlobstore;
 use context::CoreContext;
 use filenodes::Filenodes;
 use futures::future::try_join_all;
+use hg_sha256_mapping::HgSha256Mapping;
 use metaconfig_types::DerivedDataTypesConfig;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
@@ -211,6 +212,10 @@ impl DerivationContext {
         self.manager.filenodes()
     }
 
+    pub fn hg_sha256_mapping(&self) -> Option<&dyn HgSha256Mapping> {
+        self.manager.hg_sha256_mapping()
+    }
+
     /// The config that should be used for derivation.
     pub fn config(&self) -> &DerivedDataTypesConfig {
         self.manager.config()
@@ -15,6 +15,7 @@ use changesets::Changesets;
 use context::CoreContext;
 use derived_data_remote::DerivationClient;
 use filenodes::Filenodes;
+use hg_sha256_mapping::HgSha256Mapping;
 use metaconfig_types::DerivedDataTypesConfig;
 use mononoke_types::ChangesetId;
 use mononoke_types::RepositoryId;
@@ -45,6 +46,7 @@ pub struct DerivedDataManagerInner {
     changesets: Arc<dyn Changesets>,
     bonsai_hg_mapping: Option<Arc<dyn BonsaiHgMapping>>,
     filenodes: Option<Arc<dyn Filenodes>>,
+    hg_sha256_mapping: Option<Arc<dyn HgSha256Mapping>>,
     repo_blobstore: RepoBlobstore,
     lease: DerivedDataLease,
     scuba: MononokeScubaSampleBuilder,
@@ -105,6 +107,7 @@ impl DerivedDataManager {
                 changesets,
                 bonsai_hg_mapping: Some(bonsai_hg_mapping),
                 filenodes: Some(filenodes),
+                hg_sha256_mapping: None,
                 repo_blobstore,
                 lease,
                 scuba,
@@ -167,6 +170,16 @@ impl DerivedDataManager {
         }
     }
 
+    /// Record the SHA-256 ids of the hg changesets this manager derives.
+    pub fn with_hg_sha256_mapping(&self, hg_sha256_mapping: Arc<dyn HgSha256Mapping>) -> Self {
+        Self {
+            inner: Arc::new(DerivedDataManagerInner {
+                hg_sha256_mapping: Some(hg_sha256_mapping),
+                ..self.inner.as_ref().clone()
+            }),
+        }
+    }
+
     pub fn with_replaced_config(
         &self,
         config_name: String,
@@ -224,6 +237,10 @@ impl DerivedDataManager {
         self.inner.filenodes.as_deref().context("Missing filenodes")
     }
 
+    pub fn hg_sha256_mapping(&self) -> Option<&dyn HgSha256Mapping> {
+        self.inner.hg_sha256_mapping.as_deref()
+    }
+
     pub fn derivation_service_client(&self) -> Option<&dyn DerivationClient> {
         self.inner.derivation_service_client.as_deref()
     }
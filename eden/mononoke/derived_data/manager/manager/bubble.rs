@@ -61,6 +61,7 @@ impl DerivedDataManager {
                                 .wrap_repo_blobstore(self.inner.repo_blobstore.clone()),
                             filenodes: None,
                             bonsai_hg_mapping: None,
+                            hg_sha256_mapping: None,
                             ..self.inner.as_ref().clone()
                         }),
                     },
@@ -25,6 +25,7 @@ use derived_data_manager::BonsaiDerivable;
 use derived_data_manager::DerivableType;
 use derived_data_manager::DerivationContext;
 use futures::future::try_join_all;
+use hg_sha256_mapping::record_hg_changeset;
 use mercurial_types::HgChangesetId;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
@@ -170,6 +171,9 @@ impl BonsaiDerivable for MappedHgChangesetId {
         derivation_ctx: &DerivationContext,
         changeset_id: ChangesetId,
     ) -> Result<()> {
+        if let Some(hg_sha256_mapping) = derivation_ctx.hg_sha256_mapping() {
+            record_hg_changeset(ctx, hg_sha256_mapping, derivation_ctx.blobstore(), self.0).await?;
+        }
         derivation_ctx
             .bonsai_hg_mapping()?
             .add(
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::time::Duration;
@@ -370,6 +371,18 @@ impl EdenApiHandler for UploadBonsaiChangesetHandler {
             })
             .try_collect()
             .await?;
+        // Collecting the changes directly into a map would silently drop all
+        // but one change to a path that is listed more than once.
+        let mut file_changes = BTreeMap::new();
+        for (path, fc) in cs.file_changes {
+            let create_change = to_create_change(fc, bubble_id)
+                .with_context(|| anyhow!("Parsing file changes for {}", path))?;
+            let path = to_mononoke_path(path)?;
+            if file_changes.contains_key(&path) {
+                return Err(anyhow!("Path {} is changed more than once", path).into());
+            }
+            file_changes.insert(path, create_change);
+        }
         let cs_id = repo
             .repo()
             .create_changeset(
@@ -380,14 +393,7 @@ impl EdenApiHandler for UploadBonsaiChangesetHandler {
                 None,
                 cs.message,
                 cs.extra.into_iter().map(|e| (e.key, e.value)).collect(),
-                cs.file_changes
-                    .into_iter()
-                    .map(|(path, fc)| {
-                        let create_change = to_create_change(fc, bubble_id)
-                            .with_context(|| anyhow!("Parsing file changes for {}", path))?;
-                        Ok((to_mononoke_path(path)?, create_change))
-                    })
-                    .collect::<anyhow::Result<_>>()?,
+                file_changes,
                 match bubble_id {
                     Some(id) => Some(repo.open_bubble(id).await?),
                     None => None,
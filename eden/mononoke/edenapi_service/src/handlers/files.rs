@@ -48,6 +48,7 @@ use mercurial_types::HgNodeHash;
 use mononoke_api_hg::HgDataContext;
 use mononoke_api_hg::HgDataId;
 use mononoke_api_hg::HgRepoContext;
+use mononoke_types::RepoPath;
 use rate_limiting::Metric;
 use serde::Deserialize;
 use types::Key;
@@ -61,6 +62,7 @@ use crate::errors::ErrorKind;
 use crate::middleware::RequestContext;
 use crate::utils::cbor_stream_filtered_errors;
 use crate::utils::get_repo;
+use crate::utils::to_mpath;
 
 /// XXX: This number was chosen arbitrarily.
 const MAX_CONCURRENT_FILE_FETCHES_PER_REQUEST: usize = 10;
@@ -207,7 +209,12 @@ async fn fetch_file(
     key: Key,
     attrs: FileAttributes,
 ) -> Result<FileEntry, Error> {
+    let path = match to_mpath(&key.path)? {
+        Some(path) => RepoPath::FilePath(path),
+        None => RepoPath::RootPath,
+    };
     let id = HgFileNodeId::from_node_hash(HgNodeHash::from(key.hgid));
+    repo.repo().require_protected_node_read(&path, id).await?;
 
     let ctx = id
         .context(repo)
@@ -231,16 +238,16 @@ async fn fetch_file(
     }
 
     if attrs.aux_data {
-        let content_metadata = ctx
-            .content_metadata()
-            .await
-            .with_context(|| ErrorKind::FileFetchFailed(key.clone()))?;
+        let (content_metadata, content_metadata_v2) =
+            futures::try_join!(ctx.content_metadata(), ctx.content_metadata_v2_readonly())
+                .with_context(|| ErrorKind::FileFetchFailed(key.clone()))?;
 
         file = file.with_aux_data(FileAuxData {
             total_size: content_metadata.total_size,
             content_id: content_metadata.content_id.into(),
             sha1: content_metadata.sha1.into(),
             sha256: content_metadata.sha256.into(),
+            kind: content_metadata_v2.map(Into::into),
         });
     }
 
@@ -43,6 +43,8 @@ use mononoke_api_hg::HgDataContext;
 use mononoke_api_hg::HgDataId;
 use mononoke_api_hg::HgRepoContext;
 use mononoke_api_hg::HgTreeContext;
+use mononoke_types::MPath;
+use mononoke_types::RepoPath;
 use rate_limiting::Metric;
 use serde::Deserialize;
 use types::Key;
@@ -58,6 +60,7 @@ use crate::middleware::RequestContext;
 use crate::utils::custom_cbor_stream;
 use crate::utils::get_repo;
 use crate::utils::parse_wire_request;
+use crate::utils::to_mpath;
 
 /// XXX: This number was chosen arbitrarily.
 const MAX_CONCURRENT_TREE_FETCHES_PER_REQUEST: usize = 10;
@@ -120,7 +123,14 @@ async fn fetch_tree(
     key: Key,
     fetch_metadata: bool,
 ) -> Result<TreeEntry, Error> {
+    let path = match to_mpath(&key.path)? {
+        Some(path) => RepoPath::DirectoryPath(path),
+        None => RepoPath::RootPath,
+    };
     let id = HgManifestId::from_node_hash(HgNodeHash::from(key.hgid));
+    repo.repo()
+        .require_protected_node_read(&path, HgFileNodeId::new(id.into_nodehash()))
+        .await?;
 
     let ctx = id
         .context(repo.clone())
@@ -138,7 +148,7 @@ async fn fetch_tree(
 
     if fetch_metadata {
         let children: Vec<Result<TreeChildEntry, EdenApiServerError>> =
-            fetch_child_metadata_entries(&repo, &ctx)
+            fetch_child_metadata_entries(&repo, &ctx, path.mpath())
                 .await?
                 .buffer_unordered(MAX_CONCURRENT_METADATA_FETCHES_PER_TREE_FETCH)
                 .map(|r| r.map_err(|e| EdenApiServerError::with_key(key.clone(), e)))
@@ -154,9 +164,14 @@ async fn fetch_tree(
 async fn fetch_child_metadata_entries<'a>(
     repo: &'a HgRepoContext,
     ctx: &'a HgTreeContext,
+    path: Option<&MPath>,
 ) -> Result<impl Stream<Item = impl Future<Output = Result<TreeChildEntry, Error>> + 'a> + 'a, Error>
 {
     let entries = ctx.entries()?.collect::<Vec<_>>();
+    for (name, _) in entries.iter() {
+        repo.repo()
+            .require_protected_path_read(Some(&MPath::join_opt_element(path, name)))?;
+    }
 
     Ok(stream::iter(entries)
         // .entries iterator is not `Send`
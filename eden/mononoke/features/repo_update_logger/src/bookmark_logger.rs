@@ -21,6 +21,9 @@ use mononoke_types::ChangesetId;
 use repo_identity::RepoIdentityRef;
 use serde_derive::Serialize;
 
+use crate::event_publisher::publish_repo_event;
+use crate::event_publisher::RepoEvent;
+
 #[derive(Serialize)]
 pub enum BookmarkOperation {
     Create(ChangesetId),
@@ -135,13 +138,38 @@ pub async fn log_bookmark_operation(
     repo: &(impl RepoIdentityRef + RepoConfigRef),
     info: &BookmarkInfo,
 ) {
+    let plain_info = PlainBookmarkInfo::new(repo, info);
     if let Some(bookmark_logging_destination) = &repo
         .repo_config()
         .update_logging_config
         .bookmark_logging_destination
     {
-        PlainBookmarkInfo::new(repo, info)
-            .log(ctx, bookmark_logging_destination)
-            .await;
+        plain_info.log(ctx, bookmark_logging_destination).await;
+    }
+
+    // Event deliveries happen in the background, don't hold the push for them.
+    let _ = publish_repo_event(
+        ctx,
+        repo,
+        RepoEvent::BookmarkMoved {
+            bookmark: plain_info.bookmark_name.clone(),
+            operation: plain_info.operation.clone(),
+            old_value: plain_info.old_bookmark_value.clone(),
+            new_value: plain_info.new_bookmark_value.clone(),
+            reason: plain_info.update_reason.clone(),
+        },
+    );
+    if let (BookmarkUpdateReason::Push | BookmarkUpdateReason::Pushrebase, Some(new_value)) =
+        (info.reason, plain_info.new_bookmark_value)
+    {
+        let _ = publish_repo_event(
+            ctx,
+            repo,
+            RepoEvent::PushAccepted {
+                bookmark: plain_info.bookmark_name,
+                old_value: plain_info.old_bookmark_value,
+                new_value,
+            },
+        );
     }
 }
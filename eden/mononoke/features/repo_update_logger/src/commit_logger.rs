@@ -21,18 +21,23 @@ use futures::stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use logger_ext::Loggable;
+use metaconfig_types::RepoConfigArc;
 use metaconfig_types::RepoConfigRef;
 #[cfg(fbcode_build)]
 use mononoke_new_commit_rust_logger::MononokeNewCommitLogger;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use mononoke_types::Generation;
+use mutable_counters::MutableCountersArc;
 use once_cell::sync::Lazy;
 use permission_checker::MononokeIdentitySet;
 use regex::Regex;
+use repo_identity::RepoIdentityArc;
 use repo_identity::RepoIdentityRef;
 use serde_derive::Serialize;
 
+use crate::growth_alarms::record_new_commits;
+
 pub struct CommitInfo {
     changeset_id: ChangesetId,
     bubble_id: Option<NonZeroU64>,
@@ -224,10 +229,27 @@ impl Loggable for PlainCommitInfo {
 
 pub async fn log_new_commits(
     ctx: &CoreContext,
-    repo: &(impl RepoIdentityRef + ChangesetsRef + RepoConfigRef),
+    repo: &(
+         impl RepoIdentityRef
+         + RepoIdentityArc
+         + ChangesetsRef
+         + RepoConfigRef
+         + RepoConfigArc
+         + MutableCountersArc
+     ),
     bookmark: Option<(&BookmarkName, BookmarkKind)>,
     commit_infos: Vec<CommitInfo>,
 ) {
+    record_new_commits(
+        ctx,
+        repo,
+        commit_infos.len() as u64,
+        commit_infos
+            .iter()
+            .map(|info| info.changed_files_info.changed_files_size)
+            .sum(),
+    );
+
     let is_public = bookmark.map_or(false, |(_, kind)| kind.is_public());
     let legacy_category = if is_public {
         repo.repo_config()
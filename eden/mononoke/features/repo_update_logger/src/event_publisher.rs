@@ -0,0 +1,382 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Publish repo events to the external subscribers configured in
+//! `update_logging_config.event_subscriptions`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::Utc;
+use context::CoreContext;
+use futures::future::join_all;
+use hyper::client::HttpConnector;
+use hyper::Client;
+use hyper::Request;
+use hyper_openssl::HttpsConnector;
+use metaconfig_types::EventSink;
+use metaconfig_types::RepoConfigRef;
+use metaconfig_types::RepoEventKind;
+use repo_identity::RepoIdentityRef;
+use serde_derive::Serialize;
+use stats::prelude::*;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixStream;
+use tokio::task::JoinHandle;
+
+define_stats! {
+    prefix = "mononoke.repo_events";
+    delivered: dynamic_timeseries("{}.{}.delivered", (repo: String, sink: String); Rate, Sum),
+    delivery_failed: dynamic_timeseries("{}.{}.delivery_failed", (repo: String, sink: String); Rate, Sum),
+    delivery_retried: dynamic_timeseries("{}.{}.delivery_retried", (repo: String, sink: String); Rate, Sum),
+}
+
+const WEBHOOK_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An event in the repo that external subscribers may be interested in.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RepoEvent {
+    PushAccepted {
+        bookmark: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        old_value: Option<String>,
+        new_value: String,
+    },
+    BookmarkMoved {
+        bookmark: String,
+        operation: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        old_value: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        new_value: Option<String>,
+        reason: String,
+    },
+    HookRejected {
+        bookmark: String,
+        changeset_id: String,
+        hook_name: String,
+        description: String,
+    },
+    RedactionAdded {
+        key_list_id: String,
+        keys: usize,
+    },
+    GrowthAlarm {
+        metric: String,
+        value: u64,
+        threshold: u64,
+    },
+}
+
+impl RepoEvent {
+    pub fn kind(&self) -> RepoEventKind {
+        match self {
+            RepoEvent::PushAccepted { .. } => RepoEventKind::PushAccepted,
+            RepoEvent::BookmarkMoved { .. } => RepoEventKind::BookmarkMoved,
+            RepoEvent::HookRejected { .. } => RepoEventKind::HookRejected,
+            RepoEvent::RedactionAdded { .. } => RepoEventKind::RedactionAdded,
+            RepoEvent::GrowthAlarm { .. } => RepoEventKind::GrowthAlarm,
+        }
+    }
+
+    pub fn bookmark(&self) -> Option<&str> {
+        match self {
+            RepoEvent::PushAccepted { bookmark, .. }
+            | RepoEvent::BookmarkMoved { bookmark, .. }
+            | RepoEvent::HookRejected { bookmark, .. } => Some(bookmark),
+            RepoEvent::RedactionAdded { .. } | RepoEvent::GrowthAlarm { .. } => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EventEnvelope<'a> {
+    repo_name: &'a str,
+    timestamp: i64,
+    #[serde(flatten)]
+    event: &'a RepoEvent,
+}
+
+/// Deliveries of an event that are still in flight.
+///
+/// Dropping this doesn't cancel the deliveries. Short-lived processes that
+/// would exit before the deliveries complete should `wait` for them.
+#[must_use = "call .wait() to wait for delivery, or drop to deliver in the background"]
+pub struct EventDelivery(Vec<JoinHandle<()>>);
+
+impl EventDelivery {
+    pub async fn wait(self) {
+        join_all(self.0).await;
+    }
+}
+
+/// Publish an event to all the subscriptions of the repo that match it.
+/// Delivery to scribe is immediate, delivery to webhooks and unix sockets
+/// happens in the background.
+pub fn publish_repo_event(
+    ctx: &CoreContext,
+    repo: &(impl RepoIdentityRef + RepoConfigRef),
+    event: RepoEvent,
+) -> EventDelivery {
+    let kind = event.kind();
+    let subscriptions = repo
+        .repo_config()
+        .update_logging_config
+        .event_subscriptions
+        .iter()
+        .filter(|subscription| subscription.matches(kind, event.bookmark()))
+        .collect::<Vec<_>>();
+    let mut deliveries = Vec::new();
+    if subscriptions.is_empty() {
+        return EventDelivery(deliveries);
+    }
+
+    let repo_name = repo.repo_identity().name();
+    let envelope = EventEnvelope {
+        repo_name,
+        timestamp: Utc::now().timestamp(),
+        event: &event,
+    };
+    let payload = match serde_json::to_vec(&envelope) {
+        Ok(json) => Bytes::from(json),
+        Err(err) => {
+            ctx.scuba().clone().log_with_msg(
+                "Failed to serialize repo event",
+                Some(format!("{:?}: {}", event, err)),
+            );
+            return EventDelivery(deliveries);
+        }
+    };
+
+    for subscription in subscriptions {
+        match &subscription.sink {
+            EventSink::Scribe { scribe_category } => {
+                let res = std::str::from_utf8(&payload)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| ctx.scribe().offer(scribe_category, json));
+                match res {
+                    Ok(()) => {
+                        STATS::delivered.add_value(1, (repo_name.to_string(), "scribe".to_string()))
+                    }
+                    Err(err) => log_failure(ctx, repo_name, &subscription.sink, &err),
+                }
+            }
+            sink => {
+                let ctx = ctx.clone();
+                let sink = sink.clone();
+                let repo_name = repo_name.to_string();
+                let payload = payload.clone();
+                deliveries.push(tokio::spawn(async move {
+                    let res = match &sink {
+                        EventSink::Webhook { url, max_attempts } => {
+                            deliver_to_webhook(&repo_name, url, *max_attempts, payload).await
+                        }
+                        EventSink::UnixSocket { path } => {
+                            deliver_to_unix_socket(path, payload).await
+                        }
+                        EventSink::Scribe { .. } => unreachable!(),
+                    };
+                    match res {
+                        Ok(()) => {
+                            STATS::delivered.add_value(1, (repo_name, sink_name(&sink)));
+                        }
+                        Err(err) => log_failure(&ctx, &repo_name, &sink, &err),
+                    }
+                }));
+            }
+        }
+    }
+
+    EventDelivery(deliveries)
+}
+
+fn sink_name(sink: &EventSink) -> String {
+    match sink {
+        EventSink::Webhook { .. } => "webhook",
+        EventSink::Scribe { .. } => "scribe",
+        EventSink::UnixSocket { .. } => "unix_socket",
+    }
+    .to_string()
+}
+
+fn log_failure(ctx: &CoreContext, repo_name: &str, sink: &EventSink, err: &anyhow::Error) {
+    STATS::delivery_failed.add_value(1, (repo_name.to_string(), sink_name(sink)));
+    ctx.scuba().clone().log_with_msg(
+        "Failed to publish repo event",
+        Some(format!("sink: {:?}, error: {:#}", sink, err)),
+    );
+}
+
+async fn deliver_to_webhook(
+    repo_name: &str,
+    url: &str,
+    max_attempts: u32,
+    payload: Bytes,
+) -> Result<()> {
+    let connector = HttpsConnector::<HttpConnector>::new()?;
+    let client = Client::builder().build(connector);
+    let mut backoff = WEBHOOK_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let req = Request::post(url)
+            .header("Content-Type", "application/json")
+            .body(payload.clone().into())
+            .context("Failed to create webhook request")?;
+        let res: Result<()> = match tokio::time::timeout(WEBHOOK_TIMEOUT, client.request(req)).await
+        {
+            Ok(Ok(response)) if response.status().is_success() => return Ok(()),
+            Ok(Ok(response)) => Err(anyhow!("webhook returned {}", response.status())),
+            Ok(Err(err)) => Err(err.into()),
+            Err(_) => Err(anyhow!("webhook timed out")),
+        };
+        if attempt >= max_attempts {
+            return res.with_context(|| format!("Giving up after {} attempts", attempt));
+        }
+        STATS::delivery_retried.add_value(1, (repo_name.to_string(), "webhook".to_string()));
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+async fn deliver_to_unix_socket(path: &Path, payload: Bytes) -> Result<()> {
+    let mut stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    stream.write_all(&payload).await?;
+    stream.write_all(b"\n").await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use fbinit::FacebookInit;
+    use metaconfig_types::EventSubscription;
+    use metaconfig_types::RepoConfig;
+    use metaconfig_types::UpdateLoggingConfig;
+    use mononoke_types::RepositoryId;
+    use regex::Regex;
+    use repo_identity::RepoIdentity;
+    use tokio::io::AsyncBufReadExt;
+    use tokio::io::BufReader;
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    #[facet::container]
+    struct TestRepo {
+        #[facet]
+        repo_identity: RepoIdentity,
+
+        #[facet]
+        repo_config: RepoConfig,
+    }
+
+    fn test_repo(event_subscriptions: Vec<EventSubscription>) -> TestRepo {
+        TestRepo {
+            repo_identity: Arc::new(RepoIdentity::new(
+                RepositoryId::new(0),
+                "events_test".to_string(),
+            )),
+            repo_config: Arc::new(RepoConfig {
+                update_logging_config: UpdateLoggingConfig {
+                    event_subscriptions,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn push_accepted(bookmark: &str) -> RepoEvent {
+        RepoEvent::PushAccepted {
+            bookmark: bookmark.to_string(),
+            old_value: None,
+            new_value: "11".repeat(32),
+        }
+    }
+
+    #[test]
+    fn test_subscription_matches() -> Result<()> {
+        let redactions = RepoEvent::RedactionAdded {
+            key_list_id: "key_list".to_string(),
+            keys: 1,
+        };
+        let subscription = |event_kinds, bookmark_regex: Option<&str>| {
+            Ok::<_, anyhow::Error>(EventSubscription {
+                sink: EventSink::Scribe {
+                    scribe_category: "events".to_string(),
+                },
+                event_kinds,
+                bookmark_regex: bookmark_regex.map(Regex::new).transpose()?.map(Into::into),
+            })
+        };
+        let matches = |subscription: &EventSubscription, event: &RepoEvent| {
+            subscription.matches(event.kind(), event.bookmark())
+        };
+
+        // No filters, everything matches.
+        let all = subscription(vec![], None)?;
+        assert!(matches(&all, &push_accepted("main")));
+        assert!(matches(&all, &redactions));
+
+        // Filtered by kind.
+        let pushes = subscription(vec![RepoEventKind::PushAccepted], None)?;
+        assert!(matches(&pushes, &push_accepted("main")));
+        assert!(!matches(&pushes, &redactions));
+
+        // Filtered by bookmark, events that aren't about a bookmark still
+        // match.
+        let main = subscription(vec![], Some("^main$"))?;
+        assert!(matches(&main, &push_accepted("main")));
+        assert!(!matches(&main, &push_accepted("release")));
+        assert!(matches(&main, &redactions));
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_publish_to_unix_socket(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("events.sock");
+        let listener = UnixListener::bind(&path)?;
+        let repo = test_repo(vec![EventSubscription {
+            sink: EventSink::UnixSocket { path },
+            event_kinds: vec![RepoEventKind::PushAccepted],
+            bookmark_regex: Some(Regex::new("^main$")?.into()),
+        }]);
+
+        // Events the subscription doesn't match aren't delivered.
+        let delivery = publish_repo_event(&ctx, &repo, push_accepted("release"));
+        assert!(delivery.0.is_empty());
+
+        let delivery = publish_repo_event(&ctx, &repo, push_accepted("main"));
+        assert_eq!(delivery.0.len(), 1);
+        let (stream, _) = listener.accept().await?;
+        delivery.wait().await;
+
+        let mut lines = BufReader::new(stream).lines();
+        let line = lines.next_line().await?.expect("event was not delivered");
+        let event: serde_json::Value = serde_json::from_str(&line)?;
+        assert_eq!(event["repo_name"], "events_test");
+        assert_eq!(event["event"], "push_accepted");
+        assert_eq!(event["bookmark"], "main");
+        assert_eq!(event["new_value"], "11".repeat(32));
+        assert!(event.get("old_value").is_none());
+        assert_eq!(lines.next_line().await?, None);
+        Ok(())
+    }
+}
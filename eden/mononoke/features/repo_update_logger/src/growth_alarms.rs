@@ -0,0 +1,530 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Alarms on the growth of repos, configured in `growth_alarms`, which catch
+//! runaway automation early, e.g. a job writing millions of scratch commits.
+//!
+//! Growth per day is measured over the last 24 hours, in hourly buckets that
+//! all servers add to through the repo's mutable counters, so that the
+//! thresholds apply to the repo as a whole rather than to each server.  When
+//! a threshold is exceeded, the alarm is logged to scuba, published as a
+//! `GrowthAlarm` repo event and passed to the sinks registered with
+//! `register_growth_alarm_sink`.  An alarm fires again at most once per
+//! `ALARM_REPEAT_INTERVAL_SECS` while its threshold is still exceeded, which
+//! is also tracked in the mutable counters so that only one server fires it.
+//!
+//! Growth is recorded and checked in the background, so it doesn't delay the
+//! pushes and bookmark moves that cause it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use anyhow::bail;
+use anyhow::Result;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::Bookmarks;
+use bookmarks::BookmarksArc;
+use bookmarks::Freshness;
+use bookmarks_types::BookmarkKind;
+use chrono::Utc;
+use context::CoreContext;
+use futures::TryStreamExt;
+use metaconfig_types::RepoConfig;
+use metaconfig_types::RepoConfigArc;
+use metaconfig_types::RepoConfigRef;
+use mutable_counters::MutableCounters;
+use mutable_counters::MutableCountersArc;
+use mutable_counters::MutableCountersRef;
+use once_cell::sync::Lazy;
+use repo_identity::RepoIdentity;
+use repo_identity::RepoIdentityArc;
+use repo_identity::RepoIdentityRef;
+use serde_derive::Serialize;
+use stats::prelude::*;
+
+use crate::event_publisher::publish_repo_event;
+use crate::event_publisher::RepoEvent;
+
+define_stats! {
+    prefix = "mononoke.repo_growth";
+    alarm_fired: dynamic_timeseries("{}.{}.alarm_fired", (repo: String, metric: &'static str); Rate, Sum),
+}
+
+const SECS_PER_HOUR: i64 = 3600;
+const HOURS_PER_DAY: i64 = 24;
+
+/// How long to wait before firing an alarm again while its threshold is
+/// still exceeded.
+const ALARM_REPEAT_INTERVAL_SECS: i64 = SECS_PER_HOUR;
+
+/// How often a server counts the bookmarks of a repo when bookmarks are
+/// created, as counting them means listing them all.
+const BOOKMARK_COUNT_CHECK_INTERVAL_SECS: i64 = 600;
+
+/// How many times adding to an hourly bucket is attempted when other servers
+/// are adding to it at the same time.
+const MAX_BUCKET_UPDATE_ATTEMPTS: usize = 10;
+
+/// Hourly buckets are stored in a single counter, with the hour in the high
+/// bits and the bucket's total in the low bits.
+const BUCKET_TOTAL_BITS: u32 = 40;
+const BUCKET_TOTAL_MAX: u64 = (1 << BUCKET_TOTAL_BITS) - 1;
+
+/// A measure of the growth of a repo that can have a threshold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrowthMetric {
+    BlobBytesPerDay,
+    CommitsPerDay,
+    BookmarkCount,
+}
+
+impl GrowthMetric {
+    fn name(&self) -> &'static str {
+        match self {
+            GrowthMetric::BlobBytesPerDay => "blob_bytes_per_day",
+            GrowthMetric::CommitsPerDay => "commits_per_day",
+            GrowthMetric::BookmarkCount => "bookmark_count",
+        }
+    }
+
+    /// The mutable counter that records when the alarm last fired.
+    fn last_fired_counter(&self) -> String {
+        format!("growth.{}.last_fired", self.name())
+    }
+
+    /// The prefix of the mutable counters that hold the hourly buckets.
+    fn bucket_counter_prefix(&self) -> String {
+        format!("growth.{}.hour.", self.name())
+    }
+}
+
+impl fmt::Display for GrowthMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A threshold on the growth of a repo was exceeded.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct GrowthAlarm {
+    pub repo_name: String,
+    pub metric: GrowthMetric,
+    pub value: u64,
+    pub threshold: u64,
+}
+
+/// Somewhere growth alarms are sent to, in addition to scuba and the repo
+/// event subscriptions.
+pub trait GrowthAlarmSink: Send + Sync {
+    fn fire(&self, ctx: &CoreContext, alarm: &GrowthAlarm);
+}
+
+static SINKS: Lazy<RwLock<Vec<Arc<dyn GrowthAlarmSink>>>> = Lazy::new(Default::default);
+
+/// Send the growth alarms of all repos to this sink as well.
+pub fn register_growth_alarm_sink(sink: Arc<dyn GrowthAlarmSink>) {
+    SINKS.write().expect("lock poisoned").push(sink);
+}
+
+/// The values added to a metric during an hour.  There are 24 of them for
+/// each metric, reused as the hours go by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct HourlyBucket {
+    hour: i64,
+    total: u64,
+}
+
+impl HourlyBucket {
+    fn counter(metric: GrowthMetric, hour: i64) -> String {
+        format!(
+            "{}{}",
+            metric.bucket_counter_prefix(),
+            hour.rem_euclid(HOURS_PER_DAY)
+        )
+    }
+
+    fn decode(value: i64) -> Self {
+        Self {
+            hour: value >> BUCKET_TOTAL_BITS,
+            total: value as u64 & BUCKET_TOTAL_MAX,
+        }
+    }
+
+    fn encode(&self) -> i64 {
+        (self.hour << BUCKET_TOTAL_BITS) | self.total.min(BUCKET_TOTAL_MAX) as i64
+    }
+}
+
+/// The parts of a repo that its growth is recorded with, which can be moved
+/// to a background task.
+#[facet::container]
+#[derive(Clone)]
+struct GrowthRepo {
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    repo_config: RepoConfig,
+
+    #[facet]
+    mutable_counters: dyn MutableCounters,
+}
+
+impl GrowthRepo {
+    fn new(repo: &(impl RepoIdentityArc + RepoConfigArc + MutableCountersArc)) -> Self {
+        Self {
+            repo_identity: repo.repo_identity_arc(),
+            repo_config: repo.repo_config_arc(),
+            mutable_counters: repo.mutable_counters_arc(),
+        }
+    }
+}
+
+/// Add a value to the bucket of the hour `now` is in, starting the bucket
+/// afresh if it was last used a day or more ago.
+async fn add_to_bucket(
+    ctx: &CoreContext,
+    counters: &dyn MutableCounters,
+    metric: GrowthMetric,
+    now: i64,
+    value: u64,
+) -> Result<()> {
+    let hour = now.div_euclid(SECS_PER_HOUR);
+    let counter = HourlyBucket::counter(metric, hour);
+    for _ in 0..MAX_BUCKET_UPDATE_ATTEMPTS {
+        let prev = counters.get_counter(ctx, &counter).await?;
+        let total = match prev.map(HourlyBucket::decode) {
+            Some(bucket) if bucket.hour == hour => bucket.total.saturating_add(value),
+            _ => value,
+        };
+        let bucket = HourlyBucket { hour, total };
+        // The first write of a bucket isn't conditional, as there is no
+        // previous value to compare with.
+        if counters
+            .set_counter(ctx, &counter, bucket.encode(), prev)
+            .await?
+        {
+            return Ok(());
+        }
+    }
+    bail!(
+        "Failed to add to growth counter {} after {} attempts",
+        counter,
+        MAX_BUCKET_UPDATE_ATTEMPTS
+    );
+}
+
+/// The total of a metric over the day up to `now`.
+async fn daily_total(
+    ctx: &CoreContext,
+    counters: &dyn MutableCounters,
+    metric: GrowthMetric,
+    now: i64,
+) -> Result<u64> {
+    let hour = now.div_euclid(SECS_PER_HOUR);
+    let prefix = metric.bucket_counter_prefix();
+    Ok(counters
+        .get_all_counters(ctx)
+        .await?
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .map(|(_, value)| HourlyBucket::decode(value))
+        .filter(|bucket| bucket.hour > hour - HOURS_PER_DAY && bucket.hour <= hour)
+        .fold(0u64, |total, bucket| total.saturating_add(bucket.total)))
+}
+
+/// Claim the alarm of a metric for this server, if it hasn't fired within
+/// the repeat interval.
+async fn claim_alarm(
+    ctx: &CoreContext,
+    counters: &dyn MutableCounters,
+    metric: GrowthMetric,
+    now: i64,
+) -> Result<bool> {
+    let counter = metric.last_fired_counter();
+    let last_fired = counters.get_counter(ctx, &counter).await?;
+    if matches!(last_fired, Some(last_fired) if now - last_fired < ALARM_REPEAT_INTERVAL_SECS) {
+        return Ok(false);
+    }
+    counters.set_counter(ctx, &counter, now, last_fired).await
+}
+
+/// Fire the alarm of a metric if its value exceeds the threshold and no
+/// server has fired it within the repeat interval.
+async fn maybe_fire_alarm(
+    ctx: &CoreContext,
+    repo: &GrowthRepo,
+    now: i64,
+    metric: GrowthMetric,
+    value: u64,
+    threshold: u64,
+) -> Result<()> {
+    if value > threshold && claim_alarm(ctx, repo.mutable_counters(), metric, now).await? {
+        fire_alarm(
+            ctx,
+            repo,
+            GrowthAlarm {
+                repo_name: repo.repo_identity().name().to_string(),
+                metric,
+                value,
+                threshold,
+            },
+        );
+    }
+    Ok(())
+}
+
+fn fire_alarm(
+    ctx: &CoreContext,
+    repo: &(impl RepoIdentityRef + RepoConfigRef),
+    alarm: GrowthAlarm,
+) {
+    STATS::alarm_fired.add_value(1, (alarm.repo_name.clone(), alarm.metric.name()));
+    ctx.scuba()
+        .clone()
+        .add("growth_metric", alarm.metric.name())
+        .add("growth_value", alarm.value)
+        .add("growth_threshold", alarm.threshold)
+        .log_with_msg("Repo growth alarm", None);
+    // Event deliveries happen in the background.
+    let _ = publish_repo_event(
+        ctx,
+        repo,
+        RepoEvent::GrowthAlarm {
+            metric: alarm.metric.to_string(),
+            value: alarm.value,
+            threshold: alarm.threshold,
+        },
+    );
+    for sink in SINKS.read().expect("lock poisoned").iter() {
+        sink.fire(ctx, &alarm);
+    }
+}
+
+/// Add new commits to the repo's daily growth, and fire the alarms whose
+/// thresholds are exceeded.
+async fn record_growth(
+    ctx: &CoreContext,
+    repo: &GrowthRepo,
+    now: i64,
+    commits: u64,
+    blob_bytes: u64,
+) -> Result<()> {
+    let config = &repo.repo_config().growth_alarms;
+    for (metric, value, threshold) in [
+        (GrowthMetric::CommitsPerDay, commits, config.commits_per_day),
+        (
+            GrowthMetric::BlobBytesPerDay,
+            blob_bytes,
+            config.blob_bytes_per_day,
+        ),
+    ] {
+        let threshold = match threshold {
+            Some(threshold) => threshold,
+            None => continue,
+        };
+        add_to_bucket(ctx, repo.mutable_counters(), metric, now, value).await?;
+        let total = daily_total(ctx, repo.mutable_counters(), metric, now).await?;
+        maybe_fire_alarm(ctx, repo, now, metric, total, threshold).await?;
+    }
+    Ok(())
+}
+
+/// Record that new commits were added to the repo, and fire the alarms
+/// whose thresholds this exceeds.
+pub(crate) fn record_new_commits(
+    ctx: &CoreContext,
+    repo: &(impl RepoIdentityArc + RepoConfigArc + MutableCountersArc),
+    commits: u64,
+    blob_bytes: u64,
+) {
+    let config = repo.repo_config_arc();
+    let config = &config.growth_alarms;
+    if config.commits_per_day.is_none() && config.blob_bytes_per_day.is_none() {
+        return;
+    }
+    let repo = GrowthRepo::new(repo);
+    let ctx = ctx.clone();
+    let now = Utc::now().timestamp();
+    tokio::spawn(async move {
+        if let Err(err) = record_growth(&ctx, &repo, now, commits, blob_bytes).await {
+            ctx.scuba()
+                .clone()
+                .log_with_msg("Failed to record repo growth", Some(format!("{:#}", err)));
+        }
+    });
+}
+
+/// When each repo's bookmarks were last counted by this server.
+static LAST_BOOKMARK_COUNT_CHECK: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(Default::default);
+
+/// Count the bookmarks of the repo, and fire the alarm if there are too many
+/// of them.  Only counts one past the threshold, which is enough to tell that
+/// it is exceeded.
+async fn count_bookmarks(
+    ctx: &CoreContext,
+    repo: &GrowthRepo,
+    bookmarks: &dyn Bookmarks,
+    now: i64,
+    threshold: u64,
+) -> Result<()> {
+    let count = bookmarks
+        .list(
+            ctx.clone(),
+            Freshness::MaybeStale,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL,
+            &BookmarkPagination::FromStart,
+            threshold.saturating_add(1),
+        )
+        .try_fold(0u64, |count, _| async move { Ok(count + 1) })
+        .await?;
+    maybe_fire_alarm(
+        ctx,
+        repo,
+        now,
+        GrowthMetric::BookmarkCount,
+        count,
+        threshold,
+    )
+    .await
+}
+
+/// Count the bookmarks of the repo in the background after bookmarks were
+/// created, and fire the alarm if there are too many of them.  Each server
+/// counts them at most once per `BOOKMARK_COUNT_CHECK_INTERVAL_SECS`.
+pub fn check_bookmark_count(
+    ctx: &CoreContext,
+    repo: &(impl RepoIdentityArc + RepoConfigArc + MutableCountersArc + BookmarksArc),
+) {
+    let threshold = match repo.repo_config_arc().growth_alarms.bookmark_count {
+        Some(threshold) => threshold,
+        None => return,
+    };
+    let now = Utc::now().timestamp();
+    {
+        let mut last_checks = LAST_BOOKMARK_COUNT_CHECK.lock().expect("lock poisoned");
+        let last_check = last_checks
+            .entry(repo.repo_identity_arc().name().to_string())
+            .or_insert(i64::MIN);
+        if now.saturating_sub(*last_check) < BOOKMARK_COUNT_CHECK_INTERVAL_SECS {
+            return;
+        }
+        *last_check = now;
+    }
+
+    let bookmarks = repo.bookmarks_arc();
+    let repo = GrowthRepo::new(repo);
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(err) = count_bookmarks(&ctx, &repo, bookmarks.as_ref(), now, threshold).await {
+            ctx.scuba().clone().log_with_msg(
+                "Failed to count bookmarks for growth alarms",
+                Some(format!("{:#}", err)),
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use metaconfig_types::GrowthAlarmsConfig;
+    use mononoke_types::RepositoryId;
+    use mutable_counters::SqlMutableCountersBuilder;
+    use sql_construct::SqlConstruct;
+
+    use super::*;
+
+    #[test]
+    fn test_hourly_bucket() {
+        let bucket = HourlyBucket {
+            hour: Utc::now().timestamp() / SECS_PER_HOUR,
+            total: 12345,
+        };
+        assert_eq!(HourlyBucket::decode(bucket.encode()), bucket);
+        let full = HourlyBucket {
+            hour: 1,
+            total: u64::MAX,
+        };
+        assert_eq!(HourlyBucket::decode(full.encode()).total, BUCKET_TOTAL_MAX);
+    }
+
+    struct RecordingSink(Mutex<Vec<GrowthAlarm>>);
+
+    impl GrowthAlarmSink for RecordingSink {
+        fn fire(&self, _ctx: &CoreContext, alarm: &GrowthAlarm) {
+            if alarm.repo_name == "growth_test" {
+                self.0.lock().unwrap().push(alarm.clone());
+            }
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_record_growth(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let sink = Arc::new(RecordingSink(Mutex::new(Vec::new())));
+        register_growth_alarm_sink(sink.clone());
+        let repo_id = RepositoryId::new(0);
+        let repo = GrowthRepo {
+            repo_identity: Arc::new(RepoIdentity::new(repo_id, "growth_test".to_string())),
+            repo_config: Arc::new(RepoConfig {
+                growth_alarms: GrowthAlarmsConfig {
+                    commits_per_day: Some(10),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            mutable_counters: Arc::new(
+                SqlMutableCountersBuilder::with_sqlite_in_memory()?.build(repo_id),
+            ),
+        };
+        // Another server of the same repo, which shares its counters.
+        let other = repo.clone();
+
+        let now = 100 * HOURS_PER_DAY * SECS_PER_HOUR;
+        record_growth(&ctx, &repo, now, 6, 0).await?;
+        assert!(sink.0.lock().unwrap().is_empty());
+        record_growth(&ctx, &other, now + SECS_PER_HOUR, 6, 0).await?;
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            vec![GrowthAlarm {
+                repo_name: "growth_test".to_string(),
+                metric: GrowthMetric::CommitsPerDay,
+                value: 12,
+                threshold: 10,
+            }]
+        );
+
+        // The alarm doesn't fire again within the repeat interval, on any
+        // server.
+        record_growth(&ctx, &repo, now + SECS_PER_HOUR, 1, 0).await?;
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+
+        // The first hour is more than a day old, so only the 7 commits
+        // since are counted.
+        let next_day = now + HOURS_PER_DAY * SECS_PER_HOUR;
+        record_growth(&ctx, &other, next_day, 0, 0).await?;
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+        assert_eq!(
+            daily_total(
+                &ctx,
+                repo.mutable_counters(),
+                GrowthMetric::CommitsPerDay,
+                next_day
+            )
+            .await?,
+            7
+        );
+        Ok(())
+    }
+}
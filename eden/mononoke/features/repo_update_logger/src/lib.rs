@@ -6,13 +6,24 @@
  */
 
 //! Log changes to the repository (new commits and bookmark updates) to
-//! external telemetry.
+//! external telemetry, publish repo events to external subscribers, and
+//! fire alarms when the repository grows too fast.
 
 mod bookmark_logger;
 mod commit_logger;
+mod event_publisher;
+mod growth_alarms;
 
 pub use crate::bookmark_logger::log_bookmark_operation;
 pub use crate::bookmark_logger::BookmarkInfo;
 pub use crate::bookmark_logger::BookmarkOperation;
 pub use crate::commit_logger::log_new_commits;
 pub use crate::commit_logger::CommitInfo;
+pub use crate::event_publisher::publish_repo_event;
+pub use crate::event_publisher::EventDelivery;
+pub use crate::event_publisher::RepoEvent;
+pub use crate::growth_alarms::check_bookmark_count;
+pub use crate::growth_alarms::register_growth_alarm_sink;
+pub use crate::growth_alarms::GrowthAlarm;
+pub use crate::growth_alarms::GrowthAlarmSink;
+pub use crate::growth_alarms::GrowthMetric;
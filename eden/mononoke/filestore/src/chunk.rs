@@ -134,6 +134,180 @@ where
     }
 }
 
+/// Table of random values used by the rolling hash of content-defined chunking.  It's
+/// generated with splitmix64 from a fixed seed: changing it would change where files are
+/// split, so that new versions of files would no longer share chunks with old ones.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6d6f6e6f6e6f6b65;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Finds content-defined chunk boundaries, using a gear rolling hash with normalized chunking
+/// (as in FastCDC). Chunks are never larger than the maximum size, so that they fit wherever
+/// fixed-size chunks of that size would, and are on average a quarter of it.
+/// Because boundaries depend on the content, rather than on offsets within the file, an edit
+/// only changes the chunks around it, and the rest of the chunks are shared with the previous
+/// version of the file.
+#[derive(Debug)]
+struct ContentDefinedChunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    /// Mask used before the average size, which makes boundaries less likely.
+    mask_small: u64,
+    /// Mask used after the average size, which makes boundaries more likely.
+    mask_large: u64,
+}
+
+impl ContentDefinedChunker {
+    fn new(max_size: usize) -> Self {
+        assert!(max_size > 0);
+
+        let avg_size = std::cmp::max(max_size / 4, 1);
+        let bits = usize::BITS - 1 - avg_size.leading_zeros();
+        let mask = |bits: u32| match bits {
+            0 => 0,
+            bits => u64::MAX << (64 - bits.min(64)),
+        };
+
+        Self {
+            min_size: std::cmp::max(avg_size / 4, 1),
+            avg_size,
+            max_size,
+            mask_small: mask(bits + 1),
+            mask_large: mask(bits.saturating_sub(1)),
+        }
+    }
+
+    /// Continue scanning `data` for a boundary from `pos`, with the rolling hash `hash` of the
+    /// data before it. Returns the size of the next chunk if a boundary was found, or updates
+    /// `pos` and `hash` so that scanning can resume once there is more data.
+    fn next_boundary(&self, data: &[u8], pos: &mut usize, hash: &mut u64) -> Option<usize> {
+        // There are never boundaries before the minimum size, so don't hash that data.
+        if *pos < self.min_size {
+            *pos = std::cmp::min(self.min_size, data.len());
+        }
+
+        while *pos < data.len() {
+            if *pos >= self.max_size {
+                return Some(self.max_size);
+            }
+
+            *hash = (*hash << 1).wrapping_add(GEAR[data[*pos] as usize]);
+            *pos += 1;
+
+            let mask = if *pos < self.avg_size {
+                self.mask_small
+            } else {
+                self.mask_large
+            };
+            if *hash & mask == 0 {
+                return Some(*pos);
+            }
+        }
+
+        if *pos >= self.max_size {
+            Some(self.max_size)
+        } else {
+            None
+        }
+    }
+}
+
+/// Like ChunkStream, but splits the data at content-defined boundaries instead of at fixed
+/// offsets, so that identical data in different files or versions of a file is split into
+/// identical chunks, which are then only stored once.
+#[must_use = "streams do nothing unless polled"]
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub struct ContentDefinedChunkStream<S> {
+    #[pin]
+    stream: S,
+    chunker: ContentDefinedChunker,
+    buff: BytesMut,
+    pos: usize,
+    hash: u64,
+    emitted: bool,
+    had_data: bool,
+    done: bool,
+}
+
+impl<S> ContentDefinedChunkStream<S> {
+    pub fn new(stream: S, max_chunk_size: usize) -> ContentDefinedChunkStream<S> {
+        ContentDefinedChunkStream {
+            stream,
+            chunker: ContentDefinedChunker::new(max_chunk_size),
+            buff: BytesMut::new(),
+            pos: 0,
+            hash: 0,
+            emitted: false,
+            had_data: false,
+            done: false,
+        }
+    }
+}
+
+impl<S, E> Stream for ContentDefinedChunkStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut proj = self.project();
+
+        if *proj.done {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            if let Some(size) = proj.chunker.next_boundary(proj.buff, proj.pos, proj.hash) {
+                *proj.emitted = true;
+                *proj.pos = 0;
+                *proj.hash = 0;
+                let chunk = proj.buff.split_to(size).freeze();
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+
+            match futures::ready!(proj.stream.as_mut().poll_next(ctx)) {
+                Some(Ok(bytes)) => {
+                    *proj.had_data = true;
+                    proj.buff.extend_from_slice(&bytes);
+                    continue;
+                }
+                Some(Err(e)) => {
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => {
+                    // Fallthrough
+                }
+            };
+
+            // No more data is coming. As with ChunkStream, return whatever is left, or one
+            // empty chunk if the underlying stream only contained empty Bytes.
+            *proj.done = true;
+
+            if !proj.buff.is_empty() || (*proj.had_data && !*proj.emitted) {
+                *proj.emitted = true;
+                let chunk = std::mem::replace(proj.buff, BytesMut::new()).freeze();
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+
+            return Poll::Ready(None);
+        }
+    }
+}
+
 pub enum Chunks<'a> {
     Inline(BoxFuture<'a, Result<Bytes, Error>>),
     Chunked(ExpectedSize, BoxStream<'a, Result<Bytes, Error>>),
@@ -149,11 +323,13 @@ impl Debug for Chunks<'_> {
 }
 
 /// Chunk a stream of incoming data for storage. We use the incoming size hint to decide whether
-/// to chunk.
+/// to chunk. If `content_defined` is set, chunk_size is the largest size of the chunks, rather
+/// than the size of every chunk.
 pub fn make_chunks<'a, S>(
     data: S,
     expected_size: ExpectedSize,
     chunk_size: Option<u64>,
+    content_defined: bool,
 ) -> Chunks<'a>
 where
     S: Stream<Item = Result<Bytes, Error>> + Send + 'a,
@@ -178,8 +354,12 @@ where
 
     match chunk_size {
         Some(chunk_size) if expected_size.should_chunk(chunk_size) => {
-            let stream = ChunkStream::new(data, chunk_size as usize);
-            Chunks::Chunked(expected_size, stream.boxed())
+            let stream = if content_defined {
+                ContentDefinedChunkStream::new(data, chunk_size as usize).boxed()
+            } else {
+                ChunkStream::new(data, chunk_size as usize).boxed()
+            };
+            Chunks::Chunked(expected_size, stream)
         }
         _ => {
             let fut = data
@@ -210,7 +390,7 @@ mod test {
     fn test_make_chunks_no_chunk_size() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(10), None) {
+        match make_chunks(in_stream, ExpectedSize::new(10), None, false) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -220,7 +400,7 @@ mod test {
     fn test_make_chunks_no_chunking() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(10), Some(100)) {
+        match make_chunks(in_stream, ExpectedSize::new(10), Some(100), false) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -230,7 +410,7 @@ mod test {
     fn test_make_chunks_no_chunking_limit() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(100), Some(100)) {
+        match make_chunks(in_stream, ExpectedSize::new(100), Some(100), false) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -240,7 +420,7 @@ mod test {
     fn test_make_chunks_chunking() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(1000), Some(100)) {
+        match make_chunks(in_stream, ExpectedSize::new(1000), Some(100), false) {
             Chunks::Chunked(h, _) if h.check_equals(1000).is_ok() => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -259,7 +439,7 @@ mod test {
         ];
         let in_stream = stream::iter(chunks).map(Ok);
 
-        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(100)) {
+        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(100), false) {
             c @ Chunks::Chunked(..) => panic!("Did not expect {:?}", c),
             Chunks::Inline(fut) => fut,
         };
@@ -280,7 +460,7 @@ mod test {
         ];
         let in_stream = stream::iter(chunks).map(Ok);
 
-        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(1)) {
+        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(1), false) {
             Chunks::Chunked(_, stream) => stream.try_collect::<Vec<_>>(),
             c @ Chunks::Inline(..) => panic!("Did not expect {:?}", c),
         };
@@ -388,6 +568,73 @@ mod test {
         assert_matches!(stream.try_next().await, Ok(None));
     }
 
+    fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    async fn content_defined_chunks(data: &[u8], piece_size: usize, max: usize) -> Vec<Bytes> {
+        let pieces = data
+            .chunks(piece_size)
+            .map(Bytes::copy_from_slice)
+            .collect::<Vec<_>>();
+        ContentDefinedChunkStream::new(stream::iter(pieces).map(Result::<_, ()>::Ok), max)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_content_defined_chunks() {
+        let max = 4096;
+        let data = pseudo_random_bytes(256 * 1024, 1);
+        let chunks = content_defined_chunks(&data, 1000, max).await;
+
+        assert_eq!(chunks.concat(), data);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() >= max / 16 && chunk.len() <= max);
+        }
+        // Chunks are a quarter of the maximum size on average.
+        assert!(chunks.len() >= data.len() / max * 2 && chunks.len() <= data.len() / max * 8);
+        // Boundaries don't depend on how the data arrives.
+        assert_eq!(content_defined_chunks(&data, 7, max).await, chunks);
+    }
+
+    #[tokio::test]
+    async fn test_content_defined_chunks_are_shared() {
+        // Inserting data near the start of a file only changes the chunks around the
+        // insertion, unlike fixed-size chunks, which would all change.
+        let max = 4096;
+        let data = pseudo_random_bytes(256 * 1024, 2);
+        let mut edited = data[..1000].to_vec();
+        edited.extend_from_slice(b"inserted data");
+        edited.extend_from_slice(&data[1000..]);
+
+        let chunks = content_defined_chunks(&data, 4096, max).await;
+        let edited_chunks = content_defined_chunks(&edited, 4096, max).await;
+        let shared = edited_chunks
+            .iter()
+            .filter(|chunk| chunks.contains(chunk))
+            .count();
+        assert!(shared + 3 >= edited_chunks.len());
+    }
+
+    #[tokio::test]
+    async fn test_content_defined_stream_of_empty_bytes() {
+        let in_stream = stream::iter(vec![Bytes::new()]).map(Result::<_, ()>::Ok);
+        let mut stream = ContentDefinedChunkStream::new(in_stream, 1);
+
+        assert_eq!(stream.try_next().await, Ok(Some(Bytes::new())));
+        assert_eq!(stream.try_next().await, Ok(None));
+    }
+
     async fn do_check_chunk_stream(in_chunks: Vec<Vec<u8>>, size: usize) -> bool {
         let in_chunks: Vec<Bytes> = in_chunks.into_iter().map(Bytes::from).collect();
         let chunk_stream = ChunkStream::new(
@@ -457,7 +704,7 @@ mod test {
 
             let len = expected_bytes.len() as u64;
 
-            let fut = match make_chunks(in_stream, ExpectedSize::new(len), Some(len)) {
+            let fut = match make_chunks(in_stream, ExpectedSize::new(len), Some(len), false) {
                 Chunks::Inline(fut) => fut,
                 c => panic!("Did not expect {:?}", c),
             };
@@ -0,0 +1,341 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use mononoke_types::ContentMetadata;
+use mononoke_types::ContentMetadataV2;
+use mononoke_types::LineEndings;
+
+use crate::incremental_hash::Hasher;
+
+/// Maximum number of bytes of the file that are kept to compute the first line.
+const FIRST_LINE_LIMIT: usize = 1024;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+
+/// The kind of a file, as determined by looking at its contents. This is the
+/// part of `ContentMetadataV2` that isn't a hash of the content.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ContentKind {
+    pub is_binary: bool,
+    pub is_ascii: bool,
+    pub is_utf8: bool,
+    pub ends_in_newline: bool,
+    pub newline_count: u64,
+    pub first_line: Option<String>,
+    pub line_endings: LineEndings,
+    pub has_bom: bool,
+    pub language: Option<String>,
+}
+
+impl ContentKind {
+    /// Combine the kind of a file with its hashes.
+    pub fn into_metadata_v2(self, metadata: ContentMetadata) -> ContentMetadataV2 {
+        ContentMetadataV2 {
+            content_id: metadata.content_id,
+            total_size: metadata.total_size,
+            sha1: metadata.sha1,
+            sha256: metadata.sha256,
+            git_sha1: metadata.git_sha1,
+            is_binary: self.is_binary,
+            is_ascii: self.is_ascii,
+            is_utf8: self.is_utf8,
+            ends_in_newline: self.ends_in_newline,
+            newline_count: self.newline_count,
+            first_line: self.first_line,
+            line_endings: self.line_endings,
+            has_bom: self.has_bom,
+            language: self.language,
+        }
+    }
+}
+
+/// Classifies file contents incrementally, so that it can run alongside the
+/// hashers while the file is being uploaded.
+pub struct ContentKindClassifier {
+    /// The start of the file, up to and including the first newline, but
+    /// no more than `FIRST_LINE_LIMIT` bytes.
+    prefix: Vec<u8>,
+    prefix_complete: bool,
+    has_null: bool,
+    is_ascii: bool,
+    is_utf8: bool,
+    /// Trailing bytes of an incomplete UTF-8 sequence at the end of the
+    /// previous chunk.
+    utf8_carry: Vec<u8>,
+    last_byte: Option<u8>,
+    newline_count: u64,
+    lf_count: u64,
+    crlf_count: u64,
+    cr_count: u64,
+}
+
+impl ContentKindClassifier {
+    pub fn new() -> Self {
+        Self {
+            prefix: Vec::new(),
+            prefix_complete: false,
+            has_null: false,
+            is_ascii: true,
+            is_utf8: true,
+            utf8_carry: Vec::new(),
+            last_byte: None,
+            newline_count: 0,
+            lf_count: 0,
+            crlf_count: 0,
+            cr_count: 0,
+        }
+    }
+
+    fn update_utf8(&mut self, bytes: &[u8]) {
+        if !self.is_utf8 {
+            return;
+        }
+        let mut carry = std::mem::take(&mut self.utf8_carry);
+        let bytes = if carry.is_empty() {
+            bytes
+        } else {
+            carry.extend_from_slice(bytes);
+            &carry[..]
+        };
+        match std::str::from_utf8(bytes) {
+            Ok(_) => {}
+            // The chunk ends in the middle of a character: keep the start of
+            // the character to validate it with the next chunk.
+            Err(e) if e.error_len().is_none() => {
+                self.utf8_carry = bytes[e.valid_up_to()..].to_vec();
+            }
+            Err(_) => self.is_utf8 = false,
+        }
+    }
+
+    fn line_endings(&self) -> LineEndings {
+        match (self.lf_count > 0, self.crlf_count > 0, self.cr_count > 0) {
+            (false, false, false) => LineEndings::Absent,
+            (true, false, false) => LineEndings::Lf,
+            (false, true, false) => LineEndings::CrLf,
+            (false, false, true) => LineEndings::Cr,
+            _ => LineEndings::Mixed,
+        }
+    }
+
+    fn first_line(&self) -> Option<String> {
+        let line = match self.prefix.iter().position(|b| *b == b'\n') {
+            Some(end) => &self.prefix[..end],
+            None => &self.prefix[..],
+        };
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        // The prefix may end in the middle of a character, in which case
+        // only the valid part is kept.
+        match std::str::from_utf8(line) {
+            Ok(line) => Some(line.to_string()),
+            Err(e) => Some(String::from_utf8_lossy(&line[..e.valid_up_to()]).into_owned()),
+        }
+    }
+}
+
+impl Hasher<ContentKind> for ContentKindClassifier {
+    fn update<T: AsRef<[u8]>>(&mut self, bytes: T) {
+        let bytes = bytes.as_ref();
+        if bytes.is_empty() {
+            return;
+        }
+
+        if !self.prefix_complete {
+            let line_len = match bytes.iter().position(|b| *b == b'\n') {
+                Some(end) => {
+                    self.prefix_complete = true;
+                    end + 1
+                }
+                None => bytes.len(),
+            };
+            let take = line_len.min(FIRST_LINE_LIMIT - self.prefix.len());
+            self.prefix.extend_from_slice(&bytes[..take]);
+            if self.prefix.len() == FIRST_LINE_LIMIT {
+                self.prefix_complete = true;
+            }
+        }
+
+        self.has_null |= bytes.contains(&0);
+        self.is_ascii &= bytes.is_ascii();
+        self.update_utf8(bytes);
+
+        let mut prev = self.last_byte;
+        for b in bytes {
+            match *b {
+                b'\n' => {
+                    self.newline_count += 1;
+                    if prev == Some(b'\r') {
+                        self.crlf_count += 1;
+                    } else {
+                        self.lf_count += 1;
+                    }
+                }
+                _ if prev == Some(b'\r') => self.cr_count += 1,
+                _ => {}
+            }
+            prev = Some(*b);
+        }
+        self.last_byte = prev;
+    }
+
+    fn finish(mut self) -> ContentKind {
+        if self.last_byte == Some(b'\r') {
+            self.cr_count += 1;
+        }
+        let is_utf8 = self.is_utf8 && self.utf8_carry.is_empty();
+        let first_line = if is_utf8 { self.first_line() } else { None };
+        let has_bom = [UTF8_BOM, UTF16_BE_BOM, UTF16_LE_BOM]
+            .iter()
+            .any(|bom| self.prefix.starts_with(bom));
+
+        ContentKind {
+            is_binary: self.has_null,
+            is_ascii: self.is_ascii,
+            is_utf8,
+            ends_in_newline: self.last_byte == Some(b'\n'),
+            newline_count: self.newline_count,
+            line_endings: self.line_endings(),
+            has_bom,
+            language: detect_language(&self.prefix),
+            first_line,
+        }
+    }
+}
+
+/// Detect the language of a file from the start of its content. Only
+/// languages that announce themselves in the content (e.g. with a shebang
+/// line) are detected, as the path of the file isn't known to the filestore.
+fn detect_language(prefix: &[u8]) -> Option<String> {
+    let prefix = prefix.strip_prefix(UTF8_BOM).unwrap_or(prefix);
+    if prefix.starts_with(b"<?php") {
+        return Some("php".to_string());
+    }
+    if prefix.starts_with(b"<?xml") {
+        return Some("xml".to_string());
+    }
+
+    let shebang = prefix.strip_prefix(b"#!")?;
+    let line = shebang.split(|b| *b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Strip versions, e.g. python3.9 is just python.
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match interpreter {
+        "" => return None,
+        "sh" | "bash" | "dash" | "ksh" | "zsh" => "shell",
+        "node" | "nodejs" => "javascript",
+        "ts-node" => "typescript",
+        "tclsh" | "wish" => "tcl",
+        "runghc" | "runhaskell" => "haskell",
+        interpreter => interpreter,
+    };
+    Some(language.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::incremental_hash::hash_bytes;
+
+    fn classify_chunks(chunks: &[&[u8]]) -> ContentKind {
+        let mut classifier = ContentKindClassifier::new();
+        for chunk in chunks {
+            classifier.update(chunk);
+        }
+        classifier.finish()
+    }
+
+    #[test]
+    fn test_classify_text() {
+        let kind = hash_bytes(
+            ContentKindClassifier::new(),
+            &Bytes::from_static(b"#!/usr/bin/env python3\nprint('hello')\n"),
+        );
+        assert_eq!(
+            kind,
+            ContentKind {
+                is_binary: false,
+                is_ascii: true,
+                is_utf8: true,
+                ends_in_newline: true,
+                newline_count: 2,
+                first_line: Some("#!/usr/bin/env python3".to_string()),
+                line_endings: LineEndings::Lf,
+                has_bom: false,
+                language: Some("python".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_classify_binary() {
+        let kind = classify_chunks(&[b"\x00\xff\xfe", b"\r"]);
+        assert!(kind.is_binary);
+        assert!(!kind.is_ascii);
+        assert!(!kind.is_utf8);
+        assert_eq!(kind.first_line, None);
+        assert_eq!(kind.line_endings, LineEndings::Cr);
+    }
+
+    #[test]
+    fn test_classify_across_chunks() {
+        // A CRLF and a multi-byte character both split across chunks.
+        let kind = classify_chunks(&[b"a\r", b"\nb \xc3", b"\xa9\r\n"]);
+        assert!(kind.is_utf8);
+        assert!(!kind.is_ascii);
+        assert_eq!(kind.newline_count, 2);
+        assert_eq!(kind.line_endings, LineEndings::CrLf);
+        assert_eq!(kind.first_line, Some("a".to_string()));
+
+        let kind = classify_chunks(&[b"a\n", b"b\r\n", b"c"]);
+        assert_eq!(kind.line_endings, LineEndings::Mixed);
+        assert!(!kind.ends_in_newline);
+
+        // A truncated character at the end of the file isn't valid UTF-8.
+        let kind = classify_chunks(&[b"a", b"\xc3"]);
+        assert!(!kind.is_utf8);
+    }
+
+    #[test]
+    fn test_classify_first_line() {
+        // A long first line split across chunks is kept whole, and nothing
+        // after it is.
+        let shebang =
+            "#!/usr/local/some/long/installation/prefix/for/the/interpreter/bin/python3 -u";
+        let kind = classify_chunks(&[
+            &shebang.as_bytes()[..40],
+            &shebang.as_bytes()[40..],
+            b"\nprint('hello')\n",
+        ]);
+        assert_eq!(kind.first_line, Some(shebang.to_string()));
+        assert_eq!(kind.language, Some("python".to_string()));
+
+        // A first line with no newline is cut at the limit.
+        let kind = classify_chunks(&[&[b'a'; FIRST_LINE_LIMIT], &[b'a'; FIRST_LINE_LIMIT]]);
+        assert_eq!(kind.first_line, Some("a".repeat(FIRST_LINE_LIMIT)));
+    }
+
+    #[test]
+    fn test_classify_bom() {
+        let kind = classify_chunks(&[b"\xef\xbb", b"\xbf#!/bin/bash\n"]);
+        assert!(kind.has_bom);
+        assert_eq!(kind.language, Some("shell".to_string()));
+
+        let kind = classify_chunks(&[b"", b"hello"]);
+        assert!(!kind.has_bom);
+        assert_eq!(kind.line_endings, LineEndings::Absent);
+        assert_eq!(kind.language, None);
+    }
+}
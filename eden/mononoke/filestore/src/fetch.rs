@@ -144,8 +144,8 @@ pub fn stream_file_bytes<'a, B: Blobstore + Clone + 'a>(
         }
         FileContents::Chunked(chunked) => {
             // File is split into multiple chunks. Dispatch fetches for the chunks that overlap the
-            // range, and buffer them. We know all chunks are the same size (same possibly for the
-            // last one) so we use that to get our buffer size.
+            // range, and buffer them. Chunks may vary in size if they were split at content-defined
+            // boundaries, so we use the largest one to get our buffer size.
             let chunks = chunked.into_chunks();
 
             let max_chunk_size = chunks.iter().map(|c| c.size()).max();
@@ -47,6 +47,7 @@ pub async fn finalize<B: Blobstore>(
         sha1,
         sha256,
         git_sha1,
+        kind,
         contents,
     } = outcome;
 
@@ -86,7 +87,7 @@ pub async fn finalize<B: Blobstore>(
     //
     // - write the forward-mapping aliases
     // - write the data blob
-    // - write the metadata blob
+    // - write the metadata blobs
     //
     // Rationale for this order: since we can't guarantee the aliases are written atomically,
     // on failure we could end up writing some but not others. If the underlying blob exists
@@ -94,9 +95,9 @@ pub async fn finalize<B: Blobstore>(
     // and the aliases are only meaningful as references to that blob (in other words, an
     // alias referring to an absent blob is itself considered to be absent, so logically all
     // all the aliases come into existence atomically when the data blob is written).
-    // Once the data blob is written we can write the metadata object. This is just a
-    // cache, as everything in it can be computed from the content id. Therefore, in principle,
-    // if it doesn't get written we can fix it up later.
+    // Once the data blob is written we can write the metadata objects. These are just a
+    // cache, as everything in them can be computed from the content id. Therefore, in principle,
+    // if they don't get written we can fix them up later.
 
     future::try_join3(put_sha1, put_sha256, put_git_sha1).await?;
 
@@ -110,7 +111,13 @@ pub async fn finalize<B: Blobstore>(
         sha256,
     };
 
-    metadata.clone().into_blob().store(ctx, blobstore).await?;
+    let metadata_v2 = kind.into_metadata_v2(metadata.clone());
+
+    future::try_join(
+        metadata.clone().into_blob().store(ctx, blobstore),
+        metadata_v2.into_blob().store(ctx, blobstore),
+    )
+    .await?;
 
     Ok(metadata)
 }
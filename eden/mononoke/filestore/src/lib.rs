@@ -27,10 +27,12 @@ use mononoke_types::hash;
 use mononoke_types::BlobstoreKey;
 use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::FileContents;
 
 mod alias;
 mod chunk;
+mod classify;
 mod copy;
 mod errors;
 mod expected_size;
@@ -79,6 +81,10 @@ mod test;
 pub struct FilestoreConfig {
     pub chunk_size: Option<u64>,
     pub concurrency: usize,
+    /// Split files into chunks at content-defined boundaries, so that chunks are shared between
+    /// files and versions of files with the same data. Chunks are still no larger than
+    /// `chunk_size`, but are a quarter of it on average.
+    pub content_defined_chunking: bool,
 }
 
 impl FilestoreConfig {
@@ -86,6 +92,7 @@ impl FilestoreConfig {
         Self {
             chunk_size: None,
             concurrency: 1,
+            content_defined_chunking: false,
         }
     }
 }
@@ -197,6 +204,56 @@ pub async fn get_metadata<B: Blobstore>(
     }
 }
 
+/// Fetch the V2 metadata for the underlying content, which describes the kind of the file
+/// (binary or text, encoding, line endings, ...) in addition to its hashes. This will return None
+/// if the content does not exist. It might compute the metadata on the fly if the content exists
+/// but the metadata does not, but doesn't store it: that only happens when the content is stored.
+pub async fn get_metadata_v2<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    key: &FetchKey,
+) -> Result<Option<ContentMetadataV2>, Error> {
+    let maybe_id = key
+        .load(ctx, blobstore)
+        .await
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })?;
+
+    match maybe_id {
+        Some(id) => metadata::get_metadata_v2(blobstore, ctx, id).await,
+        None => Ok(None),
+    }
+}
+
+/// Fetch the V2 metadata for the underlying content. This will return None if the content does
+/// not exist, Some(None) if the metadata does not exist, and Some(Some(ContentMetadataV2)) when
+/// metadata found. It will not compute metadata on the fly, which would require reading the
+/// whole content.
+pub async fn get_metadata_v2_readonly<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    key: &FetchKey,
+) -> Result<Option<Option<ContentMetadataV2>>, Error> {
+    let maybe_id = key
+        .load(ctx, blobstore)
+        .await
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })?;
+
+    match maybe_id {
+        Some(id) => metadata::get_metadata_v2_readonly(blobstore, ctx, id)
+            .await
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
 /// Fetch the metadata for the underlying content. This will return None if the content does
 /// not exist, Some(None) if the metadata does not exist, and Some(Some(ContentMetadata))
 /// when metadata found. It will not recompute metadata on the fly
@@ -414,7 +471,12 @@ pub async fn store<B: Blobstore + Clone + 'static>(
 ) -> Result<ContentMetadata, Error> {
     use chunk::Chunks;
 
-    let prepared = match chunk::make_chunks(data, req.expected_size, config.chunk_size) {
+    let prepared = match chunk::make_chunks(
+        data,
+        req.expected_size,
+        config.chunk_size,
+        config.content_defined_chunking,
+    ) {
         Chunks::Inline(fut) => prepare::prepare_bytes(fut.await?),
         Chunks::Chunked(expected_size, chunks) => {
             prepare::prepare_chunked(
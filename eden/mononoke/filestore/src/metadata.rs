@@ -11,15 +11,21 @@ use blobstore::Loadable;
 use blobstore::LoadableError;
 use blobstore::Storable;
 use context::CoreContext;
+use futures::future;
+use futures::stream::TryStreamExt;
 use mononoke_types::BlobstoreValue;
 use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
 use mononoke_types::ContentMetadataId;
+use mononoke_types::ContentMetadataV2;
+use mononoke_types::ContentMetadataV2Id;
 use thiserror::Error;
 
 use crate::alias::alias_stream;
+use crate::classify::ContentKindClassifier;
 use crate::expected_size::ExpectedSize;
 use crate::fetch;
+use crate::incremental_hash::Hasher;
 
 #[derive(Debug, Error)]
 pub enum RebuildBackmappingError {
@@ -132,3 +138,65 @@ async fn rebuild_metadata<B: Blobstore>(
 
     Ok(metadata)
 }
+
+/// Finds the V2 metadata for a ContentId, which includes the kind of the file. Like
+/// `get_metadata`, this returns None if the content does not exist, and computes the metadata
+/// on the fly if it is missing. The computed metadata is not stored: V2 metadata is only written
+/// when the content is stored, so content stored before it was introduced has it computed on
+/// every request until the content is stored again.
+pub async fn get_metadata_v2<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadataV2>, Error> {
+    let maybe_metadata = get_metadata_v2_readonly(blobstore, ctx, content_id).await?;
+
+    if let Some(metadata) = maybe_metadata {
+        return Ok(Some(metadata));
+    }
+
+    // The hashes are shared with the V1 metadata, so only the kind of the file needs
+    // computing here.
+    let metadata = match get_metadata(blobstore, ctx, content_id).await? {
+        Some(metadata) => metadata,
+        None => return Ok(None),
+    };
+
+    let file_contents = match content_id.load(ctx, blobstore).await {
+        Ok(file_contents) => file_contents,
+        Err(LoadableError::Missing(_)) => return Ok(None),
+        Err(LoadableError::Error(err)) => {
+            return Err(RebuildBackmappingError::InternalError(content_id, err).into());
+        }
+    };
+    let content_stream =
+        fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::all())
+            .map_err(|e| RebuildBackmappingError::InternalError(content_id, e))?;
+
+    let kind = content_stream
+        .try_fold(ContentKindClassifier::new(), |mut classifier, bytes| {
+            classifier.update(bytes);
+            future::ok(classifier)
+        })
+        .await
+        .map_err(|e| RebuildBackmappingError::InternalError(content_id, e))?
+        .finish();
+    Ok(Some(kind.into_metadata_v2(metadata)))
+}
+
+/// Finds the V2 metadata for a ContentId. Returns None if the metadata does not exist. Does not
+/// recompute it on the fly.
+pub async fn get_metadata_v2_readonly<B: Blobstore>(
+    blobstore: &B,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Option<ContentMetadataV2>, Error> {
+    ContentMetadataV2Id::from(content_id)
+        .load(ctx, blobstore)
+        .await
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })
+}
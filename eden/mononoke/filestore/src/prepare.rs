@@ -25,6 +25,8 @@ use mononoke_types::ChunkedFileContents;
 use mononoke_types::FileContents;
 
 use crate::alias::add_aliases_to_multiplexer;
+use crate::classify::ContentKind;
+use crate::classify::ContentKindClassifier;
 use crate::expected_size::ExpectedSize;
 use crate::incremental_hash::hash_bytes;
 use crate::incremental_hash::ContentIdIncrementalHasher;
@@ -40,6 +42,7 @@ pub struct Prepared {
     pub sha1: hash::Sha1,
     pub sha256: hash::Sha256,
     pub git_sha1: hash::RichGitSha1,
+    pub kind: ContentKind,
     pub contents: FileContents,
 }
 
@@ -47,6 +50,7 @@ pub fn prepare_bytes(bytes: Bytes) -> Prepared {
     let sha1 = hash_bytes(Sha1IncrementalHasher::new(), &bytes);
     let sha256 = hash_bytes(Sha256IncrementalHasher::new(), &bytes);
     let git_sha1 = hash_bytes(GitSha1IncrementalHasher::new(&bytes), &bytes);
+    let kind = hash_bytes(ContentKindClassifier::new(), &bytes);
 
     let contents = FileContents::Bytes(bytes);
 
@@ -54,6 +58,7 @@ pub fn prepare_bytes(bytes: Bytes) -> Prepared {
         sha1,
         sha256,
         git_sha1,
+        kind,
         contents,
     }
 }
@@ -81,6 +86,8 @@ where
 
     let aliases = add_aliases_to_multiplexer(&mut multiplexer, expected_size);
 
+    let kind = multiplexer.add(|stream| hash_stream(ContentKindClassifier::new(), stream));
+
     // For the file's contents, spawn new tasks for each individual chunk. This ensures that
     // each chunk is hashed and uploaded separately, and potentially on a different CPU core.
     // We allow up to concurrency uploads to progress at the same time, which creates
@@ -125,14 +132,15 @@ where
     // Coerce the Error value for all our futures to Error.
     let content_id = content_id.map_err(Error::from);
     let aliases = aliases.map_err(Error::from);
+    let kind = kind.map_err(Error::from);
     let contents = contents.map_err(Error::from);
 
-    let futs = future::try_join3(content_id, aliases, contents);
+    let futs = future::try_join4(content_id, aliases, kind, contents);
 
     match res {
         // All is well - get the results when our futures complete.
         Ok(_) => {
-            let (content_id, aliases, chunks) = futs.await?;
+            let (content_id, aliases, kind, chunks) = futs.await?;
 
             let contents = FileContents::Chunked(ChunkedFileContents::new(content_id, chunks));
 
@@ -142,6 +150,7 @@ where
                 sha1,
                 sha256,
                 git_sha1,
+                kind,
                 contents,
             };
 
@@ -79,6 +79,7 @@ pub async fn rechunk<B: Blobstore + Clone + 'static>(
                 blobstore,
                 chunk_size,
                 filestore_config.concurrency,
+                filestore_config.content_defined_chunking,
                 ctx,
                 content_metadata,
             )
@@ -136,13 +137,14 @@ fn uses_larger_chunks(
 
 /// For content, represented by `content_metadata`, rechunk it
 /// if it is unchunked or uses larger chunk sizes
-/// Note: this fn expects `expected_chunk_size` and `concurrency`
-/// instead of `FilestoreConfig` to emphasize that it can only be
-/// called, if the filestore's chunk size is not `None`
+/// Note: this fn expects `expected_chunk_size`, `concurrency` and
+/// `content_defined_chunking` instead of `FilestoreConfig` to emphasize
+/// that it can only be called, if the filestore's chunk size is not `None`
 async fn rechunk_if_uses_larger_chunk_size<B: Blobstore + Clone + 'static>(
     blobstore: &B,
     expected_chunk_size: u64,
     concurrency: usize,
+    content_defined_chunking: bool,
     ctx: &CoreContext,
     content_metadata: ContentMetadata,
 ) -> Result<(ContentMetadata, bool), Error> {
@@ -167,6 +169,7 @@ async fn rechunk_if_uses_larger_chunk_size<B: Blobstore + Clone + 'static>(
         let filestore_config = FilestoreConfig {
             chunk_size: Some(expected_chunk_size),
             concurrency,
+            content_defined_chunking,
         };
 
         let content_metadata: ContentMetadata =
@@ -25,6 +25,9 @@ use mononoke_types::typed_hash::BlobstoreKey;
 use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
 use mononoke_types::ContentMetadataId;
+use mononoke_types::ContentMetadataV2;
+use mononoke_types::ContentMetadataV2Id;
+use mononoke_types::LineEndings;
 use mononoke_types_mocks::contentid::ONES_CTID;
 
 use super::canonical;
@@ -44,6 +47,7 @@ const HELLO_WORLD_LENGTH: u64 = 12;
 const DEFAULT_CONFIG: FilestoreConfig = FilestoreConfig {
     chunk_size: None,
     concurrency: 1,
+    content_defined_chunking: false,
 };
 
 lazy_static! {
@@ -230,6 +234,7 @@ async fn filestore_chunked_put_get(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
 
     let ctx = CoreContext::test_mock(fb);
@@ -252,6 +257,57 @@ async fn filestore_chunked_put_get(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn filestore_content_defined_chunked_put_get(fb: FacebookInit) -> Result<()> {
+    let data = (0..4096u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect::<Bytes>();
+    let req = request(&data);
+    let content_id = canonical(&data);
+
+    let blob = memblob::Memblob::default();
+    let config = FilestoreConfig {
+        chunk_size: Some(64),
+        concurrency: 5,
+        content_defined_chunking: true,
+    };
+
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob, req);
+
+    filestore::store(
+        blob,
+        config,
+        ctx,
+        req,
+        stream::iter(data.chunks(100).map(|c| Ok(Bytes::copy_from_slice(c)))),
+    )
+    .await?;
+
+    let res = filestore::fetch_concat_opt(blob, ctx, &FetchKey::Canonical(content_id)).await?;
+    assert_eq!(res, Some(data.clone()));
+
+    // Ranges that start and end within chunks of different sizes are
+    // reassembled correctly.
+    let bytes = filestore::fetch_range_with_size(
+        blob,
+        ctx,
+        &FetchKey::Canonical(content_id),
+        filestore::Range::sized(1000, 2000),
+    )
+    .await?
+    .ok_or_else(|| Error::msg("Object does not exist"))?
+    .0
+    .try_fold(BytesMut::new(), |mut buff, chunk| async move {
+        buff.extend_from_slice(&chunk);
+        Result::<_, Error>::Ok(buff)
+    })
+    .await?
+    .freeze();
+    assert_eq!(bytes, data.slice(1000..3000));
+    Ok(())
+}
+
 #[fbinit::test]
 async fn filestore_chunked_put_get_nested(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
@@ -259,10 +315,12 @@ async fn filestore_chunked_put_get_nested(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -323,6 +381,7 @@ async fn filestore_chunk_not_found(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -364,6 +423,7 @@ async fn filestore_put_invalid_size(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -393,6 +453,7 @@ async fn filestore_put_content_id(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -436,6 +497,7 @@ async fn filestore_put_sha1(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -478,6 +540,7 @@ async fn filestore_put_git_sha1(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -524,6 +587,7 @@ async fn filestore_put_sha256(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -668,6 +732,7 @@ async fn filestore_get_chunked_range(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
 
     let blob = memblob::Memblob::default();
@@ -834,6 +899,71 @@ async fn filestore_rebuild_metadata(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn filestore_store_metadata_v2(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+    let metadata: ContentMetadataV2Id = content_id.clone().into();
+
+    let expected = Some(ContentMetadataV2 {
+        total_size: HELLO_WORLD_LENGTH,
+        content_id,
+        sha1: *HELLO_WORLD_SHA1,
+        git_sha1: *HELLO_WORLD_GIT_SHA1,
+        sha256: *HELLO_WORLD_SHA256,
+        is_binary: false,
+        is_ascii: true,
+        is_utf8: true,
+        ends_in_newline: false,
+        newline_count: 0,
+        first_line: Some("hello, world".to_string()),
+        line_endings: LineEndings::Absent,
+        has_bom: false,
+        language: None,
+    });
+
+    let blob = memblob::Memblob::default();
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob, req);
+
+    // Store the content in small chunks, so the classification has to work across chunks.
+    filestore::store(
+        blob,
+        FilestoreConfig {
+            chunk_size: Some(5),
+            concurrency: 5,
+            content_defined_chunking: false,
+        },
+        ctx,
+        req,
+        stream::once(future::ready(Ok(Bytes::from(HELLO_WORLD)))),
+    )
+    .await?;
+
+    let res = filestore::get_metadata_v2(blob, ctx, &FetchKey::Canonical(content_id)).await;
+    println!("res = {:#?}", res);
+    assert_eq!(res?, expected);
+
+    // Remove the metadata. It should get recomputed from the content, but
+    // not stored.
+    assert!(
+        blob.unlink(metadata.blobstore_key())
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    let res = filestore::get_metadata_v2(blob, ctx, &FetchKey::Canonical(content_id)).await;
+    println!("res = {:#?}", res);
+    assert_eq!(res?, expected);
+
+    let res =
+        filestore::get_metadata_v2_readonly(blob, ctx, &FetchKey::Canonical(content_id)).await;
+    assert_eq!(res?, Some(None));
+
+    Ok(())
+}
+
 #[fbinit::test]
 async fn filestore_test_missing_metadata(fb: FacebookInit) -> Result<()> {
     let content_id = canonical(HELLO_WORLD);
@@ -913,6 +1043,7 @@ async fn filestore_test_chunked_peek(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
 
     let blob = memblob::Memblob::default();
@@ -1022,6 +1153,7 @@ async fn filestore_store_error(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
 
     let res = filestore::store(
@@ -1048,10 +1180,12 @@ async fn filestore_test_rechunk(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1085,10 +1219,12 @@ async fn filestore_test_rechunk_larger(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1122,11 +1258,13 @@ async fn filestore_test_rechunk_unchunked(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     // This is large enough that the data we upload won't be chunked.
     let large = FilestoreConfig {
         chunk_size: Some(100),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1158,6 +1296,7 @@ async fn filestore_test_rechunk_missing_content(fb: FacebookInit) -> Result<()>
     let conf = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
     borrowed!(ctx, blob);
@@ -1186,6 +1325,7 @@ async fn filestore_chunked_put_get_with_size(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
 
     let ctx = CoreContext::test_mock(fb);
@@ -1229,10 +1369,12 @@ async fn filestore_test_rechunk_if_needed_tiny_unchunked_file(fb: FacebookInit)
     let large1 = FilestoreConfig {
         chunk_size: Some(100),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let large2 = FilestoreConfig {
         chunk_size: Some(200),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1272,10 +1414,12 @@ async fn filestore_test_rechunk_if_needed_large_unchunked_file(fb: FacebookInit)
     let large = FilestoreConfig {
         chunk_size: Some(100),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1310,10 +1454,12 @@ async fn filestore_test_rechunk_if_needed_large_chunks(fb: FacebookInit) -> Resu
     let large = FilestoreConfig {
         chunk_size: Some(5),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
@@ -1348,6 +1494,7 @@ async fn filestore_test_rechunk_if_needed_tiny_chunks(fb: FacebookInit) -> Resul
     let large = FilestoreConfig {
         chunk_size: Some(4),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
 
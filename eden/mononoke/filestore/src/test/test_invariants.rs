@@ -99,6 +99,7 @@ fn test_invariants(fb: FacebookInit) -> Result<()> {
     let config = FilestoreConfig {
         chunk_size: Some(16),
         concurrency: 5,
+        content_defined_chunking: false,
     };
     let ctx = CoreContext::test_mock(fb);
     borrowed!(ctx, blob, memblob: &Arc<_>);
@@ -144,16 +145,19 @@ async fn test_store_bytes_consistency(fb: FacebookInit) -> Result<(), Error> {
         let no_chunking = FilestoreConfig {
             chunk_size: None,
             concurrency: 1,
+            content_defined_chunking: false,
         };
 
         let chunked = FilestoreConfig {
             chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) / 2)),
             concurrency: 1,
+            content_defined_chunking: false,
         };
 
         let too_small_to_chunk = FilestoreConfig {
             chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) * 2)),
             concurrency: 1,
+            content_defined_chunking: false,
         };
 
         let ((id1, len1), fut1) = filestore::store_bytes(memblob, no_chunking, ctx, bytes.clone());
@@ -0,0 +1,17 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Parse arbitrary data as the contents of a Mercurial manifest.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mercurial_types::blobs::ManifestContent;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ManifestContent::parse(data);
+});
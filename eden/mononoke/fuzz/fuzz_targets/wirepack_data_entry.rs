@@ -0,0 +1,27 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Decode arbitrary data as wirepack data entries, as sent by clients in
+//! treegroup parts.  The first byte selects the entry version.
+
+#![no_main]
+
+use bytes_old::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use mercurial_bundles::fuzzing::decode_wirepack_data_entry;
+use mercurial_bundles::wirepack::DataEntryVersion;
+
+fuzz_target!(|data: &[u8]| {
+    let (version, data) = match data.split_first() {
+        Some((version, data)) if version % 2 == 0 => (DataEntryVersion::V1, data),
+        Some((_, data)) => (DataEntryVersion::V2, data),
+        None => return,
+    };
+    let mut buf = BytesMut::from(data);
+    // Decode entries until the data runs out, as the unpacker does.
+    while let Ok(Some(_)) = decode_wirepack_data_entry(&mut buf, version) {}
+});
@@ -0,0 +1,24 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Decode arbitrary data as an SSH wireproto request, as sent by clients.
+
+#![no_main]
+
+use bytes_old::BytesMut;
+use hgproto::sshproto::request::parse_request;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = BytesMut::from(data);
+    let len = buf.len();
+    match parse_request(&mut buf) {
+        Ok(None) => assert_eq!(buf.len(), len, "incomplete request consumed input"),
+        Ok(Some(_)) => assert!(buf.len() < len, "request parsed from no input"),
+        Err(_) => {}
+    }
+});
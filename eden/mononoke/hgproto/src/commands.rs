@@ -16,6 +16,7 @@ use std::io;
 use std::io::BufRead;
 use std::io::Cursor;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::bail;
 use anyhow::Error;
@@ -55,8 +56,15 @@ use tokio_io::AsyncRead;
 
 use crate::dechunker::Dechunker;
 use crate::errors::*;
+use crate::DiscoverySample;
+use crate::DiscoverySampleArgs;
+use crate::GetManifestPageArgs;
 use crate::GetbundleArgs;
 use crate::GettreepackArgs;
+use crate::HeadsPage;
+use crate::HeadsPaginatedArgs;
+use crate::ManifestPage;
+use crate::PrefetchHintsArgs;
 use crate::SingleRequest;
 use crate::SingleResponse;
 
@@ -135,6 +143,14 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
+            SingleRequest::Clonebundles => (
+                hgcmds
+                    .clonebundles()
+                    .map(SingleResponse::Clonebundles)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
             SingleRequest::Debugwireargs { one, two, all_args } => (
                 self.debugwireargs(one, two, all_args)
                     .map(SingleResponse::Debugwireargs)
@@ -142,6 +158,14 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
+            SingleRequest::DiscoverySample(args) => (
+                hgcmds
+                    .discoverysample(args)
+                    .map(SingleResponse::DiscoverySample)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
             SingleRequest::Getbundle(args) => (
                 hgcmds
                     .getbundle(args)
@@ -149,6 +173,14 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
+            SingleRequest::GetManifestPage(args) => (
+                hgcmds
+                    .getmanifestpage(args)
+                    .map(SingleResponse::GetManifestPage)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
             SingleRequest::Heads => (
                 hgcmds
                     .heads()
@@ -157,6 +189,22 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
+            SingleRequest::HeadsPaginated(args) => (
+                hgcmds
+                    .headspaginated(args)
+                    .map(SingleResponse::HeadsPaginated)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
+            SingleRequest::PrefetchHints(args) => (
+                hgcmds
+                    .prefetchhints(args)
+                    .map(SingleResponse::PrefetchHints)
+                    .into_stream()
+                    .boxify(),
+                ok(instream).boxify(),
+            ),
             SingleRequest::Hello => (
                 hgcmds
                     .hello()
@@ -165,9 +213,13 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
                     .boxify(),
                 ok(instream).boxify(),
             ),
-            SingleRequest::Listkeys { namespace } => (
+            SingleRequest::Listkeys {
+                namespace,
+                prefix,
+                limit,
+            } => (
                 hgcmds
-                    .listkeys(namespace)
+                    .listkeys(namespace, prefix, limit)
                     .map(SingleResponse::Listkeys)
                     .into_stream()
                     .boxify(),
@@ -278,7 +330,15 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
         S: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
     {
         let hgcmds = &self.commands;
-        let dechunker = Dechunker::new(instream);
+        let maybe_full_content = if hgcmds.should_preserve_raw_bundle2() {
+            Some(Arc::new(Mutex::new(bytes::BytesMut::new())))
+        } else {
+            None
+        };
+        let dechunker = match &maybe_full_content {
+            Some(full_content) => Dechunker::with_full_content(instream, full_content.clone()),
+            None => Dechunker::new(instream),
+        };
 
         let bundle2stream =
             Bundle2Stream::new(self.logger.clone(), LimitedAsyncRead::new(dechunker));
@@ -318,7 +378,13 @@ impl<H: HgCommands + Send + 'static> HgCommandHandler<H> {
             Either::A(ok(SingleResponse::ReadyForStream)),
             Either::B({
                 hgcmds
-                    .unbundle(heads, bundle2stream, respondlightly, replaydata)
+                    .unbundle(
+                        heads,
+                        bundle2stream,
+                        maybe_full_content,
+                        respondlightly,
+                        replaydata,
+                    )
                     .map(SingleResponse::Unbundle)
             }),
         ]);
@@ -630,24 +696,54 @@ pub trait HgCommands {
         unimplemented("clienttelemetry")
     }
 
+    // @wireprotocommand('clonebundles')
+    fn clonebundles(&self) -> HgCommandRes<Bytes> {
+        unimplemented("clonebundles")
+    }
+
+    // @wireprotocommand('discoverysample', '*')
+    fn discoverysample(&self, _args: DiscoverySampleArgs) -> HgCommandRes<DiscoverySample> {
+        unimplemented("discoverysample")
+    }
+
     // @wireprotocommand('getbundle', '*')
     // TODO: make this streaming
     fn getbundle(&self, _args: GetbundleArgs) -> BoxStream<Bytes, Error> {
         once(Err(ErrorKind::Unimplemented("getbundle".into()).into())).boxify()
     }
 
+    // @wireprotocommand('getmanifestpage', '*')
+    fn getmanifestpage(&self, _args: GetManifestPageArgs) -> HgCommandRes<ManifestPage> {
+        unimplemented("getmanifestpage")
+    }
+
+    // @wireprotocommand('prefetchhints', '*')
+    fn prefetchhints(&self, _args: PrefetchHintsArgs) -> HgCommandRes<usize> {
+        unimplemented("prefetchhints")
+    }
+
     // @wireprotocommand('heads')
     fn heads(&self) -> HgCommandRes<HashSet<HgChangesetId>> {
         unimplemented("heads")
     }
 
+    // @wireprotocommand('headspaginated', '*')
+    fn headspaginated(&self, _args: HeadsPaginatedArgs) -> HgCommandRes<HeadsPage> {
+        unimplemented("headspaginated")
+    }
+
     // @wireprotocommand('hello')
     fn hello(&self) -> HgCommandRes<HashMap<String, Vec<String>>> {
         unimplemented("hello")
     }
 
-    // @wireprotocommand('listkeys', 'namespace')
-    fn listkeys(&self, _namespace: String) -> HgCommandRes<HashMap<Vec<u8>, Vec<u8>>> {
+    // @wireprotocommand('listkeys', 'namespace *')
+    fn listkeys(
+        &self,
+        _namespace: String,
+        _prefix: String,
+        _limit: Option<u64>,
+    ) -> HgCommandRes<HashMap<Vec<u8>, Vec<u8>>> {
         unimplemented("listkeys")
     }
 
@@ -675,11 +771,18 @@ pub trait HgCommands {
         unimplemented("knownnodes")
     }
 
+    // Whether `unbundle` should be given a copy of the raw bundle2 that the
+    // client sent, e.g. to preserve it for debugging.
+    fn should_preserve_raw_bundle2(&self) -> bool {
+        false
+    }
+
     // @wireprotocommand('unbundle', 'heads')
     fn unbundle(
         &self,
         _heads: Vec<String>,
         _stream: BoxStream<Bundle2Item<'static>, Error>,
+        _maybe_full_content: Option<Arc<Mutex<bytes::BytesMut>>>,
         _respondlightly: Option<bool>,
         _replaydata: Option<String>,
     ) -> HgCommandRes<Bytes> {
@@ -18,7 +18,10 @@
 use std::io;
 use std::io::BufRead;
 use std::io::Read;
+use std::sync::Arc;
+use std::sync::Mutex;
 
+use bytes::BytesMut;
 use futures::future::poll_fn;
 use futures::Async;
 use futures::Future;
@@ -34,6 +37,7 @@ use tokio_io::AsyncRead;
 pub struct Dechunker<R> {
     bufread: R,
     state: DechunkerState,
+    maybe_full_content: Option<Arc<Mutex<BytesMut>>>,
 }
 
 enum DechunkerState {
@@ -52,6 +56,18 @@ where
         Self {
             bufread,
             state: ParsingInt(Vec::new()),
+            maybe_full_content: None,
+        }
+    }
+
+    /// Like `new`, but also appends every dechunked byte that is read or
+    /// consumed to `full_content`, so that the caller can keep a copy of the
+    /// whole decoded stream.
+    pub fn with_full_content(bufread: R, full_content: Arc<Mutex<BytesMut>>) -> Self {
+        Self {
+            bufread,
+            state: ParsingInt(Vec::new()),
+            maybe_full_content: Some(full_content),
         }
     }
 
@@ -154,6 +170,12 @@ where
         };
 
         let buf_size = self.bufread.read(&mut buf[0..buf_size])?;
+        if let Some(full_content) = &self.maybe_full_content {
+            full_content
+                .lock()
+                .expect("lock poisoned")
+                .extend_from_slice(&buf[0..buf_size]);
+        }
         self.consume_chunk(buf_size);
         Ok(buf_size)
     }
@@ -183,6 +205,16 @@ where
     }
 
     fn consume(&mut self, amt: usize) {
+        if let Some(full_content) = &self.maybe_full_content {
+            // The bytes being consumed were returned by a previous
+            // `fill_buf`, so they are still buffered and this doesn't do IO.
+            if let Ok(buf) = self.bufread.fill_buf() {
+                full_content
+                    .lock()
+                    .expect("lock poisoned")
+                    .extend_from_slice(&buf[0..amt]);
+            }
+        }
         self.consume_chunk(amt);
         self.bufread.consume(amt);
     }
@@ -260,6 +292,31 @@ mod tests {
                 Err(e) => TestResult::error(format!("{}", e)),
             }
         }
+
+        fn test_full_content(chunks: Chunks, remainder: Vec<u8>, use_read: bool) -> TestResult {
+            let chunks = &chunks;
+            let remainder = remainder.as_slice();
+            let concat_chunks = concat_chunks(chunks, remainder);
+
+            let full_content = Arc::new(Mutex::new(BytesMut::new()));
+            let dechunker = Dechunker::with_full_content(
+                Cursor::new(&concat_chunks),
+                full_content.clone(),
+            );
+
+            let res = if use_read {
+                check_read_api(dechunker, chunks, remainder)
+            } else {
+                check_bufread_api(dechunker, chunks, remainder)
+            };
+            if let Err(e) = res {
+                return TestResult::error(format!("{}", e));
+            }
+
+            let expected: Vec<u8> = chunks.0.iter().flatten().copied().collect();
+            let full_content = full_content.lock().unwrap();
+            TestResult::from_bool(full_content.as_ref() == expected.as_slice())
+        }
     }
 
     fn concat_chunks(chunks: &Chunks, remainder: &[u8]) -> Vec<u8> {
@@ -21,6 +21,15 @@ pub enum ErrorKind {
     Bundle2Invalid(String),
     #[error("unknown escape character in batch command '{0}'")]
     BatchEscape(u8),
+    #[error("argument '{argument}' of '{command}' has size {size}, over the limit of {limit}")]
+    ArgumentTooLarge {
+        command: String,
+        argument: String,
+        size: usize,
+        limit: usize,
+    },
+    #[error("request of {size} bytes is over the limit of {limit} bytes")]
+    RequestTooLarge { size: usize, limit: usize },
     #[error("Repo error")]
     RepoError,
     #[error("cannot serve revlog repos")]
@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Framed variant of the hg protocol
+//!
+//! Over ssh, the boundaries of requests and responses can only be found by
+//! parsing them, which needs knowledge of each command.  This variant puts
+//! each request and response in explicit frames, so that transports that
+//! carry messages rather than a byte stream, such as websockets or HTTP/2,
+//! and the proxies in front of them, can find the boundaries without
+//! understanding the commands.
+//!
+//! The encoding is:
+//! ```text
+//! request := <length: u32 BE> <ssh-encoded request>{length}
+//! response := data* end
+//! data := 'd' <length: u32 BE> <ssh-encoded response bytes>{length}
+//! end := 'e' <0: u32 BE>
+//! ```
+//!
+//! Commands and their arguments are encoded as they are over ssh (see
+//! `sshproto`).  Streamed arguments, such as the bundle of `unbundle`,
+//! follow the request frame in the same chunked encoding as over ssh.
+//! Every request is answered by a sequence of data frames, concatenating to
+//! what would be sent over ssh, followed by an end frame.
+
+use anyhow::Error;
+use anyhow::Result;
+use bytes_old::BufMut;
+use bytes_old::Bytes;
+use bytes_old::BytesMut;
+use futures::stream;
+use futures::Stream;
+use futures_ext::StreamExt;
+use tokio_io::codec::Decoder;
+
+use crate::errors::ErrorKind;
+use crate::handler::OutputStream;
+use crate::handler::ResponseEncoder;
+use crate::limits::MAX_REQUEST_SIZE;
+use crate::sshproto;
+use crate::Request;
+use crate::Response;
+
+/// Length of the header of a request frame.
+const REQUEST_HEADER_LEN: usize = 4;
+
+/// Frame containing part of a response.
+pub const FRAME_DATA: u8 = b'd';
+/// Frame marking the end of the response to a request.
+pub const FRAME_END: u8 = b'e';
+
+#[derive(Clone)]
+pub struct HgFramedCommandEncode;
+#[derive(Clone)]
+pub struct HgFramedCommandDecode;
+
+fn encode_frame(kind: u8, payload: Bytes) -> Bytes {
+    let mut frame = BytesMut::with_capacity(1 + 4 + payload.len());
+    frame.put_u8(kind);
+    frame.put_u32_be(payload.len() as u32);
+    frame.put_slice(&payload);
+    frame.freeze()
+}
+
+impl ResponseEncoder for HgFramedCommandEncode {
+    fn encode(&self, response: Response) -> OutputStream {
+        sshproto::response::encode(response)
+            .map(|bytes| encode_frame(FRAME_DATA, bytes))
+            .boxify()
+    }
+
+    fn end_of_response(&self) -> OutputStream {
+        stream::once(Ok(encode_frame(FRAME_END, Bytes::new()))).boxify()
+    }
+}
+
+impl Decoder for HgFramedCommandDecode {
+    type Item = Request;
+    type Error = Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Request>> {
+        if buf.len() < REQUEST_HEADER_LEN {
+            return Ok(None);
+        }
+        let mut len_bytes = [0; REQUEST_HEADER_LEN];
+        len_bytes.copy_from_slice(&buf[..REQUEST_HEADER_LEN]);
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        if len > MAX_REQUEST_SIZE {
+            return Err(ErrorKind::RequestTooLarge {
+                size: len,
+                limit: MAX_REQUEST_SIZE,
+            }
+            .into());
+        }
+        if buf.len() < REQUEST_HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let mut frame = buf.split_to(REQUEST_HEADER_LEN + len);
+        let mut payload = frame.split_off(REQUEST_HEADER_LEN);
+        match sshproto::request::parse_request(&mut payload)? {
+            Some(request) if payload.is_empty() => Ok(Some(request)),
+            Some(_) => Err(ErrorKind::UnconsumedData(
+                String::from_utf8_lossy(payload.as_ref()).into_owned(),
+            )
+            .into()),
+            None => Err(ErrorKind::CommandParse(
+                String::from_utf8_lossy(payload.as_ref()).into_owned(),
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+
+    use super::*;
+    use crate::SingleRequest;
+    use crate::SingleResponse;
+
+    fn request_frame(payload: &[u8]) -> BytesMut {
+        let mut frame = BytesMut::with_capacity(REQUEST_HEADER_LEN + payload.len());
+        frame.put_u32_be(payload.len() as u32);
+        frame.put_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_decode_request() -> Result<()> {
+        let mut buf = request_frame(b"heads\n");
+        buf.extend_from_slice(b"hel");
+
+        // Nothing is decoded until the whole frame has arrived.
+        let mut partial = BytesMut::from(&buf[..4]);
+        assert_eq!(HgFramedCommandDecode.decode(&mut partial)?, None);
+
+        assert_eq!(
+            HgFramedCommandDecode.decode(&mut buf)?,
+            Some(Request::Single(SingleRequest::Heads))
+        );
+        assert_eq!(buf, BytesMut::from(&b"hel"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_request_with_trailing_data() {
+        let mut buf = request_frame(b"heads\nhello\n");
+        assert!(HgFramedCommandDecode.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_request_too_large() {
+        let mut buf = BytesMut::with_capacity(REQUEST_HEADER_LEN);
+        buf.put_u32_be(MAX_REQUEST_SIZE as u32 + 1);
+        let err = HgFramedCommandDecode.decode(&mut buf).unwrap_err();
+        match err.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::RequestTooLarge { .. }) => {}
+            _ => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let response = Response::Single(SingleResponse::Known(vec![true, false]));
+        let frames = HgFramedCommandEncode
+            .encode(response)
+            .chain(HgFramedCommandEncode.end_of_response())
+            .collect()
+            .wait()
+            .unwrap();
+        let encoded: Vec<u8> = frames.iter().flat_map(|frame| frame.to_vec()).collect();
+        assert_eq!(
+            encoded,
+            b"d\x00\x00\x00\x022\nd\x00\x00\x00\x0210e\x00\x00\x00\x00".to_vec()
+        );
+    }
+}
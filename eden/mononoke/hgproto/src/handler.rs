@@ -6,9 +6,11 @@
  */
 
 use std::io;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use anyhow::anyhow;
 use anyhow::Error;
 use bytes_old::Bytes;
 use failure_ext::FutureErrorContext;
@@ -31,6 +33,10 @@ use tokio_io::codec::Decoder;
 
 use crate::commands::HgCommandHandler;
 use crate::errors::*;
+use crate::framedproto::HgFramedCommandDecode;
+use crate::framedproto::HgFramedCommandEncode;
+use crate::sshproto::HgSshCommandDecode;
+use crate::sshproto::HgSshCommandEncode;
 use crate::HgCommands;
 use crate::Request;
 use crate::Response;
@@ -39,6 +45,41 @@ pub type OutputStream = BoxStream<Bytes, Error>;
 
 pub trait ResponseEncoder {
     fn encode(&self, response: Response) -> OutputStream;
+
+    /// Sent once all the responses to a request have been encoded.
+    fn end_of_response(&self) -> OutputStream {
+        stream::empty().boxify()
+    }
+}
+
+/// The encodings of the hg protocol that the server can speak.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WireProtocol {
+    /// The line-oriented encoding used over ssh.
+    Ssh,
+    /// The ssh encoding in explicit frames, for message-oriented transports.
+    Framed,
+}
+
+impl WireProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WireProtocol::Ssh => "ssh",
+            WireProtocol::Framed => "framed",
+        }
+    }
+}
+
+impl FromStr for WireProtocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ssh" => Ok(WireProtocol::Ssh),
+            "framed" => Ok(WireProtocol::Framed),
+            _ => Err(anyhow!("unknown wire protocol '{}'", s)),
+        }
+    }
 }
 
 pub struct HgProtoHandler {
@@ -82,6 +123,44 @@ impl HgProtoHandler {
             outstream: handle(input, inner),
         }
     }
+
+    /// Create a handler that speaks the given encoding of the protocol.
+    pub fn with_protocol<In, H>(
+        protocol: WireProtocol,
+        logger: Logger,
+        input: In,
+        commands: H,
+        wireproto_calls: Arc<Mutex<Vec<String>>>,
+        qps: Option<Arc<Qps>>,
+        src_region: Option<String>,
+    ) -> Self
+    where
+        In: Stream<Item = Bytes, Error = io::Error> + Send + 'static,
+        H: HgCommands + Send + Sync + 'static,
+    {
+        match protocol {
+            WireProtocol::Ssh => Self::new(
+                logger,
+                input,
+                commands,
+                HgSshCommandDecode,
+                HgSshCommandEncode,
+                wireproto_calls,
+                qps,
+                src_region,
+            ),
+            WireProtocol::Framed => Self::new(
+                logger,
+                input,
+                commands,
+                HgFramedCommandDecode,
+                HgFramedCommandEncode,
+                wireproto_calls,
+                qps,
+                src_region,
+            ),
+        }
+    }
 }
 
 impl Stream for HgProtoHandler {
@@ -141,8 +220,12 @@ where
                                 Either::B(ok((
                                     Some(
                                         resps
-                                            .map(move |resp| handler.respenc.encode(resp))
+                                            .map({
+                                                let handler = handler.clone();
+                                                move |resp| handler.respenc.encode(resp)
+                                            })
                                             .flatten()
+                                            .chain(handler.respenc.end_of_response())
                                             .boxify(),
                                     ),
                                     Some(remainder),
@@ -21,14 +21,18 @@ use std::sync::Mutex;
 
 use bytes_old::Bytes;
 use mercurial_types::HgChangesetId;
+use mercurial_types::HgEntryId;
 use mercurial_types::HgManifestId;
 use mononoke_types::MPath;
+use mononoke_types::MPathElement;
 
 pub mod batch;
 mod commands;
 mod dechunker;
 mod errors;
+pub mod framedproto;
 mod handler;
+pub mod limits;
 pub mod sshproto;
 
 const MAX_NODES_TO_LOG: usize = 5;
@@ -59,16 +63,25 @@ pub enum SingleRequest {
     ClientTelemetry {
         args: HashMap<Vec<u8>, Vec<u8>>,
     },
+    Clonebundles,
+    DiscoverySample(DiscoverySampleArgs),
     Debugwireargs {
         one: Vec<u8>,
         two: Vec<u8>,
         all_args: HashMap<Vec<u8>, Vec<u8>>,
     },
     Getbundle(GetbundleArgs),
+    GetManifestPage(GetManifestPageArgs),
     Heads,
+    HeadsPaginated(HeadsPaginatedArgs),
     Hello,
+    PrefetchHints(PrefetchHintsArgs),
     Listkeys {
         namespace: String,
+        /// Only list keys that start with this prefix.
+        prefix: String,
+        /// List at most this many keys.
+        limit: Option<u64>,
     },
     ListKeysPatterns {
         namespace: String,
@@ -109,10 +122,15 @@ impl SingleRequest {
             SingleRequest::Branchmap => "branchmap",
             SingleRequest::Capabilities => "capabilities",
             SingleRequest::ClientTelemetry { .. } => "clienttelemetry",
+            SingleRequest::Clonebundles => "clonebundles",
             SingleRequest::Debugwireargs { .. } => "debugwireargs",
+            SingleRequest::DiscoverySample(_) => "discoverysample",
             SingleRequest::Getbundle(_) => "getbundle",
+            SingleRequest::GetManifestPage(_) => "getmanifestpage",
             SingleRequest::Heads => "heads",
+            SingleRequest::HeadsPaginated(_) => "headspaginated",
             SingleRequest::Hello => "hello",
+            SingleRequest::PrefetchHints(_) => "prefetchhints",
             SingleRequest::Listkeys { .. } => "listkeys",
             SingleRequest::Lookup { .. } => "lookup",
             SingleRequest::Known { .. } => "known",
@@ -189,6 +207,102 @@ pub struct GettreepackArgs {
     pub depth: Option<usize>,
 }
 
+/// The arguments that `headspaginated` accepts, in a separate struct for
+/// the convenience of callers.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HeadsPaginatedArgs {
+    /// Only return heads of bookmarks whose names start with this prefix.
+    pub prefix: String,
+    /// Only return heads of bookmarks whose names sort after this one. This
+    /// is the cursor returned with the previous page.
+    pub after: Option<String>,
+    /// The maximum number of bookmarks to consider for this page.
+    pub limit: Option<u64>,
+    /// Only return heads committed at or after this time, in seconds since
+    /// the epoch.
+    pub since: Option<u64>,
+}
+
+/// The arguments that `getmanifestpage` accepts, in a separate struct for
+/// the convenience of callers.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GetManifestPageArgs {
+    /// The directory the manifest is for.  If set to `None`, that means
+    /// "root of the repo".
+    pub rootdir: Option<MPath>,
+    /// The manifest node to list.
+    pub mfnode: HgManifestId,
+    /// Only return entries whose names sort after this one.  This is the
+    /// continuation token returned with the previous page.
+    pub after: Option<Vec<u8>>,
+    /// The maximum number of entries to return.
+    pub limit: Option<u64>,
+}
+
+/// A page of manifest entries returned by `getmanifestpage`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ManifestPage {
+    /// Entries of the manifest, ordered by name.
+    pub entries: Vec<(MPathElement, HgEntryId)>,
+    /// The token to pass as `after` to fetch the next page, or `None` if
+    /// this is the last page.
+    pub next: Option<Vec<u8>>,
+}
+
+/// A page of heads returned by `headspaginated`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HeadsPage {
+    /// Bookmark names and the commits they point to, ordered by name.
+    pub heads: Vec<(String, HgChangesetId)>,
+    /// The cursor to pass as `after` to fetch the next page, or `None` if
+    /// this is the last page.
+    pub next: Option<String>,
+}
+
+/// The arguments that `prefetchhints` accepts, in a separate struct for
+/// the convenience of callers.
+///
+/// Clients send hints for data they are about to request, such as the
+/// commits they will check out after a pull, so that the server can start
+/// fetching it from the blobstore before the requests arrive.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PrefetchHintsArgs {
+    /// The commits the client is about to request data for.
+    pub commits: Vec<HgChangesetId>,
+    /// The directories of those commits that the client is about to request
+    /// trees for.  The root directory is always prefetched.
+    pub directories: Vec<Bytes>,
+}
+
+/// The arguments that `discoverysample` accepts, in a separate struct for
+/// the convenience of callers.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DiscoverySampleArgs {
+    /// The client's heads.
+    pub heads: Vec<HgChangesetId>,
+    /// The maximum number of sample nodes to return.
+    pub size: Option<u64>,
+}
+
+/// The result of `discoverysample`.
+///
+/// Instead of bisecting the commit graph with `known` and `between` round
+/// trips, clients learn in one exchange which of their heads the server has,
+/// and which of a sample of the server's ancestors they have themselves.  The
+/// sample is taken at exponentially increasing distances from each server
+/// head, so a client that is far behind can bound the common ancestors in a
+/// few exchanges rather than dozens.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DiscoverySample {
+    /// Whether the server has each of the client's heads, in the order they
+    /// were given.
+    pub known: Vec<bool>,
+    /// The server's heads.
+    pub heads: Vec<HgChangesetId>,
+    /// Ancestors of the server's heads.
+    pub sample: Vec<HgChangesetId>,
+}
+
 #[derive(Debug)]
 pub enum Response {
     Batch(Vec<SingleResponse>),
@@ -201,10 +315,15 @@ pub enum SingleResponse {
     Branchmap(HashMap<String, HashSet<HgChangesetId>>),
     Capabilities(Vec<String>),
     ClientTelemetry(String),
+    Clonebundles(Bytes),
     Debugwireargs(Bytes),
+    DiscoverySample(DiscoverySample),
     Getbundle(Bytes),
+    GetManifestPage(ManifestPage),
     Heads(HashSet<HgChangesetId>),
+    HeadsPaginated(HeadsPage),
     Hello(HashMap<String, Vec<String>>),
+    PrefetchHints(usize),
     Listkeys(HashMap<Vec<u8>, Vec<u8>>),
     ListKeysPatterns(BTreeMap<String, HgChangesetId>),
     Lookup(Bytes),
@@ -236,3 +355,4 @@ pub use commands::HgCommandRes;
 pub use commands::HgCommands;
 pub use errors::ErrorKind;
 pub use handler::HgProtoHandler;
+pub use handler::WireProtocol;
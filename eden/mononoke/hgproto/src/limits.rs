@@ -0,0 +1,170 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Limits on the size of wireproto requests.
+//!
+//! Requests are buffered and decoded in full before they are handled, so
+//! without limits a client can make the server allocate as much memory as it
+//! likes, either by sending an enormous request, or by claiming that one is
+//! coming.  The limits are generous enough that no well-behaved client
+//! should reach them.
+
+use crate::errors::ErrorKind;
+use crate::Request;
+use crate::SingleRequest;
+
+/// Maximum size in bytes of a request that is still being received.
+pub const MAX_REQUEST_SIZE: usize = 256 * 1024 * 1024;
+
+/// Maximum number of parameters of a single command.
+pub const MAX_PARAMS: usize = 1024;
+
+/// Maximum number of commands in a batch.
+pub const MAX_BATCH_COMMANDS: usize = 10_000;
+
+/// Maximum number of nodes in a single argument, such as the heads of
+/// `getbundle` or the nodes of `known`.
+pub const MAX_NODES: usize = 1_000_000;
+
+/// Maximum number of directories requested by `gettreepack`.
+pub const MAX_DIRECTORIES: usize = 1_000_000;
+
+/// Maximum length of a bookmark namespace.
+pub const MAX_NAMESPACE_LEN: usize = 256;
+
+/// Maximum number of patterns of `listkeyspatterns`, and of namespaces
+/// requested by `getbundle`.
+pub const MAX_PATTERNS: usize = 10_000;
+
+/// Maximum length of a single string argument, such as a lookup key or a
+/// bookmark pattern.
+pub const MAX_STRING_LEN: usize = 64 * 1024;
+
+fn check(command: &str, argument: &str, size: usize, limit: usize) -> Result<(), ErrorKind> {
+    if size > limit {
+        return Err(ErrorKind::ArgumentTooLarge {
+            command: command.to_string(),
+            argument: argument.to_string(),
+            size,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+fn check_each<'a>(
+    command: &str,
+    argument: &str,
+    values: impl IntoIterator<Item = &'a [u8]>,
+    limit: usize,
+) -> Result<(), ErrorKind> {
+    values
+        .into_iter()
+        .try_for_each(|value| check(command, argument, value.len(), limit))
+}
+
+/// Check that a decoded request is within the limits for its commands.
+pub fn validate_request(request: &Request) -> Result<(), ErrorKind> {
+    match request {
+        Request::Batch(requests) => {
+            check("batch", "cmds", requests.len(), MAX_BATCH_COMMANDS)?;
+            requests.iter().try_for_each(validate_single_request)
+        }
+        Request::Single(request) => validate_single_request(request),
+    }
+}
+
+fn validate_single_request(request: &SingleRequest) -> Result<(), ErrorKind> {
+    use SingleRequest::*;
+
+    let command = request.name();
+    match request {
+        Between { pairs } => check(command, "pairs", pairs.len(), MAX_NODES),
+        DiscoverySample(args) => check(command, "heads", args.heads.len(), MAX_NODES),
+        Getbundle(args) => {
+            check(command, "heads", args.heads.len(), MAX_NODES)?;
+            check(command, "common", args.common.len(), MAX_NODES)?;
+            check(command, "listkeys", args.listkeys.len(), MAX_PATTERNS)?;
+            check_each(
+                command,
+                "listkeys",
+                args.listkeys.iter().map(Vec::as_slice),
+                MAX_NAMESPACE_LEN,
+            )
+        }
+        GetManifestPage(args) => check(
+            command,
+            "after",
+            args.after.as_ref().map_or(0, Vec::len),
+            MAX_STRING_LEN,
+        ),
+        HeadsPaginated(args) => {
+            check(command, "prefix", args.prefix.len(), MAX_STRING_LEN)?;
+            check(
+                command,
+                "after",
+                args.after.as_ref().map_or(0, String::len),
+                MAX_STRING_LEN,
+            )
+        }
+        Listkeys {
+            namespace, prefix, ..
+        } => {
+            check(command, "namespace", namespace.len(), MAX_NAMESPACE_LEN)?;
+            check(command, "prefix", prefix.len(), MAX_STRING_LEN)
+        }
+        ListKeysPatterns {
+            namespace,
+            patterns,
+        } => {
+            check(command, "namespace", namespace.len(), MAX_NAMESPACE_LEN)?;
+            check(command, "patterns", patterns.len(), MAX_PATTERNS)?;
+            check_each(
+                command,
+                "patterns",
+                patterns.iter().map(String::as_bytes),
+                MAX_STRING_LEN,
+            )
+        }
+        PrefetchHints(args) => {
+            check(command, "commits", args.commits.len(), MAX_NODES)?;
+            check(
+                command,
+                "directories",
+                args.directories.len(),
+                MAX_DIRECTORIES,
+            )
+        }
+        Lookup { key } => check(command, "key", key.len(), MAX_STRING_LEN),
+        Known { nodes } | Knownnodes { nodes } | GetCommitData { nodes } => {
+            check(command, "nodes", nodes.len(), MAX_NODES)
+        }
+        Unbundle { heads } | UnbundleReplay { heads, .. } => {
+            check(command, "heads", heads.len(), MAX_NODES)
+        }
+        Gettreepack(args) => {
+            check(command, "mfnodes", args.mfnodes.len(), MAX_NODES)?;
+            check(command, "basemfnodes", args.basemfnodes.len(), MAX_NODES)?;
+            check(
+                command,
+                "directories",
+                args.directories.len(),
+                MAX_DIRECTORIES,
+            )
+        }
+        Branchmap
+        | Capabilities
+        | ClientTelemetry { .. }
+        | Clonebundles
+        | Debugwireargs { .. }
+        | Heads
+        | Hello
+        | StreamOutShallow { .. }
+        | GetpackV1
+        | GetpackV2 => Ok(()),
+    }
+}
@@ -29,6 +29,7 @@ use nom::eof;
 use nom::error_position;
 use nom::is_alphanumeric;
 use nom::is_digit;
+use nom::is_hex_digit;
 use nom::many0;
 use nom::map;
 use nom::map_res;
@@ -51,13 +52,21 @@ use nom::Slice;
 
 use crate::batch;
 use crate::errors;
+use crate::limits::validate_request;
+use crate::limits::MAX_PARAMS;
+use crate::limits::MAX_REQUEST_SIZE;
+use crate::DiscoverySampleArgs;
+use crate::GetManifestPageArgs;
 use crate::GetbundleArgs;
 use crate::GettreepackArgs;
+use crate::HeadsPaginatedArgs;
+use crate::PrefetchHintsArgs;
 use crate::Request;
 use crate::SingleRequest;
 
 const BAD_UTF8_ERR_CODE: u32 = 111;
 const BAD_PATH_ERR_CODE: u32 = 222;
+const TOO_MANY_PARAMS_ERR_CODE: u32 = 333;
 
 /// Parse an unsigned decimal integer. If it reaches the end of input, it returns Incomplete,
 /// as there may be more digits following
@@ -120,6 +129,21 @@ named!(
     })
 );
 
+// Assumption: input is complete
+named!(
+    integer_complete<u64>,
+    map_res!(
+        map_res!(take_while1!(is_digit), str::from_utf8),
+        u64::from_str
+    )
+);
+
+// Assumption: input is complete
+named!(
+    hex_bytes_complete<Vec<u8>>,
+    map_res!(take_while!(is_hex_digit), |v: &[u8]| Vec::from_hex(v))
+);
+
 named!(
     batch_param_comma_separated<Bytes>,
     map_res!(
@@ -166,6 +190,11 @@ named!(
 /// "count" is the number of required parameters, including the "*" parameter - but *not*
 /// the parameters that the "*" parameter expands to.
 fn params_ref(inp: &[u8], count: usize) -> IResult<&[u8], HashMap<&[u8], &[u8]>> {
+    // The count comes from the client, so must be checked before anything
+    // is allocated for it.
+    if count > MAX_PARAMS {
+        return IResult::Error(Err::Code(ErrorKind::Custom(TOO_MANY_PARAMS_ERR_CODE)));
+    }
     let mut inp = inp;
     let mut have = 0;
 
@@ -568,6 +597,15 @@ fn parse_batchrequest(inp: &[u8]) -> IResult<&[u8], Vec<SingleRequest>> {
 }
 
 pub fn parse_request(buf: &mut BytesMut) -> Result<Option<Request>> {
+    parse_request_with_max_size(buf, MAX_REQUEST_SIZE)
+}
+
+/// Parse a request, rejecting it once more than `max_request_size` bytes of
+/// it have been buffered without it being complete.
+fn parse_request_with_max_size(
+    buf: &mut BytesMut,
+    max_request_size: usize,
+) -> Result<Option<Request>> {
     let res = {
         let origlen = buf.len();
         let parse_res = alt!(
@@ -576,8 +614,21 @@ pub fn parse_request(buf: &mut BytesMut) -> Result<Option<Request>> {
         );
 
         match parse_res {
-            IResult::Done(rest, val) => Some((origlen - rest.len(), val)),
-            IResult::Incomplete(_) => None,
+            IResult::Done(rest, val) => {
+                validate_request(&val)?;
+                Some((origlen - rest.len(), val))
+            }
+            IResult::Incomplete(_) => {
+                // Don't keep buffering a request that claims to be larger
+                // than any we'd be willing to handle.
+                if origlen > max_request_size {
+                    Err(errors::ErrorKind::RequestTooLarge {
+                        size: origlen,
+                        limit: max_request_size,
+                    })?
+                }
+                None
+            }
             IResult::Error(err) => {
                 println!("parse_request parsing error: {:?}", err);
                 Err(errors::ErrorKind::CommandParse(
@@ -609,6 +660,7 @@ fn parse_with_params(
           })
         | command!("branchmap", Branchmap, parse_params, {})
         | command!("capabilities", Capabilities, parse_params, {})
+        | command!("clonebundles", Clonebundles, parse_params, {})
         | call!(parse_command, "debugwireargs", parse_params, 2+1,
             |kv| Ok(Debugwireargs {
                 one: parseval(&kv, "one", ident_complete)?.to_vec(),
@@ -619,6 +671,11 @@ fn parse_with_params(
             |kv| Ok(ClientTelemetry{
                 args: kv,
             }))
+        | call!(parse_command, "discoverysample", parse_params, 1,
+            |kv| Ok(DiscoverySample(DiscoverySampleArgs {
+                heads: parseval_default(&kv, "heads", hashlist)?,
+                size: parseval_option(&kv, "size", integer_complete)?,
+            })))
         | call!(parse_command, "getbundle", parse_params, 1,
             |kv| Ok(Getbundle(GetbundleArgs {
                 // Some params are currently ignored, like:
@@ -632,11 +689,33 @@ fn parse_with_params(
                 listkeys: parseval_default(&kv, "listkeys", commavalues)?,
                 phases: parseval_default(&kv, "phases", boolean)?,
             })))
+        | call!(parse_command, "getmanifestpage", parse_params, 1,
+            |kv| Ok(GetManifestPage(GetManifestPageArgs {
+                rootdir: parseval_default(&kv, "rootdir", path_complete)?,
+                mfnode: parseval(&kv, "mfnode", manifestid)?,
+                after: parseval_option(&kv, "after", hex_bytes_complete)?,
+                limit: parseval_option(&kv, "limit", integer_complete)?,
+            })))
         | command!("heads", Heads, parse_params, {})
+        | call!(parse_command, "headspaginated", parse_params, 1,
+            |kv| Ok(HeadsPaginated(HeadsPaginatedArgs {
+                prefix: parseval_default(&kv, "prefix", utf8_string_complete)?,
+                after: parseval_option(&kv, "after", utf8_string_complete)?,
+                limit: parseval_option(&kv, "limit", integer_complete)?,
+                since: parseval_option(&kv, "since", integer_complete)?,
+            })))
         | command!("hello", Hello, parse_params, {})
-        | command!("listkeys", Listkeys, parse_params, {
-              namespace => ident_string,
-        })
+        | call!(parse_command, "prefetchhints", parse_params, 1,
+            |kv| Ok(PrefetchHints(PrefetchHintsArgs {
+                commits: parseval_default(&kv, "commits", hashlist)?,
+                directories: parseval_default(&kv, "directories", gettreepack_directories)?,
+            })))
+        | call!(parse_command, "listkeys", parse_params, 1,
+            |kv| Ok(Listkeys {
+                namespace: parseval(&kv, "namespace", ident_string)?,
+                prefix: parseval_default(&kv, "prefix", utf8_string_complete)?,
+                limit: parseval_option(&kv, "limit", integer_complete)?,
+            }))
         | command!("listkeyspatterns", ListKeysPatterns, parse_params, {
              namespace => ident_string,
              patterns => hex_stringlist,
@@ -1192,8 +1271,10 @@ mod test_parse {
     use maplit::btreeset;
     use maplit::hashmap;
     use maplit::hashset;
+    use quickcheck::quickcheck;
 
     use super::*;
+    use crate::limits::MAX_NAMESPACE_LEN;
 
     fn hash_ones() -> HgChangesetId {
         HgChangesetId::new("1111111111111111111111111111111111111111".parse().unwrap())
@@ -1349,6 +1430,13 @@ mod test_parse {
         test_parse(inp, Request::Single(SingleRequest::Capabilities {}));
     }
 
+    #[test]
+    fn test_parse_clonebundles() {
+        let inp = "clonebundles\n";
+
+        test_parse(inp, Request::Single(SingleRequest::Clonebundles {}));
+    }
+
     #[test]
     fn test_parse_debugwireargs() {
         let inp = "debugwireargs\n\
@@ -1440,6 +1528,29 @@ mod test_parse {
             inp,
             Request::Single(SingleRequest::Listkeys {
                 namespace: "bookmarks".to_string(),
+                prefix: String::new(),
+                limit: None,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_parse_listkeys_filter() {
+        let inp = "listkeys\n\
+                   * 3\n\
+                   namespace 9\n\
+                   bookmarks\
+                   prefix 8\n\
+                   release/\
+                   limit 2\n\
+                   10";
+
+        test_parse(
+            inp,
+            Request::Single(SingleRequest::Listkeys {
+                namespace: "bookmarks".to_string(),
+                prefix: "release/".to_string(),
+                limit: Some(10),
             }),
         );
     }
@@ -1678,6 +1789,121 @@ mod test_parse {
         );
     }
 
+    #[test]
+    fn test_parse_headspaginated() {
+        let input = "headspaginated\n\
+                     * 0\n";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::HeadsPaginated(Default::default())),
+        );
+
+        let input = "headspaginated\n\
+                     * 4\n\
+                     prefix 8\n\
+                     release/\
+                     after 12\n\
+                     release/v1.2\
+                     limit 3\n\
+                     100\
+                     since 10\n\
+                     1600000000";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::HeadsPaginated(HeadsPaginatedArgs {
+                prefix: "release/".to_string(),
+                after: Some("release/v1.2".to_string()),
+                limit: Some(100),
+                since: Some(1600000000),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_parse_getmanifestpage() {
+        let input = "getmanifestpage\n\
+                     * 1\n\
+                     mfnode 40\n\
+                     1111111111111111111111111111111111111111";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::GetManifestPage(GetManifestPageArgs {
+                rootdir: None,
+                mfnode: hash_ones_manifest(),
+                after: None,
+                limit: None,
+            })),
+        );
+
+        let input = "getmanifestpage\n\
+                     * 4\n\
+                     rootdir 5\n\
+                     ololo\
+                     mfnode 40\n\
+                     1111111111111111111111111111111111111111\
+                     after 6\n\
+                     666f6f\
+                     limit 3\n\
+                     500";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::GetManifestPage(GetManifestPageArgs {
+                rootdir: MPath::new_opt("ololo").unwrap(),
+                mfnode: hash_ones_manifest(),
+                after: Some(b"foo".to_vec()),
+                limit: Some(500),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_parse_discoverysample() {
+        let input = "discoverysample\n\
+                     * 0\n";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::DiscoverySample(Default::default())),
+        );
+
+        let input = "discoverysample\n\
+                     * 2\n\
+                     heads 81\n\
+                     1111111111111111111111111111111111111111 2222222222222222222222222222222222222222\
+                     size 2\n\
+                     50";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::DiscoverySample(DiscoverySampleArgs {
+                heads: vec![hash_ones(), hash_twos()],
+                size: Some(50),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_parse_prefetchhints() {
+        let input = "prefetchhints\n\
+                     * 0\n";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::PrefetchHints(Default::default())),
+        );
+
+        let input = "prefetchhints\n\
+                     * 2\n\
+                     commits 81\n\
+                     1111111111111111111111111111111111111111 2222222222222222222222222222222222222222\
+                     directories 11\n\
+                     foo/bar,:o,";
+        test_parse(
+            input,
+            Request::Single(SingleRequest::PrefetchHints(PrefetchHintsArgs {
+                commits: vec![hash_ones(), hash_twos()],
+                directories: vec![Bytes::from(b"foo/bar".as_ref()), Bytes::from(b",".as_ref())],
+            })),
+        );
+    }
+
     #[test]
     fn test_parse_getcommitdata() {
         let input = "getcommitdata\n\
@@ -1690,4 +1916,94 @@ mod test_parse {
             }),
         );
     }
+
+    #[test]
+    fn test_params_star_too_many() {
+        let inp = format!("* {}\n", MAX_PARAMS + 1);
+        assert_eq!(
+            params(inp.as_bytes(), 1),
+            IResult::Error(Err::Code(ErrorKind::Custom(TOO_MANY_PARAMS_ERR_CODE)))
+        );
+    }
+
+    #[test]
+    fn test_parse_request_too_large() {
+        // A parameter that claims to be larger than any request we accept
+        // is rejected once we've buffered too much of it.
+        const MAX_SIZE: usize = 1024;
+        let mut inp = format!("lookup\nkey {}\n", MAX_SIZE * 2).into_bytes();
+        let mut buf = BytesMut::from(inp.clone());
+        assert!(
+            parse_request_with_max_size(&mut buf, MAX_SIZE)
+                .unwrap()
+                .is_none()
+        );
+
+        inp.resize(MAX_SIZE, b'x');
+        let mut buf = BytesMut::from(inp.clone());
+        assert!(
+            parse_request_with_max_size(&mut buf, MAX_SIZE)
+                .unwrap()
+                .is_none()
+        );
+
+        inp.push(b'x');
+        let mut buf = BytesMut::from(inp);
+        let err = parse_request_with_max_size(&mut buf, MAX_SIZE).unwrap_err();
+        match err.downcast_ref::<errors::ErrorKind>() {
+            Some(errors::ErrorKind::RequestTooLarge { .. }) => {}
+            _ => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_parse_listkeys_namespace_too_long() {
+        let namespace = "a".repeat(MAX_NAMESPACE_LEN + 1);
+        let inp = format!("listkeys\nnamespace {}\n{}", namespace.len(), namespace);
+        let mut buf = BytesMut::from(inp.into_bytes());
+        let err = parse_request(&mut buf).unwrap_err();
+        match err.downcast_ref::<errors::ErrorKind>() {
+            Some(errors::ErrorKind::ArgumentTooLarge {
+                command, argument, ..
+            }) => {
+                assert_eq!(command, "listkeys");
+                assert_eq!(argument, "namespace");
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    quickcheck! {
+        fn test_parse_request_arbitrary(data: Vec<u8>) -> bool {
+            // Arbitrary input must be rejected cleanly, and never consume
+            // more than was given.
+            let mut buf = BytesMut::from(data);
+            let len = buf.len();
+            match parse_request(&mut buf) {
+                Ok(None) => buf.len() == len,
+                Ok(Some(_)) => buf.len() < len,
+                Err(_) => true,
+            }
+        }
+
+        fn test_parse_request_arbitrary_args(command: usize, args: Vec<u8>) -> bool {
+            // A known command followed by arbitrary arguments.
+            const COMMANDS: &[&str] = &[
+                "batch", "between", "getbundle", "gettreepack", "known", "listkeys",
+                "listkeyspatterns", "lookup", "unbundle", "getpackv1", "getpackv2",
+                "getcommitdata", "headspaginated", "discoverysample", "getmanifestpage",
+                "prefetchhints",
+            ];
+            let mut data = COMMANDS[command % COMMANDS.len()].as_bytes().to_vec();
+            data.push(b'\n');
+            data.extend(args);
+            let mut buf = BytesMut::from(data);
+            let len = buf.len();
+            match parse_request(&mut buf) {
+                Ok(None) => buf.len() == len,
+                Ok(Some(_)) => buf.len() < len,
+                Err(_) => true,
+            }
+        }
+    }
 }
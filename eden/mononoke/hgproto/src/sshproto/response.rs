@@ -108,8 +108,45 @@ fn encode_cmd(response: SingleResponse) -> Bytes {
 
         ClientTelemetry(hostname) => Bytes::from(hostname),
 
+        Clonebundles(res) => res,
+
         Debugwireargs(res) => res,
 
+        DiscoverySample(sample) => {
+            // Three lines: whether each of the client's heads is known, the
+            // server's heads, and the sample of their ancestors.
+            let mut out: Vec<_> = sample
+                .known
+                .into_iter()
+                .map(|known| if known { b'1' } else { b'0' })
+                .collect();
+            out.push(b'\n');
+            separated(&mut out, sample.heads, " ").expect("write to vec failed");
+            out.push(b'\n');
+            separated(&mut out, sample.sample, " ").expect("write to vec failed");
+
+            Bytes::from(out)
+        }
+
+        GetManifestPage(page) => {
+            // The first line is the hex-encoded token for the next page, which
+            // is empty for the last page.  The entries follow in the same
+            // format as manifest text.
+            let mut out = hex::encode(page.next.unwrap_or_default()).into_bytes();
+            out.push(b'\n');
+            for (name, entry) in page.entries {
+                out.extend_from_slice(name.as_ref());
+                out.push(b'\0');
+                out.extend_from_slice(entry.to_hex().as_bytes());
+                out.extend_from_slice(entry.get_type().manifest_suffix().as_bytes());
+                out.push(b'\n');
+            }
+            Bytes::from(out)
+        }
+
+        // The number of commits the server will prefetch.
+        PrefetchHints(accepted) => Bytes::from(accepted.to_string()),
+
         Heads(set) => {
             let mut out = Vec::new();
 
@@ -118,6 +155,16 @@ fn encode_cmd(response: SingleResponse) -> Bytes {
             Bytes::from(out)
         }
 
+        HeadsPaginated(page) => {
+            // The first line is the cursor for the next page, which is empty
+            // for the last page.
+            let mut out = page.next.unwrap_or_default().into_bytes();
+            for (bookmark, hash) in page.heads {
+                write!(out, "\n{}\t{}", bookmark, hash).expect("write to vec failed");
+            }
+            Bytes::from(out)
+        }
+
         Known(knowns) => {
             let out: Vec<_> = knowns
                 .into_iter()
@@ -16,6 +16,8 @@ pub enum ErrorKind {
     BackingStore(#[from] anyhow::Error),
     #[error("Content too large to fit in memory")]
     ContentTooLarge,
+    #[error("Exceeded the limit of {0} file fetches")]
+    FetchLimitExceeded(u64),
 }
 
 impl From<std::num::TryFromIntError> for ErrorKind {
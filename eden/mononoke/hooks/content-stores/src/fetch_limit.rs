@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use changeset_info::ChangesetInfo;
+use context::CoreContext;
+use futures::stream::BoxStream;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
+use mononoke_types::MPath;
+
+use crate::ErrorKind;
+use crate::FileChange;
+use crate::FileContentManager;
+use crate::PathContent;
+
+/// Wraps a content manager for the duration of a single hook execution and
+/// fails file content fetches once the hook has done more than `max_fetches`
+/// of them.
+pub struct FetchLimitedFileContentManager<'a> {
+    inner: &'a dyn FileContentManager,
+    max_fetches: Option<u64>,
+    fetches: AtomicU64,
+}
+
+impl<'a> FetchLimitedFileContentManager<'a> {
+    pub fn new(inner: &'a dyn FileContentManager, max_fetches: Option<u64>) -> Self {
+        Self {
+            inner,
+            max_fetches,
+            fetches: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of file content fetches attempted so far.
+    pub fn fetches(&self) -> u64 {
+        self.fetches.load(Ordering::Relaxed)
+    }
+
+    /// Whether at least one fetch was refused because of the limit.
+    pub fn limit_exceeded(&self) -> bool {
+        match self.max_fetches {
+            Some(max_fetches) => self.fetches() > max_fetches,
+            None => false,
+        }
+    }
+
+    fn record_fetch(&self) -> Result<(), ErrorKind> {
+        let fetches = self.fetches.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.max_fetches {
+            Some(max_fetches) if fetches > max_fetches => {
+                Err(ErrorKind::FetchLimitExceeded(max_fetches))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<'b> FileContentManager for FetchLimitedFileContentManager<'b> {
+    async fn get_file_size<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<u64, ErrorKind> {
+        self.inner.get_file_size(ctx, id).await
+    }
+
+    async fn get_file_metadata<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind> {
+        self.inner.get_file_metadata(ctx, id).await
+    }
+
+    async fn get_file_text<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        self.record_fetch()?;
+        self.inner.get_file_text(ctx, id).await
+    }
+
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        self.record_fetch()?;
+        self.inner.get_file_stream(ctx, id).await
+    }
+
+    async fn get_file_diff<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        old: Option<ContentId>,
+        new: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        // A diff needs the content of both sides.
+        if old.is_some() {
+            self.record_fetch()?;
+        }
+        self.record_fetch()?;
+        self.inner.get_file_diff(ctx, old, new).await
+    }
+
+    async fn find_content<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkName,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner.find_content(ctx, bookmark, paths).await
+    }
+
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        changeset_id: ChangesetId,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner
+            .find_content_in_changeset(ctx, changeset_id, paths)
+            .await
+    }
+
+    async fn file_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        new_cs_id: ChangesetId,
+        old_cs_id: ChangesetId,
+    ) -> Result<Vec<(MPath, FileChange)>, ErrorKind> {
+        self.inner.file_changes(ctx, new_cs_id, old_cs_id).await
+    }
+
+    async fn latest_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkName,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind> {
+        self.inner.latest_changes(ctx, bookmark, paths).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::InMemoryFileContentManager;
+
+    #[fbinit::test]
+    fn test_fetch_limit(fb: FacebookInit) {
+        let rt = Runtime::new().unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+
+        let store = FetchLimitedFileContentManager::new(&inner, Some(1));
+        let ret = rt.block_on(store.get_file_text(&ctx, ONES_CTID)).unwrap();
+        assert_eq!(ret, Some("foobar".into()));
+        assert!(!store.limit_exceeded());
+
+        // Sizes come from metadata, so they are not counted as fetches
+        let ret = rt.block_on(store.get_file_size(&ctx, ONES_CTID)).unwrap();
+        assert_eq!(ret, 6);
+        assert!(!store.limit_exceeded());
+
+        let ret = rt.block_on(store.get_file_text(&ctx, ONES_CTID));
+        assert!(matches!(ret, Err(ErrorKind::FetchLimitExceeded(1))));
+        assert!(store.limit_exceeded());
+    }
+}
@@ -6,8 +6,10 @@
  */
 
 mod errors;
+mod fetch_limit;
 mod memory;
 mod repo;
+mod shared;
 mod store;
 mod text_only;
 
@@ -19,9 +21,11 @@ pub use store::FileChange;
 pub use store::FileContentManager;
 pub use store::PathContent;
 
+pub use crate::fetch_limit::FetchLimitedFileContentManager;
 pub use crate::memory::InMemoryFileContentManager;
 pub use crate::memory::InMemoryFileText;
 pub use crate::repo::RepoFileContentManager;
+pub use crate::shared::SharedFileContentManager;
 pub use crate::text_only::TextOnlyFileContentManager;
 
 pub fn repo_text_only_fetcher(
@@ -15,6 +15,7 @@ use changeset_info::ChangesetInfo;
 use context::CoreContext;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::MPath;
 
 use crate::ErrorKind;
@@ -68,6 +69,17 @@ impl FileContentManager for InMemoryFileContentManager {
             })
     }
 
+    async fn get_file_metadata<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        _id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind> {
+        Err(
+            format_err!("`get_file_metadata` is not implemented for `InMemoryFileContentManager`")
+                .into(),
+        )
+    }
+
     async fn get_file_text<'a>(
         &'a self,
         _ctx: &'a CoreContext,
@@ -94,6 +106,18 @@ impl FileContentManager for InMemoryFileContentManager {
         )
     }
 
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        _changeset_id: ChangesetId,
+        _paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        Err(format_err!(
+            "`find_content_in_changeset` is not implemented for `InMemoryFileContentManager`"
+        )
+        .into())
+    }
+
     async fn file_changes<'a>(
         &'a self,
         _ctx: &'a CoreContext,
@@ -18,6 +18,8 @@ use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
 use futures::future;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures_util::future::TryFutureExt;
 use manifest::Diff;
@@ -29,6 +31,7 @@ use mercurial_types::HgFileNodeId;
 use mercurial_types::HgManifestId;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::MPath;
 use mononoke_types::ManifestUnodeId;
 use repo_blobstore::ArcRepoBlobstore;
@@ -65,6 +68,16 @@ impl FileContentManager for RepoFileContentManager {
         )
     }
 
+    async fn get_file_metadata<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind> {
+        filestore::get_metadata_v2(&self.repo_blobstore, ctx, &id.into())
+            .await?
+            .ok_or(ErrorKind::ContentIdNotFound(id))
+    }
+
     async fn get_file_text<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -76,6 +89,17 @@ impl FileContentManager for RepoFileContentManager {
             .map(Option::Some)
     }
 
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let stream = filestore::fetch(&self.repo_blobstore, ctx, &id.into())
+            .await?
+            .ok_or(ErrorKind::ContentIdNotFound(id))?;
+        Ok(Some(stream.map_err(ErrorKind::from).boxed()))
+    }
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -89,15 +113,24 @@ impl FileContentManager for RepoFileContentManager {
             .with_context(|| format!("Error fetching bookmark: {}", bookmark))?
             .ok_or_else(|| format_err!("Bookmark {} does not exist", bookmark))?;
 
-        let master_mf = derive_hg_manifest(
+        self.find_content_in_changeset(ctx, changeset_id, paths)
+            .await
+    }
+
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        changeset_id: ChangesetId,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        let mf = derive_hg_manifest(
             ctx,
             &self.repo_derived_data,
             &self.repo_blobstore,
             changeset_id,
         )
         .await?;
-        master_mf
-            .find_entries(ctx.clone(), self.repo_blobstore.clone(), paths)
+        mf.find_entries(ctx.clone(), self.repo_blobstore.clone(), paths)
             .map_ok(|(mb_path, entry)| async move {
                 if let Some(path) = mb_path {
                     let content = resolve_content_id(ctx, &self.repo_blobstore, entry).await?;
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use changeset_info::ChangesetInfo;
+use context::CoreContext;
+use futures::stream::BoxStream;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
+use mononoke_types::MPath;
+
+use crate::store::compute_file_diff;
+use crate::ErrorKind;
+use crate::FileChange;
+use crate::FileContentManager;
+use crate::PathContent;
+
+/// Don't keep more than this many bytes of file text for a single run.
+const MAX_SHARED_TEXT_BYTES: u64 = 100 * 1024 * 1024;
+
+#[derive(Default)]
+struct SharedData {
+    sizes: HashMap<ContentId, u64>,
+    metadata: HashMap<ContentId, ContentMetadataV2>,
+    texts: HashMap<ContentId, Option<Bytes>>,
+    diffs: HashMap<(Option<ContentId>, ContentId), Option<Bytes>>,
+    text_bytes: u64,
+    changeset_contents: HashMap<(ChangesetId, MPath), Option<PathContent>>,
+}
+
+/// Wraps a content manager for the duration of a single run of the hooks,
+/// so that file sizes, metadata, texts and diffs fetched by one hook are reused by the
+/// others instead of being fetched again.
+pub struct SharedFileContentManager<'a> {
+    inner: &'a dyn FileContentManager,
+    data: Mutex<SharedData>,
+}
+
+impl<'a> SharedFileContentManager<'a> {
+    pub fn new(inner: &'a dyn FileContentManager) -> Self {
+        Self {
+            inner,
+            data: Mutex::new(SharedData::default()),
+        }
+    }
+}
+
+impl SharedData {
+    /// Keep `bytes` of text or diff if it fits within the limit.
+    fn reserve(&mut self, bytes: &Option<Bytes>) -> bool {
+        let len = bytes.as_ref().map_or(0, |bytes| bytes.len() as u64);
+        if self.text_bytes + len <= MAX_SHARED_TEXT_BYTES {
+            self.text_bytes += len;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[async_trait]
+impl<'b> FileContentManager for SharedFileContentManager<'b> {
+    async fn get_file_size<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<u64, ErrorKind> {
+        if let Some(size) = self.data.lock().expect("lock poisoned").sizes.get(&id) {
+            return Ok(*size);
+        }
+        let size = self.inner.get_file_size(ctx, id).await?;
+        self.data
+            .lock()
+            .expect("lock poisoned")
+            .sizes
+            .insert(id, size);
+        Ok(size)
+    }
+
+    async fn get_file_metadata<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind> {
+        if let Some(metadata) = self.data.lock().expect("lock poisoned").metadata.get(&id) {
+            return Ok(metadata.clone());
+        }
+        let metadata = self.inner.get_file_metadata(ctx, id).await?;
+        self.data
+            .lock()
+            .expect("lock poisoned")
+            .metadata
+            .insert(id, metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn get_file_text<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        if let Some(text) = self.data.lock().expect("lock poisoned").texts.get(&id) {
+            return Ok(text.clone());
+        }
+        let text = self.inner.get_file_text(ctx, id).await?;
+        let mut data = self.data.lock().expect("lock poisoned");
+        if !data.texts.contains_key(&id) && data.reserve(&text) {
+            data.texts.insert(id, text.clone());
+        }
+        Ok(text)
+    }
+
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        // Streams are used for files too large to hold in memory, so they
+        // are never shared.
+        self.inner.get_file_stream(ctx, id).await
+    }
+
+    async fn get_file_diff<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        old: Option<ContentId>,
+        new: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        let key = (old, new);
+        if let Some(diff) = self.data.lock().expect("lock poisoned").diffs.get(&key) {
+            return Ok(diff.clone());
+        }
+        // Fetch the texts through this store, so that they are shared too.
+        let diff = compute_file_diff(self, ctx, old, new).await?;
+        let mut data = self.data.lock().expect("lock poisoned");
+        if !data.diffs.contains_key(&key) && data.reserve(&diff) {
+            data.diffs.insert(key, diff.clone());
+        }
+        Ok(diff)
+    }
+
+    async fn find_content<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkName,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner.find_content(ctx, bookmark, paths).await
+    }
+
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        changeset_id: ChangesetId,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let data = self.data.lock().expect("lock poisoned");
+            for path in paths {
+                match data.changeset_contents.get(&(changeset_id, path.clone())) {
+                    Some(Some(content)) => {
+                        found.insert(path, content.clone());
+                    }
+                    Some(None) => {}
+                    None => missing.push(path),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(found);
+        }
+
+        let mut fetched = self
+            .inner
+            .find_content_in_changeset(ctx, changeset_id, missing.clone())
+            .await?;
+        let mut data = self.data.lock().expect("lock poisoned");
+        for path in missing {
+            let content = fetched.remove(&path);
+            data.changeset_contents
+                .insert((changeset_id, path.clone()), content.clone());
+            if let Some(content) = content {
+                found.insert(path, content);
+            }
+        }
+        Ok(found)
+    }
+
+    async fn file_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        new_cs_id: ChangesetId,
+        old_cs_id: ChangesetId,
+    ) -> Result<Vec<(MPath, FileChange)>, ErrorKind> {
+        self.inner.file_changes(ctx, new_cs_id, old_cs_id).await
+    }
+
+    async fn latest_changes<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        bookmark: BookmarkName,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind> {
+        self.inner.latest_changes(ctx, bookmark, paths).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+    use mononoke_types_mocks::contentid::THREES_CTID;
+    use mononoke_types_mocks::contentid::TWOS_CTID;
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::FetchLimitedFileContentManager;
+    use crate::InMemoryFileContentManager;
+    use crate::InMemoryFileText;
+
+    #[fbinit::test]
+    fn test_shared_fetches(fb: FacebookInit) {
+        let rt = Runtime::new().unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+        inner.insert(TWOS_CTID, "baz");
+
+        // Count the fetches that reach the underlying store.
+        let counted = FetchLimitedFileContentManager::new(&inner, None);
+        let store = SharedFileContentManager::new(&counted);
+
+        for _ in 0..3 {
+            let ret = rt.block_on(store.get_file_text(&ctx, ONES_CTID)).unwrap();
+            assert_eq!(ret, Some("foobar".into()));
+        }
+        assert_eq!(counted.fetches(), 1);
+
+        let ret = rt.block_on(store.get_file_text(&ctx, TWOS_CTID)).unwrap();
+        assert_eq!(ret, Some("baz".into()));
+        assert_eq!(counted.fetches(), 2);
+    }
+
+    #[fbinit::test]
+    fn test_shared_diffs(fb: FacebookInit) {
+        let rt = Runtime::new().unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foo\nbar\n");
+        inner.insert(TWOS_CTID, "foo\nbaz\n");
+        inner.insert(THREES_CTID, InMemoryFileText::Elided(10));
+
+        let counted = FetchLimitedFileContentManager::new(&inner, None);
+        let store = SharedFileContentManager::new(&counted);
+
+        for _ in 0..3 {
+            let ret = rt
+                .block_on(store.get_file_diff(&ctx, Some(ONES_CTID), TWOS_CTID))
+                .unwrap();
+            assert_eq!(ret, Some("@@ -1,2 +1,2 @@\n foo\n-bar\n+baz\n".into()));
+        }
+        assert_eq!(counted.fetches(), 2);
+
+        // Added files are diffed against an empty file, and the text of the
+        // new side is already shared.
+        let ret = rt
+            .block_on(store.get_file_diff(&ctx, None, TWOS_CTID))
+            .unwrap();
+        assert!(ret.unwrap().ends_with(b"\n+foo\n+baz\n"));
+        assert_eq!(counted.fetches(), 2);
+
+        // There is no diff if either side isn't text.
+        let ret = rt
+            .block_on(store.get_file_diff(&ctx, Some(ONES_CTID), THREES_CTID))
+            .unwrap();
+        assert_eq!(ret, None);
+    }
+}
@@ -12,9 +12,15 @@ use bookmarks::BookmarkName;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::MPath;
+use xdiff::HeaderlessDiffOpts;
 
 use crate::ErrorKind;
 
@@ -26,12 +32,39 @@ pub trait FileContentManager: Send + Sync {
         id: ContentId,
     ) -> Result<u64, ErrorKind>;
 
+    /// Fetch the metadata of a file, which describes what kind of file it
+    /// is (binary or text, encoding, line endings, language, ...). This is
+    /// computed when the file is uploaded, so hooks can use it to skip
+    /// files without fetching their content.
+    async fn get_file_metadata<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind>;
+
     async fn get_file_text<'a>(
         &'a self,
         ctx: &'a CoreContext,
         id: ContentId,
     ) -> Result<Option<Bytes>, ErrorKind>;
 
+    /// Fetch the content of a file as a stream of chunks. Unlike
+    /// `get_file_text`, this never buffers the whole file, so callers that
+    /// can make a decision based on a prefix of the file (e.g. it is too
+    /// large, or it contains a forbidden pattern) can stop polling the
+    /// stream early and the rest of the file will not be fetched.
+    ///
+    /// The default implementation falls back to `get_file_text` and yields
+    /// the whole file as a single chunk.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let maybe_text = self.get_file_text(ctx, id).await?;
+        Ok(maybe_text.map(|text| stream::once(future::ok(text)).boxed()))
+    }
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -39,6 +72,29 @@ pub trait FileContentManager: Send + Sync {
         paths: Vec<MPath>,
     ) -> Result<HashMap<MPath, PathContent>, ErrorKind>;
 
+    /// Like `find_content`, but for the paths as of a given changeset
+    /// rather than a bookmark.
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        changeset_id: ChangesetId,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind>;
+
+    /// Compute a unified diff, without file headers, from the `old` content
+    /// (or an empty file if `None`) to the `new` content. Returns `None` if
+    /// either side is not available as text.
+    ///
+    /// The default implementation fetches both sides with `get_file_text`.
+    async fn get_file_diff<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        old: Option<ContentId>,
+        new: ContentId,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        compute_file_diff(self, ctx, old, new).await
+    }
+
     async fn file_changes<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -54,6 +110,36 @@ pub trait FileContentManager: Send + Sync {
     ) -> Result<HashMap<MPath, ChangesetInfo>, ErrorKind>;
 }
 
+/// Number of lines of context around the changes in file diffs.
+const FILE_DIFF_CONTEXT: usize = 3;
+
+pub(crate) async fn compute_file_diff<M: FileContentManager + ?Sized>(
+    manager: &M,
+    ctx: &CoreContext,
+    old: Option<ContentId>,
+    new: ContentId,
+) -> Result<Option<Bytes>, ErrorKind> {
+    let old_text = match old {
+        Some(old) => match manager.get_file_text(ctx, old).await? {
+            Some(text) => text,
+            None => return Ok(None),
+        },
+        None => Bytes::new(),
+    };
+    let new_text = match manager.get_file_text(ctx, new).await? {
+        Some(text) => text,
+        None => return Ok(None),
+    };
+    let diff = xdiff::diff_unified_headerless(
+        &old_text,
+        &new_text,
+        HeaderlessDiffOpts {
+            context: FILE_DIFF_CONTEXT,
+        },
+    );
+    Ok(Some(Bytes::from(diff)))
+}
+
 #[derive(Clone, Debug)]
 pub enum PathContent {
     Directory,
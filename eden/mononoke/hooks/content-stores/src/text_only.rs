@@ -13,8 +13,14 @@ use bookmarks::BookmarkName;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use futures::TryStreamExt;
 use mononoke_types::ChangesetId;
 use mononoke_types::ContentId;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::MPath;
 
 use crate::ErrorKind;
@@ -48,6 +54,14 @@ impl<T: FileContentManager + 'static> FileContentManager for TextOnlyFileContent
         self.inner.get_file_size(ctx, id).await
     }
 
+    async fn get_file_metadata<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<ContentMetadataV2, ErrorKind> {
+        self.inner.get_file_metadata(ctx, id).await
+    }
+
     /// Override the inner store's get_file_text by filtering out files that are to large or
     /// contain null bytes (those are assumed to be binary).
     async fn get_file_text<'a>(
@@ -72,6 +86,36 @@ impl<T: FileContentManager + 'static> FileContentManager for TextOnlyFileContent
         }))
     }
 
+    /// Like `get_file_text`, files that are too large are filtered out, as
+    /// are files whose first chunk contains null bytes.  Later chunks are not
+    /// checked, so callers that only care about text content are expected to
+    /// stop reading once they see binary data.
+    async fn get_file_stream<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        id: ContentId,
+    ) -> Result<Option<BoxStream<'a, Result<Bytes, ErrorKind>>>, ErrorKind> {
+        let size = self.get_file_size(ctx, id).await?;
+        if size > self.max_size {
+            return Ok(None);
+        }
+
+        let mut file_stream = match self.inner.get_file_stream(ctx, id).await? {
+            Some(file_stream) => file_stream,
+            None => return Ok(None),
+        };
+        let first = match file_stream.try_next().await? {
+            Some(first) => first,
+            None => return Ok(Some(stream::empty().boxed())),
+        };
+        if looks_like_binary(&first) {
+            return Ok(None);
+        }
+        Ok(Some(
+            stream::once(future::ok(first)).chain(file_stream).boxed(),
+        ))
+    }
+
     async fn find_content<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -81,6 +125,17 @@ impl<T: FileContentManager + 'static> FileContentManager for TextOnlyFileContent
         self.inner.find_content(ctx, bookmark, paths).await
     }
 
+    async fn find_content_in_changeset<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        changeset_id: ChangesetId,
+        paths: Vec<MPath>,
+    ) -> Result<HashMap<MPath, PathContent>, ErrorKind> {
+        self.inner
+            .find_content_in_changeset(ctx, changeset_id, paths)
+            .await
+    }
+
     async fn file_changes<'a>(
         &'a self,
         ctx: &'a CoreContext,
@@ -158,4 +213,40 @@ mod test {
         let ret = rt.block_on(store.get_file_size(&ctx, ONES_CTID)).unwrap();
         assert_eq!(ret, 4);
     }
+
+    async fn collect_stream(
+        store: &TextOnlyFileContentManager<InMemoryFileContentManager>,
+        ctx: &CoreContext,
+    ) -> Result<Option<Vec<u8>>, ErrorKind> {
+        match store.get_file_stream(ctx, ONES_CTID).await? {
+            Some(file_stream) => Ok(Some(file_stream.try_collect::<Vec<_>>().await?.concat())),
+            None => Ok(None),
+        }
+    }
+
+    #[fbinit::test]
+    fn test_stream_files(fb: FacebookInit) {
+        let rt = Runtime::new().unwrap();
+        let ctx = CoreContext::test_mock(fb);
+
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+        let store = TextOnlyFileContentManager::new(inner, 10);
+        let ret = rt.block_on(collect_stream(&store, &ctx)).unwrap();
+        assert_eq!(ret, Some(b"foobar".to_vec()));
+
+        // Large files are not streamed.
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foobar");
+        let store = TextOnlyFileContentManager::new(inner, 2);
+        let ret = rt.block_on(collect_stream(&store, &ctx)).unwrap();
+        assert_eq!(ret, None);
+
+        // Nor are binary files.
+        let mut inner = InMemoryFileContentManager::new();
+        inner.insert(ONES_CTID, "foo\0");
+        let store = TextOnlyFileContentManager::new(inner, 10);
+        let ret = rt.block_on(collect_stream(&store, &ctx)).unwrap();
+        assert_eq!(ret, None);
+    }
 }
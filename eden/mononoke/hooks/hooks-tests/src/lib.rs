@@ -8,6 +8,7 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::time::Duration;
 
 use anyhow::Error;
 use async_trait::async_trait;
@@ -41,8 +42,14 @@ use maplit::hashmap;
 use maplit::hashset;
 use metaconfig_types::BookmarkParams;
 use metaconfig_types::HookConfig;
+use metaconfig_types::HookExecutionLimits;
+use metaconfig_types::HookFailurePolicy;
 use metaconfig_types::HookManagerParams;
 use metaconfig_types::HookParams;
+use metaconfig_types::HookRules;
+use metaconfig_types::HookScheduling;
+use metaconfig_types::HookSeverity;
+use metaconfig_types::MergePolicy;
 use metaconfig_types::RepoConfig;
 use mononoke_types::BasicFileChange;
 use mononoke_types::BonsaiChangeset;
@@ -102,6 +109,27 @@ fn always_rejecting_changeset_hook() -> Box<dyn ChangesetHook> {
     Box::new(FnChangesetHook::new(f))
 }
 
+#[derive(Clone, Debug)]
+struct SlowChangesetHook {
+    delay: Duration,
+}
+
+#[async_trait]
+impl ChangesetHook for SlowChangesetHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        _changeset: &'cs BonsaiChangeset,
+        _content_manager: &'fetcher dyn FileContentManager,
+        _cross_repo_push_source: CrossRepoPushSource,
+        _push_authored_by: PushAuthoredBy,
+    ) -> Result<HookExecution, Error> {
+        tokio::time::sleep(self.delay).await;
+        Ok(default_rejection())
+    }
+}
+
 #[derive(Clone)]
 struct FindFilesChangesetHook {
     pub filename: String,
@@ -364,6 +392,7 @@ impl FileHook for FnFileHook {
         _content_manager: &'fetcher dyn FileContentManager,
         _change: Option<&'change BasicFileChange>,
         _path: &'path MPath,
+        _diff: &'change hooks::FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         _push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -394,6 +423,7 @@ impl FileHook for PathMatchingFileHook {
         _content_manager: &'fetcher dyn FileContentManager,
         _change: Option<&'change BasicFileChange>,
         path: &'path MPath,
+        _diff: &'change hooks::FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         _push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -422,6 +452,7 @@ impl FileHook for FileContentMatchingFileHook {
         content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         _path: &'path MPath,
+        _diff: &'change hooks::FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         _push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -466,6 +497,7 @@ impl FileHook for IsSymLinkMatchingFileHook {
         _content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         _path: &'path MPath,
+        _diff: &'change hooks::FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         _push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -498,6 +530,7 @@ impl FileHook for LengthMatchingFileHook {
         content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         _path: &'path MPath,
+        _diff: &'change hooks::FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         _push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -520,6 +553,47 @@ fn length_matching_file_hook(length: u64) -> Box<dyn FileHook> {
     Box::new(LengthMatchingFileHook { length })
 }
 
+/// Accepts a file only if the lines its diff adds are exactly the lines of
+/// its new content.
+#[derive(Clone, Debug)]
+struct AddedLinesFileHook;
+
+#[async_trait]
+impl FileHook for AddedLinesFileHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        content_manager: &'fetcher dyn FileContentManager,
+        change: Option<&'change BasicFileChange>,
+        _path: &'path MPath,
+        diff: &'change hooks::FileDiff,
+        _cross_repo_push_source: CrossRepoPushSource,
+        _push_authored_by: PushAuthoredBy,
+    ) -> Result<HookExecution, Error> {
+        let text = match change {
+            Some(change) => content_manager
+                .get_file_text(ctx, change.content_id())
+                .await?
+                .expect("file text should be present"),
+            None => return Ok(default_rejection()),
+        };
+        let added_lines = diff.added_lines(ctx, content_manager).await?;
+        let expected: Vec<_> = text
+            .split(|c| *c == b'\n')
+            .map(|line| text.slice_ref(line))
+            .collect();
+        Ok(if added_lines == Some(expected) {
+            HookExecution::Accepted
+        } else {
+            default_rejection()
+        })
+    }
+}
+
+fn added_lines_file_hook() -> Box<dyn FileHook> {
+    Box::new(AddedLinesFileHook)
+}
+
 #[fbinit::test]
 async fn test_changeset_hook_accepted(fb: FacebookInit) {
     let ctx = CoreContext::test_mock(fb);
@@ -552,6 +626,56 @@ async fn test_changeset_hook_rejected(fb: FacebookInit) {
     run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
 }
 
+#[fbinit::test]
+async fn test_changeset_hook_timeout(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = hashmap! {
+        "bm1".to_string() => vec!["fail_open".to_string(), "fail_closed".to_string()]
+    };
+    let mut hook_manager =
+        setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+    for (hook_name, failure_policy) in [
+        ("fail_open", HookFailurePolicy::FailOpen),
+        ("fail_closed", HookFailurePolicy::FailClosed),
+    ] {
+        let config = HookConfig {
+            limits: HookExecutionLimits {
+                timeout: Some(Duration::from_millis(10)),
+                failure_policy,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let hook = Box::new(SlowChangesetHook {
+            delay: Duration::from_secs(3600),
+        });
+        hook_manager.register_changeset_hook(hook_name, hook, config);
+    }
+
+    let res = hook_manager
+        .run_hooks_for_bookmark(
+            &ctx,
+            vec![default_changeset()].iter(),
+            &BookmarkName::new("bm1").unwrap(),
+            None,
+            CrossRepoPushSource::NativeToThisRepo,
+            PushAuthoredBy::User,
+        )
+        .await
+        .unwrap();
+    let map: HashMap<String, bool> = res
+        .into_iter()
+        .map(|outcome| (outcome.get_hook_name().to_string(), outcome.is_rejection()))
+        .collect();
+    assert_eq!(
+        map,
+        hashmap! {
+            "fail_open".to_string() => false,
+            "fail_closed".to_string() => true,
+        }
+    );
+}
+
 #[fbinit::test]
 async fn test_changeset_hook_mix(fb: FacebookInit) {
     let ctx = CoreContext::test_mock(fb);
@@ -574,6 +698,93 @@ async fn test_changeset_hook_mix(fb: FacebookInit) {
     run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
 }
 
+#[fbinit::test]
+async fn test_changeset_hook_run_after(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = hashmap! {
+        "bm1".to_string() => vec![
+            "after_rejecting".to_string(),
+            "after_accepting".to_string(),
+            "rejecting".to_string(),
+            "accepting".to_string(),
+        ]
+    };
+    let mut hook_manager =
+        setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+    let run_after = |hook: &str| HookConfig {
+        scheduling: HookScheduling {
+            run_after: vec![hook.to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    hook_manager.register_changeset_hook(
+        "rejecting",
+        always_rejecting_changeset_hook(),
+        Default::default(),
+    );
+    hook_manager.register_changeset_hook(
+        "accepting",
+        always_accepting_changeset_hook(),
+        Default::default(),
+    );
+    hook_manager.register_changeset_hook(
+        "after_rejecting",
+        always_accepting_changeset_hook(),
+        run_after("rejecting"),
+    );
+    hook_manager.register_changeset_hook(
+        "after_accepting",
+        always_rejecting_changeset_hook(),
+        run_after("accepting"),
+    );
+
+    let res = hook_manager
+        .run_hooks_for_bookmark(
+            &ctx,
+            vec![default_changeset()].iter(),
+            &BookmarkName::new("bm1").unwrap(),
+            None,
+            CrossRepoPushSource::NativeToThisRepo,
+            PushAuthoredBy::User,
+        )
+        .await
+        .unwrap();
+    let map: HashMap<String, bool> = res
+        .into_iter()
+        .map(|outcome| (outcome.get_hook_name().to_string(), outcome.is_rejection()))
+        .collect();
+    // The hook that runs after a rejection is skipped.
+    assert_eq!(
+        map,
+        hashmap! {
+            "rejecting".to_string() => true,
+            "accepting".to_string() => false,
+            "after_accepting".to_string() => true,
+        }
+    );
+
+    // Hooks that run after each other in a cycle can't be scheduled.
+    hook_manager.register_changeset_hook(
+        "accepting",
+        always_accepting_changeset_hook(),
+        run_after("after_accepting"),
+    );
+    assert!(
+        hook_manager
+            .run_hooks_for_bookmark(
+                &ctx,
+                vec![default_changeset()].iter(),
+                &BookmarkName::new("bm1").unwrap(),
+                None,
+                CrossRepoPushSource::NativeToThisRepo,
+                PushAuthoredBy::User,
+            )
+            .await
+            .is_err()
+    );
+}
+
 #[fbinit::test]
 async fn test_changeset_hook_file_text(fb: FacebookInit) {
     let ctx = CoreContext::test_mock(fb);
@@ -776,6 +987,108 @@ async fn test_file_hooks_paths(fb: FacebookInit) {
     .await;
 }
 
+#[fbinit::test]
+async fn test_file_hook_rules(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = hashmap! {
+        "bm1".to_string() => vec!["included".to_string(), "warning".to_string()]
+    };
+    let mut hook_manager =
+        setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+    let included = HookConfig {
+        rules: HookRules {
+            include_paths: vec![Regex::new("^dir1/subdir1/subsubdir2/").unwrap().into()],
+            exclude_paths: vec![Regex::new("file_2$").unwrap().into()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    hook_manager.register_file_hook("included", always_rejecting_file_hook(), included);
+    let warning = HookConfig {
+        rules: HookRules {
+            severity: HookSeverity::Warn,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    hook_manager.register_file_hook("warning", always_rejecting_file_hook(), warning);
+
+    let res = hook_manager
+        .run_hooks_for_bookmark(
+            &ctx,
+            vec![default_changeset()].iter(),
+            &BookmarkName::new("bm1").unwrap(),
+            None,
+            CrossRepoPushSource::NativeToThisRepo,
+            PushAuthoredBy::User,
+        )
+        .await
+        .unwrap();
+    let rejected: HashSet<String> = res
+        .iter()
+        .filter(|outcome| outcome.is_rejection())
+        .map(|outcome| {
+            format!(
+                "{}:{}",
+                outcome.get_hook_name(),
+                outcome.get_file_path().unwrap()
+            )
+        })
+        .collect();
+    assert_eq!(
+        rejected,
+        hashset! {"included:dir1/subdir1/subsubdir2/file_1".to_string()}
+    );
+    assert_eq!(res.len(), 4);
+}
+
+#[fbinit::test]
+async fn test_file_hook_diff_in_stack(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let bookmarks = hashmap! {
+        "bm1".to_string() => vec!["hook1".to_string()]
+    };
+    let mut hook_manager =
+        setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+    hook_manager.register_file_hook("hook1", added_lines_file_hook(), Default::default());
+
+    // The second changeset's parent is only in the stack being checked, so
+    // its diff must be taken against the first changeset's change.  The
+    // in-memory content manager can't look up files in changesets, so this
+    // would fail if it tried to.
+    let first = BonsaiChangesetMut {
+        file_changes: sorted_vector_map! {
+            to_mpath("file") => FileChange::tracked(ONES_CTID, FileType::Regular, 9, None),
+        },
+        ..default_changeset().into_mut()
+    }
+    .freeze()
+    .expect("Created changeset");
+    let second = BonsaiChangesetMut {
+        parents: vec![first.get_changeset_id()],
+        file_changes: sorted_vector_map! {
+            to_mpath("file") => FileChange::tracked(TWOS_CTID, FileType::Regular, 11, None),
+        },
+        ..default_changeset().into_mut()
+    }
+    .freeze()
+    .expect("Created changeset");
+
+    let outcomes = hook_manager
+        .run_hooks_for_bookmark(
+            &ctx,
+            vec![first, second].iter(),
+            &BookmarkName::new("bm1").unwrap(),
+            None,
+            CrossRepoPushSource::NativeToThisRepo,
+            PushAuthoredBy::User,
+        )
+        .await
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes.iter().all(|outcome| outcome.is_accept()));
+}
+
 #[fbinit::test]
 async fn test_file_hooks_paths_mix(fb: FacebookInit) {
     let ctx = CoreContext::test_mock(fb);
@@ -1482,6 +1795,9 @@ async fn test_verify_integrity_fast_failure(fb: FacebookInit) {
         hooks_skip_ancestors_of: vec![],
         ensure_ancestor_of: None,
         allow_move_to_public_commits_without_hooks: false,
+        required_derived_data: vec![],
+        merge_policy: MergePolicy::default(),
+        publishing_delay: None,
     }];
     config.hooks = vec![HookParams {
         name: "verify_integrity".into(),
@@ -1516,6 +1832,9 @@ async fn test_load_hooks_bad_rust_hook(fb: FacebookInit) {
         hooks_skip_ancestors_of: vec![],
         ensure_ancestor_of: None,
         allow_move_to_public_commits_without_hooks: false,
+        required_derived_data: vec![],
+        merge_policy: MergePolicy::default(),
+        publishing_delay: None,
     }];
 
     config.hooks = vec![HookParams {
@@ -1579,6 +1898,9 @@ async fn test_load_disabled_hooks_referenced_by_bookmark(fb: FacebookInit) {
         hooks_skip_ancestors_of: vec![],
         ensure_ancestor_of: None,
         allow_move_to_public_commits_without_hooks: false,
+        required_derived_data: vec![],
+        merge_policy: MergePolicy::default(),
+        publishing_delay: None,
     }];
 
     config.hooks = vec![HookParams {
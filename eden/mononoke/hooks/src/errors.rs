@@ -38,4 +38,10 @@ pub enum ErrorKind {
 
     #[error("Disabled hook(s) do(es) not exist: {0:?}")]
     NoSuchHookToDisable(HashSet<String>),
+
+    #[error("Hook '{0}' is configured to run after hook(s) that do not exist: {1:?}")]
+    NoSuchHookDependency(String, HashSet<String>),
+
+    #[error("Hooks are configured to run after each other in a cycle: {0:?}")]
+    HookDependencyCycle(Vec<String>),
 }
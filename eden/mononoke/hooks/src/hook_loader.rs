@@ -40,6 +40,7 @@ pub async fn load_hooks(
     disabled_hooks: &HashSet<String>,
 ) -> Result<(), Error> {
     let mut hooks_not_disabled = disabled_hooks.clone();
+    let configured_hooks: HashSet<_> = config.hooks.iter().map(|hook| &hook.name).collect();
 
     let mut hook_set = HashSet::new();
     for hook in config.hooks.clone() {
@@ -50,6 +51,20 @@ pub async fn load_hooks(
             continue;
         }
 
+        // Hooks may run after disabled hooks, in which case the ordering is
+        // ignored, but not after hooks that don't exist at all.
+        let unknown_dependencies: HashSet<_> = hook
+            .config
+            .scheduling
+            .run_after
+            .iter()
+            .filter(|dep| !configured_hooks.contains(dep))
+            .cloned()
+            .collect();
+        if !unknown_dependencies.is_empty() {
+            return Err(ErrorKind::NoSuchHookDependency(hook.name, unknown_dependencies).into());
+        }
+
         let rust_hook = {
             if let Some(hook) = hook_name_to_changeset_hook(
                 fb,
@@ -85,6 +100,8 @@ pub async fn load_hooks(
         return Err(ErrorKind::NoSuchHookToDisable(hooks_not_disabled).into());
     }
 
+    hook_manager.validate_hook_order()?;
+
     for bookmark_hook in config.bookmarks.clone() {
         let bookmark = bookmark_hook.bookmark;
         let hooks: Vec<_> = bookmark_hook
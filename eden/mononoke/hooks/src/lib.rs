@@ -22,6 +22,7 @@ mod rust_hooks;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
 use std::hash::Hash;
 use std::str;
@@ -30,6 +31,8 @@ use anyhow::Error;
 use anyhow::Result;
 use async_trait::async_trait;
 use bookmarks::BookmarkName;
+use bulkhead::repo_bulkheads;
+use bulkhead::BulkheadOp;
 use bytes::Bytes;
 use context::CoreContext;
 pub use errors::*;
@@ -38,17 +41,23 @@ use futures::stream::futures_unordered::FuturesUnordered;
 use futures::stream::TryStreamExt;
 use futures::try_join;
 use futures::Future;
-use futures::TryFutureExt;
+use futures::FutureExt;
 use futures_stats::TimedFutureExt;
+use hooks_content_stores::FetchLimitedFileContentManager;
 pub use hooks_content_stores::FileContentManager;
 pub use hooks_content_stores::PathContent;
+use hooks_content_stores::SharedFileContentManager;
+use itertools::Itertools;
 use metaconfig_types::BookmarkOrRegex;
 use metaconfig_types::HookBypass;
 use metaconfig_types::HookConfig;
+use metaconfig_types::HookFailurePolicy;
 use metaconfig_types::HookManagerParams;
+use metaconfig_types::HookSeverity;
 use mononoke_types::BasicFileChange;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
 use mononoke_types::MPath;
 use permission_checker::AclProvider;
 use permission_checker::ArcMembershipChecker;
@@ -57,6 +66,8 @@ use regex::Regex;
 use scuba::builder::ServerData;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::debug;
+use slog::warn;
+use tunables::tunables;
 
 /// Manages hooks and allows them to be installed and uninstalled given a name
 /// Knows how to run hooks
@@ -209,6 +220,45 @@ impl HookManager {
         hooks.into_iter()
     }
 
+    /// Split the hooks into stages that are run one after the other, so
+    /// that every hook runs in a later stage than the hooks it is configured
+    /// to run after.  Hooks within a stage run in parallel.  Dependencies on
+    /// hooks that aren't in `hooks` are ignored.
+    fn hook_stages<'a>(
+        &'a self,
+        hooks: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<Vec<&'a str>>, ErrorKind> {
+        let mut remaining: Vec<&'a str> = hooks.into_iter().unique().collect();
+        let mut stages = Vec::new();
+        while !remaining.is_empty() {
+            let pending: HashSet<&str> = remaining.iter().copied().collect();
+            let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|name| {
+                self.hooks.get(*name).map_or(true, |hook| {
+                    !hook
+                        .get_config()
+                        .scheduling
+                        .run_after
+                        .iter()
+                        .any(|dep| dep.as_str() != *name && pending.contains(dep.as_str()))
+                })
+            });
+            if ready.is_empty() {
+                return Err(ErrorKind::HookDependencyCycle(
+                    blocked.into_iter().map(String::from).sorted().collect(),
+                ));
+            }
+            stages.push(ready);
+            remaining = blocked;
+        }
+        Ok(stages)
+    }
+
+    /// Check that the hooks don't need to run after each other in a cycle.
+    pub(crate) fn validate_hook_order(&self) -> Result<(), ErrorKind> {
+        self.hook_stages(self.hooks.keys().map(|name| name.as_str()))?;
+        Ok(())
+    }
+
     pub fn all_hooks_bypassed(&self) -> bool {
         self.all_hooks_bypassed
     }
@@ -228,9 +278,18 @@ impl HookManager {
     ) -> Result<Vec<HookOutcome>, Error> {
         debug!(ctx.logger(), "Running hooks for bookmark {:?}", bookmark);
 
-        let hooks = self.hooks_for_bookmark(bookmark);
+        let stages = self.hook_stages(self.hooks_for_bookmark(bookmark))?;
+        let changesets: Vec<&BonsaiChangeset> = changesets.collect();
+        // Changesets in the batch may not have been stored yet, so files
+        // are diffed against the batch before their stored ancestors.
+        let batch: HashMap<ChangesetId, &BonsaiChangeset> = changesets
+            .iter()
+            .map(|cs| (cs.get_changeset_id(), *cs))
+            .collect();
 
-        let futs = FuturesUnordered::new();
+        // Hooks often look at the same files, so share the contents fetched
+        // by one hook with the others.
+        let content_manager = SharedFileContentManager::new(&*self.content_manager);
 
         let mut scuba = self.scuba.clone();
         let username = ctx.metadata().unix_name();
@@ -240,40 +299,146 @@ impl HookManager {
             scuba.add("user", user);
         }
 
-        for (cs, hook_name) in changesets.cartesian_product(hooks) {
-            let hook = self
-                .hooks
-                .get(hook_name)
-                .ok_or_else(|| ErrorKind::NoSuchHook(hook_name.to_string()))?;
-
-            let mut scuba = scuba.clone();
-            scuba.add("hook", hook_name.to_string());
-            scuba.add("hash", cs.get_changeset_id().to_string());
-
-            if let Some(bypass_reason) = get_bypass_reason(
-                hook.get_config().bypass.as_ref(),
-                cs.message(),
-                maybe_pushvars,
-            ) {
-                scuba.add("bypass_reason", bypass_reason);
-                scuba.log();
-                continue;
-            }
+        let disabled_hooks = tunables()
+            .get_by_repo_disabled_hooks(&self.repo_name)
+            .unwrap_or_default();
 
-            for future in hook.get_futures(
-                ctx,
-                bookmark,
-                &*self.content_manager,
-                hook_name,
-                cs,
-                scuba,
-                cross_repo_push_source,
-                push_authored_by,
-            ) {
-                futs.push(future);
+        let mut outcomes = Vec::new();
+        // Changesets rejected by each hook, so that the hooks that run after
+        // it can skip them.
+        let mut rejected: HashSet<(ChangesetId, String)> = HashSet::new();
+
+        for stage in stages {
+            let futs = FuturesUnordered::new();
+            let mut disabled_outcomes = Vec::new();
+
+            for (cs, hook_name) in changesets.iter().copied().cartesian_product(stage) {
+                let hook = self
+                    .hooks
+                    .get(hook_name)
+                    .ok_or_else(|| ErrorKind::NoSuchHook(hook_name.to_string()))?;
+
+                let rules = &hook.get_config().rules;
+                if !rules.applies_to_bookmark(bookmark) {
+                    continue;
+                }
+                if let Hook::Changeset(..) = hook {
+                    if !rules.applies_to_any_path(cs.file_changes().map(|(path, _)| path)) {
+                        continue;
+                    }
+                }
+
+                let mut scuba = scuba.clone();
+                scuba.add("hook", hook_name.to_string());
+                scuba.add("hash", cs.get_changeset_id().to_string());
+
+                if let Some(bypass_reason) = get_bypass_reason(
+                    hook.get_config().bypass.as_ref(),
+                    cs.message(),
+                    maybe_pushvars,
+                ) {
+                    scuba.add("bypass_reason", bypass_reason);
+                    scuba.log();
+                    continue;
+                }
+
+                if let Some(dependency) = hook
+                    .get_config()
+                    .scheduling
+                    .run_after
+                    .iter()
+                    .find(|dep| rejected.contains(&(cs.get_changeset_id(), dep.to_string())))
+                {
+                    scuba.add("skipped_after_rejection_by", dependency.clone());
+                    scuba.log();
+                    continue;
+                }
+
+                if disabled_hooks.iter().any(|name| name == hook_name) {
+                    let failure_policy = hook.get_config().limits.failure_policy;
+                    let execution =
+                        apply_failure_policy(ctx, failure_policy, hook_name, "hook is disabled");
+                    scuba
+                        .add("disabled", true)
+                        .add("failure_policy", format!("{:?}", failure_policy))
+                        .log();
+                    if let HookExecution::Rejected(_) = execution {
+                        disabled_outcomes.push(HookOutcome::ChangesetHook(
+                            ChangesetHookExecutionID {
+                                cs_id: cs.get_changeset_id(),
+                                hook_name: hook_name.to_string(),
+                            },
+                            execution,
+                        ));
+                    }
+                    continue;
+                }
+
+                for future in hook.get_futures(
+                    ctx,
+                    bookmark,
+                    &content_manager,
+                    hook_name,
+                    cs,
+                    &batch,
+                    scuba,
+                    cross_repo_push_source,
+                    push_authored_by,
+                ) {
+                    futs.push(future);
+                }
             }
+
+            let mut stage_outcomes: Vec<HookOutcome> = repo_bulkheads(&self.repo_name)
+                .get(BulkheadOp::Hooks)
+                .run(ctx, futs.try_collect())
+                .await?;
+            stage_outcomes.extend(disabled_outcomes);
+
+            rejected.extend(
+                stage_outcomes
+                    .iter()
+                    .filter(|outcome| outcome.is_rejection())
+                    .map(|outcome| {
+                        (
+                            outcome.get_changeset_id(),
+                            outcome.get_hook_name().to_string(),
+                        )
+                    }),
+            );
+            outcomes.extend(stage_outcomes);
+        }
+
+        Ok(outcomes)
+    }
+}
+
+/// Decides the outcome of a hook that could not run to completion, and logs
+/// the decision.
+fn apply_failure_policy(
+    ctx: &CoreContext,
+    failure_policy: HookFailurePolicy,
+    hook_name: &str,
+    reason: &str,
+) -> HookExecution {
+    match failure_policy {
+        HookFailurePolicy::FailOpen => {
+            warn!(ctx.logger(), "Hook {}: {}, failing open", hook_name, reason);
+            HookExecution::Accepted
+        }
+        HookFailurePolicy::FailClosed => {
+            warn!(
+                ctx.logger(),
+                "Hook {}: {}, failing closed", hook_name, reason
+            );
+            HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Hook could not complete",
+                format!(
+                    "Hook {} could not complete: {}. Please retry, and contact the repository owners if this persists.",
+                    hook_name, reason
+                ),
+            ))
         }
-        futs.try_collect().await
     }
 }
 
@@ -349,7 +514,12 @@ enum Hook {
 
 enum HookInstance<'a> {
     Changeset(&'a dyn ChangesetHook),
-    File(&'a dyn FileHook, &'a MPath, Option<&'a BasicFileChange>),
+    File(
+        &'a dyn FileHook,
+        &'a MPath,
+        Option<&'a BasicFileChange>,
+        FileDiff,
+    ),
 }
 
 impl<'a> HookInstance<'a> {
@@ -359,58 +529,122 @@ impl<'a> HookInstance<'a> {
         bookmark: &BookmarkName,
         content_manager: &dyn FileContentManager,
         hook_name: &str,
+        config: &HookConfig,
         mut scuba: MononokeScubaSampleBuilder,
         cs: &BonsaiChangeset,
         cs_id: ChangesetId,
         cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
     ) -> Result<HookOutcome, Error> {
-        let (stats, result) = match self {
-            Self::Changeset(hook) => {
-                hook.run(
-                    ctx,
-                    bookmark,
-                    cs,
-                    content_manager,
-                    cross_repo_push_source,
-                    push_authored_by,
-                )
-                .map_ok(|exec| {
-                    HookOutcome::ChangesetHook(
-                        ChangesetHookExecutionID {
-                            cs_id,
-                            hook_name: hook_name.to_string(),
-                        },
-                        exec,
+        let limits = &config.limits;
+        // Hooks that only look at metadata must not fetch any contents.
+        let max_file_fetches = if config.scheduling.metadata_only {
+            Some(0)
+        } else {
+            limits.max_file_fetches
+        };
+        let content_manager =
+            FetchLimitedFileContentManager::new(content_manager, max_file_fetches);
+        let file_path = match &self {
+            Self::Changeset(_) => None,
+            Self::File(_, path, _, _) => Some(*path),
+        };
+
+        let execution = async {
+            match self {
+                Self::Changeset(hook) => {
+                    hook.run(
+                        ctx,
+                        bookmark,
+                        cs,
+                        &content_manager,
+                        cross_repo_push_source,
+                        push_authored_by,
                     )
-                })
-                .timed()
-                .await
+                    .await
+                }
+                Self::File(hook, path, change, diff) => {
+                    hook.run(
+                        ctx,
+                        &content_manager,
+                        change,
+                        path,
+                        &diff,
+                        cross_repo_push_source,
+                        push_authored_by,
+                    )
+                    .await
+                }
             }
-            Self::File(hook, path, change) => {
-                hook.run(
+        };
+
+        let (stats, result) = match limits.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, execution).timed().await,
+            None => execution.map(Ok).timed().await,
+        };
+
+        let limit_exceeded = match &result {
+            Ok(_) if content_manager.limit_exceeded() => Some(format!(
+                "exceeded the limit of {} file fetches",
+                max_file_fetches.unwrap_or_default()
+            )),
+            Ok(_) => None,
+            Err(_) => Some(format!(
+                "timed out after {}ms",
+                limits.timeout.unwrap_or_default().as_millis()
+            )),
+        };
+
+        let result = match (result, limit_exceeded) {
+            (Ok(result), None) => result,
+            (_, Some(reason)) => {
+                scuba
+                    .add("limit_exceeded", reason.clone())
+                    .add("failure_policy", format!("{:?}", limits.failure_policy));
+                Ok(apply_failure_policy(
                     ctx,
-                    content_manager,
-                    change,
-                    path,
-                    cross_repo_push_source,
-                    push_authored_by,
-                )
-                .map_ok(|exec| {
-                    HookOutcome::FileHook(
-                        FileHookExecutionID {
-                            cs_id,
-                            path: path.clone(),
-                            hook_name: hook_name.to_string(),
-                        },
-                        exec,
-                    )
-                })
-                .timed()
-                .await
+                    limits.failure_policy,
+                    hook_name,
+                    &reason,
+                ))
             }
+            (Err(elapsed), None) => Err(elapsed.into()),
         };
 
+        let result = result.map(|exec| match exec {
+            HookExecution::Rejected(info) if config.rules.severity == HookSeverity::Warn => {
+                warn!(
+                    ctx.logger(),
+                    "Hook {} rejected the change, but only warns: {}",
+                    hook_name,
+                    info.long_description
+                );
+                scuba
+                    .add("severity", "warn")
+                    .add("warning", info.long_description);
+                HookExecution::Accepted
+            }
+            exec => exec,
+        });
+
+        let result = result.map(|exec| match file_path {
+            None => HookOutcome::ChangesetHook(
+                ChangesetHookExecutionID {
+                    cs_id,
+                    hook_name: hook_name.to_string(),
+                },
+                exec,
+            ),
+            Some(path) => HookOutcome::FileHook(
+                FileHookExecutionID {
+                    cs_id,
+                    path: path.clone(),
+                    hook_name: hook_name.to_string(),
+                },
+                exec,
+            ),
+        });
+
         let mut errorcode = 0;
         let mut failed_hooks = 0;
         let mut stderr = None;
@@ -468,6 +702,7 @@ impl Hook {
         content_manager: &'a dyn FileContentManager,
         hook_name: &'cs str,
         cs: &'cs BonsaiChangeset,
+        batch: &'cs HashMap<ChangesetId, &'cs BonsaiChangeset>,
         scuba: MononokeScubaSampleBuilder,
         cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
@@ -477,32 +712,37 @@ impl Hook {
         let cs_id = cs.get_changeset_id();
 
         match self {
-            Self::Changeset(hook, _) => futures.push(HookInstance::Changeset(&**hook).run(
+            Self::Changeset(hook, config) => futures.push(HookInstance::Changeset(&**hook).run(
                 ctx,
                 bookmark,
                 content_manager,
                 hook_name,
+                config,
                 scuba,
                 cs,
                 cs_id,
                 cross_repo_push_source,
                 push_authored_by,
             )),
-            Self::File(hook, _) => {
-                futures.extend(cs.simplified_file_changes().map(move |(path, change)| {
-                    HookInstance::File(&**hook, path, change).run(
-                        ctx,
-                        bookmark,
-                        content_manager,
-                        hook_name,
-                        scuba.clone(),
-                        cs,
-                        cs_id,
-                        cross_repo_push_source,
-                        push_authored_by,
-                    )
-                }))
-            }
+            Self::File(hook, config) => futures.extend(
+                cs.simplified_file_changes()
+                    .filter(move |(path, _)| config.rules.applies_to_path(path))
+                    .map(move |(path, change)| {
+                        let diff = FileDiff::new(batch, cs, path, change);
+                        HookInstance::File(&**hook, path, change, diff).run(
+                            ctx,
+                            bookmark,
+                            content_manager,
+                            hook_name,
+                            config,
+                            scuba.clone(),
+                            cs,
+                            cs_id,
+                            cross_repo_push_source,
+                            push_authored_by,
+                        )
+                    }),
+            ),
         };
         futures.into_iter()
     }
@@ -529,11 +769,116 @@ pub trait FileHook: Send + Sync {
         content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         path: &'path MPath,
+        diff: &'change FileDiff,
         cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error>;
 }
 
+/// What a file is diffed against.
+enum DiffBase {
+    /// The file as it is in a stored changeset.
+    Changeset(ChangesetId),
+    /// The file as it was last changed in the batch of changesets being
+    /// checked, or `None` if it was deleted or has no earlier version.
+    Content(Option<ContentId>),
+}
+
+/// The change made to a file by a changeset, as a unified diff against the
+/// changeset's first parent.  The diff is only computed when a hook asks for
+/// it, and it's computed through the content manager, which shares it
+/// between all the hooks that check the same file.
+pub struct FileDiff {
+    base: DiffBase,
+    path: MPath,
+    new: Option<ContentId>,
+}
+
+impl FileDiff {
+    /// The diff of a file changed by `cs`, which is one of the `batch` of
+    /// changesets being checked together.  Those changesets may not have
+    /// been stored yet, so the first-parent chain is followed through the
+    /// batch to the last change to the file, and only a changeset outside
+    /// the batch is read from the repo.
+    fn new(
+        batch: &HashMap<ChangesetId, &BonsaiChangeset>,
+        cs: &BonsaiChangeset,
+        path: &MPath,
+        change: Option<&BasicFileChange>,
+    ) -> Self {
+        let mut base = DiffBase::Content(None);
+        let mut parent = cs.parents().next();
+        while let Some(parent_id) = parent {
+            match batch.get(&parent_id) {
+                Some(parent_cs) => {
+                    if let Some(parent_change) = parent_cs.file_changes_map().get(path) {
+                        base = DiffBase::Content(
+                            parent_change.simplify().map(BasicFileChange::content_id),
+                        );
+                        break;
+                    }
+                    parent = parent_cs.parents().next();
+                }
+                None => {
+                    base = DiffBase::Changeset(parent_id);
+                    break;
+                }
+            }
+        }
+        Self {
+            base,
+            path: path.clone(),
+            new: change.map(BasicFileChange::content_id),
+        }
+    }
+
+    /// The unified diff of the file, without file headers.  Returns `None`
+    /// if the file was deleted, or if either side of the diff is not text.
+    pub async fn unified(
+        &self,
+        ctx: &CoreContext,
+        content_manager: &dyn FileContentManager,
+    ) -> Result<Option<Bytes>, Error> {
+        let new = match self.new {
+            Some(new) => new,
+            None => return Ok(None),
+        };
+        let old = match self.base {
+            DiffBase::Changeset(parent) => {
+                let mut contents = content_manager
+                    .find_content_in_changeset(ctx, parent, vec![self.path.clone()])
+                    .await?;
+                match contents.remove(&self.path) {
+                    Some(PathContent::File(old)) => Some(old),
+                    Some(PathContent::Directory) | None => None,
+                }
+            }
+            DiffBase::Content(old) => old,
+        };
+        if old == Some(new) {
+            // Only the file type changed.
+            return Ok(Some(Bytes::new()));
+        }
+        Ok(content_manager.get_file_diff(ctx, old, new).await?)
+    }
+
+    /// The lines added to the file, without their line endings, so that
+    /// lint-style hooks can check only what the changeset added.  Returns
+    /// `None` in the same cases as `unified`.
+    pub async fn added_lines(
+        &self,
+        ctx: &CoreContext,
+        content_manager: &dyn FileContentManager,
+    ) -> Result<Option<Vec<Bytes>>, Error> {
+        Ok(self.unified(ctx, content_manager).await?.map(|diff| {
+            diff.split(|c| *c == b'\n')
+                .filter_map(|line| line.strip_prefix(b"+"))
+                .map(|line| diff.slice_ref(line))
+                .collect()
+        }))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum HookOutcome {
     ChangesetHook(ChangesetHookExecutionID, HookExecution),
@@ -15,6 +15,7 @@ use regex::Regex;
 
 use crate::CrossRepoPushSource;
 use crate::FileContentManager;
+use crate::FileDiff;
 use crate::FileHook;
 use crate::HookConfig;
 use crate::HookExecution;
@@ -57,6 +58,7 @@ impl FileHook for CheckNocommitHook {
         content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         path: &'path MPath,
+        _diff: &'change FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution, Error> {
@@ -10,12 +10,15 @@ use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
 use context::CoreContext;
+use futures::TryStreamExt;
 use mononoke_types::BasicFileChange;
+use mononoke_types::ContentId;
 use mononoke_types::MPath;
 use regex::Regex;
 
 use crate::CrossRepoPushSource;
 use crate::FileContentManager;
+use crate::FileDiff;
 use crate::FileHook;
 use crate::HookConfig;
 use crate::HookExecution;
@@ -92,6 +95,7 @@ impl FileHook for LimitFilesize {
         content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         path: &'path MPath,
+        _diff: &'change FileDiff,
         cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution> {
@@ -109,27 +113,51 @@ impl FileHook for LimitFilesize {
             None => return Ok(HookExecution::Accepted),
         };
 
-        let len = content_manager
-            .get_file_size(ctx, change.content_id())
-            .await?;
-        for (regex, maybe_limit) in &self.path_regexes_with_limits {
-            if !regex.is_match(&path) {
-                continue;
-            }
-            match maybe_limit {
-                None => return Ok(HookExecution::Accepted),
-                Some(limit) if len <= *limit => return Ok(HookExecution::Accepted),
-                Some(limit) => {
-                    return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
-                        "File too large",
-                        format!(
-                            "File size limit is {} bytes. You tried to push file {} that is over the limit ({} bytes). This limit is enforced for files matching the following regex: \"{}\". See https://fburl.com/landing_big_diffs for instructions.",
-                            limit, path, len, regex
-                        ),
-                    )));
-                }
-            }
+        // The first regex that matches the path decides the limit.
+        let (regex, limit) = match self
+            .path_regexes_with_limits
+            .iter()
+            .find(|(regex, _)| regex.is_match(&path))
+        {
+            Some((regex, Some(limit))) => (regex, *limit),
+            _ => return Ok(HookExecution::Accepted),
+        };
+        let content_id = change.content_id();
+        if !exceeds_limit(ctx, content_manager, content_id, limit).await? {
+            return Ok(HookExecution::Accepted);
+        }
+
+        let len = content_manager.get_file_size(ctx, content_id).await?;
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "File too large",
+            format!(
+                "File size limit is {} bytes. You tried to push file {} that is over the limit ({} bytes). This limit is enforced for files matching the following regex: \"{}\". See https://fburl.com/landing_big_diffs for instructions.",
+                limit, path, len, regex
+            ),
+        )))
+    }
+}
+
+/// Whether a file is larger than `limit`.  The file is streamed, and
+/// reading stops as soon as it is over the limit.  Files that the content
+/// manager doesn't stream, e.g. because they are binary, are checked using
+/// their metadata instead.
+async fn exceeds_limit(
+    ctx: &CoreContext,
+    content_manager: &dyn FileContentManager,
+    content_id: ContentId,
+    limit: u64,
+) -> Result<bool> {
+    let mut file_stream = match content_manager.get_file_stream(ctx, content_id).await? {
+        Some(file_stream) => file_stream,
+        None => return Ok(content_manager.get_file_size(ctx, content_id).await? > limit),
+    };
+    let mut len = 0;
+    while let Some(chunk) = file_stream.try_next().await? {
+        len += chunk.len() as u64;
+        if len > limit {
+            return Ok(true);
         }
-        Ok(HookExecution::Accepted)
     }
+    Ok(false)
 }
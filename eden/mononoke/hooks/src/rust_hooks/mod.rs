@@ -17,11 +17,14 @@ pub(crate) mod limit_commitsize;
 pub(crate) mod limit_filesize;
 mod limit_path_length;
 mod lua_pattern;
+mod no_absolute_symlinks;
 pub(crate) mod no_bad_extensions;
 pub(crate) mod no_bad_filenames;
 mod no_insecure_filenames;
+mod no_non_utf8_paths;
 pub(crate) mod no_questionable_filenames;
 pub(crate) mod no_windows_filenames;
+mod secret_scanning;
 
 use anyhow::Result;
 use fbinit::FacebookInit;
@@ -59,6 +62,9 @@ pub fn hook_name_to_changeset_hook<'a>(
             "limit_commitsize" => Some(b(limit_commitsize::LimitCommitsize::builder()
                 .set_from_config(config)
                 .build()?)),
+            "secret_scanning" => Some(b(secret_scanning::SecretScanning::builder()
+                .set_from_config(config)?
+                .build()?)),
             _ => None,
         })
     }
@@ -85,6 +91,11 @@ pub fn hook_name_to_file_hook(
         "limit_path_length" => Some(Box::new(limit_path_length::LimitPathLengthHook::new(
             config,
         )?)),
+        "no_absolute_symlinks" => Some(Box::new(
+            no_absolute_symlinks::NoAbsoluteSymlinks::builder()
+                .set_from_config(config)
+                .build()?,
+        )),
         "no_bad_filenames" => Some(Box::new(
             no_bad_filenames::NoBadFilenames::builder()
                 .set_from_config(config)
@@ -98,6 +109,7 @@ pub fn hook_name_to_file_hook(
         "no_insecure_filenames" => {
             Some(Box::new(no_insecure_filenames::NoInsecureFilenames::new()?))
         }
+        "no_non_utf8_paths" => Some(Box::new(no_non_utf8_paths::NoNonUtf8Paths)),
         "no_questionable_filenames" => Some(Box::new(
             no_questionable_filenames::NoQuestionableFilenames::builder()
                 .set_from_config(config)
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use lazy_static::lazy_static;
+use metaconfig_types::HookConfig;
+use mononoke_types::BasicFileChange;
+use mononoke_types::FileType;
+use mononoke_types::MPath;
+use regex::bytes::Regex;
+
+use crate::CrossRepoPushSource;
+use crate::FileContentManager;
+use crate::FileDiff;
+use crate::FileHook;
+use crate::HookExecution;
+use crate::HookRejectionInfo;
+use crate::PushAuthoredBy;
+
+#[derive(Default)]
+pub struct NoAbsoluteSymlinksBuilder<'a> {
+    /// Paths on which absolute symlinks are allowed.
+    allowed_paths: Option<&'a str>,
+}
+
+impl<'a> NoAbsoluteSymlinksBuilder<'a> {
+    pub fn set_from_config(mut self, config: &'a HookConfig) -> Self {
+        if let Some(v) = config.strings.get("allowed_paths") {
+            self = self.allowed_paths(v)
+        }
+
+        self
+    }
+
+    pub fn allowed_paths(mut self, regex: &'a str) -> Self {
+        self.allowed_paths = Some(regex);
+        self
+    }
+
+    pub fn build(self) -> Result<NoAbsoluteSymlinks> {
+        Ok(NoAbsoluteSymlinks {
+            allowed_paths: self
+                .allowed_paths
+                .map(Regex::new)
+                .transpose()
+                .context("Failed to create allowed_paths regex")?,
+        })
+    }
+}
+
+/// Hook to disallow symlinks whose target is an absolute path, either in
+/// the Unix (`/foo`) or in the Windows (`C:\foo`, `\\server\foo`) form.
+/// Such symlinks point outside of the checkout, and are broken on most
+/// machines other than the author's.
+pub struct NoAbsoluteSymlinks {
+    allowed_paths: Option<Regex>,
+}
+
+impl NoAbsoluteSymlinks {
+    pub fn builder<'a>() -> NoAbsoluteSymlinksBuilder<'a> {
+        NoAbsoluteSymlinksBuilder::default()
+    }
+}
+
+fn is_absolute_target(target: &[u8]) -> bool {
+    lazy_static! {
+        static ref WINDOWS_ABSOLUTE: Regex = Regex::new(r"^([A-Za-z]:)?[\\/]").unwrap();
+    }
+    WINDOWS_ABSOLUTE.is_match(target)
+}
+
+#[async_trait]
+impl FileHook for NoAbsoluteSymlinks {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        content_manager: &'fetcher dyn FileContentManager,
+        change: Option<&'change BasicFileChange>,
+        path: &'path MPath,
+        _diff: &'change FileDiff,
+        cross_repo_push_source: CrossRepoPushSource,
+        push_authored_by: PushAuthoredBy,
+    ) -> Result<HookExecution> {
+        if push_authored_by.service() {
+            return Ok(HookExecution::Accepted);
+        }
+        if cross_repo_push_source == CrossRepoPushSource::PushRedirected {
+            // For push-redirected pushes we rely on the hook
+            // running in the original repo
+            return Ok(HookExecution::Accepted);
+        }
+
+        let change = match change {
+            Some(change) if change.file_type() == FileType::Symlink => change,
+            _ => return Ok(HookExecution::Accepted),
+        };
+
+        if let Some(allowed_paths) = &self.allowed_paths {
+            if allowed_paths.is_match(&path.to_vec()) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        let target = content_manager
+            .get_file_text(ctx, change.content_id())
+            .await?;
+
+        match target {
+            Some(target) if is_absolute_target(&target) => {
+                Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Absolute symlink target",
+                    format!(
+                        "ABORT: Symlink {} points to an absolute path: {}",
+                        path,
+                        String::from_utf8_lossy(&target)
+                    ),
+                )))
+            }
+            _ => Ok(HookExecution::Accepted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_relative_targets() {
+        assert!(!is_absolute_target(b"foo"));
+        assert!(!is_absolute_target(b"../foo/bar"));
+        assert!(!is_absolute_target(b"./foo"));
+        assert!(!is_absolute_target(b"C/foo"));
+    }
+
+    #[test]
+    fn test_absolute_targets() {
+        assert!(is_absolute_target(b"/"));
+        assert!(is_absolute_target(b"/usr/bin/python3"));
+        assert!(is_absolute_target(b"C:\\Windows"));
+        assert!(is_absolute_target(b"d:/foo"));
+        assert!(is_absolute_target(b"\\\\server\\share"));
+    }
+}
@@ -15,6 +15,7 @@ use mononoke_types::MPath;
 
 use crate::CrossRepoPushSource;
 use crate::FileContentManager;
+use crate::FileDiff;
 use crate::FileHook;
 use crate::HookExecution;
 use crate::HookRejectionInfo;
@@ -66,6 +67,7 @@ impl FileHook for NoBadExtensions {
         _content_manager: &'fetcher dyn FileContentManager,
         change: Option<&'change BasicFileChange>,
         path: &'path MPath,
+        _diff: &'change FileDiff,
         _cross_repo_push_source: CrossRepoPushSource,
         push_authored_by: PushAuthoredBy,
     ) -> Result<HookExecution> {
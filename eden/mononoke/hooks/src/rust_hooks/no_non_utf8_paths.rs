@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use mononoke_types::BasicFileChange;
+use mononoke_types::MPath;
+
+use crate::CrossRepoPushSource;
+use crate::FileContentManager;
+use crate::FileDiff;
+use crate::FileHook;
+use crate::HookExecution;
+use crate::HookRejectionInfo;
+use crate::PushAuthoredBy;
+
+/// Hook to disallow paths that are not valid UTF-8, as most clients other
+/// than Mercurial itself can't represent them.
+pub struct NoNonUtf8Paths;
+
+fn check_path_is_utf8(path: &MPath) -> HookExecution {
+    for element in path {
+        if std::str::from_utf8(element.as_ref()).is_err() {
+            return HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Path is not valid UTF-8",
+                format!(
+                    "ABORT: Path is not valid UTF-8: {}",
+                    String::from_utf8_lossy(&path.to_vec())
+                ),
+            ));
+        }
+    }
+    HookExecution::Accepted
+}
+
+#[async_trait]
+impl FileHook for NoNonUtf8Paths {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _content_manager: &'fetcher dyn FileContentManager,
+        change: Option<&'change BasicFileChange>,
+        path: &'path MPath,
+        _diff: &'change FileDiff,
+        cross_repo_push_source: CrossRepoPushSource,
+        push_authored_by: PushAuthoredBy,
+    ) -> Result<HookExecution> {
+        if push_authored_by.service() {
+            return Ok(HookExecution::Accepted);
+        }
+        if cross_repo_push_source == CrossRepoPushSource::PushRedirected {
+            // For push-redirected pushes we rely on the hook
+            // running in the original repo
+            return Ok(HookExecution::Accepted);
+        }
+
+        if change.is_none() {
+            return Ok(HookExecution::Accepted);
+        }
+
+        Ok(check_path_is_utf8(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_path(path: &[u8]) -> bool {
+        match check_path_is_utf8(&MPath::new(path).unwrap()) {
+            HookExecution::Accepted => true,
+            HookExecution::Rejected(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_utf8_paths() {
+        assert!(check_path(b"dir/file.txt"));
+        assert!(check_path("dir/f\u{00ee}chier".as_bytes()));
+        assert!(check_path("\u{65e5}\u{672c}/\u{8a9e}".as_bytes()));
+    }
+
+    #[test]
+    fn test_non_utf8_paths() {
+        assert!(!check_path(b"dir/f\xeechier"));
+        assert!(!check_path(b"\xff\xfe/file"));
+        assert!(!check_path(b"dir/truncated\xe6\x97"));
+    }
+}
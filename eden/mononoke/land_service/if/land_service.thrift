@@ -105,6 +105,49 @@ safe stateful client exception HookRejectionsException {
   2: list<HookRejection> rejections;
 } (message = "reason", rust.exhaustive)
 
+struct SubmitLandChangesetsRequest {
+  /// The stack to land.
+  1: LandChangesetRequest land_changesets;
+
+  /// If set, the final status of the ticket is POSTed to this URL as JSON
+  /// once the stack is landed or fails to land. The URL's host must be one
+  /// the service is configured to send callbacks to.
+  2: optional string callback_url;
+} (rust.exhaustive)
+
+struct SubmitLandChangesetsResponse {
+  /// Id to query the status of the landing with get_land_ticket.
+  1: string ticket_id;
+} (rust.exhaustive)
+
+struct GetLandTicketRequest {
+  1: string ticket_id;
+} (rust.exhaustive)
+
+enum LandTicketState {
+  /// The stack is waiting to be landed.
+  PENDING = 0,
+  /// The stack is being pushrebased.
+  IN_PROGRESS = 1,
+  /// The stack was landed, see pushrebase_outcome.
+  LANDED = 2,
+  /// The stack could not be landed, see failure_reason.
+  FAILED = 3,
+}
+
+struct LandTicketStatus {
+  1: string ticket_id;
+  2: LandTicketState state;
+  /// Set when the state is LANDED.
+  3: optional PushrebaseOutcome pushrebase_outcome;
+  /// Set when the state is FAILED.
+  4: optional string failure_reason;
+  /// Conflicts that prevented the landing, if any.
+  5: list<PushrebaseConflicts> conflicts;
+  /// Hook rejections that prevented the landing, if any.
+  6: list<HookRejection> hook_rejections;
+} (rust.exhaustive)
+
 safe client exception InternalError {
   1: string reason;
   2: optional string backtrace;
@@ -120,4 +163,20 @@ service LandService extends fb303_core.BaseService {
     3: HookRejectionsException hook_rejections,
     4: InternalError internal_error,
   );
+
+  /// Submit a stack of commits for asynchronous landing. Returns
+  /// immediately with a ticket that can be polled with get_land_ticket.
+  ///
+  /// Tickets are best-effort: they are kept in memory by the server that
+  /// accepted them, and are lost if it restarts. If a ticket is no longer
+  /// known, check the bookmark to find out whether the stack was landed.
+  SubmitLandChangesetsResponse submit_land_changesets(
+    1: SubmitLandChangesetsRequest request,
+  ) throws (1: InternalError internal_error);
+
+  /// Get the status of a landing submitted with submit_land_changesets.
+  /// Finished tickets are kept for a limited time only.
+  LandTicketStatus get_land_ticket(1: GetLandTicketRequest request) throws (
+    1: InternalError internal_error,
+  );
 } (rust.request_context)
@@ -12,6 +12,7 @@ use bookmarks_movement::describe_hook_rejections;
 use bookmarks_movement::BookmarkMovementError;
 use bookmarks_movement::HookRejection;
 use land_service_if::services::land_service::LandChangesetsExn;
+use land_service_if::services::land_service::SubmitLandChangesetsExn;
 use land_service_if::InternalError;
 use mononoke_api::MononokeError;
 use pushrebase::PushrebaseConflict;
@@ -92,6 +93,15 @@ impl From<LandChangesetsError> for LandChangesetsExn {
     }
 }
 
+impl From<LandChangesetsError> for SubmitLandChangesetsExn {
+    fn from(e: LandChangesetsError) -> SubmitLandChangesetsExn {
+        match e {
+            LandChangesetsError::InternalError(e) => SubmitLandChangesetsExn::internal_error(e),
+            e => SubmitLandChangesetsExn::internal_error(internal_error(&e)),
+        }
+    }
+}
+
 pub(crate) fn internal_error(error: &dyn StdError) -> land_service_if::InternalError {
     let _reason = format!("{:#}", error);
     let mut source_chain = Vec::new();
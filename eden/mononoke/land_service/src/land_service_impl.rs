@@ -8,13 +8,17 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use async_trait::async_trait;
+use cloned::cloned;
 use futures::channel::oneshot;
 use futures::future::BoxFuture;
 use futures::future::Shared;
 use land_service_if::server::LandService;
+use land_service_if::services::land_service::GetLandTicketExn;
 use land_service_if::services::land_service::LandChangesetsExn;
+use land_service_if::services::land_service::SubmitLandChangesetsExn;
 use land_service_if::types::*;
 use mononoke_api::CoreContext;
 use parking_lot::Mutex;
@@ -25,6 +29,8 @@ use crate::errors;
 use crate::errors::LandChangesetsError;
 use crate::factory::Factory;
 use crate::land_changeset_object::LandChangesetObject;
+use crate::tickets::CallbackNotifier;
+use crate::tickets::LandTickets;
 use crate::worker;
 use crate::worker::EnqueueSender;
 
@@ -34,22 +40,62 @@ pub(crate) struct LandServiceImpl {
     #[allow(dead_code)]
     repo_bookmark_map:
         Arc<Mutex<HashMap<RepoBookmarkKey, (EnqueueSender, Shared<BoxFuture<'static, ()>>)>>>,
+    tickets: LandTickets,
+    callbacks: CallbackNotifier,
 }
 
 pub(crate) struct LandServiceThriftImpl(LandServiceImpl);
 
 impl LandServiceImpl {
-    pub fn new(factory: Factory) -> Self {
+    pub fn new(factory: Factory, callbacks: CallbackNotifier) -> Self {
         let repo_bookmark_map = Arc::new(Mutex::new(HashMap::new()));
         Self {
             factory,
             repo_bookmark_map,
+            tickets: LandTickets::new(),
+            callbacks,
         }
     }
 
     pub(crate) fn thrift_server(&self) -> LandServiceThriftImpl {
         LandServiceThriftImpl(self.clone())
     }
+
+    /// Land a stack, batching it with other landings to the same bookmark if
+    /// enabled.
+    async fn land(
+        &self,
+        land_changeset_object: LandChangesetObject,
+    ) -> Result<LandChangesetsResponse, LandChangesetsError> {
+        if tunables().get_batching_to_land_service() {
+            let (sender, receiver) =
+                oneshot::channel::<Result<LandChangesetsResponse, LandChangesetsError>>();
+
+            let serialized_key = RepoBookmarkKey {
+                repo_name: land_changeset_object.request.repo_name.clone(),
+                bookmark: land_changeset_object.request.bookmark.clone(),
+            };
+
+            let (worker_sender, worker_process_future) = {
+                let mut repo_bookmark_map = self.repo_bookmark_map.lock();
+
+                repo_bookmark_map
+                    .entry(serialized_key)
+                    .or_insert_with(worker::setup_worker)
+                    .clone()
+            };
+
+            worker_sender
+                .unbounded_send((sender, land_changeset_object.clone()))
+                .map_err(|e| errors::internal_error(&e))?;
+
+            worker_process_future.await;
+
+            return receiver.await.map_err(|e| errors::internal_error(&e))?;
+        }
+
+        worker::impl_land_changesets(land_changeset_object).await
+    }
 }
 
 #[derive(Hash, PartialEq, Eq)]
@@ -81,33 +127,73 @@ impl LandService for LandServiceThriftImpl {
             land_changesets.clone(),
         );
 
-        if tunables().get_batching_to_land_service() {
-            let (sender, receiver) =
-                oneshot::channel::<Result<LandChangesetsResponse, LandChangesetsError>>();
-
-            let serialized_key = RepoBookmarkKey {
-                repo_name: land_changeset_object.request.repo_name.clone(),
-                bookmark: land_changeset_object.request.bookmark.clone(),
-            };
-
-            let (worker_sender, worker_process_future) = {
-                let mut repo_bookmark_map = self.0.repo_bookmark_map.lock();
+        Ok(self.0.land(land_changeset_object).await?)
+    }
 
-                repo_bookmark_map
-                    .entry(serialized_key)
-                    .or_insert_with(worker::setup_worker)
-                    .clone()
-            };
+    async fn submit_land_changesets(
+        &self,
+        req_ctxt: &RequestContext,
+        request: SubmitLandChangesetsRequest,
+    ) -> Result<SubmitLandChangesetsResponse, SubmitLandChangesetsExn> {
+        let ctx: CoreContext = self
+            .0
+            .factory
+            .create_ctx("submit_land_changesets", req_ctxt)
+            .await?;
 
-            worker_sender
-                .unbounded_send((sender, land_changeset_object.clone()))
-                .map_err(|e| errors::internal_error(&e))?;
+        let callback_url = request
+            .callback_url
+            .map(|callback_url| self.0.callbacks.validate(&callback_url))
+            .transpose()
+            .map_err(|e| {
+                SubmitLandChangesetsExn::internal_error(errors::internal_error(e.as_ref()))
+            })?;
 
-            worker_process_future.await;
+        let land_changeset_object = LandChangesetObject::new(
+            self.0.factory.mononoke.clone(),
+            self.0.factory.identity.clone(),
+            ctx.clone(),
+            request.land_changesets,
+        );
 
-            return Ok(receiver.await.map_err(|e| errors::internal_error(&e))??);
-        }
+        let ticket_id = self.0.tickets.create();
+        ctx.scuba()
+            .clone()
+            .add("ticket_id", ticket_id.clone())
+            .log_with_msg("Land ticket created", None);
+
+        // The landing outlives the request, so it gets its own task.
+        let this = self.0.clone();
+        tokio::spawn({
+            cloned!(ticket_id);
+            async move {
+                this.tickets.set_in_progress(&ticket_id);
+                let result = this.land(land_changeset_object).await;
+                let status = this.tickets.finish(&ticket_id, result);
+
+                if let Some(callback_url) = callback_url {
+                    if let Err(err) = this.callbacks.notify(&callback_url, &status).await {
+                        ctx.scuba()
+                            .clone()
+                            .add("ticket_id", ticket_id)
+                            .log_with_msg("Land ticket callback failed", format!("{:#}", err));
+                    }
+                }
+            }
+        });
+
+        Ok(SubmitLandChangesetsResponse { ticket_id })
+    }
 
-        Ok(worker::impl_land_changesets(land_changeset_object).await?)
+    async fn get_land_ticket(
+        &self,
+        _req_ctxt: &RequestContext,
+        request: GetLandTicketRequest,
+    ) -> Result<LandTicketStatus, GetLandTicketExn> {
+        self.0.tickets.get(&request.ticket_id).ok_or_else(|| {
+            GetLandTicketExn::internal_error(errors::internal_error(
+                anyhow!("Unknown or expired land ticket: {}", request.ticket_id).as_ref(),
+            ))
+        })
     }
 }
@@ -45,6 +45,7 @@ mod land_changeset_object;
 mod land_service_impl;
 mod scuba_request;
 mod scuba_response;
+mod tickets;
 mod worker;
 
 #[derive(Debug, Parser)]
@@ -62,6 +63,10 @@ struct LandServiceServerArgs {
     /// Path for file in which to write the bound tcp address in rust std::net::SocketAddr format
     #[clap(long)]
     bound_address_file: Option<String>,
+    /// Host that land ticket callbacks may be sent to. May be repeated.
+    /// Callbacks are refused if no hosts are given.
+    #[clap(long = "land-ticket-callback-host")]
+    land_ticket_callback_hosts: Vec<String>,
 }
 
 #[fbinit::main]
@@ -97,7 +102,10 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         &app.repo_configs().common,
     );
 
-    let land_service_server = land_service_impl::LandServiceImpl::new(factory);
+    let land_service_server = land_service_impl::LandServiceImpl::new(
+        factory,
+        tickets::CallbackNotifier::new(args.land_ticket_callback_hosts),
+    );
 
     let service = {
         move |proto| {
@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bytes::Bytes;
+use hyper::client::HttpConnector;
+use hyper::http::uri::Scheme;
+use hyper::Client;
+use hyper::Request;
+use hyper::Uri;
+use hyper_openssl::HttpsConnector;
+use land_service_if::services::land_service::LandChangesetsExn;
+use land_service_if::types::*;
+use parking_lot::Mutex;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::conversion_helpers::convert_hex_to_str;
+use crate::errors::LandChangesetsError;
+
+/// How long the status of a finished ticket stays available.
+const FINISHED_TICKET_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// How long a callback request may take before it is abandoned.
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many times a callback is attempted before giving up.
+const CALLBACK_MAX_ATTEMPTS: u32 = 5;
+const CALLBACK_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+struct TicketEntry {
+    status: LandTicketStatus,
+    finished_at: Option<Instant>,
+}
+
+/// In-memory registry of asynchronous landing requests.
+///
+/// Tickets are best-effort: they are only known to the server that
+/// accepted them, and are lost if it restarts, in which case the landing
+/// may or may not have happened.
+#[derive(Clone, Default)]
+pub(crate) struct LandTickets {
+    tickets: Arc<Mutex<HashMap<String, TicketEntry>>>,
+}
+
+impl LandTickets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new ticket in the PENDING state and return its id.
+    pub fn create(&self) -> String {
+        let ticket_id = Uuid::new_v4().to_string();
+        let mut tickets = self.tickets.lock();
+        tickets.retain(|_, entry| {
+            entry.finished_at.map_or(true, |finished_at| {
+                finished_at.elapsed() < FINISHED_TICKET_RETENTION
+            })
+        });
+        tickets.insert(
+            ticket_id.clone(),
+            TicketEntry {
+                status: LandTicketStatus {
+                    ticket_id: ticket_id.clone(),
+                    state: LandTicketState::PENDING,
+                    pushrebase_outcome: None,
+                    failure_reason: None,
+                    conflicts: Vec::new(),
+                    hook_rejections: Vec::new(),
+                },
+                finished_at: None,
+            },
+        );
+        ticket_id
+    }
+
+    pub fn set_in_progress(&self, ticket_id: &str) {
+        if let Some(entry) = self.tickets.lock().get_mut(ticket_id) {
+            entry.status.state = LandTicketState::IN_PROGRESS;
+        }
+    }
+
+    /// Record the outcome of the landing, and return the final status.
+    pub fn finish(
+        &self,
+        ticket_id: &str,
+        result: Result<LandChangesetsResponse, LandChangesetsError>,
+    ) -> LandTicketStatus {
+        let mut status = LandTicketStatus {
+            ticket_id: ticket_id.to_string(),
+            state: LandTicketState::LANDED,
+            pushrebase_outcome: None,
+            failure_reason: None,
+            conflicts: Vec::new(),
+            hook_rejections: Vec::new(),
+        };
+        match result {
+            Ok(response) => {
+                status.pushrebase_outcome = Some(response.pushrebase_outcome);
+            }
+            Err(err) => {
+                status.state = LandTicketState::FAILED;
+                status.failure_reason = Some(err.to_string());
+                match LandChangesetsExn::from(err) {
+                    LandChangesetsExn::pushrebase_conflicts(e) => {
+                        status.conflicts = e.conflicts;
+                    }
+                    LandChangesetsExn::hook_rejections(e) => {
+                        status.hook_rejections = e.rejections;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.tickets.lock().insert(
+            ticket_id.to_string(),
+            TicketEntry {
+                status: status.clone(),
+                finished_at: Some(Instant::now()),
+            },
+        );
+        status
+    }
+
+    pub fn get(&self, ticket_id: &str) -> Option<LandTicketStatus> {
+        self.tickets
+            .lock()
+            .get(ticket_id)
+            .map(|entry| entry.status.clone())
+    }
+}
+
+/// Body of the request sent to the callback URL of a ticket.
+#[derive(Serialize)]
+struct CallbackBody<'a> {
+    ticket_id: &'a str,
+    state: String,
+    head: Option<String>,
+    failure_reason: Option<&'a str>,
+}
+
+/// Sends the final status of tickets to the callback URLs that their
+/// submitters asked for.
+#[derive(Clone, Default)]
+pub(crate) struct CallbackNotifier {
+    /// Hosts that callbacks may be sent to.  If there are none, callbacks
+    /// are refused.
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl CallbackNotifier {
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        Self {
+            allowed_hosts: Arc::new(allowed_hosts),
+        }
+    }
+
+    /// Check that callbacks may be sent to this URL.  This is done when the
+    /// ticket is submitted, so that the submitter finds out straight away.
+    pub fn validate(&self, callback_url: &str) -> Result<Uri> {
+        let uri: Uri = callback_url
+            .parse()
+            .with_context(|| format!("Invalid callback URL: {}", callback_url))?;
+        if uri.scheme() != Some(&Scheme::HTTPS) && uri.scheme() != Some(&Scheme::HTTP) {
+            bail!("Callback URL must be http or https: {}", callback_url);
+        }
+        let allowed = uri.host().map_or(false, |host| {
+            self.allowed_hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        });
+        if !allowed {
+            bail!("Callbacks to {} are not allowed", callback_url);
+        }
+        Ok(uri)
+    }
+
+    /// Tell the submitter of a ticket that it's done.  Failed or timed out
+    /// requests are retried with backoff.
+    pub async fn notify(&self, callback_url: &Uri, status: &LandTicketStatus) -> Result<()> {
+        let body = CallbackBody {
+            ticket_id: &status.ticket_id,
+            state: status.state.to_string(),
+            head: status
+                .pushrebase_outcome
+                .as_ref()
+                .map(|outcome| convert_hex_to_str(&outcome.head)),
+            failure_reason: status.failure_reason.as_deref(),
+        };
+        let body = Bytes::from(serde_json::to_vec(&body).context("Failed to serialize callback")?);
+
+        let connector = HttpsConnector::<HttpConnector>::new()?;
+        let client = Client::builder().build(connector);
+        let mut backoff = CALLBACK_INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let req = Request::post(callback_url.clone())
+                .header("Content-Type", "application/json")
+                .body(body.clone().into())
+                .context("Failed to create callback request")?;
+            let res: Result<()> =
+                match tokio::time::timeout(CALLBACK_TIMEOUT, client.request(req)).await {
+                    Ok(Ok(response)) if response.status().is_success() => return Ok(()),
+                    Ok(Ok(response)) => Err(anyhow!("callback returned {}", response.status())),
+                    Ok(Err(err)) => Err(err.into()),
+                    Err(_) => Err(anyhow!("callback timed out")),
+                };
+            if attempt >= CALLBACK_MAX_ATTEMPTS {
+                return res.with_context(|| {
+                    format!(
+                        "Callback to {} failed after {} attempts",
+                        callback_url, attempt
+                    )
+                });
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    use hyper::service::make_service_fn;
+    use hyper::service::service_fn;
+    use hyper::Body;
+    use hyper::Response;
+    use hyper::Server;
+    use hyper::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+
+    fn landed() -> LandChangesetsResponse {
+        LandChangesetsResponse {
+            pushrebase_outcome: PushrebaseOutcome {
+                head: vec![0x11; 32],
+                rebased_changesets: vec![],
+                pushrebase_distance: 1,
+                retry_num: 0,
+                old_bookmark_value: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_ticket_lifecycle() {
+        let tickets = LandTickets::new();
+        let landed_id = tickets.create();
+        let failed_id = tickets.create();
+        assert_ne!(landed_id, failed_id);
+        assert!(tickets.get("unknown").is_none());
+
+        let state = |ticket_id: &str| tickets.get(ticket_id).unwrap().state;
+        assert_eq!(state(&landed_id), LandTicketState::PENDING);
+        tickets.set_in_progress(&landed_id);
+        assert_eq!(state(&landed_id), LandTicketState::IN_PROGRESS);
+        assert_eq!(state(&failed_id), LandTicketState::PENDING);
+
+        let status = tickets.finish(&landed_id, Ok(landed()));
+        assert_eq!(status.state, LandTicketState::LANDED);
+        assert_eq!(status.pushrebase_outcome, Some(landed().pushrebase_outcome));
+        assert_eq!(tickets.get(&landed_id), Some(status));
+
+        let status = tickets.finish(
+            &failed_id,
+            Err(LandChangesetsError::from(anyhow!("bookmark moved"))),
+        );
+        assert_eq!(status.state, LandTicketState::FAILED);
+        assert!(status.pushrebase_outcome.is_none());
+        assert!(status.failure_reason.unwrap().contains("bookmark moved"));
+        assert_eq!(state(&failed_id), LandTicketState::FAILED);
+    }
+
+    #[test]
+    fn test_validate_callback_url() {
+        let callbacks = CallbackNotifier::new(vec!["land.example.com".to_string()]);
+        assert!(callbacks.validate("https://land.example.com/done").is_ok());
+        assert!(
+            callbacks
+                .validate("http://LAND.example.com:8080/done")
+                .is_ok()
+        );
+        assert!(
+            callbacks
+                .validate("https://other.example.com/done")
+                .is_err()
+        );
+        assert!(callbacks.validate("ftp://land.example.com/done").is_err());
+        assert!(callbacks.validate("/done").is_err());
+        assert!(callbacks.validate("not a url").is_err());
+
+        // Without any allowed hosts, callbacks are refused.
+        assert!(
+            CallbackNotifier::default()
+                .validate("https://land.example.com/done")
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notify_callback() -> Result<()> {
+        // The first request fails, so that the callback is retried.
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let make_service = make_service_fn({
+            let received = received.clone();
+            move |_| {
+                let received = received.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| {
+                        let received = received.clone();
+                        async move {
+                            let body = hyper::body::to_bytes(req.into_body()).await?;
+                            let mut received = received.lock();
+                            received
+                                .push(serde_json::from_slice::<serde_json::Value>(&body).unwrap());
+                            let status = if received.len() == 1 {
+                                StatusCode::SERVICE_UNAVAILABLE
+                            } else {
+                                StatusCode::OK
+                            };
+                            Ok::<_, hyper::Error>(
+                                Response::builder()
+                                    .status(status)
+                                    .body(Body::empty())
+                                    .unwrap(),
+                            )
+                        }
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let tickets = LandTickets::new();
+        let ticket_id = tickets.create();
+        let status = tickets.finish(&ticket_id, Ok(landed()));
+        let callbacks = CallbackNotifier::new(vec!["127.0.0.1".to_string()]);
+        let callback_url = callbacks.validate(&format!("http://{}/landed", addr))?;
+        callbacks.notify(&callback_url, &status).await?;
+
+        let expected = json!({
+            "ticket_id": ticket_id,
+            "state": "LANDED",
+            "head": "11".repeat(32),
+            "failure_reason": null,
+        });
+        assert_eq!(*received.lock(), vec![expected.clone(), expected]);
+        Ok(())
+    }
+}
@@ -38,7 +38,12 @@ pub fn decode_delta(buf: BytesMut) -> Result<Delta> {
         let start = buf.drain_i32();
         let end = buf.drain_i32();
         let new_len = buf.drain_i32();
-        // TODO: handle negative values for all the above
+        if start < 0 || end < 0 || new_len < 0 {
+            bail!(ErrorKind::InvalidDelta(format!(
+                "negative value in fragment header: start {}, end {}, new length {}",
+                start, end, new_len
+            )));
+        }
 
         let delta_len = (new_len as usize) + DELTA_HEADER_LEN;
         if remaining < delta_len {
@@ -119,6 +124,14 @@ mod test {
                 None => panic!("Unexpected error {:?}", err),
             },
         }
+
+        // start = 0, end = 0, new length = -1
+        let negative_len = BytesMut::from(&b"\0\0\0\0\0\0\0\0\xff\xff\xff\xff"[..]);
+        assert_matches!(
+            err_downcast!(decode_delta(negative_len).unwrap_err(), err: ErrorKind => err),
+            Ok(ErrorKind::InvalidDelta(ref msg))
+            if msg == "negative value in fragment header: start 0, end 0, new length -1"
+        );
     }
 
     quickcheck! {
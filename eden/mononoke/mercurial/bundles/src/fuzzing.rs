@@ -0,0 +1,31 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Entry points for fuzz targets into decoders that are otherwise internal
+//! to this crate.  Only built with `--cfg fuzzing`, which cargo-fuzz sets.
+
+use anyhow::Result;
+use bytes_old::BytesMut;
+
+use crate::wirepack::DataEntry;
+use crate::wirepack::DataEntryVersion;
+use crate::wirepack::HistoryEntry;
+use crate::wirepack::Kind;
+
+pub fn decode_wirepack_data_entry(
+    buf: &mut BytesMut,
+    version: DataEntryVersion,
+) -> Result<Option<DataEntry>> {
+    DataEntry::decode(buf, version)
+}
+
+pub fn decode_wirepack_history_entry(
+    buf: &mut BytesMut,
+    kind: Kind,
+) -> Result<Option<HistoryEntry>> {
+    HistoryEntry::decode(buf, kind)
+}
@@ -11,6 +11,8 @@ pub mod capabilities;
 pub mod changegroup;
 mod chunk;
 mod delta;
+#[cfg(fuzzing)]
+pub mod fuzzing;
 pub mod infinitepush;
 pub mod obsmarkers;
 pub mod part_encode;
@@ -52,6 +54,7 @@ use futures_old::Stream as OldStream;
 
 pub use crate::bundle2_encode::Bundle2EncodeBuilder;
 pub use crate::part_header::PartHeader;
+pub use crate::part_header::PartHeaderBuilder;
 pub use crate::part_header::PartHeaderInner;
 pub use crate::part_header::PartHeaderType;
 pub use crate::part_header::PartId;
@@ -80,6 +83,8 @@ pub enum Bundle2Item<'a> {
     ),
     Pushkey(PartHeader, BoxFuture<'a, Result<()>>),
     Pushvars(PartHeader, BoxFuture<'a, Result<()>>),
+    /// A custom part, with its undecoded payload.
+    Custom(PartHeader, BoxStream<'a, Result<bytes_old::Bytes>>),
 }
 
 impl<'a> Bundle2Item<'a> {
@@ -122,6 +127,7 @@ impl<'a> fmt::Debug for Bundle2Item<'a> {
             Replycaps(ref header, _) => write!(f, "Bundle2Item::Replycaps({:?}, ...)", header),
             Pushkey(ref header, _) => write!(f, "Bundle2Item::Pushkey({:?}, ...)", header),
             Pushvars(ref header, _) => write!(f, "Bundle2Item::Pushvars({:?}, ...)", header),
+            Custom(ref header, _) => write!(f, "Bundle2Item::Custom({:?}, ...)", header),
         }
     }
 }
@@ -157,6 +163,7 @@ impl<'a> From<OldBundle2Item> for Bundle2Item<'a> {
             Replycaps(header, future) => Bundle2Item::Replycaps(header, future.compat().boxed()),
             Pushkey(header, future) => Bundle2Item::Pushkey(header, future.compat().boxed()),
             Pushvars(header, future) => Bundle2Item::Pushvars(header, future.compat().boxed()),
+            Custom(header, stream) => Bundle2Item::Custom(header, stream.compat().boxed()),
         }
     }
 }
@@ -181,6 +188,7 @@ pub(crate) enum OldBundle2Item {
     Replycaps(PartHeader, OldBoxFuture<capabilities::Capabilities, Error>),
     Pushkey(PartHeader, OldBoxFuture<(), Error>),
     Pushvars(PartHeader, OldBoxFuture<(), Error>),
+    Custom(PartHeader, OldBoxStream<bytes_old::Bytes, Error>),
 }
 
 /// Given bundle parts, returns a stream of Bytes that represent an encoded bundle with these parts
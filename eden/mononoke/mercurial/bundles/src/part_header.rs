@@ -24,7 +24,7 @@ use crate::utils::BytesExt;
 
 pub type PartId = u32;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum PartHeaderType {
     /// Responsible for sending Changesets and Filelogs during a push. In Mercurial it also sends
     /// flat Manifests, but with Mononoke we support only TreeManifests.
@@ -53,6 +53,9 @@ pub enum PartHeaderType {
     B2xInfinitepushBookmarks,
     /// Contains mutation info for infinitepush commits
     B2xInfinitepushMutation,
+    /// Contains hints about which of the client's caches are out of date
+    /// after a pull, so that the client doesn't have to invalidate them all.
+    B2xInvalidationHints,
     /// Pushrebase part with changegroup
     B2xRebase,
     /// Pushrebase part that contains packs
@@ -84,36 +87,43 @@ pub enum PartHeaderType {
     Obsmarkers,
     // ReplyObsmarkers,         // TODO Do we want to support this?
     // HgtagsFnodes,            // TODO Do we want to support this?
+    /// A part that isn't part of the Mercurial protocol, such as parts sent
+    /// by internal tooling.  Its payload is passed through undecoded, so
+    /// that it can be handled by whoever consumes the bundle.
+    Custom(String),
 }
 
 impl PartHeaderType {
-    fn decode(data: &str) -> Result<Self> {
+    /// The part type with the given name.  Names are case-insensitive, and
+    /// names that aren't known are custom part types.
+    pub fn from_name(name: &str) -> Self {
         use self::PartHeaderType::*;
-        match data.to_ascii_lowercase().as_str() {
-            "changegroup" => Ok(Changegroup),
-            "reply:changegroup" => Ok(ReplyChangegroup),
-            "replycaps" => Ok(Replycaps),
-            "listkeys" => Ok(Listkeys),
-            "b2x:treegroup2" => Ok(B2xTreegroup2),
-            "b2x:infinitepush" => Ok(B2xInfinitepush),
-            "b2x:infinitepushscratchbookmarks" => Ok(B2xInfinitepushBookmarks),
-            "b2x:infinitepushmutation" => Ok(B2xInfinitepushMutation),
-            "b2x:commonheads" => Ok(B2xCommonHeads),
-            "b2x:rebase" => Ok(B2xRebase),
-            "b2x:rebasepackpart" => Ok(B2xRebasePack),
-            "check:heads" => Ok(CheckHeads),
-            "pushkey" => Ok(Pushkey),
-            "reply:pushkey" => Ok(ReplyPushkey),
-            "pushvars" => Ok(Pushvars),
-            "phase-heads" => Ok(PhaseHeads),
-            "obsmarkers" => Ok(Obsmarkers),
-            bad => bail!("unknown header type {}", bad),
+        match name.to_ascii_lowercase().as_str() {
+            "changegroup" => Changegroup,
+            "reply:changegroup" => ReplyChangegroup,
+            "replycaps" => Replycaps,
+            "listkeys" => Listkeys,
+            "b2x:treegroup2" => B2xTreegroup2,
+            "b2x:infinitepush" => B2xInfinitepush,
+            "b2x:infinitepushscratchbookmarks" => B2xInfinitepushBookmarks,
+            "b2x:infinitepushmutation" => B2xInfinitepushMutation,
+            "b2x:invalidationhints" => B2xInvalidationHints,
+            "b2x:commonheads" => B2xCommonHeads,
+            "b2x:rebase" => B2xRebase,
+            "b2x:rebasepackpart" => B2xRebasePack,
+            "check:heads" => CheckHeads,
+            "pushkey" => Pushkey,
+            "reply:pushkey" => ReplyPushkey,
+            "pushvars" => Pushvars,
+            "phase-heads" => PhaseHeads,
+            "obsmarkers" => Obsmarkers,
+            custom => Custom(custom.to_string()),
         }
     }
 
-    fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &str {
         use self::PartHeaderType::*;
-        match *self {
+        match self {
             Changegroup => "changegroup",
             ReplyChangegroup => "reply:changegroup",
             Replycaps => "replycaps",
@@ -123,6 +133,7 @@ impl PartHeaderType {
             B2xInfinitepush => "b2x:infinitepush",
             B2xInfinitepushBookmarks => "b2x:infinitepushscratchbookmarks",
             B2xInfinitepushMutation => "b2x:infinitepushmutation",
+            B2xInvalidationHints => "b2x:invalidationhints",
             B2xRebase => "b2x:rebase",
             B2xRebasePack => "b2x:rebasepackpart",
             CheckHeads => "check:heads",
@@ -131,6 +142,7 @@ impl PartHeaderType {
             ReplyPushkey => "reply:pushkey",
             PhaseHeads => "phase-heads",
             Obsmarkers => "obsmarkers",
+            Custom(name) => name.as_str(),
         }
     }
 }
@@ -263,7 +275,7 @@ pub fn decode(mut header_bytes: Bytes) -> Result<PartHeader> {
     let part_type_encoded = header_bytes
         .drain_str(type_size)
         .with_context(|| ErrorKind::Bundle2Decode("invalid part type".into()))?;
-    let part_type = PartHeaderType::decode(&part_type_encoded)?;
+    let part_type = PartHeaderType::from_name(&part_type_encoded);
 
     let mandatory = part_type_encoded.chars().any(|c| c.is_ascii_uppercase());
 
@@ -455,6 +467,7 @@ impl Arbitrary for PartHeaderType {
             Listkeys,
             B2xTreegroup2,
             CheckHeads,
+            Custom("x:custom".to_string()),
         ])
         .expect("empty choice provided")
         .clone()
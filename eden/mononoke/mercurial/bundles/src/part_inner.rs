@@ -82,12 +82,15 @@ pub fn validate_header(header: PartHeader) -> Result<Option<PartHeader>> {
                 .collect();
             if !unknown_params.is_empty() {
                 bail!(ErrorKind::BundleUnknownPartParams(
-                    *header.part_type(),
+                    header.part_type().clone(),
                     unknown_params,
                 ));
             }
             Ok(Some(header))
         }
+        // Custom parts are passed through to the consumer of the bundle,
+        // which decides what to do with them.
+        None if matches!(header.part_type(), PartHeaderType::Custom(_)) => Ok(Some(header)),
         None => {
             if header.mandatory() {
                 bail!(ErrorKind::BundleUnknownPart(header));
@@ -150,7 +153,7 @@ pub(crate) fn inner_stream<R: AsyncRead + BufRead + 'static + Send>(
         .map(OuterFrame::get_payload as fn(OuterFrame) -> Bytes);
     let (wrapped_stream, remainder) = wrapped_stream.return_remainder();
 
-    let bundle2item = match *header.part_type() {
+    let bundle2item = match header.part_type() {
         PartHeaderType::Changegroup => {
             let cg2_stream = wrapped_stream.decode(get_cg_unpacker(
                 logger.new(o!("stream" => "changegroup")),
@@ -229,6 +232,7 @@ pub(crate) fn inner_stream<R: AsyncRead + BufRead + 'static + Send>(
             let empty = wrapped_stream.decode(EmptyUnpacker).for_each(|_| Ok(()));
             OldBundle2Item::Pushvars(header, Box::new(empty))
         }
+        PartHeaderType::Custom(_) => OldBundle2Item::Custom(header, wrapped_stream.boxify()),
         _ => panic!("TODO: make this an error"),
     };
 
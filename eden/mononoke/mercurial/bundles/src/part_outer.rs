@@ -184,7 +184,7 @@ impl OuterDecoder {
                 match part_header {
                     None => (Ok(Some(OuterFrame::Discard)), OuterState::DiscardPayload),
                     Some(header) => {
-                        let part_type = *header.part_type();
+                        let part_type = header.part_type().clone();
                         let part_id = header.part_id();
                         (
                             Ok(Some(OuterFrame::Header(header))),
@@ -85,6 +85,26 @@ where
     Ok(builder)
 }
 
+/// Build an advisory part with hints about which of the client's caches
+/// should be invalidated.  Each hint is a class of cached objects and a
+/// value that tells the client which of those objects are out of date.
+pub fn invalidation_hints_part<C, V>(hints: Vec<(C, V)>) -> Result<PartEncodeBuilder>
+where
+    C: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut builder = PartEncodeBuilder::advisory(PartHeaderType::B2xInvalidationHints)?;
+    let mut payload = Vec::with_capacity(256);
+    for (class, value) in hints {
+        payload.extend_from_slice(class.as_ref());
+        payload.push(b'\t');
+        payload.extend_from_slice(value.as_ref());
+        payload.push(b'\n');
+    }
+    builder.set_data_fixed(Chunk::new(payload)?);
+    Ok(builder)
+}
+
 pub fn phases_part<S>(ctx: CoreContext, phases_entries: S) -> Result<PartEncodeBuilder>
 where
     S: Stream<Item = (HgChangesetId, Phase), Error = Error> + Send + 'static,
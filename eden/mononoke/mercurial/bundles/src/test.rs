@@ -275,6 +275,47 @@ fn unknown_part(ct: Option<CompressorType>) {
                     if header.part_type() == &PartHeaderType::Listkeys && header.mandatory());
 }
 
+#[test]
+fn test_custom_part() {
+    let cursor = Cursor::new(Vec::with_capacity(32 * 1024));
+    let mut builder = Bundle2EncodeBuilder::new(cursor);
+    builder.set_compressor_type(None);
+
+    let mut custom_part =
+        PartEncodeBuilder::mandatory(PartHeaderType::from_name("X:Metadata")).unwrap();
+    custom_part.add_mparam("key", "value").unwrap();
+    custom_part.set_data_bytes(&b"custom payload"[..]).unwrap();
+    builder.add_part(custom_part);
+
+    let runtime = Runtime::new().unwrap();
+    let mut buf = runtime.block_on(builder.build().compat()).unwrap();
+    buf.set_position(0);
+
+    let stream = parse_stream_start(&runtime, buf, Some("UN")).unwrap();
+    let (res, stream) = runtime.old_next_stream(stream);
+    let payload = match res.unwrap().into_next().unwrap() {
+        Bundle2Item::Custom(header, payload) => {
+            assert_eq!(
+                header.part_type(),
+                &PartHeaderType::Custom("x:metadata".to_string())
+            );
+            assert!(header.mandatory());
+            assert_eq!(header.mparams().get("key").unwrap().as_ref(), &b"value"[..]);
+            payload
+        }
+        bad => panic!("Unexpected bundle2 item: {:?}", bad),
+    };
+    let payload = runtime
+        .block_on(payload.try_collect::<Vec<_>>())
+        .unwrap()
+        .concat();
+    assert_eq!(payload, b"custom payload");
+
+    let (res, stream) = runtime.old_next_stream(stream);
+    assert_matches!(res, Some(StreamEvent::Done(_)));
+    assert!(stream.app_errors().is_empty());
+}
+
 fn parse_bundle(
     input: &[u8],
     compression: Option<&str>,
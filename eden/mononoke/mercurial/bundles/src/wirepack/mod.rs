@@ -268,12 +268,13 @@ impl DataEntry {
 
         // First, check that we have enough data to proceed.
         let delta_len = BigEndian::read_u64(&buf[DATA_DELTA_OFFSET..DATA_HEADER_SIZE]) as usize;
+        // Compare against the remaining length rather than adding to the
+        // header size, as a corrupt length could overflow.
+        if buf.len() - DATA_HEADER_SIZE < delta_len {
+            return Ok(None);
+        }
         match version {
-            DataEntryVersion::V1 => {
-                if buf.len() < DATA_HEADER_SIZE + delta_len {
-                    return Ok(None);
-                }
-            }
+            DataEntryVersion::V1 => {}
             DataEntryVersion::V2 => {
                 let meta_offset = DATA_HEADER_SIZE + delta_len;
                 let meta_header_size = 4; // Metadata header is a u32.
@@ -545,5 +546,32 @@ mod test {
             assert_eq!(encoded_bytes.len(), 0);
             true
         }
+
+        fn test_data_decode_arbitrary(data: Vec<u8>, v2: bool) -> bool {
+            let version = if v2 { DataEntryVersion::V2 } else { DataEntryVersion::V1 };
+            let mut buf = BytesMut::from(data);
+            let len = buf.len();
+            match DataEntry::decode(&mut buf, version) {
+                // Incomplete entries must leave the buffer untouched.
+                Ok(None) => buf.len() == len,
+                Ok(Some(_)) | Err(_) => true,
+            }
+        }
+
+        fn test_data_decode_arbitrary_delta(fulltext: bool, delta: Vec<u8>) -> bool {
+            // Arbitrary data with a valid header, so that the delta decoder
+            // sees it.
+            let mut data = vec![];
+            data.put_slice(AS_HASH.as_ref());
+            data.put_slice(if fulltext { NULL_HASH.as_ref() } else { BS_HASH.as_ref() });
+            data.put_u64_be(delta.len() as u64);
+            data.put_slice(&delta);
+            let mut buf = BytesMut::from(data);
+            match DataEntry::decode(&mut buf, DataEntryVersion::V1) {
+                Ok(Some(_)) => buf.is_empty(),
+                Ok(None) => false,
+                Err(_) => !fulltext,
+            }
+        }
     }
 }
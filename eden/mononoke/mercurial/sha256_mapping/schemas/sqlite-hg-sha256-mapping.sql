@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS hg_sha256_mapping (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  repo_id INTEGER NOT NULL,
+  hg_id BINARY(20) NOT NULL,
+  sha256 BINARY(32) NOT NULL,
+  UNIQUE (repo_id, hg_id),
+  UNIQUE (repo_id, sha256)
+);
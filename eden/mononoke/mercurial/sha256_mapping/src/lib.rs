@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Mapping between the SHA-1 and SHA-256 ids of Mercurial nodes.
+//!
+//! The SHA-256 id of a node covers the SHA-256 ids of its parents, so it
+//! can't be computed from the node alone, and the SHA-1 id can't be found
+//! from the SHA-256 id at all.  Nodes that should be addressable by SHA-256
+//! are therefore recorded here when they are stored.
+
+mod sql;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use anyhow::bail;
+use anyhow::Error;
+use async_trait::async_trait;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use context::CoreContext;
+use mercurial_types::HgChangesetId;
+use mercurial_types::HgDualNodeHash;
+use mercurial_types::HgNodeHash;
+use mercurial_types::HgNodeHash256;
+use mononoke_types::RepositoryId;
+use tunables::tunables;
+
+pub use crate::sql::SqlHgSha256Mapping;
+pub use crate::sql::SqlHgSha256MappingBuilder;
+
+pub enum Sha1sOrSha256s {
+    Sha1(Vec<HgNodeHash>),
+    Sha256(Vec<HgNodeHash256>),
+}
+
+impl Sha1sOrSha256s {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Sha1sOrSha256s::Sha1(v) => v.is_empty(),
+            Sha1sOrSha256s::Sha256(v) => v.is_empty(),
+        }
+    }
+}
+
+impl From<Vec<HgNodeHash>> for Sha1sOrSha256s {
+    fn from(hashes: Vec<HgNodeHash>) -> Self {
+        Sha1sOrSha256s::Sha1(hashes)
+    }
+}
+
+impl From<Vec<HgNodeHash256>> for Sha1sOrSha256s {
+    fn from(hashes: Vec<HgNodeHash256>) -> Self {
+        Sha1sOrSha256s::Sha256(hashes)
+    }
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait HgSha256Mapping: Send + Sync {
+    fn repo_id(&self) -> RepositoryId;
+
+    async fn add(&self, ctx: &CoreContext, entries: &[HgDualNodeHash]) -> Result<(), Error>;
+
+    async fn get(
+        &self,
+        ctx: &CoreContext,
+        hashes: Sha1sOrSha256s,
+    ) -> Result<Vec<HgDualNodeHash>, Error>;
+
+    async fn get_sha1_from_sha256(
+        &self,
+        ctx: &CoreContext,
+        sha256: HgNodeHash256,
+    ) -> Result<Option<HgNodeHash>, Error> {
+        let result = self.get(ctx, Sha1sOrSha256s::Sha256(vec![sha256])).await?;
+        Ok(result.into_iter().next().map(|entry| entry.sha1))
+    }
+
+    async fn get_sha256_from_sha1(
+        &self,
+        ctx: &CoreContext,
+        sha1: HgNodeHash,
+    ) -> Result<Option<HgNodeHash256>, Error> {
+        let result = self.get(ctx, Sha1sOrSha256s::Sha1(vec![sha1])).await?;
+        Ok(result.into_iter().next().map(|entry| entry.sha256))
+    }
+}
+
+/// Maximum number of ancestors of a changeset whose SHA-256 ids are
+/// computed together with its own.  Ancestors that are stored at the same
+/// time, e.g. in the same derivation batch, may not have been recorded yet.
+const MAX_UNRECORDED_ANCESTORS: usize = 1000;
+
+/// Record the SHA-256 id of an hg changeset when it is stored, if SHA-256
+/// ids are enabled.  The id covers the SHA-256 ids of the changeset's
+/// parents, so ancestors that haven't been recorded yet are recorded too.
+/// If there are too many of them, e.g. because the history was stored before
+/// SHA-256 ids were enabled and hasn't been backfilled, nothing is recorded.
+pub async fn record_hg_changeset<B: Blobstore>(
+    ctx: &CoreContext,
+    mapping: &dyn HgSha256Mapping,
+    blobstore: &B,
+    hg_cs_id: HgChangesetId,
+) -> Result<Option<HgNodeHash256>, Error> {
+    if !tunables().get_hg_sha256_nodes_enabled() {
+        return Ok(None);
+    }
+
+    let mut sha256s = HashMap::new();
+    let mut unrecorded = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = vec![hg_cs_id.into_nodehash()];
+    while !queue.is_empty() {
+        let batch = queue
+            .drain(..)
+            .filter(|node| seen.insert(*node))
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        sha256s.extend(
+            mapping
+                .get(ctx, batch.clone().into())
+                .await?
+                .into_iter()
+                .map(|node| (node.sha1, node.sha256)),
+        );
+        for node in batch {
+            if sha256s.contains_key(&node) {
+                continue;
+            }
+            if unrecorded.len() >= MAX_UNRECORDED_ANCESTORS {
+                return Ok(None);
+            }
+            let hg_cs = HgChangesetId::new(node).load(ctx, blobstore).await?;
+            queue.extend(hg_cs.p1().into_iter().chain(hg_cs.p2()));
+            unrecorded.push(hg_cs);
+        }
+    }
+
+    // Compute the ids of the unrecorded changesets once the ids of their
+    // parents are known.
+    let mut nodes = Vec::new();
+    while !unrecorded.is_empty() {
+        let remaining = unrecorded.len();
+        let mut pending = Vec::new();
+        for hg_cs in unrecorded {
+            let sha256 = |parent: Option<HgNodeHash>| match parent {
+                Some(parent) => sha256s.get(&parent).copied().map(Some),
+                None => Some(None),
+            };
+            match (sha256(hg_cs.p1()), sha256(hg_cs.p2())) {
+                (Some(p1), Some(p2)) => {
+                    let node = hg_cs.dual_node_id(p1, p2)?;
+                    sha256s.insert(node.sha1, node.sha256);
+                    nodes.push(node);
+                }
+                _ => pending.push(hg_cs),
+            }
+        }
+        if pending.len() == remaining {
+            bail!("Cycle in the history of hg changeset {}", hg_cs_id);
+        }
+        unrecorded = pending;
+    }
+
+    if !nodes.is_empty() {
+        mapping.add(ctx, &nodes).await?;
+    }
+    Ok(sha256s.get(&hg_cs_id.into_nodehash()).copied())
+}
@@ -0,0 +1,183 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+
+use ::sql::Connection;
+use ::sql_ext::mononoke_queries;
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mercurial_types::HgDualNodeHash;
+use mercurial_types::HgNodeHash;
+use mercurial_types::HgNodeHash256;
+use mononoke_types::RepositoryId;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::SqlConnections;
+
+use super::HgSha256Mapping;
+use super::Sha1sOrSha256s;
+
+mononoke_queries! {
+    write InsertMappings(values: (
+        repo_id: RepositoryId,
+        hg_id: HgNodeHash,
+        sha256: HgNodeHash256,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO hg_sha256_mapping (repo_id, hg_id, sha256) VALUES {values}"
+    }
+
+    read SelectMappingBySha1(
+        repo_id: RepositoryId,
+        >list hg_id: HgNodeHash
+    ) -> (HgNodeHash, HgNodeHash256) {
+        "SELECT hg_id, sha256
+         FROM hg_sha256_mapping
+         WHERE repo_id = {repo_id} AND hg_id IN {hg_id}"
+    }
+
+    read SelectMappingBySha256(
+        repo_id: RepositoryId,
+        >list sha256: HgNodeHash256
+    ) -> (HgNodeHash, HgNodeHash256) {
+        "SELECT hg_id, sha256
+         FROM hg_sha256_mapping
+         WHERE repo_id = {repo_id} AND sha256 IN {sha256}"
+    }
+}
+
+pub struct SqlHgSha256Mapping {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+#[derive(Clone)]
+pub struct SqlHgSha256MappingBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlHgSha256MappingBuilder {
+    const LABEL: &'static str = "hg_sha256_mapping";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-hg-sha256-mapping.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlHgSha256MappingBuilder {}
+
+impl SqlHgSha256MappingBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlHgSha256Mapping {
+        SqlHgSha256Mapping {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+#[async_trait]
+impl HgSha256Mapping for SqlHgSha256Mapping {
+    fn repo_id(&self) -> RepositoryId {
+        self.repo_id
+    }
+
+    async fn add(&self, ctx: &CoreContext, entries: &[HgDualNodeHash]) -> Result<(), Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| (&self.repo_id, &entry.sha1, &entry.sha256))
+            .collect();
+
+        InsertMappings::query(&self.connections.write_connection, &entries[..]).await?;
+
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        ctx: &CoreContext,
+        hashes: Sha1sOrSha256s,
+    ) -> Result<Vec<HgDualNodeHash>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let mut mappings =
+            select_mapping(&self.connections.read_connection, self.repo_id, &hashes).await?;
+
+        let left_to_fetch = filter_fetched_hashes(hashes, &mappings[..]);
+
+        if left_to_fetch.is_empty() {
+            return Ok(mappings);
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+
+        let mut master_mappings = select_mapping(
+            &self.connections.read_master_connection,
+            self.repo_id,
+            &left_to_fetch,
+        )
+        .await?;
+        mappings.append(&mut master_mappings);
+        Ok(mappings)
+    }
+}
+
+fn filter_fetched_hashes(hashes: Sha1sOrSha256s, mappings: &[HgDualNodeHash]) -> Sha1sOrSha256s {
+    match hashes {
+        Sha1sOrSha256s::Sha1(sha1s) => {
+            let fetched: HashSet<_> = mappings.iter().map(|m| m.sha1).collect();
+            Sha1sOrSha256s::Sha1(
+                sha1s
+                    .into_iter()
+                    .filter(|sha1| !fetched.contains(sha1))
+                    .collect(),
+            )
+        }
+        Sha1sOrSha256s::Sha256(sha256s) => {
+            let fetched: HashSet<_> = mappings.iter().map(|m| m.sha256).collect();
+            Sha1sOrSha256s::Sha256(
+                sha256s
+                    .into_iter()
+                    .filter(|sha256| !fetched.contains(sha256))
+                    .collect(),
+            )
+        }
+    }
+}
+
+async fn select_mapping(
+    connection: &Connection,
+    repo_id: RepositoryId,
+    hashes: &Sha1sOrSha256s,
+) -> Result<Vec<HgDualNodeHash>, Error> {
+    if hashes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let rows = match hashes {
+        Sha1sOrSha256s::Sha1(sha1s) => {
+            SelectMappingBySha1::query(connection, &repo_id, &sha1s[..]).await?
+        }
+        Sha1sOrSha256s::Sha256(sha256s) => {
+            SelectMappingBySha256::query(connection, &repo_id, &sha256s[..]).await?
+        }
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(|(sha1, sha256)| HgDualNodeHash { sha1, sha256 })
+        .collect())
+}
@@ -0,0 +1,79 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use hg_sha256_mapping::HgSha256Mapping;
+use hg_sha256_mapping::Sha1sOrSha256s;
+use hg_sha256_mapping::SqlHgSha256MappingBuilder;
+use mercurial_types::HgDualNodeHash;
+use mercurial_types::HgNodeHash256;
+use mercurial_types_mocks::nodehash::ONES_HASH;
+use mercurial_types_mocks::nodehash::TWOS_HASH;
+use mononoke_types::hash::Sha256;
+use mononoke_types_mocks::repo::REPO_ONE;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use sql_construct::SqlConstruct;
+
+const ONES_SHA256: HgNodeHash256 = HgNodeHash256::new(Sha256::from_byte_array([0x11; 32]));
+const TWOS_SHA256: HgNodeHash256 = HgNodeHash256::new(Sha256::from_byte_array([0x22; 32]));
+
+#[fbinit::test]
+async fn test_add_and_get(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mapping = SqlHgSha256MappingBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    let entry = HgDualNodeHash {
+        sha1: ONES_HASH,
+        sha256: ONES_SHA256,
+    };
+    mapping.add(&ctx, &[entry]).await?;
+    // Adding the same entry again is a no-op.
+    mapping.add(&ctx, &[entry]).await?;
+
+    let result = mapping
+        .get(&ctx, Sha1sOrSha256s::Sha1(vec![ONES_HASH, TWOS_HASH]))
+        .await?;
+    assert_eq!(result, vec![entry]);
+    assert_eq!(
+        mapping.get_sha1_from_sha256(&ctx, ONES_SHA256).await?,
+        Some(ONES_HASH)
+    );
+    assert_eq!(
+        mapping.get_sha256_from_sha1(&ctx, ONES_HASH).await?,
+        Some(ONES_SHA256)
+    );
+    assert_eq!(mapping.get_sha1_from_sha256(&ctx, TWOS_SHA256).await?, None);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let builder = SqlHgSha256MappingBuilder::with_sqlite_in_memory()?;
+    let zero = builder.clone().build(REPO_ZERO);
+    let one = builder.build(REPO_ONE);
+
+    zero.add(
+        &ctx,
+        &[HgDualNodeHash {
+            sha1: TWOS_HASH,
+            sha256: TWOS_SHA256,
+        }],
+    )
+    .await?;
+
+    assert_eq!(
+        zero.get_sha1_from_sha256(&ctx, TWOS_SHA256).await?,
+        Some(TWOS_HASH)
+    );
+    assert_eq!(one.get_sha1_from_sha256(&ctx, TWOS_SHA256).await?, None);
+
+    Ok(())
+}
@@ -22,6 +22,17 @@ struct HgChangesetEnvelope {
 // Manifest contents are expected to generally be small, so they're stored
 // inline in the envelope. There's also no real dedup possible between native
 // Mononoke data structures and these ones.
+//
+// The exception is manifests of very wide directories, which are split into
+// shards of consecutive entries. Shard boundaries depend only on entry names,
+// so changing an entry only rewrites the shard that contains it.
+struct HgManifestShard {
+  // Name of the first entry in the shard.
+  1: required binary first_name;
+  2: required mononoke_types_thrift.Blake2 id;
+  3: required i64 size;
+} (rust.exhaustive)
+
 struct HgManifestEnvelope {
   1: required HgNodeHash node_id;
   2: optional HgNodeHash p1;
@@ -33,6 +44,9 @@ struct HgManifestEnvelope {
   4: required HgNodeHash computed_node_id;
   // These contents are exactly as they would be serialized by Mercurial.
   5: optional binary contents;
+  // Set instead of contents for sharded manifests. The contents are the
+  // concatenation of the shards, in order.
+  6: optional list<HgManifestShard> shards;
 } (rust.exhaustive)
 
 struct HgFileEnvelope {
@@ -8,10 +8,13 @@
 use bytes::Bytes;
 use futures_old::Future;
 use futures_old::Stream;
+use mononoke_types::hash::Sha256;
 use quickcheck::Arbitrary;
 use quickcheck::Gen;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
+use sha2::Digest;
+use sha2::Sha256 as Sha256Hasher;
 /// Equivalent type from Mercurial's Rust code representing parents.
 use types::Parents as HgTypesParents;
 
@@ -19,6 +22,8 @@ use crate::blob::HgBlob;
 use crate::hash;
 use crate::hash::Context;
 use crate::nodehash::HgNodeHash;
+use crate::nodehash256::HgDualNodeHash;
+use crate::nodehash256::HgNodeHash256;
 
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 #[derive(Serialize, Deserialize)]
@@ -142,6 +147,18 @@ impl HgBlobNode {
     pub fn nodeid(&self) -> HgNodeHash {
         calculate_hg_node_id(self.as_blob().as_slice(), &self.parents)
     }
+
+    /// Both ids of this node, given the SHA-256 ids of its parents.
+    pub fn dual_nodeid(
+        &self,
+        p1_sha256: Option<HgNodeHash256>,
+        p2_sha256: Option<HgNodeHash256>,
+    ) -> HgDualNodeHash {
+        HgDualNodeHash {
+            sha1: self.nodeid(),
+            sha256: calculate_hg_node_id_sha256(self.as_blob().as_slice(), p1_sha256, p2_sha256),
+        }
+    }
 }
 
 fn hg_node_id_hash_context(parents: &HgParents) -> Context {
@@ -169,6 +186,34 @@ pub fn calculate_hg_node_id(data: &[u8], parents: &HgParents) -> HgNodeHash {
     HgNodeHash(ctxt.finish())
 }
 
+/// Compute the SHA-256 id of a node from the SHA-256 ids of its parents and
+/// in-place data.
+///
+/// The data is hashed in the same way as for the SHA-1 id, but the parents
+/// are hashed by their SHA-256 ids, so that the id of a node doesn't depend
+/// on SHA-1 anywhere in its history.
+pub fn calculate_hg_node_id_sha256(
+    data: &[u8],
+    p1: Option<HgNodeHash256>,
+    p2: Option<HgNodeHash256>,
+) -> HgNodeHash256 {
+    let null = HgNodeHash256::new(Sha256::from_byte_array([0; 32]));
+
+    let (h1, h2) = match (p1, p2) {
+        (None, None) => (null, null),
+        (Some(p), None) | (None, Some(p)) => (null, p),
+        (Some(p1), Some(p2)) if p1 == p2 => (null, p1),
+        (Some(p1), Some(p2)) if p1 > p2 => (p2, p1),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+
+    let mut hasher = Sha256Hasher::new();
+    hasher.update(h1.as_bytes());
+    hasher.update(h2.as_bytes());
+    hasher.update(data);
+    HgNodeHash256::new(Sha256::from_byte_array(hasher.finalize().into()))
+}
+
 /// Compute a Hg Node ID from parents and a stream of data.
 pub fn calculate_hg_node_id_stream<S, E>(
     stream: S,
@@ -252,6 +297,49 @@ mod test {
         assert_eq!(node1, node2);
     }
 
+    #[test]
+    fn test_node_sha256() {
+        let root = |data: &'static [u8]| {
+            HgBlobNode::new(HgBlob::from(Bytes::from(data)), None, None).dual_nodeid(None, None)
+        };
+        let p1 = root(b"foo1");
+        let p2 = root(b"foo2");
+
+        let dual1 = HgBlobNode::new(
+            HgBlob::from(Bytes::from(&b"bar"[..])),
+            Some(p1.sha1),
+            Some(p2.sha1),
+        )
+        .dual_nodeid(Some(p1.sha256), Some(p2.sha256));
+        let dual2 = HgBlobNode::new(
+            HgBlob::from(Bytes::from(&b"bar"[..])),
+            Some(p2.sha1),
+            Some(p1.sha1),
+        )
+        .dual_nodeid(Some(p2.sha256), Some(p1.sha256));
+        assert_eq!(dual1, dual2);
+
+        let other = HgBlobNode::new(
+            HgBlob::from(Bytes::from(&b"baz"[..])),
+            Some(p1.sha1),
+            Some(p2.sha1),
+        )
+        .dual_nodeid(Some(p1.sha256), Some(p2.sha256));
+        assert_ne!(dual1.sha256, other.sha256);
+
+        // The SHA-256 id covers the SHA-256 ids of the parents, not their
+        // SHA-1 ids.
+        let other_parent = root(b"foo3");
+        let rehashed = HgBlobNode::new(
+            HgBlob::from(Bytes::from(&b"bar"[..])),
+            Some(p1.sha1),
+            Some(p2.sha1),
+        )
+        .dual_nodeid(Some(p1.sha256), Some(other_parent.sha256));
+        assert_eq!(dual1.sha1, rehashed.sha1);
+        assert_ne!(dual1.sha256, rehashed.sha256);
+    }
+
     quickcheck! {
         // Verify that the two Node Id computation implementations (in place and streaming) are
         // consistent.
@@ -24,11 +24,14 @@ use mononoke_types::DateTime;
 use super::revlog::serialize_extras;
 use super::revlog::Extra;
 use super::revlog::RevlogChangeset;
+use crate::calculate_hg_node_id_sha256;
 use crate::nodehash::HgChangesetId;
 use crate::nodehash::HgManifestId;
 use crate::HgBlobNode;
 use crate::HgChangesetEnvelopeMut;
+use crate::HgDualNodeHash;
 use crate::HgNodeHash;
+use crate::HgNodeHash256;
 use crate::HgParents;
 use crate::MPath;
 
@@ -212,6 +215,22 @@ impl HgBlobChangeset {
         }))
     }
 
+    /// Both ids of this changeset, given the SHA-256 ids of its parents.
+    /// The SHA-256 id is computed from the serialized changeset, in the same
+    /// way as the SHA-1 id.
+    pub fn dual_node_id(
+        &self,
+        p1_sha256: Option<HgNodeHash256>,
+        p2_sha256: Option<HgNodeHash256>,
+    ) -> Result<HgDualNodeHash> {
+        let mut v = Vec::new();
+        self.content.generate(&mut v)?;
+        Ok(HgDualNodeHash {
+            sha1: self.changesetid.into_nodehash(),
+            sha256: calculate_hg_node_id_sha256(&v, p1_sha256, p2_sha256),
+        })
+    }
+
     pub async fn save<'a, B: Blobstore>(
         &'a self,
         ctx: &'a CoreContext,
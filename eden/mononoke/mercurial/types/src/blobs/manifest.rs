@@ -24,6 +24,7 @@ use manifest::Manifest;
 use sorted_vector_map::SortedVectorMap;
 
 use super::errors::ErrorKind;
+use super::manifest_shards::decode_manifest_envelope;
 use crate::nodehash::HgNodeHash;
 use crate::nodehash::NULL_HASH;
 use crate::FileType;
@@ -129,12 +130,12 @@ pub async fn fetch_manifest_envelope_opt<B: Blobstore>(
         .get(ctx, &blobstore_key)
         .await
         .context("While fetching manifest envelope blob")?;
-    (|| {
+    async {
         let blobstore_bytes = match bytes {
             Some(bytes) => bytes,
             None => return Ok(None),
         };
-        let envelope = HgManifestEnvelope::from_blob(blobstore_bytes.into())?;
+        let envelope = decode_manifest_envelope(ctx, blobstore, blobstore_bytes).await?;
         if node_id.into_nodehash() != envelope.node_id() {
             bail!(
                 "Manifest ID mismatch (requested: {}, got: {})",
@@ -143,7 +144,8 @@ pub async fn fetch_manifest_envelope_opt<B: Blobstore>(
             );
         }
         Ok(Some(envelope))
-    })()
+    }
+    .await
     .context(ErrorKind::ManifestDeserializeFailed(blobstore_key))
 }
 
@@ -294,3 +296,78 @@ where
 {
     haystack.iter().position(|e| e == needle)
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use quickcheck::quickcheck;
+
+    use super::*;
+
+    /// Serialize manifest entries in the format Mercurial stores them in.
+    fn serialize(
+        entries: &BTreeMap<MPathElement, Entry<HgManifestId, (FileType, HgFileNodeId)>>,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (name, entry) in entries {
+            let (hash, flag) = match entry {
+                Entry::Tree(id) => (id.into_nodehash(), "t"),
+                Entry::Leaf((file_type, id)) => {
+                    (id.into_nodehash(), Type::File(*file_type).manifest_suffix())
+                }
+            };
+            data.extend_from_slice(name.as_ref());
+            data.push(b'\0');
+            data.extend_from_slice(hash.to_hex().as_bytes());
+            data.extend_from_slice(flag.as_bytes());
+            data.push(b'\n');
+        }
+        data
+    }
+
+    quickcheck! {
+        fn test_manifest_roundtrip(
+            trees: Vec<(MPathElement, HgManifestId)>,
+            leaves: Vec<(MPathElement, FileType, HgFileNodeId)>
+        ) -> bool {
+            let mut entries = BTreeMap::new();
+            for (name, id) in trees {
+                entries.insert(name, Entry::Tree(id));
+            }
+            for (name, file_type, id) in leaves {
+                entries.insert(name, Entry::Leaf((file_type, id)));
+            }
+
+            let parsed = ManifestContent::parse(&serialize(&entries))
+                .expect("serialized manifest should parse");
+            parsed.files.into_iter().collect::<BTreeMap<_, _>>() == entries
+        }
+
+        fn test_manifest_parse_arbitrary(data: Vec<u8>) -> bool {
+            // Arbitrary data may not be a valid manifest, but parsing it
+            // must fail cleanly.
+            let _ = ManifestContent::parse(&data);
+            true
+        }
+
+        fn test_manifest_parse_mutated(
+            leaves: Vec<(MPathElement, FileType, HgFileNodeId)>,
+            position: usize,
+            byte: u8
+        ) -> bool {
+            // Damage a valid manifest in one place.
+            let entries: BTreeMap<_, _> = leaves
+                .into_iter()
+                .map(|(name, file_type, id)| (name, Entry::Leaf((file_type, id))))
+                .collect();
+            let mut data = serialize(&entries);
+            if !data.is_empty() {
+                let position = position % data.len();
+                data[position] = byte;
+            }
+            let _ = ManifestContent::parse(&data);
+            true
+        }
+    }
+}
@@ -0,0 +1,272 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+// Sharded storage for the manifests of very wide directories.
+//
+// Manifest envelopes normally hold the whole manifest inline, so a directory
+// with hundreds of thousands of entries produces a multi-megabyte envelope
+// that is written again every time any entry changes. Above a configurable
+// size, the manifest is instead split into shards of consecutive entries,
+// each stored in its own content-addressed blob, and the envelope holds an
+// index of the shards in place of the contents.
+//
+// Shard boundaries are chosen from the entry names alone, so adding, removing
+// or modifying an entry only changes the shard that contains it, and the
+// other shards are shared with the previous version of the manifest.
+//
+// Sharding is purely a storage detail: the envelope is reassembled when it is
+// fetched, so readers, differs and clients see the same contents either way.
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+use blobstore::Blobstore;
+use bytes::Bytes;
+use context::CoreContext;
+use fbthrift::compact_protocol;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::hash::Blake2;
+use mononoke_types::hash::Context;
+use mononoke_types::BlobstoreBytes;
+use tunables::tunables;
+
+use crate::errors::ErrorKind;
+use crate::thrift;
+use crate::HgManifestEnvelope;
+use crate::HgManifestEnvelopeMut;
+use crate::HgManifestId;
+
+/// A shard ends after an entry whose name hashes to a multiple of this, so
+/// shards hold this many entries on average.
+const SHARD_TARGET_ENTRIES: u64 = 4096;
+const SHARD_MIN_ENTRIES: usize = 1024;
+const SHARD_MAX_ENTRIES: usize = 16384;
+
+const SHARD_CONCURRENCY: usize = 20;
+
+/// A range of consecutive lines of a manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ManifestShard {
+    pub first_name: Bytes,
+    pub contents: Bytes,
+}
+
+/// Whether a manifest with these contents should be stored in shards.
+pub fn should_shard_manifest(contents: &[u8]) -> bool {
+    let min_entries = tunables().get_hg_manifest_shard_min_entries();
+    min_entries > 0 && contents.iter().filter(|b| **b == b'\n').count() >= min_entries as usize
+}
+
+/// Split manifest contents into shards.  Concatenating the contents of the
+/// shards gives back the original contents.
+pub fn split_manifest(contents: &Bytes) -> Vec<ManifestShard> {
+    let mut shards = Vec::new();
+    let mut shard_start = 0;
+    let mut first_name = None;
+    let mut entries = 0;
+    let mut line_start = 0;
+
+    while line_start < contents.len() {
+        let line_end = contents[line_start..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(contents.len(), |pos| line_start + pos + 1);
+        let line = &contents[line_start..line_end];
+        let name = line
+            .split(|b| *b == 0 || *b == b'\n')
+            .next()
+            .unwrap_or(line);
+        if first_name.is_none() {
+            first_name = Some(Bytes::copy_from_slice(name));
+        }
+        entries += 1;
+        line_start = line_end;
+
+        let boundary = name_hash(name) % SHARD_TARGET_ENTRIES == 0;
+        if (boundary && entries >= SHARD_MIN_ENTRIES) || entries >= SHARD_MAX_ENTRIES {
+            shards.push(ManifestShard {
+                first_name: first_name.take().unwrap_or_default(),
+                contents: contents.slice(shard_start..line_start),
+            });
+            shard_start = line_start;
+            entries = 0;
+        }
+    }
+
+    if shard_start < contents.len() {
+        shards.push(ManifestShard {
+            first_name: first_name.unwrap_or_default(),
+            contents: contents.slice(shard_start..),
+        });
+    }
+
+    shards
+}
+
+/// FNV-1a, which is stable across releases, unlike the standard library's
+/// hasher.
+fn name_hash(name: &[u8]) -> u64 {
+    name.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn shard_id(contents: &[u8]) -> Blake2 {
+    let mut ctx = Context::new(b"hgmanifestshard");
+    ctx.update(contents);
+    ctx.finish()
+}
+
+fn shard_key(id: &Blake2) -> String {
+    format!("hgmanifestshard.blake2.{}", id)
+}
+
+/// Store a manifest envelope in shards.  The shards are stored before the
+/// envelope, so an envelope is never visible before all of its shards are.
+pub async fn store_sharded_manifest<B: Blobstore>(
+    ctx: &CoreContext,
+    blobstore: &B,
+    envelope: HgManifestEnvelopeMut,
+) -> Result<()> {
+    let index = stream::iter(split_manifest(&envelope.contents))
+        .map(|shard| async move {
+            let id = shard_id(&shard.contents);
+            let size = shard.contents.len() as i64;
+            blobstore
+                .put(
+                    ctx,
+                    shard_key(&id),
+                    BlobstoreBytes::from_bytes(shard.contents),
+                )
+                .await?;
+            Ok::<_, Error>(thrift::HgManifestShard {
+                first_name: shard.first_name.to_vec(),
+                id: id.into_thrift(),
+                size,
+            })
+        })
+        .buffered(SHARD_CONCURRENCY)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let thrift = thrift::HgManifestEnvelope {
+        node_id: envelope.node_id.into_thrift(),
+        p1: envelope.p1.map(|p1| p1.into_thrift()),
+        p2: envelope.p2.map(|p2| p2.into_thrift()),
+        computed_node_id: envelope.computed_node_id.into_thrift(),
+        contents: None,
+        shards: Some(index),
+    };
+    let key = HgManifestId::new(envelope.node_id).blobstore_key();
+    blobstore
+        .put(
+            ctx,
+            key,
+            BlobstoreBytes::from_bytes(compact_protocol::serialize(&thrift)),
+        )
+        .await
+}
+
+/// Decode a manifest envelope blob, fetching and reassembling its shards if
+/// it is sharded.
+pub async fn decode_manifest_envelope<B: Blobstore>(
+    ctx: &CoreContext,
+    blobstore: &B,
+    bytes: BlobstoreBytes,
+) -> Result<HgManifestEnvelope> {
+    let mut thrift: thrift::HgManifestEnvelope =
+        compact_protocol::deserialize(&bytes.as_bytes()[..])
+            .with_context(|| ErrorKind::BlobDeserializeError("HgManifestEnvelope".into()))?;
+
+    if let Some(shards) = thrift.shards.take() {
+        let parts = stream::iter(shards)
+            .map(|shard| fetch_shard(ctx, blobstore, shard))
+            .buffered(SHARD_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        thrift.contents = Some(parts.concat());
+    }
+
+    HgManifestEnvelope::from_thrift(thrift)
+}
+
+async fn fetch_shard<B: Blobstore>(
+    ctx: &CoreContext,
+    blobstore: &B,
+    shard: thrift::HgManifestShard,
+) -> Result<Bytes> {
+    let id = Blake2::from_thrift(shard.id)?;
+    let key = shard_key(&id);
+    let data = match blobstore.get(ctx, &key).await? {
+        Some(data) => data.into_raw_bytes(),
+        None => bail!("Manifest shard {} is missing", key),
+    };
+    if data.len() as i64 != shard.size || shard_id(&data) != id {
+        bail!("Manifest shard {} is corrupt", key);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest(entries: usize) -> Bytes {
+        let mut contents = Vec::new();
+        for i in 0..entries {
+            contents.extend_from_slice(format!("file{:08}\0{:040x}\n", i, i).as_bytes());
+        }
+        Bytes::from(contents)
+    }
+
+    #[test]
+    fn test_split_roundtrip() {
+        let contents = manifest(50_000);
+        let shards = split_manifest(&contents);
+        assert!(shards.len() > 1);
+        assert_eq!(shards[0].first_name, Bytes::from("file00000000"));
+        for shard in &shards {
+            assert!(shard.contents.starts_with(&shard.first_name));
+            assert!(shard.contents.ends_with(b"\n"));
+        }
+        let rejoined: Vec<u8> = shards.iter().flat_map(|s| s.contents.to_vec()).collect();
+        assert_eq!(rejoined, contents.to_vec());
+    }
+
+    #[test]
+    fn test_split_is_local() {
+        let contents = manifest(50_000);
+        let shards = split_manifest(&contents);
+
+        // Changing one entry only changes the shard that contains it.
+        let mut modified = contents.to_vec();
+        let offset = contents.len() / 2;
+        let hash_start = offset + modified[offset..].iter().position(|b| *b == 0).unwrap() + 1;
+        modified[hash_start] = b'f';
+        let modified_shards = split_manifest(&Bytes::from(modified));
+
+        assert_eq!(shards.len(), modified_shards.len());
+        let changed = shards
+            .iter()
+            .zip(modified_shards.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(changed, 1);
+    }
+
+    #[test]
+    fn test_split_small() {
+        assert_eq!(split_manifest(&Bytes::new()), vec![]);
+        let contents = manifest(10);
+        let shards = split_manifest(&contents);
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].contents, contents);
+    }
+}
@@ -34,6 +34,7 @@ pub use changeset::HgChangesetContent;
 pub use changeset::RevlogChangeset;
 
 pub mod filenode_lookup;
+pub mod manifest_shards;
 
 mod upload;
 pub use upload::ContentBlobMeta;
@@ -49,6 +49,8 @@ use super::errors::ErrorKind;
 use super::filenode_lookup::lookup_filenode_id;
 use super::filenode_lookup::store_filenode_id;
 use super::filenode_lookup::FileNodeIdPointer;
+use super::manifest_shards::should_shard_manifest;
+use super::manifest_shards::store_sharded_manifest;
 use super::File;
 use super::META_SZ;
 use crate::calculate_hg_node_id_stream;
@@ -139,7 +141,7 @@ impl UploadHgTreeEntry {
         };
 
         // This is the blob that gets uploaded. Manifest contents are usually small so they're
-        // stored inline.
+        // stored inline, but the manifests of very wide directories are stored in shards.
         let envelope = HgManifestEnvelopeMut {
             node_id,
             p1,
@@ -147,7 +149,6 @@ impl UploadHgTreeEntry {
             computed_node_id,
             contents,
         };
-        let envelope_blob = envelope.freeze().into_blob();
 
         let manifest_id = HgManifestId::new(node_id);
         let blobstore_key = manifest_id.blobstore_key();
@@ -172,9 +173,14 @@ impl UploadHgTreeEntry {
 
         // Upload the blob.
         let upload = async move {
-            blobstore
-                .put(&ctx, blobstore_key, envelope_blob.into())
-                .await
+            if should_shard_manifest(&envelope.contents) {
+                store_sharded_manifest(&ctx, &blobstore, envelope).await
+            } else {
+                let envelope_blob = envelope.freeze().into_blob();
+                blobstore
+                    .put(&ctx, blobstore_key, envelope_blob.into())
+                    .await
+            }
         }
         .map_ok({
             let path = path.clone();
@@ -23,9 +23,9 @@ use quickcheck::Arbitrary;
 use quickcheck::Gen;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
-use sha1::Digest;
 
 use crate::errors::ErrorKind;
+use crate::sha1_backend::Sha1Hasher;
 use crate::thrift;
 
 pub const SHA1_HASH_LENGTH_BYTES: usize = 20;
@@ -112,15 +112,15 @@ impl Sha1 {
 
 /// Context for incrementally computing a `Sha1` hash.
 #[derive(Clone)]
-pub struct Context(sha1::Sha1);
+pub struct Context(Sha1Hasher);
 
 /// Compute the `Sha1` for a slice of bytes.
 impl<'a> From<&'a [u8]> for Sha1 {
     fn from(data: &[u8]) -> Sha1 {
-        let mut sha1 = sha1::Sha1::new();
+        let mut sha1 = Sha1Hasher::new();
         sha1.update(data);
 
-        Sha1(sha1.finalize().into())
+        Sha1(sha1.finish())
     }
 }
 
@@ -283,7 +283,7 @@ impl Debug for Sha1Prefix {
 impl Context {
     /// Construct a `Context`
     pub fn new() -> Context {
-        Context(sha1::Sha1::new())
+        Context(Sha1Hasher::new())
     }
 
     /// Update a context from something that can be turned into a `&[u8]`
@@ -295,7 +295,7 @@ impl Context {
     }
 
     pub fn finish(self) -> Sha1 {
-        Sha1(self.0.finalize().into())
+        Sha1(self.0.finish())
     }
 }
 
@@ -59,12 +59,15 @@ pub mod hash;
 pub mod manifest;
 mod node;
 pub mod nodehash;
+pub mod nodehash256;
 pub mod remotefilelog;
+mod sha1_backend;
 pub mod sql_types;
 pub mod utils;
 
 pub use blob::HgBlob;
 pub use blobnode::calculate_hg_node_id;
+pub use blobnode::calculate_hg_node_id_sha256;
 pub use blobnode::calculate_hg_node_id_stream;
 pub use blobnode::HgBlobNode;
 pub use blobnode::HgParents;
@@ -103,8 +106,14 @@ pub use nodehash::HgNodeHash;
 pub use nodehash::HgNodeKey;
 pub use nodehash::NULL_CSID;
 pub use nodehash::NULL_HASH;
+pub use nodehash256::HgAnyNodeHash;
+pub use nodehash256::HgDualNodeHash;
+pub use nodehash256::HgNodeHash256;
+pub use nodehash256::HgNodeHashScheme;
+pub use nodehash256::SHA256_NODES_CAPABILITY;
 pub use remotefilelog::convert_parents_to_remotefilelog_format;
 pub use remotefilelog::HgFileHistoryEntry;
+pub use sha1_backend::SHA1_BACKEND;
 pub use utils::percent_encode;
 
 pub use self::manifest::Type;
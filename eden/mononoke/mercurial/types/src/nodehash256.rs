@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! SHA-256 node ids.
+//!
+//! Mercurial addresses nodes by a SHA-1 hash of their parents and contents.
+//! To allow clients to move to a stronger hash without a flag day, each node
+//! also has a SHA-256 id, computed over the same contents as its SHA-1 id and
+//! the SHA-256 ids of its parents (see `calculate_hg_node_id_sha256`).  Nodes
+//! remain stored under their SHA-1 id; the SHA-256 id is recorded in a mapping
+//! when the node is stored, so that the node can be found by either.
+//!
+//! Which ids are used on the wire is negotiated per session: servers that
+//! support SHA-256 ids advertise `SHA256_NODES_CAPABILITY`, and clients that
+//! don't ask for it keep seeing SHA-1 ids only.
+
+use std::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+use anyhow::Error;
+use anyhow::Result;
+use ascii::AsciiString;
+use mononoke_types::hash::Sha256;
+use quickcheck::Arbitrary;
+use quickcheck::Gen;
+use sql::mysql;
+
+use crate::nodehash::HgNodeHash;
+
+/// Capability advertised by servers that can serve SHA-256 node ids.
+pub const SHA256_NODES_CAPABILITY: &str = "sha256nodes";
+
+/// A SHA-256 based id of a Mercurial node.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+#[derive(mysql::OptTryFromRowField)]
+pub struct HgNodeHash256(pub(crate) Sha256);
+
+impl HgNodeHash256 {
+    pub const fn new(sha256: Sha256) -> Self {
+        HgNodeHash256(sha256)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Sha256::from_bytes(bytes).map(HgNodeHash256)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+
+    pub fn sha256(&self) -> &Sha256 {
+        &self.0
+    }
+
+    /// Returns a 64 hex digits representation of the sha256 hash
+    #[inline]
+    pub fn to_hex(&self) -> AsciiString {
+        self.0.to_hex()
+    }
+}
+
+impl FromStr for HgNodeHash256 {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Sha256::from_str(s).map(HgNodeHash256)
+    }
+}
+
+impl Display for HgNodeHash256 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl Arbitrary for HgNodeHash256 {
+    fn arbitrary(g: &mut Gen) -> Self {
+        HgNodeHash256(Sha256::arbitrary(g))
+    }
+}
+
+/// Both ids of a node.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub struct HgDualNodeHash {
+    pub sha1: HgNodeHash,
+    pub sha256: HgNodeHash256,
+}
+
+/// A node id given by a client, which may use either hash.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum HgAnyNodeHash {
+    Sha1(HgNodeHash),
+    Sha256(HgNodeHash256),
+}
+
+impl FromStr for HgAnyNodeHash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // The two hashes have different lengths, so the hex representation
+        // is never ambiguous.
+        match s.len() {
+            40 => HgNodeHash::from_str(s).map(HgAnyNodeHash::Sha1),
+            64 => HgNodeHash256::from_str(s).map(HgAnyNodeHash::Sha256),
+            len => bail!("invalid node hash length {} for '{}'", len, s),
+        }
+    }
+}
+
+impl Display for HgAnyNodeHash {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HgAnyNodeHash::Sha1(hash) => hash.fmt(fmt),
+            HgAnyNodeHash::Sha256(hash) => hash.fmt(fmt),
+        }
+    }
+}
+
+/// The hash used to identify nodes to a client.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum HgNodeHashScheme {
+    Sha1,
+    Sha256,
+}
+
+impl HgNodeHashScheme {
+    /// Pick the scheme for a session.  SHA-256 ids are only used if the
+    /// server has them enabled and the client asked for them; every other
+    /// combination falls back to SHA-1, which all clients understand.
+    pub fn negotiate<'a>(
+        client_capabilities: impl IntoIterator<Item = &'a str>,
+        server_enabled: bool,
+    ) -> Self {
+        if server_enabled
+            && client_capabilities
+                .into_iter()
+                .any(|cap| cap == SHA256_NODES_CAPABILITY)
+        {
+            HgNodeHashScheme::Sha256
+        } else {
+            HgNodeHashScheme::Sha1
+        }
+    }
+
+    /// Format the id of a node according to this scheme.
+    pub fn format(&self, node: &HgDualNodeHash) -> String {
+        match self {
+            HgNodeHashScheme::Sha1 => node.sha1.to_string(),
+            HgNodeHashScheme::Sha256 => node.sha256.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHA1_HEX: &str = "a9473beb2eb03ddb1cccc3fbaeb8a4820f9cd157";
+    const SHA256_HEX: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_parse_any() {
+        assert_eq!(
+            HgAnyNodeHash::from_str(SHA1_HEX).unwrap(),
+            HgAnyNodeHash::Sha1(HgNodeHash::from_str(SHA1_HEX).unwrap())
+        );
+        assert_eq!(
+            HgAnyNodeHash::from_str(SHA256_HEX).unwrap(),
+            HgAnyNodeHash::Sha256(HgNodeHash256::from_str(SHA256_HEX).unwrap())
+        );
+        assert!(HgAnyNodeHash::from_str(&SHA256_HEX[..50]).is_err());
+        assert_eq!(
+            HgAnyNodeHash::from_str(SHA256_HEX).unwrap().to_string(),
+            SHA256_HEX
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let node = HgDualNodeHash {
+            sha1: HgNodeHash::from_str(SHA1_HEX).unwrap(),
+            sha256: HgNodeHash256::from_str(SHA256_HEX).unwrap(),
+        };
+        let new_client = ["lookup", SHA256_NODES_CAPABILITY];
+        let old_client = ["lookup"];
+
+        let scheme = HgNodeHashScheme::negotiate(new_client, true);
+        assert_eq!(scheme, HgNodeHashScheme::Sha256);
+        assert_eq!(scheme.format(&node), SHA256_HEX);
+
+        let scheme = HgNodeHashScheme::negotiate(old_client, true);
+        assert_eq!(scheme, HgNodeHashScheme::Sha1);
+        assert_eq!(scheme.format(&node), SHA1_HEX);
+
+        assert_eq!(
+            HgNodeHashScheme::negotiate(new_client, false),
+            HgNodeHashScheme::Sha1
+        );
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! The implementation of SHA-1 used for Mercurial hashes.
+//!
+//! Hashing dominates CPU time when validating large amounts of Mercurial
+//! data, so the implementation can be chosen at build time:
+//!
+//! * By default, the pure-Rust `sha-1` crate is used.  On x86-64 this
+//!   detects and uses the SHA-NI instructions at runtime if the CPU has them.
+//! * Building with `--cfg sha1_openssl` uses OpenSSL's implementation, which
+//!   selects the fastest code for the CPU itself.  Like `fbcode_build`, this
+//!   is set by the build rather than being a crate feature.
+//!
+//! All implementations produce identical hashes.
+
+#[cfg(not(sha1_openssl))]
+use sha1::Digest;
+
+use crate::hash::SHA1_HASH_LENGTH_BYTES;
+
+/// Name of the SHA-1 implementation in use, for logging and benchmarks.
+#[cfg(not(sha1_openssl))]
+pub const SHA1_BACKEND: &str = "sha-1";
+
+/// Name of the SHA-1 implementation in use, for logging and benchmarks.
+#[cfg(sha1_openssl)]
+pub const SHA1_BACKEND: &str = "openssl";
+
+/// Incremental SHA-1 hasher using the selected implementation.
+#[derive(Clone)]
+pub(crate) struct Sha1Hasher(
+    #[cfg(not(sha1_openssl))] sha1::Sha1,
+    #[cfg(sha1_openssl)] openssl::sha::Sha1,
+);
+
+impl Sha1Hasher {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        #[cfg(not(sha1_openssl))]
+        let hasher = sha1::Sha1::new();
+        #[cfg(sha1_openssl)]
+        let hasher = openssl::sha::Sha1::new();
+        Sha1Hasher(hasher)
+    }
+
+    #[inline]
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    #[inline]
+    pub(crate) fn finish(self) -> [u8; SHA1_HASH_LENGTH_BYTES] {
+        #[cfg(not(sha1_openssl))]
+        let digest = self.0.finalize().into();
+        #[cfg(sha1_openssl)]
+        let digest = self.0.finish();
+        digest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::quickcheck;
+    use sha1::Digest;
+
+    use super::*;
+    use crate::hash::Sha1;
+
+    /// The reference implementation that the selected implementation must
+    /// agree with.
+    fn reference(chunks: &[Vec<u8>]) -> [u8; SHA1_HASH_LENGTH_BYTES] {
+        let mut reference = sha1::Sha1::new();
+        for chunk in chunks {
+            reference.update(chunk);
+        }
+        reference.finalize().into()
+    }
+
+    fn hex(hash: [u8; SHA1_HASH_LENGTH_BYTES]) -> String {
+        Sha1::from_byte_array(hash).to_string()
+    }
+
+    fn hash(chunks: &[Vec<u8>]) -> [u8; SHA1_HASH_LENGTH_BYTES] {
+        let mut hasher = Sha1Hasher::new();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_known_hashes() {
+        // Test vectors from FIPS 180-2.
+        assert_eq!(
+            hex(hash(&[b"abc".to_vec()])),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(hash(&[
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".to_vec()
+            ])),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex(hash(&[vec![b'a'; 1_000_000]])),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        // Exercise lengths around the 64 byte block size, where padding
+        // differs.
+        for len in 0..=200 {
+            let data = (0..len).map(|i| i as u8).collect::<Vec<u8>>();
+            assert_eq!(hash(&[data.clone()]), reference(&[data]), "length {}", len);
+        }
+    }
+
+    quickcheck! {
+        fn matches_reference(chunks: Vec<Vec<u8>>) -> bool {
+            hash(&chunks) == reference(&chunks)
+        }
+
+        fn chunking_is_irrelevant(chunks: Vec<Vec<u8>>) -> bool {
+            hash(&chunks) == hash(&[chunks.concat()])
+        }
+    }
+}
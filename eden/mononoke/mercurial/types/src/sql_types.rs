@@ -15,9 +15,16 @@ use sql::mysql_async::Value;
 use crate::HgChangesetId;
 use crate::HgFileNodeId;
 use crate::HgNodeHash;
+use crate::HgNodeHash256;
 
 type FromValueResult<T> = Result<T, FromValueError>;
 
+impl From<HgNodeHash> for Value {
+    fn from(hash: HgNodeHash) -> Self {
+        Value::Bytes(hash.0.as_ref().into())
+    }
+}
+
 impl From<HgFileNodeId> for Value {
     fn from(id: HgFileNodeId) -> Self {
         Value::Bytes(id.into_nodehash().0.as_ref().into())
@@ -34,6 +41,12 @@ pub trait FromNodeHash {
     fn from_nodehash(hash: HgNodeHash) -> Self;
 }
 
+impl FromNodeHash for HgNodeHash {
+    fn from_nodehash(hash: HgNodeHash) -> Self {
+        hash
+    }
+}
+
 impl FromNodeHash for HgFileNodeId {
     fn from_nodehash(hash: HgNodeHash) -> Self {
         HgFileNodeId::new(hash)
@@ -65,6 +78,10 @@ impl<T: FromNodeHash> ConvIr<T> for HgNodeHash {
     }
 }
 
+impl FromValue for HgNodeHash {
+    type Intermediate = HgNodeHash;
+}
+
 impl FromValue for HgFileNodeId {
     type Intermediate = HgNodeHash;
 }
@@ -72,3 +89,31 @@ impl FromValue for HgFileNodeId {
 impl FromValue for HgChangesetId {
     type Intermediate = HgNodeHash;
 }
+
+impl From<HgNodeHash256> for Value {
+    fn from(hash: HgNodeHash256) -> Self {
+        Value::Bytes(hash.as_bytes().into())
+    }
+}
+
+impl ConvIr<HgNodeHash256> for HgNodeHash256 {
+    fn new(v: Value) -> FromValueResult<Self> {
+        match v {
+            Value::Bytes(bytes) => HgNodeHash256::from_bytes(&bytes)
+                .map_err(move |_| FromValueError(Value::Bytes(bytes))),
+            v => Err(FromValueError(v)),
+        }
+    }
+
+    fn commit(self) -> Self {
+        self
+    }
+
+    fn rollback(self) -> Value {
+        self.into()
+    }
+}
+
+impl FromValue for HgNodeHash256 {
+    type Intermediate = HgNodeHash256;
+}
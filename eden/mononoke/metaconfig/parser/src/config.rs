@@ -135,7 +135,7 @@ pub fn load_configs_from_raw(
     ))
 }
 
-fn parse_with_repo_definition(
+pub(crate) fn parse_with_repo_definition(
     repo_definition: RawRepoDefinition,
     named_repo_configs: &HashMap<String, RawRepoConfig>,
     named_storage_configs: &HashMap<String, RawStorageConfig>,
@@ -223,6 +223,12 @@ fn parse_with_repo_definition(
         backup_hg_sync_config,
         deep_sharded,
         update_logging_config,
+        path_policy,
+        commit_limits,
+        path_read_acls,
+        freeze_windows,
+        author_policy,
+        growth_alarms,
         ..
     } = named_repo_config;
 
@@ -308,6 +314,17 @@ fn parse_with_repo_definition(
     let source_control_service_monitoring = source_control_service_monitoring.convert()?;
 
     let derived_data_config = derived_data_config.convert()?.unwrap_or_default();
+    for bookmark_params in bookmarks.iter() {
+        for derived_data_type in bookmark_params.required_derived_data.iter() {
+            if !derived_data_config.is_enabled(derived_data_type) {
+                return Err(ConfigurationError::InvalidConfig(format!(
+                    "Bookmark {:?} requires derived data type {}, which is not enabled",
+                    bookmark_params.bookmark, derived_data_type
+                ))
+                .into());
+            }
+        }
+    }
 
     let enforce_lfs_acl_check = enforce_lfs_acl_check.unwrap_or(false);
     let repo_client_use_warm_bookmarks_cache =
@@ -336,6 +353,12 @@ fn parse_with_repo_definition(
     let backup_hg_sync_config = backup_hg_sync_config.convert()?;
 
     let update_logging_config = update_logging_config.convert()?.unwrap_or_default();
+    let path_policy = path_policy.convert()?.unwrap_or_default();
+    let commit_limits = commit_limits.convert()?.unwrap_or_default();
+    let path_read_acls = path_read_acls.convert()?.unwrap_or_default();
+    let freeze_windows = freeze_windows.convert()?.unwrap_or_default();
+    let author_policy = author_policy.convert()?.unwrap_or_default();
+    let growth_alarms = growth_alarms.convert()?.unwrap_or_default();
 
     Ok(RepoConfig {
         enabled,
@@ -377,6 +400,12 @@ fn parse_with_repo_definition(
         backup_hg_sync_config,
         deep_sharded,
         update_logging_config,
+        path_policy,
+        commit_limits,
+        path_read_acls,
+        freeze_windows,
+        author_policy,
+        growth_alarms,
         default_commit_identity_scheme,
     })
 }
@@ -490,36 +519,56 @@ mod test {
     use metaconfig_types::AclRegionConfig;
     use metaconfig_types::AclRegionRule;
     use metaconfig_types::Address;
+    use metaconfig_types::AuthorPolicyConfig;
+    use metaconfig_types::AuthorPolicyEnforcement;
     use metaconfig_types::BlameVersion;
     use metaconfig_types::BlobConfig;
     use metaconfig_types::BlobstoreId;
     use metaconfig_types::BookmarkParams;
+    use metaconfig_types::BookmarkPublishingDelay;
     use metaconfig_types::BubbleDeletionMode;
     use metaconfig_types::CacheWarmupParams;
     use metaconfig_types::CommitIdentityScheme;
+    use metaconfig_types::CommitLimitsConfig;
+    use metaconfig_types::CommitMessageRewriteRule;
     use metaconfig_types::CommitSyncConfig;
     use metaconfig_types::CommitSyncConfigVersion;
     use metaconfig_types::CrossRepoCommitValidation;
     use metaconfig_types::DatabaseConfig;
     use metaconfig_types::DefaultSmallToLargeCommitSyncPathAction;
+    use metaconfig_types::DerivationQueueConfig;
     use metaconfig_types::DerivedDataConfig;
     use metaconfig_types::DerivedDataTypesConfig;
     use metaconfig_types::EphemeralBlobstoreConfig;
+    use metaconfig_types::EventSink;
+    use metaconfig_types::EventSubscription;
     use metaconfig_types::FilestoreParams;
+    use metaconfig_types::FreezeWindow;
+    use metaconfig_types::GrowthAlarmsConfig;
     use metaconfig_types::HgSyncConfig;
     use metaconfig_types::HookBypass;
     use metaconfig_types::HookConfig;
+    use metaconfig_types::HookExecutionLimits;
+    use metaconfig_types::HookFailurePolicy;
     use metaconfig_types::HookManagerParams;
     use metaconfig_types::HookParams;
+    use metaconfig_types::HookRules;
+    use metaconfig_types::HookScheduling;
+    use metaconfig_types::HookSeverity;
     use metaconfig_types::Identity;
     use metaconfig_types::InfinitepushNamespace;
     use metaconfig_types::InfinitepushParams;
     use metaconfig_types::LfsParams;
     use metaconfig_types::LocalDatabaseConfig;
     use metaconfig_types::LoggingDestination;
+    use metaconfig_types::MergeCommitPolicy;
+    use metaconfig_types::MergePolicy;
     use metaconfig_types::MetadataDatabaseConfig;
     use metaconfig_types::MultiplexId;
     use metaconfig_types::MultiplexedStoreType;
+    use metaconfig_types::PathPolicyConfig;
+    use metaconfig_types::PathReadAcl;
+    use metaconfig_types::PrivateScratchNamespace;
     use metaconfig_types::PushParams;
     use metaconfig_types::PushrebaseFlags;
     use metaconfig_types::PushrebaseParams;
@@ -527,6 +576,7 @@ mod test {
     use metaconfig_types::RemoteDatabaseConfig;
     use metaconfig_types::RemoteMetadataDatabaseConfig;
     use metaconfig_types::RepoClientKnobs;
+    use metaconfig_types::RepoEventKind;
     use metaconfig_types::SegmentedChangelogConfig;
     use metaconfig_types::SegmentedChangelogHeadConfig;
     use metaconfig_types::ShardableRemoteDatabaseConfig;
@@ -535,9 +585,11 @@ mod test {
     use metaconfig_types::SourceControlServiceMonitoring;
     use metaconfig_types::SourceControlServiceParams;
     use metaconfig_types::SparseProfilesConfig;
+    use metaconfig_types::TraceSamplingConfig;
     use metaconfig_types::UnodeVersion;
     use metaconfig_types::UpdateLoggingConfig;
     use metaconfig_types::WalkerConfig;
+    use metaconfig_types::WireprotoShadowingConfig;
     use mononoke_types::MPath;
     use mononoke_types_mocks::changesetid::ONES_CSID;
     use nonzero_ext::nonzero;
@@ -787,6 +839,7 @@ mod test {
             [cache_warmup]
             bookmark="master"
             commit_limit=100
+            published_heads=true
             [hook_manager_params]
             disable_acl_checker=false
             all_hooks_bypassed=false
@@ -800,9 +853,26 @@ mod test {
             unode_version = 2
             blame_filesize_limit = 101
 
+            [derived_data_config.queue]
+            types = ["blame", "fsnodes"]
+
             [[bookmarks]]
             name="master"
             allowed_users="^(svcscm|twsvcscm)$"
+            required_derived_data=["fsnodes", "blame"]
+
+            [bookmarks.merge_policy]
+            deny_merges=true
+            allowed_merge_identities=[
+                { identity_type = "SERVICE_IDENTITY", identity_data = "merge_bot" },
+            ]
+
+            [bookmarks.publishing_delay]
+            delay_secs=1800
+            approval_bookmark="master_approved"
+            canary_identities=[
+                { identity_type = "USER", identity_data = "canary" },
+            ]
 
             [[bookmarks.hooks]]
             hook_name="hook1"
@@ -818,13 +888,22 @@ mod test {
             [[hooks]]
             name="hook1"
             bypass_commit_string="@allow_hook1"
+            timeout_ms=5000
+            fail_open=true
 
             [[hooks]]
             name="rust:rusthook"
             config_ints={ int1 = 44 }
             config_ints_64={ int2 = 42 }
+            run_after=["hook1"]
+            metadata_only=true
             [hooks.config_string_lists]
                 list1 = ["val1", "val2"]
+            [hooks.rules]
+                include_paths = ["fbcode/**/*.py"]
+                exclude_paths = ["fbcode/third-party/**"]
+                severity = "warn"
+                bypass_pushvar_name = "SKIP_RUSTHOOK"
 
             [push]
             pure_push_allowed = false
@@ -837,10 +916,16 @@ mod test {
             casefolding_check = false
             emit_obsmarkers = false
             allow_change_xrepo_mapping_extra = true
+            serialize_per_bookmark = true
 
             [pushrebase.remote_mode]
             remote_scs = { tier = "my-tier" }
 
+            [[pushrebase.commit_message_rewrite_rules]]
+            bookmark_regex = "^master$"
+            strip_patterns = ["(?m)^Local-Marker: .*$"]
+            append_template = "Landed-On: {bookmark}"
+
             [lfs]
             threshold = 1000
             rollout_percentage = 56
@@ -850,6 +935,15 @@ mod test {
             allow_writes = true
             namespace_pattern = "foobar/.+"
 
+            [infinitepush.private_namespace]
+            pattern = "foobar/(?P<user>[^/]+)/.+"
+            owner_identity_type = "USER"
+
+            [infinitepush.private_namespace.shared_with]
+            alice = [
+                { identity_type = "USER", identity_data = "bob" },
+            ]
+
             [filestore]
             chunk_size = 768
             concurrency = 48
@@ -859,6 +953,26 @@ mod test {
 
             [repo_client_knobs]
             allow_short_getpack_history = true
+            legacy_heads_include_scratch = true
+            max_concurrent_getbundles = 10
+            reject_legacy_changegroup_clients = true
+            manifest_verification_sample_rate = 1000
+            accepted_bundle2_parts = ["x:metadata"]
+
+            [repo_client_knobs.listkeys_namespaces.release]
+            channel = "stable"
+
+            [repo_client_knobs.shadowing]
+            scribe_category = "mononoke_shadow_traffic"
+            commands = ["getbundle", "gettreepack"]
+
+            [repo_client_knobs.trace_sampling]
+            sample_rate = 50
+            command_sample_rates = { getbundle = 10, known = 0 }
+            full_trace_commands = ["getbundle"]
+            always_trace_identities = [
+                { identity_type = "USER", identity_data = "alice" },
+            ]
 
             [segmented_changelog_config]
             enabled = true
@@ -898,6 +1012,45 @@ mod test {
 
             [update_logging_config]
             new_commit_logging_destination = { scribe = { scribe_category = "cat" } }
+
+            [[update_logging_config.event_subscriptions]]
+            sink = { webhook = { url = "https://example.com/hook" } }
+            events = ["bookmark_moved"]
+            bookmark_regex = "^master$"
+
+            [path_policy]
+            max_depth = 64
+            reserved_names = [".hg"]
+
+            [commit_limits]
+            max_files_changed = 100000
+            max_new_directory_entries = 10000
+            large_commit_identities = [
+                { identity_type = "USER", identity_data = "bob" },
+            ]
+
+            [[path_read_acls]]
+            path_prefix = "secret/keys"
+            allowed_identities = [
+                { identity_type = "USER", identity_data = "alice" },
+            ]
+
+            [[freeze_windows]]
+            name = "weekly_release"
+            schedule = "0 0 18 * * Fri"
+            duration_secs = 216000
+            bookmark_regex = "^release/"
+            landing_queue = "release-queue"
+
+            [author_policy]
+            enforcement = "require_service_identity"
+            service_identities = [
+                { identity_type = "SERVICE_IDENTITY", identity_data = "landing_service" },
+            ]
+
+            [growth_alarms]
+            commits_per_day = 100000
+            bookmark_count = 1000000
         "#;
         let fbsource_repo_def = r#"
             repo_id=0
@@ -1068,6 +1221,7 @@ mod test {
                     bookmark: BookmarkName::new("master").unwrap(),
                     commit_limit: 100,
                     microwave_preload: false,
+                    published_heads: true,
                 }),
                 hook_manager_params: Some(HookManagerParams {
                     disable_acl_checker: false,
@@ -1085,6 +1239,22 @@ mod test {
                         hooks_skip_ancestors_of: vec![],
                         ensure_ancestor_of: None,
                         allow_move_to_public_commits_without_hooks: false,
+                        required_derived_data: vec!["fsnodes".to_string(), "blame".to_string()],
+                        merge_policy: MergePolicy {
+                            merges: MergeCommitPolicy::AllowFrom(vec![Identity {
+                                id_type: "SERVICE_IDENTITY".to_string(),
+                                id_data: "merge_bot".to_string(),
+                            }]),
+                            require_linear_history: false,
+                        },
+                        publishing_delay: Some(BookmarkPublishingDelay {
+                            delay: Duration::from_secs(1800),
+                            canary_identities: vec![Identity {
+                                id_type: "USER".to_string(),
+                                id_data: "canary".to_string(),
+                            }],
+                            approval_bookmark: Some(BookmarkName::new("master_approved").unwrap()),
+                        }),
                     },
                     BookmarkParams {
                         bookmark: Regex::new("[^/]*/stable").unwrap().into(),
@@ -1096,6 +1266,9 @@ mod test {
                         hooks_skip_ancestors_of: vec![],
                         ensure_ancestor_of: Some(BookmarkName::new("master").unwrap()),
                         allow_move_to_public_commits_without_hooks: true,
+                        required_derived_data: vec![],
+                        merge_policy: MergePolicy::default(),
+                        publishing_delay: None,
                     },
                 ],
                 hooks: vec![
@@ -1109,12 +1282,22 @@ mod test {
                             string_lists: hashmap! {},
                             int_lists: hashmap! {},
                             int_64_lists: hashmap! {},
+                            rules: HookRules::default(),
+                            limits: HookExecutionLimits {
+                                timeout: Some(Duration::from_millis(5000)),
+                                max_file_fetches: None,
+                                failure_policy: HookFailurePolicy::FailOpen,
+                            },
+                            scheduling: HookScheduling::default(),
                         },
                     },
                     HookParams {
                         name: "rust:rusthook".to_string(),
                         config: HookConfig {
-                            bypass: None,
+                            bypass: Some(HookBypass::new_with_pushvar(
+                                "SKIP_RUSTHOOK".into(),
+                                "true".into(),
+                            )),
                             strings: hashmap! {},
                             ints: hashmap! {
                                 "int1".into() => 44,
@@ -1127,6 +1310,21 @@ mod test {
                             },
                             int_lists: hashmap! {},
                             int_64_lists: hashmap! {},
+                            limits: HookExecutionLimits::default(),
+                            rules: HookRules {
+                                include_paths: vec![
+                                    Regex::new("^fbcode/(?:.*/)?[^/]*\\.py$").unwrap().into(),
+                                ],
+                                exclude_paths: vec![
+                                    Regex::new("^fbcode/third\\-party/.*$").unwrap().into(),
+                                ],
+                                bookmarks: vec![],
+                                severity: HookSeverity::Warn,
+                            },
+                            scheduling: HookScheduling {
+                                run_after: vec!["hook1".to_string()],
+                                metadata_only: true,
+                            },
                         },
                     },
                 ],
@@ -1142,6 +1340,7 @@ mod test {
                         casefolding_check: false,
                         not_generated_filenodes_limit: 500,
                         monitoring_bookmark: None,
+                        serialize_per_bookmark: true,
                     },
                     block_merges: false,
                     emit_obsmarkers: false,
@@ -1152,6 +1351,11 @@ mod test {
                     remote_mode: PushrebaseRemoteMode::RemoteScs(Address::Tier(
                         "my-tier".to_string(),
                     )),
+                    commit_message_rewrite_rules: vec![CommitMessageRewriteRule {
+                        bookmark_regex: Some(Regex::new("^master$").unwrap().into()),
+                        strip_patterns: vec![Regex::new("(?m)^Local-Marker: .*$").unwrap().into()],
+                        append_template: Some("Landed-On: {bookmark}".to_string()),
+                    }],
                 },
                 lfs: LfsParams {
                     threshold: Some(1000),
@@ -1167,12 +1371,24 @@ mod test {
                     namespace: Some(InfinitepushNamespace::new(Regex::new("foobar/.+").unwrap())),
                     hydrate_getbundle_response: false,
                     commit_scribe_category: None,
+                    private_namespace: Some(PrivateScratchNamespace {
+                        pattern: Regex::new("foobar/(?P<user>[^/]+)/.+").unwrap().into(),
+                        owner_identity_type: "USER".to_string(),
+                        readers: vec![],
+                        shared_with: hashmap! {
+                            "alice".to_string() => vec![Identity {
+                                id_type: "USER".to_string(),
+                                id_data: "bob".to_string(),
+                            }],
+                        },
+                    }),
                 },
                 list_keys_patterns_max: 123,
                 hook_max_file_size: 456,
                 filestore: Some(FilestoreParams {
                     chunk_size: 768,
                     concurrency: 48,
+                    content_defined_chunking: false,
                 }),
                 hipster_acl: Some("foo/test".to_string()),
                 source_control_service: SourceControlServiceParams {
@@ -1181,6 +1397,7 @@ mod test {
                     service_write_hipster_acl: None,
                     permit_commits_without_parents: false,
                     service_write_restrictions: Default::default(),
+                    raw_blob_readers: vec![],
                 },
                 source_control_service_monitoring: Some(SourceControlServiceMonitoring {
                     bookmarks_to_report_age: vec![
@@ -1203,6 +1420,10 @@ mod test {
                         blame_version: BlameVersion::V1,
                     },],
                     scuba_table: None,
+                    queue: Some(DerivationQueueConfig {
+                        types: vec![String::from("blame"), String::from("fsnodes")],
+                        max_attempts: 5,
+                    }),
                 },
                 enforce_lfs_acl_check: false,
                 repo_client_use_warm_bookmarks_cache: true,
@@ -1219,6 +1440,35 @@ mod test {
                 },
                 repo_client_knobs: RepoClientKnobs {
                     allow_short_getpack_history: true,
+                    shadowing: Some(WireprotoShadowingConfig {
+                        scribe_category: "mononoke_shadow_traffic".to_string(),
+                        sample_rate: nonzero!(100u64),
+                        commands: vec!["getbundle".to_string(), "gettreepack".to_string()],
+                    }),
+                    legacy_heads_include_scratch: true,
+                    max_concurrent_getbundles: Some(nonzero!(10usize)),
+                    max_concurrent_unbundles: None,
+                    max_concurrent_hook_runs: None,
+                    reject_legacy_changegroup_clients: true,
+                    manifest_verification_sample_rate: Some(nonzero!(1000u64)),
+                    trace_sampling: Some(TraceSamplingConfig {
+                        sample_rate: Some(nonzero!(50u64)),
+                        command_sample_rates: hashmap! {
+                            "getbundle".to_string() => 10,
+                            "known".to_string() => 0,
+                        },
+                        full_trace_commands: vec!["getbundle".to_string()],
+                        always_trace_identities: vec![Identity {
+                            id_type: "USER".to_string(),
+                            id_data: "alice".to_string(),
+                        }],
+                    }),
+                    accepted_bundle2_parts: vec!["x:metadata".to_string()],
+                    listkeys_namespaces: hashmap! {
+                        "release".to_string() => hashmap! {
+                            "channel".to_string() => "stable".to_string(),
+                        },
+                    },
                 },
                 phabricator_callsign: Some("FBS".to_string()),
                 backup_repo_config: Some(BackupRepoConfig {
@@ -1267,6 +1517,54 @@ mod test {
                     new_commit_logging_destination: Some(LoggingDestination::Scribe {
                         scribe_category: "cat".to_string(),
                     }),
+                    event_subscriptions: vec![EventSubscription {
+                        sink: EventSink::Webhook {
+                            url: "https://example.com/hook".to_string(),
+                            max_attempts: 3,
+                        },
+                        event_kinds: vec![RepoEventKind::BookmarkMoved],
+                        bookmark_regex: Some(Regex::new("^master$").unwrap().into()),
+                    }],
+                },
+                path_policy: PathPolicyConfig {
+                    max_depth: Some(64),
+                    reserved_names: vec![".hg".to_string()],
+                    ..Default::default()
+                },
+                commit_limits: CommitLimitsConfig {
+                    max_files_changed: Some(100000),
+                    max_new_directory_entries: Some(10000),
+                    max_directory_manifest_size: None,
+                    large_commit_identities: vec![Identity {
+                        id_type: "USER".to_string(),
+                        id_data: "bob".to_string(),
+                    }],
+                },
+                path_read_acls: vec![PathReadAcl {
+                    path_prefix: MPath::new("secret/keys").unwrap(),
+                    allowed_identities: vec![Identity {
+                        id_type: "USER".to_string(),
+                        id_data: "alice".to_string(),
+                    }],
+                }],
+                freeze_windows: vec![FreezeWindow {
+                    name: "weekly_release".to_string(),
+                    schedule: "0 0 18 * * Fri".parse().unwrap(),
+                    duration: Duration::from_secs(216000),
+                    bookmark_regex: Some(Regex::new("^release/").unwrap().into()),
+                    landing_queue: Some("release-queue".to_string()),
+                }],
+                author_policy: AuthorPolicyConfig {
+                    enforcement: AuthorPolicyEnforcement::AllowFrom(vec![Identity {
+                        id_type: "SERVICE_IDENTITY".to_string(),
+                        id_data: "landing_service".to_string(),
+                    }]),
+                    check_committer: false,
+                },
+                growth_alarms: GrowthAlarmsConfig {
+                    blob_bytes_per_day: None,
+                    commits_per_day: Some(100000),
+                    bookmark_count: Some(1000000),
                 },
             },
         );
@@ -1342,6 +1640,12 @@ mod test {
                 backup_hg_sync_config: None,
                 deep_sharded: false,
                 update_logging_config: UpdateLoggingConfig::default(),
+                path_policy: PathPolicyConfig::default(),
+                commit_limits: CommitLimitsConfig::default(),
+                path_read_acls: Vec::new(),
+                freeze_windows: Vec::new(),
+                author_policy: AuthorPolicyConfig::default(),
+                growth_alarms: GrowthAlarmsConfig::default(),
             },
         );
         assert_eq!(
@@ -1438,6 +1742,84 @@ mod test {
         assert!(msg.contains("InvalidPushvar"));
     }
 
+    #[test]
+    fn test_zero_concurrency_limit() {
+        let content = r#"
+            storage_config = "sqlite"
+
+            [storage.sqlite.metadata.local]
+            local_db_path = "/tmp/fbsource"
+
+            [storage.sqlite.blobstore.blob_files]
+            path = "/tmp/fbsource"
+
+            [repo_client_knobs]
+            max_concurrent_unbundles = 0
+        "#;
+
+        let content_def = r#"
+            repo_id = 0
+            repo_name = "fbsource"
+            repo_config = "fbsource"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/fbsource/server.toml" => content,
+            "repo_definitions/fbsource/server.toml" => content_def,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        assert!(res.is_err());
+        assert!(msg.contains("max_concurrent_unbundles must be an integer larger than zero"));
+    }
+
+    #[test]
+    fn test_required_derived_data_not_enabled() {
+        let content = r#"
+            storage_config = "sqlite"
+
+            [storage.sqlite.metadata.local]
+            local_db_path = "/tmp/fbsource"
+
+            [storage.sqlite.blobstore.blob_files]
+            path = "/tmp/fbsource"
+
+            [derived_data_config]
+            enabled_config_name = "default"
+
+            [derived_data_config.available_configs.default]
+            types = ["fsnodes"]
+
+            [[bookmarks]]
+            name="master"
+            required_derived_data=["fsnodes", "blame"]
+        "#;
+
+        let content_def = r#"
+            repo_id = 0
+            repo_name = "fbsource"
+            repo_config = "fbsource"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => "",
+            "repos/fbsource/server.toml" => content,
+            "repo_definitions/fbsource/server.toml" => content_def,
+        };
+
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        println!("res = {}", msg);
+        assert!(res.is_err());
+        assert!(msg.contains("requires derived data type blame"));
+    }
+
     #[test]
     fn test_broken_common_config() {
         fn check_fails(common: &str, expect: &str) {
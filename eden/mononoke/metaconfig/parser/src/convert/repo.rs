@@ -6,6 +6,8 @@
  */
 
 use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -14,24 +16,45 @@ use anyhow::Context;
 use anyhow::Result;
 use bookmarks_types::BookmarkName;
 use metaconfig_types::Address;
+use metaconfig_types::AuthorPolicyConfig;
+use metaconfig_types::AuthorPolicyEnforcement;
 use metaconfig_types::BlameVersion;
 use metaconfig_types::BookmarkOrRegex;
 use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPublishingDelay;
 use metaconfig_types::CacheWarmupParams;
 use metaconfig_types::CommitIdentityScheme;
+use metaconfig_types::CommitLimitsConfig;
+use metaconfig_types::CommitMessageRewriteRule;
 use metaconfig_types::ComparableRegex;
+use metaconfig_types::CronSchedule;
 use metaconfig_types::CrossRepoCommitValidation;
+use metaconfig_types::DerivationQueueConfig;
 use metaconfig_types::DerivedDataConfig;
 use metaconfig_types::DerivedDataTypesConfig;
+use metaconfig_types::EventSink;
+use metaconfig_types::EventSubscription;
+use metaconfig_types::FreezeWindow;
+use metaconfig_types::GrowthAlarmsConfig;
 use metaconfig_types::HgSyncConfig;
 use metaconfig_types::HookBypass;
 use metaconfig_types::HookConfig;
+use metaconfig_types::HookExecutionLimits;
+use metaconfig_types::HookFailurePolicy;
 use metaconfig_types::HookManagerParams;
 use metaconfig_types::HookParams;
+use metaconfig_types::HookRules;
+use metaconfig_types::HookScheduling;
+use metaconfig_types::HookSeverity;
 use metaconfig_types::InfinitepushNamespace;
 use metaconfig_types::InfinitepushParams;
 use metaconfig_types::LfsParams;
 use metaconfig_types::LoggingDestination;
+use metaconfig_types::MergeCommitPolicy;
+use metaconfig_types::MergePolicy;
+use metaconfig_types::PathPolicyConfig;
+use metaconfig_types::PathReadAcl;
+use metaconfig_types::PrivateScratchNamespace;
 use metaconfig_types::PushParams;
 use metaconfig_types::PushrebaseFlags;
 use metaconfig_types::PushrebaseParams;
@@ -43,28 +66,47 @@ use metaconfig_types::ServiceWriteRestrictions;
 use metaconfig_types::SourceControlServiceMonitoring;
 use metaconfig_types::SourceControlServiceParams;
 use metaconfig_types::SparseProfilesConfig;
+use metaconfig_types::TraceSamplingConfig;
 use metaconfig_types::UnodeVersion;
 use metaconfig_types::UpdateLoggingConfig;
 use metaconfig_types::WalkerConfig;
 use metaconfig_types::WalkerJobParams;
 use metaconfig_types::WalkerJobType;
+use metaconfig_types::WireprotoShadowingConfig;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
 use mononoke_types::PrefixTrie;
+use nonzero_ext::nonzero;
 use regex::Regex;
+use repos::RawAuthorPolicyConfig;
 use repos::RawBookmarkConfig;
+use repos::RawBookmarkPublishingDelay;
 use repos::RawCacheWarmupConfig;
 use repos::RawCommitIdentityScheme;
+use repos::RawCommitLimitsConfig;
+use repos::RawCommitMessageRewriteRule;
 use repos::RawCrossRepoCommitValidationConfig;
+use repos::RawDerivationQueueConfig;
 use repos::RawDerivedDataConfig;
 use repos::RawDerivedDataTypesConfig;
+use repos::RawEventSink;
+use repos::RawEventSinkUnixSocket;
+use repos::RawEventSinkWebhook;
+use repos::RawEventSubscription;
+use repos::RawFreezeWindow;
+use repos::RawGrowthAlarmsConfig;
 use repos::RawHgSyncConfig;
 use repos::RawHookConfig;
 use repos::RawHookManagerParams;
+use repos::RawHookRules;
 use repos::RawInfinitepushParams;
 use repos::RawLfsParams;
 use repos::RawLoggingDestination;
 use repos::RawLoggingDestinationScribe;
+use repos::RawMergePolicy;
+use repos::RawPathPolicyConfig;
+use repos::RawPathReadAcl;
+use repos::RawPrivateScratchNamespace;
 use repos::RawPushParams;
 use repos::RawPushrebaseParams;
 use repos::RawPushrebaseRemoteMode;
@@ -76,14 +118,20 @@ use repos::RawServiceWriteRestrictions;
 use repos::RawSourceControlServiceMonitoring;
 use repos::RawSourceControlServiceParams;
 use repos::RawSparseProfilesConfig;
+use repos::RawTraceSampling;
 use repos::RawUpdateLoggingConfig;
 use repos::RawWalkerConfig;
 use repos::RawWalkerJobParams;
 use repos::RawWalkerJobType;
+use repos::RawWireprotoShadowing;
 
 use crate::convert::Convert;
 use crate::errors::ConfigurationError;
 
+/// listkeys namespaces that repo_client serves itself, and so can't be
+/// configured.
+const BUILTIN_LISTKEYS_NAMESPACES: &[&str] = &["bookmarks", "infinitepush", "phases"];
+
 impl Convert for RawCacheWarmupConfig {
     type Output = CacheWarmupParams;
 
@@ -96,6 +144,7 @@ impl Convert for RawCacheWarmupConfig {
                 .transpose()?
                 .unwrap_or(200000),
             microwave_preload: self.microwave_preload.unwrap_or(false),
+            published_heads: self.published_heads.unwrap_or(false),
         })
     }
 }
@@ -118,16 +167,29 @@ impl Convert for RawHookConfig {
     fn convert(self) -> Result<Self::Output> {
         let bypass_commit_message = self.bypass_commit_string;
 
-        let bypass_pushvar = self
-            .bypass_pushvar
-            .map(|s| {
+        let bypass_pushvar_name = self
+            .rules
+            .as_ref()
+            .and_then(|rules| rules.bypass_pushvar_name.clone());
+
+        let bypass_pushvar = match (self.bypass_pushvar, bypass_pushvar_name) {
+            (Some(s), None) => {
                 let parts: Vec<_> = s.split('=').collect();
                 match parts.as_slice() {
-                    [name, value] => Ok((name.to_string(), value.to_string())),
-                    _ => Err(ConfigurationError::InvalidPushvar(s)),
+                    [name, value] => Some((name.to_string(), value.to_string())),
+                    _ => return Err(ConfigurationError::InvalidPushvar(s).into()),
                 }
-            })
-            .transpose()?;
+            }
+            (None, Some(name)) => Some((name, "true".to_string())),
+            (Some(_), Some(_)) => {
+                return Err(ConfigurationError::InvalidConfig(format!(
+                    "hook {} sets both bypass_pushvar and rules.bypass_pushvar_name",
+                    self.name
+                ))
+                .into());
+            }
+            (None, None) => None,
+        };
 
         let bypass = match (bypass_commit_message, bypass_pushvar) {
             (Some(msg), None) => Some(HookBypass::new_with_commit_msg(msg)),
@@ -138,6 +200,19 @@ impl Convert for RawHookConfig {
             (None, None) => None,
         };
 
+        let limits = HookExecutionLimits {
+            timeout: self
+                .timeout_ms
+                .map(|ms| ms.try_into().map(Duration::from_millis))
+                .transpose()?,
+            max_file_fetches: self.max_file_fetches.map(|n| n.try_into()).transpose()?,
+            failure_policy: if self.fail_open.unwrap_or(false) {
+                HookFailurePolicy::FailOpen
+            } else {
+                HookFailurePolicy::FailClosed
+            },
+        };
+
         let config = HookConfig {
             bypass,
             strings: self.config_strings.unwrap_or_default(),
@@ -146,6 +221,12 @@ impl Convert for RawHookConfig {
             string_lists: self.config_string_lists.unwrap_or_default(),
             int_lists: self.config_int_lists.unwrap_or_default(),
             int_64_lists: self.config_int_64_lists.unwrap_or_default(),
+            limits,
+            rules: self.rules.convert()?.unwrap_or_default(),
+            scheduling: HookScheduling {
+                run_after: self.run_after.unwrap_or_default(),
+                metadata_only: self.metadata_only.unwrap_or(false),
+            },
         };
 
         Ok(HookParams {
@@ -155,6 +236,69 @@ impl Convert for RawHookConfig {
     }
 }
 
+impl Convert for RawHookRules {
+    type Output = HookRules;
+
+    fn convert(self) -> Result<Self::Output> {
+        let globs_to_regexes = |globs: Option<Vec<String>>| {
+            globs
+                .unwrap_or_default()
+                .iter()
+                .map(|glob| glob_to_regex(glob).map(ComparableRegex::new))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        let bookmarks = self
+            .bookmarks
+            .unwrap_or_default()
+            .iter()
+            .map(|regex| {
+                Regex::new(regex)
+                    .map(ComparableRegex::new)
+                    .with_context(|| format!("invalid bookmark regex: {}", regex))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(HookRules {
+            include_paths: globs_to_regexes(self.include_paths)?,
+            exclude_paths: globs_to_regexes(self.exclude_paths)?,
+            bookmarks,
+            severity: self
+                .severity
+                .as_deref()
+                .map(HookSeverity::from_str)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Translate a path glob into an anchored regex. `*` and `?` only match
+/// within a path component, while `**` matches any number of components.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    // `**/` may also match no components at all
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).with_context(|| format!("invalid path glob: {}", glob))
+}
+
 impl Convert for RawBookmarkConfig {
     type Output = BookmarkParams;
 
@@ -198,6 +342,9 @@ impl Convert for RawBookmarkConfig {
         let allow_move_to_public_commits_without_hooks = self
             .allow_move_to_public_commits_without_hooks
             .unwrap_or(false);
+        let required_derived_data = self.required_derived_data.unwrap_or_default();
+        let merge_policy = self.merge_policy.convert()?.unwrap_or_default();
+        let publishing_delay = self.publishing_delay.convert()?;
 
         Ok(BookmarkParams {
             bookmark: bookmark_or_regex,
@@ -209,6 +356,53 @@ impl Convert for RawBookmarkConfig {
             hooks_skip_ancestors_of,
             ensure_ancestor_of,
             allow_move_to_public_commits_without_hooks,
+            required_derived_data,
+            merge_policy,
+            publishing_delay,
+        })
+    }
+}
+
+impl Convert for RawBookmarkPublishingDelay {
+    type Output = BookmarkPublishingDelay;
+
+    fn convert(self) -> Result<Self::Output> {
+        let delay = Duration::from_secs(
+            self.delay_secs
+                .try_into()
+                .context("Failed to convert publishing delay")?,
+        );
+        let approval_bookmark = self
+            .approval_bookmark
+            .map(BookmarkName::new)
+            .transpose()
+            .context("Invalid publishing approval bookmark")?;
+        Ok(BookmarkPublishingDelay {
+            delay,
+            canary_identities: self.canary_identities.convert()?.unwrap_or_default(),
+            approval_bookmark,
+        })
+    }
+}
+
+impl Convert for RawMergePolicy {
+    type Output = MergePolicy;
+
+    fn convert(self) -> Result<Self::Output> {
+        let allowed_merge_identities = self.allowed_merge_identities.convert()?;
+        let merges = match (self.deny_merges.unwrap_or(false), allowed_merge_identities) {
+            (false, None) => MergeCommitPolicy::AllowAll,
+            (false, Some(_)) => {
+                return Err(anyhow!(
+                    "allowed_merge_identities can only be set together with deny_merges"
+                ));
+            }
+            (true, None) => MergeCommitPolicy::DenyAll,
+            (true, Some(identities)) => MergeCommitPolicy::AllowFrom(identities),
+        };
+        Ok(MergePolicy {
+            merges,
+            require_linear_history: self.require_linear_history.unwrap_or(false),
         })
     }
 }
@@ -294,6 +488,9 @@ impl Convert for RawPushrebaseParams {
                     .unwrap_or(default.flags.casefolding_check),
                 not_generated_filenodes_limit: 500,
                 monitoring_bookmark: self.monitoring_bookmark,
+                serialize_per_bookmark: self
+                    .serialize_per_bookmark
+                    .unwrap_or(default.flags.serialize_per_bookmark),
             },
             commit_scribe_category: self.commit_scribe_category,
             block_merges: self.block_merges.unwrap_or(default.block_merges),
@@ -311,6 +508,36 @@ impl Convert for RawPushrebaseParams {
             remote_mode: self
                 .remote_mode
                 .map_or(Ok(default.remote_mode), Convert::convert)?,
+            commit_message_rewrite_rules: self
+                .commit_message_rewrite_rules
+                .convert()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl Convert for RawCommitMessageRewriteRule {
+    type Output = CommitMessageRewriteRule;
+
+    fn convert(self) -> Result<Self::Output> {
+        let bookmark_regex = self
+            .bookmark_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("invalid commit message rewrite bookmark regex")?
+            .map(ComparableRegex::new);
+        let strip_patterns = self
+            .strip_patterns
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| Regex::new(pattern).map(ComparableRegex::new))
+            .collect::<Result<_, _>>()
+            .context("invalid commit message rewrite strip pattern")?;
+        Ok(CommitMessageRewriteRule {
+            bookmark_regex,
+            strip_patterns,
+            append_template: self.append_template,
         })
     }
 }
@@ -340,6 +567,40 @@ impl Convert for RawInfinitepushParams {
                 .and_then(|ns| Regex::new(&ns).ok().map(InfinitepushNamespace::new)),
             hydrate_getbundle_response: self.hydrate_getbundle_response.unwrap_or(false),
             commit_scribe_category: self.commit_scribe_category,
+            private_namespace: self.private_namespace.convert()?,
+        })
+    }
+}
+
+impl Convert for RawPrivateScratchNamespace {
+    type Output = PrivateScratchNamespace;
+
+    fn convert(self) -> Result<Self::Output> {
+        let pattern = Regex::new(&self.pattern)
+            .with_context(|| format!("invalid private scratch namespace: {}", self.pattern))?;
+        if !pattern.capture_names().any(|name| name == Some("user")) {
+            return Err(anyhow!(
+                "private scratch namespace '{}' must have a capture group named 'user'",
+                self.pattern
+            ));
+        }
+        if self.owner_identity_type.is_empty() {
+            return Err(anyhow!(
+                "private scratch namespace '{}' must have an owner identity type",
+                self.pattern
+            ));
+        }
+        let shared_with = self
+            .shared_with
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(owner, identities)| Ok((owner, identities.convert()?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(PrivateScratchNamespace {
+            pattern: pattern.into(),
+            owner_identity_type: self.owner_identity_type,
+            readers: self.readers.convert()?.unwrap_or_default(),
+            shared_with,
         })
     }
 }
@@ -361,6 +622,7 @@ impl Convert for RawSourceControlServiceParams {
             service_write_hipster_acl: self.service_write_hipster_acl,
             permit_commits_without_parents: self.permit_commits_without_parents,
             service_write_restrictions,
+            raw_blob_readers: self.raw_blob_readers.unwrap_or_default().convert()?,
         })
     }
 }
@@ -458,7 +720,7 @@ impl Convert for RawDerivedDataConfig {
     type Output = DerivedDataConfig;
 
     fn convert(self) -> Result<Self::Output> {
-        Ok(DerivedDataConfig {
+        let config = DerivedDataConfig {
             scuba_table: self.scuba_table,
             enabled_config_name: self.enabled_config_name.unwrap_or_default(),
             available_configs: self
@@ -467,16 +729,147 @@ impl Convert for RawDerivedDataConfig {
                 .into_iter()
                 .map(|(s, raw_config)| Ok((s, raw_config.convert()?)))
                 .collect::<Result<_, anyhow::Error>>()?,
+            queue: self.queue.convert()?,
+        };
+        if let Some(queue) = &config.queue {
+            for derived_data_type in queue.types.iter() {
+                if !config.is_enabled(derived_data_type) {
+                    return Err(anyhow!(
+                        "Derived data type {} is queued for derivation, but is not enabled",
+                        derived_data_type
+                    ));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Convert for RawDerivationQueueConfig {
+    type Output = DerivationQueueConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let max_attempts = self
+            .max_attempts
+            .map(u32::try_from)
+            .transpose()?
+            .unwrap_or(5);
+        if max_attempts == 0 {
+            return Err(anyhow!("max_attempts must be an integer larger than zero"));
+        }
+        Ok(DerivationQueueConfig {
+            types: self.types,
+            max_attempts,
         })
     }
 }
 
+/// A limit of 0 would stop all operations of the kind, so it is rejected
+/// rather than treated as unlimited; leave the limit unset for that.
+fn convert_concurrency_limit(name: &str, limit: Option<i64>) -> Result<Option<NonZeroUsize>> {
+    limit
+        .map(|limit| {
+            NonZeroUsize::new(limit.try_into()?)
+                .ok_or_else(|| anyhow!("{} must be an integer larger than zero", name))
+        })
+        .transpose()
+}
+
 impl Convert for RawRepoClientKnobs {
     type Output = RepoClientKnobs;
 
     fn convert(self) -> Result<Self::Output> {
         Ok(RepoClientKnobs {
             allow_short_getpack_history: self.allow_short_getpack_history,
+            shadowing: self.shadowing.convert()?,
+            legacy_heads_include_scratch: self.legacy_heads_include_scratch.unwrap_or(false),
+            max_concurrent_getbundles: convert_concurrency_limit(
+                "max_concurrent_getbundles",
+                self.max_concurrent_getbundles,
+            )?,
+            max_concurrent_unbundles: convert_concurrency_limit(
+                "max_concurrent_unbundles",
+                self.max_concurrent_unbundles,
+            )?,
+            max_concurrent_hook_runs: convert_concurrency_limit(
+                "max_concurrent_hook_runs",
+                self.max_concurrent_hook_runs,
+            )?,
+            reject_legacy_changegroup_clients: self
+                .reject_legacy_changegroup_clients
+                .unwrap_or(false),
+            manifest_verification_sample_rate: self
+                .manifest_verification_sample_rate
+                .map(|rate| {
+                    NonZeroU64::new(rate.try_into()?).ok_or_else(|| {
+                        anyhow!(
+                            "manifest_verification_sample_rate must be an integer larger than zero"
+                        )
+                    })
+                })
+                .transpose()?,
+            trace_sampling: self.trace_sampling.convert()?,
+            accepted_bundle2_parts: self.accepted_bundle2_parts.unwrap_or_default(),
+            listkeys_namespaces: self
+                .listkeys_namespaces
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(namespace, keys)| {
+                    if BUILTIN_LISTKEYS_NAMESPACES.contains(&namespace.as_str()) {
+                        return Err(anyhow!(
+                            "listkeys namespace '{}' is built in and can't be configured",
+                            namespace
+                        ));
+                    }
+                    Ok((namespace, keys.into_iter().collect()))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl Convert for RawWireprotoShadowing {
+    type Output = WireprotoShadowingConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let sample_rate = self
+            .sample_rate
+            .map(|rate| {
+                NonZeroU64::new(rate.try_into()?)
+                    .ok_or_else(|| anyhow!("sample_rate must be an integer larger than zero"))
+            })
+            .transpose()?
+            .unwrap_or(nonzero!(100_u64));
+        Ok(WireprotoShadowingConfig {
+            scribe_category: self.scribe_category,
+            sample_rate,
+            commands: self.commands.unwrap_or_default(),
+        })
+    }
+}
+
+impl Convert for RawTraceSampling {
+    type Output = TraceSamplingConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let sample_rate = self
+            .sample_rate
+            .map(|rate| {
+                NonZeroU64::new(rate.try_into()?)
+                    .ok_or_else(|| anyhow!("sample_rate must be an integer larger than zero"))
+            })
+            .transpose()?;
+        let command_sample_rates = self
+            .command_sample_rates
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(command, rate)| Ok((command, rate.try_into()?)))
+            .collect::<Result<_>>()?;
+        Ok(TraceSamplingConfig {
+            sample_rate,
+            command_sample_rates,
+            full_trace_commands: self.full_trace_commands.unwrap_or_default(),
+            always_trace_identities: self.always_trace_identities.convert()?.unwrap_or_default(),
         })
     }
 }
@@ -677,6 +1070,50 @@ impl Convert for RawLoggingDestination {
     }
 }
 
+impl Convert for RawEventSink {
+    type Output = EventSink;
+
+    fn convert(self) -> Result<Self::Output> {
+        let sink = match self {
+            Self::webhook(RawEventSinkWebhook { url, max_attempts }) => EventSink::Webhook {
+                url,
+                max_attempts: max_attempts.unwrap_or(3).try_into()?,
+            },
+            Self::scribe(RawLoggingDestinationScribe { scribe_category }) => {
+                EventSink::Scribe { scribe_category }
+            }
+            Self::unix_socket(RawEventSinkUnixSocket { path }) => {
+                EventSink::UnixSocket { path: path.into() }
+            }
+            Self::UnknownField(f) => {
+                return Err(anyhow!("Unknown variant {} of RawEventSink", f));
+            }
+        };
+        Ok(sink)
+    }
+}
+
+impl Convert for RawEventSubscription {
+    type Output = EventSubscription;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(EventSubscription {
+            sink: self.sink.convert()?,
+            event_kinds: self
+                .events
+                .unwrap_or_default()
+                .iter()
+                .map(|event| event.parse())
+                .collect::<Result<_>>()?,
+            bookmark_regex: self
+                .bookmark_regex
+                .map(|re| Regex::new(&re))
+                .transpose()?
+                .map(ComparableRegex::new),
+        })
+    }
+}
+
 impl Convert for RawUpdateLoggingConfig {
     type Output = UpdateLoggingConfig;
 
@@ -684,6 +1121,145 @@ impl Convert for RawUpdateLoggingConfig {
         Ok(UpdateLoggingConfig {
             bookmark_logging_destination: self.bookmark_logging_destination.convert()?,
             new_commit_logging_destination: self.new_commit_logging_destination.convert()?,
+            event_subscriptions: self.event_subscriptions.convert()?.unwrap_or_default(),
+        })
+    }
+}
+
+impl Convert for RawPathPolicyConfig {
+    type Output = PathPolicyConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(PathPolicyConfig {
+            max_component_length: self
+                .max_component_length
+                .map(|len| len.try_into())
+                .transpose()?,
+            max_path_length: self.max_path_length.map(|len| len.try_into()).transpose()?,
+            max_depth: self.max_depth.map(|depth| depth.try_into()).transpose()?,
+            forbidden_characters: self
+                .forbidden_characters
+                .map(|chars| chars.chars().collect())
+                .unwrap_or_default(),
+            reserved_names: self.reserved_names.unwrap_or_default(),
+            reserved_names_case_insensitive: self.reserved_names_case_insensitive.unwrap_or(false),
+            forbid_absolute_symlinks: self.forbid_absolute_symlinks.unwrap_or(false),
+            forbid_escaping_symlinks: self.forbid_escaping_symlinks.unwrap_or(false),
+        })
+    }
+}
+
+impl Convert for RawCommitLimitsConfig {
+    type Output = CommitLimitsConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(CommitLimitsConfig {
+            max_files_changed: self.max_files_changed.map(|n| n.try_into()).transpose()?,
+            max_new_directory_entries: self
+                .max_new_directory_entries
+                .map(|n| n.try_into())
+                .transpose()?,
+            max_directory_manifest_size: self
+                .max_directory_manifest_size
+                .map(|n| n.try_into())
+                .transpose()?,
+            large_commit_identities: self.large_commit_identities.convert()?.unwrap_or_default(),
+        })
+    }
+}
+
+impl Convert for RawPathReadAcl {
+    type Output = PathReadAcl;
+
+    fn convert(self) -> Result<Self::Output> {
+        let path_prefix = MPath::new(self.path_prefix.as_bytes())
+            .context("path_prefix of a path read ACL must be a non-empty path")?;
+        if self.allowed_identities.is_empty() {
+            return Err(anyhow!(
+                "path read ACL for '{}' must allow at least one identity",
+                path_prefix
+            ));
+        }
+        Ok(PathReadAcl {
+            path_prefix,
+            allowed_identities: self.allowed_identities.convert()?,
+        })
+    }
+}
+
+impl Convert for RawFreezeWindow {
+    type Output = FreezeWindow;
+
+    fn convert(self) -> Result<Self::Output> {
+        let schedule = CronSchedule::from_str(&self.schedule)
+            .with_context(|| format!("invalid schedule for freeze window '{}'", self.name))?;
+        if self.duration_secs <= 0 {
+            return Err(anyhow!(
+                "duration_secs of freeze window '{}' must be positive",
+                self.name
+            ));
+        }
+        let bookmark_regex = self
+            .bookmark_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .with_context(|| format!("invalid bookmark regex for freeze window '{}'", self.name))?
+            .map(ComparableRegex::new);
+        Ok(FreezeWindow {
+            name: self.name,
+            schedule,
+            duration: Duration::from_secs(self.duration_secs.try_into()?),
+            bookmark_regex,
+            landing_queue: self.landing_queue,
+        })
+    }
+}
+
+impl Convert for RawAuthorPolicyConfig {
+    type Output = AuthorPolicyConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        let service_identities = self.service_identities.convert()?;
+        let enforcement = match (self.enforcement.as_deref(), service_identities) {
+            (None | Some("off"), None) => AuthorPolicyEnforcement::Off,
+            (Some("warn"), None) => AuthorPolicyEnforcement::Warn,
+            (Some("block"), None) => AuthorPolicyEnforcement::Block,
+            (Some("require_service_identity"), Some(identities)) if !identities.is_empty() => {
+                AuthorPolicyEnforcement::AllowFrom(identities)
+            }
+            (Some("require_service_identity"), _) => {
+                return Err(anyhow!(
+                    "author policy enforcement 'require_service_identity' requires service_identities"
+                ));
+            }
+            (None | Some("off" | "warn" | "block"), Some(_)) => {
+                return Err(anyhow!(
+                    "service_identities can only be set together with author policy enforcement 'require_service_identity'"
+                ));
+            }
+            (Some(enforcement), _) => {
+                return Err(anyhow!(
+                    "unknown author policy enforcement '{}'",
+                    enforcement
+                ));
+            }
+        };
+        Ok(AuthorPolicyConfig {
+            enforcement,
+            check_committer: self.check_committer.unwrap_or(false),
+        })
+    }
+}
+
+impl Convert for RawGrowthAlarmsConfig {
+    type Output = GrowthAlarmsConfig;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(GrowthAlarmsConfig {
+            blob_bytes_per_day: self.blob_bytes_per_day.map(|n| n.try_into()).transpose()?,
+            commits_per_day: self.commits_per_day.map(|n| n.try_into()).transpose()?,
+            bookmark_count: self.bookmark_count.map(|n| n.try_into()).transpose()?,
         })
     }
 }
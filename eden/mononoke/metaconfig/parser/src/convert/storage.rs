@@ -233,6 +233,10 @@ impl Convert for RawBlobstoreConfig {
                 blobconfig: Box::new(raw.blobstore.convert()?),
                 pack_config: raw.pack_config.map(|c| c.convert()).transpose()?,
             },
+            RawBlobstoreConfig::fallback(raw) => BlobConfig::Fallback {
+                primary: Box::new(raw.primary.convert()?),
+                secondary: Box::new(raw.secondary.convert()?),
+            },
             RawBlobstoreConfig::s3(raw) => BlobConfig::S3 {
                 bucket: raw.bucket,
                 keychain_group: raw.keychain_group,
@@ -397,6 +401,7 @@ impl Convert for RawFilestoreParams {
         Ok(FilestoreParams {
             chunk_size: self.chunk_size.try_into()?,
             concurrency: self.concurrency.try_into()?,
+            content_defined_chunking: self.content_defined_chunking.unwrap_or(false),
         })
     }
 }
@@ -13,6 +13,7 @@ pub mod config;
 mod convert;
 pub mod errors;
 mod raw;
+pub mod snapshot;
 
 pub use convert::Convert;
 
@@ -22,3 +23,5 @@ pub use crate::config::load_storage_configs;
 pub use crate::config::RepoConfigs;
 pub use crate::config::StorageConfigs;
 pub use crate::errors::ConfigurationError;
+pub use crate::snapshot::load_repo_config_snapshots;
+pub use crate::snapshot::RawRepoConfigSnapshot;
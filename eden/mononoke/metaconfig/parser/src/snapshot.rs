@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Snapshots of the raw configuration of a single repository.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use cached_config::ConfigStore;
+use metaconfig_types::RepoConfig;
+use repos::RawAclRegionConfig;
+use repos::RawRepoConfig;
+use repos::RawRepoConfigs;
+use repos::RawRepoDefinition;
+use repos::RawStorageConfig;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::parse_with_repo_definition;
+use crate::errors::ConfigurationError;
+
+/// The raw configuration that the config of a single repository is parsed
+/// from.  This can be stored and parsed again later to find out what the
+/// config of the repository was at the time the snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RawRepoConfigSnapshot {
+    /// Definition of the repository.
+    pub repo_definition: RawRepoDefinition,
+    /// Named repo configs referenced by the definition.
+    pub repo_configs: BTreeMap<String, RawRepoConfig>,
+    /// Named storage configs referenced by the repo config.
+    pub storage_configs: BTreeMap<String, RawStorageConfig>,
+    /// Named ACL region configs referenced by the definition.
+    pub acl_region_configs: BTreeMap<String, RawAclRegionConfig>,
+}
+
+impl RawRepoConfigSnapshot {
+    /// Take a snapshot of the configuration of a repository from the raw
+    /// configuration of all repositories.
+    pub fn from_raw(raw_repo_configs: &RawRepoConfigs, repo_name: &str) -> Result<Self> {
+        let repo_definition = raw_repo_configs
+            .repo_definitions
+            .repo_definitions
+            .get(repo_name)
+            .cloned()
+            .ok_or_else(|| {
+                ConfigurationError::InvalidConfig(format!(
+                    "No repo_definition for repo \"{}\"",
+                    repo_name
+                ))
+            })?;
+
+        let repo_configs: BTreeMap<_, _> = repo_definition
+            .repo_config
+            .iter()
+            .filter_map(|name| {
+                let repo_config = raw_repo_configs.repos.get(name)?;
+                Some((name.clone(), repo_config.clone()))
+            })
+            .collect();
+
+        let storage_configs = repo_configs
+            .values()
+            .filter_map(|repo_config| repo_config.storage_config.as_ref())
+            .filter_map(|name| {
+                let storage_config = raw_repo_configs.storage.get(name)?;
+                Some((name.clone(), storage_config.clone()))
+            })
+            .collect();
+
+        let acl_region_configs = repo_definition
+            .acl_region_config
+            .iter()
+            .filter_map(|name| {
+                let acl_region_config = raw_repo_configs.acl_region_configs.get(name)?;
+                Some((name.clone(), acl_region_config.clone()))
+            })
+            .collect();
+
+        Ok(Self {
+            repo_definition,
+            repo_configs,
+            storage_configs,
+            acl_region_configs,
+        })
+    }
+
+    /// Parse the repository config from the snapshot.
+    pub fn parse(&self) -> Result<RepoConfig> {
+        let repo_configs: HashMap<_, _> = self.repo_configs.clone().into_iter().collect();
+        let storage_configs: HashMap<_, _> = self.storage_configs.clone().into_iter().collect();
+        let acl_region_configs: HashMap<_, _> =
+            self.acl_region_configs.clone().into_iter().collect();
+        parse_with_repo_definition(
+            self.repo_definition.clone(),
+            &repo_configs,
+            &storage_configs,
+            &acl_region_configs,
+        )
+    }
+
+    /// Serialize the snapshot for storage.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).context("Failed to serialize repo config snapshot")
+    }
+
+    /// Deserialize a snapshot from storage.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).context("Failed to deserialize repo config snapshot")
+    }
+}
+
+/// Load snapshots of the current raw configuration of all repositories,
+/// keyed by repository name.
+pub fn load_repo_config_snapshots(
+    config_path: impl AsRef<Path>,
+    config_store: &ConfigStore,
+) -> Result<HashMap<String, RawRepoConfigSnapshot>> {
+    let raw_repo_configs = crate::raw::read_raw_configs(config_path.as_ref(), config_store)?;
+    raw_repo_configs
+        .repo_definitions
+        .repo_definitions
+        .keys()
+        .map(|repo_name| {
+            let snapshot = RawRepoConfigSnapshot::from_raw(&raw_repo_configs, repo_name)?;
+            Ok((repo_name.clone(), snapshot))
+        })
+        .collect()
+}
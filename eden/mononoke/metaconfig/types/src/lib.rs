@@ -224,6 +224,19 @@ pub struct RepoConfig {
     pub deep_sharded: bool,
     /// Configuration for update logging.
     pub update_logging_config: UpdateLoggingConfig,
+    /// Rules that the paths of new files must follow
+    pub path_policy: PathPolicyConfig,
+    /// Limits on the size of pushed commits
+    pub commit_limits: CommitLimitsConfig,
+    /// Paths whose contents may only be read by specific identities
+    pub path_read_acls: Vec<PathReadAcl>,
+    /// Scheduled windows during which pushes to publishing bookmarks are
+    /// rejected
+    pub freeze_windows: Vec<FreezeWindow>,
+    /// Policy comparing the authors of pushed commits with the pusher
+    pub author_policy: AuthorPolicyConfig,
+    /// Thresholds on the growth of the repo that fire growth alarms
+    pub growth_alarms: GrowthAlarmsConfig,
     /// Default commit identity scheme. Some repos can be hg-mirrored git repos.
     pub default_commit_identity_scheme: CommitIdentityScheme,
 }
@@ -256,10 +269,69 @@ pub struct BackupRepoConfig {
 }
 
 /// Configuration for repo_client module
-#[derive(Eq, Copy, Clone, Default, Debug, PartialEq)]
+#[derive(Eq, Clone, Default, Debug, PartialEq)]
 pub struct RepoClientKnobs {
     /// Return shorter file history in getpack call
     pub allow_short_getpack_history: bool,
+    /// Shadow a sample of read-only wireproto commands to a test tier
+    pub shadowing: Option<WireprotoShadowingConfig>,
+    /// Include scratch bookmarks in the legacy `heads` response, rather
+    /// than only publishing bookmarks.  Only the first page of scratch
+    /// bookmarks is included
+    pub legacy_heads_include_scratch: bool,
+    /// Maximum number of concurrent getbundle responses being generated
+    pub max_concurrent_getbundles: Option<NonZeroUsize>,
+    /// Maximum number of concurrent pushes being processed
+    pub max_concurrent_unbundles: Option<NonZeroUsize>,
+    /// Maximum number of concurrent hook runs
+    pub max_concurrent_hook_runs: Option<NonZeroUsize>,
+    /// Refuse getbundle requests from clients that don't support the
+    /// changegroup version the repo serves, rather than downgrading the
+    /// response for them
+    pub reject_legacy_changegroup_clients: bool,
+    /// Re-verify one in this many manifests served by gettreepack, and
+    /// quarantine the ones that fail
+    pub manifest_verification_sample_rate: Option<NonZeroU64>,
+    /// How wireproto commands are sampled for tracing
+    pub trace_sampling: Option<TraceSamplingConfig>,
+    /// Custom bundle2 part types that pushes may include, even if the
+    /// client marks them as mandatory
+    pub accepted_bundle2_parts: Vec<String>,
+    /// Additional listkeys namespaces, each served with a fixed set of keys
+    pub listkeys_namespaces: HashMap<String, HashMap<String, String>>,
+}
+
+/// Policy for deciding which wireproto commands are traced, and in how much
+/// detail.
+#[derive(Eq, Clone, Debug, Default, PartialEq)]
+pub struct TraceSamplingConfig {
+    /// Trace one in this many commands.  If unset, the
+    /// `wireproto_trace_sampling_rate` tunable is used.
+    pub sample_rate: Option<NonZeroU64>,
+    /// Sample rates for specific commands, overriding `sample_rate`.  A rate
+    /// of 0 disables tracing of the command.
+    pub command_sample_rates: HashMap<String, u64>,
+    /// Commands that are traced in full when sampled.  Other commands are
+    /// only traced at the top level.  If empty, all commands are traced in
+    /// full.
+    pub full_trace_commands: Vec<String>,
+    /// Identities whose commands are always traced in full.
+    pub always_trace_identities: Vec<Identity>,
+}
+
+/// Configuration for shadowing wireproto commands to a test tier, so that
+/// new server builds can be qualified against production traffic.
+#[derive(Eq, Clone, Debug, PartialEq)]
+pub struct WireprotoShadowingConfig {
+    /// Scribe category shadowed requests are published to. The test tier
+    /// replays the requests from this category and logs its own responses
+    /// for comparison.
+    pub scribe_category: String,
+    /// Shadow one in this many requests.
+    pub sample_rate: NonZeroU64,
+    /// Commands to shadow. If empty, no commands are shadowed. Pushes are
+    /// never shadowed.
+    pub commands: Vec<String>,
 }
 
 /// Config for derived data
@@ -273,6 +345,9 @@ pub struct DerivedDataConfig {
 
     /// All available configs for derived data types
     pub available_configs: HashMap<String, DerivedDataTypesConfig>,
+
+    /// Derived data to derive asynchronously for commits landed by pushes
+    pub queue: Option<DerivationQueueConfig>,
 }
 
 impl DerivedDataConfig {
@@ -305,6 +380,17 @@ impl DerivedDataConfig {
     }
 }
 
+/// Config for deriving data asynchronously via the derivation queue
+#[derive(Eq, Clone, Debug, PartialEq)]
+pub struct DerivationQueueConfig {
+    /// Derived data types to enqueue for derivation when a push lands
+    pub types: Vec<String>,
+
+    /// Number of attempts at deriving a type before the task is moved to
+    /// the dead letter queue
+    pub max_attempts: u32,
+}
+
 /// Config for derived data types
 #[derive(Eq, Clone, Default, Debug, PartialEq)]
 pub struct DerivedDataTypesConfig {
@@ -413,6 +499,9 @@ pub struct CacheWarmupParams {
     pub commit_limit: usize,
     /// Whether to use microwave to accelerate cache warmup.
     pub microwave_preload: bool,
+    /// Whether to also warm up the tips, ancestry and root trees of all
+    /// publishing bookmarks.
+    pub published_heads: bool,
 }
 
 /// Configuration for the hook manager
@@ -486,6 +575,55 @@ pub struct BookmarkParams {
     /// because commit is already public, meaning that hooks already
     /// should have been run when the commit was first made public.
     pub allow_move_to_public_commits_without_hooks: bool,
+    /// Derived data types that must be derived for a commit before this
+    /// bookmark can be moved to it.
+    pub required_derived_data: Vec<String>,
+    /// Restrictions on merge commits landing on this bookmark
+    pub merge_policy: MergePolicy,
+    /// Publish moves of this bookmark in stages, rather than to all clients
+    /// as soon as they land
+    pub publishing_delay: Option<BookmarkPublishingDelay>,
+}
+
+/// Staged publishing of a bookmark's moves
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BookmarkPublishingDelay {
+    /// How long after a move the bookmark's new position is published to
+    /// all clients, unless it is approved sooner
+    pub delay: Duration,
+    /// Identities that see moves of the bookmark as soon as they land
+    pub canary_identities: Vec<Identity>,
+    /// Bookmark that canary checks move to the positions of the bookmark
+    /// that they approve, which are then published straight away
+    pub approval_bookmark: Option<BookmarkName>,
+}
+
+/// Restrictions on merge commits landing on a bookmark
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct MergePolicy {
+    /// Which merge commits may be pushed or pushrebased onto the bookmark
+    pub merges: MergeCommitPolicy,
+    /// Whether the bookmark's history must be linear, i.e. the bookmark may
+    /// not be moved such that any merge commit becomes an ancestor of it,
+    /// even if the commit is already in the repo
+    pub require_linear_history: bool,
+}
+
+/// Which merge commits may be pushed or pushrebased onto a bookmark
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MergeCommitPolicy {
+    /// All merge commits are allowed
+    AllowAll,
+    /// No merge commits are allowed
+    DenyAll,
+    /// Only merge commits pushed by these identities are allowed
+    AllowFrom(Vec<Identity>),
+}
+
+impl Default for MergeCommitPolicy {
+    fn default() -> Self {
+        MergeCommitPolicy::AllowAll
+    }
 }
 
 /// The type of the hook
@@ -560,6 +698,116 @@ impl HookBypass {
     }
 }
 
+/// What the hook manager does with a hook that could not run to completion,
+/// either because it exceeded its execution limits or because it was
+/// disabled at runtime.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HookFailurePolicy {
+    /// The hook is treated as if it rejected the change
+    FailClosed,
+    /// The hook is treated as if it accepted the change
+    FailOpen,
+}
+
+impl Default for HookFailurePolicy {
+    fn default() -> Self {
+        HookFailurePolicy::FailClosed
+    }
+}
+
+/// Limits applied by the hook manager to every execution of a hook
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct HookExecutionLimits {
+    /// Maximum wall time a single execution of the hook may take
+    pub timeout: Option<Duration>,
+    /// Maximum number of file contents a single execution of the hook may
+    /// fetch
+    pub max_file_fetches: Option<u64>,
+    /// What to do when the limits are exceeded or the hook is disabled
+    pub failure_policy: HookFailurePolicy,
+}
+
+/// What the hook manager does when a hook rejects a change
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HookSeverity {
+    /// The change is rejected
+    Block,
+    /// The rejection is logged, but the change is accepted
+    Warn,
+}
+
+impl Default for HookSeverity {
+    fn default() -> Self {
+        HookSeverity::Block
+    }
+}
+
+impl FromStr for HookSeverity {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "block" => Ok(HookSeverity::Block),
+            "warn" => Ok(HookSeverity::Warn),
+            _ => Err(anyhow!("Unable to parse {} as {}", string, "HookSeverity")),
+        }
+    }
+}
+
+/// Filters and behaviors common to all hooks, applied by the hook manager
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct HookRules {
+    /// Paths the hook applies to. If empty, the hook applies to all paths
+    pub include_paths: Vec<ComparableRegex>,
+    /// Paths the hook never applies to, even if they are included
+    pub exclude_paths: Vec<ComparableRegex>,
+    /// Bookmarks the hook applies to. If empty, the hook applies to all
+    /// bookmarks it is enabled for
+    pub bookmarks: Vec<ComparableRegex>,
+    /// What to do when the hook rejects a change
+    pub severity: HookSeverity,
+}
+
+impl HookRules {
+    /// Whether the hook applies to changes to the given path
+    pub fn applies_to_path(&self, path: &MPath) -> bool {
+        if self.include_paths.is_empty() && self.exclude_paths.is_empty() {
+            return true;
+        }
+        let path = path.to_string();
+        (self.include_paths.is_empty() || self.include_paths.iter().any(|re| re.is_match(&path)))
+            && !self.exclude_paths.iter().any(|re| re.is_match(&path))
+    }
+
+    /// Whether the hook applies to any of the given paths
+    pub fn applies_to_any_path<'a>(&self, mut paths: impl Iterator<Item = &'a MPath>) -> bool {
+        if self.include_paths.is_empty() && self.exclude_paths.is_empty() {
+            return true;
+        }
+        paths.any(|path| self.applies_to_path(path))
+    }
+
+    /// Whether the hook applies to pushes to the given bookmark
+    pub fn applies_to_bookmark(&self, bookmark: &BookmarkName) -> bool {
+        self.bookmarks.is_empty()
+            || self
+                .bookmarks
+                .iter()
+                .any(|re| re.is_match(bookmark.as_str()))
+    }
+}
+
+/// How the hook manager schedules a hook relative to the other hooks
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct HookScheduling {
+    /// Hooks that must have run on a changeset before this hook runs on it.
+    /// If any of them rejects the changeset, this hook is skipped for it
+    pub run_after: Vec<String>,
+    /// The hook only looks at which files changed and their sizes, so it
+    /// is not allowed to fetch file contents
+    pub metadata_only: bool,
+}
+
 /// Configs that are being passed to the hook during runtime
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct HookConfig {
@@ -577,6 +825,12 @@ pub struct HookConfig {
     pub int_lists: HashMap<String, Vec<i32>>,
     /// Map of config to it's value. Values here are lists of 64bit integers
     pub int_64_lists: HashMap<String, Vec<i64>>,
+    /// Execution limits enforced by the hook manager
+    pub limits: HookExecutionLimits,
+    /// Filters and severity applied by the hook manager
+    pub rules: HookRules,
+    /// Ordering and data needs used to schedule the hook
+    pub scheduling: HookScheduling,
 }
 
 /// Configuration for a hook
@@ -621,6 +875,9 @@ pub struct PushrebaseFlags {
     pub not_generated_filenodes_limit: u64,
     /// Which bookmark to track in ODS
     pub monitoring_bookmark: Option<String>,
+    /// Whether pushrebases onto the same bookmark should wait for each
+    /// other instead of racing for the bookmark move
+    pub serialize_per_bookmark: bool,
 }
 
 impl Default for PushrebaseFlags {
@@ -632,6 +889,7 @@ impl Default for PushrebaseFlags {
             casefolding_check: true,
             not_generated_filenodes_limit: 500,
             monitoring_bookmark: None,
+            serialize_per_bookmark: false,
         }
     }
 }
@@ -683,6 +941,9 @@ pub struct PushrebaseParams {
     pub allow_change_xrepo_mapping_extra: bool,
     /// How to do pushrebase on Mononoke
     pub remote_mode: PushrebaseRemoteMode,
+    /// Rules for rewriting the messages of pushrebased commits, applied in
+    /// order.  Empty if messages are never rewritten.
+    pub commit_message_rewrite_rules: Vec<CommitMessageRewriteRule>,
 }
 
 impl Default for PushrebaseParams {
@@ -696,10 +957,26 @@ impl Default for PushrebaseParams {
             populate_git_mapping: false,
             allow_change_xrepo_mapping_extra: false,
             remote_mode: PushrebaseRemoteMode::Local,
+            commit_message_rewrite_rules: Vec::new(),
         }
     }
 }
 
+/// A rule for rewriting the messages of commits as they are pushrebased
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CommitMessageRewriteRule {
+    /// Only rewrite commits pushrebased onto bookmarks matching this regex.
+    /// If None, the rule applies to all bookmarks.
+    pub bookmark_regex: Option<ComparableRegex>,
+    /// Text matching these regexes is removed from the message
+    pub strip_patterns: Vec<ComparableRegex>,
+    /// Template for a line appended to the message.  `{bookmark}` is replaced
+    /// with the bookmark pushrebased onto, `{push_id}` with the id of the
+    /// pushing session, and `{original_commit}` with the id of the commit
+    /// before it was pushrebased.
+    pub append_template: Option<String>,
+}
+
 /// LFS configuration options
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct LfsParams {
@@ -902,6 +1179,15 @@ pub enum BlobConfig {
         /// Optional configuration for setting things like default compression levels
         pack_config: Option<PackConfig>,
     },
+    /// A blobstore that reads blobs missing from the primary blobstore from
+    /// the secondary one, and copies them into the primary.  Used to migrate
+    /// between storages.
+    Fallback {
+        /// The config for the blobstore that is read first and written to.
+        primary: Box<BlobConfig>,
+        /// The config for the blobstore that missing blobs are read from.
+        secondary: Box<BlobConfig>,
+    },
     /// Store in a S3 compatible storage
     S3 {
         /// Bucket to connect to
@@ -934,6 +1220,7 @@ impl BlobConfig {
                 .all(BlobConfig::is_local),
             Logging { blobconfig, .. } => blobconfig.is_local(),
             Pack { blobconfig, .. } => blobconfig.is_local(),
+            Fallback { primary, secondary } => primary.is_local() && secondary.is_local(),
         }
     }
 
@@ -1156,6 +1443,54 @@ pub struct InfinitepushParams {
 
     /// Scribe category we log new commits to
     pub commit_scribe_category: Option<String>,
+
+    /// Scratch bookmarks whose commits are private to the user that owns
+    /// them. If None, all scratch commits can be read by anyone with read
+    /// access to the repo.
+    pub private_namespace: Option<PrivateScratchNamespace>,
+}
+
+/// Scratch bookmarks that are private to the user they are named after.
+///
+/// While they are draft, commits pushed to these bookmarks may only be read
+/// by their owner and the identities they have been shared with.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PrivateScratchNamespace {
+    /// Regex matching private scratch bookmarks, with a capture group named
+    /// `user` that matches the user name of the bookmark's owner.
+    pub pattern: ComparableRegex,
+    /// Identity type that owners' user names are matched against.
+    pub owner_identity_type: String,
+    /// Identities that may read all private scratch commits.
+    pub readers: Vec<Identity>,
+    /// Identities that each owner has shared their private scratch commits
+    /// with, keyed by the owner's user name.
+    pub shared_with: HashMap<String, Vec<Identity>>,
+}
+
+impl PrivateScratchNamespace {
+    /// Returns the user name of the owner of a bookmark, if it is a private
+    /// scratch bookmark.
+    pub fn owner<'a>(&self, bookmark: &'a BookmarkName) -> Option<&'a str> {
+        self.pattern
+            .captures(bookmark.as_str())?
+            .name("user")
+            .map(|user| user.as_str())
+    }
+
+    /// Returns the identities that may read the private scratch commits of
+    /// an owner, including the owner themselves.
+    pub fn permitted_identities(&self, owner: &str) -> Vec<Identity> {
+        let mut identities = vec![Identity {
+            id_type: self.owner_identity_type.clone(),
+            id_data: owner.to_string(),
+        }];
+        identities.extend(self.readers.iter().cloned());
+        if let Some(shared_with) = self.shared_with.get(owner) {
+            identities.extend(shared_with.iter().cloned());
+        }
+        identities
+    }
 }
 
 /// Filestore configuration.
@@ -1165,6 +1500,9 @@ pub struct FilestoreParams {
     pub chunk_size: u64,
     /// Max number of concurrent chunk uploads to perform in the Filestore.
     pub concurrency: usize,
+    /// Whether to split files at content-defined boundaries, with chunks of
+    /// at most `chunk_size`.
+    pub content_defined_chunking: bool,
 }
 
 /// Default path action to perform when syncing commits
@@ -1291,6 +1629,10 @@ pub struct SourceControlServiceParams {
 
     /// Whether users can create commits without parents.
     pub permit_commits_without_parents: bool,
+
+    /// Identities that may fetch raw blobs from the repo's blobstore by key,
+    /// for debugging.
+    pub raw_blob_readers: Vec<Identity>,
 }
 
 impl SourceControlServiceParams {
@@ -1730,4 +2072,234 @@ pub struct UpdateLoggingConfig {
     pub bookmark_logging_destination: Option<LoggingDestination>,
     /// Destination where new commits are logged to
     pub new_commit_logging_destination: Option<LoggingDestination>,
+    /// External subscribers notified of events in the repo
+    pub event_subscriptions: Vec<EventSubscription>,
+}
+
+/// Rules that the paths of files added to the repo must follow
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct PathPolicyConfig {
+    /// Maximum length in bytes of each path component
+    pub max_component_length: Option<usize>,
+    /// Maximum length in bytes of the whole path
+    pub max_path_length: Option<usize>,
+    /// Maximum number of components in a path
+    pub max_depth: Option<usize>,
+    /// Characters that may not appear anywhere in a path
+    pub forbidden_characters: Vec<char>,
+    /// Path components that may not be used
+    pub reserved_names: Vec<String>,
+    /// Whether reserved names are matched regardless of case
+    pub reserved_names_case_insensitive: bool,
+    /// Whether symlinks may not point to absolute paths
+    pub forbid_absolute_symlinks: bool,
+    /// Whether symlinks may not point outside of the repo
+    pub forbid_escaping_symlinks: bool,
+}
+
+/// Limits on the size of the commits pushed to the repo
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct CommitLimitsConfig {
+    /// Maximum number of files a commit may change
+    pub max_files_changed: Option<u64>,
+    /// Maximum number of entries a commit may add to a single directory
+    pub max_new_directory_entries: Option<u64>,
+    /// Maximum size in bytes of the manifest of any directory a commit changes
+    pub max_directory_manifest_size: Option<u64>,
+    /// Identities that may push commits over the limits with the
+    /// ALLOW_LARGE_COMMITS pushvar
+    pub large_commit_identities: Vec<Identity>,
+}
+
+/// A path prefix whose contents may only be read by the listed identities,
+/// even if they have read access to the rest of the repo
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PathReadAcl {
+    /// The protected path; everything under it is protected
+    pub path_prefix: MPath,
+    /// Identities that are permitted to read the protected path
+    pub allowed_identities: Vec<Identity>,
+}
+
+/// A cron expression with the fields
+/// "sec min hour day-of-month month day-of-week [year]", parsed when the
+/// config is loaded.  Schedules are compared by their expression.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    expression: String,
+    schedule: cron::Schedule,
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        Ok(CronSchedule {
+            expression: expression.to_string(),
+            schedule: cron::Schedule::from_str(expression)?,
+        })
+    }
+}
+
+impl Deref for CronSchedule {
+    type Target = cron::Schedule;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.schedule
+    }
+}
+
+impl PartialEq for CronSchedule {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+    }
+}
+
+impl Eq for CronSchedule {}
+
+/// A recurring window during which pushes to publishing bookmarks are
+/// rejected
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FreezeWindow {
+    /// Name of the window, reported to clients whose pushes are rejected
+    pub name: String,
+    /// When the window starts, evaluated in UTC
+    pub schedule: CronSchedule,
+    /// How long the window lasts once it starts
+    pub duration: Duration,
+    /// If set, the window only applies to matching bookmarks
+    pub bookmark_regex: Option<ComparableRegex>,
+    /// Landing queue that clients should send their changes to instead
+    /// while the window is active
+    pub landing_queue: Option<String>,
+}
+
+impl FreezeWindow {
+    /// Whether the window applies to this bookmark
+    pub fn applies_to(&self, bookmark: &BookmarkName) -> bool {
+        self.bookmark_regex
+            .as_ref()
+            .map_or(true, |regex| regex.is_match(bookmark.as_str()))
+    }
+}
+
+/// Policy comparing the authors of commits pushed to publishing bookmarks
+/// with the identity of the pusher
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct AuthorPolicyConfig {
+    /// What happens to commits whose author doesn't match the pusher
+    pub enforcement: AuthorPolicyEnforcement,
+    /// Whether the committer must match the pusher as well as the author
+    pub check_committer: bool,
+}
+
+/// How commits whose author doesn't match the pusher are handled
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AuthorPolicyEnforcement {
+    /// Authors are not checked
+    Off,
+    /// Mismatches are logged, but the commits are accepted
+    Warn,
+    /// Commits with mismatches are rejected
+    Block,
+    /// Commits with mismatches are only accepted from these service
+    /// identities, which push on behalf of others
+    AllowFrom(Vec<Identity>),
+}
+
+impl Default for AuthorPolicyEnforcement {
+    fn default() -> Self {
+        AuthorPolicyEnforcement::Off
+    }
+}
+
+/// Thresholds on the growth of a repo. A growth alarm fires when one of
+/// them is exceeded.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct GrowthAlarmsConfig {
+    /// Total size of the files changed by new commits per day
+    pub blob_bytes_per_day: Option<u64>,
+    /// Number of new commits per day, including draft commits
+    pub commits_per_day: Option<u64>,
+    /// Number of bookmarks, including scratch bookmarks
+    pub bookmark_count: Option<u64>,
+}
+
+/// Kinds of repo events that can be subscribed to
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RepoEventKind {
+    /// A push or pushrebase moved a bookmark
+    PushAccepted,
+    /// A bookmark was created, moved or deleted
+    BookmarkMoved,
+    /// A hook rejected a changeset
+    HookRejected,
+    /// A redaction key list was created for the repo
+    RedactionAdded,
+    /// A threshold on the growth of the repo was exceeded
+    GrowthAlarm,
+}
+
+impl FromStr for RepoEventKind {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "push_accepted" => Ok(RepoEventKind::PushAccepted),
+            "bookmark_moved" => Ok(RepoEventKind::BookmarkMoved),
+            "hook_rejected" => Ok(RepoEventKind::HookRejected),
+            "redaction_added" => Ok(RepoEventKind::RedactionAdded),
+            "growth_alarm" => Ok(RepoEventKind::GrowthAlarm),
+            _ => Err(anyhow!("Unable to parse {} as {}", string, "RepoEventKind")),
+        }
+    }
+}
+
+/// Where repo events are delivered to
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EventSink {
+    /// Events are POSTed as JSON to an HTTP endpoint
+    Webhook {
+        /// URL to POST events to
+        url: String,
+        /// How many times delivery is attempted before giving up
+        max_attempts: u32,
+    },
+    /// Events are logged as JSON to scribe
+    Scribe {
+        /// Scribe category events are logged to
+        scribe_category: String,
+    },
+    /// Events are written as JSON lines to a unix socket
+    UnixSocket {
+        /// Path of the socket
+        path: PathBuf,
+    },
+}
+
+/// A subscription to repo events
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventSubscription {
+    /// Where the events are delivered
+    pub sink: EventSink,
+    /// Which kinds of events are delivered. If empty, all events are
+    pub event_kinds: Vec<RepoEventKind>,
+    /// If set, only events about matching bookmarks (and events that are
+    /// not about a bookmark) are delivered
+    pub bookmark_regex: Option<ComparableRegex>,
+}
+
+impl EventSubscription {
+    /// Whether an event of this kind, optionally about this bookmark, should
+    /// be delivered to this subscription
+    pub fn matches(&self, kind: RepoEventKind, bookmark: Option<&str>) -> bool {
+        if !self.event_kinds.is_empty() && !self.event_kinds.contains(&kind) {
+            return false;
+        }
+        match (&self.bookmark_regex, bookmark) {
+            (Some(regex), Some(bookmark)) => regex.is_match(bookmark),
+            _ => true,
+        }
+    }
 }
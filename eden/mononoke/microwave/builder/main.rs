@@ -132,6 +132,7 @@ async fn async_main(app: MononokeApp) -> Result<(), Error> {
                                 bookmark,
                                 commit_limit,
                                 microwave_preload,
+                                published_heads,
                             } = params;
 
                             let target = cache_warmup_target(&warmup_ctx, &repo, &bookmark).await?;
@@ -140,6 +141,7 @@ async fn async_main(app: MononokeApp) -> Result<(), Error> {
                                 target,
                                 commit_limit,
                                 microwave_preload,
+                                published_heads,
                             })
                         }
                         None => None,
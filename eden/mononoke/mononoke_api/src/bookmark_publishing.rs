@@ -0,0 +1,243 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Staged publishing of bookmark moves.
+//!
+//! Moves of bookmarks that are configured with a publishing delay are seen
+//! straight away by the bookmark's canary identities, but other clients
+//! keep seeing an earlier position of the bookmark until the move is older
+//! than the delay, or until the bookmark's approval bookmark is moved to it.
+//! The position other clients see is always one the bookmark actually had,
+//! found by walking back through the bookmark's update log.  If no such
+//! position can be found, the bookmark can't be read rather than show a
+//! position that hasn't been published.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::bail;
+use anyhow::Result;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkUpdateLogRef;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use context::CoreContext;
+use futures::TryStreamExt;
+use metaconfig_types::BookmarkOrRegex;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPublishingDelay;
+use metaconfig_types::Identity;
+use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+
+/// Number of moves of a bookmark that are read from its update log at a
+/// time.
+const LOG_PAGE_SIZE: u32 = 100;
+
+/// Maximum number of pages of a bookmark's update log that are examined to
+/// find the position that has been published.
+const MAX_LOG_PAGES: u32 = 10;
+
+/// How long the published position of a bookmark is reused for before the
+/// update log is examined again.
+const PUBLISHED_POSITION_TTL: Duration = Duration::from_secs(5);
+
+/// Whether a published position found at `found_at` can still be reused.
+fn is_fresh(found_at: SystemTime, now: SystemTime) -> bool {
+    now.duration_since(found_at).unwrap_or_default() < PUBLISHED_POSITION_TTL
+}
+
+/// Where a bookmark stands in its publishing, as found in its update log.
+struct PublishedPosition {
+    /// The newest position of the bookmark that has been published.
+    published: Option<ChangesetId>,
+    /// The positions the bookmark has had since, which haven't been
+    /// published yet.
+    unpublished: HashSet<Option<ChangesetId>>,
+}
+
+impl PublishedPosition {
+    /// The position that has been published, given the bookmark's current
+    /// position.
+    fn resolve(&self, current: Option<ChangesetId>) -> Option<ChangesetId> {
+        if self.unpublished.contains(&current) {
+            self.published
+        } else {
+            // The current position is the published one, or older than it,
+            // e.g. because it was read from a cache that is behind the log.
+            current
+        }
+    }
+}
+
+/// The published positions of a repo's delayed bookmarks, keyed by the
+/// bookmark and its current position.  They are shared by all of the repo's
+/// clients so that each request doesn't have to go through the update log
+/// again.
+#[derive(Default)]
+pub struct PublishedBookmarks {
+    positions:
+        Mutex<HashMap<(BookmarkName, Option<ChangesetId>), (Option<ChangesetId>, SystemTime)>>,
+}
+
+impl PublishedBookmarks {
+    fn get(
+        &self,
+        bookmark: &BookmarkName,
+        current: Option<ChangesetId>,
+        now: SystemTime,
+    ) -> Option<Option<ChangesetId>> {
+        self.positions
+            .lock()
+            .expect("lock poisoned")
+            .get(&(bookmark.clone(), current))
+            .filter(|(_, found_at)| is_fresh(*found_at, now))
+            .map(|(published, _)| *published)
+    }
+
+    fn insert(
+        &self,
+        bookmark: BookmarkName,
+        current: Option<ChangesetId>,
+        published: Option<ChangesetId>,
+        now: SystemTime,
+    ) {
+        let mut positions = self.positions.lock().expect("lock poisoned");
+        positions.retain(|_, (_, found_at)| is_fresh(*found_at, now));
+        positions.insert((bookmark, current), (published, now));
+    }
+}
+
+/// Decides which position of each bookmark a client sees.
+#[derive(Clone, Default)]
+pub struct BookmarkPublishingGate {
+    /// The publishing delays of the bookmarks the client isn't a canary
+    /// for.
+    delays: Vec<(BookmarkOrRegex, BookmarkPublishingDelay)>,
+    published: Arc<PublishedBookmarks>,
+}
+
+impl BookmarkPublishingGate {
+    pub fn new(
+        bookmarks: &[BookmarkParams],
+        identities: &MononokeIdentitySet,
+        published: Arc<PublishedBookmarks>,
+    ) -> Self {
+        let delays = bookmarks
+            .iter()
+            .filter_map(|params| {
+                let publishing_delay = params.publishing_delay.as_ref()?;
+                let is_canary = publishing_delay.canary_identities.iter().any(
+                    |Identity { id_type, id_data }| {
+                        identities.contains(&MononokeIdentity::new(id_type, id_data))
+                    },
+                );
+                (!is_canary).then(|| (params.bookmark.clone(), publishing_delay.clone()))
+            })
+            .collect();
+        Self { delays, published }
+    }
+
+    /// Whether moves of a bookmark are published to this client after a
+    /// delay.
+    pub fn is_delayed(&self, bookmark: &BookmarkName) -> bool {
+        self.delay(bookmark).is_some()
+    }
+
+    fn delay(&self, bookmark: &BookmarkName) -> Option<&BookmarkPublishingDelay> {
+        self.delays
+            .iter()
+            .find(|(bookmark_or_regex, _)| bookmark_or_regex.matches(bookmark))
+            .map(|(_, delay)| delay)
+    }
+
+    /// The position of a bookmark that has been published to this client,
+    /// given the bookmark's current position.
+    pub async fn published_position(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl BookmarksRef + BookmarkUpdateLogRef),
+        bookmark: &BookmarkName,
+        current: Option<ChangesetId>,
+    ) -> Result<Option<ChangesetId>> {
+        let delay = match self.delay(bookmark) {
+            Some(delay) => delay,
+            None => return Ok(current),
+        };
+        if let Some(published) = self.published.get(bookmark, current, ctx.now()) {
+            return Ok(published);
+        }
+        let published = find_published_position(ctx, repo, bookmark, delay)
+            .await?
+            .resolve(current);
+        self.published
+            .insert(bookmark.clone(), current, published, ctx.now());
+        Ok(published)
+    }
+}
+
+async fn find_published_position(
+    ctx: &CoreContext,
+    repo: &(impl BookmarksRef + BookmarkUpdateLogRef),
+    bookmark: &BookmarkName,
+    delay: &BookmarkPublishingDelay,
+) -> Result<PublishedPosition> {
+    let approved = match &delay.approval_bookmark {
+        Some(approval_bookmark) => repo.bookmarks().get(ctx.clone(), approval_bookmark).await?,
+        None => None,
+    };
+
+    let now = Timestamp::from(ctx.now());
+    let mut unpublished = HashSet::new();
+    for page in 0..MAX_LOG_PAGES {
+        // Moves are listed from the newest to the oldest.
+        let moves = repo
+            .bookmark_update_log()
+            .list_bookmark_log_entries(
+                ctx.clone(),
+                bookmark.clone(),
+                LOG_PAGE_SIZE,
+                Some(page * LOG_PAGE_SIZE),
+                Freshness::MaybeStale,
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        for (_, cs_id, _, timestamp) in moves.iter() {
+            let age = now.timestamp_seconds() - timestamp.timestamp_seconds();
+            let published =
+                age >= delay.delay.as_secs() as i64 || (cs_id.is_some() && *cs_id == approved);
+            if published {
+                return Ok(PublishedPosition {
+                    published: *cs_id,
+                    unpublished,
+                });
+            }
+            unpublished.insert(*cs_id);
+        }
+        if moves.len() < LOG_PAGE_SIZE as usize {
+            // None of the bookmark's logged moves have been published, so it
+            // didn't exist for other clients before them.  If it hasn't
+            // moved since before the log was kept, there is nothing to hide.
+            return Ok(PublishedPosition {
+                published: None,
+                unpublished,
+            });
+        }
+    }
+
+    bail!(
+        "None of the last {} moves of bookmark {} have been published",
+        LOG_PAGE_SIZE * MAX_LOG_PAGES,
+        bookmark
+    );
+}
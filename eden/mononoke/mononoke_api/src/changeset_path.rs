@@ -17,6 +17,8 @@ use blobstore::Blobstore;
 use blobstore::Loadable;
 use bytes::Bytes;
 use changeset_info::ChangesetInfo;
+use chrono::DateTime;
+use chrono::FixedOffset;
 use cloned::cloned;
 use context::CoreContext;
 use deleted_manifest::DeletedManifestOps;
@@ -76,6 +78,28 @@ pub enum PathEntry {
     File(FileContext, FileType),
 }
 
+/// Aggregate information about all of the files at or under a path.
+///
+/// All of this comes from derived data that is maintained incrementally for
+/// each commit (fsnodes for the sizes and counts, and unodes for the last
+/// modification), so it is cheap to compute for any path, including the
+/// root of the repository.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PathRollup {
+    /// Total size of all files at or under the path.
+    pub total_size: u64,
+
+    /// Number of files at or under the path.
+    pub file_count: u64,
+
+    /// The last commit that modified any file at or under the path.
+    pub last_modified: ChangesetId,
+
+    /// The author date of `last_modified`.  This is the equivalent of the
+    /// most recent modification time of the files under the path.
+    pub last_modified_date: DateTime<FixedOffset>,
+}
+
 type UnodeResult = Result<Option<Entry<ManifestUnodeId, FileUnodeId>>, MononokeError>;
 type FsnodeResult = Result<Option<Entry<FsnodeId, FsnodeFile>>, MononokeError>;
 type SkeletonResult = Result<Option<Entry<SkeletonManifestId, ()>>, MononokeError>;
@@ -300,6 +324,41 @@ impl ChangesetPathContentContext {
         };
         Ok(entry)
     }
+
+    /// Returns the total size and number of files at or under this path,
+    /// together with the last commit that modified any of them.  Returns
+    /// `None` if nothing exists at this path.
+    pub async fn rollup(&self) -> Result<Option<PathRollup>, MononokeError> {
+        let (total_size, file_count) = match self.fsnode_id().await? {
+            Some(Entry::Tree(fsnode_id)) => {
+                let ctx = self.changeset.ctx();
+                let blobstore = self.repo().blob_repo().blobstore();
+                let fsnode = fsnode_id.load(ctx, blobstore).await?;
+                let summary = fsnode.summary();
+                (
+                    summary.descendant_files_total_size,
+                    summary.descendant_files_count,
+                )
+            }
+            Some(Entry::Leaf(file)) => (file.size(), 1),
+            None => return Ok(None),
+        };
+        let history =
+            ChangesetPathHistoryContext::new(self.changeset.clone(), self.path.clone()).await?;
+        let last_modified = history.last_modified().await?.ok_or_else(|| {
+            MononokeError::from(anyhow!(
+                "Path {} exists in {} but has no last modification",
+                self.path,
+                self.changeset.id()
+            ))
+        })?;
+        Ok(Some(PathRollup {
+            total_size,
+            file_count,
+            last_modified: last_modified.id(),
+            last_modified_date: last_modified.author_date().await?,
+        }))
+    }
 }
 
 impl ChangesetPathHistoryContext {
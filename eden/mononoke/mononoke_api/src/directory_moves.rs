@@ -0,0 +1,240 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use futures::future;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use futures::try_join;
+use manifest::Diff;
+use manifest::Entry;
+use manifest::ManifestOps;
+use manifest::PathOrPrefix;
+use mononoke_types::fsnode::FsnodeFile;
+use mononoke_types::FsnodeId;
+use mononoke_types::MPath;
+
+use crate::changeset::ChangesetContext;
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+
+/// Minimum similarity of the contents of two directories that are not
+/// identical for one to be considered a move of the other.
+const MIN_DIRECTORY_SIMILARITY: f64 = 0.5;
+
+/// Directories with more files than this are only considered moves if their
+/// contents are identical.
+const MAX_SIMILARITY_FILES: usize = 10_000;
+
+/// Maximum number of pairs of removed and added directories whose contents
+/// are compared for similarity.
+const MAX_SIMILARITY_CANDIDATES: usize = 100;
+
+/// A directory that was moved, possibly with changes to its contents.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DirectoryMove {
+    /// The path of the directory before it was moved.
+    pub from: MononokePath,
+    /// The path of the directory after it was moved.
+    pub to: MononokePath,
+    /// Whether the contents of the directory are unchanged by the move.
+    pub exact: bool,
+}
+
+impl ChangesetContext {
+    /// Find the directories that were moved between `other` and this
+    /// commit.
+    ///
+    /// A directory was moved if it was removed and another directory was
+    /// added whose contents are identical, which is found by comparing
+    /// their manifest ids.  Otherwise, directories whose files mostly have
+    /// the same paths relative to the directory are also considered moves.
+    /// Only the outermost moved directories are returned: the directories
+    /// within them are implied to have moved with them.
+    pub async fn directory_moves(
+        &self,
+        other: &ChangesetContext,
+    ) -> Result<Vec<DirectoryMove>, MononokeError> {
+        let (root, other_root) = try_join!(self.root_fsnode_id(), other.root_fsnode_id())?;
+        let blobstore = self.repo().blob_repo().get_blobstore();
+        let (added, removed) = other_root
+            .fsnode_id()
+            .diff(self.ctx().clone(), blobstore, *root.fsnode_id())
+            .try_fold(
+                (HashMap::new(), HashMap::new()),
+                |(mut added, mut removed), diff| async move {
+                    match diff {
+                        Diff::Added(Some(path), Entry::Tree(id)) => {
+                            added.insert(path, id);
+                        }
+                        Diff::Removed(Some(path), Entry::Tree(id)) => {
+                            removed.insert(path, id);
+                        }
+                        _ => {}
+                    }
+                    Ok((added, removed))
+                },
+            )
+            .await?;
+
+        // Directories with identical contents have the same manifest id.
+        // Match removed directories before the directories within them, so
+        // that only the outermost moved directories are reported.
+        let mut removed: Vec<(MPath, FsnodeId)> = removed.into_iter().collect();
+        removed.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut added_by_id: HashMap<FsnodeId, Vec<&MPath>> = HashMap::new();
+        let mut added_paths: Vec<&MPath> = added.keys().collect();
+        added_paths.sort();
+        for path in added_paths.into_iter().rev() {
+            added_by_id.entry(added[path]).or_default().push(path);
+        }
+        let mut moves = Vec::new();
+        let mut moved_from = HashSet::new();
+        let mut moved_to = HashSet::new();
+        for (path, id) in removed.iter() {
+            if is_within(path, &moved_from) {
+                continue;
+            }
+            let to = added_by_id.get_mut(id).and_then(|paths| {
+                while let Some(to) = paths.pop() {
+                    if !is_within(to, &moved_to) {
+                        return Some(to);
+                    }
+                }
+                None
+            });
+            if let Some(to) = to {
+                moved_from.insert(path.clone());
+                moved_to.insert(to.clone());
+                moves.push(DirectoryMove {
+                    from: MononokePath::new(Some(path.clone())),
+                    to: MononokePath::new(Some(to.clone())),
+                    exact: true,
+                });
+            }
+        }
+
+        let unmatched_removed = outermost(
+            removed
+                .iter()
+                .map(|(path, _)| path)
+                .filter(|path| !is_within(path, &moved_from)),
+        );
+        let unmatched_added = outermost(added.keys().filter(|path| {
+            !is_within(path, &moved_to) && !moved_to.iter().any(|to| path.is_prefix_of(to))
+        }));
+
+        // Fall back to comparing the files of the remaining directories.
+        let candidates = unmatched_added.len() * unmatched_removed.len();
+        if candidates > 0 && candidates <= MAX_SIMILARITY_CANDIDATES {
+            let (added_files, removed_files) = try_join!(
+                future::try_join_all(unmatched_added.iter().map(|dir| files_under(self, dir))),
+                future::try_join_all(unmatched_removed.iter().map(|dir| files_under(other, dir))),
+            )?;
+            let mut used = HashSet::new();
+            for (from, from_files) in unmatched_removed.into_iter().zip(removed_files.iter()) {
+                let from_files = match from_files {
+                    Some(from_files) => from_files,
+                    None => continue,
+                };
+                let best = unmatched_added
+                    .iter()
+                    .zip(added_files.iter())
+                    .enumerate()
+                    .filter(|(index, _)| !used.contains(index))
+                    .filter_map(|(index, (to, to_files))| {
+                        let similarity = similarity(from_files, to_files.as_ref()?);
+                        (similarity >= MIN_DIRECTORY_SIMILARITY).then(|| (index, *to, similarity))
+                    })
+                    .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+                if let Some((index, to, _)) = best {
+                    used.insert(index);
+                    moves.push(DirectoryMove {
+                        from: MononokePath::new(Some(from.clone())),
+                        to: MononokePath::new(Some(to.clone())),
+                        exact: false,
+                    });
+                }
+            }
+        }
+
+        moves.sort_by(|a, b| a.from.cmp(&b.from));
+        Ok(moves)
+    }
+}
+
+/// Whether a path is one of `dirs`, or within one of them.
+fn is_within(path: &MPath, dirs: &HashSet<MPath>) -> bool {
+    path.clone()
+        .into_parent_dir_iter()
+        .any(|dir| dirs.contains(&dir))
+}
+
+/// The directories that are not within any of the other directories, in
+/// order.
+fn outermost<'a>(dirs: impl Iterator<Item = &'a MPath>) -> Vec<&'a MPath> {
+    let dirs: HashSet<&MPath> = dirs.collect();
+    let mut outermost: Vec<_> = dirs
+        .iter()
+        .filter(|path| {
+            (**path)
+                .clone()
+                .into_parent_dir_iter()
+                .skip(1)
+                .all(|parent| !dirs.contains(&parent))
+        })
+        .copied()
+        .collect();
+    outermost.sort();
+    outermost
+}
+
+/// The files in a directory, by their path relative to the directory, or
+/// `None` if there are too many of them to compare.
+async fn files_under(
+    changeset: &ChangesetContext,
+    dir: &MPath,
+) -> Result<Option<HashMap<MPath, FsnodeFile>>, MononokeError> {
+    let root = changeset.root_fsnode_id().await?;
+    let blobstore = changeset.repo().blob_repo().get_blobstore();
+    let files = root
+        .fsnode_id()
+        .find_entries(
+            changeset.ctx().clone(),
+            blobstore,
+            vec![PathOrPrefix::Prefix(Some(dir.clone()))],
+        )
+        .try_filter_map(|(path, entry)| async move {
+            Ok(match (path, entry) {
+                (Some(path), Entry::Leaf(file)) => {
+                    path.remove_prefix_component(dir).map(|path| (path, file))
+                }
+                _ => None,
+            })
+        })
+        .take(MAX_SIMILARITY_FILES + 1)
+        .try_collect::<HashMap<_, _>>()
+        .await?;
+    if files.len() > MAX_SIMILARITY_FILES {
+        return Ok(None);
+    }
+    Ok(Some(files))
+}
+
+/// Proportion of the files of the larger directory that are also in the
+/// other directory, at the same relative path.
+fn similarity(a: &HashMap<MPath, FsnodeFile>, b: &HashMap<MPath, FsnodeFile>) -> f64 {
+    let total = a.len().max(b.len());
+    if total == 0 {
+        return 0.0;
+    }
+    let common = a.keys().filter(|path| b.contains_key(*path)).count();
+    common as f64 / total as f64
+}
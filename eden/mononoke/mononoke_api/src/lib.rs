@@ -13,6 +13,9 @@ use std::time::Instant;
 
 use anyhow::Error;
 pub use bookmarks::BookmarkName;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
 use mononoke_app::MononokeApp;
 use mononoke_repos::MononokeRepos;
 use mononoke_types::RepositoryId;
@@ -21,11 +24,15 @@ use stats::prelude::*;
 
 use crate::repo::RepoContextBuilder;
 
+pub mod bookmark_publishing;
 pub mod changeset;
 pub mod changeset_path;
 pub mod changeset_path_diff;
+mod directory_moves;
 pub mod errors;
 pub mod file;
+mod merge_copy_trace;
+mod patch_id;
 pub mod path;
 pub mod repo;
 pub mod sparse_profile;
@@ -41,6 +48,7 @@ pub use blame::CompatBlame;
 pub use context::CoreContext;
 pub use context::LoggingContainer;
 pub use context::SessionContainer;
+pub use patch_id_index::PatchId;
 
 pub use crate::changeset::ChangesetContext;
 pub use crate::changeset::ChangesetDiffItem;
@@ -50,11 +58,13 @@ pub use crate::changeset::Generation;
 pub use crate::changeset_path::ChangesetPathContentContext;
 pub use crate::changeset_path::ChangesetPathHistoryOptions;
 pub use crate::changeset_path::PathEntry;
+pub use crate::changeset_path::PathRollup;
 pub use crate::changeset_path_diff::ChangesetPathDiffContext;
 pub use crate::changeset_path_diff::CopyInfo;
 pub use crate::changeset_path_diff::MetadataDiff;
 pub use crate::changeset_path_diff::UnifiedDiff;
 pub use crate::changeset_path_diff::UnifiedDiffMode;
+pub use crate::directory_moves::DirectoryMove;
 pub use crate::errors::MononokeError;
 pub use crate::file::headerless_unified_diff;
 pub use crate::file::FileContext;
@@ -62,15 +72,22 @@ pub use crate::file::FileId;
 pub use crate::file::FileMetadata;
 pub use crate::file::FileType;
 pub use crate::file::HeaderlessUnifiedDiff;
+pub use crate::merge_copy_trace::MergeRename;
+pub use crate::merge_copy_trace::MergeSide;
 pub use crate::path::MononokePath;
 pub use crate::repo::create_changeset::CreateChange;
 pub use crate::repo::create_changeset::CreateChangeFile;
 pub use crate::repo::create_changeset::CreateCopyInfo;
+pub use crate::repo::hooks_dry_run::DryRunChangeset;
 pub use crate::repo::land_stack::PushrebaseOutcome;
+pub use crate::repo::modify_bookmarks::BookmarkModification;
 pub use crate::repo::BookmarkFreshness;
 pub use crate::repo::BookmarkInfo;
+pub use crate::repo::ChangesetTail;
 pub use crate::repo::Repo;
 pub use crate::repo::RepoContext;
+pub use crate::repo::RepoResourceUsage;
+pub use crate::repo::TailedChangeset;
 pub use crate::specifiers::ChangesetId;
 pub use crate::specifiers::ChangesetIdPrefix;
 pub use crate::specifiers::ChangesetPrefixSpecifier;
@@ -90,6 +107,9 @@ define_stats! {
     completion_duration_secs: timeseries(Average, Sum, Count),
 }
 
+/// Number of repos searched concurrently when locating a changeset.
+const LOCATE_CHANGESET_CONCURRENCY: usize = 100;
+
 /// An instance of Mononoke, which may manage multiple repositories.
 pub struct Mononoke {
     // Collection of instantiated repos currently being served.
@@ -208,6 +228,75 @@ impl Mononoke {
             .map(|repo| repo.repoid())
     }
 
+    /// Find the repos containing changesets that match a prefix.
+    ///
+    /// Only the repos in `repo_names` are searched, or every repo if it is
+    /// `None`.  Returns the resolution of the prefix in each repo that has
+    /// at least one match, ordered by repo name.  When searching every
+    /// repo, those the caller isn't permitted to read are skipped, so that
+    /// their contents aren't revealed.  Globalrevs are only meaningful
+    /// within a single repo, so can't be located.
+    pub async fn locate_changeset_prefix(
+        &self,
+        ctx: CoreContext,
+        prefix: ChangesetPrefixSpecifier,
+        repo_names: Option<&[String]>,
+    ) -> Result<Vec<(RepoContext, ChangesetSpecifierPrefixResolution)>, MononokeError> {
+        if let ChangesetPrefixSpecifier::Globalrev(_) = prefix {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "globalrevs can't be located across repos",
+            )));
+        }
+
+        let repos = match repo_names {
+            Some(repo_names) => repo_names
+                .iter()
+                .map(|name| {
+                    self.repos.get_by_name(name).ok_or_else(|| {
+                        MononokeError::InvalidRequest(format!("repo does not exist: {}", name))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => self.repos.iter().collect(),
+        };
+        let skip_unauthorized = repo_names.is_none();
+
+        let mut located = stream::iter(repos)
+            .map(|repo| {
+                let ctx = ctx.clone();
+                async move {
+                    let repo = match RepoContextBuilder::new(ctx, repo, self.repos.as_ref())
+                        .await?
+                        .build()
+                        .await
+                    {
+                        Ok(repo) => repo,
+                        Err(MononokeError::AuthorizationError(_)) if skip_unauthorized => {
+                            return Ok(None);
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    match repo.resolve_changeset_id_prefix(prefix).await? {
+                        ChangesetSpecifierPrefixResolution::NoMatch => Ok(None),
+                        resolution => Ok(Some((repo, resolution))),
+                    }
+                }
+            })
+            .buffer_unordered(LOCATE_CHANGESET_CONCURRENCY)
+            .try_filter_map(|located| async move { Ok(located) })
+            .try_collect::<Vec<_>>()
+            .await?;
+        located.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        Ok(located)
+    }
+
+    /// Report the resources held for each repo.
+    pub fn report_resource_usage(&self, ctx: &CoreContext) {
+        for repo in self.repos.iter() {
+            repo.report_resource_usage(ctx);
+        }
+    }
+
     /// Report configured monitoring stats
     pub async fn report_monitoring_stats(&self, ctx: &CoreContext) -> Result<(), MononokeError> {
         for repo in self.repos.iter() {
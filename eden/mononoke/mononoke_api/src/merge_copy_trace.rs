@@ -0,0 +1,282 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use futures::future;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use futures::try_join;
+use manifest::Entry;
+use manifest::ManifestOps;
+use manifest::PathOrPrefix;
+use mononoke_types::fsnode::FsnodeFile;
+use mononoke_types::FileChange;
+use mononoke_types::MPath;
+
+use crate::changeset::ChangesetContext;
+use crate::changeset::ChangesetHistoryOptions;
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+
+/// Maximum number of commits between the merge base and either head that
+/// will be searched for renames.
+const MAX_COPY_TRACE_COMMITS: usize = 10000;
+
+/// Which side of a merge a rename happened on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum MergeSide {
+    /// The commit the copy trace was requested for.
+    Local,
+    /// The commit it is being merged with.
+    Other,
+}
+
+/// A file that was renamed on one side of a merge, and modified at its
+/// original path on the other side.  Merging these correctly requires the
+/// modification to be applied to the renamed file.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MergeRename {
+    /// The side the file was renamed on.
+    pub renamed_on: MergeSide,
+    /// The path of the file in the merge base.
+    pub base_path: MononokePath,
+    /// The path of the file after it was renamed.
+    pub renamed_path: MononokePath,
+}
+
+impl ChangesetContext {
+    /// Find the files that were renamed since `base` on one side of a merge
+    /// of this commit with `other`, and modified at their original path on
+    /// the other side.
+    ///
+    /// If `base` is not given, the common ancestor of the two commits is
+    /// used.  If they have no common ancestor then nothing can have been
+    /// renamed, and the result is empty.
+    pub async fn trace_merge_copies(
+        &self,
+        other: &ChangesetContext,
+        base: Option<&ChangesetContext>,
+    ) -> Result<Vec<MergeRename>, MononokeError> {
+        let base = match base {
+            Some(base) => base.clone(),
+            None => match self.common_base_with(other.id()).await? {
+                Some(base) => base,
+                None => return Ok(Vec::new()),
+            },
+        };
+        if !base.is_ancestor_of(self.id()).await? || !base.is_ancestor_of(other.id()).await? {
+            return Err(MononokeError::InvalidRequest(format!(
+                "{} is not an ancestor of both {} and {}",
+                base.id(),
+                self.id(),
+                other.id()
+            )));
+        }
+
+        let (local_renames, other_renames) =
+            try_join!(renames_since(self, &base), renames_since(other, &base))?;
+
+        let (local_modified, other_modified) = try_join!(
+            modified_since(self, &base, other_renames.keys()),
+            modified_since(other, &base, local_renames.keys()),
+        )?;
+
+        let mut result = Vec::new();
+        for (renamed_on, renames, modified) in [
+            (MergeSide::Local, &local_renames, other_modified),
+            (MergeSide::Other, &other_renames, local_modified),
+        ] {
+            for (base_path, renamed_path) in renames {
+                if modified.contains(base_path) {
+                    result.push(MergeRename {
+                        renamed_on,
+                        base_path: MononokePath::new(Some(base_path.clone())),
+                        renamed_path: MononokePath::new(Some(renamed_path.clone())),
+                    });
+                }
+            }
+        }
+
+        // Files in directories that were moved as a whole may not have been
+        // recorded as renamed, so also apply modifications made in a moved
+        // directory on one side to the files that moved with it on the
+        // other.
+        let (local_moves, other_moves) =
+            try_join!(self.directory_moves(&base), other.directory_moves(&base))?;
+        for (renamed_on, moves, renames, renamed_side, modified_side) in [
+            (MergeSide::Local, local_moves, &local_renames, self, other),
+            (MergeSide::Other, other_moves, &other_renames, other, self),
+        ] {
+            for dir_move in moves {
+                let (from, to) = match (dir_move.from.into_mpath(), dir_move.to.into_mpath()) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => continue,
+                };
+                let moved: HashMap<MPath, MPath> = modified_under(modified_side, &base, &from)
+                    .await?
+                    .into_iter()
+                    .filter(|base_path| !renames.contains_key(base_path))
+                    .filter_map(|base_path| {
+                        let relative = base_path.remove_prefix_component(&from)?;
+                        Some((to.join(&relative), base_path))
+                    })
+                    .collect();
+                if moved.is_empty() {
+                    continue;
+                }
+                // Files that didn't survive the move have nothing to apply
+                // the modifications to.
+                let present = find_files(renamed_side, moved.keys().cloned().collect()).await?;
+                for (renamed_path, base_path) in moved {
+                    if present.contains_key(&renamed_path) {
+                        result.push(MergeRename {
+                            renamed_on,
+                            base_path: MononokePath::new(Some(base_path)),
+                            renamed_path: MononokePath::new(Some(renamed_path)),
+                        });
+                    }
+                }
+            }
+        }
+
+        result.sort_by(|a, b| a.base_path.cmp(&b.base_path));
+        Ok(result)
+    }
+}
+
+/// Find the files renamed between `base` and `head`, as a map from their
+/// path in `base` to their path in `head`.  Renames are followed through
+/// the intermediate commits, so a file renamed twice maps to its final path.
+async fn renames_since(
+    head: &ChangesetContext,
+    base: &ChangesetContext,
+) -> Result<HashMap<MPath, MPath>, MononokeError> {
+    let mut commits = head
+        .history(ChangesetHistoryOptions {
+            descendants_of: Some(base.id()),
+            ..Default::default()
+        })
+        .await
+        .try_filter(|cs| future::ready(cs.id() != base.id()))
+        .take(MAX_COPY_TRACE_COMMITS + 1)
+        .and_then(|cs| async move {
+            let generation = cs.generation().await?;
+            Ok((generation, cs))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+    if commits.len() > MAX_COPY_TRACE_COMMITS {
+        return Err(MononokeError::InvalidRequest(format!(
+            "Too many commits between {} and {} to trace copies (limit is {})",
+            base.id(),
+            head.id(),
+            MAX_COPY_TRACE_COMMITS
+        )));
+    }
+    // Renames must be applied in the order they happened.
+    commits.sort_by_key(|(generation, cs)| (*generation, cs.id()));
+
+    // Map from the current path of each renamed file to its base path.
+    let mut origins: HashMap<MPath, MPath> = HashMap::new();
+    for (_, cs) in commits {
+        let file_changes = cs.file_changes().await?;
+        let mut renames = Vec::new();
+        for (path, change) in file_changes.iter() {
+            if let FileChange::Change(tc) = change {
+                if let Some((from_path, _)) = tc.copy_from() {
+                    // A copy whose source was deleted in the same commit
+                    // is a rename.  Plain copies leave the original in
+                    // place, so changes to it still apply there.
+                    if matches!(file_changes.get(from_path), Some(FileChange::Deletion)) {
+                        renames.push((from_path.clone(), path.clone()));
+                    }
+                }
+            }
+        }
+        let sources: HashSet<_> = renames.iter().map(|(from, _)| from.clone()).collect();
+        for (path, change) in file_changes.iter() {
+            if change.is_removed() && !sources.contains(path) {
+                origins.remove(path);
+            }
+        }
+        for (from_path, to_path) in renames {
+            let origin = origins
+                .remove(&from_path)
+                .unwrap_or_else(|| from_path.clone());
+            origins.insert(to_path, origin);
+        }
+    }
+
+    Ok(origins
+        .into_iter()
+        .filter(|(path, origin)| path != origin)
+        .map(|(path, origin)| (origin, path))
+        .collect())
+}
+
+/// Find which of `paths` are files whose contents or type differ between
+/// `base` and `head`.
+async fn modified_since(
+    head: &ChangesetContext,
+    base: &ChangesetContext,
+    paths: impl Iterator<Item = &MPath>,
+) -> Result<HashSet<MPath>, MononokeError> {
+    let paths: Vec<MPath> = paths.cloned().collect();
+    if paths.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let (head_files, base_files) =
+        try_join!(find_files(head, paths.clone()), find_files(base, paths))?;
+    Ok(head_files
+        .into_iter()
+        .filter(|(path, file)| base_files.get(path).map_or(false, |base| base != file))
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// Find the files in `dir` whose contents or type differ between `base` and
+/// `head`.
+async fn modified_under(
+    head: &ChangesetContext,
+    base: &ChangesetContext,
+    dir: &MPath,
+) -> Result<Vec<MPath>, MononokeError> {
+    let prefix = vec![PathOrPrefix::Prefix(Some(dir.clone()))];
+    let (head_files, base_files) =
+        try_join!(find_files(head, prefix.clone()), find_files(base, prefix))?;
+    let mut modified: Vec<_> = head_files
+        .into_iter()
+        .filter(|(path, file)| base_files.get(path).map_or(false, |base| base != file))
+        .map(|(path, _)| path)
+        .collect();
+    modified.sort();
+    Ok(modified)
+}
+
+async fn find_files<P>(
+    changeset: &ChangesetContext,
+    paths: Vec<P>,
+) -> Result<HashMap<MPath, FsnodeFile>, MononokeError>
+where
+    PathOrPrefix: From<P>,
+{
+    let root = changeset.root_fsnode_id().await?;
+    let blobstore = changeset.repo().blob_repo().get_blobstore();
+    Ok(root
+        .fsnode_id()
+        .find_entries(changeset.ctx().clone(), blobstore, paths)
+        .try_filter_map(|(path, entry)| async move {
+            Ok(match (path, entry) {
+                (Some(path), Entry::Leaf(file)) => Some((path, file)),
+                _ => None,
+            })
+        })
+        .try_collect::<HashMap<_, _>>()
+        .await?)
+}
@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use futures::future;
+use futures::try_join;
+use maplit::btreeset;
+use patch_id_index::PatchId;
+use patch_id_index::PatchIdContext;
+
+use crate::changeset::ChangesetContext;
+use crate::changeset::ChangesetDiffItem;
+use crate::changeset_path::ChangesetPathContentContext;
+use crate::changeset_path_diff::ChangesetPathDiffContext;
+use crate::changeset_path_diff::UnifiedDiffMode;
+use crate::errors::MononokeError;
+
+/// Files larger than this contribute their content ids to the patch id
+/// rather than the lines that changed.
+const MAX_PATCH_ID_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+impl ChangesetContext {
+    /// The patch id of this changeset: a hash of the changes it makes to
+    /// its parent, in the style of `git patch-id`.  Commit metadata, the
+    /// position of the changed lines in the files and whitespace are
+    /// ignored, so the cherry-picks of a commit usually have the same patch
+    /// id as the commit.
+    ///
+    /// Merge commits have no patch id.  Patch ids are recorded in the
+    /// repo's patch id index the first time they are computed.
+    pub async fn patch_id(&self) -> Result<Option<PatchId>, MononokeError> {
+        let parents = self.parents().await?;
+        if parents.len() > 1 {
+            return Ok(None);
+        }
+
+        let index = self.repo().patch_id_index();
+        if let Some(patch_id) = index
+            .get_patch_ids(self.ctx(), &[self.id()])
+            .await?
+            .remove(&self.id())
+        {
+            return Ok(Some(patch_id));
+        }
+
+        let mut diffs = match parents.first() {
+            Some(parent) => {
+                let parent = ChangesetContext::new(self.repo().clone(), *parent);
+                self.diff_unordered(&parent, true, None, btreeset! {ChangesetDiffItem::FILES})
+                    .await?
+            }
+            None => {
+                self.diff_root_unordered(None, btreeset! {ChangesetDiffItem::FILES})
+                    .await?
+            }
+        };
+        diffs.sort_by(|a, b| a.path().path().cmp(b.path().path()));
+
+        let hashed = future::try_join_all(diffs.iter().map(hash_file_diff)).await?;
+        let mut hasher = PatchIdContext::new();
+        for data in hashed {
+            hasher.update(data);
+        }
+        let patch_id = hasher.finish();
+
+        index.add(self.ctx(), &[(self.id(), patch_id)]).await?;
+        Ok(Some(patch_id))
+    }
+
+    /// Find the other changesets in the repo with the same patch id as this
+    /// one, which are likely to be duplicates or cherry-picks of it.
+    ///
+    /// Only changesets whose patch id has been computed are found.  The patch
+    /// ids of a repo's public changesets can be computed ahead of time with
+    /// `newadmin backfill-patch-ids`.
+    pub async fn find_duplicates(&self) -> Result<Vec<ChangesetContext>, MononokeError> {
+        let patch_id = match self.patch_id().await? {
+            Some(patch_id) => patch_id,
+            None => return Ok(Vec::new()),
+        };
+        let mut duplicates = self
+            .repo()
+            .patch_id_index()
+            .get_changesets(self.ctx(), patch_id)
+            .await?;
+        duplicates.retain(|cs_id| *cs_id != self.id());
+        duplicates.sort();
+        Ok(duplicates
+            .into_iter()
+            .map(|cs_id| ChangesetContext::new(self.repo().clone(), cs_id))
+            .collect())
+    }
+}
+
+/// The content id and size of a side of a file diff, if the file exists on
+/// that side.
+async fn file_info(
+    path: Option<&ChangesetPathContentContext>,
+) -> Result<Option<(String, u64)>, MononokeError> {
+    let file = match path {
+        Some(path) => path.file().await?,
+        None => None,
+    };
+    match file {
+        Some(file) => {
+            let metadata = file.metadata().await?;
+            Ok(Some((metadata.content_id.to_string(), metadata.total_size)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The data that a file diff contributes to the patch id.
+async fn hash_file_diff(diff: &ChangesetPathDiffContext) -> Result<Vec<u8>, MononokeError> {
+    let (new, old) = try_join!(file_info(diff.base()), file_info(diff.other()))?;
+    let too_large = [&new, &old]
+        .iter()
+        .any(|info| matches!(info, Some((_, size)) if *size > MAX_PATCH_ID_FILE_SIZE));
+    if !too_large {
+        let unified = diff.unified_diff(0, UnifiedDiffMode::Inline).await?;
+        if !unified.is_binary {
+            return Ok(normalize_diff(&unified.raw_diff));
+        }
+    }
+
+    // Binary and large files are identified by their contents.
+    let mut data = format!("diff {}\n", diff.path().path()).into_bytes();
+    for (side, info) in [("old", old), ("new", new)] {
+        if let Some((content_id, _)) = info {
+            data.extend(format!("{} {}\n", side, content_id).into_bytes());
+        }
+    }
+    Ok(data)
+}
+
+/// Strip a unified diff of the parts that depend on where in the file the
+/// changes are, and of whitespace, so that the same change made at a
+/// different position in the file hashes the same.
+fn normalize_diff(raw_diff: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(raw_diff.len());
+    for line in raw_diff.split(|b| *b == b'\n') {
+        if line.starts_with(b"@@") || line.starts_with(b"index ") {
+            continue;
+        }
+        normalized.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+        normalized.push(b'\n');
+    }
+    normalized
+}
@@ -51,6 +51,8 @@ use cross_repo_sync::CandidateSelectionHint;
 use cross_repo_sync::CommitSyncContext;
 use cross_repo_sync::CommitSyncRepos;
 use cross_repo_sync::CommitSyncer;
+use derivation_queue::DerivationQueue;
+use derivation_queue::DerivationQueueBuilder;
 use derived_data_manager::BonsaiDerivable as NewBonsaiDerivable;
 use ephemeral_blobstore::ArcRepoEphemeralStore;
 use ephemeral_blobstore::Bubble;
@@ -64,6 +66,7 @@ use filestore::Alias;
 use filestore::FetchKey;
 use futures::compat::Stream01CompatExt;
 use futures::stream;
+use futures::stream::BoxStream;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
@@ -77,6 +80,8 @@ use live_commit_sync_config::TestLiveCommitSyncConfig;
 use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_mutation::HgMutationStore;
 use mercurial_types::Globalrev;
+use mercurial_types::HgFileNodeId;
+use mercurial_types::HgNodeHash;
 use metaconfig_types::HookManagerParams;
 use metaconfig_types::InfinitepushNamespace;
 use metaconfig_types::InfinitepushParams;
@@ -89,6 +94,8 @@ use mononoke_types::hash::GitSha1;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
 use mononoke_types::Generation;
+use mononoke_types::MPath;
+use mononoke_types::RepoPath;
 use mononoke_types::RepositoryId;
 use mononoke_types::Svnrev;
 use mononoke_types::Timestamp;
@@ -97,9 +104,15 @@ use mutable_renames::ArcMutableRenames;
 use mutable_renames::MutableRenames;
 use mutable_renames::MutableRenamesArc;
 use mutable_renames::SqlMutableRenamesStore;
+use patch_id_index::ArcPatchIdIndex;
+use patch_id_index::PatchIdIndex;
+use patch_id_index::PatchIdIndexArc;
+use patch_id_index::SqlPatchIdIndexBuilder;
 use phases::Phases;
 use phases::PhasesArc;
 use phases::PhasesRef;
+use preserved_bundles::PreservedBundles;
+use preserved_bundles::PreservedBundlesBuilder;
 use pushrebase_mutation_mapping::PushrebaseMutationMapping;
 use reachabilityindex::LeastCommonAncestorsHint;
 use regex::Regex;
@@ -130,6 +143,8 @@ use skiplist::SkiplistIndex;
 use skiplist::SkiplistIndexArc;
 use slog::debug;
 use slog::error;
+use snapshot_bundles::SnapshotBundles;
+use snapshot_bundles::SnapshotBundlesBuilder;
 use sql_construct::SqlConstruct;
 use sql_ext::facebook::MysqlOptions;
 use stats::prelude::*;
@@ -139,14 +154,18 @@ use synced_commit_mapping::SqlSyncedCommitMapping;
 use synced_commit_mapping::SyncedCommitMapping;
 use test_repo_factory::TestRepoFactory;
 use tunables::tunables;
+use unbundle::PartHandlerRegistry;
 use unbundle::PushRedirector;
 use unbundle::PushRedirectorArgs;
+use usage_attribution::UsageAttribution;
 use warm_bookmarks_cache::BookmarksCache;
 use warm_bookmarks_cache::WarmBookmarksCacheBuilder;
 use wireproto_handler::PushRedirectorBase;
 use wireproto_handler::RepoHandlerBase;
 use wireproto_handler::RepoHandlerBaseRef;
 
+use crate::bookmark_publishing::BookmarkPublishingGate;
+use crate::bookmark_publishing::PublishedBookmarks;
 use crate::changeset::ChangesetContext;
 use crate::errors::MononokeError;
 use crate::file::FileContext;
@@ -160,11 +179,15 @@ use crate::tree::TreeContext;
 use crate::tree::TreeId;
 use crate::xrepo::CandidateSelectionHintArgs;
 
+pub mod amend_extras;
 pub mod create_bookmark;
 pub mod create_changeset;
 pub mod delete_bookmark;
+pub mod hooks_dry_run;
 pub mod land_stack;
+pub mod modify_bookmarks;
 pub mod move_bookmark;
+pub mod raw_blob;
 pub mod set_git_mapping;
 
 define_stats! {
@@ -181,6 +204,42 @@ define_stats! {
         "missing_from_repo.{}.{}",
         (repoid: ::mononoke_types::RepositoryId, bookmark: String)
     ),
+    bookmarks_cache_entries: dynamic_singleton_counter(
+        "resources.bookmarks_cache.entries.{}",
+        (repoid: ::mononoke_types::RepositoryId)
+    ),
+    bookmarks_cache_bytes: dynamic_singleton_counter(
+        "resources.bookmarks_cache.bytes.{}",
+        (repoid: ::mononoke_types::RepositoryId)
+    ),
+    skiplist_nodes: dynamic_singleton_counter(
+        "resources.skiplist.nodes.{}",
+        (repoid: ::mononoke_types::RepositoryId)
+    ),
+    skiplist_bytes: dynamic_singleton_counter(
+        "resources.skiplist.bytes.{}",
+        (repoid: ::mononoke_types::RepositoryId)
+    ),
+    background_tasks: dynamic_singleton_counter(
+        "resources.background_tasks.{}",
+        (repoid: ::mononoke_types::RepositoryId)
+    ),
+}
+
+/// Resources held in memory on behalf of a repo, so that it can be seen
+/// which repos are using the memory of a server that serves many repos.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RepoResourceUsage {
+    /// Number of bookmarks held in the warm bookmarks cache.
+    pub bookmarks_cache_entries: usize,
+    /// Approximate memory used by the warm bookmarks cache.
+    pub bookmarks_cache_bytes: usize,
+    /// Number of changesets in the skiplist index.
+    pub skiplist_nodes: usize,
+    /// Approximate memory used by the skiplist index.
+    pub skiplist_bytes: usize,
+    /// Number of background tasks running for the repo.
+    pub background_tasks: usize,
 }
 
 #[facet::container]
@@ -212,12 +271,24 @@ pub struct Repo {
         dyn AclRegions,
         RepoSparseProfiles,
         StreamingClone,
+        dyn PatchIdIndex,
+        SnapshotBundles,
+        PreservedBundles,
+        DerivationQueue,
     )]
     pub inner: InnerRepo,
 
     #[init(inner.repo_identity().name().to_string())]
     pub name: String,
 
+    #[init(Arc::new(PublishedBookmarks::default()))]
+    pub published_bookmarks: Arc<PublishedBookmarks>,
+
+    #[init(Arc::new(PartHandlerRegistry::with_accepted_parts(
+        &inner.repo_config.repo_client_knobs.accepted_bundle2_parts,
+    )))]
+    pub part_handlers: Arc<PartHandlerRegistry>,
+
     #[facet]
     pub warm_bookmarks_cache: dyn BookmarksCache,
 
@@ -226,6 +297,9 @@ pub struct Repo {
 
     #[facet]
     pub repo_handler_base: RepoHandlerBase,
+
+    #[facet]
+    pub usage_attribution: dyn UsageAttribution,
 }
 
 impl AsBlobRepo for Repo {
@@ -369,10 +443,13 @@ impl Repo {
         };
         Self {
             name: self.name.clone(),
+            published_bookmarks: self.published_bookmarks.clone(),
+            part_handlers: self.part_handlers.clone(),
             inner,
             warm_bookmarks_cache: self.warm_bookmarks_cache.clone(),
             hook_manager: self.hook_manager.clone(),
             repo_handler_base: self.repo_handler_base.clone(),
+            usage_attribution: self.usage_attribution.clone(),
         }
     }
 
@@ -383,7 +460,23 @@ impl Repo {
             blob_repo,
             None,
             Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
-            Default::default(),
+            |_| {},
+        )
+        .await
+    }
+
+    /// Construct a Repo from a test BlobRepo, with changes to the test config
+    pub async fn new_test_with_config(
+        ctx: CoreContext,
+        blob_repo: BlobRepo,
+        configure: impl FnOnce(&mut RepoConfig),
+    ) -> Result<Self, Error> {
+        Self::new_test_common(
+            ctx,
+            blob_repo,
+            None,
+            Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            configure,
         )
         .await
     }
@@ -399,7 +492,7 @@ impl Repo {
             blob_repo,
             None,
             Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
-            lfs,
+            |config| config.lfs = lfs,
         )
         .await
     }
@@ -416,7 +509,7 @@ impl Repo {
             blob_repo,
             Some(live_commit_sync_config),
             synced_commit_mapping,
-            Default::default(),
+            |_| {},
         )
         .await
     }
@@ -427,7 +520,7 @@ impl Repo {
         blob_repo: BlobRepo,
         live_commit_sync_config: Option<Arc<dyn LiveCommitSyncConfig>>,
         synced_commit_mapping: Arc<dyn SyncedCommitMapping>,
-        lfs: LfsParams,
+        configure: impl FnOnce(&mut RepoConfig),
     ) -> Result<Self, Error> {
         // TODO: Migrate more of this code to use the TestRepoFactory so that we can eventually
         // replace these test methods.
@@ -435,8 +528,7 @@ impl Repo {
 
         let repo_id = blob_repo.get_repoid();
 
-        let config = RepoConfig {
-            lfs,
+        let mut config = RepoConfig {
             infinitepush: InfinitepushParams {
                 namespace: Some(InfinitepushNamespace::new(
                     Regex::new("scratch/.+").unwrap(),
@@ -453,6 +545,7 @@ impl Repo {
             }),
             ..Default::default()
         };
+        configure(&mut config);
 
         let name = blob_repo.name().clone();
         let repo_blobstore = blob_repo.repo_blobstore_arc();
@@ -477,6 +570,7 @@ impl Repo {
             blob_repo.bookmark_update_log(),
             &mutable_counters,
         )?;
+        let usage_attribution = repo_factory.usage_attribution(&blob_repo.repo_identity_arc());
 
         let inner = InnerRepo {
             blob_repo,
@@ -494,6 +588,18 @@ impl Repo {
             streaming_clone: Arc::new(
                 StreamingCloneBuilder::with_sqlite_in_memory()?.build(repo_id, repo_blobstore),
             ),
+            patch_id_index: Arc::new(
+                SqlPatchIdIndexBuilder::with_sqlite_in_memory()?.build(repo_id),
+            ),
+            snapshot_bundles: Arc::new(
+                SnapshotBundlesBuilder::with_sqlite_in_memory()?.build(repo_id),
+            ),
+            preserved_bundles: Arc::new(
+                PreservedBundlesBuilder::with_sqlite_in_memory()?.build(repo_id),
+            ),
+            derivation_queue: Arc::new(
+                DerivationQueueBuilder::with_sqlite_in_memory()?.build(repo_id),
+            ),
         };
 
         let mut warm_bookmarks_cache_builder = WarmBookmarksCacheBuilder::new(
@@ -511,10 +617,15 @@ impl Repo {
 
         Ok(Self {
             name: name.clone(),
+            published_bookmarks: Arc::new(PublishedBookmarks::default()),
+            part_handlers: Arc::new(PartHandlerRegistry::with_accepted_parts(
+                &inner.repo_config.repo_client_knobs.accepted_bundle2_parts,
+            )),
             inner,
             warm_bookmarks_cache: Arc::new(warm_bookmarks_cache),
             hook_manager,
             repo_handler_base,
+            usage_attribution,
         })
     }
 
@@ -563,7 +674,33 @@ impl Repo {
         &self.inner.repo_config
     }
 
+    /// Resources currently held in memory for this repo.
+    pub fn resource_usage(&self) -> RepoResourceUsage {
+        let bookmarks_cache = self.warm_bookmarks_cache.usage();
+        let skiplist_index = &self.inner.skiplist_index;
+        RepoResourceUsage {
+            bookmarks_cache_entries: bookmarks_cache.entries,
+            bookmarks_cache_bytes: bookmarks_cache.bytes,
+            skiplist_nodes: skiplist_index.indexed_node_count(),
+            skiplist_bytes: skiplist_index.estimated_memory_bytes(),
+            background_tasks: bookmarks_cache.background_tasks,
+        }
+    }
+
+    /// Report the resources held for this repo to the stats.
+    pub fn report_resource_usage(&self, ctx: &CoreContext) {
+        let usage = self.resource_usage();
+        let key = (self.repo_identity().id(),);
+        STATS::bookmarks_cache_entries.set_value(ctx.fb, usage.bookmarks_cache_entries as i64, key);
+        STATS::bookmarks_cache_bytes.set_value(ctx.fb, usage.bookmarks_cache_bytes as i64, key);
+        STATS::skiplist_nodes.set_value(ctx.fb, usage.skiplist_nodes as i64, key);
+        STATS::skiplist_bytes.set_value(ctx.fb, usage.skiplist_bytes as i64, key);
+        STATS::background_tasks.set_value(ctx.fb, usage.background_tasks as i64, key);
+    }
+
     pub async fn report_monitoring_stats(&self, ctx: &CoreContext) -> Result<(), MononokeError> {
+        self.report_resource_usage(ctx);
+
         match self.config().source_control_service_monitoring.as_ref() {
             None => {}
             Some(monitoring_config) => {
@@ -779,6 +916,27 @@ pub struct BookmarkInfo {
     pub last_update_timestamp: Timestamp,
 }
 
+/// A changeset that landed on a public bookmark, as returned by
+/// `RepoContext::tail_changesets`.
+pub struct TailedChangeset {
+    pub changeset: ChangesetContext,
+    /// The bookmark the changeset was pushed to.
+    pub bookmark: BookmarkName,
+    /// When the bookmark was moved.
+    pub pushed_at: Timestamp,
+    /// Cursor of the bookmark move that landed the changeset.  Tailing can
+    /// be resumed after this move by passing it as the cursor.
+    pub cursor: u64,
+}
+
+/// Changesets that landed after a cursor.
+pub struct ChangesetTail {
+    /// The changesets, in the order they landed.
+    pub changesets: Vec<TailedChangeset>,
+    /// Cursor to pass to the next call to continue tailing.
+    pub next_cursor: u64,
+}
+
 /// A context object representing a query to a particular repo.
 impl RepoContext {
     pub async fn new(
@@ -837,6 +995,62 @@ impl RepoContext {
         &self.authz
     }
 
+    /// Require that the caller may read a tree or file node at the path the
+    /// client claims it is at, according to the repo's path read ACLs.
+    /// Content fetched by hash without going through a changeset must be
+    /// checked with this.
+    pub async fn require_protected_node_read(
+        &self,
+        path: &RepoPath,
+        node: HgFileNodeId,
+    ) -> Result<(), MononokeError> {
+        self.authz
+            .require_protected_node_read(self.ctx(), self.inner_repo(), path, node)
+            .await?;
+        Ok(())
+    }
+
+    /// Require that the caller may read a path according to the repo's path
+    /// read ACLs.  The path must be bound to the data being served, e.g. the
+    /// path of a child of a tree that was checked with
+    /// `require_protected_node_read`.
+    pub fn require_protected_path_read(&self, path: Option<&MPath>) -> Result<(), MononokeError> {
+        self.authz
+            .require_protected_path_read(self.ctx(), self.inner_repo(), path)?;
+        Ok(())
+    }
+
+    /// Require that the caller may read the files touched by a changeset,
+    /// according to the repo's path read ACLs.
+    pub fn require_protected_paths_read<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a MPath>,
+    ) -> Result<(), MononokeError> {
+        self.authz
+            .require_protected_paths_read(self.ctx(), self.inner_repo(), paths)?;
+        Ok(())
+    }
+
+    /// Require that the caller may read a changeset according to the repo's
+    /// private scratch namespace.  Changesets resolved from a specifier are
+    /// checked automatically.
+    pub async fn require_draft_commit_read(&self, cs_id: ChangesetId) -> Result<(), MononokeError> {
+        self.authz
+            .require_draft_commit_read(self.ctx(), self.inner_repo(), cs_id)
+            .await?;
+        Ok(())
+    }
+
+    /// Require that the caller may read an hg tree or file node according to
+    /// the repo's private scratch namespace.  Trees and files fetched by hash
+    /// without going through a changeset must be checked with this.
+    pub async fn require_draft_hg_node_read(&self, node: HgNodeHash) -> Result<(), MononokeError> {
+        self.authz
+            .require_draft_hg_node_read(self.ctx(), self.inner_repo(), node)
+            .await?;
+        Ok(())
+    }
+
     pub fn mononoke_api_repo(&self) -> Arc<Repo> {
         self.repo.clone()
     }
@@ -900,10 +1114,23 @@ impl RepoContext {
         self.repo.config()
     }
 
+    /// Which positions of the repo's delayed bookmarks this client sees.
+    fn bookmark_publishing_gate(&self) -> BookmarkPublishingGate {
+        BookmarkPublishingGate::new(
+            &self.config().bookmarks,
+            self.ctx.metadata().identities(),
+            self.repo.published_bookmarks.clone(),
+        )
+    }
+
     pub fn mutable_renames(&self) -> ArcMutableRenames {
         self.repo.mutable_renames_arc()
     }
 
+    pub fn patch_id_index(&self) -> ArcPatchIdIndex {
+        self.repo.patch_id_index_arc()
+    }
+
     pub fn sparse_profiles(&self) -> ArcRepoSparseProfiles {
         self.repo.repo_sparse_profiles_arc()
     }
@@ -1007,6 +1234,9 @@ impl RepoContext {
                     .await?
             }
         };
+        if let Some(cs_id) = id {
+            self.require_draft_commit_read(cs_id).await?;
+        }
         Ok(id)
     }
 
@@ -1039,6 +1269,18 @@ impl RepoContext {
                 .await?
         }
 
+        // Clients only see moves of delayed bookmarks once they have been
+        // published to them.  Callers that need the most recent position,
+        // e.g. to move the bookmark, see it regardless.
+        if freshness == BookmarkFreshness::MaybeStale {
+            let gate = self.bookmark_publishing_gate();
+            if gate.is_delayed(&bookmark) {
+                cs_id = gate
+                    .published_position(&self.ctx, self.blob_repo(), &bookmark, cs_id)
+                    .await?;
+            }
+        }
+
         Ok(cs_id.map(|cs_id| ChangesetContext::new(self.clone(), cs_id)))
     }
 
@@ -1293,6 +1535,168 @@ impl RepoContext {
         }))
     }
 
+    /// Changesets that were pushed to a bookmark between `min_ts` and
+    /// `max_ts` (inclusive).
+    ///
+    /// The pushes are found with the bookmark update log, which is indexed
+    /// by time, so only the part of the graph that was added by the pushes
+    /// in the range is walked.  These are the changesets that are ancestors
+    /// of a position of the bookmark within the range, but not of its
+    /// position before the range started.
+    pub async fn pushed_changesets_in_range(
+        &self,
+        bookmark: impl AsRef<str>,
+        min_ts: Timestamp,
+        max_ts: Timestamp,
+    ) -> Result<impl Stream<Item = Result<ChangesetContext, MononokeError>>, MononokeError> {
+        // Limit on the number of bookmark moves in a single query, so that
+        // a huge range can't be used to walk the whole repository.
+        const MAX_PUSHES_IN_RANGE: u32 = 10000;
+
+        let bookmark = BookmarkName::new(bookmark.as_ref())
+            .map_err(|e| MononokeError::InvalidRequest(e.to_string()))?;
+        if max_ts.timestamp_nanos() < min_ts.timestamp_nanos() {
+            return Err(MononokeError::InvalidRequest(
+                "end of time range is before its start".to_string(),
+            ));
+        }
+
+        let log = self.repo.blob_repo().bookmark_update_log();
+        let (in_range, before_range) = try_join!(
+            log.list_bookmark_log_entries_ts_in_range(
+                self.ctx.clone(),
+                bookmark.clone(),
+                MAX_PUSHES_IN_RANGE + 1,
+                min_ts,
+                max_ts,
+            )
+            .try_collect::<Vec<_>>(),
+            async {
+                if min_ts.timestamp_nanos() <= 0 {
+                    return Ok(None);
+                }
+                log.list_bookmark_log_entries_ts_in_range(
+                    self.ctx.clone(),
+                    bookmark.clone(),
+                    1,
+                    Timestamp::from_timestamp_nanos(0),
+                    Timestamp::from_timestamp_nanos(min_ts.timestamp_nanos() - 1),
+                )
+                .try_next()
+                .await
+            },
+        )?;
+        if in_range.len() > MAX_PUSHES_IN_RANGE as usize {
+            return Err(MononokeError::InvalidRequest(format!(
+                "bookmark {} moved more than {} times in the range, use a narrower range",
+                bookmark, MAX_PUSHES_IN_RANGE,
+            )));
+        }
+
+        let includes = in_range
+            .into_iter()
+            .filter_map(|(_id, cs_id, _reason, _ts)| cs_id)
+            .collect();
+        let excludes = before_range
+            .and_then(|(_id, cs_id, _reason, _ts)| cs_id)
+            .into_iter()
+            .collect();
+        Ok(self.difference_of_unions_of_ancestors(includes, excludes))
+    }
+
+    /// Get the changesets that landed on public bookmarks after `cursor`,
+    /// in the order they landed.  If `cursor` is `None`, tailing starts
+    /// from the latest bookmark move, so no changesets are returned, only
+    /// the cursor to continue from.
+    ///
+    /// Each bookmark move contributes the changesets it made reachable from
+    /// the bookmark, oldest first, and each bookmark creation contributes
+    /// the changeset it points to.  Moves are never split between calls, so
+    /// more than `limit` changesets may be returned if a single move
+    /// landed more than that.  Deletions and moves of other bookmarks than
+    /// `bookmark` (if given) advance the cursor without returning anything.
+    pub async fn tail_changesets(
+        &self,
+        cursor: Option<u64>,
+        bookmark: Option<&str>,
+        limit: usize,
+    ) -> Result<ChangesetTail, MononokeError> {
+        // Limit on the number of changesets returned for a single bookmark
+        // move, so that tailers aren't sent the whole history of a bookmark
+        // that was force-moved to an unrelated commit.
+        const MAX_CHANGESETS_PER_MOVE: usize = 10000;
+
+        let bookmark = bookmark
+            .map(BookmarkName::new)
+            .transpose()
+            .map_err(|e| MononokeError::InvalidRequest(e.to_string()))?;
+        let log = self.repo.blob_repo().bookmark_update_log();
+        let cursor = match cursor {
+            Some(cursor) => cursor,
+            None => {
+                let latest = log
+                    .get_largest_log_id(self.ctx.clone(), Freshness::MostRecent)
+                    .await?;
+                return Ok(ChangesetTail {
+                    changesets: Vec::new(),
+                    next_cursor: latest.unwrap_or(0),
+                });
+            }
+        };
+
+        let entries = log
+            .read_next_bookmark_log_entries(
+                self.ctx.clone(),
+                cursor,
+                limit as u64,
+                Freshness::MaybeStale,
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut changesets = Vec::new();
+        let mut next_cursor = cursor;
+        for entry in entries {
+            if !changesets.is_empty() && changesets.len() >= limit {
+                break;
+            }
+            next_cursor = entry.id as u64;
+            if bookmark
+                .as_ref()
+                .map_or(false, |b| b != &entry.bookmark_name)
+            {
+                continue;
+            }
+            let to = match entry.to_changeset_id {
+                Some(to) => to,
+                None => continue,
+            };
+            let mut landed = match entry.from_changeset_id {
+                Some(from) => {
+                    self.difference_of_unions_of_ancestors(vec![to], vec![from])
+                        .take(MAX_CHANGESETS_PER_MOVE)
+                        .try_collect::<Vec<_>>()
+                        .await?
+                }
+                // The bookmark was created, which doesn't land anything
+                // other than the commit it points to.
+                None => vec![ChangesetContext::new(self.clone(), to)],
+            };
+            landed.reverse();
+            changesets.extend(landed.into_iter().map(|changeset| TailedChangeset {
+                changeset,
+                bookmark: entry.bookmark_name.clone(),
+                pushed_at: entry.timestamp,
+                cursor: next_cursor,
+            }));
+        }
+
+        Ok(ChangesetTail {
+            changesets,
+            next_cursor,
+        })
+    }
+
     /// Get a list of bookmarks.
     pub async fn list_bookmarks(
         &self,
@@ -1338,6 +1742,7 @@ impl RepoContext {
             None => BookmarkPagination::FromStart,
         };
 
+        let gate = self.bookmark_publishing_gate();
         if include_scratch {
             // Scratch bookmarks must be queried directly from the blobrepo as
             // they are not stored in the cache.  To maintain ordering with
@@ -1356,7 +1761,7 @@ impl RepoContext {
                 )
                 .try_filter_map(move |(bookmark, cs_id)| async move {
                     if bookmark.kind() == &BookmarkKind::Scratch {
-                        Ok(Some((bookmark.into_name().into_string(), cs_id)))
+                        Ok(Some((bookmark.into_name(), cs_id)))
                     } else {
                         // For non-scratch bookmarks, always return the value
                         // from the cache so that clients only ever see the
@@ -1365,23 +1770,47 @@ impl RepoContext {
                         // filter this bookmark out.
                         let bookmark_name = bookmark.into_name();
                         let maybe_cs_id = cache.get(&self.ctx, &bookmark_name).await?;
-                        Ok(maybe_cs_id.map(|cs_id| (bookmark_name.into_string(), cs_id)))
+                        Ok(maybe_cs_id.map(|cs_id| (bookmark_name, cs_id)))
                     }
                 })
-                .map_err(MononokeError::from)
                 .boxed();
-            Ok(bookmarks)
+            Ok(self.published_bookmarks(gate, bookmarks))
         } else {
             // Public bookmarks can be fetched from the warm bookmarks cache.
             let cache = self.warm_bookmarks_cache();
-            Ok(
-                stream::iter(cache.list(&self.ctx, &prefix, &pagination, limit).await?)
-                    .map(|(bookmark, (cs_id, _kind))| Ok((bookmark.into_string(), cs_id)))
-                    .boxed(),
-            )
+            let bookmarks = stream::iter(cache.list(&self.ctx, &prefix, &pagination, limit).await?)
+                .map(|(bookmark, (cs_id, _kind))| Ok((bookmark, cs_id)))
+                .boxed();
+            Ok(self.published_bookmarks(gate, bookmarks))
         }
     }
 
+    /// Replace the positions of delayed bookmarks with the positions that
+    /// have been published to this client, leaving out the bookmarks that
+    /// haven't been published yet.
+    fn published_bookmarks<'a>(
+        &'a self,
+        gate: BookmarkPublishingGate,
+        bookmarks: BoxStream<'a, Result<(BookmarkName, ChangesetId), Error>>,
+    ) -> BoxStream<'a, Result<(String, ChangesetId), MononokeError>> {
+        let gate = Arc::new(gate);
+        bookmarks
+            .try_filter_map(move |(bookmark, cs_id)| {
+                let gate = gate.clone();
+                async move {
+                    let published = if gate.is_delayed(&bookmark) {
+                        gate.published_position(&self.ctx, self.blob_repo(), &bookmark, Some(cs_id))
+                            .await?
+                    } else {
+                        Some(cs_id)
+                    };
+                    Ok(published.map(|cs_id| (bookmark.into_string(), cs_id)))
+                }
+            })
+            .map_err(MononokeError::from)
+            .boxed()
+    }
+
     /// Get a stack for the list of heads (up to the first public commit).
     ///
     /// Limit constrains the number of draft commits returned.
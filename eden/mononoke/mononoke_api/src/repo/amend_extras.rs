@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+
+use anyhow::Context;
+use blobstore::Loadable;
+use chrono::Local;
+use mercurial_derived_data::DeriveHgChangeset;
+use mercurial_mutation::HgMutationEntry;
+use mercurial_mutation::HgMutationStoreRef;
+use mononoke_types::ChangesetId;
+use repo_authorization::RepoWriteOperation;
+use repo_blobstore::RepoBlobstoreRef;
+
+use crate::changeset::ChangesetContext;
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// Name of the mutation operation recorded for amended extras. This matches
+/// the operation Mercurial records when only commit metadata is edited.
+const AMEND_EXTRAS_MUTATION_OP: &str = "metaedit";
+
+impl RepoContext {
+    /// Create a successor of a changeset that has the same contents and
+    /// metadata, apart from its extras.
+    ///
+    /// Extras in `set_extras` are added or replaced, and extras in
+    /// `remove_extras` are removed.  If Mercurial changesets are derived for
+    /// this repo, a mutation entry is recorded from the original changeset to
+    /// the new one, so that clients that have the original changeset follow
+    /// to the amended version.
+    pub async fn amend_changeset_extras(
+        &self,
+        changeset: ChangesetId,
+        set_extras: BTreeMap<String, Vec<u8>>,
+        remove_extras: BTreeSet<String>,
+    ) -> Result<ChangesetContext, MononokeError> {
+        self.start_write()?;
+        self.authorization_context()
+            .require_repo_write(
+                self.ctx(),
+                self.inner_repo(),
+                RepoWriteOperation::CreateChangeset,
+            )
+            .await?;
+
+        if let Some(name) = set_extras.keys().find(|name| remove_extras.contains(*name)) {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Extra '{}' cannot be both set and removed",
+                name
+            )));
+        }
+
+        let original = changeset
+            .load(self.ctx(), self.blob_repo().repo_blobstore())
+            .await?;
+        if original.is_snapshot() {
+            return Err(MononokeError::InvalidRequest(format!(
+                "Cannot amend snapshot {}",
+                changeset
+            )));
+        }
+
+        let mut amended = original.into_mut();
+        for name in remove_extras {
+            amended.extra.remove(&name);
+        }
+        for (name, value) in set_extras {
+            amended.extra.insert(name, value);
+        }
+        let amended = amended.freeze().map_err(|e| {
+            MononokeError::InvalidRequest(format!(
+                "Amended extras create invalid bonsai changeset: {}",
+                e
+            ))
+        })?;
+        let amended_id = amended.get_changeset_id();
+        if amended_id == changeset {
+            return Err(MononokeError::InvalidRequest(String::from(
+                "Amending extras did not change the changeset",
+            )));
+        }
+
+        self.save_changeset(amended, self.inner_repo(), None)
+            .await?;
+
+        if self.derive_hgchangesets_enabled() {
+            self.record_amend_mutation(changeset, amended_id).await?;
+        }
+
+        Ok(ChangesetContext::new(self.clone(), amended_id))
+    }
+
+    async fn record_amend_mutation(
+        &self,
+        predecessor: ChangesetId,
+        successor: ChangesetId,
+    ) -> Result<(), MononokeError> {
+        let blob_repo = self.blob_repo();
+        let (predecessor_hg, successor_hg) = futures::try_join!(
+            blob_repo.derive_hg_changeset(self.ctx(), predecessor),
+            blob_repo.derive_hg_changeset(self.ctx(), successor),
+        )?;
+
+        let user = self
+            .ctx()
+            .metadata()
+            .unix_name()
+            .unwrap_or("unknown")
+            .to_string();
+        let now = Local::now();
+        let entry = HgMutationEntry::new(
+            successor_hg,
+            vec![predecessor_hg],
+            Vec::new(),
+            AMEND_EXTRAS_MUTATION_OP.to_string(),
+            user,
+            now.timestamp(),
+            // Mercurial timezone offsets are in seconds west of UTC.
+            -now.offset().local_minus_utc(),
+            Vec::new(),
+        );
+        self.inner_repo()
+            .hg_mutation_store()
+            .add_entries(self.ctx(), HashSet::from([successor_hg]), vec![entry])
+            .await
+            .context("Failed to record amend mutation")?;
+
+        Ok(())
+    }
+}
@@ -28,6 +28,7 @@ use futures::try_join;
 use futures::StreamExt;
 use futures_stats::TimedFutureExt;
 use manifest::PathTree;
+use metaconfig_types::RepoConfigArc;
 use metaconfig_types::RepoConfigRef;
 use mononoke_types::fsnode::FsnodeEntry;
 use mononoke_types::BonsaiChangeset;
@@ -37,9 +38,12 @@ use mononoke_types::DateTime as MononokeDateTime;
 use mononoke_types::FileChange;
 use mononoke_types::MPath;
 use mononoke_types::MPathElement;
+use mutable_counters::MutableCountersArc;
+use path_policy::PathPolicy;
 use repo_authorization::RepoWriteOperation;
 use repo_blobstore::RepoBlobstore;
 use repo_blobstore::RepoBlobstoreRef;
+use repo_identity::RepoIdentityArc;
 use repo_identity::RepoIdentityRef;
 use repo_update_logger::log_new_commits;
 use repo_update_logger::CommitInfo;
@@ -407,10 +411,18 @@ async fn check_addless_union_conflicts(
 }
 
 impl RepoContext {
-    async fn save_changeset(
+    pub(crate) async fn save_changeset(
         &self,
         changeset: BonsaiChangeset,
-        repo: &(impl ChangesetsRef + RepoBlobstoreRef + RepoIdentityRef + RepoConfigRef),
+        repo: &(
+             impl ChangesetsRef
+             + RepoBlobstoreRef
+             + RepoIdentityRef
+             + RepoIdentityArc
+             + RepoConfigRef
+             + RepoConfigArc
+             + MutableCountersArc
+         ),
         bubble: Option<&Bubble>,
     ) -> Result<(), MononokeError> {
         blobrepo::save_bonsai_changesets(vec![changeset.clone()], self.ctx().clone(), repo).await?;
@@ -477,6 +489,30 @@ impl RepoContext {
             )));
         }
 
+        // Check that new and modified paths follow the repo's path policy.
+        let path_policy = PathPolicy::new(&self.config().path_policy);
+        for (path, change) in changes.iter() {
+            if change.change_type() == CreateChangeType::Change {
+                if let Some(mpath) = path.as_mpath() {
+                    path_policy
+                        .check_path(mpath)
+                        .map_err(|e| MononokeError::InvalidRequest(e.to_string()))?;
+                    if let CreateChange::Tracked(
+                        CreateChangeFile::New {
+                            bytes,
+                            file_type: FileType::Symlink,
+                        },
+                        _,
+                    ) = change
+                    {
+                        path_policy
+                            .check_symlink_target(mpath, bytes)
+                            .map_err(|e| MononokeError::InvalidRequest(e.to_string()))?;
+                    }
+                }
+            }
+        }
+
         // Obtain contexts for each of the parents (which should exist).
         let parent_ctxs: Vec<_> = parents
             .iter()
@@ -652,8 +688,8 @@ impl RepoContext {
         let committer_date = committer_date.map(MononokeDateTime::new);
         let extra = extra.into();
 
-        // Create the new Bonsai Changeset. The `freeze` method validates
-        // that the bonsai changeset is internally consistent.
+        // Create the new Bonsai Changeset. The `validate` method checks
+        // that the bonsai changeset is well-formed.
         let new_changeset = BonsaiChangesetMut {
             parents,
             author,
@@ -664,9 +700,11 @@ impl RepoContext {
             extra,
             file_changes,
             is_snapshot: bubble.is_some(),
-        }
-        .freeze()
-        .map_err(|e| {
+        };
+        new_changeset.validate().map_err(|e| {
+            MononokeError::InvalidRequest(format!("Changes create invalid bonsai changeset: {}", e))
+        })?;
+        let new_changeset = new_changeset.freeze().map_err(|e| {
             MononokeError::InvalidRequest(format!("Changes create invalid bonsai changeset: {}", e))
         })?;
 
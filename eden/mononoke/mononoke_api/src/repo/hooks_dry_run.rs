@@ -0,0 +1,174 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use chrono::DateTime;
+use chrono::FixedOffset;
+use futures::stream::FuturesUnordered;
+use futures::stream::TryStreamExt;
+use hooks::CrossRepoPushSource;
+use hooks::HookOutcome;
+use hooks::PushAuthoredBy;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::BonsaiChangesetMut;
+use mononoke_types::ChangesetId;
+use mononoke_types::DateTime as MononokeDateTime;
+use mononoke_types::FileChange;
+use mononoke_types::MPath;
+use sorted_vector_map::SortedVectorMap;
+
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+use crate::repo::create_changeset::CreateChange;
+use crate::repo::create_changeset::CreateChangeFile;
+use crate::repo::RepoContext;
+use crate::specifiers::ChangesetSpecifier;
+
+/// A commit in a prospective stack to run hooks on.
+pub struct DryRunChangeset {
+    pub author: String,
+    pub author_date: DateTime<FixedOffset>,
+    pub committer: Option<String>,
+    pub committer_date: Option<DateTime<FixedOffset>>,
+    pub message: String,
+    pub extra: BTreeMap<String, Vec<u8>>,
+    pub changes: BTreeMap<MononokePath, CreateChange>,
+}
+
+impl RepoContext {
+    /// Run the hooks for a bookmark on a prospective stack of changesets,
+    /// without storing the changesets or moving the bookmark.
+    ///
+    /// The first changeset in the stack has the given parents, and each
+    /// other changeset is a child of the one before it.  New file contents
+    /// must already have been uploaded, and copy information is ignored.
+    ///
+    /// Returns the outcomes of the hooks for each changeset, in stack order.
+    pub async fn run_hooks_dry_run(
+        &self,
+        bookmark: &BookmarkName,
+        parents: Vec<ChangesetId>,
+        stack: Vec<DryRunChangeset>,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<Vec<(ChangesetId, Vec<HookOutcome>)>, MononokeError> {
+        self.authorization_context()
+            .require_full_repo_read(self.ctx(), self.inner_repo())
+            .await?;
+
+        for parent in parents.iter() {
+            if self
+                .changeset(ChangesetSpecifier::Bonsai(*parent))
+                .await?
+                .is_none()
+            {
+                return Err(MononokeError::InvalidRequest(format!(
+                    "Parent {} does not exist",
+                    parent
+                )));
+            }
+        }
+
+        let mut changesets = Vec::with_capacity(stack.len());
+        let mut parents = parents;
+        for dry_run_changeset in stack {
+            let changeset = self.dry_run_changeset(parents, dry_run_changeset).await?;
+            parents = vec![changeset.get_changeset_id()];
+            changesets.push(changeset);
+        }
+
+        let outcomes = self
+            .hook_manager()
+            .run_hooks_for_bookmark(
+                self.ctx(),
+                changesets.iter(),
+                bookmark,
+                pushvars,
+                CrossRepoPushSource::NativeToThisRepo,
+                PushAuthoredBy::User,
+            )
+            .await?;
+
+        let mut outcomes_by_changeset: HashMap<ChangesetId, Vec<HookOutcome>> = HashMap::new();
+        for outcome in outcomes {
+            outcomes_by_changeset
+                .entry(outcome.get_changeset_id())
+                .or_default()
+                .push(outcome);
+        }
+        Ok(changesets
+            .iter()
+            .map(|changeset| {
+                let cs_id = changeset.get_changeset_id();
+                (
+                    cs_id,
+                    outcomes_by_changeset.remove(&cs_id).unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    /// Build the bonsai changeset for a commit in a dry run, without
+    /// storing anything.
+    async fn dry_run_changeset(
+        &self,
+        parents: Vec<ChangesetId>,
+        changeset: DryRunChangeset,
+    ) -> Result<BonsaiChangeset, MononokeError> {
+        let file_changes = changeset
+            .changes
+            .into_iter()
+            .map(|(path, change)| async move {
+                let mpath = path.into_mpath().ok_or_else(|| {
+                    MononokeError::InvalidRequest(String::from(
+                        "Cannot create a file with an empty path",
+                    ))
+                })?;
+                let change = match change {
+                    CreateChange::Tracked(CreateChangeFile::New { .. }, _)
+                    | CreateChange::Untracked(CreateChangeFile::New { .. }) => {
+                        return Err(MononokeError::InvalidRequest(format!(
+                            "File contents for '{}' must be uploaded before running hooks",
+                            mpath
+                        )));
+                    }
+                    CreateChange::Tracked(file, _copy_info) => CreateChange::Tracked(file, None),
+                    change => change,
+                };
+                let change = change
+                    .resolve(
+                        self.ctx(),
+                        self.blob_repo().filestore_config(),
+                        self.blob_repo().blobstore().clone(),
+                        &[],
+                    )
+                    .await?;
+                Ok::<_, MononokeError>((mpath, change))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect::<SortedVectorMap<MPath, FileChange>>()
+            .await?;
+
+        let changeset = BonsaiChangesetMut {
+            parents,
+            author: changeset.author,
+            author_date: MononokeDateTime::new(changeset.author_date),
+            committer: changeset.committer,
+            committer_date: changeset.committer_date.map(MononokeDateTime::new),
+            message: changeset.message,
+            extra: changeset.extra.into(),
+            file_changes,
+            is_snapshot: false,
+        };
+        changeset.freeze().map_err(|e| {
+            MononokeError::InvalidRequest(format!("Changes create invalid bonsai changeset: {}", e))
+        })
+    }
+}
@@ -0,0 +1,283 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks_movement::BookmarkMovement;
+use bookmarks_movement::BookmarkUpdatePolicy;
+use bookmarks_movement::BookmarkUpdateTargets;
+use bookmarks_movement::MultipleBookmarksOp;
+use bytes::Bytes;
+use futures::future::try_join_all;
+use hooks::HookManagerRef;
+use mononoke_types::ChangesetId;
+use reachabilityindex::LeastCommonAncestorsHint;
+use skiplist::SkiplistIndexArc;
+use tunables::tunables;
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// A modification of a single bookmark, as part of a request to modify
+/// several bookmarks at once.
+#[derive(Clone, Debug)]
+pub enum BookmarkModification {
+    /// Create a new bookmark.
+    Create {
+        bookmark: String,
+        target: ChangesetId,
+    },
+    /// Move an existing bookmark.  If `old_target` is provided, the bookmark
+    /// is only moved if it currently points at that commit.
+    Move {
+        bookmark: String,
+        target: ChangesetId,
+        old_target: Option<ChangesetId>,
+        allow_non_fast_forward: bool,
+    },
+    /// Delete an existing bookmark.  If `old_target` is provided, the
+    /// bookmark is only deleted if it currently points at that commit.
+    Delete {
+        bookmark: String,
+        old_target: Option<ChangesetId>,
+    },
+}
+
+/// A bookmark modification where the old target of moved or deleted
+/// bookmarks is known.
+enum ResolvedModification {
+    Create {
+        bookmark: BookmarkName,
+        target: ChangesetId,
+    },
+    Move {
+        bookmark: BookmarkName,
+        target: ChangesetId,
+        old_target: ChangesetId,
+        allow_non_fast_forward: bool,
+    },
+    Delete {
+        bookmark: BookmarkName,
+        old_target: ChangesetId,
+    },
+}
+
+impl ResolvedModification {
+    fn bookmark(&self) -> &BookmarkName {
+        match self {
+            Self::Create { bookmark, .. }
+            | Self::Move { bookmark, .. }
+            | Self::Delete { bookmark, .. } => bookmark,
+        }
+    }
+
+    fn movement(&self) -> BookmarkMovement<'_> {
+        match self {
+            Self::Create { bookmark, target } => BookmarkMovement::Create {
+                bookmark,
+                target: *target,
+            },
+            Self::Move {
+                bookmark,
+                target,
+                old_target,
+                allow_non_fast_forward,
+            } => BookmarkMovement::Update {
+                bookmark,
+                targets: BookmarkUpdateTargets {
+                    old: *old_target,
+                    new: *target,
+                },
+                update_policy: if *allow_non_fast_forward {
+                    BookmarkUpdatePolicy::AnyPermittedByConfig
+                } else {
+                    BookmarkUpdatePolicy::FastForwardOnly
+                },
+            },
+            Self::Delete {
+                bookmark,
+                old_target,
+            } => BookmarkMovement::Delete {
+                bookmark,
+                old_target: *old_target,
+            },
+        }
+    }
+}
+
+impl RepoContext {
+    /// Find out where a bookmark currently points to in order to move or
+    /// delete it.  Make sure to bypass any out-of-date caches.
+    async fn current_bookmark_target(
+        &self,
+        bookmark: &BookmarkName,
+    ) -> Result<ChangesetId, MononokeError> {
+        self.blob_repo()
+            .bookmarks()
+            .get(self.ctx().clone(), bookmark)
+            .await
+            .context("Failed to fetch old bookmark target")?
+            .ok_or_else(|| {
+                MononokeError::InvalidRequest(format!("bookmark '{}' does not exist", bookmark))
+            })
+    }
+
+    async fn resolve_bookmark_modification(
+        &self,
+        modification: BookmarkModification,
+    ) -> Result<ResolvedModification, MononokeError> {
+        Ok(match modification {
+            BookmarkModification::Create { bookmark, target } => ResolvedModification::Create {
+                bookmark: BookmarkName::new(bookmark)?,
+                target,
+            },
+            BookmarkModification::Move {
+                bookmark,
+                target,
+                old_target,
+                allow_non_fast_forward,
+            } => {
+                let bookmark = BookmarkName::new(bookmark)?;
+                let old_target = match old_target {
+                    Some(old_target) => old_target,
+                    None => self.current_bookmark_target(&bookmark).await?,
+                };
+                ResolvedModification::Move {
+                    bookmark,
+                    target,
+                    old_target,
+                    allow_non_fast_forward,
+                }
+            }
+            BookmarkModification::Delete {
+                bookmark,
+                old_target,
+            } => {
+                let bookmark = BookmarkName::new(bookmark)?;
+                let old_target = match old_target {
+                    Some(old_target) => old_target,
+                    None => self.current_bookmark_target(&bookmark).await?,
+                };
+                ResolvedModification::Delete {
+                    bookmark,
+                    old_target,
+                }
+            }
+        })
+    }
+
+    /// Modify several bookmarks atomically.  Either all of the modifications
+    /// are applied, or none of them are.
+    ///
+    /// Hooks are run for all of the modifications before any of them are
+    /// applied, and any hook rejections are reported together.
+    pub async fn modify_bookmarks(
+        &self,
+        modifications: Vec<BookmarkModification>,
+        pushvars: Option<&HashMap<String, Bytes>>,
+    ) -> Result<(), MononokeError> {
+        self.start_write()?;
+
+        let modifications = try_join_all(
+            modifications
+                .into_iter()
+                .map(|modification| self.resolve_bookmark_modification(modification)),
+        )
+        .await?;
+
+        fn make_op<'a>(
+            modifications: &'a [ResolvedModification],
+            pushvars: Option<&'a HashMap<String, Bytes>>,
+        ) -> MultipleBookmarksOp<'a> {
+            let mut op = MultipleBookmarksOp::new(
+                modifications
+                    .iter()
+                    .map(ResolvedModification::movement)
+                    .collect(),
+                BookmarkUpdateReason::ApiRequest,
+            )
+            .with_pushvars(pushvars);
+            if !tunables().get_disable_commit_scribe_logging_scs() {
+                op = op.log_new_public_commits_to_scribe();
+            }
+            op
+        }
+        if let Some(redirector) = self.push_redirector.as_ref() {
+            let ctx = self.ctx();
+            let large_modifications =
+                try_join_all(modifications.iter().map(|modification| async move {
+                    let bookmark = modification.bookmark();
+                    let large_bookmark = redirector.small_to_large_bookmark(bookmark).await?;
+                    if &large_bookmark == bookmark {
+                        return Err(MononokeError::InvalidRequest(format!(
+                            "Cannot modify shared bookmark '{}' from small repo",
+                            bookmark
+                        )));
+                    }
+                    let to_large =
+                        |cs_id| redirector.get_small_to_large_commit_equivalent(ctx, cs_id);
+                    Ok(match modification {
+                        ResolvedModification::Create { target, .. } => {
+                            ResolvedModification::Create {
+                                bookmark: large_bookmark,
+                                target: to_large(*target).await?,
+                            }
+                        }
+                        ResolvedModification::Move {
+                            target,
+                            old_target,
+                            allow_non_fast_forward,
+                            ..
+                        } => {
+                            let (target, old_target) =
+                                futures::try_join!(to_large(*target), to_large(*old_target))?;
+                            ResolvedModification::Move {
+                                bookmark: large_bookmark,
+                                target,
+                                old_target,
+                                allow_non_fast_forward: *allow_non_fast_forward,
+                            }
+                        }
+                        ResolvedModification::Delete { old_target, .. } => {
+                            ResolvedModification::Delete {
+                                bookmark: large_bookmark,
+                                old_target: to_large(*old_target).await?,
+                            }
+                        }
+                    })
+                }))
+                .await?;
+            make_op(&large_modifications, pushvars)
+                .run(
+                    self.ctx(),
+                    self.authorization_context(),
+                    redirector.repo.inner_repo(),
+                    &(redirector.repo.skiplist_index_arc() as Arc<dyn LeastCommonAncestorsHint>),
+                    redirector.repo.hook_manager(),
+                )
+                .await?;
+            // Wait for bookmarks to catch up on small repo
+            redirector.backsync_latest(ctx).await?;
+        } else {
+            make_op(&modifications, pushvars)
+                .run(
+                    self.ctx(),
+                    self.authorization_context(),
+                    self.inner_repo(),
+                    &(self.skiplist_index_arc() as Arc<dyn LeastCommonAncestorsHint>),
+                    self.hook_manager().as_ref(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
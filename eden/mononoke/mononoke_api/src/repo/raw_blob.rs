@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use blobstore::Blobstore;
+use blobstore::BlobstoreGetData;
+use redactedblobstore::ErrorKind as RedactionErrorKind;
+use repo_blobstore::RepoBlobstoreRef;
+use slog::info;
+
+use crate::errors::MononokeError;
+use crate::repo::RepoContext;
+
+/// Raw blobs larger than this are refused.
+const MAX_RAW_BLOB_SIZE: usize = 100 * 1024 * 1024;
+
+/// Prefixes of keys that can't be fetched.  Redaction applies to whole file
+/// contents, so the chunks and aliases of redacted contents are not redacted
+/// themselves.
+const UNREDACTED_KEY_PREFIXES: &[&str] = &["chunk.", "alias."];
+
+impl RepoContext {
+    /// Fetch the raw contents and metadata of a blob in the repo's
+    /// blobstore, for debugging.
+    ///
+    /// The key is relative to the repo, so only the repo's own blobs can be
+    /// fetched.  Redacted blobs are never returned, nor are file content
+    /// chunks and aliases, which redaction does not cover.  Every fetch is
+    /// logged for audit.
+    pub async fn fetch_raw_blob(
+        &self,
+        key: &str,
+    ) -> Result<Option<BlobstoreGetData>, MononokeError> {
+        self.authorization_context()
+            .require_raw_blob_read(self.ctx(), self.inner_repo())
+            .await?;
+
+        let mut scuba = self.ctx().scuba().clone();
+        scuba.add("blobstore_key", key);
+
+        if UNREDACTED_KEY_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+        {
+            scuba.log_with_msg("Raw blob fetch of unredacted key denied", None);
+            return Err(MononokeError::InvalidRequest(format!(
+                "raw blob fetch of {} is not permitted, as it is not covered by redaction",
+                key
+            )));
+        }
+        info!(
+            self.ctx().logger(),
+            "Raw blob fetch of {} from {} by {:?}",
+            key,
+            self.name(),
+            self.ctx().metadata().identities(),
+        );
+
+        let blob = match self
+            .inner_repo()
+            .repo_blobstore()
+            .get(self.ctx(), key)
+            .await
+        {
+            Ok(blob) => blob,
+            Err(e) => {
+                if let Some(RedactionErrorKind::Censored(..)) = e.downcast_ref() {
+                    scuba.log_with_msg("Raw blob fetch of redacted blob denied", None);
+                    return Err(MononokeError::AuthorizationError(e.to_string()));
+                }
+                return Err(e.into());
+            }
+        };
+
+        if let Some(blob) = &blob {
+            scuba.add("blob_size", blob.len());
+            if blob.len() > MAX_RAW_BLOB_SIZE {
+                scuba.log_with_msg("Raw blob fetch of large blob denied", None);
+                return Err(MononokeError::InvalidRequest(format!(
+                    "blob {} is {} bytes, which is larger than the limit of {} bytes",
+                    key,
+                    blob.len(),
+                    MAX_RAW_BLOB_SIZE
+                )));
+            }
+        }
+        scuba.log_with_msg("Raw blob fetched", None);
+
+        Ok(blob)
+    }
+}
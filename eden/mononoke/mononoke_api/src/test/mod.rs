@@ -6,10 +6,16 @@
  */
 
 mod test_blame;
+mod test_bookmark_publishing;
 mod test_changeset_diff;
+mod test_directory_moves;
 mod test_file_diff;
 mod test_history;
+mod test_merge_copy_trace;
+mod test_patch_id;
+mod test_path_rollup;
 mod test_repo;
+mod test_repo_amend_extras;
 mod test_repo_bookmarks;
 mod test_repo_create_changeset;
 mod test_repo_land_stack;
@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPublishingDelay;
+use metaconfig_types::Identity;
+use metaconfig_types::MergePolicy;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use tests_utils::bookmark;
+use tests_utils::CreateCommitContext;
+
+use crate::bookmark_publishing::BookmarkPublishingGate;
+use crate::bookmark_publishing::PublishedBookmarks;
+
+fn delayed_bookmark(bookmark: &str, approval_bookmark: &str) -> Result<BookmarkParams> {
+    Ok(BookmarkParams {
+        bookmark: BookmarkName::new(bookmark)?.into(),
+        hooks: vec![],
+        only_fast_forward: false,
+        rewrite_dates: None,
+        allowed_users: None,
+        allowed_hipster_group: None,
+        hooks_skip_ancestors_of: vec![],
+        ensure_ancestor_of: None,
+        allow_move_to_public_commits_without_hooks: false,
+        required_derived_data: vec![],
+        merge_policy: MergePolicy::default(),
+        publishing_delay: Some(BookmarkPublishingDelay {
+            delay: Duration::from_secs(3600),
+            canary_identities: vec![Identity {
+                id_type: "USER".to_string(),
+                id_data: "canary".to_string(),
+            }],
+            approval_bookmark: Some(BookmarkName::new(approval_bookmark)?),
+        }),
+    })
+}
+
+fn identities(user: &str) -> MononokeIdentitySet {
+    [MononokeIdentity::new("USER", user)].into_iter().collect()
+}
+
+#[fbinit::test]
+async fn test_bookmark_publishing_gate(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let bookmarks = vec![delayed_bookmark("main", "main_approved")?];
+    let main = BookmarkName::new("main")?;
+    let other = BookmarkName::new("other")?;
+
+    let first = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("1", "1")
+        .commit()
+        .await?;
+    let second = CreateCommitContext::new(&ctx, &repo, vec![first])
+        .add_file("2", "2")
+        .commit()
+        .await?;
+    bookmark(&ctx, &repo, "main").set_to(first).await?;
+    bookmark(&ctx, &repo, "main").set_to(second).await?;
+
+    // Canaries see moves straight away.
+    let gate = BookmarkPublishingGate::new(
+        &bookmarks,
+        &identities("canary"),
+        Arc::new(PublishedBookmarks::default()),
+    );
+    assert!(!gate.is_delayed(&main));
+    assert_eq!(
+        gate.published_position(&ctx, &repo, &main, Some(second))
+            .await?,
+        Some(second)
+    );
+
+    // Other clients don't see moves that haven't been published yet.
+    let other_gate = || {
+        BookmarkPublishingGate::new(
+            &bookmarks,
+            &identities("other"),
+            Arc::new(PublishedBookmarks::default()),
+        )
+    };
+    let gate = other_gate();
+    assert!(gate.is_delayed(&main));
+    assert!(!gate.is_delayed(&other));
+    assert_eq!(
+        gate.published_position(&ctx, &repo, &main, Some(second))
+            .await?,
+        None
+    );
+
+    // Until the approval bookmark is moved to them.
+    bookmark(&ctx, &repo, "main_approved").set_to(first).await?;
+    assert_eq!(
+        other_gate()
+            .published_position(&ctx, &repo, &main, Some(second))
+            .await?,
+        Some(first)
+    );
+    bookmark(&ctx, &repo, "main_approved")
+        .set_to(second)
+        .await?;
+    let gate = other_gate();
+    assert_eq!(
+        gate.published_position(&ctx, &repo, &main, Some(second))
+            .await?,
+        Some(second)
+    );
+
+    // A current position that is behind the published one is shown as is.
+    assert_eq!(
+        gate.published_position(&ctx, &repo, &main, Some(first))
+            .await?,
+        Some(first)
+    );
+
+    // Bookmarks without a delay are always published.
+    assert_eq!(
+        gate.published_position(&ctx, &repo, &other, Some(first))
+            .await?,
+        Some(first)
+    );
+    Ok(())
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use fbinit::FacebookInit;
+use pretty_assertions::assert_eq;
+use tests_utils::CreateCommitContext;
+
+use crate::CoreContext;
+use crate::DirectoryMove;
+use crate::MergeRename;
+use crate::MergeSide;
+use crate::Mononoke;
+use crate::MononokePath;
+
+#[fbinit::test]
+async fn test_directory_moves(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let base = CreateCommitContext::new_root(&ctx, &blobrepo)
+        .add_file("exact/sub/a", "a")
+        .add_file("exact/b", "b")
+        .add_file("similar/c", "c")
+        .add_file("similar/d", "d")
+        .add_file("similar/e", "e")
+        .add_file("unrelated/f", "f")
+        .commit()
+        .await?;
+
+    // Move one directory unchanged, move another while changing some of its
+    // files, and replace a third with a directory that has nothing in common
+    // with it.
+    let moved = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .delete_file("exact/sub/a")
+        .delete_file("exact/b")
+        .add_file("moved/exact/sub/a", "a")
+        .add_file("moved/exact/b", "b")
+        .delete_file("similar/c")
+        .delete_file("similar/d")
+        .delete_file("similar/e")
+        .add_file("renamed/c", "c")
+        .add_file("renamed/d", "d modified")
+        .add_file("renamed/g", "g")
+        .delete_file("unrelated/f")
+        .add_file("other/h", "h")
+        .commit()
+        .await?;
+
+    // Modify files in the moved directories on another branch.
+    let other = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .add_file("exact/sub/a", "a modified")
+        .add_file("similar/c", "c modified")
+        .commit()
+        .await?;
+
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let base_ctx = repo.changeset(base).await?.context("commit not found")?;
+    let moved_ctx = repo.changeset(moved).await?.context("commit not found")?;
+    let other_ctx = repo.changeset(other).await?.context("commit not found")?;
+
+    assert_eq!(
+        moved_ctx.directory_moves(&base_ctx).await?,
+        vec![
+            DirectoryMove {
+                from: MononokePath::try_from("exact")?,
+                to: MononokePath::try_from("moved/exact")?,
+                exact: true,
+            },
+            DirectoryMove {
+                from: MononokePath::try_from("similar")?,
+                to: MononokePath::try_from("renamed")?,
+                exact: false,
+            },
+        ]
+    );
+
+    // Copy tracing follows the moved directories.
+    assert_eq!(
+        moved_ctx.trace_merge_copies(&other_ctx, None).await?,
+        vec![
+            MergeRename {
+                renamed_on: MergeSide::Local,
+                base_path: MononokePath::try_from("exact/sub/a")?,
+                renamed_path: MononokePath::try_from("moved/exact/sub/a")?,
+            },
+            MergeRename {
+                renamed_on: MergeSide::Local,
+                base_path: MononokePath::try_from("similar/c")?,
+                renamed_path: MononokePath::try_from("renamed/c")?,
+            },
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use fbinit::FacebookInit;
+use pretty_assertions::assert_eq;
+use tests_utils::CreateCommitContext;
+
+use crate::CoreContext;
+use crate::MergeRename;
+use crate::MergeSide;
+use crate::Mononoke;
+use crate::MononokePath;
+
+#[fbinit::test]
+async fn test_trace_merge_copies(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let base = CreateCommitContext::new_root(&ctx, &blobrepo)
+        .add_file("renamed_twice", "a")
+        .add_file("renamed_unmodified", "b")
+        .add_file("copied", "c")
+        .add_file("renamed_other", "d")
+        .commit()
+        .await?;
+
+    // Local side: rename a file twice, rename a file that the other side
+    // doesn't touch, and copy a file without deleting it.
+    let local1 = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .add_file_with_copy_info("renamed_once", "a", (base, "renamed_twice"))
+        .delete_file("renamed_twice")
+        .add_file_with_copy_info("moved_unmodified", "b", (base, "renamed_unmodified"))
+        .delete_file("renamed_unmodified")
+        .add_file_with_copy_info("copy", "c", (base, "copied"))
+        .commit()
+        .await?;
+    let local2 = CreateCommitContext::new(&ctx, &blobrepo, vec![local1])
+        .add_file_with_copy_info("dir/final_name", "a", (local1, "renamed_once"))
+        .delete_file("renamed_once")
+        .commit()
+        .await?;
+
+    // Other side: modify the files at their original paths, and rename a
+    // file that the local side modifies.
+    let other = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .add_file("renamed_twice", "a modified")
+        .add_file("copied", "c modified")
+        .add_file_with_copy_info("other_name", "d", (base, "renamed_other"))
+        .delete_file("renamed_other")
+        .commit()
+        .await?;
+    let local3 = CreateCommitContext::new(&ctx, &blobrepo, vec![local2])
+        .add_file("renamed_other", "d modified")
+        .commit()
+        .await?;
+
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let local_ctx = repo.changeset(local3).await?.context("commit not found")?;
+    let other_ctx = repo.changeset(other).await?.context("commit not found")?;
+
+    let renames = local_ctx.trace_merge_copies(&other_ctx, None).await?;
+    assert_eq!(
+        renames,
+        vec![
+            MergeRename {
+                renamed_on: MergeSide::Other,
+                base_path: MononokePath::try_from("renamed_other")?,
+                renamed_path: MononokePath::try_from("other_name")?,
+            },
+            MergeRename {
+                renamed_on: MergeSide::Local,
+                base_path: MononokePath::try_from("renamed_twice")?,
+                renamed_path: MononokePath::try_from("dir/final_name")?,
+            },
+        ]
+    );
+
+    // Merging a commit with its own ancestor finds no renames.
+    let base_ctx = repo.changeset(base).await?.context("commit not found")?;
+    assert_eq!(local_ctx.trace_merge_copies(&base_ctx, None).await?, vec![]);
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use fbinit::FacebookInit;
+use tests_utils::CreateCommitContext;
+
+use crate::CoreContext;
+use crate::Mononoke;
+
+#[fbinit::test]
+async fn test_find_duplicates(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let blobrepo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let base = CreateCommitContext::new_root(&ctx, &blobrepo)
+        .add_file("file", "a\nb\nc\nd\ne\n")
+        .commit()
+        .await?;
+    let release = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .add_file("file", "release\na\nb\nc\nd\ne\n")
+        .commit()
+        .await?;
+
+    // A fix, and its cherry-pick onto the release branch, where the lines
+    // it changes are at a different position in the file.
+    let fix = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .set_message("fix")
+        .add_file("file", "a\nb\nC\nd\ne\n")
+        .commit()
+        .await?;
+    let backport = CreateCommitContext::new(&ctx, &blobrepo, vec![release])
+        .set_message("backport of fix")
+        .add_file("file", "release\na\nb\nC\nd\ne\n")
+        .commit()
+        .await?;
+    let different = CreateCommitContext::new(&ctx, &blobrepo, vec![base])
+        .add_file("file", "a\nb\nc\nD\ne\n")
+        .commit()
+        .await?;
+
+    let mononoke =
+        Mononoke::new_test(ctx.clone(), vec![("test".to_string(), blobrepo.clone())]).await?;
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let fix_ctx = repo.changeset(fix).await?.context("commit not found")?;
+    let backport_ctx = repo
+        .changeset(backport)
+        .await?
+        .context("commit not found")?;
+    let different_ctx = repo
+        .changeset(different)
+        .await?
+        .context("commit not found")?;
+
+    // Only changesets whose patch ids have been computed can be found.
+    assert!(fix_ctx.find_duplicates().await?.is_empty());
+    assert!(different_ctx.patch_id().await?.is_some());
+
+    let duplicates = backport_ctx.find_duplicates().await?;
+    assert_eq!(
+        duplicates.iter().map(|cs| cs.id()).collect::<Vec<_>>(),
+        vec![fix]
+    );
+    assert_eq!(fix_ctx.patch_id().await?, backport_ctx.patch_id().await?);
+    assert_ne!(fix_ctx.patch_id().await?, different_ctx.patch_id().await?);
+
+    Ok(())
+}
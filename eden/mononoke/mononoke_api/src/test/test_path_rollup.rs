@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types::DateTime;
+use tests_utils::CreateCommitContext;
+
+use crate::Repo;
+use crate::RepoContext;
+
+#[fbinit::test]
+async fn path_rollup(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let blob_repo = test_repo_factory::build_empty(ctx.fb)?;
+
+    let c1 = CreateCommitContext::new_root(&ctx, &blob_repo)
+        .add_file("a", "1")
+        .add_file("dir/b", "22")
+        .add_file("dir/sub/c", "333")
+        .set_author_date(DateTime::from_timestamp(1000, 0)?)
+        .commit()
+        .await?;
+    let c2 = CreateCommitContext::new(&ctx, &blob_repo, vec![c1])
+        .add_file("dir/sub/c", "4444")
+        .add_file("other", "5")
+        .set_author_date(DateTime::from_timestamp(2000, 0)?)
+        .commit()
+        .await?;
+    let c3 = CreateCommitContext::new(&ctx, &blob_repo, vec![c2])
+        .add_file("a", "66")
+        .set_author_date(DateTime::from_timestamp(3000, 0)?)
+        .commit()
+        .await?;
+
+    let repo = Repo::new_test(ctx.clone(), blob_repo).await?;
+    let repo = RepoContext::new_test(ctx.clone(), Arc::new(repo)).await?;
+    let cs = repo.changeset(c3).await?.expect("changeset exists");
+
+    let root = cs.root().await?.rollup().await?.expect("root exists");
+    assert_eq!(root.total_size, 9);
+    assert_eq!(root.file_count, 4);
+    assert_eq!(root.last_modified, c3);
+    assert_eq!(root.last_modified_date.timestamp(), 3000);
+
+    let dir = cs
+        .path_with_content("dir")
+        .await?
+        .rollup()
+        .await?
+        .expect("dir exists");
+    assert_eq!(dir.total_size, 6);
+    assert_eq!(dir.file_count, 2);
+    assert_eq!(dir.last_modified, c2);
+    assert_eq!(dir.last_modified_date.timestamp(), 2000);
+
+    let file = cs
+        .path_with_content("dir/b")
+        .await?
+        .rollup()
+        .await?
+        .expect("file exists");
+    assert_eq!(file.total_size, 2);
+    assert_eq!(file.file_count, 1);
+    assert_eq!(file.last_modified, c1);
+
+    assert!(
+        cs.path_with_content("missing")
+            .await?
+            .rollup()
+            .await?
+            .is_none()
+    );
+
+    Ok(())
+}
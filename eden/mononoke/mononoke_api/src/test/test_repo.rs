@@ -37,6 +37,7 @@ use mononoke_types::hash::RichGitSha1;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
 use mononoke_types::MPath;
+use mononoke_types::RepositoryId;
 use slog::info;
 use synced_commit_mapping::SyncedCommitMapping;
 use tests_utils::bookmark;
@@ -56,6 +57,7 @@ use crate::CoreContext;
 use crate::FileId;
 use crate::FileMetadata;
 use crate::FileType;
+use crate::Globalrev;
 use crate::HgChangesetId;
 use crate::HgChangesetIdPrefix;
 use crate::Mononoke;
@@ -1412,3 +1414,152 @@ async fn resolve_changeset_id_prefix(fb: FacebookInit) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn resolve_ambiguous_changeset_id_prefix(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), BranchUneven::getrepo(fb).await)],
+    )
+    .await?;
+
+    let repo = mononoke
+        .repo(ctx, "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+
+    // An ambiguous prefix resolves to every commit it matches, so that they
+    // can be reported as candidates.
+    let resolution = repo
+        .resolve_changeset_id_prefix(HgChangesetIdPrefix::from_str("b6")?.into())
+        .await?;
+    assert!(matches!(
+        resolution,
+        ChangesetSpecifierPrefixResolution::Multiple(_)
+    ));
+    let mut candidates = Vec::new();
+    for specifier in resolution.into_list() {
+        let changeset = repo.changeset(specifier).await?.expect("changeset exists");
+        candidates.push(changeset.hg_id().await?.expect("hg id exists"));
+    }
+    candidates.sort();
+    assert_eq!(
+        candidates,
+        vec![
+            HgChangesetId::from_str("b65231269f651cfe784fd1d97ef02a049a37b8a0")?,
+            HgChangesetId::from_str("b6a8169454af58b4b72b3665f9aa0d25529755ff")?,
+        ]
+    );
+
+    assert_eq!(
+        repo.resolve_changeset_id_prefix(HgChangesetIdPrefix::from_str("1")?.into())
+            .await?
+            .into_list()
+            .len(),
+        3
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn locate_changeset_prefix(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![
+            (
+                "linear".to_string(),
+                Linear::getrepo_with_id(fb, RepositoryId::new(0)).await,
+            ),
+            (
+                "branch".to_string(),
+                BranchUneven::getrepo_with_id(fb, RepositoryId::new(1)).await,
+            ),
+            (
+                "linear-fork".to_string(),
+                Linear::getrepo_with_id(fb, RepositoryId::new(2)).await,
+            ),
+        ],
+    )
+    .await?;
+
+    let bonsai_cs_id = ChangesetSpecifier::Bonsai(ChangesetId::from_str(
+        "7785606eb1f26ff5722c831de402350cf97052dc44bc175da6ac0d715a3dbbf6",
+    )?);
+    let located = mononoke
+        .locate_changeset_prefix(
+            ctx.clone(),
+            ChangesetIdPrefix::from_str("7785606")?.into(),
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|(repo, resolution)| (repo.name().to_string(), resolution))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        located,
+        vec![
+            (
+                "linear".to_string(),
+                ChangesetSpecifierPrefixResolution::Single(bonsai_cs_id),
+            ),
+            (
+                "linear-fork".to_string(),
+                ChangesetSpecifierPrefixResolution::Single(bonsai_cs_id),
+            ),
+        ]
+    );
+
+    assert!(
+        mononoke
+            .locate_changeset_prefix(
+                ctx.clone(),
+                HgChangesetIdPrefix::from_str("607314efffff")?.into(),
+                None,
+            )
+            .await?
+            .is_empty()
+    );
+
+    let scoped = mononoke
+        .locate_changeset_prefix(
+            ctx.clone(),
+            ChangesetIdPrefix::from_str("7785606")?.into(),
+            Some(&["linear-fork".to_string(), "branch".to_string()][..]),
+        )
+        .await?
+        .into_iter()
+        .map(|(repo, resolution)| (repo.name().to_string(), resolution))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        scoped,
+        vec![(
+            "linear-fork".to_string(),
+            ChangesetSpecifierPrefixResolution::Single(bonsai_cs_id),
+        )]
+    );
+
+    assert!(
+        mononoke
+            .locate_changeset_prefix(
+                ctx.clone(),
+                ChangesetIdPrefix::from_str("7785606")?.into(),
+                Some(&["nonexistent".to_string()][..]),
+            )
+            .await
+            .is_err()
+    );
+
+    assert!(
+        mononoke
+            .locate_changeset_prefix(ctx, Globalrev::new(1).into(), None)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
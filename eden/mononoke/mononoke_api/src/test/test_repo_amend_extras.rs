@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::Error;
+use assert_matches::assert_matches;
+use fbinit::FacebookInit;
+use fixtures::Linear;
+use fixtures::TestRepoFixture;
+use mercurial_mutation::HgMutationStoreRef;
+
+use crate::ChangesetId;
+use crate::CoreContext;
+use crate::Mononoke;
+use crate::MononokeError;
+
+#[fbinit::test]
+async fn test_amend_extras(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), Linear::getrepo(fb).await)],
+    )
+    .await?;
+    let repo = mononoke
+        .repo(ctx.clone(), "test")
+        .await?
+        .expect("repo exists")
+        .build()
+        .await?;
+    let cs_id =
+        ChangesetId::from_str("7785606eb1f26ff5722c831de402350cf97052dc44bc175da6ac0d715a3dbbf6")?;
+    let original = repo.changeset(cs_id).await?.expect("changeset exists");
+
+    let set_extras = BTreeMap::from([(
+        String::from("review_url"),
+        b"https://example.com/D123".to_vec(),
+    )]);
+    let amended = repo
+        .amend_changeset_extras(cs_id, set_extras.clone(), BTreeSet::new())
+        .await?;
+    assert_ne!(amended.id(), cs_id);
+    assert_eq!(amended.message().await?, original.message().await?);
+    assert_eq!(amended.parents().await?, original.parents().await?);
+    assert!(amended.extras().await?.contains(&(
+        String::from("review_url"),
+        b"https://example.com/D123".to_vec()
+    )));
+
+    // The amended commit is recorded as a successor of the original one.
+    let original_hg = original.hg_id().await?.expect("hg changeset exists");
+    let amended_hg = amended.hg_id().await?.expect("hg changeset exists");
+    let entries = repo
+        .inner_repo()
+        .hg_mutation_store()
+        .all_predecessors(&ctx, HashSet::from([amended_hg]))
+        .await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].successor(), &amended_hg);
+    assert_eq!(entries[0].predecessors(), &[original_hg]);
+    assert_eq!(entries[0].op(), "metaedit");
+
+    // Removing the extra again gives back the original commit, which is
+    // rejected as it doesn't change anything.
+    let removed = repo
+        .amend_changeset_extras(
+            amended.id(),
+            BTreeMap::new(),
+            BTreeSet::from([String::from("review_url")]),
+        )
+        .await;
+    assert_matches!(removed, Err(MononokeError::InvalidRequest(_)));
+
+    // Setting and removing the same extra is invalid.
+    let conflicting = repo
+        .amend_changeset_extras(
+            cs_id,
+            set_extras,
+            BTreeSet::from([String::from("review_url")]),
+        )
+        .await;
+    assert_matches!(conflicting, Err(MononokeError::InvalidRequest(_)));
+
+    Ok(())
+}
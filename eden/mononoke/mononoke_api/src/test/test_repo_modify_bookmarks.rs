@@ -6,7 +6,9 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use blobrepo::BlobRepo;
@@ -16,9 +18,12 @@ use bookmarks::Freshness;
 use context::CoreContext;
 use fbinit::FacebookInit;
 use futures::stream::TryStreamExt;
+use maplit::hashset;
 use mononoke_types::ChangesetId;
+use mononoke_types::Timestamp;
 use tests_utils::drawdag::create_from_dag;
 
+use crate::repo::modify_bookmarks::BookmarkModification;
 use crate::repo::BookmarkFreshness;
 use crate::repo::Repo;
 use crate::repo::RepoContext;
@@ -201,3 +206,231 @@ async fn delete_bookmark(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn modify_multiple_bookmarks(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    repo.create_bookmark("release", changesets["B"], None)
+        .await?;
+
+    // All of the modifications are applied together.
+    repo.modify_bookmarks(
+        vec![
+            BookmarkModification::Move {
+                bookmark: "trunk".to_string(),
+                target: changesets["E"],
+                old_target: Some(changesets["C"]),
+                allow_non_fast_forward: false,
+            },
+            BookmarkModification::Create {
+                bookmark: "release_candidate".to_string(),
+                target: changesets["D"],
+            },
+            BookmarkModification::Delete {
+                bookmark: "release".to_string(),
+                old_target: None,
+            },
+        ],
+        None,
+    )
+    .await?;
+    let trunk = repo
+        .resolve_bookmark("trunk", BookmarkFreshness::MostRecent)
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(trunk.id(), changesets["E"]);
+    let release_candidate = repo
+        .resolve_bookmark("release_candidate", BookmarkFreshness::MostRecent)
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(release_candidate.id(), changesets["D"]);
+    assert!(
+        repo.resolve_bookmark("release", BookmarkFreshness::MostRecent)
+            .await?
+            .is_none()
+    );
+
+    // If any modification is not permitted, none of them are applied.
+    assert!(
+        repo.modify_bookmarks(
+            vec![
+                BookmarkModification::Create {
+                    bookmark: "release".to_string(),
+                    target: changesets["E"],
+                },
+                BookmarkModification::Move {
+                    bookmark: "trunk".to_string(),
+                    target: changesets["G"],
+                    old_target: None,
+                    allow_non_fast_forward: false,
+                },
+            ],
+            None,
+        )
+        .await
+        .is_err()
+    );
+    assert!(
+        repo.resolve_bookmark("release", BookmarkFreshness::MostRecent)
+            .await?
+            .is_none()
+    );
+
+    // If any bookmark has moved since the request was made, none of the
+    // modifications are applied.
+    assert!(
+        repo.modify_bookmarks(
+            vec![
+                BookmarkModification::Create {
+                    bookmark: "release".to_string(),
+                    target: changesets["E"],
+                },
+                BookmarkModification::Delete {
+                    bookmark: "release_candidate".to_string(),
+                    old_target: Some(changesets["C"]),
+                },
+            ],
+            None,
+        )
+        .await
+        .is_err()
+    );
+    assert!(
+        repo.resolve_bookmark("release", BookmarkFreshness::MostRecent)
+            .await?
+            .is_none()
+    );
+
+    // The same bookmark can't be modified twice.
+    assert!(
+        repo.modify_bookmarks(
+            vec![
+                BookmarkModification::Delete {
+                    bookmark: "trunk".to_string(),
+                    old_target: None,
+                },
+                BookmarkModification::Create {
+                    bookmark: "trunk".to_string(),
+                    target: changesets["G"],
+                },
+            ],
+            None,
+        )
+        .await
+        .is_err()
+    );
+    let trunk = repo
+        .resolve_bookmark("trunk", BookmarkFreshness::MostRecent)
+        .await?
+        .expect("bookmark should be set");
+    assert_eq!(trunk.id(), changesets["E"]);
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn pushed_changesets_in_range(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    // Make sure the moves below are logged strictly after `start`.
+    let start = Timestamp::now();
+    std::thread::sleep(Duration::from_millis(10));
+
+    repo.move_bookmark("trunk", changesets["E"], None, false, None)
+        .await?;
+    let end = Timestamp::now();
+
+    // Only the changesets added by the moves within the range are listed.
+    let pushed = repo
+        .pushed_changesets_in_range("trunk", start, end)
+        .await?
+        .map_ok(|cs| cs.id())
+        .try_collect::<HashSet<_>>()
+        .await?;
+    assert_eq!(pushed, hashset! {changesets["D"], changesets["E"]});
+
+    // A range covering the creation of the bookmark includes everything.
+    let pushed = repo
+        .pushed_changesets_in_range("trunk", Timestamp::from_timestamp_secs(0), end)
+        .await?
+        .map_ok(|cs| cs.id())
+        .try_collect::<HashSet<_>>()
+        .await?;
+    assert_eq!(
+        pushed,
+        ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|name| changesets[*name])
+            .collect::<HashSet<_>>()
+    );
+
+    // Reversed ranges are rejected.
+    assert!(
+        repo.pushed_changesets_in_range("trunk", end, start)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn tail_changesets(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let (repo, changesets) = init_repo(&ctx).await?;
+
+    // Without a cursor, tailing starts from the latest move.
+    let tail = repo.tail_changesets(None, None, 100).await?;
+    assert!(tail.changesets.is_empty());
+    let start = tail.next_cursor;
+
+    repo.move_bookmark("trunk", changesets["E"], None, false, None)
+        .await?;
+    repo.create_bookmark("other", changesets["G"], None).await?;
+
+    // Moves contribute the changesets they landed, in order.  Creating a
+    // bookmark contributes only the changeset it points to.
+    let tail = repo.tail_changesets(Some(start), None, 100).await?;
+    let tailed = tail
+        .changesets
+        .iter()
+        .map(|tailed| (tailed.bookmark.to_string(), tailed.changeset.id()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tailed,
+        vec![
+            ("trunk".to_string(), changesets["D"]),
+            ("trunk".to_string(), changesets["E"]),
+            ("other".to_string(), changesets["G"]),
+        ]
+    );
+    let end = tail.next_cursor;
+    assert!(
+        repo.tail_changesets(Some(end), None, 100)
+            .await?
+            .changesets
+            .is_empty()
+    );
+
+    // Moves aren't split to fit the limit, and can be resumed from.
+    let tail = repo.tail_changesets(Some(start), None, 1).await?;
+    assert_eq!(tail.changesets.len(), 2);
+    let tail = repo
+        .tail_changesets(Some(tail.next_cursor), None, 1)
+        .await?;
+    assert_eq!(tail.changesets.len(), 1);
+    assert_eq!(tail.changesets[0].changeset.id(), changesets["G"]);
+    assert_eq!(tail.next_cursor, end);
+
+    // Moves of other bookmarks are skipped, but still advance the cursor.
+    let tail = repo
+        .tail_changesets(Some(start), Some("other"), 100)
+        .await?;
+    assert_eq!(tail.changesets.len(), 1);
+    assert_eq!(tail.next_cursor, end);
+
+    Ok(())
+}
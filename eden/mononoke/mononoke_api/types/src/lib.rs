@@ -15,12 +15,16 @@ use bookmarks::BookmarkUpdateLog;
 use bookmarks::Bookmarks;
 use changeset_fetcher::ChangesetFetcher;
 use changesets::Changesets;
+use derivation_queue::DerivationQueue;
 use ephemeral_blobstore::RepoEphemeralStore;
+use filenodes::Filenodes;
 use mercurial_mutation::HgMutationStore;
 use metaconfig_types::RepoConfig;
 use mutable_counters::MutableCounters;
 use mutable_renames::MutableRenames;
+use patch_id_index::PatchIdIndex;
 use phases::Phases;
+use preserved_bundles::PreservedBundles;
 use pushrebase_mutation_mapping::PushrebaseMutationMapping;
 use repo_blobstore::RepoBlobstore;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
@@ -32,6 +36,7 @@ use repo_permission_checker::RepoPermissionChecker;
 use repo_sparse_profiles::RepoSparseProfiles;
 use segmented_changelog_types::SegmentedChangelog;
 use skiplist::SkiplistIndex;
+use snapshot_bundles::SnapshotBundles;
 use streaming_clone::StreamingClone;
 
 // Eventually everything inside Repo should really be here
@@ -52,6 +57,7 @@ pub struct InnerRepo {
         dyn Bookmarks,
         dyn ChangesetFetcher,
         dyn Changesets,
+        dyn Filenodes,
         dyn Phases,
         dyn PushrebaseMutationMapping,
         dyn HgMutationStore,
@@ -87,6 +93,18 @@ pub struct InnerRepo {
 
     #[facet]
     pub streaming_clone: StreamingClone,
+
+    #[facet]
+    pub patch_id_index: dyn PatchIdIndex,
+
+    #[facet]
+    pub snapshot_bundles: SnapshotBundles,
+
+    #[facet]
+    pub preserved_bundles: PreservedBundles,
+
+    #[facet]
+    pub derivation_queue: DerivationQueue,
 }
 
 impl AsBlobRepo for InnerRepo {
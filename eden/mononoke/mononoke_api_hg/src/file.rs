@@ -23,6 +23,7 @@ use mercurial_types::HgParents;
 use mononoke_api::errors::MononokeError;
 use mononoke_types::fsnode::FsnodeFile;
 use mononoke_types::ContentMetadata;
+use mononoke_types::ContentMetadataV2;
 use mononoke_types::MPath;
 use remotefilelog::create_getpack_v2_blob;
 use revisionstore_types::Metadata;
@@ -53,6 +54,9 @@ impl HgFileContext {
         repo: HgRepoContext,
         filenode_id: HgFileNodeId,
     ) -> Result<Self, MononokeError> {
+        repo.repo()
+            .require_draft_hg_node_read(filenode_id.into_nodehash())
+            .await?;
         // Fetch and store Mononoke's internal representation of the metadata of this
         // file. The actual file contents are not fetched here.
         let ctx = repo.ctx();
@@ -65,6 +69,9 @@ impl HgFileContext {
         repo: HgRepoContext,
         filenode_id: HgFileNodeId,
     ) -> Result<Option<Self>, MononokeError> {
+        repo.repo()
+            .require_draft_hg_node_read(filenode_id.into_nodehash())
+            .await?;
         let ctx = repo.ctx();
         let blobstore = repo.blob_repo().blobstore();
         match filenode_id.load(ctx, blobstore).await {
@@ -113,6 +120,22 @@ impl HgFileContext {
             })
     }
 
+    /// Fetches the V2 metadata of the file, which describes what kind of file it is, if it has
+    /// already been computed. This is computed when the file is uploaded, but files uploaded
+    /// before that was introduced may not have it.
+    pub async fn content_metadata_v2_readonly(
+        &self,
+    ) -> Result<Option<ContentMetadataV2>, MononokeError> {
+        let content_id = self.envelope.content_id();
+        let fetch_key = filestore::FetchKey::Canonical(content_id);
+        let blobstore = self.repo.blob_repo().blobstore();
+        Ok(
+            filestore::get_metadata_v2_readonly(blobstore, self.repo.ctx(), &fetch_key)
+                .await?
+                .flatten(),
+        )
+    }
+
     /// Fetches the metadata that would be present in this file's corresponding FsNode, returning
     /// it with the FsNode type, but without actually fetching the FsNode.
     ///
@@ -65,6 +65,7 @@ use mononoke_types::ContentId;
 use mononoke_types::ContentMetadata;
 use mononoke_types::MPath;
 use mononoke_types::RepoPath;
+use path_policy::PathPolicy;
 use phases::PhasesRef;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_blobstore::RepoBlobstore;
@@ -393,6 +394,7 @@ impl HgRepoContext {
         let mut uploaded_changesets: HashMap<HgChangesetId, ChangesetHandle> = HashMap::new();
         let filelogs = HashMap::new();
         let manifests = HashMap::new();
+        let path_policy = PathPolicy::new(&self.config().path_policy);
         for (node, revlog_cs) in changesets {
             uploaded_changesets = upload_changeset(
                 self.ctx().clone(),
@@ -403,6 +405,7 @@ impl HgRepoContext {
                 uploaded_changesets,
                 &filelogs,
                 &manifests,
+                &path_policy,
                 None, /* maybe_backup_repo_source (unsupported here) */
             )
             .await
@@ -654,6 +657,11 @@ impl HgRepoContext {
         hg_cs_id: HgChangesetId,
     ) -> Result<Option<Bytes>, MononokeError> {
         let ctx = self.ctx();
+        if self.config().infinitepush.private_namespace.is_some() {
+            if let Some(cs_id) = self.get_bonsai_from_hg(hg_cs_id).await? {
+                self.repo().require_draft_commit_read(cs_id).await?;
+            }
+        }
         let blobstore = self.blob_repo().blobstore();
         let revlog_cs = RevlogChangeset::load(ctx, blobstore, hg_cs_id)
             .await
@@ -662,6 +670,8 @@ impl HgRepoContext {
             None => return Ok(None),
             Some(x) => x,
         };
+        self.repo()
+            .require_protected_paths_read(revlog_cs.files())?;
 
         let mut buffer = Vec::new();
         revlog_cs
@@ -42,6 +42,9 @@ impl HgTreeContext {
         repo: HgRepoContext,
         manifest_id: HgManifestId,
     ) -> Result<Self, MononokeError> {
+        repo.repo()
+            .require_draft_hg_node_read(manifest_id.into_nodehash())
+            .await?;
         let ctx = repo.ctx();
         let blobstore = repo.blob_repo().blobstore();
         let envelope = fetch_manifest_envelope(ctx, blobstore, manifest_id).await?;
@@ -52,6 +55,9 @@ impl HgTreeContext {
         repo: HgRepoContext,
         manifest_id: HgManifestId,
     ) -> Result<Option<Self>, MononokeError> {
+        repo.repo()
+            .require_draft_hg_node_read(manifest_id.into_nodehash())
+            .await?;
         let ctx = repo.ctx();
         let blobstore = repo.blob_repo().blobstore();
         let envelope = fetch_manifest_envelope_opt(ctx, blobstore, manifest_id).await?;
@@ -193,9 +193,19 @@ struct ContentMetadata {
   5: optional GitSha1 git_sha1;
 } (rust.exhaustive)
 
+enum LineEndings {
+  // The file contains no line endings
+  Absent = 0,
+  Lf = 1,
+  CrLf = 2,
+  Cr = 3,
+  // The file contains more than one kind of line ending
+  Mixed = 4,
+}
+
 // Metadata and properties associated with a file.
-// NOTE: Fields 1 through 10 will always be written by Mononoke, and Mononoke
-// will expect them to be present when reading ContentMetadataV2 structs back
+// NOTE: Fields 1 through 10, 12 and 13 will always be written by Mononoke,
+// and Mononoke will expect them to be present when reading ContentMetadataV2 structs back
 // from its Filestore. They're marked optional so we can report errors if
 // they're absent at runtime (as opposed to letting Thrift give us a default
 // values).
@@ -227,6 +237,13 @@ struct ContentMetadataV2 {
   // whichever is the shortest. If is_utf8 is false, the
   // first_line is None
   11: optional string first_line;
+  // Which line endings does the file use?
+  12: optional LineEndings line_endings;
+  // Does the file start with a UTF-8 or UTF-16 byte order mark?
+  13: optional bool has_bom;
+  // The language of the file, if it could be detected from its content
+  // (e.g. from a shebang line)
+  14: optional string language;
 } (rust.exhaustive)
 
 union RawBundle2 {
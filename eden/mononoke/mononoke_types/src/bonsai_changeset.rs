@@ -20,6 +20,7 @@ use crate::blob::Blob;
 use crate::blob::BlobstoreValue;
 use crate::blob::ChangesetBlob;
 use crate::datetime::DateTime;
+use crate::errors::BonsaiValidationError;
 use crate::errors::ErrorKind;
 use crate::file_change::BasicFileChange;
 use crate::file_change::FileChange;
@@ -159,6 +160,88 @@ impl BonsaiChangesetMut {
 
         Ok(())
     }
+
+    /// Validate that this is a well-formed changeset to be added to a
+    /// repository.
+    ///
+    /// This is stricter than `verify`, which must continue to accept every
+    /// changeset that has ever been stored.  It should be used on changesets
+    /// that are being created (e.g. pushed), so that malformed changesets are
+    /// rejected up front with a precise reason, rather than being stored and
+    /// breaking derivations later.  Text fields are `String`s, so they are
+    /// always valid UTF-8.
+    pub fn validate(&self) -> Result<(), BonsaiValidationError> {
+        for (index, parent) in self.parents.iter().enumerate() {
+            if self.parents[..index].contains(parent) {
+                return Err(BonsaiValidationError::DuplicateParent(*parent));
+            }
+        }
+
+        validate_signature("author", &self.author)?;
+        if self.author.is_empty() {
+            return Err(BonsaiValidationError::InvalidSignature {
+                field: "author",
+                reason: "must not be empty".to_string(),
+            });
+        }
+        if let Some(committer) = &self.committer {
+            validate_signature("committer", committer)?;
+        }
+
+        for key in self.extra.keys() {
+            if key.is_empty() || key.contains(&[':', '\0', '\n'][..]) {
+                return Err(BonsaiValidationError::InvalidExtraKey(key.clone()));
+            }
+        }
+
+        let mut last_changed_path: Option<&MPath> = None;
+        for (path, fc) in &self.file_changes {
+            if let Some((copy_from_path, parent)) = fc.copy_from() {
+                if !self.parents.contains(parent) {
+                    return Err(BonsaiValidationError::CopyFromUnknownParent {
+                        path: path.clone(),
+                        copy_from_path: copy_from_path.clone(),
+                        parent: *parent,
+                    });
+                }
+            }
+
+            if !self.is_snapshot {
+                match fc {
+                    FileChange::UntrackedDeletion | FileChange::UntrackedChange(_) => {
+                        return Err(BonsaiValidationError::UntrackedChange(path.clone()));
+                    }
+                    FileChange::Deletion | FileChange::Change(_) => {}
+                }
+            }
+
+            // File changes are sorted, so a changed path is always
+            // immediately followed by any paths it is a prefix of.
+            if let Some(prefix) = last_changed_path {
+                if prefix.is_prefix_of(path) {
+                    return Err(BonsaiValidationError::PathConflict {
+                        prefix: prefix.clone(),
+                        path: path.clone(),
+                    });
+                }
+            }
+            if fc.is_changed() {
+                last_changed_path = Some(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_signature(field: &'static str, value: &str) -> Result<(), BonsaiValidationError> {
+    if value.contains(&['\0', '\n'][..]) {
+        return Err(BonsaiValidationError::InvalidSignature {
+            field,
+            reason: "must not contain newlines or NUL characters".to_string(),
+        });
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -278,6 +361,12 @@ impl BonsaiChangeset {
         self.inner.is_snapshot
     }
 
+    /// Validate that this is a well-formed changeset to be added to a
+    /// repository.  See `BonsaiChangesetMut::validate`.
+    pub fn validate(&self) -> Result<(), BonsaiValidationError> {
+        self.inner.validate()
+    }
+
     /// Allow mutating this instance of `BonsaiChangeset`.
     pub fn into_mut(self) -> BonsaiChangesetMut {
         self.inner
@@ -495,4 +584,90 @@ mod test {
         create(false, true, false).expect_err("Non-snapshot can't have missing");
         create(true, true, false).unwrap_err();
     }
+
+    #[test]
+    fn validate_new_changesets() {
+        let parent = ChangesetId::from_byte_array([3; 32]);
+        let other = ChangesetId::from_byte_array([4; 32]);
+        let change = |copy_from: Option<(&str, ChangesetId)>| {
+            FileChange::tracked(
+                ContentId::from_byte_array([1; 32]),
+                FileType::Regular,
+                42,
+                copy_from.map(|(path, cs_id)| (MPath::new(path).unwrap(), cs_id)),
+            )
+        };
+        let base = BonsaiChangesetMut {
+            parents: vec![parent],
+            author: "foo".into(),
+            author_date: DateTime::from_timestamp(1, 2).unwrap(),
+            committer: None,
+            committer_date: None,
+            message: "a".into(),
+            extra: SortedVectorMap::new(),
+            file_changes: sorted_vector_map![
+                MPath::new("a").unwrap() => change(Some(("b", parent))),
+                MPath::new("b").unwrap() => FileChange::Deletion,
+            ],
+            is_snapshot: false,
+        };
+        assert_eq!(base.validate(), Ok(()));
+
+        let mut cs = base.clone();
+        cs.parents.push(parent);
+        assert_eq!(
+            cs.validate(),
+            Err(BonsaiValidationError::DuplicateParent(parent))
+        );
+
+        let mut cs = base.clone();
+        cs.file_changes
+            .insert(MPath::new("c").unwrap(), change(Some(("b", other))));
+        assert_eq!(
+            cs.validate(),
+            Err(BonsaiValidationError::CopyFromUnknownParent {
+                path: MPath::new("c").unwrap(),
+                copy_from_path: MPath::new("b").unwrap(),
+                parent: other,
+            })
+        );
+
+        let mut cs = base.clone();
+        cs.file_changes
+            .insert(MPath::new("a/b").unwrap(), change(None));
+        assert_eq!(
+            cs.validate(),
+            Err(BonsaiValidationError::PathConflict {
+                prefix: MPath::new("a").unwrap(),
+                path: MPath::new("a/b").unwrap(),
+            })
+        );
+
+        let mut cs = base.clone();
+        cs.author = "".into();
+        assert!(matches!(
+            cs.validate(),
+            Err(BonsaiValidationError::InvalidSignature {
+                field: "author",
+                ..
+            })
+        ));
+
+        let mut cs = base.clone();
+        cs.committer = Some("bar\nbaz".into());
+        assert!(matches!(
+            cs.validate(),
+            Err(BonsaiValidationError::InvalidSignature {
+                field: "committer",
+                ..
+            })
+        ));
+
+        let mut cs = base;
+        cs.extra.insert("a:b".into(), vec![]);
+        assert_eq!(
+            cs.validate(),
+            Err(BonsaiValidationError::InvalidExtraKey("a:b".into()))
+        );
+    }
 }
@@ -5,8 +5,11 @@
  * GNU General Public License version 2.
  */
 
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
+use edenapi_types::FileKind as EdenapiFileKind;
+use edenapi_types::LineEndings as EdenapiLineEndings;
 use fbthrift::compact_protocol;
 use quickcheck::Arbitrary;
 use quickcheck::Gen;
@@ -34,6 +37,86 @@ pub struct ContentMetadataV2 {
     pub ends_in_newline: bool,
     pub newline_count: u64,
     pub first_line: Option<String>,
+    pub line_endings: LineEndings,
+    pub has_bom: bool,
+    pub language: Option<String>,
+}
+
+/// The style of line endings used in a file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LineEndings {
+    /// The file contains no line endings at all.
+    Absent,
+    Lf,
+    CrLf,
+    Cr,
+    /// The file contains more than one kind of line ending.
+    Mixed,
+}
+
+impl LineEndings {
+    pub fn from_thrift(line_endings: thrift::LineEndings) -> Result<Self> {
+        let line_endings = match line_endings {
+            thrift::LineEndings::Absent => LineEndings::Absent,
+            thrift::LineEndings::Lf => LineEndings::Lf,
+            thrift::LineEndings::CrLf => LineEndings::CrLf,
+            thrift::LineEndings::Cr => LineEndings::Cr,
+            thrift::LineEndings::Mixed => LineEndings::Mixed,
+            thrift::LineEndings(x) => bail!(ErrorKind::InvalidThrift(
+                "LineEndings".into(),
+                format!("unknown line endings '{}'", x)
+            )),
+        };
+        Ok(line_endings)
+    }
+
+    pub fn into_thrift(self) -> thrift::LineEndings {
+        match self {
+            LineEndings::Absent => thrift::LineEndings::Absent,
+            LineEndings::Lf => thrift::LineEndings::Lf,
+            LineEndings::CrLf => thrift::LineEndings::CrLf,
+            LineEndings::Cr => thrift::LineEndings::Cr,
+            LineEndings::Mixed => thrift::LineEndings::Mixed,
+        }
+    }
+}
+
+impl From<LineEndings> for EdenapiLineEndings {
+    fn from(v: LineEndings) -> Self {
+        match v {
+            LineEndings::Absent => EdenapiLineEndings::Absent,
+            LineEndings::Lf => EdenapiLineEndings::Lf,
+            LineEndings::CrLf => EdenapiLineEndings::CrLf,
+            LineEndings::Cr => EdenapiLineEndings::Cr,
+            LineEndings::Mixed => EdenapiLineEndings::Mixed,
+        }
+    }
+}
+
+impl From<ContentMetadataV2> for EdenapiFileKind {
+    fn from(v: ContentMetadataV2) -> Self {
+        EdenapiFileKind {
+            is_binary: v.is_binary,
+            is_ascii: v.is_ascii,
+            is_utf8: v.is_utf8,
+            line_endings: v.line_endings.into(),
+            has_bom: v.has_bom,
+            language: v.language,
+        }
+    }
+}
+
+impl Arbitrary for LineEndings {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&[
+            LineEndings::Absent,
+            LineEndings::Lf,
+            LineEndings::CrLf,
+            LineEndings::Cr,
+            LineEndings::Mixed,
+        ])
+        .unwrap()
+    }
 }
 
 impl ContentMetadataV2 {
@@ -66,6 +149,13 @@ impl ContentMetadataV2 {
             is_utf8: thrift_field!(ContentMetadataV2, metadata, is_utf8)?,
             ends_in_newline: thrift_field!(ContentMetadataV2, metadata, ends_in_newline)?,
             first_line: metadata.first_line,
+            line_endings: LineEndings::from_thrift(thrift_field!(
+                ContentMetadataV2,
+                metadata,
+                line_endings
+            )?)?,
+            has_bom: thrift_field!(ContentMetadataV2, metadata, has_bom)?,
+            language: metadata.language,
         };
 
         Ok(res)
@@ -84,6 +174,9 @@ impl ContentMetadataV2 {
             git_sha1: Some(self.git_sha1.into_thrift()),
             sha256: Some(self.sha256.into_thrift()),
             first_line: self.first_line,
+            line_endings: Some(self.line_endings.into_thrift()),
+            has_bom: Some(self.has_bom),
+            language: self.language,
         }
     }
 }
@@ -104,6 +197,9 @@ impl Arbitrary for ContentMetadataV2 {
             sha256: hash::Sha256::arbitrary(g),
             git_sha1: hash::RichGitSha1::from_sha1(hash::GitSha1::arbitrary(g), "blob", total_size),
             first_line: Option::arbitrary(g),
+            line_endings: LineEndings::arbitrary(g),
+            has_bom: bool::arbitrary(g),
+            language: Option::arbitrary(g),
         }
     }
 }
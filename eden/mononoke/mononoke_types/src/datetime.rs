@@ -8,6 +8,7 @@
 use std::fmt;
 use std::fmt::Display;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use abomonation_derive::Abomonation;
 use anyhow::bail;
@@ -194,6 +195,12 @@ impl Timestamp {
         Timestamp(ts * SEC_IN_NS)
     }
 
+    /// Like `from_timestamp_secs`, but returns `None` if the timestamp
+    /// can't be represented.
+    pub fn checked_from_timestamp_secs(ts: i64) -> Option<Self> {
+        ts.checked_mul(SEC_IN_NS).map(Timestamp)
+    }
+
     pub fn from_timestamp_nanos(ts: i64) -> Self {
         Timestamp(ts)
     }
@@ -226,6 +233,16 @@ impl From<DateTime> for Timestamp {
     }
 }
 
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        let nanos = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since_epoch) => since_epoch.as_nanos() as i64,
+            Err(err) => -(err.duration().as_nanos() as i64),
+        };
+        Timestamp(nanos)
+    }
+}
+
 impl From<Timestamp> for DateTime {
     fn from(ts: Timestamp) -> Self {
         let ts_secs = ts.timestamp_seconds();
@@ -305,6 +322,16 @@ mod test {
         .expect_err("unexpected OK - timestamp_secs out of bounds");
     }
 
+    #[test]
+    fn checked_timestamp_secs() {
+        assert_eq!(
+            Timestamp::checked_from_timestamp_secs(1_600_000_000),
+            Some(Timestamp::from_timestamp_secs(1_600_000_000))
+        );
+        assert_eq!(Timestamp::checked_from_timestamp_secs(i64::MAX), None);
+        assert_eq!(Timestamp::checked_from_timestamp_secs(i64::MIN), None);
+    }
+
     #[test]
     fn timestamp_round_trip() {
         let ts0 = Timestamp::now();
@@ -321,4 +348,12 @@ mod test {
         let ts1 = Timestamp::from_timestamp_secs(1);
         assert_eq!(ts0, ts1);
     }
+
+    #[test]
+    fn from_system_time() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(5);
+        assert_eq!(Timestamp::from(time), Timestamp::from_timestamp_secs(5));
+        let time = SystemTime::UNIX_EPOCH - std::time::Duration::from_secs(5);
+        assert_eq!(Timestamp::from(time), Timestamp::from_timestamp_secs(-5));
+    }
 }
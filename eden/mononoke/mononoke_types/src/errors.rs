@@ -8,6 +8,7 @@
 use thiserror::Error;
 
 use crate::path::MPath;
+use crate::typed_hash::ChangesetId;
 
 #[derive(Debug, Error)]
 pub enum ErrorKind {
@@ -38,3 +39,24 @@ pub enum ErrorKind {
     #[error("Failed to parse RepositoryId from '{0}'")]
     FailedToParseRepositoryId(String),
 }
+
+/// Reasons a new bonsai changeset is rejected by `BonsaiChangesetMut::validate`.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum BonsaiValidationError {
+    #[error("parent {0} is listed more than once")]
+    DuplicateParent(ChangesetId),
+    #[error("path '{path}' is copied from '{copy_from_path}' in {parent}, which is not a parent")]
+    CopyFromUnknownParent {
+        path: MPath,
+        copy_from_path: MPath,
+        parent: ChangesetId,
+    },
+    #[error("changed path '{prefix}' is a prefix of changed path '{path}'")]
+    PathConflict { prefix: MPath, path: MPath },
+    #[error("path '{0}' has an untracked change in a non-snapshot changeset")]
+    UntrackedChange(MPath),
+    #[error("invalid {field}: {reason}")]
+    InvalidSignature { field: &'static str, reason: String },
+    #[error("invalid extra key '{0}'")]
+    InvalidExtraKey(String),
+}
@@ -52,8 +52,11 @@ pub use bonsai_changeset::BonsaiChangesetMut;
 pub use content_chunk::ContentChunk;
 pub use content_metadata::ContentAlias;
 pub use content_metadata::ContentMetadata;
+pub use content_metadata_v2::ContentMetadataV2;
+pub use content_metadata_v2::LineEndings;
 pub use datetime::DateTime;
 pub use datetime::Timestamp;
+pub use errors::BonsaiValidationError;
 pub use file_change::BasicFileChange;
 pub use file_change::FileChange;
 pub use file_change::FileType;
@@ -86,6 +89,7 @@ pub use typed_hash::ChangesetIdsResolvedFromPrefix;
 pub use typed_hash::ContentChunkId;
 pub use typed_hash::ContentId;
 pub use typed_hash::ContentMetadataId;
+pub use typed_hash::ContentMetadataV2Id;
 pub use typed_hash::DeletedManifestV2Id;
 pub use typed_hash::FastlogBatchId;
 pub use typed_hash::FileUnodeId;
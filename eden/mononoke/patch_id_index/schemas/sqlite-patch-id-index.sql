@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS patch_ids (
+  repo_id INTEGER NOT NULL,
+  cs_id BINARY(32) NOT NULL,
+  patch_id BINARY(32) NOT NULL,
+  PRIMARY KEY (repo_id, cs_id)
+);
+
+CREATE INDEX IF NOT EXISTS repo_patch_id ON patch_ids (repo_id, patch_id);
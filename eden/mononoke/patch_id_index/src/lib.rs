@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Index of the patch ids of changesets.
+//!
+//! The patch id of a changeset is a hash of the changes it makes relative to
+//! its parent, ignoring its metadata and where in the files the changes are.
+//! Changesets with the same patch id are likely to be duplicates of one
+//! another, for example a commit and its cherry-picks onto other branches.
+//!
+//! Patch ids can always be recomputed from the changeset and its parent, so
+//! this index serves as both a cache of them and a way of finding the
+//! changesets with a given patch id.
+
+mod sql;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use mononoke_types::hash::Blake2;
+use mononoke_types::hash::Context;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+
+pub use crate::sql::SqlPatchIdIndex;
+pub use crate::sql::SqlPatchIdIndexBuilder;
+
+/// Hash of the changes made by a changeset.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct PatchId(Blake2);
+
+impl PatchId {
+    pub const fn new(hash: Blake2) -> Self {
+        PatchId(hash)
+    }
+
+    pub fn blake2(&self) -> &Blake2 {
+        &self.0
+    }
+}
+
+impl fmt::Display for PatchId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for PatchId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(PatchId(Blake2::from_str(s)?))
+    }
+}
+
+/// Incremental computation of a patch id.
+pub struct PatchIdContext(Context);
+
+impl PatchIdContext {
+    pub fn new() -> Self {
+        PatchIdContext(Context::new(b"patchid"))
+    }
+
+    pub fn update(&mut self, data: impl AsRef<[u8]>) {
+        self.0.update(data)
+    }
+
+    pub fn finish(self) -> PatchId {
+        PatchId(self.0.finish())
+    }
+}
+
+impl Default for PatchIdContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait PatchIdIndex: Send + Sync {
+    fn repo_id(&self) -> RepositoryId;
+
+    /// Record the patch ids of some changesets.
+    async fn add(&self, ctx: &CoreContext, entries: &[(ChangesetId, PatchId)])
+    -> Result<(), Error>;
+
+    /// Get the recorded patch ids of some changesets.
+    async fn get_patch_ids(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, PatchId>, Error>;
+
+    /// Get the changesets that have been recorded with a patch id.
+    async fn get_changesets(
+        &self,
+        ctx: &CoreContext,
+        patch_id: PatchId,
+    ) -> Result<Vec<ChangesetId>, Error>;
+}
@@ -0,0 +1,171 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+use ::sql_ext::mononoke_queries;
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::hash::Blake2;
+use mononoke_types::ChangesetId;
+use mononoke_types::RepositoryId;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::SqlConnections;
+
+use super::PatchId;
+use super::PatchIdIndex;
+
+mononoke_queries! {
+    write InsertPatchIds(values: (
+        repo_id: RepositoryId,
+        cs_id: ChangesetId,
+        patch_id: Blake2,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO patch_ids (repo_id, cs_id, patch_id) VALUES {values}"
+    }
+
+    read SelectPatchIds(
+        repo_id: RepositoryId,
+        >list cs_id: ChangesetId
+    ) -> (ChangesetId, Blake2) {
+        "SELECT cs_id, patch_id
+         FROM patch_ids
+         WHERE repo_id = {repo_id} AND cs_id IN {cs_id}"
+    }
+
+    read SelectChangesets(
+        repo_id: RepositoryId,
+        patch_id: Blake2,
+    ) -> (ChangesetId,) {
+        "SELECT cs_id
+         FROM patch_ids
+         WHERE repo_id = {repo_id} AND patch_id = {patch_id}"
+    }
+}
+
+pub struct SqlPatchIdIndex {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+#[derive(Clone)]
+pub struct SqlPatchIdIndexBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlPatchIdIndexBuilder {
+    const LABEL: &'static str = "patch_id_index";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-patch-id-index.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlPatchIdIndexBuilder {}
+
+impl SqlPatchIdIndexBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlPatchIdIndex {
+        SqlPatchIdIndex {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+#[async_trait]
+impl PatchIdIndex for SqlPatchIdIndex {
+    fn repo_id(&self) -> RepositoryId {
+        self.repo_id
+    }
+
+    async fn add(
+        &self,
+        ctx: &CoreContext,
+        entries: &[(ChangesetId, PatchId)],
+    ) -> Result<(), Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(cs_id, patch_id)| (&self.repo_id, cs_id, patch_id.blake2()))
+            .collect();
+
+        InsertPatchIds::query(&self.connections.write_connection, &entries[..]).await?;
+
+        Ok(())
+    }
+
+    async fn get_patch_ids(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashMap<ChangesetId, PatchId>, Error> {
+        if cs_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let mut patch_ids: HashMap<_, _> =
+            SelectPatchIds::query(&self.connections.read_connection, &self.repo_id, cs_ids)
+                .await?
+                .into_iter()
+                .map(|(cs_id, patch_id)| (cs_id, PatchId::new(patch_id)))
+                .collect();
+
+        let left_to_fetch: Vec<_> = cs_ids
+            .iter()
+            .filter(|cs_id| !patch_ids.contains_key(cs_id))
+            .copied()
+            .collect();
+        if left_to_fetch.is_empty() {
+            return Ok(patch_ids);
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+
+        let rows = SelectPatchIds::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &left_to_fetch[..],
+        )
+        .await?;
+        patch_ids.extend(
+            rows.into_iter()
+                .map(|(cs_id, patch_id)| (cs_id, PatchId::new(patch_id))),
+        );
+        Ok(patch_ids)
+    }
+
+    async fn get_changesets(
+        &self,
+        ctx: &CoreContext,
+        patch_id: PatchId,
+    ) -> Result<Vec<ChangesetId>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let rows = SelectChangesets::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            patch_id.blake2(),
+        )
+        .await?;
+        Ok(rows.into_iter().map(|(cs_id,)| cs_id).collect())
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::Error;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types::hash::Blake2;
+use mononoke_types_mocks::changesetid::ONES_CSID;
+use mononoke_types_mocks::changesetid::THREES_CSID;
+use mononoke_types_mocks::changesetid::TWOS_CSID;
+use mononoke_types_mocks::repo::REPO_ONE;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use patch_id_index::PatchId;
+use patch_id_index::PatchIdIndex;
+use patch_id_index::SqlPatchIdIndexBuilder;
+use sql_construct::SqlConstruct;
+
+const PATCH_A: PatchId = PatchId::new(Blake2::from_byte_array([0xaa; 32]));
+const PATCH_B: PatchId = PatchId::new(Blake2::from_byte_array([0xbb; 32]));
+
+#[fbinit::test]
+async fn test_add_and_get(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let index = SqlPatchIdIndexBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    index
+        .add(&ctx, &[(ONES_CSID, PATCH_A), (TWOS_CSID, PATCH_A)])
+        .await?;
+    // Adding the same entries again is a no-op.
+    index.add(&ctx, &[(ONES_CSID, PATCH_A)]).await?;
+    index.add(&ctx, &[(THREES_CSID, PATCH_B)]).await?;
+
+    let mut changesets = index.get_changesets(&ctx, PATCH_A).await?;
+    changesets.sort();
+    assert_eq!(changesets, vec![ONES_CSID, TWOS_CSID]);
+    assert_eq!(
+        index.get_patch_ids(&ctx, &[ONES_CSID, THREES_CSID]).await?,
+        HashMap::from([(ONES_CSID, PATCH_A), (THREES_CSID, PATCH_B)])
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let builder = SqlPatchIdIndexBuilder::with_sqlite_in_memory()?;
+    let zero = builder.clone().build(REPO_ZERO);
+    let one = builder.build(REPO_ONE);
+
+    zero.add(&ctx, &[(ONES_CSID, PATCH_A)]).await?;
+
+    assert_eq!(zero.get_changesets(&ctx, PATCH_A).await?, vec![ONES_CSID]);
+    assert!(one.get_changesets(&ctx, PATCH_A).await?.is_empty());
+    assert!(one.get_patch_ids(&ctx, &[ONES_CSID]).await?.is_empty());
+
+    Ok(())
+}
@@ -24,6 +24,13 @@ pub trait PushrebaseHook: Send + Sync + 'static {
     /// bookmark for pushrebase. It should return a PushrebaseCommitHook for further processing of
     /// the changeset generated by pushrebase.
     async fn prepushrebase(&self) -> Result<Box<dyn PushrebaseCommitHook>, Error>;
+
+    /// Whether pushes using this hook can be pushrebased in the same bookmark move as other
+    /// pushes. This requires the hooks of each push to work independently of the others, e.g.
+    /// not to allocate from a sequence that is read in prepushrebase.
+    fn batchable(&self) -> bool {
+        false
+    }
 }
 
 #[async_trait]
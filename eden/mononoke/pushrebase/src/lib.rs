@@ -49,6 +49,8 @@
 
 #![feature(trait_alias)]
 
+mod queue;
+
 use std::cmp::max;
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -101,6 +103,7 @@ use pushrebase_hook::PushrebaseCommitHook;
 use pushrebase_hook::PushrebaseHook;
 use pushrebase_hook::PushrebaseTransactionHook;
 use pushrebase_hook::RebasedChangesets;
+use queue::pushrebase_queue;
 use repo_blobstore::RepoBlobstoreArc;
 use repo_derived_data::RepoDerivedDataRef;
 use repo_identity::RepoIdentityRef;
@@ -108,6 +111,7 @@ use revset::RangeNodeStream;
 use slog::info;
 use stats::prelude::*;
 use thiserror::Error;
+use tokio::sync::oneshot;
 use tunables::tunables;
 
 define_stats! {
@@ -117,10 +121,15 @@ define_stats! {
     critical_section_failure_duration_us: dynamic_timeseries("{}.critical_section_failure_duration_us", (reponame: String); Average, Sum, Count),
     critical_section_retries_failed: dynamic_timeseries("{}.critical_section_retries_failed", (reponame: String); Average, Sum),
     commits_rebased: dynamic_timeseries("{}.commits_rebased", (reponame: String); Average, Sum, Count),
+    batched_pushes: dynamic_timeseries("{}.batched_pushes", (reponame: String); Average, Sum, Count),
 }
 
 const MAX_REBASE_ATTEMPTS: usize = 100;
 
+/// Maximum number of queued pushes that are pushrebased in one bookmark
+/// move.
+const MAX_BATCH_SIZE: usize = 10;
+
 pub const MUTATION_KEYS: &[&str] = &["mutpred", "mutuser", "mutdate", "mutop", "mutsplit"];
 
 pub const FAIL_PUSHREBASE_EXTRA: &str = "failpushrebase";
@@ -230,6 +239,9 @@ impl PushrebaseDistance {
 
 #[derive(Debug, Clone)]
 pub struct PushrebaseOutcome {
+    /// The value of the bookmark before it was moved.  For pushes that were
+    /// rebased as part of a batch, this is its value before the whole batch,
+    /// not the head of the push before them in the batch.
     pub old_bookmark_value: Option<ChangesetId>,
     pub head: ChangesetId,
     pub retry_num: PushrebaseRetryNum,
@@ -275,7 +287,142 @@ pub async fn do_pushrebase_bonsai(
     // many commits are missing filenodes.
     check_filenodes_backfilled(ctx, repo, &head, config.not_generated_filenodes_limit).await?;
 
-    let res = rebase_in_loop(
+    let res = if config.serialize_per_bookmark {
+        push_in_queue(
+            ctx,
+            repo,
+            config,
+            onto_bookmark,
+            head,
+            root,
+            client_cf,
+            &client_bcs,
+            prepushrebase_hooks,
+        )
+        .await?
+    } else {
+        rebase_in_loop(
+            ctx,
+            repo,
+            config,
+            onto_bookmark,
+            head,
+            root,
+            client_cf,
+            &client_bcs,
+            prepushrebase_hooks,
+        )
+        .await?
+    };
+
+    Ok(res)
+}
+
+/// A push that is waiting in the queue of its bookmark, which the push at
+/// the front of the queue can pushrebase together with its own.
+pub(crate) struct BatchedPush {
+    config: PushrebaseFlags,
+    head: ChangesetId,
+    root: ChangesetId,
+    client_cf: Vec<MPath>,
+    client_bcs: Vec<BonsaiChangeset>,
+    hooks: Vec<Box<dyn PushrebaseCommitHook>>,
+    /// Where the outcome is sent, or `None` if the push wasn't included in
+    /// a batch and must be pushrebased on its own.
+    outcome: oneshot::Sender<Option<Result<PushrebaseOutcome, PushrebaseError>>>,
+}
+
+impl BatchedPush {
+    /// Whether the push that submitted this has stopped waiting for it.
+    pub(crate) fn is_abandoned(&self) -> bool {
+        self.outcome.is_closed()
+    }
+}
+
+/// Wait for the pushrebases onto the same bookmark that are ahead of us,
+/// instead of racing them for the bookmark move.  If all of the push's hooks
+/// allow it, the push is offered to the pushes ahead of it, so that it may
+/// be pushrebased in the same bookmark move as one of them.  When it gets
+/// to the front of the queue, it pushrebases the compatible pushes behind
+/// it together with its own.
+async fn push_in_queue(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    config: &PushrebaseFlags,
+    onto_bookmark: &BookmarkName,
+    head: ChangesetId,
+    root: ChangesetId,
+    client_cf: Vec<MPath>,
+    client_bcs: &[BonsaiChangeset],
+    prepushrebase_hooks: &[Box<dyn PushrebaseHook>],
+) -> Result<PushrebaseOutcome, PushrebaseError> {
+    let mut ticket = pushrebase_queue().enqueue(repo.repo_identity().name(), onto_bookmark);
+
+    let mut batched = None;
+    if prepushrebase_hooks.iter().all(|hook| hook.batchable()) {
+        let hooks = try_join_all(prepushrebase_hooks.iter().map(|h| h.prepushrebase())).await?;
+        let (sender, receiver) = oneshot::channel();
+        ticket.submit(BatchedPush {
+            config: config.clone(),
+            head,
+            root,
+            client_cf: client_cf.clone(),
+            client_bcs: client_bcs.to_vec(),
+            hooks,
+            outcome: sender,
+        });
+        batched = Some(receiver);
+    }
+
+    let mut position = ticket.position();
+    ctx.scuba()
+        .clone()
+        .add("bookmark", onto_bookmark.to_string())
+        .add("pushrebase_queue_position", position)
+        .log_with_msg("Pushrebase queued", None);
+    while position > 0 {
+        info!(
+            ctx.logger(),
+            "Waiting for {} pushrebase(s) onto {} ahead of this one", position, onto_bookmark;
+            "remote" => "true"
+        );
+        tokio::select! {
+            new_position = ticket.position_changed() => position = new_position,
+            outcome = async {
+                match batched.as_mut() {
+                    Some(receiver) => receiver.await,
+                    None => future::pending().await,
+                }
+            } => match outcome {
+                Ok(Some(outcome)) => return outcome,
+                // Not included in the batch, so wait for our own turn.
+                Ok(None) | Err(_) => batched = None,
+            },
+        }
+    }
+
+    let guard = ticket.wait_turn().await;
+    let own = guard.take_own();
+    if own.is_none() {
+        if let Some(receiver) = batched {
+            // A push ahead of us took ours into its batch just before it
+            // finished, and has already sent the outcome.
+            if let Ok(Some(outcome)) = receiver.await {
+                return outcome;
+            }
+        }
+    }
+
+    if let Some(own) = own {
+        let batch = guard.take_batch(MAX_BATCH_SIZE - 1, |push| push.config == *config);
+        if !batch.is_empty() {
+            if let Some(outcome) = rebase_batch(ctx, repo, onto_bookmark, own, batch).await? {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    rebase_in_loop(
         ctx,
         repo,
         config,
@@ -283,12 +430,159 @@ pub async fn do_pushrebase_bonsai(
         head,
         root,
         client_cf,
-        &client_bcs,
+        client_bcs,
         prepushrebase_hooks,
     )
-    .await?;
+    .await
+}
 
-    Ok(res)
+/// Pushrebase a batch of queued pushes in one bookmark move, each onto the
+/// one before it.  The first push is our own.  Pushes that can't be
+/// rebased onto the batch are told to pushrebase on their own.
+///
+/// Returns the outcome of our own push, or `None` if the bookmark couldn't
+/// be moved and it should be pushrebased on its own as well.
+async fn rebase_batch(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    onto_bookmark: &BookmarkName,
+    own: BatchedPush,
+    batch: Vec<BatchedPush>,
+) -> Result<Option<PushrebaseOutcome>, PushrebaseError> {
+    let old_bookmark_value = match get_bookmark_value(ctx, repo, onto_bookmark).await? {
+        Some(old_bookmark_value) => old_bookmark_value,
+        None => {
+            // Creating the bookmark is left to the pushes themselves.
+            for push in batch {
+                let _ = push.outcome.send(None);
+            }
+            return Ok(None);
+        }
+    };
+
+    let mut tip = old_bookmark_value;
+    let mut transaction_hooks = Vec::new();
+    let mut included = Vec::new();
+    for (index, push) in std::iter::once(own).chain(batch).enumerate() {
+        match rebase_batched_push(ctx, repo, old_bookmark_value, tip, push).await {
+            Ok((outcome, hooks, sender)) => {
+                tip = outcome.head;
+                transaction_hooks.extend(hooks);
+                included.push((outcome, sender));
+            }
+            // Our own push is rebased onto the bookmark itself, so its
+            // errors are final.
+            Err((err, _)) if index == 0 => return Err(err),
+            Err((_, sender)) => {
+                let _ = sender.send(None);
+            }
+        }
+    }
+
+    let moved = try_move_bookmark(
+        ctx.clone(),
+        repo,
+        onto_bookmark,
+        Some(old_bookmark_value),
+        tip,
+        RebasedChangesets::new(),
+        transaction_hooks,
+    )
+    .await;
+
+    let mut included = included.into_iter();
+    let own_outcome = included.next().map(|(outcome, _)| outcome);
+    match moved {
+        Ok(Some(_)) => {
+            STATS::batched_pushes.add_value(
+                included.len() as i64 + 1,
+                (repo.repo_identity().name().to_string(),),
+            );
+            for (outcome, sender) in included {
+                let _ = sender.send(Some(Ok(outcome)));
+            }
+            Ok(own_outcome)
+        }
+        Ok(None) | Err(_) => {
+            for (_, sender) in included {
+                let _ = sender.send(None);
+            }
+            Ok(None)
+        }
+    }
+}
+
+type BatchedPushSender = oneshot::Sender<Option<Result<PushrebaseOutcome, PushrebaseError>>>;
+
+/// Rebase one push of a batch onto the tip of the batch so far.  The batch
+/// moves the bookmark from `old_bookmark_value`.
+async fn rebase_batched_push(
+    ctx: &CoreContext,
+    repo: &impl Repo,
+    old_bookmark_value: ChangesetId,
+    tip: ChangesetId,
+    push: BatchedPush,
+) -> Result<
+    (
+        PushrebaseOutcome,
+        Vec<Box<dyn PushrebaseTransactionHook>>,
+        BatchedPushSender,
+    ),
+    (PushrebaseError, BatchedPushSender),
+> {
+    let BatchedPush {
+        config,
+        head,
+        root,
+        client_cf,
+        client_bcs,
+        mut hooks,
+        outcome: sender,
+    } = push;
+    let res = async {
+        let server_bcs = fetch_bonsai_range_ancestor_not_included(ctx, repo, root, tip).await?;
+        for bcs in server_bcs.iter() {
+            if should_fail_pushrebase(bcs) {
+                return Err(PushrebaseError::ForceFailPushrebase(bcs.get_changeset_id()));
+            }
+        }
+        if config.casefolding_check {
+            let conflict =
+                check_case_conflicts(server_bcs.iter().rev().chain(client_bcs.iter().rev()));
+            if let Some(conflict) = conflict {
+                return Err(PushrebaseError::PotentialCaseConflict(conflict.1));
+            }
+        }
+        let server_cf = find_changed_files(ctx, repo, root, tip).await?;
+        intersect_changed_files(server_cf, client_cf)?;
+
+        let (new_head, rebased_changesets) =
+            create_rebased_changesets(ctx, repo, &config, root, head, tip, &mut hooks).await?;
+        let retry_num = PushrebaseRetryNum(0);
+        for (old_id, (new_id, _)) in &rebased_changesets {
+            maybe_validate_commit(ctx, repo, old_id, new_id, retry_num).await?;
+        }
+        let transaction_hooks = try_join_all(
+            hooks
+                .into_iter()
+                .map(|h| h.into_transaction_hook(ctx, &rebased_changesets)),
+        )
+        .await?;
+
+        let outcome = PushrebaseOutcome {
+            old_bookmark_value: Some(old_bookmark_value),
+            head: new_head,
+            retry_num,
+            rebased_changesets: rebased_changesets_into_pairs(rebased_changesets),
+            pushrebase_distance: PushrebaseDistance(server_bcs.len()),
+        };
+        Ok((outcome, transaction_hooks))
+    }
+    .await;
+    match res {
+        Ok((outcome, transaction_hooks)) => Ok((outcome, transaction_hooks, sender)),
+        Err(err) => Err((err, sender)),
+    }
 }
 
 async fn check_filenodes_backfilled<'a>(
@@ -2409,6 +2703,164 @@ mod tests {
         })
     }
 
+    #[derive(Copy, Clone)]
+    struct BatchableHook;
+
+    #[async_trait]
+    impl PushrebaseHook for BatchableHook {
+        async fn prepushrebase(&self) -> Result<Box<dyn PushrebaseCommitHook>, Error> {
+            Ok(Box::new(SleepHook) as Box<dyn PushrebaseCommitHook>)
+        }
+
+        fn batchable(&self) -> bool {
+            true
+        }
+    }
+
+    #[fbinit::test]
+    async fn pushrebase_serialized_and_batched(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = Linear::getrepo(fb).await;
+        let root = HgChangesetId::from_str("2d7d4ba9ce0a6ffd222de7785b249ead9c51c536")?;
+        let p = repo
+            .bonsai_hg_mapping()
+            .get_bonsai_from_hg(&ctx, root)
+            .await?
+            .ok_or_else(|| Error::msg("Root is missing"))?;
+
+        let book = master_bookmark();
+        set_bookmark(
+            ctx.clone(),
+            repo.clone(),
+            &book,
+            "a5ffa77602a066db7d5cfb9fb5823a0895717c5a",
+        )
+        .await?;
+        let flags = PushrebaseFlags {
+            serialize_per_bookmark: true,
+            ..Default::default()
+        };
+
+        let num_pushes = 10;
+        let mut futs = vec![];
+        for i in 0..num_pushes {
+            cloned!(ctx, repo, book, flags);
+            // Alternate between hooks that can be batched and hooks that
+            // can't, so that both paths are taken.
+            let hooks = if i % 2 == 0 {
+                [Box::new(BatchableHook) as Box<dyn PushrebaseHook>]
+            } else {
+                [Box::new(SleepHook) as Box<dyn PushrebaseHook>]
+            };
+            let bcs = CreateCommitContext::new(&ctx, &repo, vec![p])
+                .add_file(format!("file{}", i).as_str(), "content")
+                .commit()
+                .await?
+                .load(&ctx, repo.repo_blobstore())
+                .await?;
+            futs.push(async move {
+                do_pushrebase_bonsai(&ctx, &repo, &flags, &book, &hashset![bcs], &hooks).await
+            });
+        }
+
+        // Queued pushes never have to retry, whether they were batched or
+        // not.
+        let res = try_join_all(futs).await?;
+        assert!(res.iter().all(|outcome| outcome.retry_num.0 == 0));
+        let heads = res
+            .iter()
+            .map(|outcome| outcome.head)
+            .collect::<HashSet<_>>();
+        assert_eq!(heads.len(), num_pushes);
+
+        let previous_master = HgChangesetId::from_str("a5ffa77602a066db7d5cfb9fb5823a0895717c5a")?;
+        let commits_between = count_commits_between(ctx, repo, previous_master, book).await?;
+        // `- 1` because RangeNodeStream is inclusive
+        assert_eq!(commits_between - 1, num_pushes);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn pushrebase_batch_old_bookmark_value(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = Linear::getrepo(fb).await;
+        let root = HgChangesetId::from_str("2d7d4ba9ce0a6ffd222de7785b249ead9c51c536")?;
+        let p = repo
+            .bonsai_hg_mapping()
+            .get_bonsai_from_hg(&ctx, root)
+            .await?
+            .ok_or_else(|| Error::msg("Root is missing"))?;
+
+        let book = master_bookmark();
+        set_bookmark(
+            ctx.clone(),
+            repo.clone(),
+            &book,
+            "a5ffa77602a066db7d5cfb9fb5823a0895717c5a",
+        )
+        .await?;
+        let old_bookmark_value = get_bookmark_value(&ctx, &repo, &book)
+            .await?
+            .ok_or_else(|| Error::msg("master is missing"))?;
+
+        let mut pushes = vec![];
+        let mut receivers = vec![];
+        for i in 0..3 {
+            let head = CreateCommitContext::new(&ctx, &repo, vec![p])
+                .add_file(format!("file{}", i).as_str(), "content")
+                .commit()
+                .await?;
+            let (client_cf, client_bcs) = try_join(
+                find_changed_files(&ctx, &repo, p, head),
+                fetch_bonsai_range_ancestor_not_included(&ctx, &repo, p, head),
+            )
+            .await?;
+            let (sender, receiver) = oneshot::channel();
+            pushes.push(BatchedPush {
+                config: PushrebaseFlags::default(),
+                head,
+                root: p,
+                client_cf,
+                client_bcs,
+                hooks: vec![],
+                outcome: sender,
+            });
+            receivers.push(receiver);
+        }
+        let mut pushes = pushes.into_iter();
+        let own = pushes.next().unwrap();
+        let own_outcome = rebase_batch(&ctx, &repo, &book, own, pushes.collect())
+            .await?
+            .ok_or_else(|| Error::msg("batch wasn't pushrebased"))?;
+
+        let mut outcomes = vec![own_outcome];
+        for receiver in receivers.into_iter().skip(1) {
+            match receiver.await? {
+                Some(outcome) => outcomes.push(outcome?),
+                None => return Err(Error::msg("push wasn't included in the batch")),
+            }
+        }
+
+        // Each push is rebased onto the one before it, but all of them
+        // report where the bookmark was before the batch moved it.
+        for (prev, outcome) in outcomes.iter().zip(outcomes.iter().skip(1)) {
+            let bcs = outcome.head.load(&ctx, repo.repo_blobstore()).await?;
+            assert_eq!(bcs.parents().collect::<Vec<_>>(), vec![prev.head]);
+        }
+        assert!(
+            outcomes
+                .iter()
+                .all(|outcome| outcome.old_bookmark_value == Some(old_bookmark_value))
+        );
+        assert_eq!(
+            get_bookmark_value(&ctx, &repo, &book).await?,
+            Some(outcomes[2].head)
+        );
+
+        Ok(())
+    }
+
     #[fbinit::test]
     fn pushrebase_create_new_bookmark(fb: FacebookInit) -> Result<(), Error> {
         let runtime = tokio::runtime::Runtime::new().unwrap();
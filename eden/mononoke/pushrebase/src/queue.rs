@@ -0,0 +1,254 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! In-process queue that serializes pushrebases onto the same bookmark.
+//!
+//! Without it, concurrent pushrebases onto a busy bookmark race each other:
+//! all of them rebase onto the same bookmark value, one of them wins the
+//! bookmark move, and the others have to fetch the new commits and retry.
+//! Under heavy landing traffic this turns into a retry loop where most of
+//! the critical sections are wasted. With the queue, pushes wait for their
+//! turn instead, and when they get it the bookmark has already moved past
+//! everything that was ahead of them, so they usually succeed on their
+//! first attempt.
+//!
+//! Pushes whose hooks allow it are also offered to the push at the front of
+//! the queue, which pushrebases up to `MAX_BATCH_SIZE` of the compatible
+//! pushes behind it in the same bookmark move as its own.  Waiting pushes
+//! are told their position in the queue as it changes.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use bookmarks::BookmarkName;
+use lazy_static::lazy_static;
+use tokio::sync::watch;
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::OwnedMutexGuard;
+
+use crate::BatchedPush;
+
+lazy_static! {
+    static ref PUSHREBASE_QUEUE: PushrebaseQueue = PushrebaseQueue::new();
+}
+
+/// Returns the process-wide pushrebase queue.
+pub fn pushrebase_queue() -> &'static PushrebaseQueue {
+    &PUSHREBASE_QUEUE
+}
+
+type QueueKey = (String, BookmarkName);
+
+/// The queues of the bookmarks that have pushes waiting, together with the
+/// number of tickets that are still held for each of them.  Queues are
+/// removed once their last ticket is dropped.
+type Queues = Mutex<HashMap<QueueKey, (Arc<BookmarkQueue>, usize)>>;
+
+struct BookmarkQueue {
+    /// Held by the pushrebase that is currently running. Tokio mutexes are
+    /// fair, so waiters are served in the order they started waiting.
+    lock: Arc<AsyncMutex<()>>,
+    /// Ticket number handed out to the next push to join the queue.
+    next_ticket: AtomicU64,
+    /// Number of pushes that have left the queue.
+    served: watch::Sender<u64>,
+    /// Pushes that can be pushrebased together with the push at the front
+    /// of the queue, by ticket number.
+    pending: Mutex<Vec<(u64, BatchedPush)>>,
+}
+
+#[derive(Default)]
+pub struct PushrebaseQueue {
+    queues: Arc<Queues>,
+}
+
+impl PushrebaseQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Join the queue of pushrebases onto `bookmark` in `repo_name`.
+    pub fn enqueue(&self, repo_name: &str, bookmark: &BookmarkName) -> QueueTicket {
+        let key = (repo_name.to_string(), bookmark.clone());
+        let queue = {
+            let mut queues = self.queues.lock().expect("lock poisoned");
+            let (queue, tickets) = queues.entry(key.clone()).or_insert_with(|| {
+                let queue = Arc::new(BookmarkQueue {
+                    lock: Arc::new(AsyncMutex::new(())),
+                    next_ticket: AtomicU64::new(0),
+                    served: watch::channel(0).0,
+                    pending: Mutex::new(Vec::new()),
+                });
+                (queue, 0)
+            });
+            *tickets += 1;
+            queue.clone()
+        };
+        let ticket = queue.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let served = queue.served.subscribe();
+        QueueTicket {
+            queues: self.queues.clone(),
+            key,
+            queue,
+            ticket,
+            served,
+        }
+    }
+}
+
+/// A place in the queue of a bookmark.
+pub struct QueueTicket {
+    queues: Arc<Queues>,
+    key: QueueKey,
+    queue: Arc<BookmarkQueue>,
+    ticket: u64,
+    served: watch::Receiver<u64>,
+}
+
+impl QueueTicket {
+    /// Number of pushes ahead of this one, including the one that is
+    /// currently running. This is approximate if pushes ahead of us give up
+    /// waiting, as they leave the queue out of order.
+    pub fn position(&self) -> u64 {
+        self.ticket.saturating_sub(*self.served.borrow())
+    }
+
+    /// Wait until the position of this ticket changes. Returns the new
+    /// position.
+    pub async fn position_changed(&mut self) -> u64 {
+        // The sender lives as long as the queue, which we hold a reference to.
+        let _ = self.served.changed().await;
+        self.position()
+    }
+
+    /// Offer this push to be pushrebased by the push at the front of the
+    /// queue, together with its own.
+    pub(crate) fn submit(&self, push: BatchedPush) {
+        self.queue
+            .pending
+            .lock()
+            .expect("lock poisoned")
+            .push((self.ticket, push));
+    }
+
+    /// Wait for our turn. The returned guard must be held for as long as
+    /// the pushrebase runs.
+    pub async fn wait_turn(self) -> QueueGuard {
+        let guard = self.queue.lock.clone().lock_owned().await;
+        QueueGuard {
+            _guard: guard,
+            ticket: self,
+        }
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        // Either the push is done, or it gave up waiting. In both cases it
+        // no longer holds up anyone behind it.
+        self.queue.served.send_modify(|served| *served += 1);
+        self.queue
+            .pending
+            .lock()
+            .expect("lock poisoned")
+            .retain(|(ticket, _)| *ticket != self.ticket);
+
+        let mut queues = self.queues.lock().expect("lock poisoned");
+        if let Some((_, tickets)) = queues.get_mut(&self.key) {
+            *tickets -= 1;
+            if *tickets == 0 {
+                queues.remove(&self.key);
+            }
+        }
+    }
+}
+
+pub struct QueueGuard {
+    // Dropped in declaration order: release the lock first, then tell the
+    // waiters that the queue moved.
+    _guard: OwnedMutexGuard<()>,
+    ticket: QueueTicket,
+}
+
+impl QueueGuard {
+    /// Take back the push this ticket submitted, unless an earlier push
+    /// already took it into its batch.
+    pub(crate) fn take_own(&self) -> Option<BatchedPush> {
+        let mut pending = self.ticket.queue.pending.lock().expect("lock poisoned");
+        let index = pending
+            .iter()
+            .position(|(ticket, _)| *ticket == self.ticket.ticket)?;
+        Some(pending.remove(index).1)
+    }
+
+    /// Take up to `limit` of the pushes waiting behind this one that can
+    /// be pushrebased together with it, in queue order.  Pushes whose
+    /// clients are no longer waiting are left for their tickets to remove.
+    pub(crate) fn take_batch(
+        &self,
+        limit: usize,
+        compatible: impl Fn(&BatchedPush) -> bool,
+    ) -> Vec<BatchedPush> {
+        let mut pending = self.ticket.queue.pending.lock().expect("lock poisoned");
+        pending.sort_by_key(|(ticket, _)| *ticket);
+        let mut batch = Vec::new();
+        let mut index = 0;
+        while index < pending.len() && batch.len() < limit {
+            let (ticket, push) = &pending[index];
+            if *ticket > self.ticket.ticket && !push.is_abandoned() && compatible(push) {
+                batch.push(pending.remove(index).1);
+            } else {
+                index += 1;
+            }
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_positions() {
+        let queue = PushrebaseQueue::new();
+        let master = BookmarkName::new("master").unwrap();
+        let other = BookmarkName::new("other").unwrap();
+
+        let first = queue.enqueue("repo", &master);
+        let mut second = queue.enqueue("repo", &master);
+        let unrelated = queue.enqueue("repo", &other);
+        assert_eq!(first.position(), 0);
+        assert_eq!(second.position(), 1);
+        assert_eq!(unrelated.position(), 0);
+
+        let guard = first.wait_turn().await;
+        // Unrelated bookmarks are not blocked.
+        let unrelated_guard = unrelated.wait_turn().await;
+        drop(guard);
+        assert_eq!(second.position_changed().await, 0);
+
+        let guard = second.wait_turn().await;
+        let third = queue.enqueue("repo", &master);
+        assert_eq!(third.position(), 1);
+        let waiter = tokio::spawn(async move {
+            let _guard = third.wait_turn().await;
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+        drop(guard);
+        waiter.await.unwrap();
+
+        // Queues are removed once nobody is waiting in them.
+        assert_eq!(queue.queues.lock().unwrap().len(), 1);
+        drop(unrelated_guard);
+        assert!(queue.queues.lock().unwrap().is_empty());
+    }
+}
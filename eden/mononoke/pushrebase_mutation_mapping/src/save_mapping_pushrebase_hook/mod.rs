@@ -41,6 +41,10 @@ impl PushrebaseHook for SaveMappingPushrebaseHook {
             repository_id: self.repository_id,
         }))
     }
+
+    fn batchable(&self) -> bool {
+        true
+    }
 }
 
 pub struct SaveMappingCommitHook {
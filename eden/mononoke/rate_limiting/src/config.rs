@@ -6,6 +6,7 @@
  */
 
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::anyhow;
@@ -19,6 +20,8 @@ use serde::Deserialize;
 pub use crate::facebook::get_region_capacity;
 #[cfg(not(fbcode_build))]
 pub use crate::oss::get_region_capacity;
+use crate::HostClass;
+use crate::HostClassifier;
 use crate::LoadShedLimit;
 use crate::Metric;
 use crate::MononokeRateLimitConfig;
@@ -27,24 +30,25 @@ use crate::RateLimitBody;
 use crate::StaticSlice;
 use crate::Target;
 
-impl TryFrom<rate_limiting_config::Target> for Target {
-    type Error = Error;
-
-    fn try_from(value: rate_limiting_config::Target) -> Result<Self, Self::Error> {
+impl Target {
+    /// Convert a target from the config, classifying hosts for any host class
+    /// targets with the given classifier.
+    fn from_config(
+        value: rate_limiting_config::Target,
+        classifier: &Arc<HostClassifier>,
+    ) -> Result<Self, Error> {
+        let convert_all = |targets: Vec<rate_limiting_config::Target>| {
+            targets
+                .into_iter()
+                .map(|t| Target::from_config(t, classifier))
+                .collect::<Result<Vec<_>, _>>()
+        };
         match value {
-            rate_limiting_config::Target::not_target(t) => {
-                Ok(Target::NotTarget(Box::new((*t).try_into()?)))
-            }
-            rate_limiting_config::Target::and_target(t) => Ok(Target::AndTarget(
-                t.into_iter()
-                    .map(|t| t.try_into())
-                    .collect::<Result<Vec<_>, _>>()?,
-            )),
-            rate_limiting_config::Target::or_target(t) => Ok(Target::OrTarget(
-                t.into_iter()
-                    .map(|t| t.try_into())
-                    .collect::<Result<Vec<_>, _>>()?,
-            )),
+            rate_limiting_config::Target::not_target(t) => Ok(Target::NotTarget(Box::new(
+                Target::from_config(*t, classifier)?,
+            ))),
+            rate_limiting_config::Target::and_target(t) => Ok(Target::AndTarget(convert_all(t)?)),
+            rate_limiting_config::Target::or_target(t) => Ok(Target::OrTarget(convert_all(t)?)),
             rate_limiting_config::Target::identity(i) => {
                 Ok(Target::Identity(FromStr::from_str(&i)?))
             }
@@ -55,11 +59,23 @@ impl TryFrom<rate_limiting_config::Target> for Target {
                     nonce: s.nonce,
                 }))
             }
+            rate_limiting_config::Target::host_class(class) => Ok(Target::HostClass(HostClass {
+                class,
+                classifier: classifier.clone(),
+            })),
             _ => Err(anyhow!("Invalid target")),
         }
     }
 }
 
+impl TryFrom<rate_limiting_config::Target> for Target {
+    type Error = Error;
+
+    fn try_from(value: rate_limiting_config::Target) -> Result<Self, Self::Error> {
+        Target::from_config(value, &Arc::new(HostClassifier::default()))
+    }
+}
+
 impl TryFrom<rate_limiting_config::RateLimitBody> for RateLimitBody {
     type Error = Error;
 
@@ -82,15 +98,17 @@ impl TryFrom<rate_limiting_config::RegionalMetric> for Metric {
             rate_limiting_config::RegionalMetric::TotalManifests => Ok(Metric::TotalManifests),
             rate_limiting_config::RegionalMetric::GetpackFiles => Ok(Metric::GetpackFiles),
             rate_limiting_config::RegionalMetric::Commits => Ok(Metric::Commits),
+            rate_limiting_config::RegionalMetric::IngressBytes => Ok(Metric::IngressBytes),
             _ => Err(anyhow!("Invalid RegionalMetric")),
         }
     }
 }
 
-impl TryFrom<rate_limiting_config::RateLimit> for RateLimit {
-    type Error = Error;
-
-    fn try_from(value: rate_limiting_config::RateLimit) -> Result<Self, Self::Error> {
+impl RateLimit {
+    fn from_config(
+        value: rate_limiting_config::RateLimit,
+        classifier: &Arc<HostClassifier>,
+    ) -> Result<Self, Error> {
         let body = value
             .limit
             .clone()
@@ -100,7 +118,7 @@ impl TryFrom<rate_limiting_config::RateLimit> for RateLimit {
         let target = value
             .target
             .clone()
-            .map(Target::try_from)
+            .map(|t| Target::from_config(t, classifier))
             .transpose()
             .context("Invalid target")?;
 
@@ -114,14 +132,23 @@ impl TryFrom<rate_limiting_config::RateLimit> for RateLimit {
     }
 }
 
-impl TryFrom<rate_limiting_config::LoadShedLimit> for LoadShedLimit {
+impl TryFrom<rate_limiting_config::RateLimit> for RateLimit {
     type Error = Error;
 
-    fn try_from(value: rate_limiting_config::LoadShedLimit) -> Result<Self, Self::Error> {
+    fn try_from(value: rate_limiting_config::RateLimit) -> Result<Self, Self::Error> {
+        RateLimit::from_config(value, &Arc::new(HostClassifier::default()))
+    }
+}
+
+impl LoadShedLimit {
+    fn from_config(
+        value: rate_limiting_config::LoadShedLimit,
+        classifier: &Arc<HostClassifier>,
+    ) -> Result<Self, Error> {
         let target = value
             .target
             .clone()
-            .map(Target::try_from)
+            .map(|t| Target::from_config(t, classifier))
             .transpose()
             .context("Invalid target")?;
 
@@ -132,6 +159,14 @@ impl TryFrom<rate_limiting_config::LoadShedLimit> for LoadShedLimit {
     }
 }
 
+impl TryFrom<rate_limiting_config::LoadShedLimit> for LoadShedLimit {
+    type Error = Error;
+
+    fn try_from(value: rate_limiting_config::LoadShedLimit) -> Result<Self, Self::Error> {
+        LoadShedLimit::from_config(value, &Arc::new(HostClassifier::default()))
+    }
+}
+
 impl<'de> Deserialize<'de> for LoadShedLimit {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -164,11 +199,20 @@ impl<'de> Deserialize<'de> for MononokeRateLimitConfig {
             None => 1.0,
         };
 
+        let host_classifier = raw_config
+            .host_classifier
+            .clone()
+            .map(HostClassifier::try_from)
+            .transpose()
+            .map_err(|e| D::Error::custom(format!("{:?}", e)))?
+            .unwrap_or_default();
+        let host_classifier = Arc::new(host_classifier);
+
         let rate_limits = raw_config
             .rate_limits
             .clone()
             .into_iter()
-            .map(|r| r.try_into())
+            .map(|r| RateLimit::from_config(r, &host_classifier))
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| D::Error::custom(format!("{:?}", e)))?;
 
@@ -176,7 +220,7 @@ impl<'de> Deserialize<'de> for MononokeRateLimitConfig {
             .load_shed_limits
             .clone()
             .into_iter()
-            .map(|r| r.try_into())
+            .map(|r| LoadShedLimit::from_config(r, &host_classifier))
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| D::Error::custom(format!("{:?}", e)))?;
 
@@ -199,6 +243,7 @@ impl<'de> Deserialize<'de> for MononokeRateLimitConfig {
             load_shed_limits,
             commits_per_author,
             total_file_changes,
+            host_classifier,
         })
     }
 }
@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Classification of client hosts.
+//!
+//! Clients are grouped into classes by their hostname so that different kinds
+//! of client can be told apart.  A hostname is first looked up in the explicit
+//! overrides, and then matched against each rule in order, with the first
+//! matching rule deciding the class.  A rule's class may refer to the regex's
+//! capture groups, e.g. `$1`.
+//!
+//! Without any configuration, hosts are classified by the alphabetic prefix
+//! of their hostname, so `devvm123.abc1.example.com` is in class `devvm`.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use anyhow::Error;
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+struct HostClassRule {
+    regex: Regex,
+    class: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostClassifier {
+    overrides: HashMap<String, String>,
+    rules: Vec<HostClassRule>,
+}
+
+impl Default for HostClassifier {
+    fn default() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            rules: vec![HostClassRule {
+                regex: Regex::new("^([a-zA-Z]+)").expect("default host class regex is valid"),
+                class: "$1".to_string(),
+            }],
+        }
+    }
+}
+
+impl HostClassifier {
+    /// Create a classifier from `(regex, class)` rules, which are tried in
+    /// order, and a map of hostnames to classes which take precedence over
+    /// the rules.
+    pub fn new(
+        rules: impl IntoIterator<Item = (String, String)>,
+        overrides: HashMap<String, String>,
+    ) -> Result<Self, Error> {
+        let rules = rules
+            .into_iter()
+            .map(|(regex, class)| {
+                let regex = Regex::new(&regex)
+                    .with_context(|| format!("Invalid host class regex '{}'", regex))?;
+                Ok(HostClassRule { regex, class })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self { overrides, rules })
+    }
+
+    /// The class of a host, or `None` if no override or rule matches it.
+    pub fn classify(&self, hostname: &str) -> Option<String> {
+        if let Some(class) = self.overrides.get(hostname) {
+            return Some(class.clone());
+        }
+        self.rules.iter().find_map(|rule| {
+            let captures = rule.regex.captures(hostname)?;
+            let mut class = String::new();
+            captures.expand(&rule.class, &mut class);
+            Some(class).filter(|class| !class.is_empty())
+        })
+    }
+}
+
+impl TryFrom<rate_limiting_config::HostClassifier> for HostClassifier {
+    type Error = Error;
+
+    fn try_from(value: rate_limiting_config::HostClassifier) -> Result<Self, Self::Error> {
+        Self::new(
+            value.rules.into_iter().map(|rule| (rule.regex, rule.class)),
+            value.overrides.into_iter().collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn test_default_classifier() {
+        let classifier = HostClassifier::default();
+        assert_eq!(
+            classifier.classify("devvm123.abc1.example.com").as_deref(),
+            Some("devvm")
+        );
+        assert_eq!(
+            classifier.classify("build-42.ci.example.org").as_deref(),
+            Some("build")
+        );
+        assert_eq!(classifier.classify("10.0.0.1"), None);
+    }
+
+    #[test]
+    fn test_configured_classifier() -> Result<(), Error> {
+        let classifier = HostClassifier::new(
+            vec![
+                (r"^ci-runner-\d+\.".to_string(), "ci".to_string()),
+                (r"^([a-z]+)-\d+\.corp\.".to_string(), "corp-$1".to_string()),
+                (r"\.corp\.".to_string(), "corp".to_string()),
+            ],
+            hashmap! {
+                "ci-runner-1.build.example.com".to_string() => "canary".to_string(),
+            },
+        )?;
+
+        // Overrides take precedence over rules.
+        assert_eq!(
+            classifier
+                .classify("ci-runner-1.build.example.com")
+                .as_deref(),
+            Some("canary")
+        );
+        assert_eq!(
+            classifier
+                .classify("ci-runner-2.build.example.com")
+                .as_deref(),
+            Some("ci")
+        );
+        // Rules are tried in order, and may refer to capture groups.
+        assert_eq!(
+            classifier.classify("laptop-7.corp.example.com").as_deref(),
+            Some("corp-laptop")
+        );
+        assert_eq!(
+            classifier.classify("printer.corp.example.com").as_deref(),
+            Some("corp")
+        );
+        // Hosts that don't match any rule are unclassified.
+        assert_eq!(classifier.classify("devvm123.abc1.example.com"), None);
+
+        assert!(
+            HostClassifier::new(vec![("(".to_string(), "x".to_string())], HashMap::new()).is_err()
+        );
+        Ok(())
+    }
+}
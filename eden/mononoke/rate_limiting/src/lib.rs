@@ -6,8 +6,10 @@
  */
 
 use std::collections::hash_map::DefaultHasher;
+use std::fmt;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::anyhow;
@@ -37,8 +39,31 @@ pub use oss::get_region_capacity;
 pub use rate_limiting_config::RateLimitStatus;
 
 pub mod config;
+mod host_class;
+
+pub use host_class::HostClassifier;
 
 pub type LoadCost = f64;
+
+/// Load shed counters are aggregated over a minute, so this is how long it
+/// takes for load that has been shed to stop counting against the limit.
+const LOAD_SHED_WINDOW: Duration = Duration::from_secs(60);
+
+/// Bounds on how long a client should wait before retrying a request that was
+/// rejected by load shedding.  Load that has been shed keeps counting against
+/// the limit for a whole window, so a client that retries sooner is likely to
+/// be rejected again.
+const MIN_LOAD_SHED_RETRY_AFTER: Duration = LOAD_SHED_WINDOW;
+const MAX_LOAD_SHED_RETRY_AFTER: Duration = Duration::from_secs(600);
+
+/// Number of queued connections that adds another load shed window to the
+/// retry-after.
+const QUEUE_DEPTH_PER_WINDOW: usize = 1000;
+
+/// Maximum percentage by which the retry-after is spread between clients, so
+/// that clients rejected at the same time don't all retry at the same time.
+const LOAD_SHED_RETRY_JITTER_PCT: u64 = 20;
+
 pub type BoxRateLimiter = Box<dyn RateLimiter + Send + Sync + 'static>;
 
 #[async_trait]
@@ -89,6 +114,11 @@ impl RateLimitEnvironment {
 
         create_rate_limiter(self.fb, self.category.clone(), config)
     }
+
+    /// The class of a client host, according to the current config.
+    pub fn host_class(&self, hostname: &str) -> Option<String> {
+        self.config.get().host_classifier.classify(hostname)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -106,6 +136,7 @@ pub struct MononokeRateLimitConfig {
     commits_per_author: RateLimitBody,
     #[allow(dead_code)]
     total_file_changes: Option<RateLimitBody>,
+    pub host_classifier: Arc<HostClassifier>,
 }
 
 #[derive(Debug, Clone)]
@@ -170,6 +201,7 @@ pub enum Metric {
     TotalManifests,
     GetpackFiles,
     Commits,
+    IngressBytes,
 }
 
 #[must_use]
@@ -181,6 +213,93 @@ pub enum RateLimitReason {
     LoadShedMetric(String, i64, i64),
 }
 
+impl RateLimitReason {
+    /// A notice for the client explaining this throttling decision.
+    pub fn notice(&self) -> RateLimitNotice {
+        self.notice_under_load(0, None)
+    }
+
+    /// A notice for the client explaining this throttling decision, taking
+    /// into account how many connections are queued on the server and which
+    /// client is being throttled when working out how long the client should
+    /// back off for.
+    pub fn notice_under_load(
+        &self,
+        queue_depth: usize,
+        identities: Option<&MononokeIdentitySet>,
+    ) -> RateLimitNotice {
+        match self {
+            Self::RateLimitedMetric(metric, window) => RateLimitNotice {
+                limit: format!("{:?}", metric),
+                usage: None,
+                retry_after: *window,
+            },
+            Self::LoadShedMetric(metric, value, limit) => RateLimitNotice {
+                limit: metric.clone(),
+                usage: Some((*value, *limit)),
+                retry_after: load_shed_retry_after(*value, *limit, queue_depth, identities),
+            },
+        }
+    }
+}
+
+/// Work out how long a client rejected by load shedding should wait before
+/// retrying.
+///
+/// The more the limit is exceeded by, and the more connections are queued,
+/// the longer the client is asked to wait, so that the load has time to drop
+/// below the limit before clients come back.  The result is spread between
+/// clients by their identities so that clients rejected together don't retry
+/// together.
+pub fn load_shed_retry_after(
+    value: i64,
+    limit: i64,
+    queue_depth: usize,
+    identities: Option<&MononokeIdentitySet>,
+) -> Duration {
+    let overload = (value as f64 / limit.max(1) as f64 - 1.0).max(0.0);
+    let queued = queue_depth as f64 / QUEUE_DEPTH_PER_WINDOW as f64;
+    let retry_after = LOAD_SHED_WINDOW
+        .mul_f64(overload + queued)
+        .clamp(MIN_LOAD_SHED_RETRY_AFTER, MAX_LOAD_SHED_RETRY_AFTER);
+
+    // The jitter is applied after clamping, so that clients are spread out
+    // even when the retry-after is at its minimum.
+    let jitter_pct = match identities {
+        Some(identities) => {
+            let mut hasher = DefaultHasher::new();
+            identities.hash(&mut hasher);
+            hasher.finish() % (LOAD_SHED_RETRY_JITTER_PCT + 1)
+        }
+        None => 0,
+    };
+    retry_after
+        .mul_f64(1.0 + jitter_pct as f64 / 100.0)
+        .min(MAX_LOAD_SHED_RETRY_AFTER)
+}
+
+/// A structured explanation of why a request was throttled, which is sent to
+/// the client so that throttling doesn't look like a slow or broken server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitNotice {
+    /// The metric whose limit was exceeded.
+    pub limit: String,
+    /// The current value of the metric and its limit, if known.
+    pub usage: Option<(i64, i64)>,
+    /// How long the client should wait before retrying.
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimitNotice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "limit={}", self.limit)?;
+        if let Some((value, limit)) = self.usage {
+            write!(f, " usage={}/{}", value, limit)?;
+        }
+        write!(f, " retry_after={}s", self.retry_after.as_secs())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Target {
     NotTarget(Box<Target>),
@@ -188,6 +307,7 @@ pub enum Target {
     OrTarget(Vec<Target>),
     Identity(MononokeIdentity),
     StaticSlice(StaticSlice),
+    HostClass(HostClass),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -213,6 +333,14 @@ pub struct StaticSlice {
     nonce: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct HostClass {
+    class: String,
+    // The classifier from the same config as this target, so that a client
+    // is classified the same way here as it is in the logs.
+    classifier: Arc<HostClassifier>,
+}
+
 impl Target {
     pub fn matches_client(&self, identities: Option<&MononokeIdentitySet>) -> bool {
         match self {
@@ -224,6 +352,7 @@ impl Target {
                 None => false,
             },
             Self::StaticSlice(s) => in_throttled_slice(identities, s.slice_pct, &s.nonce),
+            Self::HostClass(c) => in_host_class(identities, &c.class, &c.classifier),
         }
     }
 }
@@ -246,6 +375,17 @@ fn in_throttled_slice(
     hasher.finish() % 100 < slice_pct.0.into()
 }
 
+fn in_host_class(
+    identities: Option<&MononokeIdentitySet>,
+    class: &str,
+    classifier: &HostClassifier,
+) -> bool {
+    identities
+        .and_then(|i| i.hostname())
+        .and_then(|hostname| classifier.classify(hostname))
+        .map_or(false, |client_class| client_class == class)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -282,6 +422,26 @@ mod test {
         assert!(not_target.matches_client(idents.as_ref()));
     }
 
+    #[test]
+    fn test_target_in_host_class() {
+        let target = |class: &str| {
+            Target::HostClass(HostClass {
+                class: class.to_string(),
+                classifier: Arc::new(HostClassifier::default()),
+            })
+        };
+        let mut identities = MononokeIdentitySet::new();
+        identities.insert(MononokeIdentity::new(
+            "MACHINE",
+            "devvm123.abc1.facebook.com",
+        ));
+
+        assert!(target("devvm").matches_client(Some(&identities)));
+        assert!(!target("twshared").matches_client(Some(&identities)));
+        assert!(!target("devvm").matches_client(Some(&MononokeIdentitySet::new())));
+        assert!(!target("devvm").matches_client(None));
+    }
+
     #[test]
     fn test_target_in_static_slice() {
         let mut identities = MononokeIdentitySet::new();
@@ -314,4 +474,96 @@ mod test {
             "abc"
         ));
     }
+
+    #[test]
+    fn test_rate_limit_notice() {
+        let notice =
+            RateLimitReason::RateLimitedMetric(Metric::EgressBytes, Duration::from_secs(10))
+                .notice();
+        assert_eq!(notice.usage, None);
+        assert_eq!(notice.retry_after, Duration::from_secs(10));
+        assert_eq!(notice.to_string(), "limit=EgressBytes retry_after=10s");
+
+        let notice = RateLimitReason::LoadShedMetric("requests".to_string(), 200, 100).notice();
+        assert_eq!(notice.usage, Some((200, 100)));
+        assert_eq!(notice.retry_after, LOAD_SHED_WINDOW);
+        assert_eq!(
+            notice.to_string(),
+            "limit=requests usage=200/100 retry_after=60s"
+        );
+
+        let notice = RateLimitReason::LoadShedMetric("requests".to_string(), 200, 100)
+            .notice_under_load(500, None);
+        assert_eq!(notice.retry_after, Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_load_shed_retry_after() {
+        // Barely exceeding the limit still backs off for a while.
+        assert_eq!(
+            load_shed_retry_after(101, 100, 0, None),
+            MIN_LOAD_SHED_RETRY_AFTER
+        );
+        // Retry-after grows with utilization and queue depth.
+        assert_eq!(
+            load_shed_retry_after(300, 100, 0, None),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            load_shed_retry_after(300, 100, 1000, None),
+            Duration::from_secs(180)
+        );
+        assert_eq!(
+            load_shed_retry_after(10000, 100, 0, None),
+            MAX_LOAD_SHED_RETRY_AFTER
+        );
+        // A zero limit doesn't divide by zero.
+        assert_eq!(
+            load_shed_retry_after(5, 0, 0, None),
+            Duration::from_secs(240)
+        );
+
+        // Clients are spread out by a bounded amount, consistently for the
+        // same client.
+        let retry_after_for = |hostname: &str| {
+            let mut identities = MononokeIdentitySet::new();
+            identities.insert(MononokeIdentity::new("MACHINE", hostname));
+            load_shed_retry_after(300, 100, 0, Some(&identities))
+        };
+        let hosts = (0..20)
+            .map(|i| format!("host{}.abc1.facebook.com", i))
+            .collect::<Vec<_>>();
+        let retry_afters = hosts
+            .iter()
+            .map(|host| retry_after_for(host))
+            .collect::<Vec<_>>();
+        for retry_after in retry_afters.iter() {
+            assert!(*retry_after >= Duration::from_secs(120));
+            assert!(*retry_after <= Duration::from_secs(144));
+        }
+        assert!(retry_afters.iter().any(|r| *r != retry_afters[0]));
+        assert_eq!(retry_after_for(&hosts[0]), retry_afters[0]);
+
+        // Clients are spread out at the minimum as well, but never beyond
+        // the bounds.
+        let identities_for = |hostname: &str| {
+            let mut identities = MononokeIdentitySet::new();
+            identities.insert(MononokeIdentity::new("MACHINE", hostname));
+            identities
+        };
+        let at_min = hosts
+            .iter()
+            .map(|host| load_shed_retry_after(101, 100, 0, Some(&identities_for(host))))
+            .collect::<Vec<_>>();
+        for retry_after in at_min.iter() {
+            assert!(*retry_after >= MIN_LOAD_SHED_RETRY_AFTER);
+        }
+        assert!(at_min.iter().any(|r| *r != at_min[0]));
+        for host in hosts.iter() {
+            assert_eq!(
+                load_shed_retry_after(10000, 100, 0, Some(&identities_for(host))),
+                MAX_LOAD_SHED_RETRY_AFTER
+            );
+        }
+    }
 }
@@ -9,6 +9,8 @@ use std::cmp::min;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::num::NonZeroI64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use anyhow::Error;
@@ -189,10 +191,13 @@ fn deserialize_skiplist_mapping(logger: Logger, bytes: Bytes) -> Result<Skiplist
     Ok(SkiplistEdgeMapping::from_map(cmap))
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct SkiplistEdgeMapping {
     pub mapping: DashMap<ChangesetId, SkiplistNodeType>,
     pub skip_edges_per_node: u32,
+    /// Approximate memory used by the mapping, kept up to date as nodes are
+    /// inserted.
+    bytes: AtomicUsize,
 }
 
 impl SkiplistEdgeMapping {
@@ -200,13 +205,35 @@ impl SkiplistEdgeMapping {
         SkiplistEdgeMapping {
             mapping: DashMap::new(),
             skip_edges_per_node: DEFAULT_EDGE_COUNT,
+            bytes: AtomicUsize::new(0),
         }
     }
 
     pub fn from_map(map: DashMap<ChangesetId, SkiplistNodeType>) -> Self {
+        let bytes = map.iter().map(|entry| node_bytes(entry.value())).sum();
         SkiplistEdgeMapping {
             mapping: map,
             skip_edges_per_node: DEFAULT_EDGE_COUNT,
+            bytes: AtomicUsize::new(bytes),
+        }
+    }
+
+    pub fn insert(&self, cs_id: ChangesetId, node: SkiplistNodeType) {
+        let new_bytes = node_bytes(&node);
+        match self.mapping.insert(cs_id, node) {
+            Some(old_node) => {
+                let old_bytes = node_bytes(&old_node);
+                if new_bytes >= old_bytes {
+                    self.bytes
+                        .fetch_add(new_bytes - old_bytes, Ordering::Relaxed);
+                } else {
+                    self.bytes
+                        .fetch_sub(old_bytes - new_bytes, Ordering::Relaxed);
+                }
+            }
+            None => {
+                self.bytes.fetch_add(new_bytes, Ordering::Relaxed);
+            }
         }
     }
 
@@ -218,6 +245,16 @@ impl SkiplistEdgeMapping {
     }
 }
 
+/// Approximate memory used by a node of the skiplist.
+fn node_bytes(node: &SkiplistNodeType) -> usize {
+    let edges = match node {
+        SkiplistNodeType::SingleEdge(_) => 0,
+        SkiplistNodeType::SkipEdges(edges) | SkiplistNodeType::ParentEdges(edges) => edges.len(),
+    };
+    std::mem::size_of::<(ChangesetId, SkiplistNodeType)>()
+        + edges * std::mem::size_of::<(ChangesetId, Generation)>()
+}
+
 /// helper function that computes a single skip edge by leveraging the existing skiplist
 /// without assuming its completeness.
 async fn compute_single_skip_edge(
@@ -416,9 +453,7 @@ async fn lazy_index_node(
         if parent_gen_pairs.len() != 1 {
             // Merge node or parentless node
             // Reflect this in the index
-            skip_edge_mapping
-                .mapping
-                .insert(curr_hash, SkiplistNodeType::ParentEdges(parent_gen_pairs));
+            skip_edge_mapping.insert(curr_hash, SkiplistNodeType::ParentEdges(parent_gen_pairs));
         } else {
             // Single parent node
             // Compute skip edges assuming a reasonable number of parents are indexed.
@@ -432,9 +467,7 @@ async fn lazy_index_node(
                 skip_edge_mapping.clone(),
             )
             .await?;
-            skip_edge_mapping
-                .mapping
-                .insert(curr_hash, SkiplistNodeType::SkipEdges(new_edges));
+            skip_edge_mapping.insert(curr_hash, SkiplistNodeType::SkipEdges(new_edges));
         }
     }
     Ok(())
@@ -620,6 +653,11 @@ impl SkiplistIndex {
         self.edges().mapping.len()
     }
 
+    /// Approximate memory used by the index.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        self.edges().bytes.load(Ordering::Relaxed)
+    }
+
     // Remove all but latest skip entry (i.e. entry with the longest jump) to save space.
     pub fn trim_to_single_entry_per_changeset(&self) {
         let skip_list_edges = self.edges();
@@ -629,7 +667,7 @@ impl SkiplistIndex {
             } else {
                 old_node
             };
-            let _old_node = skip_list_edges.mapping.insert(cs_id, new_node);
+            skip_list_edges.insert(cs_id, new_node);
         }
     }
 }
@@ -1177,6 +1215,7 @@ mod test {
     async fn simple_init() {
         let sli = SkiplistIndex::new();
         assert_eq!(sli.skip_edge_count(), DEFAULT_EDGE_COUNT);
+        assert_eq!(sli.estimated_memory_bytes(), 0);
 
         let sli_with_20 = SkiplistIndex::with_skip_edge_count(20);
         assert_eq!(sli_with_20.skip_edge_count(), 20);
@@ -1212,6 +1251,10 @@ mod test {
             string_to_bonsai(&ctx, &repo, "2d7d4ba9ce0a6ffd222de7785b249ead9c51c536").await,
         ];
         assert_eq!(sli.indexed_node_count(), ordered_hashes.len());
+        assert!(
+            sli.estimated_memory_bytes()
+                >= ordered_hashes.len() * std::mem::size_of::<(ChangesetId, SkiplistNodeType)>()
+        );
         for node in ordered_hashes.into_iter() {
             assert!(sli.is_node_indexed(node));
         }
@@ -0,0 +1,18 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS reachable_contents (
+  repo_id INTEGER NOT NULL,
+  content_id BINARY(32) NOT NULL,
+  PRIMARY KEY (repo_id, content_id)
+);
+
+CREATE TABLE IF NOT EXISTS reachable_contents_changesets (
+  repo_id INTEGER NOT NULL,
+  cs_id BINARY(32) NOT NULL,
+  PRIMARY KEY (repo_id, cs_id)
+);
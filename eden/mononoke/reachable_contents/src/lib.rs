@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Index of the file contents that are reachable from the public history of
+//! a repo.
+//!
+//! Changesets are immutable, so once a content is reachable from a public
+//! changeset it stays reachable.  The index is therefore maintained
+//! incrementally: updating it from the public heads only visits the
+//! changesets that have not been processed before, and records the contents
+//! they add.  Garbage collection can then check whether a content is
+//! reachable with a single indexed lookup, rather than walking the history
+//! of the repo at collection time.
+
+mod sql;
+
+use std::collections::HashSet;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use blobstore::Loadable;
+use changesets::ChangesetsRef;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::RepositoryId;
+use repo_blobstore::RepoBlobstoreRef;
+use slog::info;
+
+pub use crate::sql::SqlReachableContents;
+pub use crate::sql::SqlReachableContentsBuilder;
+
+/// Number of changesets to process at a time when updating the index.
+const UPDATE_BATCH_SIZE: usize = 1000;
+
+/// Number of changesets to load concurrently when updating the index.
+const LOAD_CONCURRENCY: usize = 100;
+
+#[facet::facet]
+#[async_trait]
+pub trait ReachableContents: Send + Sync {
+    fn repo_id(&self) -> RepositoryId;
+
+    /// Record that some contents are reachable.
+    async fn add_contents(&self, ctx: &CoreContext, contents: &[ContentId]) -> Result<(), Error>;
+
+    /// Record that the contents of some changesets, and of all of their
+    /// ancestors, have been recorded.
+    async fn add_changesets(&self, ctx: &CoreContext, cs_ids: &[ChangesetId]) -> Result<(), Error>;
+
+    /// Return which of the given changesets have been processed.
+    async fn processed_changesets(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashSet<ChangesetId>, Error>;
+
+    /// Return which of the given contents are reachable.
+    async fn filter_reachable(
+        &self,
+        ctx: &CoreContext,
+        contents: &[ContentId],
+    ) -> Result<HashSet<ContentId>, Error>;
+
+    /// Return whether a content is reachable.
+    async fn is_reachable(&self, ctx: &CoreContext, content: ContentId) -> Result<bool, Error> {
+        Ok(!self.filter_reachable(ctx, &[content]).await?.is_empty())
+    }
+}
+
+/// Update the index with the contents reachable from some public heads.
+///
+/// Only changesets that have not been processed before are visited.  They
+/// are processed in batches in generation order, and the contents of each
+/// batch are recorded before its changesets are marked as processed.  Every
+/// unprocessed ancestor of a changeset is in the same or an earlier batch,
+/// so each marked batch is a checkpoint: an interrupted update resumes after
+/// the last marked batch when it is run again.
+///
+/// Returns the number of changesets that were processed.
+pub async fn update_reachable_contents(
+    ctx: &CoreContext,
+    repo: &(impl ReachableContentsRef + ChangesetsRef + RepoBlobstoreRef),
+    heads: Vec<ChangesetId>,
+) -> Result<usize, Error> {
+    let index = repo.reachable_contents();
+
+    // Find the unprocessed changesets.  This only needs the changesets'
+    // parents and generations, which are cheap to fetch compared to loading
+    // the changesets themselves.
+    let mut to_visit = heads;
+    let mut visited = HashSet::new();
+    let mut unprocessed = Vec::new();
+    while !to_visit.is_empty() {
+        let batch_size = to_visit.len().min(UPDATE_BATCH_SIZE);
+        let batch: Vec<_> = to_visit
+            .drain(..batch_size)
+            .filter(|cs_id| visited.insert(*cs_id))
+            .collect();
+        let already_processed = index.processed_changesets(ctx, &batch).await?;
+        let batch: Vec<_> = batch
+            .into_iter()
+            .filter(|cs_id| !already_processed.contains(cs_id))
+            .collect();
+        if batch.is_empty() {
+            continue;
+        }
+
+        for entry in repo.changesets().get_many(ctx.clone(), batch).await? {
+            to_visit.extend(entry.parents.iter().copied());
+            unprocessed.push((entry.gen, entry.cs_id));
+        }
+    }
+
+    unprocessed.sort_unstable();
+    let mut processed = 0;
+    for chunk in unprocessed.chunks(UPDATE_BATCH_SIZE) {
+        let contents = stream::iter(chunk)
+            .map(|(_gen, cs_id)| async move {
+                let bcs = cs_id.load(ctx, repo.repo_blobstore()).await?;
+                let contents = bcs
+                    .file_changes()
+                    .filter_map(|(_path, change)| change.simplify())
+                    .map(|change| change.content_id())
+                    .collect::<Vec<_>>();
+                Ok::<_, Error>(contents)
+            })
+            .buffer_unordered(LOAD_CONCURRENCY)
+            .try_concat()
+            .await?;
+        index.add_contents(ctx, &contents).await?;
+
+        let cs_ids: Vec<_> = chunk.iter().map(|(_gen, cs_id)| *cs_id).collect();
+        index.add_changesets(ctx, &cs_ids).await?;
+        processed += chunk.len();
+        info!(
+            ctx.logger(),
+            "Processed {} of {} changesets",
+            processed,
+            unprocessed.len()
+        );
+    }
+
+    Ok(processed)
+}
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+
+use ::sql_ext::mononoke_queries;
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::hash::Blake2;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::RepositoryId;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::SqlConnections;
+
+use super::ReachableContents;
+
+mononoke_queries! {
+    write InsertContents(values: (
+        repo_id: RepositoryId,
+        content_id: Blake2,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO reachable_contents (repo_id, content_id) VALUES {values}"
+    }
+
+    write InsertChangesets(values: (
+        repo_id: RepositoryId,
+        cs_id: ChangesetId,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO reachable_contents_changesets (repo_id, cs_id) VALUES {values}"
+    }
+
+    read SelectChangesets(
+        repo_id: RepositoryId,
+        >list cs_id: ChangesetId
+    ) -> (ChangesetId,) {
+        "SELECT cs_id
+         FROM reachable_contents_changesets
+         WHERE repo_id = {repo_id} AND cs_id IN {cs_id}"
+    }
+
+    read SelectContents(
+        repo_id: RepositoryId,
+        >list content_id: Blake2
+    ) -> (Blake2,) {
+        "SELECT content_id
+         FROM reachable_contents
+         WHERE repo_id = {repo_id} AND content_id IN {content_id}"
+    }
+}
+
+pub struct SqlReachableContents {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+#[derive(Clone)]
+pub struct SqlReachableContentsBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlReachableContentsBuilder {
+    const LABEL: &'static str = "reachable_contents";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-reachable-contents.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlReachableContentsBuilder {}
+
+impl SqlReachableContentsBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlReachableContents {
+        SqlReachableContents {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+#[async_trait]
+impl ReachableContents for SqlReachableContents {
+    fn repo_id(&self) -> RepositoryId {
+        self.repo_id
+    }
+
+    async fn add_contents(&self, ctx: &CoreContext, contents: &[ContentId]) -> Result<(), Error> {
+        if contents.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let values: Vec<_> = contents
+            .iter()
+            .map(|content_id| (&self.repo_id, content_id.blake2()))
+            .collect();
+
+        InsertContents::query(&self.connections.write_connection, &values[..]).await?;
+
+        Ok(())
+    }
+
+    async fn add_changesets(&self, ctx: &CoreContext, cs_ids: &[ChangesetId]) -> Result<(), Error> {
+        if cs_ids.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let values: Vec<_> = cs_ids.iter().map(|cs_id| (&self.repo_id, cs_id)).collect();
+
+        InsertChangesets::query(&self.connections.write_connection, &values[..]).await?;
+
+        Ok(())
+    }
+
+    async fn processed_changesets(
+        &self,
+        ctx: &CoreContext,
+        cs_ids: &[ChangesetId],
+    ) -> Result<HashSet<ChangesetId>, Error> {
+        if cs_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        // Only the updater reads this, and it has just written to the
+        // master, so read from there to avoid replication lag.
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+
+        let rows = SelectChangesets::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            cs_ids,
+        )
+        .await?;
+        Ok(rows.into_iter().map(|(cs_id,)| cs_id).collect())
+    }
+
+    async fn filter_reachable(
+        &self,
+        ctx: &CoreContext,
+        contents: &[ContentId],
+    ) -> Result<HashSet<ContentId>, Error> {
+        if contents.is_empty() {
+            return Ok(HashSet::new());
+        }
+        // Garbage collection must not miss a reachable content because of
+        // replication lag, so this always reads from the master.
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+
+        let content_ids: Vec<_> = contents
+            .iter()
+            .map(|content_id| *content_id.blake2())
+            .collect();
+        let rows = SelectContents::query(
+            &self.connections.read_master_connection,
+            &self.repo_id,
+            &content_ids[..],
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(content_id,)| ContentId::new(content_id))
+            .collect())
+    }
+}
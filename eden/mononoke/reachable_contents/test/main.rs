@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+
+use anyhow::Error;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types_mocks::changesetid::ONES_CSID;
+use mononoke_types_mocks::changesetid::THREES_CSID;
+use mononoke_types_mocks::changesetid::TWOS_CSID;
+use mononoke_types_mocks::contentid::ONES_CTID;
+use mononoke_types_mocks::contentid::THREES_CTID;
+use mononoke_types_mocks::contentid::TWOS_CTID;
+use mononoke_types_mocks::repo::REPO_ONE;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use reachable_contents::ReachableContents;
+use reachable_contents::SqlReachableContentsBuilder;
+use sql_construct::SqlConstruct;
+
+#[fbinit::test]
+async fn test_add_and_filter(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let index = SqlReachableContentsBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    index.add_contents(&ctx, &[ONES_CTID, TWOS_CTID]).await?;
+    // Adding the same contents again is a no-op.
+    index.add_contents(&ctx, &[ONES_CTID]).await?;
+
+    assert!(index.is_reachable(&ctx, ONES_CTID).await?);
+    assert!(!index.is_reachable(&ctx, THREES_CTID).await?);
+    assert_eq!(
+        index
+            .filter_reachable(&ctx, &[ONES_CTID, TWOS_CTID, THREES_CTID])
+            .await?,
+        HashSet::from([ONES_CTID, TWOS_CTID])
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_processed_changesets(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let index = SqlReachableContentsBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    index.add_changesets(&ctx, &[ONES_CSID, TWOS_CSID]).await?;
+
+    assert_eq!(
+        index
+            .processed_changesets(&ctx, &[ONES_CSID, TWOS_CSID, THREES_CSID])
+            .await?,
+        HashSet::from([ONES_CSID, TWOS_CSID])
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let builder = SqlReachableContentsBuilder::with_sqlite_in_memory()?;
+    let zero = builder.clone().build(REPO_ZERO);
+    let one = builder.build(REPO_ONE);
+
+    zero.add_contents(&ctx, &[ONES_CTID]).await?;
+    zero.add_changesets(&ctx, &[ONES_CSID]).await?;
+
+    assert!(zero.is_reachable(&ctx, ONES_CTID).await?);
+    assert!(!one.is_reachable(&ctx, ONES_CTID).await?);
+    assert!(
+        one.processed_changesets(&ctx, &[ONES_CSID])
+            .await?
+            .is_empty()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use blobstore::LoadableError;
+use mononoke_types::ChangesetId;
+use mononoke_types::MPath;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RepoApiError {
+    #[error("Commit {0} not found")]
+    CommitNotFound(ChangesetId),
+
+    #[error("Path '{}' not found in commit {changeset_id}", display_path(.path))]
+    PathNotFound {
+        changeset_id: ChangesetId,
+        path: Option<MPath>,
+    },
+
+    #[error("Path '{}' in commit {changeset_id} is not a directory", display_path(.path))]
+    NotADirectory {
+        changeset_id: ChangesetId,
+        path: Option<MPath>,
+    },
+
+    #[error("Path '{path}' in commit {changeset_id} is not a file")]
+    NotAFile {
+        changeset_id: ChangesetId,
+        path: MPath,
+    },
+
+    #[error("File '{path}' in commit {changeset_id} is larger than {limit} bytes")]
+    FileTooLarge {
+        changeset_id: ChangesetId,
+        path: MPath,
+        size: u64,
+        limit: u64,
+    },
+
+    #[error(transparent)]
+    InternalError(#[from] Error),
+}
+
+fn display_path(path: &Option<MPath>) -> String {
+    match path {
+        Some(path) => path.to_string(),
+        None => String::new(),
+    }
+}
+
+impl From<LoadableError> for RepoApiError {
+    fn from(err: LoadableError) -> Self {
+        RepoApiError::InternalError(err.into())
+    }
+}
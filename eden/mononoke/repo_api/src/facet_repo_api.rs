@@ -0,0 +1,297 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use blobstore::Loadable;
+use blobstore::LoadableError;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use bytes::Bytes;
+use changesets::ChangesetEntry;
+use changesets::ChangesetsRef;
+use context::CoreContext;
+use fsnodes::RootFsnodeId;
+use futures::TryStreamExt;
+use manifest::Entry;
+use manifest::ManifestOps;
+use mononoke_types::fsnode::FsnodeEntry;
+use mononoke_types::fsnode::FsnodeFile;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::FsnodeId;
+use mononoke_types::MPath;
+use mononoke_types::MPathElement;
+use phases::PhasesRef;
+use repo_blobstore::RepoBlobstoreRef;
+use repo_derived_data::RepoDerivedDataRef;
+use repo_identity::RepoIdentityRef;
+
+use crate::RepoApi;
+use crate::RepoApiError;
+use crate::TreeEntry;
+
+/// `RepoApi` for a repo with the attributes it reads from.
+pub struct FacetRepoApi<R> {
+    repo: R,
+}
+
+impl<R> FacetRepoApi<R>
+where
+    R: RepoIdentityRef
+        + BookmarksRef
+        + ChangesetsRef
+        + PhasesRef
+        + RepoBlobstoreRef
+        + RepoDerivedDataRef
+        + Send
+        + Sync,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    /// The repo this reads from.
+    pub fn repo(&self) -> &R {
+        &self.repo
+    }
+
+    async fn changeset_entry(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<ChangesetEntry, RepoApiError> {
+        self.repo
+            .changesets()
+            .get(ctx.clone(), cs_id)
+            .await?
+            .ok_or(RepoApiError::CommitNotFound(cs_id))
+    }
+
+    /// The fsnode entry of a path in a commit, or `None` if the path doesn't
+    /// exist.
+    async fn fsnode_entry(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        path: Option<&MPath>,
+    ) -> Result<Option<Entry<FsnodeId, FsnodeFile>>, RepoApiError> {
+        // Check that the commit exists, as deriving data for commits that
+        // don't exist fails with an internal error.
+        self.changeset_entry(ctx, cs_id).await?;
+        let root = self
+            .repo
+            .repo_derived_data()
+            .derive::<RootFsnodeId>(ctx, cs_id)
+            .await
+            .map_err(Error::from)?;
+        Ok(root
+            .fsnode_id()
+            .find_entry(
+                ctx.clone(),
+                self.repo.repo_blobstore().clone(),
+                path.cloned(),
+            )
+            .await?)
+    }
+}
+
+#[async_trait]
+impl<R> RepoApi for FacetRepoApi<R>
+where
+    R: RepoIdentityRef
+        + BookmarksRef
+        + ChangesetsRef
+        + PhasesRef
+        + RepoBlobstoreRef
+        + RepoDerivedDataRef
+        + Send
+        + Sync,
+{
+    fn name(&self) -> &str {
+        self.repo.repo_identity().name()
+    }
+
+    async fn resolve_bookmark(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+    ) -> Result<Option<ChangesetId>, RepoApiError> {
+        Ok(self.repo.bookmarks().get(ctx.clone(), bookmark).await?)
+    }
+
+    async fn list_bookmarks(
+        &self,
+        ctx: &CoreContext,
+        prefix: &BookmarkPrefix,
+        limit: u64,
+    ) -> Result<Vec<(BookmarkName, ChangesetId)>, RepoApiError> {
+        Ok(self
+            .repo
+            .bookmarks()
+            .list(
+                ctx.clone(),
+                Freshness::MaybeStale,
+                prefix,
+                BookmarkKind::ALL_PUBLISHING,
+                &BookmarkPagination::FromStart,
+                limit,
+            )
+            .map_ok(|(bookmark, cs_id)| (bookmark.name, cs_id))
+            .try_collect()
+            .await?)
+    }
+
+    async fn commit_exists(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<bool, RepoApiError> {
+        Ok(self
+            .repo
+            .changesets()
+            .get(ctx.clone(), cs_id)
+            .await?
+            .is_some())
+    }
+
+    async fn commit(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<BonsaiChangeset, RepoApiError> {
+        match cs_id.load(ctx, self.repo.repo_blobstore()).await {
+            Ok(bcs) => Ok(bcs),
+            Err(LoadableError::Missing(_)) => Err(RepoApiError::CommitNotFound(cs_id)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn parents(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<ChangesetId>, RepoApiError> {
+        Ok(self.changeset_entry(ctx, cs_id).await?.parents)
+    }
+
+    async fn is_public(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<bool, RepoApiError> {
+        Ok(self
+            .repo
+            .phases()
+            .get_public(ctx, vec![cs_id], false)
+            .await?
+            .contains(&cs_id))
+    }
+
+    async fn history(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        limit: usize,
+    ) -> Result<Vec<ChangesetId>, RepoApiError> {
+        // Visit commits in decreasing order of generation, so that every
+        // commit comes before its ancestors.
+        let start = self.changeset_entry(ctx, cs_id).await?;
+        let mut queue = BinaryHeap::from([(start.gen, start.cs_id)]);
+        let mut parents = HashMap::from([(start.cs_id, start.parents)]);
+        let mut history = Vec::new();
+        while history.len() < limit {
+            let cs_id = match queue.pop() {
+                Some((_, cs_id)) => cs_id,
+                None => break,
+            };
+            history.push(cs_id);
+            for parent in parents.get(&cs_id).cloned().unwrap_or_default() {
+                if !parents.contains_key(&parent) {
+                    let entry = self.changeset_entry(ctx, parent).await?;
+                    queue.push((entry.gen, entry.cs_id));
+                    parents.insert(entry.cs_id, entry.parents);
+                }
+            }
+        }
+        Ok(history)
+    }
+
+    async fn list_directory(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        path: Option<&MPath>,
+    ) -> Result<Vec<(MPathElement, TreeEntry)>, RepoApiError> {
+        let fsnode_id = match self.fsnode_entry(ctx, cs_id, path).await? {
+            Some(Entry::Tree(fsnode_id)) => fsnode_id,
+            Some(Entry::Leaf(_)) => {
+                return Err(RepoApiError::NotADirectory {
+                    changeset_id: cs_id,
+                    path: path.cloned(),
+                });
+            }
+            None => {
+                return Err(RepoApiError::PathNotFound {
+                    changeset_id: cs_id,
+                    path: path.cloned(),
+                });
+            }
+        };
+        let fsnode = fsnode_id.load(ctx, self.repo.repo_blobstore()).await?;
+        Ok(fsnode
+            .list()
+            .map(|(name, entry)| {
+                let entry = match entry {
+                    FsnodeEntry::File(file) => TreeEntry::File {
+                        content_id: *file.content_id(),
+                        file_type: *file.file_type(),
+                        size: file.size(),
+                    },
+                    FsnodeEntry::Directory(dir) => TreeEntry::Directory { id: *dir.id() },
+                };
+                (name.clone(), entry)
+            })
+            .collect())
+    }
+
+    async fn file_content(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        path: &MPath,
+        max_size: u64,
+    ) -> Result<Bytes, RepoApiError> {
+        let file = match self.fsnode_entry(ctx, cs_id, Some(path)).await? {
+            Some(Entry::Leaf(file)) => file,
+            Some(Entry::Tree(_)) => {
+                return Err(RepoApiError::NotAFile {
+                    changeset_id: cs_id,
+                    path: path.clone(),
+                });
+            }
+            None => {
+                return Err(RepoApiError::PathNotFound {
+                    changeset_id: cs_id,
+                    path: Some(path.clone()),
+                });
+            }
+        };
+        if file.size() > max_size {
+            return Err(RepoApiError::FileTooLarge {
+                changeset_id: cs_id,
+                path: path.clone(),
+                size: file.size(),
+                limit: max_size,
+            });
+        }
+        Ok(filestore::fetch_concat(self.repo.repo_blobstore(), ctx, *file.content_id()).await?)
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Read-only facade over a Mononoke repository.
+//!
+//! `RepoApi` answers the queries most services need — resolving bookmarks,
+//! loading commits and their history, listing directories and reading files
+//! — without the caller having to know which repo attribute or derived data
+//! type each of them is served from.  All of its methods are async, take the
+//! `CoreContext` of the request, and fail with `RepoApiError`, which tells
+//! apart the things that don't exist from internal failures.
+//!
+//! `FacetRepoApi` implements it for any repo with the attributes it reads
+//! from, such as `BlobRepo`.  Services that need more than this surface,
+//! e.g. to write to the repo, should use `mononoke_api` instead.
+
+mod errors;
+mod facet_repo_api;
+#[cfg(test)]
+mod test;
+
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkPrefix;
+use bytes::Bytes;
+use context::CoreContext;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use mononoke_types::FileType;
+use mononoke_types::FsnodeId;
+use mononoke_types::MPath;
+use mononoke_types::MPathElement;
+
+pub use crate::errors::RepoApiError;
+pub use crate::facet_repo_api::FacetRepoApi;
+
+/// An entry of a directory listed by `RepoApi::list_directory`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TreeEntry {
+    File {
+        content_id: ContentId,
+        file_type: FileType,
+        size: u64,
+    },
+    Directory {
+        id: FsnodeId,
+    },
+}
+
+/// Read operations on a repository.
+#[async_trait]
+pub trait RepoApi: Send + Sync {
+    /// The name of the repository.
+    fn name(&self) -> &str;
+
+    /// The commit a bookmark points to, or `None` if it doesn't exist.
+    async fn resolve_bookmark(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+    ) -> Result<Option<ChangesetId>, RepoApiError>;
+
+    /// The publishing bookmarks that start with a prefix, ordered by name,
+    /// with the commits they point to.  At most `limit` bookmarks are
+    /// returned.
+    async fn list_bookmarks(
+        &self,
+        ctx: &CoreContext,
+        prefix: &BookmarkPrefix,
+        limit: u64,
+    ) -> Result<Vec<(BookmarkName, ChangesetId)>, RepoApiError>;
+
+    /// Whether a commit exists in the repository.
+    async fn commit_exists(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<bool, RepoApiError>;
+
+    /// Load a commit.
+    async fn commit(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<BonsaiChangeset, RepoApiError>;
+
+    /// The parents of a commit.
+    async fn parents(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+    ) -> Result<Vec<ChangesetId>, RepoApiError>;
+
+    /// Whether a commit is public.
+    async fn is_public(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<bool, RepoApiError>;
+
+    /// The history of a commit: the commit and its ancestors, with each
+    /// commit listed before its ancestors.  At most `limit` commits are
+    /// returned.
+    async fn history(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        limit: usize,
+    ) -> Result<Vec<ChangesetId>, RepoApiError>;
+
+    /// The entries of a directory in a commit, ordered by name.  The root
+    /// directory is listed if `path` is `None`.
+    async fn list_directory(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        path: Option<&MPath>,
+    ) -> Result<Vec<(MPathElement, TreeEntry)>, RepoApiError>;
+
+    /// The content of a file in a commit.  The content is loaded into
+    /// memory, so files larger than `max_size` bytes are rejected.
+    async fn file_content(
+        &self,
+        ctx: &CoreContext,
+        cs_id: ChangesetId,
+        path: &MPath,
+        max_size: u64,
+    ) -> Result<Bytes, RepoApiError>;
+}
@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkPrefix;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types::FileType;
+use mononoke_types::MPath;
+use mononoke_types::MPathElement;
+use mononoke_types_mocks::changesetid::ONES_CSID;
+use tests_utils::bookmark;
+use tests_utils::CreateCommitContext;
+
+use crate::FacetRepoApi;
+use crate::RepoApi;
+use crate::RepoApiError;
+use crate::TreeEntry;
+
+#[fbinit::test]
+async fn test_repo_api(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+
+    let root = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("dir/file", "content")
+        .add_file("top", "top")
+        .commit()
+        .await?;
+    let child = CreateCommitContext::new(&ctx, &repo, vec![root])
+        .add_file("dir/file", "changed")
+        .commit()
+        .await?;
+    let draft = CreateCommitContext::new(&ctx, &repo, vec![child])
+        .add_file("draft", "draft")
+        .commit()
+        .await?;
+    bookmark(&ctx, &repo, "main")
+        .create_publishing(child)
+        .await?;
+
+    let api = FacetRepoApi::new(repo);
+
+    let main = BookmarkName::new("main")?;
+    assert_eq!(api.resolve_bookmark(&ctx, &main).await?, Some(child));
+    assert_eq!(
+        api.resolve_bookmark(&ctx, &BookmarkName::new("other")?)
+            .await?,
+        None
+    );
+    assert_eq!(
+        api.list_bookmarks(&ctx, &BookmarkPrefix::new("ma")?, 10)
+            .await?,
+        vec![(main, child)]
+    );
+
+    assert!(api.commit_exists(&ctx, draft).await?);
+    assert!(!api.commit_exists(&ctx, ONES_CSID).await?);
+    assert_eq!(api.commit(&ctx, child).await?.get_changeset_id(), child);
+    assert!(matches!(
+        api.commit(&ctx, ONES_CSID).await,
+        Err(RepoApiError::CommitNotFound(_))
+    ));
+    assert_eq!(api.parents(&ctx, draft).await?, vec![child]);
+    assert!(api.is_public(&ctx, root).await?);
+    assert!(!api.is_public(&ctx, draft).await?);
+
+    assert_eq!(
+        api.history(&ctx, draft, 10).await?,
+        vec![draft, child, root]
+    );
+    assert_eq!(api.history(&ctx, draft, 2).await?, vec![draft, child]);
+
+    let listing = api.list_directory(&ctx, child, None).await?;
+    assert_eq!(
+        listing
+            .iter()
+            .map(|(name, entry)| (name.clone(), matches!(entry, TreeEntry::Directory { .. })))
+            .collect::<Vec<_>>(),
+        vec![
+            (MPathElement::new(b"dir".to_vec())?, true),
+            (MPathElement::new(b"top".to_vec())?, false),
+        ]
+    );
+    let dir = MPath::new("dir")?;
+    match &api.list_directory(&ctx, child, Some(&dir)).await?[..] {
+        [
+            (
+                name,
+                TreeEntry::File {
+                    file_type, size, ..
+                },
+            ),
+        ] => {
+            assert_eq!(name, &MPathElement::new(b"file".to_vec())?);
+            assert_eq!(*file_type, FileType::Regular);
+            assert_eq!(*size, 7);
+        }
+        listing => panic!("unexpected listing of dir: {:?}", listing),
+    }
+
+    let file = MPath::new("dir/file")?;
+    assert_eq!(api.file_content(&ctx, root, &file, 1024).await?, "content");
+    assert_eq!(api.file_content(&ctx, child, &file, 1024).await?, "changed");
+    // The limit is inclusive.
+    assert_eq!(api.file_content(&ctx, child, &file, 7).await?, "changed");
+    assert!(matches!(
+        api.file_content(&ctx, child, &file, 6).await,
+        Err(RepoApiError::FileTooLarge {
+            size: 7,
+            limit: 6,
+            ..
+        })
+    ));
+    assert!(matches!(
+        api.file_content(&ctx, child, &dir, 1024).await,
+        Err(RepoApiError::NotAFile { .. })
+    ));
+    assert!(matches!(
+        api.list_directory(&ctx, child, Some(&file)).await,
+        Err(RepoApiError::NotADirectory { .. })
+    ));
+    assert!(matches!(
+        api.file_content(&ctx, root, &MPath::new("draft")?, 1024)
+            .await,
+        Err(RepoApiError::PathNotFound { .. })
+    ));
+
+    Ok(())
+}
@@ -22,6 +22,7 @@ use derived_data_manager::DerivationError;
 use derived_data_manager::DerivedDataManager;
 use derived_data_remote::DerivationClient;
 use filenodes::Filenodes;
+use hg_sha256_mapping::HgSha256Mapping;
 use metaconfig_types::DerivedDataConfig;
 use metaconfig_types::DerivedDataTypesConfig;
 use mononoke_types::ChangesetId;
@@ -123,6 +124,14 @@ impl RepoDerivedData {
         }
     }
 
+    /// Record the SHA-256 ids of the hg changesets derived for this repo.
+    pub fn with_hg_sha256_mapping(&self, hg_sha256_mapping: Arc<dyn HgSha256Mapping>) -> Self {
+        Self {
+            config: self.config.clone(),
+            manager: self.manager.with_hg_sha256_mapping(hg_sha256_mapping),
+        }
+    }
+
     pub fn with_manager(&self, manager: DerivedDataManager) -> Self {
         Self {
             config: self.config.clone(),
@@ -11,13 +11,25 @@ use anyhow::Result;
 use bookmarks::BookmarkKind;
 use bookmarks::BookmarkName;
 use context::CoreContext;
+use filenodes::FilenodeResult;
+use filenodes::FilenodesRef;
+use mercurial_types::HgFileNodeId;
+use mercurial_types::HgNodeHash;
+use metaconfig_types::Identity;
+use metaconfig_types::PrivateScratchNamespace;
 use metaconfig_types::RepoConfigRef;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
+use mononoke_types::RepoPath;
+use permission_checker::MononokeIdentity;
+use phases::PhasesRef;
+use repo_blobstore::RepoBlobstoreRef;
 use repo_bookmark_attrs::RepoBookmarkAttrsRef;
 use repo_permission_checker::RepoPermissionCheckerRef;
 
+use crate::draft_commit_owner::load_draft_commit_owner;
+use crate::draft_commit_owner::load_draft_hg_node_commit;
 use crate::error::AuthorizationError;
 use crate::error::DeniedAction;
 use crate::error::PermissionDenied;
@@ -162,10 +174,13 @@ impl AuthorizationContext {
     pub async fn check_path_read(
         &self,
         ctx: &CoreContext,
-        repo: &(impl RepoPermissionCheckerRef + AclRegionsRef),
+        repo: &(impl RepoPermissionCheckerRef + RepoConfigRef + AclRegionsRef),
         csid: ChangesetId,
         path: Option<&MPath>,
     ) -> Result<AuthorizationCheckOutcome> {
+        if self.check_protected_path_read(ctx, repo, path).is_denied() {
+            return Ok(AuthorizationCheckOutcome::Denied);
+        }
         let permitted = match self {
             AuthorizationContext::FullAccess => true,
             AuthorizationContext::Identity
@@ -192,7 +207,7 @@ impl AuthorizationContext {
     pub async fn require_path_read(
         &self,
         ctx: &CoreContext,
-        repo: &(impl RepoPermissionCheckerRef + AclRegionsRef),
+        repo: &(impl RepoPermissionCheckerRef + RepoConfigRef + AclRegionsRef),
         csid: ChangesetId,
         path: Option<&MPath>,
     ) -> Result<(), AuthorizationError> {
@@ -203,6 +218,326 @@ impl AuthorizationContext {
             })
     }
 
+    /// Check if the user may read every protected path in the repo, in which
+    /// case the repo's path read ACLs do not restrict them at all.
+    pub fn check_all_protected_paths_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+    ) -> AuthorizationCheckOutcome {
+        if let AuthorizationContext::FullAccess = self {
+            return AuthorizationCheckOutcome::Permitted;
+        }
+        let identities = ctx.metadata().identities();
+        let permitted = repo.repo_config().path_read_acls.iter().all(|acl| {
+            acl.allowed_identities
+                .iter()
+                .any(|Identity { id_type, id_data }| {
+                    identities.contains(&MononokeIdentity::new(id_type, id_data))
+                })
+        });
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
+    /// Check if the user may read a path according to the repo's path read
+    /// ACLs.
+    ///
+    /// Paths under a protected prefix may only be read by the identities the
+    /// prefix's ACL allows, regardless of any other read access the user has.
+    /// Paths that are not protected are always permitted by this check.
+    /// Denials are logged to scuba for auditing.
+    ///
+    /// The path must be bound to the data being served, e.g. because it was
+    /// found by traversing from a changeset.  For trees and files requested
+    /// by hash, use `check_protected_node_read` instead.
+    pub fn check_protected_path_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        path: Option<&MPath>,
+    ) -> AuthorizationCheckOutcome {
+        let path = match (self, path) {
+            (AuthorizationContext::FullAccess, _) | (_, None) => {
+                return AuthorizationCheckOutcome::Permitted;
+            }
+            (_, Some(path)) => path,
+        };
+        let identities = ctx.metadata().identities();
+        let denying_acl = repo.repo_config().path_read_acls.iter().find(|acl| {
+            acl.path_prefix.is_prefix_of(path)
+                && !acl
+                    .allowed_identities
+                    .iter()
+                    .any(|Identity { id_type, id_data }| {
+                        identities.contains(&MononokeIdentity::new(id_type, id_data))
+                    })
+        });
+        match denying_acl {
+            Some(acl) => {
+                let mut scuba = ctx.scuba().clone();
+                scuba.add("protected_path_prefix", acl.path_prefix.to_string());
+                scuba.add("path", path.to_string());
+                scuba.add("authorization_context", format!("{:?}", self));
+                scuba.log_with_msg("Protected path read denied", None);
+                AuthorizationCheckOutcome::Denied
+            }
+            None => AuthorizationCheckOutcome::Permitted,
+        }
+    }
+
+    /// Require that the user may read a path according to the repo's path
+    /// read ACLs.
+    pub fn require_protected_path_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        path: Option<&MPath>,
+    ) -> Result<(), AuthorizationError> {
+        self.check_protected_path_read(ctx, repo, path)
+            .permitted_or_else(|| {
+                self.permission_denied(ctx, DeniedAction::ProtectedPathRead(path.cloned()))
+            })
+    }
+
+    /// Check if the user may read an hg tree or file node, requested by hash
+    /// together with the path the client claims it is at, according to the
+    /// repo's path read ACLs.
+    ///
+    /// The hash alone does not say where the node is, so unless the user may
+    /// read every protected path, the node must be recorded in filenodes at
+    /// the claimed path (the root manifest is recorded at the root path).
+    /// Nodes that cannot be found there, including when filenodes are
+    /// disabled, are denied.
+    pub async fn check_protected_node_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + FilenodesRef),
+        path: &RepoPath,
+        node: HgFileNodeId,
+    ) -> Result<AuthorizationCheckOutcome> {
+        if self
+            .check_all_protected_paths_read(ctx, repo)
+            .is_permitted()
+        {
+            return Ok(AuthorizationCheckOutcome::Permitted);
+        }
+        if self
+            .check_protected_path_read(ctx, repo, path.mpath())
+            .is_denied()
+        {
+            return Ok(AuthorizationCheckOutcome::Denied);
+        }
+        let found = match repo.filenodes().get_filenode(ctx, path, node).await? {
+            FilenodeResult::Present(info) => info.is_some(),
+            FilenodeResult::Disabled => false,
+        };
+        if !found {
+            let mut scuba = ctx.scuba().clone();
+            scuba.add("path", path.to_string());
+            scuba.add("node", node.to_string());
+            scuba.add("authorization_context", format!("{:?}", self));
+            scuba.log_with_msg("Unverified node read denied", None);
+        }
+        Ok(AuthorizationCheckOutcome::from_permitted(found))
+    }
+
+    /// Require that the user may read an hg tree or file node, requested by
+    /// hash at a client-supplied path, according to the repo's path read
+    /// ACLs.
+    pub async fn require_protected_node_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + FilenodesRef),
+        path: &RepoPath,
+        node: HgFileNodeId,
+    ) -> Result<(), AuthorizationError> {
+        self.check_protected_node_read(ctx, repo, path, node)
+            .await?
+            .permitted_or_else(|| {
+                self.permission_denied(ctx, DeniedAction::ProtectedNodeRead(path.clone(), node))
+            })
+    }
+
+    /// Require that the user may read the files touched by a changeset
+    /// according to the repo's path read ACLs.  The file list is part of the
+    /// changeset, so changesets touching protected paths may only be read by
+    /// those who may read the paths.
+    pub fn require_protected_paths_read<'a>(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        paths: impl IntoIterator<Item = &'a MPath>,
+    ) -> Result<(), AuthorizationError> {
+        if self
+            .check_all_protected_paths_read(ctx, repo)
+            .is_permitted()
+        {
+            return Ok(());
+        }
+        paths
+            .into_iter()
+            .try_for_each(|path| self.require_protected_path_read(ctx, repo, Some(path)))
+    }
+
+    /// Check if the user may read something private to the owner of a
+    /// private scratch namespace.  Denials are logged to scuba for auditing.
+    fn check_private_scratch_read(
+        &self,
+        ctx: &CoreContext,
+        namespace: &PrivateScratchNamespace,
+        owner: &str,
+        subject: (&str, String),
+    ) -> AuthorizationCheckOutcome {
+        let identities = ctx.metadata().identities();
+        let permitted =
+            namespace
+                .permitted_identities(owner)
+                .iter()
+                .any(|Identity { id_type, id_data }| {
+                    identities.contains(&MononokeIdentity::new(id_type, id_data))
+                });
+        if !permitted {
+            let (subject_key, subject_value) = subject;
+            let mut scuba = ctx.scuba().clone();
+            scuba.add("private_scratch_owner", owner);
+            scuba.add(subject_key, subject_value);
+            scuba.add("authorization_context", format!("{:?}", self));
+            scuba.log_with_msg("Private scratch read denied", None);
+        }
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
+    /// Check if the user may read a scratch bookmark according to the repo's
+    /// private scratch namespace.
+    ///
+    /// Private scratch bookmarks may only be read by their owner and the
+    /// identities they have been shared with.  Other bookmarks are always
+    /// permitted by this check.
+    pub fn check_scratch_bookmark_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        bookmark: &BookmarkName,
+    ) -> AuthorizationCheckOutcome {
+        if let AuthorizationContext::FullAccess = self {
+            return AuthorizationCheckOutcome::Permitted;
+        }
+        let namespace = match &repo.repo_config().infinitepush.private_namespace {
+            Some(namespace) => namespace,
+            None => return AuthorizationCheckOutcome::Permitted,
+        };
+        match namespace.owner(bookmark) {
+            Some(owner) => self.check_private_scratch_read(
+                ctx,
+                namespace,
+                owner,
+                ("bookmark", bookmark.to_string()),
+            ),
+            None => AuthorizationCheckOutcome::Permitted,
+        }
+    }
+
+    /// Require that the user may read a scratch bookmark according to the
+    /// repo's private scratch namespace.
+    pub fn require_scratch_bookmark_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+        bookmark: &BookmarkName,
+    ) -> Result<(), AuthorizationError> {
+        self.check_scratch_bookmark_read(ctx, repo, bookmark)
+            .permitted_or_else(|| {
+                self.permission_denied(ctx, DeniedAction::ScratchBookmarkRead(bookmark.clone()))
+            })
+    }
+
+    /// Check if the user may read a commit according to the repo's private
+    /// scratch namespace.
+    ///
+    /// Draft commits that were pushed to a private scratch bookmark may only
+    /// be read by the bookmark's owner and the identities they have been
+    /// shared with.  Once such a commit is public, it is no longer private.
+    pub async fn check_draft_commit_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoBlobstoreRef + PhasesRef),
+        csid: ChangesetId,
+    ) -> Result<AuthorizationCheckOutcome> {
+        if let AuthorizationContext::FullAccess = self {
+            return Ok(AuthorizationCheckOutcome::Permitted);
+        }
+        let namespace = match &repo.repo_config().infinitepush.private_namespace {
+            Some(namespace) => namespace,
+            None => return Ok(AuthorizationCheckOutcome::Permitted),
+        };
+        let owner = match load_draft_commit_owner(ctx, repo, csid).await? {
+            Some(owner) => owner,
+            None => return Ok(AuthorizationCheckOutcome::Permitted),
+        };
+        let public = repo
+            .phases()
+            .get_public(ctx, vec![csid], false /* ephemeral_derive */)
+            .await?;
+        if public.contains(&csid) {
+            return Ok(AuthorizationCheckOutcome::Permitted);
+        }
+        Ok(self.check_private_scratch_read(
+            ctx,
+            namespace,
+            &owner,
+            ("changeset_id", csid.to_string()),
+        ))
+    }
+
+    /// Require that the user may read a commit according to the repo's
+    /// private scratch namespace.
+    pub async fn require_draft_commit_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoBlobstoreRef + PhasesRef),
+        csid: ChangesetId,
+    ) -> Result<(), AuthorizationError> {
+        self.check_draft_commit_read(ctx, repo, csid)
+            .await?
+            .permitted_or_else(|| self.permission_denied(ctx, DeniedAction::DraftCommitRead(csid)))
+    }
+
+    /// Check if the user may read an hg tree or file node according to the
+    /// repo's private scratch namespace.
+    ///
+    /// Nodes introduced by a draft commit that was pushed to a private
+    /// scratch bookmark may only be read by those that may read the commit.
+    pub async fn check_draft_hg_node_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoBlobstoreRef + PhasesRef),
+        node: HgNodeHash,
+    ) -> Result<AuthorizationCheckOutcome> {
+        if let AuthorizationContext::FullAccess = self {
+            return Ok(AuthorizationCheckOutcome::Permitted);
+        }
+        if repo.repo_config().infinitepush.private_namespace.is_none() {
+            return Ok(AuthorizationCheckOutcome::Permitted);
+        }
+        match load_draft_hg_node_commit(ctx, repo, node).await? {
+            Some(csid) => self.check_draft_commit_read(ctx, repo, csid).await,
+            None => Ok(AuthorizationCheckOutcome::Permitted),
+        }
+    }
+
+    /// Require that the user may read an hg tree or file node according to
+    /// the repo's private scratch namespace.
+    pub async fn require_draft_hg_node_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &(impl RepoConfigRef + RepoBlobstoreRef + PhasesRef),
+        node: HgNodeHash,
+    ) -> Result<(), AuthorizationError> {
+        self.check_draft_hg_node_read(ctx, repo, node)
+            .await?
+            .permitted_or_else(|| self.permission_denied(ctx, DeniedAction::DraftHgNodeRead(node)))
+    }
+
     /// Check whether the user has general draft access to the repo.
     ///
     /// This does not check specific paths or bookmarks, which must be checked
@@ -434,6 +769,47 @@ impl AuthorizationContext {
             .await
             .permitted_or_else(|| self.permission_denied(ctx, DeniedAction::OverrideGitMapping))
     }
+
+    /// Check whether the user is allowed to read raw blobs from the repo's
+    /// blobstore by key.
+    ///
+    /// This bypasses all other read checks, so is only permitted to the
+    /// identities listed as raw blob readers in the repo's source control
+    /// service config, whether or not they act as a service.
+    pub async fn check_raw_blob_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+    ) -> AuthorizationCheckOutcome {
+        let permitted = match self {
+            AuthorizationContext::FullAccess => true,
+            AuthorizationContext::Identity
+            | AuthorizationContext::ReadOnlyIdentity
+            | AuthorizationContext::Service(_) => {
+                let identities = ctx.metadata().identities();
+                repo.repo_config()
+                    .source_control_service
+                    .raw_blob_readers
+                    .iter()
+                    .any(|Identity { id_type, id_data }| {
+                        identities.contains(&MononokeIdentity::new(id_type, id_data))
+                    })
+            }
+        };
+        AuthorizationCheckOutcome::from_permitted(permitted)
+    }
+
+    /// Require that the user is allowed to read raw blobs from the repo's
+    /// blobstore by key.
+    pub async fn require_raw_blob_read(
+        &self,
+        ctx: &CoreContext,
+        repo: &impl RepoConfigRef,
+    ) -> Result<(), AuthorizationError> {
+        self.check_raw_blob_read(ctx, repo)
+            .await
+            .permitted_or_else(|| self.permission_denied(ctx, DeniedAction::RawBlobRead))
+    }
 }
 
 /// Write operations that can be performed on a repo.
@@ -0,0 +1,145 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Owners of commits pushed to private scratch bookmarks.
+//!
+//! When commits are first pushed to a private scratch bookmark, the user
+//! that owns the bookmark is recorded in the blobstore as the owner of the
+//! commits.  While the commits are draft, reads of them are restricted to
+//! their owner and the identities the owner has shared them with.
+//!
+//! The hg trees and file nodes that each of these commits introduces are
+//! recorded too, so that reads of them by hash can be checked against the
+//! commit that introduced them.
+//!
+//! Records are never overwritten, so that pushing another user's commit to
+//! a private scratch bookmark doesn't take it over.  The check for an
+//! existing record and the write aren't atomic, so if the same commit is
+//! first pushed to two owners' bookmarks concurrently, either owner may be
+//! recorded.
+
+use std::str::FromStr;
+
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mercurial_types::HgNodeHash;
+use mononoke_types::BlobstoreKey;
+use mononoke_types::ChangesetId;
+use repo_blobstore::RepoBlobstoreRef;
+
+/// Maximum number of owners to record concurrently.
+const RECORD_CONCURRENCY: usize = 100;
+
+fn draft_commit_owner_key(cs_id: ChangesetId) -> String {
+    format!("draft_commit_owner.{}", cs_id.blobstore_key())
+}
+
+fn draft_hg_node_commit_key(node: HgNodeHash) -> String {
+    format!("draft_hg_node_commit.{}", node)
+}
+
+/// Write each key that doesn't exist yet, leaving existing keys unchanged.
+async fn put_if_absent(
+    ctx: &CoreContext,
+    repo: &impl RepoBlobstoreRef,
+    keys: impl IntoIterator<Item = String>,
+    value: BlobstoreBytes,
+) -> Result<()> {
+    stream::iter(keys)
+        .map(Ok)
+        .try_for_each_concurrent(RECORD_CONCURRENCY, |key| {
+            let value = value.clone();
+            async move {
+                let blobstore = repo.repo_blobstore();
+                if !blobstore.is_present(ctx, &key).await?.fail_if_unsure()? {
+                    blobstore.put(ctx, key, value).await?;
+                }
+                Ok(())
+            }
+        })
+        .await
+}
+
+/// Record the owner of commits that have been pushed to a private scratch
+/// bookmark.  Commits that already have an owner keep it.
+pub async fn record_draft_commit_owner(
+    ctx: &CoreContext,
+    repo: &impl RepoBlobstoreRef,
+    owner: &str,
+    cs_ids: impl IntoIterator<Item = ChangesetId>,
+) -> Result<()> {
+    let value = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(owner.as_bytes()));
+    put_if_absent(
+        ctx,
+        repo,
+        cs_ids.into_iter().map(draft_commit_owner_key),
+        value,
+    )
+    .await
+}
+
+/// Record the hg trees and file nodes that a commit pushed to a private
+/// scratch bookmark introduced.  Nodes that were already recorded for
+/// another commit keep it.
+pub async fn record_draft_hg_nodes(
+    ctx: &CoreContext,
+    repo: &impl RepoBlobstoreRef,
+    cs_id: ChangesetId,
+    nodes: impl IntoIterator<Item = HgNodeHash>,
+) -> Result<()> {
+    let value = BlobstoreBytes::from_bytes(Bytes::from(cs_id.to_string()));
+    put_if_absent(
+        ctx,
+        repo,
+        nodes.into_iter().map(draft_hg_node_commit_key),
+        value,
+    )
+    .await
+}
+
+/// Load the owner of a commit, if it was pushed to a private scratch
+/// bookmark.
+pub async fn load_draft_commit_owner(
+    ctx: &CoreContext,
+    repo: &impl RepoBlobstoreRef,
+    cs_id: ChangesetId,
+) -> Result<Option<String>> {
+    repo.repo_blobstore()
+        .get(ctx, &draft_commit_owner_key(cs_id))
+        .await?
+        .map(|data| {
+            String::from_utf8(data.into_raw_bytes().to_vec())
+                .with_context(|| format!("Invalid owner for draft commit {}", cs_id))
+        })
+        .transpose()
+}
+
+/// Load the commit that introduced an hg tree or file node, if it was
+/// pushed to a private scratch bookmark.
+pub(crate) async fn load_draft_hg_node_commit(
+    ctx: &CoreContext,
+    repo: &impl RepoBlobstoreRef,
+    node: HgNodeHash,
+) -> Result<Option<ChangesetId>> {
+    repo.repo_blobstore()
+        .get(ctx, &draft_hg_node_commit_key(node))
+        .await?
+        .map(|data| {
+            std::str::from_utf8(data.as_raw_bytes())
+                .map_err(anyhow::Error::from)
+                .and_then(ChangesetId::from_str)
+                .with_context(|| format!("Invalid commit for draft hg node {}", node))
+        })
+        .transpose()
+}
@@ -9,8 +9,11 @@ use std::fmt;
 
 use anyhow::Error;
 use bookmarks::BookmarkName;
+use mercurial_types::HgFileNodeId;
+use mercurial_types::HgNodeHash;
 use mononoke_types::ChangesetId;
 use mononoke_types::MPath;
+use mononoke_types::RepoPath;
 use permission_checker::MononokeIdentitySet;
 use thiserror::Error;
 
@@ -23,10 +26,16 @@ pub enum DeniedAction {
     FullRepoDraft,
     RepoMetadataRead,
     PathRead(ChangesetId, Option<MPath>),
+    ProtectedPathRead(Option<MPath>),
+    ProtectedNodeRead(RepoPath, HgFileNodeId),
+    ScratchBookmarkRead(BookmarkName),
+    DraftCommitRead(ChangesetId),
+    DraftHgNodeRead(HgNodeHash),
     RepoWrite(RepoWriteOperation),
     PathWrite(MPath),
     BookmarkModification(BookmarkName),
     OverrideGitMapping,
+    RawBlobRead,
 }
 
 impl fmt::Display for DeniedAction {
@@ -43,12 +52,31 @@ impl fmt::Display for DeniedAction {
                 "Repo read access for path '{}' in changeset {}",
                 path, csid
             ),
+            DeniedAction::ProtectedPathRead(None) => f.write_str("Repo read access for root"),
+            DeniedAction::ProtectedPathRead(Some(path)) => {
+                write!(f, "Repo read access for protected path '{}'", path)
+            }
+            DeniedAction::ProtectedNodeRead(path, node) => write!(
+                f,
+                "Repo read access for node {} at protected path '{}'",
+                node, path
+            ),
+            DeniedAction::ScratchBookmarkRead(bookmark) => {
+                write!(f, "Read access for private scratch bookmark '{}'", bookmark)
+            }
+            DeniedAction::DraftCommitRead(csid) => {
+                write!(f, "Read access for private draft commit {}", csid)
+            }
+            DeniedAction::DraftHgNodeRead(node) => {
+                write!(f, "Read access for private draft hg node {}", node)
+            }
             DeniedAction::RepoWrite(op) => write!(f, "Repo write access for {:?}", op),
             DeniedAction::PathWrite(path) => write!(f, "Repo write access to path '{}'", path),
             DeniedAction::BookmarkModification(bookmark) => {
                 write!(f, "Modification of bookmark '{}'", bookmark)
             }
             DeniedAction::OverrideGitMapping => f.write_str("Overriding of Git mapping"),
+            DeniedAction::RawBlobRead => f.write_str("Raw blobstore read access"),
         }
     }
 }
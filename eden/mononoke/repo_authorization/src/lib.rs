@@ -6,10 +6,14 @@
  */
 
 mod context;
+mod draft_commit_owner;
 mod error;
 #[cfg(test)]
 mod tests;
 
 pub use crate::context::AuthorizationContext;
 pub use crate::context::RepoWriteOperation;
+pub use crate::draft_commit_owner::load_draft_commit_owner;
+pub use crate::draft_commit_owner::record_draft_commit_owner;
+pub use crate::draft_commit_owner::record_draft_hg_nodes;
 pub use crate::error::AuthorizationError;
@@ -15,18 +15,44 @@ use bookmarks::BookmarkName;
 use context::CoreContext;
 use context::SessionContainer;
 use fbinit::FacebookInit;
+use filenodes::FilenodeInfo;
+use filenodes::Filenodes;
+use filenodes::FilenodesRef;
+use filenodes::PreparedFilenode;
 use futures::FutureExt;
+use maplit::btreeset;
 use maplit::hashmap;
 use maplit::hashset;
+use mercurial_types::HgChangesetId;
+use mercurial_types_mocks::nodehash::ONES_FNID;
+use mercurial_types_mocks::nodehash::ONES_HASH;
+use mercurial_types_mocks::nodehash::THREES_FNID;
+use mercurial_types_mocks::nodehash::TWOS_FNID;
+use mercurial_types_mocks::nodehash::TWOS_HASH;
+use metaconfig_types::Identity;
+use metaconfig_types::PathReadAcl;
+use metaconfig_types::PrivateScratchNamespace;
 use metaconfig_types::RepoConfig;
 use metaconfig_types::ServiceWriteRestrictions;
+use metadata::Metadata;
+use mononoke_types::MPath;
 use mononoke_types::PrefixTrie;
+use mononoke_types::RepoPath;
+use mononoke_types_mocks::changesetid::ONES_CSID;
+use mononoke_types_mocks::changesetid::TWOS_CSID;
+use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
+use phases::Phases;
+use regex::Regex;
+use repo_blobstore::RepoBlobstore;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
+use repo_identity::RepoIdentity;
 use repo_permission_checker::RepoPermissionChecker;
 use tunables::with_tunables_async;
 use tunables::MononokeTunables;
 
+use crate::record_draft_commit_owner;
+use crate::record_draft_hg_nodes;
 use crate::AuthorizationContext;
 use crate::RepoWriteOperation;
 
@@ -40,6 +66,18 @@ struct Repo {
 
     #[facet]
     repo_bookmark_attrs: RepoBookmarkAttrs,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    phases: dyn Phases,
+
+    #[facet]
+    repo_identity: RepoIdentity,
+
+    #[facet]
+    filenodes: dyn Filenodes,
 }
 
 #[fbinit::test]
@@ -445,3 +483,297 @@ async fn test_user_readonly_instance(fb: FacebookInit) -> () {
             .is_err()
     );
 }
+
+#[fbinit::test]
+async fn test_raw_blob_read(fb: FacebookInit) -> Result<()> {
+    let inspector = MononokeIdentity::new("USER", "inspector");
+    let metadata = Metadata::default().set_identities(btreeset! { inspector });
+    let inspector_ctx = CoreContext::test_mock_session(
+        SessionContainer::builder(fb)
+            .metadata(Arc::new(metadata))
+            .build(),
+    );
+    let ctx = CoreContext::test_mock(fb);
+    let checker = Arc::new(TestPermissionChecker {
+        read: true,
+        write: true,
+        service_writes: hashmap! {
+            String::from("inspector") => true,
+        },
+        ..Default::default()
+    });
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_permission_checker(checker)
+        .with_config_override(|config| {
+            config.source_control_service.raw_blob_readers = vec![Identity {
+                id_type: String::from("USER"),
+                id_data: String::from("inspector"),
+            }];
+            config.source_control_service.service_write_restrictions = hashmap! {
+                String::from("inspector") =>
+                ServiceWriteRestrictions {
+                    permitted_methods: hashset! { String::from("repo_blob_fetch") },
+                    ..Default::default()
+                },
+            };
+        })
+        .build()?;
+
+    AuthorizationContext::new_bypass_access_control()
+        .require_raw_blob_read(&ctx, &repo)
+        .await?;
+    AuthorizationContext::new(&inspector_ctx)
+        .require_raw_blob_read(&inspector_ctx, &repo)
+        .await?;
+
+    // Write access, or acting as a service that may write, does not give
+    // raw blob read access.
+    assert!(
+        AuthorizationContext::new(&ctx)
+            .require_raw_blob_read(&ctx, &repo)
+            .await
+            .is_err()
+    );
+    assert!(
+        AuthorizationContext::new_for_service_writes("inspector")
+            .require_raw_blob_read(&ctx, &repo)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_protected_path_read(fb: FacebookInit) -> Result<()> {
+    let alice = MononokeIdentity::new("USER", "alice");
+    let metadata = Metadata::default().set_identities(btreeset! { alice });
+    let alice_ctx = CoreContext::test_mock_session(
+        SessionContainer::builder(fb)
+            .metadata(Arc::new(metadata))
+            .build(),
+    );
+    let ctx = CoreContext::test_mock(fb);
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.path_read_acls = vec![PathReadAcl {
+                path_prefix: MPath::new("secret").unwrap(),
+                allowed_identities: vec![Identity {
+                    id_type: String::from("USER"),
+                    id_data: String::from("alice"),
+                }],
+            }];
+        })
+        .build()?;
+
+    let secret = MPath::new("secret/file")?;
+    let public = MPath::new("public/file")?;
+
+    let authz = AuthorizationContext::new(&ctx);
+    authz.require_protected_path_read(&ctx, &repo, None)?;
+    authz.require_protected_path_read(&ctx, &repo, Some(&public))?;
+    assert!(
+        authz
+            .require_protected_path_read(&ctx, &repo, Some(&secret))
+            .is_err()
+    );
+
+    AuthorizationContext::new(&alice_ctx).require_protected_path_read(
+        &alice_ctx,
+        &repo,
+        Some(&secret),
+    )?;
+    AuthorizationContext::new_bypass_access_control().require_protected_path_read(
+        &ctx,
+        &repo,
+        Some(&secret),
+    )?;
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_protected_node_read(fb: FacebookInit) -> Result<()> {
+    let alice = MononokeIdentity::new("USER", "alice");
+    let metadata = Metadata::default().set_identities(btreeset! { alice });
+    let alice_ctx = CoreContext::test_mock_session(
+        SessionContainer::builder(fb)
+            .metadata(Arc::new(metadata))
+            .build(),
+    );
+    let ctx = CoreContext::test_mock(fb);
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.path_read_acls = vec![PathReadAcl {
+                path_prefix: MPath::new("secret").unwrap(),
+                allowed_identities: vec![Identity {
+                    id_type: String::from("USER"),
+                    id_data: String::from("alice"),
+                }],
+            }];
+        })
+        .build()?;
+
+    let root = RepoPath::RootPath;
+    let public = RepoPath::file("public/file")?;
+    let secret = RepoPath::file("secret/file")?;
+    let filenodes = [
+        (root.clone(), THREES_FNID),
+        (public.clone(), ONES_FNID),
+        (secret.clone(), TWOS_FNID),
+    ];
+    repo.filenodes()
+        .add_filenodes(
+            &ctx,
+            filenodes
+                .into_iter()
+                .map(|(path, filenode)| PreparedFilenode {
+                    path,
+                    info: FilenodeInfo {
+                        filenode,
+                        p1: None,
+                        p2: None,
+                        copyfrom: None,
+                        linknode: HgChangesetId::new(ONES_HASH),
+                    },
+                })
+                .collect(),
+        )
+        .await?
+        .do_not_handle_disabled_filenodes()?;
+
+    let authz = AuthorizationContext::new(&ctx);
+    authz
+        .require_protected_node_read(&ctx, &repo, &root, THREES_FNID)
+        .await?;
+    authz
+        .require_protected_node_read(&ctx, &repo, &public, ONES_FNID)
+        .await?;
+    assert!(
+        authz
+            .require_protected_node_read(&ctx, &repo, &secret, TWOS_FNID)
+            .await
+            .is_err()
+    );
+    // The client can't read the protected node by claiming it is somewhere
+    // else, including at the root.
+    for path in [&public, &root] {
+        assert!(
+            authz
+                .require_protected_node_read(&ctx, &repo, path, TWOS_FNID)
+                .await
+                .is_err()
+        );
+    }
+
+    AuthorizationContext::new(&alice_ctx)
+        .require_protected_node_read(&alice_ctx, &repo, &public, TWOS_FNID)
+        .await?;
+
+    assert!(
+        authz
+            .require_protected_paths_read(&ctx, &repo, [&MPath::new("secret/file")?])
+            .is_err()
+    );
+    authz.require_protected_paths_read(&ctx, &repo, [&MPath::new("public/file")?])?;
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_private_scratch_read(fb: FacebookInit) -> Result<()> {
+    let user_ctx = |user: &str| {
+        let metadata =
+            Metadata::default().set_identities(btreeset! { MononokeIdentity::new("USER", user) });
+        CoreContext::test_mock_session(
+            SessionContainer::builder(fb)
+                .metadata(Arc::new(metadata))
+                .build(),
+        )
+    };
+    let alice_ctx = user_ctx("alice");
+    let bob_ctx = user_ctx("bob");
+    let carol_ctx = user_ctx("carol");
+    let ctx = CoreContext::test_mock(fb);
+    let repo: Repo = test_repo_factory::TestRepoFactory::new(fb)?
+        .with_config_override(|config| {
+            config.infinitepush.private_namespace = Some(PrivateScratchNamespace {
+                pattern: Regex::new("scratch/(?P<user>[^/]+)/.+").unwrap().into(),
+                owner_identity_type: String::from("USER"),
+                readers: vec![],
+                shared_with: hashmap! {
+                    String::from("alice") => vec![Identity {
+                        id_type: String::from("USER"),
+                        id_data: String::from("bob"),
+                    }],
+                },
+            });
+        })
+        .build()?;
+
+    let private = BookmarkName::new("scratch/alice/feature")?;
+    let shared = BookmarkName::new("scratch/feature")?;
+    for user_ctx in [&alice_ctx, &bob_ctx, &carol_ctx] {
+        AuthorizationContext::new(user_ctx)
+            .require_scratch_bookmark_read(user_ctx, &repo, &shared)?;
+    }
+    for user_ctx in [&alice_ctx, &bob_ctx] {
+        AuthorizationContext::new(user_ctx)
+            .require_scratch_bookmark_read(user_ctx, &repo, &private)?;
+    }
+    assert!(
+        AuthorizationContext::new(&carol_ctx)
+            .require_scratch_bookmark_read(&carol_ctx, &repo, &private)
+            .is_err()
+    );
+
+    record_draft_commit_owner(&ctx, &repo, "alice", vec![ONES_CSID]).await?;
+    for user_ctx in [&alice_ctx, &bob_ctx, &carol_ctx] {
+        AuthorizationContext::new(user_ctx)
+            .require_draft_commit_read(user_ctx, &repo, TWOS_CSID)
+            .await?;
+    }
+    for user_ctx in [&alice_ctx, &bob_ctx] {
+        AuthorizationContext::new(user_ctx)
+            .require_draft_commit_read(user_ctx, &repo, ONES_CSID)
+            .await?;
+    }
+    assert!(
+        AuthorizationContext::new(&carol_ctx)
+            .require_draft_commit_read(&carol_ctx, &repo, ONES_CSID)
+            .await
+            .is_err()
+    );
+    AuthorizationContext::new_bypass_access_control()
+        .require_draft_commit_read(&carol_ctx, &repo, ONES_CSID)
+        .await?;
+
+    // Pushing the commit to another owner's bookmark doesn't take it over.
+    record_draft_commit_owner(&ctx, &repo, "carol", vec![ONES_CSID]).await?;
+    assert!(
+        AuthorizationContext::new(&carol_ctx)
+            .require_draft_commit_read(&carol_ctx, &repo, ONES_CSID)
+            .await
+            .is_err()
+    );
+
+    // Trees and files introduced by the commit are as private as the commit.
+    record_draft_hg_nodes(&ctx, &repo, ONES_CSID, vec![ONES_HASH]).await?;
+    record_draft_hg_nodes(&ctx, &repo, TWOS_CSID, vec![ONES_HASH]).await?;
+    for user_ctx in [&alice_ctx, &bob_ctx] {
+        AuthorizationContext::new(user_ctx)
+            .require_draft_hg_node_read(user_ctx, &repo, ONES_HASH)
+            .await?;
+    }
+    assert!(
+        AuthorizationContext::new(&carol_ctx)
+            .require_draft_hg_node_read(&carol_ctx, &repo, ONES_HASH)
+            .await
+            .is_err()
+    );
+    AuthorizationContext::new(&carol_ctx)
+        .require_draft_hg_node_read(&carol_ctx, &repo, TWOS_HASH)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Error;
+use changeset_fetcher::ArcChangesetFetcher;
+use changesets::ChangesetEntry;
+use changesets::Changesets;
+use context::CoreContext;
+use futures_stats::TimedTryFutureExt;
+use mononoke_types::ChangesetId;
+use mononoke_types::Generation;
+use reachabilityindex::LeastCommonAncestorsHint;
+use reachabilityindex::NodeFrontier;
+
+use crate::warn_expensive_getbundle;
+use crate::Params;
+use crate::GETBUNDLE_COMMIT_NUM_WARN;
+
+/// Changesets ordered by generation number.
+type Frontier = BTreeMap<Generation, HashSet<ChangesetId>>;
+
+fn frontier_from(nodes: Vec<(ChangesetId, Generation)>) -> Frontier {
+    let mut frontier = Frontier::new();
+    for (cs_id, gen) in nodes {
+        frontier.entry(gen).or_default().insert(cs_id);
+    }
+    frontier
+}
+
+#[derive(Debug, Default)]
+struct TraversalStats {
+    /// Number of generations of the heads that were walked.
+    generations: u64,
+    /// Number of batches of changesets fetched while walking the heads.
+    changeset_batches: u64,
+}
+
+/// Fetch the entries of all of `cs_ids` that aren't in `entries` yet, in a
+/// single batch.
+async fn fetch_entries(
+    ctx: &CoreContext,
+    changesets: &dyn Changesets,
+    entries: &mut HashMap<ChangesetId, ChangesetEntry>,
+    cs_ids: impl IntoIterator<Item = ChangesetId>,
+    stats: &mut TraversalStats,
+) -> Result<(), Error> {
+    let missing = cs_ids
+        .into_iter()
+        .filter(|cs_id| !entries.contains_key(cs_id))
+        .collect::<HashSet<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    stats.changeset_batches += 1;
+    for entry in changesets
+        .get_many(ctx.clone(), missing.into_iter().collect())
+        .await?
+    {
+        entries.insert(entry.cs_id, entry);
+    }
+    Ok(())
+}
+
+async fn difference(
+    ctx: &CoreContext,
+    changesets: &dyn Changesets,
+    changeset_fetcher: &ArcChangesetFetcher,
+    lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+    params: Params,
+) -> Result<(Vec<ChangesetId>, TraversalStats), Error> {
+    let Params { heads, excludes } = params;
+    let mut heads = frontier_from(heads);
+    let mut excludes = excludes.into_iter().collect::<NodeFrontier>();
+
+    let mut stats = TraversalStats::default();
+    let mut nodes = Vec::new();
+    // Entries of the changesets in `heads`.  Fetching the entries of the
+    // parents of a generation gives both their generation numbers and their
+    // own parents, so each generation needs a single batch.
+    let mut entries = HashMap::new();
+    let mut notified_expensive_getbundle = false;
+    while let Some(gen) = heads.keys().next_back().copied() {
+        let all_cs_ids = heads.remove(&gen).unwrap_or_default();
+        stats.generations += 1;
+        if !excludes.is_empty() {
+            excludes = lca_hint
+                .lca_hint(ctx, changeset_fetcher, excludes, gen)
+                .await?;
+        }
+        let mut cs_ids = match excludes.get(&gen) {
+            Some(excluded) => all_cs_ids
+                .iter()
+                .filter(|cs_id| !excluded.contains(cs_id))
+                .copied()
+                .collect::<Vec<_>>(),
+            None => all_cs_ids.iter().copied().collect::<Vec<_>>(),
+        };
+        // Keep the order stable between runs.
+        cs_ids.sort();
+        nodes.extend(cs_ids.iter().copied());
+
+        // Only the entries of the initial heads haven't been fetched yet.
+        fetch_entries(
+            ctx,
+            changesets,
+            &mut entries,
+            cs_ids.iter().copied(),
+            &mut stats,
+        )
+        .await?;
+        let mut parents = Vec::new();
+        for cs_id in &cs_ids {
+            let entry = entries
+                .get(cs_id)
+                .ok_or_else(|| anyhow!("changeset {} not found", cs_id))?;
+            parents.extend(entry.parents.iter().copied());
+        }
+        for cs_id in &all_cs_ids {
+            entries.remove(cs_id);
+        }
+        fetch_entries(
+            ctx,
+            changesets,
+            &mut entries,
+            parents.iter().copied(),
+            &mut stats,
+        )
+        .await?;
+        for parent in parents {
+            let entry = entries
+                .get(&parent)
+                .ok_or_else(|| anyhow!("changeset {} not found", parent))?;
+            heads
+                .entry(Generation::new(entry.gen))
+                .or_default()
+                .insert(parent);
+        }
+
+        if nodes.len() as u64 > GETBUNDLE_COMMIT_NUM_WARN && !notified_expensive_getbundle {
+            notified_expensive_getbundle = true;
+            warn_expensive_getbundle(ctx);
+        }
+    }
+    Ok((nodes, stats))
+}
+
+/// Find the ancestors of `params.heads` that are not ancestors of
+/// `params.excludes`, in descending order of generation number.
+///
+/// The heads are walked one generation at a time, starting from the highest
+/// generation, and the parents of each generation are fetched in a single
+/// batch.  The excludes are moved down to each generation of the heads using
+/// the skip edges of `lca_hint`, so when the client is only a few commits
+/// behind, only those few generations are fetched, and the excludes take a
+/// few skips to catch up however far apart they are.
+pub(crate) async fn generation_aware_difference(
+    ctx: &CoreContext,
+    changesets: &dyn Changesets,
+    changeset_fetcher: &ArcChangesetFetcher,
+    lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
+    params: Params,
+) -> Result<Vec<ChangesetId>, Error> {
+    let mut scuba = ctx.scuba().clone();
+    scuba.add_opt("heads_signature", params.heads_signature().ok());
+    scuba.add_opt("excludes_signature", params.excludes_signature().ok());
+    scuba.add("heads_count", params.heads.len());
+    scuba.add("excludes_count", params.excludes.len());
+
+    let (stats, (nodes, traversal_stats)) =
+        difference(ctx, changesets, changeset_fetcher, lca_hint, params)
+            .try_timed()
+            .await?;
+
+    scuba.add_future_stats(&stats);
+    scuba.add("generations_traversed", traversal_stats.generations);
+    scuba.add("changeset_batches", traversal_stats.changeset_batches);
+    scuba.log_with_msg("generation_aware_difference", None);
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    use async_trait::async_trait;
+    use blobrepo::BlobRepo;
+    use changeset_fetcher::ChangesetFetcher;
+    use changesets::ChangesetsRef;
+    use fbinit::FacebookInit;
+    use futures::compat::Stream01CompatExt;
+    use futures::future::try_join_all;
+    use futures::future::TryFutureExt;
+    use futures::stream::TryStreamExt;
+    use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+    use skiplist::SkiplistIndex;
+    use tests_utils::drawdag::create_from_dag;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+
+    /// Counts the calls made to the inner changeset fetcher.
+    struct CountingChangesetFetcher {
+        inner: ArcChangesetFetcher,
+        calls: AtomicU64,
+    }
+
+    #[async_trait]
+    impl ChangesetFetcher for CountingChangesetFetcher {
+        async fn get_generation_number(
+            &self,
+            ctx: CoreContext,
+            cs_id: ChangesetId,
+        ) -> Result<Generation, Error> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_generation_number(ctx, cs_id).await
+        }
+
+        async fn get_parents(
+            &self,
+            ctx: CoreContext,
+            cs_id: ChangesetId,
+        ) -> Result<Vec<ChangesetId>, Error> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_parents(ctx, cs_id).await
+        }
+    }
+
+    async fn with_generations(
+        ctx: &CoreContext,
+        changeset_fetcher: &ArcChangesetFetcher,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<Vec<(ChangesetId, Generation)>, Error> {
+        try_join_all(cs_ids.into_iter().map(|cs_id| {
+            changeset_fetcher
+                .get_generation_number(ctx.clone(), cs_id)
+                .map_ok(move |gen| (cs_id, gen))
+        }))
+        .await
+    }
+
+    #[fbinit::test]
+    async fn test_generation_aware_difference(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+        let changeset_fetcher = repo.get_changeset_fetcher();
+
+        let commits = create_from_dag(
+            &ctx,
+            &repo,
+            r##"
+                A-B-C-D-E-F-G-H
+                   \     /     \
+                    I-J-K       L-M
+                         \
+                          N-O
+            "##,
+        )
+        .await?;
+
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["H"], &["G"]),
+            (&["H"], &["F"]),
+            (&["M"], &["H"]),
+            (&["M", "O"], &["D"]),
+            (&["O"], &["K"]),
+            (&["M"], &["O"]),
+            (&["H", "O"], &[]),
+            (&["E"], &["H"]),
+        ];
+
+        // Both without skip edges, and with all the commits indexed.
+        let unindexed: Arc<dyn LeastCommonAncestorsHint> = Arc::new(SkiplistIndex::new());
+        let indexed = SkiplistIndex::new();
+        for name in ["H", "M", "O"] {
+            indexed
+                .add_node(&ctx, &changeset_fetcher, commits[name], 100)
+                .await?;
+        }
+        let indexed: Arc<dyn LeastCommonAncestorsHint> = Arc::new(indexed);
+
+        for ((heads, excludes), lca_hint) in cases
+            .iter()
+            .flat_map(|case| [(case, &unindexed), (case, &indexed)])
+        {
+            let heads = heads.iter().map(|name| commits[*name]).collect();
+            let excludes = excludes.iter().map(|name| commits[*name]).collect();
+            let params = Params {
+                heads: with_generations(&ctx, &changeset_fetcher, heads).await?,
+                excludes: with_generations(&ctx, &changeset_fetcher, excludes).await?,
+            };
+
+            let mut expected: Vec<_> =
+                DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes_gen_num(
+                    ctx.clone(),
+                    &changeset_fetcher,
+                    Arc::new(SkiplistIndex::new()),
+                    params.heads.clone(),
+                    params.excludes.clone(),
+                )
+                .compat()
+                .try_collect()
+                .await?;
+            let mut actual = generation_aware_difference(
+                &ctx,
+                repo.changesets(),
+                &changeset_fetcher,
+                lca_hint,
+                params.clone(),
+            )
+            .await?;
+
+            // The result must be in descending generation order.
+            let generations = with_generations(&ctx, &changeset_fetcher, actual.clone()).await?;
+            assert!(generations.windows(2).all(|w| w[0].1 >= w[1].1));
+
+            expected.sort();
+            actual.sort();
+            assert_eq!(
+                actual, expected,
+                "heads {:?}, excludes {:?}",
+                params.heads, params.excludes
+            );
+        }
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_generation_aware_difference_round_trips(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+
+        // A long history, and a branch off its third commit.
+        let mut main = Vec::new();
+        for i in 0..100 {
+            let parents: Vec<ChangesetId> = main.last().copied().into_iter().collect();
+            let cs_id = CreateCommitContext::new(&ctx, &repo, parents)
+                .add_file("file", format!("{}", i))
+                .commit()
+                .await?;
+            main.push(cs_id);
+        }
+        let branch = CreateCommitContext::new(&ctx, &repo, vec![main[2]])
+            .add_file("branch", "branch")
+            .commit()
+            .await?;
+
+        let changeset_fetcher = Arc::new(CountingChangesetFetcher {
+            inner: repo.get_changeset_fetcher(),
+            calls: AtomicU64::new(0),
+        });
+        let skiplist = SkiplistIndex::new();
+        skiplist
+            .add_node(&ctx, &repo.get_changeset_fetcher(), main[99], 1000)
+            .await?;
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = Arc::new(skiplist);
+
+        // The client has all of the history, and fetches the branch.
+        let params = Params {
+            heads: vec![(branch, Generation::new(4))],
+            excludes: vec![(main[99], Generation::new(100))],
+        };
+        let fetcher: ArcChangesetFetcher = changeset_fetcher.clone();
+        let (nodes, stats) =
+            difference(&ctx, repo.changesets(), &fetcher, &lca_hint, params).await?;
+        assert_eq!(nodes, vec![branch]);
+
+        // The branch and its parent, which is excluded, are fetched in one
+        // batch each.
+        assert_eq!(stats.generations, 2);
+        assert_eq!(stats.changeset_batches, 2);
+
+        // Walking the excludes down one generation at a time would take two
+        // calls for each of the 96 generations.  Skipping takes a handful.
+        let calls = changeset_fetcher.calls.load(Ordering::Relaxed);
+        assert!(calls < 20, "{} changeset fetcher calls", calls);
+        Ok(())
+    }
+}
@@ -88,7 +88,9 @@ use tunables::tunables;
 use crate::errors::ErrorKind;
 
 mod errors;
+mod generation_traversal;
 mod low_gen_nums_optimization;
+use generation_traversal::generation_aware_difference;
 use low_gen_nums_optimization::compute_partial_getbundle;
 use low_gen_nums_optimization::low_gen_num_optimization;
 use low_gen_nums_optimization::LowGenNumChecker;
@@ -115,6 +117,10 @@ pub struct SessionLfsParams {
     pub threshold: Option<u64>,
 }
 
+/// Check run on the files touched by each changeset before it is sent, as
+/// the file list is part of the changeset.
+pub type ChangesetFilesCheck = Arc<dyn Fn(&[MPath]) -> Result<()> + Send + Sync>;
+
 pub async fn create_getbundle_response(
     ctx: &CoreContext,
     blobrepo: &BlobRepo,
@@ -123,6 +129,7 @@ pub async fn create_getbundle_response(
     lca_hint: &Arc<dyn LeastCommonAncestorsHint>,
     return_phases: PhasesPart,
     lfs_params: &SessionLfsParams,
+    files_check: Option<ChangesetFilesCheck>,
 ) -> Result<Vec<PartEncodeBuilder>, Error> {
     let return_phases = return_phases == PhasesPart::Yes;
     debug!(ctx.logger(), "Return phases is: {:?}", return_phases);
@@ -144,8 +151,14 @@ pub async fn create_getbundle_response(
     if heads_len != 0 {
         // no heads means bookmark-only pushrebase, and the client
         // does not expect a changegroup part in this case
-        let cg_part =
-            create_hg_changeset_part(ctx, blobrepo, commits_to_send.clone(), lfs_params).await?;
+        let cg_part = create_hg_changeset_part(
+            ctx,
+            blobrepo,
+            commits_to_send.clone(),
+            lfs_params,
+            files_check,
+        )
+        .await?;
         parts.push(cg_part);
 
         if !draft_commits.is_empty() && tunables().get_mutation_generate_for_draft() {
@@ -249,15 +262,26 @@ pub async fn find_commits_to_send(
     let nodes_to_send = if !tunables().get_getbundle_use_low_gen_optimization()
         || !low_gen_num_checker.is_low_gen_num(lowest_head_gen_num)
     {
-        call_difference_of_union_of_ancestors_revset(
-            ctx,
-            &changeset_fetcher,
-            params,
-            lca_hint,
-            None,
-        )
-        .await?
-        .ok_or_else(|| anyhow!(UNEXPECTED_NONE_ERR_MSG))?
+        if tunables().get_getbundle_use_generation_aware_traversal() {
+            generation_aware_difference(
+                ctx,
+                blobrepo.changesets(),
+                &changeset_fetcher,
+                lca_hint,
+                params,
+            )
+            .await?
+        } else {
+            call_difference_of_union_of_ancestors_revset(
+                ctx,
+                &changeset_fetcher,
+                params,
+                lca_hint,
+                None,
+            )
+            .await?
+            .ok_or_else(|| anyhow!(UNEXPECTED_NONE_ERR_MSG))?
+        }
     } else {
         ctx.scuba()
             .clone()
@@ -447,6 +471,7 @@ async fn create_hg_changeset_part(
     blobrepo: &BlobRepo,
     nodes_to_send: Vec<ChangesetId>,
     lfs_params: &SessionLfsParams,
+    files_check: Option<ChangesetFilesCheck>,
 ) -> Result<PartEncodeBuilder> {
     let map_chunk_size = 100;
     let load_buffer_size = 1000;
@@ -499,26 +524,32 @@ async fn create_hg_changeset_part(
             }
         })
         .buffered(load_buffer_size)
-        .and_then(|(hg_cs_id, cs)| async move {
-            let node = hg_cs_id.into_nodehash();
-
-            let revlogcs = RevlogChangeset::new_from_parts(
-                cs.parents(),
-                cs.manifestid(),
-                cs.user().into(),
-                cs.time().clone(),
-                cs.extra().clone(),
-                cs.files().into(),
-                cs.message().into(),
-            );
-
-            let mut v = Vec::new();
-            mercurial_revlog::changeset::serialize_cs(&revlogcs, &mut v)?;
-
-            Ok((
-                node,
-                HgBlobNode::new(Bytes::from(v), revlogcs.p1(), revlogcs.p2()),
-            ))
+        .and_then(move |(hg_cs_id, cs)| {
+            cloned!(files_check);
+            async move {
+                if let Some(files_check) = files_check {
+                    files_check(cs.files())?;
+                }
+                let node = hg_cs_id.into_nodehash();
+
+                let revlogcs = RevlogChangeset::new_from_parts(
+                    cs.parents(),
+                    cs.manifestid(),
+                    cs.user().into(),
+                    cs.time().clone(),
+                    cs.extra().clone(),
+                    cs.files().into(),
+                    cs.message().into(),
+                );
+
+                let mut v = Vec::new();
+                mercurial_revlog::changeset::serialize_cs(&revlogcs, &mut v)?;
+
+                Ok((
+                    node,
+                    HgBlobNode::new(Bytes::from(v), revlogcs.p1(), revlogcs.p2()),
+                ))
+            }
         })
         .boxed()
         .compat();
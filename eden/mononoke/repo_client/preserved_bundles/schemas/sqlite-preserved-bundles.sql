@@ -0,0 +1,33 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `preserved_bundles` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `bundle_id` BINARY(32) NOT NULL,
+  `size` BIGINT NOT NULL,
+  `session_id` VARBINARY(255) NOT NULL,
+  `user` VARBINARY(255) NULL,
+  `created_at` BIGINT NOT NULL,
+  `error` BLOB NULL
+);
+
+CREATE INDEX IF NOT EXISTS `preserved_bundles_session_id`
+  ON `preserved_bundles` (`repo_id`, `session_id`);
+
+CREATE INDEX IF NOT EXISTS `preserved_bundles_user`
+  ON `preserved_bundles` (`repo_id`, `user`, `id`);
+
+CREATE TABLE IF NOT EXISTS `preserved_bundle_changesets` (
+  `repo_id` INTEGER NOT NULL,
+  `preserved_bundle_id` INTEGER NOT NULL,
+  `cs_id` BINARY(32) NOT NULL,
+  PRIMARY KEY (`repo_id`, `preserved_bundle_id`, `cs_id`)
+);
+
+CREATE INDEX IF NOT EXISTS `preserved_bundle_changesets_cs_id`
+  ON `preserved_bundle_changesets` (`repo_id`, `cs_id`);
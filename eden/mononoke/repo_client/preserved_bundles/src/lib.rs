@@ -0,0 +1,485 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Raw bundles of pushes, preserved for later inspection.
+//!
+//! When enabled for a repo, the bundle2 sent by a client for a push is
+//! stored verbatim in the blobstore and indexed by the session that sent it,
+//! the user that pushed it and the changesets it contained, along with the
+//! error the push failed with, if any.  This makes it possible to find the
+//! bundle of a problematic push after the fact, download it and replay it.
+
+use std::collections::HashMap;
+
+use anyhow::format_err;
+use anyhow::Error;
+use blobstore::Blobstore;
+use blobstore::Loadable;
+use blobstore::Storable;
+use bytes::Bytes;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::hash::Blake2;
+use mononoke_types::BlobstoreValue;
+use mononoke_types::ChangesetId;
+use mononoke_types::RawBundle2;
+use mononoke_types::RawBundle2Id;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+#[facet::facet]
+pub struct PreservedBundles {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+pub struct PreservedBundlesBuilder {
+    connections: SqlConnections,
+}
+
+/// A preserved push bundle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PreservedBundle {
+    pub id: u64,
+    /// The raw bundle2 in the blobstore.
+    pub bundle_id: RawBundle2Id,
+    pub size: u64,
+    /// The session that sent the push.
+    pub session_id: String,
+    /// The user that sent the push, if known.
+    pub user: Option<String>,
+    pub created_at: Timestamp,
+    /// The error the push failed with, if it failed.
+    pub error: Option<String>,
+    /// The changesets that the push uploaded.
+    pub changesets: Vec<ChangesetId>,
+}
+
+/// Which preserved bundles to list.
+#[derive(Clone, Debug)]
+pub enum PreservedBundleFilter {
+    All,
+    SessionId(String),
+    User(String),
+    Changeset(ChangesetId),
+}
+
+type PreservedBundleRow = (
+    u64,
+    Blake2,
+    u64,
+    Vec<u8>,
+    Option<Vec<u8>>,
+    Timestamp,
+    Option<Vec<u8>>,
+);
+
+mononoke_queries! {
+    write InsertPreservedBundle(
+        repo_id: RepositoryId,
+        bundle_id: Blake2,
+        size: u64,
+        session_id: &str,
+        user: Option<&str>,
+        created_at: Timestamp,
+        error: Option<&str>,
+    ) {
+        none,
+        "INSERT INTO preserved_bundles
+         (repo_id, bundle_id, size, session_id, user, created_at, error)
+         VALUES ({repo_id}, {bundle_id}, {size}, {session_id}, {user}, {created_at}, {error})"
+    }
+
+    write InsertPreservedBundleChangesets(values: (
+        repo_id: RepositoryId,
+        preserved_bundle_id: u64,
+        cs_id: ChangesetId,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO preserved_bundle_changesets
+         (repo_id, preserved_bundle_id, cs_id)
+         VALUES {values}"
+    }
+
+    read SelectPreservedBundleById(repo_id: RepositoryId, id: u64) -> (
+        u64, Blake2, u64, Vec<u8>, Option<Vec<u8>>, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, bundle_id, size, session_id, user, created_at, error
+         FROM preserved_bundles
+         WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    read SelectPreservedBundles(repo_id: RepositoryId, limit: u64) -> (
+        u64, Blake2, u64, Vec<u8>, Option<Vec<u8>>, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, bundle_id, size, session_id, user, created_at, error
+         FROM preserved_bundles
+         WHERE repo_id = {repo_id}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+
+    read SelectPreservedBundlesBySession(repo_id: RepositoryId, session_id: &str, limit: u64) -> (
+        u64, Blake2, u64, Vec<u8>, Option<Vec<u8>>, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, bundle_id, size, session_id, user, created_at, error
+         FROM preserved_bundles
+         WHERE repo_id = {repo_id} AND session_id = {session_id}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+
+    read SelectPreservedBundlesByUser(repo_id: RepositoryId, user: &str, limit: u64) -> (
+        u64, Blake2, u64, Vec<u8>, Option<Vec<u8>>, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT id, bundle_id, size, session_id, user, created_at, error
+         FROM preserved_bundles
+         WHERE repo_id = {repo_id} AND user = {user}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+
+    read SelectPreservedBundlesByChangeset(repo_id: RepositoryId, cs_id: ChangesetId, limit: u64) -> (
+        u64, Blake2, u64, Vec<u8>, Option<Vec<u8>>, Timestamp, Option<Vec<u8>>
+    ) {
+        "SELECT b.id, b.bundle_id, b.size, b.session_id, b.user, b.created_at, b.error
+         FROM preserved_bundle_changesets c
+         JOIN preserved_bundles b ON b.id = c.preserved_bundle_id
+         WHERE c.repo_id = {repo_id} AND c.cs_id = {cs_id}
+         ORDER BY b.id DESC
+         LIMIT {limit}"
+    }
+
+    read SelectPreservedBundleChangesets(repo_id: RepositoryId, >list ids: u64) -> (
+        u64, ChangesetId
+    ) {
+        "SELECT preserved_bundle_id, cs_id
+         FROM preserved_bundle_changesets
+         WHERE repo_id = {repo_id} AND preserved_bundle_id IN {ids}"
+    }
+}
+
+impl SqlConstruct for PreservedBundlesBuilder {
+    const LABEL: &'static str = "preserved-bundles";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-preserved-bundles.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for PreservedBundlesBuilder {}
+
+impl PreservedBundlesBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> PreservedBundles {
+        PreservedBundles {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl PreservedBundles {
+    /// Store the raw bundle of a push in the blobstore and index it under
+    /// the session and user of `ctx`.  Returns the id of the preserved
+    /// bundle.
+    pub async fn preserve(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &impl Blobstore,
+        raw_bundle2: Bytes,
+        changesets: &[ChangesetId],
+        error: Option<&str>,
+    ) -> Result<u64, Error> {
+        let size = raw_bundle2.len() as u64;
+        let bundle_id = RawBundle2::new_bytes(raw_bundle2)
+            .into_blob()
+            .store(ctx, blobstore)
+            .await?;
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        let metadata = ctx.metadata();
+        let txn = self
+            .connections
+            .write_connection
+            .start_transaction()
+            .await?;
+        let (txn, res) = InsertPreservedBundle::query_with_transaction(
+            txn,
+            &self.repo_id,
+            bundle_id.blake2(),
+            &size,
+            &metadata.session_id().as_str(),
+            &metadata.unix_name(),
+            &Timestamp::from(ctx.now()),
+            &error,
+        )
+        .await?;
+        let id = res
+            .last_insert_id()
+            .ok_or_else(|| format_err!("failed to index preserved bundle {}", bundle_id))?;
+
+        let txn = if changesets.is_empty() {
+            txn
+        } else {
+            let rows = changesets
+                .iter()
+                .map(|cs_id| (&self.repo_id, &id, cs_id))
+                .collect::<Vec<_>>();
+            let (txn, _) =
+                InsertPreservedBundleChangesets::query_with_transaction(txn, &rows).await?;
+            txn
+        };
+        txn.commit().await?;
+
+        Ok(id)
+    }
+
+    /// Get a preserved bundle by id.
+    pub async fn get(&self, ctx: &CoreContext, id: u64) -> Result<Option<PreservedBundle>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let rows =
+            SelectPreservedBundleById::query(&self.connections.read_connection, &self.repo_id, &id)
+                .await?;
+        Ok(self.with_changesets(ctx, rows).await?.into_iter().next())
+    }
+
+    /// The most recent preserved bundles matching a filter, newest first.
+    pub async fn list(
+        &self,
+        ctx: &CoreContext,
+        filter: &PreservedBundleFilter,
+        limit: u64,
+    ) -> Result<Vec<PreservedBundle>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let conn = &self.connections.read_connection;
+        let rows = match filter {
+            PreservedBundleFilter::All => {
+                SelectPreservedBundles::query(conn, &self.repo_id, &limit).await?
+            }
+            PreservedBundleFilter::SessionId(session_id) => {
+                SelectPreservedBundlesBySession::query(
+                    conn,
+                    &self.repo_id,
+                    &session_id.as_str(),
+                    &limit,
+                )
+                .await?
+            }
+            PreservedBundleFilter::User(user) => {
+                SelectPreservedBundlesByUser::query(conn, &self.repo_id, &user.as_str(), &limit)
+                    .await?
+            }
+            PreservedBundleFilter::Changeset(cs_id) => {
+                SelectPreservedBundlesByChangeset::query(conn, &self.repo_id, cs_id, &limit).await?
+            }
+        };
+        self.with_changesets(ctx, rows).await
+    }
+
+    /// Fetch the raw bundle of a preserved bundle from the blobstore.
+    pub async fn fetch(
+        &self,
+        ctx: &CoreContext,
+        blobstore: &impl Blobstore,
+        bundle: &PreservedBundle,
+    ) -> Result<Bytes, Error> {
+        Ok(bundle.bundle_id.load(ctx, blobstore).await?.into_bytes())
+    }
+
+    async fn with_changesets(
+        &self,
+        ctx: &CoreContext,
+        rows: Vec<PreservedBundleRow>,
+    ) -> Result<Vec<PreservedBundle>, Error> {
+        if rows.is_empty() {
+            return Ok(vec![]);
+        }
+
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let ids = rows.iter().map(|row| row.0).collect::<Vec<_>>();
+        let mut changesets: HashMap<u64, Vec<ChangesetId>> = HashMap::new();
+        for (id, cs_id) in SelectPreservedBundleChangesets::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &ids[..],
+        )
+        .await?
+        {
+            changesets.entry(id).or_default().push(cs_id);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, bundle_id, size, session_id, user, created_at, error)| PreservedBundle {
+                    id,
+                    bundle_id: RawBundle2Id::new(bundle_id),
+                    size,
+                    session_id: utf8(session_id),
+                    user: user.map(utf8),
+                    created_at,
+                    error: error.map(utf8),
+                    changesets: changesets.remove(&id).unwrap_or_default(),
+                },
+            )
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_preserve_and_fetch(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let bundles = PreservedBundlesBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        let first = bundles
+            .preserve(
+                &ctx,
+                &blobstore,
+                Bytes::from("HG20first"),
+                &[ONES_CSID, TWOS_CSID],
+                None,
+            )
+            .await?;
+        let second = bundles
+            .preserve(
+                &ctx,
+                &blobstore,
+                Bytes::from("HG20second"),
+                &[THREES_CSID],
+                Some("hook failed"),
+            )
+            .await?;
+
+        let bundle = bundles.get(&ctx, first).await?.expect("bundle exists");
+        assert_eq!(bundle.size, 9);
+        assert_eq!(bundle.error, None);
+        assert_eq!(
+            bundle.session_id,
+            ctx.metadata().session_id().as_str().to_string()
+        );
+        let mut changesets = bundle.changesets.clone();
+        changesets.sort();
+        assert_eq!(changesets, vec![ONES_CSID, TWOS_CSID]);
+        assert_eq!(
+            bundles.fetch(&ctx, &blobstore, &bundle).await?,
+            Bytes::from("HG20first")
+        );
+
+        let all = bundles.list(&ctx, &PreservedBundleFilter::All, 10).await?;
+        assert_eq!(
+            all.iter().map(|b| b.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(all[0].error.as_deref(), Some("hook failed"));
+        assert_eq!(
+            bundles
+                .list(&ctx, &PreservedBundleFilter::All, 1)
+                .await?
+                .len(),
+            1
+        );
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_filters(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let bundles = PreservedBundlesBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+        let first = bundles
+            .preserve(&ctx, &blobstore, Bytes::from("a"), &[ONES_CSID], None)
+            .await?;
+        let second = bundles
+            .preserve(&ctx, &blobstore, Bytes::from("b"), &[TWOS_CSID], None)
+            .await?;
+
+        let by_changeset = bundles
+            .list(&ctx, &PreservedBundleFilter::Changeset(TWOS_CSID), 10)
+            .await?;
+        assert_eq!(
+            by_changeset.iter().map(|b| b.id).collect::<Vec<_>>(),
+            vec![second]
+        );
+
+        let session_id = ctx.metadata().session_id().as_str().to_string();
+        let by_session = bundles
+            .list(&ctx, &PreservedBundleFilter::SessionId(session_id), 10)
+            .await?;
+        assert_eq!(
+            by_session.iter().map(|b| b.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+
+        let by_session = bundles
+            .list(
+                &ctx,
+                &PreservedBundleFilter::SessionId("unknown".to_string()),
+                10,
+            )
+            .await?;
+        assert!(by_session.is_empty());
+
+        let by_user = bundles
+            .list(&ctx, &PreservedBundleFilter::User("nobody".to_string()), 10)
+            .await?;
+        assert!(by_user.is_empty());
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let blobstore = Memblob::default();
+        let zero = PreservedBundlesBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        let id = zero
+            .preserve(&ctx, &blobstore, Bytes::from("a"), &[ONES_CSID], None)
+            .await?;
+        let one = PreservedBundles {
+            connections: zero.connections.clone(),
+            repo_id: REPO_ONE,
+        };
+        assert!(one.get(&ctx, id).await?.is_none());
+        assert!(
+            one.list(&ctx, &PreservedBundleFilter::Changeset(ONES_CSID), 10)
+                .await?
+                .is_empty()
+        );
+        Ok(())
+    }
+}
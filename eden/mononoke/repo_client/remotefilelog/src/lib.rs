@@ -433,6 +433,7 @@ mod test {
                 config.filestore = Some(FilestoreParams {
                     chunk_size: 1,
                     concurrency: 1,
+                    content_defined_chunking: false,
                 })
             })
             .build()?;
@@ -0,0 +1,223 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Snapshot bundles of the whole repo, for cloning.
+//!
+//! A snapshot bundle contains everything a fresh clone would fetch from the
+//! server at the time the snapshot was taken.  It is stored in the filestore,
+//! so it is immutable and can be downloaded from the LFS server, or a cache
+//! in front of it, rather than generated by the server for each clone.
+//! Clients are pointed at the latest snapshot by the `clonebundles` wire
+//! protocol command.  After applying it, they pull the commits made since the
+//! snapshot from the server as usual, which is a small incremental pull as
+//! long as snapshots are taken regularly.
+
+use anyhow::Error;
+use bytes::Bytes;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::hash::Blake2;
+use mononoke_types::ContentId;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// Bundle specification of snapshot bundles, as understood by Mercurial's
+/// clone bundles extension: an uncompressed bundle2.
+pub const SNAPSHOT_BUNDLE_SPEC: &str = "none-v2";
+
+#[facet::facet]
+pub struct SnapshotBundles {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+pub struct SnapshotBundlesBuilder {
+    connections: SqlConnections,
+}
+
+/// A snapshot bundle of the repo.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SnapshotBundle {
+    pub id: u64,
+    /// The contents of the bundle in the filestore.
+    pub content_id: ContentId,
+    pub size: u64,
+    /// Where clients download the bundle from.
+    pub url: String,
+    pub created_at: Timestamp,
+}
+
+mononoke_queries! {
+    write InsertSnapshotBundle(values: (
+        repo_id: RepositoryId,
+        content_id: Blake2,
+        size: u64,
+        url: &str,
+        created_at: Timestamp,
+    )) {
+        none,
+        "INSERT INTO snapshot_bundles (repo_id, content_id, size, url, created_at) VALUES {values}"
+    }
+
+    read SelectSnapshotBundles(repo_id: RepositoryId, limit: u64)
+        -> (u64, Blake2, u64, Vec<u8>, Timestamp) {
+        "SELECT id, content_id, size, url, created_at
+         FROM snapshot_bundles
+         WHERE repo_id = {repo_id}
+         ORDER BY id DESC
+         LIMIT {limit}"
+    }
+}
+
+impl SqlConstruct for SnapshotBundlesBuilder {
+    const LABEL: &'static str = "snapshot-bundles";
+
+    const CREATION_QUERY: &'static str = "
+        CREATE TABLE IF NOT EXISTS `snapshot_bundles` (
+        `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+        `repo_id` int(11) NOT NULL,
+        `content_id` binary(32) NOT NULL,
+        `size` bigint(20) NOT NULL,
+        `url` varbinary(4096) NOT NULL,
+        `created_at` bigint(20) NOT NULL
+        )
+    ";
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SnapshotBundlesBuilder {}
+
+impl SnapshotBundlesBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SnapshotBundles {
+        SnapshotBundles {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+impl SnapshotBundles {
+    /// Record a new snapshot bundle, which becomes the one clients are
+    /// pointed at.
+    pub async fn add(
+        &self,
+        ctx: &CoreContext,
+        content_id: ContentId,
+        size: u64,
+        url: &str,
+    ) -> Result<(), Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+
+        InsertSnapshotBundle::query(
+            &self.connections.write_connection,
+            &[(
+                &self.repo_id,
+                content_id.blake2(),
+                &size,
+                &url,
+                &Timestamp::from(ctx.now()),
+            )],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The most recent snapshot bundles, newest first.
+    pub async fn list(&self, ctx: &CoreContext, limit: u64) -> Result<Vec<SnapshotBundle>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+
+        let rows =
+            SelectSnapshotBundles::query(&self.connections.read_connection, &self.repo_id, &limit)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, content_id, size, url, created_at)| SnapshotBundle {
+                id,
+                content_id: ContentId::new(content_id),
+                size,
+                url: String::from_utf8_lossy(&url).into_owned(),
+                created_at,
+            })
+            .collect())
+    }
+
+    /// The latest snapshot bundle, if there is one.
+    pub async fn latest(&self, ctx: &CoreContext) -> Result<Option<SnapshotBundle>, Error> {
+        Ok(self.list(ctx, 1).await?.into_iter().next())
+    }
+
+    /// The clone bundles manifest that points clients at the latest
+    /// snapshot bundle.  It is empty if there are no snapshot bundles, in
+    /// which case clients clone from the server.
+    pub async fn clonebundles_manifest(&self, ctx: &CoreContext) -> Result<Bytes, Error> {
+        Ok(match self.latest(ctx).await? {
+            Some(bundle) => Bytes::from(format!(
+                "{} BUNDLESPEC={}\n",
+                bundle.url, SNAPSHOT_BUNDLE_SPEC
+            )),
+            None => Bytes::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::contentid::ONES_CTID;
+    use mononoke_types_mocks::contentid::TWOS_CTID;
+    use mononoke_types_mocks::repo::REPO_ONE;
+    use mononoke_types_mocks::repo::REPO_ZERO;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_latest_snapshot(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let snapshots = SnapshotBundlesBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        assert_eq!(snapshots.clonebundles_manifest(&ctx).await?, Bytes::new());
+
+        snapshots
+            .add(&ctx, ONES_CTID, 100, "https://example.com/one")
+            .await?;
+        snapshots
+            .add(&ctx, TWOS_CTID, 200, "https://example.com/two")
+            .await?;
+
+        let latest = snapshots.latest(&ctx).await?.expect("snapshot exists");
+        assert_eq!(latest.content_id, TWOS_CTID);
+        assert_eq!(latest.size, 200);
+        assert_eq!(
+            snapshots.clonebundles_manifest(&ctx).await?,
+            Bytes::from("https://example.com/two BUNDLESPEC=none-v2\n")
+        );
+        assert_eq!(snapshots.list(&ctx, 10).await?.len(), 2);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        let zero = SnapshotBundlesBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+        zero.add(&ctx, ONES_CTID, 100, "https://example.com/one")
+            .await?;
+        let one = SnapshotBundles {
+            connections: zero.connections.clone(),
+            repo_id: REPO_ONE,
+        };
+        assert!(one.latest(&ctx).await?.is_none());
+        Ok(())
+    }
+}
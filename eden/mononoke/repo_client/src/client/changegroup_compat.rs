@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Changegroup version negotiation for `getbundle` responses.
+//!
+//! Repos with LFS enabled serve changegroup version 03, which can carry LFS
+//! pointers and revlog flags.  Older clients only understand version 02, and
+//! fail to apply a version 03 changegroup.  For these clients the response
+//! is downgraded on the fly: file contents are sent inline rather than as
+//! LFS pointers, and no revlog flags are sent.  Repos can be configured to
+//! refuse these clients instead.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use context::CoreContext;
+use getbundle_response::SessionLfsParams;
+use metaconfig_types::RepoClientKnobs;
+use slog::warn;
+use stats::prelude::*;
+
+use crate::errors::ErrorKind;
+
+/// Bundle2 capability clients use to advertise which changegroup versions
+/// they support.
+pub(crate) const CHANGEGROUP_CAP: &str = "changegroup";
+
+/// Changegroup version served by repos with LFS enabled.
+const CG3_VERSION: &str = "03";
+
+define_stats! {
+    prefix = "mononoke.repo_client.changegroup_compat";
+    downgraded: dynamic_timeseries("downgraded.{}", (reponame: String); Rate, Sum),
+    rejected: dynamic_timeseries("rejected.{}", (reponame: String); Rate, Sum),
+}
+
+/// Work out the LFS parameters to use for a `getbundle` response, given the
+/// changegroup versions the client supports.
+///
+/// Clients that don't advertise the versions they support are assumed to
+/// support all of them.
+pub(crate) fn negotiate_changegroup_lfs_params(
+    ctx: &CoreContext,
+    repo_name: &str,
+    knobs: &RepoClientKnobs,
+    lfs_params: SessionLfsParams,
+    client_versions: Option<&HashSet<String>>,
+) -> Result<SessionLfsParams> {
+    let supports_cg3 = client_versions.map_or(true, |versions| versions.contains(CG3_VERSION));
+    if supports_cg3 || lfs_params.threshold.is_none() {
+        return Ok(lfs_params);
+    }
+
+    let mut client_versions = client_versions
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    client_versions.sort();
+    let client_versions = client_versions.join(",");
+
+    if knobs.reject_legacy_changegroup_clients {
+        STATS::rejected.add_value(1, (repo_name.to_string(),));
+        ctx.scuba().clone().log_with_msg(
+            "Rejected legacy changegroup client",
+            Some(client_versions.clone()),
+        );
+        return Err(ErrorKind::UnsupportedChangegroupVersions(client_versions).into());
+    }
+
+    STATS::downgraded.add_value(1, (repo_name.to_string(),));
+    ctx.scuba().clone().log_with_msg(
+        "Downgraded changegroup for legacy client",
+        Some(client_versions),
+    );
+    warn!(
+        ctx.logger(),
+        "Your client does not support changegroup version {}, so file contents are sent \
+        without LFS. Please upgrade your client.", CG3_VERSION;
+        "remote" => "true"
+    );
+
+    Ok(SessionLfsParams { threshold: None })
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use maplit::hashset;
+
+    use super::*;
+
+    #[fbinit::test]
+    fn test_negotiate_changegroup_lfs_params(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut knobs = RepoClientKnobs::default();
+        let lfs = || SessionLfsParams {
+            threshold: Some(100),
+        };
+        let no_lfs = || SessionLfsParams { threshold: None };
+        let modern = hashset! {"02".to_string(), "03".to_string()};
+        let legacy = hashset! {"01".to_string(), "02".to_string()};
+
+        let negotiate = |knobs: &RepoClientKnobs, lfs_params, versions| {
+            negotiate_changegroup_lfs_params(&ctx, "repo", knobs, lfs_params, versions)
+                .map(|params| params.threshold)
+        };
+
+        assert_eq!(negotiate(&knobs, lfs(), Some(&modern))?, Some(100));
+        assert_eq!(negotiate(&knobs, lfs(), None)?, Some(100));
+        assert_eq!(negotiate(&knobs, no_lfs(), Some(&legacy))?, None);
+        assert_eq!(negotiate(&knobs, lfs(), Some(&legacy))?, None);
+
+        knobs.reject_legacy_changegroup_clients = true;
+        assert_eq!(negotiate(&knobs, lfs(), Some(&modern))?, Some(100));
+        assert_eq!(negotiate(&knobs, no_lfs(), Some(&legacy))?, None);
+        assert!(negotiate(&knobs, lfs(), Some(&legacy)).is_err());
+
+        Ok(())
+    }
+}
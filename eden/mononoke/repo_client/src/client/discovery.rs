@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sampling of the commit graph for the `discoverysample` command.
+//!
+//! The sample for each head is taken along its first-parent chain at
+//! distances 1, 2, 4, 8, ..., so a client that has fallen far behind can
+//! find the range its common ancestors lie in from a single sample, however
+//! far behind it is.
+
+use std::collections::HashSet;
+
+use anyhow::Error;
+use anyhow::Result;
+use bounded_traversal::bounded_traversal_stream;
+use changeset_fetcher::ArcChangesetFetcher;
+use context::CoreContext;
+use futures::future;
+use futures::future::FutureExt;
+use futures::stream::TryStreamExt;
+use mononoke_types::ChangesetId;
+
+/// Number of sample nodes returned when the client doesn't ask for a size.
+pub const DISCOVERY_SAMPLE_DEFAULT_SIZE: u64 = 100;
+
+/// Maximum number of sample nodes returned.
+pub const DISCOVERY_SAMPLE_MAX_SIZE: u64 = 1000;
+
+/// Samples are not taken further than this from a head, which bounds the
+/// number of parents walked for each head.
+const MAX_SAMPLE_DISTANCE: u64 = 1 << 15;
+
+const HEAD_CONCURRENCY: usize = 10;
+
+/// The distances from each head at which ancestors are sampled, when `size`
+/// samples are shared between `num_heads` heads.
+fn sample_distances(num_heads: usize, size: u64) -> Vec<u64> {
+    if num_heads == 0 {
+        return Vec::new();
+    }
+    let per_head = (size as usize + num_heads - 1) / num_heads;
+    std::iter::successors(Some(1u64), |distance| Some(distance * 2))
+        .take_while(|distance| *distance <= MAX_SAMPLE_DISTANCE)
+        .take(per_head)
+        .collect()
+}
+
+/// Sample the first-parent ancestors of `heads`.  At most `size` distinct
+/// ancestors are returned, and none of them are heads.
+pub async fn sample_ancestors(
+    ctx: &CoreContext,
+    changeset_fetcher: &ArcChangesetFetcher,
+    heads: &[ChangesetId],
+    size: u64,
+) -> Result<Vec<ChangesetId>> {
+    let distances = sample_distances(heads.len(), size);
+    let per_head = sample_heads(ctx, changeset_fetcher, heads, &distances).await?;
+
+    // Interleave the samples of each head, closest first, so that when the
+    // sample is truncated every head keeps its closest ancestors.
+    let mut seen: HashSet<_> = heads.iter().copied().collect();
+    let mut sample = Vec::new();
+    for index in 0..distances.len() {
+        for head_sample in &per_head {
+            if let Some(cs_id) = head_sample.get(index) {
+                if seen.insert(*cs_id) {
+                    sample.push(*cs_id);
+                }
+            }
+        }
+    }
+    sample.truncate(size as usize);
+    Ok(sample)
+}
+
+/// Walk the first-parent chains of `heads`, returning for each head the
+/// ancestors at each of `distances` that exist.  The chains are walked
+/// together, with at most `HEAD_CONCURRENCY` parent fetches in flight.
+async fn sample_heads(
+    ctx: &CoreContext,
+    changeset_fetcher: &ArcChangesetFetcher,
+    heads: &[ChangesetId],
+    distances: &[u64],
+) -> Result<Vec<Vec<ChangesetId>>> {
+    let max_distance = distances.last().copied().unwrap_or(0);
+    let mut per_head = vec![Vec::with_capacity(distances.len()); heads.len()];
+
+    // Each chain is walked one parent at a time, so the ancestors of each
+    // head are found in order of distance.
+    let mut samples = bounded_traversal_stream(
+        HEAD_CONCURRENCY,
+        heads
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(index, head)| (index, head, 0)),
+        move |(index, cs_id, distance)| {
+            async move {
+                let sampled = distances
+                    .binary_search(&distance)
+                    .ok()
+                    .map(|_| (index, cs_id));
+                let next = if distance < max_distance {
+                    changeset_fetcher
+                        .get_parents(ctx.clone(), cs_id)
+                        .await?
+                        .first()
+                        .map(|parent| (index, *parent, distance + 1))
+                } else {
+                    None
+                };
+                Ok::<_, Error>((sampled, next))
+            }
+            .boxed()
+        },
+    )
+    .try_filter_map(future::ok);
+
+    while let Some((index, cs_id)) = samples.try_next().await? {
+        per_head[index].push(cs_id);
+    }
+    Ok(per_head)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_distances() {
+        assert_eq!(sample_distances(0, 100), Vec::<u64>::new());
+        assert_eq!(sample_distances(1, 5), vec![1, 2, 4, 8, 16]);
+        assert_eq!(sample_distances(3, 7), vec![1, 2, 4]);
+        assert_eq!(
+            sample_distances(1, 1000).last().copied(),
+            Some(MAX_SAMPLE_DISTANCE)
+        );
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Cache invalidation hints for `getbundle` responses.
+//!
+//! Without hints, clients have to assume that any of their caches may be
+//! out of date after a pull.  Clients that advertise the `invalidationhints`
+//! bundle2 capability are sent an extra advisory part that lists what has
+//! changed, so they only need to invalidate those caches.
+//!
+//! Each hint is one of:
+//!
+//! * `listkeys:<namespace>` with a signature of the keys in that namespace.
+//!   The client only needs to invalidate caches derived from the namespace
+//!   (e.g. bookmarks) if the signature differs from the one it was sent
+//!   last time.
+//!
+//! * `public` with the hash of a commit the client has in common with the
+//!   server that is public.  The phase boundary has moved past this commit,
+//!   so any draft commits the client has among its ancestors are now public.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use blobrepo_hg::BlobRepoHg;
+use context::CoreContext;
+use mercurial_types::HgChangesetId;
+use mononoke_types::hash::Context as HashContext;
+use phases::PhasesRef;
+
+use crate::client::listkeys::ListKeys;
+
+/// Bundle2 capability clients advertise to be sent invalidation hints.
+pub(crate) const INVALIDATION_HINTS_CAP: &str = "invalidationhints";
+
+/// Key used to compute the signature of a `listkeys` namespace.
+const LISTKEYS_SIGNATURE_KEY: &[u8] = b"listkeys-signature";
+
+/// Compute a signature for the keys of a `listkeys` namespace that doesn't
+/// depend on the order they were listed in.
+fn listkeys_signature(keys: &ListKeys) -> String {
+    let sorted: BTreeMap<_, _> = keys.iter().collect();
+    let mut context = HashContext::new(LISTKEYS_SIGNATURE_KEY);
+    for (key, value) in sorted {
+        context.update((key.len() as u64).to_be_bytes());
+        context.update(key);
+        context.update((value.len() as u64).to_be_bytes());
+        context.update(value);
+    }
+    context.finish().to_hex().to_string()
+}
+
+/// Find the invalidation hints for a `getbundle` response, given the
+/// commits the client has in common with the server and the `listkeys`
+/// namespaces included in the response.
+pub(crate) async fn find_invalidation_hints(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    common: &[HgChangesetId],
+    listkeys: &[(String, ListKeys)],
+) -> Result<Vec<(String, String)>> {
+    let mut hints = listkeys
+        .iter()
+        .map(|(namespace, keys)| (format!("listkeys:{}", namespace), listkeys_signature(keys)))
+        .collect::<Vec<_>>();
+
+    let common = repo
+        .get_hg_bonsai_mapping(ctx.clone(), common.to_vec())
+        .await?;
+    let public = repo
+        .phases()
+        .get_public(
+            ctx,
+            common.iter().map(|(_, cs_id)| *cs_id).collect(),
+            false, /* ephemeral_derive */
+        )
+        .await?;
+    let mut public_common = common
+        .into_iter()
+        .filter(|(_, cs_id)| public.contains(cs_id))
+        .map(|(hg_cs_id, _)| hg_cs_id)
+        .collect::<Vec<_>>();
+    public_common.sort();
+    hints.extend(
+        public_common
+            .into_iter()
+            .map(|hg_cs_id| ("public".to_string(), hg_cs_id.to_string())),
+    );
+
+    Ok(hints)
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn test_listkeys_signature() {
+        let keys = hashmap! {
+            b"a".to_vec() => b"1".to_vec(),
+            b"b".to_vec() => b"2".to_vec(),
+        };
+        let same_keys = hashmap! {
+            b"b".to_vec() => b"2".to_vec(),
+            b"a".to_vec() => b"1".to_vec(),
+        };
+        let moved_keys = hashmap! {
+            b"a".to_vec() => b"1".to_vec(),
+            b"b".to_vec() => b"3".to_vec(),
+        };
+        let ambiguous_keys = hashmap! {
+            b"a1".to_vec() => b"".to_vec(),
+            b"b".to_vec() => b"2".to_vec(),
+        };
+        assert_eq!(listkeys_signature(&keys), listkeys_signature(&same_keys));
+        assert_ne!(listkeys_signature(&keys), listkeys_signature(&moved_keys));
+        assert_ne!(
+            listkeys_signature(&keys),
+            listkeys_signature(&ambiguous_keys)
+        );
+        assert_ne!(listkeys_signature(&keys), listkeys_signature(&hashmap! {}));
+    }
+}
@@ -0,0 +1,476 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Providers for the namespaces served by the `listkeys` command.
+//!
+//! Each namespace is served by a `ListKeysProvider` registered with a
+//! `ListKeysRegistry`.  Namespaces with no provider return no keys, which is
+//! how Mercurial servers respond to namespaces they don't know about.
+//!
+//! Providers list keys in batches, applying a `ListKeysFilter` where the keys
+//! are stored, so that large namespaces aren't loaded into memory all at once
+//! just to be filtered.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Result;
+use bookmarks_types::BookmarkKind;
+use bookmarks_types::BookmarkPrefix;
+use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use futures::TryFutureExt;
+use futures::TryStreamExt;
+use metaconfig_types::InfinitepushNamespace;
+use mononoke_api::Repo;
+use repo_authorization::AuthorizationContext;
+use stats::prelude::*;
+use tunables::tunables;
+
+use crate::client::session_bookmarks_cache::SessionBookmarkCache;
+
+define_stats! {
+    prefix = "mononoke.repo_client.listkeys";
+    namespace_size: dynamic_histogram("{}.size", (namespace: String); 1_000, 0, 100_000, Average, Sum, Count; P 50; P 95; P 99),
+    namespace_batches: dynamic_histogram("{}.batches", (namespace: String); 1, 0, 100, Average, Sum, Count; P 50; P 95; P 99),
+    namespace_too_large: dynamic_timeseries("{}.too_large", (namespace: String); Rate, Sum),
+}
+
+/// Number of keys fetched from the store at a time if not set by tunable.
+const DEFAULT_BATCH_SIZE: u64 = 1_000;
+
+/// Maximum number of keys listed for a namespace if not set by tunable.
+const DEFAULT_MAX_KEYS: u64 = 100_000;
+
+fn batch_size() -> u64 {
+    match tunables().get_listkeys_batch_size() {
+        size if size > 0 => size as u64,
+        _ => DEFAULT_BATCH_SIZE,
+    }
+}
+
+fn max_keys() -> u64 {
+    match tunables().get_listkeys_max_keys() {
+        max if max > 0 => max as u64,
+        _ => DEFAULT_MAX_KEYS,
+    }
+}
+
+/// The keys listed for a namespace, as raw bytes.
+pub type ListKeys = HashMap<Vec<u8>, Vec<u8>>;
+
+/// A batch of the keys listed for a namespace.
+pub type ListKeysBatch = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Which keys of a namespace to list.
+#[derive(Clone, Debug, Default)]
+pub struct ListKeysFilter {
+    /// Only list keys that start with this prefix.
+    pub prefix: String,
+    /// List at most this many keys.
+    pub limit: Option<u64>,
+}
+
+impl ListKeysFilter {
+    /// Apply the filter to keys that have already been loaded.
+    pub fn apply(&self, keys: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) -> ListKeysBatch {
+        let matching = keys
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(self.prefix.as_bytes()));
+        match self.limit {
+            Some(limit) => matching
+                .take(limit.try_into().unwrap_or(usize::MAX))
+                .collect(),
+            None => matching.collect(),
+        }
+    }
+}
+
+/// Provides the keys of a single `listkeys` namespace.
+pub trait ListKeysProvider: Send + Sync {
+    /// List the keys of the namespace that match the filter, in batches.
+    fn list_keys_batched<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        filter: ListKeysFilter,
+    ) -> BoxStream<'a, Result<ListKeysBatch>>;
+}
+
+/// List the keys of a namespace that match the filter, in batches, and
+/// record the size of the namespace once it has been listed.
+///
+/// Fails once the namespace has listed more keys than the
+/// `listkeys_max_keys` tunable allows, rather than building an unbounded
+/// response.
+pub fn list_namespace<'a>(
+    ctx: &'a CoreContext,
+    namespace: String,
+    provider: &'a dyn ListKeysProvider,
+    filter: ListKeysFilter,
+) -> BoxStream<'a, Result<ListKeysBatch>> {
+    let max_keys = max_keys();
+    let batches = provider.list_keys_batched(ctx, filter);
+    stream::try_unfold(
+        (batches, namespace, 0, 0),
+        move |(mut batches, namespace, key_count, batch_count)| async move {
+            let batch = match batches.try_next().await? {
+                Some(batch) => batch,
+                None => {
+                    STATS::namespace_size.add_value(key_count as i64, (namespace.clone(),));
+                    STATS::namespace_batches.add_value(batch_count, (namespace,));
+                    return Ok(None);
+                }
+            };
+            let key_count = key_count + batch.len() as u64;
+            if key_count > max_keys {
+                STATS::namespace_too_large.add_value(1, (namespace.clone(),));
+                bail!(
+                    "listkeys namespace '{}' has more than {} keys, use listkeyspatterns instead",
+                    namespace,
+                    max_keys,
+                );
+            }
+            Ok(Some((
+                batch,
+                (batches, namespace, key_count, batch_count + 1),
+            )))
+        },
+    )
+    .boxed()
+}
+
+/// List all the keys of a namespace that match the filter, for callers
+/// that need all of them at once.
+pub async fn collect_namespace(
+    ctx: &CoreContext,
+    namespace: String,
+    provider: &dyn ListKeysProvider,
+    filter: ListKeysFilter,
+) -> Result<ListKeys> {
+    list_namespace(ctx, namespace, provider, filter)
+        .try_fold(ListKeys::new(), |mut keys, batch| {
+            keys.extend(batch);
+            future::ok(keys)
+        })
+        .await
+}
+
+/// The set of namespaces served by `listkeys`, each with its provider.
+#[derive(Clone, Default)]
+pub struct ListKeysRegistry {
+    providers: BTreeMap<String, Arc<dyn ListKeysProvider>>,
+}
+
+impl ListKeysRegistry {
+    /// Create a registry with the namespaces every repo serves, plus
+    /// `infinitepush` if the repo has an infinitepush namespace.
+    pub fn with_default_namespaces(
+        repo: &Arc<Repo>,
+        session_bookmarks_cache: Arc<SessionBookmarkCache>,
+    ) -> Self {
+        let mut registry = Self::default();
+        registry
+            .register(
+                "bookmarks",
+                Arc::new(BookmarksListKeys::new(session_bookmarks_cache.clone())),
+            )
+            .expect("default namespaces are distinct");
+        // Mononoke is always a publishing server.
+        registry
+            .register(
+                "phases",
+                Arc::new(StaticListKeys::new(vec![("publishing", "True")])),
+            )
+            .expect("default namespaces are distinct");
+        if let Some(namespace) = &repo.inner_repo().repo_config().infinitepush.namespace {
+            registry
+                .register(
+                    "infinitepush",
+                    Arc::new(InfinitepushListKeys::new(
+                        repo.clone(),
+                        namespace.clone(),
+                        session_bookmarks_cache,
+                    )),
+                )
+                .expect("default namespaces are distinct");
+        }
+        registry
+    }
+
+    /// Register the provider for a namespace.  Each namespace may only
+    /// have one provider.
+    pub fn register(
+        &mut self,
+        namespace: impl Into<String>,
+        provider: Arc<dyn ListKeysProvider>,
+    ) -> Result<()> {
+        let namespace = namespace.into();
+        if self.providers.contains_key(&namespace) {
+            bail!("listkeys namespace '{}' is already registered", namespace);
+        }
+        self.providers.insert(namespace, provider);
+        Ok(())
+    }
+
+    /// The provider for a namespace, if one is registered.
+    pub fn provider(&self, namespace: &str) -> Option<&Arc<dyn ListKeysProvider>> {
+        self.providers.get(namespace)
+    }
+}
+
+/// Lists the pull-default bookmarks, using the session's view of them so
+/// that they're consistent with discovery.
+pub struct BookmarksListKeys {
+    session_bookmarks_cache: Arc<SessionBookmarkCache>,
+}
+
+impl BookmarksListKeys {
+    pub fn new(session_bookmarks_cache: Arc<SessionBookmarkCache>) -> Self {
+        Self {
+            session_bookmarks_cache,
+        }
+    }
+}
+
+impl ListKeysProvider for BookmarksListKeys {
+    fn list_keys_batched<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        filter: ListKeysFilter,
+    ) -> BoxStream<'a, Result<ListKeysBatch>> {
+        let prefix = match BookmarkPrefix::new(&filter.prefix) {
+            Ok(prefix) => prefix,
+            Err(e) => return stream::once(future::err(e)).boxed(),
+        };
+        self.session_bookmarks_cache
+            .list_publishing_bookmarks_batched(
+                ctx,
+                prefix,
+                BookmarkKind::PullDefaultPublishing,
+                filter.limit,
+                batch_size(),
+            )
+            .map_ok(|batch| {
+                batch
+                    .into_iter()
+                    .map(|(bookmark, cs_id)| {
+                        let hash: Vec<u8> = cs_id.into_nodehash().to_hex().into();
+                        (bookmark.into_byte_vec(), hash)
+                    })
+                    .collect()
+            })
+            .boxed()
+    }
+}
+
+/// Lists the scratch bookmarks in the repo's infinitepush namespace that the
+/// session may read.  At most `list_keys_patterns_max` bookmarks are listed,
+/// so clients should narrow the listing down with a prefix.
+pub struct InfinitepushListKeys {
+    repo: Arc<Repo>,
+    namespace: InfinitepushNamespace,
+    session_bookmarks_cache: Arc<SessionBookmarkCache>,
+}
+
+impl InfinitepushListKeys {
+    pub fn new(
+        repo: Arc<Repo>,
+        namespace: InfinitepushNamespace,
+        session_bookmarks_cache: Arc<SessionBookmarkCache>,
+    ) -> Self {
+        Self {
+            repo,
+            namespace,
+            session_bookmarks_cache,
+        }
+    }
+}
+
+impl ListKeysProvider for InfinitepushListKeys {
+    fn list_keys_batched<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        filter: ListKeysFilter,
+    ) -> BoxStream<'a, Result<ListKeysBatch>> {
+        let prefix = match BookmarkPrefix::new(&filter.prefix) {
+            Ok(prefix) => prefix,
+            Err(e) => return stream::once(future::err(e)).boxed(),
+        };
+        let max = self.repo.inner_repo().repo_config().list_keys_patterns_max;
+        let limit = filter.limit.map_or(max, |limit| limit.min(max));
+        let batch_size = batch_size().try_into().unwrap_or(usize::MAX);
+        async move {
+            // Private scratch bookmarks of other users are left out.
+            let authz = AuthorizationContext::new(ctx);
+            let keys: ListKeysBatch = self
+                .session_bookmarks_cache
+                .get_bookmarks_by_prefix(ctx, &prefix, limit)
+                .await?
+                .try_filter(|(bookmark, _)| {
+                    future::ready(
+                        self.namespace.matches_bookmark(bookmark)
+                            && authz
+                                .check_scratch_bookmark_read(ctx, self.repo.inner_repo(), bookmark)
+                                .is_permitted(),
+                    )
+                })
+                .map_ok(|(bookmark, hg_cs_id)| {
+                    let hash: Vec<u8> = hg_cs_id.into_nodehash().to_hex().into();
+                    (bookmark.into_byte_vec(), hash)
+                })
+                .try_collect()
+                .await?;
+            let batches: Vec<Result<ListKeysBatch>> = keys
+                .chunks(batch_size)
+                .map(|batch| Ok(batch.to_vec()))
+                .collect();
+            Ok::<_, anyhow::Error>(stream::iter(batches))
+        }
+        .try_flatten_stream()
+        .boxed()
+    }
+}
+
+/// Lists a fixed set of keys, for namespaces that expose metadata that
+/// doesn't change during a session.
+pub struct StaticListKeys {
+    keys: ListKeys,
+}
+
+impl StaticListKeys {
+    pub fn new(keys: impl IntoIterator<Item = (impl Into<Vec<u8>>, impl Into<Vec<u8>>)>) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl ListKeysProvider for StaticListKeys {
+    fn list_keys_batched<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        filter: ListKeysFilter,
+    ) -> BoxStream<'a, Result<ListKeysBatch>> {
+        stream::once(future::ok(filter.apply(self.keys.clone()))).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use futures::FutureExt;
+    use maplit::hashmap;
+    use tunables::with_tunables_async;
+    use tunables::MononokeTunables;
+
+    use super::*;
+
+    #[fbinit::test]
+    async fn test_registry(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let mut registry = ListKeysRegistry::default();
+        registry.register(
+            "phases",
+            Arc::new(StaticListKeys::new(vec![("publishing", "True")])),
+        )?;
+        assert!(
+            registry
+                .register(
+                    "phases",
+                    Arc::new(StaticListKeys::new(Vec::<(&str, &str)>::new()))
+                )
+                .is_err()
+        );
+        assert!(registry.provider("unknown").is_none());
+
+        let provider = registry.provider("phases").expect("phases is registered");
+        let keys = collect_namespace(
+            &ctx,
+            "phases".to_string(),
+            provider.as_ref(),
+            Default::default(),
+        )
+        .await?;
+        assert_eq!(
+            keys,
+            hashmap! { b"publishing".to_vec() => b"True".to_vec() }
+        );
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_filter(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let provider = StaticListKeys::new(vec![("a/1", "1"), ("a/2", "2"), ("b/1", "3")]);
+
+        let keys = collect_namespace(
+            &ctx,
+            "test".to_string(),
+            &provider,
+            ListKeysFilter {
+                prefix: String::from("a/"),
+                limit: None,
+            },
+        )
+        .await?;
+        assert_eq!(
+            keys,
+            hashmap! {
+                b"a/1".to_vec() => b"1".to_vec(),
+                b"a/2".to_vec() => b"2".to_vec(),
+            }
+        );
+
+        let keys = collect_namespace(
+            &ctx,
+            "test".to_string(),
+            &provider,
+            ListKeysFilter {
+                prefix: String::from("a/"),
+                limit: Some(1),
+            },
+        )
+        .await?;
+        assert_eq!(keys.len(), 1);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_max_keys(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let provider = StaticListKeys::new(vec![("a/1", "1"), ("a/2", "2"), ("b/1", "3")]);
+        let tunables = MononokeTunables::default();
+        tunables.update_ints(&hashmap! { "listkeys_max_keys".to_string() => 2 });
+
+        with_tunables_async(
+            tunables,
+            async {
+                let prefixed = ListKeysFilter {
+                    prefix: String::from("a/"),
+                    limit: None,
+                };
+                let keys = collect_namespace(&ctx, "test".to_string(), &provider, prefixed).await?;
+                assert_eq!(keys.len(), 2);
+
+                let all =
+                    collect_namespace(&ctx, "test".to_string(), &provider, Default::default());
+                assert!(all.await.is_err());
+                Ok(())
+            }
+            .boxed(),
+        )
+        .await
+    }
+}
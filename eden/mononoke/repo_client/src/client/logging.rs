@@ -7,6 +7,7 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use context::CoreContext;
 use context::PerfCounters;
@@ -19,6 +20,10 @@ use mononoke_types::MPath;
 use scuba_ext::MononokeScubaSampleBuilder;
 use scuba_ext::ScubaValue;
 use scuba_ext::ScubaVerbosityLevel;
+use time_ext::DurationExt;
+
+use super::shadowing::CommandShadow;
+use super::shadowing::ResponseRecorder;
 
 const COLUMN_SIZE_LIMIT: usize = 500_1000;
 const FULL_ARGS_LOG_TAG: &str = "Full Command Args";
@@ -30,6 +35,13 @@ pub enum CommandStats<'a> {
 }
 
 impl<'a> CommandStats<'a> {
+    pub fn completion_time(&self) -> Duration {
+        match self {
+            Self::Future(stats) => stats.completion_time,
+            Self::Stream(stats) => stats.completion_time,
+        }
+    }
+
     fn insert_stats<'b>(
         &self,
         scuba: &'b mut MononokeScubaSampleBuilder,
@@ -53,18 +65,24 @@ impl<'a> From<&'a StreamStats> for CommandStats<'a> {
     }
 }
 
-/// Logs wireproto requests both to scuba.
+/// Logs wireproto requests both to scuba and, if the command is being
+/// shadowed, to the shadow tier.
 /// Scuba logs are used for analysis of performance.
 #[must_use = "A CommandLogger does not do anything if you don't use it"]
 pub struct CommandLogger {
     inner: ScubaOnlyCommandLogger,
+    shadow: Option<CommandShadow>,
 }
 
 impl CommandLogger {
-    pub fn new(ctx: CoreContext, request_perf_counters: Arc<PerfCounters>) -> Self {
+    pub fn new(
+        ctx: CoreContext,
+        request_perf_counters: Arc<PerfCounters>,
+        shadow: Option<CommandShadow>,
+    ) -> Self {
         let inner = ScubaOnlyCommandLogger::new(ctx, request_perf_counters);
 
-        Self { inner }
+        Self { inner, shadow }
     }
 
     /// Opts-out of replaying the wireproto request on the shadow tier.
@@ -73,8 +91,28 @@ impl CommandLogger {
         self.inner
     }
 
+    /// Set the arguments needed to replay the request on the shadow tier.
+    pub fn set_shadow_args(&mut self, args: serde_json::Value) {
+        if let Some(shadow) = self.shadow.as_mut() {
+            shadow.set_args(args);
+        }
+    }
+
+    /// Returns a recorder for the response sent to the client, which is
+    /// compared with the response of the shadow tier.
+    pub fn response_recorder(&self) -> ResponseRecorder {
+        self.shadow
+            .as_ref()
+            .map(CommandShadow::response_recorder)
+            .unwrap_or_default()
+    }
+
     pub fn finalize_command<'a>(self, stats: impl Into<CommandStats<'a>>) {
-        self.inner.log_command_processed(stats.into());
+        let stats = stats.into();
+        if let Some(shadow) = self.shadow {
+            shadow.publish(&self.inner.ctx, stats);
+        }
+        self.inner.log_command_processed(stats);
     }
 
     pub fn add_scuba_extra(&mut self, k: impl Into<String>, v: impl Into<ScubaValue>) {
@@ -124,7 +162,32 @@ impl ScubaOnlyCommandLogger {
             scuba.add(k, v);
         }
 
-        scuba.log_with_msg("Command processed", None);
+        let dropped_spans = self.ctx.trace().dropped_spans();
+        if dropped_spans > 0 {
+            scuba.add("trace_dropped_spans", dropped_spans);
+        }
+
+        scuba.clone().log_with_msg("Command processed", None);
+        log_trace_spans(&self.ctx, scuba);
+    }
+}
+
+/// Log the spans of a traced command, one sample per span.  The samples
+/// share the columns of the command's sample, so they can be found by its
+/// `trace_id`.
+fn log_trace_spans(ctx: &CoreContext, scuba: MononokeScubaSampleBuilder) {
+    for span in ctx.trace().spans() {
+        let mut scuba = scuba.clone();
+        scuba
+            .add("span_id", span.id)
+            .add_opt("span_parent_id", span.parent)
+            .add("span_name", span.name)
+            .add("span_start_us", span.start.as_micros_unchecked())
+            .add("span_duration_us", span.duration.as_micros_unchecked());
+        for (key, value) in span.attributes {
+            scuba.add(format!("span_{}", key), value);
+        }
+        scuba.log_with_msg("Trace span", None);
     }
 }
 
@@ -0,0 +1,203 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Verification of manifests served by `gettreepack`.
+//!
+//! Manifests are stored under their node hash, but nothing checks that the
+//! stored content still matches the hash when it is read back, so storage
+//! corruption is usually only noticed when a client fails to apply the
+//! response.  Repos can be configured to re-verify a sample of the manifests
+//! they serve, by recomputing the node hash from the manifest content and
+//! parents.
+//!
+//! A manifest that fails verification is quarantined: the server refuses to
+//! serve it again, without fetching it, until it is restarted.  Each
+//! mismatch is logged and counted, so that it can be alerted on.  Checking
+//! the quarantine doesn't take a lock until something has been quarantined
+//! in the repo, and at most `MAX_QUARANTINED` manifests are quarantined per
+//! repo.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::num::NonZeroU64;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use anyhow::Error;
+use anyhow::Result;
+use context::CoreContext;
+use lazy_static::lazy_static;
+use mercurial_types::HgManifestId;
+use metaconfig_types::RepoClientKnobs;
+use rand::Rng;
+use slog::error;
+use stats::prelude::*;
+use tunables::tunables;
+
+use crate::errors::ErrorKind;
+
+define_stats! {
+    prefix = "mononoke.repo_client.manifest_verification";
+    verified: dynamic_timeseries("verified.{}", (reponame: String); Rate, Sum),
+    corrupt: dynamic_timeseries("corrupt.{}", (reponame: String); Rate, Sum),
+    quarantined: dynamic_timeseries("quarantined.{}", (reponame: String); Rate, Sum),
+}
+
+/// Maximum number of manifests quarantined in each repo.  Once it is
+/// reached, further corrupt manifests are still logged and counted.
+const MAX_QUARANTINED: usize = 10_000;
+
+/// The manifests quarantined in a repo.
+#[derive(Default)]
+struct Quarantine {
+    /// Whether any manifest has been quarantined, so that checks don't need
+    /// to lock `manifests` until one has.
+    active: AtomicBool,
+    manifests: RwLock<HashSet<HgManifestId>>,
+}
+
+impl Quarantine {
+    fn contains(&self, hg_mf_id: &HgManifestId) -> bool {
+        self.active.load(Ordering::Acquire)
+            && self
+                .manifests
+                .read()
+                .expect("lock poisoned")
+                .contains(hg_mf_id)
+    }
+
+    fn insert(&self, hg_mf_id: HgManifestId) {
+        let mut manifests = self.manifests.write().expect("lock poisoned");
+        if manifests.len() < MAX_QUARANTINED {
+            manifests.insert(hg_mf_id);
+            self.active.store(true, Ordering::Release);
+        }
+    }
+}
+
+lazy_static! {
+    static ref QUARANTINES: Mutex<HashMap<String, Arc<Quarantine>>> = Mutex::new(HashMap::new());
+}
+
+/// Decides which manifests served by a `gettreepack` request are verified,
+/// and quarantines the ones that fail.
+pub(crate) struct ManifestVerifier {
+    repo_name: String,
+    verify_all: bool,
+    sample_rate: Option<NonZeroU64>,
+    quarantine: Arc<Quarantine>,
+}
+
+impl ManifestVerifier {
+    pub(crate) fn new(repo_name: String, knobs: &RepoClientKnobs) -> Self {
+        // The `hash_validation_percentage` tunable verifies every manifest
+        // in a sample of requests, regardless of the repo's sample rate.
+        let hash_validation_percentage = tunables().get_hash_validation_percentage();
+        let verify_all = ((rand::random::<usize>() % 100) as i64) < hash_validation_percentage;
+        let quarantine = QUARANTINES
+            .lock()
+            .expect("lock poisoned")
+            .entry(repo_name.clone())
+            .or_default()
+            .clone();
+        Self {
+            repo_name,
+            verify_all,
+            sample_rate: knobs.manifest_verification_sample_rate,
+            quarantine,
+        }
+    }
+
+    /// Whether the next manifest served should be verified.
+    pub(crate) fn should_verify(&self) -> bool {
+        let sampled = match self.sample_rate {
+            Some(rate) => {
+                rand::thread_rng().gen_ratio(1, rate.get().try_into().unwrap_or(u32::MAX))
+            }
+            None => false,
+        };
+        if self.verify_all || sampled {
+            STATS::verified.add_value(1, (self.repo_name.clone(),));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Fail if the manifest has been quarantined.
+    pub(crate) fn check_quarantine(&self, hg_mf_id: HgManifestId) -> Result<()> {
+        if self.quarantine.contains(&hg_mf_id) {
+            STATS::quarantined.add_value(1, (self.repo_name.clone(),));
+            return Err(ErrorKind::QuarantinedManifest(hg_mf_id).into());
+        }
+        Ok(())
+    }
+
+    /// Quarantine the manifest if serving it failed because it is corrupt.
+    pub(crate) fn handle_error(
+        &self,
+        ctx: &CoreContext,
+        hg_mf_id: HgManifestId,
+        err: Error,
+    ) -> Error {
+        if let Some(ErrorKind::DataCorruption { path, .. }) = err.downcast_ref::<ErrorKind>() {
+            STATS::corrupt.add_value(1, (self.repo_name.clone(),));
+            error!(
+                ctx.logger(),
+                "Quarantining corrupt manifest {} for {} in {}", hg_mf_id, path, self.repo_name
+            );
+            self.quarantine.insert(hg_mf_id);
+        }
+        err
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fbinit::FacebookInit;
+    use mercurial_types::RepoPath;
+    use mercurial_types_mocks::nodehash::ONES_HASH;
+    use mercurial_types_mocks::nodehash::TWOS_HASH;
+    use nonzero_ext::nonzero;
+
+    use super::*;
+
+    #[fbinit::test]
+    fn test_quarantine(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let knobs = RepoClientKnobs {
+            manifest_verification_sample_rate: Some(nonzero!(1u64)),
+            ..Default::default()
+        };
+        let verifier = ManifestVerifier::new("quarantine_test".to_string(), &knobs);
+        let other_repo = ManifestVerifier::new("quarantine_test_other".to_string(), &knobs);
+        assert!(verifier.should_verify());
+
+        let corrupt = HgManifestId::new(ONES_HASH);
+        let healthy = HgManifestId::new(TWOS_HASH);
+        verifier.check_quarantine(corrupt)?;
+
+        // Errors other than corruption don't quarantine the manifest.
+        verifier.handle_error(&ctx, corrupt, Error::msg("blobstore unavailable"));
+        verifier.check_quarantine(corrupt)?;
+
+        let corruption = ErrorKind::DataCorruption {
+            path: RepoPath::dir("dir")?,
+            expected: TWOS_HASH,
+            actual: ONES_HASH,
+        };
+        verifier.handle_error(&ctx, corrupt, corruption.into());
+        assert!(verifier.check_quarantine(corrupt).is_err());
+        verifier.check_quarantine(healthy)?;
+        other_repo.check_quarantine(corrupt)?;
+
+        Ok(())
+    }
+}
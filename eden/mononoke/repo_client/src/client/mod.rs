@@ -7,6 +7,7 @@
 
 use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Write;
@@ -14,6 +15,7 @@ use std::hash::Hash;
 use std::hash::Hasher;
 use std::mem;
 use std::num::NonZeroU64;
+use std::ops::Bound;
 use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -28,13 +30,21 @@ use anyhow::Error;
 use anyhow::Result;
 use blobrepo::AsBlobRepo;
 use blobrepo::BlobRepo;
+use blobrepo_hg::to_hg_bookmark_stream;
 use blobrepo_hg::BlobRepoHg;
 use blobstore::Loadable;
 use bookmarks::Bookmark;
 use bookmarks::BookmarkName;
+use bookmarks::BookmarkPagination;
 use bookmarks::BookmarkPrefix;
+use bookmarks::Freshness;
 use bookmarks_types::BookmarkKind;
+use bulkhead::configure_repo_bulkheads;
+use bulkhead::BulkheadLimits;
+use bulkhead::BulkheadOp;
+use bulkhead::RepoBulkheads;
 use bytes::Bytes;
+use bytes::BytesMut;
 use bytes_old::BufMut as BufMutOld;
 use bytes_old::Bytes as BytesOld;
 use bytes_old::BytesMut as BytesMutOld;
@@ -44,6 +54,9 @@ use context::LoggingContainer;
 use context::PerfCounterType;
 use context::PerfCounters;
 use context::SessionContainer;
+use context::TraceContext;
+use context::TraceLevel;
+use feature_flags::BoolFlag;
 use filenodes::FilenodeResult;
 use futures::channel::oneshot;
 use futures::channel::oneshot::Sender;
@@ -78,12 +91,20 @@ use futures_old::Stream;
 use futures_stats::TimedFutureExt;
 use futures_stats::TimedStreamExt;
 use getbundle_response::create_getbundle_response;
+use getbundle_response::ChangesetFilesCheck;
 use getbundle_response::PhasesPart;
 use getbundle_response::SessionLfsParams;
+use hgproto::DiscoverySample;
+use hgproto::DiscoverySampleArgs;
 use hgproto::GetbundleArgs;
+use hgproto::GetManifestPageArgs;
 use hgproto::GettreepackArgs;
+use hgproto::HeadsPage;
+use hgproto::HeadsPaginatedArgs;
 use hgproto::HgCommandRes;
 use hgproto::HgCommands;
+use hgproto::ManifestPage;
+use hgproto::PrefetchHintsArgs;
 use hooks::HookManagerArc;
 use hostname::get_hostname;
 use itertools::Itertools;
@@ -99,6 +120,7 @@ use mercurial_bundles::Bundle2Item;
 use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_revlog::RevlogChangeset;
 use mercurial_types::blobs::HgBlobChangeset;
+use mercurial_types::blobs::HgBlobManifest;
 use mercurial_types::calculate_hg_node_id;
 use mercurial_types::convert_parents_to_remotefilelog_format;
 use mercurial_types::fetch_manifest_envelope;
@@ -107,23 +129,33 @@ use mercurial_types::Delta;
 use mercurial_types::HgChangesetId;
 use mercurial_types::HgChangesetIdPrefix;
 use mercurial_types::HgChangesetIdsResolvedFromPrefix;
+use mercurial_types::HgEntryId;
 use mercurial_types::HgFileNodeId;
 use mercurial_types::HgManifestId;
 use mercurial_types::HgNodeHash;
+use mercurial_types::HgNodeHash256;
+use mercurial_types::HgNodeHashScheme;
 use mercurial_types::HgParents;
 use mercurial_types::MPath;
+use mercurial_types::MPathElement;
 use mercurial_types::RepoPath;
 use mercurial_types::NULL_CSID;
 use mercurial_types::NULL_HASH;
+use mercurial_types::SHA256_NODES_CAPABILITY;
 use metaconfig_types::RepoClientKnobs;
 use metaconfig_types::RepoConfigRef;
+use mononoke_api::bookmark_publishing::BookmarkPublishingGate;
 use mononoke_api::Repo;
+use mononoke_api_types::InnerRepo;
 use mononoke_types::hash::GitSha1;
 use mononoke_types::ChangesetId;
 use nonzero_ext::nonzero;
+use path_policy::PathPolicy;
 use phases::PhasesArc;
+use preserved_bundles::PreservedBundlesArc;
 use rand::Rng;
 use rate_limiting::Metric;
+use rate_limiting::RateLimitReason;
 use reachabilityindex::LeastCommonAncestorsHint;
 use regex::Regex;
 use remotefilelog::create_getpack_v1_blob;
@@ -131,6 +163,7 @@ use remotefilelog::create_getpack_v2_blob;
 use remotefilelog::get_unordered_file_history_for_multiple_nodes;
 use remotefilelog::GetpackBlobInfo;
 use repo_authorization::AuthorizationContext;
+use repo_derived_data::RepoDerivedDataRef;
 use repo_identity::RepoIdentityRef;
 use revisionstore_types::Metadata;
 use serde::Deserialize;
@@ -140,6 +173,8 @@ use slog::debug;
 use slog::error;
 use slog::info;
 use slog::o;
+use slog::warn;
+use snapshot_bundles::SnapshotBundlesArc;
 use stats::prelude::*;
 use streaming_clone::RevlogStreamingChunks;
 use streaming_clone::StreamingCloneArc;
@@ -151,22 +186,53 @@ use unbundle::BundleResolverError;
 use unbundle::CrossRepoPushSource;
 use unbundle::PushRedirector;
 use unbundle::PushRedirectorArgs;
+use unbundle::UnbundleResponse;
 use wireproto_handler::BackupSourceRepo;
 
 use crate::errors::ErrorKind;
 
+mod changegroup_compat;
+mod discovery;
+mod invalidation_hints;
+mod listkeys;
 mod logging;
+mod manifest_verification;
 mod monitor;
+mod prefetch_hints;
 mod session_bookmarks_cache;
+mod shadowing;
+mod shared_getbundle;
 mod tests;
-
+mod trace_sampling;
+
+use changegroup_compat::negotiate_changegroup_lfs_params;
+use changegroup_compat::CHANGEGROUP_CAP;
+use invalidation_hints::find_invalidation_hints;
+use invalidation_hints::INVALIDATION_HINTS_CAP;
+use listkeys::collect_namespace;
+use listkeys::list_namespace;
+pub use listkeys::ListKeys;
+pub use listkeys::ListKeysBatch;
+pub use listkeys::ListKeysFilter;
+pub use listkeys::ListKeysProvider;
+use listkeys::ListKeysRegistry;
+pub use listkeys::StaticListKeys;
 use logging::debug_format_manifest;
 use logging::debug_format_path;
 use logging::log_getpack_params_verbose;
 use logging::log_gettreepack_params_verbose;
 use logging::CommandLogger;
+use manifest_verification::ManifestVerifier;
 use monitor::Monitor;
+use prefetch_hints::try_reserve_prefetch;
+use prefetch_hints::PrefetchHints;
+use prefetch_hints::PREFETCH_HINTS_TIMEOUT;
 use session_bookmarks_cache::SessionBookmarkCache;
+use shadowing::should_shadow;
+use shadowing::CommandShadow;
+use shared_getbundle::shared_getbundle;
+use shared_getbundle::SharedGetbundleKey;
+use trace_sampling::TraceSamplingPolicy;
 
 define_stats! {
     prefix = "mononoke.repo_client";
@@ -187,6 +253,11 @@ define_stats! {
     null_linknode_gettreepack: timeseries(Rate, Sum),
     null_linknode_getpack: timeseries(Rate, Sum),
     getcommitdata_commit_count: timeseries(Rate, Sum),
+    prefetch_hints_commits: timeseries(Rate, Sum),
+    prefetch_hints_trees: timeseries(Rate, Sum),
+    prefetch_hints_skipped_commits: timeseries(Rate, Sum),
+    prefetch_hints_timeouts: timeseries(Rate, Sum),
+    prefetch_hints_dropped: timeseries(Rate, Sum),
 
     push_success: dynamic_timeseries("push_success.{}", (reponame: String); Rate, Sum),
     push_hook_failure: dynamic_timeseries("push_hook_failure.{}.{}", (reponame: String, hook_failure: String); Rate, Sum),
@@ -201,9 +272,14 @@ define_stats! {
 
 mod ops {
     pub static CLIENTTELEMETRY: &str = "clienttelemetry";
+    pub static CLONEBUNDLES: &str = "clonebundles";
     pub static HELLO: &str = "hello";
     pub static UNBUNDLE: &str = "unbundle";
     pub static HEADS: &str = "heads";
+    pub static HEADSPAGINATED: &str = "headspaginated";
+    pub static GETMANIFESTPAGE: &str = "getmanifestpage";
+    pub static DISCOVERYSAMPLE: &str = "discoverysample";
+    pub static PREFETCHHINTS: &str = "prefetchhints";
     pub static LOOKUP: &str = "lookup";
     pub static LISTKEYS: &str = "listkeys";
     pub static LISTKEYSPATTERNS: &str = "listkeyspatterns";
@@ -224,6 +300,23 @@ struct SamplingRate(NonZeroU64);
 const GETTREEPACK_FEW_MFNODES_SAMPLING_RATE: SamplingRate = SamplingRate(nonzero!(100u64));
 const UNSAMPLED: SamplingRate = SamplingRate(nonzero!(1u64));
 
+/// Default amount of a shared getbundle response that is kept for sessions
+/// that attach late.
+const GETBUNDLE_SHARED_MAX_BUFFERED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Maximum number of bookmarks considered for a single page of
+/// `headspaginated`.  Clients asking for more get this many.  The legacy
+/// `heads` response includes at most this many scratch heads.
+const HEADS_PAGE_MAX: u64 = 10_000;
+
+/// Maximum number of entries returned in a single page of
+/// `getmanifestpage`.  Clients asking for more get this many.
+const MANIFEST_PAGE_MAX: u64 = 10_000;
+
+// Feature flags overriding the repo client knobs of the same name.
+const ALLOW_SHORT_GETPACK_HISTORY: BoolFlag = BoolFlag::new("allow_short_getpack_history", false);
+const LEGACY_HEADS_INCLUDE_SCRATCH: BoolFlag = BoolFlag::new("legacy_heads_include_scratch", false);
+
 fn gettreepack_scuba_sampling_rate(params: &GettreepackArgs) -> SamplingRate {
     if params.mfnodes.len() == 1 {
         GETTREEPACK_FEW_MFNODES_SAMPLING_RATE
@@ -318,6 +411,11 @@ fn wireprotocaps() -> Vec<String> {
         "knownnodes".to_string(),
         "designatednodes".to_string(),
         "getcommitdata".to_string(),
+        "headspaginated".to_string(),
+        "getmanifestpage".to_string(),
+        "discoverysample".to_string(),
+        "clonebundles".to_string(),
+        "prefetchhints".to_string(),
     ]
 }
 
@@ -335,6 +433,7 @@ fn bundle2caps() -> String {
             ("phases", vec!["heads"]),
             ("obsmarkers", vec!["V1"]),
             ("listkeys", vec![]),
+            (INVALIDATION_HINTS_CAP, vec![]),
         ];
 
         if tunables().get_mutation_advertise_for_infinitepush() {
@@ -464,7 +563,16 @@ pub struct RepoClient {
     session_bookmarks_cache: Arc<SessionBookmarkCache>,
     maybe_push_redirector_args: Option<PushRedirectorArgs<Repo>>,
     force_lfs: Arc<AtomicBool>,
+    // Whether nodes are identified to the client by their SHA-256 ids.
+    sha256_nodes: Arc<AtomicBool>,
     knobs: RepoClientKnobs,
+    // Decides which commands of this session are traced.
+    trace_sampling: Arc<TraceSamplingPolicy>,
+    // Providers for the namespaces served by listkeys.
+    listkeys_registry: Arc<ListKeysRegistry>,
+    // Limits on concurrent expensive operations, shared by all sessions for
+    // this repo.
+    bulkheads: Arc<RepoBulkheads>,
     request_perf_counters: Arc<PerfCounters>,
     // In case `repo` is a backup of another repository `maybe_backup_repo_source` points to
     // a source for this repository.
@@ -480,7 +588,29 @@ impl RepoClient {
         knobs: RepoClientKnobs,
         maybe_backup_repo_source: Option<BackupSourceRepo>,
     ) -> Self {
-        let session_bookmarks_cache = Arc::new(SessionBookmarkCache::new(repo.clone()));
+        let publishing_gate = BookmarkPublishingGate::new(
+            &repo.config().bookmarks,
+            session.metadata().identities(),
+            repo.published_bookmarks.clone(),
+        );
+        let session_bookmarks_cache =
+            Arc::new(SessionBookmarkCache::new(repo.clone()).with_publishing_gate(publishing_gate));
+        let listkeys_registry = Arc::new(ListKeysRegistry::with_default_namespaces(
+            &repo,
+            session_bookmarks_cache.clone(),
+        ));
+        let bulkheads = configure_repo_bulkheads(
+            repo.inner_repo().repo_identity().name(),
+            BulkheadLimits {
+                getbundle: knobs.max_concurrent_getbundles,
+                unbundle: knobs.max_concurrent_unbundles,
+                hooks: knobs.max_concurrent_hook_runs,
+            },
+        );
+        let trace_sampling = Arc::new(TraceSamplingPolicy::new(
+            knobs.trace_sampling.as_ref(),
+            session.metadata().identities(),
+        ));
 
         Self {
             repo,
@@ -489,12 +619,26 @@ impl RepoClient {
             session_bookmarks_cache,
             maybe_push_redirector_args,
             force_lfs: Arc::new(AtomicBool::new(false)),
+            sha256_nodes: Arc::new(AtomicBool::new(false)),
             knobs,
+            trace_sampling,
+            listkeys_registry,
+            bulkheads,
             request_perf_counters: Arc::new(PerfCounters::default()),
             maybe_backup_repo_source,
         }
     }
 
+    /// Serve an additional listkeys namespace from this provider.  Fails if
+    /// the namespace is already served.
+    pub fn register_listkeys_provider(
+        &mut self,
+        namespace: impl Into<String>,
+        provider: Arc<dyn ListKeysProvider>,
+    ) -> Result<()> {
+        Arc::make_mut(&mut self.listkeys_registry).register(namespace, provider)
+    }
+
     pub fn request_perf_counters(&self) -> Arc<PerfCounters> {
         self.request_perf_counters.clone()
     }
@@ -543,13 +687,42 @@ impl RepoClient {
         scuba
             .sampled_unless_verbose(sampling_rate.0)
             .add("command", command);
-        scuba.clone().log_with_msg("Start processing", None);
 
-        let ctx =
-            self.session
-                .new_context_with_scribe(logger, scuba, self.logging.scribe().clone());
+        let decision = self.trace_sampling.decide(command);
+        scuba
+            .add("trace_level", format!("{:?}", decision.level))
+            .add("trace_sample_rate", decision.sample_rate);
+        let trace = if decision.level != TraceLevel::Off {
+            let trace_id = self.session.id_generator().trace_id();
+            // Log all samples of traced commands, so that none of the spans
+            // are missing.
+            scuba.unsampled().add("trace_id", trace_id.as_str());
+            TraceContext::with_level(trace_id, decision.level)
+        } else {
+            TraceContext::disabled()
+        };
+
+        scuba.clone().log_with_msg("Start processing", None);
 
-        let command_logger = CommandLogger::new(ctx.clone(), self.request_perf_counters.clone());
+        let ctx = self
+            .session
+            .new_context_with_scribe(logger, scuba, self.logging.scribe().clone())
+            .clone_with_trace(trace);
+
+        let shadow = self
+            .knobs
+            .shadowing
+            .as_ref()
+            .filter(|config| should_shadow(config, command))
+            .map(|config| {
+                CommandShadow::new(
+                    config,
+                    self.repo.inner_repo().repo_identity().name(),
+                    command,
+                )
+            });
+        let command_logger =
+            CommandLogger::new(ctx.clone(), self.request_perf_counters.clone(), shadow);
 
         (ctx, command_logger)
     }
@@ -564,30 +737,31 @@ impl RepoClient {
             .compat()
     }
 
-    fn get_pull_default_bookmarks_maybe_stale(
+    fn list_keys(
         &self,
         ctx: CoreContext,
-    ) -> impl Future<Item = HashMap<Vec<u8>, Vec<u8>>, Error = Error> {
-        self.get_publishing_bookmarks_maybe_stale(ctx)
-            .map(|bookmarks| {
-                bookmarks
-                    .into_iter()
-                    .filter_map(|(book, cs)| {
-                        let hash: Vec<u8> = cs.into_nodehash().to_hex().into();
-                        if book.kind() == &BookmarkKind::PullDefaultPublishing {
-                            Some((book.into_name().into_byte_vec(), hash))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            })
+        namespace: String,
+        provider: Arc<dyn ListKeysProvider>,
+        filter: ListKeysFilter,
+    ) -> impl Future<Item = ListKeys, Error = Error> {
+        async move {
+            // The response is sent in one piece, so the batches are added to
+            // it as they are listed rather than being collected first.
+            list_namespace(&ctx, namespace, provider.as_ref(), filter)
+                .try_fold(ListKeys::new(), |mut keys, batch| {
+                    keys.extend(batch);
+                    future::ok(keys)
+                })
+                .await
+        }
+        .boxed()
+        .compat()
     }
 
     fn create_bundle(&self, ctx: CoreContext, args: GetbundleArgs) -> BoxStream<BytesOld, Error> {
         let lfs_params = self.lfs_params();
         let blobrepo = self.repo.blob_repo().clone();
-        let mut bundle2_parts = vec![];
+        let repo_name = self.repo.inner_repo().repo_identity().name().to_string();
 
         let GetbundleArgs {
             bundlecaps,
@@ -598,60 +772,159 @@ impl RepoClient {
         } = args;
 
         let mut use_phases = phases;
-        if use_phases {
-            for cap in &bundlecaps {
-                if let Some((cap_name, caps)) = parse_utf8_getbundle_caps(cap) {
-                    if cap_name != "bundle2" {
-                        continue;
-                    }
+        let mut use_invalidation_hints = false;
+        let mut changegroup_versions = None;
+        for cap in &bundlecaps {
+            if let Some((cap_name, caps)) = parse_utf8_getbundle_caps(cap) {
+                if cap_name != "bundle2" {
+                    continue;
+                }
+                if use_phases {
                     if let Some(phases) = caps.get("phases") {
                         use_phases = phases.contains("heads");
-                        break;
                     }
                 }
+                use_invalidation_hints = caps.contains_key(INVALIDATION_HINTS_CAP);
+                changegroup_versions = caps.get(CHANGEGROUP_CAP).cloned();
+                break;
             }
         }
-        let pull_default_bookmarks = self.get_pull_default_bookmarks_maybe_stale(ctx.clone());
+        // Clients that don't support the changegroup version the repo serves
+        // are sent a downgraded response, or refused, depending on config.
+        let lfs_params = negotiate_changegroup_lfs_params(
+            &ctx,
+            &repo_name,
+            &self.knobs,
+            lfs_params,
+            changegroup_versions.as_ref(),
+        );
+        let listkeys_providers: Vec<_> = listkeys
+            .iter()
+            .filter_map(|namespace| {
+                let namespace = String::from_utf8(namespace.clone()).ok()?;
+                let provider = self.listkeys_registry.provider(&namespace)?.clone();
+                Some((namespace, provider))
+            })
+            .collect();
         let lca_hint: Arc<dyn LeastCommonAncestorsHint> =
             self.repo.inner_repo().skiplist_index_arc();
+        let repo = self.repo.clone();
 
         async move {
-            create_getbundle_response(
-                &ctx,
-                &blobrepo,
-                common,
-                &heads,
-                &lca_hint,
-                if use_phases {
-                    PhasesPart::Yes
+            let lfs_params = lfs_params?;
+
+            // Responses may be shared between callers, so the heads are
+            // checked for each caller before the response is computed.
+            require_hg_changesets_read(&ctx, repo.inner_repo(), heads.clone()).await?;
+
+            // The keys are part of the response, so are listed up front to
+            // decide whether the response can be shared.
+            let listkeys = stream::iter(listkeys_providers)
+                .then(|(namespace, provider)| {
+                    cloned!(ctx);
+                    async move {
+                        let keys = collect_namespace(
+                            &ctx,
+                            namespace.clone(),
+                            provider.as_ref(),
+                            ListKeysFilter::default(),
+                        )
+                        .await?;
+                        Ok::<_, Error>((namespace, keys))
+                    }
+                })
+                .try_collect::<Vec<_>>()
+                .await?;
+
+            // The changesets list the files they touch, so callers that may
+            // not read every protected path have them checked as they are
+            // sent, and can't share responses with other callers.
+            let authz = AuthorizationContext::new(&ctx);
+            let files_check: Option<ChangesetFilesCheck> = if authz
+                .check_all_protected_paths_read(&ctx, repo.inner_repo())
+                .is_denied()
+            {
+                cloned!(ctx, repo);
+                Some(Arc::new(move |files: &[MPath]| {
+                    authz.require_protected_paths_read(&ctx, repo.inner_repo(), files)?;
+                    Ok::<_, Error>(())
+                }))
+            } else {
+                None
+            };
+
+            let shared_key =
+                if files_check.is_none() && tunables().get_getbundle_share_concurrent_requests() {
+                    Some(SharedGetbundleKey::new(
+                        repo_name,
+                        &heads,
+                        &common,
+                        use_phases,
+                        use_invalidation_hints,
+                        &lfs_params,
+                        &listkeys,
+                    ))
                 } else {
-                    PhasesPart::No
-                },
-                &lfs_params,
-            )
-            .await
+                    None
+                };
+
+            let compute = move || {
+                async move {
+                    let invalidation_hints = if use_invalidation_hints {
+                        Some(find_invalidation_hints(&ctx, &blobrepo, &common, &listkeys).await?)
+                    } else {
+                        None
+                    };
+
+                    let mut bundle2_parts = create_getbundle_response(
+                        &ctx,
+                        &blobrepo,
+                        common,
+                        &heads,
+                        &lca_hint,
+                        if use_phases {
+                            PhasesPart::Yes
+                        } else {
+                            PhasesPart::No
+                        },
+                        &lfs_params,
+                        files_check,
+                    )
+                    .await?;
+
+                    // listkeys parts are added separately, for each requested
+                    // namespace that has a provider.
+                    for (namespace, keys) in listkeys {
+                        bundle2_parts
+                            .push(parts::listkey_part(namespace, stream_old::iter_ok(keys))?);
+                    }
+                    if let Some(invalidation_hints) = invalidation_hints {
+                        bundle2_parts.push(parts::invalidation_hints_part(invalidation_hints)?);
+                    }
+                    // TODO(stash): handle includepattern= and excludepattern=
+
+                    let compression = None;
+                    Ok::<_, Error>(create_bundle_stream(bundle2_parts, compression).compat())
+                }
+                .try_flatten_stream()
+                .boxed()
+            };
+
+            Ok::<_, Error>(match shared_key {
+                Some(key) => {
+                    let max_buffered_bytes =
+                        match tunables().get_getbundle_shared_max_buffered_bytes() {
+                            bytes if bytes > 0 => bytes as usize,
+                            _ => GETBUNDLE_SHARED_MAX_BUFFERED_BYTES,
+                        };
+                    shared_getbundle(key, max_buffered_bytes, compute)
+                }
+                None => compute(),
+            })
         }
+        .try_flatten_stream()
         .boxed()
         .compat()
-        .and_then(move |mut getbundle_response| {
-            bundle2_parts.append(&mut getbundle_response);
-
-            // listkeys bookmarks part is added separately.
-
-            // TODO: generalize this to other listkey types
-            // (note: just calling &b"bookmarks"[..] doesn't work because https://fburl.com/0p0sq6kp)
-            if listkeys.contains(&b"bookmarks".to_vec()) {
-                let items = pull_default_bookmarks
-                    .map(stream_old::iter_ok)
-                    .flatten_stream();
-                bundle2_parts.push(parts::listkey_part("bookmarks", items)?);
-            }
-            // TODO(stash): handle includepattern= and excludepattern=
-
-            let compression = None;
-            Ok(create_bundle_stream(bundle2_parts, compression).boxify())
-        })
-        .flatten_stream()
         .boxify()
     }
 
@@ -660,17 +933,52 @@ impl RepoClient {
         ctx: CoreContext,
         params: GettreepackArgs,
     ) -> BoxStream<BytesOld, Error> {
-        let hash_validation_percentage = tunables().get_hash_validation_percentage();
-        let validate_hash = ((rand::random::<usize>() % 100) as i64) < hash_validation_percentage;
+        let verifier = Arc::new(ManifestVerifier::new(
+            self.repo.inner_repo().repo_identity().name().to_string(),
+            &self.knobs,
+        ));
 
         let undesired_path_logger =
             try_boxstream!(UndesiredPathLogger::new(ctx.clone(), self.repo.blob_repo()));
 
+        // Every tree served is reachable from one of the requested trees, so
+        // only those need to be checked for private scratch commits.  The
+        // client chooses the requested trees and their paths, so they are
+        // checked against the path read ACLs by hash.  The paths of the other
+        // trees are found by traversal, and are checked as they are served.
+        let requested_trees = try_boxstream!(gettreepack_requested_trees(&params));
+        let mfnodes_read = {
+            cloned!(ctx);
+            let repo = self.repo.clone();
+            let nodes = params
+                .mfnodes
+                .iter()
+                .map(|mfnode| mfnode.into_nodehash())
+                .collect();
+            async move {
+                require_hg_nodes_read(&ctx, repo.inner_repo(), nodes).await?;
+                require_protected_nodes_read(&ctx, repo.inner_repo(), requested_trees).await
+            }
+            .boxed()
+            .compat()
+        };
+
         let changed_entries = gettreepack_entries(ctx.clone(), self.repo.blob_repo(), params)
             .filter({
                 let mut used_hashes = HashSet::new();
                 move |(hg_mf_id, _)| used_hashes.insert(hg_mf_id.clone())
             })
+            .and_then({
+                cloned!(ctx);
+                let repo = self.repo.clone();
+                cloned!(verifier);
+                let authz = AuthorizationContext::new(&ctx);
+                move |(hg_mf_id, path)| {
+                    authz.require_protected_path_read(&ctx, repo.inner_repo(), path.as_ref())?;
+                    verifier.check_quarantine(hg_mf_id)?;
+                    Result::<_, Error>::Ok((hg_mf_id, path))
+                }
+            })
             .map({
                 cloned!(ctx);
                 let blobrepo = self.repo.blob_repo().clone();
@@ -685,7 +993,18 @@ impl RepoClient {
                     if ctx.session().is_quicksand() {
                         STATS::quicksand_tree_count.add_value(1);
                     }
-                    fetch_treepack_part_input(ctx.clone(), &blobrepo, hg_mf_id, path, validate_hash)
+                    fetch_treepack_part_input(
+                        ctx.clone(),
+                        &blobrepo,
+                        hg_mf_id,
+                        path,
+                        verifier.should_verify(),
+                    )
+                    .map_err({
+                        cloned!(ctx, verifier);
+                        move |err| verifier.handle_error(&ctx, hg_mf_id, err)
+                    })
+                    .boxify()
                 }
             });
 
@@ -694,7 +1013,8 @@ impl RepoClient {
         // https://bz.mercurial-scm.org/show_bug.cgi?id=5646
         // TODO: possibly enable compression support once this is fixed.
         let compression = None;
-        part.into_future()
+        mfnodes_read
+            .and_then(move |()| part)
             .map(move |part| create_bundle_stream(vec![part], compression))
             .flatten_stream()
             .boxify()
@@ -714,8 +1034,11 @@ impl RepoClient {
             + Send
             + 'static,
     {
-        let allow_short_getpack_history = self.knobs.allow_short_getpack_history;
-        self.command_stream(name, UNSAMPLED, |ctx, command_logger| {
+        self.command_stream(name, UNSAMPLED, |ctx, mut command_logger| {
+            let allow_short_getpack_history = ctx.feature_flags().get_or(
+                &ALLOW_SHORT_GETPACK_HISTORY,
+                self.knobs.allow_short_getpack_history,
+            );
             let undesired_path_logger =
                 try_boxstream!(UndesiredPathLogger::new(ctx.clone(), self.repo.blob_repo()));
             let undesired_path_logger = Arc::new(undesired_path_logger);
@@ -723,6 +1046,7 @@ impl RepoClient {
             // That shouldn't be a problem because requests are quite small
             let getpack_params = Arc::new(Mutex::new(vec![]));
             let repo = self.repo.blob_repo().clone();
+            let authz_repo = self.repo.clone();
 
             let lfs_params = self.lfs_params();
 
@@ -733,7 +1057,13 @@ impl RepoClient {
 
             let request_stream = move || {
                 let content_stream = {
-                    cloned!(ctx, getpack_params, lfs_params, undesired_path_logger);
+                    cloned!(
+                        ctx,
+                        getpack_params,
+                        lfs_params,
+                        undesired_path_logger,
+                        authz_repo
+                    );
 
                     async move {
                         let buffered_params = BufferedParams {
@@ -751,6 +1081,30 @@ impl RepoClient {
                             .add("getpack_paths", params.len())
                             .log_with_msg("Getpack Params", None);
 
+                        require_protected_nodes_read(
+                            &ctx,
+                            authz_repo.inner_repo(),
+                            params
+                                .iter()
+                                .flat_map(|(path, filenodes)| {
+                                    filenodes.iter().map(|filenode| {
+                                        (RepoPath::FilePath(path.clone()), *filenode)
+                                    })
+                                })
+                                .collect(),
+                        )
+                        .await?;
+                        require_hg_nodes_read(
+                            &ctx,
+                            authz_repo.inner_repo(),
+                            params
+                                .iter()
+                                .flat_map(|(_, filenodes)| filenodes.iter())
+                                .map(|filenode| filenode.into_nodehash())
+                                .collect(),
+                        )
+                        .await?;
+
                         let res = stream::iter(params.into_iter())
                             .map({
                                 cloned!(ctx, getpack_params, repo, lfs_params);
@@ -908,7 +1262,9 @@ impl RepoClient {
                     .and_then(|chunk| chunk.into_bytes())
                     .inspect({
                         cloned!(ctx);
+                        let response_recorder = command_logger.response_recorder();
                         move |bytes| {
+                            response_recorder.record(bytes);
                             let len = bytes.len() as i64;
                             ctx.perf_counters()
                                 .add_to_counter(PerfCounterType::GetpackResponseSize, len);
@@ -948,6 +1304,7 @@ impl RepoClient {
                             );
 
                             log_getpack_params_verbose(&ctx, &encoded_params);
+                            command_logger.set_shadow_args(json!(encoded_params));
                             command_logger.finalize_command(&stats);
 
                             future::ready(())
@@ -957,7 +1314,14 @@ impl RepoClient {
                     .compat()
             };
 
-            throttle_stream(&self.session, Metric::GetpackFiles, name, request_stream).boxify()
+            throttle_stream(
+                &self.session,
+                &self.logging,
+                Metric::GetpackFiles,
+                name,
+                request_stream,
+            )
+            .boxify()
         })
     }
 
@@ -1106,8 +1470,32 @@ impl RepoClient {
     }
 }
 
+/// Tell the client why a request was throttled and when it can retry, rather
+/// than just failing it, and record the advisory in scuba.
+fn report_throttled(ctx: &CoreContext, request_name: &str, reason: &RateLimitReason) {
+    let notice = reason.notice();
+    warn!(
+        ctx.logger(),
+        "Request {} was throttled: {}", request_name, notice;
+        "remote" => "true"
+    );
+
+    let mut scuba = ctx.scuba().clone();
+    scuba
+        .add("throttled_request", request_name)
+        .add("rate_limit", notice.limit.as_str())
+        .add("rate_limit_retry_after_secs", notice.retry_after.as_secs());
+    if let Some((value, limit)) = notice.usage {
+        scuba
+            .add("rate_limit_usage", value)
+            .add("rate_limit_value", limit);
+    }
+    scuba.log_with_msg("Rate limit advisory", format!("{}", reason));
+}
+
 fn throttle_stream<F, S, V>(
     session: &SessionContainer,
+    logging: &LoggingContainer,
     metric: Metric,
     request_name: &'static str,
     func: F,
@@ -1117,14 +1505,17 @@ where
     S: Stream<Item = V, Error = Error> + Send + 'static,
 {
     let session = session.clone();
+    let logging = logging.clone();
     async move {
-        session
-            .check_rate_limit(metric)
-            .await
-            .map_err(|reason| ErrorKind::RequestThrottled {
+        if let Err(reason) = session.check_rate_limit(metric).await {
+            let ctx = session.new_context(logging.logger().clone(), logging.scuba().clone());
+            report_throttled(&ctx, request_name, &reason);
+            return Err(ErrorKind::RequestThrottled {
                 request_name: request_name.into(),
                 reason,
-            })?;
+            }
+            .into());
+        }
 
         Result::<_, Error>::Ok(func())
     }
@@ -1263,6 +1654,16 @@ impl HgCommands for RepoClient {
                     }
                 }
 
+                if let Some(caps) = args.get(b"capabilities" as &[u8]) {
+                    let caps = String::from_utf8_lossy(caps);
+                    let scheme = HgNodeHashScheme::negotiate(
+                        caps.split_whitespace(),
+                        tunables().get_hg_sha256_nodes_enabled(),
+                    );
+                    self.sha256_nodes
+                        .store(scheme == HgNodeHashScheme::Sha256, Ordering::Relaxed);
+                }
+
                 future::ok(hostname)
                     .timed()
                     .map(move |(stats, res)| {
@@ -1280,10 +1681,373 @@ impl HgCommands for RepoClient {
         // TODO: directly return stream of heads
         self.command_future(ops::HEADS, UNSAMPLED, |ctx, command_logger| {
             // Heads are all the commits that has a publishing bookmarks
-            // that points to it.
-            self.get_publishing_bookmarks_maybe_stale(ctx)
-                .map(|map| map.into_iter().map(|(_, hg_cs_id)| hg_cs_id).collect())
+            // that points to it.  Repos may opt in to also returning the
+            // targets of scratch bookmarks, of which at most a page is
+            // returned so that the response stays bounded.  Clients that
+            // need them all should use `headspaginated`.
+            let include_scratch = ctx.feature_flags().get_or(
+                &LEGACY_HEADS_INCLUDE_SCRATCH,
+                self.knobs.legacy_heads_include_scratch,
+            );
+            let repo = self.repo.clone();
+            let blobrepo = self.repo.blob_repo().clone();
+            let publishing = self.get_publishing_bookmarks_maybe_stale(ctx.clone());
+            async move {
+                let mut heads: HashSet<_> = publishing
+                    .compat()
+                    .await?
+                    .into_iter()
+                    .map(|(_, hg_cs_id)| hg_cs_id)
+                    .collect();
+                if include_scratch {
+                    // Private scratch bookmarks of other users are left out.
+                    let authz = AuthorizationContext::new(&ctx);
+                    let scratch = blobrepo
+                        .bookmarks()
+                        .list(
+                            ctx.clone(),
+                            Freshness::MaybeStale,
+                            &BookmarkPrefix::empty(),
+                            &[BookmarkKind::Scratch],
+                            &BookmarkPagination::FromStart,
+                            HEADS_PAGE_MAX,
+                        )
+                        .try_filter(|(bookmark, _)| {
+                            future::ready(
+                                authz
+                                    .check_scratch_bookmark_read(
+                                        &ctx,
+                                        repo.inner_repo(),
+                                        &bookmark.name,
+                                    )
+                                    .is_permitted(),
+                            )
+                        })
+                        .map_ok(|(bookmark, cs_id)| (bookmark.name, cs_id));
+                    let scratch = to_hg_bookmark_stream(&blobrepo, &ctx, scratch)
+                        .map_ok(|(_, hg_cs_id)| hg_cs_id)
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                    heads.extend(scratch);
+                }
+                Ok(heads)
+            }
+            .timeout(default_timeout())
+            .flatten_err()
+            .timed()
+            .map(move |(stats, res)| {
+                command_logger.without_wireproto().finalize_command(&stats);
+                res
+            })
+            .boxed()
+            .compat()
+        })
+    }
+
+    // @wireprotocommand('headspaginated', '*')
+    fn headspaginated(&self, args: HeadsPaginatedArgs) -> HgCommandRes<HeadsPage> {
+        self.command_future(ops::HEADSPAGINATED, UNSAMPLED, |ctx, command_logger| {
+            let repo = self.repo.clone();
+            let blobrepo = self.repo.blob_repo().clone();
+            let session_bookmarks_cache = self.session_bookmarks_cache.clone();
+            async move {
+                let prefix = BookmarkPrefix::new(&args.prefix)?;
+                let pagination = match args.after {
+                    Some(after) => BookmarkPagination::After(BookmarkName::new(after)?),
+                    None => BookmarkPagination::FromStart,
+                };
+                let limit = args
+                    .limit
+                    .unwrap_or(HEADS_PAGE_MAX)
+                    .clamp(1, HEADS_PAGE_MAX);
+                let bookmarks = blobrepo
+                    .bookmarks()
+                    .list(
+                        ctx.clone(),
+                        Freshness::MaybeStale,
+                        &prefix,
+                        BookmarkKind::ALL,
+                        &pagination,
+                        limit,
+                    )
+                    .try_collect::<Vec<_>>()
+                    .await?;
+
+                // The cursor is the last bookmark considered, not the last one
+                // returned, so that filtering by recency doesn't end the
+                // pagination early.
+                let next = if bookmarks.len() as u64 == limit {
+                    bookmarks
+                        .last()
+                        .map(|(bookmark, _)| bookmark.name.to_string())
+                } else {
+                    None
+                };
+
+                // Private scratch bookmarks of other users are left out.
+                let authz = AuthorizationContext::new(&ctx);
+                let bookmarks = bookmarks.into_iter().filter(|(bookmark, _)| {
+                    bookmark.kind != BookmarkKind::Scratch
+                        || authz
+                            .check_scratch_bookmark_read(&ctx, repo.inner_repo(), &bookmark.name)
+                            .is_permitted()
+                });
+
+                let since = args.since;
+                let heads = stream::iter(bookmarks)
+                    .map(|(bookmark, cs_id)| {
+                        cloned!(ctx, blobrepo, session_bookmarks_cache);
+                        async move {
+                            // Bookmarks whose moves are published after a
+                            // delay are shown where this session sees them.
+                            let cs_id = match session_bookmarks_cache
+                                .published_position(&ctx, &bookmark.name, cs_id)
+                                .await?
+                            {
+                                Some(cs_id) => cs_id,
+                                None => return Ok(None),
+                            };
+                            if let Some(since) = since {
+                                let bcs = cs_id.load(&ctx, blobrepo.blobstore()).await?;
+                                if bcs.author_date().timestamp_secs() < since as i64 {
+                                    return Ok(None);
+                                }
+                            }
+                            let hg_cs_id = blobrepo.derive_hg_changeset(&ctx, cs_id).await?;
+                            Ok(Some((bookmark.name.to_string(), hg_cs_id)))
+                        }
+                    })
+                    .buffered(100)
+                    .try_filter_map(|head| future::ready(Ok(head)))
+                    .try_collect()
+                    .await?;
+
+                Ok(HeadsPage { heads, next })
+            }
+            .timeout(default_timeout())
+            .flatten_err()
+            .timed()
+            .map(move |(stats, res)| {
+                command_logger.without_wireproto().finalize_command(&stats);
+                res
+            })
+            .boxed()
+            .compat()
+        })
+    }
+
+    // @wireprotocommand('getmanifestpage', '*')
+    fn getmanifestpage(&self, args: GetManifestPageArgs) -> HgCommandRes<ManifestPage> {
+        self.command_future(ops::GETMANIFESTPAGE, UNSAMPLED, |ctx, command_logger| {
+            let repo = self.repo.clone();
+            async move {
+                let path = match args.rootdir {
+                    Some(rootdir) => RepoPath::DirectoryPath(rootdir),
+                    None => RepoPath::RootPath,
+                };
+                require_protected_nodes_read(
+                    &ctx,
+                    repo.inner_repo(),
+                    vec![(path, HgFileNodeId::new(args.mfnode.into_nodehash()))],
+                )
+                .await?;
+                let limit = args
+                    .limit
+                    .unwrap_or(MANIFEST_PAGE_MAX)
+                    .clamp(1, MANIFEST_PAGE_MAX) as usize;
+                let manifest = args.mfnode.load(&ctx, repo.blob_repo().blobstore()).await?;
+                manifest_page(&manifest, args.after.as_deref(), limit)
+            }
+            .timeout(default_timeout())
+            .flatten_err()
+            .timed()
+            .map(move |(stats, res)| {
+                command_logger.without_wireproto().finalize_command(&stats);
+                res
+            })
+            .boxed()
+            .compat()
+        })
+    }
+
+    // @wireprotocommand('prefetchhints', '*')
+    fn prefetchhints(&self, args: PrefetchHintsArgs) -> HgCommandRes<usize> {
+        self.command_future(ops::PREFETCHHINTS, UNSAMPLED, |ctx, mut command_logger| {
+            let hints = PrefetchHints::new(args);
+            command_logger.add_trimmed_scuba_extra(
+                "command_args",
+                &json!({
+                    "commits": hints.commit_count(),
+                    "directories": hints.directory_count(),
+                }),
+            );
+            // Prefetching is best-effort, so hints are dropped rather than
+            // queued when too many requests are already being prefetched.
+            let permit = if tunables().get_disable_prefetch_hints() || hints.commit_count() == 0 {
+                None
+            } else {
+                let permit = try_reserve_prefetch();
+                if permit.is_none() {
+                    STATS::prefetch_hints_dropped.add_value(1);
+                }
+                permit
+            };
+            let accepted = match permit {
+                Some(_) => hints.commit_count(),
+                None => 0,
+            };
+
+            if let Some(permit) = permit {
+                // The client doesn't wait for the data to be prefetched, so
+                // prefetching continues after the command has completed.  The
+                // work is charged to the client's rate limits.
+                let repo = self.repo.clone();
+                let mut hints = hints;
+                tokio::task::spawn(async move {
+                    let _permit = permit;
+                    if let Err(reason) =
+                        ctx.session().check_rate_limit(Metric::TotalManifests).await
+                    {
+                        report_throttled(&ctx, ops::PREFETCHHINTS, &reason);
+                        STATS::prefetch_hints_dropped.add_value(1);
+                        return;
+                    }
+                    let unreadable = hints.retain_readable(&ctx, &repo).await;
+                    STATS::prefetch_hints_skipped_commits.add_value(unreadable as i64);
+                    match tokio::time::timeout(
+                        PREFETCH_HINTS_TIMEOUT,
+                        hints.prefetch(&ctx, repo.blob_repo()),
+                    )
+                    .await
+                    {
+                        Ok(stats) => {
+                            ctx.session()
+                                .bump_load(Metric::TotalManifests, stats.trees as f64);
+                            STATS::prefetch_hints_commits.add_value(stats.commits as i64);
+                            STATS::prefetch_hints_trees.add_value(stats.trees as i64);
+                            STATS::prefetch_hints_skipped_commits
+                                .add_value(stats.skipped_commits as i64);
+                            debug!(
+                                ctx.logger(),
+                                "Prefetched {} trees of {} commits, skipped {} commits",
+                                stats.trees,
+                                stats.commits,
+                                stats.skipped_commits + unreadable,
+                            );
+                        }
+                        Err(_) => {
+                            STATS::prefetch_hints_timeouts.add_value(1);
+                            debug!(ctx.logger(), "Prefetching hints timed out");
+                        }
+                    }
+                });
+            }
+
+            future::ok(accepted)
+                .timed()
+                .map(move |(stats, res)| {
+                    command_logger.without_wireproto().finalize_command(&stats);
+                    res
+                })
                 .compat()
+        })
+    }
+
+    // @wireprotocommand('clonebundles')
+    fn clonebundles(&self) -> HgCommandRes<BytesOld> {
+        self.command_future(ops::CLONEBUNDLES, UNSAMPLED, |ctx, command_logger| {
+            let snapshot_bundles = self.repo.inner_repo().snapshot_bundles_arc();
+            async move {
+                let manifest = snapshot_bundles.clonebundles_manifest(&ctx).await?;
+                Ok(bytes_ext::copy_from_new(manifest))
+            }
+            .timeout(default_timeout())
+            .flatten_err()
+            .timed()
+            .map(move |(stats, res)| {
+                command_logger.without_wireproto().finalize_command(&stats);
+                res
+            })
+            .boxed()
+            .compat()
+        })
+    }
+
+    // @wireprotocommand('discoverysample', '*')
+    fn discoverysample(&self, args: DiscoverySampleArgs) -> HgCommandRes<DiscoverySample> {
+        self.command_future(
+            ops::DISCOVERYSAMPLE,
+            UNSAMPLED,
+            |ctx, mut command_logger| {
+                command_logger.add_trimmed_scuba_extra(
+                    "command_args",
+                    &json!({
+                        "heads_count": args.heads.len(),
+                        "size": args.size,
+                    }),
+                );
+                let blobrepo = self.repo.blob_repo().clone();
+                let phases = self.repo.inner_repo().phases_arc();
+                let publishing = self.get_publishing_bookmarks_maybe_stale(ctx.clone());
+                async move {
+                    let size = args
+                        .size
+                        .unwrap_or(discovery::DISCOVERY_SAMPLE_DEFAULT_SIZE)
+                        .clamp(1, discovery::DISCOVERY_SAMPLE_MAX_SIZE);
+
+                    // The client's heads are known if they are public, as
+                    // for `known`.
+                    let client_heads = blobrepo
+                        .get_hg_bonsai_mapping(ctx.clone(), args.heads.clone())
+                        .await?;
+                    let public = phases
+                        .get_public(
+                            &ctx,
+                            client_heads.iter().map(|(_, cs_id)| *cs_id).collect(),
+                            false,
+                        )
+                        .await?;
+                    let known_heads: HashSet<_> = client_heads
+                        .into_iter()
+                        .filter(|(_, cs_id)| public.contains(cs_id))
+                        .map(|(hg_cs_id, _)| hg_cs_id)
+                        .collect();
+                    let known = args
+                        .heads
+                        .iter()
+                        .map(|head| known_heads.contains(head))
+                        .collect();
+
+                    let heads: BTreeSet<_> = publishing.compat().await?.into_values().collect();
+                    let heads: Vec<_> = heads.into_iter().collect();
+                    let head_cs_ids: Vec<_> = blobrepo
+                        .get_hg_bonsai_mapping(ctx.clone(), heads.clone())
+                        .await?
+                        .into_iter()
+                        .map(|(_, cs_id)| cs_id)
+                        .collect();
+                    let sample = discovery::sample_ancestors(
+                        &ctx,
+                        blobrepo.changeset_fetcher(),
+                        &head_cs_ids,
+                        size,
+                    )
+                    .await?;
+                    let sample = stream::iter(sample)
+                        .map(|cs_id| {
+                            cloned!(ctx, blobrepo);
+                            async move { blobrepo.derive_hg_changeset(&ctx, cs_id).await }
+                        })
+                        .buffered(100)
+                        .try_collect()
+                        .await?;
+
+                    Ok(DiscoverySample {
+                        known,
+                        heads,
+                        sample,
+                    })
+                }
                 .timeout(default_timeout())
                 .flatten_err()
                 .timed()
@@ -1293,7 +2057,8 @@ impl HgCommands for RepoClient {
                 })
                 .boxed()
                 .compat()
-        })
+            },
+        )
     }
 
     // @wireprotocommand('lookup', 'key')
@@ -1342,8 +2107,11 @@ impl HgCommands for RepoClient {
         const MAX_NUMBER_OF_SUGGESTIONS_TO_FETCH: usize = 10;
 
         let maybe_git_lookup = parse_git_lookup(&key);
+        let maybe_sha256_lookup =
+            parse_sha256_lookup(&key, self.sha256_nodes.load(Ordering::Relaxed));
         self.command_future(ops::LOOKUP, UNSAMPLED, |ctx, command_logger| {
             let repo = self.repo.blob_repo().clone();
+            let mononoke_repo = self.repo.clone();
 
             // Resolves changeset or set of suggestions from the key (full hex hash or a prefix) if exist.
             // Note: `get_many_hg_by_prefix` works for the full hex hashes well but
@@ -1396,6 +2164,7 @@ impl HgCommands for RepoClient {
                     cloned!(ctx, repo);
                     move |resolved_cids| {
                         use HgChangesetIdsResolvedFromPrefix::*;
+                        let inner_repo = mononoke_repo.inner_repo();
 
                         // Describing the priority relative to bookmark presence for the key.
                         enum LookupOutcome {
@@ -1404,9 +2173,21 @@ impl HgCommands for RepoClient {
                         }
 
                         let outcome = match resolved_cids {
-                            Single(csid) => {
-                                LookupOutcome::HighPriority(generate_changeset_resp_buf(csid))
-                            }
+                            Single(csid) => LookupOutcome::HighPriority({
+                                cloned!(ctx, mononoke_repo);
+                                async move {
+                                    require_hg_changesets_read(
+                                        &ctx,
+                                        mononoke_repo.inner_repo(),
+                                        vec![csid],
+                                    )
+                                    .await
+                                }
+                                .boxed()
+                                .compat()
+                                .and_then(move |()| generate_changeset_resp_buf(csid))
+                                .boxify()
+                            }),
                             Multiple(suggestion_cids) => {
                                 LookupOutcome::LowPriority(generate_suggestions_resp_buf(
                                     ctx.clone(),
@@ -1432,6 +2213,14 @@ impl HgCommands for RepoClient {
                             ),
                         };
 
+                        // Private scratch bookmarks of other users are treated
+                        // as if they don't exist.
+                        let bookmark = bookmark.filter(|bookmark| {
+                            AuthorizationContext::new(&ctx)
+                                .check_scratch_bookmark_read(&ctx, inner_repo, bookmark)
+                                .is_permitted()
+                        });
+
                         match (outcome, bookmark) {
                             (LookupOutcome::HighPriority(res), _) => res,
                             (LookupOutcome::LowPriority(res), Some(bookmark)) => {
@@ -1454,6 +2243,11 @@ impl HgCommands for RepoClient {
                 .boxify();
 
             async move {
+                if let Some(sha256_lookup) = maybe_sha256_lookup {
+                    if let Some(res) = sha256_lookup.lookup(&ctx, &repo).await? {
+                        return Ok(res);
+                    }
+                }
                 if let Some(git_lookup) = maybe_git_lookup {
                     if let Some(res) = git_lookup.lookup(&ctx, &repo).await? {
                         return Ok(res);
@@ -1526,26 +2320,57 @@ impl HgCommands for RepoClient {
 
     // @wireprotocommand('getbundle', '*')
     fn getbundle(&self, args: GetbundleArgs) -> BoxStream<BytesOld, Error> {
-        self.command_stream(ops::GETBUNDLE, UNSAMPLED, |ctx, command_logger| {
-            let s = self
-                .create_bundle(ctx, args)
-                .compat()
-                .whole_stream_timeout(getbundle_timeout())
-                .yield_periodically()
-                .flatten_err()
-                .timed({
-                    move |stats| {
-                        STATS::getbundle_ms
-                            .add_value(stats.completion_time.as_millis_unchecked() as i64);
-                        command_logger.finalize_command(&stats);
-                        future::ready(())
-                    }
-                })
-                .boxed()
-                .compat()
-                .boxify();
+        self.command_stream(ops::GETBUNDLE, UNSAMPLED, |ctx, mut command_logger| {
+            command_logger.set_shadow_args(json!({
+                "heads": args.heads.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "common": args.common.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "bundlecaps": args
+                    .bundlecaps
+                    .iter()
+                    .map(|cap| String::from_utf8_lossy(cap).into_owned())
+                    .collect::<Vec<_>>(),
+                "listkeys": args
+                    .listkeys
+                    .iter()
+                    .map(|key| String::from_utf8_lossy(key).into_owned())
+                    .collect::<Vec<_>>(),
+                "phases": args.phases,
+            }));
+            let response_recorder = command_logger.response_recorder();
+            let bulkheads = self.bulkheads.clone();
+            let bundle = self.create_bundle(ctx.clone(), args).compat();
+            let s = async move {
+                // Hold the permit until the whole bundle has been sent.
+                let permit = bulkheads.get(BulkheadOp::Getbundle).acquire(&ctx).await;
+                Ok::<_, Error>(bundle.map_ok(move |bytes| {
+                    let _permit = &permit;
+                    bytes
+                }))
+            }
+            .try_flatten_stream()
+            .whole_stream_timeout(getbundle_timeout())
+            .yield_periodically()
+            .flatten_err()
+            .inspect_ok(move |bytes| response_recorder.record(bytes))
+            .timed({
+                move |stats| {
+                    STATS::getbundle_ms
+                        .add_value(stats.completion_time.as_millis_unchecked() as i64);
+                    command_logger.finalize_command(&stats);
+                    future::ready(())
+                }
+            })
+            .boxed()
+            .compat()
+            .boxify();
 
-            throttle_stream(&self.session, Metric::Commits, ops::GETBUNDLE, move || s)
+            throttle_stream(
+                &self.session,
+                &self.logging,
+                Metric::Commits,
+                ops::GETBUNDLE,
+                move || s,
+            )
         })
     }
 
@@ -1555,6 +2380,9 @@ impl HgCommands for RepoClient {
             let mut res = HashMap::new();
             let mut caps = wireprotocaps();
             caps.push(format!("bundle2={}", bundle2caps()));
+            if tunables().get_hg_sha256_nodes_enabled() {
+                caps.push(SHA256_NODES_CAPABILITY.to_string());
+            }
             res.insert("capabilities".to_string(), caps);
 
             future::ok(res)
@@ -1569,11 +2397,17 @@ impl HgCommands for RepoClient {
         })
     }
 
-    // @wireprotocommand('listkeys', 'namespace')
-    fn listkeys(&self, namespace: String) -> HgCommandRes<HashMap<Vec<u8>, Vec<u8>>> {
-        if namespace == "bookmarks" {
+    // @wireprotocommand('listkeys', 'namespace *')
+    fn listkeys(
+        &self,
+        namespace: String,
+        prefix: String,
+        limit: Option<u64>,
+    ) -> HgCommandRes<HashMap<Vec<u8>, Vec<u8>>> {
+        if let Some(provider) = self.listkeys_registry.provider(&namespace).cloned() {
             self.command_future(ops::LISTKEYS, UNSAMPLED, |ctx, command_logger| {
-                self.get_pull_default_bookmarks_maybe_stale(ctx)
+                let filter = ListKeysFilter { prefix, limit };
+                self.list_keys(ctx, namespace, provider, filter)
                     .compat()
                     .timed()
                     .map(move |(stats, res)| {
@@ -1613,16 +2447,26 @@ impl HgCommands for RepoClient {
         self.command_future(ops::LISTKEYSPATTERNS, UNSAMPLED, |ctx, command_logger| {
             let max = self.repo.inner_repo().repo_config().list_keys_patterns_max;
             let session_bookmarks_cache = self.session_bookmarks_cache.clone();
+            let repo = self.repo.clone();
 
             let queries = patterns.into_iter().map(move |pattern| {
-                cloned!(ctx, session_bookmarks_cache);
+                cloned!(ctx, session_bookmarks_cache, repo);
                 async move {
+                    // Private scratch bookmarks of other users are left out.
+                    let authz = AuthorizationContext::new(&ctx);
+                    let readable = |bookmark: &BookmarkName| {
+                        authz
+                            .check_scratch_bookmark_read(&ctx, repo.inner_repo(), bookmark)
+                            .is_permitted()
+                    };
+
                     if pattern.ends_with('*') {
                         // prefix match
                         let prefix = BookmarkPrefix::new(&pattern[..pattern.len() - 1])?;
 
                         let bookmarks = session_bookmarks_cache
                             .get_bookmarks_by_prefix(&ctx, &prefix, max).await?
+                            .try_filter(|(bookmark, _)| future::ready(readable(bookmark)))
                             .map_ok(|(bookmark, cs_id)| {
                                 (bookmark.to_string(), cs_id)
                             })
@@ -1639,6 +2483,9 @@ impl HgCommands for RepoClient {
                     } else {
                         // literal match
                         let bookmark = BookmarkName::new(&pattern)?;
+                        if !readable(&bookmark) {
+                            return Ok(Vec::new());
+                        }
 
                         let cs_id = session_bookmarks_cache.get_bookmark(ctx, bookmark).await?;
                         match cs_id {
@@ -1667,11 +2514,18 @@ impl HgCommands for RepoClient {
         })
     }
 
+    fn should_preserve_raw_bundle2(&self) -> bool {
+        tunables()
+            .get_by_repo_preserve_raw_bundle2(self.repo.inner_repo().repo_identity().name())
+            .unwrap_or(false)
+    }
+
     // @wireprotocommand('unbundle')
     fn unbundle(
         &self,
         _heads: Vec<String>,
         stream: BoxStream<Bundle2Item<'static>, Error>,
+        maybe_full_content: Option<Arc<Mutex<BytesMut>>>,
         respondlightly: Option<bool>,
         maybereplaydata: Option<String>,
     ) -> HgCommandRes<BytesOld> {
@@ -1690,8 +2544,29 @@ impl HgCommands for RepoClient {
         repoclient
             .command_future(ops::UNBUNDLE, UNSAMPLED, move |ctx, command_logger| {
                 async move {
+                    let _permit = client
+                        .bulkheads
+                        .get(BulkheadOp::Unbundle)
+                        .acquire(&ctx)
+                        .await;
                     let repo = client.repo.inner_repo();
 
+                    // Reject pushes from clients that have exceeded their
+                    // ingress limit before reading any of the bundle.
+                    ctx.session()
+                        .check_rate_limit(Metric::IngressBytes)
+                        .await
+                        .map_err(|reason| {
+                            report_throttled(&ctx, ops::UNBUNDLE, &reason);
+                            BundleResolverError::Error(
+                                ErrorKind::RequestThrottled {
+                                    request_name: ops::UNBUNDLE.into(),
+                                    reason,
+                                }
+                                .into(),
+                            )
+                        })?;
+
                     // To use unbundle wireproto command the user needs at least all-repo `draft` permission.
                     // This is overkill - we could check more granular permissions but wireproto is deprecated and
                     // it doesn't seem worth auditing each codepath there so let's use the big hammer!
@@ -1708,22 +2583,41 @@ impl HgCommands for RepoClient {
                     let maybe_backup_repo_source = client.maybe_backup_repo_source.clone();
 
                     let pushrebase_flags = pushrebase_params.flags.clone();
-                    let action = unbundle::resolve(
+                    let path_policy = PathPolicy::new(&repo.repo_config().path_policy);
+                    let action = match unbundle::resolve(
                         &ctx,
                         repo.as_blob_repo(),
                         infinitepush_writes_allowed,
                         stream.compat().boxed(),
+                        client.repo.part_handlers.clone(),
                         pure_push_allowed,
                         pushrebase_flags,
+                        path_policy,
                         maybe_backup_repo_source,
                     )
-                    .await?;
+                    .await
+                    {
+                        Ok(action) => action,
+                        Err(err) => {
+                            maybe_preserve_raw_bundle2(
+                                &ctx,
+                                repo,
+                                maybe_full_content,
+                                Vec::new(),
+                                Some(&err),
+                            );
+                            return Err(err);
+                        }
+                    };
+                    let mut landed = action.uploaded_changesets();
 
                     let unbundle_future = async {
                         maybe_validate_pushed_bonsais(&ctx, repo.as_blob_repo(), &maybereplaydata)
                             .await?;
 
-                        match client.maybe_get_pushredirector_for_action(&ctx, &action)? {
+                        let response = match client
+                            .maybe_get_pushredirector_for_action(&ctx, &action)?
+                        {
                             Some(push_redirector) => {
                                 // Push-redirection will cause
                                 // hooks to be run in the large
@@ -1767,19 +2661,29 @@ impl HgCommands for RepoClient {
                                 )
                                 .await?
                             }
-                        }
-                        .generate_bytes(
-                            &ctx,
-                            repo.as_blob_repo(),
-                            pushrebase_params,
-                            &lca_hint,
-                            &lfs_params,
-                            respondlightly,
-                        )
-                        .await
+                        };
+                        landed = landed_changesets(&response, std::mem::take(&mut landed));
+                        response
+                            .generate_bytes(
+                                &ctx,
+                                repo.as_blob_repo(),
+                                pushrebase_params,
+                                &lca_hint,
+                                &lfs_params,
+                                respondlightly,
+                            )
+                            .await
                     };
 
-                    let response = unbundle_future.await?;
+                    let response = unbundle_future.await;
+                    maybe_preserve_raw_bundle2(
+                        &ctx,
+                        repo,
+                        maybe_full_content,
+                        landed,
+                        response.as_ref().err(),
+                    );
+                    let response = response?;
 
                     // There's a bookmarks race condition where the client requests bookmarks after we return commits to it,
                     // and is then confused because the bookmarks refer to commits that it doesn't know about. Ultimately,
@@ -1881,6 +2785,8 @@ impl HgCommands for RepoClient {
 
                 log_gettreepack_params_verbose(&ctx, &params);
 
+                command_logger.set_shadow_args(args.clone());
+                let response_recorder = command_logger.response_recorder();
                 let s = self
                     .gettreepack_untimed(ctx.clone(), params)
                     .compat()
@@ -1890,6 +2796,7 @@ impl HgCommands for RepoClient {
                     .inspect_ok({
                         cloned!(ctx);
                         move |bytes| {
+                            response_recorder.record(bytes);
                             ctx.perf_counters().add_to_counter(
                                 PerfCounterType::GettreepackResponseSize,
                                 bytes.len() as i64,
@@ -1916,6 +2823,7 @@ impl HgCommands for RepoClient {
 
                 throttle_stream(
                     &self.session,
+                    &self.logging,
                     Metric::TotalManifests,
                     ops::GETTREEPACK,
                     move || s,
@@ -1926,127 +2834,134 @@ impl HgCommands for RepoClient {
 
     // @wireprotocommand('stream_out_shallow')
     fn stream_out_shallow(&self, tag: Option<String>) -> BoxStream<BytesOld, Error> {
-        self.command_stream(ops::STREAMOUTSHALLOW, UNSAMPLED, |ctx, command_logger| {
-            let streaming_clone = self.repo.inner_repo().streaming_clone_arc();
+        self.command_stream(
+            ops::STREAMOUTSHALLOW,
+            UNSAMPLED,
+            |ctx, mut command_logger| {
+                command_logger.set_shadow_args(json!({ "tag": tag }));
+                let response_recorder = command_logger.response_recorder();
+                let streaming_clone = self.repo.inner_repo().streaming_clone_arc();
 
-            let stream = {
-                cloned!(ctx);
-                async move {
-                    let changelog = streaming_clone
-                        .fetch_changelog(ctx.clone(), tag.as_deref())
-                        .await?;
+                let stream = {
+                    cloned!(ctx);
+                    async move {
+                        let changelog = streaming_clone
+                            .fetch_changelog(ctx.clone(), tag.as_deref())
+                            .await?;
 
-                    let data_blobs = changelog
-                        .data_blobs
-                        .into_iter()
-                        .map(|fut| {
-                            cloned!(ctx);
-                            async move {
-                                let (stats, res) = fut.timed().await;
-                                ctx.perf_counters().add_to_counter(
-                                    PerfCounterType::SumManifoldPollTime,
-                                    stats.poll_time.as_nanos_unchecked() as i64,
-                                );
-                                if let Ok(bytes) = res.as_ref() {
+                        let data_blobs = changelog
+                            .data_blobs
+                            .into_iter()
+                            .map(|fut| {
+                                cloned!(ctx);
+                                async move {
+                                    let (stats, res) = fut.timed().await;
                                     ctx.perf_counters().add_to_counter(
-                                        PerfCounterType::BytesSent,
-                                        bytes.len() as i64,
-                                    )
+                                        PerfCounterType::SumManifoldPollTime,
+                                        stats.poll_time.as_nanos_unchecked() as i64,
+                                    );
+                                    if let Ok(bytes) = res.as_ref() {
+                                        ctx.perf_counters().add_to_counter(
+                                            PerfCounterType::BytesSent,
+                                            bytes.len() as i64,
+                                        )
+                                    }
+                                    res
                                 }
-                                res
-                            }
-                            .boxed()
-                        })
-                        .collect();
+                                .boxed()
+                            })
+                            .collect();
 
-                    let index_blobs = changelog
-                        .index_blobs
-                        .into_iter()
-                        .map(|fut| {
-                            cloned!(ctx);
-                            async move {
-                                let (stats, res) = fut.timed().await;
-                                ctx.perf_counters().add_to_counter(
-                                    PerfCounterType::SumManifoldPollTime,
-                                    stats.poll_time.as_nanos_unchecked() as i64,
-                                );
-                                if let Ok(bytes) = res.as_ref() {
+                        let index_blobs = changelog
+                            .index_blobs
+                            .into_iter()
+                            .map(|fut| {
+                                cloned!(ctx);
+                                async move {
+                                    let (stats, res) = fut.timed().await;
                                     ctx.perf_counters().add_to_counter(
-                                        PerfCounterType::BytesSent,
-                                        bytes.len() as i64,
-                                    )
+                                        PerfCounterType::SumManifoldPollTime,
+                                        stats.poll_time.as_nanos_unchecked() as i64,
+                                    );
+                                    if let Ok(bytes) = res.as_ref() {
+                                        ctx.perf_counters().add_to_counter(
+                                            PerfCounterType::BytesSent,
+                                            bytes.len() as i64,
+                                        )
+                                    }
+                                    res
                                 }
-                                res
-                            }
-                            .boxed()
-                        })
-                        .collect();
+                                .boxed()
+                            })
+                            .collect();
 
-                    let changelog = RevlogStreamingChunks {
-                        data_size: changelog.data_size,
-                        index_size: changelog.index_size,
-                        data_blobs,
-                        index_blobs,
-                    };
+                        let changelog = RevlogStreamingChunks {
+                            data_size: changelog.data_size,
+                            index_size: changelog.index_size,
+                            data_blobs,
+                            index_blobs,
+                        };
 
-                    debug!(
-                        ctx.logger(),
-                        "streaming changelog {} index bytes, {} data bytes",
-                        changelog.index_size,
-                        changelog.data_size
-                    );
+                        debug!(
+                            ctx.logger(),
+                            "streaming changelog {} index bytes, {} data bytes",
+                            changelog.index_size,
+                            changelog.data_size
+                        );
 
-                    let mut response_header = Vec::new();
-                    // Send OK response.
-                    response_header.push(Bytes::from_static(b"0\n"));
-                    // send header.
-                    let total_size = changelog.index_size + changelog.data_size;
-                    let file_count = 2;
-                    let header = format!("{} {}\n", file_count, total_size);
-                    response_header.push(header.into_bytes().into());
-                    let response = stream::iter(response_header.into_iter().map(Ok));
-
-                    fn build_file_stream(
-                        name: &str,
-                        size: usize,
-                        data: Vec<futures::future::BoxFuture<'static, Result<Bytes, Error>>>,
-                    ) -> impl futures::stream::Stream<Item = Result<Bytes, Error>> + Send
-                    {
-                        let header = format!("{}\0{}\n", name, size);
+                        let mut response_header = Vec::new();
+                        // Send OK response.
+                        response_header.push(Bytes::from_static(b"0\n"));
+                        // send header.
+                        let total_size = changelog.index_size + changelog.data_size;
+                        let file_count = 2;
+                        let header = format!("{} {}\n", file_count, total_size);
+                        response_header.push(header.into_bytes().into());
+                        let response = stream::iter(response_header.into_iter().map(Ok));
+
+                        fn build_file_stream(
+                            name: &str,
+                            size: usize,
+                            data: Vec<futures::future::BoxFuture<'static, Result<Bytes, Error>>>,
+                        ) -> impl futures::stream::Stream<Item = Result<Bytes, Error>> + Send
+                        {
+                            let header = format!("{}\0{}\n", name, size);
 
-                        stream::once(future::ready(Ok(header.into_bytes().into())))
-                            .chain(stream::iter(data.into_iter()).buffered(100))
-                    }
+                            stream::once(future::ready(Ok(header.into_bytes().into())))
+                                .chain(stream::iter(data.into_iter()).buffered(100))
+                        }
 
-                    let res = response
-                        .chain(build_file_stream(
-                            "00changelog.i",
-                            changelog.index_size,
-                            changelog.index_blobs,
-                        ))
-                        .chain(build_file_stream(
-                            "00changelog.d",
-                            changelog.data_size,
-                            changelog.data_blobs,
-                        ));
+                        let res = response
+                            .chain(build_file_stream(
+                                "00changelog.i",
+                                changelog.index_size,
+                                changelog.index_blobs,
+                            ))
+                            .chain(build_file_stream(
+                                "00changelog.d",
+                                changelog.data_size,
+                                changelog.data_blobs,
+                            ));
 
-                    Ok(res)
+                        Ok(res)
+                    }
                 }
-            }
-            .try_flatten_stream();
+                .try_flatten_stream();
 
-            stream
-                .whole_stream_timeout(clone_timeout())
-                .yield_periodically()
-                .flatten_err()
-                .map_ok(bytes_ext::copy_from_new)
-                .timed(|stats| {
-                    command_logger.finalize_command(&stats);
-                    future::ready(())
-                })
-                .boxed()
-                .compat()
-        })
+                stream
+                    .whole_stream_timeout(clone_timeout())
+                    .yield_periodically()
+                    .flatten_err()
+                    .inspect_ok(move |bytes| response_recorder.record(bytes))
+                    .map_ok(bytes_ext::copy_from_new)
+                    .timed(|stats| {
+                        command_logger.finalize_command(&stats);
+                        future::ready(())
+                    })
+                    .boxed()
+                    .compat()
+            },
+        )
     }
 
     // @wireprotocommand('getpackv1')
@@ -2091,7 +3006,9 @@ impl HgCommands for RepoClient {
     fn getcommitdata(&self, nodes: Vec<HgChangesetId>) -> BoxStream<BytesOld, Error> {
         self.command_stream(ops::GETCOMMITDATA, UNSAMPLED, |ctx, mut command_logger| {
             let args = json!(nodes);
+            command_logger.set_shadow_args(args.clone());
             let blobrepo = self.repo.blob_repo().clone();
+            let repo = self.repo.clone();
             ctx.scuba()
                 .clone()
                 .add("getcommitdata_nodes", nodes.len())
@@ -2101,10 +3018,17 @@ impl HgCommands for RepoClient {
                 .map({
                     cloned!(ctx, blobrepo);
                     move |hg_cs_id| {
-                        cloned!(ctx, blobrepo, hg_cs_id);
+                        cloned!(ctx, blobrepo, hg_cs_id, repo);
                         async move {
+                            require_hg_changesets_read(&ctx, repo.inner_repo(), vec![hg_cs_id])
+                                .await?;
                             let revlog_cs =
                                 RevlogChangeset::load(&ctx, blobrepo.blobstore(), hg_cs_id).await?;
+                            AuthorizationContext::new(&ctx).require_protected_paths_read(
+                                &ctx,
+                                repo.inner_repo(),
+                                revlog_cs.files(),
+                            )?;
                             let bytes = serialize_getcommitdata(hg_cs_id, revlog_cs)?;
                             Result::<_, Error>::Ok(bytes)
                         }
@@ -2113,7 +3037,9 @@ impl HgCommands for RepoClient {
                 .buffered(100)
                 .inspect_ok({
                     cloned!(ctx);
+                    let response_recorder = command_logger.response_recorder();
                     move |bytes| {
+                        response_recorder.record(bytes);
                         ctx.perf_counters().add_to_counter(
                             PerfCounterType::GetcommitdataResponseSize,
                             bytes.len() as i64,
@@ -2140,6 +3066,7 @@ impl HgCommands for RepoClient {
 
             throttle_stream(
                 &self.session,
+                &self.logging,
                 Metric::Commits,
                 ops::GETCOMMITDATA,
                 move || s,
@@ -2148,6 +3075,145 @@ impl HgCommands for RepoClient {
     }
 }
 
+/// Require that the caller may read the given commits, which may have been
+/// pushed to private scratch bookmarks.
+async fn require_hg_changesets_read(
+    ctx: &CoreContext,
+    repo: &InnerRepo,
+    hg_cs_ids: Vec<HgChangesetId>,
+) -> Result<(), Error> {
+    if repo.repo_config().infinitepush.private_namespace.is_none() {
+        return Ok(());
+    }
+    let authz = AuthorizationContext::new(ctx);
+    let cs_ids = repo
+        .blob_repo
+        .get_hg_bonsai_mapping(ctx.clone(), hg_cs_ids)
+        .await?;
+    future::try_join_all(
+        cs_ids
+            .into_iter()
+            .map(|(_, cs_id)| authz.require_draft_commit_read(ctx, repo, cs_id)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Require that the caller may read the given hg trees or file nodes, which
+/// may have been introduced by commits pushed to private scratch bookmarks.
+async fn require_hg_nodes_read(
+    ctx: &CoreContext,
+    repo: &InnerRepo,
+    nodes: Vec<HgNodeHash>,
+) -> Result<(), Error> {
+    if repo.repo_config().infinitepush.private_namespace.is_none() {
+        return Ok(());
+    }
+    let authz = AuthorizationContext::new(ctx);
+    stream::iter(nodes)
+        .map(|node| authz.require_draft_hg_node_read(ctx, repo, node))
+        .buffer_unordered(100)
+        .try_collect::<()>()
+        .await?;
+    Ok(())
+}
+
+/// Require that the caller may read the given hg trees or file nodes at the
+/// paths the client says they are at, according to the repo's path read ACLs.
+async fn require_protected_nodes_read(
+    ctx: &CoreContext,
+    repo: &InnerRepo,
+    nodes: Vec<(RepoPath, HgFileNodeId)>,
+) -> Result<(), Error> {
+    let authz = &AuthorizationContext::new(ctx);
+    if authz
+        .check_all_protected_paths_read(ctx, repo)
+        .is_permitted()
+    {
+        return Ok(());
+    }
+    stream::iter(nodes)
+        .map(|(path, node)| async move {
+            authz
+                .require_protected_node_read(ctx, repo, &path, node)
+                .await
+        })
+        .buffer_unordered(100)
+        .try_collect::<()>()
+        .await?;
+    Ok(())
+}
+
+/// Return up to `limit` entries of a manifest, starting after the entry
+/// named by the continuation token `after`.
+fn manifest_page(
+    manifest: &HgBlobManifest,
+    after: Option<&[u8]>,
+    limit: usize,
+) -> Result<ManifestPage> {
+    let after = after
+        .map(|after| MPathElement::new(after.to_vec()).context("Invalid continuation token"))
+        .transpose()?;
+    let start = match &after {
+        Some(after) => Bound::Excluded(after),
+        None => Bound::Unbounded,
+    };
+
+    // Take one more entry than requested to find out whether there is
+    // another page.
+    let mut entries = manifest
+        .content()
+        .files
+        .range((start, Bound::Unbounded))
+        .take(limit + 1)
+        .map(|(name, entry)| {
+            let entry = match entry {
+                Entry::Tree(mf_id) => HgEntryId::Manifest(*mf_id),
+                Entry::Leaf((file_type, filenode_id)) => HgEntryId::File(*file_type, *filenode_id),
+            };
+            (name.clone(), entry)
+        })
+        .collect::<Vec<_>>();
+
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|(name, _)| name.as_ref().to_vec())
+    } else {
+        None
+    };
+
+    Ok(ManifestPage { entries, next })
+}
+
+/// The trees requested by a gettreepack call, with the paths the client says
+/// they are at.
+fn gettreepack_requested_trees(params: &GettreepackArgs) -> Result<Vec<(RepoPath, HgFileNodeId)>> {
+    let dir_path = |path: Option<MPath>| match path {
+        Some(path) => RepoPath::DirectoryPath(path),
+        None => RepoPath::RootPath,
+    };
+    if params.depth == Some(1) && !params.directories.is_empty() {
+        params
+            .mfnodes
+            .iter()
+            .zip(params.directories.iter())
+            .map(|(mfnode, directory)| {
+                let path = dir_path(MPath::new_opt(directory.as_ref())?);
+                Ok((path, HgFileNodeId::new(mfnode.into_nodehash())))
+            })
+            .collect()
+    } else {
+        Ok(params
+            .mfnodes
+            .iter()
+            .map(|mfnode| {
+                let path = dir_path(params.rootdir.clone());
+                (path, HgFileNodeId::new(mfnode.into_nodehash()))
+            })
+            .collect())
+    }
+}
+
 pub fn gettreepack_entries(
     ctx: CoreContext,
     repo: &BlobRepo,
@@ -2511,7 +3577,7 @@ fn with_command_monitor<T>(ctx: CoreContext, t: T) -> Monitor<T, Sender<()>> {
                     ctx.perf_counters().get_counter(PerfCounterType::SqlWrites),
                     ctx.perf_counters().get_counter(PerfCounterType::SqlReadsMaster),
                     ctx.perf_counters().get_counter(PerfCounterType::SqlReadsReplica),
-                    ; o!("remote" => "true")
+                    ; o!("remote" => "progress")
                 );
             }
 
@@ -2603,6 +3669,57 @@ fn parse_git_lookup(s: &str) -> Option<GitLookup> {
     }
 }
 
+/// Lookups for clients that address changesets by their SHA-256 node ids.
+enum Sha256Lookup {
+    /// A full SHA-256 node id, resolved to the SHA-1 id it is an alias of.
+    Sha256ToHg(HgNodeHash256),
+    /// The SHA-256 node id of a changeset given by its SHA-1 id.
+    HgToSha256(HgChangesetId),
+}
+
+impl Sha256Lookup {
+    pub async fn lookup(
+        &self,
+        ctx: &CoreContext,
+        repo: &BlobRepo,
+    ) -> Result<Option<BytesOld>, Error> {
+        use Sha256Lookup::*;
+        let mapping = match repo.repo_derived_data().manager().hg_sha256_mapping() {
+            Some(mapping) => mapping,
+            None => return Ok(None),
+        };
+        match self {
+            Sha256ToHg(sha256) => {
+                let maybe_hg = mapping.get_sha1_from_sha256(ctx, *sha256).await?;
+                Ok(maybe_hg.map(|hg| generate_lookup_resp_buf(true, hg.to_hex().as_bytes())))
+            }
+            HgToSha256(hg_cs_id) => {
+                let maybe_sha256 = mapping
+                    .get_sha256_from_sha1(ctx, hg_cs_id.into_nodehash())
+                    .await?;
+                Ok(maybe_sha256
+                    .map(|sha256| generate_lookup_resp_buf(true, sha256.to_hex().as_bytes())))
+            }
+        }
+    }
+}
+
+fn parse_sha256_lookup(s: &str, sha256_nodes: bool) -> Option<Sha256Lookup> {
+    if !sha256_nodes {
+        return None;
+    }
+
+    if let Some(hg_hash) = s.strip_prefix("_sha256lookup_hg_") {
+        Some(Sha256Lookup::HgToSha256(
+            HgChangesetId::from_str(hg_hash).ok()?,
+        ))
+    } else if s.len() == 64 {
+        Some(Sha256Lookup::Sha256ToHg(HgNodeHash256::from_str(s).ok()?))
+    } else {
+        None
+    }
+}
+
 fn generate_lookup_resp_buf(success: bool, message: &[u8]) -> BytesOld {
     let mut buf = BytesMutOld::with_capacity(message.len() + 3);
     if success {
@@ -2621,6 +3738,74 @@ struct ReplayData {
     hgbonsaimapping: Option<HashMap<HgChangesetId, ChangesetId>>,
 }
 
+fn describe_bundle_resolver_error(err: &BundleResolverError) -> String {
+    use BundleResolverError::*;
+    match err {
+        HookError(hooks) => format!(
+            "hooks failed: {}",
+            hooks.iter().map(|fail| fail.get_hook_name()).join(", ")
+        ),
+        PushrebaseConflicts(conflicts) => format!("pushrebase failed Conflicts({:?})", conflicts),
+        RateLimitExceeded {
+            limit_name, entity, ..
+        } => format!("Rate limit exceeded: {} for {}", limit_name, entity),
+        Error(err) => format!("{:#}", err),
+    }
+}
+
+/// Preserve the raw bundle2 that the client sent, if it was captured, along
+/// with the changesets that landed and the error the push failed with.  The
+/// bundle is preserved in the background, so that the push doesn't wait for
+/// it, and failing to preserve it doesn't fail the push.
+fn maybe_preserve_raw_bundle2(
+    ctx: &CoreContext,
+    repo: &(impl PreservedBundlesArc + AsBlobRepo),
+    maybe_full_content: Option<Arc<Mutex<BytesMut>>>,
+    changesets: Vec<ChangesetId>,
+    error: Option<&BundleResolverError>,
+) {
+    let full_content = match maybe_full_content {
+        Some(full_content) => full_content,
+        None => return,
+    };
+    // The bundle has been read, so take the buffer rather than copy it.
+    let raw_bundle2 = std::mem::take(&mut *full_content.lock().expect("lock poisoned")).freeze();
+    let error = error.map(describe_bundle_resolver_error);
+    let preserved_bundles = repo.preserved_bundles_arc();
+    let blobstore = repo.as_blob_repo().blobstore().clone();
+    let ctx = ctx.clone();
+
+    tokio::task::spawn(async move {
+        match preserved_bundles
+            .preserve(&ctx, &blobstore, raw_bundle2, &changesets, error.as_deref())
+            .await
+        {
+            Ok(id) => {
+                ctx.scuba()
+                    .clone()
+                    .add("preserved_bundle_id", id)
+                    .log_with_msg("Preserved raw bundle2", None);
+            }
+            Err(err) => {
+                error!(ctx.logger(), "Failed to preserve raw bundle2: {:#}", err);
+            }
+        }
+    });
+}
+
+/// The changesets a push landed: the rebased changesets for pushrebases,
+/// and otherwise the changesets that were uploaded.
+fn landed_changesets(response: &UnbundleResponse, uploaded: Vec<ChangesetId>) -> Vec<ChangesetId> {
+    match response {
+        UnbundleResponse::PushRebase(response) => response
+            .pushrebased_changesets
+            .iter()
+            .map(|pair| pair.id_new)
+            .collect(),
+        _ => uploaded,
+    }
+}
+
 // Client might send us the bonsai commits it expects to see for given hg changesets.
 // This function verifies them.
 async fn maybe_validate_pushed_bonsais(
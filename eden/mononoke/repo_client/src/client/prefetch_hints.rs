@@ -0,0 +1,226 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Prefetching of data that clients say they are about to request.
+//!
+//! Clients that can predict their next requests, e.g. the trees of the
+//! commit they will check out after a pull, send `prefetchhints` ahead of
+//! them.  The hints are acknowledged straight away, and the commits and the
+//! trees of the hinted directories are loaded in the background, so that
+//! they are in the blobstore caches by the time the requests arrive.
+//!
+//! Hints are advisory: hints beyond the limits are dropped, as are all the
+//! hints of a request when too many requests are already being prefetched.
+//! Hints that can't be resolved, such as commits the server doesn't have or
+//! that the client may not read, are skipped.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use context::CoreContext;
+use futures::future;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use hgproto::PrefetchHintsArgs;
+use lazy_static::lazy_static;
+use manifest::ManifestOps;
+use mercurial_types::HgChangesetId;
+use mercurial_types::MPath;
+use mononoke_api::Repo;
+use repo_authorization::AuthorizationContext;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+
+use super::require_hg_changesets_read;
+
+/// Maximum number of commits prefetched for a single request.
+pub(crate) const PREFETCH_HINTS_MAX_COMMITS: usize = 100;
+
+/// Maximum number of directories prefetched for a single request.
+pub(crate) const PREFETCH_HINTS_MAX_DIRECTORIES: usize = 1_000;
+
+/// Maximum time spent prefetching the hints of a single request.  Data that
+/// is needed after this is likely to have been requested already.
+pub(crate) const PREFETCH_HINTS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of blobstore reads issued concurrently while prefetching.
+const PREFETCH_HINTS_CONCURRENCY: usize = 100;
+
+/// Maximum number of requests whose hints are prefetched at the same time
+/// across the process.
+const PREFETCH_HINTS_MAX_IN_FLIGHT: usize = 20;
+
+lazy_static! {
+    static ref IN_FLIGHT: Arc<Semaphore> = Arc::new(Semaphore::new(PREFETCH_HINTS_MAX_IN_FLIGHT));
+}
+
+/// Reserve a slot for prefetching the hints of a request, or return `None`
+/// if too many requests are already being prefetched, in which case the
+/// hints should be dropped.  The slot is released when the permit is dropped.
+pub(crate) fn try_reserve_prefetch() -> Option<OwnedSemaphorePermit> {
+    IN_FLIGHT.clone().try_acquire_owned().ok()
+}
+
+/// The hints accepted from a `prefetchhints` request.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct PrefetchHints {
+    commits: Vec<HgChangesetId>,
+    /// The directories whose trees are prefetched.  This always includes
+    /// the root directory.
+    directories: BTreeSet<Option<MPath>>,
+}
+
+/// The outcome of prefetching the hints of a request.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub(crate) struct PrefetchHintsStats {
+    pub commits: u64,
+    pub trees: u64,
+    pub skipped_commits: u64,
+}
+
+impl PrefetchHints {
+    /// Accept the hints of a request, dropping hints beyond the limits and
+    /// directories that aren't valid paths.
+    pub(crate) fn new(args: PrefetchHintsArgs) -> Self {
+        let mut commits = args.commits;
+        commits.truncate(PREFETCH_HINTS_MAX_COMMITS);
+        let directories = std::iter::once(None)
+            .chain(
+                args.directories
+                    .iter()
+                    .filter_map(|dir| MPath::new_opt(dir.as_ref()).ok())
+                    .take(PREFETCH_HINTS_MAX_DIRECTORIES),
+            )
+            .collect();
+        Self {
+            commits,
+            directories,
+        }
+    }
+
+    /// The number of commits that will be prefetched.
+    pub(crate) fn commit_count(&self) -> usize {
+        self.commits.len()
+    }
+
+    /// The number of directories that will be prefetched for each commit.
+    pub(crate) fn directory_count(&self) -> usize {
+        self.directories.len()
+    }
+
+    /// Drop the hints the client may not read: commits pushed to private
+    /// scratch bookmarks of others, and protected directories.  Returns the
+    /// number of commits dropped.
+    pub(crate) async fn retain_readable(&mut self, ctx: &CoreContext, repo: &Repo) -> u64 {
+        let commits = std::mem::take(&mut self.commits);
+        let count = commits.len();
+        self.commits = stream::iter(commits)
+            .map(|hg_cs_id| async move {
+                require_hg_changesets_read(ctx, repo.inner_repo(), vec![hg_cs_id])
+                    .await
+                    .ok()
+                    .map(|()| hg_cs_id)
+            })
+            .buffered(PREFETCH_HINTS_CONCURRENCY)
+            .filter_map(future::ready)
+            .collect()
+            .await;
+
+        let authz = AuthorizationContext::new(ctx);
+        self.directories.retain(|dir| {
+            authz
+                .check_protected_path_read(ctx, repo.inner_repo(), dir.as_ref())
+                .is_permitted()
+        });
+
+        (count - self.commits.len()) as u64
+    }
+
+    /// Load the hinted commits and the trees of the hinted directories.
+    pub(crate) async fn prefetch(self, ctx: &CoreContext, repo: &BlobRepo) -> PrefetchHintsStats {
+        let directories = &self.directories;
+        stream::iter(self.commits)
+            .map(|hg_cs_id| async move {
+                let cs = hg_cs_id.load(ctx, repo.blobstore()).await?;
+                // Looking up the directories loads the trees on the path to
+                // each of them, after which the directories' trees are loaded
+                // themselves.
+                cs.manifestid()
+                    .find_entries(ctx.clone(), repo.get_blobstore(), directories.clone())
+                    .try_filter_map(|(_, entry)| future::ok(entry.into_tree()))
+                    .map_ok(|mf_id| async move { Ok(mf_id.load(ctx, repo.blobstore()).await?) })
+                    .try_buffer_unordered(PREFETCH_HINTS_CONCURRENCY)
+                    .try_fold(0, |trees, _| future::ok(trees + 1))
+                    .await
+            })
+            .buffer_unordered(PREFETCH_HINTS_CONCURRENCY)
+            .fold(PrefetchHintsStats::default(), |mut stats, res| async move {
+                match res {
+                    Ok(trees) => {
+                        stats.commits += 1;
+                        stats.trees += trees;
+                    }
+                    Err(_) => stats.skipped_commits += 1,
+                }
+                stats
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes_old::Bytes;
+    use mercurial_types_mocks::nodehash::ONES_CSID;
+
+    use super::*;
+
+    #[test]
+    fn test_prefetch_hints_limits() {
+        let hints = PrefetchHints::new(PrefetchHintsArgs {
+            commits: vec![ONES_CSID; PREFETCH_HINTS_MAX_COMMITS + 1],
+            directories: vec![
+                Bytes::from(b"foo/bar".as_ref()),
+                Bytes::from(b"foo/\0bar".as_ref()),
+                Bytes::from(b"".as_ref()),
+                Bytes::from(b"foo/bar".as_ref()),
+            ],
+        });
+        assert_eq!(hints.commit_count(), PREFETCH_HINTS_MAX_COMMITS);
+        // The root is always prefetched, invalid paths are dropped, and
+        // directories are only prefetched once.
+        assert_eq!(
+            hints.directories,
+            [None, Some(MPath::new("foo/bar").unwrap())]
+                .into_iter()
+                .collect()
+        );
+
+        let hints = PrefetchHints::new(PrefetchHintsArgs {
+            commits: vec![],
+            directories: (0..PREFETCH_HINTS_MAX_DIRECTORIES + 10)
+                .map(|i| Bytes::from(format!("dir{}", i)))
+                .collect(),
+        });
+        assert_eq!(hints.directory_count(), PREFETCH_HINTS_MAX_DIRECTORIES + 1);
+    }
+
+    #[test]
+    fn test_prefetch_hints_in_flight() {
+        let permits = (0..PREFETCH_HINTS_MAX_IN_FLIGHT)
+            .map(|_| try_reserve_prefetch())
+            .collect::<Option<Vec<_>>>()
+            .expect("permits should be available");
+        assert!(try_reserve_prefetch().is_none());
+        drop(permits);
+        assert!(try_reserve_prefetch().is_some());
+    }
+}
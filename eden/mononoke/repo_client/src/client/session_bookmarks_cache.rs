@@ -35,7 +35,9 @@ use futures_ext::FbTryFutureExt;
 use futures_old::Future;
 use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_types::HgChangesetId;
+use mononoke_api::bookmark_publishing::BookmarkPublishingGate;
 use mononoke_api::Repo;
+use mononoke_types::ChangesetId;
 use tunables::tunables;
 use warm_bookmarks_cache::BookmarksCache;
 
@@ -44,6 +46,8 @@ use warm_bookmarks_cache::BookmarksCache;
 pub struct SessionBookmarkCache<R = Arc<Repo>> {
     cached_publishing_bookmarks_maybe_stale: Arc<Mutex<Option<HashMap<Bookmark, HgChangesetId>>>>,
     repo: R,
+    // Decides which position of delayed bookmarks the session sees.
+    publishing_gate: BookmarkPublishingGate,
 }
 
 pub trait BookmarkCacheRepo {
@@ -85,6 +89,14 @@ where
         Self {
             cached_publishing_bookmarks_maybe_stale: Arc::new(Mutex::new(None)),
             repo,
+            publishing_gate: BookmarkPublishingGate::default(),
+        }
+    }
+
+    pub(crate) fn with_publishing_gate(self, publishing_gate: BookmarkPublishingGate) -> Self {
+        Self {
+            publishing_gate,
+            ..self
         }
     }
 
@@ -182,7 +194,140 @@ where
             futures::stream::iter(result.into_iter())
                 .map(Ok)
                 .chain(new_bookmarks),
-        ))
+        )
+        .try_filter_map({
+            let ctx = ctx.clone();
+            move |(bookmark, hg_cs_id)| {
+                let ctx = ctx.clone();
+                async move {
+                    let published = self.published_hg(&ctx, &bookmark, Some(hg_cs_id)).await?;
+                    Ok(published.map(|hg_cs_id| (bookmark, hg_cs_id)))
+                }
+            }
+        }))
+    }
+
+    /// List the publishing bookmarks of a kind that start with a prefix, in
+    /// batches of at most `batch_size`, stopping after `limit` bookmarks.
+    ///
+    /// If the session has already loaded the publishing bookmarks, they are
+    /// listed from there so that they're consistent with what the session
+    /// has seen.  Otherwise they are paged from the warm bookmarks cache or
+    /// the database, so that the whole namespace is never loaded at once.
+    pub fn list_publishing_bookmarks_batched<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        prefix: BookmarkPrefix,
+        kind: BookmarkKind,
+        limit: Option<u64>,
+        batch_size: u64,
+    ) -> impl Stream<Item = Result<Vec<(BookmarkName, HgChangesetId)>, Error>> + 'a {
+        let cached = self
+            .cached_publishing_bookmarks_maybe_stale
+            .lock()
+            .expect("lock poisoned")
+            .as_ref()
+            .map(|bookmarks| {
+                let mut matching = bookmarks
+                    .iter()
+                    .filter(|(bookmark, _)| {
+                        bookmark.kind() == &kind && prefix.is_prefix_of(bookmark.name())
+                    })
+                    .map(|(bookmark, hg_cs_id)| (bookmark.name().clone(), *hg_cs_id))
+                    .collect::<Vec<_>>();
+                matching.sort();
+                if let Some(limit) = limit {
+                    matching.truncate(limit.try_into().unwrap_or(usize::MAX));
+                }
+                matching
+            });
+        if let Some(matching) = cached {
+            let batches = matching
+                .chunks(batch_size.try_into().unwrap_or(usize::MAX))
+                .map(<[_]>::to_vec)
+                .collect::<Vec<_>>();
+            return futures::stream::iter(batches)
+                .map(Ok::<_, Error>)
+                .left_stream();
+        }
+
+        futures::stream::try_unfold(
+            (
+                Some(BookmarkPagination::FromStart),
+                limit.unwrap_or(u64::MAX),
+            ),
+            move |(pagination, remaining)| {
+                let prefix = prefix.clone();
+                async move {
+                    let pagination = match pagination {
+                        Some(pagination) if remaining > 0 => pagination,
+                        _ => return Ok(None),
+                    };
+                    let page_size = std::cmp::min(batch_size, remaining);
+                    let (page, next) = self
+                        .fetch_publishing_page(ctx, &prefix, kind, &pagination, page_size)
+                        .await?;
+                    let remaining = remaining.saturating_sub(page.len().try_into().unwrap());
+                    let batch = to_hg_bookmark_stream(
+                        self.repo.blobrepo(),
+                        ctx,
+                        futures::stream::iter(page).map(Ok),
+                    )
+                    .try_filter_map(|(bookmark, hg_cs_id)| async move {
+                        let published = self.published_hg(ctx, &bookmark, Some(hg_cs_id)).await?;
+                        Ok(published.map(|hg_cs_id| (bookmark, hg_cs_id)))
+                    })
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                    Ok(Some((batch, (next, remaining))))
+                }
+            },
+        )
+        .right_stream()
+    }
+
+    /// Fetch a page of publishing bookmarks of a kind.  Returns the page and
+    /// where the next page starts, if there are more bookmarks.
+    async fn fetch_publishing_page(
+        &self,
+        ctx: &CoreContext,
+        prefix: &BookmarkPrefix,
+        kind: BookmarkKind,
+        pagination: &BookmarkPagination,
+        page_size: u64,
+    ) -> Result<(Vec<(BookmarkName, ChangesetId)>, Option<BookmarkPagination>), Error> {
+        let listed = if let Some(warm_bookmarks_cache) = self.get_warm_bookmark_cache() {
+            warm_bookmarks_cache
+                .list(ctx, prefix, pagination, Some(page_size))
+                .await?
+        } else {
+            self.repo
+                .blobrepo()
+                .bookmarks()
+                .list(
+                    ctx.clone(),
+                    Freshness::MaybeStale,
+                    prefix,
+                    &[kind],
+                    pagination,
+                    page_size,
+                )
+                .map_ok(|(bookmark, cs_id)| (bookmark.name, (cs_id, bookmark.kind)))
+                .try_collect()
+                .await?
+        };
+        let next = match listed.last() {
+            Some((last, _)) if listed.len() as u64 == page_size => {
+                Some(BookmarkPagination::After(last.clone()))
+            }
+            _ => None,
+        };
+        let page = listed
+            .into_iter()
+            .filter(|(_, (_, bookmark_kind))| *bookmark_kind == kind)
+            .map(|(bookmark, (cs_id, _))| (bookmark, cs_id))
+            .collect();
+        Ok((page, next))
     }
 
     // Tries to fetch a bookmark from warm bookmark cache first, but if the bookmark is not found
@@ -192,18 +337,91 @@ where
         ctx: CoreContext,
         bookmark: BookmarkName,
     ) -> Result<Option<HgChangesetId>, Error> {
+        let mut current = None;
         if let Some(warm_bookmarks_cache) = self.get_warm_bookmark_cache() {
             if let Some(cs_id) = warm_bookmarks_cache.get(&ctx, &bookmark).await? {
-                return self
-                    .repo
-                    .blobrepo()
-                    .derive_hg_changeset(&ctx, cs_id)
-                    .map_ok(Some)
-                    .await;
+                current = Some(
+                    self.repo
+                        .blobrepo()
+                        .derive_hg_changeset(&ctx, cs_id)
+                        .await?,
+                );
+            }
+        }
+        if current.is_none() {
+            current = self
+                .repo
+                .blobrepo()
+                .get_bookmark_hg(ctx.clone(), &bookmark)
+                .await?;
+        }
+
+        self.published_hg(&ctx, &bookmark, current).await
+    }
+
+    /// The position of a bookmark that has been published to this session,
+    /// given its current position, for bookmarks that are listed without
+    /// going through the session's cache.
+    pub async fn published_position(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+        current: ChangesetId,
+    ) -> Result<Option<ChangesetId>, Error> {
+        self.publishing_gate
+            .published_position(ctx, self.repo.blobrepo(), bookmark, Some(current))
+            .await
+    }
+
+    /// The position of a bookmark that has been published to this session,
+    /// given its current position.
+    async fn published_hg(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+        current: Option<HgChangesetId>,
+    ) -> Result<Option<HgChangesetId>, Error> {
+        if !self.publishing_gate.is_delayed(bookmark) {
+            return Ok(current);
+        }
+        let blobrepo = self.repo.blobrepo();
+        let current_cs_id = match current {
+            Some(hg_cs_id) => {
+                blobrepo
+                    .bonsai_hg_mapping()
+                    .get_bonsai_from_hg(ctx, hg_cs_id)
+                    .await?
             }
+            None => None,
+        };
+        let published = self
+            .publishing_gate
+            .published_position(ctx, blobrepo, bookmark, current_cs_id)
+            .await?;
+        match published {
+            _ if published == current_cs_id => Ok(current),
+            Some(cs_id) => Ok(Some(blobrepo.derive_hg_changeset(ctx, cs_id).await?)),
+            None => Ok(None),
         }
+    }
 
-        self.repo.blobrepo().get_bookmark_hg(ctx, &bookmark).await
+    /// The positions of these bookmarks that have been published to this
+    /// session.
+    async fn published_hg_bookmarks(
+        &self,
+        ctx: &CoreContext,
+        bookmarks: HashMap<Bookmark, HgChangesetId>,
+    ) -> Result<HashMap<Bookmark, HgChangesetId>, Error> {
+        let mut published = HashMap::with_capacity(bookmarks.len());
+        for (bookmark, hg_cs_id) in bookmarks {
+            if let Some(hg_cs_id) = self
+                .published_hg(ctx, bookmark.name(), Some(hg_cs_id))
+                .await?
+            {
+                published.insert(bookmark, hg_cs_id);
+            }
+        }
+        Ok(published)
     }
 
     async fn get_publishing_bookmarks_maybe_stale_updating_cache(
@@ -238,9 +456,10 @@ where
             )
             .boxify()
             .compat();
-            return to_hg_bookmark_stream(self.repo.blobrepo(), &ctx, s)
+            let bookmarks = to_hg_bookmark_stream(self.repo.blobrepo(), &ctx, s)
                 .try_collect()
-                .await;
+                .await?;
+            return self.published_hg_bookmarks(&ctx, bookmarks).await;
         }
         self.get_publishing_maybe_stale_from_db(ctx).compat().await
     }
@@ -259,17 +478,22 @@ where
         &self,
         ctx: CoreContext,
     ) -> impl Future<Item = HashMap<Bookmark, HgChangesetId>, Error = Error> + '_ {
-        self.repo
-            .blobrepo()
-            .get_publishing_bookmarks_maybe_stale_hg(ctx)
-            .try_fold(HashMap::new(), |mut map, item| {
-                map.insert(item.0, item.1);
-                future::ready(Ok(map))
-            })
-            .timeout(bookmarks_timeout())
-            .flatten_err()
-            .boxed()
-            .compat()
+        async move {
+            let bookmarks = self
+                .repo
+                .blobrepo()
+                .get_publishing_bookmarks_maybe_stale_hg(ctx.clone())
+                .try_fold(HashMap::new(), |mut map, item| {
+                    map.insert(item.0, item.1);
+                    future::ready(Ok(map))
+                })
+                .await?;
+            self.published_hg_bookmarks(&ctx, bookmarks).await
+        }
+        .timeout(bookmarks_timeout())
+        .flatten_err()
+        .boxed()
+        .compat()
     }
 }
 
@@ -399,6 +623,33 @@ mod test {
             .await?;
         assert_eq!(res.len(), 1);
 
+        let batches = session_bookmark_cache
+            .list_publishing_bookmarks_batched(
+                ctx,
+                BookmarkPrefix::new("prefix")?,
+                BookmarkKind::PullDefaultPublishing,
+                None,
+                1,
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(
+            batches.into_iter().flatten().collect::<Vec<_>>(),
+            vec![(BookmarkName::new("prefix/pulldefault")?, hg_cs_id)]
+        );
+
+        let batches = session_bookmark_cache
+            .list_publishing_bookmarks_batched(
+                ctx,
+                BookmarkPrefix::new("other")?,
+                BookmarkKind::PullDefaultPublishing,
+                None,
+                1,
+            )
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(batches.into_iter().flatten().next().is_none());
+
         Ok(())
     }
 }
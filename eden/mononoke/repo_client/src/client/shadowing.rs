@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Shadowing of read-only wireproto commands to a test tier.
+//!
+//! A sample of the commands is published to a scribe category, together with
+//! a digest of the response that was sent to the client.  The test tier
+//! replays the commands from that category against the server build being
+//! qualified, and logs the digest of its own responses, so that the two can
+//! be compared offline.  Publishing is fire-and-forget: failures are counted
+//! and never affect the client.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use context::CoreContext;
+use metaconfig_types::WireprotoShadowingConfig;
+use mononoke_types::hash::Context as HashContext;
+use rand::Rng;
+use serde::Serialize;
+use stats::prelude::*;
+
+use super::logging::CommandStats;
+use super::ops;
+
+define_stats! {
+    prefix = "mononoke.repo_client.shadowing";
+    published: dynamic_timeseries("{}.published", (command: String); Rate, Sum),
+    publish_failed: dynamic_timeseries("{}.publish_failed", (command: String); Rate, Sum),
+}
+
+const RESPONSE_DIGEST_KEY: &[u8] = b"shadowed wireproto response";
+
+/// Returns true if this run of `command` should be shadowed.  Only the
+/// configured commands are shadowed, and pushes never are, as replaying
+/// them would write to the test tier.
+pub fn should_shadow(config: &WireprotoShadowingConfig, command: &str) -> bool {
+    if command == ops::UNBUNDLE || !config.commands.iter().any(|c| c == command) {
+        return false;
+    }
+    rand::thread_rng().gen_range(0..config.sample_rate.get()) == 0
+}
+
+struct ResponseDigest {
+    context: HashContext,
+    size: u64,
+}
+
+/// Handle used by the command implementation to record the response that
+/// was sent to the client.  Recording is a no-op if the command isn't being
+/// shadowed.
+#[derive(Clone, Default)]
+pub struct ResponseRecorder {
+    digest: Option<Arc<Mutex<Option<ResponseDigest>>>>,
+}
+
+impl ResponseRecorder {
+    pub fn record(&self, data: &[u8]) {
+        if let Some(digest) = &self.digest {
+            if let Some(digest) = digest.lock().expect("lock poisoned").as_mut() {
+                digest.context.update(data);
+                digest.size += data.len() as u64;
+            }
+        }
+    }
+
+    /// Stop recording, and return what was recorded so far.
+    fn finish(&self) -> Option<ResponseDigest> {
+        self.digest
+            .as_ref()
+            .and_then(|digest| digest.lock().expect("lock poisoned").take())
+    }
+}
+
+#[derive(Serialize)]
+struct ShadowedCommand<'a> {
+    repo: &'a str,
+    session_id: String,
+    command: &'a str,
+    args: &'a serde_json::Value,
+    response_size: u64,
+    response_digest: String,
+    completion_time_us: u64,
+}
+
+/// Collects what is needed to replay a single command on the test tier.
+pub struct CommandShadow {
+    scribe_category: String,
+    repo: String,
+    command: String,
+    args: serde_json::Value,
+    recorder: ResponseRecorder,
+}
+
+impl CommandShadow {
+    pub fn new(config: &WireprotoShadowingConfig, repo: &str, command: &str) -> Self {
+        Self {
+            scribe_category: config.scribe_category.clone(),
+            repo: repo.to_string(),
+            command: command.to_string(),
+            args: serde_json::Value::Null,
+            recorder: ResponseRecorder {
+                digest: Some(Arc::new(Mutex::new(Some(ResponseDigest {
+                    context: HashContext::new(RESPONSE_DIGEST_KEY),
+                    size: 0,
+                })))),
+            },
+        }
+    }
+
+    /// Set the full arguments of the command, as needed to replay it.
+    pub fn set_args(&mut self, args: serde_json::Value) {
+        self.args = args;
+    }
+
+    pub fn response_recorder(&self) -> ResponseRecorder {
+        self.recorder.clone()
+    }
+
+    /// Publish the command to the test tier.
+    pub fn publish(self, ctx: &CoreContext, stats: CommandStats) {
+        let digest = match self.recorder.finish() {
+            Some(digest) => digest,
+            None => return,
+        };
+        let shadowed = ShadowedCommand {
+            repo: &self.repo,
+            session_id: ctx.metadata().session_id().to_string(),
+            command: &self.command,
+            args: &self.args,
+            response_size: digest.size,
+            response_digest: digest.context.finish().to_hex().to_string(),
+            completion_time_us: stats.completion_time().as_micros() as u64,
+        };
+        let res = serde_json::to_string(&shadowed)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| ctx.scribe().offer(&self.scribe_category, &json));
+        match res {
+            Ok(()) => STATS::published.add_value(1, (self.command,)),
+            Err(_) => STATS::publish_failed.add_value(1, (self.command,)),
+        }
+    }
+}
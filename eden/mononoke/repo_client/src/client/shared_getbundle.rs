@@ -0,0 +1,545 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sharing of identical concurrent `getbundle` responses.
+//!
+//! When many clients pull the same commits at once, as CI machines do after
+//! a land, every session would otherwise compute the same changegroup.
+//! Instead, the first session starts computing the response, and later
+//! sessions with an identical request attach to it: they are sent the
+//! chunks produced so far, and then follow the computation as it goes.
+//!
+//! Requests are only shared when everything that affects the response is
+//! the same, including session-specific inputs such as the LFS threshold
+//! and the contents of the requested `listkeys` namespaces.  Requests that
+//! differ in any of these are computed independently.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use anyhow::Error;
+use bytes_old::Bytes as BytesOld;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::stream::StreamExt;
+use getbundle_response::SessionLfsParams;
+use lazy_static::lazy_static;
+use mercurial_types::HgChangesetId;
+use stats::prelude::*;
+use tokio::sync::Notify;
+
+use super::listkeys::ListKeys;
+
+define_stats! {
+    prefix = "mononoke.repo_client.shared_getbundle";
+    computed: dynamic_timeseries("{}.computed", (repo: String); Rate, Sum),
+    attached: dynamic_timeseries("{}.attached", (repo: String); Rate, Sum),
+    attached_bytes: dynamic_timeseries("{}.attached_bytes", (repo: String); Sum),
+    buffer_exceeded: dynamic_timeseries("{}.buffer_exceeded", (repo: String); Rate, Sum),
+}
+
+lazy_static! {
+    static ref IN_FLIGHT: Mutex<HashMap<SharedGetbundleKey, Arc<SharedGetbundle>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Everything that affects a `getbundle` response.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct SharedGetbundleKey {
+    repo_name: String,
+    heads: Vec<HgChangesetId>,
+    common: Vec<HgChangesetId>,
+    phases: bool,
+    invalidation_hints: bool,
+    lfs_threshold: Option<u64>,
+    listkeys: Vec<(String, BTreeMap<Vec<u8>, Vec<u8>>)>,
+}
+
+impl SharedGetbundleKey {
+    pub(crate) fn new(
+        repo_name: String,
+        heads: &[HgChangesetId],
+        common: &[HgChangesetId],
+        phases: bool,
+        invalidation_hints: bool,
+        lfs_params: &SessionLfsParams,
+        listkeys: &[(String, ListKeys)],
+    ) -> Self {
+        Self {
+            repo_name,
+            heads: heads.to_vec(),
+            common: common.to_vec(),
+            phases,
+            invalidation_hints,
+            lfs_threshold: lfs_params.threshold,
+            listkeys: listkeys
+                .iter()
+                .map(|(namespace, keys)| {
+                    let keys = keys.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                    (namespace.clone(), keys)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A response that is being computed, and the sessions attached to it.
+struct SharedGetbundle {
+    state: Mutex<SharedGetbundleState>,
+    /// Notified when a chunk is produced, or the response is finished.
+    produced: Notify,
+    /// Notified when a session consumes a chunk, or detaches.
+    consumed: Notify,
+}
+
+struct SharedGetbundleState {
+    /// Chunks that some attached session has yet to be sent.
+    chunks: VecDeque<BytesOld>,
+    /// Index in the response of the first chunk in `chunks`.
+    first: usize,
+    retained_bytes: usize,
+    total_bytes: usize,
+    /// Whether new sessions can still attach.  Until they can't, every
+    /// chunk has to be kept so that they can be sent from the start.
+    joinable: bool,
+    /// Index of the next chunk to send to each attached session.
+    attachments: HashMap<u64, usize>,
+    next_attachment_id: u64,
+    /// Set once the response is finished.  Errors are kept as messages, as
+    /// they are sent to every attached session.
+    result: Option<Result<(), String>>,
+}
+
+impl SharedGetbundle {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(SharedGetbundleState {
+                chunks: VecDeque::new(),
+                first: 0,
+                retained_bytes: 0,
+                total_bytes: 0,
+                joinable: true,
+                attachments: HashMap::new(),
+                next_attachment_id: 0,
+                result: None,
+            }),
+            produced: Notify::new(),
+            consumed: Notify::new(),
+        }
+    }
+
+    /// Attach a session to the response, if it can still be sent in full.
+    fn attach(self: &Arc<Self>, repo_name: &str) -> Option<Attachment> {
+        let mut state = self.state.lock().expect("lock poisoned");
+        if !state.joinable {
+            return None;
+        }
+        let id = state.next_attachment_id;
+        state.next_attachment_id += 1;
+        let first = state.first;
+        state.attachments.insert(id, first);
+        Some(Attachment {
+            shared: self.clone(),
+            repo_name: repo_name.to_string(),
+            id,
+            next: first,
+            bytes: 0,
+        })
+    }
+}
+
+impl SharedGetbundleState {
+    /// Drop the chunks that every attached session has been sent.
+    fn trim(&mut self) {
+        if self.joinable {
+            return;
+        }
+        let end = self.first + self.chunks.len();
+        let min_next = self.attachments.values().copied().min().unwrap_or(end);
+        while self.first < min_next {
+            match self.chunks.pop_front() {
+                Some(chunk) => {
+                    self.retained_bytes -= chunk.len();
+                    self.first += 1;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// A session's view of a shared response.
+struct Attachment {
+    shared: Arc<SharedGetbundle>,
+    repo_name: String,
+    id: u64,
+    next: usize,
+    bytes: usize,
+}
+
+impl Attachment {
+    async fn next_chunk(&mut self) -> Option<Result<BytesOld, Error>> {
+        loop {
+            let produced = self.shared.produced.notified();
+            {
+                let mut state = self.shared.state.lock().expect("lock poisoned");
+                if let Some(chunk) = state.chunks.get(self.next - state.first).cloned() {
+                    self.next += 1;
+                    self.bytes += chunk.len();
+                    state.attachments.insert(self.id, self.next);
+                    state.trim();
+                    self.shared.consumed.notify_waiters();
+                    return Some(Ok(chunk));
+                }
+                match &state.result {
+                    Some(Ok(())) => return None,
+                    Some(Err(msg)) => return Some(Err(anyhow!("{}", msg))),
+                    None => {}
+                }
+            }
+            produced.await;
+        }
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.attachments.remove(&self.id);
+            state.trim();
+        }
+        self.shared.consumed.notify_waiters();
+        STATS::attached_bytes.add_value(self.bytes as i64, (self.repo_name.clone(),));
+    }
+}
+
+fn attachment_stream(attachment: Attachment) -> BoxStream<'static, Result<BytesOld, Error>> {
+    stream::unfold(Some(attachment), |attachment| async move {
+        let mut attachment = attachment?;
+        match attachment.next_chunk().await? {
+            Ok(chunk) => Some((Ok(chunk), Some(attachment))),
+            // Nothing follows an error.
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+    .boxed()
+}
+
+fn remove_in_flight(key: &SharedGetbundleKey, shared: &Arc<SharedGetbundle>) {
+    let mut in_flight = IN_FLIGHT.lock().expect("lock poisoned");
+    if let Some(current) = in_flight.get(key) {
+        if Arc::ptr_eq(current, shared) {
+            in_flight.remove(key);
+        }
+    }
+}
+
+/// Compute the response to a `getbundle` request, or attach to an identical
+/// request that is already being computed.
+///
+/// The response is computed in the background, and kept in memory so that
+/// sessions can attach to it, until it is larger than `max_buffered_bytes`.
+/// After that, chunks are dropped once every attached session has been sent
+/// them, and the computation waits for the slowest session if it gets too
+/// far ahead.  The computation stops if every session detaches.
+pub(crate) fn shared_getbundle<F>(
+    key: SharedGetbundleKey,
+    max_buffered_bytes: usize,
+    compute: F,
+) -> BoxStream<'static, Result<BytesOld, Error>>
+where
+    F: FnOnce() -> BoxStream<'static, Result<BytesOld, Error>>,
+{
+    let repo_name = key.repo_name.clone();
+    let mut in_flight = IN_FLIGHT.lock().expect("lock poisoned");
+    if let Some(attachment) = in_flight
+        .get(&key)
+        .and_then(|shared| shared.attach(&repo_name))
+    {
+        STATS::attached.add_value(1, (repo_name.clone(),));
+        return attachment_stream(attachment);
+    }
+
+    STATS::computed.add_value(1, (repo_name.clone(),));
+    let shared = Arc::new(SharedGetbundle::new());
+    in_flight.insert(key.clone(), shared.clone());
+    drop(in_flight);
+
+    // Attach before starting, so that the computation doesn't see that
+    // there are no sessions and stop immediately.
+    let attachment = shared
+        .attach(&repo_name)
+        .expect("new response must be joinable");
+    let response = compute();
+    tokio::spawn(produce(key, shared, response, max_buffered_bytes));
+    attachment_stream(attachment)
+}
+
+async fn produce(
+    key: SharedGetbundleKey,
+    shared: Arc<SharedGetbundle>,
+    mut response: BoxStream<'static, Result<BytesOld, Error>>,
+    max_buffered_bytes: usize,
+) {
+    let result = 'produce: loop {
+        // Don't get too far ahead of the slowest session.
+        loop {
+            let consumed = shared.consumed.notified();
+            {
+                let state = shared.state.lock().expect("lock poisoned");
+                if state.attachments.is_empty() {
+                    break 'produce Err("all sessions detached".to_string());
+                }
+                if state.joinable || state.retained_bytes <= max_buffered_bytes {
+                    break;
+                }
+            }
+            consumed.await;
+        }
+
+        match response.next().await {
+            Some(Ok(chunk)) => {
+                let exceeded = {
+                    let mut state = shared.state.lock().expect("lock poisoned");
+                    state.retained_bytes += chunk.len();
+                    state.total_bytes += chunk.len();
+                    state.chunks.push_back(chunk);
+                    let exceeded = state.joinable && state.total_bytes > max_buffered_bytes;
+                    if exceeded {
+                        // Too large to keep in full: sessions that arrive
+                        // from now on compute their own response.
+                        state.joinable = false;
+                        state.trim();
+                    }
+                    exceeded
+                };
+                if exceeded {
+                    remove_in_flight(&key, &shared);
+                    STATS::buffer_exceeded.add_value(1, (key.repo_name.clone(),));
+                }
+                shared.produced.notify_waiters();
+            }
+            Some(Err(e)) => break Err(format!("{:#}", e)),
+            None => break Ok(()),
+        }
+    };
+
+    remove_in_flight(&key, &shared);
+    shared.state.lock().expect("lock poisoned").result = Some(result);
+    shared.produced.notify_waiters();
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use futures::stream::TryStreamExt;
+    use mercurial_types_mocks::nodehash::ONES_CSID;
+    use mercurial_types_mocks::nodehash::TWOS_CSID;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    fn key(repo_name: &str, head: HgChangesetId) -> SharedGetbundleKey {
+        SharedGetbundleKey::new(
+            repo_name.to_string(),
+            &[head],
+            &[],
+            true,
+            false,
+            &SessionLfsParams { threshold: None },
+            &[],
+        )
+    }
+
+    /// A response that is only produced once `release` is sent.
+    fn gated_response(
+        computed: &Arc<AtomicUsize>,
+        release: oneshot::Receiver<()>,
+        chunks: &[&'static str],
+    ) -> impl FnOnce() -> BoxStream<'static, Result<BytesOld, Error>> {
+        let computed = computed.clone();
+        let chunks: Vec<_> = chunks.iter().map(|c| Ok(BytesOld::from(*c))).collect();
+        move || {
+            computed.fetch_add(1, Ordering::SeqCst);
+            stream::once(async move {
+                let _ = release.await;
+                stream::iter(chunks)
+            })
+            .flatten()
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_are_shared() -> Result<(), Error> {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (release, gate) = oneshot::channel();
+        let first = shared_getbundle(
+            key("shared", ONES_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["a", "b"]),
+        );
+        let (_unused, gate) = oneshot::channel();
+        let second = shared_getbundle(
+            key("shared", ONES_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["x"]),
+        );
+        release.send(()).unwrap();
+
+        let first: Vec<_> = first.try_collect().await?;
+        let second: Vec<_> = second.try_collect().await?;
+        assert_eq!(first, vec![BytesOld::from("a"), BytesOld::from("b")]);
+        assert_eq!(second, first);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_different_requests_are_not_shared() -> Result<(), Error> {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (release_first, gate) = oneshot::channel();
+        let first = shared_getbundle(
+            key("unshared", ONES_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["a"]),
+        );
+        let (release_second, gate) = oneshot::channel();
+        let second = shared_getbundle(
+            key("unshared", TWOS_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["b"]),
+        );
+        release_first.send(()).unwrap();
+        release_second.send(()).unwrap();
+
+        let first: Vec<_> = first.try_collect().await?;
+        let second: Vec<_> = second.try_collect().await?;
+        assert_eq!(first, vec![BytesOld::from("a")]);
+        assert_eq!(second, vec![BytesOld::from("b")]);
+        assert_eq!(computed.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_shared() {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (release, gate) = oneshot::channel::<()>();
+        let compute = {
+            let computed = computed.clone();
+            move || {
+                computed.fetch_add(1, Ordering::SeqCst);
+                stream::once(async move {
+                    let _ = gate.await;
+                    Err::<BytesOld, _>(anyhow!("broken"))
+                })
+                .boxed()
+            }
+        };
+        let first = shared_getbundle(key("errors", ONES_CSID), usize::MAX, compute);
+        let (_unused, gate) = oneshot::channel();
+        let second = shared_getbundle(
+            key("errors", ONES_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["x"]),
+        );
+        release.send(()).unwrap();
+
+        assert!(first.try_collect::<Vec<_>>().await.is_err());
+        assert!(second.try_collect::<Vec<_>>().await.is_err());
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_late_sessions_are_sent_the_whole_response() -> Result<(), Error> {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (release, gate) = oneshot::channel::<()>();
+        let compute = {
+            let computed = computed.clone();
+            move || {
+                computed.fetch_add(1, Ordering::SeqCst);
+                stream::iter(vec![Ok(BytesOld::from("a"))])
+                    .chain(stream::once(async move {
+                        let _ = gate.await;
+                        Ok(BytesOld::from("b"))
+                    }))
+                    .boxed()
+            }
+        };
+        let mut first = shared_getbundle(key("late", ONES_CSID), usize::MAX, compute);
+        assert_eq!(first.try_next().await?, Some(BytesOld::from("a")));
+
+        // The first chunk has already been sent, but is still kept for the
+        // sessions that attach later.
+        let (_unused, gate) = oneshot::channel();
+        let second = shared_getbundle(
+            key("late", ONES_CSID),
+            usize::MAX,
+            gated_response(&computed, gate, &["x"]),
+        );
+        release.send(()).unwrap();
+
+        let first: Vec<_> = first.try_collect().await?;
+        let second: Vec<_> = second.try_collect().await?;
+        assert_eq!(first, vec![BytesOld::from("b")]);
+        assert_eq!(second, vec![BytesOld::from("a"), BytesOld::from("b")]);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_responses_are_not_shared() -> Result<(), Error> {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let (release_first, gate) = oneshot::channel();
+        let mut first = shared_getbundle(
+            key("large", ONES_CSID),
+            1,
+            gated_response(&computed, gate, &["ab", "c"]),
+        );
+        release_first.send(()).unwrap();
+        assert_eq!(first.try_next().await?, Some(BytesOld::from("ab")));
+
+        // The response is larger than can be kept, so a later session
+        // computes its own.
+        let (release_second, gate) = oneshot::channel();
+        let second = shared_getbundle(
+            key("large", ONES_CSID),
+            1,
+            gated_response(&computed, gate, &["x"]),
+        );
+        release_second.send(()).unwrap();
+
+        let first: Vec<_> = first.try_collect().await?;
+        let second: Vec<_> = second.try_collect().await?;
+        assert_eq!(first, vec![BytesOld::from("c")]);
+        assert_eq!(second, vec![BytesOld::from("x")]);
+        assert_eq!(computed.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_computation_stops_when_sessions_detach() {
+        // The response never ends, and signals when it's dropped.
+        let (guard, dropped) = oneshot::channel::<()>();
+        let compute = move || {
+            stream::unfold(guard, |guard| async move {
+                Some((Ok(BytesOld::from("a")), guard))
+            })
+            .boxed()
+        };
+        let response = shared_getbundle(key("detached", ONES_CSID), 1, compute);
+        drop(response);
+
+        assert!(dropped.await.is_err());
+    }
+}
@@ -14,14 +14,27 @@ use fixtures::TestRepoFixture;
 use futures::compat::Future01CompatExt;
 use manifest::Entry;
 use manifest::ManifestOps;
+use maplit::btreeset;
+use maplit::hashmap;
 use maplit::hashset;
 use mercurial_derived_data::DeriveHgChangeset;
 use mercurial_types::HgFileNodeId;
+use metaconfig_types::BookmarkParams;
+use metaconfig_types::BookmarkPublishingDelay;
+use metaconfig_types::Identity;
+use metaconfig_types::InfinitepushNamespace;
 use metaconfig_types::LfsParams;
+use metaconfig_types::MergePolicy;
+use metaconfig_types::PrivateScratchNamespace;
+use metaconfig_types::WireprotoShadowingConfig;
+use metadata::Metadata;
 use mononoke_api::Repo;
 use mononoke_types_mocks::changesetid::ONES_CSID;
+use permission_checker::MononokeIdentity;
+use regex::Regex;
 use scuba_ext::MononokeScubaSampleBuilder;
 use serde_json::json;
+use tests_utils::bookmark;
 use tests_utils::CreateCommitContext;
 
 use super::*;
@@ -449,6 +462,360 @@ async fn fetch_mfs(
     Ok(fetched_mfs)
 }
 
+#[fbinit::test]
+async fn test_manifest_page(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo = ManyFilesDirs::getrepo(fb).await;
+
+    let manifest = HgChangesetId::from_str("d261bc7900818dea7c86935b3fb17a33b2e3a6b4")?
+        .load(&ctx, &repo.get_blobstore())
+        .await?
+        .manifestid()
+        .load(&ctx, &repo.get_blobstore())
+        .await?;
+    let all = manifest_page(&manifest, None, usize::MAX - 1)?;
+    assert!(all.next.is_none());
+    assert!(all.entries.len() > 2);
+
+    // Paging one entry at a time returns every entry, in order.
+    let mut entries = vec![];
+    let mut after = None;
+    loop {
+        let page = manifest_page(&manifest, after.as_deref(), 1)?;
+        entries.extend(page.entries);
+        match page.next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(entries, all.entries);
+
+    // Paging after the last entry returns nothing.
+    let last = all.entries.last().unwrap().0.as_ref().to_vec();
+    assert_eq!(
+        manifest_page(&manifest, Some(&last), 1)?,
+        ManifestPage::default()
+    );
+
+    Ok(())
+}
+
+fn test_repo_client(ctx: &CoreContext, repo: Arc<Repo>, knobs: RepoClientKnobs) -> RepoClient {
+    let logging = LoggingContainer::new(
+        ctx.fb,
+        ctx.logger().clone(),
+        MononokeScubaSampleBuilder::with_discard(),
+    );
+    RepoClient::new(
+        repo,
+        ctx.session().clone(),
+        logging,
+        None, // No PushRedirectorArgs
+        knobs,
+        None, // No backup repo source
+    )
+}
+
+async fn heads_page(
+    repo_client: &RepoClient,
+    after: Option<&str>,
+    limit: u64,
+) -> Result<(Vec<String>, Option<String>), Error> {
+    let page = repo_client
+        .headspaginated(HeadsPaginatedArgs {
+            after: after.map(String::from),
+            limit: Some(limit),
+            ..Default::default()
+        })
+        .compat()
+        .await?;
+    let names = page.heads.into_iter().map(|(name, _)| name).collect();
+    Ok((names, page.next))
+}
+
+#[fbinit::test]
+async fn test_headspaginated(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let cs_id = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("1", "1")
+        .commit()
+        .await?;
+    let hg_cs_id = repo.derive_hg_changeset(&ctx, cs_id).await?;
+    bookmark(&ctx, &repo, "a").create_publishing(cs_id).await?;
+    bookmark(&ctx, &repo, "b").create_scratch(cs_id).await?;
+    bookmark(&ctx, &repo, "c").create_publishing(cs_id).await?;
+    let repo_client = test_repo_client(
+        &ctx,
+        Arc::new(Repo::new_test(ctx.clone(), repo).await?),
+        Default::default(),
+    );
+
+    // Scratch heads are paged along with the publishing ones, and the
+    // cursor continues from the last bookmark of the page.
+    let page = repo_client
+        .headspaginated(HeadsPaginatedArgs {
+            limit: Some(2),
+            ..Default::default()
+        })
+        .compat()
+        .await?;
+    assert_eq!(
+        page,
+        HeadsPage {
+            heads: vec![("a".to_string(), hg_cs_id), ("b".to_string(), hg_cs_id)],
+            next: Some("b".to_string()),
+        }
+    );
+    assert_eq!(
+        heads_page(&repo_client, Some("b"), 2).await?,
+        (vec!["c".to_string()], None)
+    );
+
+    // A page that ends on the last bookmark is followed by an empty one.
+    assert_eq!(
+        heads_page(&repo_client, None, 3).await?,
+        (
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            Some("c".to_string())
+        )
+    );
+    assert_eq!(
+        heads_page(&repo_client, Some("c"), 3).await?,
+        (vec![], None)
+    );
+
+    // Limits outside of the permitted range are clamped.
+    assert_eq!(
+        heads_page(&repo_client, None, 0).await?,
+        (vec!["a".to_string()], Some("a".to_string()))
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_heads_private_scratch(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let mut hg_cs_ids = HashMap::new();
+    for (name, scratch) in [
+        ("main", false),
+        ("scratch/alice/feature", true),
+        ("scratch/carol/feature", true),
+    ] {
+        let cs_id = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file(name, name)
+            .commit()
+            .await?;
+        if scratch {
+            bookmark(&ctx, &repo, name).create_scratch(cs_id).await?;
+        } else {
+            bookmark(&ctx, &repo, name)
+                .create_pull_default(cs_id)
+                .await?;
+        }
+        hg_cs_ids.insert(name, repo.derive_hg_changeset(&ctx, cs_id).await?);
+    }
+    let repo = Repo::new_test_with_config(ctx.clone(), repo, |config| {
+        config.infinitepush.private_namespace = Some(PrivateScratchNamespace {
+            pattern: Regex::new("scratch/(?P<user>[^/]+)/.+").unwrap().into(),
+            owner_identity_type: String::from("USER"),
+            readers: vec![],
+            shared_with: HashMap::new(),
+        });
+    })
+    .await?;
+    let repo = Arc::new(repo);
+    let knobs = RepoClientKnobs {
+        legacy_heads_include_scratch: true,
+        ..Default::default()
+    };
+
+    // Users only see their own private scratch bookmarks.
+    let client_for = |user: &str| {
+        let metadata =
+            Metadata::default().set_identities(btreeset! { MononokeIdentity::new("USER", user) });
+        let session = SessionContainer::builder(fb)
+            .metadata(Arc::new(metadata))
+            .build();
+        let ctx = CoreContext::test_mock_session(session);
+        test_repo_client(&ctx, repo.clone(), knobs.clone())
+    };
+    for (user, visible) in [
+        ("alice", "scratch/alice/feature"),
+        ("carol", "scratch/carol/feature"),
+    ] {
+        let repo_client = client_for(user);
+        let page = repo_client
+            .headspaginated(HeadsPaginatedArgs::default())
+            .compat()
+            .await?;
+        assert_eq!(
+            page.heads,
+            vec![
+                ("main".to_string(), hg_cs_ids["main"]),
+                (visible.to_string(), hg_cs_ids[visible]),
+            ]
+        );
+        let heads = repo_client.heads().compat().await?;
+        assert_eq!(heads, hashset! { hg_cs_ids["main"], hg_cs_ids[visible] });
+    }
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_listkeys_namespaces(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let mut hg_cs_ids = HashMap::new();
+    for name in ["scratch/alice/feature", "scratch/carol/feature"] {
+        let cs_id = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file(name, name)
+            .commit()
+            .await?;
+        bookmark(&ctx, &repo, name).create_scratch(cs_id).await?;
+        hg_cs_ids.insert(name, repo.derive_hg_changeset(&ctx, cs_id).await?);
+    }
+    let repo = Repo::new_test_with_config(ctx.clone(), repo, |config| {
+        config.infinitepush.namespace = Some(InfinitepushNamespace::new(
+            Regex::new("scratch/.+").unwrap(),
+        ));
+        config.infinitepush.private_namespace = Some(PrivateScratchNamespace {
+            pattern: Regex::new("scratch/(?P<user>[^/]+)/.+").unwrap().into(),
+            owner_identity_type: String::from("USER"),
+            readers: vec![],
+            shared_with: HashMap::new(),
+        });
+    })
+    .await?;
+
+    let metadata =
+        Metadata::default().set_identities(btreeset! { MononokeIdentity::new("USER", "alice") });
+    let session = SessionContainer::builder(fb)
+        .metadata(Arc::new(metadata))
+        .build();
+    let ctx = CoreContext::test_mock_session(session);
+    let mut repo_client = test_repo_client(&ctx, Arc::new(repo), Default::default());
+    let listkeys = |namespace: &str, prefix: &str| {
+        repo_client
+            .listkeys(namespace.to_string(), prefix.to_string(), None)
+            .compat()
+    };
+
+    assert_eq!(
+        listkeys("phases", "").await?,
+        hashmap! { b"publishing".to_vec() => b"True".to_vec() }
+    );
+
+    // Users only see their own private scratch bookmarks.
+    let hash: Vec<u8> = hg_cs_ids["scratch/alice/feature"]
+        .into_nodehash()
+        .to_hex()
+        .into();
+    assert_eq!(
+        listkeys("infinitepush", "scratch/").await?,
+        hashmap! { b"scratch/alice/feature".to_vec() => hash }
+    );
+    assert_eq!(
+        listkeys("infinitepush", "scratch/carol/").await?,
+        hashmap! {}
+    );
+    assert_eq!(listkeys("unknown", "").await?, hashmap! {});
+
+    // Further namespaces can be registered, but not the built-in ones.
+    repo_client.register_listkeys_provider(
+        "release",
+        Arc::new(StaticListKeys::new(vec![("channel", "stable")])),
+    )?;
+    assert!(
+        repo_client
+            .register_listkeys_provider(
+                "phases",
+                Arc::new(StaticListKeys::new(Vec::<(&str, &str)>::new())),
+            )
+            .is_err()
+    );
+    assert_eq!(
+        repo_client
+            .listkeys("release".to_string(), String::new(), None)
+            .compat()
+            .await?,
+        hashmap! { b"channel".to_vec() => b"stable".to_vec() }
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_headspaginated_publishing_delay(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let repo: BlobRepo = test_repo_factory::build_empty(fb)?;
+    let first = CreateCommitContext::new_root(&ctx, &repo)
+        .add_file("1", "1")
+        .commit()
+        .await?;
+    let second = CreateCommitContext::new(&ctx, &repo, vec![first])
+        .add_file("2", "2")
+        .commit()
+        .await?;
+    let first_hg = repo.derive_hg_changeset(&ctx, first).await?;
+    let second_hg = repo.derive_hg_changeset(&ctx, second).await?;
+    bookmark(&ctx, &repo, "main").set_to(first).await?;
+    bookmark(&ctx, &repo, "main").set_to(second).await?;
+    bookmark(&ctx, &repo, "main_approved").set_to(first).await?;
+    let repo = Repo::new_test_with_config(ctx.clone(), repo, |config| {
+        config.bookmarks = vec![BookmarkParams {
+            bookmark: BookmarkName::new("main").unwrap().into(),
+            hooks: vec![],
+            only_fast_forward: false,
+            rewrite_dates: None,
+            allowed_users: None,
+            allowed_hipster_group: None,
+            hooks_skip_ancestors_of: vec![],
+            ensure_ancestor_of: None,
+            allow_move_to_public_commits_without_hooks: false,
+            required_derived_data: vec![],
+            merge_policy: MergePolicy::default(),
+            publishing_delay: Some(BookmarkPublishingDelay {
+                delay: Duration::from_secs(3600),
+                canary_identities: vec![Identity {
+                    id_type: String::from("USER"),
+                    id_data: String::from("canary"),
+                }],
+                approval_bookmark: Some(BookmarkName::new("main_approved").unwrap()),
+            }),
+        }];
+    })
+    .await?;
+    let repo = Arc::new(repo);
+
+    // Canaries see the move straight away, other clients only see the
+    // position that has been approved.
+    for (user, main) in [("canary", second_hg), ("other", first_hg)] {
+        let metadata =
+            Metadata::default().set_identities(btreeset! { MononokeIdentity::new("USER", user) });
+        let session = SessionContainer::builder(fb)
+            .metadata(Arc::new(metadata))
+            .build();
+        let ctx = CoreContext::test_mock_session(session);
+        let page = test_repo_client(&ctx, repo.clone(), Default::default())
+            .headspaginated(HeadsPaginatedArgs::default())
+            .compat()
+            .await?;
+        assert_eq!(
+            page.heads,
+            vec![
+                ("main".to_string(), main),
+                ("main_approved".to_string(), first_hg),
+            ]
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_debug_format_directories() {
     assert_eq!(&debug_format_directories(vec![&"foo"]), "foo,");
@@ -477,3 +844,23 @@ fn test_parse_git_lookup() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_should_shadow() {
+    let mut config = WireprotoShadowingConfig {
+        scribe_category: "shadow".to_string(),
+        sample_rate: nonzero!(1u64),
+        commands: vec![],
+    };
+    // Nothing is shadowed unless it is configured.
+    assert!(!should_shadow(&config, ops::GETBUNDLE));
+    assert!(!should_shadow(&config, ops::GETTREEPACK));
+
+    config.commands = vec![ops::GETTREEPACK.to_string()];
+    assert!(!should_shadow(&config, ops::GETBUNDLE));
+    assert!(should_shadow(&config, ops::GETTREEPACK));
+
+    // Pushes are never shadowed.
+    config.commands.push(ops::UNBUNDLE.to_string());
+    assert!(!should_shadow(&config, ops::UNBUNDLE));
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Sampling of wireproto commands for tracing.
+//!
+//! Commands from identities that are always traced are traced in full.  The
+//! other commands are sampled at the rate configured for the command, or the
+//! repo's default rate, falling back to the global tunable.  A sampled
+//! command is traced in full if it is one of the commands configured for
+//! full traces, and otherwise only its top-level spans are recorded.
+//!
+//! The decision is logged with each command, together with the rate it was
+//! sampled at, so that metrics computed from traces can be re-weighted.
+
+use context::TraceLevel;
+use metaconfig_types::Identity;
+use metaconfig_types::TraceSamplingConfig;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+use rand::Rng;
+use tunables::tunables;
+
+/// Whether and how a command is traced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TraceSamplingDecision {
+    pub level: TraceLevel,
+    /// The command was sampled with probability `1 / sample_rate`, or not
+    /// at all if this is 0.
+    pub sample_rate: u64,
+}
+
+/// Trace sampling policy for the commands of a session.
+pub struct TraceSamplingPolicy {
+    config: TraceSamplingConfig,
+    always_trace: bool,
+}
+
+impl TraceSamplingPolicy {
+    pub fn new(config: Option<&TraceSamplingConfig>, identities: &MononokeIdentitySet) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        let always_trace =
+            config
+                .always_trace_identities
+                .iter()
+                .any(|Identity { id_type, id_data }| {
+                    identities.contains(&MononokeIdentity::new(id_type, id_data))
+                });
+        Self {
+            config,
+            always_trace,
+        }
+    }
+
+    fn sample_rate(&self, command: &str) -> u64 {
+        if let Some(rate) = self.config.command_sample_rates.get(command) {
+            return *rate;
+        }
+        match self.config.sample_rate {
+            Some(rate) => rate.get(),
+            None => tunables()
+                .get_wireproto_trace_sampling_rate()
+                .try_into()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Decide whether to trace this run of `command`.
+    pub fn decide(&self, command: &str) -> TraceSamplingDecision {
+        if self.always_trace {
+            return TraceSamplingDecision {
+                level: TraceLevel::Full,
+                sample_rate: 1,
+            };
+        }
+        let sample_rate = self.sample_rate(command);
+        let level = if sample_rate == 0 || rand::thread_rng().gen_range(0..sample_rate) != 0 {
+            TraceLevel::Off
+        } else if self.config.full_trace_commands.is_empty()
+            || self.config.full_trace_commands.iter().any(|c| c == command)
+        {
+            TraceLevel::Full
+        } else {
+            TraceLevel::TopLevel
+        };
+        TraceSamplingDecision { level, sample_rate }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU64;
+
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn test_trace_sampling_policy() {
+        let config = TraceSamplingConfig {
+            sample_rate: NonZeroU64::new(1),
+            command_sample_rates: hashmap! {
+                "known".to_string() => 0,
+            },
+            full_trace_commands: vec!["getbundle".to_string()],
+            always_trace_identities: vec![Identity {
+                id_type: "USER".to_string(),
+                id_data: "alice".to_string(),
+            }],
+        };
+        let alice = [MononokeIdentity::new("USER", "alice")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+        let bob = [MononokeIdentity::new("USER", "bob")]
+            .into_iter()
+            .collect::<MononokeIdentitySet>();
+
+        let policy = TraceSamplingPolicy::new(Some(&config), &bob);
+        assert_eq!(
+            policy.decide("getbundle"),
+            TraceSamplingDecision {
+                level: TraceLevel::Full,
+                sample_rate: 1,
+            }
+        );
+        assert_eq!(
+            policy.decide("gettreepack"),
+            TraceSamplingDecision {
+                level: TraceLevel::TopLevel,
+                sample_rate: 1,
+            }
+        );
+        assert_eq!(
+            policy.decide("known"),
+            TraceSamplingDecision {
+                level: TraceLevel::Off,
+                sample_rate: 0,
+            }
+        );
+
+        // Always traced identities are traced in full, even for commands
+        // that are otherwise never sampled.
+        let policy = TraceSamplingPolicy::new(Some(&config), &alice);
+        assert_eq!(
+            policy.decide("known"),
+            TraceSamplingDecision {
+                level: TraceLevel::Full,
+                sample_rate: 1,
+            }
+        );
+    }
+}
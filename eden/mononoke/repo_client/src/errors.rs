@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use mercurial_types::HgManifestId;
 use mercurial_types::HgNodeHash;
 use mercurial_types::RepoPath;
 use rate_limiting::RateLimitReason;
@@ -24,4 +25,8 @@ pub enum ErrorKind {
         #[source]
         reason: RateLimitReason,
     },
+    #[error("Client only supports changegroup versions {0}, but this repo requires version 03")]
+    UnsupportedChangegroupVersions(String),
+    #[error("Manifest {0} has been quarantined because it is corrupt")]
+    QuarantinedManifest(HgManifestId),
 }
@@ -14,8 +14,15 @@ mod errors;
 
 pub use client::fetch_treepack_part_input;
 pub use client::gettreepack_entries;
+pub use client::ListKeys;
+pub use client::ListKeysBatch;
+pub use client::ListKeysFilter;
+pub use client::ListKeysProvider;
 pub use client::RepoClient;
+pub use client::StaticListKeys;
 pub use getbundle_response::find_commits_to_send;
 pub use getbundle_response::find_new_draft_commits_and_derive_filenodes_for_public_roots;
+pub use unbundle::PartHandler;
+pub use unbundle::PartHandlerError;
 pub use unbundle::PushRedirector;
 pub use unbundle::PushRedirectorArgs;
@@ -11,6 +11,7 @@
 mod changegroup;
 mod errors;
 mod hook_running;
+mod part_handlers;
 mod processing;
 mod push_redirector;
 mod rate_limits;
@@ -22,6 +23,10 @@ mod upload_changesets;
 
 pub use hook_running::run_hooks;
 pub use hooks::CrossRepoPushSource;
+pub use part_handlers::AcceptPartHandler;
+pub use part_handlers::PartHandler;
+pub use part_handlers::PartHandlerError;
+pub use part_handlers::PartHandlerRegistry;
 pub use processing::run_post_resolve_action;
 pub use push_redirector::PushRedirector;
 pub use push_redirector::PushRedirectorArgs;
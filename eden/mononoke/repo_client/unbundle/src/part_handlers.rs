@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Handlers for custom bundle2 parts.
+//!
+//! Parts that aren't part of the Mercurial protocol, such as metadata parts
+//! sent by internal tooling, are passed through by the bundle2 parser with
+//! their payload undecoded.  Before a bundle is resolved, each of these parts
+//! is dispatched to the handler registered for its type in the repo's
+//! registry.  Unregistered advisory parts are skipped, but unregistered
+//! mandatory parts fail the push, as the client requires them to be
+//! understood.
+//!
+//! Each repo's registry is built once, when the repo is opened, and shared
+//! by all of its sessions.  The part types listed in the repo's
+//! `accepted_bundle2_parts` knob are registered with `AcceptPartHandler`.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::future;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mercurial_bundles::Bundle2Item;
+use mercurial_bundles::PartHeader;
+use mercurial_bundles::PartHeaderType;
+use mercurial_bundles::PartId;
+use slog::debug;
+use thiserror::Error;
+
+use crate::stats::*;
+
+/// Name stats are recorded under for part types without a handler, as the
+/// client chooses these names.
+const UNREGISTERED_PART_TYPE: &str = "other";
+
+#[derive(Debug, Error)]
+pub enum PartHandlerError {
+    #[error("Bundle2 part type '{0}' is handled by Mononoke and can't be registered")]
+    ReservedPartType(String),
+
+    #[error("A handler for bundle2 part type '{0}' is already registered")]
+    AlreadyRegistered(String),
+
+    #[error("Unsupported mandatory bundle2 part '{part_type}' (part id {part_id})")]
+    UnsupportedMandatoryPart { part_type: String, part_id: PartId },
+}
+
+/// Handler for a custom bundle2 part type.
+#[async_trait]
+pub trait PartHandler: Send + Sync {
+    /// Handle a part.  Any of the payload that the handler doesn't read is
+    /// discarded once it returns.
+    async fn handle(
+        &self,
+        ctx: &CoreContext,
+        header: &PartHeader,
+        payload: &mut BoxStream<'static, Result<Bytes>>,
+    ) -> Result<()>;
+}
+
+/// The handlers for the custom bundle2 parts accepted by a repo.
+#[derive(Clone, Default)]
+pub struct PartHandlerRegistry {
+    handlers: HashMap<String, Arc<dyn PartHandler>>,
+}
+
+impl PartHandlerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the handler for a custom part type.  Part types are
+    /// case-insensitive, and the types that Mononoke handles itself can't be
+    /// registered.
+    pub fn register(
+        &mut self,
+        part_type: &str,
+        handler: Arc<dyn PartHandler>,
+    ) -> Result<(), PartHandlerError> {
+        let part_type = match PartHeaderType::from_name(part_type) {
+            PartHeaderType::Custom(part_type) => part_type,
+            _ => return Err(PartHandlerError::ReservedPartType(part_type.to_string())),
+        };
+        match self.handlers.entry(part_type) {
+            Entry::Occupied(entry) => Err(PartHandlerError::AlreadyRegistered(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(handler);
+                Ok(())
+            }
+        }
+    }
+
+    /// A registry that accepts these custom part types with
+    /// `AcceptPartHandler`.  Part types that Mononoke handles itself, or
+    /// that are listed more than once, are already accepted, so they are
+    /// skipped.
+    pub fn with_accepted_parts<'a>(part_types: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut registry = Self::new();
+        let handler = Arc::new(AcceptPartHandler);
+        for part_type in part_types {
+            let _ = registry.register(part_type, handler.clone());
+        }
+        registry
+    }
+
+    /// The custom part types that have handlers.
+    pub fn part_types(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+}
+
+/// Handler that accepts a custom part, logging its size, without otherwise
+/// acting on it.  Used for parts that clients mark as mandatory but that
+/// the server doesn't need to understand.
+pub struct AcceptPartHandler;
+
+#[async_trait]
+impl PartHandler for AcceptPartHandler {
+    async fn handle(
+        &self,
+        ctx: &CoreContext,
+        header: &PartHeader,
+        payload: &mut BoxStream<'static, Result<Bytes>>,
+    ) -> Result<()> {
+        let mut size = 0;
+        while let Some(chunk) = payload.try_next().await? {
+            size += chunk.len();
+        }
+        ctx.scuba()
+            .clone()
+            .add("bundle2_part_type", header.part_type().as_str())
+            .add("bundle2_part_size", size)
+            .log_with_msg("Accepted custom bundle2 part", None);
+        Ok(())
+    }
+}
+
+/// Dispatch the custom parts of a bundle to their handlers as they are
+/// read, returning the rest of the bundle.
+pub(crate) fn dispatch_custom_parts(
+    ctx: CoreContext,
+    registry: Arc<PartHandlerRegistry>,
+    bundle2: BoxStream<'static, Result<Bundle2Item<'static>>>,
+) -> BoxStream<'static, Result<Bundle2Item<'static>>> {
+    bundle2
+        .and_then(move |item| {
+            let ctx = ctx.clone();
+            let registry = registry.clone();
+            async move {
+                match item {
+                    Bundle2Item::Custom(header, payload) => {
+                        handle_custom_part(&ctx, &registry, header, payload).await?;
+                        Ok(None)
+                    }
+                    item => Ok(Some(item)),
+                }
+            }
+        })
+        .try_filter_map(future::ok)
+        .boxed()
+}
+
+async fn handle_custom_part(
+    ctx: &CoreContext,
+    registry: &PartHandlerRegistry,
+    header: PartHeader,
+    payload: BoxStream<'static, Result<bytes_old::Bytes>>,
+) -> Result<()> {
+    let part_type = header.part_type().as_str().to_string();
+    let mut payload = payload
+        .map_ok(|chunk| Bytes::copy_from_slice(chunk.as_ref()))
+        .boxed();
+
+    match registry.handlers.get(&part_type) {
+        Some(handler) => {
+            handler
+                .handle(ctx, &header, &mut payload)
+                .await
+                .with_context(|| format!("While handling bundle2 part '{}'", part_type))?;
+            STATS::custom_parts_handled.add_value(1, (part_type,));
+        }
+        None if header.mandatory() => {
+            STATS::custom_parts_rejected.add_value(1, (UNREGISTERED_PART_TYPE.to_string(),));
+            return Err(PartHandlerError::UnsupportedMandatoryPart {
+                part_type,
+                part_id: header.part_id(),
+            }
+            .into());
+        }
+        None => {
+            debug!(
+                ctx.logger(),
+                "Skipping unsupported advisory bundle2 part '{}'", part_type
+            );
+            STATS::custom_parts_skipped.add_value(1, (UNREGISTERED_PART_TYPE.to_string(),));
+        }
+    }
+
+    // The rest of the bundle can only be read once the payload of this part
+    // has been consumed.
+    while payload.try_next().await?.is_some() {}
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use fbinit::FacebookInit;
+    use futures::stream;
+    use futures::FutureExt;
+    use mercurial_bundles::PartHeaderBuilder;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        payloads: Mutex<Vec<Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl PartHandler for RecordingHandler {
+        async fn handle(
+            &self,
+            _ctx: &CoreContext,
+            _header: &PartHeader,
+            payload: &mut BoxStream<'static, Result<Bytes>>,
+        ) -> Result<()> {
+            // Only read the first chunk, to check that the rest is drained.
+            let chunk = payload.try_next().await?.unwrap_or_default();
+            self.payloads.lock().unwrap().push(chunk.to_vec());
+            Ok(())
+        }
+    }
+
+    fn custom_part(part_type: &str, mandatory: bool, part_id: PartId) -> Bundle2Item<'static> {
+        let header = PartHeaderBuilder::new(PartHeaderType::from_name(part_type), mandatory)
+            .unwrap()
+            .build(part_id);
+        let payload = stream::iter(vec![
+            Ok(bytes_old::Bytes::from(&b"first"[..])),
+            Ok(bytes_old::Bytes::from(&b"second"[..])),
+        ]);
+        Bundle2Item::Custom(header, payload.boxed())
+    }
+
+    fn pushvars_part(part_id: PartId) -> Bundle2Item<'static> {
+        let header = PartHeaderBuilder::new(PartHeaderType::Pushvars, false)
+            .unwrap()
+            .build(part_id);
+        Bundle2Item::Pushvars(header, future::ok(()).boxed())
+    }
+
+    #[test]
+    fn test_register() {
+        let handler = Arc::new(RecordingHandler::default());
+        let mut registry = PartHandlerRegistry::new();
+        registry.register("X:Metadata", handler.clone()).unwrap();
+        assert_eq!(
+            registry.part_types().collect::<Vec<_>>(),
+            vec!["x:metadata"]
+        );
+        assert!(matches!(
+            registry.register("x:metadata", handler.clone()),
+            Err(PartHandlerError::AlreadyRegistered(_))
+        ));
+        assert!(matches!(
+            registry.register("pushvars", handler),
+            Err(PartHandlerError::ReservedPartType(_))
+        ));
+    }
+
+    #[test]
+    fn test_with_accepted_parts() {
+        let part_types = vec![
+            "X:Metadata".to_string(),
+            "x:metadata".to_string(),
+            "pushvars".to_string(),
+        ];
+        let registry = PartHandlerRegistry::with_accepted_parts(&part_types);
+        assert_eq!(
+            registry.part_types().collect::<Vec<_>>(),
+            vec!["x:metadata"]
+        );
+    }
+
+    #[fbinit::test]
+    async fn test_dispatch_custom_parts(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let handler = Arc::new(RecordingHandler::default());
+        let mut registry = PartHandlerRegistry::new();
+        registry.register("x:metadata", handler.clone())?;
+        let registry = Arc::new(registry);
+
+        let bundle2 = stream::iter(vec![
+            Ok(custom_part("X:METADATA", true, 0)),
+            Ok(pushvars_part(1)),
+            Ok(custom_part("x:unknown", false, 2)),
+        ])
+        .boxed();
+        let items = dispatch_custom_parts(ctx.clone(), registry.clone(), bundle2)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Bundle2Item::Pushvars(..)));
+        assert_eq!(*handler.payloads.lock().unwrap(), vec![b"first".to_vec()]);
+
+        let bundle2 = stream::iter(vec![Ok(custom_part("X:UNKNOWN", true, 3))]).boxed();
+        let err = dispatch_custom_parts(ctx, registry, bundle2)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PartHandlerError>(),
+            Some(PartHandlerError::UnsupportedMandatoryPart { part_id: 3, .. })
+        ));
+        Ok(())
+    }
+}
@@ -47,6 +47,7 @@ use mercurial_types::HgChangesetId;
 use metaconfig_types::PushrebaseFlags;
 use mononoke_types::BonsaiChangeset;
 use mononoke_types::ChangesetId;
+use path_policy::PathPolicy;
 use rate_limiting::RateLimitBody;
 use slog::trace;
 use topo_sort::sort_topological;
@@ -60,6 +61,8 @@ use crate::changegroup::split_changegroup;
 use crate::errors::*;
 use crate::hook_running::make_hook_rejection_remapper;
 use crate::hook_running::HookRejectionRemapper;
+use crate::part_handlers::dispatch_custom_parts;
+use crate::part_handlers::PartHandlerRegistry;
 use crate::rate_limits::enforce_file_changes_rate_limits;
 use crate::rate_limits::RateLimitedPushKind;
 use crate::stats::*;
@@ -254,18 +257,38 @@ pub enum PostResolveAction {
     BookmarkOnlyPushRebase(PostResolveBookmarkOnlyPushRebase),
 }
 
+impl PostResolveAction {
+    /// The changesets that were uploaded while resolving the bundle.
+    pub fn uploaded_changesets(&self) -> Vec<ChangesetId> {
+        let uploaded_bonsais = match self {
+            Self::Push(action) => &action.uploaded_bonsais,
+            Self::InfinitePush(action) => &action.uploaded_bonsais,
+            Self::PushRebase(action) => &action.uploaded_bonsais,
+            Self::BookmarkOnlyPushRebase(_) => return Vec::new(),
+        };
+        uploaded_bonsais
+            .iter()
+            .map(|bcs| bcs.get_changeset_id())
+            .collect()
+    }
+}
+
 /// The resolve function takes a bundle2, interprets it's content as Changesets, Filelogs and
 /// Manifests and uploades all of them to the provided BlobRepo in the correct order.
+/// Custom parts in the bundle are dispatched to their handlers in `part_handlers`.
 /// It returns a Future that contains the response that should be send back to the requester.
 pub async fn resolve<'a>(
     ctx: &'a CoreContext,
     repo: &'a BlobRepo,
     infinitepush_writes_allowed: bool,
     bundle2: BoxStream<'static, Result<Bundle2Item<'static>>>,
+    part_handlers: Arc<PartHandlerRegistry>,
     pure_push_allowed: bool,
     pushrebase_flags: PushrebaseFlags,
+    path_policy: PathPolicy,
     maybe_backup_repo_source: Option<BackupSourceRepo>,
 ) -> Result<PostResolveAction, BundleResolverError> {
+    let bundle2 = dispatch_custom_parts(ctx.clone(), part_handlers, bundle2);
     let result = resolve_impl(
         ctx,
         repo,
@@ -273,6 +296,7 @@ pub async fn resolve<'a>(
         bundle2,
         pure_push_allowed,
         pushrebase_flags,
+        path_policy,
         maybe_backup_repo_source,
     )
     .await;
@@ -287,9 +311,16 @@ async fn resolve_impl<'a>(
     bundle2: BoxStream<'static, Result<Bundle2Item<'static>>>,
     pure_push_allowed: bool,
     pushrebase_flags: PushrebaseFlags,
+    path_policy: PathPolicy,
     maybe_backup_repo_source: Option<BackupSourceRepo>,
 ) -> Result<PostResolveAction, BundleResolverError> {
-    let resolver = Bundle2Resolver::new(ctx, repo, infinitepush_writes_allowed, pushrebase_flags);
+    let resolver = Bundle2Resolver::new(
+        ctx,
+        repo,
+        infinitepush_writes_allowed,
+        pushrebase_flags,
+        path_policy,
+    );
     let bundle2 = resolver.resolve_stream_params(bundle2).await?;
     let bundle2 = resolver.resolve_replycaps(bundle2).await?;
 
@@ -793,6 +824,7 @@ struct Bundle2Resolver<'r> {
     infinitepush_writes_allowed: bool,
     #[allow(dead_code)]
     pushrebase_flags: PushrebaseFlags,
+    path_policy: PathPolicy,
 }
 
 impl<'r> Bundle2Resolver<'r> {
@@ -801,12 +833,14 @@ impl<'r> Bundle2Resolver<'r> {
         repo: &'r BlobRepo,
         infinitepush_writes_allowed: bool,
         pushrebase_flags: PushrebaseFlags,
+        path_policy: PathPolicy,
     ) -> Self {
         Self {
             ctx,
             repo,
             infinitepush_writes_allowed,
             pushrebase_flags,
+            path_policy,
         }
     }
 
@@ -1217,6 +1251,7 @@ impl<'r> Bundle2Resolver<'r> {
                     uploaded_changesets,
                     &filelogs,
                     &manifests,
+                    &self.path_policy,
                     maybe_backup_repo_source.clone(),
                 )
                 .await
@@ -175,6 +175,7 @@ impl UnbundleResponse {
                 lca_hint,
                 PhasesPart::Yes,
                 lfs_params,
+                None,
             )
             .await?;
 
@@ -21,4 +21,7 @@ define_stats! {
     per_changeset_manifests_count: timeseries(Rate, Average, Sum),
     per_changeset_filelogs_count: timeseries(Rate, Average, Sum),
     per_changeset_content_blobs_count: timeseries(Rate, Average, Sum),
+    custom_parts_handled: dynamic_timeseries("custom_parts.{}.handled", (part_type: String); Rate, Sum),
+    custom_parts_skipped: dynamic_timeseries("custom_parts.{}.skipped", (part_type: String); Rate, Sum),
+    custom_parts_rejected: dynamic_timeseries("custom_parts.{}.rejected", (part_type: String); Rate, Sum),
 }
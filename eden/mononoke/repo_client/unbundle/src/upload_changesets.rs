@@ -38,6 +38,7 @@ use mercurial_types::HgNodeKey;
 use mercurial_types::MPath;
 use mercurial_types::RepoPath;
 use mercurial_types::NULL_HASH;
+use path_policy::PathPolicy;
 use scuba_ext::MononokeScubaSampleBuilder;
 use wirepack::TreemanifestEntry;
 use wireproto_handler::BackupSourceRepo;
@@ -84,6 +85,7 @@ impl NewBlobs {
         manifest_root_id: HgManifestId,
         manifests: &Manifests,
         filelogs: &Filelogs,
+        path_policy: &PathPolicy,
     ) -> Result<Self> {
         if manifest_root_id.into_nodehash() == NULL_HASH {
             // If manifest root id is NULL_HASH then there is no content in this changest
@@ -107,6 +109,7 @@ impl NewBlobs {
                     get_manifest_parent_content(manifests, RepoPath::root(), p2.clone()),
                     manifests,
                     filelogs,
+                    path_policy,
                 )?;
                 STATS::per_changeset_manifests_count.add_value(counters.manifests_count as i64);
                 STATS::per_changeset_filelogs_count.add_value(counters.filelogs_count as i64);
@@ -158,6 +161,7 @@ impl NewBlobs {
         p2: Option<&ManifestContent>,
         manifests: &Manifests,
         filelogs: &Filelogs,
+        path_policy: &PathPolicy,
     ) -> Result<(Vec<HgBlobFuture>, WalkHelperCounters)> {
         if path_taken.len() > 4096 {
             bail!(
@@ -213,11 +217,15 @@ impl NewBlobs {
                         get_manifest_parent_content(manifests, key.path.clone(), p2.clone()),
                         manifests,
                         filelogs,
+                        path_policy,
                     )?;
                     entries.append(&mut walked_entries);
                     counters += sub_counters;
                 }
             } else {
+                // The file is new or modified in this manifest, so its path
+                // must follow the repo's path policy.
+                path_policy.check_path(&next_path)?;
                 let key = HgNodeKey {
                     path: RepoPath::FilePath(next_path),
                     hash: nodehash,
@@ -289,12 +297,13 @@ pub async fn upload_changeset(
     mut uploaded_changesets: UploadedChangesets,
     filelogs: &Filelogs,
     manifests: &Manifests,
+    path_policy: &PathPolicy,
     maybe_backup_repo_source: Option<BackupSourceRepo>,
 ) -> Result<UploadedChangesets, Error> {
     let NewBlobs {
         root_manifest,
         sub_entries,
-    } = NewBlobs::new(revlog_cs.manifestid(), manifests, filelogs)?;
+    } = NewBlobs::new(revlog_cs.manifestid(), manifests, filelogs, path_policy)?;
 
     let cs_metadata = ChangesetMetadata {
         user: String::from_utf8(revlog_cs.user().into())?,
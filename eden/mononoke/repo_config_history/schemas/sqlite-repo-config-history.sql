@@ -0,0 +1,18 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS `repo_config_history` (
+  `id` INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  `repo_id` INTEGER NOT NULL,
+  `previous_id` BIGINT NOT NULL,
+  `recorded_at` BIGINT NOT NULL,
+  `config` BLOB NOT NULL,
+  UNIQUE (`repo_id`, `previous_id`)
+);
+
+CREATE INDEX IF NOT EXISTS `repo_config_history_recorded_at`
+  ON `repo_config_history` (`repo_id`, `recorded_at`, `id`);
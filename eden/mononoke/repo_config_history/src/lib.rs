@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! History of the configuration of a repo.
+//!
+//! Each time the raw configuration of a repo changes, a snapshot of it is
+//! recorded along with the time it was recorded.  The config that was live
+//! at any later time can then be found by parsing the most recent snapshot
+//! recorded before it.  This is used to audit why past operations, such as
+//! pushes, were accepted under the bookmark, ACL and hook configuration of
+//! the time, and to replay them with that configuration.
+//!
+//! Snapshots are stored raw and parsed when they are read, so a snapshot
+//! recorded by an older server is parsed in the same way as the current
+//! config.
+
+mod sql;
+
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use metaconfig_parser::RawRepoConfigSnapshot;
+use metaconfig_types::RepoConfig;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+
+pub use crate::sql::SqlRepoConfigHistory;
+pub use crate::sql::SqlRepoConfigHistoryBuilder;
+
+/// A recorded version of the configuration of a repo.
+#[derive(Clone, Debug)]
+pub struct RepoConfigVersion {
+    /// Id of the version.  Later versions have larger ids.
+    pub id: u64,
+    /// When the version was recorded.  The version was live from this time
+    /// until the next version was recorded.
+    pub recorded_at: Timestamp,
+    /// The raw configuration of the repo.
+    pub snapshot: RawRepoConfigSnapshot,
+}
+
+impl RepoConfigVersion {
+    /// Parse the config of the repo for this version.
+    pub fn config(&self) -> Result<RepoConfig, Error> {
+        self.snapshot.parse()
+    }
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait RepoConfigHistory: Send + Sync {
+    fn repo_id(&self) -> RepositoryId;
+
+    /// Record the configuration of the repo at a given time, if it differs
+    /// from the latest recorded version.  Returns whether a new version was
+    /// recorded.
+    async fn record(
+        &self,
+        ctx: &CoreContext,
+        snapshot: &RawRepoConfigSnapshot,
+        recorded_at: Timestamp,
+    ) -> Result<bool, Error>;
+
+    /// Return the version of the configuration that was live at a given
+    /// time, if any had been recorded by then.
+    async fn version_at(
+        &self,
+        ctx: &CoreContext,
+        at: Timestamp,
+    ) -> Result<Option<RepoConfigVersion>, Error>;
+
+    /// List the versions recorded in a time range, oldest first, up to a
+    /// limit.
+    async fn list_versions(
+        &self,
+        ctx: &CoreContext,
+        from: Timestamp,
+        to: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RepoConfigVersion>, Error>;
+
+    /// Return the config of the repo that was live at a given time.
+    async fn config_at(
+        &self,
+        ctx: &CoreContext,
+        at: Timestamp,
+    ) -> Result<Option<RepoConfig>, Error> {
+        self.version_at(ctx, at)
+            .await?
+            .map(|version| version.config())
+            .transpose()
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use ::sql_ext::mononoke_queries;
+use anyhow::anyhow;
+use anyhow::Error;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use metaconfig_parser::RawRepoConfigSnapshot;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::SqlConnections;
+
+use super::RepoConfigHistory;
+use super::RepoConfigVersion;
+
+/// Number of times to try recording a version when other versions are
+/// being recorded concurrently.
+const RECORD_ATTEMPTS: usize = 10;
+
+mononoke_queries! {
+    // Each version records the id of the version it follows, which is
+    // unique, so only one of several concurrent writers that saw the same
+    // latest version can add a version after it.
+    write InsertVersion(values: (
+        repo_id: RepositoryId,
+        previous_id: u64,
+        recorded_at: Timestamp,
+        config: &[u8],
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO repo_config_history (repo_id, previous_id, recorded_at, config)
+         VALUES {values}"
+    }
+
+    read SelectLatestVersion(
+        repo_id: RepositoryId,
+    ) -> (u64, Timestamp, Vec<u8>) {
+        "SELECT id, recorded_at, config
+         FROM repo_config_history
+         WHERE repo_id = {repo_id}
+         ORDER BY id DESC
+         LIMIT 1"
+    }
+
+    read SelectVersionAt(
+        repo_id: RepositoryId,
+        at: Timestamp,
+    ) -> (u64, Timestamp, Vec<u8>) {
+        "SELECT id, recorded_at, config
+         FROM repo_config_history
+         WHERE repo_id = {repo_id} AND recorded_at <= {at}
+         ORDER BY recorded_at DESC, id DESC
+         LIMIT 1"
+    }
+
+    read SelectVersionsInRange(
+        repo_id: RepositoryId,
+        from: Timestamp,
+        to: Timestamp,
+        limit: u64,
+    ) -> (u64, Timestamp, Vec<u8>) {
+        "SELECT id, recorded_at, config
+         FROM repo_config_history
+         WHERE repo_id = {repo_id} AND recorded_at >= {from} AND recorded_at <= {to}
+         ORDER BY recorded_at ASC, id ASC
+         LIMIT {limit}"
+    }
+}
+
+pub struct SqlRepoConfigHistory {
+    connections: SqlConnections,
+    repo_id: RepositoryId,
+}
+
+#[derive(Clone)]
+pub struct SqlRepoConfigHistoryBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlRepoConfigHistoryBuilder {
+    const LABEL: &'static str = "repo_config_history";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-repo-config-history.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlRepoConfigHistoryBuilder {}
+
+impl SqlRepoConfigHistoryBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlRepoConfigHistory {
+        SqlRepoConfigHistory {
+            connections: self.connections,
+            repo_id,
+        }
+    }
+}
+
+fn to_version(row: (u64, Timestamp, Vec<u8>)) -> Result<RepoConfigVersion, Error> {
+    let (id, recorded_at, config) = row;
+    Ok(RepoConfigVersion {
+        id,
+        recorded_at,
+        snapshot: RawRepoConfigSnapshot::from_bytes(&config)?,
+    })
+}
+
+#[async_trait]
+impl RepoConfigHistory for SqlRepoConfigHistory {
+    fn repo_id(&self) -> RepositoryId {
+        self.repo_id
+    }
+
+    async fn record(
+        &self,
+        ctx: &CoreContext,
+        snapshot: &RawRepoConfigSnapshot,
+        recorded_at: Timestamp,
+    ) -> Result<bool, Error> {
+        let config = snapshot.to_bytes()?;
+        for _ in 0..RECORD_ATTEMPTS {
+            // Compare against the master, so that a change that was just
+            // recorded is not recorded again.
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlReadsMaster);
+            let latest =
+                SelectLatestVersion::query(&self.connections.read_master_connection, &self.repo_id)
+                    .await?;
+            let previous_id = match latest.into_iter().next() {
+                Some(row) => {
+                    let id = row.0;
+                    // A version that can no longer be deserialized is
+                    // treated as different, so that the current config is
+                    // recorded.
+                    if let Ok(latest) = to_version(row) {
+                        if &latest.snapshot == snapshot {
+                            return Ok(false);
+                        }
+                    }
+                    id
+                }
+                // Ids start at 1, so the first version follows version 0.
+                None => 0,
+            };
+
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlWrites);
+            let result = InsertVersion::query(
+                &self.connections.write_connection,
+                &[(
+                    &self.repo_id,
+                    &previous_id,
+                    &recorded_at,
+                    &config.as_slice(),
+                )],
+            )
+            .await?;
+            if result.affected_rows() > 0 {
+                return Ok(true);
+            }
+            // Another version was recorded after the one we compared
+            // against.  Compare against that one instead.
+        }
+        Err(anyhow!(
+            "repo {}: too many concurrent attempts to record the config",
+            self.repo_id
+        ))
+    }
+
+    async fn version_at(
+        &self,
+        ctx: &CoreContext,
+        at: Timestamp,
+    ) -> Result<Option<RepoConfigVersion>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows =
+            SelectVersionAt::query(&self.connections.read_connection, &self.repo_id, &at).await?;
+        rows.into_iter().next().map(to_version).transpose()
+    }
+
+    async fn list_versions(
+        &self,
+        ctx: &CoreContext,
+        from: Timestamp,
+        to: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<RepoConfigVersion>, Error> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = SelectVersionsInRange::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &from,
+            &to,
+            &limit,
+        )
+        .await?;
+        rows.into_iter().map(to_version).collect()
+    }
+}
@@ -0,0 +1,239 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::Error;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::try_join;
+use metaconfig_parser::RawRepoConfigSnapshot;
+use mononoke_types::Timestamp;
+use mononoke_types_mocks::repo::REPO_ONE;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use repo_config_history::RepoConfigHistory;
+use repo_config_history::SqlRepoConfigHistoryBuilder;
+use repos::RawRepoDefinition;
+use sql_construct::SqlConstruct;
+
+fn snapshot(hipster_acl: &str) -> RawRepoConfigSnapshot {
+    let mut repo_definition = RawRepoDefinition::default();
+    repo_definition.repo_id = Some(0);
+    repo_definition.repo_name = Some("repo".to_string());
+    repo_definition.hipster_acl = Some(hipster_acl.to_string());
+    RawRepoConfigSnapshot {
+        repo_definition,
+        repo_configs: BTreeMap::new(),
+        storage_configs: BTreeMap::new(),
+        acl_region_configs: BTreeMap::new(),
+    }
+}
+
+fn hipster_acl(snapshot: &RawRepoConfigSnapshot) -> Option<&str> {
+    snapshot.repo_definition.hipster_acl.as_deref()
+}
+
+#[fbinit::test]
+async fn test_version_at(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let history = SqlRepoConfigHistoryBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    assert!(
+        history
+            .record(
+                &ctx,
+                &snapshot("first"),
+                Timestamp::from_timestamp_secs(100)
+            )
+            .await?
+    );
+    assert!(
+        history
+            .record(
+                &ctx,
+                &snapshot("second"),
+                Timestamp::from_timestamp_secs(200)
+            )
+            .await?
+    );
+
+    assert!(
+        history
+            .version_at(&ctx, Timestamp::from_timestamp_secs(99))
+            .await?
+            .is_none()
+    );
+    let version = history
+        .version_at(&ctx, Timestamp::from_timestamp_secs(150))
+        .await?
+        .expect("version should exist");
+    assert_eq!(hipster_acl(&version.snapshot), Some("first"));
+    assert_eq!(version.recorded_at, Timestamp::from_timestamp_secs(100));
+    let version = history
+        .version_at(&ctx, Timestamp::from_timestamp_secs(200))
+        .await?
+        .expect("version should exist");
+    assert_eq!(hipster_acl(&version.snapshot), Some("second"));
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_record_unchanged(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let history = SqlRepoConfigHistoryBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    assert!(
+        history
+            .record(
+                &ctx,
+                &snapshot("first"),
+                Timestamp::from_timestamp_secs(100)
+            )
+            .await?
+    );
+    // Recording the same config again doesn't add a version.
+    assert!(
+        !history
+            .record(
+                &ctx,
+                &snapshot("first"),
+                Timestamp::from_timestamp_secs(200)
+            )
+            .await?
+    );
+    // Changing back to an earlier config does.
+    assert!(
+        history
+            .record(
+                &ctx,
+                &snapshot("second"),
+                Timestamp::from_timestamp_secs(300)
+            )
+            .await?
+    );
+    assert!(
+        history
+            .record(
+                &ctx,
+                &snapshot("first"),
+                Timestamp::from_timestamp_secs(400)
+            )
+            .await?
+    );
+
+    let versions = history
+        .list_versions(
+            &ctx,
+            Timestamp::from_timestamp_secs(0),
+            Timestamp::from_timestamp_secs(1000),
+            10,
+        )
+        .await?;
+    assert_eq!(
+        versions
+            .iter()
+            .map(|version| hipster_acl(&version.snapshot))
+            .collect::<Vec<_>>(),
+        vec![Some("first"), Some("second"), Some("first")]
+    );
+
+    let versions = history
+        .list_versions(
+            &ctx,
+            Timestamp::from_timestamp_secs(200),
+            Timestamp::from_timestamp_secs(1000),
+            1,
+        )
+        .await?;
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0].recorded_at, Timestamp::from_timestamp_secs(300));
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_repos_are_separate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let builder = SqlRepoConfigHistoryBuilder::with_sqlite_in_memory()?;
+    let zero = builder.clone().build(REPO_ZERO);
+    let one = builder.build(REPO_ONE);
+
+    zero.record(
+        &ctx,
+        &snapshot("first"),
+        Timestamp::from_timestamp_secs(100),
+    )
+    .await?;
+
+    assert!(
+        one.version_at(&ctx, Timestamp::from_timestamp_secs(100))
+            .await?
+            .is_none()
+    );
+    // The first config recorded for a repo is always a new version.
+    assert!(
+        one.record(
+            &ctx,
+            &snapshot("first"),
+            Timestamp::from_timestamp_secs(100)
+        )
+        .await?
+    );
+
+    Ok(())
+}
+
+#[fbinit::test]
+async fn test_record_concurrently(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let history = SqlRepoConfigHistoryBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    // Concurrent recorders of different configs each add a version, one
+    // after the other.
+    let (first, second) = try_join!(
+        history.record(
+            &ctx,
+            &snapshot("first"),
+            Timestamp::from_timestamp_secs(100)
+        ),
+        history.record(
+            &ctx,
+            &snapshot("second"),
+            Timestamp::from_timestamp_secs(100)
+        ),
+    )?;
+    assert!(first && second);
+
+    // Concurrent recorders of the same config add only one version.
+    let (first, second) = try_join!(
+        history.record(
+            &ctx,
+            &snapshot("third"),
+            Timestamp::from_timestamp_secs(200)
+        ),
+        history.record(
+            &ctx,
+            &snapshot("third"),
+            Timestamp::from_timestamp_secs(200)
+        ),
+    )?;
+    assert!(first != second);
+
+    let versions = history
+        .list_versions(
+            &ctx,
+            Timestamp::from_timestamp_secs(0),
+            Timestamp::from_timestamp_secs(1000),
+            10,
+        )
+        .await?;
+    assert_eq!(versions.len(), 3);
+    assert_eq!(hipster_acl(&versions[2].snapshot), Some("third"));
+
+    Ok(())
+}
@@ -62,6 +62,8 @@ use context::SessionContainer;
 use cross_repo_sync::create_commit_syncer_lease;
 use dbbookmarks::ArcSqlBookmarks;
 use dbbookmarks::SqlBookmarksBuilder;
+use derivation_queue::ArcDerivationQueue;
+use derivation_queue::DerivationQueueBuilder;
 #[cfg(fbcode_build)]
 use derived_data_client_library::Client as DerivationServiceClient;
 use derived_data_remote::DerivationClient;
@@ -77,6 +79,8 @@ use filenodes::ArcFilenodes;
 use filestore::ArcFilestoreConfig;
 use filestore::FilestoreConfig;
 use futures_watchdog::WatchdogExt;
+use hg_sha256_mapping::ArcHgSha256Mapping;
+use hg_sha256_mapping::SqlHgSha256MappingBuilder;
 use hooks::hook_loader::load_hooks;
 use hooks::ArcHookManager;
 use hooks::HookManager;
@@ -103,10 +107,16 @@ use mutable_renames::MutableRenames;
 use mutable_renames::SqlMutableRenamesStore;
 use newfilenodes::NewFilenodesBuilder;
 use parking_lot::Mutex;
+use patch_id_index::ArcPatchIdIndex;
+use patch_id_index::SqlPatchIdIndexBuilder;
 use permission_checker::AclProvider;
 use phases::ArcPhases;
+use preserved_bundles::ArcPreservedBundles;
+use preserved_bundles::PreservedBundlesBuilder;
 use pushrebase_mutation_mapping::ArcPushrebaseMutationMapping;
 use pushrebase_mutation_mapping::SqlPushrebaseMutationMappingConnection;
+use reachable_contents::ArcReachableContents;
+use reachable_contents::SqlReachableContentsBuilder;
 use readonlyblob::ReadOnlyBlobstore;
 use redactedblobstore::ArcRedactionConfigBlobstore;
 use redactedblobstore::RedactedBlobs;
@@ -116,6 +126,8 @@ use repo_blobstore::ArcRepoBlobstore;
 use repo_blobstore::RepoBlobstore;
 use repo_bookmark_attrs::ArcRepoBookmarkAttrs;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
+use repo_config_history::ArcRepoConfigHistory;
+use repo_config_history::SqlRepoConfigHistoryBuilder;
 use repo_cross_repo::ArcRepoCrossRepo;
 use repo_cross_repo::RepoCrossRepo;
 use repo_derived_data::ArcRepoDerivedData;
@@ -144,6 +156,8 @@ use segmented_changelog_types::ArcSegmentedChangelog;
 use skiplist::ArcSkiplistIndex;
 use skiplist::SkiplistIndex;
 use slog::o;
+use snapshot_bundles::ArcSnapshotBundles;
+use snapshot_bundles::SnapshotBundlesBuilder;
 use sql::SqlConnections;
 use sql::SqlConnectionsWithSchema;
 use sql_construct::SqlConstruct;
@@ -157,6 +171,8 @@ use streaming_clone::StreamingCloneBuilder;
 use synced_commit_mapping::SqlSyncedCommitMapping;
 use thiserror::Error;
 use tunables::tunables;
+use usage_attribution::ArcUsageAttribution;
+use usage_attribution::SqlUsageAttributionBuilder;
 use virtually_sharded_blobstore::VirtuallyShardedBlobstore;
 use warm_bookmarks_cache::ArcBookmarksCache;
 use warm_bookmarks_cache::NoopBookmarksCache;
@@ -578,6 +594,9 @@ pub enum RepoFactoryError {
     #[error("Error opening bonsai-svnrev mapping")]
     BonsaiSvnrevMapping,
 
+    #[error("Error opening hg SHA-256 mapping")]
+    HgSha256Mapping,
+
     #[error("Error opening pushrebase mutation mapping")]
     PushrebaseMutationMapping,
 
@@ -617,6 +636,27 @@ pub enum RepoFactoryError {
     #[error("Error creating streaming clone")]
     StreamingClone,
 
+    #[error("Error opening patch id index")]
+    PatchIdIndex,
+
+    #[error("Error opening preserved bundles")]
+    PreservedBundles,
+
+    #[error("Error opening derivation queue")]
+    DerivationQueue,
+
+    #[error("Error opening reachable contents index")]
+    ReachableContents,
+
+    #[error("Error opening repo config history")]
+    RepoConfigHistory,
+
+    #[error("Error opening snapshot bundles")]
+    SnapshotBundles,
+
+    #[error("Error opening usage attribution")]
+    UsageAttribution,
+
     #[error("Error creating push redirector base")]
     PushRedirectorBase,
 
@@ -817,6 +857,19 @@ impl RepoFactory {
         }
     }
 
+    pub async fn hg_sha256_mapping(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcHgSha256Mapping> {
+        let hg_sha256_mapping = self
+            .open::<SqlHgSha256MappingBuilder>(&repo_config.storage_config.metadata)
+            .await
+            .context(RepoFactoryError::HgSha256Mapping)?
+            .build(repo_identity.id());
+        Ok(Arc::new(hg_sha256_mapping))
+    }
+
     pub async fn pushrebase_mutation_mapping(
         &self,
         repo_config: &ArcRepoConfig,
@@ -986,6 +1039,7 @@ impl RepoFactory {
         changesets: &ArcChangesets,
         bonsai_hg_mapping: &ArcBonsaiHgMapping,
         filenodes: &ArcFilenodes,
+        hg_sha256_mapping: &ArcHgSha256Mapping,
         repo_blobstore: &ArcRepoBlobstore,
     ) -> Result<ArcRepoDerivedData> {
         let config = repo_config.derived_data_config.clone();
@@ -997,7 +1051,7 @@ impl RepoFactory {
         )?;
         let derivation_service_client =
             get_derivation_client(self.env.fb, self.env.remote_derivation_options.clone())?;
-        Ok(Arc::new(RepoDerivedData::new(
+        let repo_derived_data = RepoDerivedData::new(
             repo_identity.id(),
             repo_identity.name().to_string(),
             changesets.clone(),
@@ -1008,7 +1062,10 @@ impl RepoFactory {
             scuba,
             config,
             derivation_service_client,
-        )?))
+        )?;
+        Ok(Arc::new(
+            repo_derived_data.with_hg_sha256_mapping(hg_sha256_mapping.clone()),
+        ))
     }
 
     pub async fn skiplist_index(
@@ -1068,6 +1125,7 @@ impl RepoFactory {
             |p| FilestoreConfig {
                 chunk_size: Some(p.chunk_size),
                 concurrency: p.concurrency,
+                content_defined_chunking: p.content_defined_chunking,
             },
         );
         Arc::new(filestore_config)
@@ -1346,6 +1404,97 @@ impl RepoFactory {
         Ok(Arc::new(streaming_clone))
     }
 
+    pub async fn snapshot_bundles(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcSnapshotBundles> {
+        let snapshot_bundles = self
+            .open::<SnapshotBundlesBuilder>(&repo_config.storage_config.metadata)
+            .await
+            .context(RepoFactoryError::SnapshotBundles)?
+            .build(repo_identity.id());
+        Ok(Arc::new(snapshot_bundles))
+    }
+
+    pub async fn preserved_bundles(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcPreservedBundles> {
+        let preserved_bundles = self
+            .open::<PreservedBundlesBuilder>(&repo_config.storage_config.metadata)
+            .await
+            .context(RepoFactoryError::PreservedBundles)?
+            .build(repo_identity.id());
+        Ok(Arc::new(preserved_bundles))
+    }
+
+    pub async fn derivation_queue(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcDerivationQueue> {
+        let derivation_queue = self
+            .open::<DerivationQueueBuilder>(&repo_config.storage_config.metadata)
+            .await
+            .context(RepoFactoryError::DerivationQueue)?
+            .build(repo_identity.id());
+        Ok(Arc::new(derivation_queue))
+    }
+
+    pub async fn usage_attribution(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcUsageAttribution> {
+        Ok(Arc::new(
+            self.open::<SqlUsageAttributionBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::UsageAttribution)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn patch_id_index(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcPatchIdIndex> {
+        Ok(Arc::new(
+            self.open::<SqlPatchIdIndexBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::PatchIdIndex)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn reachable_contents(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcReachableContents> {
+        Ok(Arc::new(
+            self.open::<SqlReachableContentsBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::ReachableContents)?
+                .build(repo_identity.id()),
+        ))
+    }
+
+    pub async fn repo_config_history(
+        &self,
+        repo_config: &ArcRepoConfig,
+        repo_identity: &ArcRepoIdentity,
+    ) -> Result<ArcRepoConfigHistory> {
+        Ok(Arc::new(
+            self.open::<SqlRepoConfigHistoryBuilder>(&repo_config.storage_config.metadata)
+                .await
+                .context(RepoFactoryError::RepoConfigHistory)?
+                .build(repo_identity.id()),
+        ))
+    }
+
     pub async fn warm_bookmarks_cache(
         &self,
         bookmarks: &ArcBookmarks,
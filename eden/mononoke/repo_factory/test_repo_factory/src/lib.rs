@@ -39,17 +39,23 @@ use context::CoreContext;
 use dbbookmarks::ArcSqlBookmarks;
 use dbbookmarks::SqlBookmarksBuilder;
 use deleted_manifest::RootDeletedManifestV2Id;
+use derivation_queue::ArcDerivationQueue;
+use derivation_queue::DerivationQueueBuilder;
 use derived_data_filenodes::FilenodesOnlyPublic;
 use derived_data_manager::BonsaiDerivable;
 use ephemeral_blobstore::ArcRepoEphemeralStore;
 use ephemeral_blobstore::RepoEphemeralStore;
 use fastlog::RootFastlog;
+use fault_injection::FaultInjector;
+use faultblob::FaultBlobstore;
 use fbinit::FacebookInit;
 use filenodes::ArcFilenodes;
 use filestore::ArcFilestoreConfig;
 use filestore::FilestoreConfig;
 use fsnodes::RootFsnodeId;
 use git_types::TreeHandle;
+use hg_sha256_mapping::ArcHgSha256Mapping;
+use hg_sha256_mapping::SqlHgSha256MappingBuilder;
 use hooks::ArcHookManager;
 use hooks::HookManager;
 use hooks_content_stores::RepoFileContentManager;
@@ -76,15 +82,23 @@ use mutable_renames::ArcMutableRenames;
 use mutable_renames::MutableRenames;
 use mutable_renames::SqlMutableRenamesStore;
 use newfilenodes::NewFilenodesBuilder;
+use patch_id_index::ArcPatchIdIndex;
+use patch_id_index::SqlPatchIdIndexBuilder;
 use phases::ArcPhases;
+use preserved_bundles::ArcPreservedBundles;
+use preserved_bundles::PreservedBundlesBuilder;
 use pushrebase_mutation_mapping::ArcPushrebaseMutationMapping;
 use pushrebase_mutation_mapping::SqlPushrebaseMutationMappingConnection;
+use reachable_contents::ArcReachableContents;
+use reachable_contents::SqlReachableContentsBuilder;
 use redactedblobstore::RedactedBlobs;
 use rendezvous::RendezVousOptions;
 use repo_blobstore::ArcRepoBlobstore;
 use repo_blobstore::RepoBlobstore;
 use repo_bookmark_attrs::ArcRepoBookmarkAttrs;
 use repo_bookmark_attrs::RepoBookmarkAttrs;
+use repo_config_history::ArcRepoConfigHistory;
+use repo_config_history::SqlRepoConfigHistoryBuilder;
 use repo_cross_repo::ArcRepoCrossRepo;
 use repo_cross_repo::RepoCrossRepo;
 use repo_derived_data::ArcRepoDerivedData;
@@ -107,6 +121,8 @@ use segmented_changelog_types::ArcSegmentedChangelog;
 use skeleton_manifest::RootSkeletonManifestId;
 use skiplist::ArcSkiplistIndex;
 use skiplist::SkiplistIndex;
+use snapshot_bundles::ArcSnapshotBundles;
+use snapshot_bundles::SnapshotBundlesBuilder;
 use sql::rusqlite::Connection as SqliteConnection;
 use sql::Connection;
 use sql::SqlConnections;
@@ -119,6 +135,8 @@ use streaming_clone::ArcStreamingClone;
 use streaming_clone::StreamingCloneBuilder;
 use synced_commit_mapping::SqlSyncedCommitMapping;
 use unodes::RootUnodeManifestId;
+use usage_attribution::ArcUsageAttribution;
+use usage_attribution::SqlUsageAttributionBuilder;
 use wireproto_handler::ArcRepoHandlerBase;
 use wireproto_handler::PushRedirectorBase;
 use wireproto_handler::RepoHandlerBase;
@@ -176,6 +194,7 @@ pub fn default_test_repo_config() -> RepoConfig {
                 "default".to_string() => derived_data_types_config.clone(),
                 "backfilling".to_string() => derived_data_types_config
             ],
+            queue: None,
         },
         segmented_changelog_config: SegmentedChangelogConfig {
             enabled: true,
@@ -223,6 +242,7 @@ impl TestRepoFactory {
         metadata_con.execute_batch(SqlBonsaiGitMappingBuilder::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlBonsaiGlobalrevMappingBuilder::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlBonsaiSvnrevMappingBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlHgSha256MappingBuilder::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlBonsaiHgMappingBuilder::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlPhasesBuilder::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlPushrebaseMutationMappingConnection::CREATION_QUERY)?;
@@ -233,6 +253,13 @@ impl TestRepoFactory {
         metadata_con.execute_batch(SqlRepoLock::CREATION_QUERY)?;
         metadata_con.execute_batch(SqlSparseProfilesSizes::CREATION_QUERY)?;
         metadata_con.execute_batch(StreamingCloneBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SnapshotBundlesBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(PreservedBundlesBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(DerivationQueueBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlPatchIdIndexBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlReachableContentsBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlRepoConfigHistoryBuilder::CREATION_QUERY)?;
+        metadata_con.execute_batch(SqlUsageAttributionBuilder::CREATION_QUERY)?;
         let metadata_db =
             SqlConnectionsWithSchema::new_single(Connection::with_sqlite(metadata_con));
 
@@ -277,6 +304,14 @@ impl TestRepoFactory {
         self
     }
 
+    /// Inject faults into the blobstore of repos built by this factory.
+    /// This wraps the blobstore set by `with_blobstore`, so must be called
+    /// after it.
+    pub fn with_fault_injection(&mut self, injector: Arc<FaultInjector>) -> &mut Self {
+        self.blobstore = Arc::new(FaultBlobstore::new(self.blobstore.clone(), injector));
+        self
+    }
+
     /// Redact content in repos that are built by this factory.
     pub fn redacted(&mut self, redacted: Option<RedactedBlobs>) -> &mut Self {
         self.redacted = redacted.map(Arc::new);
@@ -430,6 +465,14 @@ impl TestRepoFactory {
         ))
     }
 
+    /// Construct Hg SHA-256 Mapping using the in-memory metadata database.
+    pub fn hg_sha256_mapping(&self, repo_identity: &ArcRepoIdentity) -> Result<ArcHgSha256Mapping> {
+        Ok(Arc::new(
+            SqlHgSha256MappingBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        ))
+    }
+
     /// Construct Pushrebase Mutation Mapping using the in-memory metadata
     /// database.
     pub fn pushrebase_mutation_mapping(
@@ -503,13 +546,14 @@ impl TestRepoFactory {
         changesets: &ArcChangesets,
         bonsai_hg_mapping: &ArcBonsaiHgMapping,
         filenodes: &ArcFilenodes,
+        hg_sha256_mapping: &ArcHgSha256Mapping,
         repo_blobstore: &ArcRepoBlobstore,
     ) -> Result<ArcRepoDerivedData> {
         let lease = self.derived_data_lease.as_ref().map_or_else(
             || Arc::new(InProcessLease::new()) as Arc<dyn LeaseOps>,
             |lease| lease(),
         );
-        Ok(Arc::new(RepoDerivedData::new(
+        let repo_derived_data = RepoDerivedData::new(
             repo_identity.id(),
             repo_identity.name().to_string(),
             changesets.clone(),
@@ -520,7 +564,10 @@ impl TestRepoFactory {
             MononokeScubaSampleBuilder::with_discard(),
             repo_config.derived_data_config.clone(),
             None, // derivation_service_client = None
-        )?))
+        )?;
+        Ok(Arc::new(
+            repo_derived_data.with_hg_sha256_mapping(hg_sha256_mapping.clone()),
+        ))
     }
 
     /// Construct the RepoBlobstore using the blobstore in the factory.
@@ -541,6 +588,7 @@ impl TestRepoFactory {
             |p| FilestoreConfig {
                 chunk_size: Some(p.chunk_size),
                 concurrency: p.concurrency,
+                content_defined_chunking: p.content_defined_chunking,
             },
         );
         Arc::new(filestore_config)
@@ -699,6 +747,62 @@ impl TestRepoFactory {
         )
     }
 
+    /// Patch id index
+    pub fn patch_id_index(&self, repo_identity: &ArcRepoIdentity) -> ArcPatchIdIndex {
+        Arc::new(
+            SqlPatchIdIndexBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Reachable contents index
+    pub fn reachable_contents(&self, repo_identity: &ArcRepoIdentity) -> ArcReachableContents {
+        Arc::new(
+            SqlReachableContentsBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Repo config history
+    pub fn repo_config_history(&self, repo_identity: &ArcRepoIdentity) -> ArcRepoConfigHistory {
+        Arc::new(
+            SqlRepoConfigHistoryBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Snapshot bundles
+    pub fn snapshot_bundles(&self, repo_identity: &ArcRepoIdentity) -> ArcSnapshotBundles {
+        Arc::new(
+            SnapshotBundlesBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Preserved bundles
+    pub fn preserved_bundles(&self, repo_identity: &ArcRepoIdentity) -> ArcPreservedBundles {
+        Arc::new(
+            PreservedBundlesBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Derivation queue
+    pub fn derivation_queue(&self, repo_identity: &ArcRepoIdentity) -> ArcDerivationQueue {
+        Arc::new(
+            DerivationQueueBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
+    /// Usage attribution
+    pub fn usage_attribution(&self, repo_identity: &ArcRepoIdentity) -> ArcUsageAttribution {
+        Arc::new(
+            SqlUsageAttributionBuilder::from_sql_connections(self.metadata_db.clone().into())
+                .build(repo_identity.id()),
+        )
+    }
+
     /// Sql query config
     pub fn sql_query_config(&self) -> ArcSqlQueryConfig {
         Arc::new(SqlQueryConfig { caching: None })
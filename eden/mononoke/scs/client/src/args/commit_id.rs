@@ -503,6 +503,7 @@ async fn try_resolve_hg_commit_id(
             "note: several hg commits with the prefix '{}' exist",
             value.as_ref()
         );
+        report_candidates(&resp, thrift::CommitIdentityScheme::HG);
     }
 
     Ok(resp
@@ -510,6 +511,21 @@ async fn try_resolve_hg_commit_id(
         .and_then(|ids| ids.get(&thrift::CommitIdentityScheme::BONSAI).cloned()))
 }
 
+/// Print the commits an ambiguous prefix could refer to.
+fn report_candidates(
+    resp: &thrift::RepoResolveCommitPrefixResponse,
+    scheme: thrift::CommitIdentityScheme,
+) {
+    for candidate in resp.candidates.iter().flatten() {
+        if let Some((_, id)) = candidate.get(&scheme).and_then(map_commit_id) {
+            eprintln!("  {}", id);
+        }
+    }
+    if resp.more_candidates {
+        eprintln!("  ...");
+    }
+}
+
 /// Try to resolve a hex string to a bonsai changeset ID (it can be prefix of the full hash)
 async fn try_resolve_bonsai_id(
     conn: &Connection,
@@ -540,6 +556,7 @@ async fn try_resolve_bonsai_id(
             "note: several bonsai commits with the prefix '{}' exist",
             value.as_ref()
         );
+        report_candidates(&resp, thrift::CommitIdentityScheme::BONSAI);
     }
 
     Ok(resp
@@ -9,6 +9,7 @@ use crate::ScscApp;
 
 base_app::subcommands! {
     type App = ScscApp;
+    mod blob_fetch;
     mod cat;
     mod blame;
     mod common_base;
@@ -21,6 +22,7 @@ base_app::subcommands! {
     mod is_ancestor;
     mod land_stack if "SCSC_WRITES_ENABLED";
     mod list_bookmarks;
+    mod locate;
     mod log;
     mod lookup;
     mod ls;
@@ -0,0 +1,64 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Result;
+use source_control::types as thrift;
+
+use crate::args::repo::RepoArgs;
+use crate::ScscApp;
+
+#[derive(clap::Parser)]
+/// Fetch the raw contents of a blob from the repo's blobstore
+///
+/// The blob's metadata is printed to stderr.  This is for debugging, and is
+/// only permitted for identities that are allowed to fetch raw blobs.
+pub(super) struct CommandArgs {
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+    #[clap(long, short)]
+    /// Key of the blob, without the repo prefix
+    key: String,
+    #[clap(long, short)]
+    /// Write the contents of the blob to this file instead of stdout
+    output: Option<PathBuf>,
+}
+
+pub(super) async fn run(app: ScscApp, args: CommandArgs) -> Result<()> {
+    let repo = args.repo_args.into_repo_specifier();
+    let params = thrift::RepoBlobFetchParams {
+        key: args.key.clone(),
+        ..Default::default()
+    };
+    let response = app.connection.repo_blob_fetch(&repo, &params).await?;
+    let data = match response.data {
+        Some(data) => data,
+        None => bail!("blob {} does not exist", args.key),
+    };
+
+    if let Some(metadata) = response.metadata {
+        if let Some(ctime) = metadata.ctime {
+            eprintln!("ctime: {}", ctime);
+        }
+        if let Some(compressed_size) = metadata.compressed_size {
+            eprintln!("compressed size: {}", compressed_size);
+        }
+        if let Some(pack_key) = metadata.pack_key {
+            eprintln!("pack key: {}", pack_key);
+        }
+    }
+    eprintln!("size: {}", data.len());
+
+    match args.output {
+        Some(path) => std::fs::write(path, &data)?,
+        None => std::io::stdout().write_all(&data)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Find which repos contain a commit.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::bail;
+use anyhow::Result;
+use serde::Serialize;
+use source_control::types as thrift;
+
+use crate::args::commit_id::map_commit_ids;
+use crate::args::commit_id::SchemeArgs;
+use crate::lib::commit_id::render_commit_id;
+use crate::render::Render;
+use crate::ScscApp;
+
+#[derive(clap::Parser)]
+/// Find which repos contain a commit, given a prefix of its hg or bonsai hash
+pub(super) struct CommandArgs {
+    #[clap(flatten)]
+    scheme_args: SchemeArgs,
+    #[clap(long = "repo", short = 'R')]
+    /// Only look in these repos (default: all repos)
+    repos: Vec<String>,
+    /// Prefix of the commit hash
+    prefix: String,
+}
+
+#[derive(Serialize)]
+struct LocatedCommit {
+    repo: String,
+    ambiguous: bool,
+    ids: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct LocateOutput {
+    #[serde(skip)]
+    requested: String,
+    commits: Vec<LocatedCommit>,
+}
+
+impl Render for LocateOutput {
+    type Args = SchemeArgs;
+
+    fn render(&self, args: &Self::Args, w: &mut dyn Write) -> Result<()> {
+        if self.commits.is_empty() {
+            bail!("{} was not found in any repo\n", self.requested);
+        }
+        let schemes = args.scheme_string_set();
+        for commit in self.commits.iter() {
+            if commit.ambiguous {
+                write!(
+                    w,
+                    "{}: several commits match {}",
+                    commit.repo, self.requested
+                )?;
+            } else {
+                render_commit_id(
+                    Some((&commit.repo, "    ")),
+                    "\n",
+                    &self.requested,
+                    &commit.ids,
+                    &schemes,
+                    w,
+                )?;
+            }
+            write!(w, "\n")?;
+        }
+        Ok(())
+    }
+
+    fn render_json(&self, _args: &Self::Args, w: &mut dyn Write) -> Result<()> {
+        Ok(serde_json::to_writer(w, self)?)
+    }
+}
+
+pub(super) async fn run(app: ScscApp, args: CommandArgs) -> Result<()> {
+    if args.prefix.chars().any(|c| !c.is_ascii_hexdigit()) {
+        bail!("{} is not a hex commit hash prefix", args.prefix);
+    }
+
+    let repos = if args.repos.is_empty() {
+        None
+    } else {
+        Some(
+            args.repos
+                .iter()
+                .map(|name| thrift::RepoSpecifier {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    // The prefix may be of either an hg or a bonsai hash, so look for both.
+    let mut commits = Vec::new();
+    for (prefix_scheme, max_len) in [
+        (thrift::CommitIdentityScheme::HG, 40),
+        (thrift::CommitIdentityScheme::BONSAI, 64),
+    ] {
+        if args.prefix.len() > max_len {
+            continue;
+        }
+        let params = thrift::LocateCommitParams {
+            prefix: args.prefix.clone(),
+            prefix_scheme,
+            identity_schemes: args.scheme_args.clone().into_request_schemes(),
+            repos: repos.clone(),
+            ..Default::default()
+        };
+        let response = app.connection.locate_commit(&params).await?;
+        for (repo, resolved) in response.repos {
+            commits.push(LocatedCommit {
+                repo,
+                ambiguous: resolved.resolved_type
+                    == thrift::RepoResolveCommitPrefixResponseType::AMBIGUOUS,
+                ids: match &resolved.ids {
+                    Some(ids) => map_commit_ids(ids.values()),
+                    None => BTreeMap::new(),
+                },
+            });
+        }
+    }
+    commits.sort_by(|a, b| a.repo.cmp(&b.repo));
+
+    let output = LocateOutput {
+        requested: args.prefix,
+        commits,
+    };
+    app.target.render_one(&args.scheme_args, output).await
+}
@@ -646,6 +646,22 @@ struct RepoInfoParams {}
 
 struct ListReposParams {}
 
+struct LocateCommitParams {
+  /// The commit hash prefix to look up.
+  1: string prefix;
+
+  /// Identity scheme of the given prefix.  Globalrevs are specific to a
+  /// repo, so can't be located.
+  2: CommitIdentityScheme prefix_scheme;
+
+  /// Commit identity schemes to return.
+  3: set<CommitIdentityScheme> identity_schemes;
+
+  /// Only look in these repos.  If not set, every repo the caller can read
+  /// is searched.
+  4: optional list<RepoSpecifier> repos;
+}
+
 struct RepoResolveBookmarkParams {
   /// The bookmark name to look up.
   1: string bookmark_name;
@@ -655,7 +671,8 @@ struct RepoResolveBookmarkParams {
 }
 
 struct RepoResolveCommitPrefixParams {
-  /// The commit hash prefix to look up.
+  /// The commit hash prefix to look up.  Hash prefixes must be at least as
+  /// long as the server's configured minimum.
   1: string prefix;
 
   /// Identity scheme of the given prefix.
@@ -676,6 +693,52 @@ struct RepoBookmarkInfoParams {
   2: set<CommitIdentityScheme> identity_schemes;
 }
 
+struct RepoPushedCommitsParams {
+  /// The bookmark the commits were pushed to.
+  1: string bookmark_name;
+
+  /// Start of the time range, as a Unix timestamp in seconds (inclusive).
+  2: i64 from_timestamp;
+
+  /// End of the time range, as a Unix timestamp in seconds (inclusive).
+  3: i64 to_timestamp;
+
+  /// Return the commits in the given format.
+  4: HistoryFormat format;
+
+  /// Number of commits to return.
+  5: i32 limit;
+
+  /// Number of commits to skip before returning commits.
+  6: i32 skip;
+
+  /// Commit identity schemes to return.
+  7: set<CommitIdentityScheme> identity_schemes;
+}
+
+const i64 REPO_TAIL_COMMITS_MAX_WAIT_MS = 60000;
+
+struct RepoTailCommitsParams {
+  /// Cursor returned by a previous call.  If not set, tailing starts from
+  /// the latest bookmark move, and only the cursor is returned.
+  1: optional i64 cursor;
+
+  /// Only return commits that landed on this bookmark.
+  2: optional string bookmark_name;
+
+  /// Number of commits to return.  All the commits landed by a single
+  /// bookmark move are returned together, so this may be exceeded.
+  3: i32 limit;
+
+  /// If no commits have landed after the cursor, wait up to this many
+  /// milliseconds for some to land before returning.  At most
+  /// REPO_TAIL_COMMITS_MAX_WAIT_MS.
+  4: i64 wait_ms;
+
+  /// Commit identity schemes to return.
+  5: set<CommitIdentityScheme> identity_schemes;
+}
+
 const i64 REPO_LIST_BOOKMARKS_MAX_LIMIT = 10000;
 
 struct RepoListBookmarksParams {
@@ -817,6 +880,23 @@ struct RepoCreateCommitParams {
   5: optional string service_identity;
 }
 
+struct RepoAmendCommitExtrasParams {
+  /// The commit to amend.
+  1: CommitId commit;
+
+  /// Extras to add to the commit, replacing any existing values.
+  2: map<string, binary> set_extras;
+
+  /// Names of extras to remove from the commit.
+  3: set<string> remove_extras;
+
+  /// Commit identity schemes to return.
+  4: set<CommitIdentityScheme> identity_schemes;
+
+  /// Service identity to use for this commit creation.
+  5: optional string service_identity;
+}
+
 struct RepoCreateBookmarkParams {
   /// The name of the bookmark to move.
   1: string bookmark;
@@ -874,6 +954,56 @@ struct RepoDeleteBookmarkParams {
   3: optional string service_identity;
 }
 
+struct BookmarkCreate {
+  /// The name of the bookmark to create.
+  1: string bookmark;
+
+  /// The target commit for the bookmark.
+  2: CommitId target;
+}
+
+struct BookmarkMove {
+  /// The name of the bookmark to move.
+  1: string bookmark;
+
+  /// The new target commit for the bookmark.
+  2: CommitId target;
+
+  /// The old bookmark target.  If provided, only move the bookmark if it
+  /// points at this commit.
+  3: optional CommitId old_target;
+
+  /// Whether non-fast-forward moves are allowed (a.k.a. force move).  See
+  /// `RepoMoveBookmarkParams.allow_non_fast_forward_move`.
+  4: bool allow_non_fast_forward_move;
+}
+
+struct BookmarkDelete {
+  /// The name of the bookmark to delete.
+  1: string bookmark;
+
+  /// The old bookmark target.  If provided, only delete the bookmark if it
+  /// points at this commit.
+  2: optional CommitId old_target;
+}
+
+union BookmarkModification {
+  1: BookmarkCreate create_bookmark;
+  2: BookmarkMove move_bookmark;
+  3: BookmarkDelete delete_bookmark;
+}
+
+struct RepoModifyBookmarksParams {
+  /// The modifications to make.  Each bookmark may only appear once.
+  1: list<BookmarkModification> modifications;
+
+  /// The pushvars to use when modifying the bookmarks.
+  2: optional map<string, binary> pushvars;
+
+  /// Service identity to use for this bookmark modification.
+  3: optional string service_identity;
+}
+
 enum CrossRepoPushSource {
   NATIVE_TO_THIS_REPO = 0,
   PUSH_REDIRECTED = 1,
@@ -938,6 +1068,37 @@ struct RepoPrepareCommitsParams {
   2: DerivedDataType derived_data_type;
 }
 
+struct RepoBlobFetchParams {
+  /// The key of the blob, without the repo's prefix.  Keys of file content
+  /// chunks and aliases cannot be fetched, as they are not redacted.
+  1: string key;
+}
+
+struct RepoRunHooksDryRunCommit {
+  /// The info for the commit.
+  1: RepoCreateCommitParamsCommitInfo info;
+
+  /// A mapping from path to the change that is made at that path.  New file
+  /// contents must have been uploaded already, and be referred to by id or
+  /// content hash.  Copy information is ignored.
+  2: map<string, RepoCreateCommitParamsChange> changes;
+}
+
+struct RepoRunHooksDryRunParams {
+  /// Run the same hooks as when landing to this bookmark.
+  1: string bookmark;
+
+  /// The parents of the first commit in the stack.
+  2: list<CommitId> parents;
+
+  /// The commits in the stack, in order.  Each commit after the first is a
+  /// child of the one before it.
+  3: list<RepoRunHooksDryRunCommit> commits;
+
+  /// Pushvars used on the push.
+  4: optional map<string, binary> pushvars;
+}
+
 struct CommitLookupParams {
   /// Commit identity schemes to return.
   1: set<CommitIdentityScheme> identity_schemes;
@@ -965,6 +1126,14 @@ struct CommitCommonBaseWithParams {
   2: set<CommitIdentityScheme> identity_schemes;
 }
 
+struct CommitTraceMergeCopiesParams {
+  /// Commit that this commit is being merged with.
+  1: CommitId other_commit_id;
+  /// Merge base to trace renames from.  By default it's the common base of
+  /// the two commits.
+  2: optional CommitId base_commit_id;
+}
+
 const i64 COMMIT_COMPARE_ORDERED_MAX_LIMIT = 10000;
 
 struct CommitCompareOrderedParams {
@@ -1143,7 +1312,14 @@ struct CommitRunHooksParams {
 
 struct CommitPathExistsParams {}
 
-struct CommitPathInfoParams {}
+struct CommitPathInfoParams {
+  /// Also return the rollup of all files at or under the path.
+  1: bool include_rollup;
+
+  /// Commit identity schemes to return for the commit that last modified
+  /// the path in the rollup.
+  2: set<CommitIdentityScheme> identity_schemes;
+}
 
 struct CommitMultiplePathInfoParams {
   /// List of paths to query.
@@ -1487,6 +1663,19 @@ struct RepoResolveCommitPrefixResponse {
 
   /// The resolve commit IDs in the requested schemes (if type == RESOLVED)
   2: optional map<CommitIdentityScheme, CommitId> ids;
+
+  /// The IDs of the commits matching the prefix in the requested schemes
+  /// (if type == AMBIGUOUS)
+  3: optional list<map<CommitIdentityScheme, CommitId>> candidates;
+
+  /// Whether more commits match the prefix than are listed in `candidates`.
+  4: bool more_candidates;
+}
+
+struct LocateCommitResponse {
+  /// How the prefix resolved in each repo where it matched at least one
+  /// commit, by repo name.  Repos the caller can't read are omitted.
+  1: map<string, RepoResolveCommitPrefixResponse> repos;
 }
 
 struct RepoBookmarkInfoResponse {
@@ -1494,6 +1683,34 @@ struct RepoBookmarkInfoResponse {
   1: optional BookmarkInfo info;
 }
 
+struct RepoPushedCommitsResponse {
+  /// The commits that were pushed in the range, newest first.
+  1: History commits;
+}
+
+struct TailedCommit {
+  /// The commit that landed.
+  1: CommitInfo info;
+
+  /// The bookmark it landed on.
+  2: string bookmark_name;
+
+  /// When the bookmark was moved, as a Unix timestamp in seconds.
+  3: i64 pushed_at;
+
+  /// Cursor of the bookmark move that landed the commit.  Tailing can be
+  /// resumed after this move by passing it as the cursor.
+  4: i64 cursor;
+}
+
+struct RepoTailCommitsResponse {
+  /// The commits that landed after the cursor, in the order they landed.
+  1: list<TailedCommit> commits;
+
+  /// Cursor to pass to the next call to continue tailing.
+  2: i64 next_cursor;
+}
+
 struct RepoListBookmarksResponse {
   /// A map from bookmark name to the bookmarked commit's IDs in the
   /// requested schemes (if available).
@@ -1524,18 +1741,50 @@ struct RepoCreateCommitResponse {
   1: map<CommitIdentityScheme, CommitId> ids;
 }
 
+struct RepoAmendCommitExtrasResponse {
+  /// The IDs of the amended commit.
+  1: map<CommitIdentityScheme, CommitId> ids;
+}
+
 struct RepoCreateBookmarkResponse {}
 
 struct RepoMoveBookmarkResponse {}
 
 struct RepoDeleteBookmarkResponse {}
 
+struct RepoModifyBookmarksResponse {}
+
 struct RepoLandStackResponse {
   1: PushrebaseOutcome pushrebase_outcome;
 }
 
 struct RepoPrepareCommitsResponse {}
 
+struct BlobMetadata {
+  /// When the blob was written, as a Unix timestamp in seconds, if known.
+  1: optional i64 ctime;
+
+  /// The size of the blob after compression, if known.
+  2: optional i64 compressed_size;
+
+  /// The key of the pack the blob is stored in, if it is packed.
+  3: optional string pack_key;
+}
+
+struct RepoBlobFetchResponse {
+  /// The raw contents of the blob, null if it doesn't exist.
+  1: optional binary data;
+
+  /// The blobstore's metadata for the blob, null if it doesn't exist.
+  2: optional BlobMetadata metadata;
+}
+
+struct RepoRunHooksDryRunResponse {
+  /// The outcomes of the hooks for each commit in the stack, in the same
+  /// order as the commits in the request.
+  1: list<CommitRunHooksResponse> commits;
+}
+
 struct CommitCompareResponse {
   /// List of the files that are different between commits with their metadata
   /// Can be used for subsequent `commit_path_diff` calls for file-level diffs.
@@ -1563,6 +1812,30 @@ struct CommitFileDiffsStoppedAtPair {
   2: optional Path other_path;
 }
 
+/// Which side of a merge a file was renamed on.
+enum MergeSide {
+  /// The commit the request was made for.
+  LOCAL = 1,
+  /// The commit it is being merged with.
+  OTHER = 2,
+}
+
+/// A file that was renamed on one side of a merge, and modified at its
+/// original path on the other side.
+struct MergeRename {
+  1: MergeSide renamed_on;
+  /// Path of the file in the merge base.
+  2: Path base_path;
+  /// Path of the file after the rename.
+  3: Path renamed_path;
+}
+
+struct CommitTraceMergeCopiesResponse {
+  /// Renames that the merge must apply the other side's changes through,
+  /// ordered by base path.
+  1: list<MergeRename> renames;
+}
+
 struct CommitFileDiffsResponse {
   1: list<CommitFileDiffsResponseElement> path_diffs;
   /// The first pair for which a diff was not returned. Start next request from this pair if you want to resume.
@@ -1641,6 +1914,29 @@ struct CommitPathInfoResponse {
 
   /// The info for the item.
   3: optional EntryInfo info;
+
+  /// Aggregate information about all files at or under this path.  Only
+  /// present if requested with `include_rollup` and the path exists.
+  4: optional PathRollup rollup;
+}
+
+/// Aggregate information about all files at or under a path.
+struct PathRollup {
+  /// Total size of all files at or under the path.
+  1: i64 total_size;
+
+  /// Number of files at or under the path.
+  2: i64 file_count;
+
+  /// The commit that last modified any file at or under the path.
+  3: map<CommitIdentityScheme, CommitId> last_modified_commit;
+
+  /// The author date of that commit, in seconds since the epoch.  This is
+  /// the equivalent of the most recent modification time of the files.
+  4: i64 last_modified_date;
+
+  /// The timezone offset of the author date, in seconds east of UTC.
+  5: i32 last_modified_tz;
 }
 
 struct CommitMultiplePathInfoResponse {
@@ -1876,6 +2172,12 @@ service SourceControlService extends fb303_core.BaseService {
     2: InternalError internal_error,
   );
 
+  /// Find the repos containing commits that match a hash prefix.
+  LocateCommitResponse locate_commit(1: LocateCommitParams params) throws (
+    1: RequestError request_error,
+    2: InternalError internal_error,
+  );
+
   /// Repository methods
   /// ==================
 
@@ -1906,6 +2208,22 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoBookmarkInfoParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// List the commits that were pushed to a bookmark within a time range.
+  /// This uses the time index of bookmark moves, so it is cheap even for
+  /// bookmarks with long histories.
+  RepoPushedCommitsResponse repo_pushed_commits(
+    1: RepoSpecifier repo,
+    2: RepoPushedCommitsParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
+  /// Tail the commits landing on public bookmarks, in the order they land.
+  /// Intended for indexers and CI systems, which can call this in a loop,
+  /// passing the returned cursor each time, instead of polling bookmarks.
+  RepoTailCommitsResponse repo_tail_commits(
+    1: RepoSpecifier repo,
+    2: RepoTailCommitsParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// List all bookmarks in the repo.
   RepoListBookmarksResponse repo_list_bookmarks(
     1: RepoSpecifier repo,
@@ -1928,6 +2246,15 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoCreateCommitParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Create a successor of a commit with amended extras, e.g. to attach
+  /// information that is only known after the commit has landed.  The
+  /// original commit is marked as rewritten to the new one, so that clients
+  /// follow to the amended version.
+  RepoAmendCommitExtrasResponse repo_amend_commit_extras(
+    1: RepoSpecifier repo,
+    2: RepoAmendCommitExtrasParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Create a bookmark.
   RepoCreateBookmarkResponse repo_create_bookmark(
     1: RepoSpecifier repo,
@@ -1946,6 +2273,13 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoDeleteBookmarkParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Create, move and delete several bookmarks in a single transaction.
+  /// Either all of the modifications are made, or none of them are.
+  RepoModifyBookmarksResponse repo_modify_bookmarks(
+    1: RepoSpecifier repo,
+    2: RepoModifyBookmarksParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Land a stack of commits via pushrebase.
   RepoLandStackResponse repo_land_stack(
     1: RepoSpecifier repo,
@@ -1963,6 +2297,24 @@ service SourceControlService extends fb303_core.BaseService {
     2: RepoPrepareCommitsParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Fetch the raw contents of a blob in the repo's blobstore, for
+  /// debugging.  Only identities listed as raw blob readers in the repo's
+  /// config may do so.  Redacted blobs cannot be fetched, blobs larger than
+  /// 100 MiB are refused, and all fetches are logged.
+  RepoBlobFetchResponse repo_blob_fetch(
+    1: RepoSpecifier repo,
+    2: RepoBlobFetchParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
+  /// Run the hooks for a bookmark on a prospective stack of commits, without
+  /// creating the commits or moving the bookmark.  Lets clients find out
+  /// about hook failures before pushing.  Like commit_run_hooks, it is NOT
+  /// guaranteed that a push will succeed if all hooks pass.
+  RepoRunHooksDryRunResponse repo_run_hooks_dry_run(
+    1: RepoSpecifier repo,
+    2: RepoRunHooksDryRunParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Commit methods
   /// ==============
 
@@ -2023,6 +2375,15 @@ service SourceControlService extends fb303_core.BaseService {
     2: CommitCommonBaseWithParams params,
   ) throws (1: RequestError request_error, 2: InternalError internal_error);
 
+  /// Find files that were renamed on one side of a merge of this commit
+  /// with another commit, and modified at their original path on the other
+  /// side, so that the merge can apply the modifications to the renamed
+  /// files without fetching full manifests.
+  CommitTraceMergeCopiesResponse commit_trace_merge_copies(
+    1: CommitSpecifier commit,
+    2: CommitTraceMergeCopiesParams params,
+  ) throws (1: RequestError request_error, 2: InternalError internal_error);
+
   /// Compute differences between two commits.
   /// note: copy/move information included only when comparing with parent
   CommitCompareResponse commit_compare(
@@ -212,19 +212,27 @@ macro_rules! impl_into_thrift_error {
 }
 
 impl_into_thrift_error!(service::ListReposExn);
+impl_into_thrift_error!(service::LocateCommitExn);
 impl_into_thrift_error!(service::RepoInfoExn);
 impl_into_thrift_error!(service::RepoResolveBookmarkExn);
 impl_into_thrift_error!(service::RepoResolveCommitPrefixExn);
 impl_into_thrift_error!(service::RepoListBookmarksExn);
 impl_into_thrift_error!(service::RepoCreateCommitExn);
+impl_into_thrift_error!(service::RepoAmendCommitExtrasExn);
 impl_into_thrift_error!(service::RepoCreateBookmarkExn);
 impl_into_thrift_error!(service::RepoMoveBookmarkExn);
 impl_into_thrift_error!(service::RepoDeleteBookmarkExn);
+impl_into_thrift_error!(service::RepoModifyBookmarksExn);
 impl_into_thrift_error!(service::RepoLandStackExn);
 impl_into_thrift_error!(service::RepoBookmarkInfoExn);
+impl_into_thrift_error!(service::RepoPushedCommitsExn);
+impl_into_thrift_error!(service::RepoTailCommitsExn);
 impl_into_thrift_error!(service::RepoStackInfoExn);
 impl_into_thrift_error!(service::RepoPrepareCommitsExn);
+impl_into_thrift_error!(service::RepoBlobFetchExn);
+impl_into_thrift_error!(service::RepoRunHooksDryRunExn);
 impl_into_thrift_error!(service::CommitCommonBaseWithExn);
+impl_into_thrift_error!(service::CommitTraceMergeCopiesExn);
 impl_into_thrift_error!(service::CommitFileDiffsExn);
 impl_into_thrift_error!(service::CommitLookupExn);
 impl_into_thrift_error!(service::CommitLookupPushrebaseHistoryExn);
@@ -39,7 +39,9 @@ use mononoke_api::MononokePath;
 use mononoke_api::TreeId;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
+use mononoke_types::Timestamp;
 use source_control as thrift;
+use tunables::tunables;
 
 use crate::commit_id::CommitIdExt;
 use crate::errors;
@@ -221,43 +223,67 @@ impl FromRequest<thrift::CopyInfo> for CopyInfo {
     }
 }
 
+fn changeset_prefix_from_request(
+    prefix: &str,
+    prefix_scheme: &thrift::CommitIdentityScheme,
+) -> Result<ChangesetPrefixSpecifier, thrift::RequestError> {
+    if *prefix_scheme == thrift::CommitIdentityScheme::HG
+        || *prefix_scheme == thrift::CommitIdentityScheme::BONSAI
+    {
+        let min_length = tunables().get_scs_min_commit_prefix_length();
+        if (prefix.len() as i64) < min_length {
+            return Err(errors::invalid_request(format!(
+                "commit id prefix is too short (scheme={} {}): at least {} characters are required",
+                prefix_scheme, prefix, min_length
+            )));
+        }
+    }
+    match *prefix_scheme {
+        thrift::CommitIdentityScheme::HG => {
+            let prefix = HgChangesetIdPrefix::from_str(prefix).map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    prefix_scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(prefix))
+        }
+        thrift::CommitIdentityScheme::BONSAI => {
+            let prefix = ChangesetIdPrefix::from_str(prefix).map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    prefix_scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(prefix))
+        }
+        thrift::CommitIdentityScheme::GLOBALREV => {
+            let rev = prefix.parse().map_err(|e| {
+                errors::invalid_request(format!(
+                    "invalid commit id prefix (scheme={} {}): {}",
+                    prefix_scheme, prefix, e
+                ))
+            })?;
+            Ok(ChangesetPrefixSpecifier::from(Globalrev::new(rev)))
+        }
+        _ => Err(errors::invalid_request(format!(
+            "unsupported prefix identity scheme ({})",
+            prefix_scheme
+        ))),
+    }
+}
+
 impl FromRequest<thrift::RepoResolveCommitPrefixParams> for ChangesetPrefixSpecifier {
     fn from_request(
         params: &thrift::RepoResolveCommitPrefixParams,
     ) -> Result<Self, thrift::RequestError> {
-        match params.prefix_scheme {
-            thrift::CommitIdentityScheme::HG => {
-                let prefix = HgChangesetIdPrefix::from_str(&params.prefix).map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(prefix))
-            }
-            thrift::CommitIdentityScheme::BONSAI => {
-                let prefix = ChangesetIdPrefix::from_str(&params.prefix).map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(prefix))
-            }
-            thrift::CommitIdentityScheme::GLOBALREV => {
-                let rev = params.prefix.parse().map_err(|e| {
-                    errors::invalid_request(format!(
-                        "invalid commit id prefix (scheme={} {}): {}",
-                        params.prefix_scheme, params.prefix, e
-                    ))
-                })?;
-                Ok(ChangesetPrefixSpecifier::from(Globalrev::new(rev)))
-            }
-            _ => Err(errors::invalid_request(format!(
-                "unsupported prefix identity scheme ({})",
-                params.prefix_scheme
-            ))),
-        }
+        changeset_prefix_from_request(&params.prefix, &params.prefix_scheme)
+    }
+}
+
+impl FromRequest<thrift::LocateCommitParams> for ChangesetPrefixSpecifier {
+    fn from_request(params: &thrift::LocateCommitParams) -> Result<Self, thrift::RequestError> {
+        changeset_prefix_from_request(&params.prefix, &params.prefix_scheme)
     }
 }
 
@@ -363,6 +389,13 @@ pub(crate) fn validate_timestamp(
     }
 }
 
+/// Convert a timestamp in seconds since the epoch, failing the request if
+/// it is out of range.
+pub(crate) fn checked_timestamp(name: &str, ts: i64) -> Result<Timestamp, errors::ServiceError> {
+    Timestamp::checked_from_timestamp_secs(ts)
+        .ok_or_else(|| errors::invalid_request(format!("{} ({}) is out of range", name, ts)).into())
+}
+
 /// Convert a pushvars map from thrift's representation to the one used
 /// internally in mononoke.
 pub(crate) fn convert_pushvars(
@@ -12,6 +12,8 @@ use std::collections::HashSet;
 use async_trait::async_trait;
 use futures::future::try_join_all;
 use futures::try_join;
+use hooks::HookExecution;
+use hooks::HookOutcome;
 use itertools::Itertools;
 use maplit::btreemap;
 use mononoke_api::BookmarkInfo;
@@ -22,6 +24,8 @@ use mononoke_api::CopyInfo;
 use mononoke_api::FileMetadata;
 use mononoke_api::FileType;
 use mononoke_api::HeaderlessUnifiedDiff;
+use mononoke_api::MergeRename;
+use mononoke_api::MergeSide;
 use mononoke_api::MetadataDiff;
 use mononoke_api::MononokeError;
 use mononoke_api::PushrebaseOutcome;
@@ -175,6 +179,20 @@ impl IntoResponse<thrift::CopyInfo> for CopyInfo {
     }
 }
 
+impl IntoResponse<thrift::MergeRename> for MergeRename {
+    fn into_response(self) -> thrift::MergeRename {
+        thrift::MergeRename {
+            renamed_on: match self.renamed_on {
+                MergeSide::Local => thrift::MergeSide::LOCAL,
+                MergeSide::Other => thrift::MergeSide::OTHER,
+            },
+            base_path: self.base_path.to_string(),
+            renamed_path: self.renamed_path.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
 impl IntoResponse<thrift::Diff> for MetadataDiff {
     fn into_response(self) -> thrift::Diff {
         let old_file_type = self.old_file_type.into_response();
@@ -207,6 +225,49 @@ impl IntoResponse<thrift::Diff> for HeaderlessUnifiedDiff {
     }
 }
 
+impl IntoResponse<thrift::CommitRunHooksResponse> for Vec<HookOutcome> {
+    fn into_response(self) -> thrift::CommitRunHooksResponse {
+        let mut outcomes_map = BTreeMap::new();
+
+        for outcome in self {
+            let (name, execution) = match outcome {
+                HookOutcome::FileHook(id, exec) => (id.hook_name, exec),
+                HookOutcome::ChangesetHook(id, exec) => (id.hook_name, exec),
+            };
+
+            match execution {
+                HookExecution::Accepted => {
+                    outcomes_map.entry(name).or_insert_with(|| {
+                        thrift::HookOutcome::accepted(thrift::HookOutcomeAccepted {
+                            ..Default::default()
+                        })
+                    });
+                }
+                HookExecution::Rejected(rej) => {
+                    let rejection = thrift::HookOutcomeRejected {
+                        description: rej.description.to_string(),
+                        long_description: rej.long_description,
+                        ..Default::default()
+                    };
+
+                    match outcomes_map
+                        .entry(name)
+                        .or_insert_with(|| thrift::HookOutcome::rejections(vec![]))
+                    {
+                        thrift::HookOutcome::rejections(rejs) => rejs.push(rejection),
+                        obj => *obj = thrift::HookOutcome::rejections(vec![rejection]),
+                    }
+                }
+            }
+        }
+
+        thrift::CommitRunHooksResponse {
+            outcomes: outcomes_map,
+            ..Default::default()
+        }
+    }
+}
+
 #[async_trait]
 impl AsyncIntoResponse<thrift::FilePathInfo> for &ChangesetPathContentContext {
     async fn into_response(self) -> Result<thrift::FilePathInfo, errors::ServiceError> {
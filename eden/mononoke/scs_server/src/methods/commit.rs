@@ -5,7 +5,6 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -17,8 +16,6 @@ use futures::stream::FuturesOrdered;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures::try_join;
-use hooks::HookExecution;
-use hooks::HookOutcome;
 use itertools::Either;
 use itertools::Itertools;
 use maplit::btreeset;
@@ -267,6 +264,36 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Find files renamed on one side of a merge and modified on the other.
+    pub(crate) async fn commit_trace_merge_copies(
+        &self,
+        ctx: CoreContext,
+        commit: thrift::CommitSpecifier,
+        params: thrift::CommitTraceMergeCopiesParams,
+    ) -> Result<thrift::CommitTraceMergeCopiesResponse, errors::ServiceError> {
+        let (repo, changeset, other_changeset) = self
+            .repo_changeset_pair(ctx, &commit, &params.other_commit_id)
+            .await?;
+        let base_changeset = match &params.base_commit_id {
+            Some(id) => Some(
+                repo.changeset(ChangesetSpecifier::from_request(id)?)
+                    .await?
+                    .ok_or_else(|| errors::commit_not_found(id.to_string()))?,
+            ),
+            None => None,
+        };
+        let renames = changeset
+            .trace_merge_copies(&other_changeset, base_changeset.as_ref())
+            .await?;
+        Ok(thrift::CommitTraceMergeCopiesResponse {
+            renames: renames
+                .into_iter()
+                .map(IntoResponse::into_response)
+                .collect(),
+            ..Default::default()
+        })
+    }
+
     /// Look up commit.
     pub(crate) async fn commit_lookup(
         &self,
@@ -935,44 +962,7 @@ impl SourceControlServiceImpl {
             .run_hooks(params.bookmark, pushvars.as_ref())
             .await?;
 
-        let mut outcomes_map = BTreeMap::new();
-
-        for outcome in outcomes {
-            let (name, execution) = match outcome {
-                HookOutcome::FileHook(id, exec) => (id.hook_name, exec),
-                HookOutcome::ChangesetHook(id, exec) => (id.hook_name, exec),
-            };
-
-            match execution {
-                HookExecution::Accepted => {
-                    outcomes_map.entry(name).or_insert_with(|| {
-                        thrift::HookOutcome::accepted(thrift::HookOutcomeAccepted {
-                            ..Default::default()
-                        })
-                    });
-                }
-                HookExecution::Rejected(rej) => {
-                    let rejection = thrift::HookOutcomeRejected {
-                        description: rej.description.to_string(),
-                        long_description: rej.long_description,
-                        ..Default::default()
-                    };
-
-                    match outcomes_map
-                        .entry(name)
-                        .or_insert_with(|| thrift::HookOutcome::rejections(vec![]))
-                    {
-                        thrift::HookOutcome::rejections(rejs) => rejs.push(rejection),
-                        obj => *obj = thrift::HookOutcome::rejections(vec![rejection]),
-                    }
-                }
-            }
-        }
-
-        Ok(thrift::CommitRunHooksResponse {
-            outcomes: outcomes_map,
-            ..Default::default()
-        })
+        Ok(outcomes.into_response())
     }
 
     /// Do a cross-repo lookup to see if a commit exists under a different hash in another repo
@@ -59,11 +59,11 @@ impl SourceControlServiceImpl {
         &self,
         ctx: CoreContext,
         commit_path: thrift::CommitPathSpecifier,
-        _params: thrift::CommitPathInfoParams,
+        params: thrift::CommitPathInfoParams,
     ) -> Result<thrift::CommitPathInfoResponse, errors::ServiceError> {
-        let (_repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
+        let (repo, changeset) = self.repo_changeset(ctx, &commit_path.commit).await?;
         let path = changeset.path_with_content(&commit_path.path).await?;
-        let response = match path.entry().await? {
+        let mut response = match path.entry().await? {
             PathEntry::NotPresent => thrift::CommitPathInfoResponse {
                 exists: false,
                 r#type: None,
@@ -107,6 +107,29 @@ impl SourceControlServiceImpl {
                 }
             }
         };
+        if params.include_rollup && response.exists {
+            if let Some(rollup) = path.rollup().await? {
+                let last_modified = repo
+                    .changeset(ChangesetSpecifier::Bonsai(rollup.last_modified))
+                    .await?
+                    .ok_or_else(|| {
+                        errors::internal_error(format!(
+                            "last modified commit {} not found",
+                            rollup.last_modified
+                        ))
+                    })?;
+                let last_modified_commit =
+                    map_commit_identity(&last_modified, &params.identity_schemes).await?;
+                response.rollup = Some(thrift::PathRollup {
+                    total_size: rollup.total_size as i64,
+                    file_count: rollup.file_count as i64,
+                    last_modified_commit,
+                    last_modified_date: rollup.last_modified_date.timestamp(),
+                    last_modified_tz: rollup.last_modified_date.offset().local_minus_utc(),
+                    ..Default::default()
+                });
+            }
+        }
         Ok(response)
     }
 
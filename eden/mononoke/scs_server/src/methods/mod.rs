@@ -5,10 +5,15 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
+
 use context::CoreContext;
+use mononoke_api::ChangesetPrefixSpecifier;
 use source_control as thrift;
 
 use crate::errors;
+use crate::from_request::FromRequest;
+use crate::methods::repo::resolve_commit_prefix_response;
 use crate::source_control_impl::SourceControlServiceImpl;
 
 pub(crate) mod commit;
@@ -37,4 +42,37 @@ impl SourceControlServiceImpl {
             .collect();
         Ok(rsp)
     }
+
+    pub(crate) async fn locate_commit(
+        &self,
+        ctx: CoreContext,
+        params: thrift::LocateCommitParams,
+    ) -> Result<thrift::LocateCommitResponse, errors::ServiceError> {
+        let prefix = ChangesetPrefixSpecifier::from_request(&params)?;
+        let repo_names = params.repos.as_ref().map(|repos| {
+            repos
+                .iter()
+                .map(|repo| repo.name.clone())
+                .collect::<Vec<_>>()
+        });
+        let located = self
+            .mononoke
+            .locate_changeset_prefix(ctx, prefix, repo_names.as_deref())
+            .await?;
+        let mut repos = BTreeMap::new();
+        for (repo, resolution) in located {
+            let response = resolve_commit_prefix_response(
+                &repo,
+                resolution,
+                &params.prefix_scheme,
+                &params.identity_schemes,
+            )
+            .await?;
+            repos.insert(repo.name().to_string(), response);
+        }
+        Ok(thrift::LocateCommitResponse {
+            repos,
+            ..Default::default()
+        })
+    }
 }
@@ -6,6 +6,9 @@
  */
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
 use chrono::DateTime;
@@ -23,6 +26,8 @@ use futures::try_join;
 use maplit::btreemap;
 use metaconfig_types::CommitIdentityScheme;
 use mononoke_api::BookmarkFreshness;
+use mononoke_api::BookmarkModification;
+use mononoke_api::BookmarkName;
 use mononoke_api::ChangesetId;
 use mononoke_api::ChangesetPrefixSpecifier;
 use mononoke_api::ChangesetSpecifier;
@@ -30,9 +35,11 @@ use mononoke_api::ChangesetSpecifierPrefixResolution;
 use mononoke_api::CreateChange;
 use mononoke_api::CreateChangeFile;
 use mononoke_api::CreateCopyInfo;
+use mononoke_api::DryRunChangeset;
 use mononoke_api::FileId;
 use mononoke_api::FileType;
 use mononoke_api::MononokePath;
+use mononoke_api::RepoContext;
 use mononoke_types::hash::GitSha1;
 use mononoke_types::hash::Sha1;
 use mononoke_types::hash::Sha256;
@@ -46,9 +53,12 @@ use crate::commit_id::CommitIdExt;
 use crate::errors;
 use crate::errors::ServiceErrorResultExt;
 use crate::from_request::check_range_and_convert;
+use crate::from_request::checked_timestamp;
 use crate::from_request::convert_pushvars;
 use crate::from_request::FromRequest;
+use crate::history::collect_history;
 use crate::into_response::AsyncIntoResponseWith;
+use crate::into_response::IntoResponse;
 use crate::source_control_impl::SourceControlServiceImpl;
 
 mod land_stack;
@@ -126,59 +136,16 @@ impl SourceControlServiceImpl {
         repo: thrift::RepoSpecifier,
         params: thrift::RepoResolveCommitPrefixParams,
     ) -> Result<thrift::RepoResolveCommitPrefixResponse, errors::ServiceError> {
-        use ChangesetSpecifierPrefixResolution::*;
-        type Response = thrift::RepoResolveCommitPrefixResponse;
-        type ResponseType = thrift::RepoResolveCommitPrefixResponseType;
-
-        let same_request_response_schemes = params.identity_schemes.len() == 1
-            && params.identity_schemes.contains(&params.prefix_scheme);
-
         let prefix = ChangesetPrefixSpecifier::from_request(&params)?;
         let repo = self.repo(ctx, &repo).await?;
-
-        // If the response requires exactly the same identity scheme as in the request,
-        // the general case works but we don't need to pay extra overhead to resolve
-        // ChangesetSpecifier to a changeset.
-
-        match repo.resolve_changeset_id_prefix(prefix).await? {
-            Single(ChangesetSpecifier::Bonsai(cs_id)) if same_request_response_schemes => {
-                Ok(Response {
-                    ids: Some(btreemap! {
-                        params.prefix_scheme => thrift::CommitId::bonsai(cs_id.as_ref().into())
-                    }),
-                    resolved_type: ResponseType::RESOLVED,
-                    ..Default::default()
-                })
-            }
-            Single(ChangesetSpecifier::Hg(cs_id)) if same_request_response_schemes => {
-                Ok(Response {
-                    ids: Some(btreemap! {
-                        params.prefix_scheme => thrift::CommitId::hg(cs_id.as_ref().into())
-                    }),
-                    resolved_type: ResponseType::RESOLVED,
-                    ..Default::default()
-                })
-            }
-            Single(cs_id) => match &repo.changeset(cs_id).await? {
-                None => Err(errors::internal_error(
-                    "unexpected failure to resolve an existing commit",
-                )
-                .into()),
-                Some(cs) => Ok(Response {
-                    ids: Some(map_commit_identity(cs, &params.identity_schemes).await?),
-                    resolved_type: ResponseType::RESOLVED,
-                    ..Default::default()
-                }),
-            },
-            NoMatch => Ok(Response {
-                resolved_type: ResponseType::NOT_FOUND,
-                ..Default::default()
-            }),
-            _ => Ok(Response {
-                resolved_type: ResponseType::AMBIGUOUS,
-                ..Default::default()
-            }),
-        }
+        let resolution = repo.resolve_changeset_id_prefix(prefix).await?;
+        resolve_commit_prefix_response(
+            &repo,
+            resolution,
+            &params.prefix_scheme,
+            &params.identity_schemes,
+        )
+        .await
     }
 
     /// Comprehensive bookmark info.
@@ -202,6 +169,105 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// List the commits pushed to a bookmark within a time range.
+    pub(crate) async fn repo_pushed_commits(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoPushedCommitsParams,
+    ) -> Result<thrift::RepoPushedCommitsResponse, errors::ServiceError> {
+        let limit: usize = check_range_and_convert("limit", params.limit, 0..)?;
+        let skip: usize = check_range_and_convert("skip", params.skip, 0..)?;
+        let from = checked_timestamp("from_timestamp", params.from_timestamp)?;
+        let to = checked_timestamp("to_timestamp", params.to_timestamp)?;
+        let repo = self.repo(ctx, &repo).await?;
+        let commits = repo
+            .pushed_changesets_in_range(params.bookmark_name, from, to)
+            .await?;
+        let commits = collect_history(
+            commits,
+            skip,
+            limit,
+            None,
+            None,
+            params.format,
+            &params.identity_schemes,
+        )
+        .await?;
+        Ok(thrift::RepoPushedCommitsResponse {
+            commits,
+            ..Default::default()
+        })
+    }
+
+    /// Tail the commits landing on public bookmarks, waiting for some to
+    /// land if there are none yet.
+    pub(crate) async fn repo_tail_commits(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoTailCommitsParams,
+    ) -> Result<thrift::RepoTailCommitsResponse, errors::ServiceError> {
+        // How often the bookmark update log is checked while waiting.
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+        let limit: usize = check_range_and_convert("limit", params.limit, 1..)?;
+        let cursor: Option<u64> = params
+            .cursor
+            .map(|cursor| check_range_and_convert("cursor", cursor, 0..))
+            .transpose()?;
+        let wait_ms: u64 = check_range_and_convert(
+            "wait_ms",
+            params.wait_ms,
+            0..=thrift::REPO_TAIL_COMMITS_MAX_WAIT_MS,
+        )?;
+        let deadline = Instant::now() + Duration::from_millis(wait_ms);
+        let repo = self.repo(ctx, &repo).await?;
+
+        let mut tail = repo
+            .tail_changesets(cursor, params.bookmark_name.as_deref(), limit)
+            .await?;
+        while cursor.is_some() && tail.changesets.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(deadline - now)).await;
+            tail = repo
+                .tail_changesets(
+                    Some(tail.next_cursor),
+                    params.bookmark_name.as_deref(),
+                    limit,
+                )
+                .await?;
+        }
+
+        let identity_schemes = &params.identity_schemes;
+        let commits = tail
+            .changesets
+            .into_iter()
+            .map(|tailed| async move {
+                Ok::<_, errors::ServiceError>(thrift::TailedCommit {
+                    info: tailed
+                        .changeset
+                        .into_response_with(identity_schemes)
+                        .await?,
+                    bookmark_name: tailed.bookmark.to_string(),
+                    pushed_at: tailed.pushed_at.timestamp_seconds(),
+                    cursor: tailed.cursor as i64,
+                    ..Default::default()
+                })
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect()
+            .await?;
+        Ok(thrift::RepoTailCommitsResponse {
+            commits,
+            next_cursor: tail.next_cursor as i64,
+            ..Default::default()
+        })
+    }
+
     /// List bookmarks.
     pub(crate) async fn repo_list_bookmarks(
         &self,
@@ -265,24 +331,7 @@ impl SourceControlServiceImpl {
         let repo = self
             .repo_for_service(ctx, &repo, params.service_identity.clone())
             .await?;
-        let parents: Vec<_> = params
-            .parents
-            .into_iter()
-            .map(|parent| {
-                let repo = &repo;
-                async move {
-                    let changeset_specifier = ChangesetSpecifier::from_request(&parent)
-                        .context("invalid commit id for parent")?;
-                    let changeset = repo
-                        .changeset(changeset_specifier)
-                        .await?
-                        .ok_or_else(|| errors::commit_not_found(parent.to_string()))?;
-                    Ok::<_, errors::ServiceError>(changeset.id())
-                }
-            })
-            .collect::<FuturesOrdered<_>>()
-            .try_collect()
-            .await?;
+        let parents = resolve_commit_parents(&repo, params.parents).await?;
 
         if parents.is_empty() && !repo.allow_no_parent_writes() {
             return Err(errors::invalid_request(
@@ -291,133 +340,11 @@ impl SourceControlServiceImpl {
             .into());
         }
 
-        // Convert changes to actions
-        let file_changes = params
-            .changes
-            .into_iter()
-            .map(|(path, change)| {
-                let repo = &repo;
-                async move {
-                    let path = MononokePath::try_from(&path).map_err(|e| {
-                        errors::invalid_request(format!("invalid path '{}': {}", path, e))
-                    })?;
-                    let change = match change {
-                        thrift::RepoCreateCommitParamsChange::changed(c) => {
-                            let file_type = FileType::from_request(&c.r#type)?;
-                            let copy_info = c
-                                .copy_info
-                                .as_ref()
-                                .map(CreateCopyInfo::from_request)
-                                .transpose()?;
-                            match c.content {
-                                thrift::RepoCreateCommitParamsFileContent::id(id) => {
-                                    let file_id = FileId::from_request(&id)?;
-                                    let file = repo.file(file_id).await?.ok_or_else(|| {
-                                        errors::file_not_found(file_id.to_string())
-                                    })?;
-                                    CreateChange::Tracked(
-                                        CreateChangeFile::Existing {
-                                            file_id: file.id().await?,
-                                            file_type,
-                                            maybe_size: None,
-                                        },
-                                        copy_info,
-                                    )
-                                }
-                                thrift::RepoCreateCommitParamsFileContent::content_sha1(sha) => {
-                                    let sha = Sha1::from_request(&sha)?;
-                                    let file = repo
-                                        .file_by_content_sha1(sha)
-                                        .await?
-                                        .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
-                                    CreateChange::Tracked(
-                                        CreateChangeFile::Existing {
-                                            file_id: file.id().await?,
-                                            file_type,
-                                            maybe_size: None,
-                                        },
-                                        copy_info,
-                                    )
-                                }
-                                thrift::RepoCreateCommitParamsFileContent::content_sha256(sha) => {
-                                    let sha = Sha256::from_request(&sha)?;
-                                    let file = repo
-                                        .file_by_content_sha256(sha)
-                                        .await?
-                                        .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
-                                    CreateChange::Tracked(
-                                        CreateChangeFile::Existing {
-                                            file_id: file.id().await?,
-                                            file_type,
-                                            maybe_size: None,
-                                        },
-                                        copy_info,
-                                    )
-                                }
-                                thrift::RepoCreateCommitParamsFileContent::content_gitsha1(sha) => {
-                                    let sha = GitSha1::from_request(&sha)?;
-                                    let file = repo
-                                        .file_by_content_gitsha1(sha)
-                                        .await?
-                                        .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
-                                    CreateChange::Tracked(
-                                        CreateChangeFile::Existing {
-                                            file_id: file.id().await?,
-                                            file_type,
-                                            maybe_size: None,
-                                        },
-                                        copy_info,
-                                    )
-                                }
-                                thrift::RepoCreateCommitParamsFileContent::data(data) => {
-                                    CreateChange::Tracked(
-                                        CreateChangeFile::New {
-                                            bytes: Bytes::from(data),
-                                            file_type,
-                                        },
-                                        copy_info,
-                                    )
-                                }
-                                thrift::RepoCreateCommitParamsFileContent::UnknownField(t) => {
-                                    return Err(errors::invalid_request(format!(
-                                        "file content type not supported: {}",
-                                        t
-                                    ))
-                                    .into());
-                                }
-                            }
-                        }
-                        thrift::RepoCreateCommitParamsChange::deleted(_d) => CreateChange::Deletion,
-                        thrift::RepoCreateCommitParamsChange::UnknownField(t) => {
-                            return Err(errors::invalid_request(format!(
-                                "file change type not supported: {}",
-                                t
-                            ))
-                            .into());
-                        }
-                    };
-                    Ok::<_, errors::ServiceError>((path, change))
-                }
-            })
-            .collect::<FuturesOrdered<_>>()
-            .try_collect()
-            .await?;
+        let file_changes = convert_create_commit_changes(&repo, params.changes).await?;
 
+        let (author_date, committer_date) = convert_commit_dates(&params.info)?;
         let author = params.info.author;
-        let author_date = params.info.date.as_ref().map_or_else(
-            || {
-                let now = Local::now();
-                Ok(now.with_timezone(now.offset()))
-            },
-            <DateTime<FixedOffset>>::from_request,
-        )?;
         let committer = params.info.committer;
-        let committer_date = params
-            .info
-            .committer_date
-            .as_ref()
-            .map(<DateTime<FixedOffset>>::from_request)
-            .transpose()?;
         let message = params.info.message;
         let extra = params.info.extra;
         let bubble = None;
@@ -453,6 +380,44 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Create a successor of a commit with amended extras.
+    pub(crate) async fn repo_amend_commit_extras(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoAmendCommitExtrasParams,
+    ) -> Result<thrift::RepoAmendCommitExtrasResponse, errors::ServiceError> {
+        let repo = self
+            .repo_for_service(ctx, &repo, params.service_identity.clone())
+            .await?;
+        let changeset_specifier = ChangesetSpecifier::from_request(&params.commit)
+            .context("invalid commit id to amend")?;
+        if changeset_specifier.in_bubble() {
+            return Err(errors::invalid_request(format!(
+                "cannot amend a snapshot: {}",
+                changeset_specifier
+            ))
+            .into());
+        }
+        let changeset = repo
+            .changeset(changeset_specifier)
+            .await?
+            .ok_or_else(|| errors::commit_not_found(params.commit.to_string()))?;
+
+        let amended = repo
+            .amend_changeset_extras(
+                changeset.id(),
+                params.set_extras.into_iter().collect(),
+                params.remove_extras.into_iter().collect(),
+            )
+            .await?;
+        let ids = map_commit_identity(&amended, &params.identity_schemes).await?;
+        Ok(thrift::RepoAmendCommitExtrasResponse {
+            ids,
+            ..Default::default()
+        })
+    }
+
     /// Build stacks for the given list of heads.
     ///
     /// Returns the IDs of the changeset in the requested identity schemes.
@@ -647,6 +612,74 @@ impl SourceControlServiceImpl {
         })
     }
 
+    pub(crate) async fn repo_modify_bookmarks(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoModifyBookmarksParams,
+    ) -> Result<thrift::RepoModifyBookmarksResponse, errors::ServiceError> {
+        let repo = self
+            .repo_for_service(ctx, &repo, params.service_identity)
+            .await?;
+        let repo = &repo;
+        let resolve = |commit: &thrift::CommitId| async move {
+            Ok::<_, errors::ServiceError>(
+                repo.changeset(ChangesetSpecifier::from_request(commit)?)
+                    .await?
+                    .ok_or_else(|| errors::commit_not_found(commit.to_string()))?
+                    .id(),
+            )
+        };
+        let mut modifications = Vec::with_capacity(params.modifications.len());
+        for modification in params.modifications.iter() {
+            let modification = match modification {
+                thrift::BookmarkModification::create_bookmark(create) => {
+                    BookmarkModification::Create {
+                        bookmark: create.bookmark.clone(),
+                        target: resolve(&create.target).await?,
+                    }
+                }
+                thrift::BookmarkModification::move_bookmark(move_) => {
+                    let old_target = match &move_.old_target {
+                        Some(old_target) => Some(resolve(old_target).await?),
+                        None => None,
+                    };
+                    BookmarkModification::Move {
+                        bookmark: move_.bookmark.clone(),
+                        target: resolve(&move_.target).await?,
+                        old_target,
+                        allow_non_fast_forward: move_.allow_non_fast_forward_move,
+                    }
+                }
+                thrift::BookmarkModification::delete_bookmark(delete) => {
+                    let old_target = match &delete.old_target {
+                        Some(old_target) => Some(resolve(old_target).await?),
+                        None => None,
+                    };
+                    BookmarkModification::Delete {
+                        bookmark: delete.bookmark.clone(),
+                        old_target,
+                    }
+                }
+                thrift::BookmarkModification::UnknownField(t) => {
+                    return Err(errors::invalid_request(format!(
+                        "bookmark modification type not supported: {}",
+                        t
+                    ))
+                    .into());
+                }
+            };
+            modifications.push(modification);
+        }
+        let pushvars = convert_pushvars(params.pushvars);
+
+        repo.modify_bookmarks(modifications, pushvars.as_ref())
+            .await?;
+        Ok(thrift::RepoModifyBookmarksResponse {
+            ..Default::default()
+        })
+    }
+
     /// Prepare commits for future operations.
     ///
     /// Perform any necessary pre-processing on the mononoke side to ensure that the commits
@@ -705,6 +738,79 @@ impl SourceControlServiceImpl {
         })
     }
 
+    /// Fetch the raw contents of a blob in the repo's blobstore.
+    pub(crate) async fn repo_blob_fetch(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoBlobFetchParams,
+    ) -> Result<thrift::RepoBlobFetchResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?;
+        let blob = match repo.fetch_raw_blob(&params.key).await? {
+            Some(blob) => blob,
+            None => return Ok(thrift::RepoBlobFetchResponse::default()),
+        };
+        let meta = blob.as_meta();
+        let sizes = meta.sizes();
+        let metadata = thrift::BlobMetadata {
+            ctime: meta.ctime(),
+            compressed_size: sizes
+                .map(|sizes| sizes.unique_compressed_size.try_into())
+                .transpose()
+                .map_err(errors::internal_error)?,
+            pack_key: sizes
+                .and_then(|sizes| sizes.pack_meta.as_ref())
+                .map(|pack_meta| pack_meta.pack_key.clone()),
+            ..Default::default()
+        };
+        Ok(thrift::RepoBlobFetchResponse {
+            metadata: Some(metadata),
+            data: Some(blob.into_raw_bytes().to_vec()),
+            ..Default::default()
+        })
+    }
+
+    /// Run the hooks for a bookmark on a prospective stack of commits,
+    /// without creating the commits.
+    pub(crate) async fn repo_run_hooks_dry_run(
+        &self,
+        ctx: CoreContext,
+        repo: thrift::RepoSpecifier,
+        params: thrift::RepoRunHooksDryRunParams,
+    ) -> Result<thrift::RepoRunHooksDryRunResponse, errors::ServiceError> {
+        let repo = self.repo(ctx, &repo).await?;
+        let bookmark = BookmarkName::from_request(params.bookmark.as_str())?;
+        let parents = resolve_commit_parents(&repo, params.parents).await?;
+
+        let mut stack = Vec::with_capacity(params.commits.len());
+        for commit in params.commits {
+            let changes = convert_create_commit_changes(&repo, commit.changes).await?;
+            let (author_date, committer_date) = convert_commit_dates(&commit.info)?;
+            stack.push(DryRunChangeset {
+                author: commit.info.author,
+                author_date,
+                committer: commit.info.committer,
+                committer_date,
+                message: commit.info.message,
+                extra: commit.info.extra,
+                changes,
+            });
+        }
+
+        let pushvars = convert_pushvars(params.pushvars);
+        let outcomes = repo
+            .run_hooks_dry_run(&bookmark, parents, stack, pushvars.as_ref())
+            .await?;
+
+        Ok(thrift::RepoRunHooksDryRunResponse {
+            commits: outcomes
+                .into_iter()
+                .map(|(_cs_id, outcomes)| outcomes.into_response())
+                .collect(),
+            ..Default::default()
+        })
+    }
+
     async fn derive_exactly_batch_data<Derivable: BonsaiDerivable>(
         manager: &DerivedDataManager,
         ctx: &CoreContext,
@@ -722,3 +828,236 @@ impl SourceControlServiceImpl {
         Ok(())
     }
 }
+
+/// Convert the resolution of a commit prefix in a repo into the response
+/// for that repo.
+pub(crate) async fn resolve_commit_prefix_response(
+    repo: &RepoContext,
+    resolution: ChangesetSpecifierPrefixResolution,
+    prefix_scheme: &thrift::CommitIdentityScheme,
+    identity_schemes: &BTreeSet<thrift::CommitIdentityScheme>,
+) -> Result<thrift::RepoResolveCommitPrefixResponse, errors::ServiceError> {
+    use ChangesetSpecifierPrefixResolution::*;
+    type Response = thrift::RepoResolveCommitPrefixResponse;
+    type ResponseType = thrift::RepoResolveCommitPrefixResponseType;
+
+    let same_request_response_schemes =
+        identity_schemes.len() == 1 && identity_schemes.contains(prefix_scheme);
+
+    // If the response requires exactly the same identity scheme as in the request,
+    // the general case works but we don't need to pay extra overhead to resolve
+    // ChangesetSpecifier to a changeset.
+
+    match resolution {
+        Single(ChangesetSpecifier::Bonsai(cs_id)) if same_request_response_schemes => {
+            Ok(Response {
+                ids: Some(btreemap! {
+                    *prefix_scheme => thrift::CommitId::bonsai(cs_id.as_ref().into())
+                }),
+                resolved_type: ResponseType::RESOLVED,
+                ..Default::default()
+            })
+        }
+        Single(ChangesetSpecifier::Hg(cs_id)) if same_request_response_schemes => Ok(Response {
+            ids: Some(btreemap! {
+                *prefix_scheme => thrift::CommitId::hg(cs_id.as_ref().into())
+            }),
+            resolved_type: ResponseType::RESOLVED,
+            ..Default::default()
+        }),
+        Single(cs_id) => match &repo.changeset(cs_id).await? {
+            None => Err(
+                errors::internal_error("unexpected failure to resolve an existing commit").into(),
+            ),
+            Some(cs) => Ok(Response {
+                ids: Some(map_commit_identity(cs, identity_schemes).await?),
+                resolved_type: ResponseType::RESOLVED,
+                ..Default::default()
+            }),
+        },
+        NoMatch => Ok(Response {
+            resolved_type: ResponseType::NOT_FOUND,
+            ..Default::default()
+        }),
+        resolution @ (Multiple(_) | TooMany(_)) => {
+            let more_candidates = matches!(resolution, TooMany(_));
+            let cs_ids = try_join_all(resolution.into_list().into_iter().map(
+                |specifier| async move {
+                    match repo.changeset(specifier).await? {
+                        Some(cs) => Ok(cs.id()),
+                        None => Err(errors::ServiceError::from(errors::internal_error(
+                            "unexpected failure to resolve an existing commit",
+                        ))),
+                    }
+                },
+            ))
+            .await?;
+            let candidates = map_commit_identities(repo, cs_ids, identity_schemes)
+                .await?
+                .into_values()
+                .collect();
+            Ok(Response {
+                resolved_type: ResponseType::AMBIGUOUS,
+                candidates: Some(candidates),
+                more_candidates,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Resolve the parents in a create commit request to the changeset ids of
+/// existing commits.
+async fn resolve_commit_parents(
+    repo: &RepoContext,
+    parents: Vec<thrift::CommitId>,
+) -> Result<Vec<ChangesetId>, errors::ServiceError> {
+    parents
+        .into_iter()
+        .map(|parent| async move {
+            let changeset_specifier = ChangesetSpecifier::from_request(&parent)
+                .context("invalid commit id for parent")?;
+            let changeset = repo
+                .changeset(changeset_specifier)
+                .await?
+                .ok_or_else(|| errors::commit_not_found(parent.to_string()))?;
+            Ok::<_, errors::ServiceError>(changeset.id())
+        })
+        .collect::<FuturesOrdered<_>>()
+        .try_collect()
+        .await
+}
+
+/// Convert the changes in a create commit request into the changes to make
+/// to the files in the commit.
+async fn convert_create_commit_changes(
+    repo: &RepoContext,
+    changes: BTreeMap<String, thrift::RepoCreateCommitParamsChange>,
+) -> Result<BTreeMap<MononokePath, CreateChange>, errors::ServiceError> {
+    changes
+        .into_iter()
+        .map(|(path, change)| async move {
+            let path = MononokePath::try_from(&path)
+                .map_err(|e| errors::invalid_request(format!("invalid path '{}': {}", path, e)))?;
+            let change = match change {
+                thrift::RepoCreateCommitParamsChange::changed(c) => {
+                    let file_type = FileType::from_request(&c.r#type)?;
+                    let copy_info = c
+                        .copy_info
+                        .as_ref()
+                        .map(CreateCopyInfo::from_request)
+                        .transpose()?;
+                    match c.content {
+                        thrift::RepoCreateCommitParamsFileContent::id(id) => {
+                            let file_id = FileId::from_request(&id)?;
+                            let file = repo
+                                .file(file_id)
+                                .await?
+                                .ok_or_else(|| errors::file_not_found(file_id.to_string()))?;
+                            CreateChange::Tracked(
+                                CreateChangeFile::Existing {
+                                    file_id: file.id().await?,
+                                    file_type,
+                                    maybe_size: None,
+                                },
+                                copy_info,
+                            )
+                        }
+                        thrift::RepoCreateCommitParamsFileContent::content_sha1(sha) => {
+                            let sha = Sha1::from_request(&sha)?;
+                            let file = repo
+                                .file_by_content_sha1(sha)
+                                .await?
+                                .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
+                            CreateChange::Tracked(
+                                CreateChangeFile::Existing {
+                                    file_id: file.id().await?,
+                                    file_type,
+                                    maybe_size: None,
+                                },
+                                copy_info,
+                            )
+                        }
+                        thrift::RepoCreateCommitParamsFileContent::content_sha256(sha) => {
+                            let sha = Sha256::from_request(&sha)?;
+                            let file = repo
+                                .file_by_content_sha256(sha)
+                                .await?
+                                .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
+                            CreateChange::Tracked(
+                                CreateChangeFile::Existing {
+                                    file_id: file.id().await?,
+                                    file_type,
+                                    maybe_size: None,
+                                },
+                                copy_info,
+                            )
+                        }
+                        thrift::RepoCreateCommitParamsFileContent::content_gitsha1(sha) => {
+                            let sha = GitSha1::from_request(&sha)?;
+                            let file = repo
+                                .file_by_content_gitsha1(sha)
+                                .await?
+                                .ok_or_else(|| errors::file_not_found(sha.to_string()))?;
+                            CreateChange::Tracked(
+                                CreateChangeFile::Existing {
+                                    file_id: file.id().await?,
+                                    file_type,
+                                    maybe_size: None,
+                                },
+                                copy_info,
+                            )
+                        }
+                        thrift::RepoCreateCommitParamsFileContent::data(data) => {
+                            CreateChange::Tracked(
+                                CreateChangeFile::New {
+                                    bytes: Bytes::from(data),
+                                    file_type,
+                                },
+                                copy_info,
+                            )
+                        }
+                        thrift::RepoCreateCommitParamsFileContent::UnknownField(t) => {
+                            return Err(errors::invalid_request(format!(
+                                "file content type not supported: {}",
+                                t
+                            ))
+                            .into());
+                        }
+                    }
+                }
+                thrift::RepoCreateCommitParamsChange::deleted(_d) => CreateChange::Deletion,
+                thrift::RepoCreateCommitParamsChange::UnknownField(t) => {
+                    return Err(errors::invalid_request(format!(
+                        "file change type not supported: {}",
+                        t
+                    ))
+                    .into());
+                }
+            };
+            Ok::<_, errors::ServiceError>((path, change))
+        })
+        .collect::<FuturesOrdered<_>>()
+        .try_collect()
+        .await
+}
+
+/// Convert the author and committer dates in a create commit request.  If
+/// the author date is omitted, the current time is used.
+fn convert_commit_dates(
+    info: &thrift::RepoCreateCommitParamsCommitInfo,
+) -> Result<(DateTime<FixedOffset>, Option<DateTime<FixedOffset>>), errors::ServiceError> {
+    let author_date = info.date.as_ref().map_or_else(
+        || {
+            let now = Local::now();
+            Ok(now.with_timezone(now.offset()))
+        },
+        <DateTime<FixedOffset>>::from_request,
+    )?;
+    let committer_date = info
+        .committer_date
+        .as_ref()
+        .map(<DateTime<FixedOffset>>::from_request)
+        .transpose()?;
+    Ok((author_date, committer_date))
+}
@@ -38,6 +38,23 @@ impl AddScubaParams for BTreeSet<thrift::CommitIdentityScheme> {
 
 impl AddScubaParams for thrift::ListReposParams {}
 
+impl AddScubaParams for thrift::LocateCommitParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_prefix", self.prefix.as_str());
+        scuba.add("param_prefix_scheme", self.prefix_scheme.to_string());
+        self.identity_schemes.add_scuba_params(scuba);
+        if let Some(repos) = &self.repos {
+            scuba.add(
+                "param_repos",
+                repos
+                    .iter()
+                    .map(|repo| repo.name.as_str())
+                    .collect::<ScubaValue>(),
+            );
+        }
+    }
+}
+
 impl AddScubaParams for thrift::RepoCreateCommitParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add(
@@ -71,6 +88,24 @@ impl AddScubaParams for thrift::RepoCreateCommitParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoAmendCommitExtrasParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("commit", self.commit.to_string());
+        scuba.add(
+            "param_set_extras",
+            self.set_extras.keys().collect::<ScubaValue>(),
+        );
+        scuba.add(
+            "param_remove_extras",
+            self.remove_extras.iter().collect::<ScubaValue>(),
+        );
+        self.identity_schemes.add_scuba_params(scuba);
+        if let Some(service_identity) = self.service_identity.as_deref() {
+            scuba.add("service_identity", service_identity);
+        }
+    }
+}
+
 impl AddScubaParams for thrift::RepoCreateBookmarkParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("bookmark_name", self.bookmark.as_str());
@@ -110,6 +145,29 @@ impl AddScubaParams for thrift::RepoDeleteBookmarkParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoModifyBookmarksParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        let bookmarks: ScubaValue = self
+            .modifications
+            .iter()
+            .filter_map(|modification| match modification {
+                thrift::BookmarkModification::create_bookmark(create) => {
+                    Some(create.bookmark.clone())
+                }
+                thrift::BookmarkModification::move_bookmark(move_) => Some(move_.bookmark.clone()),
+                thrift::BookmarkModification::delete_bookmark(delete) => {
+                    Some(delete.bookmark.clone())
+                }
+                thrift::BookmarkModification::UnknownField(_) => None,
+            })
+            .collect();
+        scuba.add("param_bookmarks", bookmarks);
+        if let Some(service_identity) = self.service_identity.as_deref() {
+            scuba.add("service_identity", service_identity);
+        }
+    }
+}
+
 impl AddScubaParams for thrift::RepoLandStackParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("bookmark_name", self.bookmark.as_str());
@@ -157,6 +215,32 @@ impl AddScubaParams for thrift::RepoBookmarkInfoParams {
     }
 }
 
+impl AddScubaParams for thrift::RepoPushedCommitsParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark_name.as_str());
+        scuba.add("param_from_timestamp", self.from_timestamp);
+        scuba.add("param_to_timestamp", self.to_timestamp);
+        scuba.add("param_format", self.format.to_string());
+        scuba.add("param_skip", self.skip);
+        scuba.add("param_limit", self.limit);
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
+impl AddScubaParams for thrift::RepoTailCommitsParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        if let Some(cursor) = self.cursor {
+            scuba.add("param_cursor", cursor);
+        }
+        if let Some(bookmark_name) = &self.bookmark_name {
+            scuba.add("bookmark_name", bookmark_name.as_str());
+        }
+        scuba.add("param_limit", self.limit);
+        scuba.add("param_wait_ms", self.wait_ms);
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
+
 impl AddScubaParams for thrift::RepoResolveCommitPrefixParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         scuba.add("param_prefix", self.prefix.as_str());
@@ -169,6 +253,19 @@ impl AddScubaParams for thrift::RepoStackInfoParams {}
 
 impl AddScubaParams for thrift::RepoPrepareCommitsParams {}
 
+impl AddScubaParams for thrift::RepoBlobFetchParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_key", self.key.as_str());
+    }
+}
+
+impl AddScubaParams for thrift::RepoRunHooksDryRunParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("bookmark_name", self.bookmark.as_str());
+        scuba.add("param_commit_count", self.commits.len());
+    }
+}
+
 impl AddScubaParams for thrift::CommitCompareParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         if let Some(other_commit_id) = self.other_commit_id.as_ref() {
@@ -229,6 +326,15 @@ impl AddScubaParams for thrift::CommitCommonBaseWithParams {
     }
 }
 
+impl AddScubaParams for thrift::CommitTraceMergeCopiesParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("other_commit", self.other_commit_id.to_string());
+        if let Some(base_commit_id) = &self.base_commit_id {
+            scuba.add("base_commit", base_commit_id.to_string());
+        }
+    }
+}
+
 impl AddScubaParams for thrift::CommitLookupParams {
     fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
         self.identity_schemes.add_scuba_params(scuba);
@@ -305,7 +411,12 @@ impl AddScubaParams for thrift::CommitPathHistoryParams {
 
 impl AddScubaParams for thrift::CommitPathExistsParams {}
 
-impl AddScubaParams for thrift::CommitPathInfoParams {}
+impl AddScubaParams for thrift::CommitPathInfoParams {
+    fn add_scuba_params(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        scuba.add("param_include_rollup", self.include_rollup);
+        self.identity_schemes.add_scuba_params(scuba);
+    }
+}
 
 impl AddScubaParams for thrift::RepoInfoParams {}
 
@@ -21,6 +21,8 @@ impl AddScubaResponse for bool {}
 
 impl AddScubaResponse for Vec<thrift::Repo> {}
 
+impl AddScubaResponse for thrift::LocateCommitResponse {}
+
 impl AddScubaResponse for thrift::RepoInfo {}
 
 impl AddScubaResponse for thrift::RepoCreateCommitResponse {
@@ -31,12 +33,22 @@ impl AddScubaResponse for thrift::RepoCreateCommitResponse {
     }
 }
 
+impl AddScubaResponse for thrift::RepoAmendCommitExtrasResponse {
+    fn add_scuba_response(&self, scuba: &mut MononokeScubaSampleBuilder) {
+        if let Some(id) = self.ids.get(&thrift::CommitIdentityScheme::BONSAI) {
+            scuba.add("commit", id.to_string());
+        }
+    }
+}
+
 impl AddScubaResponse for thrift::RepoCreateBookmarkResponse {}
 
 impl AddScubaResponse for thrift::RepoMoveBookmarkResponse {}
 
 impl AddScubaResponse for thrift::RepoDeleteBookmarkResponse {}
 
+impl AddScubaResponse for thrift::RepoModifyBookmarksResponse {}
+
 impl AddScubaResponse for thrift::RepoLandStackResponse {}
 
 impl AddScubaResponse for thrift::RepoListBookmarksResponse {}
@@ -47,10 +59,18 @@ impl AddScubaResponse for thrift::RepoResolveCommitPrefixResponse {}
 
 impl AddScubaResponse for thrift::RepoBookmarkInfoResponse {}
 
+impl AddScubaResponse for thrift::RepoPushedCommitsResponse {}
+
+impl AddScubaResponse for thrift::RepoTailCommitsResponse {}
+
 impl AddScubaResponse for thrift::RepoStackInfoResponse {}
 
 impl AddScubaResponse for thrift::RepoPrepareCommitsResponse {}
 
+impl AddScubaResponse for thrift::RepoBlobFetchResponse {}
+
+impl AddScubaResponse for thrift::RepoRunHooksDryRunResponse {}
+
 impl AddScubaResponse for thrift::CommitCompareResponse {}
 
 impl AddScubaResponse for thrift::CommitFileDiffsResponse {}
@@ -61,6 +81,8 @@ impl AddScubaResponse for thrift::CommitInfo {}
 
 impl AddScubaResponse for thrift::CommitLookupResponse {}
 
+impl AddScubaResponse for thrift::CommitTraceMergeCopiesResponse {}
+
 impl AddScubaResponse for thrift::CommitLookupPushrebaseHistoryResponse {}
 
 impl AddScubaResponse for thrift::CommitHistoryResponse {}
@@ -652,6 +652,10 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::ListReposParams,
         ) -> Result<Vec<thrift::Repo>, service::ListReposExn>;
 
+        async fn locate_commit(
+            params: thrift::LocateCommitParams,
+        ) -> Result<thrift::LocateCommitResponse, service::LocateCommitExn>;
+
         async fn repo_info(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoInfoParams,
@@ -677,6 +681,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::CommitCommonBaseWithParams,
         ) -> Result<thrift::CommitLookupResponse, service::CommitCommonBaseWithExn>;
 
+        async fn commit_trace_merge_copies(
+            commit: thrift::CommitSpecifier,
+            params: thrift::CommitTraceMergeCopiesParams,
+        ) -> Result<thrift::CommitTraceMergeCopiesResponse, service::CommitTraceMergeCopiesExn>;
+
         async fn commit_lookup(
             commit: thrift::CommitSpecifier,
             params: thrift::CommitLookupParams,
@@ -812,11 +821,26 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoCreateCommitParams,
         ) -> Result<thrift::RepoCreateCommitResponse, service::RepoCreateCommitExn>;
 
+        async fn repo_amend_commit_extras(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoAmendCommitExtrasParams,
+        ) -> Result<thrift::RepoAmendCommitExtrasResponse, service::RepoAmendCommitExtrasExn>;
+
         async fn repo_bookmark_info(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoBookmarkInfoParams,
         ) -> Result<thrift::RepoBookmarkInfoResponse, service::RepoBookmarkInfoExn>;
 
+        async fn repo_pushed_commits(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoPushedCommitsParams,
+        ) -> Result<thrift::RepoPushedCommitsResponse, service::RepoPushedCommitsExn>;
+
+        async fn repo_tail_commits(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoTailCommitsParams,
+        ) -> Result<thrift::RepoTailCommitsResponse, service::RepoTailCommitsExn>;
+
         async fn repo_stack_info(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoStackInfoParams,
@@ -837,6 +861,11 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoDeleteBookmarkParams,
         ) -> Result<thrift::RepoDeleteBookmarkResponse, service::RepoDeleteBookmarkExn>;
 
+        async fn repo_modify_bookmarks(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoModifyBookmarksParams,
+        ) -> Result<thrift::RepoModifyBookmarksResponse, service::RepoModifyBookmarksExn>;
+
         async fn repo_land_stack(
             repo: thrift::RepoSpecifier,
             params: thrift::RepoLandStackParams,
@@ -847,6 +876,16 @@ impl SourceControlService for SourceControlServiceThriftImpl {
             params: thrift::RepoPrepareCommitsParams,
         ) -> Result<thrift::RepoPrepareCommitsResponse, service::RepoPrepareCommitsExn>;
 
+        async fn repo_blob_fetch(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoBlobFetchParams,
+        ) -> Result<thrift::RepoBlobFetchResponse, service::RepoBlobFetchExn>;
+
+        async fn repo_run_hooks_dry_run(
+            repo: thrift::RepoSpecifier,
+            params: thrift::RepoRunHooksDryRunParams,
+        ) -> Result<thrift::RepoRunHooksDryRunResponse, service::RepoRunHooksDryRunExn>;
+
         async fn megarepo_add_sync_target_config(
             params: thrift::MegarepoAddConfigParams,
         ) -> Result<thrift::MegarepoAddConfigResponse, service::MegarepoAddSyncTargetConfigExn>;
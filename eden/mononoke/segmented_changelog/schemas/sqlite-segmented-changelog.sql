@@ -35,3 +35,10 @@ CREATE TABLE IF NOT EXISTS segmented_changelog_clone_hints (
   blob_name STRING NOT NULL,
   PRIMARY KEY (repo_id, idmap_version, blob_name)
 );
+
+CREATE TABLE IF NOT EXISTS segmented_changelog_superseded_idmap (
+  repo_id INTEGER NOT NULL,
+  idmap_version INTEGER NOT NULL,
+  superseded_at BIGINT NOT NULL,
+  PRIMARY KEY (repo_id, idmap_version)
+);
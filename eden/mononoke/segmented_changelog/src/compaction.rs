@@ -0,0 +1,458 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Offline compaction of the persisted segmented changelog.
+//!
+//! Every reseed or truncation creates a new IdMap version, and the rows of
+//! the versions it supersedes are no longer read once servers have reloaded
+//! the segmented changelog.  Meanwhile the tailer extends the IdDag a few
+//! commits at a time, leaving it with many small flat segments.  Compaction
+//! deletes the superseded IdMap versions (in rate-limited batches, so the
+//! database isn't overloaded) and rebuilds the IdDag so that adjacent flat
+//! segments are merged and the high-level segments are recomputed.
+//!
+//! A superseded version is only deleted once a grace period has passed
+//! since compaction first found it superseded, so that servers still using
+//! it have time to move to the current version.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::format_err;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use slog::info;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+use crate::iddag::IdDagSaveStore;
+use crate::types::IdDagVersion;
+use crate::types::IdMapVersion;
+use crate::version_store::SegmentedChangelogVersionStore;
+use crate::Group;
+use crate::InProcessIdDag;
+
+mononoke_queries! {
+    read SelectIdMapVersions(repo_id: RepositoryId) -> (IdMapVersion,) {
+        "
+        SELECT DISTINCT version
+        FROM segmented_changelog_idmap
+        WHERE repo_id = {repo_id}
+        "
+    }
+
+    read SelectCopySources(repo_id: RepositoryId, version: IdMapVersion) -> (IdMapVersion,) {
+        "
+        SELECT copied_version
+        FROM segmented_changelog_idmap_copy_mappings
+        WHERE repo_id = {repo_id} AND idmap_version = {version}
+        "
+    }
+
+    read SelectMinVertex(repo_id: RepositoryId, version: IdMapVersion) -> (Option<u64>,) {
+        "
+        SELECT MIN(vertex)
+        FROM segmented_changelog_idmap
+        WHERE repo_id = {repo_id} AND version = {version}
+        "
+    }
+
+    read SelectSupersededIdMapVersions(repo_id: RepositoryId) -> (IdMapVersion, Timestamp) {
+        "
+        SELECT idmap_version, superseded_at
+        FROM segmented_changelog_superseded_idmap
+        WHERE repo_id = {repo_id}
+        "
+    }
+
+    write InsertSupersededIdMapVersions(
+        values: (repo_id: RepositoryId, idmap_version: IdMapVersion, superseded_at: Timestamp)
+    ) {
+        insert_or_ignore,
+        "
+        {insert_or_ignore} INTO segmented_changelog_superseded_idmap (repo_id, idmap_version, superseded_at)
+        VALUES {values}
+        "
+    }
+
+    write DeleteSupersededIdMapVersion(repo_id: RepositoryId, version: IdMapVersion) {
+        none,
+        "
+        DELETE FROM segmented_changelog_superseded_idmap
+        WHERE repo_id = {repo_id} AND idmap_version = {version}
+        "
+    }
+
+    write DeleteIdMapRange(
+        repo_id: RepositoryId,
+        version: IdMapVersion,
+        low: u64,
+        high: u64,
+    ) {
+        none,
+        "
+        DELETE FROM segmented_changelog_idmap
+        WHERE repo_id = {repo_id} AND version = {version} AND vertex >= {low} AND vertex < {high}
+        "
+    }
+
+    write DeleteCopyMappings(repo_id: RepositoryId, version: IdMapVersion) {
+        none,
+        "
+        DELETE FROM segmented_changelog_idmap_copy_mappings
+        WHERE repo_id = {repo_id} AND idmap_version = {version}
+        "
+    }
+
+    write DeleteCloneHints(repo_id: RepositoryId, version: IdMapVersion) {
+        none,
+        "
+        DELETE FROM segmented_changelog_clone_hints
+        WHERE repo_id = {repo_id} AND idmap_version = {version}
+        "
+    }
+
+    write ReplaceIdDagVersion(
+        repo_id: RepositoryId,
+        idmap_version: IdMapVersion,
+        old_iddag_version: IdDagVersion,
+        new_iddag_version: IdDagVersion,
+    ) {
+        none,
+        "
+        UPDATE segmented_changelog_version
+        SET iddag_version = {new_iddag_version}
+        WHERE repo_id = {repo_id}
+            AND idmap_version = {idmap_version}
+            AND iddag_version = {old_iddag_version}
+        "
+    }
+}
+
+/// Limits on how quickly compaction modifies the database.
+#[derive(Clone, Debug)]
+pub struct CompactionOptions {
+    /// Number of IdMap rows to delete in a single query.
+    pub batch_size: u64,
+    /// How long to wait between deletions.
+    pub batch_delay: Duration,
+    /// How long to keep an IdMap version after it has been superseded.
+    pub grace_period: Duration,
+    /// Only report what would be compacted.
+    pub dry_run: bool,
+}
+
+impl Default for CompactionOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 10_000,
+            batch_delay: Duration::from_millis(100),
+            grace_period: Duration::from_secs(24 * 60 * 60),
+            dry_run: false,
+        }
+    }
+}
+
+/// What a compaction did (or would have done, for a dry run).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// IdMap versions that were superseded by the current version before
+    /// the grace period, and so were removed.
+    pub superseded_idmap_versions: Vec<IdMapVersion>,
+    /// IdMap versions that were superseded within the grace period, and so
+    /// were kept.
+    pub retained_idmap_versions: Vec<IdMapVersion>,
+    /// Number of IdMap rows deleted.
+    pub idmap_rows_deleted: u64,
+    /// Number of flat segments in the IdDag before and after rebuilding it.
+    pub flat_segments_before: usize,
+    pub flat_segments_after: usize,
+}
+
+pub struct SegmentedChangelogCompactor {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+    version_store: SegmentedChangelogVersionStore,
+    iddag_save_store: IdDagSaveStore,
+}
+
+impl SegmentedChangelogCompactor {
+    pub fn new(
+        repo_id: RepositoryId,
+        connections: SqlConnections,
+        blobstore: Arc<dyn Blobstore>,
+    ) -> Self {
+        Self {
+            repo_id,
+            version_store: SegmentedChangelogVersionStore::new(connections.clone(), repo_id),
+            iddag_save_store: IdDagSaveStore::new(repo_id, blobstore),
+            connections,
+        }
+    }
+
+    pub async fn compact(
+        &self,
+        ctx: &CoreContext,
+        options: &CompactionOptions,
+    ) -> Result<CompactionStats> {
+        let version = self
+            .version_store
+            .get(ctx)
+            .await
+            .with_context(|| {
+                format!(
+                    "repo {}: error loading segmented changelog version",
+                    self.repo_id
+                )
+            })?
+            .ok_or_else(|| {
+                format_err!(
+                    "repo {}: segmented changelog metadata not found, maybe repo is not seeded",
+                    self.repo_id
+                )
+            })?;
+
+        let mut stats = CompactionStats::default();
+        let superseded = self
+            .superseded_idmap_versions(ctx, version.idmap_version)
+            .await?;
+        let superseded_at = self
+            .record_superseded_idmap_versions(ctx, &superseded, options)
+            .await?;
+        let cutoff = Timestamp::from(
+            ctx.now()
+                .checked_sub(options.grace_period)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        );
+        for superseded in superseded {
+            if superseded_at[&superseded] > cutoff {
+                info!(
+                    ctx.logger(),
+                    "repo {}: keeping recently superseded idmap version {}",
+                    self.repo_id,
+                    superseded
+                );
+                stats.retained_idmap_versions.push(superseded);
+                continue;
+            }
+            info!(
+                ctx.logger(),
+                "repo {}: removing superseded idmap version {}", self.repo_id, superseded
+            );
+            stats.superseded_idmap_versions.push(superseded);
+            if !options.dry_run {
+                stats.idmap_rows_deleted +=
+                    self.delete_idmap_version(ctx, superseded, options).await?;
+            }
+        }
+
+        let (before, after) = self
+            .rebuild_iddag(ctx, version.idmap_version, version.iddag_version, options)
+            .await?;
+        stats.flat_segments_before = before;
+        stats.flat_segments_after = after;
+
+        Ok(stats)
+    }
+
+    /// Find the IdMap versions that are older than `current` and not used
+    /// by it.  Newer versions are never superseded, as they may be in the
+    /// process of being built.
+    async fn superseded_idmap_versions(
+        &self,
+        ctx: &CoreContext,
+        current: IdMapVersion,
+    ) -> Result<Vec<IdMapVersion>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let conn = &self.connections.write_connection;
+
+        // The current version may read from the versions it was copied from.
+        let mut in_use = BTreeSet::new();
+        let mut to_visit = vec![current];
+        while let Some(version) = to_visit.pop() {
+            if in_use.insert(version) {
+                let sources = SelectCopySources::query(conn, &self.repo_id, &version).await?;
+                to_visit.extend(sources.into_iter().map(|(source,)| source));
+            }
+        }
+
+        let versions = SelectIdMapVersions::query(conn, &self.repo_id).await?;
+        let mut superseded = versions
+            .into_iter()
+            .map(|(version,)| version)
+            .filter(|version| *version < current && !in_use.contains(version))
+            .collect::<Vec<_>>();
+        superseded.sort();
+        Ok(superseded)
+    }
+
+    /// Record when each of the `superseded` versions was first found to be
+    /// superseded, returning those times.  Versions found for the first
+    /// time are recorded as superseded now.
+    async fn record_superseded_idmap_versions(
+        &self,
+        ctx: &CoreContext,
+        superseded: &[IdMapVersion],
+        options: &CompactionOptions,
+    ) -> Result<BTreeMap<IdMapVersion, Timestamp>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsMaster);
+        let conn = &self.connections.write_connection;
+        let mut superseded_at = SelectSupersededIdMapVersions::query(conn, &self.repo_id)
+            .await?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let now = Timestamp::from(ctx.now());
+        let new = superseded
+            .iter()
+            .filter(|version| !superseded_at.contains_key(version))
+            .collect::<Vec<_>>();
+        if !new.is_empty() && !options.dry_run {
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlWrites);
+            let rows = new
+                .iter()
+                .map(|version| (&self.repo_id, *version, &now))
+                .collect::<Vec<_>>();
+            InsertSupersededIdMapVersions::query(conn, &rows)
+                .await
+                .with_context(|| {
+                    format!(
+                        "repo {}: error recording superseded idmap versions",
+                        self.repo_id
+                    )
+                })?;
+        }
+        superseded_at.extend(new.into_iter().map(|version| (*version, now)));
+        Ok(superseded_at)
+    }
+
+    /// Delete an IdMap version and its copy mappings and clone hints,
+    /// returning the number of IdMap rows deleted.
+    async fn delete_idmap_version(
+        &self,
+        ctx: &CoreContext,
+        version: IdMapVersion,
+        options: &CompactionOptions,
+    ) -> Result<u64> {
+        let conn = &self.connections.write_connection;
+        let batch_size = options.batch_size.max(1);
+        let mut deleted = 0;
+        while let Some((Some(low),)) = SelectMinVertex::query(conn, &self.repo_id, &version)
+            .await?
+            .into_iter()
+            .next()
+        {
+            ctx.perf_counters()
+                .increment_counter(PerfCounterType::SqlWrites);
+            let result = DeleteIdMapRange::query(
+                conn,
+                &self.repo_id,
+                &version,
+                &low,
+                &low.saturating_add(batch_size),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "repo {}: error deleting idmap version {}",
+                    self.repo_id, version
+                )
+            })?;
+            deleted += result.affected_rows();
+            tokio::time::sleep(options.batch_delay).await;
+        }
+
+        // Only delete the copy mappings once the rows are gone, so that an
+        // interrupted compaction can't leave a version that looks complete.
+        DeleteCopyMappings::query(conn, &self.repo_id, &version).await?;
+        DeleteCloneHints::query(conn, &self.repo_id, &version).await?;
+        DeleteSupersededIdMapVersion::query(conn, &self.repo_id, &version).await?;
+        Ok(deleted)
+    }
+
+    /// Rebuild the IdDag from scratch, which merges adjacent flat segments
+    /// and recomputes the high-level segments.  The new IdDag is only saved
+    /// if it is smaller and the IdDag hasn't been updated in the meantime.
+    ///
+    /// Returns the number of flat segments before and after.
+    async fn rebuild_iddag(
+        &self,
+        ctx: &CoreContext,
+        idmap_version: IdMapVersion,
+        iddag_version: IdDagVersion,
+        options: &CompactionOptions,
+    ) -> Result<(usize, usize)> {
+        let old_iddag = self
+            .iddag_save_store
+            .load(ctx, iddag_version)
+            .await
+            .with_context(|| format!("repo {}: failed to load iddag", self.repo_id))?;
+        let all = old_iddag.all()?;
+        let before = old_iddag.flat_segments(Group::MASTER)?.segment_count();
+
+        let mut new_iddag = InProcessIdDag::new_in_process();
+        let get_parents = |id| old_iddag.parent_ids(id);
+        for head in old_iddag.heads(all.clone())?.iter_asc() {
+            new_iddag.build_segments(head, &get_parents)?;
+        }
+        let new_all = new_iddag.all()?;
+        if new_all.count() != all.count() || !all.difference(&new_all).is_empty() {
+            return Err(format_err!(
+                "repo {}: rebuilt iddag does not cover the same ids",
+                self.repo_id
+            ));
+        }
+        let after = new_iddag.flat_segments(Group::MASTER)?.segment_count();
+        info!(
+            ctx.logger(),
+            "repo {}: rebuilt iddag has {} flat segments, down from {}",
+            self.repo_id,
+            after,
+            before
+        );
+        if after >= before || options.dry_run {
+            return Ok((before, after));
+        }
+
+        let new_iddag_version = self
+            .iddag_save_store
+            .save(ctx, &new_iddag)
+            .await
+            .with_context(|| format!("repo {}: error saving iddag", self.repo_id))?;
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let result = ReplaceIdDagVersion::query(
+            &self.connections.write_connection,
+            &self.repo_id,
+            &idmap_version,
+            &iddag_version,
+            &new_iddag_version,
+        )
+        .await?;
+        if result.affected_rows() == 0 {
+            // The tailer got there first.  Its IdDag is newer, so keep it,
+            // and leave the merging to the next compaction.
+            info!(
+                ctx.logger(),
+                "repo {}: iddag was updated during compaction, not replacing it", self.repo_id
+            );
+            return Ok((before, before));
+        }
+        Ok((before, after))
+    }
+}
@@ -19,6 +19,7 @@ use mononoke_types::ChangesetId;
 
 mod builder;
 mod clone_hints;
+mod compaction;
 mod copy;
 mod iddag;
 mod idmap;
@@ -60,6 +61,9 @@ pub use crate::builder::new_server_segmented_changelog_manager;
 pub use crate::builder::new_test_segmented_changelog;
 pub use crate::builder::SegmentedChangelogSqlConnections;
 pub use crate::clone_hints::CloneHints;
+pub use crate::compaction::CompactionOptions;
+pub use crate::compaction::CompactionStats;
+pub use crate::compaction::SegmentedChangelogCompactor;
 pub use crate::copy::copy_segmented_changelog;
 // public for benchmarking
 pub use crate::idmap::ConcurrentMemIdMap;
@@ -25,6 +25,8 @@ use changesets::ChangesetEntry;
 use changesets::ChangesetsArc;
 use changesets::ChangesetsRef;
 use context::CoreContext;
+use context::SessionContainer;
+use context::TestClock;
 use fbinit::FacebookInit;
 use fixtures::set_bookmark;
 use fixtures::BranchEven;
@@ -53,6 +55,8 @@ use tunables::override_tunables;
 use tunables::with_tunables_async;
 
 use crate::builder::SegmentedChangelogSqlConnections;
+use crate::compaction::CompactionOptions;
+use crate::compaction::SegmentedChangelogCompactor;
 use crate::iddag::IdDagSaveStore;
 use crate::idmap::CacheHandlers;
 use crate::idmap::ConcurrentMemIdMap;
@@ -1223,3 +1227,75 @@ async fn test_seeding_with_included_bonsais(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+#[fbinit::test]
+async fn test_compaction(fb: FacebookInit) -> Result<()> {
+    let clock = Arc::new(TestClock::at_unix_secs(1000));
+    let ctx =
+        CoreContext::test_mock_session(SessionContainer::builder(fb).clock(clock.clone()).build());
+    let blobrepo = Linear::getrepo(fb).await;
+    let conns = SegmentedChangelogSqlConnections::with_sqlite_in_memory()?;
+    let repo_id = blobrepo.get_repoid();
+
+    let known_cs =
+        resolve_cs_id(&ctx, &blobrepo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+    // Reseeding leaves the IdMap of the first seeding behind.
+    seed(&ctx, &blobrepo, &conns, known_cs).await?;
+    let first_version = load_sc_version(&ctx, repo_id, &conns).await?;
+    let tailer = new_tailer(&blobrepo, &conns, None, Some(vec![known_cs])).await?;
+    tailer.once(&ctx, true).await?;
+    let second_version = load_sc_version(&ctx, repo_id, &conns).await?;
+    assert!(first_version.idmap_version < second_version.idmap_version);
+
+    let compactor = SegmentedChangelogCompactor::new(
+        repo_id,
+        conns.0.clone(),
+        Arc::new(blobrepo.get_blobstore()),
+    );
+    let options = CompactionOptions {
+        batch_size: 2,
+        batch_delay: Duration::from_millis(0),
+        grace_period: Duration::from_secs(3600),
+        dry_run: false,
+    };
+
+    // The first version is kept until the grace period has passed, as
+    // servers may still be using it.
+    let stats = compactor.compact(&ctx, &options).await?;
+    assert!(stats.superseded_idmap_versions.is_empty());
+    assert_eq!(
+        stats.retained_idmap_versions,
+        vec![first_version.idmap_version]
+    );
+    assert_eq!(stats.idmap_rows_deleted, 0);
+    clock.advance(Duration::from_secs(1800));
+    let stats = compactor.compact(&ctx, &options).await?;
+    assert_eq!(
+        stats.retained_idmap_versions,
+        vec![first_version.idmap_version]
+    );
+
+    clock.advance(Duration::from_secs(1800));
+    let stats = compactor.compact(&ctx, &options).await?;
+    assert_eq!(
+        stats.superseded_idmap_versions,
+        vec![first_version.idmap_version]
+    );
+    assert!(stats.retained_idmap_versions.is_empty());
+    assert!(stats.idmap_rows_deleted > 0);
+    assert!(stats.flat_segments_after <= stats.flat_segments_before);
+
+    // The current version still works, and there is nothing left to compact.
+    let sc = load_owned(&ctx, &blobrepo, &conns).await?;
+    let answer = sc
+        .location_to_changeset_id(&ctx, Location::new(known_cs, 2))
+        .await?;
+    let expected_cs =
+        resolve_cs_id(&ctx, &blobrepo, "3e0e761030db6e479a7fb58b12881883f9f8c63f").await?;
+    assert_eq!(answer, expected_cs);
+    let stats = compactor.compact(&ctx, &options).await?;
+    assert!(stats.superseded_idmap_versions.is_empty());
+    assert!(stats.retained_idmap_versions.is_empty());
+
+    Ok(())
+}
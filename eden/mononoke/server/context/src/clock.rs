@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Source of the current time for a session.  Code that records timestamps
+/// or decides whether something has expired should ask the session's clock
+/// rather than the system, so that tests can control time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system clock.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock for tests, which only moves when it is advanced or set.
+#[derive(Debug)]
+pub struct TestClock {
+    now: Mutex<SystemTime>,
+}
+
+impl TestClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// A clock set to the given number of seconds after the unix epoch.
+    pub fn at_unix_secs(secs: u64) -> Self {
+        Self::new(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().expect("lock poisoned") += duration;
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().expect("lock poisoned") = now;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().expect("lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_test_clock() {
+        let clock = TestClock::at_unix_secs(100);
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(100)
+        );
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(105)
+        );
+
+        clock.set(SystemTime::UNIX_EPOCH);
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH);
+    }
+}
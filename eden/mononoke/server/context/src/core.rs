@@ -6,8 +6,10 @@
  */
 
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use fbinit::FacebookInit;
+use feature_flags::SessionFeatureFlags;
 use metadata::Metadata;
 use scribe_ext::Scribe;
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -20,6 +22,7 @@ use crate::perf_counters::PerfCounters;
 use crate::perf_counters_stack::PerfCountersStack;
 use crate::session::SessionClass;
 use crate::session::SessionContainer;
+use crate::trace::TraceContext;
 
 #[derive(Clone)]
 pub struct CoreContext {
@@ -92,6 +95,16 @@ impl CoreContext {
         }
     }
 
+    /// Create a new CoreContext whose operations are recorded in `trace`,
+    /// e.g. as children of a span.
+    pub fn clone_with_trace(&self, trace: TraceContext) -> Self {
+        Self {
+            fb: self.fb,
+            session: self.session.clone(),
+            logging: self.logging.clone_with_trace(trace),
+        }
+    }
+
     pub fn with_mutated_scuba(
         &self,
         mutator: impl FnOnce(MononokeScubaSampleBuilder) -> MononokeScubaSampleBuilder,
@@ -123,6 +136,10 @@ impl CoreContext {
         self.session.metadata()
     }
 
+    pub fn feature_flags(&self) -> &SessionFeatureFlags {
+        self.session.feature_flags()
+    }
+
     pub fn session(&self) -> &SessionContainer {
         &self.session
     }
@@ -135,7 +152,17 @@ impl CoreContext {
         self.logging.scribe()
     }
 
+    pub fn trace(&self) -> &TraceContext {
+        self.logging.trace()
+    }
+
     pub fn fork_perf_counters(&mut self) -> Arc<PerfCounters> {
         self.logging.fork_perf_counters()
     }
+
+    /// The current time, according to the session's clock.  Use this rather
+    /// than the system time for timestamps and expiry decisions.
+    pub fn now(&self) -> SystemTime {
+        self.session.now()
+    }
 }
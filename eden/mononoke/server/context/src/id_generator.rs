@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use rand::Rng;
+use session_id::generate_session_id;
+use session_id::SessionId;
+
+/// Source of the ids given to sessions and traces.  Tests can use a
+/// deterministic generator so that they can match on these ids.
+pub trait IdGenerator: Send + Sync {
+    fn session_id(&self) -> SessionId;
+
+    fn trace_id(&self) -> String;
+}
+
+/// Generates random ids.
+#[derive(Debug, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn session_id(&self) -> SessionId {
+        generate_session_id()
+    }
+
+    fn trace_id(&self) -> String {
+        format!("{:016x}", rand::thread_rng().gen::<u64>())
+    }
+}
+
+/// Generates ids from a counter, for tests.  The first session id is
+/// "session-1" and the first trace id is "0000000000000001", and both
+/// share the same counter.
+#[derive(Debug)]
+pub struct SequentialIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new() -> Self {
+        Self {
+            next: AtomicU64::new(1),
+        }
+    }
+
+    fn next(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl Default for SequentialIdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn session_id(&self) -> SessionId {
+        SessionId::from_string(format!("session-{}", self.next()))
+    }
+
+    fn trace_id(&self) -> String {
+        format!("{:016x}", self.next())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIdGenerator::new();
+        assert_eq!(ids.session_id().as_str(), "session-1");
+        assert_eq!(ids.trace_id(), "0000000000000002");
+        assert_eq!(ids.session_id().as_str(), "session-3");
+    }
+}
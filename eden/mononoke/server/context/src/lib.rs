@@ -9,17 +9,31 @@
 
 pub use session_id::SessionId;
 
+pub use crate::clock::Clock;
+pub use crate::clock::SystemClock;
+pub use crate::clock::TestClock;
 pub use crate::core::CoreContext;
+pub use crate::id_generator::IdGenerator;
+pub use crate::id_generator::RandomIdGenerator;
+pub use crate::id_generator::SequentialIdGenerator;
 pub use crate::logging::LoggingContainer;
 pub use crate::logging::SamplingKey;
 pub use crate::perf_counters::PerfCounterType;
 pub use crate::perf_counters::PerfCounters;
+pub use crate::session::BlobMemo;
 pub use crate::session::SessionClass;
 pub use crate::session::SessionContainer;
 pub use crate::session::SessionContainerBuilder;
+pub use crate::trace::ActiveSpan;
+pub use crate::trace::TraceContext;
+pub use crate::trace::TraceLevel;
+pub use crate::trace::TraceSpan;
 
+mod clock;
 mod core;
+mod id_generator;
 mod logging;
 mod perf_counters;
 mod perf_counters_stack;
 mod session;
+mod trace;
@@ -17,6 +17,7 @@ use slog::Logger;
 
 use crate::perf_counters::PerfCounters;
 use crate::perf_counters_stack::PerfCountersStack;
+use crate::trace::TraceContext;
 
 /// Used to correlation a high level action on a CoreContext
 /// e.g. walk of a repo,  with low level actions using that context
@@ -46,6 +47,7 @@ pub struct LoggingContainer {
     perf_counters: PerfCountersStack,
     sampling_key: Option<SamplingKey>,
     scribe: Scribe,
+    trace: TraceContext,
 }
 
 impl LoggingContainer {
@@ -56,6 +58,7 @@ impl LoggingContainer {
             perf_counters: Default::default(),
             sampling_key: None,
             scribe: Scribe::new(fb),
+            trace: TraceContext::disabled(),
         }
     }
 
@@ -72,6 +75,7 @@ impl LoggingContainer {
             perf_counters: self.perf_counters.clone(),
             sampling_key: Some(sampling_key),
             scribe: self.scribe.clone(),
+            trace: self.trace.clone(),
         }
     }
 
@@ -82,6 +86,7 @@ impl LoggingContainer {
             perf_counters: self.perf_counters.clone(),
             sampling_key: self.sampling_key.clone(),
             scribe: self.scribe.clone(),
+            trace: self.trace.clone(),
         }
     }
 
@@ -92,6 +97,18 @@ impl LoggingContainer {
             perf_counters: self.perf_counters.clone(),
             sampling_key: self.sampling_key.clone(),
             scribe: self.scribe.clone(),
+            trace: self.trace.clone(),
+        }
+    }
+
+    pub fn clone_with_trace(&self, trace: TraceContext) -> Self {
+        Self {
+            logger: self.logger.clone(),
+            scuba: self.scuba.clone(),
+            perf_counters: self.perf_counters.clone(),
+            sampling_key: self.sampling_key.clone(),
+            scribe: self.scribe.clone(),
+            trace,
         }
     }
 
@@ -120,6 +137,10 @@ impl LoggingContainer {
         &self.scribe
     }
 
+    pub fn trace(&self) -> &TraceContext {
+        &self.trace
+    }
+
     pub fn with_mutated_scuba(
         &self,
         mutator: impl FnOnce(MononokeScubaSampleBuilder) -> MononokeScubaSampleBuilder,
@@ -130,6 +151,7 @@ impl LoggingContainer {
             perf_counters: self.perf_counters.clone(),
             sampling_key: self.sampling_key.clone(),
             scribe: self.scribe.clone(),
+            trace: self.trace.clone(),
         }
     }
 }
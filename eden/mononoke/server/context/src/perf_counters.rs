@@ -56,6 +56,7 @@ define_perf_counters! {
         BlobGetsMaxLatency,
         BlobGetsNotFoundMaxLatency,
         BlobGetsDeduplicated,
+        BlobGetsMemoized,
         BlobGetsTotalSize,
         BlobPresenceChecks,
         BlobPresenceChecksMaxLatency,
@@ -65,6 +66,7 @@ define_perf_counters! {
         BlobPutsMaxLatency,
         BlobPutsDeduplicated,
         BlobPutsTotalSize,
+        BulkheadQueueTimeMs,
         BytesSent,
         CachelibHits,
         CachelibMisses,
@@ -160,6 +162,7 @@ impl PerfCounterType {
             | BlobGetsAccessWait
             | BlobGetsShardAccessWait
             | BlobGetsDeduplicated
+            | BlobGetsMemoized
             | BlobGetsTotalSize
             | BlobPresenceChecks
             | BlobPuts
@@ -167,6 +170,7 @@ impl PerfCounterType {
             | BlobPutsShardAccessWait
             | BlobPutsDeduplicated
             | BlobPutsTotalSize
+            | BulkheadQueueTimeMs
             | BytesSent
             | CachelibHits
             | CachelibMisses
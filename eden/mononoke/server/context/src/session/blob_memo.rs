@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct BlobMemoState {
+    entries: HashMap<String, Box<dyn Any + Send + Sync>>,
+    size: usize,
+}
+
+/// Memo of the blobs fetched during a session, keyed by blobstore key, so
+/// that a request that fetches the same blob from several code paths only
+/// goes to the blobstore once.
+///
+/// The memo does not evict: once it holds the maximum size it is given,
+/// further blobs are not added.  Sessions are short-lived, so the memory is
+/// released when the request completes.
+#[derive(Default)]
+pub struct BlobMemo {
+    state: Mutex<BlobMemoState>,
+}
+
+impl BlobMemo {
+    /// Get a memoized blob, if there is one of the expected type.
+    pub fn get<T: Any + Clone>(&self, key: &str) -> Option<T> {
+        let state = self.state.lock().expect("lock poisoned");
+        state
+            .entries
+            .get(key)
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Memoize a blob of the given size, unless that would take the memo
+    /// over `max_size` bytes.  Returns whether the blob was added.
+    pub fn insert<T: Any + Send + Sync>(
+        &self,
+        key: String,
+        value: T,
+        size: usize,
+        max_size: usize,
+    ) -> bool {
+        let mut state = self.state.lock().expect("lock poisoned");
+        if state.size + size > max_size || state.entries.contains_key(&key) {
+            return false;
+        }
+        state.size += size;
+        state.entries.insert(key, Box::new(value));
+        true
+    }
+
+    /// Total size of the memoized blobs.
+    pub fn size(&self) -> usize {
+        self.state.lock().expect("lock poisoned").size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_blob_memo() {
+        let memo = BlobMemo::default();
+        assert!(memo.insert("a".to_string(), 1u32, 4, 10));
+        assert!(memo.insert("b".to_string(), 2u32, 4, 10));
+        // Over the maximum size.
+        assert!(!memo.insert("c".to_string(), 3u32, 4, 10));
+        assert_eq!(memo.size(), 8);
+
+        assert_eq!(memo.get::<u32>("a"), Some(1));
+        assert_eq!(memo.get::<u32>("c"), None);
+        // Of another type.
+        assert_eq!(memo.get::<u64>("b"), None);
+    }
+}
@@ -10,23 +10,44 @@ use std::sync::Arc;
 
 use async_limiter::AsyncLimiter;
 use fbinit::FacebookInit;
+use feature_flags::FeatureFlags;
+use feature_flags::SessionFeatureFlags;
 use metadata::Metadata;
 use rate_limiting::BoxRateLimiter;
 use ratelimit_meter::algorithms::LeakyBucket;
 use ratelimit_meter::DirectRateLimiter;
 
+use super::BlobMemo;
 use super::SessionClass;
 use super::SessionContainer;
 use super::SessionContainerInner;
+use crate::clock::Clock;
+use crate::clock::SystemClock;
+use crate::id_generator::IdGenerator;
+use crate::id_generator::RandomIdGenerator;
 
 pub struct SessionContainerBuilder {
     fb: FacebookInit,
     inner: SessionContainerInner,
     session_class: SessionClass,
+    feature_flags: Option<(FeatureFlags, Option<String>)>,
+    has_metadata: bool,
 }
 
 impl SessionContainerBuilder {
-    pub fn build(self) -> SessionContainer {
+    pub fn build(mut self) -> SessionContainer {
+        if !self.has_metadata {
+            let session_id = self.inner.id_generator.session_id();
+            self.inner.metadata = Arc::new(Metadata::default().set_session_id(session_id));
+        }
+        if let Some((feature_flags, repo_name)) = self.feature_flags {
+            let metadata = &self.inner.metadata;
+            self.inner.feature_flags = feature_flags.for_session(
+                repo_name,
+                metadata.identities(),
+                &metadata.session_id().to_string(),
+            );
+        }
         SessionContainer {
             fb: self.fb,
             inner: Arc::new(self.inner),
@@ -43,13 +64,35 @@ impl SessionContainerBuilder {
                 blobstore_write_limiter: None,
                 blobstore_read_limiter: None,
                 readonly: false,
+                feature_flags: SessionFeatureFlags::defaults(),
+                blob_memo: BlobMemo::default(),
+                clock: Arc::new(SystemClock),
+                id_generator: Arc::new(RandomIdGenerator),
             },
             session_class: SessionClass::UserWaiting,
+            feature_flags: None,
+            has_metadata: false,
         }
     }
 
+    /// Use this metadata for the session.  Otherwise the session gets
+    /// default metadata with a session id from the session's id generator.
     pub fn metadata(mut self, value: Arc<Metadata>) -> Self {
         self.inner.metadata = value;
+        self.has_metadata = true;
+        self
+    }
+
+    /// Use this clock instead of the system clock, e.g. for tests of expiry.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.inner.clock = clock;
+        self
+    }
+
+    /// Generate session and trace ids with this generator instead of
+    /// randomly, e.g. so that tests can match on them.
+    pub fn id_generator(mut self, id_generator: Arc<dyn IdGenerator>) -> Self {
+        self.inner.id_generator = id_generator;
         self
     }
 
@@ -92,4 +135,11 @@ impl SessionContainerBuilder {
         self.inner.readonly = readonly;
         self
     }
+
+    /// Scope the feature flags of the session to its identities and, if it
+    /// is for a single repo, to `repo_name`.
+    pub fn feature_flags(mut self, feature_flags: FeatureFlags, repo_name: Option<String>) -> Self {
+        self.feature_flags = Some((feature_flags, repo_name));
+        self
+    }
 }
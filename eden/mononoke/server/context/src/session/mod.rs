@@ -6,9 +6,11 @@
  */
 
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use async_limiter::AsyncLimiter;
 use fbinit::FacebookInit;
+use feature_flags::SessionFeatureFlags;
 use metadata::Metadata;
 use permission_checker::MononokeIdentitySetExt;
 use rate_limiting::BoxRateLimiter;
@@ -20,10 +22,14 @@ use scribe_ext::Scribe;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::Logger;
 
+pub use self::blob_memo::BlobMemo;
 pub use self::builder::SessionContainerBuilder;
+use crate::clock::Clock;
 use crate::core::CoreContext;
+use crate::id_generator::IdGenerator;
 use crate::logging::LoggingContainer;
 
+mod blob_memo;
 mod builder;
 
 #[derive(Clone)]
@@ -59,6 +65,10 @@ struct SessionContainerInner {
     // Whether this session is supposed to be readonly, this will cause the right
     // AuthContext to constructed.
     readonly: bool,
+    feature_flags: SessionFeatureFlags,
+    blob_memo: BlobMemo,
+    clock: Arc<dyn Clock>,
+    id_generator: Arc<dyn IdGenerator>,
 }
 
 impl SessionContainer {
@@ -96,6 +106,28 @@ impl SessionContainer {
         &self.inner.metadata
     }
 
+    pub fn feature_flags(&self) -> &SessionFeatureFlags {
+        &self.inner.feature_flags
+    }
+
+    /// Memo of the blobs fetched during this session.
+    pub fn blob_memo(&self) -> &BlobMemo {
+        &self.inner.blob_memo
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.inner.clock
+    }
+
+    /// The current time, according to the session's clock.
+    pub fn now(&self) -> SystemTime {
+        self.inner.clock.now()
+    }
+
+    pub fn id_generator(&self) -> &dyn IdGenerator {
+        &*self.inner.id_generator
+    }
+
     pub fn rate_limiter(&self) -> Option<&(dyn RateLimiter + Send + Sync)> {
         match self.inner.rate_limiter {
             Some(ref rate_limiter) => Some(&**rate_limiter),
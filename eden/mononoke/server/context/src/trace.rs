@@ -0,0 +1,234 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum number of spans kept for a single trace.  Spans beyond this are
+/// counted, but not kept.
+const MAX_TRACE_SPANS: usize = 10_000;
+
+/// A finished span of a trace.
+#[derive(Clone, Debug)]
+pub struct TraceSpan {
+    pub id: u64,
+    /// The span this span is a child of, or `None` if it is a child of the
+    /// traced request itself.
+    pub parent: Option<u64>,
+    pub name: &'static str,
+    /// Start of the span, relative to the start of the trace.
+    pub start: Duration,
+    pub duration: Duration,
+    pub attributes: Vec<(&'static str, String)>,
+}
+
+/// How much of a request is traced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceLevel {
+    /// Nothing is recorded.
+    Off,
+    /// Only spans that are direct children of the request are recorded.
+    TopLevel,
+    /// All spans are recorded.
+    Full,
+}
+
+#[derive(Debug)]
+struct TraceInner {
+    id: String,
+    level: TraceLevel,
+    start: Instant,
+    next_span_id: AtomicU64,
+    spans: Mutex<Vec<TraceSpan>>,
+    dropped_spans: AtomicU64,
+}
+
+/// Trace of the operations performed for a request, such as a wireproto
+/// command.  Only a sample of requests are traced: for the others the trace
+/// is disabled, and starting spans in it does nothing.
+#[derive(Clone, Debug, Default)]
+pub struct TraceContext {
+    inner: Option<Arc<TraceInner>>,
+    parent: Option<u64>,
+}
+
+impl TraceContext {
+    /// Start a new trace with the given id that records all spans.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self::with_level(id, TraceLevel::Full)
+    }
+
+    /// Start a new trace with the given id that records spans up to the
+    /// given level.
+    pub fn with_level(id: impl Into<String>, level: TraceLevel) -> Self {
+        if level == TraceLevel::Off {
+            return Self::disabled();
+        }
+        Self {
+            inner: Some(Arc::new(TraceInner {
+                id: id.into(),
+                level,
+                start: Instant::now(),
+                next_span_id: AtomicU64::new(1),
+                spans: Mutex::new(Vec::new()),
+                dropped_spans: AtomicU64::new(0),
+            })),
+            parent: None,
+        }
+    }
+
+    /// A trace that records nothing.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.inner.as_ref().map(|inner| inner.id.as_str())
+    }
+
+    pub fn level(&self) -> TraceLevel {
+        self.inner
+            .as_ref()
+            .map_or(TraceLevel::Off, |inner| inner.level)
+    }
+
+    /// Start a span that is a child of the current span.  The span is
+    /// recorded when it is finished, unless it is nested deeper than the
+    /// trace's level records.
+    pub fn start_span(&self, name: &'static str) -> ActiveSpan {
+        let trace = match &self.inner {
+            Some(inner) if inner.level == TraceLevel::Full || self.parent.is_none() => Self {
+                inner: Some(inner.clone()),
+                parent: Some(inner.next_span_id.fetch_add(1, Ordering::Relaxed)),
+            },
+            _ => Self::disabled(),
+        };
+        ActiveSpan {
+            parent: self.parent,
+            trace,
+            name,
+            start: Instant::now(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// The spans finished so far.
+    pub fn spans(&self) -> Vec<TraceSpan> {
+        match &self.inner {
+            Some(inner) => inner.spans.lock().expect("lock poisoned").clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The number of spans that were not kept because the trace was full.
+    pub fn dropped_spans(&self) -> u64 {
+        self.inner
+            .as_ref()
+            .map_or(0, |inner| inner.dropped_spans.load(Ordering::Relaxed))
+    }
+}
+
+/// A span that has been started, but not finished.
+#[must_use = "A span is only recorded once it is finished"]
+pub struct ActiveSpan {
+    parent: Option<u64>,
+    trace: TraceContext,
+    name: &'static str,
+    start: Instant,
+    attributes: Vec<(&'static str, String)>,
+}
+
+impl ActiveSpan {
+    /// The trace for operations that are children of this span.
+    pub fn trace(&self) -> &TraceContext {
+        &self.trace
+    }
+
+    pub fn add(&mut self, key: &'static str, value: impl ToString) -> &mut Self {
+        if self.trace.is_enabled() {
+            self.attributes.push((key, value.to_string()));
+        }
+        self
+    }
+
+    pub fn finish(self) {
+        if let (Some(inner), Some(id)) = (self.trace.inner, self.trace.parent) {
+            let mut spans = inner.spans.lock().expect("lock poisoned");
+            if spans.len() >= MAX_TRACE_SPANS {
+                inner.dropped_spans.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            spans.push(TraceSpan {
+                id,
+                parent: self.parent,
+                name: self.name,
+                start: self.start.saturating_duration_since(inner.start),
+                duration: self.start.elapsed(),
+                attributes: self.attributes,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_trace_spans() {
+        let trace = TraceContext::new("trace");
+        let mut outer = trace.start_span("outer");
+        outer.add("key", 1);
+        let inner = outer.trace().start_span("inner");
+        inner.finish();
+        outer.finish();
+
+        let spans = trace.spans();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].name, "inner");
+        assert_eq!(spans[0].parent, Some(spans[1].id));
+        assert_eq!(spans[1].name, "outer");
+        assert_eq!(spans[1].parent, None);
+        assert_eq!(spans[1].attributes, vec![("key", "1".to_string())]);
+
+        let trace = TraceContext::disabled();
+        let mut span = trace.start_span("span");
+        span.add("key", 1);
+        span.finish();
+        assert!(trace.spans().is_empty());
+        assert_eq!(trace.level(), TraceLevel::Off);
+    }
+
+    #[test]
+    fn test_trace_levels() {
+        let trace = TraceContext::with_level("trace", TraceLevel::TopLevel);
+        assert_eq!(trace.level(), TraceLevel::TopLevel);
+        let outer = trace.start_span("outer");
+        let inner = outer.trace().start_span("inner");
+        let innermost = inner.trace().start_span("innermost");
+        innermost.finish();
+        inner.finish();
+        outer.finish();
+
+        let spans = trace.spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "outer");
+
+        let trace = TraceContext::with_level("trace", TraceLevel::Off);
+        assert!(!trace.is_enabled());
+        trace.start_span("span").finish();
+        assert!(trace.spans().is_empty());
+    }
+}
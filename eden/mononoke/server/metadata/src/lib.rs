@@ -7,6 +7,8 @@
 
 #![feature(result_flattening)]
 
+mod preamble;
+
 use std::net::IpAddr;
 use std::time::Duration;
 
@@ -21,6 +23,9 @@ use session_id::SessionId;
 use tokio::time::timeout;
 use trust_dns_resolver::TokioAsyncResolver;
 
+pub use crate::preamble::ClientPreamble;
+pub use crate::preamble::CLIENT_PREAMBLE_VERSION;
+
 #[derive(Clone, Debug, Default)]
 pub struct Metadata {
     session_id: SessionId,
@@ -34,6 +39,15 @@ pub struct Metadata {
     revproxy_region: Option<String>,
     raw_encoded_cats: Option<String>,
     client_info: Option<ClientInfo>,
+    /// Version string reported by the client.
+    client_version: Option<String>,
+    /// Optional features the client reported as enabled.
+    client_features: Vec<String>,
+    /// How far the client's clock is ahead of ours, in seconds.  Negative
+    /// if the client's clock is behind.
+    client_clock_offset_secs: Option<i64>,
+    /// The preamble the client described itself with.
+    client_preamble: Option<ClientPreamble>,
 }
 
 impl Metadata {
@@ -74,6 +88,10 @@ impl Metadata {
             revproxy_region: None,
             raw_encoded_cats: None,
             client_info: None,
+            client_version: None,
+            client_features: Vec::new(),
+            client_clock_offset_secs: None,
+            client_preamble: None,
         }
     }
 
@@ -111,6 +129,32 @@ impl Metadata {
         self
     }
 
+    pub fn add_client_version(&mut self, client_version: String) -> &mut Self {
+        self.client_version = Some(client_version);
+        self
+    }
+
+    pub fn add_client_features(&mut self, client_features: Vec<String>) -> &mut Self {
+        self.client_features = client_features;
+        self
+    }
+
+    pub fn add_client_clock_offset_secs(&mut self, offset: i64) -> &mut Self {
+        self.client_clock_offset_secs = Some(offset);
+        self
+    }
+
+    /// Record the preamble the client sent, along with the client version
+    /// and features it reports.
+    pub fn add_client_preamble(&mut self, client_preamble: ClientPreamble) -> &mut Self {
+        if let Some(client_version) = &client_preamble.client_version {
+            self.client_version = Some(client_version.clone());
+        }
+        self.client_features = client_preamble.features.clone();
+        self.client_preamble = Some(client_preamble);
+        self
+    }
+
     pub fn add_original_identities(&mut self, identities: MononokeIdentitySet) -> &mut Self {
         self.original_identities = Some(identities);
         self
@@ -120,6 +164,11 @@ impl Metadata {
         &self.session_id
     }
 
+    pub fn set_session_id(mut self, session_id: SessionId) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
     pub fn identities(&self) -> &MononokeIdentitySet {
         &self.identities
     }
@@ -163,6 +212,22 @@ impl Metadata {
         self
     }
 
+    pub fn client_version(&self) -> Option<&str> {
+        self.client_version.as_deref()
+    }
+
+    pub fn client_features(&self) -> &[String] {
+        &self.client_features
+    }
+
+    pub fn client_clock_offset_secs(&self) -> Option<i64> {
+        self.client_clock_offset_secs
+    }
+
+    pub fn client_preamble(&self) -> Option<&ClientPreamble> {
+        self.client_preamble.as_ref()
+    }
+
     pub fn unix_name(&self) -> Option<&str> {
         for identity in self.identities() {
             if identity.id_type() == "USER" {
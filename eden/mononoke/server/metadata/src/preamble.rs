@@ -0,0 +1,230 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! The preamble a client sends to describe itself when it connects.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Version of the preamble schema written by this code.  Bump this when
+/// adding fields, so that servers can tell which fields an older client
+/// could not have sent.
+pub const CLIENT_PREAMBLE_VERSION: u32 = 1;
+
+// Keys of the untyped key-value form of the preamble.
+const MISC_CLIENT_VERSION: &str = "client_version";
+const MISC_IDENTITIES: &str = "identities";
+const MISC_FEATURES: &str = "features";
+const MISC_CLIENT_TIME: &str = "client_time";
+const MISC_ENV_PREFIX: &str = "env.";
+
+/// What a client reports about itself when it connects.
+///
+/// The preamble is serialized as JSON.  Fields from newer versions of the
+/// schema that this server doesn't know about are kept in `unknown`, so
+/// that they can be logged and forwarded rather than silently dropped.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientPreamble {
+    /// Version of the schema the client wrote the preamble with.
+    #[serde(default)]
+    pub version: u32,
+    /// Version string of the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_version: Option<String>,
+    /// Identities the client claims, as `TYPE:data`.  These are not
+    /// authenticated, so are only for logging.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identities: Vec<String>,
+    /// Optional features the client has enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Environment variables of the client that are relevant to the server.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env_vars: BTreeMap<String, String>,
+    /// Current time at the client, in seconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_time: Option<i64>,
+    /// Fields this server doesn't know about.
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+impl ClientPreamble {
+    pub fn new() -> Self {
+        Self {
+            version: CLIENT_PREAMBLE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Invalid client preamble")
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Failed to serialize client preamble")
+    }
+
+    /// Convert from the untyped key-value form of the preamble, as sent by
+    /// older clients.  Keys that aren't recognized, and values that can't
+    /// be parsed, are kept in `unknown` as strings.
+    pub fn from_misc(misc: HashMap<String, String>) -> Self {
+        fn split_list(value: &str) -> Vec<String> {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        }
+
+        // Untyped preambles predate versioning.
+        let mut preamble = Self::default();
+        for (key, value) in misc {
+            match key.as_str() {
+                MISC_CLIENT_VERSION => preamble.client_version = Some(value.trim().to_string()),
+                MISC_IDENTITIES => preamble.identities = split_list(&value),
+                MISC_FEATURES => preamble.features = split_list(&value),
+                MISC_CLIENT_TIME => match value.trim().parse() {
+                    Ok(client_time) => preamble.client_time = Some(client_time),
+                    Err(_) => {
+                        preamble
+                            .unknown
+                            .insert(key, serde_json::Value::String(value));
+                    }
+                },
+                _ => match key.strip_prefix(MISC_ENV_PREFIX) {
+                    Some(name) => {
+                        preamble.env_vars.insert(name.to_string(), value);
+                    }
+                    None => {
+                        preamble
+                            .unknown
+                            .insert(key, serde_json::Value::String(value));
+                    }
+                },
+            }
+        }
+        preamble
+    }
+
+    /// Convert to the untyped key-value form of the preamble, for peers
+    /// that don't understand the typed form.  Unknown fields that aren't
+    /// strings are written as JSON.
+    pub fn into_misc(self) -> HashMap<String, String> {
+        let mut misc = HashMap::new();
+        if let Some(client_version) = self.client_version {
+            misc.insert(MISC_CLIENT_VERSION.to_string(), client_version);
+        }
+        if !self.identities.is_empty() {
+            misc.insert(MISC_IDENTITIES.to_string(), self.identities.join(","));
+        }
+        if !self.features.is_empty() {
+            misc.insert(MISC_FEATURES.to_string(), self.features.join(","));
+        }
+        if let Some(client_time) = self.client_time {
+            misc.insert(MISC_CLIENT_TIME.to_string(), client_time.to_string());
+        }
+        for (name, value) in self.env_vars {
+            misc.insert(format!("{}{}", MISC_ENV_PREFIX, name), value);
+        }
+        for (key, value) in self.unknown {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            misc.insert(key, value);
+        }
+        misc
+    }
+
+    /// Names of the fields this server doesn't know about.
+    pub fn unknown_fields(&self) -> impl Iterator<Item = &str> {
+        self.unknown.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip() -> Result<()> {
+        let mut preamble = ClientPreamble::new();
+        preamble.client_version = Some("20221018-123456-abcdef01".to_string());
+        preamble.features = vec!["progress-stream".to_string()];
+        preamble
+            .env_vars
+            .insert("SANDCASTLE".to_string(), "1".to_string());
+        preamble.client_time = Some(1666000000);
+
+        let json = preamble.to_json()?;
+        assert_eq!(ClientPreamble::from_json(&json)?, preamble);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_fields_are_kept() -> Result<()> {
+        let json = r#"{"version":2,"client_version":"1.0","shiny":{"enabled":true}}"#;
+        let preamble = ClientPreamble::from_json(json)?;
+        assert_eq!(preamble.version, 2);
+        assert_eq!(preamble.client_version.as_deref(), Some("1.0"));
+        assert_eq!(preamble.unknown_fields().collect::<Vec<_>>(), vec!["shiny"]);
+
+        // Unknown fields survive being forwarded.
+        let forwarded = ClientPreamble::from_json(&preamble.to_json()?)?;
+        assert_eq!(forwarded, preamble);
+        Ok(())
+    }
+
+    #[test]
+    fn test_misc_roundtrip() {
+        let misc = hashmap! {
+            "client_version".to_string() => "1.0".to_string(),
+            "features".to_string() => "a, b,".to_string(),
+            "client_time".to_string() => "1666000000".to_string(),
+            "env.HGPLAIN".to_string() => "1".to_string(),
+            "something_new".to_string() => "value".to_string(),
+        };
+        let preamble = ClientPreamble::from_misc(misc);
+        assert_eq!(preamble.version, 0);
+        assert_eq!(preamble.client_version.as_deref(), Some("1.0"));
+        assert_eq!(preamble.features, vec!["a", "b"]);
+        assert_eq!(preamble.client_time, Some(1666000000));
+        assert_eq!(
+            preamble.env_vars.get("HGPLAIN").map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(
+            preamble.unknown_fields().collect::<Vec<_>>(),
+            vec!["something_new"]
+        );
+
+        let misc = preamble.clone().into_misc();
+        assert_eq!(misc.get("features").map(String::as_str), Some("a,b"));
+        assert_eq!(ClientPreamble::from_misc(misc), preamble);
+    }
+
+    #[test]
+    fn test_misc_invalid_values_are_kept() {
+        let preamble = ClientPreamble::from_misc(hashmap! {
+            "client_time".to_string() => "yesterday".to_string(),
+        });
+        assert_eq!(preamble.client_time, None);
+        assert_eq!(
+            preamble.unknown.get("client_time"),
+            Some(&serde_json::Value::String("yesterday".to_string()))
+        );
+    }
+}
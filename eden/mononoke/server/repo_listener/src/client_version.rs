@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Policy for clients older than the minimum supported version.
+
+use std::cmp::Ordering;
+
+use metadata::Metadata;
+use tunables::tunables;
+
+pub enum ClientVersionCheck {
+    /// The client is recent enough, or didn't report its version.
+    Supported,
+    /// The client is older than the minimum version.  `message` tells the
+    /// user what to do about it.
+    Outdated { message: String, reject: bool },
+}
+
+/// Check the version the client reported against the configured minimum.
+pub fn check_client_version(metadata: &Metadata) -> ClientVersionCheck {
+    let tunables = tunables();
+    let min_version = tunables.get_repo_client_min_client_version();
+    let version = match metadata.client_version() {
+        Some(version) if !min_version.is_empty() => version,
+        _ => return ClientVersionCheck::Supported,
+    };
+
+    if compare_versions(version, &min_version) != Ordering::Less {
+        return ClientVersionCheck::Supported;
+    }
+
+    let mut message = format!(
+        "Your client version {} is older than the minimum supported version {}.",
+        version, min_version
+    );
+    let instructions = tunables.get_repo_client_upgrade_instructions();
+    if !instructions.is_empty() {
+        message.push(' ');
+        message.push_str(&instructions);
+    }
+
+    ClientVersionCheck::Outdated {
+        message,
+        reject: tunables.get_repo_client_reject_old_versions(),
+    }
+}
+
+/// Compare versions by their numeric components, ignoring any separators.
+/// This orders both dotted versions (`4.4.2`) and date-based versions
+/// (`20221018-123456-abcdef01`) correctly.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn components(version: &str) -> impl Iterator<Item = u64> + '_ {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+    }
+    components(a).cmp(components(b))
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+    use tunables::with_tunables;
+    use tunables::MononokeTunables;
+
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("4.4.2", "4.4.2"), Ordering::Equal);
+        assert_eq!(compare_versions("4.4.2", "4.10.0"), Ordering::Less);
+        assert_eq!(compare_versions("4.4", "4.4.1"), Ordering::Less);
+        assert_eq!(
+            compare_versions("20221018-123456-abcdef01", "20221017-235959-01234567"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_check_client_version() {
+        let tunables = MononokeTunables::default();
+        tunables.update_strings(&hashmap! {
+            "repo_client_min_client_version".to_string() => "4.4.2".to_string(),
+            "repo_client_upgrade_instructions".to_string() => "Please upgrade.".to_string(),
+        });
+        tunables.update_bools(&hashmap! {
+            "repo_client_reject_old_versions".to_string() => true,
+        });
+
+        with_tunables(tunables, || {
+            let mut metadata = Metadata::default();
+            assert!(matches!(
+                check_client_version(&metadata),
+                ClientVersionCheck::Supported
+            ));
+
+            metadata.add_client_version("4.5.0".to_string());
+            assert!(matches!(
+                check_client_version(&metadata),
+                ClientVersionCheck::Supported
+            ));
+
+            metadata.add_client_version("4.4.1".to_string());
+            match check_client_version(&metadata) {
+                ClientVersionCheck::Outdated { message, reject } => {
+                    assert!(reject);
+                    assert_eq!(
+                        message,
+                        "Your client version 4.4.1 is older than the minimum supported version 4.4.2. Please upgrade."
+                    );
+                }
+                ClientVersionCheck::Supported => panic!("4.4.1 should be outdated"),
+            }
+        });
+    }
+}
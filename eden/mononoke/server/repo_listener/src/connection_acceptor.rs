@@ -5,10 +5,17 @@
  * GNU General Public License version 2.
  */
 
+use std::fmt;
 use std::fs::File;
+use std::fs::Permissions;
 use std::io;
 use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
 use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicUsize;
@@ -26,7 +33,9 @@ use connection_security_checker::ConnectionSecurityChecker;
 use edenapi_service::EdenApi;
 use failure_ext::SlogKVError;
 use fbinit::FacebookInit;
+use feature_flags::FeatureFlags;
 use futures::channel::oneshot;
+use futures::future;
 use futures::future::Future;
 use futures::select_biased;
 use futures_01_ext::BoxStream;
@@ -39,6 +48,7 @@ use futures_util::future::AbortHandle;
 use futures_util::future::FutureExt;
 use futures_util::stream::StreamExt;
 use futures_util::stream::TryStreamExt;
+use hgproto::WireProtocol;
 use hostname::get_hostname;
 use hyper::server::conn::Http;
 use lazy_static::lazy_static;
@@ -46,7 +56,6 @@ use metaconfig_types::CommonConfig;
 use metadata::Metadata;
 use mononoke_api::Mononoke;
 use openssl::ssl::Ssl;
-use openssl::ssl::SslAcceptor;
 use permission_checker::AclProvider;
 use permission_checker::MononokeIdentity;
 use permission_checker::MononokeIdentitySet;
@@ -65,11 +74,14 @@ use sshrelay::SshDecoder;
 use sshrelay::SshEncoder;
 use sshrelay::SshMsg;
 use sshrelay::Stdio;
+use sshrelay::CLIENT_FEATURE_PROGRESS_STREAM;
 use stats::prelude::*;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio::net::UnixListener;
+use tokio::net::UnixStream;
 use tokio::task::JoinHandle;
 use tokio_openssl::SslStream;
 use tokio_util::codec::FramedRead;
@@ -77,8 +89,10 @@ use tokio_util::codec::FramedWrite;
 
 use crate::errors::ErrorKind;
 use crate::http_service::MononokeHttpService;
+use crate::identity_provider::IdentityProvider;
 use crate::request_handler::create_conn_logger;
 use crate::request_handler::request_handler;
+use crate::tls::TlsAcceptor;
 use crate::wireproto_sink::WireprotoSink;
 
 define_stats! {
@@ -90,12 +104,26 @@ pub trait MononokeStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static
 
 impl<T> MononokeStream for T where T: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static {}
 
-const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(5000);
+/// Interval between keepalive messages sent to wireproto clients, unless the
+/// client asks for a different one.
+pub(crate) const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(5000);
+/// Bounds on the keepalive interval that clients can ask for.
+pub(crate) const MIN_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+pub(crate) const MAX_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(300);
 const CHUNK_SIZE: usize = 10000;
+/// Mode of the Unix domain socket.  Local services are given access by
+/// running as the server's user or group.
+const UNIX_SOCKET_MODE: u32 = 0o660;
 lazy_static! {
     static ref OPEN_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
 }
 
+/// Number of connections that are currently open, including those that are
+/// still being set up.
+pub(crate) fn open_connections() -> usize {
+    OPEN_CONNECTIONS.load(Ordering::Relaxed)
+}
+
 pub async fn wait_for_connections_closed(logger: &Logger) {
     loop {
         let conns = OPEN_CONNECTIONS.load(Ordering::Relaxed);
@@ -115,9 +143,10 @@ pub async fn connection_acceptor(
     service: ReadyFlagService,
     root_log: Logger,
     mononoke: Arc<Mononoke>,
-    tls_acceptor: SslAcceptor,
+    tls_acceptor: TlsAcceptor,
     terminate_process: oneshot::Receiver<()>,
     rate_limiter: Option<RateLimitEnvironment>,
+    feature_flags: FeatureFlags,
     scribe: Scribe,
     edenapi: EdenApi,
     will_exit: Arc<AtomicBool>,
@@ -125,7 +154,9 @@ pub async fn connection_acceptor(
     cslb_config: Option<String>,
     wireproto_scuba: MononokeScubaSampleBuilder,
     bound_addr_path: Option<PathBuf>,
+    unix_socket_path: Option<PathBuf>,
     acl_provider: &dyn AclProvider,
+    identity_provider: Arc<dyn IdentityProvider>,
     readonly: bool,
 ) -> Result<()> {
     let enable_http_control_api = common_config.enable_http_control_api;
@@ -138,6 +169,11 @@ pub async fn connection_acceptor(
         .await
         .with_context(|| format!("could not bind mononoke on '{}'", sockname))?;
 
+    let unix_listener = unix_socket_path
+        .as_deref()
+        .map(bind_unix_socket)
+        .transpose()?;
+
     let mut terminate_process = terminate_process.fuse();
 
     let qps = match cslb_config {
@@ -166,6 +202,7 @@ pub async fn connection_acceptor(
         mononoke,
         security_checker,
         rate_limiter,
+        feature_flags,
         scribe,
         logger: root_log.clone(),
         edenapi,
@@ -176,6 +213,7 @@ pub async fn connection_acceptor(
         qps,
         wireproto_scuba,
         common_config,
+        identity_provider,
         readonly,
     });
 
@@ -187,7 +225,7 @@ pub async fn connection_acceptor(
             },
             sock_tuple = listener.accept().fuse() => match sock_tuple {
                 Ok((stream, addr)) => {
-                    let conn = PendingConnection { acceptor: acceptor.clone(), addr };
+                    let conn = PendingConnection { acceptor: acceptor.clone(), addr: PeerAddr::Tcp(addr) };
                     let task = handle_connection(conn.clone(), stream);
                     conn.spawn_task(task, "Failed to handle_connection");
                 }
@@ -195,17 +233,63 @@ pub async fn connection_acceptor(
                     error!(root_log, "{}", err.to_string(); SlogKVError(Error::from(err)));
                 }
             },
+            sock = accept_unix(unix_listener.as_ref()).fuse() => match sock {
+                Ok(stream) => match stream.peer_cred() {
+                    Ok(cred) => {
+                        let uid = cred.uid();
+                        let conn = PendingConnection { acceptor: acceptor.clone(), addr: PeerAddr::Unix { uid } };
+                        let task = handle_unix_connection(conn.clone(), stream, uid);
+                        conn.spawn_task(task, "Failed to handle_unix_connection");
+                    }
+                    Err(err) => {
+                        error!(root_log, "Failed to get peer credentials"; SlogKVError(Error::from(err)));
+                    }
+                },
+                Err(err) => {
+                    error!(root_log, "{}", err.to_string(); SlogKVError(Error::from(err)));
+                }
+            },
         };
     }
 }
 
+/// Bind the Unix domain socket at `path`.  A socket left behind by a
+/// previous server is removed first, but any other kind of file at `path` is
+/// an error rather than something we delete.
+fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("could not remove old socket '{}'", path.display()))?,
+        Ok(_) => {
+            return Err(ErrorKind::NotASocket(path.to_path_buf()).into());
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(err).with_context(|| format!("could not stat '{}'", path.display()));
+        }
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("could not bind mononoke on '{}'", path.display()))?;
+    std::fs::set_permissions(path, Permissions::from_mode(UNIX_SOCKET_MODE))
+        .with_context(|| format!("could not set permissions on '{}'", path.display()))?;
+    Ok(listener)
+}
+
+async fn accept_unix(listener: Option<&UnixListener>) -> io::Result<UnixStream> {
+    match listener {
+        Some(listener) => listener.accept().await.map(|(stream, _)| stream),
+        None => future::pending().await,
+    }
+}
+
 /// Our environment for accepting connections.
 pub struct Acceptor {
     pub fb: FacebookInit,
-    pub tls_acceptor: SslAcceptor,
+    pub tls_acceptor: TlsAcceptor,
     pub mononoke: Arc<Mononoke>,
     pub security_checker: ConnectionSecurityChecker,
     pub rate_limiter: Option<RateLimitEnvironment>,
+    pub feature_flags: FeatureFlags,
     pub scribe: Scribe,
     pub logger: Logger,
     pub edenapi: EdenApi,
@@ -216,14 +300,54 @@ pub struct Acceptor {
     pub qps: Option<Arc<Qps>>,
     pub wireproto_scuba: MononokeScubaSampleBuilder,
     pub common_config: CommonConfig,
+    pub identity_provider: Arc<dyn IdentityProvider>,
     pub readonly: bool,
 }
 
+/// Where a peer connected to us from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    /// Connected over the Unix domain socket by a process running as `uid`.
+    Unix {
+        uid: u32,
+    },
+}
+
+impl PeerAddr {
+    /// The IP address of the peer, if it connected over the network.
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            PeerAddr::Tcp(addr) => Some(addr.ip()),
+            PeerAddr::Unix { .. } => None,
+        }
+    }
+
+    /// Address to hand to HTTP handlers, which require a socket address.
+    /// Unix domain socket peers have none, so they get the unspecified
+    /// address rather than one that could be mistaken for a real client.
+    pub fn socket_addr(&self) -> SocketAddr {
+        match self {
+            PeerAddr::Tcp(addr) => *addr,
+            PeerAddr::Unix { .. } => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        }
+    }
+}
+
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddr::Tcp(addr) => write!(f, "{}", addr),
+            PeerAddr::Unix { uid } => write!(f, "unix:uid={}", uid),
+        }
+    }
+}
+
 /// Details for a socket we've just opened.
 #[derive(Clone)]
 pub struct PendingConnection {
     pub acceptor: Arc<Acceptor>,
-    pub addr: SocketAddr,
+    pub addr: PeerAddr,
 }
 
 /// A connection where we completed the initial TLS handshake.
@@ -264,7 +388,8 @@ impl PendingConnection {
 }
 
 async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<()> {
-    let ssl = Ssl::new(conn.acceptor.tls_acceptor.context()).context("Error creating Ssl")?;
+    let tls_acceptor = conn.acceptor.tls_acceptor.load_full();
+    let ssl = Ssl::new(tls_acceptor.context()).context("Error creating Ssl")?;
     let ssl_socket = SslStream::new(ssl, sock).context("Error creating SslStream")?;
     let mut ssl_socket = Box::pin(ssl_socket);
 
@@ -300,6 +425,32 @@ async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<(
     Ok(())
 }
 
+/// Handle a connection over the Unix domain socket.  These aren't encrypted,
+/// and the client is identified by the user id of the connecting process,
+/// which the kernel vouches for.
+async fn handle_unix_connection(conn: PendingConnection, sock: UnixStream, uid: u32) -> Result<()> {
+    let mut identities = MononokeIdentitySet::new();
+    identities.insert(MononokeIdentity::new("UNIX_UID", uid.to_string()));
+
+    let is_trusted = conn
+        .acceptor
+        .security_checker
+        .check_if_trusted(&identities)
+        .await;
+
+    let conn = AcceptedConnection {
+        pending: conn,
+        is_trusted,
+        identities: Arc::new(identities),
+    };
+
+    handle_http(conn, sock)
+        .await
+        .context("Failed to handle_http")?;
+
+    Ok(())
+}
+
 async fn handle_http<S: MononokeStream>(conn: AcceptedConnection, stream: S) -> Result<()> {
     STATS::http_accepted.add_value(1);
 
@@ -321,6 +472,8 @@ pub async fn handle_wireproto<R, W>(
     framed: FramedConn<R, W>,
     reponame: String,
     metadata: Metadata,
+    keep_alive_interval: Duration,
+    wire_protocol: WireProtocol,
 ) -> Result<()>
 where
     R: AsyncRead + Send + std::marker::Unpin + 'static,
@@ -332,10 +485,11 @@ where
         stdin,
         stdout,
         stderr,
+        progress,
         logger,
         keep_alive,
         join_handle,
-    } = ChannelConn::setup(framed, conn.clone(), metadata.clone());
+    } = ChannelConn::setup(framed, conn.clone(), metadata.clone(), keep_alive_interval);
 
     if metadata.client_debug() {
         info!(&logger, "{:#?}", metadata; "remote" => "true");
@@ -350,6 +504,7 @@ where
         stdin,
         stdout,
         stderr,
+        progress,
     };
 
     // Don't immediately return error here, we need to cleanup our
@@ -360,7 +515,9 @@ where
         Arc::clone(&conn.pending.acceptor.mononoke),
         &conn.pending.acceptor.security_checker,
         stdio,
+        wire_protocol,
         conn.pending.acceptor.rate_limiter.clone(),
+        conn.pending.acceptor.feature_flags.clone(),
         conn.pending.acceptor.scribe.clone(),
         conn.pending.acceptor.qps.clone(),
         conn.pending.acceptor.readonly,
@@ -401,6 +558,7 @@ pub struct ChannelConn {
     stdin: BoxStream<Bytes, io::Error>,
     stdout: mpsc::Sender<Bytes>,
     stderr: mpsc::UnboundedSender<Bytes>,
+    progress: mpsc::UnboundedSender<Bytes>,
     logger: Logger,
     keep_alive: AbortHandle,
     join_handle: JoinHandle<Result<(), io::Error>>,
@@ -411,6 +569,7 @@ impl ChannelConn {
         framed: FramedConn<R, W>,
         conn: AcceptedConnection,
         metadata: Arc<Metadata>,
+        keep_alive_interval: Duration,
     ) -> Self
     where
         R: AsyncRead + Send + std::marker::Unpin + 'static,
@@ -426,9 +585,22 @@ impl ChannelConn {
             }
         }));
 
-        let (stdout, stderr, keep_alive, join_handle) = {
+        // Clients that don't know about the progress stream get progress
+        // messages mixed into stderr, as they always have.
+        let progress_stream = if metadata
+            .client_features()
+            .iter()
+            .any(|f| f == CLIENT_FEATURE_PROGRESS_STREAM)
+        {
+            IoStream::Progress
+        } else {
+            IoStream::Stderr
+        };
+
+        let (stdout, stderr, progress, keep_alive, join_handle) = {
             let (otx, orx) = mpsc::channel(1);
             let (etx, erx) = mpsc::unbounded();
+            let (ptx, prx) = mpsc::unbounded();
             let (ktx, krx) = mpsc::unbounded();
 
             let orx = orx
@@ -439,6 +611,10 @@ impl ChannelConn {
                 .map(|blob| split_bytes_in_chunk(blob, CHUNK_SIZE))
                 .flatten()
                 .map(|v| SshMsg::new(IoStream::Stderr, v));
+            let prx = prx
+                .map(|blob| split_bytes_in_chunk(blob, CHUNK_SIZE))
+                .flatten()
+                .map(move |v| SshMsg::new(progress_stream.clone(), v));
             let krx = krx.map(|v| SshMsg::new(IoStream::Stderr, v));
 
             // Glue them together
@@ -449,6 +625,7 @@ impl ChannelConn {
 
                 let res = orx
                     .select(erx)
+                    .select(prx)
                     .select(krx)
                     .compat()
                     .map_err(|()| io::Error::new(io::ErrorKind::Other, "huh?"))
@@ -477,6 +654,8 @@ impl ChannelConn {
                     scuba.add("stdout_messages", data.stdout.messages);
                     scuba.add("stderr_bytes", data.stderr.bytes);
                     scuba.add("stderr_messages", data.stderr.messages);
+                    scuba.add("progress_bytes", data.progress.bytes);
+                    scuba.add("progress_messages", data.progress.messages);
                     scuba.log_with_msg("Forwarding failed", format!("{:#}", e));
                 }
 
@@ -485,7 +664,7 @@ impl ChannelConn {
 
             let keep_alive_sender = async move {
                 loop {
-                    tokio::time::sleep(KEEP_ALIVE_INTERVAL).await;
+                    tokio::time::sleep(keep_alive_interval).await;
                     if ktx.unbounded_send(Bytes::new()).is_err() {
                         break;
                     }
@@ -512,15 +691,16 @@ impl ChannelConn {
             // this thread goes do some expensive CPU-bound work, we won't delay keepalives.
             tokio::spawn(async {});
 
-            (otx, etx, keep_alive_abort, join_handle)
+            (otx, etx, ptx, keep_alive_abort, join_handle)
         };
 
-        let logger = create_conn_logger(stderr.clone(), None, None);
+        let logger = create_conn_logger(stderr.clone(), progress.clone(), None, None);
 
         ChannelConn {
             stdin,
             stdout,
             stderr,
+            progress,
             logger,
             keep_alive,
             join_handle,
@@ -541,3 +721,45 @@ fn split_bytes_in_chunk<E>(blob: Bytes, chunksize: usize) -> impl Stream<Item =
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_unix_socket() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("mononoke.sock");
+
+        // A socket left behind by a previous server is replaced.
+        drop(std::os::unix::net::UnixListener::bind(&path)?);
+        let _listener = bind_unix_socket(&path)?;
+        let metadata = std::fs::symlink_metadata(&path)?;
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, UNIX_SOCKET_MODE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_socket_keeps_other_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("mononoke.sock");
+        std::fs::write(&path, "not a socket")?;
+
+        assert!(bind_unix_socket(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, "not a socket");
+        Ok(())
+    }
+
+    #[test]
+    fn test_peer_addr() {
+        let tcp = PeerAddr::Tcp("10.0.0.1:443".parse().unwrap());
+        assert_eq!(tcp.ip(), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(tcp.to_string(), "10.0.0.1:443");
+
+        let unix = PeerAddr::Unix { uid: 1000 };
+        assert_eq!(unix.ip(), None);
+        assert_eq!(unix.to_string(), "unix:uid=1000");
+        assert!(unix.socket_addr().ip().is_unspecified());
+    }
+}
@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::path::PathBuf;
+
 use mononoke_types::RepositoryId;
 use thiserror::Error;
 
@@ -14,6 +16,10 @@ pub enum ErrorKind {
     ConnectionNoClientCertificate,
     #[error("Unauthorized access, permission denied")]
     AuthorizationFailed,
+    #[error("{0}")]
+    ClientTooOld(String),
     #[error("Large repo not found: {0}")]
     LargeRepoNotFound(RepositoryId),
+    #[error("'{}' exists and is not a socket", .0.display())]
+    NotASocket(PathBuf),
 }
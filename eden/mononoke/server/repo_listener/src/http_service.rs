@@ -5,11 +5,15 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::marker::PhantomData;
 use std::str::FromStr;
 use std::sync::atomic::Ordering;
 use std::task;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -22,6 +26,7 @@ use clientinfo::CLIENT_INFO_HEADER;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
 use gotham_ext::socket_data::TlsSocketData;
+use hgproto::WireProtocol;
 use http::HeaderMap;
 use http::HeaderValue;
 use http::Method;
@@ -30,8 +35,10 @@ use http::Response;
 use http::Uri;
 use hyper::service::Service;
 use hyper::Body;
+use metadata::ClientPreamble;
 use metadata::Metadata;
 use percent_encoding::percent_decode;
+use permission_checker::MononokeIdentitySet;
 use qps::Qps;
 use session_id::generate_session_id;
 use sha1::Digest;
@@ -50,13 +57,25 @@ use crate::connection_acceptor::AcceptedConnection;
 use crate::connection_acceptor::Acceptor;
 use crate::connection_acceptor::FramedConn;
 use crate::connection_acceptor::MononokeStream;
+use crate::connection_acceptor::DEFAULT_KEEP_ALIVE_INTERVAL;
+use crate::connection_acceptor::MAX_KEEP_ALIVE_INTERVAL;
+use crate::connection_acceptor::MIN_KEEP_ALIVE_INTERVAL;
+use crate::identity_provider::ConnectionCredentials;
+use crate::identity_provider::IdentityProvider;
 
 const HEADER_CLIENT_COMPRESSION: &str = "x-client-compression";
 const HEADER_CLIENT_DEBUG: &str = "x-client-debug";
+const HEADER_CLIENT_KEEPALIVE: &str = "x-client-keepalive";
+const HEADER_CLIENT_VERSION: &str = "x-client-version";
+const HEADER_CLIENT_FEATURES: &str = "x-client-features";
+const HEADER_CLIENT_TIME: &str = "x-client-time";
+const HEADER_CLIENT_PREAMBLE: &str = "x-client-preamble";
+const HEADER_CLIENT_WIRE_PROTOCOL: &str = "x-client-wire-protocol";
 const HEADER_WEBSOCKET_KEY: &str = "sec-websocket-key";
 const HEADER_WEBSOCKET_ACCEPT: &str = "sec-websocket-accept";
 const HEADER_MONONOKE_ENCODING: &str = "x-mononoke-encoding";
 const HEADER_MONONOKE_HOST: &str = "x-mononoke-host";
+const HEADER_MONONOKE_KEEPALIVE: &str = "x-mononoke-keepalive";
 const HEADER_REVPROXY_REGION: &str = "x-fb-revproxy-region";
 
 // See https://tools.ietf.org/html/rfc6455#section-1.3
@@ -229,10 +248,11 @@ where
             .header(http::header::UPGRADE, "websocket")
             .header(HEADER_WEBSOCKET_ACCEPT, websocket_key);
 
-        let metadata = h2m::try_convert_headers_to_metadata(&self.conn, req.headers())
+        let mut metadata = h2m::try_convert_headers_to_metadata(&self.conn, req.headers())
             .await
             .context("Invalid metadata")
             .map_err(HttpError::BadRequest)?;
+        metadata_populate_client_details(&mut metadata, req.headers());
 
         let zstd_level: i32 = tunables::tunables()
             .get_zstd_compression_level()
@@ -257,6 +277,30 @@ where
             _ => {}
         };
 
+        // Clients behind NATs or firewalls with short idle timeouts can ask
+        // for keepalives to be sent more often, so that their connection
+        // isn't dropped while the server computes a response.
+        let keep_alive_interval = match req.headers().get(HEADER_CLIENT_KEEPALIVE) {
+            Some(header_value) => {
+                let interval = parse_keep_alive_interval(header_value.as_bytes())
+                    .map_err(HttpError::BadRequest)?;
+                builder = builder.header(HEADER_MONONOKE_KEEPALIVE, interval.as_secs());
+                interval
+            }
+            None => DEFAULT_KEEP_ALIVE_INTERVAL,
+        };
+
+        // Clients on transports that carry messages rather than a byte
+        // stream can ask for requests and responses to be framed.
+        let wire_protocol = match req.headers().get(HEADER_CLIENT_WIRE_PROTOCOL) {
+            Some(header_value) => header_value
+                .to_str()
+                .map_err(Error::from)
+                .and_then(WireProtocol::from_str)
+                .map_err(HttpError::BadRequest)?,
+            None => WireProtocol::Ssh,
+        };
+
         let res = builder.body(Body::empty()).map_err(HttpError::internal)?;
 
         let this = self.clone();
@@ -297,9 +341,16 @@ where
 
             let framed = FramedConn::setup(rx, tx, compression)?;
 
-            connection_acceptor::handle_wireproto(this.conn, framed, reponame, metadata)
-                .await
-                .context("Failed to handle_wireproto")?;
+            connection_acceptor::handle_wireproto(
+                this.conn,
+                framed,
+                reponame,
+                metadata,
+                keep_alive_interval,
+                wire_protocol,
+            )
+            .await
+            .context("Failed to handle_wireproto")?;
 
             Result::<_, Error>::Ok(())
         };
@@ -387,7 +438,7 @@ where
             .acceptor()
             .edenapi
             .clone()
-            .into_service(self.conn.pending.addr, Some(tls_socket_data))
+            .into_service(self.conn.pending.addr.socket_addr(), Some(tls_socket_data))
             .call_gotham(req)
             .await;
 
@@ -462,6 +513,57 @@ where
     }
 }
 
+/// Record what the client told us about itself.  Newer clients send a
+/// typed preamble as JSON; older clients send individual headers, which are
+/// converted to a preamble.  These are informational, so malformed values
+/// are ignored rather than failing the request.
+fn metadata_populate_client_details(metadata: &mut Metadata, headers: &HeaderMap<HeaderValue>) {
+    let header_str = |name| headers.get(name).and_then(|h| h.to_str().ok());
+
+    let preamble = header_str(HEADER_CLIENT_PREAMBLE)
+        .and_then(|json| ClientPreamble::from_json(json).ok())
+        .unwrap_or_else(|| {
+            let misc: HashMap<_, _> = [
+                ("client_version", HEADER_CLIENT_VERSION),
+                ("features", HEADER_CLIENT_FEATURES),
+                ("client_time", HEADER_CLIENT_TIME),
+            ]
+            .into_iter()
+            .filter_map(|(key, name)| Some((key.to_string(), header_str(name)?.to_string())))
+            .collect();
+            ClientPreamble::from_misc(misc)
+        });
+
+    // The client sends its current time in seconds since the epoch, so we
+    // can tell how far its clock is from ours.  Times too far off to
+    // compare are ignored.
+    if let Some(client_time) = preamble.client_time {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        if let Some(offset) = client_time.checked_sub(now) {
+            metadata.add_client_clock_offset_secs(offset);
+        }
+    }
+
+    metadata.add_client_preamble(preamble);
+}
+
+/// Parse the keepalive interval requested by the client, in seconds, and
+/// clamp it to the range the server permits.
+fn parse_keep_alive_interval(header_value: &[u8]) -> Result<Duration> {
+    let secs = std::str::from_utf8(header_value)
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "'{}' is not a recognized keepalive interval",
+                String::from_utf8_lossy(header_value),
+            )
+        })?;
+    Ok(Duration::from_secs(secs).clamp(MIN_KEEP_ALIVE_INTERVAL, MAX_KEEP_ALIVE_INTERVAL))
+}
+
 // See https://tools.ietf.org/html/rfc6455#section-1.3
 fn calculate_websocket_accept(headers: &HeaderMap<HeaderValue>) -> String {
     let mut sha1 = Sha1::new();
@@ -478,6 +580,25 @@ fn calculate_websocket_accept(headers: &HeaderMap<HeaderValue>) -> String {
     base64::encode(&hash)
 }
 
+/// Extract the client's identities using the server's identity provider.
+async fn connection_identities(
+    conn: &AcceptedConnection,
+    headers: &HeaderMap<HeaderValue>,
+) -> Result<MononokeIdentitySet> {
+    let credentials = ConnectionCredentials {
+        peer_identities: &conn.identities,
+        is_trusted: conn.is_trusted,
+        headers,
+    };
+    Ok(conn
+        .pending
+        .acceptor
+        .identity_provider
+        .identities(&credentials)
+        .await?
+        .unwrap_or_default())
+}
+
 #[cfg(not(fbcode_build))]
 mod h2m {
     use super::*;
@@ -487,12 +608,13 @@ mod h2m {
         headers: &HeaderMap<HeaderValue>,
     ) -> Result<Metadata> {
         let debug = headers.contains_key(HEADER_CLIENT_DEBUG);
+        let identities = connection_identities(conn, headers).await?;
 
         Ok(Metadata::new(
             Some(&generate_session_id().to_string()),
-            (*conn.identities).clone(),
+            identities,
             debug,
-            Some(conn.pending.addr.ip()),
+            conn.pending.addr.ip(),
         )
         .await)
     }
@@ -591,15 +713,45 @@ mod h2m {
         }
 
         let mut identities = cats_identities.unwrap_or_default();
-        identities.extend(conn.identities.iter().cloned());
+        identities.extend(connection_identities(conn, headers).await?);
 
         // Generic fallback
         Ok(Metadata::new(
             Some(&generate_session_id().to_string()),
             identities,
             debug,
-            Some(conn.pending.addr.ip()),
+            conn.pending.addr.ip(),
         )
         .await)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_keep_alive_interval() {
+        assert_eq!(
+            parse_keep_alive_interval(b"30").unwrap(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            parse_keep_alive_interval(b" 10 ").unwrap(),
+            Duration::from_secs(10)
+        );
+        // Intervals outside the permitted range are clamped.
+        assert_eq!(
+            parse_keep_alive_interval(b"0").unwrap(),
+            MIN_KEEP_ALIVE_INTERVAL
+        );
+        assert_eq!(
+            parse_keep_alive_interval(b"86400").unwrap(),
+            MAX_KEEP_ALIVE_INTERVAL
+        );
+        assert!(parse_keep_alive_interval(b"").is_err());
+        assert!(parse_keep_alive_interval(b"-5").is_err());
+        assert!(parse_keep_alive_interval(b"5s").is_err());
+        assert!(parse_keep_alive_interval(b"\xff").is_err());
+    }
+}
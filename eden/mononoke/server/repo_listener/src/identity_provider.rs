@@ -0,0 +1,360 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Extraction of client identities from the credentials a client presents
+//! when it opens a wireproto connection.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use permission_checker::MononokeIdentity;
+use permission_checker::MononokeIdentitySet;
+
+/// Header in which trusted proxies forward the identities of the client
+/// they authenticated, as a comma-separated list of `TYPE:data`.
+pub const DEFAULT_FORWARDED_IDENTITIES_HEADER: &str = "x-mononoke-forwarded-identities";
+
+/// The credentials a client presented when opening a connection.
+pub struct ConnectionCredentials<'a> {
+    /// Identities of the connecting peer, from its TLS certificate or the
+    /// credentials of the Unix socket.
+    pub peer_identities: &'a MononokeIdentitySet,
+    /// Whether the connecting peer is a trusted proxy.
+    pub is_trusted: bool,
+    /// Headers of the request that opened the connection.
+    pub headers: &'a HeaderMap<HeaderValue>,
+}
+
+/// Extracts the identities of a client from its credentials.
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// Extract the client's identities.  Returns `None` if the client did
+    /// not present the kind of credentials this provider handles, and an
+    /// error if it did but they are not valid.
+    async fn identities(
+        &self,
+        credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>>;
+}
+
+/// Tries each provider in turn, using the identities from the first one
+/// that finds credentials it handles.
+pub struct IdentityProviderChain {
+    providers: Vec<Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviderChain {
+    pub fn new(providers: Vec<Arc<dyn IdentityProvider>>) -> Self {
+        Self { providers }
+    }
+}
+
+impl Default for IdentityProviderChain {
+    /// Identities forwarded by a trusted proxy, otherwise the identities of
+    /// the connecting peer.
+    fn default() -> Self {
+        Self::new(vec![
+            Arc::new(ForwardedIdentityProvider::default()),
+            Arc::new(MtlsIdentityProvider),
+        ])
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for IdentityProviderChain {
+    async fn identities(
+        &self,
+        credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>> {
+        for provider in self.providers.iter() {
+            if let Some(identities) = provider.identities(credentials).await? {
+                return Ok(Some(identities));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Identities forwarded by a trusted proxy, such as an ssh relay, that has
+/// already authenticated the client.  The header is ignored for peers that
+/// are not trusted proxies.
+pub struct ForwardedIdentityProvider {
+    header: HeaderName,
+}
+
+impl ForwardedIdentityProvider {
+    pub fn new(header: HeaderName) -> Self {
+        Self { header }
+    }
+}
+
+impl Default for ForwardedIdentityProvider {
+    fn default() -> Self {
+        Self::new(HeaderName::from_static(DEFAULT_FORWARDED_IDENTITIES_HEADER))
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for ForwardedIdentityProvider {
+    async fn identities(
+        &self,
+        credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>> {
+        if !credentials.is_trusted {
+            return Ok(None);
+        }
+        let value = match credentials.headers.get(&self.header) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let identities = value
+            .to_str()
+            .context("Invalid forwarded identities")?
+            .split(',')
+            .map(|identity| identity.trim().parse::<MononokeIdentity>())
+            .collect::<Result<MononokeIdentitySet>>()
+            .context("Invalid forwarded identities")?;
+        Ok(Some(identities))
+    }
+}
+
+/// Identities of the connecting peer, taken from its TLS client
+/// certificate, or from the credentials of a Unix socket connection.
+pub struct MtlsIdentityProvider;
+
+#[async_trait]
+impl IdentityProvider for MtlsIdentityProvider {
+    async fn identities(
+        &self,
+        credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>> {
+        if credentials.peer_identities.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(credentials.peer_identities.clone()))
+    }
+}
+
+/// Validates OAuth bearer tokens, e.g. by introspecting them with the
+/// authorization server that issued them.
+#[async_trait]
+pub trait OAuthTokenValidator: Send + Sync {
+    /// Return the identities the token was issued to, or an error if the
+    /// token is not valid.
+    async fn validate(&self, token: &str) -> Result<MononokeIdentitySet>;
+}
+
+/// Identities from an OAuth bearer token in the `Authorization` header.
+pub struct OAuthTokenIdentityProvider {
+    validator: Arc<dyn OAuthTokenValidator>,
+}
+
+impl OAuthTokenIdentityProvider {
+    pub fn new(validator: Arc<dyn OAuthTokenValidator>) -> Self {
+        Self { validator }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for OAuthTokenIdentityProvider {
+    async fn identities(
+        &self,
+        credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>> {
+        let value = match credentials.headers.get(AUTHORIZATION) {
+            Some(value) => value.to_str().context("Invalid Authorization header")?,
+            None => return Ok(None),
+        };
+        let token = match value.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+            _ => return Ok(None),
+        };
+        let identities = self
+            .validator
+            .validate(token)
+            .await
+            .context("Invalid OAuth token")?;
+        Ok(Some(identities))
+    }
+}
+
+/// Always returns the same identities, whatever the client presented.  This
+/// is intended for tests.
+pub struct StaticIdentityProvider {
+    identities: MononokeIdentitySet,
+}
+
+impl StaticIdentityProvider {
+    pub fn new(identities: MononokeIdentitySet) -> Self {
+        Self { identities }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for StaticIdentityProvider {
+    async fn identities(
+        &self,
+        _credentials: &ConnectionCredentials<'_>,
+    ) -> Result<Option<MononokeIdentitySet>> {
+        Ok(Some(self.identities.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use maplit::btreeset;
+
+    use super::*;
+
+    fn identities(ids: &[(&str, &str)]) -> MononokeIdentitySet {
+        ids.iter()
+            .map(|(id_type, id_data)| MononokeIdentity::new(*id_type, *id_data))
+            .collect()
+    }
+
+    fn header_map(pairs: &[(HeaderName, &str)]) -> HeaderMap<HeaderValue> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    fn untrusted<'a>(
+        peer_identities: &'a MononokeIdentitySet,
+        headers: &'a HeaderMap<HeaderValue>,
+    ) -> ConnectionCredentials<'a> {
+        ConnectionCredentials {
+            peer_identities,
+            is_trusted: false,
+            headers,
+        }
+    }
+
+    struct TestValidator;
+
+    #[async_trait]
+    impl OAuthTokenValidator for TestValidator {
+        async fn validate(&self, token: &str) -> Result<MononokeIdentitySet> {
+            match token {
+                "good" => Ok(identities(&[("USER", "oauth")])),
+                _ => Err(anyhow!("unknown token")),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_identities() -> Result<()> {
+        let peer = identities(&[("SERVICE", "proxy")]);
+        let headers = header_map(&[(
+            HeaderName::from_static(DEFAULT_FORWARDED_IDENTITIES_HEADER),
+            "USER:alice, MACHINE:devvm",
+        )]);
+        let provider = ForwardedIdentityProvider::default();
+
+        let trusted = ConnectionCredentials {
+            peer_identities: &peer,
+            is_trusted: true,
+            headers: &headers,
+        };
+        assert_eq!(
+            provider.identities(&trusted).await?,
+            Some(identities(&[("USER", "alice"), ("MACHINE", "devvm")]))
+        );
+
+        // Only trusted proxies can forward identities.
+        let untrusted = ConnectionCredentials {
+            is_trusted: false,
+            ..trusted
+        };
+        assert_eq!(provider.identities(&untrusted).await?, None);
+
+        let invalid = header_map(&[(
+            HeaderName::from_static(DEFAULT_FORWARDED_IDENTITIES_HEADER),
+            "alice",
+        )]);
+        let invalid = ConnectionCredentials {
+            peer_identities: &peer,
+            is_trusted: true,
+            headers: &invalid,
+        };
+        assert!(provider.identities(&invalid).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth_identities() -> Result<()> {
+        let peer = MononokeIdentitySet::new();
+        let provider = OAuthTokenIdentityProvider::new(Arc::new(TestValidator));
+
+        let good = header_map(&[(AUTHORIZATION, "Bearer good")]);
+        assert_eq!(
+            provider.identities(&untrusted(&peer, &good)).await?,
+            Some(identities(&[("USER", "oauth")]))
+        );
+        let bad = header_map(&[(AUTHORIZATION, "bearer bad")]);
+        assert!(provider.identities(&untrusted(&peer, &bad)).await.is_err());
+        // Other authorization schemes are left to other providers.
+        let basic = header_map(&[(AUTHORIZATION, "Basic dXNlcjpwYXNz")]);
+        assert_eq!(provider.identities(&untrusted(&peer, &basic)).await?, None);
+        let none = HeaderMap::new();
+        assert_eq!(provider.identities(&untrusted(&peer, &none)).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_chain() -> Result<()> {
+        let peer = identities(&[("SERVICE", "proxy")]);
+        let forwarded = header_map(&[(
+            HeaderName::from_static(DEFAULT_FORWARDED_IDENTITIES_HEADER),
+            "USER:alice",
+        )]);
+        let none = HeaderMap::new();
+        let chain = IdentityProviderChain::default();
+
+        // Forwarded identities take precedence over the peer's.
+        let credentials = ConnectionCredentials {
+            peer_identities: &peer,
+            is_trusted: true,
+            headers: &forwarded,
+        };
+        assert_eq!(
+            chain.identities(&credentials).await?,
+            Some(identities(&[("USER", "alice")]))
+        );
+
+        let credentials = ConnectionCredentials {
+            peer_identities: &peer,
+            is_trusted: true,
+            headers: &none,
+        };
+        assert_eq!(chain.identities(&credentials).await?, Some(peer.clone()));
+
+        let empty = MononokeIdentitySet::new();
+        let credentials = ConnectionCredentials {
+            peer_identities: &empty,
+            is_trusted: false,
+            headers: &none,
+        };
+        assert_eq!(chain.identities(&credentials).await?, None);
+
+        let chain = IdentityProviderChain::new(vec![Arc::new(StaticIdentityProvider::new(
+            btreeset! {MononokeIdentity::new("USER", "static")},
+        ))]);
+        assert_eq!(
+            chain.identities(&credentials).await?,
+            Some(identities(&[("USER", "static")]))
+        );
+        Ok(())
+    }
+}
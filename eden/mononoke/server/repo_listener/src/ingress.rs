@@ -0,0 +1,251 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Accounting and shaping of the bytes a client sends us.
+//!
+//! Every chunk read from the client counts towards the session's
+//! `IngressBytes` load, which is what rate limits on ingress are enforced
+//! against.  Once a session has sent more than a threshold, further reads
+//! are shaped by a token bucket.  Only uploads (in practice, unbundles of
+//! large pushes) get that far, so ordinary requests are never delayed, but a
+//! handful of huge pushes can't saturate the network interface and starve
+//! every other client of the host.
+
+use std::io;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use context::SessionContainer;
+use futures::compat::Stream01CompatExt;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use futures_01_ext::BoxStream;
+use futures_old::Stream;
+use rate_limiting::Metric;
+use tunables::tunables;
+
+/// A token bucket, refilled at `bytes_per_sec` up to `burst_bytes`.
+struct TokenBucket {
+    bytes_per_sec: f64,
+    burst_bytes: f64,
+    available: f64,
+    last_refill: SystemTime,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_sec: u64, burst_bytes: u64, now: SystemTime) -> Self {
+        // The bucket must be able to hold at least a second's worth of
+        // bytes, otherwise it would never refill enough for a large chunk.
+        let burst_bytes = burst_bytes.max(bytes_per_sec) as f64;
+        TokenBucket {
+            bytes_per_sec: bytes_per_sec as f64,
+            burst_bytes,
+            available: burst_bytes,
+            last_refill: now,
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long the caller must wait
+    /// before they would have been available.  The bucket may go into debt,
+    /// so a chunk larger than the burst size is delayed rather than stuck.
+    fn take(&mut self, bytes: u64, now: SystemTime) -> Option<Duration> {
+        let elapsed = now.duration_since(self.last_refill).unwrap_or_default();
+        self.last_refill = now;
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.bytes_per_sec).min(self.burst_bytes);
+        self.available -= bytes as f64;
+        if self.available >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                -self.available / self.bytes_per_sec,
+            ))
+        }
+    }
+}
+
+/// Shaping settings for a session, read from tunables when it starts.
+struct IngressShaping {
+    bytes_per_sec: u64,
+    burst_bytes: u64,
+    threshold_bytes: u64,
+}
+
+impl IngressShaping {
+    fn from_tunables() -> Option<Self> {
+        let bytes_per_sec = tunables().get_ingress_shaping_bytes_per_sec();
+        if bytes_per_sec <= 0 {
+            return None;
+        }
+        Some(IngressShaping {
+            bytes_per_sec: bytes_per_sec as u64,
+            burst_bytes: tunables().get_ingress_shaping_burst_bytes().max(0) as u64,
+            threshold_bytes: tunables().get_ingress_shaping_threshold_bytes().max(0) as u64,
+        })
+    }
+}
+
+/// Wrap a client's stdin so that the bytes read from it are accounted to
+/// the session and added to `total`, and shaped if shaping is enabled.
+pub fn ingress_stream(
+    stdin: BoxStream<Bytes, io::Error>,
+    session: SessionContainer,
+    total: Arc<AtomicU64>,
+) -> BoxStream<Bytes, io::Error> {
+    let clock_session = session.clone();
+    let stdin = stdin.inspect(move |bytes| {
+        session.bump_load(Metric::IngressBytes, bytes.len() as f64);
+        total.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    });
+
+    let shaping = match IngressShaping::from_tunables() {
+        Some(shaping) => shaping,
+        None => return Box::new(stdin),
+    };
+
+    let mut received = 0;
+    let mut bucket = None;
+    let shaped = stdin.compat().and_then(move |bytes| {
+        received += bytes.len() as u64;
+        let delay = if received > shaping.threshold_bytes {
+            // Ask the session for the time, so that tests can control how
+            // the bucket refills.
+            let now = clock_session.now();
+            bucket
+                .get_or_insert_with(|| {
+                    TokenBucket::new(shaping.bytes_per_sec, shaping.burst_bytes, now)
+                })
+                .take(bytes.len() as u64, now)
+        } else {
+            None
+        };
+        async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(bytes)
+        }
+    });
+
+    Box::new(shaped.boxed().compat())
+}
+
+#[cfg(test)]
+mod test {
+    use context::TestClock;
+    use fbinit::FacebookInit;
+    use futures_old::stream::iter_ok;
+    use maplit::hashmap;
+    use tunables::with_tunables;
+    use tunables::MononokeTunables;
+
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_token_bucket_burst() {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut bucket = TokenBucket::new(100, 1000, start);
+
+        // A full bucket lets a burst through straight away.
+        assert_eq!(bucket.take(600, start), None);
+        assert_eq!(bucket.take(400, start), None);
+        // After that, bytes are delayed until they would have been refilled.
+        assert_eq!(bucket.take(50, start), Some(SECOND / 2));
+        // Chunks larger than the burst put the bucket into debt.
+        let mut bucket = TokenBucket::new(100, 1000, start);
+        assert_eq!(bucket.take(1500, start), Some(5 * SECOND));
+
+        // The burst is at least a second's worth of bytes.
+        let mut bucket = TokenBucket::new(100, 10, start);
+        assert_eq!(bucket.take(100, start), None);
+        assert_eq!(bucket.take(1, start), Some(SECOND / 100));
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let clock = TestClock::at_unix_secs(0);
+        let mut bucket = TokenBucket::new(100, 1000, clock.now());
+        assert_eq!(bucket.take(1000, clock.now()), None);
+
+        // The bucket refills at the configured rate.
+        clock.advance(5 * SECOND);
+        assert_eq!(bucket.take(500, clock.now()), None);
+        assert_eq!(bucket.take(100, clock.now()), Some(SECOND));
+
+        // Debt is paid off before the bucket fills up again.
+        clock.advance(3 * SECOND);
+        assert_eq!(bucket.take(200, clock.now()), None);
+
+        // But it never holds more than the burst.
+        clock.advance(100 * SECOND);
+        assert_eq!(bucket.take(1000, clock.now()), None);
+        assert_eq!(bucket.take(10, clock.now()), Some(SECOND / 10));
+
+        // A clock that goes backwards doesn't refill anything.
+        clock.set(SystemTime::UNIX_EPOCH);
+        assert_eq!(bucket.take(10, clock.now()), Some(SECOND / 5));
+    }
+
+    async fn read_all(
+        fb: FacebookInit,
+        tunables: MononokeTunables,
+        chunks: Vec<usize>,
+    ) -> (Duration, u64) {
+        let session = SessionContainer::builder(fb)
+            .clock(Arc::new(TestClock::at_unix_secs(0)))
+            .build();
+        let total = Arc::new(AtomicU64::new(0));
+        let stdin =
+            iter_ok::<_, io::Error>(chunks.into_iter().map(|len| Bytes::from(vec![0; len])));
+        let stream = with_tunables(tunables, || {
+            ingress_stream(Box::new(stdin), session, total.clone())
+        });
+
+        let start = tokio::time::Instant::now();
+        let read = stream
+            .compat()
+            .map_ok(|bytes| bytes.len())
+            .try_collect::<Vec<_>>()
+            .await
+            .expect("reading stdin failed");
+        assert_eq!(
+            read.into_iter().sum::<usize>() as u64,
+            total.load(Ordering::Relaxed)
+        );
+        (start.elapsed(), total.load(Ordering::Relaxed))
+    }
+
+    #[fbinit::test]
+    async fn test_ingress_shaping(fb: FacebookInit) {
+        tokio::time::pause();
+
+        // Without shaping, nothing is delayed.
+        let (elapsed, total) =
+            read_all(fb, MononokeTunables::default(), vec![1000, 1000, 1000]).await;
+        assert_eq!(elapsed, Duration::ZERO);
+        assert_eq!(total, 3000);
+
+        let tunables = MononokeTunables::default();
+        tunables.update_ints(&hashmap! {
+            "ingress_shaping_bytes_per_sec".to_string() => 1000,
+            "ingress_shaping_burst_bytes".to_string() => 1000,
+            "ingress_shaping_threshold_bytes".to_string() => 1000,
+        });
+        // The first 1000 bytes are under the threshold, the next 1000 use up
+        // the burst, and the rest has to wait for the bucket to refill.  The
+        // session's clock doesn't move, so that only happens by waiting.
+        let (elapsed, total) = read_all(fb, tunables, vec![1000, 1000, 500, 1500]).await;
+        assert_eq!(elapsed, 2 * SECOND + SECOND / 2);
+        assert_eq!(total, 4000);
+    }
+}
@@ -9,12 +9,16 @@
 #![feature(never_type)]
 #![recursion_limit = "256"]
 
+mod client_version;
 mod connection_acceptor;
 mod errors;
 mod http_service;
+mod identity_provider;
+mod ingress;
 mod netspeedtest;
 mod repo_handlers;
 mod request_handler;
+mod tls;
 mod wireproto_sink;
 
 use std::path::PathBuf;
@@ -26,10 +30,10 @@ use anyhow::Result;
 use cached_config::ConfigStore;
 use cmdlib::monitoring::ReadyFlagService;
 use fbinit::FacebookInit;
+use feature_flags::FeatureFlags;
 use futures::channel::oneshot;
 use metaconfig_types::CommonConfig;
 use mononoke_api::Mononoke;
-use openssl::ssl::SslAcceptor;
 use permission_checker::AclProvider;
 use rate_limiting::RateLimitEnvironment;
 use scribe_ext::Scribe;
@@ -39,8 +43,21 @@ use slog::Logger;
 
 use crate::connection_acceptor::connection_acceptor;
 pub use crate::connection_acceptor::wait_for_connections_closed;
+pub use crate::identity_provider::ConnectionCredentials;
+pub use crate::identity_provider::ForwardedIdentityProvider;
+pub use crate::identity_provider::IdentityProvider;
+pub use crate::identity_provider::IdentityProviderChain;
+pub use crate::identity_provider::MtlsIdentityProvider;
+pub use crate::identity_provider::OAuthTokenIdentityProvider;
+pub use crate::identity_provider::OAuthTokenValidator;
+pub use crate::identity_provider::StaticIdentityProvider;
+pub use crate::identity_provider::DEFAULT_FORWARDED_IDENTITIES_HEADER;
+pub use crate::tls::tls_acceptor;
+pub use crate::tls::BuildTlsAcceptor;
+pub use crate::tls::TlsAcceptor;
 
 const CONFIGERATOR_RATE_LIMITING_CONFIG: &str = "scm/mononoke/ratelimiting/ratelimits";
+const CONFIGERATOR_FEATURE_FLAGS_CONFIG: &str = "scm/mononoke/feature_flags/server";
 
 pub async fn create_repo_listeners<'a>(
     fb: FacebookInit,
@@ -48,7 +65,7 @@ pub async fn create_repo_listeners<'a>(
     mononoke: Arc<Mononoke>,
     root_log: Logger,
     sockname: String,
-    tls_acceptor: SslAcceptor,
+    tls_acceptor: TlsAcceptor,
     service: ReadyFlagService,
     terminate_process: oneshot::Receiver<()>,
     config_store: &'a ConfigStore,
@@ -57,7 +74,9 @@ pub async fn create_repo_listeners<'a>(
     will_exit: Arc<AtomicBool>,
     cslb_config: Option<String>,
     bound_addr_file: Option<PathBuf>,
+    unix_socket_path: Option<PathBuf>,
     acl_provider: &dyn AclProvider,
+    identity_provider: Arc<dyn IdentityProvider>,
     readonly: bool,
 ) -> Result<()> {
     let rate_limiter = {
@@ -73,6 +92,11 @@ pub async fn create_repo_listeners<'a>(
         })
     };
 
+    // Servers without the config run with the default flags.
+    let feature_flags =
+        FeatureFlags::from_config_store(config_store, CONFIGERATOR_FEATURE_FLAGS_CONFIG)
+            .unwrap_or_default();
+
     let edenapi = {
         let mut scuba = scuba.clone();
         scuba.add("service", "edenapi");
@@ -102,6 +126,7 @@ pub async fn create_repo_listeners<'a>(
         tls_acceptor,
         terminate_process,
         rate_limiter,
+        feature_flags,
         scribe,
         edenapi,
         will_exit,
@@ -113,7 +138,9 @@ pub async fn create_repo_listeners<'a>(
             scuba
         },
         bound_addr_file,
+        unix_socket_path,
         acl_provider,
+        identity_provider,
         readonly,
     )
     .await
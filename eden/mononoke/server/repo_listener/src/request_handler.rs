@@ -5,6 +5,8 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -12,30 +14,37 @@ use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
+use batched_stats::BatchedHistogram;
+use batched_stats::BatchedTimeseries;
 use bytes::Bytes;
 use connection_security_checker::ConnectionSecurityChecker;
 use context::LoggingContainer;
+use context::PerfCounterType;
 use context::SessionContainer;
 use context::SessionId;
 use failure_ext::SlogKVError;
 use fbinit::FacebookInit;
+use feature_flags::FeatureFlags;
 use futures::compat::Future01CompatExt;
 use futures_old::sync::mpsc;
 use futures_old::Future;
 use futures_old::Stream;
 use futures_stats::TimedFutureExt;
-use hgproto::sshproto;
 use hgproto::HgProtoHandler;
+use hgproto::WireProtocol;
 use maplit::hashmap;
 use maplit::hashset;
+use metadata::Metadata;
 use mononoke_api::Mononoke;
 use qps::Qps;
 use rate_limiting::Metric;
 use rate_limiting::RateLimitEnvironment;
 use repo_client::RepoClient;
+use repo_client::StaticListKeys;
 use scribe_ext::Scribe;
 use slog::error;
 use slog::o;
+use slog::warn;
 use slog::Drain;
 use slog::Level;
 use slog::Logger;
@@ -45,8 +54,14 @@ use sshrelay::SenderBytesWrite;
 use sshrelay::Stdio;
 use stats::prelude::*;
 use time_ext::DurationExt;
+use usage_attribution::Usage;
+use usage_attribution::UsageAttributionArc;
 
+use crate::client_version::check_client_version;
+use crate::client_version::ClientVersionCheck;
+use crate::connection_acceptor::open_connections;
 use crate::errors::ErrorKind;
+use crate::ingress::ingress_stream;
 use crate::repo_handlers::repo_handler;
 use crate::repo_handlers::RepoHandler;
 
@@ -65,7 +80,9 @@ pub async fn request_handler(
     mononoke: Arc<Mononoke>,
     _security_checker: &ConnectionSecurityChecker,
     stdio: Stdio,
+    wire_protocol: WireProtocol,
     rate_limiter: Option<RateLimitEnvironment>,
+    feature_flags: FeatureFlags,
     scribe: Scribe,
     qps: Option<Arc<Qps>>,
     readonly: bool,
@@ -74,13 +91,14 @@ pub async fn request_handler(
         stdin,
         stdout,
         stderr,
+        progress,
         metadata,
     } = stdio;
 
     let session_id = metadata.session_id();
 
     // We don't have a repository yet, so create without server drain
-    let conn_log = create_conn_logger(stderr.clone(), None, Some(session_id));
+    let conn_log = create_conn_logger(stderr.clone(), progress.clone(), None, Some(session_id));
 
     let handler = repo_handler(mononoke, &reponame).with_context(|| {
         error!(
@@ -102,18 +120,39 @@ pub async fn request_handler(
     } = handler;
 
     // Upgrade log to include server drain
-    let conn_log = create_conn_logger(stderr.clone(), Some(logger), Some(session_id));
+    let conn_log = create_conn_logger(
+        stderr.clone(),
+        progress.clone(),
+        Some(logger),
+        Some(session_id),
+    );
 
     scuba = scuba.with_seq("seq");
-    scuba.add("repo", reponame);
+    scuba.add("repo", reponame.as_str());
     scuba.add_metadata(&metadata);
     scuba.sample_for_identities(metadata.identities());
+    if let (Some(rate_limiter), Some(hostname)) = (&rate_limiter, metadata.client_hostname()) {
+        if let Some(host_class) = rate_limiter.host_class(hostname) {
+            scuba.add("client_host_class", host_class);
+        }
+    }
 
     let rate_limiter = rate_limiter.map(|r| r.get_rate_limiter());
     if let Some(ref rate_limiter) = rate_limiter {
         if let Err(err) = rate_limiter.check_load_shed(metadata.identities()) {
-            scuba.log_with_msg("Request rejected due to load shedding", format!("{}", err));
-            error!(conn_log, "Request rejected due to load shedding: {}", err; "remote" => "true");
+            // Tell the client how long to back off for based on how loaded
+            // the server is, so rejected clients don't all come straight back.
+            let notice = err.notice_under_load(open_connections(), Some(metadata.identities()));
+            scuba
+                .clone()
+                .add("rate_limit", notice.limit.as_str())
+                .add("rate_limit_retry_after_secs", notice.retry_after.as_secs())
+                .log_with_msg("Request rejected due to load shedding", format!("{}", err));
+            error!(
+                conn_log,
+                "Request rejected due to load shedding: {}", notice;
+                "remote" => "true"
+            );
 
             return Err(err.into());
         }
@@ -133,6 +172,26 @@ pub async fn request_handler(
         return Err(err);
     }
 
+    match check_client_version(&metadata) {
+        ClientVersionCheck::Supported => {}
+        ClientVersionCheck::Outdated {
+            message,
+            reject: false,
+        } => {
+            scuba.log_with_msg("Outdated client", message.clone());
+            warn!(conn_log, "{}", message; "remote" => "true");
+        }
+        ClientVersionCheck::Outdated {
+            message,
+            reject: true,
+        } => {
+            scuba.log_with_msg("Request rejected for outdated client", message.clone());
+            error!(conn_log, "{}", message; "remote" => "true");
+
+            return Err(ErrorKind::ClientTooOld(message).into());
+        }
+    }
+
     // Info per wireproto command within this session
     let wireproto_calls = Arc::new(Mutex::new(Vec::new()));
 
@@ -141,14 +200,18 @@ pub async fn request_handler(
     let session_builder = SessionContainer::builder(fb)
         .metadata(metadata.clone())
         .readonly(readonly)
-        .rate_limiter(rate_limiter);
+        .rate_limiter(rate_limiter)
+        .feature_flags(feature_flags, Some(reponame.clone()));
 
     let session = session_builder.build();
+    let usage_ctx = session.new_context(conn_log.clone(), scuba.clone());
+    let usage_attribution = repo.usage_attribution_arc();
 
     let mut logging = LoggingContainer::new(fb, conn_log.clone(), scuba.clone());
     logging.with_scribe(scribe);
 
-    let repo_client = RepoClient::new(
+    let listkeys_namespaces = repo_client_knobs.listkeys_namespaces.clone();
+    let mut repo_client = RepoClient::new(
         repo,
         session.clone(),
         logging,
@@ -156,15 +219,21 @@ pub async fn request_handler(
         repo_client_knobs,
         maybe_backup_repo_source,
     );
+    for (namespace, keys) in listkeys_namespaces {
+        repo_client.register_listkeys_provider(namespace, Arc::new(StaticListKeys::new(keys)))?;
+    }
     let request_perf_counters = repo_client.request_perf_counters();
 
+    let ingress_bytes = Arc::new(AtomicU64::new(0));
+    let stdin = ingress_stream(stdin, session.clone(), ingress_bytes.clone());
+    let egress_bytes = Arc::new(AtomicU64::new(0));
+
     // Construct a hg protocol handler
-    let proto_handler = HgProtoHandler::new(
+    let proto_handler = HgProtoHandler::with_protocol(
+        wire_protocol,
         conn_log.clone(),
         stdin.map(|b| bytes_old::Bytes::from(b.as_ref())),
         repo_client,
-        sshproto::HgSshCommandDecode,
-        sshproto::HgSshCommandEncode,
         wireproto_calls.clone(),
         qps.clone(),
         metadata.revproxy_region().clone(),
@@ -172,7 +241,13 @@ pub async fn request_handler(
 
     // send responses back
     let endres = proto_handler
-        .inspect(move |bytes| session.bump_load(Metric::EgressBytes, bytes.len() as f64))
+        .inspect({
+            let egress_bytes = egress_bytes.clone();
+            move |bytes| {
+                session.bump_load(Metric::EgressBytes, bytes.len() as f64);
+                egress_bytes.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+        })
         .map_err(Error::from)
         .map(|b| Bytes::copy_from_slice(b.as_ref()))
         .forward(stdout)
@@ -186,34 +261,56 @@ pub async fn request_handler(
         std::mem::take(&mut *wireproto_calls)
     };
 
-    STATS::wireproto_ms.add_value(stats.completion_time.as_millis_unchecked() as i64);
+    STATS::wireproto_ms.add_value_batched(stats.completion_time.as_millis_unchecked() as i64);
+
+    let usage = Usage {
+        sessions: 1,
+        commands: wireproto_calls.len() as u64,
+        bytes_served: egress_bytes.load(Ordering::Relaxed),
+        manifests_fetched: (request_perf_counters
+            .get_counter(PerfCounterType::GettreepackNumTreepacks)
+            + request_perf_counters.get_counter(PerfCounterType::GetbundleNumManifests))
+        .max(0) as u64,
+    };
+    if let Err(err) = usage_attribution
+        .record(&usage_ctx, &usage_identity(&metadata), usage)
+        .await
+    {
+        warn!(conn_log, "Failed to record usage: {:#}", err);
+    }
 
     let mut scuba = scuba.clone();
 
     scuba
         .add_future_stats(&stats)
-        .add("wireproto_commands", wireproto_calls);
+        .add("wireproto_commands", wireproto_calls)
+        .add("ingress_bytes", ingress_bytes.load(Ordering::Relaxed));
+
+    let feature_flags = usage_ctx.feature_flags().decisions();
+    if !feature_flags.is_empty() {
+        scuba.add("feature_flags", feature_flags);
+    }
 
     // Populate stats no matter what to avoid dead detectors firing.
-    STATS::request_success.add_value(0);
-    STATS::request_failure.add_value(0);
+    STATS::request_success.add_value_batched(0);
+    STATS::request_failure.add_value_batched(0);
 
     // Log request level perf counters
     request_perf_counters.insert_perf_counters(&mut scuba);
 
     match &result {
         Ok(_) => {
-            STATS::request_success.add_value(1);
-            STATS::request_outcome_permille.add_value(1000);
+            STATS::request_success.add_value_batched(1);
+            STATS::request_outcome_permille.add_value_batched(1000);
             scuba.log_with_msg("Request finished - Success", None)
         }
         Err(err) => {
             if err.is::<mpsc::SendError<Bytes>>() {
-                STATS::request_outcome_permille.add_value(0);
+                STATS::request_outcome_permille.add_value_batched(0);
                 scuba.log_with_msg("Request finished - Client Disconnected", format!("{}", err));
             } else {
-                STATS::request_failure.add_value(1);
-                STATS::request_outcome_permille.add_value(0);
+                STATS::request_failure.add_value_batched(1);
+                STATS::request_outcome_permille.add_value_batched(0);
                 scuba.log_with_msg("Request finished - Failure", format!("{:#?}", err));
             }
         }
@@ -229,8 +326,29 @@ pub async fn request_handler(
     Ok(())
 }
 
+/// The identity that a session's usage is attributed to: the user, if
+/// known, otherwise the service making the request.
+fn usage_identity(metadata: &Metadata) -> String {
+    if let Some(unix_name) = metadata.unix_name() {
+        return format!("user:{}", unix_name);
+    }
+    metadata
+        .identities()
+        .iter()
+        .find(|identity| identity.id_type() == "SERVICE_IDENTITY")
+        .map_or_else(
+            || "unknown".to_string(),
+            |identity| format!("service:{}", identity.id_data()),
+        )
+}
+
+/// Create the logger for a connection.  Records tagged with "remote" =>
+/// "true" or "remote_only" are sent to the client's stderr, and records
+/// tagged with "remote" => "progress" are sent to the client's progress
+/// stream.
 pub fn create_conn_logger(
     stderr: mpsc::UnboundedSender<Bytes>,
+    progress: mpsc::UnboundedSender<Bytes>,
     server_logger: Option<Logger>,
     session_id: Option<&SessionId>,
 ) -> Logger {
@@ -250,6 +368,17 @@ pub fn create_conn_logger(
         .into(),
     );
 
+    let progress_write = SenderBytesWrite { chan: progress };
+    let progress_drain = slog_term::PlainSyncDecorator::new(progress_write);
+    let progress_drain = SimpleFormatWithError::new(progress_drain);
+    let progress_drain = KVFilter::new(progress_drain, Level::Critical).only_pass_any_on_all_keys(
+        (hashmap! {
+            "remote".into() => hashset!["progress".into()],
+        })
+        .into(),
+    );
+    let client_drain = slog::Duplicate::new(client_drain, progress_drain);
+
     if let Some(logger) = server_logger {
         let server_drain = KVFilter::new(logger, Level::Critical).always_suppress_any(
             (hashmap! {
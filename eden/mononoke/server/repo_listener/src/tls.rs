@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Reloading of the TLS acceptor used to terminate client connections.
+//!
+//! Certificates are rotated on disk by deployment tooling.  Rather than
+//! needing a restart to pick up the new ones, the listener can watch the
+//! certificate, key and CA files and rebuild its acceptor when any of them
+//! changes.  Connections that are already established keep the acceptor they
+//! were accepted with; new connections use the latest one.  If the new files
+//! can't be loaded (e.g. because they were caught half-written), the previous
+//! acceptor is kept and the reload is retried on the next check.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use openssl::ssl::SslAcceptor;
+use reloader::Loader;
+use reloader::Reloader;
+
+/// The TLS acceptor for new connections.
+pub type TlsAcceptor = Reloader<SslAcceptor>;
+
+/// Builds an acceptor from the files on disk.
+pub type BuildTlsAcceptor = Box<dyn Fn() -> Result<SslAcceptor> + Send + Sync>;
+
+struct TlsAcceptorLoader {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    build: BuildTlsAcceptor,
+}
+
+impl TlsAcceptorLoader {
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.paths
+            .iter()
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Loader<SslAcceptor> for TlsAcceptorLoader {
+    async fn load(&mut self) -> Result<Option<SslAcceptor>> {
+        // Record the times before building, so that a change made while the
+        // acceptor is being built is picked up by the next check.
+        let modified = self.modified_times();
+        let acceptor = (self.build)()?;
+        self.modified = modified;
+        Ok(Some(acceptor))
+    }
+
+    async fn needs_reload(&mut self) -> Result<bool> {
+        Ok(self.modified_times() != self.modified)
+    }
+}
+
+/// Create the TLS acceptor.  If `reload_interval` is given, the files in
+/// `paths` are checked for changes at that interval, and the acceptor is
+/// rebuilt with `build` whenever they have changed.
+pub async fn tls_acceptor(
+    ctx: CoreContext,
+    reload_interval: Option<Duration>,
+    paths: Vec<PathBuf>,
+    build: BuildTlsAcceptor,
+) -> Result<TlsAcceptor> {
+    match reload_interval {
+        None => Ok(Reloader::fixed(build()?)),
+        Some(interval) => {
+            let loader = TlsAcceptorLoader {
+                modified: Vec::new(),
+                paths,
+                build,
+            };
+            Reloader::reload_periodically(ctx, move || interval, loader).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use anyhow::bail;
+    use fbinit::FacebookInit;
+    use filetime::FileTime;
+    use openssl::ssl::SslMethod;
+
+    use super::*;
+
+    /// Write `contents` to `path`, with a distinct modification time so that
+    /// the change is noticed regardless of the filesystem's resolution.
+    fn write(path: &Path, contents: &str, mtime: i64) -> Result<()> {
+        fs::write(path, contents)?;
+        filetime::set_file_mtime(path, FileTime::from_unix_time(mtime, 0))?;
+        Ok(())
+    }
+
+    /// Builds acceptors as long as `path` contains "good", and counts how
+    /// many it has built.
+    fn build(path: PathBuf, builds: Arc<AtomicUsize>) -> BuildTlsAcceptor {
+        Box::new(move || {
+            if fs::read_to_string(&path)? != "good" {
+                bail!("invalid certificate");
+            }
+            builds.fetch_add(1, Ordering::Relaxed);
+            Ok(SslAcceptor::mozilla_intermediate(SslMethod::tls())?.build())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_loader_reloads_on_change() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cert.pem");
+        let builds = Arc::new(AtomicUsize::new(0));
+        write(&path, "good", 1000)?;
+
+        let mut loader = TlsAcceptorLoader {
+            paths: vec![path.clone()],
+            modified: Vec::new(),
+            build: build(path.clone(), builds.clone()),
+        };
+        assert!(loader.load().await?.is_some());
+        assert_eq!(builds.load(Ordering::Relaxed), 1);
+        assert!(!loader.needs_reload().await?);
+
+        // A rotated certificate is picked up.
+        write(&path, "good", 2000)?;
+        assert!(loader.needs_reload().await?);
+        assert!(loader.load().await?.is_some());
+        assert_eq!(builds.load(Ordering::Relaxed), 2);
+        assert!(!loader.needs_reload().await?);
+
+        // A certificate that can't be loaded is retried on the next check.
+        write(&path, "bad", 3000)?;
+        assert!(loader.needs_reload().await?);
+        assert!(loader.load().await.is_err());
+        assert!(loader.needs_reload().await?);
+
+        // As is one that has gone missing.
+        fs::remove_file(&path)?;
+        assert!(loader.needs_reload().await?);
+        assert!(loader.load().await.is_err());
+        assert_eq!(builds.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_failed_reload_keeps_acceptor(fb: FacebookInit) -> Result<()> {
+        tokio::time::pause();
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cert.pem");
+        let builds = Arc::new(AtomicUsize::new(0));
+        write(&path, "good", 1000)?;
+
+        let acceptor = tls_acceptor(
+            CoreContext::test_mock(fb),
+            Some(Duration::from_millis(10)),
+            vec![path.clone()],
+            build(path.clone(), builds.clone()),
+        )
+        .await?;
+        let first = acceptor.load_full();
+
+        write(&path, "bad", 2000)?;
+        tokio::time::advance(Duration::from_millis(11)).await;
+        acceptor.wait_for_update().await;
+        assert!(Arc::ptr_eq(&first, &acceptor.load_full()));
+
+        write(&path, "good", 3000)?;
+        tokio::time::advance(Duration::from_millis(11)).await;
+        acceptor.wait_for_update().await;
+        assert!(!Arc::ptr_eq(&first, &acceptor.load_full()));
+        assert_eq!(builds.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+}
@@ -73,6 +73,7 @@ pub struct WireprotoSinkData {
     pub last_failed_io: Option<DateTime<Utc>>,
     pub stdout: ChannelData,
     pub stderr: ChannelData,
+    pub progress: ChannelData,
 }
 
 impl WireprotoSinkData {
@@ -83,6 +84,7 @@ impl WireprotoSinkData {
             last_failed_io: None,
             stdout: ChannelData::default(),
             stderr: ChannelData::default(),
+            progress: ChannelData::default(),
         }
     }
 
@@ -90,6 +92,7 @@ impl WireprotoSinkData {
         match item.stream_ref() {
             IoStream::Stdout => self.stdout.peek(item.as_ref()),
             IoStream::Stderr => self.stderr.peek(item.as_ref()),
+            IoStream::Progress => self.progress.peek(item.as_ref()),
             IoStream::Stdin => {}
         }
     }
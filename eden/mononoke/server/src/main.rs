@@ -11,6 +11,7 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::bail;
 use anyhow::Context;
@@ -50,6 +51,8 @@ use slog::o;
 use slog::Logger;
 
 const SM_CLEANUP_TIMEOUT_SECS: u64 = 120;
+/// How often the resources held for each repo are reported.
+const RESOURCE_USAGE_REPORT_INTERVAL: Duration = Duration::from_secs(10);
 
 /// Mononoke Server
 #[derive(Parser)]
@@ -64,6 +67,10 @@ struct MononokeServerArgs {
     /// Path for file in which to write the bound tcp address in rust std::net::SocketAddr format
     #[clap(long)]
     bound_address_file: Option<PathBuf>,
+    /// Also listen on a Unix domain socket at this path, for clients on the
+    /// same host. These are identified by the user id of the client process
+    #[clap(long)]
+    listening_unix_socket: Option<PathBuf>,
     /// If provided the thrift server will start on this port
     #[clap(long, short = 'p')]
     thrift_port: Option<String>,
@@ -85,6 +92,10 @@ struct MononokeServerArgs {
     /// Path to a file with encryption keys for SSL tickets
     #[clap(long)]
     ssl_ticket_seeds: Option<String>,
+    /// If set, check the certificate, private key and CA files for changes
+    /// this often, and use the new ones for new connections without restarting
+    #[clap(long)]
+    tls_reload_interval_secs: Option<u64>,
     /// Top level Mononoke tier where CSLB publishes routing table
     #[clap(long)]
     cslb_config: Option<String>,
@@ -262,23 +273,42 @@ fn main(fb: FacebookInit) -> Result<()> {
     let configs = app.repo_configs();
 
     let acceptor = {
-        let mut builder = secure_utils::SslConfig::new(
-            args.ca_pem,
-            args.cert,
-            args.private_key,
-            args.ssl_ticket_seeds,
-        )
-        .tls_acceptor_builder(root_log.clone())
-        .context("Failed to instantiate TLS Acceptor builder")?;
-
-        builder.set_alpn_select_callback(|_, protos| {
-            // NOTE: Currently we do not support HTTP/2 here yet.
-            alpn::alpn_select(protos, alpn::HGCLI_ALPN)
-                .map_err(|_| AlpnError::ALERT_FATAL)?
-                .ok_or(AlpnError::NOACK)
+        let tls_paths = vec![
+            PathBuf::from(&args.ca_pem),
+            PathBuf::from(&args.cert),
+            PathBuf::from(&args.private_key),
+        ];
+        let build: repo_listener::BuildTlsAcceptor = Box::new({
+            cloned!(root_log, args.ca_pem, args.cert, args.private_key);
+            let ssl_ticket_seeds = args.ssl_ticket_seeds;
+            move || {
+                let mut builder = secure_utils::SslConfig::new(
+                    ca_pem.clone(),
+                    cert.clone(),
+                    private_key.clone(),
+                    ssl_ticket_seeds.clone(),
+                )
+                .tls_acceptor_builder(root_log.clone())
+                .context("Failed to instantiate TLS Acceptor builder")?;
+
+                builder.set_alpn_select_callback(|_, protos| {
+                    // NOTE: Currently we do not support HTTP/2 here yet.
+                    alpn::alpn_select(protos, alpn::HGCLI_ALPN)
+                        .map_err(|_| AlpnError::ALERT_FATAL)?
+                        .ok_or(AlpnError::NOACK)
+                });
+
+                Ok(builder.build())
+            }
         });
 
-        builder.build()
+        let ctx = CoreContext::new_with_logger(fb, root_log.clone());
+        runtime.block_on(repo_listener::tls_acceptor(
+            ctx,
+            args.tls_reload_interval_secs.map(Duration::from_secs),
+            tls_paths,
+            build,
+        ))?
     };
 
     info!(root_log, "Creating repo listeners");
@@ -290,6 +320,7 @@ fn main(fb: FacebookInit) -> Result<()> {
     let host_port = args.listening_host_port;
 
     let bound_addr_file = args.bound_address_file;
+    let unix_socket_path = args.listening_unix_socket;
 
     let env = app.environment();
 
@@ -328,6 +359,18 @@ fn main(fb: FacebookInit) -> Result<()> {
                 .try_collect()
                 .await?;
             info!(&root_log, "Cache warmup completed");
+            runtime.spawn({
+                cloned!(mononoke, will_exit);
+                let ctx = CoreContext::new_with_logger(fb, root_log.clone());
+                async move {
+                    let mut interval = tokio::time::interval(RESOURCE_USAGE_REPORT_INTERVAL);
+                    // Stop reporting once the server starts shutting down.
+                    while !will_exit.load(Ordering::Relaxed) {
+                        interval.tick().await;
+                        mononoke.report_resource_usage(&ctx);
+                    }
+                }
+            });
             if let Some(mut executor) = args.sharded_executor_args.build_executor(
                 app.fb,
                 runtime.clone(),
@@ -358,7 +401,9 @@ fn main(fb: FacebookInit) -> Result<()> {
                 will_exit,
                 cslb_config,
                 bound_addr_file,
+                unix_socket_path,
                 env.acl_provider.as_ref(),
+                Arc::new(repo_listener::IdentityProviderChain::default()),
                 args.readonly.readonly,
             )
             .await
@@ -369,7 +414,7 @@ fn main(fb: FacebookInit) -> Result<()> {
     let fb303_args = app.extension_args::<Fb303AppExtension>()?;
     fb303_args.start_fb303_server(fb, "mononoke_server", root_log, service)?;
 
-    cmdlib::helpers::serve_forever(
+    let result = cmdlib::helpers::serve_forever(
         runtime,
         repo_listeners,
         root_log,
@@ -383,5 +428,11 @@ fn main(fb: FacebookInit) -> Result<()> {
             repo_listener::wait_for_connections_closed(root_log).await;
         },
         args.shutdown_timeout_args.shutdown_timeout,
-    )
+    );
+
+    // Request handlers batch some of their stats, so flush whatever they
+    // haven't flushed yet before exiting.
+    batched_stats::flush_all();
+
+    result
 }
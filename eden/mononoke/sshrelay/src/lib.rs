@@ -39,8 +39,15 @@ pub struct Stdio {
     pub stdin: BoxStream<Bytes, io::Error>,
     pub stdout: mpsc::Sender<Bytes>,
     pub stderr: mpsc::UnboundedSender<Bytes>,
+    /// Progress messages for the client.  These are sent as separate
+    /// frames to clients that support it, and mixed into stderr otherwise.
+    pub progress: mpsc::UnboundedSender<Bytes>,
 }
 
+/// Client feature advertised by clients that can receive progress messages
+/// on their own stream, separately from diagnostics on stderr.
+pub const CLIENT_FEATURE_PROGRESS_STREAM: &str = "progress-stream";
+
 pub struct SenderBytesWrite {
     pub chan: mpsc::UnboundedSender<Bytes>,
 }
@@ -65,6 +72,7 @@ pub enum IoStream {
     Stdin,
     Stdout,
     Stderr,
+    Progress,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -124,6 +132,7 @@ impl Decoder for SshDecoder {
                 0 => Ok(Some(SshMsg(IoStream::Stdin, data.freeze()))),
                 1 => Ok(Some(SshMsg(IoStream::Stdout, data.freeze()))),
                 2 => Ok(Some(SshMsg(IoStream::Stderr, data.freeze()))),
+                3 => Ok(Some(SshMsg(IoStream::Progress, data.freeze()))),
                 _ => Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
                     "bad ssh stream",
@@ -203,6 +212,11 @@ impl Encoder<SshMsg> for SshEncoder {
                 self.compress_into(&mut v, &msg.1).map_err(ioerr_cvt)?;
                 Ok(self.netstring.encode(v.freeze(), buf).map_err(ioerr_cvt)?)
             }
+            IoStream::Progress => {
+                v.put_u8(3);
+                self.compress_into(&mut v, &msg.1).map_err(ioerr_cvt)?;
+                Ok(self.netstring.encode(v.freeze(), buf).map_err(ioerr_cvt)?)
+            }
         }
     }
 }
@@ -275,8 +289,11 @@ mod test {
         encoder
             .encode(SshMsg::new(Stderr, b"Z".bytes()), &mut buf)
             .expect("encode failed");
+        encoder
+            .encode(SshMsg::new(Progress, b"P".bytes()), &mut buf)
+            .expect("encode failed");
 
-        assert_eq!(buf.as_ref(), b"2:\x00X,2:\x01Y,2:\x02Z,");
+        assert_eq!(buf.as_ref(), b"2:\x00X,2:\x01Y,2:\x02Z,2:\x03P,");
     }
 
     #[test]
@@ -349,7 +366,7 @@ mod test {
     #[test]
     fn decode_multi() {
         let mut buf = BytesMut::with_capacity(1024);
-        buf.put_slice(b"2:\x00X,2:\x01Y,2:\x02Z,");
+        buf.put_slice(b"2:\x00X,2:\x01Y,2:\x02Z,2:\x03P,");
 
         let mut decoder = SshDecoder::new();
 
@@ -365,12 +382,16 @@ mod test {
             Ok(Some(ref res)) if res == &SshMsg::new(Stderr, b"Z".bytes()) => {}
             bad => panic!("decode failed: {:?}", bad.as_ref()),
         }
+        match decoder.decode(&mut buf) {
+            Ok(Some(ref res)) if res == &SshMsg::new(Progress, b"P".bytes()) => {}
+            bad => panic!("decode failed: {:?}", bad.as_ref()),
+        }
     }
 
     #[test]
     fn decode_bad() {
         let mut buf = BytesMut::with_capacity(1024);
-        buf.put_slice(b"2:\x03X,");
+        buf.put_slice(b"2:\x04X,");
 
         let mut decoder = SshDecoder::new();
 
@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Test repos built from DrawDAG specifications.
+//!
+//! These allow tests to describe the repo they need in a few lines, rather
+//! than constructing each bonsai changeset by hand:
+//!
+//! ```ignore
+//!     let fixture: DrawDagFixture<BasicTestRepo> = DrawDagFixture::new(
+//!         fb,
+//!         r##"
+//!             A-B-C
+//!                \
+//!                 D
+//!             # modify: B dir/file "content\n"
+//!             # symlink: C dir/link "file"
+//!             # copy: D dir/moved "content\n" B dir/file
+//!             # delete: D dir/file
+//!             # bookmark: C main
+//!         "##,
+//!     )
+//!     .await?;
+//!     let hg_id = fixture.hg_changeset_id("C")?;
+//! ```
+//!
+//! See `tests_utils::drawdag::DrawDagSpec` for the full set of properties
+//! that can be set on each commit.  Commits are created with fixed dates, so
+//! the resulting changeset ids are stable across runs, and Mercurial
+//! changesets, with their manifests and filenodes, are derived for all of
+//! them.
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mercurial_derived_data::MappedHgChangesetId;
+use mercurial_types::HgChangesetId;
+use mononoke_types::ChangesetId;
+use test_repo_factory::TestRepoFactoryBuilder;
+use tests_utils::bookmark;
+use tests_utils::drawdag::extend_from_dag_with_changes;
+use tests_utils::drawdag::DrawDagSpec;
+use tests_utils::Repo;
+
+/// A test repo, together with the commits created from a DrawDAG
+/// specification.
+pub struct DrawDagFixture<R> {
+    pub repo: R,
+    pub commits: BTreeMap<String, ChangesetId>,
+    pub hg_commits: BTreeMap<String, HgChangesetId>,
+}
+
+impl<R> DrawDagFixture<R>
+where
+    R: Repo + Clone + for<'builder> facet::Buildable<TestRepoFactoryBuilder<'builder>>,
+{
+    /// Create a new empty test repo, and populate it from the
+    /// specification.  Each commit contains a file named after the commit,
+    /// as well as the changes in the specification.
+    pub async fn new(fb: FacebookInit, spec: &str) -> Result<Self> {
+        Self::new_with_options(fb, spec, true).await
+    }
+
+    /// Like `new`, but without the default files, so that commits only
+    /// contain the changes in the specification.
+    pub async fn new_without_default_files(fb: FacebookInit, spec: &str) -> Result<Self> {
+        Self::new_with_options(fb, spec, false).await
+    }
+
+    async fn new_with_options(fb: FacebookInit, spec: &str, default_files: bool) -> Result<Self> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: R = test_repo_factory::build_empty(fb)?;
+        let spec = DrawDagSpec::parse(spec).context("Failed to parse DrawDAG specification")?;
+        if !spec.existing.is_empty() {
+            return Err(anyhow!(
+                "Existing commits cannot be used when creating a new repo"
+            ));
+        }
+
+        let change_fns = DrawDagSpec::change_fns::<R>(spec.changes);
+        let (commits, _dag) = extend_from_dag_with_changes(
+            &ctx,
+            &repo,
+            &spec.dag,
+            change_fns,
+            BTreeMap::new(),
+            default_files,
+        )
+        .await?;
+
+        for (name, commit) in spec.bookmarks {
+            let target = commits
+                .get(&commit)
+                .ok_or_else(|| anyhow!("No commit {} for bookmark {}", commit, name))?;
+            bookmark(&ctx, &repo, name).set_to(*target).await?;
+        }
+
+        let mut hg_commits = BTreeMap::new();
+        for (name, cs_id) in commits.iter() {
+            let hg_cs_id = repo
+                .repo_derived_data()
+                .derive::<MappedHgChangesetId>(&ctx, *cs_id)
+                .await
+                .with_context(|| format!("Failed to derive hg changeset for {}", name))?
+                .hg_changeset_id();
+            hg_commits.insert(name.clone(), hg_cs_id);
+        }
+
+        Ok(DrawDagFixture {
+            repo,
+            commits,
+            hg_commits,
+        })
+    }
+
+    /// The bonsai changeset id of the named commit.
+    pub fn changeset_id(&self, name: &str) -> Result<ChangesetId> {
+        self.commits
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("No commit named {}", name))
+    }
+
+    /// The Mercurial changeset id of the named commit.
+    pub fn hg_changeset_id(&self, name: &str) -> Result<HgChangesetId> {
+        self.hg_commits
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("No commit named {}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use blobstore::Loadable;
+    use bookmarks::BookmarkName;
+    use bookmarks::BookmarksRef;
+    use mononoke_types::FileType;
+    use repo_blobstore::RepoBlobstoreRef;
+    use tests_utils::BasicTestRepo;
+
+    use super::*;
+
+    const SPEC: &str = r##"
+        A-B-C
+           \
+            D
+        # modify: B dir/file "content\n"
+        # executable: B dir/script "#!/bin/sh\n"
+        # symlink: C dir/link "file"
+        # copy: D dir/moved "content\n" B dir/file
+        # delete: D dir/file
+        # message: D "move file"
+        # bookmark: C main
+    "##;
+
+    #[fbinit::test]
+    async fn test_drawdag_fixture(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let fixture: DrawDagFixture<BasicTestRepo> = DrawDagFixture::new(fb, SPEC).await?;
+        assert_eq!(fixture.commits.len(), 4);
+        assert_eq!(fixture.hg_commits.len(), 4);
+
+        let main = fixture
+            .repo
+            .bookmarks()
+            .get(ctx.clone(), &BookmarkName::new("main")?)
+            .await?;
+        assert_eq!(main, Some(fixture.changeset_id("C")?));
+
+        let b = fixture
+            .changeset_id("B")?
+            .load(&ctx, fixture.repo.repo_blobstore())
+            .await?;
+        let script = b
+            .file_changes()
+            .find(|(path, _)| path.to_string() == "dir/script")
+            .and_then(|(_, change)| change.simplify().map(|change| change.file_type()));
+        assert_eq!(script, Some(FileType::Executable));
+
+        let d = fixture
+            .changeset_id("D")?
+            .load(&ctx, fixture.repo.repo_blobstore())
+            .await?;
+        assert_eq!(d.message(), "move file");
+        assert_eq!(d.file_changes().count(), 3);
+
+        // Creating the same repo again gives the same commits.
+        let again: DrawDagFixture<BasicTestRepo> = DrawDagFixture::new(fb, SPEC).await?;
+        assert_eq!(again.commits, fixture.commits);
+        assert_eq!(again.hg_commits, fixture.hg_commits);
+
+        Ok(())
+    }
+
+    #[fbinit::test]
+    async fn test_drawdag_fixture_without_default_files(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let fixture: DrawDagFixture<BasicTestRepo> =
+            DrawDagFixture::new_without_default_files(fb, SPEC).await?;
+        let a = fixture
+            .changeset_id("A")?
+            .load(&ctx, fixture.repo.repo_blobstore())
+            .await?;
+        assert_eq!(a.file_changes().count(), 0);
+        Ok(())
+    }
+}
@@ -38,6 +38,8 @@ use test_repo_factory::TestRepoFactoryBuilder;
 use tests_utils::BasicTestRepo;
 use tests_utils::Repo;
 
+pub mod drawdag;
+
 pub async fn store_files(
     ctx: &CoreContext,
     files: BTreeMap<&str, Option<&str>>,
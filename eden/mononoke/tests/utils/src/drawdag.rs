@@ -9,10 +9,14 @@ use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 
 use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Result;
+use bookmarks::BookmarkName;
 use context::CoreContext;
 use mononoke_types::ChangesetId;
+use mononoke_types::FileType;
 
+use crate::CommitIdentifier;
 use crate::CreateCommitContext;
 use crate::Repo;
 
@@ -165,6 +169,392 @@ pub async fn create_from_dag<R: Repo>(
     create_from_dag_with_changes(ctx, repo, dag, BTreeMap::new()).await
 }
 
+/// A DrawDAG specification, consisting of an ASCII graph (either
+/// left-to-right or bottom-to-top), and a series of comments that define
+/// additional properties for each commit.
+///
+/// Valid properties are:
+///
+/// * Set a known changeset id for an already-existing commit
+///     # exists: COMMIT id
+///
+/// * Set a bookmark on a commit
+///     # bookmark: COMMIT name
+///
+/// * Set the content of a file.
+///     # modify: COMMIT path/to/file "content"
+///
+/// * Set the content of an executable file.
+///     # executable: COMMIT path/to/file "content"
+///
+/// * Set the target of a symlink.
+///     # symlink: COMMIT path/to/link "target"
+///
+/// * Mark a file as deleted.
+///     # delete: COMMIT path/to/file
+///
+/// * Forget file that was about to be added (useful for getting rid of files
+///   that are added by default):
+///     # forget: COMMIT path/to/file
+///
+/// * Add a file that was copied from a file in another commit:
+///     # copy: COMMIT path/to/file "content" PARENT path/to/source
+///
+/// * Set the commit message, author or an extra:
+///     # message: COMMIT "message"
+///     # author: COMMIT "author"
+///     # extra: COMMIT key "value"
+///
+/// Paths can be surrounded by quotes if they contain special characters.
+#[derive(Clone, Debug, Default)]
+pub struct DrawDagSpec {
+    /// The ASCII graph, with all comments removed.
+    pub dag: String,
+    /// Commits in the graph that already exist.
+    pub existing: BTreeMap<String, ChangesetId>,
+    /// Changes to make to each commit.
+    pub changes: BTreeMap<String, Vec<ChangeAction>>,
+    /// Bookmarks to set, and the commit they should point to.
+    pub bookmarks: BTreeMap<BookmarkName, String>,
+}
+
+impl DrawDagSpec {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut spec = DrawDagSpec::default();
+        for line in input.lines() {
+            let dag_line = match line.split_once('#') {
+                Some((dag_line, comment)) => {
+                    match Action::new(comment)? {
+                        Action::Exists { name, id } => {
+                            spec.existing.insert(name, id);
+                        }
+                        Action::Bookmark { name, bookmark } => {
+                            spec.bookmarks.insert(bookmark, name);
+                        }
+                        Action::Change { name, change } => {
+                            spec.changes
+                                .entry(name)
+                                .or_insert_with(Vec::new)
+                                .push(change);
+                        }
+                    }
+                    dag_line
+                }
+                None => line,
+            };
+            spec.dag.push_str(dag_line);
+            spec.dag.push('\n');
+        }
+        Ok(spec)
+    }
+
+    /// Convert the changes in this spec into the closures expected by
+    /// `extend_from_dag_with_changes`.
+    pub fn change_fns<R: Repo>(
+        changes: BTreeMap<String, Vec<ChangeAction>>,
+    ) -> BTreeMap<String, Box<ChangeFn<R>>> {
+        changes
+            .into_iter()
+            .map(|(name, changes)| {
+                let apply: Box<ChangeFn<R>> = Box::new(
+                    move |c: CreateCommitContext<R>,
+                          committed: &'_ BTreeMap<String, ChangesetId>| {
+                        apply_changes(c, committed, changes)
+                    },
+                );
+                (name, apply)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Action {
+    Exists {
+        name: String,
+        id: ChangesetId,
+    },
+    Bookmark {
+        name: String,
+        bookmark: BookmarkName,
+    },
+    Change {
+        name: String,
+        change: ChangeAction,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChangeAction {
+    Modify {
+        path: Vec<u8>,
+        content: Vec<u8>,
+        file_type: FileType,
+    },
+    Delete {
+        path: Vec<u8>,
+    },
+    Forget {
+        path: Vec<u8>,
+    },
+    Extra {
+        key: String,
+        value: Vec<u8>,
+    },
+    Message {
+        message: String,
+    },
+    Author {
+        author: String,
+    },
+    Copy {
+        path: Vec<u8>,
+        content: Vec<u8>,
+        parent: String,
+        parent_path: Vec<u8>,
+    },
+}
+
+impl Action {
+    fn new(spec: &str) -> Result<Self> {
+        if let Some((key, args)) = spec.trim().split_once(':') {
+            let args = ActionArg::parse_args(args)
+                .with_context(|| format!("Failed to parse args for '{}'", key))?;
+            match (key, args.as_slice()) {
+                ("exists", [name, id]) => {
+                    let name = name.to_string()?;
+                    let id = id.to_string()?.parse()?;
+                    Ok(Action::Exists { name, id })
+                }
+                ("bookmark", [name, bookmark]) => {
+                    let name = name.to_string()?;
+                    let bookmark = bookmark.to_string()?.parse()?;
+                    Ok(Action::Bookmark { name, bookmark })
+                }
+                ("message", [name, message]) => {
+                    let name = name.to_string()?;
+                    let message = message.to_string()?;
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Message { message },
+                    })
+                }
+                ("author", [name, author]) => {
+                    let name = name.to_string()?;
+                    let author = author.to_string()?;
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Author { author },
+                    })
+                }
+                ("modify", [name, path, content]) => {
+                    Action::modify(name, path, content, FileType::Regular)
+                }
+                ("executable", [name, path, content]) => {
+                    Action::modify(name, path, content, FileType::Executable)
+                }
+                ("symlink", [name, path, target]) => {
+                    Action::modify(name, path, target, FileType::Symlink)
+                }
+                ("delete", [name, path]) => {
+                    let name = name.to_string()?;
+                    let path = path.to_bytes();
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Delete { path },
+                    })
+                }
+                ("forget", [name, path]) => {
+                    let name = name.to_string()?;
+                    let path = path.to_bytes();
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Forget { path },
+                    })
+                }
+                ("extra", [name, key, value]) => {
+                    let name = name.to_string()?;
+                    let key = key.to_string()?;
+                    let value = value.to_bytes();
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Extra { key, value },
+                    })
+                }
+                ("copy", [name, path, content, parent, parent_path]) => {
+                    let name = name.to_string()?;
+                    let path = path.to_bytes();
+                    let content = content.to_bytes();
+                    let parent = parent.to_string()?;
+                    let parent_path = parent_path.to_bytes();
+                    Ok(Action::Change {
+                        name,
+                        change: ChangeAction::Copy {
+                            path,
+                            content,
+                            parent,
+                            parent_path,
+                        },
+                    })
+                }
+                _ => Err(anyhow!("Invalid spec for key: {}", key)),
+            }
+        } else {
+            Err(anyhow!("Invalid spec: {}", spec))
+        }
+    }
+
+    fn modify(
+        name: &ActionArg,
+        path: &ActionArg,
+        content: &ActionArg,
+        file_type: FileType,
+    ) -> Result<Self> {
+        let name = name.to_string()?;
+        let path = path.to_bytes();
+        let content = content.to_bytes();
+        Ok(Action::Change {
+            name,
+            change: ChangeAction::Modify {
+                path,
+                content,
+                file_type,
+            },
+        })
+    }
+}
+
+struct ActionArg(Vec<u8>);
+
+impl ActionArg {
+    fn new() -> Self {
+        ActionArg(Vec::new())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+
+    fn to_string(&self) -> Result<String> {
+        let s = std::str::from_utf8(&self.0)
+            .context("Expected UTF-8 string for drawdag action argument")?;
+        Ok(s.to_string())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn push(&mut self, ch: char) {
+        let mut buf = [0; 4];
+        self.0
+            .extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.0.push(byte)
+    }
+
+    fn push_hex(&mut self, mut iter: impl Iterator<Item = char>) -> Result<()> {
+        if let (Some(top_hex), Some(bottom_hex)) = (iter.next(), iter.next()) {
+            if let (Some(top_digit), Some(bottom_digit)) =
+                (top_hex.to_digit(16), bottom_hex.to_digit(16))
+            {
+                self.push_byte((top_digit * 0x10 + bottom_digit) as u8);
+                return Ok(());
+            }
+        }
+        Err(anyhow!("Expected two hex digits"))
+    }
+
+    fn parse_args(args: &str) -> Result<Vec<Self>> {
+        let mut iter = args.trim().chars();
+        let mut args = Vec::new();
+        let mut arg = ActionArg::new();
+        let mut in_quotes = false;
+        while let Some(ch) = iter.next() {
+            if in_quotes {
+                match ch {
+                    '"' => in_quotes = false,
+                    '\\' => match iter
+                        .next()
+                        .ok_or_else(|| anyhow!("Unexpected end-of-line after '\\'"))?
+                    {
+                        '\\' => arg.push('\\'),
+                        'r' => arg.push('\r'),
+                        'n' => arg.push('\n'),
+                        't' => arg.push('\t'),
+                        'f' => arg.push('\u{0C}'),
+                        'b' => arg.push('\u{08}'),
+                        '"' => arg.push('"'),
+                        'x' => arg.push_hex(&mut iter)?,
+                        esc => return Err(anyhow!("Unexpected escape sequence: '\\{}'", esc)),
+                    },
+                    ch => arg.push(ch),
+                }
+            } else {
+                match ch {
+                    '"' => in_quotes = true,
+                    ch if ch.is_whitespace() => {
+                        if !arg.is_empty() {
+                            args.push(arg);
+                            arg = ActionArg::new();
+                        }
+                    }
+                    ch if ch.is_alphanumeric() || "_./".contains(ch) => {
+                        arg.push(ch);
+                    }
+                    ch => return Err(anyhow!("Unexpected character: '{}'", ch)),
+                }
+            }
+        }
+        if in_quotes {
+            return Err(anyhow!("Unterminated string literal"));
+        }
+        if !arg.is_empty() {
+            args.push(arg);
+        }
+        Ok(args)
+    }
+}
+
+fn apply_changes<'a, R: Repo>(
+    mut c: CreateCommitContext<'a, R>,
+    committed: &'_ BTreeMap<String, ChangesetId>,
+    changes: Vec<ChangeAction>,
+) -> CreateCommitContext<'a, R> {
+    for change in changes {
+        match change {
+            ChangeAction::Modify {
+                path,
+                content,
+                file_type,
+            } => c = c.add_file_with_type(path.as_slice(), content, file_type),
+            ChangeAction::Delete { path, .. } => c = c.delete_file(path.as_slice()),
+            ChangeAction::Forget { path, .. } => c = c.forget_file(path.as_slice()),
+            ChangeAction::Extra { key, value, .. } => c = c.add_extra(key, value),
+            ChangeAction::Message { message } => c = c.set_message(message),
+            ChangeAction::Author { author } => c = c.set_author(author),
+            ChangeAction::Copy {
+                path,
+                content,
+                parent,
+                parent_path,
+                ..
+            } => {
+                let parent: CommitIdentifier =
+                    committed.get(&parent).map_or(parent.into(), |&c| c.into());
+                c = c.add_file_with_copy_info(
+                    path.as_slice(),
+                    content,
+                    (parent, parent_path.as_slice()),
+                )
+            }
+        }
+    }
+    c
+}
+
 /// Macro to allow creation of `changes` for `create_from_dag_with_changes`.
 ///
 /// Example:
@@ -207,3 +597,62 @@ macro_rules! __drawdag_changes {
 
 // Export macro within this module.
 pub use __drawdag_changes as changes;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_action_specs() -> Result<()> {
+        assert_eq!(
+            Action::new(
+                "exists: A aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            )?,
+            Action::Exists {
+                name: "A".to_string(),
+                id: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".parse()?,
+            }
+        );
+        assert_eq!(
+            Action::new("bookmark: \"A-bookmark\" \"main\"/\"bookmark\"")?,
+            Action::Bookmark {
+                name: "A-bookmark".to_string(),
+                bookmark: "main/bookmark".parse()?,
+            }
+        );
+        assert_eq!(
+            Action::new(
+                "modify: _1 path/to/file \"this has \\xaa content\\n\\ton \\x02 lines with \\\"quotes\\\"\""
+            )?,
+            Action::Change {
+                name: "_1".to_string(),
+                change: ChangeAction::Modify {
+                    path: b"path/to/file".to_vec(),
+                    content: b"this has \xaa content\n\ton \x02 lines with \"quotes\"".to_vec(),
+                    file_type: FileType::Regular,
+                }
+            }
+        );
+        assert_eq!(
+            Action::new("delete: x path/\"to a deleted file\"")?,
+            Action::Change {
+                name: "x".to_string(),
+                change: ChangeAction::Delete {
+                    path: b"path/to a deleted file".to_vec(),
+                }
+            }
+        );
+        assert_eq!(
+            Action::new("symlink: B link \"../target\"")?,
+            Action::Change {
+                name: "B".to_string(),
+                change: ChangeAction::Modify {
+                    path: b"link".to_vec(),
+                    content: b"../target".to_vec(),
+                    file_type: FileType::Symlink,
+                }
+            }
+        );
+        Ok(())
+    }
+}
@@ -6,20 +6,28 @@
  */
 
 mononoke_app::subcommands! {
+    mod backfill_patch_ids;
     mod blobstore;
+    mod blobstore_migrate_keys;
     mod blobstore_unlink;
     mod bookmarks;
     mod changelog;
     mod commit;
+    mod config_history;
     mod convert;
     mod fetch;
     mod filestore;
+    mod hg_mapping;
     mod hg_sync;
     mod list_repos;
     mod mutable_renames;
+    mod preserved_bundles;
+    mod reachable_contents;
     mod redaction;
     mod repo_info;
     mod skiplist;
+    mod snapshot_bundles;
+    mod usage;
     mod ephemeral_store;
     mod dump_changesets;
     mod async_requests;
@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::num::NonZeroU64;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use bulkops::Direction;
+use bulkops::PublicChangesetBulkFetch;
+use changesets::ChangesetsArc;
+use clap::Parser;
+use futures::TryStreamExt;
+use mononoke_api::Mononoke;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use phases::PhasesArc;
+use slog::info;
+
+use crate::commit_id::parse_commit_id;
+
+/// Number of changesets between progress messages.
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Compute the patch ids of a repo's public changesets
+///
+/// Patch ids are otherwise only recorded in the patch id index when they are
+/// first asked for, so duplicates of a changeset can only be found among the
+/// changesets whose patch ids have been computed.  Changesets are processed
+/// from the oldest, and those already in the index are skipped.  The last
+/// changeset that was processed is logged periodically, and can be given as
+/// --start-commit to resume the backfill.
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    /// Start from this changeset rather than the repo's first one
+    #[clap(long)]
+    start_commit: Option<String>,
+
+    /// Only process this many public changesets
+    #[clap(long)]
+    limit: Option<NonZeroU64>,
+
+    /// Number of patch ids to compute at a time
+    #[clap(long, default_value_t = 20)]
+    concurrency: usize,
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+    let logger = app.logger().clone();
+    let (repo_name, _) = app.repo_config(args.repo.id_or_name()?)?;
+    let mononoke = Mononoke::new(Arc::new(app))
+        .await
+        .context("Failed to initialize Mononoke API")?;
+    let repo = mononoke
+        .repo(ctx.clone(), &repo_name)
+        .await?
+        .ok_or_else(|| anyhow!("Repo {} is not available", repo_name))?
+        .build()
+        .await?;
+
+    let blob_repo = repo.blob_repo();
+    let start_commit = match args.start_commit {
+        Some(start_commit) => Some(parse_commit_id(&ctx, blob_repo, &start_commit).await?),
+        None => None,
+    };
+
+    let fetcher = PublicChangesetBulkFetch::new(blob_repo.changesets_arc(), blob_repo.phases_arc());
+    let mut bounds = fetcher
+        .get_repo_bounds_after_commits(&ctx, start_commit.into_iter().collect())
+        .await?;
+    if let Some(limit) = args.limit {
+        bounds.1 = bounds.1.min(bounds.0 + limit.get());
+    }
+
+    let mut processed = 0;
+    let mut changesets = fetcher
+        .fetch_bounded(&ctx, Direction::OldestFirst, Some(bounds))
+        .map_ok(|entry| {
+            let repo = &repo;
+            async move {
+                if let Some(changeset) = repo.changeset(entry.cs_id).await? {
+                    changeset.patch_id().await?;
+                }
+                Ok::<_, anyhow::Error>(entry.cs_id)
+            }
+        })
+        .try_buffered(args.concurrency);
+    while let Some(cs_id) = changesets.try_next().await? {
+        processed += 1;
+        if processed % PROGRESS_INTERVAL == 0 {
+            info!(
+                logger,
+                "Processed {} changesets, up to {}", processed, cs_id
+            );
+        }
+    }
+    info!(logger, "Processed {} changesets", processed);
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Blobstore;
+use blobstore::BlobstoreBytes;
+use blobstore::BlobstoreKeyParam;
+use blobstore::BlobstoreKeyRange;
+use blobstore::BlobstoreKeySource;
+use blobstore_factory::default_scrub_handler;
+use blobstore_factory::make_blobstore;
+use blobstore_factory::make_blobstore_enumerable_with_unlink;
+use clap::Parser;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::hash::Blake2;
+use mononoke_types::hash::Context as HashContext;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::blobstore_unlink::get_blobconfig;
+
+/// Copy blobs from one key prefix to another
+///
+/// Used when the keys of a repo change, e.g. because its repo id changes.
+/// Every blob whose key starts with the old prefix is copied to the same key
+/// with the new prefix instead, and the copy is verified against the hash of
+/// the original.  A forwarding marker with the new key is then written for
+/// each old key.
+///
+/// Progress is saved to the checkpoint file after each batch of keys, and
+/// running the same command again resumes the migration from there.
+///
+/// Keys are enumerated from a single blobstore, which must be able to
+/// enumerate its keys (files and manifold).  For multiplexed blobstores this
+/// is the component given by --inner-blobstore-id.  Blobs are read, copied
+/// and marked through the repo's whole blobstore, so that every component of
+/// a multiplexed blobstore gets the copies and the markers.
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo_args: RepoArgs,
+
+    /// If the repo's blobstore is multiplexed, enumerate the keys from this
+    /// inner blobstore
+    #[clap(long)]
+    inner_blobstore_id: Option<u64>,
+
+    /// Prefix of the keys to migrate, e.g. "repo0001."
+    #[clap(long)]
+    old_prefix: String,
+
+    /// Prefix to migrate the keys to, e.g. "repo0042."
+    #[clap(long)]
+    new_prefix: String,
+
+    /// File that records the progress of the migration
+    #[clap(long)]
+    checkpoint: PathBuf,
+
+    /// Prefix of the keys of the forwarding markers, which are followed by
+    /// the old key
+    #[clap(long, default_value = "forwarded.")]
+    marker_prefix: String,
+
+    /// Stop once this many blobs have been migrated by this run, at the end
+    /// of the batch of keys that reaches it
+    #[clap(long)]
+    limit: Option<u64>,
+
+    /// Number of blobs to migrate concurrently
+    #[clap(long, default_value_t = 100)]
+    concurrency: usize,
+}
+
+/// Progress of a migration, which is saved between batches of keys.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    old_prefix: String,
+    new_prefix: String,
+    /// Where to continue enumerating the old keys from, or `None` once all
+    /// of them have been migrated.
+    next: Option<BlobstoreKeyParam>,
+    /// Number of blobs copied to their new key.
+    copied: u64,
+    /// Number of blobs whose new key already had the same content.
+    already_present: u64,
+}
+
+impl Checkpoint {
+    fn new(old_prefix: &str, new_prefix: &str) -> Self {
+        Checkpoint {
+            old_prefix: old_prefix.to_string(),
+            new_prefix: new_prefix.to_string(),
+            next: Some(BlobstoreKeyParam::Start(BlobstoreKeyRange {
+                begin_key: old_prefix.to_string(),
+                // The range is inclusive, and keys are ASCII.
+                end_key: format!("{}\x7f", old_prefix),
+            })),
+            copied: 0,
+            already_present: 0,
+        }
+    }
+
+    fn load_or_new(path: &Path, old_prefix: &str, new_prefix: &str) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Checkpoint::new(old_prefix, new_prefix));
+        }
+        let checkpoint: Checkpoint = serde_json::from_slice(
+            &fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?,
+        )
+        .with_context(|| format!("Failed to parse checkpoint {}", path.display()))?;
+        if checkpoint.old_prefix != old_prefix || checkpoint.new_prefix != new_prefix {
+            bail!(
+                "Checkpoint {} is for a migration from '{}' to '{}'",
+                path.display(),
+                checkpoint.old_prefix,
+                checkpoint.new_prefix
+            );
+        }
+        Ok(checkpoint)
+    }
+
+    /// Save the checkpoint, replacing the previous one atomically.
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Written to the marker key of each migrated blob.
+#[derive(Serialize)]
+struct ForwardingMarker<'a> {
+    forwarded_to: &'a str,
+    blake2: String,
+}
+
+enum Migrated {
+    Copied,
+    AlreadyPresent,
+}
+
+fn blob_hash(bytes: &[u8]) -> Blake2 {
+    let mut context = HashContext::new(b"");
+    context.update(bytes);
+    context.finish()
+}
+
+async fn migrate_key(
+    ctx: &CoreContext,
+    blobstore: &dyn Blobstore,
+    args: &CommandArgs,
+    old_key: &str,
+) -> Result<Migrated> {
+    let new_key = format!("{}{}", args.new_prefix, &old_key[args.old_prefix.len()..]);
+    let data = blobstore
+        .get(ctx, old_key)
+        .await?
+        .ok_or_else(|| anyhow!("Blob {} was enumerated but doesn't exist", old_key))?;
+    let hash = blob_hash(data.as_raw_bytes());
+
+    let migrated = match blobstore.get(ctx, &new_key).await? {
+        Some(existing) if blob_hash(existing.as_raw_bytes()) == hash => Migrated::AlreadyPresent,
+        Some(_) => bail!(
+            "Blob {} already exists with different content than {}",
+            new_key,
+            old_key
+        ),
+        None => {
+            blobstore
+                .put(ctx, new_key.clone(), data.into_bytes())
+                .await
+                .with_context(|| format!("Failed to copy {} to {}", old_key, new_key))?;
+            let copy = blobstore
+                .get(ctx, &new_key)
+                .await?
+                .ok_or_else(|| anyhow!("Blob {} is missing after copying it", new_key))?;
+            if blob_hash(copy.as_raw_bytes()) != hash {
+                bail!("Copy of {} to {} doesn't match its hash", old_key, new_key);
+            }
+            Migrated::Copied
+        }
+    };
+
+    let marker = ForwardingMarker {
+        forwarded_to: &new_key,
+        blake2: hash.to_hex().to_string(),
+    };
+    blobstore
+        .put(
+            ctx,
+            format!("{}{}", args.marker_prefix, old_key),
+            BlobstoreBytes::from_bytes(serde_json::to_vec(&marker)?),
+        )
+        .await
+        .with_context(|| format!("Failed to write forwarding marker for {}", old_key))?;
+
+    Ok(migrated)
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    if args.old_prefix.is_empty() || args.old_prefix == args.new_prefix {
+        bail!("--old-prefix must be non-empty and different from --new-prefix");
+    }
+    // Keys written by the migration must not be enumerated as old keys.
+    if args.new_prefix.starts_with(&args.old_prefix)
+        || args.marker_prefix.starts_with(&args.old_prefix)
+    {
+        bail!("--new-prefix and --marker-prefix must not start with --old-prefix");
+    }
+
+    let repo_arg = args.repo_args.id_or_name()?;
+    let (_repo_name, repo_config) = app.repo_config(repo_arg)?;
+    let env = app.environment();
+    let enumerable_blobconfig = get_blobconfig(
+        repo_config.storage_config.blobstore.clone(),
+        args.inner_blobstore_id,
+    )?;
+    let enumerable = make_blobstore_enumerable_with_unlink(
+        app.fb,
+        enumerable_blobconfig,
+        &env.blobstore_options,
+        app.logger(),
+    )
+    .await?;
+    let blobstore = make_blobstore(
+        app.fb,
+        repo_config.storage_config.blobstore,
+        &env.mysql_options,
+        env.readonly_storage,
+        &env.blobstore_options,
+        app.logger(),
+        app.config_store(),
+        &default_scrub_handler(),
+        None,
+    )
+    .await?;
+
+    let mut checkpoint =
+        Checkpoint::load_or_new(&args.checkpoint, &args.old_prefix, &args.new_prefix)?;
+    let mut migrated_by_run = 0;
+    while let Some(next) = checkpoint.next.clone() {
+        if args.limit.map_or(false, |limit| migrated_by_run >= limit) {
+            break;
+        }
+        let data = enumerable
+            .enumerate(&ctx, &next)
+            .await
+            .context("Failed to enumerate keys")?;
+        let mut keys = data
+            .keys
+            .into_iter()
+            .filter(|key| key.starts_with(&args.old_prefix))
+            .collect::<Vec<_>>();
+        keys.sort();
+
+        let results = stream::iter(keys)
+            .map(|key| {
+                let ctx = &ctx;
+                let blobstore = &blobstore;
+                let args = &args;
+                async move { migrate_key(ctx, blobstore.as_ref(), args, &key).await }
+            })
+            .buffer_unordered(args.concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+        for migrated in results {
+            match migrated {
+                Migrated::Copied => checkpoint.copied += 1,
+                Migrated::AlreadyPresent => checkpoint.already_present += 1,
+            }
+            migrated_by_run += 1;
+        }
+
+        checkpoint.next = data.next_token;
+        checkpoint.save(&args.checkpoint)?;
+        writeln!(
+            std::io::stdout(),
+            "Migrated {} blobs ({} copied, {} already present)",
+            checkpoint.copied + checkpoint.already_present,
+            checkpoint.copied,
+            checkpoint.already_present,
+        )?;
+    }
+
+    if checkpoint.next.is_none() {
+        writeln!(
+            std::io::stdout(),
+            "Migration from '{}' to '{}' is complete",
+            args.old_prefix,
+            args.new_prefix
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("checkpoint");
+
+        let mut checkpoint = Checkpoint::load_or_new(&path, "repo0001.", "repo0042.")?;
+        assert_eq!(
+            checkpoint.next,
+            Some(BlobstoreKeyParam::Start(BlobstoreKeyRange {
+                begin_key: "repo0001.".to_string(),
+                end_key: "repo0001.\x7f".to_string(),
+            }))
+        );
+        checkpoint.next = None;
+        checkpoint.copied = 3;
+        checkpoint.save(&path)?;
+
+        let loaded = Checkpoint::load_or_new(&path, "repo0001.", "repo0042.")?;
+        assert_eq!(loaded.next, None);
+        assert_eq!(loaded.copied, 3);
+        assert!(Checkpoint::load_or_new(&path, "repo0001.", "repo0043.").is_err());
+        Ok(())
+    }
+}
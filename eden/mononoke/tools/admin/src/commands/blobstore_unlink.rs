@@ -49,7 +49,10 @@ fn remove_wrapper_blobconfigs(mut blob_config: BlobConfig) -> BlobConfig {
     blob_config
 }
 
-fn get_blobconfig(blob_config: BlobConfig, inner_blobstore_id: Option<u64>) -> Result<BlobConfig> {
+pub(super) fn get_blobconfig(
+    blob_config: BlobConfig,
+    inner_blobstore_id: Option<u64>,
+) -> Result<BlobConfig> {
     match inner_blobstore_id {
         None => Ok(blob_config),
         Some(inner_blobstore_id) => match blob_config {
@@ -5,7 +5,11 @@
  * GNU General Public License version 2.
  */
 
+mod export_graph;
+mod export_spans;
 mod graph;
+mod graph_interchange;
+mod import_graph;
 mod list_ancestors;
 
 use anyhow::Context;
@@ -14,6 +18,8 @@ use bonsai_git_mapping::BonsaiGitMapping;
 use bonsai_globalrev_mapping::BonsaiGlobalrevMapping;
 use bonsai_hg_mapping::BonsaiHgMapping;
 use bonsai_svnrev_mapping::BonsaiSvnrevMapping;
+use bookmarks::BookmarkUpdateLog;
+use bookmarks::Bookmarks;
 use changeset_fetcher::ChangesetFetcher;
 use changesets::Changesets;
 use clap::Parser;
@@ -21,10 +27,15 @@ use clap::Subcommand;
 use metaconfig_types::RepoConfig;
 use mononoke_app::args::RepoArgs;
 use mononoke_app::MononokeApp;
+use phases::Phases;
 use repo_blobstore::RepoBlobstore;
 use repo_identity::RepoIdentity;
+use skiplist::SkiplistIndex;
 
+use self::export_graph::ChangelogExportGraphArgs;
+use self::export_spans::ChangelogExportSpansArgs;
 use self::graph::ChangelogGraphArgs;
+use self::import_graph::ChangelogImportGraphArgs;
 use self::list_ancestors::ChangelogListAncestorsArgs;
 
 /// Manipulate changelogs
@@ -65,13 +76,35 @@ pub struct Repo {
 
     #[facet]
     changeset_fetcher: dyn ChangesetFetcher,
+
+    #[facet]
+    phases: dyn Phases,
+
+    #[facet]
+    bookmarks: dyn Bookmarks,
+
+    #[facet]
+    bookmark_update_log: dyn BookmarkUpdateLog,
+
+    #[facet]
+    skiplist_index: SkiplistIndex,
 }
 
 #[derive(Subcommand)]
 pub enum ChangelogSubcommand {
+    /// Export the public commit graph and publishing bookmarks to an
+    /// interchange file
+    ExportGraph(ChangelogExportGraphArgs),
+
+    /// Export public commits as columnar span files for offline analytics
+    ExportSpans(ChangelogExportSpansArgs),
+
     /// Display parts of the commit DAG
     Graph(ChangelogGraphArgs),
 
+    /// Import a commit graph and bookmarks from an interchange file
+    ImportGraph(ChangelogImportGraphArgs),
+
     /// List ancestors of a commit
     ListAncestors(ChangelogListAncestorsArgs),
 }
@@ -85,7 +118,16 @@ pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
         .context("Failed to open repo")?;
 
     match args.subcommand {
+        ChangelogSubcommand::ExportGraph(export_graph_args) => {
+            export_graph::export_graph(&ctx, &repo, export_graph_args).await?
+        }
+        ChangelogSubcommand::ExportSpans(export_spans_args) => {
+            export_spans::export_spans(&ctx, &repo, export_spans_args).await?
+        }
         ChangelogSubcommand::Graph(graph_args) => graph::graph(&ctx, &repo, graph_args).await?,
+        ChangelogSubcommand::ImportGraph(import_graph_args) => {
+            import_graph::import_graph(&ctx, &repo, import_graph_args).await?
+        }
         ChangelogSubcommand::ListAncestors(list_ancestors_args) => {
             list_ancestors::list_ancestors(&ctx, &repo, list_ancestors_args).await?
         }
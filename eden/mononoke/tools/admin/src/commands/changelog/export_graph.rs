@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use bulkops::Direction;
+use bulkops::PublicChangesetBulkFetch;
+use changesets::ChangesetsArc;
+use clap::Args;
+use context::CoreContext;
+use futures::TryStreamExt;
+use phases::PhasesArc;
+use tokio::io::BufWriter;
+
+use super::graph_interchange::GraphWriter;
+use super::Repo;
+
+#[derive(Args)]
+pub struct ChangelogExportGraphArgs {
+    /// File to write the commit graph to
+    #[clap(long, parse(from_os_str))]
+    output_file: PathBuf,
+}
+
+pub async fn export_graph(
+    ctx: &CoreContext,
+    repo: &Repo,
+    export_graph_args: ChangelogExportGraphArgs,
+) -> Result<()> {
+    let output_file = export_graph_args.output_file;
+    // Write to a temporary file first, so that a partial graph is never
+    // mistaken for a complete one.
+    let tmp_file = output_file.with_extension("tmp");
+    let file = tokio::fs::File::create(&tmp_file)
+        .await
+        .with_context(|| format!("Failed to create {}", tmp_file.display()))?;
+    let mut writer = GraphWriter::new(BufWriter::new(file)).await?;
+
+    // Bookmarks are listed before the commits are exported, so that every
+    // bookmark points to an exported commit.
+    let bookmarks = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            u64::MAX,
+        )
+        .try_collect::<Vec<_>>()
+        .await
+        .context("Failed to list bookmarks")?;
+
+    let fetcher = PublicChangesetBulkFetch::new(repo.changesets_arc(), repo.phases_arc());
+    let mut changesets = fetcher.fetch(ctx, Direction::OldestFirst);
+    while let Some(entry) = changesets.try_next().await? {
+        writer.write_commit(&entry).await?;
+    }
+    for (bookmark, cs_id) in bookmarks {
+        writer
+            .write_bookmark(bookmark.name(), *bookmark.kind(), cs_id)
+            .await?;
+    }
+    let (commits, bookmarks) = writer.finish().await?;
+
+    tokio::fs::rename(&tmp_file, &output_file).await?;
+    println!(
+        "Exported {} commits and {} bookmarks to {}",
+        commits,
+        bookmarks,
+        output_file.display()
+    );
+    Ok(())
+}
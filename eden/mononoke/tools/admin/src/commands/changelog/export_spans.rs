@@ -0,0 +1,411 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Loadable;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkName;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarkUpdateLogRef;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use changeset_fetcher::ChangesetFetcherArc;
+use clap::Args;
+use context::CoreContext;
+use futures::compat::Stream01CompatExt;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use itertools::Itertools;
+use mononoke_types::BonsaiChangeset;
+use mononoke_types::ChangesetId;
+use reachabilityindex::LeastCommonAncestorsHint;
+use repo_blobstore::RepoBlobstoreRef;
+use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+use skiplist::SkiplistIndexArc;
+
+use super::Repo;
+
+const SPAN_PREFIX: &str = "span-";
+const COMMIT_ID_COLUMN: &str = "commit_id";
+
+/// File in the output directory that records the id of the last bookmark
+/// update log entry whose commits have been exported.
+const CHECKPOINT_FILE: &str = "bookmark_log_id";
+
+/// Number of bookmark update log entries that are read at a time.
+const LOG_PAGE_SIZE: u64 = 10_000;
+
+#[derive(Args)]
+pub struct ChangelogExportSpansArgs {
+    /// Directory to write the spans to.  Spans that are already in the
+    /// directory are kept, and only commits that have become public since
+    /// the last export are appended as new spans.
+    #[clap(long, parse(from_os_str))]
+    output_dir: PathBuf,
+
+    /// Maximum number of commits in each span
+    #[clap(long, default_value_t = 100_000)]
+    span_size: usize,
+}
+
+/// A span of consecutive public commits, stored column by column.  Each
+/// span is a directory with one file per column, and one line per commit in
+/// each file.
+#[derive(Default)]
+struct Span {
+    commit_ids: Vec<String>,
+    parents: Vec<String>,
+    authors: Vec<String>,
+    dates: Vec<String>,
+    files_counts: Vec<String>,
+}
+
+impl Span {
+    fn len(&self) -> usize {
+        self.commit_ids.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.commit_ids.is_empty()
+    }
+
+    fn push(&mut self, bcs: &BonsaiChangeset) {
+        self.commit_ids.push(bcs.get_changeset_id().to_string());
+        self.parents.push(bcs.parents().join(","));
+        self.authors
+            .push(bcs.author().replace(|c| c == '\n' || c == '\r', " "));
+        self.dates
+            .push(bcs.author_date().timestamp_secs().to_string());
+        self.files_counts
+            .push(bcs.file_changes_map().len().to_string());
+    }
+
+    /// Write the span to `output_dir`.  The span is written to a temporary
+    /// directory first, so that readers never see a partial span.
+    async fn write(self, output_dir: &Path, index: u64) -> Result<()> {
+        let name = format!("{}{:08}", SPAN_PREFIX, index);
+        let tmp_dir = output_dir.join(format!("{}.tmp", name));
+        if tmp_dir.exists() {
+            tokio::fs::remove_dir_all(&tmp_dir).await?;
+        }
+        tokio::fs::create_dir_all(&tmp_dir).await?;
+
+        for (column, values) in [
+            (COMMIT_ID_COLUMN, self.commit_ids),
+            ("parents", self.parents),
+            ("author", self.authors),
+            ("date", self.dates),
+            ("files_count", self.files_counts),
+        ] {
+            let mut content = values.join("\n");
+            content.push('\n');
+            tokio::fs::write(tmp_dir.join(column), content)
+                .await
+                .with_context(|| format!("Failed to write column {} of {}", column, name))?;
+        }
+
+        tokio::fs::rename(&tmp_dir, output_dir.join(&name)).await?;
+        Ok(())
+    }
+}
+
+/// Find the index of the newest span in `output_dir`.
+async fn find_last_span(output_dir: &Path) -> Result<Option<u64>> {
+    let mut last_span = None;
+    let mut entries = tokio::fs::read_dir(output_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let index = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(SPAN_PREFIX))
+            .and_then(|index| index.parse::<u64>().ok());
+        if let Some(index) = index {
+            if last_span.map_or(true, |last| index > last) {
+                last_span = Some(index);
+            }
+        }
+    }
+    Ok(last_span)
+}
+
+async fn read_checkpoint(output_dir: &Path) -> Result<Option<u64>> {
+    let path = output_dir.join(CHECKPOINT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = tokio::fs::read_to_string(&path).await?;
+    let log_id = content
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid checkpoint in {}", path.display()))?;
+    Ok(Some(log_id))
+}
+
+async fn write_checkpoint(output_dir: &Path, log_id: u64) -> Result<()> {
+    let path = output_dir.join(CHECKPOINT_FILE);
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, format!("{}\n", log_id)).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(())
+}
+
+/// The positions of the publishing bookmarks just after bookmark update log
+/// entry `log_id`.  The current positions are read first, and then the
+/// moves logged since `log_id` are undone.  Reading the positions first
+/// means any move that happens in between is found in the log.
+async fn bookmark_positions_at(
+    ctx: &CoreContext,
+    repo: &Repo,
+    log_id: u64,
+) -> Result<HashMap<BookmarkName, ChangesetId>> {
+    let mut positions: HashMap<_, _> = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            u64::MAX,
+        )
+        .map_ok(|(bookmark, cs_id)| (bookmark.into_name(), cs_id))
+        .try_collect()
+        .await
+        .context("Failed to list bookmarks")?;
+
+    let mut undone = HashSet::new();
+    let mut next_id = log_id;
+    loop {
+        let entries = repo
+            .bookmark_update_log()
+            .read_next_bookmark_log_entries(
+                ctx.clone(),
+                next_id,
+                LOG_PAGE_SIZE,
+                Freshness::MostRecent,
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .context("Failed to read bookmark update log")?;
+        for entry in entries.iter() {
+            // The first move of a bookmark since `log_id` starts from where
+            // the bookmark was then.  Moves that don't record where they
+            // started from are treated as creations, which at worst means
+            // some commits are exported again.
+            if undone.insert(entry.bookmark_name.clone()) {
+                match entry.from_changeset_id {
+                    Some(cs_id) => positions.insert(entry.bookmark_name.clone(), cs_id),
+                    None => positions.remove(&entry.bookmark_name),
+                };
+            }
+            next_id = entry.id as u64;
+        }
+        if entries.len() < LOG_PAGE_SIZE as usize {
+            return Ok(positions);
+        }
+    }
+}
+
+/// Export the commits that are public as of bookmark update log entry
+/// `log_id`, and weren't as of `exported_log_id`, i.e. the ancestors of the
+/// publishing bookmarks then that aren't ancestors of the publishing
+/// bookmarks at the last export.  Unlike exporting by changeset insertion
+/// order, this finds commits that were uploaded as drafts before the last
+/// export and have become public since.  Returns the number of commits
+/// exported.
+async fn export_new_commits(
+    ctx: &CoreContext,
+    repo: &Repo,
+    output_dir: &Path,
+    span_size: usize,
+    mut next_index: u64,
+    exported_log_id: Option<u64>,
+    log_id: u64,
+) -> Result<usize> {
+    let heads = bookmark_positions_at(ctx, repo, log_id).await?;
+    let exported_heads = match exported_log_id {
+        Some(exported_log_id) => bookmark_positions_at(ctx, repo, exported_log_id).await?,
+        None => HashMap::new(),
+    };
+
+    let lca_hint: Arc<dyn LeastCommonAncestorsHint> = repo.skiplist_index_arc();
+    // Commits are found from the newest, so they are reversed to export
+    // parents before their children.
+    let mut cs_ids = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+        ctx.clone(),
+        &repo.changeset_fetcher_arc(),
+        lca_hint,
+        heads.into_values().unique().collect(),
+        exported_heads.into_values().unique().collect(),
+    )
+    .compat()
+    .try_collect::<Vec<_>>()
+    .await?;
+    cs_ids.reverse();
+
+    let mut changesets = stream::iter(cs_ids)
+        .map(|cs_id| async move { cs_id.load(ctx, repo.repo_blobstore()).await })
+        .buffered(100);
+
+    let mut span = Span::default();
+    let mut exported = 0;
+    while let Some(bcs) = changesets.try_next().await? {
+        span.push(&bcs);
+        if span.len() >= span_size {
+            exported += span.len();
+            mem::take(&mut span).write(output_dir, next_index).await?;
+            next_index += 1;
+        }
+    }
+    if !span.is_empty() {
+        exported += span.len();
+        span.write(output_dir, next_index).await?;
+    }
+    Ok(exported)
+}
+
+pub async fn export_spans(
+    ctx: &CoreContext,
+    repo: &Repo,
+    export_spans_args: ChangelogExportSpansArgs,
+) -> Result<()> {
+    let output_dir = export_spans_args.output_dir;
+    tokio::fs::create_dir_all(&output_dir).await?;
+
+    let last_span = find_last_span(&output_dir).await?;
+    let exported_log_id = read_checkpoint(&output_dir).await?;
+    if last_span.is_some() && exported_log_id.is_none() {
+        bail!(
+            "{} contains spans but no {} file to resume from",
+            output_dir.display(),
+            CHECKPOINT_FILE
+        );
+    }
+    let next_index = last_span.map_or(0, |index| index + 1);
+
+    let log_id = repo
+        .bookmark_update_log()
+        .get_largest_log_id(ctx.clone(), Freshness::MostRecent)
+        .await?
+        .unwrap_or(0);
+    let exported = export_new_commits(
+        ctx,
+        repo,
+        &output_dir,
+        export_spans_args.span_size,
+        next_index,
+        exported_log_id,
+        log_id,
+    )
+    .await?;
+    write_checkpoint(&output_dir, log_id).await?;
+
+    println!("Exported {} commits to {}", exported, output_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bonsai_hg_mapping::BonsaiHgMapping;
+    use bookmarks::BookmarkUpdateLog;
+    use bookmarks::BookmarkUpdateReason;
+    use bookmarks::Bookmarks;
+    use changeset_fetcher::ChangesetFetcher;
+    use changesets::Changesets;
+    use fbinit::FacebookInit;
+    use filestore::FilestoreConfig;
+    use repo_blobstore::RepoBlobstore;
+    use repo_derived_data::RepoDerivedData;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+
+    #[facet::container]
+    struct TestRepo {
+        #[delegate(
+            dyn BonsaiHgMapping,
+            dyn Bookmarks,
+            dyn BookmarkUpdateLog,
+            dyn Changesets,
+            dyn ChangesetFetcher,
+            RepoBlobstore,
+        )]
+        repo: Repo,
+
+        #[facet]
+        filestore_config: FilestoreConfig,
+
+        #[facet]
+        repo_derived_data: RepoDerivedData,
+    }
+
+    async fn read_span(output_dir: &Path, index: u64) -> Result<Vec<ChangesetId>> {
+        let span_dir = output_dir.join(format!("{}{:08}", SPAN_PREFIX, index));
+        tokio::fs::read_to_string(span_dir.join(COMMIT_ID_COLUMN))
+            .await?
+            .lines()
+            .map(|cs_id| cs_id.parse())
+            .collect()
+    }
+
+    #[fbinit::test]
+    async fn test_export_drafts_that_became_public(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo: TestRepo = test_repo_factory::build_empty(fb)?;
+        let output_dir = tempfile::tempdir()?;
+        let args = || ChangelogExportSpansArgs {
+            output_dir: output_dir.path().to_path_buf(),
+            span_size: 100,
+        };
+        let main = BookmarkName::new("main")?;
+
+        let root = CreateCommitContext::new_root(&ctx, &repo)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let mut txn = repo.repo.bookmarks().create_transaction(ctx.clone());
+        txn.create(&main, root, BookmarkUpdateReason::TestMove)?;
+        txn.commit().await?;
+
+        // A draft commit that was uploaded before the first export.
+        let draft = CreateCommitContext::new(&ctx, &repo, vec![root])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+
+        export_spans(&ctx, &repo.repo, args()).await?;
+        assert_eq!(read_span(output_dir.path(), 0).await?, vec![root]);
+
+        // The draft becomes public after the first export.
+        let landed = CreateCommitContext::new(&ctx, &repo, vec![draft])
+            .add_file("c", "c")
+            .commit()
+            .await?;
+        let mut txn = repo.repo.bookmarks().create_transaction(ctx.clone());
+        txn.update(&main, landed, root, BookmarkUpdateReason::TestMove)?;
+        txn.commit().await?;
+
+        export_spans(&ctx, &repo.repo, args()).await?;
+        assert_eq!(read_span(output_dir.path(), 1).await?, vec![draft, landed]);
+
+        // Nothing new has become public, so no span is added.
+        export_spans(&ctx, &repo.repo, args()).await?;
+        assert_eq!(find_last_span(output_dir.path()).await?, Some(1));
+        Ok(())
+    }
+}
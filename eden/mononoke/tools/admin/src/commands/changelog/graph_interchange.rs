@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Commit graph interchange format.
+//!
+//! A commit graph file contains the public commit graph of a repository and
+//! its publishing bookmarks, so that the graph can be moved between
+//! deployments or analysed offline without access to the blobstore.  All
+//! integers are big-endian.
+//!
+//! ```text
+//! file     := header record* end
+//! header   := "MNKGRAPH" version:u32
+//! record   := commit | bookmark
+//! commit   := 0x01 cs_id:[u8; 32] generation:u64 parent_count:u32
+//!             parent_cs_id:[u8; 32]{parent_count}
+//! bookmark := 0x02 name_len:u32 name:[u8; name_len] kind:u8 cs_id:[u8; 32]
+//! end      := 0x00 commit_count:u64 bookmark_count:u64
+//! ```
+//!
+//! Commits are always written after their parents, and bookmarks after all
+//! of the commits, so a reader can insert records in the order they are
+//! read.  Bookmark names are UTF-8, and the bookmark kind is 0 for
+//! publishing bookmarks and 1 for pull-default publishing bookmarks.  The
+//! end record holds the number of records of each type, so that truncated
+//! files are detected.
+//!
+//! The version is incremented whenever the format changes in a way that
+//! older readers can't handle.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkName;
+use changesets::ChangesetEntry;
+use mononoke_types::ChangesetId;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+const MAGIC: &[u8; 8] = b"MNKGRAPH";
+const VERSION: u32 = 1;
+
+const TAG_END: u8 = 0;
+const TAG_COMMIT: u8 = 1;
+const TAG_BOOKMARK: u8 = 2;
+
+const KIND_PUBLISHING: u8 = 0;
+const KIND_PULL_DEFAULT_PUBLISHING: u8 = 1;
+
+/// A record read from a commit graph file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GraphRecord {
+    Commit {
+        cs_id: ChangesetId,
+        generation: u64,
+        parents: Vec<ChangesetId>,
+    },
+    Bookmark {
+        name: BookmarkName,
+        kind: BookmarkKind,
+        cs_id: ChangesetId,
+    },
+}
+
+/// Writer for commit graph files.  `finish` must be called once all of the
+/// records have been written.
+pub struct GraphWriter<W> {
+    writer: W,
+    commit_count: u64,
+    bookmark_count: u64,
+}
+
+impl<W: AsyncWrite + Unpin> GraphWriter<W> {
+    pub async fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC).await?;
+        writer.write_u32(VERSION).await?;
+        Ok(GraphWriter {
+            writer,
+            commit_count: 0,
+            bookmark_count: 0,
+        })
+    }
+
+    pub async fn write_commit(&mut self, entry: &ChangesetEntry) -> Result<()> {
+        if self.bookmark_count > 0 {
+            bail!("Commits must be written before bookmarks");
+        }
+        self.writer.write_u8(TAG_COMMIT).await?;
+        self.writer.write_all(entry.cs_id.as_ref()).await?;
+        self.writer.write_u64(entry.gen).await?;
+        self.writer
+            .write_u32(entry.parents.len().try_into()?)
+            .await?;
+        for parent in entry.parents.iter() {
+            self.writer.write_all(parent.as_ref()).await?;
+        }
+        self.commit_count += 1;
+        Ok(())
+    }
+
+    pub async fn write_bookmark(
+        &mut self,
+        name: &BookmarkName,
+        kind: BookmarkKind,
+        cs_id: ChangesetId,
+    ) -> Result<()> {
+        let kind = match kind {
+            BookmarkKind::Publishing => KIND_PUBLISHING,
+            BookmarkKind::PullDefaultPublishing => KIND_PULL_DEFAULT_PUBLISHING,
+            BookmarkKind::Scratch => bail!("Scratch bookmark {} can't be exported", name),
+        };
+        let name = name.as_str().as_bytes();
+        self.writer.write_u8(TAG_BOOKMARK).await?;
+        self.writer.write_u32(name.len().try_into()?).await?;
+        self.writer.write_all(name).await?;
+        self.writer.write_u8(kind).await?;
+        self.writer.write_all(cs_id.as_ref()).await?;
+        self.bookmark_count += 1;
+        Ok(())
+    }
+
+    /// Write the end record and flush the writer.  Returns the number of
+    /// commits and bookmarks that were written.
+    pub async fn finish(mut self) -> Result<(u64, u64)> {
+        self.writer.write_u8(TAG_END).await?;
+        self.writer.write_u64(self.commit_count).await?;
+        self.writer.write_u64(self.bookmark_count).await?;
+        self.writer.flush().await?;
+        Ok((self.commit_count, self.bookmark_count))
+    }
+}
+
+/// Reader for commit graph files.
+pub struct GraphReader<R> {
+    reader: R,
+    commit_count: u64,
+    bookmark_count: u64,
+    finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> GraphReader<R> {
+    pub async fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        reader
+            .read_exact(&mut magic)
+            .await
+            .context("Failed to read commit graph header")?;
+        if &magic != MAGIC {
+            bail!("Not a commit graph file");
+        }
+        let version = reader.read_u32().await?;
+        if version != VERSION {
+            bail!(
+                "Unsupported commit graph file version {} (expected {})",
+                version,
+                VERSION
+            );
+        }
+        Ok(GraphReader {
+            reader,
+            commit_count: 0,
+            bookmark_count: 0,
+            finished: false,
+        })
+    }
+
+    async fn read_cs_id(&mut self) -> Result<ChangesetId> {
+        let mut bytes = [0u8; 32];
+        self.reader.read_exact(&mut bytes).await?;
+        ChangesetId::from_bytes(bytes)
+    }
+
+    /// Read the next record, or `None` once the end record has been read.
+    pub async fn next(&mut self) -> Result<Option<GraphRecord>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let tag = self
+            .reader
+            .read_u8()
+            .await
+            .context("Commit graph file is truncated")?;
+        match tag {
+            TAG_COMMIT => {
+                if self.bookmark_count > 0 {
+                    bail!("Commit record found after bookmark records");
+                }
+                let cs_id = self.read_cs_id().await?;
+                let generation = self.reader.read_u64().await?;
+                let parent_count = self.reader.read_u32().await?;
+                let mut parents = Vec::with_capacity(parent_count as usize);
+                for _ in 0..parent_count {
+                    parents.push(self.read_cs_id().await?);
+                }
+                self.commit_count += 1;
+                Ok(Some(GraphRecord::Commit {
+                    cs_id,
+                    generation,
+                    parents,
+                }))
+            }
+            TAG_BOOKMARK => {
+                let name_len = self.reader.read_u32().await?;
+                let mut name = vec![0u8; name_len as usize];
+                self.reader.read_exact(&mut name).await?;
+                let name = BookmarkName::new(String::from_utf8(name)?)?;
+                let kind = match self.reader.read_u8().await? {
+                    KIND_PUBLISHING => BookmarkKind::Publishing,
+                    KIND_PULL_DEFAULT_PUBLISHING => BookmarkKind::PullDefaultPublishing,
+                    kind => bail!("Unknown kind {} for bookmark {}", kind, name),
+                };
+                let cs_id = self.read_cs_id().await?;
+                self.bookmark_count += 1;
+                Ok(Some(GraphRecord::Bookmark { name, kind, cs_id }))
+            }
+            TAG_END => {
+                let commit_count = self.reader.read_u64().await?;
+                let bookmark_count = self.reader.read_u64().await?;
+                if commit_count != self.commit_count || bookmark_count != self.bookmark_count {
+                    bail!(
+                        "Commit graph file is inconsistent: expected {} commits and {} bookmarks, found {} and {}",
+                        commit_count,
+                        bookmark_count,
+                        self.commit_count,
+                        self.bookmark_count
+                    );
+                }
+                self.finished = true;
+                Ok(None)
+            }
+            tag => bail!("Unknown commit graph record type {}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mononoke_types::RepositoryId;
+    use mononoke_types_mocks::changesetid::ONES_CSID;
+    use mononoke_types_mocks::changesetid::THREES_CSID;
+    use mononoke_types_mocks::changesetid::TWOS_CSID;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_roundtrip() -> Result<()> {
+        let mut file = Vec::new();
+        let mut writer = GraphWriter::new(&mut file).await?;
+        let entry = |cs_id, parents, gen| ChangesetEntry {
+            repo_id: RepositoryId::new(0),
+            cs_id,
+            parents,
+            gen,
+        };
+        writer.write_commit(&entry(ONES_CSID, vec![], 1)).await?;
+        writer
+            .write_commit(&entry(TWOS_CSID, vec![ONES_CSID], 2))
+            .await?;
+        writer
+            .write_commit(&entry(THREES_CSID, vec![ONES_CSID, TWOS_CSID], 3))
+            .await?;
+        let main = BookmarkName::new("main")?;
+        writer
+            .write_bookmark(&main, BookmarkKind::PullDefaultPublishing, THREES_CSID)
+            .await?;
+        assert!(
+            writer
+                .write_commit(&entry(THREES_CSID, vec![], 1))
+                .await
+                .is_err()
+        );
+        assert_eq!(writer.finish().await?, (3, 1));
+
+        let mut reader = GraphReader::new(file.as_slice()).await?;
+        let mut records = Vec::new();
+        while let Some(record) = reader.next().await? {
+            records.push(record);
+        }
+        assert_eq!(
+            records,
+            vec![
+                GraphRecord::Commit {
+                    cs_id: ONES_CSID,
+                    generation: 1,
+                    parents: vec![],
+                },
+                GraphRecord::Commit {
+                    cs_id: TWOS_CSID,
+                    generation: 2,
+                    parents: vec![ONES_CSID],
+                },
+                GraphRecord::Commit {
+                    cs_id: THREES_CSID,
+                    generation: 3,
+                    parents: vec![ONES_CSID, TWOS_CSID],
+                },
+                GraphRecord::Bookmark {
+                    name: main,
+                    kind: BookmarkKind::PullDefaultPublishing,
+                    cs_id: THREES_CSID,
+                },
+            ]
+        );
+
+        // Truncated files are detected.
+        let mut reader = GraphReader::new(&file[..file.len() - 40]).await?;
+        let mut result = Ok(None);
+        for _ in 0..5 {
+            result = reader.next().await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.is_err());
+
+        // Files in other formats or versions are rejected.
+        assert!(GraphReader::new(&b"NOTGRAPH\0\0\0\x01"[..]).await.is_err());
+        let mut future_version = file.clone();
+        future_version[11] = 2;
+        assert!(GraphReader::new(future_version.as_slice()).await.is_err());
+
+        Ok(())
+    }
+}
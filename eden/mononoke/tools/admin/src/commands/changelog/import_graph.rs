@@ -0,0 +1,355 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkUpdateReason;
+use bookmarks::BookmarksRef;
+use changesets::ChangesetInsert;
+use changesets::ChangesetsRef;
+use clap::Args;
+use context::CoreContext;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use mononoke_types::ChangesetId;
+use phases::PhasesRef;
+use tokio::io::BufReader;
+
+use super::graph_interchange::GraphReader;
+use super::graph_interchange::GraphRecord;
+use super::Repo;
+
+#[derive(Args)]
+pub struct ChangelogImportGraphArgs {
+    /// File to read the commit graph from, as written by export-graph
+    #[clap(long, parse(from_os_str))]
+    input_file: PathBuf,
+}
+
+/// Number of commits that are read from the file and imported together.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Number of commits that are inserted at a time.
+const IMPORT_CONCURRENCY: usize = 100;
+
+/// A commit read from the file, with its generation number and parents.
+type ImportedCommit = (ChangesetId, u64, Vec<ChangesetId>);
+
+/// Import a batch of commits, whose ancestors outside the batch must already
+/// have been imported.  Returns the number of commits that were added.
+async fn import_commits(
+    ctx: &CoreContext,
+    repo: &Repo,
+    commits: Vec<ImportedCommit>,
+) -> Result<u64> {
+    // Fetch the commits that already exist, and the parents from earlier
+    // batches, in one query.
+    let known = commits
+        .iter()
+        .flat_map(|(cs_id, _, parents)| std::iter::once(*cs_id).chain(parents.iter().copied()))
+        .collect::<HashSet<_>>();
+    let mut generations = repo
+        .changesets()
+        .get_many(ctx.clone(), known.into_iter().collect())
+        .await?
+        .into_iter()
+        .map(|entry| (entry.cs_id, entry.gen))
+        .collect::<HashMap<_, _>>();
+
+    // The generation numbers in the file must match the ones this instance
+    // assigns, as they are used to order the inserts.  Commits with the
+    // same generation number can't be ancestors of each other, so each
+    // generation is inserted concurrently once the previous ones are.
+    let mut to_add: BTreeMap<u64, Vec<ChangesetInsert>> = BTreeMap::new();
+    for (cs_id, generation, parents) in commits.iter() {
+        let mut expected = 1;
+        for parent in parents {
+            let parent_generation = generations.get(parent).with_context(|| {
+                format!(
+                    "Parent {} of commit {} has not been imported",
+                    parent, cs_id
+                )
+            })?;
+            expected = expected.max(parent_generation + 1);
+        }
+        match generations.get(cs_id) {
+            Some(existing) if *existing != *generation => bail!(
+                "Commit {} already exists with generation {}, but the graph has generation {}",
+                cs_id,
+                existing,
+                generation
+            ),
+            Some(_) => {}
+            None if expected != *generation => bail!(
+                "Commit {} has generation {} in the graph, but its parents give it generation {}",
+                cs_id,
+                generation,
+                expected
+            ),
+            None => {
+                generations.insert(*cs_id, *generation);
+                to_add
+                    .entry(*generation)
+                    .or_default()
+                    .push(ChangesetInsert {
+                        cs_id: *cs_id,
+                        parents: parents.clone(),
+                    });
+            }
+        }
+    }
+
+    let mut added = 0;
+    for (_, inserts) in to_add {
+        added += stream::iter(inserts)
+            .map(|insert| async move {
+                let cs_id = insert.cs_id;
+                repo.changesets()
+                    .add(ctx.clone(), insert)
+                    .await
+                    .with_context(|| format!("Failed to import commit {}", cs_id))
+            })
+            .buffer_unordered(IMPORT_CONCURRENCY)
+            .try_fold(
+                0,
+                |count, inserted| async move { Ok(count + inserted as u64) },
+            )
+            .await?;
+    }
+
+    // Only public commits are exported, so they are imported as public.
+    // Their ancestors have been marked as public with earlier batches.
+    repo.phases()
+        .add_public_with_known_public_ancestors(
+            ctx,
+            commits.into_iter().map(|(cs_id, _, _)| cs_id).collect(),
+        )
+        .await
+        .context("Failed to mark imported commits as public")?;
+
+    Ok(added)
+}
+
+pub async fn import_graph(
+    ctx: &CoreContext,
+    repo: &Repo,
+    import_graph_args: ChangelogImportGraphArgs,
+) -> Result<()> {
+    let input_file = import_graph_args.input_file;
+    let file = tokio::fs::File::open(&input_file)
+        .await
+        .with_context(|| format!("Failed to open {}", input_file.display()))?;
+    let mut reader = GraphReader::new(BufReader::new(file)).await?;
+
+    // Commits are written after their parents, so they can be imported in
+    // batches in the order they are read.  Commits that already exist are
+    // skipped, so an interrupted import can be resumed.
+    let mut read = 0;
+    let mut added = 0;
+    let mut batch = Vec::new();
+    let mut bookmarks = Vec::new();
+    while let Some(record) = reader.next().await? {
+        match record {
+            GraphRecord::Commit {
+                cs_id,
+                generation,
+                parents,
+            } => {
+                read += 1;
+                batch.push((cs_id, generation, parents));
+                if batch.len() >= IMPORT_BATCH_SIZE {
+                    added += import_commits(ctx, repo, std::mem::take(&mut batch)).await?;
+                }
+            }
+            GraphRecord::Bookmark { name, kind, cs_id } => bookmarks.push((name, kind, cs_id)),
+        }
+    }
+    if !batch.is_empty() {
+        added += import_commits(ctx, repo, batch).await?;
+    }
+    println!(
+        "Imported {} commits ({} already present)",
+        added,
+        read - added
+    );
+
+    let mut transaction = repo.bookmarks().create_transaction(ctx.clone());
+    let mut created = 0;
+    for (name, kind, cs_id) in bookmarks {
+        match repo.bookmarks().get(ctx.clone(), &name).await? {
+            Some(existing) if existing == cs_id => continue,
+            Some(existing) => bail!(
+                "Bookmark {} already exists at {}, but the graph has it at {}",
+                name,
+                existing,
+                cs_id
+            ),
+            None => {}
+        }
+        match kind {
+            BookmarkKind::Publishing => {
+                transaction.create_publishing(&name, cs_id, BookmarkUpdateReason::ManualMove)?
+            }
+            BookmarkKind::PullDefaultPublishing => {
+                transaction.create(&name, cs_id, BookmarkUpdateReason::ManualMove)?
+            }
+            BookmarkKind::Scratch => bail!("Scratch bookmark {} can't be imported", name),
+        }
+        created += 1;
+    }
+    if !transaction.commit().await? {
+        bail!("Failed to create bookmarks: they were modified concurrently");
+    }
+    println!("Imported {} bookmarks", created);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bonsai_hg_mapping::BonsaiHgMapping;
+    use bookmarks::BookmarkName;
+    use bookmarks::Bookmarks;
+    use changeset_fetcher::ChangesetFetcher;
+    use changesets::ChangesetEntry;
+    use changesets::Changesets;
+    use fbinit::FacebookInit;
+    use filestore::FilestoreConfig;
+    use repo_blobstore::RepoBlobstore;
+    use repo_derived_data::RepoDerivedData;
+    use tests_utils::CreateCommitContext;
+
+    use super::super::graph_interchange::GraphWriter;
+    use super::*;
+
+    #[facet::container]
+    struct TestRepo {
+        #[delegate(
+            dyn BonsaiHgMapping,
+            dyn Bookmarks,
+            dyn Changesets,
+            dyn ChangesetFetcher,
+            RepoBlobstore,
+        )]
+        repo: Repo,
+
+        #[facet]
+        filestore_config: FilestoreConfig,
+
+        #[facet]
+        repo_derived_data: RepoDerivedData,
+    }
+
+    async fn write_graph(
+        entries: &[ChangesetEntry],
+        bookmark: &BookmarkName,
+        cs_id: ChangesetId,
+    ) -> Result<tempfile::NamedTempFile> {
+        let mut graph = Vec::new();
+        let mut writer = GraphWriter::new(&mut graph).await?;
+        for entry in entries {
+            writer.write_commit(entry).await?;
+        }
+        writer
+            .write_bookmark(bookmark, BookmarkKind::PullDefaultPublishing, cs_id)
+            .await?;
+        writer.finish().await?;
+
+        let file = tempfile::NamedTempFile::new()?;
+        tokio::fs::write(file.path(), graph).await?;
+        Ok(file)
+    }
+
+    #[fbinit::test]
+    async fn test_import_graph(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let source: TestRepo = test_repo_factory::build_empty(fb)?;
+        let root = CreateCommitContext::new_root(&ctx, &source)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let left = CreateCommitContext::new(&ctx, &source, vec![root])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        let right = CreateCommitContext::new(&ctx, &source, vec![root])
+            .add_file("c", "c")
+            .commit()
+            .await?;
+        let merge = CreateCommitContext::new(&ctx, &source, vec![left, right])
+            .commit()
+            .await?;
+        let cs_ids = vec![root, left, right, merge];
+        let mut entries = source
+            .repo
+            .changesets()
+            .get_many(ctx.clone(), cs_ids.clone())
+            .await?;
+        entries.sort_by_key(|entry| entry.gen);
+        let main = BookmarkName::new("main")?;
+        let file = write_graph(&entries, &main, merge).await?;
+        let args = || ChangelogImportGraphArgs {
+            input_file: file.path().to_path_buf(),
+        };
+
+        let target: TestRepo = test_repo_factory::build_empty(fb)?;
+        import_graph(&ctx, &target.repo, args()).await?;
+        for entry in &entries {
+            let imported = target
+                .repo
+                .changesets()
+                .get(ctx.clone(), entry.cs_id)
+                .await?
+                .with_context(|| format!("Commit {} was not imported", entry.cs_id))?;
+            assert_eq!(imported.parents, entry.parents);
+            assert_eq!(imported.gen, entry.gen);
+        }
+        assert_eq!(
+            target
+                .repo
+                .phases()
+                .get_public(&ctx, cs_ids.clone(), false)
+                .await?,
+            cs_ids.iter().copied().collect::<HashSet<_>>()
+        );
+        assert_eq!(
+            target.repo.bookmarks().get(ctx.clone(), &main).await?,
+            Some(merge)
+        );
+
+        // Importing the graph again changes nothing, so an interrupted
+        // import can be resumed.
+        import_graph(&ctx, &target.repo, args()).await?;
+
+        // Graphs whose generation numbers don't match their parents are
+        // rejected before anything is imported.
+        let mut wrong_generation = entries.clone();
+        wrong_generation[1].gen += 1;
+        let file = write_graph(&wrong_generation, &main, merge).await?;
+        let other: TestRepo = test_repo_factory::build_empty(fb)?;
+        let result = import_graph(
+            &ctx,
+            &other.repo,
+            ChangelogImportGraphArgs {
+                input_file: file.path().to_path_buf(),
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!other.repo.changesets().exists(&ctx, root).await?);
+
+        Ok(())
+    }
+}
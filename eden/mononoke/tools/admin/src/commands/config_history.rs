@@ -0,0 +1,66 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod list;
+mod record;
+mod show;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use list::ConfigHistoryListArgs;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use record::ConfigHistoryRecordArgs;
+use repo_config_history::RepoConfigHistory;
+use show::ConfigHistoryShowArgs;
+
+/// Record and inspect the history of a repository's configuration
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: ConfigHistorySubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_config_history: dyn RepoConfigHistory,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigHistorySubcommand {
+    /// Record the current configuration, if it has changed
+    Record(ConfigHistoryRecordArgs),
+    /// Show the configuration that was live at a given time
+    Show(ConfigHistoryShowArgs),
+    /// List the versions of the configuration recorded in a time range
+    List(ConfigHistoryListArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        ConfigHistorySubcommand::Record(record_args) => {
+            record::record(&ctx, &app, &args.repo, &repo, record_args).await?
+        }
+        ConfigHistorySubcommand::Show(show_args) => show::show(&ctx, &repo, show_args).await?,
+        ConfigHistorySubcommand::List(list_args) => list::list(&ctx, &repo, list_args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use mononoke_types::DateTime;
+use repo_config_history::RepoConfigHistoryRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ConfigHistoryListArgs {
+    /// List versions recorded from this time
+    /// (either absolute time, or e.g. "2 hours ago").
+    #[clap(long, short = 's')]
+    start_time: DateTime,
+
+    /// List versions recorded until this time
+    /// (either absolute time, or e.g. "2 hours ago").
+    #[clap(long, short = 'e')]
+    end_time: Option<DateTime>,
+
+    /// Maximum number of versions to list
+    #[clap(long, default_value_t = 100)]
+    limit: u64,
+}
+
+pub async fn list(ctx: &CoreContext, repo: &Repo, list_args: ConfigHistoryListArgs) -> Result<()> {
+    let end_time = list_args.end_time.unwrap_or_else(DateTime::now);
+    let versions = repo
+        .repo_config_history()
+        .list_versions(
+            ctx,
+            list_args.start_time.into(),
+            end_time.into(),
+            list_args.limit,
+        )
+        .await?;
+
+    for version in versions {
+        println!(
+            "{}\t{}",
+            version.id,
+            DateTime::from_timestamp(version.recorded_at.timestamp_seconds(), 0)?
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use metaconfig_parser::load_repo_config_snapshots;
+use mononoke_app::args::ConfigArgs;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::Timestamp;
+use repo_config_history::RepoConfigHistoryRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ConfigHistoryRecordArgs {}
+
+pub async fn record(
+    ctx: &CoreContext,
+    app: &MononokeApp,
+    repo_args: &RepoArgs,
+    repo: &Repo,
+    _record_args: ConfigHistoryRecordArgs,
+) -> Result<()> {
+    let (repo_name, _repo_config) = app.repo_config(repo_args.id_or_name()?)?;
+    let config_path = app.args::<ConfigArgs>()?.config_path();
+    let mut snapshots = load_repo_config_snapshots(&config_path, app.config_store())
+        .context("Failed to load raw repo configs")?;
+    let snapshot = snapshots
+        .remove(&repo_name)
+        .with_context(|| format!("No raw config for repo {}", repo_name))?;
+
+    if repo
+        .repo_config_history()
+        .record(ctx, &snapshot, Timestamp::now())
+        .await?
+    {
+        println!("Recorded a new version of the config of {}", repo_name);
+    } else {
+        println!("Config of {} is unchanged", repo_name);
+    }
+
+    Ok(())
+}
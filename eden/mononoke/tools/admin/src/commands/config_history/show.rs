@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use mononoke_types::DateTime;
+use repo_config_history::RepoConfigHistoryRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ConfigHistoryShowArgs {
+    /// Show the configuration that was live at this time
+    /// (either absolute time, or e.g. "2 hours ago").
+    #[clap(long, short = 'a')]
+    at: Option<DateTime>,
+
+    /// Show the whole parsed configuration, rather than just the ACL,
+    /// bookmark and hook configuration.
+    #[clap(long)]
+    full: bool,
+}
+
+pub async fn show(ctx: &CoreContext, repo: &Repo, show_args: ConfigHistoryShowArgs) -> Result<()> {
+    let at = show_args.at.unwrap_or_else(DateTime::now);
+    let version = repo
+        .repo_config_history()
+        .version_at(ctx, at.into())
+        .await?
+        .ok_or_else(|| anyhow!("No config was recorded by {}", at))?;
+    let config = version.config()?;
+
+    println!("Version: {}", version.id);
+    println!(
+        "Recorded at: {}",
+        DateTime::from_timestamp(version.recorded_at.timestamp_seconds(), 0)?
+    );
+    if show_args.full {
+        println!("{:#?}", config);
+    } else {
+        println!("ACL: {:?}", config.hipster_acl);
+        println!("ACL regions: {:#?}", config.acl_region_config);
+        println!("Bookmarks: {:#?}", config.bookmarks);
+        println!("Hooks: {:#?}", config.hooks);
+        println!("Hook manager: {:#?}", config.hook_manager_params);
+    }
+
+    Ok(())
+}
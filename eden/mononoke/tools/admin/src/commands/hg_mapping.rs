@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod rebuild;
+
+use anyhow::Context;
+use anyhow::Result;
+use bonsai_hg_mapping::BonsaiHgMapping;
+use changesets::Changesets;
+use clap::Parser;
+use clap::Subcommand;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use rebuild::HgMappingRebuildArgs;
+use repo_derived_data::RepoDerivedData;
+
+/// Inspect and repair the mapping between bonsai and hg changesets
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: HgMappingSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    bonsai_hg_mapping: dyn BonsaiHgMapping,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    repo_derived_data: RepoDerivedData,
+}
+
+#[derive(Subcommand)]
+pub enum HgMappingSubcommand {
+    /// Rebuild missing mapping entries from the changeset blobs
+    Rebuild(HgMappingRebuildArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = match &args.subcommand {
+        // Replacing mapping entries needs a mapping that overwrites them.
+        HgMappingSubcommand::Rebuild(rebuild_args) if rebuild_args.overwrite => {
+            let (repo_name, repo_config) = app.repo_config(args.repo.id_or_name()?)?;
+            let common_config = app.repo_configs().common.clone();
+            let mut repo_factory = app.repo_factory();
+            repo_factory.with_bonsai_hg_mapping_override();
+            repo_factory
+                .build(repo_name, repo_config, common_config)
+                .await
+        }
+        _ => app.open_repo(&args.repo).await,
+    }
+    .context("Failed to open repo")?;
+
+    match args.subcommand {
+        HgMappingSubcommand::Rebuild(args) => rebuild::rebuild(&ctx, &repo, args).await?,
+    }
+
+    Ok(())
+}
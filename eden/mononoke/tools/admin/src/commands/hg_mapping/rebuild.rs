@@ -0,0 +1,373 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use blobstore::Loadable;
+use bonsai_hg_mapping::BonsaiHgMappingEntry;
+use bonsai_hg_mapping::BonsaiHgMappingRef;
+use cacheblob::MemWritesBlobstore;
+use changesets::ChangesetsRef;
+use clap::Args;
+use context::CoreContext;
+use derived_data_manager::BonsaiDerivable;
+use derived_data_manager::DerivationContext;
+use futures::stream::TryStreamExt;
+use governor::Quota;
+use governor::RateLimiter;
+use mercurial_derived_data::MappedHgChangesetId;
+use mercurial_types::HgChangesetId;
+use mononoke_types::ChangesetId;
+use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedDataRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct HgMappingRebuildArgs {
+    /// Changeset id (from the changesets table) to start from.  Use the
+    /// last id reported by a previous run to resume it.
+    #[clap(long)]
+    start_id: Option<u64>,
+
+    /// Changeset id (from the changesets table) to stop before
+    #[clap(long)]
+    end_id: Option<u64>,
+
+    /// Number of changeset ids to check at a time
+    #[clap(long, default_value_t = 1000)]
+    batch_size: u64,
+
+    /// Maximum number of mapping entries to rebuild per second
+    #[clap(long)]
+    max_per_second: Option<NonZeroU32>,
+
+    /// Only report the changesets with missing or mismatched entries,
+    /// without rebuilding them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Also derive the hg changesets of changesets that have a mapping
+    /// entry again, and report the entries that don't match
+    #[clap(long)]
+    verify: bool,
+
+    /// Replace the mapping entries that don't match the hg changeset derived
+    /// again, including entries of other changesets for the same hg
+    /// changeset.  Without this, such entries are only reported.
+    #[clap(long)]
+    pub(super) overwrite: bool,
+}
+
+/// Derives hg changesets again from bonsai changesets, without storing
+/// mapping entries.  Ancestors that have no mapping entry are derived first,
+/// and everything derived is remembered, so that descendants can be derived
+/// from them even though their entries were never stored (e.g. with
+/// `--dry-run`).
+struct Rederiver {
+    derivation_ctx: DerivationContext,
+    derived: HashMap<ChangesetId, MappedHgChangesetId>,
+}
+
+impl Rederiver {
+    /// Unless `store_blobs` is set, the hg blobs are only written to memory.
+    fn new(repo: &Repo, store_blobs: bool) -> Self {
+        let manager = repo.repo_derived_data().manager();
+        let manager = if store_blobs {
+            manager.clone()
+        } else {
+            manager.with_replaced_blobstore(RepoBlobstore::new_with_wrapped_inner_blobstore(
+                manager.repo_blobstore().clone(),
+                MemWritesBlobstore::new,
+            ))
+        };
+        Rederiver {
+            derivation_ctx: manager.derivation_context(None),
+            derived: HashMap::new(),
+        }
+    }
+
+    async fn rederive(&mut self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<HgChangesetId> {
+        let mut stack = vec![cs_id];
+        while let Some(&cur_id) = stack.last() {
+            if self.derived.contains_key(&cur_id) {
+                stack.pop();
+                continue;
+            }
+            let bonsai = cur_id.load(ctx, self.derivation_ctx.blobstore()).await?;
+            let mut missing = Vec::new();
+            for parent in bonsai.parents() {
+                if !self.derived.contains_key(&parent)
+                    && self
+                        .derivation_ctx
+                        .fetch_derived::<MappedHgChangesetId>(ctx, parent)
+                        .await?
+                        .is_none()
+                {
+                    missing.push(parent);
+                }
+            }
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+            let parents = self
+                .derivation_ctx
+                .fetch_unknown_parents(ctx, Some(&self.derived), &bonsai)
+                .await?;
+            let derived =
+                MappedHgChangesetId::derive_single(ctx, &self.derivation_ctx, bonsai, parents)
+                    .await
+                    .with_context(|| format!("Failed to derive hg changeset for {}", cur_id))?;
+            self.derived.insert(cur_id, derived);
+            stack.pop();
+        }
+        Ok(self.derived[&cs_id].hg_changeset_id())
+    }
+}
+
+/// Rebuild missing entries in the bonsai-hg mapping.
+///
+/// Hg changesets are derived deterministically from bonsai changesets, so
+/// a missing entry can be recovered by deriving the hg changeset again from
+/// the bonsai changeset blob.  The hg changeset is first derived in memory,
+/// and if its id is already mapped to another changeset, the entry is only
+/// rebuilt with `--overwrite`.  Otherwise the same hg blobs that were stored
+/// originally are rewritten, and then the mapping entry, which is used for
+/// lookups in both directions, is stored.
+///
+/// Ancestors without a mapping entry are derived before their descendants,
+/// whether or not they are in the range being rebuilt.  The hg changesets
+/// derived in memory are kept for one batch at a time.
+pub async fn rebuild(ctx: &CoreContext, repo: &Repo, args: HgMappingRebuildArgs) -> Result<()> {
+    let (min_id, max_id) = match repo
+        .changesets()
+        .enumeration_bounds(ctx, true, vec![])
+        .await?
+    {
+        Some(bounds) => bounds,
+        None => {
+            println!("Repo has no changesets");
+            return Ok(());
+        }
+    };
+    let start_id = args.start_id.unwrap_or(min_id).max(min_id);
+    let end_id = args.end_id.unwrap_or(max_id + 1).min(max_id + 1);
+    let batch_size = args.batch_size.max(1);
+    let limiter = args
+        .max_per_second
+        .map(|rate| RateLimiter::direct(Quota::per_second(rate)));
+
+    let mut checked = 0;
+    let mut rebuilt = 0;
+    let mut mismatched = 0;
+    let mut stored_rederiver = Rederiver::new(repo, true);
+    let mut cur_id = start_id;
+    while cur_id < end_id {
+        let mut memory_rederiver = Rederiver::new(repo, false);
+        let batch_end_id = end_id.min(cur_id + batch_size);
+        let cs_ids: Vec<_> = repo
+            .changesets()
+            .list_enumeration_range(ctx, cur_id, batch_end_id, None, true)
+            .map_ok(|(cs_id, _id)| cs_id)
+            .try_collect()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to list changesets with ids [{}, {})",
+                    cur_id, batch_end_id
+                )
+            })?;
+        checked += cs_ids.len();
+
+        let mapped: HashMap<_, _> = repo
+            .bonsai_hg_mapping()
+            .get(ctx, cs_ids.clone().into())
+            .await?
+            .into_iter()
+            .map(|entry| (entry.bcs_id, entry.hg_cs_id))
+            .collect();
+
+        for cs_id in cs_ids {
+            let stored = mapped.get(&cs_id).copied();
+            if stored.is_some() && !args.verify {
+                continue;
+            }
+            if let Some(limiter) = &limiter {
+                limiter.until_ready().await;
+            }
+            let hg_cs_id = memory_rederiver.rederive(ctx, cs_id).await?;
+
+            match stored {
+                Some(stored) if stored == hg_cs_id => continue,
+                Some(stored) => {
+                    println!(
+                        "{} is mapped to {}, but derives to {}",
+                        cs_id, stored, hg_cs_id
+                    );
+                    mismatched += 1;
+                    if !args.overwrite {
+                        continue;
+                    }
+                }
+                None => println!("{} has no hg mapping entry", cs_id),
+            }
+            match repo
+                .bonsai_hg_mapping()
+                .get_bonsai_from_hg(ctx, hg_cs_id)
+                .await?
+            {
+                Some(other) if other != cs_id => {
+                    println!(
+                        "{} derives to {}, which is mapped to {}",
+                        cs_id, hg_cs_id, other
+                    );
+                    mismatched += 1;
+                    if !args.overwrite {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            if args.dry_run {
+                continue;
+            }
+
+            let stored_hg_cs_id = stored_rederiver.rederive(ctx, cs_id).await?;
+            ensure!(
+                stored_hg_cs_id == hg_cs_id,
+                "{} derived to {} and then to {}",
+                cs_id,
+                hg_cs_id,
+                stored_hg_cs_id
+            );
+            repo.bonsai_hg_mapping()
+                .add(
+                    ctx,
+                    BonsaiHgMappingEntry {
+                        hg_cs_id,
+                        bcs_id: cs_id,
+                    },
+                )
+                .await
+                .with_context(|| format!("Failed to rebuild hg mapping entry for {}", cs_id))?;
+            println!("Rebuilt {} -> {}", cs_id, hg_cs_id);
+            rebuilt += 1;
+        }
+
+        // Everything before `batch_end_id` is now done, so a later run can
+        // resume from there.
+        println!("Checked changeset ids up to {}", batch_end_id);
+        cur_id = batch_end_id;
+    }
+
+    if args.dry_run {
+        println!(
+            "Checked {} changesets, {} mismatched mapping entries",
+            checked, mismatched
+        );
+    } else {
+        println!(
+            "Checked {} changesets, {} mismatched mapping entries, rebuilt {} mapping entries",
+            checked, mismatched, rebuilt
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bonsai_hg_mapping::BonsaiHgMappingArc;
+    use changesets::ChangesetsArc;
+    use fbinit::FacebookInit;
+    use repo_blobstore::RepoBlobstoreRef;
+    use repo_derived_data::RepoDerivedDataArc;
+    use tests_utils::BasicTestRepo;
+    use tests_utils::CreateCommitContext;
+
+    use super::*;
+
+    fn args(start_id: Option<u64>, dry_run: bool) -> HgMappingRebuildArgs {
+        HgMappingRebuildArgs {
+            start_id,
+            end_id: None,
+            batch_size: 1000,
+            max_per_second: None,
+            dry_run,
+            verify: false,
+            overwrite: false,
+        }
+    }
+
+    #[fbinit::test]
+    async fn test_rebuild_missing_chain(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let test_repo: BasicTestRepo = test_repo_factory::build_empty(fb)?;
+        let repo = Repo {
+            bonsai_hg_mapping: test_repo.bonsai_hg_mapping_arc(),
+            changesets: test_repo.changesets_arc(),
+            repo_derived_data: test_repo.repo_derived_data_arc(),
+        };
+
+        // Only the root has an hg changeset, so `b` and `c` are missing
+        // their mapping entries.
+        let a = CreateCommitContext::new_root(&ctx, &test_repo)
+            .add_file("a", "a")
+            .commit()
+            .await?;
+        let b = CreateCommitContext::new(&ctx, &test_repo, vec![a])
+            .add_file("b", "b")
+            .commit()
+            .await?;
+        let c = CreateCommitContext::new(&ctx, &test_repo, vec![b])
+            .add_file("c", "c")
+            .commit()
+            .await?;
+        repo.repo_derived_data()
+            .derive::<MappedHgChangesetId>(&ctx, a)
+            .await?;
+        let mapped = |cs_id| {
+            let repo = &repo;
+            let ctx = &ctx;
+            async move {
+                repo.bonsai_hg_mapping()
+                    .get_hg_from_bonsai(ctx, cs_id)
+                    .await
+            }
+        };
+
+        // A dry run derives `c` from `b` in memory, and stores nothing.
+        rebuild(&ctx, &repo, args(None, true)).await?;
+        assert_eq!(mapped(b).await?, None);
+        assert_eq!(mapped(c).await?, None);
+
+        // Rebuilding only `c` derives `b` too, but doesn't store its entry.
+        let (_, max_id) = repo
+            .changesets()
+            .enumeration_bounds(&ctx, true, vec![])
+            .await?
+            .expect("repo has changesets");
+        rebuild(&ctx, &repo, args(Some(max_id), false)).await?;
+        assert_eq!(mapped(b).await?, None);
+        let c_hg = mapped(c).await?.expect("c was not rebuilt");
+
+        // The hg changeset of `c` is the same as the one derived normally.
+        let b_hg = repo
+            .repo_derived_data()
+            .derive::<MappedHgChangesetId>(&ctx, b)
+            .await?
+            .hg_changeset_id();
+        let c_hg_cs = c_hg.load(&ctx, test_repo.repo_blobstore()).await?;
+        assert_eq!(c_hg_cs.p1(), Some(b_hg.into_nodehash()));
+        Ok(())
+    }
+}
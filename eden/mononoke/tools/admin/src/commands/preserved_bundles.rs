@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod download;
+mod list;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use download::PreservedBundlesDownloadArgs;
+use list::PreservedBundlesListArgs;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use preserved_bundles::PreservedBundles;
+use repo_blobstore::RepoBlobstore;
+
+/// List and download the raw bundles preserved from pushes
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: PreservedBundlesSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    preserved_bundles: PreservedBundles,
+}
+
+#[derive(Subcommand)]
+pub enum PreservedBundlesSubcommand {
+    /// List the most recent preserved bundles
+    List(PreservedBundlesListArgs),
+    /// Download the raw bundle2 of a preserved bundle
+    Download(PreservedBundlesDownloadArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        PreservedBundlesSubcommand::List(args) => list::list(&ctx, &repo, args).await?,
+        PreservedBundlesSubcommand::Download(args) => download::download(&ctx, &repo, args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::path::PathBuf;
+
+use anyhow::format_err;
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use preserved_bundles::PreservedBundlesRef;
+use repo_blobstore::RepoBlobstoreRef;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct PreservedBundlesDownloadArgs {
+    /// Id of the preserved bundle to download
+    #[clap(long)]
+    id: u64,
+
+    /// File to write the raw bundle2 to
+    #[clap(long, short = 'o', value_name = "FILE", parse(from_os_str))]
+    output: PathBuf,
+}
+
+pub async fn download(
+    ctx: &CoreContext,
+    repo: &Repo,
+    download_args: PreservedBundlesDownloadArgs,
+) -> Result<()> {
+    let bundle = repo
+        .preserved_bundles()
+        .get(ctx, download_args.id)
+        .await?
+        .ok_or_else(|| format_err!("No preserved bundle with id {}", download_args.id))?;
+    let bytes = repo
+        .preserved_bundles()
+        .fetch(ctx, repo.repo_blobstore(), &bundle)
+        .await?;
+
+    let mut file = File::create(&download_args.output)
+        .await
+        .context("Failed to create output file")?;
+    file.write_all(&bytes)
+        .await
+        .context("Failed to write to output file")?;
+    file.flush().await?;
+
+    println!(
+        "Wrote {} bytes of preserved bundle {} to {}",
+        bytes.len(),
+        bundle.id,
+        download_args.output.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,75 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use clap::ArgGroup;
+use clap::Args;
+use context::CoreContext;
+use itertools::Itertools;
+use mononoke_types::ChangesetId;
+use preserved_bundles::PreservedBundleFilter;
+use preserved_bundles::PreservedBundlesRef;
+
+use super::Repo;
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("filter").args(&["session-id", "user", "changeset"])))]
+pub struct PreservedBundlesListArgs {
+    /// Only list bundles pushed by this session
+    #[clap(long)]
+    session_id: Option<String>,
+
+    /// Only list bundles pushed by this user
+    #[clap(long)]
+    user: Option<String>,
+
+    /// Only list bundles that uploaded this changeset
+    #[clap(long)]
+    changeset: Option<ChangesetId>,
+
+    /// Maximum number of preserved bundles to list
+    #[clap(long, default_value_t = 10)]
+    limit: u64,
+}
+
+impl PreservedBundlesListArgs {
+    fn filter(&self) -> PreservedBundleFilter {
+        if let Some(session_id) = &self.session_id {
+            PreservedBundleFilter::SessionId(session_id.clone())
+        } else if let Some(user) = &self.user {
+            PreservedBundleFilter::User(user.clone())
+        } else if let Some(cs_id) = self.changeset {
+            PreservedBundleFilter::Changeset(cs_id)
+        } else {
+            PreservedBundleFilter::All
+        }
+    }
+}
+
+pub async fn list(
+    ctx: &CoreContext,
+    repo: &Repo,
+    list_args: PreservedBundlesListArgs,
+) -> Result<()> {
+    let bundles = repo
+        .preserved_bundles()
+        .list(ctx, &list_args.filter(), list_args.limit)
+        .await?;
+    for bundle in bundles {
+        println!(
+            "{}\t{}\t{}\t{}\t{} bytes\t{}\t{}",
+            bundle.id,
+            bundle.created_at.timestamp_seconds(),
+            bundle.session_id,
+            bundle.user.as_deref().unwrap_or("-"),
+            bundle.size,
+            bundle.changesets.iter().join(","),
+            bundle.error.as_deref().unwrap_or(""),
+        );
+    }
+    Ok(())
+}
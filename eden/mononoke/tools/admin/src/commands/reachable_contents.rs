@@ -0,0 +1,71 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod check;
+mod update;
+
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::Bookmarks;
+use changesets::Changesets;
+use check::ReachableContentsCheckArgs;
+use clap::Parser;
+use clap::Subcommand;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use reachable_contents::ReachableContents;
+use repo_blobstore::RepoBlobstore;
+use update::ReachableContentsUpdateArgs;
+
+/// Maintain the index of contents reachable from public history
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: ReachableContentsSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    bookmarks: dyn Bookmarks,
+
+    #[facet]
+    changesets: dyn Changesets,
+
+    #[facet]
+    repo_blobstore: RepoBlobstore,
+
+    #[facet]
+    reachable_contents: dyn ReachableContents,
+}
+
+#[derive(Subcommand)]
+pub enum ReachableContentsSubcommand {
+    /// Add the contents reachable from the publishing bookmarks to the index
+    Update(ReachableContentsUpdateArgs),
+    /// Check whether contents are reachable
+    Check(ReachableContentsCheckArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        ReachableContentsSubcommand::Update(args) => update::update(&ctx, &repo, args).await?,
+        ReachableContentsSubcommand::Check(args) => check::check(&ctx, &repo, args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::str::FromStr;
+
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use mononoke_types::ContentId;
+use reachable_contents::ReachableContentsRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ReachableContentsCheckArgs {
+    /// Content ids to check
+    #[clap(required = true)]
+    content_ids: Vec<String>,
+}
+
+pub async fn check(
+    ctx: &CoreContext,
+    repo: &Repo,
+    check_args: ReachableContentsCheckArgs,
+) -> Result<()> {
+    let content_ids = check_args
+        .content_ids
+        .iter()
+        .map(|id| ContentId::from_str(id))
+        .collect::<Result<Vec<_>>>()?;
+    let reachable = repo
+        .reachable_contents()
+        .filter_reachable(ctx, &content_ids)
+        .await?;
+    for content_id in content_ids {
+        let status = if reachable.contains(&content_id) {
+            "reachable"
+        } else {
+            "unreachable"
+        };
+        println!("{} {}", content_id, status);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use clap::Args;
+use context::CoreContext;
+use futures::stream::TryStreamExt;
+use reachable_contents::update_reachable_contents;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct ReachableContentsUpdateArgs {}
+
+pub async fn update(
+    ctx: &CoreContext,
+    repo: &Repo,
+    _update_args: ReachableContentsUpdateArgs,
+) -> Result<()> {
+    let mut heads: Vec<_> = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            u64::MAX,
+        )
+        .map_ok(|(_bookmark, cs_id)| cs_id)
+        .try_collect()
+        .await
+        .context("Failed to list publishing bookmarks")?;
+    heads.sort();
+    heads.dedup();
+
+    let processed = update_reachable_contents(ctx, repo, heads)
+        .await
+        .context("Failed to update reachable contents")?;
+    println!("Processed {} new changesets", processed);
+
+    Ok(())
+}
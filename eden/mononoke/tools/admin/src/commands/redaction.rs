@@ -23,6 +23,7 @@ use metaconfig_types::RepoConfig;
 use mononoke_app::MononokeApp;
 use repo_blobstore::RepoBlobstore;
 use repo_derived_data::RepoDerivedData;
+use repo_identity::RepoIdentity;
 
 /// Manage repository bookmarks
 #[derive(Parser)]
@@ -56,6 +57,9 @@ pub struct Repo {
 
     #[facet]
     repo_derived_data: RepoDerivedData,
+
+    #[facet]
+    repo_identity: RepoIdentity,
 }
 
 #[derive(Subcommand)]
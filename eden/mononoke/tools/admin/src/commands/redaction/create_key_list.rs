@@ -39,6 +39,8 @@ use mononoke_types::MPath;
 use mononoke_types::RedactionKeyList;
 use repo_blobstore::RepoBlobstoreArc;
 use repo_derived_data::RepoDerivedDataRef;
+use repo_update_logger::publish_repo_event;
+use repo_update_logger::RepoEvent;
 
 use super::list::paths_for_content_keys;
 use super::Repo;
@@ -95,7 +97,7 @@ async fn create_key_list(
     app: &MononokeApp,
     keys: Vec<String>,
     output_file: Option<&Path>,
-) -> Result<()> {
+) -> Result<String> {
     let redaction_blobstore = app.redaction_config_blobstore().await?;
     let darkstorm_blobstore = app.redaction_config_blobstore_for_darkstorm().await?;
 
@@ -134,7 +136,7 @@ async fn create_key_list(
                 )
             })?;
     }
-    Ok(())
+    Ok(id1.to_string())
 }
 
 /// Returns the content keys for the given paths.
@@ -249,13 +251,17 @@ pub async fn create_key_list_from_commit_files(
         }
     }
 
-    create_key_list(
+    let keys_count = keys.len();
+    let key_list_id = create_key_list(
         ctx,
         app,
         keys.into_iter().collect(),
         create_args.output_file.as_deref(),
     )
-    .await
+    .await?;
+
+    publish_redaction_added(ctx, &repo, key_list_id, keys_count).await;
+    Ok(())
 }
 
 pub async fn create_key_list_from_blobstore_keys(
@@ -263,11 +269,36 @@ pub async fn create_key_list_from_blobstore_keys(
     app: &MononokeApp,
     create_args: RedactionCreateKeyListFromIdsArgs,
 ) -> Result<()> {
-    create_key_list(
+    let keys_count = create_args.keys.len();
+    let key_list_id = create_key_list(
         ctx,
         app,
         create_args.keys,
         create_args.output_file.as_deref(),
     )
-    .await
+    .await?;
+
+    // Events are published to the repo's subscribers, so there is nothing to
+    // publish to if the blobstore was selected by storage name.
+    let blobstore_args = create_args.repo_blobstore_args;
+    let repo_args = match (blobstore_args.repo_id, blobstore_args.repo_name) {
+        (Some(repo_id), _) => RepoArgs::from_repo_id(repo_id),
+        (None, Some(repo_name)) => RepoArgs::from_repo_name(repo_name),
+        (None, None) => {
+            println!("No repo specified, not publishing the redaction event");
+            return Ok(());
+        }
+    };
+    let repo: Repo = app
+        .open_repo(&repo_args)
+        .await
+        .context("Failed to open repo")?;
+    publish_redaction_added(ctx, &repo, key_list_id, keys_count).await;
+    Ok(())
+}
+
+async fn publish_redaction_added(ctx: &CoreContext, repo: &Repo, key_list_id: String, keys: usize) {
+    publish_repo_event(ctx, repo, RepoEvent::RedactionAdded { key_list_id, keys })
+        .wait()
+        .await;
 }
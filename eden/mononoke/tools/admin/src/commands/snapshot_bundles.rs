@@ -0,0 +1,71 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+mod create;
+mod list;
+
+use anyhow::Context;
+use anyhow::Result;
+use blobrepo::BlobRepo;
+use bookmarks::Bookmarks;
+use clap::Parser;
+use clap::Subcommand;
+use create::SnapshotBundlesCreateArgs;
+use filestore::FilestoreConfig;
+use list::SnapshotBundlesListArgs;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use repo_blobstore::RepoBlobstore;
+use repo_derived_data::RepoDerivedData;
+use skiplist::SkiplistIndex;
+use snapshot_bundles::SnapshotBundles;
+
+/// Create and list the snapshot bundles that clones start from
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    #[clap(subcommand)]
+    subcommand: SnapshotBundlesSubcommand,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[delegate(FilestoreConfig, RepoBlobstore, RepoDerivedData, dyn Bookmarks)]
+    blob_repo: BlobRepo,
+
+    #[facet]
+    skiplist_index: SkiplistIndex,
+
+    #[facet]
+    snapshot_bundles: SnapshotBundles,
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotBundlesSubcommand {
+    /// Create a snapshot bundle of the repo and point clients at it
+    Create(SnapshotBundlesCreateArgs),
+    /// List the most recent snapshot bundles
+    List(SnapshotBundlesListArgs),
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    match args.subcommand {
+        SnapshotBundlesSubcommand::Create(args) => create::create(&ctx, &repo, args).await?,
+        SnapshotBundlesSubcommand::List(args) => list::list(&ctx, &repo, args).await?,
+    }
+
+    Ok(())
+}
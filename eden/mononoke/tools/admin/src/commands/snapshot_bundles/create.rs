@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use bookmarks::BookmarkKind;
+use bookmarks::BookmarkPagination;
+use bookmarks::BookmarkPrefix;
+use bookmarks::BookmarksRef;
+use bookmarks::Freshness;
+use clap::Args;
+use context::CoreContext;
+use filestore::FilestoreConfigRef;
+use filestore::StoreRequest;
+use futures::compat::Stream01CompatExt;
+use futures::stream::TryStreamExt;
+use getbundle_response::create_getbundle_response;
+use getbundle_response::PhasesPart;
+use getbundle_response::SessionLfsParams;
+use mercurial_bundles::create_bundle_stream;
+use mercurial_derived_data::DeriveHgChangeset;
+use reachabilityindex::LeastCommonAncestorsHint;
+use repo_blobstore::RepoBlobstoreRef;
+use skiplist::SkiplistIndexArc;
+use snapshot_bundles::SnapshotBundlesRef;
+use tokio::fs::File;
+use tokio::io::AsyncSeekExt;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct SnapshotBundlesCreateArgs {
+    /// Prefix of the URL clients download the bundle from, which is
+    /// followed by the bundle's content id.  This is usually the download
+    /// endpoint of the repo on the LFS server, e.g.
+    /// `https://lfs.example.com/myrepo/download`.
+    #[clap(long)]
+    url_prefix: String,
+}
+
+pub async fn create(
+    ctx: &CoreContext,
+    repo: &Repo,
+    create_args: SnapshotBundlesCreateArgs,
+) -> Result<()> {
+    let bookmarks: Vec<_> = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            u64::MAX,
+        )
+        .try_collect()
+        .await
+        .context("Failed to list publishing bookmarks")?;
+
+    let mut heads = Vec::with_capacity(bookmarks.len());
+    for (bookmark, cs_id) in bookmarks {
+        let hg_cs_id = repo
+            .derive_hg_changeset(ctx, cs_id)
+            .await
+            .with_context(|| format!("Failed to derive hg changeset for {}", bookmark.name()))?;
+        heads.push(hg_cs_id);
+    }
+    heads.sort();
+    heads.dedup();
+
+    let lca_hint: Arc<dyn LeastCommonAncestorsHint> = repo.skiplist_index_arc();
+    let parts = create_getbundle_response(
+        ctx,
+        &repo.blob_repo,
+        vec![],
+        &heads,
+        &lca_hint,
+        PhasesPart::Yes,
+        &SessionLfsParams { threshold: None },
+        None,
+    )
+    .await
+    .context("Failed to generate bundle")?;
+
+    // The filestore needs to know the size of the bundle before storing
+    // it, and the bundle of a large repo doesn't fit in memory, so it is
+    // spooled to a temporary file first.
+    let mut spool = File::from_std(tempfile::tempfile().context("Failed to create spool file")?);
+    let mut size = 0;
+    let mut chunks = create_bundle_stream(parts, None).compat();
+    while let Some(chunk) = chunks.try_next().await.context("Failed to encode bundle")? {
+        spool
+            .write_all(&chunk)
+            .await
+            .context("Failed to write bundle to spool file")?;
+        size += chunk.len() as u64;
+    }
+    spool.flush().await?;
+    spool.seek(SeekFrom::Start(0)).await?;
+
+    let metadata = filestore::store(
+        repo.repo_blobstore(),
+        *repo.filestore_config(),
+        ctx,
+        &StoreRequest::new(size),
+        ReaderStream::new(spool).map_err(Error::from),
+    )
+    .await
+    .context("Failed to write bundle to filestore")?;
+
+    let url = format!(
+        "{}/{}",
+        create_args.url_prefix.trim_end_matches('/'),
+        metadata.content_id
+    );
+    repo.snapshot_bundles()
+        .add(ctx, metadata.content_id, size, &url)
+        .await
+        .context("Failed to record snapshot bundle")?;
+
+    println!(
+        "Created snapshot bundle of {} heads: {} ({} bytes)",
+        heads.len(),
+        url,
+        size
+    );
+
+    Ok(())
+}
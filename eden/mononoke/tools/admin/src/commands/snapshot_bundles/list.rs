@@ -0,0 +1,38 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use clap::Args;
+use context::CoreContext;
+use snapshot_bundles::SnapshotBundlesRef;
+
+use super::Repo;
+
+#[derive(Args)]
+pub struct SnapshotBundlesListArgs {
+    /// Maximum number of snapshot bundles to list
+    #[clap(long, default_value_t = 10)]
+    limit: u64,
+}
+
+pub async fn list(
+    ctx: &CoreContext,
+    repo: &Repo,
+    list_args: SnapshotBundlesListArgs,
+) -> Result<()> {
+    for bundle in repo.snapshot_bundles().list(ctx, list_args.limit).await? {
+        println!(
+            "{}\t{}\t{}\t{} bytes\t{}",
+            bundle.id,
+            bundle.created_at.timestamp_seconds(),
+            bundle.content_id,
+            bundle.size,
+            bundle.url,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use mononoke_app::args::RepoArgs;
+use mononoke_app::MononokeApp;
+use mononoke_types::DateTime;
+use usage_attribution::Usage;
+use usage_attribution::UsageAttribution;
+use usage_attribution::UsageAttributionRef;
+
+/// Show how much load each user or service has put on a repository
+#[derive(Parser)]
+pub struct CommandArgs {
+    #[clap(flatten)]
+    repo: RepoArgs,
+
+    /// Show usage starting at this time
+    /// (either absolute time, or e.g. "2 hours ago").
+    #[clap(long, short = 's')]
+    start_time: DateTime,
+
+    /// Show usage ending at this time
+    /// (either absolute time, or e.g. "2 hours ago").
+    #[clap(long, short = 'e')]
+    end_time: Option<DateTime>,
+
+    /// Show the total usage of each identity over the whole range, rather
+    /// than the usage in each period.
+    #[clap(long)]
+    total: bool,
+}
+
+#[facet::container]
+pub struct Repo {
+    #[facet]
+    usage_attribution: dyn UsageAttribution,
+}
+
+fn print_usage(period: &str, identity: &str, usage: &Usage) {
+    println!(
+        "{}\t{}\tsessions={}\tcommands={}\tbytes_served={}\tmanifests_fetched={}",
+        period,
+        identity,
+        usage.sessions,
+        usage.commands,
+        usage.bytes_served,
+        usage.manifests_fetched,
+    );
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
+    let ctx = app.new_basic_context();
+
+    let repo: Repo = app
+        .open_repo(&args.repo)
+        .await
+        .context("Failed to open repo")?;
+
+    let end_time = args.end_time.unwrap_or_else(DateTime::now);
+    let rollups = repo
+        .usage_attribution()
+        .list_usage(&ctx, args.start_time.into(), end_time.into())
+        .await?;
+
+    if args.total {
+        let mut totals: BTreeMap<String, Usage> = BTreeMap::new();
+        for rollup in rollups {
+            *totals.entry(rollup.identity).or_default() += rollup.usage;
+        }
+        for (identity, usage) in totals {
+            print_usage("total", &identity, &usage);
+        }
+    } else {
+        for rollup in rollups {
+            let period = DateTime::from_timestamp(rollup.period_start.timestamp_seconds(), 0)?;
+            print_usage(&period.to_string(), &rollup.identity, &rollup.usage);
+        }
+    }
+
+    Ok(())
+}
@@ -109,6 +109,9 @@ pub struct MononokeTunables {
     disable_hooks_on_plain_push: AtomicBool,
     run_hooks_on_additional_changesets: AtomicBool,
     hooks_additional_changesets_limit: AtomicI64,
+    // Runtime kill switch for individual hooks. Disabled hooks are not run,
+    // and the hook's failure policy decides whether the push is accepted.
+    disabled_hooks: TunableVecOfStringsByRepo,
     // SCS scuba sampling knobs
     scs_popular_methods_sampling_rate: AtomicI64,
     scs_other_methods_sampling_rate: AtomicI64,
@@ -120,7 +123,29 @@ pub struct MononokeTunables {
     getbundle_high_low_gen_num_difference_threshold: AtomicI64,
     getbundle_low_gen_optimization_max_traversal_limit: AtomicI64,
     getbundle_partial_getbundle_traversal_limit: AtomicI64,
+    // Walk heads and common one generation at a time, instead of using the
+    // DifferenceOfUnionsOfAncestors revset
+    getbundle_use_generation_aware_traversal: AtomicBool,
+    // Share the response to identical concurrent getbundle requests between
+    // sessions, rather than computing it for each of them
+    getbundle_share_concurrent_requests: AtomicBool,
+    // How much of a shared getbundle response is kept for sessions that
+    // attach late.  0 means the default of 256 MiB.
+    getbundle_shared_max_buffered_bytes: AtomicI64,
     repo_client_bookmarks_timeout_secs: AtomicI64,
+    // How many keys listkeys fetches from the store at a time.  0 means the
+    // default of 1000.
+    listkeys_batch_size: AtomicI64,
+    // Fail listkeys for namespaces with more keys than this, rather than
+    // building an unbounded response.  0 means the default of 100000.
+    listkeys_max_keys: AtomicI64,
+    // Clients reporting a version older than this are warned, or rejected
+    // if repo_client_reject_old_versions is set.  Empty disables the check.
+    repo_client_min_client_version: TunableString,
+    repo_client_reject_old_versions: AtomicBool,
+    // Appended to the message shown to clients that are too old, to tell
+    // them how to upgrade.
+    repo_client_upgrade_instructions: TunableString,
     repo_client_clone_timeout_secs: AtomicI64,
     repo_client_default_timeout_secs: AtomicI64,
     repo_client_getbundle_timeout_secs: AtomicI64,
@@ -131,6 +156,9 @@ pub struct MononokeTunables {
     repo_client_gettreepack_buffer_size: AtomicI64,
     derived_data_slow_derivation_threshold_secs: AtomicI64,
     disable_running_hooks_in_pushredirected_repo: AtomicBool,
+    // Commit hash prefixes shorter than this are rejected by SCS, as they
+    // are unlikely to resolve to a single commit.  0 disables the check.
+    scs_min_commit_prefix_length: AtomicI64,
     scs_request_read_qps: AtomicI64,
     scs_request_write_qps: AtomicI64,
     enable_logging_commit_rewrite_data: AtomicBool,
@@ -337,10 +365,52 @@ pub struct MononokeTunables {
     // Override author check during squashing
     megarepo_override_author_check: TunableBoolByRepo,
 
+    // Preserve the raw bundle2 of every push to the repo, so that failed
+    // pushes can be inspected and replayed
+    preserve_raw_bundle2: TunableBoolByRepo,
+
     // Disable SQL queries being retried after admission control errors
     disable_sql_auto_retries: AtomicBool,
+    // Number of attempts for SQL queries that fail with retryable errors.
+    // 0 uses the default.
+    sql_retry_max_attempts: AtomicI64,
     // Disable SQL queries being cached using `cacheable` keyword
     disable_sql_auto_cache: AtomicBool,
+
+    // Record SHA-256 aliases for Mercurial changesets when they are stored,
+    // and advertise SHA-256 node ids to clients that ask for them
+    hg_sha256_nodes_enabled: AtomicBool,
+
+    // Store Mercurial manifests with at least this many entries in shards.
+    // 0 disables sharding.
+    hg_manifest_shard_min_entries: AtomicI64,
+
+    // Shape the bytes read from a client once its session has sent more than
+    // the threshold, so that large uploads can't saturate the network
+    // interface. 0 disables shaping.
+    ingress_shaping_bytes_per_sec: AtomicI64,
+    ingress_shaping_burst_bytes: AtomicI64,
+    ingress_shaping_threshold_bytes: AtomicI64,
+
+    // Trace one in this many wireproto commands, logging their blobstore
+    // operations as spans.  0 disables tracing.
+    wireproto_trace_sampling_rate: AtomicI64,
+
+    // Memoize up to this many bytes of the blobs fetched during a session, so
+    // that fetching the same blob again within a request doesn't go to the
+    // blobstore.  0 disables the memo.
+    session_blob_memo_max_bytes: AtomicI64,
+
+    // Acknowledge prefetch hints from clients without prefetching anything.
+    disable_prefetch_hints: AtomicBool,
+
+    // How often stats updated through batched_stats are flushed from the
+    // per-thread buffers to the exported stats.  0 disables batching.
+    batched_stats_flush_interval_ms: AtomicI64,
+
+    // How long caching blobstores remember that a key is missing from the
+    // backing store.  0 disables the negative cache.
+    blobstore_negative_cache_ttl_ms: AtomicI64,
 }
 
 fn log_tunables(tunables: &TunablesStruct) -> String {
@@ -0,0 +1,17 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS usage_attribution (
+  repo_id INT UNSIGNED NOT NULL,
+  period_start BIGINT NOT NULL,
+  identity VARCHAR(255) NOT NULL,
+  sessions BIGINT UNSIGNED NOT NULL,
+  commands BIGINT UNSIGNED NOT NULL,
+  bytes_served BIGINT UNSIGNED NOT NULL,
+  manifests_fetched BIGINT UNSIGNED NOT NULL,
+  PRIMARY KEY (repo_id, period_start, identity)
+);
@@ -0,0 +1,268 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Usage attribution records how much load each user or service puts on a
+//! repository, so that capacity can be attributed to the teams that use it.
+//!
+//! The usage of each session is aggregated in memory by identity, and the
+//! aggregate is periodically added to hourly rollups stored in the metadata
+//! database.
+
+use std::collections::HashMap;
+use std::mem;
+use std::ops::AddAssign;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use context::CoreContext;
+use context::PerfCounterType;
+use mononoke_types::RepositoryId;
+use mononoke_types::Timestamp;
+use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use sql_ext::mononoke_queries;
+use sql_ext::SqlConnections;
+
+/// Length of the period covered by each rollup.
+pub const ROLLUP_PERIOD_SECS: i64 = 3600;
+
+/// How often aggregated usage is written to the database.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Load put on a repository.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    pub sessions: u64,
+    pub commands: u64,
+    pub bytes_served: u64,
+    pub manifests_fetched: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.sessions += other.sessions;
+        self.commands += other.commands;
+        self.bytes_served += other.bytes_served;
+        self.manifests_fetched += other.manifests_fetched;
+    }
+}
+
+/// Usage by one identity over one rollup period.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageRollup {
+    pub period_start: Timestamp,
+    pub identity: String,
+    pub usage: Usage,
+}
+
+#[facet::facet]
+#[async_trait]
+pub trait UsageAttribution: Send + Sync {
+    /// Record usage by an identity.  This is aggregated in memory, and
+    /// only written out periodically.
+    async fn record(&self, ctx: &CoreContext, identity: &str, usage: Usage) -> Result<()>;
+
+    /// Write out any usage that has been recorded but not yet stored.
+    async fn flush(&self, ctx: &CoreContext) -> Result<()>;
+
+    /// List the stored rollups for periods starting between `min_period`
+    /// and `max_period` (inclusive), ordered by period and identity.
+    async fn list_usage(
+        &self,
+        ctx: &CoreContext,
+        min_period: Timestamp,
+        max_period: Timestamp,
+    ) -> Result<Vec<UsageRollup>>;
+}
+
+mononoke_queries! {
+    write AddUsage(values: (
+        repo_id: RepositoryId,
+        period_start: i64,
+        identity: String,
+        sessions: u64,
+        commands: u64,
+        bytes_served: u64,
+        manifests_fetched: u64,
+    )) {
+        none,
+        mysql(
+            "INSERT INTO usage_attribution
+                (repo_id, period_start, identity, sessions, commands, bytes_served, manifests_fetched)
+            VALUES {values}
+            ON DUPLICATE KEY UPDATE
+                sessions = sessions + VALUES(sessions),
+                commands = commands + VALUES(commands),
+                bytes_served = bytes_served + VALUES(bytes_served),
+                manifests_fetched = manifests_fetched + VALUES(manifests_fetched)"
+        )
+        sqlite(
+            "INSERT INTO usage_attribution
+                (repo_id, period_start, identity, sessions, commands, bytes_served, manifests_fetched)
+            VALUES {values}
+            ON CONFLICT(repo_id, period_start, identity) DO UPDATE SET
+                sessions = sessions + excluded.sessions,
+                commands = commands + excluded.commands,
+                bytes_served = bytes_served + excluded.bytes_served,
+                manifests_fetched = manifests_fetched + excluded.manifests_fetched"
+        )
+    }
+
+    read ListUsage(repo_id: RepositoryId, min_period: i64, max_period: i64) -> (
+        i64, String, u64, u64, u64, u64
+    ) {
+        "SELECT period_start, identity, sessions, commands, bytes_served, manifests_fetched
+        FROM usage_attribution
+        WHERE repo_id = {repo_id}
+          AND period_start >= {min_period}
+          AND period_start <= {max_period}
+        ORDER BY period_start, identity"
+    }
+}
+
+struct PendingUsage {
+    usage: HashMap<(i64, String), Usage>,
+    last_flush: Instant,
+}
+
+pub struct SqlUsageAttribution {
+    repo_id: RepositoryId,
+    connections: SqlConnections,
+    pending: Mutex<PendingUsage>,
+}
+
+pub struct SqlUsageAttributionBuilder {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlUsageAttributionBuilder {
+    const LABEL: &'static str = "usage_attribution";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-usage-attribution.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlUsageAttributionBuilder {}
+
+impl SqlUsageAttributionBuilder {
+    pub fn build(self, repo_id: RepositoryId) -> SqlUsageAttribution {
+        SqlUsageAttribution {
+            repo_id,
+            connections: self.connections,
+            pending: Mutex::new(PendingUsage {
+                usage: HashMap::new(),
+                last_flush: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl SqlUsageAttribution {
+    fn take_pending(&self) -> HashMap<(i64, String), Usage> {
+        let mut pending = self.pending.lock().expect("lock poisoned");
+        pending.last_flush = Instant::now();
+        mem::take(&mut pending.usage)
+    }
+
+    async fn write(&self, ctx: &CoreContext, usage: HashMap<(i64, String), Usage>) -> Result<()> {
+        if usage.is_empty() {
+            return Ok(());
+        }
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlWrites);
+        let rows = usage
+            .iter()
+            .map(|((period_start, identity), usage)| {
+                (
+                    &self.repo_id,
+                    period_start,
+                    identity,
+                    &usage.sessions,
+                    &usage.commands,
+                    &usage.bytes_served,
+                    &usage.manifests_fetched,
+                )
+            })
+            .collect::<Vec<_>>();
+        let result = AddUsage::query(&self.connections.write_connection, &rows[..]).await;
+        if result.is_err() {
+            // Keep the usage so that it is retried on the next flush.
+            let mut pending = self.pending.lock().expect("lock poisoned");
+            for (key, usage) in usage {
+                *pending.usage.entry(key).or_default() += usage;
+            }
+        }
+        result?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UsageAttribution for SqlUsageAttribution {
+    async fn record(&self, ctx: &CoreContext, identity: &str, usage: Usage) -> Result<()> {
+        let now = Timestamp::now().timestamp_seconds();
+        let period_start = now - now.rem_euclid(ROLLUP_PERIOD_SECS);
+        let to_write = {
+            let mut pending = self.pending.lock().expect("lock poisoned");
+            *pending
+                .usage
+                .entry((period_start, identity.to_string()))
+                .or_default() += usage;
+            if pending.last_flush.elapsed() < FLUSH_INTERVAL {
+                return Ok(());
+            }
+            pending.last_flush = Instant::now();
+            mem::take(&mut pending.usage)
+        };
+        self.write(ctx, to_write).await
+    }
+
+    async fn flush(&self, ctx: &CoreContext) -> Result<()> {
+        let to_write = self.take_pending();
+        self.write(ctx, to_write).await
+    }
+
+    async fn list_usage(
+        &self,
+        ctx: &CoreContext,
+        min_period: Timestamp,
+        max_period: Timestamp,
+    ) -> Result<Vec<UsageRollup>> {
+        ctx.perf_counters()
+            .increment_counter(PerfCounterType::SqlReadsReplica);
+        let rows = ListUsage::query(
+            &self.connections.read_connection,
+            &self.repo_id,
+            &min_period.timestamp_seconds(),
+            &max_period.timestamp_seconds(),
+        )
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(period_start, identity, sessions, commands, bytes_served, manifests_fetched)| {
+                    UsageRollup {
+                        period_start: Timestamp::from_timestamp_secs(period_start),
+                        identity,
+                        usage: Usage {
+                            sessions,
+                            commands,
+                            bytes_served,
+                            manifests_fetched,
+                        },
+                    }
+                },
+            )
+            .collect())
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use mononoke_types::Timestamp;
+use mononoke_types_mocks::repo::REPO_ZERO;
+use sql_construct::SqlConstruct;
+use usage_attribution::SqlUsageAttributionBuilder;
+use usage_attribution::Usage;
+use usage_attribution::UsageAttribution;
+use usage_attribution::ROLLUP_PERIOD_SECS;
+
+fn usage(commands: u64, bytes_served: u64) -> Usage {
+    Usage {
+        sessions: 1,
+        commands,
+        bytes_served,
+        manifests_fetched: 0,
+    }
+}
+
+#[fbinit::test]
+async fn test_usage_rollups(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    let usage_attribution = SqlUsageAttributionBuilder::with_sqlite_in_memory()?.build(REPO_ZERO);
+
+    usage_attribution
+        .record(&ctx, "user:alice", usage(2, 100))
+        .await?;
+    usage_attribution
+        .record(&ctx, "service:bot", usage(5, 1000))
+        .await?;
+    usage_attribution
+        .record(&ctx, "user:alice", usage(1, 50))
+        .await?;
+
+    let all_time = (
+        Timestamp::from_timestamp_secs(0),
+        Timestamp::from_timestamp_secs(i64::MAX / 1_000_000_000),
+    );
+
+    // Nothing is stored until the usage is flushed.
+    assert!(
+        usage_attribution
+            .list_usage(&ctx, all_time.0, all_time.1)
+            .await?
+            .is_empty()
+    );
+
+    usage_attribution.flush(&ctx).await?;
+    let rollups = usage_attribution
+        .list_usage(&ctx, all_time.0, all_time.1)
+        .await?;
+    assert_eq!(rollups.len(), 2);
+    assert_eq!(rollups[0].identity, "service:bot");
+    assert_eq!(rollups[0].usage, usage(5, 1000));
+    assert_eq!(rollups[1].identity, "user:alice");
+    assert_eq!(
+        rollups[1].usage,
+        Usage {
+            sessions: 2,
+            commands: 3,
+            bytes_served: 150,
+            manifests_fetched: 0,
+        }
+    );
+    assert_eq!(
+        rollups[0].period_start.timestamp_seconds() % ROLLUP_PERIOD_SECS,
+        0
+    );
+
+    // Later flushes add to the existing rollups.
+    usage_attribution
+        .record(&ctx, "service:bot", usage(1, 1))
+        .await?;
+    usage_attribution.flush(&ctx).await?;
+    let rollups = usage_attribution
+        .list_usage(&ctx, all_time.0, all_time.1)
+        .await?;
+    assert_eq!(rollups[0].usage.sessions, 2);
+    assert_eq!(rollups[0].usage.bytes_served, 1001);
+
+    Ok(())
+}
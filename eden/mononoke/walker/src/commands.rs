@@ -20,6 +20,8 @@ pub const SCRUB: &str = "scrub";
 pub const COMPRESSION_BENEFIT: &str = "compression-benefit";
 pub const VALIDATE: &str = "validate";
 pub const CORPUS: &str = "corpus";
+pub const EXPORT: &str = "export";
+pub const MANIFEST_STATS: &str = "manifest-stats";
 
 // Per repo things we don't pass into the walk
 #[derive(Clone)]
@@ -48,6 +50,8 @@ pub struct JobParams {
 mononoke_app::subcommands! {
     mod compression_benefit;
     mod corpus;
+    mod export;
+    mod manifest_stats;
     mod scrub;
     mod validate;
 }
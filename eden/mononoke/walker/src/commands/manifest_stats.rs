@@ -0,0 +1,237 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Error;
+use async_trait::async_trait;
+use clap::Parser;
+use executor_lib::RepoShardedProcess;
+use executor_lib::RepoShardedProcessExecutor;
+use executor_lib::ShardedProcessExecutor;
+use fbinit::FacebookInit;
+use mononoke_app::args::MultiRepoArgs;
+use mononoke_app::MononokeApp;
+use once_cell::sync::OnceCell;
+use slog::info;
+use slog::Logger;
+
+use crate::args::WalkerCommonArgs;
+use crate::commands::JobParams;
+use crate::commands::MANIFEST_STATS;
+use crate::detail::manifest_stats::manifest_stats;
+use crate::detail::manifest_stats::ManifestStatsCommand;
+use crate::setup::setup_common;
+use crate::WalkerArgs;
+
+const SM_SERVICE_SCOPE: &str = "global";
+const SM_CLEANUP_TIMEOUT_SECS: u64 = 120;
+
+/// Compute the distributions of hg manifest fan-out, depth and entry name
+/// lengths, for capacity planning.
+#[derive(Parser)]
+pub struct CommandArgs {
+    /// Where to write the reports. Each repo is written to its own
+    /// subdirectory, with one report per run.
+    #[clap(long)]
+    pub output_dir: String,
+
+    #[clap(flatten)]
+    pub common_args: WalkerCommonArgs,
+}
+
+/// Struct representing the Walker Manifest Stats BP.
+pub struct WalkerManifestStatsProcess {
+    app: MononokeApp,
+    args: CommandArgs,
+}
+
+impl WalkerManifestStatsProcess {
+    fn new(app: MononokeApp, args: CommandArgs) -> Self {
+        Self { app, args }
+    }
+}
+
+#[async_trait]
+impl RepoShardedProcess for WalkerManifestStatsProcess {
+    async fn setup(&self, repo_name: &str) -> anyhow::Result<Arc<dyn RepoShardedProcessExecutor>> {
+        let logger = self.app.repo_logger(repo_name);
+        info!(
+            &logger,
+            "Setting up walker manifest stats for repo {}", repo_name
+        );
+        let repos = MultiRepoArgs {
+            repo_name: vec![repo_name.to_string()],
+            repo_id: vec![],
+        };
+        let (job_params, command) = setup_manifest_stats(&repos, &self.app, &self.args)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failure in setting up walker manifest stats for repo {}",
+                    &repo_name
+                )
+            })?;
+        info!(
+            &logger,
+            "Completed walker manifest stats setup for repo {}", repo_name
+        );
+        Ok(Arc::new(WalkerManifestStatsProcessExecutor::new(
+            self.app.fb,
+            logger,
+            job_params,
+            command,
+            repo_name.to_string(),
+        )))
+    }
+}
+
+/// Struct representing the execution of Walker Manifest Stats
+/// BP over the context of a provided repo.
+pub struct WalkerManifestStatsProcessExecutor {
+    fb: FacebookInit,
+    logger: Logger,
+    job_params: JobParams,
+    command: ManifestStatsCommand,
+    cancellation_requested: Arc<AtomicBool>,
+    repo_name: String,
+}
+
+impl WalkerManifestStatsProcessExecutor {
+    fn new(
+        fb: FacebookInit,
+        logger: Logger,
+        job_params: JobParams,
+        command: ManifestStatsCommand,
+        repo_name: String,
+    ) -> Self {
+        Self {
+            cancellation_requested: Arc::new(AtomicBool::new(false)),
+            fb,
+            logger,
+            job_params,
+            command,
+            repo_name,
+        }
+    }
+}
+
+#[async_trait]
+impl RepoShardedProcessExecutor for WalkerManifestStatsProcessExecutor {
+    async fn execute(&self) -> anyhow::Result<()> {
+        info!(
+            self.logger,
+            "Initiating walker manifest stats execution for repo {}", &self.repo_name,
+        );
+        manifest_stats(
+            self.fb,
+            self.job_params.clone(),
+            self.command.clone(),
+            Arc::clone(&self.cancellation_requested),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Error while executing walker manifest stats execution for repo {}",
+                &self.repo_name
+            )
+        })
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        info!(
+            self.logger,
+            "Terminating walker manifest stats execution for repo {}", &self.repo_name,
+        );
+        self.cancellation_requested.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+async fn setup_manifest_stats(
+    repos: &MultiRepoArgs,
+    app: &MononokeApp,
+    args: &CommandArgs,
+) -> Result<(JobParams, ManifestStatsCommand), Error> {
+    let CommandArgs {
+        output_dir,
+        common_args,
+    } = args;
+
+    let repo_name = repos.repo_name.clone().pop();
+    let logger = match repo_name {
+        Some(repo_name) => app.repo_logger(&repo_name),
+        None => app.logger().clone(),
+    };
+    let job_params = setup_common(
+        MANIFEST_STATS,
+        app,
+        repos,
+        common_args,
+        None, // blobstore sampler
+        None, // blobstore component sampler
+        &logger,
+    )
+    .await?;
+
+    let command = ManifestStatsCommand {
+        output_dir: output_dir.clone(),
+    };
+
+    Ok((job_params, command))
+}
+
+pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<(), Error> {
+    let walker_args = &app.args::<WalkerArgs>()?;
+    match &walker_args.sharded_service_name {
+        Some(service_name) => run_sharded(app, args, service_name.to_string()).await,
+        None => run_unsharded(&walker_args.repos, app, args).await,
+    }
+}
+
+/// The run variant for sharded execution of walker manifest stats.
+pub async fn run_sharded(
+    app: MononokeApp,
+    args: CommandArgs,
+    service_name: String,
+) -> Result<(), Error> {
+    let manifest_stats_process = WalkerManifestStatsProcess::new(app, args);
+    let logger = manifest_stats_process.app.logger().clone();
+    // The service name needs to be 'static to satisfy SM contract
+    static SM_SERVICE_NAME: OnceCell<String> = OnceCell::new();
+    let mut executor = ShardedProcessExecutor::new(
+        manifest_stats_process.app.fb,
+        manifest_stats_process.app.runtime().clone(),
+        &logger,
+        SM_SERVICE_NAME.get_or_init(|| service_name),
+        SM_SERVICE_SCOPE,
+        SM_CLEANUP_TIMEOUT_SECS,
+        Arc::new(manifest_stats_process),
+        true, // enable shard (repo) level healing
+    )?;
+    executor.block_and_execute(&logger).await
+}
+
+pub async fn run_unsharded(
+    repos: &MultiRepoArgs,
+    app: MononokeApp,
+    args: CommandArgs,
+) -> Result<(), Error> {
+    let (job_params, command) = setup_manifest_stats(repos, &app, &args).await?;
+    // When running in unsharded setting, walker manifest stats doesn't need to
+    // be cancelled midway.
+    manifest_stats(
+        app.fb,
+        job_params,
+        command,
+        Arc::new(AtomicBool::new(false)),
+    )
+    .await
+}
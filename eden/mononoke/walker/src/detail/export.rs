@@ -0,0 +1,422 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Export of file contents for external indexing pipelines.
+//!
+//! Each file content reached by the walk is written as a record of
+//! (path, commit, generation, content id, size, blob) to one of a fixed number of
+//! partition files, chosen by content id so that partitions are of similar
+//! size and can be processed in parallel.  Records are CBOR encoded and
+//! prefixed with their length as a big-endian u32.
+//!
+//! In incremental mode, only contents reached from commits with a higher
+//! generation number than the watermark left by the previous export are
+//! written.  Commit times aren't used for this, as they are set by the
+//! author and needn't increase as commits land.  For this to be
+//! useful the walk should step from changesets directly to the file
+//! contents they change, so that each content is reached from the commit
+//! that introduced it rather than from every commit containing it.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::Context;
+use anyhow::Error;
+use blobrepo::BlobRepo;
+use bytes::Bytes;
+use bytes::BytesMut;
+use cloned::cloned;
+use context::CoreContext;
+use context::SamplingKey;
+use fbinit::FacebookInit;
+use futures::future;
+use futures::future::try_join_all;
+use futures::future::FutureExt;
+use futures::stream::Stream;
+use futures::stream::TryStreamExt;
+use maplit::hashset;
+use mononoke_types::datetime::DateTime;
+use mononoke_types::ChangesetId;
+use mononoke_types::ContentId;
+use regex::Regex;
+use serde::Serialize;
+use slog::info;
+use tokio::fs::{self as tkfs};
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufWriter;
+use tokio::sync::Mutex;
+
+use crate::commands::JobParams;
+use crate::commands::JobWalkParams;
+use crate::commands::RepoSubcommandParams;
+use crate::commands::EXPORT;
+use crate::detail::graph::FileContentData;
+use crate::detail::graph::Node;
+use crate::detail::graph::NodeData;
+use crate::detail::graph::NodeType;
+use crate::detail::graph::WrappedPath;
+use crate::detail::progress::progress_stream;
+use crate::detail::progress::report_state;
+use crate::detail::progress::ProgressOptions;
+use crate::detail::progress::ProgressReporter;
+use crate::detail::progress::ProgressStateCountByType;
+use crate::detail::progress::ProgressStateMutex;
+use crate::detail::sampling::PathTrackingRoute;
+use crate::detail::sampling::SampleTrigger;
+use crate::detail::sampling::SamplingOptions;
+use crate::detail::sampling::SamplingWalkVisitor;
+use crate::detail::sampling::WalkKeyOptPath;
+use crate::detail::sampling::WalkPayloadMtime;
+use crate::detail::scrub::ScrubStats;
+use crate::detail::tail::walk_exact_tail;
+use crate::detail::walk::RepoWalkParams;
+use crate::detail::walk::RepoWalkTypeParams;
+
+// Generation number of the newest commit contents were exported from
+const WATERMARK_FILE: &str = "watermark";
+
+/// A single exported file content.
+#[derive(Debug, Serialize)]
+pub struct ExportRecord {
+    /// Repo path the content was reached by, if any.
+    pub path: Option<Bytes>,
+    /// Last bonsai changeset stepped through to reach the content.
+    pub commit: Option<String>,
+    /// Commit time of that changeset, in seconds since the epoch.
+    pub commit_time: Option<i64>,
+    /// Generation number of that changeset.
+    pub generation: Option<u64>,
+    pub content_id: String,
+    pub size: u64,
+    pub blob: Bytes,
+}
+
+/// Writes records for one repo into partition files under
+/// `<output_dir>/<repo>/<run start>/`, and maintains the watermark in
+/// `<output_dir>/<repo>/`.
+#[derive(Debug)]
+pub struct ExportSink {
+    run_dir: PathBuf,
+    watermark_path: PathBuf,
+    partitions: Vec<Mutex<Option<BufWriter<tkfs::File>>>>,
+    since: Option<u64>,
+    // Generation numbers start at 1, so 0 means nothing has been exported
+    latest: AtomicU64,
+}
+
+impl ExportSink {
+    pub async fn open(
+        repo_dir: PathBuf,
+        num_partitions: u64,
+        incremental: bool,
+    ) -> Result<Self, Error> {
+        let watermark_path = repo_dir.join(WATERMARK_FILE);
+        let since = if incremental {
+            match tkfs::read_to_string(&watermark_path).await {
+                Ok(watermark) => Some(watermark.trim().parse::<u64>().with_context(|| {
+                    format!("Invalid watermark in {}", watermark_path.display())
+                })?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            None
+        };
+
+        let run_dir = repo_dir.join(DateTime::now().timestamp_secs().to_string());
+        tkfs::create_dir_all(&run_dir).await?;
+
+        Ok(Self {
+            run_dir,
+            watermark_path,
+            partitions: (0..num_partitions.max(1))
+                .map(|_| Mutex::new(None))
+                .collect(),
+            latest: AtomicU64::new(since.unwrap_or(0)),
+            since,
+        })
+    }
+
+    /// Whether a content reached from a commit with this generation number
+    /// is new since the previous export.  Contents not reached via a commit
+    /// can't be placed relative to the watermark, so are always exported.
+    fn is_new(&self, generation: Option<u64>) -> bool {
+        match (self.since, generation) {
+            (Some(since), Some(generation)) => generation > since,
+            _ => true,
+        }
+    }
+
+    async fn write(&self, fingerprint: u64, record: &ExportRecord) -> Result<(), Error> {
+        let encoded = serde_cbor::to_vec(record)?;
+        let len = u32::try_from(encoded.len())
+            .with_context(|| format!("Record for {} is too large", record.content_id))?;
+
+        let index = (fingerprint % self.partitions.len() as u64) as usize;
+        let mut partition = self.partitions[index].lock().await;
+        if partition.is_none() {
+            let path = self.run_dir.join(format!("part-{:05}.cbor", index));
+            let file = tkfs::File::create(&path)
+                .await
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            *partition = Some(BufWriter::new(file));
+        }
+        let writer = partition.as_mut().expect("partition was just opened");
+        writer.write_u32(len).await?;
+        writer.write_all(&encoded).await?;
+
+        if let Some(generation) = record.generation {
+            self.latest.fetch_max(generation, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Flush the partition files, then advance the watermark to the newest
+    /// commit exported so far.
+    pub async fn flush(&self) -> Result<(), Error> {
+        for partition in &self.partitions {
+            if let Some(writer) = &mut *partition.lock().await {
+                writer.flush().await?;
+            }
+        }
+
+        let latest = self.latest.load(Ordering::Relaxed);
+        if latest != 0 && Some(latest) != self.since {
+            let tmp_path = self.watermark_path.with_extension("tmp");
+            tkfs::write(&tmp_path, latest.to_string()).await?;
+            tkfs::rename(&tmp_path, &self.watermark_path).await?;
+        }
+        Ok(())
+    }
+}
+
+// Export reads contents from the walk output, so has nothing to sample
+impl SampleTrigger<WalkKeyOptPath<WrappedPath>> for ExportSink {
+    fn map_keys(&self, _key: SamplingKey, _walk_key: WalkKeyOptPath<WrappedPath>) {}
+}
+
+// Write out the file contents that pass the path and watermark filters
+fn export_stream<InStream, SS>(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    scheduled_max: usize,
+    path_regex: Option<Regex>,
+    s: InStream,
+    sink: Arc<ExportSink>,
+) -> impl Stream<Item = Result<(Node, Option<()>, Option<ScrubStats>), Error>>
+where
+    InStream: Stream<Item = Result<(WalkKeyOptPath<WrappedPath>, WalkPayloadMtime, Option<SS>), Error>>
+        + 'static
+        + Send,
+{
+    s.map_ok(move |(walk_key, payload, _progress_stats)| {
+        let WalkKeyOptPath { node, path } = walk_key;
+        let commit_time = payload.mtime.as_ref().map(|mtime| mtime.timestamp_secs());
+        let path_matches = path_regex.as_ref().map_or(true, |re| {
+            path.as_ref()
+                .map_or(false, |path| re.is_match(&path.to_string()))
+        });
+        let content_id = match &node {
+            Node::FileContent(content_id) => Some(*content_id),
+            _ => None,
+        };
+        match (content_id, payload.data) {
+            (
+                Some(content_id),
+                Some(NodeData::FileContent(FileContentData::ContentStream(file_bytes_stream))),
+            ) if path_matches => {
+                cloned!(ctx, repo, sink);
+                let fingerprint = node.sampling_fingerprint().unwrap_or_default();
+                let changeset = payload.changeset;
+                async move {
+                    let generation = match changeset {
+                        Some(cs_id) => repo
+                            .get_generation_number(ctx, cs_id)
+                            .await?
+                            .map(|generation| generation.value()),
+                        None => None,
+                    };
+                    if !sink.is_new(generation) {
+                        return Ok((node, Some(()), None));
+                    }
+                    let blob = file_bytes_stream
+                        .try_fold(BytesMut::new(), |mut acc, file_bytes| {
+                            acc.extend_from_slice(&file_bytes.0);
+                            future::ok(acc)
+                        })
+                        .await?
+                        .freeze();
+                    let record =
+                        make_record(path, changeset, commit_time, generation, content_id, blob);
+                    sink.write(fingerprint, &record).await?;
+                    let stats = ScrubStats {
+                        blobstore_bytes: record.size,
+                        blobstore_keys: 1,
+                    };
+                    Ok::<_, Error>((node, Some(()), Some(stats)))
+                }
+                .left_future()
+            }
+            _ => future::ok((node, Some(()), None)).right_future(),
+        }
+    })
+    .try_buffer_unordered(scheduled_max)
+}
+
+fn make_record(
+    path: Option<WrappedPath>,
+    changeset: Option<ChangesetId>,
+    commit_time: Option<i64>,
+    generation: Option<u64>,
+    content_id: ContentId,
+    blob: Bytes,
+) -> ExportRecord {
+    ExportRecord {
+        path: path
+            .as_ref()
+            .and_then(|path| path.as_ref())
+            .map(|mpath| Bytes::from(mpath.to_vec())),
+        commit: changeset.map(|cs_id| cs_id.to_string()),
+        commit_time,
+        generation,
+        content_id: content_id.to_string(),
+        size: blob.len() as u64,
+        blob,
+    }
+}
+
+#[derive(Clone)]
+pub struct ExportCommand {
+    pub output_dir: String,
+    pub partitions: u64,
+    pub incremental: bool,
+    pub path_regex: Option<Regex>,
+    pub progress_options: ProgressOptions,
+}
+
+// Subcommand entry point for exporting file contents
+pub async fn export(
+    fb: FacebookInit,
+    job_params: JobParams,
+    command: ExportCommand,
+    cancellation_requested: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    let JobParams {
+        walk_params,
+        per_repo,
+    } = job_params;
+
+    let mut all_walks = Vec::new();
+    for (sub_params, repo_params) in per_repo {
+        cloned!(command, walk_params);
+        let walk = run_one(
+            fb,
+            walk_params,
+            sub_params,
+            repo_params,
+            command,
+            Arc::clone(&cancellation_requested),
+        );
+        all_walks.push(walk);
+    }
+    try_join_all(all_walks).await.map(|_| ())
+}
+
+async fn run_one(
+    fb: FacebookInit,
+    job_params: JobWalkParams,
+    sub_params: RepoSubcommandParams,
+    repo_params: RepoWalkParams,
+    command: ExportCommand,
+    cancellation_requested: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    let mut repo_dir = PathBuf::from(&command.output_dir);
+    repo_dir.push(repo_params.repo.name());
+    let sink = Arc::new(
+        ExportSink::open(repo_dir, command.partitions, command.incremental)
+            .await
+            .context("Failed to open export output")?,
+    );
+    info!(
+        repo_params.logger,
+        "Exporting to {} (since watermark {:?})",
+        sink.run_dir.display(),
+        sink.since
+    );
+
+    let sizing_progress_state =
+        ProgressStateMutex::new(ProgressStateCountByType::<ScrubStats, ScrubStats>::new(
+            fb,
+            repo_params.logger.clone(),
+            EXPORT,
+            repo_params.repo.name().clone(),
+            hashset![NodeType::FileContent],
+            command.progress_options,
+        ));
+
+    let make_sink = {
+        cloned!(command, sink, job_params.quiet, sub_params.progress_state,);
+        move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
+            cloned!(ctx, repo_params.repo, repo_params.scheduled_max);
+            async move |walk_output, _run_start, _chunk_num, _checkpoint_name| {
+                cloned!(ctx, sizing_progress_state);
+                let walk_progress = progress_stream(quiet, &progress_state, walk_output);
+
+                let export = export_stream(
+                    ctx.clone(),
+                    repo.clone(),
+                    scheduled_max,
+                    command.path_regex,
+                    walk_progress,
+                    sink.clone(),
+                );
+                let report_sizing = progress_stream(quiet, &sizing_progress_state, export);
+                report_state(ctx, report_sizing).await?;
+                sink.flush().await?;
+                sizing_progress_state.report_progress();
+                progress_state.report_progress();
+                Ok(())
+            }
+        }
+    };
+
+    let walk_state = SamplingWalkVisitor::new(
+        repo_params.include_node_types.clone(),
+        repo_params.include_edge_types.clone(),
+        SamplingOptions::default(),
+        None,
+        sink,
+        job_params.enable_derive,
+        sub_params
+            .tail_params
+            .chunking
+            .as_ref()
+            .map(|v| v.direction),
+    );
+
+    let type_params = RepoWalkTypeParams {
+        required_node_data_types: hashset![NodeType::FileContent],
+        always_emit_edge_types: HashSet::new(),
+        keep_edge_paths: true,
+    };
+
+    walk_exact_tail::<_, _, _, _, _, PathTrackingRoute<WrappedPath>>(
+        fb,
+        job_params,
+        repo_params,
+        type_params,
+        sub_params.tail_params,
+        walk_state,
+        make_sink,
+        cancellation_requested,
+    )
+    .await
+}
@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Statistics on the shape of hg manifests, for capacity planning.
+//!
+//! For each manifest reached by the walk this records its fan-out (number
+//! of entries), its depth (number of path components of the directory it
+//! is for), and the length of each entry name.  The distributions are
+//! written as a JSON report per run, so that successive runs show how the
+//! shape of the repo changes over time.
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Error;
+use cloned::cloned;
+use context::CoreContext;
+use context::SamplingKey;
+use fbinit::FacebookInit;
+use futures::future::try_join_all;
+use futures::stream::Stream;
+use futures::stream::TryStreamExt;
+use manifest::Manifest;
+use maplit::hashset;
+use mononoke_types::Timestamp;
+use serde::Serialize;
+use slog::info;
+use tokio::fs::{self as tkfs};
+
+use crate::commands::JobParams;
+use crate::commands::JobWalkParams;
+use crate::commands::RepoSubcommandParams;
+use crate::detail::graph::Node;
+use crate::detail::graph::NodeData;
+use crate::detail::graph::NodeType;
+use crate::detail::graph::WrappedPath;
+use crate::detail::progress::progress_stream;
+use crate::detail::progress::report_state;
+use crate::detail::sampling::PathTrackingRoute;
+use crate::detail::sampling::SampleTrigger;
+use crate::detail::sampling::SamplingOptions;
+use crate::detail::sampling::SamplingWalkVisitor;
+use crate::detail::sampling::WalkKeyOptPath;
+use crate::detail::sampling::WalkPayloadMtime;
+use crate::detail::tail::walk_exact_tail;
+use crate::detail::walk::RepoWalkParams;
+use crate::detail::walk::RepoWalkTypeParams;
+
+/// Exact counts of the values seen for one measurement.
+#[derive(Clone, Debug, Default)]
+pub struct Distribution {
+    counts: BTreeMap<u64, u64>,
+}
+
+impl Distribution {
+    pub fn add(&mut self, value: u64) {
+        *self.counts.entry(value).or_default() += 1;
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    // Smallest value such that at least `quantile` of the values are no
+    // larger than it.
+    fn quantile(&self, quantile: f64) -> u64 {
+        let target = (self.count() as f64 * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (value, count) in &self.counts {
+            seen += count;
+            if seen >= target {
+                return *value;
+            }
+        }
+        0
+    }
+
+    pub fn summary(&self) -> DistributionSummary {
+        let count = self.count();
+        let total: u64 = self.counts.iter().map(|(value, count)| value * count).sum();
+        let mut histogram = BTreeMap::new();
+        for (value, count) in &self.counts {
+            *histogram.entry(value.next_power_of_two()).or_default() += count;
+        }
+        DistributionSummary {
+            count,
+            min: self.counts.keys().next().copied().unwrap_or_default(),
+            max: self.counts.keys().next_back().copied().unwrap_or_default(),
+            mean: if count == 0 {
+                0.0
+            } else {
+                total as f64 / count as f64
+            },
+            p50: self.quantile(0.5),
+            p90: self.quantile(0.9),
+            p99: self.quantile(0.99),
+            p999: self.quantile(0.999),
+            histogram,
+        }
+    }
+}
+
+/// Summary of a distribution for the report.  The histogram maps each
+/// power of two to the number of values greater than the previous power of
+/// two and no greater than it.
+#[derive(Debug, Serialize)]
+pub struct DistributionSummary {
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub histogram: BTreeMap<u64, u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ManifestStats {
+    pub fan_out: Distribution,
+    pub depth: Distribution,
+    pub name_length: Distribution,
+}
+
+/// The report written for each run.
+#[derive(Debug, Serialize)]
+pub struct ManifestStatsReport {
+    pub repo: String,
+    /// Start of the run, in seconds since the epoch.
+    pub run_start: i64,
+    pub manifests: u64,
+    pub fan_out: DistributionSummary,
+    pub depth: DistributionSummary,
+    pub name_length: DistributionSummary,
+}
+
+/// Accumulates the statistics for the current run of one repo, and writes
+/// them to `<output_dir>/<repo>/<run start>.json`.
+#[derive(Debug)]
+pub struct ManifestStatsSink {
+    repo: String,
+    repo_dir: PathBuf,
+    run: Mutex<Option<(Timestamp, ManifestStats)>>,
+}
+
+impl ManifestStatsSink {
+    pub async fn open(repo: String, repo_dir: PathBuf) -> Result<Self, Error> {
+        tkfs::create_dir_all(&repo_dir).await?;
+        Ok(Self {
+            repo,
+            repo_dir,
+            run: Mutex::new(None),
+        })
+    }
+
+    fn record(&self, run_start: Timestamp, path: Option<&WrappedPath>, names: &[usize]) {
+        let depth = path
+            .and_then(|path| path.as_ref())
+            .map_or(0, |mpath| mpath.num_components());
+
+        let mut run = self.run.lock().expect("lock poisoned");
+        // Chunks of the same run share the start time, and a new run starts
+        // from empty statistics.
+        if run.as_ref().map(|(start, _)| *start) != Some(run_start) {
+            *run = Some((run_start, ManifestStats::default()));
+        }
+        let (_, stats) = run.as_mut().expect("run was just set");
+        stats.fan_out.add(names.len() as u64);
+        stats.depth.add(depth as u64);
+        for name_length in names {
+            stats.name_length.add(*name_length as u64);
+        }
+    }
+
+    fn report(&self) -> Option<ManifestStatsReport> {
+        let run = self.run.lock().expect("lock poisoned");
+        run.as_ref().map(|(run_start, stats)| ManifestStatsReport {
+            repo: self.repo.clone(),
+            run_start: run_start.timestamp_seconds(),
+            manifests: stats.fan_out.count(),
+            fan_out: stats.fan_out.summary(),
+            depth: stats.depth.summary(),
+            name_length: stats.name_length.summary(),
+        })
+    }
+
+    /// Write the report for the statistics of the current run so far.
+    pub async fn write_report(&self) -> Result<Option<ManifestStatsReport>, Error> {
+        let report = match self.report() {
+            Some(report) => report,
+            None => return Ok(None),
+        };
+        let path = self.repo_dir.join(format!("{}.json", report.run_start));
+        let tmp_path = path.with_extension("tmp");
+        tkfs::write(&tmp_path, serde_json::to_vec_pretty(&report)?).await?;
+        tkfs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write report {}", path.display()))?;
+        Ok(Some(report))
+    }
+}
+
+// Statistics are taken from the walk output, so there is nothing to sample
+impl SampleTrigger<WalkKeyOptPath<WrappedPath>> for ManifestStatsSink {
+    fn map_keys(&self, _key: SamplingKey, _walk_key: WalkKeyOptPath<WrappedPath>) {}
+}
+
+// Record the shape of each manifest in the walk output
+fn manifest_stats_stream<InStream, SS>(
+    run_start: Timestamp,
+    s: InStream,
+    sink: Arc<ManifestStatsSink>,
+) -> impl Stream<Item = Result<(Node, Option<()>, Option<()>), Error>>
+where
+    InStream: Stream<Item = Result<(WalkKeyOptPath<WrappedPath>, WalkPayloadMtime, Option<SS>), Error>>
+        + 'static
+        + Send,
+{
+    s.map_ok(move |(walk_key, payload, _progress_stats)| {
+        let WalkKeyOptPath { node, path } = walk_key;
+        if let Some(NodeData::HgManifest(manifest)) = payload.data {
+            let names: Vec<_> = manifest.list().map(|(name, _entry)| name.len()).collect();
+            sink.record(run_start, path.as_ref(), &names);
+        }
+        (node, Some(()), None)
+    })
+}
+
+#[derive(Clone)]
+pub struct ManifestStatsCommand {
+    pub output_dir: String,
+}
+
+// Subcommand entry point for manifest statistics
+pub async fn manifest_stats(
+    fb: FacebookInit,
+    job_params: JobParams,
+    command: ManifestStatsCommand,
+    cancellation_requested: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    let JobParams {
+        walk_params,
+        per_repo,
+    } = job_params;
+
+    let mut all_walks = Vec::new();
+    for (sub_params, repo_params) in per_repo {
+        cloned!(command, walk_params);
+        let walk = run_one(
+            fb,
+            walk_params,
+            sub_params,
+            repo_params,
+            command,
+            Arc::clone(&cancellation_requested),
+        );
+        all_walks.push(walk);
+    }
+    try_join_all(all_walks).await.map(|_| ())
+}
+
+async fn run_one(
+    fb: FacebookInit,
+    job_params: JobWalkParams,
+    sub_params: RepoSubcommandParams,
+    repo_params: RepoWalkParams,
+    command: ManifestStatsCommand,
+    cancellation_requested: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    let repo_name = repo_params.repo.name().clone();
+    let mut repo_dir = PathBuf::from(&command.output_dir);
+    repo_dir.push(&repo_name);
+    let sink = Arc::new(
+        ManifestStatsSink::open(repo_name, repo_dir)
+            .await
+            .context("Failed to open manifest stats output")?,
+    );
+
+    let make_sink = {
+        cloned!(sink, job_params.quiet, sub_params.progress_state);
+        move |ctx: &CoreContext, repo_params: &RepoWalkParams| {
+            cloned!(ctx, repo_params.logger);
+            async move |walk_output, run_start, _chunk_num, _checkpoint_name| {
+                cloned!(ctx);
+                let walk_progress = progress_stream(quiet, &progress_state, walk_output);
+                let stats = manifest_stats_stream(run_start, walk_progress, sink.clone());
+                report_state(ctx, stats).await?;
+                progress_state.report_progress();
+                if let Some(report) = sink.write_report().await? {
+                    info!(
+                        logger,
+                        "Manifests: {}, fan-out p50/p99/max: {}/{}/{}, depth p50/p99/max: {}/{}/{}",
+                        report.manifests,
+                        report.fan_out.p50,
+                        report.fan_out.p99,
+                        report.fan_out.max,
+                        report.depth.p50,
+                        report.depth.p99,
+                        report.depth.max,
+                    );
+                }
+                Ok(())
+            }
+        }
+    };
+
+    let walk_state = SamplingWalkVisitor::new(
+        repo_params.include_node_types.clone(),
+        repo_params.include_edge_types.clone(),
+        SamplingOptions::default(),
+        None,
+        sink,
+        job_params.enable_derive,
+        sub_params
+            .tail_params
+            .chunking
+            .as_ref()
+            .map(|v| v.direction),
+    );
+
+    let type_params = RepoWalkTypeParams {
+        required_node_data_types: hashset![NodeType::HgManifest],
+        always_emit_edge_types: HashSet::new(),
+        keep_edge_paths: true,
+    };
+
+    walk_exact_tail::<_, _, _, _, _, PathTrackingRoute<WrappedPath>>(
+        fb,
+        job_params,
+        repo_params,
+        type_params,
+        sub_params.tail_params,
+        walk_state,
+        make_sink,
+        cancellation_requested,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_summary() {
+        let mut distribution = Distribution::default();
+        for value in 1..=100 {
+            distribution.add(value);
+        }
+        distribution.add(1000);
+
+        let summary = distribution.summary();
+        assert_eq!(summary.count, 101);
+        assert_eq!(summary.min, 1);
+        assert_eq!(summary.max, 1000);
+        assert_eq!(summary.p50, 51);
+        assert_eq!(summary.p99, 100);
+        assert_eq!(summary.p999, 1000);
+        assert_eq!(summary.histogram.get(&64), Some(&32));
+        assert_eq!(summary.histogram.get(&1024), Some(&1));
+    }
+
+    #[test]
+    fn test_empty_distribution_summary() {
+        let summary = Distribution::default().summary();
+        assert_eq!(summary.count, 0);
+        assert_eq!(summary.max, 0);
+        assert_eq!(summary.mean, 0.0);
+        assert!(summary.histogram.is_empty());
+    }
+}
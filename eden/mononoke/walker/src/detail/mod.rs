@@ -10,7 +10,9 @@ pub mod checkpoint;
 #[macro_use]
 pub mod graph;
 pub mod corpus;
+pub mod export;
 pub mod log;
+pub mod manifest_stats;
 pub mod pack;
 pub mod parse_node;
 pub mod progress;
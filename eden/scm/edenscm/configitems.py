@@ -390,6 +390,7 @@ coreconfigitem(
 coreconfigitem("merge-tools", r".*\.symlink$", default=False, generic=True, priority=-1)
 coreconfigitem("metalog", "track-config", default=True)
 coreconfigitem("mononokepeer", "compression", default=False)
+coreconfigitem("mononokepeer", "keepalive", default=None)
 coreconfigitem("mononokepeer", "sockettimeout", default=15.0)
 coreconfigitem("mutation", "date", default=None)
 coreconfigitem("mutation", "enabled", default=True)
@@ -56,6 +56,11 @@ class IoStream(Enum):
     STDIN = 0
     STDOUT = 1
     STDERR = 2
+    PROGRESS = 3
+
+
+# Client features advertised to Mononoke in the X-Client-Features header
+CLIENT_FEATURES = ["progress-stream"]
 
 
 class mononokepipe(object):
@@ -68,6 +73,7 @@ class mononokepipe(object):
         self._readoffset = 0
         self._pipe = pipe
         self._ui = ui
+        self._progress = None
 
         self._ui.log("mononokepeer", compression_enabled=decompress)
 
@@ -123,10 +129,14 @@ class mononokepipe(object):
                 segment = self._decompresser.decompress_buffer(segment)
 
             if stdtype == IoStream.STDOUT.value:
+                self._closeprogress()
                 return segment
             elif stdtype == IoStream.STDERR.value:
                 stdiopeer._writestderror(self._ui, segment)
                 continue
+            elif stdtype == IoStream.PROGRESS.value:
+                self._updateprogress(segment)
+                continue
             else:
                 raise error.Abort("unexpected stdtype '{}'".format(stdtype))
 
@@ -173,11 +183,28 @@ class mononokepipe(object):
         self._ui.metrics.gauge("mononoke_read_bytes", len(r))
         return r
 
+    def _updateprogress(self, segment):
+        """Show the latest progress message from the server in a spinner
+        rather than writing it to stderr"""
+        lines = segment.strip().splitlines()
+        if not lines:
+            return
+        if self._progress is None:
+            self._progress = progress.spinner(self._ui, _("remote"))
+            self._progress.__enter__()
+        self._progress.value = (None, decodeutf8(lines[-1], errors="replace"))
+
+    def _closeprogress(self):
+        if self._progress is not None:
+            self._progress.__exit__(None, None, None)
+            self._progress = None
+
     def _reset_read_buf(self):
         self._readbuf = b""
         self._readoffset = 0
 
     def close(self):
+        self._closeprogress()
         return self._pipe.close()
 
     def flush(self):
@@ -240,6 +267,7 @@ class mononokepeer(stdiopeer.stdiopeer):
         self._path = u.path
         self._compression = ui.configwith(bool, "mononokepeer", "compression")
         self._sockettimeout = ui.configwith(float, "mononokepeer", "sockettimeout")
+        self._keepalive = ui.configint("mononokepeer", "keepalive")
         self._unix_socket_proxy = ui.config("auth_proxy", "unix_socket_path")
         self._auth_proxy_http = ui.config("auth_proxy", "http_proxy")
         self._confheaders = ui.config("http", "extra_headers_json")
@@ -359,6 +387,7 @@ class mononokepeer(stdiopeer.stdiopeer):
                     "Upgrade": "websocket",
                 }
                 headers["X-Client-Info"] = self._clientinfo.into_json().decode()
+                headers["X-Client-Features"] = ",".join(CLIENT_FEATURES)
 
                 if self._cats:
                     headers["x-forwarded-cats"] = self._cats
@@ -366,6 +395,9 @@ class mononokepeer(stdiopeer.stdiopeer):
                 if self._compression:
                     headers["X-Client-Compression"] = "zstd=stdin"
 
+                if self._keepalive:
+                    headers["X-Client-Keepalive"] = str(self._keepalive)
+
                 if os.getenv("CLIENT_DEBUG"):
                     headers["X-Client-Debug"] = "true"
 
@@ -412,6 +444,7 @@ class mononokepeer(stdiopeer.stdiopeer):
                 httpcode = headerparts[1]
                 httpstatus = b" ".join(headerparts[2:])
                 bodylength = 0
+                keepalive = None
                 apeadvice = b""
                 x2pagentderrortype = b""
                 x2pagentderrormsg = b""
@@ -424,6 +457,8 @@ class mononokepeer(stdiopeer.stdiopeer):
                         print("< {}".format(line))
                     if line.lower().startswith(b"x-mononoke-encoding:"):
                         decompress = True
+                    elif line.lower().startswith(b"x-mononoke-keepalive:"):
+                        keepalive = int(line.split(b" ", 1)[1])
                     elif line.lower().startswith(b"content-length:"):
                         bodylength = int(line.split(b" ", 1)[1])
                     elif line.lower().startswith(b"x-fb-validated-x2pauth-advice"):
@@ -455,6 +490,12 @@ class mononokepeer(stdiopeer.stdiopeer):
                         )
                     )
 
+                if keepalive and self._sockettimeout:
+                    # The server sends a keepalive at this interval while
+                    # it is busy, so only consider the connection dead if
+                    # several in a row are missed.
+                    self.sock.settimeout(max(self._sockettimeout, 3 * keepalive))
+
             except IOError as ex:
                 self._connectionerror(ex)
 
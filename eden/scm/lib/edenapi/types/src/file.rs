@@ -75,6 +75,52 @@ pub struct FileAuxData {
     pub sha1: Sha1,
     #[id(3)]
     pub sha256: Sha256,
+    /// What kind of file this is. Only present if the server has classified
+    /// the file.
+    #[id(4)]
+    pub kind: Option<FileKind>,
+}
+
+/// What kind of file a file is, as classified by the server from its
+/// content. See mononoke_types::ContentMetadataV2.
+#[auto_wire]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[cfg_attr(any(test, feature = "for-tests"), derive(Arbitrary))]
+pub struct FileKind {
+    #[id(0)]
+    pub is_binary: bool,
+    #[id(1)]
+    pub is_ascii: bool,
+    #[id(2)]
+    pub is_utf8: bool,
+    #[id(3)]
+    pub line_endings: LineEndings,
+    #[id(4)]
+    pub has_bom: bool,
+    #[id(5)]
+    pub language: Option<String>,
+}
+
+#[auto_wire]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(any(test, feature = "for-tests"), derive(Arbitrary))]
+pub enum LineEndings {
+    #[id(1)]
+    Absent,
+    #[id(2)]
+    Lf,
+    #[id(3)]
+    CrLf,
+    #[id(4)]
+    Cr,
+    #[id(5)]
+    Mixed,
+}
+
+impl Default for LineEndings {
+    fn default() -> Self {
+        Self::Absent
+    }
 }
 
 /// File content
@@ -107,10 +107,12 @@ pub use crate::file::FileAuxData;
 pub use crate::file::FileContent;
 pub use crate::file::FileEntry;
 pub use crate::file::FileError;
+pub use crate::file::FileKind;
 pub use crate::file::FileRequest;
 pub use crate::file::FileResponse;
 pub use crate::file::FileSpec;
 pub use crate::file::HgFilenodeData;
+pub use crate::file::LineEndings;
 pub use crate::file::UploadHgFilenodeRequest;
 pub use crate::file::UploadTokensResponse;
 pub use crate::history::HistoryEntry;
@@ -16,9 +16,11 @@ use crate::file::FileEntry;
 use crate::file::FileResponse;
 pub use crate::file::WireFileAttributes;
 pub use crate::file::WireFileAuxData;
+pub use crate::file::WireFileKind;
 pub use crate::file::WireFileRequest;
 pub use crate::file::WireFileSpec;
 pub use crate::file::WireHgFilenodeData;
+pub use crate::file::WireLineEndings;
 pub use crate::file::WireUploadHgFilenodeRequest;
 pub use crate::file::WireUploadTokensResponse;
 use crate::wire::is_default;
@@ -62,6 +62,7 @@ impl From<FileAuxData> for EdenApiFileAuxData {
             content_id: v.content_id,
             sha1: v.content_sha1,
             sha256: v.content_sha256.into_inner().into(),
+            kind: None,
         }
     }
 }